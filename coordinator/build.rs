@@ -1,4 +1,6 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
   tonic_build::compile_protos("../proto/coordinator.proto")?;
+  tonic_build::compile_protos("../proto/coordinator_admin.proto")?;
+  tonic_build::compile_protos("../proto/checkpoint.proto")?;
   Ok(())
 }
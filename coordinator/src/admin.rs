@@ -0,0 +1,706 @@
+use crate::{
+  audit::{audited, decode_audit_record, AuditLog, ADMIN_SERVICE},
+  authz::{Identity, Role},
+  coordinator_admin_proto::{
+    admin_server::Admin, AddEndorserReq, AuditEntry, DelegationTokenResp, EndorserStatus,
+    GetOperationStatusReq, GetOperationStatusResp, GetRateLimitReq, GetTenantReq,
+    GetViewHistoryReq, GetViewHistoryResp, IssueDelegationTokenReq, ListEndorsersReq,
+    ListEndorsersResp, OperationResp, OperationState, PurgeBlocksReq, RateLimitResp, ReadAuditReq,
+    ReadAuditResp, ReloadEndorserAllowlistReq, ReloadEndorserAllowlistResp, RemoveEndorserReq,
+    RenewDelegationTokenReq, RotateEndorserKeyReq, SealLedgerReq, SealLedgerResp, SetQuorumReq,
+    SetRateLimitReq, SetTenantQuotaReq, TenantResp, TriggerRepairReq, ViewEntry, ViewMember,
+  },
+  coordinator_state::{CoordinatorState, Deadline},
+  delegation::{DelegationTokens, TokenError, TokenIdentifier, MAX_PRINCIPAL_SIZE},
+  errors::CoordinatorError,
+  process_error,
+  rate_limit::{RateLimiter, RateLimits},
+  tenant::{ClientAuth, Tenant},
+  validate::MAX_READ_RANGE_PAGE_SIZE,
+};
+use ledger::CustomSerde;
+use std::{
+  cmp,
+  collections::HashMap,
+  future::Future,
+  sync::{Arc, RwLock},
+};
+use store::ledger::TenantRecord;
+use tonic::{Request, Response, Status};
+use tracing::warn;
+
+const DEFAULT_AUDIT_PAGE_SIZE: u64 = 64; // entries: the page size of ReadAudit by default
+
+enum Operation {
+  Running,
+  Succeeded,
+  Failed(String),
+}
+
+/// the admin service of the coordinator; membership changes and repairs run in the background
+/// and are tracked as operations that clients poll
+pub struct AdminServiceState {
+  state: Arc<CoordinatorState>,
+  operations: Arc<RwLock<HashMap<String, Operation>>>,
+  /// the limiter of the client service, whose limits the admin service changes
+  rate_limiter: Arc<RateLimiter>,
+  /// issues the delegation tokens that the client service accepts, if any
+  delegation_tokens: Option<Arc<DelegationTokens>>,
+  /// records the calls that change something, if set
+  audit: Option<Arc<AuditLog>>,
+}
+
+impl AdminServiceState {
+  pub fn new(coordinator: Arc<CoordinatorState>) -> Self {
+    AdminServiceState {
+      state: coordinator,
+      operations: Arc::new(RwLock::new(HashMap::new())),
+      rate_limiter: Arc::new(RateLimiter::default()),
+      delegation_tokens: None,
+      audit: None,
+    }
+  }
+
+  /// makes the admin service change the limits of `rate_limiter`
+  pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+    self.rate_limiter = rate_limiter;
+    self
+  }
+
+  /// makes the admin service issue and renew delegation tokens with `delegation_tokens`
+  pub fn with_delegation_tokens(mut self, delegation_tokens: Arc<DelegationTokens>) -> Self {
+    self.delegation_tokens = Some(delegation_tokens);
+    self
+  }
+
+  /// records the calls that change something in `audit`, and serves its ledger
+  pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+    self.audit = Some(audit);
+    self
+  }
+
+  /// runs `handler` on `req`, a call to `method` that changes something, and records the call
+  async fn audited<R, T, F>(
+    &self,
+    method: &'static str,
+    req: Request<R>,
+    handler: impl FnOnce(Request<R>) -> F,
+  ) -> Result<Response<T>, Status>
+  where
+    R: prost::Message,
+    F: Future<Output = Result<Response<T>, Status>>,
+  {
+    audited(&self.audit, ADMIN_SERVICE, method, req, handler).await
+  }
+
+  #[allow(clippy::result_large_err)]
+  fn delegation_tokens(&self) -> Result<&DelegationTokens, Status> {
+    self.delegation_tokens.as_deref().ok_or_else(|| {
+      Status::failed_precondition("The coordinator does not issue delegation tokens")
+    })
+  }
+
+  fn rate_limit_resp(&self, key: String) -> RateLimitResp {
+    let limits = self.rate_limiter.limits(&key);
+    let counters = self.rate_limiter.counters(&key);
+    RateLimitResp {
+      key,
+      reads_per_sec: limits.reads_per_sec,
+      read_burst: limits.read_burst,
+      appends_per_sec: limits.appends_per_sec,
+      append_burst: limits.append_burst,
+      reads_allowed: counters.reads_allowed,
+      reads_rejected: counters.reads_rejected,
+      appends_allowed: counters.appends_allowed,
+      appends_rejected: counters.appends_rejected,
+    }
+  }
+
+  /// runs `op` in the background and returns the id of the operation that tracks it
+  #[allow(clippy::result_large_err)]
+  fn start_operation<F>(&self, op: F) -> Result<Response<OperationResp>, Status>
+  where
+    F: Future<Output = Result<(), CoordinatorError>> + Send + 'static,
+  {
+    let operation_id = uuid::Uuid::new_v4().to_string();
+    if let Ok(mut operations) = self.operations.write() {
+      operations.insert(operation_id.clone(), Operation::Running);
+    } else {
+      return Err(Status::internal("Failed to acquire the write lock"));
+    }
+
+    let operations = self.operations.clone();
+    let id = operation_id.clone();
+    let _job = tokio::spawn(async move {
+      let operation = match op.await {
+        Ok(()) => Operation::Succeeded,
+        Err(error) => {
+          warn!(operation = %id, %error, "the admin operation failed");
+          Operation::Failed(error.to_string())
+        },
+      };
+      if let Ok(mut operations) = operations.write() {
+        operations.insert(id, operation);
+      }
+    });
+
+    Ok(Response::new(OperationResp { operation_id }))
+  }
+
+  async fn find_view_member(&self, pk: &[u8]) -> Result<String, Status> {
+    let view = self
+      .state
+      .read_current_view()
+      .await
+      .map_err(|_e| Status::aborted("Failed to read the current view"))?;
+    match view.into_iter().find(|(view_pk, _uri)| view_pk == pk) {
+      Some((_pk, uri)) => Ok(uri),
+      None => Err(Status::not_found(
+        "No endorser of the current view has this key",
+      )),
+    }
+  }
+}
+
+fn tenant_status(error: CoordinatorError) -> Status {
+  match error {
+    CoordinatorError::UnknownTenant => Status::not_found("Unknown tenant"),
+    _ => Status::aborted("Failed to access the tenant in the ledger store"),
+  }
+}
+
+fn tenant_resp(tenant: String, record: TenantRecord) -> TenantResp {
+  TenantResp {
+    tenant,
+    max_ledgers: record.max_ledgers,
+    max_appends_per_sec: record.max_appends_per_sec,
+    max_stored_bytes: record.max_stored_bytes,
+    num_ledgers: record.num_ledgers,
+    stored_bytes: record.stored_bytes,
+  }
+}
+
+fn delegation_token_resp(token: String, identifier: TokenIdentifier) -> DelegationTokenResp {
+  DelegationTokenResp {
+    token,
+    tenant: identifier.tenant,
+    owner: identifier.owner,
+    renewer: identifier.renewer,
+    issue_date: identifier.issue_date,
+    expiry: identifier.expiry,
+    max_date: identifier.max_date,
+    sequence_number: identifier.sequence_number,
+  }
+}
+
+#[allow(clippy::result_large_err)]
+fn reload_endorser_allowlist(
+  state: &CoordinatorState,
+) -> Result<Response<ReloadEndorserAllowlistResp>, Status> {
+  let num_keys = state
+    .reload_endorser_allowlist()
+    .map_err(|e| process_error(e, "Failed to reload the allowlist of endorsers"))?;
+  Ok(Response::new(ReloadEndorserAllowlistResp {
+    num_keys: num_keys as u64,
+  }))
+}
+
+/// reloads the allowlist of endorsers whenever the coordinator receives SIGHUP, recording each
+/// reload like a call to ReloadEndorserAllowlist by the principal `Signal:SIGHUP`
+#[cfg(unix)]
+pub async fn reload_endorser_allowlist_on_hangup(
+  state: Arc<CoordinatorState>,
+  audit: Option<Arc<AuditLog>>,
+) {
+  use tokio::signal::unix::{signal, SignalKind};
+  let mut hangups = match signal(SignalKind::hangup()) {
+    Ok(hangups) => hangups,
+    Err(e) => {
+      warn!(error = %e, "cannot reload the allowlist of endorsers on SIGHUP");
+      return;
+    },
+  };
+  while hangups.recv().await.is_some() {
+    let mut req = Request::new(ReloadEndorserAllowlistReq {});
+    req
+      .extensions_mut()
+      .insert(Identity::new("Signal", "SIGHUP", None, Role::Admin));
+    let reload =
+      |_req: Request<ReloadEndorserAllowlistReq>| async { reload_endorser_allowlist(&state) };
+    let res = audited(
+      &audit,
+      ADMIN_SERVICE,
+      "ReloadEndorserAllowlist",
+      req,
+      reload,
+    )
+    .await;
+    if let Err(status) = res {
+      warn!(
+        error = %status.message(),
+        "failed to reload the allowlist of endorsers on SIGHUP"
+      );
+    }
+  }
+}
+
+/// the token of the operators of the admin service, which they send as
+/// `authorization: Bearer <token>`; it authenticates an admin of no tenant
+pub struct AdminToken(String);
+
+impl AdminToken {
+  pub fn new(token: String) -> Self {
+    AdminToken(token)
+  }
+}
+
+impl ClientAuth for AdminToken {
+  fn scheme(&self) -> &'static str {
+    "Bearer"
+  }
+
+  fn authenticate(&self, credentials: &str) -> Result<Tenant, Status> {
+    self.identify(credentials)?;
+    Err(Status::permission_denied(
+      "The admin token authenticates no tenant",
+    ))
+  }
+
+  fn identify(&self, credentials: &str) -> Result<Identity, Status> {
+    if credentials != self.0 {
+      return Err(Status::unauthenticated("Invalid admin token"));
+    }
+    Ok(Identity::new(self.scheme(), "admin", None, Role::Admin))
+  }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminServiceState {
+  async fn add_endorser(
+    &self,
+    req: Request<AddEndorserReq>,
+  ) -> Result<Response<OperationResp>, Status> {
+    self
+      .audited("AddEndorser", req, |req| async move {
+        let AddEndorserReq { uri } = req.into_inner();
+        if uri.is_empty() {
+          return Err(Status::invalid_argument("Empty endorser uri"));
+        }
+
+        let state = self.state.clone();
+        self.start_operation(async move { state.add_endorsers(&[uri]).await })
+      })
+      .await
+  }
+
+  async fn remove_endorser(
+    &self,
+    req: Request<RemoveEndorserReq>,
+  ) -> Result<Response<OperationResp>, Status> {
+    self
+      .audited("RemoveEndorser", req, |req| async move {
+        let RemoveEndorserReq { pk } = req.into_inner();
+        let uri = self.find_view_member(&pk).await?;
+
+        let state = self.state.clone();
+        self.start_operation(async move { state.remove_endorsers(&[uri]).await.map(|_e| ()) })
+      })
+      .await
+  }
+
+  async fn rotate_endorser_key(
+    &self,
+    req: Request<RotateEndorserKeyReq>,
+  ) -> Result<Response<OperationResp>, Status> {
+    self
+      .audited("RotateEndorserKey", req, |req| async move {
+        let RotateEndorserKeyReq { pk } = req.into_inner();
+        let _uri = self.find_view_member(&pk).await?;
+
+        let state = self.state.clone();
+        self.start_operation(async move { state.rotate_endorser_key(&pk).await.map(|_pk| ()) })
+      })
+      .await
+  }
+
+  async fn set_quorum(
+    &self,
+    req: Request<SetQuorumReq>,
+  ) -> Result<Response<OperationResp>, Status> {
+    self
+      .audited("SetQuorum", req, |req| async move {
+        let SetQuorumReq { quorum } = req.into_inner();
+
+        let state = self.state.clone();
+        self.start_operation(async move { state.change_quorum(quorum as usize).await })
+      })
+      .await
+  }
+
+  async fn list_endorsers(
+    &self,
+    _req: Request<ListEndorsersReq>,
+  ) -> Result<Response<ListEndorsersResp>, Status> {
+    let statuses = self
+      .state
+      .get_endorser_statuses()
+      .await
+      .map_err(|_e| Status::aborted("Failed to read the status of the endorsers"))?;
+
+    let endorsers = statuses
+      .into_iter()
+      .map(|status| EndorserStatus {
+        pk: status.pk,
+        uri: status.uri,
+        connected: status.connected,
+        healthy: status.mode.is_some(),
+        mode: status.mode.unwrap_or_default(),
+        lag: status.lag.unwrap_or_default() as u64,
+        invalid_signatures: status.invalid_signatures as u64,
+        num_ledgers: status.num_ledgers.unwrap_or_default() as u64,
+        tail_map_bytes: status.tail_map_bytes.unwrap_or_default() as u64,
+      })
+      .collect();
+    Ok(Response::new(ListEndorsersResp { endorsers }))
+  }
+
+  async fn get_view_history(
+    &self,
+    _req: Request<GetViewHistoryReq>,
+  ) -> Result<Response<GetViewHistoryResp>, Status> {
+    let history = self
+      .state
+      .get_view_history()
+      .await
+      .map_err(|_e| Status::aborted("Failed to read the view ledger"))?;
+
+    let views = history
+      .into_iter()
+      .map(|(height, endorsers, quorum)| ViewEntry {
+        height: height as u64,
+        endorsers: endorsers
+          .into_iter()
+          .map(|(pk, uri)| ViewMember { pk, uri })
+          .collect(),
+        quorum: quorum as u32,
+      })
+      .collect();
+    Ok(Response::new(GetViewHistoryResp { views }))
+  }
+
+  async fn trigger_repair(
+    &self,
+    req: Request<TriggerRepairReq>,
+  ) -> Result<Response<OperationResp>, Status> {
+    self
+      .audited("TriggerRepair", req, |req| async move {
+        let TriggerRepairReq { pk } = req.into_inner();
+        self.find_view_member(&pk).await?;
+
+        let state = self.state.clone();
+        self.start_operation(async move { state.repair_endorser(&pk).await })
+      })
+      .await
+  }
+
+  async fn get_operation_status(
+    &self,
+    req: Request<GetOperationStatusReq>,
+  ) -> Result<Response<GetOperationStatusResp>, Status> {
+    let GetOperationStatusReq { operation_id } = req.into_inner();
+
+    let operations = self
+      .operations
+      .read()
+      .map_err(|_e| Status::internal("Failed to acquire the read lock"))?;
+    let (state, error) = match operations.get(&operation_id) {
+      None => return Err(Status::not_found("Unknown operation")),
+      Some(Operation::Running) => (OperationState::Running, String::new()),
+      Some(Operation::Succeeded) => (OperationState::Succeeded, String::new()),
+      Some(Operation::Failed(error)) => (OperationState::Failed, error.clone()),
+    };
+    Ok(Response::new(GetOperationStatusResp {
+      state: state as i32,
+      error,
+    }))
+  }
+
+  async fn get_tenant(&self, req: Request<GetTenantReq>) -> Result<Response<TenantResp>, Status> {
+    let GetTenantReq { tenant } = req.into_inner();
+    let record = self
+      .state
+      .read_tenant(&tenant)
+      .await
+      .map_err(tenant_status)?;
+    Ok(Response::new(tenant_resp(tenant, record)))
+  }
+
+  async fn set_tenant_quota(
+    &self,
+    req: Request<SetTenantQuotaReq>,
+  ) -> Result<Response<TenantResp>, Status> {
+    self
+      .audited("SetTenantQuota", req, |req| async move {
+        let SetTenantQuotaReq {
+          tenant,
+          max_ledgers,
+          max_appends_per_sec,
+          max_stored_bytes,
+        } = req.into_inner();
+        let record = self
+          .state
+          .set_tenant_quota(&tenant, max_ledgers, max_appends_per_sec, max_stored_bytes)
+          .await
+          .map_err(tenant_status)?;
+        Ok(Response::new(tenant_resp(tenant, record)))
+      })
+      .await
+  }
+
+  async fn seal_ledger(
+    &self,
+    req: Request<SealLedgerReq>,
+  ) -> Result<Response<SealLedgerResp>, Status> {
+    self
+      .audited("SealLedger", req, |req| async move {
+        let SealLedgerReq { handle } = req.into_inner();
+        if handle.is_empty() {
+          return Err(Status::invalid_argument("Handle is empty"));
+        }
+
+        let (height, block, hash_nonces, receipts) = self
+          .state
+          .seal_ledger(&handle, Deadline::none())
+          .await
+          .map_err(|e| process_error(e, "Failed to seal the ledger"))?;
+        Ok(Response::new(SealLedgerResp {
+          block: block.to_bytes(),
+          hash_nonces: hash_nonces.to_bytes(),
+          receipts: receipts.to_bytes(),
+          height: height as u64,
+        }))
+      })
+      .await
+  }
+
+  async fn purge_blocks(
+    &self,
+    req: Request<PurgeBlocksReq>,
+  ) -> Result<Response<OperationResp>, Status> {
+    self
+      .audited("PurgeBlocks", req, |req| async move {
+        let PurgeBlocksReq {
+          handle,
+          before_height,
+        } = req.into_inner();
+        if handle.is_empty() {
+          return Err(Status::invalid_argument("Handle is empty"));
+        }
+
+        let state = self.state.clone();
+        self.start_operation(async move {
+          state
+            .purge_ledger_blocks(&handle, before_height as usize)
+            .await
+        })
+      })
+      .await
+  }
+
+  async fn get_rate_limit(
+    &self,
+    req: Request<GetRateLimitReq>,
+  ) -> Result<Response<RateLimitResp>, Status> {
+    let GetRateLimitReq { key } = req.into_inner();
+    Ok(Response::new(self.rate_limit_resp(key)))
+  }
+
+  async fn set_rate_limit(
+    &self,
+    req: Request<SetRateLimitReq>,
+  ) -> Result<Response<RateLimitResp>, Status> {
+    self
+      .audited("SetRateLimit", req, |req| async move {
+        let SetRateLimitReq {
+          key,
+          reads_per_sec,
+          read_burst,
+          appends_per_sec,
+          append_burst,
+          clear,
+        } = req.into_inner();
+        let limits = RateLimits {
+          reads_per_sec,
+          read_burst,
+          appends_per_sec,
+          append_burst,
+        };
+        self
+          .rate_limiter
+          .set_limits(&key, if clear { None } else { Some(limits) });
+        Ok(Response::new(self.rate_limit_resp(key)))
+      })
+      .await
+  }
+
+  async fn issue_delegation_token(
+    &self,
+    req: Request<IssueDelegationTokenReq>,
+  ) -> Result<Response<DelegationTokenResp>, Status> {
+    self
+      .audited("IssueDelegationToken", req, |req| async move {
+        let IssueDelegationTokenReq {
+          tenant,
+          owner,
+          renewer,
+        } = req.into_inner();
+        let delegation_tokens = self.delegation_tokens()?;
+        if owner.len() > MAX_PRINCIPAL_SIZE || renewer.len() > MAX_PRINCIPAL_SIZE {
+          return Err(Status::invalid_argument(format!(
+            "The owner and the renewer must be at most {} bytes",
+            MAX_PRINCIPAL_SIZE
+          )));
+        }
+        // only the tenants of the tenant file, whose quotas apply to the tokens, get tokens
+        self
+          .state
+          .read_tenant(&tenant)
+          .await
+          .map_err(tenant_status)?;
+        let (token, identifier) = delegation_tokens
+          .issue(&tenant, &owner, &renewer)
+          .map_err(|e| e.to_status())?;
+        Ok(Response::new(delegation_token_resp(token, identifier)))
+      })
+      .await
+  }
+
+  async fn renew_delegation_token(
+    &self,
+    req: Request<RenewDelegationTokenReq>,
+  ) -> Result<Response<DelegationTokenResp>, Status> {
+    self
+      .audited("RenewDelegationToken", req, |req| async move {
+        let RenewDelegationTokenReq { token, renewer } = req.into_inner();
+        let (token, identifier) =
+          self
+            .delegation_tokens()?
+            .renew(&token, &renewer)
+            .map_err(|e| match e {
+              TokenError::Invalid => Status::invalid_argument("Invalid delegation token"),
+              TokenError::Expired => Status::failed_precondition("Expired delegation token"),
+              e => e.to_status(),
+            })?;
+        Ok(Response::new(delegation_token_resp(token, identifier)))
+      })
+      .await
+  }
+
+  async fn read_audit(
+    &self,
+    req: Request<ReadAuditReq>,
+  ) -> Result<Response<ReadAuditResp>, Status> {
+    let audit = self
+      .audit
+      .as_ref()
+      .ok_or_else(|| Status::failed_precondition("The coordinator keeps no audit ledger"))?;
+    let ReadAuditReq { from, page_size } = req.into_inner();
+    if page_size > MAX_READ_RANGE_PAGE_SIZE {
+      return Err(Status::invalid_argument(format!(
+        "The page size must be at most {}",
+        MAX_READ_RANGE_PAGE_SIZE
+      )));
+    }
+    let from = cmp::max(from, 1);
+    let page_size = if page_size == 0 {
+      DEFAULT_AUDIT_PAGE_SIZE
+    } else {
+      page_size
+    };
+
+    // the audit ledger is created with the first record
+    let tail_height = match self.state.read_cached_ledger_tail(audit.ledger()).await {
+      Ok((_entry, height)) => height as u64,
+      Err(CoordinatorError::LedgerNotFound) => 0,
+      Err(e) => return Err(process_error(e, "Failed to read the audit ledger")),
+    };
+    if from > tail_height {
+      return Ok(Response::new(ReadAuditResp {
+        tail_height,
+        ..Default::default()
+      }));
+    }
+    let to = cmp::min(tail_height, from.saturating_add(page_size - 1));
+    let range = self
+      .state
+      .read_ledger_range(audit.ledger(), from as usize, to as usize)
+      .await
+      .map_err(|e| process_error(e, "Failed to read the audit ledger"))?;
+
+    let entries = range
+      .entries
+      .iter()
+      .zip(from..)
+      .map(|(entry, height)| {
+        let block = entry.get_block().to_bytes();
+        AuditEntry {
+          height,
+          record: decode_audit_record(&block),
+          block,
+          nonces: entry.get_nonces().to_bytes(),
+        }
+      })
+      .collect();
+    Ok(Response::new(ReadAuditResp {
+      entries,
+      checkpoint: range
+        .checkpoint
+        .map(|checkpoint| checkpoint.to_bytes())
+        .unwrap_or_default(),
+      block_hashes: range.block_hashes.iter().map(|h| h.to_bytes()).collect(),
+      tail_receipts: range.tail_receipts.to_bytes(),
+      tail_height,
+      next_from: if to < tail_height { to + 1 } else { 0 },
+    }))
+  }
+
+  async fn reload_endorser_allowlist(
+    &self,
+    req: Request<ReloadEndorserAllowlistReq>,
+  ) -> Result<Response<ReloadEndorserAllowlistResp>, Status> {
+    self
+      .audited("ReloadEndorserAllowlist", req, |_req| async move {
+        reload_endorser_allowlist(&self.state)
+      })
+      .await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::AdminToken;
+  use crate::{authz::Role, tenant::Authenticator};
+  use std::sync::Arc;
+  use tonic::Code;
+
+  #[test]
+  pub fn test_admin_token() {
+    let auth = Authenticator::default().with_auth(Arc::new(AdminToken::new("secret".to_string())));
+    let check = |authorization: Option<&str>| auth.identify(authorization);
+
+    let identity = check(Some("Bearer secret")).unwrap();
+    assert_eq!(identity.role, Role::Admin);
+    assert_eq!(identity.tenant, None);
+
+    assert_eq!(
+      check(Some("Bearer other")).unwrap_err().code(),
+      Code::Unauthenticated
+    );
+    assert_eq!(check(None).unwrap_err().code(), Code::Unauthenticated);
+    // the operators are no tenant
+    assert_eq!(
+      auth.authenticate(Some("Bearer secret")).unwrap_err().code(),
+      Code::PermissionDenied
+    );
+  }
+}
@@ -0,0 +1,200 @@
+//! The allowlist of endorsers. In high-assurance deployments, the coordinator talks only to the
+//! endorsers whose public keys an operator approved offline: the allowlist file lists their keys,
+//! hex-encoded one per line with `#` comments, and a detached signature of the file by the key of
+//! the operator sits next to it. The coordinator verifies the signature with the operator key of
+//! its configuration every time it loads the file, so that a file that was changed on the host of
+//! the coordinator is refused rather than trusted, and it keeps the allowlist it has then.
+//!
+//! The signature is the hex of a P-256 signature (r || s, 64 bytes) of `ALLOWLIST_DOMAIN`
+//! followed by the bytes of the file, so that a signature the operator key makes for anything
+//! else does not pass for one of an allowlist.
+use ledger::signature::{PublicKey, PublicKeyTrait, Signature, SignatureTrait};
+use std::collections::HashSet;
+
+/// what the operator key signs ahead of the bytes of the allowlist file
+pub const ALLOWLIST_DOMAIN: &[u8] = b"NimbleEndorserAllowlist/v1\n";
+
+/// the file of the signature of an allowlist unless one is configured
+pub fn default_signature_path(path: &str) -> String {
+  format!("{}.sig", path)
+}
+
+/// the operator key of the configuration, hex-encoded
+pub fn parse_operator_key(hex_key: &str) -> Result<PublicKey, String> {
+  hex::decode(hex_key.trim())
+    .ok()
+    .and_then(|bytes| PublicKey::from_bytes(&bytes).ok())
+    .ok_or_else(|| "the operator key must be a hex-encoded public key".to_string())
+}
+
+/// what the operator key signs for the allowlist file `contents`
+pub fn signed_message(contents: &[u8]) -> Vec<u8> {
+  [ALLOWLIST_DOMAIN, contents].concat()
+}
+
+/// the keys of the allowlist file `contents`, if `signature` is the signature of the file by
+/// `operator_key`
+pub fn parse_allowlist(
+  contents: &[u8],
+  signature: &[u8],
+  operator_key: &PublicKey,
+) -> Result<HashSet<Vec<u8>>, String> {
+  let signature = std::str::from_utf8(signature)
+    .ok()
+    .and_then(|signature| hex::decode(signature.trim()).ok())
+    .and_then(|bytes| Signature::from_bytes(&bytes).ok())
+    .ok_or("the signature is not a hex-encoded signature")?;
+  signature
+    .verify(operator_key, &signed_message(contents))
+    .map_err(|_e| "the signature does not verify with the operator key")?;
+
+  let contents = std::str::from_utf8(contents).map_err(|_e| "the allowlist is not UTF-8")?;
+  let mut keys = HashSet::new();
+  let lines = contents
+    .lines()
+    .map(|line| line.split('#').next().unwrap_or("").trim())
+    .filter(|line| !line.is_empty());
+  for line in lines {
+    let key = hex::decode(line)
+      .ok()
+      .filter(|bytes| PublicKey::from_bytes(bytes).is_ok())
+      .ok_or_else(|| format!("{} is not a hex-encoded public key", line))?;
+    keys.insert(key);
+  }
+  Ok(keys)
+}
+
+/// the endorsers that the coordinator may connect to, as the operator approved them
+#[derive(Clone, Debug)]
+pub struct EndorserAllowlist {
+  path: String,
+  signature_path: String,
+  operator_key: PublicKey,
+  keys: HashSet<Vec<u8>>,
+}
+
+impl EndorserAllowlist {
+  /// reads the allowlist at `path` and its signature at `signature_path`, which must verify with
+  /// `operator_key`
+  pub fn load(path: &str, signature_path: &str, operator_key: PublicKey) -> Result<Self, String> {
+    let read = |path: &str| {
+      std::fs::read(path).map_err(|e| format!("cannot read the endorser allowlist {}: {}", path, e))
+    };
+    let keys = parse_allowlist(&read(path)?, &read(signature_path)?, &operator_key)
+      .map_err(|e| format!("invalid endorser allowlist {}: {}", path, e))?;
+    Ok(EndorserAllowlist {
+      path: path.to_string(),
+      signature_path: signature_path.to_string(),
+      operator_key,
+      keys,
+    })
+  }
+
+  /// reads the files of the allowlist again
+  pub fn reload(&self) -> Result<Self, String> {
+    EndorserAllowlist::load(&self.path, &self.signature_path, self.operator_key.clone())
+  }
+
+  pub fn contains(&self, pk: &[u8]) -> bool {
+    self.keys.contains(pk)
+  }
+
+  /// the number of keys of the allowlist
+  pub fn num_keys(&self) -> usize {
+    self.keys.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::signature::{PrivateKey, PrivateKeyTrait};
+
+  #[test]
+  pub fn test_allowlist() {
+    let operator = PrivateKey::new();
+    let operator_key = operator.get_public_key().unwrap();
+    let sign = |contents: &[u8]| {
+      let signature = operator.sign(&signed_message(contents)).unwrap();
+      hex::encode(signature.to_bytes()).into_bytes()
+    };
+    let endorser = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    let other = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    let contents = format!(
+      "# the endorsers of the cluster\n{}  # endorser-1\n\n{}\n",
+      hex::encode(&endorser),
+      hex::encode(&other)
+    )
+    .into_bytes();
+
+    let keys = parse_allowlist(&contents, &sign(&contents), &operator_key).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert!(keys.contains(&endorser) && keys.contains(&other));
+
+    // a file that differs from the one the operator signed is refused
+    let mut tampered = contents.clone();
+    tampered.extend_from_slice(hex::encode(&endorser[1..]).as_bytes());
+    let res = parse_allowlist(&tampered, &sign(&contents), &operator_key);
+    assert!(res.unwrap_err().contains("does not verify"));
+    let res = parse_allowlist(&contents, &sign(&contents), &parse_key(&other));
+    assert!(res.unwrap_err().contains("does not verify"));
+    // the signature covers the domain too
+    let signature = operator.sign(&contents).unwrap().to_bytes();
+    let res = parse_allowlist(&contents, hex::encode(signature).as_bytes(), &operator_key);
+    assert!(res.is_err());
+    let res = parse_allowlist(&contents, b"not a signature", &operator_key);
+    assert!(res.unwrap_err().contains("hex-encoded signature"));
+    // the lines of a signed file must still be keys
+    let res = parse_allowlist(b"endorser-1\n", &sign(b"endorser-1\n"), &operator_key);
+    assert!(res.unwrap_err().contains("endorser-1"));
+
+    assert!(parse_operator_key(&hex::encode(operator_key.to_bytes())).is_ok());
+    assert!(parse_operator_key("00").is_err());
+    assert!(parse_operator_key("operator").is_err());
+  }
+
+  fn parse_key(bytes: &[u8]) -> PublicKey {
+    PublicKey::from_bytes(bytes).unwrap()
+  }
+
+  #[test]
+  pub fn test_load_allowlist() {
+    let operator = PrivateKey::new();
+    let endorser = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    let dir = std::env::temp_dir().join(format!("nimble-allowlist-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("endorsers.allow").to_str().unwrap().to_string();
+    let write = |keys: &[&[u8]]| {
+      let contents = keys
+        .iter()
+        .map(|key| format!("{}\n", hex::encode(key)))
+        .collect::<String>();
+      let signature = operator.sign(&signed_message(contents.as_bytes())).unwrap();
+      std::fs::write(&path, &contents).unwrap();
+      std::fs::write(
+        default_signature_path(&path),
+        hex::encode(signature.to_bytes()),
+      )
+      .unwrap();
+    };
+
+    write(&[&endorser]);
+    let operator_key = operator.get_public_key().unwrap();
+    let allowlist =
+      EndorserAllowlist::load(&path, &default_signature_path(&path), operator_key).unwrap();
+    assert_eq!(allowlist.num_keys(), 1);
+    assert!(allowlist.contains(&endorser));
+
+    let other = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    write(&[&endorser, &other]);
+    let reloaded = allowlist.reload().unwrap();
+    assert!(reloaded.contains(&other));
+    // a file changed without the operator key does not load
+    std::fs::write(&path, hex::encode(&other)).unwrap();
+    assert!(reloaded.reload().unwrap_err().contains("does not verify"));
+    std::fs::remove_file(default_signature_path(&path)).unwrap();
+    assert!(reloaded.reload().unwrap_err().starts_with("cannot read"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
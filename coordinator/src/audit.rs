@@ -0,0 +1,435 @@
+//! The audit ledger of the coordinator, which makes its own history as tamper-evident as the
+//! ledgers of its clients. Every call that changes something, to the client services or to the
+//! admin service, becomes an `AuditRecord` with who made it, its method, the digest of its
+//! parameters, its outcome and the time of the coordinator, and the records are appended in order
+//! to an internal ledger through the normal append path, so that they get the receipts of the
+//! endorsers like any other entry.
+//!
+//! A record is queued once its call returned, and a writer of its own appends the queue; a call
+//! never waits for its record, so that an audit append cannot deadlock with the operation that it
+//! records, e.g., on the lock of a ledger, a view change or a shutdown. While the appends fail, the
+//! queue holds the records until the writer catches up; once it is full, records are dropped and
+//! only logged to the `nimble_audit` target.
+use crate::{
+  authz::{Identity, AUDIT_TARGET},
+  config::AuditConfig,
+  coordinator_admin_proto::AuditRecord,
+  coordinator_state::{CoordinatorState, Deadline},
+  errors::CoordinatorError,
+  tenant::request_tenant,
+};
+use ledger::hash::{NimbleHasher, Sha256Hasher};
+use prost::Message;
+use std::{
+  cmp,
+  future::Future,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+/// the handle of the audit ledger unless one is configured
+pub const DEFAULT_AUDIT_LEDGER: &str = "nimble-audit";
+pub const DEFAULT_AUDIT_QUEUE_SIZE: usize = 65536; // records: queued for the audit ledger at most
+pub const AUDIT_RECORD_VERSION: u32 = 1; // the version of the records that are appended
+const AUDIT_LEDGER_GENESIS: &[u8] = b"NimbleAuditLedger/v1"; // the block at height 0
+const AUDIT_REQUEST_ID_PREFIX: &str = "audit-"; // followed by the sequence number of the record
+const APPEND_TIMEOUT: Duration = Duration::from_secs(10); // the time an audit append may take
+/// how long the writer waits after the first append that fails; the wait doubles with every
+/// failure after it, up to `MAX_BACKOFF`
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// the services whose calls are recorded, as they name the methods of the records
+pub const CALL_SERVICE: &str = "coordinator_proto.Call";
+pub const CHECKPOINT_SERVICE: &str = "checkpoint_proto.Checkpoint";
+pub const ADMIN_SERVICE: &str = "coordinator_admin_proto.Admin";
+
+/// the methods of the client service that change ledgers; the others only read
+const CALL_MUTATIONS: [&str; 4] = ["NewLedger", "Append", "AppendBatch", "SealLedger"];
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+/// whether a call to method `method` of the client service is recorded
+pub fn is_call_mutation(method: &str) -> bool {
+  CALL_MUTATIONS.contains(&method)
+}
+
+/// the block of the entry of `record`
+pub fn encode_audit_record(record: &AuditRecord) -> Vec<u8> {
+  record.encode_to_vec()
+}
+
+/// decodes the block of an entry of the audit ledger; a block of another version, or that is not
+/// the canonical encoding of its record, is not a record
+pub fn decode_audit_record(block: &[u8]) -> Option<AuditRecord> {
+  let record = AuditRecord::decode(block).ok()?;
+  if record.version != AUDIT_RECORD_VERSION || record.encode_to_vec() != block {
+    return None;
+  }
+  Some(record)
+}
+
+/// the record of a call to `method` of `service` with `request`, before it returns
+fn start_record<R: Message>(service: &str, method: &str, request: &Request<R>) -> AuditRecord {
+  let identity = request.extensions().get::<Identity>();
+  let tenant = request_tenant(request).map(|tenant| tenant.0);
+  AuditRecord {
+    version: AUDIT_RECORD_VERSION,
+    timestamp_ms: 0,
+    principal: identity
+      .map(|identity| identity.principal.clone())
+      .unwrap_or_default(),
+    tenant: tenant.unwrap_or_default(),
+    role: identity
+      .map(|identity| identity.role.as_str().to_string())
+      .unwrap_or_default(),
+    method: format!("{}/{}", service, method),
+    params_digest: Sha256Hasher::digest(&request.get_ref().encode_to_vec()).to_vec(),
+    code: 0,
+    message: String::new(),
+  }
+}
+
+/// the records of the calls that changed something, which `AuditWriter` appends to the audit
+/// ledger
+pub struct AuditLog {
+  ledger: Vec<u8>,
+  queue: mpsc::Sender<AuditRecord>,
+  /// the records that were dropped since the queue was full
+  dropped: AtomicU64,
+}
+
+/// the records queued for the audit ledger, which `AuditLog::new` makes for the writer
+pub struct AuditQueue {
+  ledger: Vec<u8>,
+  records: mpsc::Receiver<AuditRecord>,
+}
+
+impl AuditLog {
+  /// an audit log of the ledger `ledger` that queues up to `capacity` records, and its queue
+  pub fn new(ledger: &[u8], capacity: usize) -> (Self, AuditQueue) {
+    let (queue, records) = mpsc::channel(cmp::max(capacity, 1));
+    let log = AuditLog {
+      ledger: ledger.to_vec(),
+      queue,
+      dropped: AtomicU64::new(0),
+    };
+    let queue = AuditQueue {
+      ledger: ledger.to_vec(),
+      records,
+    };
+    (log, queue)
+  }
+
+  /// the handle of the audit ledger
+  pub fn ledger(&self) -> &[u8] {
+    &self.ledger
+  }
+
+  /// queues `record` for the audit ledger without waiting; it is dropped if the queue is full
+  pub fn record(&self, record: AuditRecord) {
+    let record = match self.queue.try_send(record) {
+      Ok(()) => return,
+      Err(TrySendError::Full(record)) | Err(TrySendError::Closed(record)) => record,
+    };
+    let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+    warn!(
+      target: AUDIT_TARGET,
+      principal = %record.principal,
+      tenant = %record.tenant,
+      method = %record.method,
+      params_digest = %hex::encode(&record.params_digest),
+      code = record.code,
+      dropped,
+      "dropped an audit record; the audit queue is full"
+    );
+  }
+}
+
+/// runs `handler` on `request`, a call to `method` of `service`, and queues the record of the
+/// call with its outcome once it returned, if there is an audit log
+pub async fn audited<R, T, F>(
+  audit: &Option<Arc<AuditLog>>,
+  service: &'static str,
+  method: &'static str,
+  request: Request<R>,
+  handler: impl FnOnce(Request<R>) -> F,
+) -> Result<Response<T>, Status>
+where
+  R: Message,
+  F: Future<Output = Result<Response<T>, Status>>,
+{
+  let audit = match audit {
+    Some(audit) => audit,
+    None => return handler(request).await,
+  };
+  let mut record = start_record(service, method, &request);
+  let res = handler(request).await;
+  record.timestamp_ms = now_ms();
+  if let Err(status) = &res {
+    record.code = status.code() as u32;
+    record.message = status.message().to_string();
+  }
+  audit.record(record);
+  res
+}
+
+/// appends the queued records to the audit ledger, in the order they were queued
+pub struct AuditWriter {
+  state: Arc<CoordinatorState>,
+  queue: AuditQueue,
+  /// the height of the tail of the audit ledger, if the writer knows it
+  tail_height: Option<usize>,
+  /// the sequence number of the next record, which makes the request ID of its append; a
+  /// coordinator starts with the time, so that its request IDs differ from those of the
+  /// coordinators before it
+  next_seq: u64,
+}
+
+impl AuditWriter {
+  pub fn new(state: Arc<CoordinatorState>, queue: AuditQueue) -> Self {
+    AuditWriter {
+      state,
+      queue,
+      tail_height: None,
+      next_seq: now_ms() << 20,
+    }
+  }
+
+  /// the handle of the audit ledger
+  pub fn ledger(&self) -> &[u8] {
+    &self.queue.ledger
+  }
+
+  /// appends `block` after the tail of the audit ledger, which is created with the first record.
+  /// The append carries `request_id`, so that retrying an append that reached the store returns
+  /// its entry rather than appending the record twice
+  async fn append(&mut self, request_id: &str, block: &[u8]) -> Result<(), CoordinatorError> {
+    let deadline = Deadline::after(APPEND_TIMEOUT);
+    let ledger = &self.queue.ledger;
+    let tail_height = match self.tail_height {
+      Some(height) => height,
+      None => match self.state.read_cached_ledger_tail(ledger).await {
+        Ok((_entry, height)) => height,
+        Err(CoordinatorError::LedgerNotFound) => {
+          self
+            .state
+            .create_ledger_with_deadline(None, ledger, AUDIT_LEDGER_GENESIS, &[], &[], deadline)
+            .await?;
+          0
+        },
+        Err(e) => return Err(e),
+      },
+    };
+    // another coordinator may have appended meanwhile, so the tail is read again after a failure
+    let res = self
+      .state
+      .append_ledger_with_request_id(ledger, block, tail_height + 1, request_id, deadline)
+      .await;
+    match res {
+      Ok((height, _hash_nonces, _receipts)) => {
+        self.tail_height = Some(height);
+        Ok(())
+      },
+      Err(e) => {
+        self.tail_height = None;
+        Err(e)
+      },
+    }
+  }
+
+  /// appends the records as they are queued until `stopped` completes. While appends fail, the
+  /// writer retries the oldest record, backing off from `MIN_BACKOFF` up to `MAX_BACKOFF`, and logs
+  /// only when the failures start and when they stop
+  pub async fn run(mut self, stopped: impl Future<Output = ()>) {
+    tokio::pin!(stopped);
+    let mut backoff: Option<Duration> = None;
+    loop {
+      let record = tokio::select! {
+        () = &mut stopped => break,
+        record = self.queue.records.recv() => match record {
+          Some(record) => record,
+          None => break,
+        },
+      };
+      let request_id = format!("{}{}", AUDIT_REQUEST_ID_PREFIX, self.next_seq);
+      self.next_seq += 1;
+      let block = encode_audit_record(&record);
+      loop {
+        let wait = match self.append(&request_id, &block).await {
+          Ok(()) => {
+            if backoff.take().is_some() {
+              info!("The audit appends recovered");
+            }
+            break;
+          },
+          Err(e) => {
+            let wait = match backoff {
+              None => {
+                warn!(error = %e, "Failed to append an audit record; backing off");
+                MIN_BACKOFF
+              },
+              Some(wait) => cmp::min(wait * 2, MAX_BACKOFF),
+            };
+            backoff = Some(wait);
+            wait
+          },
+        };
+        tokio::select! {
+          () = &mut stopped => {
+            warn!(
+              method = %record.method,
+              "The coordinator stopped before it appended an audit record"
+            );
+            return;
+          },
+          () = tokio::time::sleep(wait) => {},
+        }
+      }
+    }
+  }
+}
+
+/// the audit log that `config` enables, and the queue of its records for `start_audit_writer`
+pub fn audit_from_config(config: &AuditConfig) -> (Option<Arc<AuditLog>>, Option<AuditQueue>) {
+  if !config.enabled {
+    return (None, None);
+  }
+  let ledger = config.ledger.as_deref().unwrap_or(DEFAULT_AUDIT_LEDGER);
+  let capacity = config.queue_size.unwrap_or(DEFAULT_AUDIT_QUEUE_SIZE);
+  let (audit, queue) = AuditLog::new(ledger.as_bytes(), capacity);
+  (Some(Arc::new(audit)), Some(queue))
+}
+
+/// appends the records of `queue` to the audit ledger until `stopped` completes
+pub fn start_audit_writer(
+  state: Arc<CoordinatorState>,
+  queue: AuditQueue,
+  stopped: impl Future<Output = ()> + Send + 'static,
+) {
+  let writer = AuditWriter::new(state, queue);
+  info!(
+    "Appending a record of every call that changes state to {}",
+    String::from_utf8_lossy(writer.ledger())
+  );
+  let _job = tokio::spawn(writer.run(stopped));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{authz::Role, tenant::Tenant};
+  use tonic::Code;
+
+  #[test]
+  pub fn test_audit_records() {
+    let record = AuditRecord {
+      version: AUDIT_RECORD_VERSION,
+      timestamp_ms: 1_700_000_000_000,
+      principal: "ApiKey:ops".to_string(),
+      tenant: "hdfs".to_string(),
+      role: "admin".to_string(),
+      method: format!("{}/AddEndorser", ADMIN_SERVICE),
+      params_digest: vec![1u8; 32],
+      code: Code::Ok as u32,
+      message: String::new(),
+    };
+    let block = encode_audit_record(&record);
+    assert_eq!(decode_audit_record(&block), Some(record.clone()));
+    let other_version = AuditRecord {
+      version: AUDIT_RECORD_VERSION + 1,
+      ..record
+    };
+    assert_eq!(
+      decode_audit_record(&encode_audit_record(&other_version)),
+      None
+    );
+    assert_eq!(decode_audit_record(AUDIT_LEDGER_GENESIS), None);
+    let mut padded = block;
+    padded.extend_from_slice(&[0x50, 0x00]);
+    assert_eq!(decode_audit_record(&padded), None);
+
+    assert!(is_call_mutation("Append") && is_call_mutation("SealLedger"));
+    assert!(!is_call_mutation("ReadLatest") && !is_call_mutation("Watch"));
+  }
+
+  #[tokio::test]
+  pub async fn test_audited() {
+    let (log, mut queue) = AuditLog::new(b"audit", 1);
+    let audit = Some(Arc::new(log));
+    let request = |handle: &[u8]| {
+      let mut request = Request::new(AuditRecord {
+        method: String::from_utf8(handle.to_vec()).unwrap(),
+        ..Default::default()
+      });
+      let identity = Identity::new(
+        "ApiKey",
+        "ops",
+        Some(Tenant("hdfs".to_string())),
+        Role::Writer,
+      );
+      request
+        .extensions_mut()
+        .insert(identity.tenant.clone().unwrap());
+      request.extensions_mut().insert(identity);
+      request
+    };
+
+    // the record of a call carries its identity, the digest of its parameters and its outcome
+    let res = audited(
+      &audit,
+      CALL_SERVICE,
+      "Append",
+      request(b"a"),
+      |req| async move { Err::<Response<()>, _>(Status::not_found(req.into_inner().method)) },
+    )
+    .await;
+    assert_eq!(res.unwrap_err().code(), Code::NotFound);
+    let record = queue.records.try_recv().unwrap();
+    assert_eq!(
+      (record.principal.as_str(), record.tenant.as_str()),
+      ("ApiKey:ops", "hdfs")
+    );
+    assert_eq!(record.role, "writer");
+    assert_eq!(record.method, "coordinator_proto.Call/Append");
+    assert_eq!((record.code, record.message.as_str()), (5, "a"));
+    assert!(record.timestamp_ms > 0);
+    let params = request(b"a").into_inner().encode_to_vec();
+    assert_eq!(record.params_digest, Sha256Hasher::digest(&params).to_vec());
+
+    // a call does not wait for a full queue; its record is dropped
+    for handle in [b"b", b"c"] {
+      let res = audited(
+        &audit,
+        CALL_SERVICE,
+        "Append",
+        request(handle),
+        |_req| async { Ok(Response::new(())) },
+      )
+      .await;
+      assert!(res.is_ok());
+    }
+    let record = queue.records.try_recv().unwrap();
+    assert_eq!((record.code, record.message.as_str()), (0, ""));
+    assert!(queue.records.try_recv().is_err());
+    assert_eq!(audit.as_ref().unwrap().dropped.load(Ordering::Relaxed), 1);
+
+    // without an audit log, nothing is recorded
+    let res = audited(&None, CALL_SERVICE, "Append", request(b"d"), |_req| async {
+      Ok(Response::new(()))
+    })
+    .await;
+    assert!(res.is_ok());
+  }
+}
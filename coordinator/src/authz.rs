@@ -0,0 +1,472 @@
+//! Authorization of the calls to the coordinator. Every way in which a client authenticates, be it
+//! a tenant token, a delegation token, SPNEGO or an API key, yields an `Identity`: who the client
+//! is, the tenant whose ledgers it works on, and its role. A call is then allowed by the role that
+//! its method needs alone, so that a new way to authenticate, such as the certificates of clients,
+//! only has to yield identities.
+//!
+//! Readers read ledgers, writers also create, append to and seal them, and admins also reach the
+//! admin service. Tenant tokens, delegation tokens and Kerberos principals make writers, as the
+//! tenants have always been; API keys carry the role that the configuration gives them. Every
+//! decision is logged to the `nimble_audit` target with the identity that it was made for.
+use crate::{
+  rate_limit::{Budget, HEALTH_SERVICE_PATH},
+  tenant::{Authenticator, ClientAuth, Tenant, TENANT_SEPARATOR},
+};
+use ledger::hash::{NimbleHasher, Sha256Hasher};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt,
+  task::{Context, Poll},
+};
+use tonic::{
+  body::BoxBody,
+  codegen::{
+    http::{Request, Response},
+    BoxFuture,
+  },
+  Status,
+};
+use tower::{Layer, Service};
+
+/// the scheme of the `authorization` headers that carry API keys
+pub const API_KEY_SCHEME: &str = "ApiKey";
+/// the target of the audit log, which records who made each call and whether it was allowed
+pub const AUDIT_TARGET: &str = "nimble_audit";
+const ADMIN_SERVICE_PATH: &str = "/coordinator_admin_proto.Admin/";
+
+/// what a client may do; each role may do what the roles before it may
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  Reader,
+  Writer,
+  Admin,
+}
+
+impl Role {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Role::Reader => "reader",
+      Role::Writer => "writer",
+      Role::Admin => "admin",
+    }
+  }
+
+  /// the role that a call to the client service needs, from the budget it draws from
+  pub fn of_budget(budget: Budget) -> Self {
+    match budget {
+      Budget::Reads => Role::Reader,
+      Budget::Appends => Role::Writer,
+    }
+  }
+
+  /// the role that a call needs, from the path of its gRPC method; the probes of orchestrators
+  /// need none
+  pub fn of_path(path: &str) -> Option<Self> {
+    if path.starts_with(HEALTH_SERVICE_PATH) {
+      None
+    } else if path.starts_with(ADMIN_SERVICE_PATH) {
+      Some(Role::Admin)
+    } else {
+      Some(Role::of_budget(Budget::of_path(path)))
+    }
+  }
+}
+
+impl fmt::Display for Role {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// who sent a call, as the coordinator authenticated it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+  /// the name of the client, prefixed with how it authenticated, e.g., `ApiKey:ops`
+  pub principal: String,
+  /// the tenant whose ledgers the client works on, if it has one
+  pub tenant: Option<Tenant>,
+  pub role: Role,
+}
+
+impl Identity {
+  pub fn new(scheme: &str, name: &str, tenant: Option<Tenant>, role: Role) -> Self {
+    Identity {
+      principal: format!("{}:{}", scheme, name),
+      tenant,
+      role,
+    }
+  }
+}
+
+/// an API key of the configuration; the coordinator keeps only the SHA-256 digest of the key,
+/// so that the configuration can be read without giving the key away
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+  /// the hex SHA-256 digest of the key, or a reference to a secret that holds it
+  pub hash: String,
+  pub role: Role,
+  pub tenant: String,
+}
+
+/// the hex SHA-256 digest of an API key, as the configuration holds it
+pub fn api_key_hash(key: &str) -> String {
+  hex::encode(Sha256Hasher::digest(key.as_bytes()))
+}
+
+/// checks the API keys of the configuration, which are keyed by their names
+pub fn check_api_keys(keys: &BTreeMap<String, ApiKeyConfig>) -> Result<(), String> {
+  let mut hashes = HashMap::new();
+  for (name, key) in keys {
+    let digest = hex::decode(&key.hash)
+      .ok()
+      .filter(|digest| digest.len() == 32);
+    if digest.is_none() {
+      return Err(format!(
+        "the hash of the API key {} is not a hex SHA-256 digest",
+        name
+      ));
+    }
+    if key.tenant.is_empty() || key.tenant.as_bytes().contains(&TENANT_SEPARATOR) {
+      return Err(format!(
+        "the API key {} has an invalid tenant {:?}",
+        name, key.tenant
+      ));
+    }
+    if let Some(other) = hashes.insert(key.hash.to_ascii_lowercase(), name) {
+      return Err(format!(
+        "the API keys {} and {} have the same hash",
+        other, name
+      ));
+    }
+  }
+  Ok(())
+}
+
+/// the API keys of the configuration, which clients send as `authorization: ApiKey <key>`
+pub struct ApiKeys {
+  /// the names and the settings of the keys, keyed by their digests
+  keys: HashMap<String, (String, ApiKeyConfig)>,
+}
+
+impl ApiKeys {
+  pub fn new(keys: &BTreeMap<String, ApiKeyConfig>) -> Self {
+    ApiKeys {
+      keys: keys
+        .iter()
+        .map(|(name, key)| (key.hash.to_ascii_lowercase(), (name.clone(), key.clone())))
+        .collect(),
+    }
+  }
+
+  /// the name and the settings of a key; the digests are looked up rather than the keys, which
+  /// the coordinator does not have
+  #[allow(clippy::result_large_err)]
+  fn key(&self, key: &str) -> Result<&(String, ApiKeyConfig), Status> {
+    self
+      .keys
+      .get(&api_key_hash(key))
+      .ok_or_else(|| Status::unauthenticated("Invalid API key"))
+  }
+}
+
+impl ClientAuth for ApiKeys {
+  fn scheme(&self) -> &'static str {
+    API_KEY_SCHEME
+  }
+
+  fn authenticate(&self, credentials: &str) -> Result<Tenant, Status> {
+    let (_name, key) = self.key(credentials)?;
+    Ok(Tenant(key.tenant.clone()))
+  }
+
+  fn identify(&self, credentials: &str) -> Result<Identity, Status> {
+    let (name, key) = self.key(credentials)?;
+    let tenant = Tenant(key.tenant.clone());
+    Ok(Identity::new(API_KEY_SCHEME, name, Some(tenant), key.role))
+  }
+}
+
+/// the identity that an `authorization` header authenticates, if it may make a call that needs
+/// `role`; the decision is logged for audits either way
+#[allow(clippy::result_large_err)]
+pub fn authorize(
+  auth: &Authenticator,
+  authorization: Option<&str>,
+  method: &str,
+  role: Role,
+) -> Result<Identity, Status> {
+  let identity = match auth.identify(authorization) {
+    Ok(identity) => identity,
+    Err(status) => {
+      tracing::warn!(
+        target: AUDIT_TARGET,
+        method,
+        allowed = false,
+        reason = status.message(),
+        "unauthenticated call"
+      );
+      return Err(status);
+    },
+  };
+  let allowed = identity.role >= role;
+  let tenant = identity.tenant.as_ref().map(|tenant| tenant.0.as_str());
+  tracing::info!(
+    target: AUDIT_TARGET,
+    principal = %identity.principal,
+    tenant = tenant.unwrap_or_default(),
+    role = identity.role.as_str(),
+    method,
+    allowed,
+    "authorized call"
+  );
+  if !allowed {
+    return Err(Status::permission_denied(format!(
+      "{} has the role {}, and {} needs the role {}",
+      identity.principal, identity.role, method, role
+    )));
+  }
+  Ok(identity)
+}
+
+/// authenticates the calls to a gRPC service and allows them by the role of their identity, which
+/// is attached to the request along with its tenant; a service without an authenticator is open
+#[derive(Clone)]
+pub struct AuthLayer {
+  auth: Option<Authenticator>,
+}
+
+impl AuthLayer {
+  pub fn new(auth: Option<Authenticator>) -> Self {
+    AuthLayer { auth }
+  }
+}
+
+impl<S> Layer<S> for AuthLayer {
+  type Service = AuthService<S>;
+
+  fn layer(&self, inner: S) -> Self::Service {
+    AuthService {
+      inner,
+      layer: self.clone(),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+  inner: S,
+  layer: AuthLayer,
+}
+
+impl<S, B> Service<Request<B>> for AuthService<S>
+where
+  S: Service<Request<B>, Response = Response<BoxBody>>,
+  S::Future: Send + 'static,
+{
+  type Response = S::Response;
+  type Error = S::Error;
+  type Future = BoxFuture<Self::Response, Self::Error>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, mut req: Request<B>) -> Self::Future {
+    let (auth, role) = match (&self.layer.auth, Role::of_path(req.uri().path())) {
+      (Some(auth), Some(role)) => (auth, role),
+      _ => return Box::pin(self.inner.call(req)),
+    };
+    let authorization = req
+      .headers()
+      .get("authorization")
+      .and_then(|value| value.to_str().ok());
+    match authorize(auth, authorization, req.uri().path(), role) {
+      Ok(identity) => {
+        // the service scopes the handles of the tenant; tonic hands the extensions on to it
+        if let Some(tenant) = identity.tenant.clone() {
+          req.extensions_mut().insert(tenant);
+        }
+        req.extensions_mut().insert(identity);
+        Box::pin(self.inner.call(req))
+      },
+      Err(status) => {
+        let response = status.to_http();
+        Box::pin(async move { Ok(response) })
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::admin::AdminToken;
+  use std::{convert::Infallible, sync::Arc};
+  use tonic::Code;
+  use tower::ServiceExt;
+
+  fn api_keys() -> BTreeMap<String, ApiKeyConfig> {
+    let key = |key: &str, role: Role| ApiKeyConfig {
+      hash: api_key_hash(key),
+      role,
+      tenant: "hdfs".to_string(),
+    };
+    BTreeMap::from([
+      ("dashboard".to_string(), key("key-r", Role::Reader)),
+      ("namenode".to_string(), key("key-w", Role::Writer)),
+      ("ops".to_string(), key("key-a", Role::Admin)),
+    ])
+  }
+
+  #[test]
+  pub fn test_api_keys() {
+    assert!(check_api_keys(&api_keys()).is_ok());
+    let mut keys = api_keys();
+    keys.get_mut("ops").unwrap().hash = "key-a".to_string();
+    assert!(check_api_keys(&keys).unwrap_err().contains("SHA-256"));
+    let mut keys = api_keys();
+    keys.get_mut("ops").unwrap().tenant = "hdfs/ops".to_string();
+    assert!(check_api_keys(&keys).unwrap_err().contains("tenant"));
+    let mut keys = api_keys();
+    keys.get_mut("ops").unwrap().hash = api_key_hash("key-r").to_uppercase();
+    assert!(check_api_keys(&keys).unwrap_err().contains("same hash"));
+
+    let tenants = HashMap::from([("token-b".to_string(), "hbase".to_string())]);
+    let auth = Authenticator::from(tenants).with_auth(Arc::new(ApiKeys::new(&api_keys())));
+    let identity = auth.identify(Some("ApiKey key-r")).unwrap();
+    assert_eq!(
+      identity,
+      Identity {
+        principal: "ApiKey:dashboard".to_string(),
+        tenant: Some(Tenant("hdfs".to_string())),
+        role: Role::Reader,
+      }
+    );
+    assert_eq!(
+      auth.authenticate(Some("ApiKey key-w")).unwrap(),
+      Tenant("hdfs".to_string())
+    );
+    // the tenants of tokens are writers
+    let identity = auth.identify(Some("Bearer token-b")).unwrap();
+    assert_eq!(identity.principal, "Bearer:hbase");
+    assert_eq!(identity.role, Role::Writer);
+    let res = auth.identify(Some(&format!("ApiKey {}", api_key_hash("key-r"))));
+    assert_eq!(res.unwrap_err().code(), Code::Unauthenticated);
+
+    // a reader reads, a writer appends, and only an admin administers
+    let append = "/coordinator_proto.Call/Append";
+    let read = "/coordinator_proto.Call/ReadLatest";
+    let add_endorser = "/coordinator_admin_proto.Admin/AddEndorser";
+    let allowed = |key: &str, path: &str| {
+      let authorization = format!("ApiKey {}", key);
+      authorize(
+        &auth,
+        Some(&authorization),
+        path,
+        Role::of_path(path).unwrap(),
+      )
+    };
+    assert!(allowed("key-r", read).is_ok());
+    assert_eq!(
+      allowed("key-r", append).unwrap_err().code(),
+      Code::PermissionDenied
+    );
+    assert!(allowed("key-w", append).is_ok());
+    assert_eq!(
+      allowed("key-w", add_endorser).unwrap_err().code(),
+      Code::PermissionDenied
+    );
+    assert!(allowed("key-a", add_endorser).is_ok());
+    assert_eq!(
+      Role::of_path("/checkpoint_proto.Checkpoint/RecordCheckpoint"),
+      Some(Role::Writer)
+    );
+    assert_eq!(Role::of_path("/grpc.health.v1.Health/Check"), None);
+  }
+
+  #[tokio::test]
+  async fn test_auth_layer() {
+    let auth = Authenticator::default()
+      .with_auth(Arc::new(AdminToken::new("secret".to_string())))
+      .with_auth(Arc::new(ApiKeys::new(&api_keys())));
+    let service = AuthLayer::new(Some(auth)).layer(tower::service_fn(|req: Request<()>| {
+      // the service sees who called it
+      let principal = req
+        .extensions()
+        .get::<Identity>()
+        .map(|identity| identity.principal.clone())
+        .unwrap_or_default();
+      let has_tenant = req.extensions().get::<Tenant>().is_some();
+      async move {
+        let mut response = Response::new(BoxBody::default());
+        let headers = response.headers_mut();
+        headers.insert("x-principal", principal.parse().unwrap());
+        headers.insert("x-tenant", has_tenant.to_string().parse().unwrap());
+        Ok::<_, Infallible>(response)
+      }
+    }));
+    let call = |path: &str, authorization: &str| {
+      let mut builder = Request::builder().uri(path);
+      if !authorization.is_empty() {
+        builder = builder.header("authorization", authorization);
+      }
+      builder.body(()).unwrap()
+    };
+    let code =
+      |res: &Response<BoxBody>| Status::from_header_map(res.headers()).map(|status| status.code());
+
+    let add_endorser = "/coordinator_admin_proto.Admin/AddEndorser";
+    let res = service
+      .clone()
+      .oneshot(call(add_endorser, "Bearer secret"))
+      .await
+      .unwrap();
+    assert_eq!(code(&res), None);
+    assert_eq!(res.headers()["x-principal"], "Bearer:admin");
+    assert_eq!(res.headers()["x-tenant"], "false");
+    let res = service
+      .clone()
+      .oneshot(call(add_endorser, "ApiKey key-a"))
+      .await
+      .unwrap();
+    assert_eq!(res.headers()["x-principal"], "ApiKey:ops");
+    assert_eq!(res.headers()["x-tenant"], "true");
+    let res = service
+      .clone()
+      .oneshot(call(add_endorser, "ApiKey key-w"))
+      .await
+      .unwrap();
+    assert_eq!(code(&res), Some(Code::PermissionDenied));
+    let res = service
+      .clone()
+      .oneshot(call(add_endorser, "Bearer other"))
+      .await
+      .unwrap();
+    assert_eq!(code(&res), Some(Code::Unauthenticated));
+    let res = service
+      .clone()
+      .oneshot(call("/coordinator_proto.Call/Append", ""))
+      .await
+      .unwrap();
+    assert_eq!(code(&res), Some(Code::Unauthenticated));
+
+    // the probes of orchestrators are not authenticated, and neither are the calls to an open
+    // service
+    let res = service
+      .oneshot(call("/grpc.health.v1.Health/Check", ""))
+      .await
+      .unwrap();
+    assert_eq!(code(&res), None);
+    let open = AuthLayer::new(None).layer(tower::service_fn(|_req: Request<()>| async {
+      Ok::<_, Infallible>(Response::new(BoxBody::default()))
+    }));
+    let res = open
+      .oneshot(call("/coordinator_proto.Call/Append", ""))
+      .await
+      .unwrap();
+    assert_eq!(code(&res), None);
+  }
+}
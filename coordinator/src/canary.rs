@@ -0,0 +1,301 @@
+//! The canary of the coordinator, a self-test that proves that the whole pipeline works before the
+//! coordinator reports ready. It creates a throwaway ledger, appends a block to it, reads the tail
+//! with a fresh nonce, verifies the receipts of all three with the verifier that clients run, and
+//! optionally seals the ledger. The coordinator is not ready until a canary passed, and runs the
+//! canary again every interval as a probe, so a failure makes it not ready again; the outcome names
+//! the phase that failed. The canary ledgers are named `nimble-canary/<random>`, outside of the
+//! namespaces of the tenants, and listings leave them out.
+use crate::{
+  config::CanaryConfig,
+  coordinator_state::{CoordinatorState, Deadline},
+  health::CanaryOutcome,
+};
+use ledger::{
+  compute_aggregated_block_hash, compute_view_block_hash, CustomSerde, NimbleDigest, Nonce,
+};
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+use tokio::time::Instant;
+use tracing::{info, warn};
+use verifier::VerifierState;
+
+/// the prefix of the handles of the canary ledgers
+pub const CANARY_PREFIX: &[u8] = b"nimble-canary/";
+/// the longest a canary may take before it fails
+const CANARY_TIMEOUT: Duration = Duration::from_secs(30);
+const GENESIS_BLOCK: &[u8] = b"nimble canary";
+const CANARY_BLOCK: &[u8] = b"nimble canary block";
+
+/// what a canary does, in order
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+  Create,
+  Append,
+  Read,
+  Verify,
+  Seal,
+}
+
+impl fmt::Display for Phase {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let phase = match self {
+      Phase::Create => "create",
+      Phase::Append => "append",
+      Phase::Read => "read",
+      Phase::Verify => "verify",
+      Phase::Seal => "seal",
+    };
+    write!(f, "{}", phase)
+  }
+}
+
+/// a canary that failed in `phase`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CanaryError {
+  pub phase: Phase,
+  pub error: String,
+}
+
+impl fmt::Display for CanaryError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.phase, self.error)
+  }
+}
+
+fn failed(phase: Phase, error: impl fmt::Display) -> CanaryError {
+  CanaryError {
+    phase,
+    error: error.to_string(),
+  }
+}
+
+/// runs the canary of the coordinator, and records its outcomes in the health of the coordinator
+pub struct Canary {
+  state: Arc<CoordinatorState>,
+  seal: bool,
+  timeout: Duration,
+}
+
+impl Canary {
+  pub fn new(state: Arc<CoordinatorState>) -> Self {
+    Canary {
+      state,
+      seal: false,
+      timeout: CANARY_TIMEOUT,
+    }
+  }
+
+  /// also seals every canary ledger, and verifies the receipts of the seal
+  pub fn with_seal(mut self, seal: bool) -> Self {
+    self.seal = seal;
+    self
+  }
+
+  /// the handle of a new canary ledger
+  fn new_handle() -> Vec<u8> {
+    let name = hex::encode(rand::random::<[u8; 8]>());
+    [CANARY_PREFIX, name.as_bytes()].concat()
+  }
+
+  /// a verifier that trusts the current view, which it reaches from the first view of the view
+  /// ledger as a client does
+  async fn verifier(&self) -> Result<VerifierState, String> {
+    let (_tail, height, _attestation) = self
+      .state
+      .read_view_tail()
+      .await
+      .map_err(|e| e.to_string())?;
+    let first = self
+      .state
+      .read_view_by_index(1)
+      .await
+      .map_err(|e| e.to_string())?;
+    let first_block = first.get_block().to_bytes();
+    let group_identity = compute_view_block_hash(&first_block)
+      .map_err(|_e| "the first view block is malformed".to_string())?;
+    let mut verifier = VerifierState::from_first_view(
+      &group_identity,
+      &first_block,
+      &first.get_receipts().to_bytes(),
+    )
+    .map_err(|e| e.to_string())?;
+    for index in 2..=height {
+      let entry = self
+        .state
+        .read_view_by_index(index)
+        .await
+        .map_err(|e| e.to_string())?;
+      verifier
+        .apply_view_change(
+          &entry.get_block().to_bytes(),
+          &entry.get_receipts().to_bytes(),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(verifier)
+  }
+
+  /// runs the phases of the canary on a new canary ledger, which it returns the handle of
+  pub async fn probe(&self) -> Result<Vec<u8>, CanaryError> {
+    let handle = Canary::new_handle();
+    let deadline = Deadline::after(self.timeout);
+
+    let created = self
+      .state
+      .create_ledger_with_deadline(None, &handle, GENESIS_BLOCK, &[], &[], deadline)
+      .await
+      .map_err(|e| failed(Phase::Create, e))?;
+
+    let (hash_nonces, appended) = self
+      .state
+      .append_ledger_with_deadline(None, &handle, CANARY_BLOCK, 1, deadline)
+      .await
+      .map_err(|e| failed(Phase::Append, e))?;
+
+    let nonce = Nonce::new();
+    let tail = self
+      .state
+      .read_ledger_tail(&handle, &nonce.to_bytes())
+      .await
+      .map_err(|e| failed(Phase::Read, e))?;
+    let height = tail
+      .get_receipts()
+      .get_metablock()
+      .map_err(|_e| failed(Phase::Read, "the receipts of the tail are inconsistent"))?
+      .get_height();
+    if height != 1 || tail.get_block().to_bytes() != CANARY_BLOCK {
+      return Err(failed(
+        Phase::Read,
+        "the tail is not the block that was appended",
+      ));
+    }
+
+    let verifier = self
+      .verifier()
+      .await
+      .map_err(|e| failed(Phase::Verify, e))?;
+    let genesis = verifier
+      .verify_new_ledger(&handle, GENESIS_BLOCK, &[], &created.to_bytes())
+      .map_err(|e| failed(Phase::Verify, e))?;
+    let block_hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(CANARY_BLOCK).to_bytes(),
+      &hash_nonces.to_bytes(),
+    );
+    let appended = verifier
+      .verify_append(
+        &handle,
+        &block_hash,
+        1,
+        Some(&genesis),
+        &appended.to_bytes(),
+      )
+      .map_err(|e| failed(Phase::Verify, e))?;
+    let block_hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(&tail.get_block().to_bytes()).to_bytes(),
+      &NimbleDigest::digest(&tail.get_nonces().to_bytes()).to_bytes(),
+    );
+    let read = verifier
+      .verify_read_latest(
+        &handle,
+        &nonce,
+        &block_hash,
+        height,
+        &tail.get_receipts().to_bytes(),
+      )
+      .map_err(|e| failed(Phase::Verify, e))?;
+    if read != appended {
+      return Err(failed(
+        Phase::Verify,
+        "the read tail is not the appended one",
+      ));
+    }
+
+    if self.seal {
+      let (height, block, hash_nonces, receipts) = self
+        .state
+        .seal_ledger(&handle, deadline)
+        .await
+        .map_err(|e| failed(Phase::Seal, e))?;
+      let block_hash = compute_aggregated_block_hash(
+        &NimbleDigest::digest(&block.to_bytes()).to_bytes(),
+        &hash_nonces.to_bytes(),
+      );
+      verifier
+        .verify_append(
+          &handle,
+          &block_hash,
+          height,
+          Some(&read),
+          &receipts.to_bytes(),
+        )
+        .map_err(|e| failed(Phase::Seal, e))?;
+    }
+    Ok(handle)
+  }
+
+  /// runs the canary once, logs its outcome and records it in the health of the coordinator;
+  /// returns whether it passed
+  pub async fn run_once(&self) -> bool {
+    let start = Instant::now();
+    let res = self.probe().await;
+    let latency = start.elapsed();
+    let failure = match &res {
+      Ok(handle) => {
+        info!(
+          ledger = %String::from_utf8_lossy(handle),
+          latency_ms = latency.as_millis() as u64,
+          "The canary passed"
+        );
+        None
+      },
+      Err(e) => {
+        warn!(
+          phase = %e.phase,
+          error = %e.error,
+          latency_ms = latency.as_millis() as u64,
+          "The canary failed"
+        );
+        Some(e.to_string())
+      },
+    };
+    self
+      .state
+      .health()
+      .record_canary(CanaryOutcome { failure, latency });
+    res.is_ok()
+  }
+
+  /// runs the canary every `interval` until `stopped` completes
+  pub async fn run(self, interval: Duration, stopped: impl Future<Output = ()>) {
+    tokio::pin!(stopped);
+    loop {
+      tokio::select! {
+        () = &mut stopped => return,
+        () = tokio::time::sleep(interval) => {},
+      }
+      self.run_once().await;
+    }
+  }
+}
+
+/// the canary that `config` enables; the coordinator is then not ready until it passes, so it is
+/// made before the coordinator reports that it recovered
+pub fn canary_from_config(state: Arc<CoordinatorState>, config: &CanaryConfig) -> Option<Canary> {
+  if !config.enabled {
+    return None;
+  }
+  state.health().require_canary();
+  Some(Canary::new(state).with_seal(config.seal))
+}
+
+/// runs `canary` once, and then every interval of `config` until `stopped` completes
+pub async fn start_canary(
+  canary: Canary,
+  config: &CanaryConfig,
+  stopped: impl Future<Output = ()> + Send + 'static,
+) {
+  canary.run_once().await;
+  if let Some(secs) = config.interval {
+    info!("Running the canary every {}s", secs);
+    let _job = tokio::spawn(canary.run(Duration::from_secs(secs), stopped));
+  }
+}
@@ -0,0 +1,858 @@
+//! The endorsement of the fsimage checkpoints of HDFS namespaces. The checkpoints of a namespace
+//! are the entries of a ledger whose handle is derived from the namespace ID, and the block of
+//! each entry is a `CheckpointRecord` in its canonical encoding, so the digest of an fsimage is
+//! checked against the entry with its txid and the receipts of the ledger.
+//!
+//! The finalized edit-log segments of a namespace, which cover the transactions between
+//! checkpoints, are the entries of an edits ledger of the namespace in the same way, with an
+//! `EditSegmentRecord` each. JournalNodes may record them in any order, so a range of txids is
+//! checked against all of them.
+use crate::{
+  audit::{audited, AuditLog, CHECKPOINT_SERVICE},
+  checkpoint_proto::{
+    checkpoint_server::Checkpoint, CheckpointConflict, CheckpointRecord, EditMismatch, EditOverlap,
+    EditSegment, EditSegmentRecord, GetLatestCheckpointReq, GetLatestCheckpointResp,
+    RecordCheckpointReq, RecordCheckpointResp, RecordEditSegmentReq, RecordEditSegmentResp,
+    TxidRange, VerifyCheckpointReq, VerifyCheckpointResp, VerifyEditRangeReq, VerifyEditRangeResp,
+  },
+  coordinator_state::{CoordinatorState, Deadline},
+  errors::CoordinatorError,
+  process_error, request_deadline,
+  tenant::{request_tenant, scope_handle},
+  validate,
+};
+use ledger::{CustomSerde, NimbleDigest, NimbleHashTrait};
+use prost::Message;
+use std::{cmp, sync::Arc};
+use store::ledger::LedgerEntry;
+use tonic::{Code, Request, Response, Status};
+use tracing::warn;
+
+pub const CHECKPOINT_RECORD_VERSION: u32 = 1; // the version of the records that are appended
+pub const CHECKPOINT_HEARTBEAT_VERSION: u32 = 2; // the version of the heartbeats of the coordinator
+pub const CHECKPOINT_HANDLE_PREFIX: &[u8] = b"hdfs-checkpoint/"; // followed by the namespace ID
+const CHECKPOINT_LEDGER_GENESIS: &[u8] = b"NimbleCheckpointLedger/v1"; // the block at height 0
+pub const EDIT_SEGMENT_RECORD_VERSION: u32 = 1; // the version of the segments that are appended
+pub const EDITS_HANDLE_PREFIX: &[u8] = b"hdfs-edits/"; // followed by the namespace ID
+const EDITS_LEDGER_GENESIS: &[u8] = b"NimbleEditsLedger/v1"; // the block at height 0
+const EDITS_READ_PAGE_SIZE: usize = 256; // entries: read from the edits ledger and verified at once
+
+/// the handle of the checkpoint ledger of namespace `namespace_id`
+pub fn checkpoint_handle(namespace_id: &str) -> Vec<u8> {
+  [CHECKPOINT_HANDLE_PREFIX, namespace_id.as_bytes()].concat()
+}
+
+/// the block of the entry of `record`
+pub fn encode_record(record: &CheckpointRecord) -> Vec<u8> {
+  record.encode_to_vec()
+}
+
+/// decodes `block` if it is the canonical encoding of its message
+fn decode_canonical<M: Message + Default>(block: &[u8]) -> Option<M> {
+  let message = M::decode(block).ok()?;
+  if message.encode_to_vec() != block {
+    return None;
+  }
+  Some(message)
+}
+
+/// decodes the block of an entry of a checkpoint ledger; a block of another version, or that is
+/// not the canonical encoding of its record, is not a record, and neither is a heartbeat without
+/// its time or a checkpoint with one
+pub fn decode_record(block: &[u8]) -> Option<CheckpointRecord> {
+  decode_canonical::<CheckpointRecord>(block).filter(|record| match record.version {
+    CHECKPOINT_RECORD_VERSION => record.heartbeat_ms == 0,
+    CHECKPOINT_HEARTBEAT_VERSION => record.heartbeat_ms > 0,
+    _ => false,
+  })
+}
+
+/// the heartbeat that repeats checkpoint `record` at `timestamp_ms` of the coordinator
+pub fn heartbeat_record(record: &CheckpointRecord, timestamp_ms: u64) -> CheckpointRecord {
+  CheckpointRecord {
+    version: CHECKPOINT_HEARTBEAT_VERSION,
+    heartbeat_ms: timestamp_ms,
+    ..record.clone()
+  }
+}
+
+/// whether `a` and `b` are the same checkpoint, either of them possibly in a heartbeat
+fn same_checkpoint(a: &CheckpointRecord, b: &CheckpointRecord) -> bool {
+  heartbeat_record(a, 0) == heartbeat_record(b, 0)
+}
+
+/// the handle of the edits ledger of namespace `namespace_id`
+pub fn edits_handle(namespace_id: &str) -> Vec<u8> {
+  [EDITS_HANDLE_PREFIX, namespace_id.as_bytes()].concat()
+}
+
+/// the block of the entry of `record`
+pub fn encode_edit_record(record: &EditSegmentRecord) -> Vec<u8> {
+  record.encode_to_vec()
+}
+
+/// decodes the block of an entry of an edits ledger, like `decode_record`
+pub fn decode_edit_record(block: &[u8]) -> Option<EditSegmentRecord> {
+  decode_canonical::<EditSegmentRecord>(block)
+    .filter(|record| record.version == EDIT_SEGMENT_RECORD_VERSION)
+}
+
+fn decode_entry(block: &[u8], height: usize) -> Result<CheckpointRecord, Status> {
+  decode_record(block).ok_or_else(|| {
+    warn!("The block at height {} is not a checkpoint record", height);
+    Status::data_loss("The checkpoint ledger holds a block that is not a checkpoint record")
+  })
+}
+
+fn decode_edit_entry(block: &[u8], height: usize) -> Result<EditSegmentRecord, Status> {
+  decode_edit_record(block).ok_or_else(|| {
+    warn!(
+      "The block at height {} is not an edit segment record",
+      height
+    );
+    Status::data_loss("The edits ledger holds a block that is not an edit segment record")
+  })
+}
+
+/// how the recorded edit-log segments cover a range of txids
+#[derive(Debug, Default, PartialEq)]
+pub struct EditCoverage {
+  /// the recorded segments that intersect the range, by txid, each once
+  pub segments: Vec<EditSegment>,
+  pub gaps: Vec<TxidRange>,
+  pub overlaps: Vec<EditOverlap>,
+  pub mismatches: Vec<EditMismatch>,
+}
+
+impl EditCoverage {
+  pub fn is_covered(&self) -> bool {
+    self.gaps.is_empty() && self.overlaps.is_empty() && self.mismatches.is_empty()
+  }
+}
+
+/// checks that the `recorded` segments, in any order, cover `start_txid..=end_txid` without gaps
+/// and overlaps, and that the digests of the `supplied` segments match those recorded with the
+/// same txids. A segment recorded twice with the same digest counts once, at its lower height; an
+/// overlap is reported against the earlier segment that reaches furthest
+pub fn check_edit_coverage(
+  start_txid: u64,
+  end_txid: u64,
+  recorded: &[EditSegment],
+  supplied: &[EditSegment],
+) -> EditCoverage {
+  let mut segments = recorded
+    .iter()
+    .filter(|segment| segment.start_txid <= end_txid && segment.end_txid >= start_txid)
+    .cloned()
+    .collect::<Vec<_>>();
+  segments.sort_by_key(|segment| (segment.start_txid, segment.end_txid, segment.height));
+  segments.dedup_by(|later, earlier| {
+    (later.start_txid, later.end_txid, &later.segment_digest)
+      == (
+        earlier.start_txid,
+        earlier.end_txid,
+        &earlier.segment_digest,
+      )
+  });
+
+  let mut coverage = EditCoverage::default();
+  // the next txid to cover, which is past u64::MAX once a segment ends there
+  let mut next_txid = start_txid as u128;
+  let mut furthest: Option<&EditSegment> = None;
+  for segment in &segments {
+    if segment.start_txid as u128 > next_txid {
+      coverage.gaps.push(TxidRange {
+        start_txid: next_txid as u64,
+        end_txid: segment.start_txid - 1,
+      });
+    }
+    match furthest {
+      Some(earlier) if segment.start_txid <= earlier.end_txid => {
+        coverage.overlaps.push(EditOverlap {
+          start_txid: cmp::max(segment.start_txid, start_txid),
+          end_txid: cmp::min(cmp::min(segment.end_txid, earlier.end_txid), end_txid),
+          first_height: earlier.height,
+          second_height: segment.height,
+        });
+        if segment.end_txid > earlier.end_txid {
+          furthest = Some(segment);
+        }
+      },
+      _ => furthest = Some(segment),
+    }
+    next_txid = cmp::max(next_txid, segment.end_txid as u128 + 1);
+  }
+  if next_txid <= end_txid as u128 {
+    coverage.gaps.push(TxidRange {
+      start_txid: next_txid as u64,
+      end_txid,
+    });
+  }
+
+  for segment in supplied {
+    let same_txids = segments
+      .iter()
+      .filter(|r| (r.start_txid, r.end_txid) == (segment.start_txid, segment.end_txid))
+      .collect::<Vec<_>>();
+    if same_txids
+      .iter()
+      .any(|r| r.segment_digest == segment.segment_digest)
+    {
+      continue;
+    }
+    coverage.mismatches.push(EditMismatch {
+      start_txid: segment.start_txid,
+      end_txid: segment.end_txid,
+      segment_digest: segment.segment_digest.clone(),
+      recorded_digest: same_txids
+        .first()
+        .map(|r| r.segment_digest.clone())
+        .unwrap_or_default(),
+      height: same_txids.first().map(|r| r.height).unwrap_or_default(),
+    });
+  }
+  coverage.segments = segments;
+  coverage
+}
+
+/// the checkpoint service of the coordinator, which is served next to the client service
+pub struct CheckpointServiceState {
+  state: Arc<CoordinatorState>,
+  /// records the calls that record checkpoints and segments, if set
+  audit: Option<Arc<AuditLog>>,
+}
+
+impl CheckpointServiceState {
+  pub fn new(coordinator: Arc<CoordinatorState>) -> Self {
+    CheckpointServiceState {
+      state: coordinator,
+      audit: None,
+    }
+  }
+
+  /// records the calls that record checkpoints and segments in `audit`
+  pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+    self.audit = Some(audit);
+    self
+  }
+
+  /// the latest checkpoint of the ledger `handle_bytes` that has its receipts, with its entry,
+  /// and its height; the ledger is created if it does not exist yet, and has no checkpoint then
+  async fn latest_or_create(
+    &self,
+    handle_bytes: &[u8],
+    namespace_id: &str,
+    request: &Request<RecordCheckpointReq>,
+  ) -> Result<(Option<(CheckpointRecord, LedgerEntry)>, usize), Status> {
+    match self.state.read_cached_ledger_tail(handle_bytes).await {
+      Ok((_entry, 0)) => Ok((None, 0)),
+      Ok((entry, height)) => {
+        let record = decode_entry(&entry.get_block().to_bytes(), height)?;
+        Ok((Some((record, entry)), height))
+      },
+      Err(CoordinatorError::LedgerNotFound) => {
+        self
+          .create_namespace_ledger(
+            handle_bytes,
+            CHECKPOINT_LEDGER_GENESIS,
+            namespace_id,
+            request_deadline(request),
+          )
+          .await?;
+        Ok((None, 0))
+      },
+      Err(e) => Err(process_error(e, "Failed to read the latest checkpoint")),
+    }
+  }
+
+  /// creates the ledger `handle_bytes` of namespace `namespace_id`, whose genesis block is
+  /// `genesis` with the namespace ID as its metadata; concurrent creations of the ledger are
+  /// idempotent, since they have the same genesis
+  async fn create_namespace_ledger(
+    &self,
+    handle_bytes: &[u8],
+    genesis: &[u8],
+    namespace_id: &str,
+    deadline: Deadline,
+  ) -> Result<(), Status> {
+    let res = self
+      .state
+      .create_ledger_with_deadline(
+        None,
+        handle_bytes,
+        genesis,
+        &[],
+        namespace_id.as_bytes(),
+        deadline,
+      )
+      .await;
+    res.map_err(|e| process_error(e, "Failed to create the ledger of the namespace"))?;
+    Ok(())
+  }
+
+  /// appends a heartbeat at `timestamp_ms` to the checkpoint ledger `handle_bytes`, repeating its
+  /// latest checkpoint; a ledger without checkpoints is left alone, and so is one that another
+  /// append reached first, since its tail is fresh then. Returns the height of the heartbeat
+  pub async fn append_heartbeat(
+    &self,
+    handle_bytes: &[u8],
+    timestamp_ms: u64,
+    deadline: Deadline,
+  ) -> Result<Option<usize>, CoordinatorError> {
+    let (entry, height) = match self.state.read_cached_ledger_tail(handle_bytes).await {
+      Ok((_entry, 0)) => return Ok(None),
+      Err(CoordinatorError::LedgerNotFound) => return Ok(None),
+      Ok(tail) => tail,
+      Err(e) => return Err(e),
+    };
+    let latest = match decode_record(&entry.get_block().to_bytes()) {
+      Some(latest) => latest,
+      None => {
+        warn!("The block at height {} is not a checkpoint record", height);
+        return Ok(None);
+      },
+    };
+    let block = encode_record(&heartbeat_record(&latest, timestamp_ms));
+    let res = self
+      .state
+      .append_ledger_with_request_id(handle_bytes, &block, height + 1, "", deadline)
+      .await;
+    match res {
+      Ok((height, _hash_nonces, _receipts)) => Ok(Some(height)),
+      Err(CoordinatorError::ConditionFailed { .. }) => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// the height of the checkpoint with `txid` in the ledger `handle_bytes`, whose txids increase
+  /// with the height; the entries are read from the ledger store, and only the one found is
+  /// verified by the caller. The entry found may be a heartbeat that repeats the checkpoint
+  async fn find_txid(&self, handle_bytes: &[u8], txid: u64) -> Result<usize, Status> {
+    let (_entry, tail_height) = self
+      .state
+      .read_cached_ledger_tail(handle_bytes)
+      .await
+      .map_err(|e| process_error(e, "Failed to read the checkpoint ledger"))?;
+    let (mut low, mut high) = (1, tail_height);
+    while low <= high {
+      let mid = low + (high - low) / 2;
+      let entry = self
+        .state
+        .read_ledger_by_index(handle_bytes, mid)
+        .await
+        .map_err(|e| process_error(e, "Failed to read a checkpoint"))?;
+      let record = decode_entry(&entry.get_block().to_bytes(), mid)?;
+      if record.txid == txid {
+        return Ok(mid);
+      } else if record.txid < txid {
+        low = mid + 1;
+      } else {
+        high = mid - 1;
+      }
+    }
+    Err(Status::not_found(
+      "The namespace has no checkpoint with the txid",
+    ))
+  }
+
+  async fn serve_record_checkpoint(
+    &self,
+    request: Request<RecordCheckpointReq>,
+  ) -> Result<Response<RecordCheckpointResp>, Status> {
+    validate::record_checkpoint(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let namespace_id = request.get_ref().namespace_id.clone();
+    let handle_bytes = scope_handle(&tenant, checkpoint_handle(&namespace_id));
+    let (latest, latest_height) = self
+      .latest_or_create(&handle_bytes, &namespace_id, &request)
+      .await?;
+
+    let deadline = request_deadline(&request);
+    let RecordCheckpointReq {
+      namespace_id,
+      txid,
+      fsimage_digest,
+      md5,
+      metadata,
+    } = request.into_inner();
+    let record = CheckpointRecord {
+      version: CHECKPOINT_RECORD_VERSION,
+      namespace_id,
+      txid,
+      fsimage_digest,
+      md5,
+      metadata,
+      heartbeat_ms: 0,
+    };
+
+    // the txids of the checkpoints of a namespace increase; the latest checkpoint recorded again
+    // is a retry, which returns its entry, or the heartbeat that repeats it at the tail
+    let block = encode_record(&record);
+    match latest {
+      Some((latest, entry)) if same_checkpoint(&latest, &record) => {
+        let reply = RecordCheckpointResp {
+          handle: handle_bytes,
+          height: latest_height as u64,
+          block: entry.get_block().to_bytes(),
+          hash_nonces: entry.get_nonces().hash().to_bytes(),
+          receipts: entry.get_receipts().to_bytes(),
+        };
+        return Ok(Response::new(reply));
+      },
+      Some((latest, _entry)) if latest.txid >= record.txid => {
+        let details = CheckpointConflict {
+          latest_txid: latest.txid,
+          latest_height: latest_height as u64,
+        };
+        return Err(Status::with_details(
+          Code::FailedPrecondition,
+          "The txid is not above the txid of the latest checkpoint",
+          details.encode_to_vec().into(),
+        ));
+      },
+      _ => {},
+    }
+
+    // a retry of an append whose receipts were still missing resolves to its entry by the ID
+    let request_id = hex::encode(NimbleDigest::digest(&block).to_bytes());
+    let res = self
+      .state
+      .append_ledger_with_request_id(
+        &handle_bytes,
+        &block,
+        latest_height + 1,
+        &request_id,
+        deadline,
+      )
+      .await;
+    let (height, hash_nonces, receipts) = res.map_err(|e| match e {
+      CoordinatorError::ConditionFailed { .. } => Status::aborted(
+        "Another checkpoint of the namespace was recorded concurrently; read the latest and retry",
+      ),
+      e => process_error(e, "Failed to record the checkpoint"),
+    })?;
+
+    let reply = RecordCheckpointResp {
+      handle: handle_bytes,
+      height: height as u64,
+      block,
+      hash_nonces: hash_nonces.to_bytes(),
+      receipts: receipts.to_bytes(),
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn serve_record_edit_segment(
+    &self,
+    request: Request<RecordEditSegmentReq>,
+  ) -> Result<Response<RecordEditSegmentResp>, Status> {
+    validate::record_edit_segment(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let deadline = request_deadline(&request);
+    let RecordEditSegmentReq {
+      namespace_id,
+      start_txid,
+      end_txid,
+      segment_digest,
+    } = request.into_inner();
+    let handle_bytes = scope_handle(&tenant, edits_handle(&namespace_id));
+
+    // the segments are appended in the order in which they are recorded
+    let tail_height = match self.state.read_cached_ledger_tail(&handle_bytes).await {
+      Ok((_entry, height)) => height,
+      Err(CoordinatorError::LedgerNotFound) => {
+        self
+          .create_namespace_ledger(&handle_bytes, EDITS_LEDGER_GENESIS, &namespace_id, deadline)
+          .await?;
+        0
+      },
+      Err(e) => return Err(process_error(e, "Failed to read the edits ledger")),
+    };
+
+    let block = encode_edit_record(&EditSegmentRecord {
+      version: EDIT_SEGMENT_RECORD_VERSION,
+      namespace_id,
+      start_txid,
+      end_txid,
+      segment_digest,
+    });
+    // a retry of the segment resolves to its entry by the ID
+    let request_id = hex::encode(NimbleDigest::digest(&block).to_bytes());
+    let res = self
+      .state
+      .append_ledger_with_request_id(
+        &handle_bytes,
+        &block,
+        tail_height + 1,
+        &request_id,
+        deadline,
+      )
+      .await;
+    let (height, hash_nonces, receipts) = res.map_err(|e| match e {
+      CoordinatorError::ConditionFailed { .. } => {
+        Status::aborted("Another segment of the namespace was recorded concurrently; retry")
+      },
+      e => process_error(e, "Failed to record the edit segment"),
+    })?;
+
+    let reply = RecordEditSegmentResp {
+      handle: handle_bytes,
+      height: height as u64,
+      block,
+      hash_nonces: hash_nonces.to_bytes(),
+      receipts: receipts.to_bytes(),
+    };
+    Ok(Response::new(reply))
+  }
+}
+
+#[tonic::async_trait]
+impl Checkpoint for CheckpointServiceState {
+  async fn record_checkpoint(
+    &self,
+    request: Request<RecordCheckpointReq>,
+  ) -> Result<Response<RecordCheckpointResp>, Status> {
+    audited(
+      &self.audit,
+      CHECKPOINT_SERVICE,
+      "RecordCheckpoint",
+      request,
+      |request| self.serve_record_checkpoint(request),
+    )
+    .await
+  }
+
+  async fn get_latest_checkpoint(
+    &self,
+    request: Request<GetLatestCheckpointReq>,
+  ) -> Result<Response<GetLatestCheckpointResp>, Status> {
+    validate::get_latest_checkpoint(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let GetLatestCheckpointReq {
+      namespace_id,
+      nonce,
+    } = request.into_inner();
+    let handle_bytes = scope_handle(&tenant, checkpoint_handle(&namespace_id));
+
+    // the endorsers attest the tail only of ledgers that exist
+    match self.state.read_cached_ledger_tail(&handle_bytes).await {
+      Ok((_entry, 0)) => return Err(Status::not_found("The namespace has no checkpoints")),
+      Err(CoordinatorError::LedgerNotFound) => {
+        return Err(Status::not_found("The namespace has no checkpoints"));
+      },
+      Ok(_tail) => {},
+      Err(e) => return Err(process_error(e, "Failed to read the latest checkpoint")),
+    }
+    let res = self.state.read_ledger_tail(&handle_bytes, &nonce).await;
+    let ledger_entry = res.map_err(|e| process_error(e, "Failed to read the latest checkpoint"))?;
+    let height = ledger_entry
+      .get_receipts()
+      .get_metablock()
+      .map_err(|_| Status::internal("Receipts of the ledger tail are inconsistent"))?
+      .get_height();
+    if height == 0 {
+      return Err(Status::not_found("The namespace has no checkpoints"));
+    }
+
+    let block = ledger_entry.get_block().to_bytes();
+    let reply = GetLatestCheckpointResp {
+      handle: handle_bytes,
+      height: height as u64,
+      record: Some(decode_entry(&block, height)?),
+      block,
+      nonces: ledger_entry.get_nonces().to_bytes(),
+      receipts: ledger_entry.get_receipts().to_bytes(),
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn verify_checkpoint(
+    &self,
+    request: Request<VerifyCheckpointReq>,
+  ) -> Result<Response<VerifyCheckpointResp>, Status> {
+    validate::verify_checkpoint(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let VerifyCheckpointReq {
+      namespace_id,
+      txid,
+      fsimage_digest,
+    } = request.into_inner();
+    let handle_bytes = scope_handle(&tenant, checkpoint_handle(&namespace_id));
+
+    let height = self.find_txid(&handle_bytes, txid).await?;
+    let res = self
+      .state
+      .read_ledger_range(&handle_bytes, height, height)
+      .await;
+    let range = res.map_err(|e| process_error(e, "Failed to read the checkpoint"))?;
+    // the digest is compared with the record only once its entry chains to the attested tail
+    if let Err(error) = self
+      .state
+      .verify_ledger_range(&handle_bytes, height, &range)
+    {
+      warn!(
+        "The checkpoint at height {} fails verification ({:?})",
+        height, error
+      );
+      return Err(Status::data_loss(
+        "The checkpoint does not match the receipts of the ledger",
+      ));
+    }
+
+    let ledger_entry = &range.entries[0];
+    let block = ledger_entry.get_block().to_bytes();
+    let record = decode_entry(&block, height)?;
+    let reply = VerifyCheckpointResp {
+      matches: record.txid == txid && record.fsimage_digest == fsimage_digest,
+      handle: handle_bytes,
+      height: height as u64,
+      record: Some(record),
+      block,
+      nonces: ledger_entry.get_nonces().to_bytes(),
+      checkpoint: range
+        .checkpoint
+        .map(|checkpoint| checkpoint.to_bytes())
+        .unwrap_or_default(),
+      block_hashes: range.block_hashes.iter().map(|h| h.to_bytes()).collect(),
+      tail_receipts: range.tail_receipts.to_bytes(),
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn record_edit_segment(
+    &self,
+    request: Request<RecordEditSegmentReq>,
+  ) -> Result<Response<RecordEditSegmentResp>, Status> {
+    audited(
+      &self.audit,
+      CHECKPOINT_SERVICE,
+      "RecordEditSegment",
+      request,
+      |request| self.serve_record_edit_segment(request),
+    )
+    .await
+  }
+
+  async fn verify_edit_range(
+    &self,
+    request: Request<VerifyEditRangeReq>,
+  ) -> Result<Response<VerifyEditRangeResp>, Status> {
+    validate::verify_edit_range(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let VerifyEditRangeReq {
+      namespace_id,
+      start_txid,
+      end_txid,
+      segments,
+    } = request.into_inner();
+    let handle_bytes = scope_handle(&tenant, edits_handle(&namespace_id));
+
+    // a namespace without an edits ledger has no segments, so the whole range is a gap
+    let tail_height = match self.state.read_cached_ledger_tail(&handle_bytes).await {
+      Ok((_entry, height)) => height,
+      Err(CoordinatorError::LedgerNotFound) => 0,
+      Err(e) => return Err(process_error(e, "Failed to read the edits ledger")),
+    };
+
+    // the segments are in no order, so every entry is read, and each page is checked against the
+    // attested tail before its segments count
+    let mut recorded = Vec::new();
+    let mut attested_height = 0;
+    let mut from = 1;
+    while from <= tail_height {
+      let to = cmp::min(tail_height, from + EDITS_READ_PAGE_SIZE - 1);
+      let res = self.state.read_ledger_range(&handle_bytes, from, to).await;
+      let range = res.map_err(|e| process_error(e, "Failed to read the edits ledger"))?;
+      match self.state.verify_ledger_range(&handle_bytes, from, &range) {
+        Ok(height) => attested_height = cmp::max(attested_height, height),
+        Err(error) => {
+          warn!(
+            "The edit segments at heights {} to {} fail verification ({:?})",
+            from, to, error
+          );
+          return Err(Status::data_loss(
+            "The edit segments do not match the receipts of the ledger",
+          ));
+        },
+      }
+      for (height, entry) in (from..).zip(range.entries.iter()) {
+        let record = decode_edit_entry(&entry.get_block().to_bytes(), height)?;
+        recorded.push(EditSegment {
+          start_txid: record.start_txid,
+          end_txid: record.end_txid,
+          segment_digest: record.segment_digest,
+          height: height as u64,
+        });
+      }
+      from = to + 1;
+    }
+
+    let coverage = check_edit_coverage(start_txid, end_txid, &recorded, &segments);
+    let reply = VerifyEditRangeResp {
+      covered: coverage.is_covered(),
+      handle: handle_bytes,
+      height: attested_height as u64,
+      segments: coverage.segments,
+      gaps: coverage.gaps,
+      overlaps: coverage.overlaps,
+      mismatches: coverage.mismatches,
+    };
+    Ok(Response::new(reply))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(txid: u64) -> CheckpointRecord {
+    CheckpointRecord {
+      version: CHECKPOINT_RECORD_VERSION,
+      namespace_id: "ns-1".to_string(),
+      txid,
+      fsimage_digest: vec![1u8; 32],
+      md5: vec![2u8; 16],
+      metadata: b"nn1".to_vec(),
+      heartbeat_ms: 0,
+    }
+  }
+
+  #[test]
+  fn test_record_encoding() {
+    let block = encode_record(&record(42));
+    assert_eq!(decode_record(&block), Some(record(42)));
+    assert_eq!(checkpoint_handle("ns-1"), b"hdfs-checkpoint/ns-1".to_vec());
+
+    // records of an unknown version are rejected
+    let mut unknown = record(42);
+    unknown.version = CHECKPOINT_RECORD_VERSION + 1;
+    unknown.version = CHECKPOINT_HEARTBEAT_VERSION + 1;
+    assert_eq!(decode_record(&encode_record(&unknown)), None);
+    unknown.version = 0;
+    assert_eq!(decode_record(&encode_record(&unknown)), None);
+
+    // a heartbeat repeats the checkpoint with its time, which only a heartbeat has
+    let heartbeat = heartbeat_record(&record(42), 1_000);
+    assert_eq!(
+      decode_record(&encode_record(&heartbeat)),
+      Some(heartbeat.clone())
+    );
+    assert!(same_checkpoint(&heartbeat, &record(42)));
+    assert!(!same_checkpoint(&heartbeat, &record(43)));
+    assert_eq!(
+      decode_record(&encode_record(&heartbeat_record(&record(42), 0))),
+      None
+    );
+    let mut timed = record(42);
+    timed.heartbeat_ms = 1_000;
+    assert_eq!(decode_record(&encode_record(&timed)), None);
+
+    // so are encodings other than the canonical one: a trailing unknown field, the fields out of
+    // order, and garbage
+    let mut trailing = block.clone();
+    trailing.extend_from_slice(&[0x38, 0x01]);
+    assert_eq!(decode_record(&trailing), None);
+    let txid = CheckpointRecord {
+      txid: 42,
+      ..Default::default()
+    }
+    .encode_to_vec();
+    let rest = CheckpointRecord {
+      txid: 0,
+      ..record(42)
+    }
+    .encode_to_vec();
+    let reordered = [rest, txid].concat();
+    assert_eq!(
+      CheckpointRecord::decode(&reordered[..]).unwrap(),
+      record(42)
+    );
+    assert_eq!(decode_record(&reordered), None);
+    assert_eq!(decode_record(&[0xff, 0xff]), None);
+
+    // an edit segment is not a checkpoint, although both decode from the same bytes
+    let edit = encode_edit_record(&EditSegmentRecord {
+      version: EDIT_SEGMENT_RECORD_VERSION,
+      namespace_id: "ns-1".to_string(),
+      start_txid: 1,
+      end_txid: 100,
+      segment_digest: vec![3u8; 32],
+    });
+    assert_eq!(decode_edit_record(&edit).unwrap().end_txid, 100);
+    assert_eq!(decode_record(&edit), None);
+  }
+
+  fn segment(start_txid: u64, end_txid: u64, digest: u8, height: u64) -> EditSegment {
+    EditSegment {
+      start_txid,
+      end_txid,
+      segment_digest: vec![digest; 32],
+      height,
+    }
+  }
+
+  #[test]
+  fn test_edit_coverage() {
+    // segments recorded out of order cover the range once sorted; a retried one counts once
+    let recorded = [
+      segment(201, 300, 3, 1),
+      segment(1, 100, 1, 2),
+      segment(301, 400, 4, 3),
+      segment(101, 200, 2, 4),
+      segment(201, 300, 3, 5),
+    ];
+    let coverage = check_edit_coverage(1, 400, &recorded, &[]);
+    assert!(coverage.is_covered());
+    let heights = coverage
+      .segments
+      .iter()
+      .map(|s| s.height)
+      .collect::<Vec<_>>();
+    assert_eq!(heights, vec![2, 4, 1, 3]);
+    // only the segments that intersect the range are considered
+    let coverage = check_edit_coverage(150, 250, &recorded, &[]);
+    assert!(coverage.is_covered());
+    assert_eq!(coverage.segments.len(), 2);
+
+    // a missing segment and a range beyond the last one are gaps
+    let coverage = check_edit_coverage(1, 450, &recorded[1..4], &[]);
+    let gaps = coverage
+      .gaps
+      .iter()
+      .map(|gap| (gap.start_txid, gap.end_txid))
+      .collect::<Vec<_>>();
+    assert_eq!(gaps, vec![(201, 300), (401, 450)]);
+    assert!(coverage.overlaps.is_empty());
+
+    // overlaps are reported with the txids and the heights of both segments, within the range
+    let mut overlapping = recorded.to_vec();
+    overlapping.push(segment(350, 420, 5, 6));
+    overlapping.push(segment(101, 200, 9, 7));
+    let coverage = check_edit_coverage(1, 410, &overlapping, &[]);
+    let overlaps = coverage
+      .overlaps
+      .iter()
+      .map(|o| (o.start_txid, o.end_txid, o.first_height, o.second_height))
+      .collect::<Vec<_>>();
+    assert_eq!(overlaps, vec![(101, 200, 4, 7), (350, 400, 3, 6)]);
+    assert!(coverage.gaps.is_empty());
+    assert!(!coverage.is_covered());
+
+    // the supplied digests must match the recorded segments with the same txids
+    let supplied = [
+      segment(1, 100, 1, 0),
+      segment(101, 200, 7, 0),
+      segment(401, 500, 1, 0),
+    ];
+    let coverage = check_edit_coverage(1, 400, &recorded, &supplied);
+    assert_eq!(coverage.mismatches.len(), 2);
+    assert_eq!(coverage.mismatches[0].recorded_digest, vec![2u8; 32]);
+    assert_eq!(coverage.mismatches[0].height, 4);
+    assert!(coverage.mismatches[1].recorded_digest.is_empty());
+    assert!(!coverage.is_covered());
+
+    // the txids end at u64::MAX
+    let coverage = check_edit_coverage(u64::MAX - 1, u64::MAX, &[segment(1, u64::MAX, 1, 1)], &[]);
+    assert!(coverage.is_covered());
+  }
+}
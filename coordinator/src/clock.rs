@@ -0,0 +1,48 @@
+//! The wall clock of the coordinator, which it reads for the times it writes to the ledger store:
+//! the expiry of its lease and the creation and sealing of ledgers. Timeouts, deadlines and the
+//! waits between attempts run on the clock of the tokio runtime instead, which a test pauses and
+//! advances. A simulated wall clock follows the clock of the runtime, so that a simulation moves
+//! both clocks at once.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub trait Clock: Send + Sync {
+  /// milliseconds since the Unix epoch
+  fn now_ms(&self) -> u64;
+}
+
+/// the clock of the system
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now_ms(&self) -> u64 {
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_millis() as u64)
+      .unwrap_or_default()
+  }
+}
+
+/// a wall clock that reads `start_ms` when it is made, and runs with the clock of the tokio
+/// runtime from then on
+#[cfg(any(test, feature = "testing"))]
+pub struct SimulatedClock {
+  start_ms: u64,
+  start: tokio::time::Instant,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl SimulatedClock {
+  pub fn new(start_ms: u64) -> Self {
+    SimulatedClock {
+      start_ms,
+      start: tokio::time::Instant::now(),
+    }
+  }
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl Clock for SimulatedClock {
+  fn now_ms(&self) -> u64 {
+    self.start_ms + self.start.elapsed().as_millis() as u64
+  }
+}
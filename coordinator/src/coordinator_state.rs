@@ -1,62 +1,555 @@
-use crate::errors::CoordinatorError;
+use crate::{
+  allowlist::EndorserAllowlist,
+  canary::CANARY_PREFIX,
+  clock::{Clock, SystemClock},
+  endorser_connection::{EndorserClient, EndorserConnector, GrpcConnector},
+  endorser_heartbeat::EndorserHeartbeats,
+  endorser_key::EndorserKey,
+  errors::{CoordinatorError, TenantQuota, WriteStage},
+  health::{Health, HealthInputs},
+  lease::Lease,
+  metrics::{backend_label, MeteredLedgerStore, Metrics},
+  telemetry,
+  tenant::TENANT_SEPARATOR,
+  watchers::Watchers,
+};
 use ledger::{
-  compute_aggregated_block_hash, compute_cut_diffs, compute_max_cut,
+  bundle::{bundle_header, BundleRecord},
+  compute_aggregated_block_hash, compute_cut_diffs, compute_genesis_block, compute_max_cut,
+  compute_seal_block, compute_snapshot_digest, compute_view_block_hash, decode_view_config,
+  encode_view_config,
   errors::VerificationError,
-  signature::{PublicKey, PublicKeyTrait},
-  Block, CustomSerde, EndorserHostnames, Handle, MetaBlock, NimbleDigest, NimbleHashTrait, Nonce,
-  Nonces, Receipt, Receipts, VerifierState,
+  majority_quorum, parse_seal_block, retrieve_quorum_from_config, view_ledger_handle, Block,
+  CustomSerde, EndorserHostnames, Handle, KeyRotation, MetaBlock, NimbleDigest, NimbleHashTrait,
+  Nonce, Nonces, Receipt, Receipts, VerifierState,
 };
-use rand::random;
+use prost::bytes::Bytes;
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
 use std::{
-  collections::{HashMap, HashSet},
+  collections::{hash_map::Entry, HashMap, HashSet},
   convert::TryInto,
+  future::Future,
   ops::Deref,
-  sync::{Arc, RwLock},
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, RwLock, Weak,
+  },
+  time::Duration,
 };
 use store::ledger::{
   azure_table::TableLedgerStore, filestore::FileStore, in_memory::InMemoryLedgerStore,
-  mongodb_cosmos::MongoCosmosLedgerStore, LedgerEntry, LedgerStore,
+  mongodb_cosmos::MongoCosmosLedgerStore, AppendToken, IntentRecord, LedgerEntry, LedgerInfo,
+  LedgerStore, TenantRecord,
 };
 use store::{errors::LedgerStoreError, errors::StorageError};
-use tokio::sync::mpsc;
-use tonic::{
-  transport::{Channel, Endpoint},
-  Code, Status,
+use tokio::{
+  sync::{mpsc, oneshot, watch, OwnedMutexGuard},
+  time::Instant,
 };
+use tonic::{Code, Status};
+use tracing::{error, info, warn};
 
 use ledger::endorser_proto;
 
 const DEFAULT_NUM_GRPC_CHANNELS: usize = 1; // the default number of GRPC channels
-
+const DEFAULT_MIN_NUM_ENDORSERS: usize = 1; // the default minimum number of endorsers in a view
+const DEFAULT_MAX_BLOCK_SIZE: usize = ledger::MAX_BLOCK_SIZE; // bytes: the largest client block
+pub const MAX_LEDGER_METADATA_SIZE: usize = 4096; // bytes: the largest metadata of a ledger
+pub const MAX_APPEND_BATCH_SIZE: usize = 1000; // the most appends in a single batch
+pub const MAX_SNAPSHOT_READ_SIZE: usize = 64; // the most ledgers that a snapshot read locks at once
+const SNAPSHOT_LOCK_TIMEOUT: Duration = Duration::from_secs(5); // a snapshot waits for its locks
+pub const DEFAULT_REQUEST_ID_RETENTION: usize = 64; // appends per ledger whose request IDs are kept
+
+#[derive(Clone)]
 struct EndorserClients {
-  clients: Vec<endorser_proto::endorser_call_client::EndorserCallClient<Channel>>,
+  clients: Vec<EndorserClient>,
   uri: String,
 }
 
-type EndorserConnMap = HashMap<Vec<u8>, EndorserClients>;
+type EndorserConnMap = HashMap<EndorserKey, EndorserClients>;
 
 type LedgerStoreRef = Arc<Box<dyn LedgerStore + Send + Sync>>;
 
+/// the state of an endorser of the current view as seen by the coordinator
+pub struct EndorserStatus {
+  pub pk: Vec<u8>,
+  pub uri: String,
+  /// whether the coordinator sends requests to the endorser
+  pub connected: bool,
+  /// the mode reported by the endorser, if it answered
+  pub mode: Option<i32>,
+  /// the number of ledger entries in the store that the endorser does not have, if it answered
+  pub lag: Option<usize>,
+  /// the number of receipts from the endorser that did not carry a valid signature
+  pub invalid_signatures: usize,
+  /// the number of ledgers that the endorser holds the tails of, if it answered
+  pub num_ledgers: Option<usize>,
+  /// the approximate bytes of memory that the tails take in the endorser, if it answered
+  pub tail_map_bytes: Option<usize>,
+}
+
+/// consecutive entries of a ledger with the material to verify them against an attested tail
+pub struct LedgerRange {
+  pub entries: Vec<LedgerEntry>,
+  /// the metablock of the entry before the first one, or `None` if the range starts at genesis
+  pub checkpoint: Option<MetaBlock>,
+  /// the block hashes of the entries after the range, up to the attested tail
+  pub block_hashes: Vec<NimbleDigest>,
+  /// the receipts of the attested tail, i.e., the highest entry with a quorum of receipts
+  pub tail_receipts: Receipts,
+}
+
+/// an event of a watch of a ledger
+pub enum WatchEvent {
+  /// the entry at `height` committed; the entry is included if the watch asked for the blocks
+  Entry {
+    height: usize,
+    block_hash: NimbleDigest,
+    entry: Option<LedgerEntry>,
+  },
+  /// no entry committed for a while; `height` is the committed height
+  Heartbeat { height: usize },
+}
+
+/// what the ledger store knows about a ledger; none of it is attested by the endorsers
+pub struct LedgerSummary {
+  pub height: usize,
+  /// the aggregated hash of the block and nonces at `height`
+  pub tail_hash: NimbleDigest,
+  pub info: LedgerInfo,
+}
+
+/// a page of ledgers in the order in which the ledger store keys them
+pub struct LedgerPage {
+  pub ledgers: Vec<LedgerSummary>,
+  /// the key of the last ledger looked at, from which the next page starts; `None` after the
+  /// last page
+  pub next_start_after: Option<Handle>,
+  /// an estimate of the number of all ledgers if the ledger store can provide it cheaply
+  pub num_ledgers_estimate: Option<usize>,
+}
+
+/// an append to one ledger of a batch
+pub struct AppendBatchItem {
+  pub handle_bytes: Vec<u8>,
+  pub block_bytes: Vec<u8>,
+  pub expected_height: usize,
+}
+
+/// the tail of one ledger of a snapshot read
+pub struct SnapshotEntry {
+  pub ledger_entry: LedgerEntry,
+  pub height: usize,
+  /// when the tail was read, in milliseconds since the Unix epoch by the coordinator's clock
+  pub captured_at: u64,
+}
+
+/// the tails of the ledgers of a snapshot read, which no append changed while they were read
+pub struct Snapshot {
+  /// in the order of the handles of the read
+  pub entries: Vec<SnapshotEntry>,
+  /// `ledger::compute_snapshot_digest` of the nonce and the tails of the entries
+  pub digest: NimbleDigest,
+  /// when the locks of all the ledgers were held, in milliseconds since the Unix epoch
+  pub locked_at: u64,
+  /// when the locks were released, in milliseconds since the Unix epoch
+  pub released_at: u64,
+}
+
+/// the instant by which a client expects the answer to its request, if it set one; the work done
+/// for the request, including the calls to the endorsers, is bounded by it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+  /// a deadline that never expires
+  pub fn none() -> Self {
+    Deadline(None)
+  }
+
+  pub fn after(timeout: Duration) -> Self {
+    Deadline(Some(Instant::now() + timeout))
+  }
+
+  fn is_expired(&self) -> bool {
+    matches!(self.0, Some(instant) if instant <= Instant::now())
+  }
+
+  /// fails with how far the write got if the deadline expired
+  fn check(&self, stage: WriteStage) -> Result<(), CoordinatorError> {
+    if self.is_expired() {
+      Err(CoordinatorError::DeadlineExceeded(stage))
+    } else {
+      Ok(())
+    }
+  }
+
+  /// replaces an error that the expiry of the deadline may have caused
+  fn exceeded_or(&self, error: CoordinatorError, stage: WriteStage) -> CoordinatorError {
+    if self.is_expired() {
+      CoordinatorError::DeadlineExceeded(stage)
+    } else {
+      error
+    }
+  }
+
+  /// a request to an endorser that carries the time left as its timeout
+  fn request<T>(&self, message: T) -> tonic::Request<T> {
+    let mut request = telemetry::outgoing(message);
+    if let Some(instant) = self.0 {
+      request.set_timeout(instant.saturating_duration_since(Instant::now()));
+    }
+    request
+  }
+
+  /// the later of two deadlines; a missing deadline is later than any
+  fn or_later(self, other: Deadline) -> Deadline {
+    match (self.0, other.0) {
+      (Some(a), Some(b)) => Deadline(Some(a.max(b))),
+      _ => Deadline::none(),
+    }
+  }
+
+  /// waits for the future until the deadline expires
+  async fn run<F: Future>(&self, future: F) -> Option<F::Output> {
+    match self.0 {
+      Some(instant) => tokio::time::timeout_at(instant, future).await.ok(),
+      None => Some(future.await),
+    }
+  }
+}
+
+/// what an append builds on, as found in the ledger store
+enum AppendBase {
+  /// the append extends the tail with this aggregated hash
+  Tail(NimbleDigest),
+  /// the store already holds the block at the expected height: the append is a retry, and this
+  /// is the hash of the nonces of the entry and its receipts
+  Retried(NimbleDigest, Receipts),
+}
+
+/// an item of a batch that the ledger store has appended and that awaits the endorsers
+struct BatchedAppend {
+  /// the position of the item in the batch
+  index: usize,
+  handle: Handle,
+  block_hash: NimbleDigest,
+  hash_nonces: NimbleDigest,
+  height: usize,
+  request: endorser_proto::AppendReq,
+}
+
+/// an append that waits in the pipeline of its ledger
+struct PipelinedAppend {
+  handle_bytes: Vec<u8>,
+  block: Block,
+  num_bytes: u64,
+  height: usize,
+  deadline: Deadline,
+  queued_at: Instant,
+  /// whether the append is the one whose request leads the pipeline
+  leads: bool,
+  reply: oneshot::Sender<PipelineReply>,
+}
+
+enum PipelineReply {
+  /// the result of the append
+  Done(Result<(NimbleDigest, Receipts), CoordinatorError>),
+  /// the request of the append leads the pipeline now, and gets the result on this receiver
+  Lead(oneshot::Receiver<PipelineReply>),
+}
+
+impl PipelinedAppend {
+  /// answers the append, whose client may have given up on it already
+  fn answer(self, res: Result<(NimbleDigest, Receipts), CoordinatorError>) {
+    let _ = self.reply.send(PipelineReply::Done(res));
+  }
+}
+
+/// the appends queued for each ledger whose pipeline has a leader
+type Pipelines = Mutex<HashMap<Handle, Vec<PipelinedAppend>>>;
+
+/// the leadership of the pipeline of a ledger; when the leader stops, also because its request is
+/// cancelled, the first queued append whose client still waits leads next. The appends that the
+/// leader took from the queue and did not answer fail with `PipelineAborted`
+struct PipelineLeader<'a> {
+  pipelines: &'a Pipelines,
+  handle: Handle,
+}
+
+impl Drop for PipelineLeader<'_> {
+  fn drop(&mut self) {
+    let mut pipelines = match self.pipelines.lock() {
+      Ok(pipelines) => pipelines,
+      Err(_) => return,
+    };
+    if let Some(queue) = pipelines.get_mut(&self.handle) {
+      while !queue.is_empty() {
+        let (tx, rx) = oneshot::channel();
+        let reply = std::mem::replace(&mut queue[0].reply, tx);
+        queue[0].leads = true;
+        if reply.send(PipelineReply::Lead(rx)).is_ok() {
+          return;
+        }
+        // the client of the append gave up on it
+        queue.remove(0);
+      }
+    }
+    pipelines.remove(&self.handle);
+  }
+}
+
 pub struct CoordinatorState {
   pub(crate) ledger_store: LedgerStoreRef,
   conn_map: Arc<RwLock<EndorserConnMap>>,
   verifier_state: Arc<RwLock<VerifierState>>,
   num_grpc_channels: usize,
+  /// opens the connections to the endorsers
+  connector: Arc<dyn EndorserConnector>,
+  min_num_endorsers: usize,
+  max_block_size: usize,
+  /// the most consecutive appends to a ledger that are sent to the endorsers together; appends
+  /// are not pipelined at 1
+  pipeline_depth: usize,
+  pipelines: Pipelines,
+  /// the appends, by ledger and height, whose intents were recorded and not yet committed
+  pending_intents: Mutex<HashSet<(Handle, usize)>>,
+  /// the number of the latest appends to a ledger whose request IDs are kept, so that a retry
+  /// with one of them returns the entry of the append
+  request_id_retention: usize,
+  /// held for writing while the view changes; client writes that cannot take it for reading are
+  /// rejected rather than sent to endorsers that are being finalized
+  view_change_lock: tokio::sync::RwLock<()>,
+  /// serializes appends to each ledger
+  pub(crate) ledger_locks: LedgerLocks,
+  /// the committed heights of the watched ledgers
+  pub(crate) watchers: Watchers,
+  /// the number of invalid receipts received from each endorser, keyed by public key
+  invalid_signatures: Mutex<HashMap<EndorserKey, usize>>,
+  /// held while the usage of a tenant is read and written back to the ledger store
+  tenants: tokio::sync::Mutex<Tenants>,
+  /// the lease the coordinator must hold to serve writes, if it runs with standbys
+  lease: Option<Lease>,
+  /// how far the coordinator is in shutting down; writes waiting for the lock of their ledger
+  /// watch it to give up the wait
+  shutdown: watch::Sender<ShutdownPhase>,
+  metrics: Arc<Metrics>,
+  health: Arc<Health>,
+  /// the last signed heartbeats of the endorsers
+  endorser_heartbeats: EndorserHeartbeats,
+  /// the number of endorsers in the current view
+  view_size: AtomicUsize,
+  /// the number of endorsers whose receipts the current view needs
+  view_quorum: AtomicUsize,
+  /// the quorum that the view ledger records for the current view, or that the first view records
+  /// before there is one; none stands for a majority of the endorsers of the view
+  quorum: RwLock<Option<usize>>,
+  /// the only endorsers that the coordinator connects to, if it has an allowlist
+  endorser_allowlist: RwLock<Option<EndorserAllowlist>>,
+  /// the clock that the times written to the ledger store are read from
+  clock: Arc<dyn Clock>,
+  /// picks the channels to endorsers and draws the nonces of the coordinator's own reads
+  rng: Mutex<StdRng>,
+}
+
+/// how far the coordinator is in shutting down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShutdownPhase {
+  Running,
+  /// writes in flight finish while new ones are rejected
+  Draining,
+  /// the grace period passed, so writes still waiting for the lock of their ledger are rejected
+  Expired,
 }
 
 const ENDORSER_MPSC_CHANNEL_BUFFER: usize = 8; // limited by the number of endorsers
-const ENDORSER_CONNECT_TIMEOUT: u64 = 10; // seconds: the connect timeout to endorsres
-const ENDORSER_REQUEST_TIMEOUT: u64 = 10; // seconds: the request timeout to endorsers
+const DEFAULT_ENDORSER_REQUEST_TIMEOUT: u64 = 10; // seconds: the request timeout to endorsers
+const RECOVERY_LIST_HANDLES_PAGE_SIZE: usize = 1000; // handles per page when scanning the store
+const RECOVERY_MAX_WORKERS: usize = 16; // tasks that read and check the tails of a page, at most
+const LIST_LEDGERS_MAX_SCAN: usize = 4096; // handles a single ListLedgers call looks at, at most
+const NUM_LEDGER_LOCK_SHARDS: usize = 64; // the number of shards of the map of per-ledger locks
+const LEDGER_LOCK_SHARD_PRUNE_LEN: usize = 1024; // a shard is pruned of unused locks at this size
+const DEFAULT_PIPELINE_DEPTH: usize = 1; // appends are not pipelined unless configured
+const PIPELINE_GAP_WAIT_MS: u64 = 100; // milliseconds: how long an append waits for those below it
+const PIPELINE_STALL_WAIT_MS: u64 = 1; // milliseconds: the pause of a pipeline whose appends wait
+pub const WATCH_STREAM_BUFFER: usize = 64; // the events of a watch queued for a slow client
+const WATCH_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10); // a silent watch sends one then
+const WATCH_SLOW_CONSUMER_TIMEOUT: Duration = Duration::from_secs(5); // a full queue drops it then
+
+/// per-ledger async locks; appends to one ledger wait for each other while appends to different
+/// ledgers take different locks, and the map of locks is sharded by handle so that looking up a
+/// lock does not contend either
+pub struct LedgerLocks {
+  shards: Vec<Mutex<HashMap<Handle, Weak<tokio::sync::Mutex<()>>>>>,
+  /// the number of appends waiting for the lock of their ledger
+  num_waiting: AtomicUsize,
+  /// the number of locks in the shards, including those not yet pruned
+  num_locks: AtomicUsize,
+}
+
+/// counts an append as waiting for the lock of its ledger until it is dropped, which it is also
+/// when the wait is given up
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+  fn new(num_waiting: &'a AtomicUsize) -> Self {
+    num_waiting.fetch_add(1, Ordering::Relaxed);
+    Waiting(num_waiting)
+  }
+}
+
+impl Drop for Waiting<'_> {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+impl LedgerLocks {
+  pub fn new() -> Self {
+    LedgerLocks {
+      shards: (0..NUM_LEDGER_LOCK_SHARDS)
+        .map(|_| Mutex::new(HashMap::new()))
+        .collect(),
+      num_waiting: AtomicUsize::new(0),
+      num_locks: AtomicUsize::new(0),
+    }
+  }
+
+  /// the number of appends waiting for the lock of their ledger
+  pub fn num_waiting(&self) -> usize {
+    self.num_waiting.load(Ordering::Relaxed)
+  }
+
+  /// the number of locks that the shards hold, including those of ledgers not locked anymore
+  /// until they are pruned
+  pub fn num_locks(&self) -> usize {
+    self.num_locks.load(Ordering::Relaxed)
+  }
+
+  /// waits for the lock of the ledger; it is released when the returned guard is dropped
+  pub async fn lock(&self, handle: &Handle) -> Result<OwnedMutexGuard<()>, CoordinatorError> {
+    let lock = {
+      // handles are digests, so any byte spreads them evenly over the shards
+      let shard_index = handle.to_bytes()[0] as usize % NUM_LEDGER_LOCK_SHARDS;
+      let mut shard = self.shards[shard_index]
+        .lock()
+        .map_err(|_e| CoordinatorError::FailedToAcquireWriteLock)?;
+      match shard.get(handle).and_then(Weak::upgrade) {
+        Some(lock) => lock,
+        None => {
+          if shard.len() >= LEDGER_LOCK_SHARD_PRUNE_LEN {
+            let len = shard.len();
+            shard.retain(|_handle, lock| lock.strong_count() > 0);
+            self
+              .num_locks
+              .fetch_sub(len - shard.len(), Ordering::Relaxed);
+          }
+          let lock = Arc::new(tokio::sync::Mutex::new(()));
+          if shard.insert(*handle, Arc::downgrade(&lock)).is_none() {
+            self.num_locks.fetch_add(1, Ordering::Relaxed);
+          }
+          lock
+        },
+      }
+    };
+    let _waiting = Waiting::new(&self.num_waiting);
+    Ok(lock.lock_owned().await)
+  }
+}
+
+impl Default for LedgerLocks {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// the tenants that the coordinator serves and the appends each of them made in the current second
+#[derive(Default)]
+struct Tenants {
+  ids: HashSet<String>,
+  /// the second since the Unix epoch and the number of appends in it, per tenant
+  append_windows: HashMap<String, (u64, u64)>,
+}
+
+impl Tenants {
+  /// the registered tenant whose namespace holds the handle bytes, if any
+  fn find(&self, handle_bytes: &[u8]) -> Option<String> {
+    let end = handle_bytes.iter().position(|b| *b == TENANT_SEPARATOR)?;
+    let tenant = std::str::from_utf8(&handle_bytes[..end]).ok()?;
+    self.ids.get(tenant).cloned()
+  }
+}
 
 const ATTESTATION_STR: &str = "THIS IS A PLACE HOLDER FOR ATTESTATION";
 
+/// the hash of the block of a stored entry, without its nonces, also once its contents are purged
+fn stored_block_hash(ledger_entry: &LedgerEntry) -> NimbleDigest {
+  match ledger_entry.get_purged_block_hash() {
+    Some(block_hash) => *block_hash,
+    None => ledger_entry.get_block().hash(),
+  }
+}
+
+/// fails with `ContentPurged` if the contents of the block of the entry at `index` were purged
+fn check_not_purged(index: usize, ledger_entry: &LedgerEntry) -> Result<(), CoordinatorError> {
+  match ledger_entry.get_purged_block_hash() {
+    Some(block_hash) => Err(CoordinatorError::ContentPurged {
+      index,
+      block_hash: *block_hash,
+      hash_nonces: ledger_entry.get_nonces().hash(),
+    }),
+    None => Ok(()),
+  }
+}
+
+/// fails with `FailedToObtainQuorum` unless `receipts` hold a quorum of the view they are for
+fn check_quorum(
+  verifier_state: &RwLock<VerifierState>,
+  receipts: &Receipts,
+) -> Result<(), CoordinatorError> {
+  let vs = verifier_state
+    .read()
+    .map_err(|_e| CoordinatorError::FailedToAcquireReadLock)?;
+  receipts
+    .check_quorum(&vs)
+    .map(|_quorum_size| ())
+    .map_err(|_e| CoordinatorError::FailedToObtainQuorum)
+}
+
+/// queues an event of a watch; returns whether the client is still there, or fails with
+/// `WatchLagged` if the client leaves the queue full for `WATCH_SLOW_CONSUMER_TIMEOUT`
+async fn push_watch_event(
+  tx: &mpsc::Sender<Result<WatchEvent, CoordinatorError>>,
+  event: WatchEvent,
+  next_height: usize,
+) -> Result<bool, CoordinatorError> {
+  match tokio::time::timeout(WATCH_SLOW_CONSUMER_TIMEOUT, tx.send(Ok(event))).await {
+    Ok(res) => Ok(res.is_ok()),
+    Err(_elapsed) => Err(CoordinatorError::WatchLagged { next_height }),
+  }
+}
+
+/// the records of an export bundle on their way to the client
+struct BundleWriter<'a> {
+  tx: &'a mpsc::Sender<Result<Vec<u8>, CoordinatorError>>,
+  num_records: u64,
+}
+
+impl<'a> BundleWriter<'a> {
+  /// queues `record` as a chunk of its own; returns whether the client is still there
+  async fn write(&mut self, record: BundleRecord) -> bool {
+    self.num_records += 1;
+    self.tx.send(Ok(record.encode())).await.is_ok()
+  }
+}
+
 async fn get_public_key_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   request: endorser_proto::GetPublicKeyReq,
 ) -> Result<tonic::Response<endorser_proto::GetPublicKeyResp>, Status> {
   loop {
     let res = endorser_client
-      .get_public_key(tonic::Request::new(request.clone()))
+      .get_public_key(telemetry::outgoing(request.clone()))
       .await;
     match res {
       Ok(resp) => {
@@ -77,12 +570,13 @@ async fn get_public_key_with_retry(
 }
 
 async fn new_ledger_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   request: endorser_proto::NewLedgerReq,
+  deadline: Deadline,
 ) -> Result<tonic::Response<endorser_proto::NewLedgerResp>, Status> {
   loop {
     let res = endorser_client
-      .new_ledger(tonic::Request::new(request.clone()))
+      .new_ledger(deadline.request(request.clone()))
       .await;
     match res {
       Ok(resp) => {
@@ -90,7 +584,7 @@ async fn new_ledger_with_retry(
       },
       Err(status) => {
         match status.code() {
-          Code::ResourceExhausted => {
+          Code::ResourceExhausted if !deadline.is_expired() => {
             continue;
           },
           _ => {
@@ -103,12 +597,13 @@ async fn new_ledger_with_retry(
 }
 
 async fn append_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   request: endorser_proto::AppendReq,
+  deadline: Deadline,
 ) -> Result<tonic::Response<endorser_proto::AppendResp>, Status> {
   loop {
     let res = endorser_client
-      .append(tonic::Request::new(request.clone()))
+      .append(deadline.request(request.clone()))
       .await;
     match res {
       Ok(resp) => {
@@ -116,7 +611,34 @@ async fn append_with_retry(
       },
       Err(status) => {
         match status.code() {
-          Code::ResourceExhausted => {
+          Code::ResourceExhausted if !deadline.is_expired() => {
+            continue;
+          },
+          _ => {
+            return Err(status);
+          },
+        };
+      },
+    };
+  }
+}
+
+async fn append_batch_with_retry(
+  endorser_client: &EndorserClient,
+  request: endorser_proto::AppendBatchReq,
+  deadline: Deadline,
+) -> Result<tonic::Response<endorser_proto::AppendBatchResp>, Status> {
+  loop {
+    let res = endorser_client
+      .append_batch(deadline.request(request.clone()))
+      .await;
+    match res {
+      Ok(resp) => {
+        return Ok(resp);
+      },
+      Err(status) => {
+        match status.code() {
+          Code::ResourceExhausted if !deadline.is_expired() => {
             continue;
           },
           _ => {
@@ -128,13 +650,90 @@ async fn append_with_retry(
   }
 }
 
+/// appends to a ledger in one endorser, bringing the endorser up to date first if it lags behind
+/// the ledger store, and returns the endorser's receipt
+async fn append_to_endorser(
+  ledger_store: LedgerStoreRef,
+  endorser_client: &EndorserClient,
+  endorser: &str,
+  handle: NimbleDigest,
+  request: endorser_proto::AppendReq,
+  deadline: Deadline,
+) -> Result<Bytes, CoordinatorError> {
+  let expected_height = request.expected_height as usize;
+  loop {
+    // the block is in the ledger store before it is sent to the endorsers
+    deadline.check(WriteStage::Persisted)?;
+    let res = append_with_retry(endorser_client, request.clone(), deadline).await;
+    match res {
+      Ok(resp) => {
+        let endorser_proto::AppendResp { receipt } = resp.into_inner();
+        return Ok(receipt);
+      },
+      Err(status) => match process_error(endorser, Some(&handle), &status) {
+        CoordinatorAction::UpdateEndorser => {
+          let height_to_start = if status.code() == Code::NotFound {
+            Some(0)
+          } else {
+            // the endorser reports its current height in the status details
+            status
+              .details()
+              .try_into()
+              .ok()
+              .and_then(|bytes| (u64::from_le_bytes(bytes) as usize).checked_add(1))
+          };
+          let height_to_start = match height_to_start {
+            Some(height) => height,
+            None => return Err(CoordinatorError::from(status)),
+          };
+          let height_to_end = expected_height - 1;
+          let res = update_endorser(
+            ledger_store.clone(),
+            endorser_client,
+            handle,
+            height_to_start,
+            height_to_end,
+            deadline,
+          )
+          .await;
+          match res {
+            Ok(_resp) => {
+              continue;
+            },
+            Err(status) => match process_error(endorser, Some(&handle), &status) {
+              CoordinatorAction::RemoveEndorser => {
+                return Err(CoordinatorError::UnexpectedError);
+              },
+              CoordinatorAction::IncrementReceipt => {
+                continue;
+              },
+              _ => {
+                return Err(CoordinatorError::FailedToAppendLedger);
+              },
+            },
+          }
+        },
+        CoordinatorAction::RemoveEndorser => {
+          return Err(CoordinatorError::UnexpectedError);
+        },
+        CoordinatorAction::IncrementReceipt => {
+          return Err(CoordinatorError::LedgerAlreadyExists);
+        },
+        _ => {
+          return Err(CoordinatorError::FailedToAppendLedger);
+        },
+      },
+    }
+  }
+}
+
 async fn read_latest_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   request: endorser_proto::ReadLatestReq,
 ) -> Result<tonic::Response<endorser_proto::ReadLatestResp>, Status> {
   loop {
     let res = endorser_client
-      .read_latest(tonic::Request::new(request.clone()))
+      .read_latest(telemetry::outgoing(request.clone()))
       .await;
     match res {
       Ok(resp) => {
@@ -155,21 +754,23 @@ async fn read_latest_with_retry(
 }
 
 async fn initialize_state_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
-  group_identity: Vec<u8>,
+  endorser_client: &EndorserClient,
+  group_identity: Bytes,
   ledger_tail_map: Arc<Vec<endorser_proto::LedgerTailMapEntry>>,
-  view_tail_metablock: Vec<u8>,
-  block_hash: Vec<u8>,
+  view_tail_metablock: Bytes,
+  block_hash: Bytes,
   expected_height: usize,
+  public_key: Bytes,
 ) -> Result<tonic::Response<endorser_proto::InitializeStateResp>, Status> {
   loop {
     let res = endorser_client
-      .initialize_state(tonic::Request::new(endorser_proto::InitializeStateReq {
+      .initialize_state(telemetry::outgoing(endorser_proto::InitializeStateReq {
         group_identity: group_identity.clone(),
         ledger_tail_map: ledger_tail_map.deref().clone(),
         view_tail_metablock: view_tail_metablock.clone(),
         block_hash: block_hash.clone(),
         expected_height: expected_height as u64,
+        public_key: public_key.clone(),
       }))
       .await;
     match res {
@@ -191,12 +792,12 @@ async fn initialize_state_with_retry(
 }
 
 async fn finalize_state_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   request: endorser_proto::FinalizeStateReq,
 ) -> Result<tonic::Response<endorser_proto::FinalizeStateResp>, Status> {
   loop {
     let res = endorser_client
-      .finalize_state(tonic::Request::new(request.clone()))
+      .finalize_state(telemetry::outgoing(request.clone()))
       .await;
     match res {
       Ok(resp) => {
@@ -217,12 +818,37 @@ async fn finalize_state_with_retry(
 }
 
 async fn read_state_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   request: endorser_proto::ReadStateReq,
 ) -> Result<tonic::Response<endorser_proto::ReadStateResp>, Status> {
   loop {
     let res = endorser_client
-      .read_state(tonic::Request::new(request.clone()))
+      .read_state(telemetry::outgoing(request.clone()))
+      .await;
+    match res {
+      Ok(resp) => {
+        return Ok(resp);
+      },
+      Err(status) => {
+        match status.code() {
+          Code::ResourceExhausted => {
+            continue;
+          },
+          _ => {
+            return Err(status);
+          },
+        };
+      },
+    };
+  }
+}
+
+async fn rotate_key_with_retry(
+  endorser_client: &EndorserClient,
+) -> Result<tonic::Response<endorser_proto::RotateKeyResp>, Status> {
+  loop {
+    let res = endorser_client
+      .rotate_key(telemetry::outgoing(endorser_proto::RotateKeyReq {}))
       .await;
     match res {
       Ok(resp) => {
@@ -243,16 +869,16 @@ async fn read_state_with_retry(
 }
 
 async fn activate_with_retry(
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
-  old_config: Vec<u8>,
-  new_config: Vec<u8>,
+  endorser_client: &EndorserClient,
+  old_config: Bytes,
+  new_config: Bytes,
   ledger_tail_maps: Arc<Vec<endorser_proto::LedgerTailMap>>,
   ledger_chunks: Vec<endorser_proto::LedgerChunkEntry>,
-  receipts: Vec<u8>,
+  receipts: Bytes,
 ) -> Result<tonic::Response<endorser_proto::ActivateResp>, Status> {
   loop {
     let res = endorser_client
-      .activate(tonic::Request::new(endorser_proto::ActivateReq {
+      .activate(telemetry::outgoing(endorser_proto::ActivateReq {
         old_config: old_config.clone(),
         new_config: new_config.clone(),
         ledger_tail_maps: ledger_tail_maps.deref().clone(),
@@ -278,18 +904,45 @@ async fn activate_with_retry(
   }
 }
 
+async fn read_view_tail_with_retry(
+  endorser_client: &EndorserClient,
+  request: endorser_proto::ReadViewTailReq,
+) -> Result<tonic::Response<endorser_proto::ReadViewTailResp>, Status> {
+  loop {
+    let res = endorser_client
+      .read_view_tail(telemetry::outgoing(request.clone()))
+      .await;
+    match res {
+      Ok(resp) => {
+        return Ok(resp);
+      },
+      Err(status) => {
+        match status.code() {
+          Code::ResourceExhausted => {
+            continue;
+          },
+          _ => {
+            return Err(status);
+          },
+        };
+      },
+    };
+  }
+}
+
 async fn update_endorser(
   ledger_store: LedgerStoreRef,
-  endorser_client: &mut endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
+  endorser_client: &EndorserClient,
   handle: NimbleDigest,
   start: usize,
   end: usize,
+  deadline: Deadline,
 ) -> Result<(), Status> {
   for idx in start..=end {
     let ledger_entry = {
       let res = ledger_store.read_ledger_by_index(&handle, idx).await;
       if res.is_err() {
-        eprintln!("Failed to read ledger by index {:?}", res);
+        warn!("Failed to read ledger by index {:?}", res);
         return Err(Status::aborted("Failed to read ledger by index"));
       }
       res.unwrap()
@@ -299,14 +952,11 @@ async fn update_endorser(
       let endorser_proto::NewLedgerResp { receipt } = new_ledger_with_retry(
         endorser_client,
         endorser_proto::NewLedgerReq {
-          handle: handle.to_bytes(),
-          block_hash: compute_aggregated_block_hash(
-            &ledger_entry.get_block().hash().to_bytes(),
-            &ledger_entry.get_nonces().hash().to_bytes(),
-          )
-          .to_bytes(),
-          block: ledger_entry.get_block().to_bytes(),
+          handle: handle.to_bytes().into(),
+          block_hash: ledger_entry.get_block_hash().to_bytes().into(),
+          block: ledger_entry.get_block().to_bytes().into(),
         },
+        deadline,
       )
       .await?
       .into_inner();
@@ -315,16 +965,14 @@ async fn update_endorser(
       let endorser_proto::AppendResp { receipt } = append_with_retry(
         endorser_client,
         endorser_proto::AppendReq {
-          handle: handle.to_bytes(),
-          block_hash: compute_aggregated_block_hash(
-            &ledger_entry.get_block().hash().to_bytes(),
-            &ledger_entry.get_nonces().hash().to_bytes(),
-          )
-          .to_bytes(),
+          handle: handle.to_bytes().into(),
+          block_hash: ledger_entry.get_block_hash().to_bytes().into(),
           expected_height: idx as u64,
-          block: ledger_entry.get_block().to_bytes(),
-          nonces: ledger_entry.get_nonces().to_bytes(),
+          block: ledger_entry.get_block().to_bytes().into(),
+          nonces: ledger_entry.get_nonces().to_bytes().into(),
+          expected_tail: Bytes::new(),
         },
+        deadline,
       )
       .await?
       .into_inner();
@@ -341,13 +989,13 @@ async fn update_endorser(
         .attach_ledger_receipts(&handle, idx, &receipts)
         .await;
       if res.is_err() {
-        eprintln!(
+        warn!(
           "Failed to attach ledger receipt to the ledger store ({:?})",
           res
         );
       }
     } else {
-      eprintln!("Failed to parse a receipt ({:?})", res);
+      warn!("Failed to parse a receipt ({:?})", res);
     }
   }
 
@@ -368,154 +1016,392 @@ fn process_error(
   handle: Option<&NimbleDigest>,
   status: &Status,
 ) -> CoordinatorAction {
+  let handle = handle.map(tracing::field::display);
   match status.code() {
     Code::Aborted => {
-      eprintln!("operation aborted to due to ledger store");
+      warn!(
+        endorser,
+        handle, "the operation was aborted in the endorser"
+      );
       CoordinatorAction::DoNothing
     },
     Code::AlreadyExists => {
-      if let Some(h) = handle {
-        eprintln!("ledger {:?} already exists in endorser {}", h, endorser);
-      } else {
-        eprintln!(
-          "the requested operation was already done in endorser {}",
-          endorser
-        );
-      }
+      warn!(
+        endorser,
+        handle, "the operation was already done in the endorser"
+      );
       CoordinatorAction::IncrementReceipt
     },
     Code::Cancelled => {
-      eprintln!("endorser {} is locked", endorser);
+      warn!(endorser, "the endorser is locked");
       CoordinatorAction::DoNothing
     },
     Code::FailedPrecondition | Code::NotFound => {
-      if let Some(h) = handle {
-        eprintln!("ledger {:?} lags behind in endorser {}", h, endorser);
-      } else {
-        eprintln!("a ledger lags behind in endorser {}", endorser);
-      }
+      warn!(endorser, handle, "the ledger lags behind in the endorser");
       CoordinatorAction::UpdateEndorser
     },
     Code::InvalidArgument => {
-      if let Some(h) = handle {
-        eprintln!(
-          "the requested height for ledger {:?} in endorser {} is too small",
-          h, endorser
-        );
-      } else {
-        eprintln!(
-          "the requested height for a ledger in endorser {} is too small",
-          endorser
-        );
-      }
+      warn!(
+        endorser,
+        handle, "the requested height is too small for the endorser"
+      );
       CoordinatorAction::DoNothing
     },
     Code::OutOfRange => {
-      if let Some(h) = handle {
-        eprintln!(
-          "the requested height for ledger {:?} in endorser {} is out of range",
-          h, endorser
-        );
-      } else {
-        eprintln!(
-          "the requested height for a ledger in endorser {} is out of range",
-          endorser
-        );
-      }
+      warn!(
+        endorser,
+        handle, "the requested height is out of range in the endorser"
+      );
       CoordinatorAction::DoNothing
     },
 
     Code::Unavailable => {
-      eprintln!("the endorser is already finalized");
+      warn!(endorser, "the endorser is already finalized");
       CoordinatorAction::DoNothing
     },
     Code::Unimplemented => {
-      eprintln!("the endorser is not initialized");
+      warn!(endorser, "the endorser is not initialized");
       CoordinatorAction::DoNothing
     },
     Code::ResourceExhausted => CoordinatorAction::Retry,
     Code::Internal | Code::Unknown => CoordinatorAction::RemoveEndorser,
     _ => {
-      eprintln!("Unhandled status={:?}", status);
+      warn!(endorser, ?status, "unhandled status");
       CoordinatorAction::DoNothing
     },
   }
 }
 
 impl CoordinatorState {
+  /// opens the ledger store and recovers the current view from it, bringing its endorsers up to
+  /// date with the store
   pub async fn new(
     ledger_store_type: &str,
     args: &HashMap<String, String>,
     num_grpc_channels_opt: Option<usize>,
+    endorser_timeout_opt: Option<u64>,
+    min_num_endorsers_opt: Option<usize>,
+    max_block_size_opt: Option<usize>,
+  ) -> Result<CoordinatorState, CoordinatorError> {
+    let coordinator = CoordinatorState::open(
+      ledger_store_type,
+      args,
+      num_grpc_channels_opt,
+      endorser_timeout_opt,
+      min_num_endorsers_opt,
+      max_block_size_opt,
+    )
+    .await?;
+    coordinator.recover().await?;
+    Ok(coordinator)
+  }
+
+  /// opens the ledger store without recovering from it or contacting any endorser; a standby
+  /// coordinator recovers only once it takes over the lease
+  pub async fn open(
+    ledger_store_type: &str,
+    args: &HashMap<String, String>,
+    num_grpc_channels_opt: Option<usize>,
+    endorser_timeout_opt: Option<u64>,
+    min_num_endorsers_opt: Option<usize>,
+    max_block_size_opt: Option<usize>,
   ) -> Result<CoordinatorState, CoordinatorError> {
     let num_grpc_channels = match num_grpc_channels_opt {
       Some(n) => n,
       None => DEFAULT_NUM_GRPC_CHANNELS,
     };
-    let coordinator = match ledger_store_type {
-      "mongodb_cosmos" => CoordinatorState {
-        ledger_store: Arc::new(Box::new(MongoCosmosLedgerStore::new(args).await.unwrap())),
-        conn_map: Arc::new(RwLock::new(HashMap::new())),
-        verifier_state: Arc::new(RwLock::new(VerifierState::new())),
-        num_grpc_channels,
-      },
-      "table" => CoordinatorState {
-        ledger_store: Arc::new(Box::new(TableLedgerStore::new(args).await.unwrap())),
-        conn_map: Arc::new(RwLock::new(HashMap::new())),
-        verifier_state: Arc::new(RwLock::new(VerifierState::new())),
-        num_grpc_channels,
-      },
-      "filestore" => CoordinatorState {
-        ledger_store: Arc::new(Box::new(FileStore::new(args).await.unwrap())),
-        conn_map: Arc::new(RwLock::new(HashMap::new())),
-        verifier_state: Arc::new(RwLock::new(VerifierState::new())),
-        num_grpc_channels,
-      },
-      _ => CoordinatorState {
-        ledger_store: Arc::new(Box::new(InMemoryLedgerStore::new())),
-        conn_map: Arc::new(RwLock::new(HashMap::new())),
-        verifier_state: Arc::new(RwLock::new(VerifierState::new())),
-        num_grpc_channels,
-      },
+    let endorser_timeout = match endorser_timeout_opt {
+      Some(t) => t,
+      None => DEFAULT_ENDORSER_REQUEST_TIMEOUT,
+    };
+    let min_num_endorsers = match min_num_endorsers_opt {
+      Some(n) => n,
+      None => DEFAULT_MIN_NUM_ENDORSERS,
+    };
+    let max_block_size = match max_block_size_opt {
+      Some(n) => n,
+      None => DEFAULT_MAX_BLOCK_SIZE,
+    };
+    let ledger_store: Box<dyn LedgerStore + Send + Sync> = match ledger_store_type {
+      "mongodb_cosmos" => Box::new(MongoCosmosLedgerStore::new(args).await?),
+      "table" => Box::new(TableLedgerStore::new(args).await?),
+      "filestore" => Box::new(FileStore::new(args).await?),
+      #[cfg(feature = "rocksdb")]
+      "rocksdb" => Box::new(store::ledger::rocksdb_store::RocksDBLedgerStore::new(args).await?),
+      _ => Box::new(InMemoryLedgerStore::new()),
+    };
+    let metrics = Arc::new(Metrics::new());
+    let health = Arc::new(Health::new());
+    let coordinator = CoordinatorState {
+      ledger_store: Arc::new(Box::new(
+        MeteredLedgerStore::new(
+          ledger_store,
+          backend_label(ledger_store_type),
+          metrics.clone(),
+        )
+        .with_health(health.clone()),
+      )),
+      conn_map: Arc::new(RwLock::new(HashMap::new())),
+      verifier_state: Arc::new(RwLock::new(VerifierState::new())),
+      num_grpc_channels,
+      connector: Arc::new(GrpcConnector::new(endorser_timeout)),
+      min_num_endorsers,
+      max_block_size,
+      pipeline_depth: DEFAULT_PIPELINE_DEPTH,
+      request_id_retention: DEFAULT_REQUEST_ID_RETENTION,
+      pipelines: Mutex::new(HashMap::new()),
+      pending_intents: Mutex::new(HashSet::new()),
+      view_change_lock: tokio::sync::RwLock::new(()),
+      ledger_locks: LedgerLocks::new(),
+      watchers: Watchers::default(),
+      invalid_signatures: Mutex::new(HashMap::new()),
+      tenants: tokio::sync::Mutex::new(Tenants::default()),
+      lease: None,
+      shutdown: watch::channel(ShutdownPhase::Running).0,
+      metrics,
+      health,
+      endorser_heartbeats: EndorserHeartbeats::default(),
+      view_size: AtomicUsize::new(0),
+      view_quorum: AtomicUsize::new(0),
+      quorum: RwLock::new(None),
+      endorser_allowlist: RwLock::new(None),
+      clock: Arc::new(SystemClock),
+      rng: Mutex::new(StdRng::from_entropy()),
     };
 
-    let res = coordinator.ledger_store.read_view_ledger_tail().await;
-    if res.is_err() {
-      eprintln!("Failed to read the view ledger tail {:?}", res);
-      return Err(CoordinatorError::FailedToReadViewLedger);
-    }
-
+    Ok(coordinator)
+  }
+
+  /// makes the coordinator hold `lease` to serve writes
+  pub fn with_lease(mut self, lease: Lease) -> Self {
+    self.lease = Some(lease);
+    self
+  }
+
+  /// makes the coordinator open its connections to endorsers with `connector` rather than over
+  /// gRPC
+  #[cfg(any(test, feature = "testing"))]
+  pub fn with_endorser_connector(mut self, connector: Arc<dyn EndorserConnector>) -> Self {
+    self.connector = connector;
+    self
+  }
+
+  /// makes the coordinator read the times it writes to the ledger store from `clock`
+  #[cfg(any(test, feature = "testing"))]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// makes the coordinator draw its channels and nonces from `seed`, so that a simulation makes
+  /// the same choices whenever it runs
+  #[cfg(any(test, feature = "testing"))]
+  pub fn with_rng_seed(mut self, seed: u64) -> Self {
+    self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+    self
+  }
+
+  /// makes the coordinator send up to `depth` consecutive appends to a ledger to the endorsers
+  /// together, instead of one append at a time
+  pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+    self.pipeline_depth = depth.clamp(1, MAX_APPEND_BATCH_SIZE);
+    self
+  }
+
+  /// makes the first view that the coordinator creates need the receipts of `quorum` of its
+  /// endorsers rather than of a majority; a view ledger that the coordinator recovers keeps the
+  /// quorum that it records
+  pub fn with_quorum(mut self, quorum: Option<usize>) -> Self {
+    self.quorum = RwLock::new(quorum);
+    self
+  }
+
+  /// makes the coordinator keep the request IDs of the latest `retention` appends to each ledger
+  pub fn with_request_id_retention(mut self, retention: usize) -> Self {
+    self.request_id_retention = retention.max(1);
+    self
+  }
+
+  /// makes the coordinator connect only to the endorsers of `allowlist`
+  pub fn with_endorser_allowlist(mut self, allowlist: EndorserAllowlist) -> Self {
+    self.endorser_allowlist = RwLock::new(Some(allowlist));
+    self
+  }
+
+  /// whether the coordinator may connect to the endorser with `pk`
+  fn is_endorser_allowlisted(&self, pk: &[u8]) -> bool {
+    match self.endorser_allowlist.read() {
+      Ok(allowlist) => allowlist
+        .as_ref()
+        .map_or(true, |allowlist| allowlist.contains(pk)),
+      Err(_) => false,
+    }
+  }
+
+  /// reads the files of the allowlist of endorsers again, and keeps the allowlist it has if they
+  /// do not verify. The new allowlist applies to the endorsers that the coordinator connects to
+  /// from now on; connected endorsers that it leaves out are only logged. Returns the number of
+  /// keys of the allowlist
+  pub fn reload_endorser_allowlist(&self) -> Result<usize, CoordinatorError> {
+    let current = match self.endorser_allowlist.read() {
+      Ok(allowlist) => allowlist.clone(),
+      Err(_) => return Err(CoordinatorError::FailedToAcquireReadLock),
+    };
+    let allowlist = current
+      .ok_or_else(|| {
+        CoordinatorError::InvalidEndorserAllowlist(
+          "the coordinator has no allowlist of endorsers".to_string(),
+        )
+      })?
+      .reload()
+      .map_err(CoordinatorError::InvalidEndorserAllowlist)?;
+    for (pk, uri) in self.get_endorser_hostnames() {
+      if !allowlist.contains(&pk) {
+        warn!(
+          endorser = %uri,
+          pk = %hex::encode(&pk),
+          "the reloaded allowlist leaves out a connected endorser"
+        );
+      }
+    }
+    let num_keys = allowlist.num_keys();
+    match self.endorser_allowlist.write() {
+      Ok(mut current) => *current = Some(allowlist),
+      Err(_) => return Err(CoordinatorError::FailedToAcquireWriteLock),
+    }
+    info!(num_keys, "reloaded the allowlist of endorsers");
+    Ok(num_keys)
+  }
+
+  pub fn metrics(&self) -> &Arc<Metrics> {
+    &self.metrics
+  }
+
+  /// the largest block that the coordinator accepts, in bytes
+  pub fn max_block_size(&self) -> usize {
+    self.max_block_size
+  }
+
+  pub fn health(&self) -> &Health {
+    &self.health
+  }
+
+  pub fn endorser_heartbeats(&self) -> &EndorserHeartbeats {
+    &self.endorser_heartbeats
+  }
+
+  /// the number of appends whose intents the coordinator recorded and did not commit yet
+  pub fn num_pending_intents(&self) -> usize {
+    self
+      .pending_intents
+      .lock()
+      .map_or(0, |pending_intents| pending_intents.len())
+  }
+
+  /// checks whether the coordinator is ready to serve, from the endorsers it is connected to, and
+  /// that answer their heartbeats if it sends them, and the writes to its ledger store; returns the
+  /// new readiness if it changed
+  pub fn check_health(&self) -> Option<bool> {
+    self.health.check(HealthInputs {
+      num_connected: self
+        .get_endorser_pks()
+        .iter()
+        .filter(|pk| self.endorser_heartbeats.is_alive(pk))
+        .count(),
+      view_size: self.view_size.load(Ordering::SeqCst),
+      quorum: self.view_quorum.load(Ordering::SeqCst),
+      accepts_writes: self.check_serving().is_ok(),
+    })
+  }
+
+  /// waits until the coordinator holds its lease, taking the lease over once the coordinator that
+  /// holds it stops renewing it; returns at once if the coordinator runs without a lease
+  pub async fn take_lease(&self) {
+    let lease = match &self.lease {
+      Some(lease) => lease,
+      None => return,
+    };
+    loop {
+      match lease.acquire(&**self.ledger_store).await {
+        Ok(true) => return,
+        Ok(false) => {},
+        Err(error) => warn!("Failed to take the lease {:?}", error),
+      }
+      tokio::time::sleep(lease.renew_interval()).await;
+    }
+  }
+
+  /// renews the lease of the coordinator until the coordinator loses it, after which it rejects
+  /// writes; returns at once if the coordinator runs without a lease
+  pub async fn keep_lease(&self) {
+    let lease = match &self.lease {
+      Some(lease) => lease,
+      None => return,
+    };
+    while lease.is_held() {
+      tokio::time::sleep(lease.renew_interval()).await;
+      if let Err(error) = lease.acquire(&**self.ledger_store).await {
+        warn!("Failed to renew the lease {:?}", error);
+      }
+    }
+  }
+
+  /// fails if the coordinator is shutting down, or runs with a lease that it does not hold
+  fn check_serving(&self) -> Result<(), CoordinatorError> {
+    if *self.shutdown.borrow() != ShutdownPhase::Running {
+      return Err(CoordinatorError::ShuttingDown);
+    }
+    match &self.lease {
+      Some(lease) if !lease.is_held() => Err(CoordinatorError::LeaseNotHeld),
+      _ => Ok(()),
+    }
+  }
+
+  /// recovers the current view from the ledger store, resuming a view change that did not
+  /// complete, and brings the endorsers of the view up to date with the store; this runs once,
+  /// when the coordinator starts or takes over the lease, before it serves any client
+  pub async fn recover(&self) -> Result<(), CoordinatorError> {
+    let res = self.ledger_store.read_view_ledger_tail().await;
+    if res.is_err() {
+      warn!("Failed to read the view ledger tail {:?}", res);
+      return Err(CoordinatorError::FailedToReadViewLedger);
+    }
+
     let (view_ledger_tail, tail_height) = res.unwrap();
 
     if tail_height > 0 {
       let view_ledger_head = if tail_height == 1 {
         view_ledger_tail.clone()
       } else {
-        let res = coordinator
-          .ledger_store
-          .read_view_ledger_by_index(1usize)
-          .await;
+        let res = self.ledger_store.read_view_ledger_by_index(1usize).await;
         match res {
           Ok(l) => l,
           Err(e) => {
-            eprintln!("Failed to read the view ledger head {:?}", e);
+            warn!("Failed to read the view ledger head {:?}", e);
             return Err(CoordinatorError::FailedToReadViewLedger);
           },
         }
       };
-      if let Ok(mut vs) = coordinator.verifier_state.write() {
-        vs.set_group_identity(view_ledger_head.get_block().hash());
+      let group_identity = match compute_view_block_hash(&view_ledger_head.get_block().to_bytes()) {
+        Ok(hash) => hash,
+        Err(e) => {
+          warn!("Failed to compute the view block hash ({:?})", e);
+          return Err(CoordinatorError::FailedToReadViewLedger);
+        },
+      };
+      if let Ok(mut vs) = self.verifier_state.write() {
+        vs.set_group_identity(group_identity);
       } else {
         return Err(CoordinatorError::FailedToAcquireWriteLock);
       }
 
       // Connect to current endorsers
-      let curr_endorsers = coordinator
+      let curr_endorsers = self
         .connect_to_existing_endorsers(&view_ledger_tail.get_block().to_bytes())
         .await?;
 
       // Check if the latest view change was completed
-      let res = if let Ok(mut vs) = coordinator.verifier_state.write() {
+      let res = if let Ok(mut vs) = self.verifier_state.write() {
         vs.apply_view_change(
           &view_ledger_tail.get_block().to_bytes(),
           &view_ledger_tail.get_receipts().to_bytes(),
@@ -524,53 +1410,60 @@ impl CoordinatorState {
       } else {
         return Err(CoordinatorError::FailedToAcquireWriteLock);
       };
-      if let Err(error) = res {
-        // Collect receipts again!
-        if error == VerificationError::InsufficientReceipts {
-          let res = coordinator
-            .ledger_store
-            .read_view_ledger_by_index(tail_height - 1)
-            .await;
-          if res.is_err() {
-            eprintln!(
-              "Failed to read the view ledger entry at index {} ({:?})",
-              tail_height - 1,
-              res
-            );
-            return Err(CoordinatorError::FailedToReadViewLedger);
-          }
-          let prev_view_ledger_entry = res.unwrap();
-          let prev_endorsers = coordinator
-            .connect_to_existing_endorsers(&prev_view_ledger_entry.get_block().to_bytes())
-            .await?;
-          let res = coordinator
-            .apply_view_change(
-              &prev_endorsers,
-              &curr_endorsers,
-              &prev_view_ledger_entry,
-              view_ledger_tail.get_block(),
-              tail_height,
-            )
-            .await;
-          if let Err(error) = res {
-            eprintln!("Failed to re-apply view change {:?}", error);
-            return Err(error);
-          }
-        } else {
-          eprintln!(
+      // Resume the latest view change if its receipts were never stored or if some of its
+      // endorsers were never activated
+      let resume = match res {
+        Ok(()) => self.has_inactive_endorsers(&curr_endorsers).await,
+        Err(VerificationError::InsufficientReceipts) => true,
+        Err(error) => {
+          warn!(
             "Failed to apply view change at the tail {} ({:?})",
             tail_height, error
           );
           return Err(CoordinatorError::FailedToActivate);
+        },
+      };
+      if resume {
+        let res = self
+          .ledger_store
+          .read_view_ledger_by_index(tail_height - 1)
+          .await;
+        if res.is_err() {
+          warn!(
+            "Failed to read the view ledger entry at index {} ({:?})",
+            tail_height - 1,
+            res
+          );
+          return Err(CoordinatorError::FailedToReadViewLedger);
+        }
+        let prev_view_ledger_entry = res.unwrap();
+        // the entry below the first view holds no endorsers
+        let prev_endorsers = if tail_height == 1 {
+          EndorserHostnames::new()
+        } else {
+          self
+            .connect_to_existing_endorsers(&prev_view_ledger_entry.get_block().to_bytes())
+            .await?
+        };
+        let res = self
+          .apply_view_change(
+            &prev_endorsers,
+            &curr_endorsers,
+            &prev_view_ledger_entry,
+            view_ledger_tail.get_block(),
+            tail_height,
+          )
+          .await;
+        if let Err(error) = res {
+          warn!("Failed to re-apply view change {:?}", error);
+          return Err(error);
         }
       }
 
       // Remove endorsers that don't have the latest view
-      let res = coordinator
-        .filter_endorsers(&curr_endorsers, tail_height)
-        .await;
+      let res = self.filter_endorsers(&curr_endorsers, tail_height).await;
       if let Err(error) = res {
-        eprintln!(
+        warn!(
           "Failed to filter the endorsers with the latest view {:?}",
           error
         );
@@ -579,22 +1472,25 @@ impl CoordinatorState {
     }
 
     for idx in (1..tail_height).rev() {
-      let res = coordinator
-        .ledger_store
-        .read_view_ledger_by_index(idx)
-        .await;
+      let res = self.ledger_store.read_view_ledger_by_index(idx).await;
       if res.is_err() {
-        eprintln!(
+        warn!(
           "Failed to read the view ledger entry at index {} ({:?})",
           idx, res
         );
         return Err(CoordinatorError::FailedToReadViewLedger);
       }
       let view_ledger_entry = res.unwrap();
-      if let Ok(mut vs) = coordinator.verifier_state.write() {
+      if let Ok(mut vs) = self.verifier_state.write() {
         // Set group identity
         if idx == 1 {
-          vs.set_group_identity(view_ledger_entry.get_block().hash());
+          match compute_view_block_hash(&view_ledger_entry.get_block().to_bytes()) {
+            Ok(group_identity) => vs.set_group_identity(group_identity),
+            Err(e) => {
+              warn!("Failed to compute the view block hash ({:?})", e);
+              return Err(CoordinatorError::FailedToReadViewLedger);
+            },
+          }
         }
         let res = vs.apply_view_change(
           &view_ledger_entry.get_block().to_bytes(),
@@ -602,7 +1498,7 @@ impl CoordinatorState {
           None,
         );
         if res.is_err() {
-          eprintln!("Failed to apply view change at index {} ({:?})", idx, res);
+          warn!("Failed to apply view change at index {} ({:?})", idx, res);
           return Err(CoordinatorError::FailedToActivate);
         }
       } else {
@@ -610,7 +1506,31 @@ impl CoordinatorState {
       }
     }
 
-    Ok(coordinator)
+    // Bring the endorsers of the current view up to date with the ledger store
+    // before accepting any client traffic
+    if tail_height > 0 {
+      // the scans of the ledgers report their progress against an estimate of their number
+      let num_ledgers = match self.ledger_store.estimate_num_ledgers().await {
+        Ok(num_ledgers) => num_ledgers,
+        Err(e) => {
+          warn!("Failed to estimate the number of ledgers {:?}", e);
+          None
+        },
+      };
+      self
+        .health
+        .start_recovery_scan("repairing the endorsers", num_ledgers);
+      self
+        .repair_endorsers(&self.get_endorser_hostnames())
+        .await?;
+      self.resolve_intents().await?;
+      self
+        .health
+        .start_recovery_scan("reconciling the ledgers", num_ledgers);
+      self.reconcile_ledgers().await?;
+    }
+
+    Ok(())
   }
 
   async fn connect_to_existing_endorsers(
@@ -619,13 +1539,14 @@ impl CoordinatorState {
   ) -> Result<EndorserHostnames, CoordinatorError> {
     let res = bincode::deserialize(view_ledger_block);
     if res.is_err() {
-      eprintln!(
+      warn!(
         "Failed to deserialize the view ledger tail's genesis block {:?}",
         res
       );
       return Err(CoordinatorError::FailedToSerde);
     }
     let endorser_hostnames: EndorserHostnames = res.unwrap();
+    self.set_view(view_ledger_block, endorser_hostnames.len())?;
 
     let mut endorsers = EndorserHostnames::new();
 
@@ -639,39 +1560,70 @@ impl CoordinatorState {
     Ok(endorsers)
   }
 
-  fn get_endorser_client(
-    &self,
-    pk: &[u8],
-  ) -> Option<(
-    endorser_proto::endorser_call_client::EndorserCallClient<Channel>,
-    String,
-  )> {
+  /// tracks the size of the view with `view_block`, which has `num_endorsers` endorsers, and the
+  /// quorum that the block records, as those of the current view
+  fn set_view(&self, view_block: &[u8], num_endorsers: usize) -> Result<(), CoordinatorError> {
+    let quorum = retrieve_quorum_from_config(view_block)?;
+    match self.quorum.write() {
+      Ok(mut recorded) => {
+        *recorded = Some(quorum).filter(|quorum| *quorum != majority_quorum(num_endorsers))
+      },
+      Err(_e) => return Err(CoordinatorError::FailedToAcquireWriteLock),
+    }
+    self.view_size.store(num_endorsers, Ordering::SeqCst);
+    self.view_quorum.store(quorum, Ordering::SeqCst);
+    Ok(())
+  }
+
+  /// the number of endorsers whose receipts the current view needs
+  pub fn get_quorum(&self) -> usize {
+    self.view_quorum.load(Ordering::SeqCst)
+  }
+
+  pub(crate) fn get_endorser_client(&self, pk: &[u8]) -> Option<(EndorserClient, String)> {
     if let Ok(conn_map_rd) = self.conn_map.read() {
       let e = conn_map_rd.get(pk);
       match e {
         None => {
-          eprintln!("No endorser has this public key {:?}", pk);
+          warn!("No endorser has this public key {:?}", pk);
           None
         },
-        Some(v) => Some((
-          v.clients[random::<usize>() % self.num_grpc_channels].clone(),
-          v.uri.clone(),
-        )),
+        Some(v) => {
+          let channel = match self.rng.lock() {
+            Ok(mut rng) => rng.gen_range(0..self.num_grpc_channels),
+            Err(_) => 0,
+          };
+          Some((v.clients[channel].clone(), v.uri.clone()))
+        },
       }
     } else {
-      eprintln!("Failed to acquire read lock");
+      error!("failed to acquire the read lock");
       None
     }
   }
 
-  pub fn get_endorser_pks(&self) -> Vec<Vec<u8>> {
+  pub fn get_endorser_pks(&self) -> Vec<EndorserKey> {
     if let Ok(conn_map_rd) = self.conn_map.read() {
-      conn_map_rd
-        .iter()
-        .map(|(pk, _endorser)| pk.clone())
-        .collect::<Vec<Vec<u8>>>()
+      conn_map_rd.keys().copied().collect::<Vec<EndorserKey>>()
+    } else {
+      error!("failed to acquire the read lock");
+      Vec::new()
+    }
+  }
+
+  /// the keys of the connections to `endorsers`, leaving out the endorsers that are not connected
+  fn get_endorser_keys(&self, endorsers: &EndorserHostnames) -> Vec<EndorserKey> {
+    if let Ok(conn_map_rd) = self.conn_map.read() {
+      let mut keys = Vec::with_capacity(endorsers.len());
+      for (pk, uri) in endorsers {
+        match conn_map_rd.get_key_value(pk.as_slice()) {
+          Some((key, _endorser)) => keys.push(*key),
+          None => warn!("No endorser has this public key {:?} ({})", pk, uri),
+        }
+      }
+      keys
     } else {
-      eprintln!("Failed to acquire read lock");
+      error!("failed to acquire the read lock");
       Vec::new()
     }
   }
@@ -683,7 +1635,7 @@ impl CoordinatorState {
         .map(|(_pk, endorser)| endorser.uri.clone())
         .collect::<Vec<String>>()
     } else {
-      eprintln!("Failed to acquire read lock");
+      error!("failed to acquire the read lock");
       Vec::new()
     }
   }
@@ -692,10 +1644,10 @@ impl CoordinatorState {
     if let Ok(conn_map_rd) = self.conn_map.read() {
       conn_map_rd
         .iter()
-        .map(|(pk, endorser)| (pk.clone(), endorser.uri.clone()))
+        .map(|(pk, endorser)| (pk.to_vec(), endorser.uri.clone()))
         .collect::<Vec<(Vec<u8>, String)>>()
     } else {
-      eprintln!("Failed to acquire read lock");
+      error!("failed to acquire the read lock");
       Vec::new()
     }
   }
@@ -704,7 +1656,7 @@ impl CoordinatorState {
     if let Ok(conn_map_rd) = self.conn_map.read() {
       for (pk, endorser) in conn_map_rd.iter() {
         if endorser.uri == hostname {
-          return Some(pk.clone());
+          return Some(pk.to_vec());
         }
       }
     }
@@ -712,45 +1664,55 @@ impl CoordinatorState {
   }
 
   pub async fn connect_endorsers(&self, hostnames: &[String]) -> EndorserHostnames {
+    self.try_connect_endorsers(hostnames).await.0
+  }
+
+  /// connects to the endorsers at `hostnames` for a view change, which fails with
+  /// `EndorserNotAllowlisted` if any of them is not on the allowlist
+  async fn connect_new_endorsers(
+    &self,
+    hostnames: &[String],
+  ) -> Result<EndorserHostnames, CoordinatorError> {
+    let (endorsers, rejected) = self.try_connect_endorsers(hostnames).await;
+    if !rejected.is_empty() {
+      self.disconnect_endorsers(&endorsers).await;
+      return Err(CoordinatorError::EndorserNotAllowlisted);
+    }
+    Ok(endorsers)
+  }
+
+  /// connects to the endorsers at `hostnames` that are on the allowlist, if there is one. Returns
+  /// the endorsers that were connected, and those that were turned away since their keys are not
+  /// on the allowlist
+  async fn try_connect_endorsers(
+    &self,
+    hostnames: &[String],
+  ) -> (EndorserHostnames, EndorserHostnames) {
     let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
     for hostname in hostnames {
       for _idx in 0..self.num_grpc_channels {
         let tx = mpsc_tx.clone();
         let endorser = hostname.clone();
+        let connector = self.connector.clone();
 
-        let _job = tokio::spawn(async move {
-          let res = Endpoint::from_shared(endorser.to_string());
-          if let Ok(endorser_endpoint) = res {
-            let endorser_endpoint = endorser_endpoint
-              .connect_timeout(std::time::Duration::from_secs(ENDORSER_CONNECT_TIMEOUT));
-            let endorser_endpoint =
-              endorser_endpoint.timeout(std::time::Duration::from_secs(ENDORSER_REQUEST_TIMEOUT));
-            let res = endorser_endpoint.connect().await;
-            if let Ok(channel) = res {
-              let mut client =
-                endorser_proto::endorser_call_client::EndorserCallClient::new(channel);
-
-              let res =
-                get_public_key_with_retry(&mut client, endorser_proto::GetPublicKeyReq {}).await;
-              if let Ok(resp) = res {
-                let endorser_proto::GetPublicKeyResp { pk } = resp.into_inner();
-                let _ = tx.send((endorser, Ok((client, pk)))).await;
-              } else {
-                eprintln!("Failed to retrieve the public key: {:?}", res);
-                let _ = tx
-                  .send((endorser, Err(CoordinatorError::UnableToRetrievePublicKey)))
-                  .await;
-              }
-            } else {
-              eprintln!("Failed to connect to the endorser {}: {:?}", endorser, res);
-              let _ = tx
-                .send((endorser, Err(CoordinatorError::FailedToConnectToEndorser)))
-                .await;
-            }
+        let span =
+          tracing::info_span!("endorser_call", call = "get_public_key", endorser.uri = %endorser);
+        let _job = telemetry::spawn(span, async move {
+          let client = match connector.connect(&endorser).await {
+            Ok(client) => client,
+            Err(error) => {
+              let _ = tx.send((endorser, Err(error))).await;
+              return;
+            },
+          };
+          let res = get_public_key_with_retry(&client, endorser_proto::GetPublicKeyReq {}).await;
+          if let Ok(resp) = res {
+            let endorser_proto::GetPublicKeyResp { pk } = resp.into_inner();
+            let _ = tx.send((endorser, Ok((client, pk.to_vec())))).await;
           } else {
-            eprintln!("Failed to resolve the endorser host name: {:?}", res);
+            warn!("Failed to retrieve the public key: {:?}", res);
             let _ = tx
-              .send((endorser, Err(CoordinatorError::CannotResolveHostName)))
+              .send((endorser, Err(CoordinatorError::UnableToRetrievePublicKey)))
               .await;
           }
         });
@@ -760,54 +1722,120 @@ impl CoordinatorState {
     drop(mpsc_tx);
 
     let mut endorser_hostnames = EndorserHostnames::new();
+    let mut rejected = EndorserHostnames::new();
     while let Some((endorser, res)) = mpsc_rx.recv().await {
       if let Ok((client, pk)) = res {
-        if PublicKey::from_bytes(&pk).is_err() {
-          eprintln!("Public key is invalid from endorser {:?}", endorser);
+        let key = match EndorserKey::from_bytes(&pk) {
+          Some(key) => key,
+          None => {
+            warn!("Public key is invalid from endorser {:?}", endorser);
+            continue;
+          },
+        };
+        if !self.is_endorser_allowlisted(&pk) {
+          if !rejected
+            .iter()
+            .any(|(rejected_pk, _uri)| *rejected_pk == pk)
+          {
+            warn!(
+              endorser = %endorser,
+              pk = %hex::encode(&pk),
+              "the endorser is not on the allowlist of endorsers"
+            );
+            rejected.push((pk, endorser));
+          }
           continue;
         }
         if let Ok(mut conn_map_wr) = self.conn_map.write() {
-          let e = conn_map_wr.get_mut(&pk);
+          let e = conn_map_wr.get_mut(&key);
           match e {
             None => {
-              endorser_hostnames.push((pk.clone(), endorser.clone()));
+              endorser_hostnames.push((pk, endorser.clone()));
               let mut endorser_clients = EndorserClients {
                 clients: Vec::new(),
                 uri: endorser,
               };
               endorser_clients.clients.push(client);
-              conn_map_wr.insert(pk, endorser_clients);
+              conn_map_wr.insert(key, endorser_clients);
             },
             Some(v) => {
               v.clients.push(client);
             },
           };
         } else {
-          eprintln!("Failed to acquire the write lock");
+          error!("failed to acquire the write lock");
         }
       }
     }
 
-    endorser_hostnames
+    (endorser_hostnames, rejected)
   }
 
   pub async fn disconnect_endorsers(&self, endorsers: &EndorserHostnames) {
     if let Ok(mut conn_map_wr) = self.conn_map.write() {
       for (pk, uri) in endorsers {
-        let res = conn_map_wr.remove_entry(pk);
+        let res = conn_map_wr.remove_entry(pk.as_slice());
         if let Some((_pk, mut endorser)) = res {
           for _idx in 0..self.num_grpc_channels {
             let client = endorser.clients.pop();
             drop(client);
           }
-          eprintln!("Removed endorser {}", uri);
+          info!(endorser = %uri, "removed the endorser");
         } else {
-          eprintln!("Failed to find the endorser to disconnect {}", uri);
+          warn!("Failed to find the endorser to disconnect {}", uri);
         }
       }
     } else {
-      eprintln!("Failed to acquire the write lock");
+      error!("failed to acquire the write lock");
+    }
+  }
+
+  /// makes the endorser connected under `old_pk` reachable under `new_pk` too, over the same
+  /// channels, for a view change that rotates its key; disconnecting the old key once the view
+  /// changed leaves the channels open for the new one
+  fn connect_rotated_endorser(&self, old_pk: &[u8], new_pk: &[u8]) {
+    let new_key = match EndorserKey::from_bytes(new_pk) {
+      Some(key) => key,
+      None => {
+        warn!("The rotated public key {} is invalid", hex::encode(new_pk));
+        return;
+      },
+    };
+    if let Ok(mut conn_map_wr) = self.conn_map.write() {
+      if conn_map_wr.contains_key(new_pk) {
+        return;
+      }
+      if let Some(endorser) = conn_map_wr.get(old_pk).cloned() {
+        conn_map_wr.insert(new_key, endorser);
+      }
+    } else {
+      error!("failed to acquire the write lock");
+    }
+  }
+
+  /// returns true if any of the given endorsers was initialized for the current view but never
+  /// activated
+  async fn has_inactive_endorsers(&self, endorsers: &EndorserHostnames) -> bool {
+    for (pk, _uri) in endorsers {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
+        None => continue,
+      };
+      match read_state_with_retry(&endorser_client, endorser_proto::ReadStateReq {}).await {
+        Ok(resp) => {
+          if resp.into_inner().mode == endorser_proto::EndorserMode::Initialized as i32 {
+            return true;
+          }
+        },
+        Err(status) => {
+          warn!(
+            "Failed to read the state of endorser {} ({:?})",
+            endorser, status
+          );
+        },
+      }
     }
+    false
   }
 
   async fn filter_endorsers(
@@ -816,17 +1844,17 @@ impl CoordinatorState {
     view_ledger_height: usize,
   ) -> Result<(), CoordinatorError> {
     let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
-    for (pk, _uri) in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
+    for pk in &self.get_endorser_keys(endorsers) {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
         Some((client, endorser)) => (client, endorser),
         None => continue,
       };
 
       let tx = mpsc_tx.clone();
-      let pk_bytes = pk.clone();
-      let _job = tokio::spawn(async move {
-        let res =
-          read_state_with_retry(&mut endorser_client, endorser_proto::ReadStateReq {}).await;
+      let pk_bytes = *pk;
+      let span = telemetry::endorser_span("read_state", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = read_state_with_retry(&endorser_client, endorser_proto::ReadStateReq {}).await;
         let _ = tx.send((endorser, pk_bytes, res)).await;
       });
     }
@@ -844,7 +1872,7 @@ impl CoordinatorState {
               if receipt_rs.get_height() == view_ledger_height {
                 to_keep = true;
               } else {
-                eprintln!(
+                warn!(
                   "expected view ledger height={}, endorser's view ledger height={}",
                   view_ledger_height,
                   receipt_rs.get_height(),
@@ -852,361 +1880,521 @@ impl CoordinatorState {
               }
             },
             Err(error) => {
-              eprintln!("Failed to parse the metablock {:?}", error);
+              warn!("Failed to parse the metablock {:?}", error);
             },
           }
         },
         Err(status) => {
-          eprintln!("Failed to get the view tail metablock {:?}", status);
+          warn!("Failed to get the view tail metablock {:?}", status);
           if CoordinatorAction::RemoveEndorser != process_error(&endorser, None, &status) {
             to_keep = true;
           }
         },
       }
       if !to_keep {
-        self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+        self
+          .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+          .await;
       }
     }
 
     Ok(())
   }
 
-  async fn endorser_initialize_state(
+  /// scans the ledger store and returns, for every ledger, the aggregated hash of its tail entry
+  /// and its height
+  async fn scan_ledger_tails(
     &self,
-    group_identity: &NimbleDigest,
-    endorsers: &EndorserHostnames,
-    ledger_tail_map: Vec<endorser_proto::LedgerTailMapEntry>,
-    view_tail_metablock: &MetaBlock,
-    block_hash: &NimbleDigest,
-    expected_height: usize,
-  ) -> Receipts {
-    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
-    let ledger_tail_map_arc = Arc::new(ledger_tail_map);
-    for (pk, _uri) in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
-        Some((client, endorser)) => (client, endorser),
-        None => continue,
+  ) -> Result<HashMap<Handle, (NimbleDigest, usize)>, CoordinatorError> {
+    let mut ledger_tails = HashMap::new();
+    let mut start_after: Option<Handle> = None;
+    loop {
+      let handles = match self
+        .ledger_store
+        .list_handles(start_after.as_ref(), RECOVERY_LIST_HANDLES_PAGE_SIZE)
+        .await
+      {
+        Ok(handles) => handles,
+        Err(e) => {
+          warn!("Failed to list the handles in the ledger store {:?}", e);
+          return Err(CoordinatorError::FailedToCallLedgerStore);
+        },
       };
 
-      let tx = mpsc_tx.clone();
-      let ledger_tail_map_arc_copy = ledger_tail_map_arc.clone();
-      let view_tail_metablock_bytes = view_tail_metablock.to_bytes().to_vec();
-      let block_hash_copy = block_hash.to_bytes();
-      let pk_bytes = pk.clone();
-      let group_identity_copy = (*group_identity).to_bytes();
-      let _job = tokio::spawn(async move {
-        let res = initialize_state_with_retry(
-          &mut endorser_client,
-          group_identity_copy,
-          ledger_tail_map_arc_copy,
-          view_tail_metablock_bytes,
-          block_hash_copy,
-          expected_height,
-        )
-        .await;
-        let _ = tx.send((endorser, pk_bytes, res)).await;
-      });
+      let tails = self
+        .map_ledger_tails(&handles, |_handle, ledger_entry, height| {
+          Ok((ledger_entry.get_block_hash(), height))
+        })
+        .await?;
+      ledger_tails.extend(handles.iter().copied().zip(tails));
+
+      if handles.len() < RECOVERY_LIST_HANDLES_PAGE_SIZE {
+        break;
+      }
+      start_after = handles.last().cloned();
     }
 
-    drop(mpsc_tx);
+    Ok(ledger_tails)
+  }
 
-    let mut receipts = Receipts::new();
-    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
-      match res {
-        Ok(resp) => {
-          let endorser_proto::InitializeStateResp { receipt } = resp.into_inner();
-          let res = Receipt::from_bytes(&receipt);
-          match res {
-            Ok(receipt_rs) => receipts.add(&receipt_rs),
-            Err(error) => eprintln!("Failed to parse a receipt ({:?})", error),
-          }
-        },
-        Err(status) => {
-          eprintln!(
-            "Failed to initialize the state of endorser {} (status={:?})",
-            endorser, status
-          );
-          if let CoordinatorAction::RemoveEndorser = process_error(&endorser, None, &status) {
-            eprintln!(
-              "initialize_state from endorser {} received unexpected error {:?}",
-              endorser, status
-            );
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+  /// reads the tails of the ledgers `handles` and maps each with `map`, in the order of `handles`.
+  /// The handles are split across up to `RECOVERY_MAX_WORKERS` tasks, since a scan of a large
+  /// store otherwise spends most of its time hashing and verifying one tail at a time
+  async fn map_ledger_tails<T, M>(
+    &self,
+    handles: &[Handle],
+    map: M,
+  ) -> Result<Vec<T>, CoordinatorError>
+  where
+    T: Send + 'static,
+    M: Fn(&Handle, LedgerEntry, usize) -> Result<T, CoordinatorError> + Clone + Send + 'static,
+  {
+    let num_workers = std::thread::available_parallelism()
+      .map_or(1, |n| n.get())
+      .min(RECOVERY_MAX_WORKERS);
+    let chunk_size = std::cmp::max(1, (handles.len() + num_workers - 1) / num_workers);
+    let jobs = handles
+      .chunks(chunk_size)
+      .map(|chunk| {
+        let ledger_store = self.ledger_store.clone();
+        let chunk = chunk.to_vec();
+        let map = map.clone();
+        tokio::spawn(async move {
+          let mut results = Vec::with_capacity(chunk.len());
+          for handle in &chunk {
+            let (ledger_entry, height) = match ledger_store.read_ledger_tail(handle).await {
+              Ok(tail) => tail,
+              Err(e) => {
+                warn!("Failed to read the tail of ledger {} ({:?})", handle, e);
+                return Err(CoordinatorError::FailedToCallLedgerStore);
+              },
+            };
+            results.push(map(handle, ledger_entry, height)?);
           }
+          Ok(results)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    let mut results = Vec::with_capacity(handles.len());
+    for job in jobs {
+      match job.await {
+        Ok(res) => results.extend(res?),
+        Err(e) => {
+          warn!("Failed to read the tails of the ledgers ({:?})", e);
+          return Err(CoordinatorError::FailedToCallLedgerStore);
         },
       }
     }
-
-    receipts
+    self.health.record_recovery_scan(handles.len());
+    Ok(results)
   }
 
-  async fn endorser_create_ledger(
+  /// checks that the ledger tail reported by an endorser is a prefix of the ledger in the store,
+  /// i.e., the endorser is either in sync with the store or lags behind it
+  async fn check_endorser_ledger_tail(
     &self,
-    endorsers: &[Vec<u8>],
-    ledger_handle: &Handle,
-    ledger_block_hash: &NimbleDigest,
-    ledger_block: Block,
-  ) -> Result<Receipts, CoordinatorError> {
-    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
-    for pk in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
-        Some((client, endorser)) => (client, endorser),
+    endorser: &str,
+    entry: &endorser_proto::LedgerTailMapEntry,
+    ledger_tails: &HashMap<Handle, (NimbleDigest, usize)>,
+  ) -> Result<(Handle, usize), CoordinatorError> {
+    let handle = NimbleDigest::from_bytes(&entry.handle)?;
+    let metablock = MetaBlock::from_bytes(&entry.metablock)?;
+    let endorser_height = metablock.get_height();
+
+    let (store_tail_hash, store_height) = match ledger_tails.get(&handle) {
+      Some(tail) => tail,
+      None => {
+        warn!(
+          "endorser {} has ledger {:?} at height {}, which does not exist in the ledger store",
+          endorser, handle, endorser_height
+        );
+        return Err(CoordinatorError::EndorserDivergedFromStore);
+      },
+    };
+
+    if endorser_height > *store_height {
+      warn!(
+        "endorser {} is ahead of the ledger store for ledger {:?}: endorser height={}, store height={}",
+        endorser, handle, endorser_height, store_height
+      );
+      return Err(CoordinatorError::EndorserDivergedFromStore);
+    }
+
+    let expected_hash = if endorser_height == *store_height {
+      *store_tail_hash
+    } else {
+      let ledger_entry = match self
+        .ledger_store
+        .read_ledger_by_index(&handle, endorser_height)
+        .await
+      {
+        Ok(ledger_entry) => ledger_entry,
+        Err(e) => {
+          warn!(
+            "Failed to read ledger {:?} at index {} ({:?})",
+            handle, endorser_height, e
+          );
+          return Err(CoordinatorError::FailedToCallLedgerStore);
+        },
+      };
+      ledger_entry.get_block_hash()
+    };
+
+    if *metablock.get_block_hash() != expected_hash {
+      warn!(
+        "endorser {} diverges from the ledger store for ledger {:?} at height {}",
+        endorser, handle, endorser_height
+      );
+      return Err(CoordinatorError::EndorserDivergedFromStore);
+    }
+
+    Ok((handle, endorser_height))
+  }
+
+  /// rebuilds the ledger tails from the ledger store, compares them against the tails reported by
+  /// the given endorsers, and repairs the endorsers that lag behind the store; an endorser whose
+  /// state cannot be explained by lag halts the repair
+  async fn repair_endorsers(&self, endorsers: &EndorserHostnames) -> Result<(), CoordinatorError> {
+    let ledger_tails = self.scan_ledger_tails().await?;
+
+    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+    for pk in &self.get_endorser_keys(endorsers) {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
         None => continue,
       };
 
       let tx = mpsc_tx.clone();
-      let handle = *ledger_handle;
-      let block_hash = *ledger_block_hash;
-      let block = ledger_block.clone();
-      let pk_bytes = pk.clone();
-      let _job = tokio::spawn(async move {
-        let res = new_ledger_with_retry(
-          &mut endorser_client,
-          endorser_proto::NewLedgerReq {
-            handle: handle.to_bytes(),
-            block_hash: block_hash.to_bytes(),
-            block: block.to_bytes(),
-          },
-        )
-        .await;
-        let _ = tx.send((endorser, pk_bytes, res)).await;
+      let pk = *pk;
+      let span = telemetry::endorser_span("read_state", &pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = read_state_with_retry(&endorser_client, endorser_proto::ReadStateReq {}).await;
+        let _ = tx.send((endorser, pk, res)).await;
       });
     }
 
     drop(mpsc_tx);
 
-    let mut receipts = Receipts::new();
+    // handle -> (endorser uri -> height) for the ledgers each endorser knows about
+    let mut endorser_heights: HashMap<Handle, HashMap<String, usize>> = HashMap::new();
+    let mut endorsers = Vec::new();
     while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
       match res {
         Ok(resp) => {
-          let endorser_proto::NewLedgerResp { receipt } = resp.into_inner();
-          let res = Receipt::from_bytes(&receipt);
-          match res {
-            Ok(receipt_rs) => {
-              receipts.add(&receipt_rs);
-              if let Ok(vs) = self.verifier_state.read() {
-                if receipts.check_quorum(&vs).is_ok() {
-                  return Ok(receipts);
-                }
-              }
-            },
-            Err(error) => eprintln!("Failed to parse a receipt ({:?})", error),
+          let endorser_proto::ReadStateResp {
+            ledger_tail_map, ..
+          } = resp.into_inner();
+          for entry in &ledger_tail_map {
+            let (handle, height) = self
+              .check_endorser_ledger_tail(&endorser, entry, &ledger_tails)
+              .await?;
+            endorser_heights
+              .entry(handle)
+              .or_default()
+              .insert(endorser.clone(), height);
           }
+          endorsers.push(pk_bytes);
         },
         Err(status) => {
-          eprintln!(
-            "Failed to create a ledger {:?} in endorser {} (status={:?})",
-            ledger_handle, endorser, status
+          warn!(
+            "Failed to read the state of endorser {} ({:?})",
+            endorser, status
           );
-          if process_error(&endorser, Some(ledger_handle), &status)
-            == CoordinatorAction::RemoveEndorser
-          {
-            eprintln!(
-              "create_ledger from endorser {} received unexpected error {:?}",
-              endorser, status
-            );
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+          if process_error(&endorser, None, &status) == CoordinatorAction::RemoveEndorser {
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
           }
         },
       }
     }
 
-    Ok(receipts)
-  }
+    let empty_height_map = HashMap::new();
+    for (handle, (_tail_hash, height)) in &ledger_tails {
+      let endorser_height_map = endorser_heights.get(handle).unwrap_or(&empty_height_map);
+      if endorser_height_map.len() == endorsers.len()
+        && endorser_height_map.values().all(|h| *h == *height)
+      {
+        continue;
+      }
+      self
+        .endorser_update_ledger(&endorsers, handle, *height, endorser_height_map)
+        .await;
+    }
 
-  pub async fn endorser_append_ledger(
-    &self,
-    endorsers: &[Vec<u8>],
-    ledger_handle: &Handle,
-    block_hash: &NimbleDigest,
-    expected_height: usize,
-    block: Block,
-    nonces: Nonces,
-  ) -> Result<Receipts, CoordinatorError> {
-    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+    Ok(())
+  }
 
-    for pk in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
-        Some((client, endorser)) => (client, endorser),
-        None => continue,
+  /// reconciles the tail of every ledger that the ledger store holds without a quorum of
+  /// receipts, which is what an append leaves behind if the coordinator fails between persisting
+  /// the block and attaching the receipts; a tail that cannot be reconciled now is reconciled by
+  /// the next append to its ledger
+  async fn reconcile_ledgers(&self) -> Result<(), CoordinatorError> {
+    let mut start_after: Option<Handle> = None;
+    loop {
+      let handles = match self
+        .ledger_store
+        .list_handles(start_after.as_ref(), RECOVERY_LIST_HANDLES_PAGE_SIZE)
+        .await
+      {
+        Ok(handles) => handles,
+        Err(e) => {
+          warn!("Failed to list the handles in the ledger store {:?}", e);
+          return Err(CoordinatorError::FailedToCallLedgerStore);
+        },
       };
 
-      let tx = mpsc_tx.clone();
-      let handle = *ledger_handle;
-      let block_hash_copy = *block_hash;
-      let block_copy = block.clone();
-      let nonces_copy = nonces.clone();
-      let pk_bytes = pk.clone();
-      let ledger_store = self.ledger_store.clone();
-      let _job = tokio::spawn(async move {
-        loop {
-          let res = append_with_retry(
-            &mut endorser_client,
-            endorser_proto::AppendReq {
-              handle: handle.to_bytes(),
-              block_hash: block_hash_copy.to_bytes(),
-              expected_height: expected_height as u64,
-              block: block_copy.to_bytes(),
-              nonces: nonces_copy.to_bytes(),
-            },
-          )
-          .await;
-          match res {
-            Ok(resp) => {
-              let endorser_proto::AppendResp { receipt } = resp.into_inner();
-              let _ = tx.send((endorser, pk_bytes, Ok(receipt))).await;
-              break;
-            },
-            Err(status) => match process_error(&endorser, Some(&handle), &status) {
-              CoordinatorAction::UpdateEndorser => {
-                let height_to_start = {
-                  if status.code() == Code::NotFound {
-                    0
-                  } else {
-                    let bytes = status.details();
-                    let ledger_height = u64::from_le_bytes(bytes[0..].try_into().unwrap()) as usize;
-                    ledger_height.checked_add(1).unwrap()
-                  }
-                };
-                let height_to_end = expected_height - 1;
-                let res = update_endorser(
-                  ledger_store.clone(),
-                  &mut endorser_client,
-                  handle,
-                  height_to_start,
-                  height_to_end,
-                )
-                .await;
-                match res {
-                  Ok(_resp) => {
-                    continue;
-                  },
-                  Err(status) => match process_error(&endorser, Some(&handle), &status) {
-                    CoordinatorAction::RemoveEndorser => {
-                      let _ = tx
-                        .send((endorser, pk_bytes, Err(CoordinatorError::UnexpectedError)))
-                        .await;
-                      break;
-                    },
-                    CoordinatorAction::IncrementReceipt => {
-                      continue;
-                    },
-                    _ => {
-                      let _ = tx
-                        .send((
-                          endorser,
-                          pk_bytes,
-                          Err(CoordinatorError::FailedToAppendLedger),
-                        ))
-                        .await;
-                      break;
-                    },
-                  },
-                }
-              },
-              CoordinatorAction::RemoveEndorser => {
-                let _ = tx
-                  .send((endorser, pk_bytes, Err(CoordinatorError::UnexpectedError)))
-                  .await;
-                break;
-              },
-              CoordinatorAction::IncrementReceipt => {
-                let _ = tx
-                  .send((
-                    endorser,
-                    pk_bytes,
-                    Err(CoordinatorError::LedgerAlreadyExists),
-                  ))
-                  .await;
-                break;
-              },
-              _ => {
-                let _ = tx
-                  .send((
-                    endorser,
-                    pk_bytes,
-                    Err(CoordinatorError::FailedToAppendLedger),
-                  ))
-                  .await;
-                break;
-              },
-            },
+      // the tails with a quorum of receipts are checked in parallel, and the rest are reconciled
+      // one at a time
+      let verifier_state = self.verifier_state.clone();
+      let tails = self
+        .map_ledger_tails(&handles, move |_handle, ledger_entry, height| {
+          if check_quorum(&verifier_state, ledger_entry.get_receipts()).is_ok() {
+            Ok(None)
+          } else {
+            Ok(Some((ledger_entry, height)))
           }
+        })
+        .await?;
+      for (handle, tail) in handles.iter().zip(tails) {
+        let (ledger_entry, height) = match tail {
+          Some(tail) => tail,
+          None => continue,
+        };
+        if let Err(e) = self
+          .reconcile_ledger_tail(handle, height, ledger_entry, Deadline::none())
+          .await
+        {
+          warn!(
+            "Failed to reconcile ledger {} at height {} ({:?})",
+            handle, height, e
+          );
         }
-      });
+      }
+
+      if handles.len() < RECOVERY_LIST_HANDLES_PAGE_SIZE {
+        break;
+      }
+      start_after = handles.last().cloned();
     }
 
-    drop(mpsc_tx);
+    Ok(())
+  }
 
-    let mut receipts = Receipts::new();
-    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
-      match res {
-        Ok(receipt) => match Receipt::from_bytes(&receipt) {
-          Ok(receipt_rs) => {
-            receipts.add(&receipt_rs);
-            if let Ok(vs) = self.verifier_state.read() {
-              if receipts.check_quorum(&vs).is_ok() {
-                return Ok(receipts);
-              }
-            }
-          },
-          Err(error) => {
-            eprintln!("Failed to parse a receipt (err={:?}", error);
-          },
-        },
-        Err(error) => {
-          if error == CoordinatorError::UnexpectedError {
-            eprintln!(
-              "append_ledger from endorser {} received unexpected error {:?}",
-              endorser, error
-            );
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
-          }
+  /// completes the tail of a ledger that the ledger store holds without a quorum of receipts.
+  /// Blocks are persisted before they are endorsed, so the store is the source of truth: the
+  /// tail is replayed to the endorsers, which endorse it again if they have it already and are
+  /// brought up to date if they lag behind, and the receipts are merged into the stored ones
+  async fn reconcile_ledger_tail(
+    &self,
+    handle: &Handle,
+    height: usize,
+    ledger_entry: LedgerEntry,
+    deadline: Deadline,
+  ) -> Result<Receipts, CoordinatorError> {
+    let block_hash = ledger_entry.get_block_hash();
+    let endorsers = self.get_endorser_pks();
+    let mut receipts = if height == 0 {
+      self
+        .endorser_create_ledger(
+          &endorsers,
+          handle,
+          &block_hash,
+          ledger_entry.get_block().clone(),
+          deadline,
+        )
+        .await?
+    } else {
+      let prev_tail = match self
+        .ledger_store
+        .read_ledger_by_index(handle, height - 1)
+        .await
+      {
+        Ok(prev_entry) => prev_entry.get_block_hash(),
+        Err(e) => {
+          warn!(
+            "Failed to read ledger {} at index {} ({:?})",
+            handle,
+            height - 1,
+            e
+          );
+          return Err(CoordinatorError::FailedToReadLedger);
         },
-      }
+      };
+      self
+        .endorser_append_ledger(
+          &endorsers,
+          handle,
+          height,
+          Some(&prev_tail),
+          ledger_entry.get_block(),
+          ledger_entry.get_nonces(),
+          deadline,
+        )
+        .await?
+    };
+    receipts.merge_receipts(ledger_entry.get_receipts());
+
+    // the receipts are kept even short of a quorum, so that the next attempt builds on them
+    let res = self
+      .ledger_store
+      .attach_ledger_receipts(handle, height, &receipts)
+      .await;
+    if let Err(e) = res {
+      warn!(
+        "Failed to attach ledger receipt to the ledger store ({:?})",
+        e
+      );
+      return Err(CoordinatorError::FailedToAttachReceipt);
     }
+    self.check_receipts_quorum(&receipts)?;
+    self.commit_intent(handle, height).await;
+    self.watchers.publish(handle, height);
 
     Ok(receipts)
   }
 
-  async fn endorser_update_ledger(
+  /// resolves the intents of the appends that a failed coordinator left behind: an append that
+  /// never reached the ledger store is discarded, and one that did is completed, so that every
+  /// persisted block ends up with a quorum of receipts; an intent that cannot be resolved now is
+  /// kept for the next recovery
+  async fn resolve_intents(&self) -> Result<(), CoordinatorError> {
+    let intents = match self.ledger_store.list_intents().await {
+      Ok(intents) => intents,
+      Err(e) => {
+        warn!("Failed to list the intents in the ledger store {:?}", e);
+        return Err(CoordinatorError::FailedToCallLedgerStore);
+      },
+    };
+
+    if let Ok(mut pending_intents) = self.pending_intents.lock() {
+      pending_intents.extend(intents.iter().map(|intent| (intent.handle, intent.height)));
+    }
+    for intent in intents {
+      match self.resolve_intent(&intent).await {
+        Ok(true) => info!(
+          "Completed the append to ledger {} at height {} (request {})",
+          intent.handle, intent.height, intent.request_id
+        ),
+        Ok(false) => info!(
+          "Discarded the append to ledger {} at height {} (request {})",
+          intent.handle, intent.height, intent.request_id
+        ),
+        Err(e) => warn!(
+          "Failed to resolve the append to ledger {} at height {} ({:?})",
+          intent.handle, intent.height, e
+        ),
+      }
+    }
+
+    Ok(())
+  }
+
+  /// resolves an intent, and returns whether its append was completed rather than discarded
+  async fn resolve_intent(&self, intent: &IntentRecord) -> Result<bool, CoordinatorError> {
+    let handle = &intent.handle;
+    let (tail, tail_height) = match self.ledger_store.read_ledger_tail(handle).await {
+      Ok(tail) => tail,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        self.commit_intent(handle, intent.height).await;
+        return Ok(false);
+      },
+      Err(e) => {
+        warn!("Failed to read the tail of ledger {} ({:?})", handle, e);
+        return Err(CoordinatorError::FailedToCallLedgerStore);
+      },
+    };
+
+    // the coordinator failed before the block was persisted, and so before any endorser saw it;
+    // the client was not answered, and the height is free for its retry
+    let entry = if intent.height > tail_height {
+      None
+    } else if intent.height == tail_height {
+      Some(tail.clone())
+    } else {
+      Some(
+        self
+          .read_ledger_by_index_internal(handle, intent.height)
+          .await?,
+      )
+    };
+    // the intent holds the hash of the block alone, which the nonces attached since do not change
+    let entry = match entry {
+      Some(entry) if stored_block_hash(&entry) == intent.block_hash => entry,
+      _ => {
+        self.commit_intent(handle, intent.height).await;
+        return Ok(false);
+      },
+    };
+
+    if self.check_receipts_quorum(entry.get_receipts()).is_ok() {
+      self.commit_intent(handle, intent.height).await;
+      return Ok(true);
+    }
+
+    // a block below the tail was endorsed before the next one, unless the endorsers lag behind
+    // the store, in which case replaying the tail brings them up to date
+    if intent.height < tail_height {
+      let endorsed_height = self
+        .read_ledger_tail_internal(handle, &self.new_nonce())
+        .await
+        .ok()
+        .and_then(|e| e.get_receipts().get_metablock().ok())
+        .map(|metablock| metablock.get_height());
+      if matches!(endorsed_height, Some(height) if height >= intent.height) {
+        self.commit_intent(handle, intent.height).await;
+        return Ok(true);
+      }
+    }
+
+    // the tail is replayed to the endorsers and its receipts stored, which commits the intent at
+    // the tail; one below it is committed once the endorsers caught up
+    self
+      .reconcile_ledger_tail(handle, tail_height, tail, Deadline::none())
+      .await?;
+    self.commit_intent(handle, intent.height).await;
+    Ok(true)
+  }
+
+  async fn endorser_initialize_state(
     &self,
-    endorsers: &[Vec<u8>],
-    ledger_handle: &Handle,
-    max_height: usize,
-    endorser_height_map: &HashMap<String, usize>,
-  ) {
+    group_identity: &NimbleDigest,
+    endorsers: &EndorserHostnames,
+    ledger_tail_map: Vec<endorser_proto::LedgerTailMapEntry>,
+    view_tail_metablock: &MetaBlock,
+    block_hash: &NimbleDigest,
+    expected_height: usize,
+  ) -> Receipts {
     let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
-
-    for pk in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
+    let ledger_tail_map_arc = Arc::new(ledger_tail_map);
+    // the requests to the endorsers share the bytes of the view tail
+    let group_identity = Bytes::from(group_identity.to_bytes());
+    let view_tail_metablock = Bytes::from(view_tail_metablock.to_bytes());
+    let block_hash = Bytes::from(block_hash.to_bytes());
+    for pk in &self.get_endorser_keys(endorsers) {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
         Some((client, endorser)) => (client, endorser),
         None => continue,
       };
 
-      let height_to_start = {
-        if !endorser_height_map.contains_key(&endorser) {
-          0
-        } else {
-          endorser_height_map[&endorser].checked_add(1).unwrap()
-        }
-      };
-
-      if height_to_start > max_height {
-        continue;
-      }
-
-      let ledger_store = self.ledger_store.clone();
-      let handle = *ledger_handle;
-      let pk_bytes = pk.clone();
       let tx = mpsc_tx.clone();
-      let _job = tokio::spawn(async move {
-        let res = update_endorser(
-          ledger_store,
-          &mut endorser_client,
-          handle,
-          height_to_start,
-          max_height,
+      let ledger_tail_map_arc_copy = ledger_tail_map_arc.clone();
+      let view_tail_metablock_bytes = view_tail_metablock.clone();
+      let block_hash_copy = block_hash.clone();
+      let pk_bytes = *pk;
+      let group_identity_copy = group_identity.clone();
+      let span = telemetry::endorser_span("initialize_state", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        // the endorser joins the view under the key that the view lists it with
+        let res = initialize_state_with_retry(
+          &endorser_client,
+          group_identity_copy,
+          ledger_tail_map_arc_copy,
+          view_tail_metablock_bytes,
+          block_hash_copy,
+          expected_height,
+          Bytes::copy_from_slice(&pk_bytes),
         )
         .await;
         let _ = tx.send((endorser, pk_bytes, res)).await;
@@ -1215,156 +2403,222 @@ impl CoordinatorState {
 
     drop(mpsc_tx);
 
+    let mut receipts = Receipts::new();
     while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
       match res {
-        Ok(()) => {},
+        Ok(resp) => {
+          let endorser_proto::InitializeStateResp { receipt } = resp.into_inner();
+          let res = Receipt::from_bytes(&receipt);
+          match res {
+            Ok(receipt_rs) => receipts.add(&receipt_rs),
+            Err(error) => warn!("Failed to parse a receipt ({:?})", error),
+          }
+        },
         Err(status) => {
-          if process_error(&endorser, Some(ledger_handle), &status)
-            == CoordinatorAction::RemoveEndorser
-          {
-            eprintln!(
-              "update_endorser {} received unexpected error {:?}",
-              endorser, status,
+          warn!(
+            "Failed to initialize the state of endorser {} (status={:?})",
+            endorser, status
+          );
+          if let CoordinatorAction::RemoveEndorser = process_error(&endorser, None, &status) {
+            error!(
+              endorser = %endorser,
+              status = ?status,
+              "initialize_state received an unexpected error"
             );
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
           }
         },
       }
     }
+
+    receipts
   }
 
-  async fn endorser_read_ledger_tail(
+  /// checks that an endorser's receipt is its valid signature on a tail of ledger `handle` with
+  /// `block_hash` at `height`, recording an invalid signature of the endorser otherwise
+  fn check_ledger_receipt(
+    &self,
+    pk: &EndorserKey,
+    receipt: &Receipt,
+    handle: &Handle,
+    block_hash: &NimbleDigest,
+    height: usize,
+    nonce: Option<&[u8]>,
+  ) -> Result<(), VerificationError> {
+    let res = if receipt.get_id_sig().get_id().as_slice() != pk.as_bytes() {
+      Err(VerificationError::InvalidPublicKey)
+    } else if receipt.get_block_hash() != block_hash {
+      Err(VerificationError::InvalidBlockHash)
+    } else if receipt.get_height() != height {
+      Err(VerificationError::InvalidHeight)
+    } else if let Ok(vs) = self.verifier_state.read() {
+      receipt.verify_ledger_tail(&vs, handle, nonce)
+    } else {
+      // not the endorser's fault
+      return Err(VerificationError::InvalidReceipt);
+    };
+    if res.is_err() {
+      self.record_invalid_signature(pk);
+    }
+    res
+  }
+
+  fn record_invalid_signature(&self, pk: &EndorserKey) {
+    self.metrics.record_receipt_failure();
+    if let Ok(mut invalid_signatures) = self.invalid_signatures.lock() {
+      *invalid_signatures.entry(*pk).or_insert(0) += 1;
+    }
+  }
+
+  fn get_invalid_signatures(&self, pk: &[u8]) -> usize {
+    match self.invalid_signatures.lock() {
+      Ok(invalid_signatures) => invalid_signatures.get(pk).cloned().unwrap_or(0),
+      Err(_) => 0,
+    }
+  }
+
+  async fn endorser_create_ledger(
     &self,
-    endorsers: &[Vec<u8>],
+    endorsers: &[EndorserKey],
     ledger_handle: &Handle,
-    client_nonce: &Nonce,
-  ) -> Result<LedgerEntry, CoordinatorError> {
+    ledger_block_hash: &NimbleDigest,
+    ledger_block: Block,
+    deadline: Deadline,
+  ) -> Result<Receipts, CoordinatorError> {
     let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
-
+    // the requests to the endorsers share the bytes of the block
+    let request = endorser_proto::NewLedgerReq {
+      handle: ledger_handle.to_bytes().into(),
+      block_hash: ledger_block_hash.to_bytes().into(),
+      block: ledger_block.to_bytes().into(),
+    };
     for pk in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
         Some((client, endorser)) => (client, endorser),
         None => continue,
       };
 
       let tx = mpsc_tx.clone();
-      let handle = *ledger_handle;
-      let nonce = *client_nonce;
-      let pk_bytes = pk.clone();
-      let _job = tokio::spawn(async move {
-        let res = read_latest_with_retry(
-          &mut endorser_client,
-          endorser_proto::ReadLatestReq {
-            handle: handle.to_bytes(),
-            nonce: nonce.to_bytes(),
-          },
-        )
-        .await;
-        match res {
-          Ok(resp) => {
-            let endorser_proto::ReadLatestResp {
-              receipt,
-              block,
-              nonces,
-            } = resp.into_inner();
-            let _ = tx
-              .send((endorser, pk_bytes, Ok((receipt, block, nonces))))
-              .await;
-          },
-          Err(status) => match process_error(&endorser, Some(&handle), &status) {
-            CoordinatorAction::RemoveEndorser => {
-              let _ = tx
-                .send((endorser, pk_bytes, Err(CoordinatorError::UnexpectedError)))
-                .await;
-            },
-            _ => {
-              let _ = tx
-                .send((
-                  endorser,
-                  pk_bytes,
-                  Err(CoordinatorError::FailedToReadLedger),
-                ))
-                .await;
-            },
-          },
-        }
+      let request = request.clone();
+      let pk_bytes = *pk;
+      let span = telemetry::endorser_span("new_ledger", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = new_ledger_with_retry(&endorser_client, request, deadline).await;
+        let _ = tx.send((endorser, pk_bytes, res)).await;
       });
     }
 
     drop(mpsc_tx);
 
     let mut receipts = Receipts::new();
-    let mut endorser_height_map: HashMap<String, usize> = HashMap::new();
-    let mut max_height = 0;
-
-    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+    while let Some(Some((endorser, pk_bytes, res))) = deadline.run(mpsc_rx.recv()).await {
       match res {
-        Ok((receipt, block, nonces)) => match Receipt::from_bytes(&receipt) {
-          Ok(receipt_rs) => {
-            let height = receipt_rs.get_height();
-            endorser_height_map.insert(endorser, height);
-            if max_height < height {
-              max_height = height;
-            }
-            receipts.add(&receipt_rs);
-            if let Ok(vs) = self.verifier_state.read() {
-              if let Ok(_h) = receipts.check_quorum(&vs) {
-                if let Ok(block_rs) = Block::from_bytes(&block) {
-                  if let Ok(nonces_rs) = Nonces::from_bytes(&nonces) {
-                    return Ok(LedgerEntry::new(block_rs, receipts, Some(nonces_rs)));
-                  }
+        Ok(resp) => {
+          let endorser_proto::NewLedgerResp { receipt } = resp.into_inner();
+          let res = Receipt::from_bytes(&receipt);
+          match res {
+            Ok(receipt_rs) => {
+              let res = self.check_ledger_receipt(
+                &pk_bytes,
+                &receipt_rs,
+                ledger_handle,
+                ledger_block_hash,
+                0,
+                None,
+              );
+              if let Err(error) = res {
+                warn!(
+                  handle = %ledger_handle,
+                  endorser = %endorser,
+                  ?error,
+                  "received an invalid receipt"
+                );
+                continue;
+              }
+              receipts.add(&receipt_rs);
+              if let Ok(vs) = self.verifier_state.read() {
+                if receipts.check_quorum(&vs).is_ok() {
+                  return Ok(receipts);
                 }
               }
-            }
-          },
-          Err(error) => {
-            eprintln!("Failed to parse a receipt (err={:?}", error);
-          },
+            },
+            Err(error) => {
+              warn!("Failed to parse a receipt ({:?})", error);
+              self.record_invalid_signature(&pk_bytes);
+            },
+          }
         },
-        Err(error) => {
-          if error == CoordinatorError::UnexpectedError {
-            eprintln!(
-              "read_ledger from endorser {} received unexpected error {:?}",
-              endorser, error
+        Err(status) => {
+          warn!(
+            "Failed to create a ledger {} in endorser {} (status={:?})",
+            ledger_handle, endorser, status
+          );
+          if process_error(&endorser, Some(ledger_handle), &status)
+            == CoordinatorAction::RemoveEndorser
+          {
+            error!(
+              endorser = %endorser,
+              status = ?status,
+              "create_ledger received an unexpected error"
             );
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
           }
         },
       }
     }
 
-    // Since we didn't reach a quorum, let's have endorsers catch up
-    self
-      .endorser_update_ledger(endorsers, ledger_handle, max_height, &endorser_height_map)
-      .await;
-
-    Err(CoordinatorError::FailedToObtainQuorum)
+    Ok(receipts)
   }
 
-  async fn endorser_finalize_state(
+  pub async fn endorser_append_ledger(
     &self,
-    endorsers: &EndorserHostnames,
-    block_hash: &NimbleDigest,
+    endorsers: &[EndorserKey],
+    ledger_handle: &Handle,
     expected_height: usize,
-  ) -> (Receipts, Vec<endorser_proto::LedgerTailMap>) {
+    expected_tail: Option<&NimbleDigest>,
+    block: &Block,
+    nonces: &Nonces,
+    deadline: Deadline,
+  ) -> Result<Receipts, CoordinatorError> {
+    let block_hash =
+      &compute_aggregated_block_hash(&block.hash().to_bytes(), &nonces.hash().to_bytes());
     let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+    // the requests to the endorsers share the bytes of the block
+    let request = endorser_proto::AppendReq {
+      handle: ledger_handle.to_bytes().into(),
+      block_hash: block_hash.to_bytes().into(),
+      expected_height: expected_height as u64,
+      block: block.to_bytes().into(),
+      nonces: nonces.to_bytes().into(),
+      expected_tail: expected_tail
+        .map(|tail| tail.to_bytes().into())
+        .unwrap_or_default(),
+    };
 
-    for (pk, _uri) in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
+    for pk in endorsers {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
         Some((client, endorser)) => (client, endorser),
         None => continue,
       };
 
       let tx = mpsc_tx.clone();
-      let block = *block_hash;
-      let pk_bytes = pk.clone();
-      let _job = tokio::spawn(async move {
-        let res = finalize_state_with_retry(
-          &mut endorser_client,
-          endorser_proto::FinalizeStateReq {
-            block_hash: block.to_bytes(),
-            expected_height: expected_height as u64,
-          },
+      let handle = *ledger_handle;
+      let request = request.clone();
+      let pk_bytes = *pk;
+      let ledger_store = self.ledger_store.clone();
+      let span = telemetry::endorser_span("append", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = append_to_endorser(
+          ledger_store,
+          &endorser_client,
+          &endorser,
+          handle,
+          request,
+          deadline,
         )
         .await;
         let _ = tx.send((endorser, pk_bytes, res)).await;
@@ -1374,463 +2628,2665 @@ impl CoordinatorState {
     drop(mpsc_tx);
 
     let mut receipts = Receipts::new();
-    let mut ledger_tail_maps = Vec::new();
-    let mut state_hashes = HashSet::new();
-
-    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+    while let Some(Some((endorser, pk_bytes, res))) = deadline.run(mpsc_rx.recv()).await {
       match res {
-        Ok(resp) => {
-          let endorser_proto::FinalizeStateResp {
-            receipt,
-            ledger_tail_map,
-          } = resp.into_inner();
-          let res = Receipt::from_bytes(&receipt);
-          let receipt_rs = match res {
-            Ok(receipt_rs) => {
-              receipts.add(&receipt_rs);
-              receipt_rs
-            },
-            Err(error) => {
-              eprintln!("Failed to parse a receipt ({:?})", error);
+        Ok(receipt) => match Receipt::from_bytes(&receipt) {
+          Ok(receipt_rs) => {
+            let res = self.check_ledger_receipt(
+              &pk_bytes,
+              &receipt_rs,
+              ledger_handle,
+              block_hash,
+              expected_height,
+              None,
+            );
+            if let Err(error) = res {
+              warn!(
+                handle = %ledger_handle,
+                endorser = %endorser,
+                ?error,
+                "received an invalid receipt"
+              );
               continue;
-            },
-          };
-          if !state_hashes.contains(receipt_rs.get_view()) {
-            ledger_tail_maps.push(endorser_proto::LedgerTailMap {
-              entries: ledger_tail_map,
-            });
-            state_hashes.insert(*receipt_rs.get_view());
-          }
+            }
+            receipts.add(&receipt_rs);
+            if let Ok(vs) = self.verifier_state.read() {
+              if receipts.check_quorum(&vs).is_ok() {
+                return Ok(receipts);
+              }
+            }
+          },
+          Err(error) => {
+            warn!(?error, "failed to parse a receipt");
+            self.record_invalid_signature(&pk_bytes);
+          },
         },
-        Err(status) => {
-          eprintln!(
-            "Failed to append view ledger to endorser {} (status={:?})",
-            endorser, status
-          );
-          if let CoordinatorAction::RemoveEndorser = process_error(&endorser, None, &status) {
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+        Err(error) => {
+          if error == CoordinatorError::UnexpectedError {
+            error!(
+              endorser = %endorser,
+              error = ?error,
+              "append_ledger received an unexpected error"
+            );
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
           }
         },
       }
     }
 
-    (receipts, ledger_tail_maps)
+    Ok(receipts)
   }
 
-  async fn endorser_verify_view_change(
+  /// sends a batch of appends to each endorser in a single call; the items an endorser does not
+  /// append in the batch, e.g., because it lags behind on their ledgers, are retried one by one
+  async fn endorser_append_batch(
     &self,
-    endorsers: &EndorserHostnames,
-    old_config: Block,
-    new_config: Block,
-    ledger_tail_maps: Vec<endorser_proto::LedgerTailMap>,
-    ledger_chunks: Vec<endorser_proto::LedgerChunkEntry>,
-    receipts: &Receipts,
-  ) -> usize {
+    endorsers: &[EndorserKey],
+    appends: &[BatchedAppend],
+    deadline: Deadline,
+  ) -> Vec<Receipts> {
+    // a batch whose items all failed before the endorsers is not sent
+    if appends.is_empty() {
+      return Vec::new();
+    }
     let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
-    let ledger_tail_maps_arc = Arc::new(ledger_tail_maps);
 
-    for (pk, _uri) in endorsers {
-      let (mut endorser_client, endorser) = match self.get_endorser_client(pk) {
+    for pk in endorsers {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
         Some((client, endorser)) => (client, endorser),
         None => continue,
       };
 
       let tx = mpsc_tx.clone();
-      let pk_bytes = pk.clone();
-      let old_config_copy = old_config.clone();
-      let new_config_copy = new_config.clone();
-      let ledger_tail_maps_arc_copy = ledger_tail_maps_arc.clone();
-      let ledger_chunks_copy = ledger_chunks.clone();
-      let receipts_copy = receipts.to_bytes();
-      let _job = tokio::spawn(async move {
-        let res = activate_with_retry(
-          &mut endorser_client,
-          old_config_copy.to_bytes(),
-          new_config_copy.to_bytes(),
-          ledger_tail_maps_arc_copy,
-          ledger_chunks_copy,
-          receipts_copy,
+      let requests = appends
+        .iter()
+        .map(|append| (append.handle, append.request.clone()))
+        .collect::<Vec<_>>();
+      let pk_bytes = *pk;
+      let ledger_store = self.ledger_store.clone();
+      let span = telemetry::endorser_span("append_batch", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = append_batch_with_retry(
+          &endorser_client,
+          endorser_proto::AppendBatchReq {
+            items: requests
+              .iter()
+              .map(|(_, request)| request.clone())
+              .collect(),
+          },
+          deadline,
         )
         .await;
-        let _ = tx.send((endorser, pk_bytes, res)).await;
+        let results = match res {
+          Ok(resp) => {
+            let endorser_proto::AppendBatchResp { results } = resp.into_inner();
+            if results.len() == requests.len() {
+              results
+            } else {
+              warn!(
+                "endorser {} returned {} results for a batch of {} appends",
+                endorser,
+                results.len(),
+                requests.len()
+              );
+              Vec::new()
+            }
+          },
+          // an endorser without batched appends takes the appends one by one
+          Err(status) if status.code() == Code::Unimplemented => Vec::new(),
+          Err(status) => {
+            if process_error(&endorser, None, &status) == CoordinatorAction::RemoveEndorser {
+              for index in 0..requests.len() {
+                let _ = tx
+                  .send((
+                    index,
+                    endorser.clone(),
+                    pk_bytes,
+                    Err(CoordinatorError::UnexpectedError),
+                  ))
+                  .await;
+              }
+              return;
+            }
+            Vec::new()
+          },
+        };
+        for (index, (handle, request)) in requests.into_iter().enumerate() {
+          let res = match results.get(index) {
+            Some(result) if result.code == Code::Ok as i32 => Ok(result.receipt.clone()),
+            _ => {
+              append_to_endorser(
+                ledger_store.clone(),
+                &endorser_client,
+                &endorser,
+                handle,
+                request,
+                deadline,
+              )
+              .await
+            },
+          };
+          let _ = tx.send((index, endorser.clone(), pk_bytes, res)).await;
+        }
       });
     }
 
     drop(mpsc_tx);
 
-    let mut num_verified_endorers = 0;
-
-    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+    let mut receipts = appends.iter().map(|_| Receipts::new()).collect::<Vec<_>>();
+    let mut has_quorum = vec![false; appends.len()];
+    let mut num_with_quorum = 0;
+    let mut disconnected = HashSet::new();
+    while let Some(Some((index, endorser, pk_bytes, res))) = deadline.run(mpsc_rx.recv()).await {
+      let append = &appends[index];
       match res {
-        Ok(_resp) => {
-          num_verified_endorers += 1;
+        Ok(receipt) => match Receipt::from_bytes(&receipt) {
+          Ok(receipt_rs) => {
+            let res = self.check_ledger_receipt(
+              &pk_bytes,
+              &receipt_rs,
+              &append.handle,
+              &append.block_hash,
+              append.height,
+              None,
+            );
+            if let Err(error) = res {
+              warn!(
+                handle = %append.handle,
+                endorser = %endorser,
+                ?error,
+                "received an invalid receipt"
+              );
+              continue;
+            }
+            receipts[index].add(&receipt_rs);
+            if !has_quorum[index] {
+              if let Ok(vs) = self.verifier_state.read() {
+                if receipts[index].check_quorum(&vs).is_ok() {
+                  has_quorum[index] = true;
+                  num_with_quorum += 1;
+                }
+              }
+              if num_with_quorum == appends.len() {
+                break;
+              }
+            }
+          },
+          Err(error) => {
+            warn!(?error, "failed to parse a receipt");
+            self.record_invalid_signature(&pk_bytes);
+          },
         },
-        Err(status) => {
-          eprintln!(
-            "Failed to prove view change to endorser {} (status={:?})",
-            endorser, status
-          );
-          if let CoordinatorAction::RemoveEndorser = process_error(&endorser, None, &status) {
-            self.disconnect_endorsers(&vec![(pk_bytes, endorser)]).await;
+        Err(error) => {
+          if error == CoordinatorError::UnexpectedError && disconnected.insert(pk_bytes) {
+            error!(
+              endorser = %endorser,
+              error = ?error,
+              "append_batch received an unexpected error"
+            );
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
           }
         },
       }
     }
 
-    num_verified_endorers
+    receipts
   }
 
-  pub async fn replace_endorsers(&self, hostnames: &[String]) -> Result<(), CoordinatorError> {
-    let existing_endorsers = self.get_endorser_hostnames();
+  async fn endorser_update_ledger(
+    &self,
+    endorsers: &[EndorserKey],
+    ledger_handle: &Handle,
+    max_height: usize,
+    endorser_height_map: &HashMap<String, usize>,
+  ) {
+    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+
+    for pk in endorsers {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
+        None => continue,
+      };
+
+      let height_to_start = {
+        if !endorser_height_map.contains_key(&endorser) {
+          0
+        } else {
+          match endorser_height_map[&endorser].checked_add(1) {
+            Some(height) => height,
+            None => continue,
+          }
+        }
+      };
+
+      if height_to_start > max_height {
+        continue;
+      }
+
+      let ledger_store = self.ledger_store.clone();
+      let handle = *ledger_handle;
+      let pk_bytes = *pk;
+      let tx = mpsc_tx.clone();
+      let span = telemetry::endorser_span("update_ledger", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = update_endorser(
+          ledger_store,
+          &endorser_client,
+          handle,
+          height_to_start,
+          max_height,
+          Deadline::none(),
+        )
+        .await;
+        let _ = tx.send((endorser, pk_bytes, res)).await;
+      });
+    }
+
+    drop(mpsc_tx);
+
+    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+      match res {
+        Ok(()) => {},
+        Err(status) => {
+          if process_error(&endorser, Some(ledger_handle), &status)
+            == CoordinatorAction::RemoveEndorser
+          {
+            error!(
+              endorser = %endorser,
+              ?status,
+              "update_ledger received an unexpected error"
+            );
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
+          }
+        },
+      }
+    }
+  }
+
+  async fn endorser_read_view_tail(
+    &self,
+    endorsers: &[EndorserKey],
+    client_nonce: &Nonce,
+  ) -> Receipts {
+    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+
+    for pk in endorsers {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
+        None => continue,
+      };
+
+      let tx = mpsc_tx.clone();
+      let nonce = *client_nonce;
+      let pk_bytes = *pk;
+      let span = telemetry::endorser_span("read_view_tail", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = read_view_tail_with_retry(
+          &endorser_client,
+          endorser_proto::ReadViewTailReq {
+            nonce: nonce.to_bytes().into(),
+          },
+        )
+        .await;
+        match res {
+          Ok(resp) => {
+            let endorser_proto::ReadViewTailResp { receipt } = resp.into_inner();
+            let _ = tx.send((endorser, pk_bytes, Ok(receipt))).await;
+          },
+          Err(status) => match process_error(&endorser, None, &status) {
+            CoordinatorAction::RemoveEndorser => {
+              let _ = tx
+                .send((endorser, pk_bytes, Err(CoordinatorError::UnexpectedError)))
+                .await;
+            },
+            _ => {
+              let _ = tx
+                .send((
+                  endorser,
+                  pk_bytes,
+                  Err(CoordinatorError::FailedToReadViewLedger),
+                ))
+                .await;
+            },
+          },
+        }
+      });
+    }
+
+    drop(mpsc_tx);
+
+    let mut receipts = Receipts::new();
+    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+      match res {
+        Ok(receipt) => match Receipt::from_bytes(&receipt) {
+          Ok(receipt_rs) => {
+            let res = self.check_ledger_receipt(
+              &pk_bytes,
+              &receipt_rs,
+              &view_ledger_handle(),
+              receipt_rs.get_block_hash(),
+              receipt_rs.get_height(),
+              Some(&client_nonce.to_bytes()),
+            );
+            if let Err(error) = res {
+              warn!(
+                "Received an invalid receipt for the view ledger from endorser {} ({:?})",
+                endorser, error
+              );
+              continue;
+            }
+            receipts.add(&receipt_rs);
+            if let Ok(vs) = self.verifier_state.read() {
+              if receipts.check_quorum(&vs).is_ok() {
+                return receipts;
+              }
+            }
+          },
+          Err(error) => {
+            warn!(?error, "failed to parse a receipt");
+            self.record_invalid_signature(&pk_bytes);
+          },
+        },
+        Err(error) => {
+          if error == CoordinatorError::UnexpectedError {
+            error!(
+              endorser = %endorser,
+              error = ?error,
+              "read_view_tail received an unexpected error"
+            );
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
+          }
+        },
+      }
+    }
+
+    receipts
+  }
+
+  async fn endorser_read_ledger_tail(
+    &self,
+    endorsers: &[EndorserKey],
+    ledger_handle: &Handle,
+    client_nonce: &Nonce,
+  ) -> Result<LedgerEntry, CoordinatorError> {
+    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+
+    for pk in endorsers {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
+        None => continue,
+      };
+
+      let tx = mpsc_tx.clone();
+      let handle = *ledger_handle;
+      let nonce = *client_nonce;
+      let pk_bytes = *pk;
+      let span = telemetry::endorser_span("read_latest", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = read_latest_with_retry(
+          &endorser_client,
+          endorser_proto::ReadLatestReq {
+            handle: handle.to_bytes().into(),
+            nonce: nonce.to_bytes().into(),
+          },
+        )
+        .await;
+        match res {
+          Ok(resp) => {
+            let endorser_proto::ReadLatestResp {
+              receipt,
+              block,
+              nonces,
+            } = resp.into_inner();
+            let _ = tx
+              .send((endorser, pk_bytes, Ok((receipt, block, nonces))))
+              .await;
+          },
+          Err(status) => match process_error(&endorser, Some(&handle), &status) {
+            CoordinatorAction::RemoveEndorser => {
+              let _ = tx
+                .send((endorser, pk_bytes, Err(CoordinatorError::UnexpectedError)))
+                .await;
+            },
+            _ => {
+              let _ = tx
+                .send((
+                  endorser,
+                  pk_bytes,
+                  Err(CoordinatorError::FailedToReadLedger),
+                ))
+                .await;
+            },
+          },
+        }
+      });
+    }
+
+    drop(mpsc_tx);
+
+    let mut receipts = Receipts::new();
+    let mut endorser_height_map: HashMap<String, usize> = HashMap::new();
+    let mut max_height = 0;
+
+    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+      match res {
+        Ok((receipt, block, nonces)) => match Receipt::from_bytes(&receipt) {
+          Ok(receipt_rs) => {
+            // the endorser must sign the tail whose block and nonces it returns
+            let block_hash = compute_aggregated_block_hash(
+              &NimbleDigest::digest(&block).to_bytes(),
+              &NimbleDigest::digest(&nonces).to_bytes(),
+            );
+            let res = self.check_ledger_receipt(
+              &pk_bytes,
+              &receipt_rs,
+              ledger_handle,
+              &block_hash,
+              receipt_rs.get_height(),
+              Some(&client_nonce.to_bytes()),
+            );
+            if let Err(error) = res {
+              warn!(
+                handle = %ledger_handle,
+                endorser = %endorser,
+                ?error,
+                "received an invalid receipt"
+              );
+              continue;
+            }
+            let height = receipt_rs.get_height();
+            endorser_height_map.insert(endorser, height);
+            if max_height < height {
+              max_height = height;
+            }
+            receipts.add(&receipt_rs);
+            if let Ok(vs) = self.verifier_state.read() {
+              if let Ok(_h) = receipts.check_quorum(&vs) {
+                if let Ok(block_rs) = Block::from_bytes(&block) {
+                  if let Ok(nonces_rs) = Nonces::from_bytes(&nonces) {
+                    return Ok(LedgerEntry::new(block_rs, receipts, Some(nonces_rs)));
+                  }
+                }
+              }
+            }
+          },
+          Err(error) => {
+            warn!(?error, "failed to parse a receipt");
+            self.record_invalid_signature(&pk_bytes);
+          },
+        },
+        Err(error) => {
+          if error == CoordinatorError::UnexpectedError {
+            error!(
+              endorser = %endorser,
+              error = ?error,
+              "read_ledger received an unexpected error"
+            );
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
+          }
+        },
+      }
+    }
+
+    // Since we didn't reach a quorum, let's have endorsers catch up
+    self
+      .endorser_update_ledger(endorsers, ledger_handle, max_height, &endorser_height_map)
+      .await;
+
+    Err(CoordinatorError::FailedToObtainQuorum)
+  }
+
+  async fn endorser_finalize_state(
+    &self,
+    endorsers: &EndorserHostnames,
+    block_hash: &NimbleDigest,
+    expected_height: usize,
+  ) -> (Receipts, Vec<endorser_proto::LedgerTailMap>) {
+    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+
+    for pk in &self.get_endorser_keys(endorsers) {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
+        None => continue,
+      };
+
+      let tx = mpsc_tx.clone();
+      let block = *block_hash;
+      let pk_bytes = *pk;
+      let span = telemetry::endorser_span("finalize_state", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = finalize_state_with_retry(
+          &endorser_client,
+          endorser_proto::FinalizeStateReq {
+            block_hash: block.to_bytes().into(),
+            expected_height: expected_height as u64,
+          },
+        )
+        .await;
+        let _ = tx.send((endorser, pk_bytes, res)).await;
+      });
+    }
+
+    drop(mpsc_tx);
+
+    let mut receipts = Receipts::new();
+    let mut ledger_tail_maps = Vec::new();
+    let mut state_hashes = HashSet::new();
+
+    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+      match res {
+        Ok(resp) => {
+          let endorser_proto::FinalizeStateResp {
+            receipt,
+            ledger_tail_map,
+          } = resp.into_inner();
+          let res = Receipt::from_bytes(&receipt);
+          let receipt_rs = match res {
+            Ok(receipt_rs) => {
+              receipts.add(&receipt_rs);
+              receipt_rs
+            },
+            Err(error) => {
+              warn!("Failed to parse a receipt ({:?})", error);
+              continue;
+            },
+          };
+          if !state_hashes.contains(receipt_rs.get_view()) {
+            ledger_tail_maps.push(endorser_proto::LedgerTailMap {
+              entries: ledger_tail_map,
+            });
+            state_hashes.insert(*receipt_rs.get_view());
+          }
+        },
+        Err(status) => {
+          warn!(
+            "Failed to append view ledger to endorser {} (status={:?})",
+            endorser, status
+          );
+          if let CoordinatorAction::RemoveEndorser = process_error(&endorser, None, &status) {
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
+          }
+        },
+      }
+    }
+
+    (receipts, ledger_tail_maps)
+  }
+
+  async fn endorser_verify_view_change(
+    &self,
+    endorsers: &EndorserHostnames,
+    old_config: Block,
+    new_config: Block,
+    ledger_tail_maps: Vec<endorser_proto::LedgerTailMap>,
+    ledger_chunks: Vec<endorser_proto::LedgerChunkEntry>,
+    receipts: &Receipts,
+  ) -> usize {
+    let (mpsc_tx, mut mpsc_rx) = mpsc::channel(ENDORSER_MPSC_CHANNEL_BUFFER);
+    let ledger_tail_maps_arc = Arc::new(ledger_tail_maps);
+    // the requests to the endorsers share the bytes of the configurations and the receipts
+    let old_config = Bytes::from(old_config.to_bytes());
+    let new_config = Bytes::from(new_config.to_bytes());
+    let receipts = Bytes::from(receipts.to_bytes());
+
+    for pk in &self.get_endorser_keys(endorsers) {
+      let (endorser_client, endorser) = match self.get_endorser_client(pk) {
+        Some((client, endorser)) => (client, endorser),
+        None => continue,
+      };
+
+      let tx = mpsc_tx.clone();
+      let pk_bytes = *pk;
+      let old_config_copy = old_config.clone();
+      let new_config_copy = new_config.clone();
+      let ledger_tail_maps_arc_copy = ledger_tail_maps_arc.clone();
+      let ledger_chunks_copy = ledger_chunks.clone();
+      let receipts_copy = receipts.clone();
+      let span = telemetry::endorser_span("activate", pk, &endorser);
+      let _job = telemetry::spawn(span, async move {
+        let res = activate_with_retry(
+          &endorser_client,
+          old_config_copy,
+          new_config_copy,
+          ledger_tail_maps_arc_copy,
+          ledger_chunks_copy,
+          receipts_copy,
+        )
+        .await;
+        let _ = tx.send((endorser, pk_bytes, res)).await;
+      });
+    }
+
+    drop(mpsc_tx);
+
+    let mut num_verified_endorers = 0;
+
+    while let Some((endorser, pk_bytes, res)) = mpsc_rx.recv().await {
+      match res {
+        Ok(_resp) => {
+          num_verified_endorers += 1;
+        },
+        Err(status) => {
+          warn!(
+            "Failed to prove view change to endorser {} (status={:?})",
+            endorser, status
+          );
+          if let CoordinatorAction::RemoveEndorser = process_error(&endorser, None, &status) {
+            self
+              .disconnect_endorsers(&vec![(pk_bytes.to_vec(), endorser)])
+              .await;
+          }
+        },
+      }
+    }
+
+    num_verified_endorers
+  }
+
+  /// keeps the current view from changing while a client write is in flight; fails if the
+  /// coordinator does not hold its lease, since another coordinator may be serving writes
+  fn hold_view(&self) -> Result<tokio::sync::RwLockReadGuard<'_, ()>, CoordinatorError> {
+    self.check_serving()?;
+    self
+      .view_change_lock
+      .try_read()
+      .map_err(|_e| CoordinatorError::ViewChangeInProgress)
+  }
+
+  /// waits for the lock of a ledger, unless the grace period of a shutdown passes first
+  async fn lock_ledger(&self, handle: &Handle) -> Result<OwnedMutexGuard<()>, CoordinatorError> {
+    let mut shutdown = self.shutdown.subscribe();
+    let expired = async move {
+      while *shutdown.borrow_and_update() != ShutdownPhase::Expired {
+        if shutdown.changed().await.is_err() {
+          std::future::pending::<()>().await;
+        }
+      }
+    };
+    let start = Instant::now();
+    let res = tokio::select! {
+      biased;
+      () = expired => Err(CoordinatorError::ShuttingDown),
+      res = self.ledger_locks.lock(handle) => res,
+    };
+    self.metrics.observe_ledger_lock_wait(start.elapsed());
+    res
+  }
+
+  /// stops the coordinator serving writes: new writes are rejected with `ShuttingDown`, and writes
+  /// in flight are given `grace` to finish, after which those still waiting for the lock of their
+  /// ledger are rejected too. A write that is cut off after the grace period leaves at most a
+  /// tail without a quorum of receipts in the ledger store, since blocks are persisted before
+  /// they are endorsed, and recovery reconciles such tails when a coordinator starts next. The
+  /// endorsers are disconnected last. Returns whether every write in flight finished in time
+  pub async fn shutdown(&self, grace: Duration) -> bool {
+    self.shutdown.send_replace(ShutdownPhase::Draining);
+    // writes hold the view for reading until they finish, so holding it for writing waits for all
+    // of them, including the ones queued behind the lock of a ledger
+    let drained = tokio::time::timeout(grace, self.view_change_lock.write())
+      .await
+      .is_ok();
+    if !drained {
+      self.shutdown.send_replace(ShutdownPhase::Expired);
+    }
+    self
+      .disconnect_endorsers(&self.get_endorser_hostnames())
+      .await;
+    drained
+  }
+
+  pub async fn replace_endorsers(&self, hostnames: &[String]) -> Result<(), CoordinatorError> {
+    self.check_serving()?;
+    let _view_change = self.view_change_lock.write().await;
+    let existing_endorsers = self.get_endorser_hostnames();
+
+    // Connect to new endorsers
+    let new_endorsers = self.connect_new_endorsers(hostnames).await?;
+    if new_endorsers.is_empty() {
+      return Err(CoordinatorError::NoNewEndorsers);
+    }
+
+    self
+      .change_view(
+        &existing_endorsers,
+        &new_endorsers,
+        self.recorded_quorum()?,
+        &[],
+      )
+      .await
+  }
+
+  /// grows the current view with the given endorsers; the endorsers of the current view are
+  /// finalized and join the new view together with the new ones
+  pub async fn add_endorsers(&self, hostnames: &[String]) -> Result<(), CoordinatorError> {
+    self.check_serving()?;
+    let _view_change = self.view_change_lock.write().await;
+    let existing_endorsers = self.get_endorser_hostnames();
+
+    // Connect to new endorsers
+    let mut endorsers = existing_endorsers.clone();
+    for (pk, uri) in self.connect_new_endorsers(hostnames).await? {
+      if !endorsers
+        .iter()
+        .any(|(existing_pk, _uri)| *existing_pk == pk)
+      {
+        endorsers.push((pk, uri));
+      }
+    }
+    if endorsers.len() == existing_endorsers.len() {
+      return Err(CoordinatorError::NoNewEndorsers);
+    }
+
+    self
+      .change_view(
+        &existing_endorsers,
+        &endorsers,
+        self.recorded_quorum()?,
+        &[],
+      )
+      .await
+  }
+
+  /// shrinks the current view by the endorsers with the given URIs; the remaining endorsers are
+  /// finalized and rejoin the new view, so a removed endorser does not need to be reachable.
+  /// Returns the removed endorsers
+  pub async fn remove_endorsers(
+    &self,
+    hostnames: &[String],
+  ) -> Result<EndorserHostnames, CoordinatorError> {
+    self.check_serving()?;
+    let _view_change = self.view_change_lock.write().await;
+    let existing_endorsers = self.get_endorser_hostnames();
+
+    // an endorser that already failed is disconnected, but is still part of the current view
+    let removed_endorsers = self
+      .read_current_view()
+      .await?
+      .into_iter()
+      .filter(|(_pk, uri)| hostnames.contains(uri))
+      .collect::<EndorserHostnames>();
+    if removed_endorsers.is_empty() {
+      return Err(CoordinatorError::InvalidEndorserUri);
+    }
+
+    let endorsers = existing_endorsers
+      .iter()
+      .filter(|(pk, _uri)| {
+        !removed_endorsers
+          .iter()
+          .any(|(removed_pk, _uri)| removed_pk == pk)
+      })
+      .cloned()
+      .collect::<EndorserHostnames>();
+    if endorsers.len() < self.min_num_endorsers {
+      warn!(
+        "removing {:?} would leave {} endorsers, fewer than the minimum of {}",
+        hostnames,
+        endorsers.len(),
+        self.min_num_endorsers
+      );
+      return Err(CoordinatorError::TooFewEndorsers);
+    }
+
+    self
+      .change_view(
+        &existing_endorsers,
+        &endorsers,
+        self.recorded_quorum()?,
+        &[],
+      )
+      .await?;
+
+    Ok(removed_endorsers)
+  }
+
+  /// rotates the key of the endorser with `pk`: the endorser generates a new key and signs the
+  /// handover to it with `pk`, and a view change swaps the keys in the view and records the
+  /// handover in the view ledger, so that the receipts signed under either key are attributed to
+  /// the same endorser. The endorser retires `pk` once the new view is activated. Returns the new
+  /// key
+  pub async fn rotate_endorser_key(&self, pk: &[u8]) -> Result<Vec<u8>, CoordinatorError> {
+    self.check_serving()?;
+    let _view_change = self.view_change_lock.write().await;
+    let existing_endorsers = self.get_endorser_hostnames();
+    if !existing_endorsers
+      .iter()
+      .any(|(existing_pk, _uri)| existing_pk == pk)
+    {
+      return Err(CoordinatorError::InvalidEndorserPublicKey);
+    }
+    let (endorser_client, endorser) = self
+      .get_endorser_client(pk)
+      .ok_or(CoordinatorError::FailedToConnectToEndorser)?;
+
+    let endorser_proto::RotateKeyResp {
+      old_pk,
+      new_pk,
+      signature,
+    } = rotate_key_with_retry(&endorser_client)
+      .await
+      .map_err(|status| {
+        warn!(
+          "Failed to rotate the key of endorser {} (status={:?})",
+          endorser, status
+        );
+        CoordinatorError::from(status)
+      })?
+      .into_inner();
+    let group_identity = match self.verifier_state.read() {
+      Ok(vs) => *vs.get_group_identity(),
+      Err(_e) => return Err(CoordinatorError::FailedToAcquireReadLock),
+    };
+    let rotation = KeyRotation::from_raw(&old_pk, &new_pk, &signature)?;
+    if old_pk != pk
+      || existing_endorsers
+        .iter()
+        .any(|(existing_pk, _uri)| *existing_pk == new_pk)
+    {
+      warn!(
+        "Endorser {} rotated another key than {}",
+        endorser,
+        hex::encode(pk)
+      );
+      return Err(VerificationError::InvalidKeyRotation.into());
+    }
+    rotation.verify(&group_identity)?;
+
+    let endorsers = existing_endorsers
+      .iter()
+      .map(|(existing_pk, uri)| {
+        if existing_pk == pk {
+          (new_pk.to_vec(), uri.clone())
+        } else {
+          (existing_pk.clone(), uri.clone())
+        }
+      })
+      .collect::<EndorserHostnames>();
+    self
+      .change_view(
+        &existing_endorsers,
+        &endorsers,
+        self.recorded_quorum()?,
+        &[rotation],
+      )
+      .await?;
+    info!(
+      endorser = %endorser,
+      old_pk = %hex::encode(pk),
+      new_pk = %hex::encode(&new_pk),
+      "rotated the key of the endorser"
+    );
+
+    Ok(new_pk.to_vec())
+  }
+
+  /// makes the views from now on need the receipts of `quorum` of their endorsers, with a view
+  /// change that keeps the endorsers, so that the view ledger records the quorum and clients hold
+  /// the receipts of the new view to it; receipts of earlier views keep the quorum of their view
+  pub async fn change_quorum(&self, quorum: usize) -> Result<(), CoordinatorError> {
+    self.check_serving()?;
+    let _view_change = self.view_change_lock.write().await;
+    if quorum == self.get_quorum() {
+      return Ok(());
+    }
+    let existing_endorsers = self.get_endorser_hostnames();
+    self
+      .change_view(&existing_endorsers, &existing_endorsers, Some(quorum), &[])
+      .await?;
+    info!(quorum, "changed the quorum of the view");
+    Ok(())
+  }
+
+  /// the quorum that the current view records, if it is not a majority
+  fn recorded_quorum(&self) -> Result<Option<usize>, CoordinatorError> {
+    match self.quorum.read() {
+      Ok(quorum) => Ok(*quorum),
+      Err(_e) => Err(CoordinatorError::FailedToAcquireReadLock),
+    }
+  }
+
+  /// returns the endorsers recorded in the latest entry of the view ledger, including the ones
+  /// that are no longer connected
+  pub async fn read_current_view(&self) -> Result<EndorserHostnames, CoordinatorError> {
+    let (view_ledger_tail, _height) = self.ledger_store.read_view_ledger_tail().await?;
+    bincode::deserialize(&view_ledger_tail.get_block().to_bytes()).map_err(|e| {
+      warn!("Failed to deserialize the view ledger tail {:?}", e);
+      CoordinatorError::FailedToSerde
+    })
+  }
+
+  /// returns the endorsers of every view, oldest first, along with the height of its entry in the
+  /// view ledger and the number of its endorsers whose receipts it needs
+  pub async fn get_view_history(
+    &self,
+  ) -> Result<Vec<(usize, EndorserHostnames, usize)>, CoordinatorError> {
+    let (_view_ledger_tail, tail_height) = self.ledger_store.read_view_ledger_tail().await?;
+    let mut views = Vec::with_capacity(tail_height);
+    for idx in 1..=tail_height {
+      let view_ledger_entry = self.ledger_store.read_view_ledger_by_index(idx).await?;
+      let endorsers: EndorserHostnames =
+        bincode::deserialize(&view_ledger_entry.get_block().to_bytes()).map_err(|e| {
+          warn!(
+            "Failed to deserialize the view ledger entry {} {:?}",
+            idx, e
+          );
+          CoordinatorError::FailedToSerde
+        })?;
+      let quorum = retrieve_quorum_from_config(&view_ledger_entry.get_block().to_bytes())?;
+      views.push((idx, endorsers, quorum));
+    }
+    Ok(views)
+  }
+
+  /// reports the health of every endorser in the current view and how many ledger entries of the
+  /// store each of them is missing
+  pub async fn get_endorser_statuses(&self) -> Result<Vec<EndorserStatus>, CoordinatorError> {
+    let ledger_tails = self.scan_ledger_tails().await?;
+
+    let mut statuses = Vec::new();
+    for (pk, uri) in self.read_current_view().await? {
+      let mut status = EndorserStatus {
+        pk: pk.clone(),
+        uri,
+        connected: false,
+        mode: None,
+        lag: None,
+        invalid_signatures: self.get_invalid_signatures(&pk),
+        num_ledgers: None,
+        tail_map_bytes: None,
+      };
+      if let Some((endorser_client, endorser)) = self.get_endorser_client(&pk) {
+        status.connected = true;
+        match read_state_with_retry(&endorser_client, endorser_proto::ReadStateReq {}).await {
+          Ok(resp) => {
+            let endorser_proto::ReadStateResp {
+              mode,
+              ledger_tail_map,
+              ..
+            } = resp.into_inner();
+            let mut endorser_heights = HashMap::new();
+            for entry in &ledger_tail_map {
+              let handle = NimbleDigest::from_bytes(&entry.handle)?;
+              endorser_heights.insert(handle, entry.height as usize);
+            }
+            let lag = ledger_tails
+              .iter()
+              .map(
+                |(handle, (_tail_hash, height))| match endorser_heights.get(handle) {
+                  Some(endorser_height) => height.saturating_sub(*endorser_height),
+                  None => height + 1,
+                },
+              )
+              .sum::<usize>();
+            status.mode = Some(mode);
+            status.lag = Some(lag);
+          },
+          Err(status) => {
+            warn!(
+              "Failed to read the state of endorser {} ({:?})",
+              endorser, status
+            );
+          },
+        }
+        // an endorser that predates the call answers that it is unimplemented
+        match endorser_client
+          .get_status(telemetry::outgoing(endorser_proto::GetStatusReq {}))
+          .await
+        {
+          Ok(resp) => {
+            let endorser_proto::GetStatusResp {
+              num_ledgers,
+              tail_map_bytes,
+              ..
+            } = resp.into_inner();
+            status.num_ledgers = Some(num_ledgers as usize);
+            status.tail_map_bytes = Some(tail_map_bytes as usize);
+          },
+          Err(status) => {
+            warn!(
+              "Failed to read the status of endorser {} ({:?})",
+              endorser, status
+            );
+          },
+        }
+      }
+      statuses.push(status);
+    }
+
+    Ok(statuses)
+  }
+
+  /// the CPU time that the connected endorsers report having used, summed; an endorser that does
+  /// not answer, or cannot tell, adds none
+  pub async fn get_endorser_cpu_time(&self) -> Duration {
+    let jobs = self
+      .get_endorser_pks()
+      .iter()
+      .filter_map(|pk| self.get_endorser_client(pk))
+      .map(|(endorser_client, _endorser)| {
+        tokio::spawn(async move {
+          endorser_client
+            .get_status(telemetry::outgoing(endorser_proto::GetStatusReq {}))
+            .await
+        })
+      })
+      .collect::<Vec<_>>();
+    let mut cpu_micros = 0;
+    for job in jobs {
+      if let Ok(Ok(resp)) = job.await {
+        cpu_micros += resp.into_inner().cpu_micros;
+      }
+    }
+    Duration::from_micros(cpu_micros)
+  }
 
-    // Connect to new endorsers
-    let new_endorsers = self.connect_endorsers(hostnames).await;
-    if new_endorsers.is_empty() {
-      return Err(CoordinatorError::NoNewEndorsers);
+  /// brings an endorser of the current view up to date with the ledger store, reconnecting to it
+  /// first if it was disconnected
+  pub async fn repair_endorser(&self, pk: &[u8]) -> Result<(), CoordinatorError> {
+    self.check_serving()?;
+    let _view = self.view_change_lock.read().await;
+
+    let uri = match self
+      .read_current_view()
+      .await?
+      .into_iter()
+      .find(|(view_pk, _uri)| view_pk == pk)
+    {
+      Some((_pk, uri)) => uri,
+      None => return Err(CoordinatorError::InvalidEndorserPublicKey),
+    };
+    if self.get_endorser_client(pk).is_none() {
+      let endorsers = self.connect_endorsers(&[uri.clone()]).await;
+      if !endorsers
+        .iter()
+        .any(|(connected_pk, _uri)| connected_pk == pk)
+      {
+        return Err(CoordinatorError::FailedToConnectToEndorser);
+      }
+    }
+
+    self.repair_endorsers(&vec![(pk.to_vec(), uri)]).await
+  }
+
+  /// appends a view entry with `new_endorsers`, their `quorum`, and the key `rotations` to the view
+  /// ledger and moves the endorsers over; the appended entry records the progress of the view
+  /// change so that a coordinator that crashes before it completes resumes it on startup
+  async fn change_view(
+    &self,
+    existing_endorsers: &EndorserHostnames,
+    new_endorsers: &EndorserHostnames,
+    quorum: Option<usize>,
+    rotations: &[KeyRotation],
+  ) -> Result<(), CoordinatorError> {
+    let start = Instant::now();
+
+    // a majority is left out of the block, which then is the same as without a quorum
+    let num_endorsers = new_endorsers.len();
+    let quorum = quorum.filter(|quorum| *quorum != majority_quorum(num_endorsers));
+    if let Some(quorum) = quorum {
+      if quorum < majority_quorum(num_endorsers) || quorum > num_endorsers {
+        warn!(
+          quorum,
+          num_endorsers, "the quorum does not fit the endorsers of the new view"
+        );
+        return Err(CoordinatorError::InvalidQuorum {
+          quorum,
+          num_endorsers,
+        });
+      }
+    }
+
+    // Package the list of endorsers into a genesis block of the view ledger
+    let view_ledger_genesis_block =
+      Block::new(&encode_view_config(new_endorsers, quorum, rotations));
+
+    // Read the current ledger tail
+    let res = self.ledger_store.read_view_ledger_tail().await;
+
+    if res.is_err() {
+      warn!(
+        "Failed to read from the view ledger in the ledger store ({:?})",
+        res.unwrap_err()
+      );
+      return Err(CoordinatorError::FailedToCallLedgerStore);
+    }
+
+    let (tail, height) = res.unwrap();
+
+    // Store the genesis block of the view ledger in the ledger store
+    let res = self
+      .ledger_store
+      .append_view_ledger(&view_ledger_genesis_block, height + 1)
+      .await;
+    if let Err(e) = res {
+      warn!(
+        "Failed to append to the view ledger in the ledger store ({:?})",
+        e,
+      );
+      return Err(CoordinatorError::FailedToCallLedgerStore);
+    }
+
+    let view_ledger_height = res.unwrap();
+
+    let res = self
+      .apply_view_change(
+        existing_endorsers,
+        new_endorsers,
+        &tail,
+        &view_ledger_genesis_block,
+        view_ledger_height,
+      )
+      .await;
+    self.metrics.observe_view_change(start.elapsed());
+    res
+  }
+
+  async fn apply_view_change(
+    &self,
+    existing_endorsers: &EndorserHostnames,
+    new_endorsers: &EndorserHostnames,
+    view_ledger_entry: &LedgerEntry,
+    view_ledger_genesis_block: &Block,
+    view_ledger_height: usize,
+  ) -> Result<(), CoordinatorError> {
+    // Compute the digest that the endorsers sign for the new view
+    let view_block_hash = match compute_view_block_hash(&view_ledger_genesis_block.to_bytes()) {
+      Ok(hash) => hash,
+      Err(e) => {
+        warn!("Failed to compute the view block hash ({:?})", e);
+        return Err(CoordinatorError::InvalidEndorserPublicKey);
+      },
+    };
+
+    // an endorser whose key the view change rotates finalizes the old view under its old key and
+    // joins the new view under its new key
+    let (_endorsers, rotations) = decode_view_config(&view_ledger_genesis_block.to_bytes())?;
+    let quorum = retrieve_quorum_from_config(&view_ledger_genesis_block.to_bytes())?;
+    for rotation in &rotations {
+      self.connect_rotated_endorser(rotation.get_old_pk(), rotation.get_new_pk());
+    }
+
+    // Retrieve the view tail metablock
+    let view_tail_receipts = view_ledger_entry.get_receipts();
+    let view_tail_metablock = if view_tail_receipts.is_empty() {
+      if view_ledger_height != 1 {
+        warn!(
+          "cannot get view tail metablock from empty receipts (height = {}",
+          view_ledger_height
+        );
+        return Err(CoordinatorError::UnexpectedError);
+      } else {
+        MetaBlock::default()
+      }
+    } else {
+      let res = view_tail_receipts.get_metablock();
+      match res {
+        Ok(metablock) => metablock,
+        Err(_e) => {
+          warn!("faield to retrieve metablock from view receipts");
+          return Err(CoordinatorError::UnexpectedError);
+        },
+      }
+    };
+
+    let (finalize_receipts, ledger_tail_maps) = if existing_endorsers.is_empty() {
+      assert!(view_ledger_height == 1);
+
+      (Receipts::new(), Vec::new())
+    } else {
+      self
+        .endorser_finalize_state(existing_endorsers, &view_block_hash, view_ledger_height)
+        .await
+    };
+
+    // Compute the max cut
+    let max_cut = compute_max_cut(&ledger_tail_maps);
+
+    // Set group identity if necessary
+    let group_identity = if view_ledger_height == 1 {
+      let id = view_block_hash;
+      if let Ok(mut vs) = self.verifier_state.write() {
+        vs.set_group_identity(id);
+        id
+      } else {
+        return Err(CoordinatorError::FailedToAcquireWriteLock);
+      }
+    } else if let Ok(vs) = self.verifier_state.read() {
+      *vs.get_group_identity()
+    } else {
+      return Err(CoordinatorError::FailedToAcquireReadLock);
+    };
+
+    // Initialize new endorsers
+    let initialize_receipts = self
+      .endorser_initialize_state(
+        &group_identity,
+        new_endorsers,
+        max_cut,
+        &view_tail_metablock,
+        &view_block_hash,
+        view_ledger_height,
+      )
+      .await;
+
+    // Store the receipts in the view ledger
+    let mut receipts = Receipts::new();
+    receipts.merge_receipts(&finalize_receipts);
+    receipts.merge_receipts(&initialize_receipts);
+    let res = self
+      .ledger_store
+      .attach_view_ledger_receipts(view_ledger_height, &receipts)
+      .await;
+    if res.is_err() {
+      warn!(
+        "Failed to attach view ledger receipt in the ledger store ({:?})",
+        res.unwrap_err()
+      );
+      return Err(CoordinatorError::FailedToCallLedgerStore);
+    }
+
+    // Retrieve blocks that need for verifying the view change
+    let cut_diffs = compute_cut_diffs(&ledger_tail_maps);
+    let mut ledger_chunks: Vec<endorser_proto::LedgerChunkEntry> = Vec::new();
+    for cut_diff in &cut_diffs {
+      if cut_diff.low == cut_diff.high {
+        continue;
+      }
+      let mut block_hashes: Vec<Bytes> =
+        Vec::with_capacity((cut_diff.high - cut_diff.low) as usize);
+      let h = NimbleDigest::from_bytes(&cut_diff.handle)?;
+      for index in (cut_diff.low + 1)..=cut_diff.high {
+        let res = self
+          .ledger_store
+          .read_ledger_by_index(&h, index as usize)
+          .await;
+        if let Err(e) = res {
+          warn!("Failed to read the ledger store {:?}", e);
+          return Err(CoordinatorError::FailedToCallLedgerStore);
+        }
+        let ledger_entry = res.unwrap();
+        let block_hash = ledger_entry.get_block_hash();
+        block_hashes.push(block_hash.to_bytes().into());
+      }
+      ledger_chunks.push(endorser_proto::LedgerChunkEntry {
+        handle: cut_diff.handle.clone(),
+        hash: cut_diff.hash.to_bytes().into(),
+        height: cut_diff.low as u64,
+        block_hashes,
+      });
+    }
+
+    let num_verified_endorsers = self
+      .endorser_verify_view_change(
+        new_endorsers,
+        view_ledger_entry.get_block().clone(),
+        view_ledger_genesis_block.clone(),
+        ledger_tail_maps,
+        ledger_chunks,
+        &receipts,
+      )
+      .await;
+    if num_verified_endorsers < quorum {
+      warn!(
+        "insufficient verified endorsers {} < {}",
+        num_verified_endorsers, quorum
+      );
+    }
+
+    // Apply view change to the verifier state
+    if let Ok(mut vs) = self.verifier_state.write() {
+      if let Err(e) = vs.apply_view_change(
+        &view_ledger_genesis_block.to_bytes(),
+        &receipts.to_bytes(),
+        Some(ATTESTATION_STR.as_bytes()),
+      ) {
+        warn!("Failed to apply view change: {:?}", e);
+      }
+    } else {
+      return Err(CoordinatorError::FailedToAcquireWriteLock);
+    }
+    self.set_view(&view_ledger_genesis_block.to_bytes(), new_endorsers.len())?;
+
+    // Disconnect existing endorsers that are not part of the new view
+    let retired_endorsers = existing_endorsers
+      .iter()
+      .filter(|(pk, _uri)| !new_endorsers.iter().any(|(new_pk, _uri)| new_pk == pk))
+      .cloned()
+      .collect::<EndorserHostnames>();
+    self.disconnect_endorsers(&retired_endorsers).await;
+
+    Ok(())
+  }
+
+  /// serves the given tenants: their quotas are enforced on the ledgers in their namespaces
+  pub async fn register_tenants(&self, tenants: &[String]) {
+    let mut registered = self.tenants.lock().await;
+    registered.ids.extend(tenants.iter().cloned());
+  }
+
+  /// reads the quota and usage of a tenant that the coordinator serves
+  pub async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, CoordinatorError> {
+    if !self.tenants.lock().await.ids.contains(tenant) {
+      return Err(CoordinatorError::UnknownTenant);
+    }
+    self.ledger_store.read_tenant(tenant).await.map_err(|e| {
+      warn!(
+        "Failed to read tenant {} from the ledger store ({:?})",
+        tenant, e
+      );
+      CoordinatorError::FailedToCallLedgerStore
+    })
+  }
+
+  /// replaces the quota of a tenant, keeping its usage; a limit of 0 lifts the quota, and a limit
+  /// below the usage only stops further writes
+  pub async fn set_tenant_quota(
+    &self,
+    tenant: &str,
+    max_ledgers: u64,
+    max_appends_per_sec: u64,
+    max_stored_bytes: u64,
+  ) -> Result<TenantRecord, CoordinatorError> {
+    self.check_serving()?;
+    let tenants = self.tenants.lock().await;
+    if !tenants.ids.contains(tenant) {
+      return Err(CoordinatorError::UnknownTenant);
+    }
+    let mut record = self.ledger_store.read_tenant(tenant).await?;
+    record.max_ledgers = max_ledgers;
+    record.max_appends_per_sec = max_appends_per_sec;
+    record.max_stored_bytes = max_stored_bytes;
+    self.ledger_store.write_tenant(tenant, &record).await?;
+    Ok(record)
+  }
+
+  /// charges a write to the tenant whose namespace holds the ledger, if any: `num_ledgers` new
+  /// ledgers, `num_appends` appends and `num_bytes` stored bytes. Nothing is charged if the write
+  /// would exceed a quota of the tenant. Returns the tenant charged
+  async fn reserve_tenant_usage(
+    &self,
+    handle_bytes: &[u8],
+    num_ledgers: u64,
+    num_appends: u64,
+    num_bytes: u64,
+  ) -> Result<Option<String>, CoordinatorError> {
+    let mut tenants = self.tenants.lock().await;
+    let tenant = match tenants.find(handle_bytes) {
+      Some(tenant) => tenant,
+      None => return Ok(None),
+    };
+    let mut record = self.ledger_store.read_tenant(&tenant).await?;
+
+    let exceeds = |charge: u64, usage: u64, limit: u64| {
+      charge > 0 && limit > 0 && usage.saturating_add(charge) > limit
+    };
+    if exceeds(num_ledgers, record.num_ledgers, record.max_ledgers) {
+      return Err(CoordinatorError::QuotaExceeded(TenantQuota::Ledgers));
+    }
+    if exceeds(num_bytes, record.stored_bytes, record.max_stored_bytes) {
+      return Err(CoordinatorError::QuotaExceeded(TenantQuota::StoredBytes));
+    }
+    let now = self.clock.now_ms() / 1000;
+    let window = tenants
+      .append_windows
+      .entry(tenant.clone())
+      .or_insert((now, 0));
+    if window.0 != now {
+      *window = (now, 0);
+    }
+    if exceeds(num_appends, window.1, record.max_appends_per_sec) {
+      return Err(CoordinatorError::QuotaExceeded(TenantQuota::AppendRate));
+    }
+    // appends that fail later still count against the rate, like appends that a client retries
+    window.1 += num_appends;
+
+    record.num_ledgers = record.num_ledgers.saturating_add(num_ledgers);
+    record.stored_bytes = record.stored_bytes.saturating_add(num_bytes);
+    self.ledger_store.write_tenant(&tenant, &record).await?;
+    Ok(Some(tenant))
+  }
+
+  /// gives back what `reserve_tenant_usage` charged for a write that the ledger store did not
+  /// take; a failure only leaves the usage of the tenant overcounted
+  async fn release_tenant_usage(&self, tenant: Option<String>, num_ledgers: u64, num_bytes: u64) {
+    let tenant = match tenant {
+      Some(tenant) => tenant,
+      None => return,
+    };
+    let _tenants = self.tenants.lock().await;
+    let res = match self.ledger_store.read_tenant(&tenant).await {
+      Ok(mut record) => {
+        record.num_ledgers = record.num_ledgers.saturating_sub(num_ledgers);
+        record.stored_bytes = record.stored_bytes.saturating_sub(num_bytes);
+        self.ledger_store.write_tenant(&tenant, &record).await
+      },
+      Err(error) => Err(error),
+    };
+    if let Err(error) = res {
+      warn!(
+        "Failed to release the usage of tenant {} ({:?})",
+        tenant, error
+      );
+    }
+  }
+
+  pub async fn reset_ledger_store(&self) {
+    let res = self.ledger_store.reset_store().await;
+    assert!(res.is_ok());
+  }
+
+  pub async fn create_ledger(
+    &self,
+    endorsers_opt: Option<Vec<EndorserKey>>,
+    handle_bytes: &[u8],
+    block_bytes: &[u8],
+    app_bytes: &[u8],
+    metadata: &[u8],
+  ) -> Result<Receipts, CoordinatorError> {
+    self
+      .create_ledger_with_deadline(
+        endorsers_opt,
+        handle_bytes,
+        block_bytes,
+        app_bytes,
+        metadata,
+        Deadline::none(),
+      )
+      .await
+  }
+
+  /// creates a ledger like `create_ledger`, but gives up once the deadline expires; a ledger that
+  /// is created in the store by then is completed by a retry or the next append to it
+  pub async fn create_ledger_with_deadline(
+    &self,
+    endorsers_opt: Option<Vec<EndorserKey>>,
+    handle_bytes: &[u8],
+    block_bytes: &[u8],
+    app_bytes: &[u8],
+    metadata: &[u8],
+    deadline: Deadline,
+  ) -> Result<Receipts, CoordinatorError> {
+    deadline.check(WriteStage::NotStarted)?;
+    let _view = self.hold_view()?;
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    Block::try_new_with_max_size(block_bytes, self.max_block_size)
+      .map_err(|_e| CoordinatorError::BlockTooLarge)?;
+    if metadata.len() > MAX_LEDGER_METADATA_SIZE {
+      return Err(CoordinatorError::MetadataTooLarge);
+    }
+    // the metadata is part of the genesis block, so the receipts of the genesis entry cover it
+    let genesis_block = compute_genesis_block(block_bytes, metadata);
+    let info = LedgerInfo {
+      created_at: self.clock.now_ms(),
+      metadata: metadata.to_vec(),
+      handle_bytes: handle_bytes.to_vec(),
+      app_bytes: app_bytes.to_vec(),
+    };
+
+    let hash_block = genesis_block.hash();
+    let hash_nonces = Nonces::new().hash();
+    let block_hash = compute_aggregated_block_hash(&hash_block.to_bytes(), &hash_nonces.to_bytes());
+
+    // creates of the same handle run one at a time, so a create that finds the ledger in the store
+    // finds it with the receipts of the create that inserted it, if that one got a quorum
+    let _ledger = deadline
+      .run(self.lock_ledger(&handle))
+      .await
+      .ok_or(CoordinatorError::DeadlineExceeded(WriteStage::NotStarted))??;
+
+    let num_bytes = (block_bytes.len() + metadata.len()) as u64;
+    let tenant = match self
+      .reserve_tenant_usage(handle_bytes, 1, 0, num_bytes)
+      .await
+    {
+      Ok(tenant) => tenant,
+      // a retried create is not charged again, so the quota that its first attempt filled does
+      // not stop it
+      Err(CoordinatorError::QuotaExceeded(_))
+        if self.ledger_store.read_ledger_info(&handle).await.is_ok() =>
+      {
+        None
+      },
+      Err(error) => return Err(error),
+    };
+    let res = self
+      .ledger_store
+      .create_ledger(&handle, genesis_block.clone(), &info)
+      .await;
+    if res.is_err() {
+      // a retried create charged the tenant the first time
+      self.release_tenant_usage(tenant, 1, num_bytes).await;
+    }
+    match res {
+      Ok(()) => {},
+      Err(LedgerStoreError::LedgerError(StorageError::DuplicateKey)) => {
+        // a create that loses the insert never runs its own create on the endorsers: with the
+        // same genesis parameters it is a retry, which returns the receipts of the original
+        // create, and with others it conflicts. An original create that ended short of a quorum
+        // is over by now, since it held the lock, so its genesis entry is reconciled instead
+        let ledger_entry = match self
+          .read_creation_receipts(&handle, &genesis_block, app_bytes)
+          .await?
+        {
+          Ok(receipts) => return Ok(receipts),
+          Err(ledger_entry) => ledger_entry,
+        };
+        deadline.check(WriteStage::Persisted)?;
+        return self
+          .reconcile_ledger_tail(&handle, 0, ledger_entry, deadline)
+          .await
+          .map_err(|e| {
+            if e == CoordinatorError::FailedToObtainQuorum {
+              self.metrics.record_quorum_shortfall("create");
+            }
+            deadline.exceeded_or(e, WriteStage::Persisted)
+          });
+      },
+      Err(error) => {
+        warn!("Failed to create ledger in the ledger store ({:?})", error);
+        return Err(CoordinatorError::FailedToCreateLedger);
+      },
     }
 
-    // Package the list of endorsers into a genesis block of the view ledger
-    let view_ledger_genesis_block = {
-      let res = bincode::serialize(&new_endorsers);
+    // Make a request to the endorsers for NewLedger using the handle which returns a signature.
+    deadline.check(WriteStage::Persisted)?;
+    let receipts = {
+      let endorsers = match endorsers_opt {
+        Some(ref endorsers) => endorsers.clone(),
+        None => self.get_endorser_pks(),
+      };
+      let res = self
+        .endorser_create_ledger(&endorsers, &handle, &block_hash, genesis_block, deadline)
+        .await;
       if res.is_err() {
-        eprintln!("Failed to serialize endorser hostnames {:?}", res);
-        return Err(CoordinatorError::FailedToSerde);
+        warn!("Failed to create ledger in endorsers ({:?})", res);
+        return Err(res.unwrap_err());
       }
-      let block_vec = res.unwrap();
-      Block::new(&block_vec)
+      res.unwrap()
     };
+    if endorsers_opt.is_none() {
+      self.check_receipts_quorum(&receipts).map_err(|e| {
+        self.metrics.record_quorum_shortfall("create");
+        deadline.exceeded_or(e, WriteStage::Persisted)
+      })?;
+    }
 
-    // Read the current ledger tail
-    let res = self.ledger_store.read_view_ledger_tail().await;
+    // receipts that are not stored are signed again by a retry, so a late write stops here
+    deadline.check(WriteStage::Endorsed)?;
 
+    // Store the receipt
+    let res = self
+      .ledger_store
+      .attach_ledger_receipts(&handle, 0, &receipts)
+      .await;
     if res.is_err() {
-      eprintln!(
-        "Failed to read from the view ledger in the ledger store ({:?})",
-        res.unwrap_err()
+      warn!(
+        "Failed to attach ledger receipt to the ledger store ({:?})",
+        res
       );
-      return Err(CoordinatorError::FailedToCallLedgerStore);
+      return Err(CoordinatorError::FailedToAttachReceipt);
     }
 
-    let (tail, height) = res.unwrap();
+    Ok(receipts)
+  }
 
-    // Store the genesis block of the view ledger in the ledger store
+  /// fails unless the verified receipts of the endorsers in the current view form a quorum
+  fn check_receipts_quorum(&self, receipts: &Receipts) -> Result<(), CoordinatorError> {
+    check_quorum(&self.verifier_state, receipts)
+  }
+
+  /// returns the receipts of the genesis entry of an existing ledger if they form a quorum, and
+  /// the entry otherwise; fails with a conflict if the ledger was created with a different
+  /// genesis block, which covers its metadata, or with different app bytes
+  async fn read_creation_receipts(
+    &self,
+    handle: &Handle,
+    genesis_block: &Block,
+    app_bytes: &[u8],
+  ) -> Result<Result<Receipts, LedgerEntry>, CoordinatorError> {
+    let info = self
+      .ledger_store
+      .read_ledger_info(handle)
+      .await
+      .map_err(|e| {
+        warn!("Failed to read the info of ledger {} ({:?})", handle, e);
+        CoordinatorError::FailedToReadLedger
+      })?;
+    let ledger_entry = match self.ledger_store.read_ledger_by_index(handle, 0).await {
+      Ok(ledger_entry) => ledger_entry,
+      Err(error) => {
+        warn!(
+          "Failed to read the genesis entry from the ledger store ({:?})",
+          error
+        );
+        return Err(CoordinatorError::FailedToReadLedger);
+      },
+    };
+    if stored_block_hash(&ledger_entry) != genesis_block.hash() || info.app_bytes != app_bytes {
+      return Err(CoordinatorError::LedgerConflict {
+        created_at: info.created_at,
+        metadata: info.metadata,
+      });
+    }
+
+    match self.check_receipts_quorum(ledger_entry.get_receipts()) {
+      Ok(()) => Ok(Ok(ledger_entry.get_receipts().clone())),
+      Err(CoordinatorError::FailedToObtainQuorum) => Ok(Err(ledger_entry)),
+      Err(error) => Err(error),
+    }
+  }
+
+  pub async fn append_ledger(
+    &self,
+    endorsers_opt: Option<Vec<EndorserKey>>,
+    handle_bytes: &[u8],
+    block_bytes: &[u8],
+    expected_height: usize,
+  ) -> Result<(NimbleDigest, Receipts), CoordinatorError> {
+    self
+      .append_ledger_with_deadline(
+        endorsers_opt,
+        handle_bytes,
+        block_bytes,
+        expected_height,
+        Deadline::none(),
+      )
+      .await
+  }
+
+  /// appends to a ledger like `append_ledger`, but gives up once the deadline expires, checking
+  /// it between the phases of the append; an error reports how far the append got, and an append
+  /// that got to persist its block is completed by a retry with the same block and height
+  pub async fn append_ledger_with_deadline(
+    &self,
+    endorsers_opt: Option<Vec<EndorserKey>>,
+    handle_bytes: &[u8],
+    block_bytes: &[u8],
+    expected_height: usize,
+    deadline: Deadline,
+  ) -> Result<(NimbleDigest, Receipts), CoordinatorError> {
+    if expected_height == 0 {
+      return Err(CoordinatorError::InvalidHeight);
+    }
+
+    let _view = self.hold_view()?;
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    telemetry::record_height(expected_height);
+    let data_block = Block::try_new_with_max_size(block_bytes, self.max_block_size)
+      .map_err(|_e| CoordinatorError::BlockTooLarge)?;
+    if endorsers_opt.is_none() && self.pipeline_depth > 1 {
+      return self
+        .append_pipelined(handle, handle_bytes, data_block, expected_height, deadline)
+        .await;
+    }
+
+    // the tail is read, extended in the store and endorsers, and its receipts persisted before
+    // the next append to this ledger starts
+    let _ledger = deadline
+      .run(self.lock_ledger(&handle))
+      .await
+      .ok_or(CoordinatorError::DeadlineExceeded(WriteStage::NotStarted))??;
+
+    // the endorsers must extend the same tail that the ledger store extends
+    let expected_tail = match self
+      .read_append_base(
+        &handle,
+        &data_block,
+        expected_height,
+        endorsers_opt.is_none(),
+        deadline,
+      )
+      .await?
+    {
+      AppendBase::Tail(expected_tail) => expected_tail,
+      AppendBase::Retried(hash_nonces, receipts) => return Ok((hash_nonces, receipts)),
+    };
+    deadline.check(WriteStage::NotStarted)?;
+    let num_bytes = block_bytes.len() as u64;
+    let tenant = self
+      .reserve_tenant_usage(handle_bytes, 0, 1, num_bytes)
+      .await?;
+
+    // the block is persisted before it is endorsed: if the coordinator fails in between, the
+    // store holds a tail without a quorum of receipts, which reconciliation completes; the
+    // endorsers never hold an entry that the store does not. The intent recorded ahead of it
+    // tells the next coordinator which appends it has to resolve
+    let persist_start = Instant::now();
+    if let Err(error) = self
+      .write_intent(&handle, expected_height, &data_block)
+      .await
+    {
+      self.release_tenant_usage(tenant, 0, num_bytes).await;
+      return Err(error);
+    }
     let res = self
       .ledger_store
-      .append_view_ledger(&view_ledger_genesis_block, height + 1)
+      .append_ledger(&handle, &data_block, expected_height)
       .await;
-    if let Err(e) = res {
-      eprintln!(
-        "Failed to append to the view ledger in the ledger store ({:?})",
-        e,
+    let mut persist_time = persist_start.elapsed();
+    if res.is_err() {
+      self.release_tenant_usage(tenant, 0, num_bytes).await;
+      self.commit_intent(&handle, expected_height).await;
+    }
+    let (actual_height, nonces) = match res {
+      Ok(appended) => appended,
+      Err(LedgerStoreError::LedgerError(StorageError::IncorrectConditionalData)) => {
+        return Err(self.condition_failed(&handle).await);
+      },
+      Err(error) => {
+        warn!(
+          "Failed to append to the ledger in the ledger store {:?}",
+          error
+        );
+        return Err(CoordinatorError::FailedToAppendLedger);
+      },
+    };
+    assert!(actual_height == expected_height);
+
+    let hash_nonces = nonces.hash();
+
+    let receipts = {
+      let endorsers = match endorsers_opt {
+        Some(ref endorsers) => endorsers.clone(),
+        None => self.get_endorser_pks(),
+      };
+      deadline.check(WriteStage::Persisted)?;
+      let endorse_start = Instant::now();
+      let res = self
+        .endorser_append_ledger(
+          &endorsers,
+          &handle,
+          actual_height,
+          Some(&expected_tail),
+          &data_block,
+          &nonces,
+          deadline,
+        )
+        .await;
+      self
+        .metrics
+        .observe_append_stage("endorse", endorse_start.elapsed());
+      if res.is_err() {
+        warn!("Failed to append to the ledger in endorsers {:?}", res);
+        return Err(res.unwrap_err());
+      }
+      res.unwrap()
+    };
+    if endorsers_opt.is_none() && self.check_receipts_quorum(&receipts).is_err() {
+      self.metrics.record_quorum_shortfall("append");
+      deadline.check(WriteStage::Persisted)?;
+      // the block is persisted already, so the append is completed rather than abandoned
+      let ledger_entry = LedgerEntry::new(data_block, receipts, Some(nonces));
+      let receipts = self
+        .reconcile_ledger_tail(&handle, expected_height, ledger_entry, deadline)
+        .await
+        .map_err(|e| deadline.exceeded_or(e, WriteStage::Persisted))?;
+      return Ok((hash_nonces, receipts));
+    }
+
+    // receipts that are not stored are signed again by a retry, so a late append stops here
+    deadline.check(WriteStage::Endorsed)?;
+
+    let attach_start = Instant::now();
+    let res = self
+      .ledger_store
+      .attach_ledger_receipts(&handle, expected_height, &receipts)
+      .await;
+    persist_time += attach_start.elapsed();
+    self.metrics.observe_append_stage("persist", persist_time);
+    if res.is_err() {
+      warn!(
+        "Failed to attach ledger receipt to the ledger store ({:?})",
+        res.unwrap_err()
       );
-      return Err(CoordinatorError::FailedToCallLedgerStore);
+      return Err(CoordinatorError::FailedToAttachReceipt);
     }
+    self.commit_intent(&handle, expected_height).await;
+    self.watchers.publish(&handle, expected_height);
 
-    let view_ledger_height = res.unwrap();
+    Ok((hash_nonces, receipts))
+  }
+
+  /// appends like `append_ledger_with_deadline`, but names the append with the request ID of the
+  /// client: a retry with an ID whose append is done returns the height and receipts of that
+  /// append, wherever the tail is by then, rather than failing its condition. An empty ID names
+  /// nothing. Returns the height of the entry, and the hash of its nonces and its receipts
+  pub async fn append_ledger_with_request_id(
+    &self,
+    handle_bytes: &[u8],
+    block_bytes: &[u8],
+    expected_height: usize,
+    request_id: &str,
+    deadline: Deadline,
+  ) -> Result<(usize, NimbleDigest, Receipts), CoordinatorError> {
+    if request_id.is_empty() {
+      let (hash_nonces, receipts) = self
+        .append_ledger_with_deadline(None, handle_bytes, block_bytes, expected_height, deadline)
+        .await?;
+      return Ok((expected_height, hash_nonces, receipts));
+    }
+
+    let handle = NimbleDigest::digest(handle_bytes);
+    let block_hash = NimbleDigest::digest(block_bytes);
+    let tokens = self.read_append_tokens(&handle).await?;
+    if let Some(token) = tokens.iter().find(|t| t.request_id == request_id) {
+      if token.block_hash != block_hash {
+        return Err(CoordinatorError::RequestIdReused {
+          height: token.height,
+        });
+      }
+      let entry = self
+        .read_ledger_by_index_internal(&handle, token.height)
+        .await?;
+      return Ok((
+        token.height,
+        entry.get_nonces().hash(),
+        entry.get_receipts().clone(),
+      ));
+    }
+
+    let (hash_nonces, receipts) = self
+      .append_ledger_with_deadline(None, handle_bytes, block_bytes, expected_height, deadline)
+      .await?;
+
+    // the ID is recorded once the append is done; an append that fails before leaves no ID, and
+    // its retry either appends or finds the block at the tail at the same height
+    match self.lock_ledger(&handle).await {
+      Ok(_ledger) => {
+        let mut tokens = match self.read_append_tokens(&handle).await {
+          Ok(tokens) => tokens,
+          Err(_error) => return Ok((expected_height, hash_nonces, receipts)),
+        };
+        tokens.retain(|t| t.request_id != request_id);
+        tokens.push(AppendToken {
+          request_id: request_id.to_string(),
+          height: expected_height,
+          block_hash,
+        });
+        if tokens.len() > self.request_id_retention {
+          tokens.drain(..tokens.len() - self.request_id_retention);
+        }
+        if let Err(error) = self
+          .ledger_store
+          .write_append_tokens(&handle, &tokens)
+          .await
+        {
+          warn!(
+            "Failed to record the request ID of the append at height {} ({:?})",
+            expected_height, error
+          );
+        }
+      },
+      Err(error) => warn!(
+        "Failed to record the request ID of the append at height {} ({:?})",
+        expected_height, error
+      ),
+    }
 
+    Ok((expected_height, hash_nonces, receipts))
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, CoordinatorError> {
     self
-      .apply_view_change(
-        &existing_endorsers,
-        &new_endorsers,
-        &tail,
-        &view_ledger_genesis_block,
-        view_ledger_height,
-      )
+      .ledger_store
+      .read_append_tokens(handle)
       .await
+      .map_err(|error| {
+        warn!("Failed to read the request IDs of a ledger ({:?})", error);
+        CoordinatorError::FailedToAppendLedger
+      })
   }
 
-  async fn apply_view_change(
+  /// records the intent of appending the block to the ledger at the height before the append
+  /// reaches the ledger store or any endorser
+  async fn write_intent(
     &self,
-    existing_endorsers: &EndorserHostnames,
-    new_endorsers: &EndorserHostnames,
-    view_ledger_entry: &LedgerEntry,
-    view_ledger_genesis_block: &Block,
-    view_ledger_height: usize,
+    handle: &Handle,
+    height: usize,
+    block: &Block,
   ) -> Result<(), CoordinatorError> {
-    // Retrieve the view tail metablock
-    let view_tail_receipts = view_ledger_entry.get_receipts();
-    let view_tail_metablock = if view_tail_receipts.is_empty() {
-      if view_ledger_height != 1 {
-        eprintln!(
-          "cannot get view tail metablock from empty receipts (height = {}",
-          view_ledger_height
-        );
-        return Err(CoordinatorError::UnexpectedError);
-      } else {
-        MetaBlock::default()
-      }
-    } else {
-      let res = view_tail_receipts.get_metablock();
-      match res {
-        Ok(metablock) => metablock,
-        Err(_e) => {
-          eprintln!("faield to retrieve metablock from view receipts");
-          return Err(CoordinatorError::UnexpectedError);
+    let intent = IntentRecord {
+      handle: *handle,
+      height,
+      block_hash: block.hash(),
+      request_id: telemetry::request_id().unwrap_or_default(),
+    };
+    self
+      .ledger_store
+      .write_intent(&intent)
+      .await
+      .map_err(|error| {
+        warn!("Failed to record the intent of an append ({:?})", error);
+        CoordinatorError::FailedToAppendLedger
+      })?;
+    if let Ok(mut pending_intents) = self.pending_intents.lock() {
+      pending_intents.insert((*handle, height));
+    }
+    Ok(())
+  }
+
+  /// marks the intent of the append at the height as resolved; an intent that stays behind is
+  /// resolved again on recovery, so a failure is not returned
+  async fn commit_intent(&self, handle: &Handle, height: usize) {
+    match self.ledger_store.commit_intent(handle, height).await {
+      Ok(()) => {
+        if let Ok(mut pending_intents) = self.pending_intents.lock() {
+          pending_intents.remove(&(*handle, height));
+        }
+      },
+      Err(error) => warn!(
+        "Failed to commit the intent of the append at height {} ({:?})",
+        height, error
+      ),
+    }
+  }
+
+  /// queues an append in the pipeline of its ledger; the request that finds no pipeline leads it
+  /// until its own append is answered, and then hands it to the request of a queued append
+  async fn append_pipelined(
+    &self,
+    handle: Handle,
+    handle_bytes: &[u8],
+    block: Block,
+    height: usize,
+    deadline: Deadline,
+  ) -> Result<(NimbleDigest, Receipts), CoordinatorError> {
+    let (tx, mut rx) = oneshot::channel();
+    let mut append = PipelinedAppend {
+      handle_bytes: handle_bytes.to_vec(),
+      num_bytes: block.to_bytes().len() as u64,
+      block,
+      height,
+      deadline,
+      queued_at: Instant::now(),
+      leads: false,
+      reply: tx,
+    };
+    let mut leads = {
+      let mut pipelines = self
+        .pipelines
+        .lock()
+        .map_err(|_e| CoordinatorError::FailedToAcquireWriteLock)?;
+      match pipelines.entry(handle) {
+        Entry::Occupied(mut queue) => {
+          queue.get_mut().push(append);
+          false
+        },
+        Entry::Vacant(queue) => {
+          append.leads = true;
+          queue.insert(vec![append]);
+          true
         },
       }
     };
 
-    let (finalize_receipts, ledger_tail_maps) = if existing_endorsers.is_empty() {
-      assert!(view_ledger_height == 1);
+    loop {
+      if leads {
+        self.lead_pipeline(handle).await;
+      }
+      match rx.await {
+        Ok(PipelineReply::Done(res)) => return res,
+        Ok(PipelineReply::Lead(next)) => {
+          rx = next;
+          leads = true;
+        },
+        Err(_) => return Err(CoordinatorError::PipelineAborted),
+      }
+    }
+  }
 
-      (Receipts::new(), Vec::new())
-    } else {
-      self
-        .endorser_finalize_state(
-          existing_endorsers,
-          &view_ledger_genesis_block.hash(),
-          view_ledger_height,
-        )
-        .await
+  /// runs the pipeline of a ledger in rounds until the append of the leader is answered
+  async fn lead_pipeline(&self, handle: Handle) {
+    let _leader = PipelineLeader {
+      pipelines: &self.pipelines,
+      handle,
     };
+    loop {
+      let queued = match self.pipelines.lock() {
+        Ok(mut pipelines) => pipelines
+          .get_mut(&handle)
+          .map(std::mem::take)
+          .unwrap_or_default(),
+        Err(_) => return,
+      };
+      let num_queued = queued.len();
+      let left = self.run_pipeline(&handle, queued).await;
+      let answered = !left.iter().any(|append| append.leads);
+      let stalled = !left.is_empty() && left.len() == num_queued;
+      match self.pipelines.lock() {
+        Ok(mut pipelines) => pipelines.entry(handle).or_default().extend(left),
+        Err(_) => return,
+      }
+      if answered {
+        return;
+      }
+      // the appends left wait for the appends below them to be queued
+      if stalled {
+        tokio::time::sleep(Duration::from_millis(PIPELINE_STALL_WAIT_MS)).await;
+      }
+    }
+  }
 
-    // Compute the max cut
-    let max_cut = compute_max_cut(&ledger_tail_maps);
+  /// runs a round of the pipeline of a ledger. The queued appends that extend the tail at
+  /// consecutive heights, up to the pipeline depth, are persisted in order and sent to the
+  /// endorsers as one batch, in which each append names the block of the one below it as its
+  /// tail. They are acknowledged in order, and the first that fails aborts those above it, so
+  /// that no append is acknowledged above one that is not. Appends at or below the tail are
+  /// answered like retries, appends above a gap fail once they waited too long for it to close,
+  /// and the other appends are returned to wait for the next round
+  async fn run_pipeline(
+    &self,
+    handle: &Handle,
+    queued: Vec<PipelinedAppend>,
+  ) -> Vec<PipelinedAppend> {
+    let (expired, mut queued): (Vec<_>, Vec<_>) = queued
+      .into_iter()
+      .partition(|append| append.deadline.is_expired());
+    for append in expired {
+      append.answer(Err(CoordinatorError::DeadlineExceeded(
+        WriteStage::NotStarted,
+      )));
+    }
+    if queued.is_empty() {
+      return queued;
+    }
+    let _ledger = match self.lock_ledger(handle).await {
+      Ok(ledger) => ledger,
+      Err(error) => {
+        for append in queued {
+          append.answer(Err(error.clone()));
+        }
+        return Vec::new();
+      },
+    };
+    let (tail_entry, tail_height) = match self.ledger_store.read_ledger_tail(handle).await {
+      Ok(tail) => tail,
+      Err(error) => {
+        let error = match error {
+          LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => {
+            CoordinatorError::InvalidHandle
+          },
+          error => {
+            warn!(
+              "Failed to read the tail of the ledger in the ledger store {:?}",
+              error
+            );
+            CoordinatorError::FailedToAppendLedger
+          },
+        };
+        for append in queued {
+          append.answer(Err(error.clone()));
+        }
+        return Vec::new();
+      },
+    };
 
-    // Set group identity if necessary
-    let group_identity = if view_ledger_height == 1 {
-      let id = view_ledger_genesis_block.hash();
-      if let Ok(mut vs) = self.verifier_state.write() {
-        vs.set_group_identity(id);
-        id
+    // the sort is stable, so of two appends at the same height the one queued first runs
+    queued.sort_by_key(|append| append.height);
+    let gap_wait = Duration::from_millis(PIPELINE_GAP_WAIT_MS);
+    let mut run = Vec::new();
+    let mut left = Vec::new();
+    for append in queued {
+      let next_height = tail_height + 1 + run.len();
+      if append.height <= tail_height {
+        let res = self
+          .read_append_base(handle, &append.block, append.height, true, append.deadline)
+          .await;
+        append.answer(match res {
+          Ok(AppendBase::Retried(hash_nonces, receipts)) => Ok((hash_nonces, receipts)),
+          Ok(AppendBase::Tail(_)) => Err(self.condition_failed(handle).await),
+          Err(error) => Err(error),
+        });
+      } else if append.height == next_height && run.len() < self.pipeline_depth {
+        run.push(append);
+      } else if append.height > next_height
+        && run.len() < self.pipeline_depth
+        && append.queued_at.elapsed() >= gap_wait
+      {
+        append.answer(Err(CoordinatorError::ConditionFailed {
+          current_height: tail_height,
+          current_tail: tail_entry.get_block_hash(),
+        }));
       } else {
-        return Err(CoordinatorError::FailedToAcquireWriteLock);
+        left.push(append);
       }
-    } else if let Ok(vs) = self.verifier_state.read() {
-      *vs.get_group_identity()
-    } else {
-      return Err(CoordinatorError::FailedToAcquireReadLock);
-    };
-
-    // Initialize new endorsers
-    let initialize_receipts = self
-      .endorser_initialize_state(
-        &group_identity,
-        new_endorsers,
-        max_cut,
-        &view_tail_metablock,
-        &view_ledger_genesis_block.hash(),
-        view_ledger_height,
-      )
-      .await;
+    }
+    if run.is_empty() {
+      return left;
+    }
+    if parse_seal_block(&tail_entry.get_block().to_bytes()).is_some() {
+      for append in run {
+        append.answer(Err(CoordinatorError::LedgerSealed));
+      }
+      return left;
+    }
 
-    // Store the receipts in the view ledger
-    let mut receipts = Receipts::new();
-    receipts.merge_receipts(&finalize_receipts);
-    receipts.merge_receipts(&initialize_receipts);
-    let res = self
-      .ledger_store
-      .attach_view_ledger_receipts(view_ledger_height, &receipts)
-      .await;
-    if res.is_err() {
-      eprintln!(
-        "Failed to attach view ledger receipt in the ledger store ({:?})",
-        res.unwrap_err()
-      );
-      return Err(CoordinatorError::FailedToCallLedgerStore);
+    // the calls to the endorsers are bounded by the latest deadline of the run, and each append
+    // checks its own
+    let deadline = run
+      .iter()
+      .skip(1)
+      .fold(run[0].deadline, |deadline, append| {
+        deadline.or_later(append.deadline)
+      });
+    let mut expected_tail = tail_entry.get_block_hash();
+    if self
+      .check_receipts_quorum(tail_entry.get_receipts())
+      .is_err()
+    {
+      if let Err(error) = self
+        .reconcile_ledger_tail(handle, tail_height, tail_entry, deadline)
+        .await
+      {
+        let error = deadline.exceeded_or(error, WriteStage::NotStarted);
+        for append in run {
+          append.answer(Err(error.clone()));
+        }
+        return left;
+      }
     }
 
-    // Retrieve blocks that need for verifying the view change
-    let cut_diffs = compute_cut_diffs(&ledger_tail_maps);
-    let mut ledger_chunks: Vec<endorser_proto::LedgerChunkEntry> = Vec::new();
-    for cut_diff in &cut_diffs {
-      if cut_diff.low == cut_diff.high {
-        continue;
+    let mut results = run
+      .iter()
+      .map(|_| Err(CoordinatorError::PipelineAborted))
+      .collect::<Vec<_>>();
+    let mut appends = Vec::with_capacity(run.len());
+    let persist_start = Instant::now();
+    for (index, append) in run.iter().enumerate() {
+      if let Err(error) = append.deadline.check(WriteStage::NotStarted) {
+        results[index] = Err(error);
+        break;
       }
-      let mut block_hashes: Vec<Vec<u8>> =
-        Vec::with_capacity((cut_diff.high - cut_diff.low) as usize);
-      let h = NimbleDigest::from_bytes(&cut_diff.handle).unwrap();
-      for index in (cut_diff.low + 1)..=cut_diff.high {
-        let res = self
-          .ledger_store
-          .read_ledger_by_index(&h, index as usize)
-          .await;
-        if let Err(e) = res {
-          eprintln!("Failed to read the ledger store {:?}", e);
-          return Err(CoordinatorError::FailedToCallLedgerStore);
-        }
-        let ledger_entry = res.unwrap();
-        let block_hash = compute_aggregated_block_hash(
-          &ledger_entry.get_block().hash().to_bytes(),
-          &ledger_entry.get_nonces().hash().to_bytes(),
-        );
-        block_hashes.push(block_hash.to_bytes());
+      let tenant = match self
+        .reserve_tenant_usage(&append.handle_bytes, 0, 1, append.num_bytes)
+        .await
+      {
+        Ok(tenant) => tenant,
+        Err(error) => {
+          results[index] = Err(error);
+          break;
+        },
+      };
+      if let Err(error) = self
+        .write_intent(handle, append.height, &append.block)
+        .await
+      {
+        self.release_tenant_usage(tenant, 0, append.num_bytes).await;
+        results[index] = Err(error);
+        break;
       }
-      ledger_chunks.push(endorser_proto::LedgerChunkEntry {
-        handle: cut_diff.handle.clone(),
-        hash: cut_diff.hash.to_bytes(),
-        height: cut_diff.low as u64,
-        block_hashes,
+      let res = self
+        .ledger_store
+        .append_ledger(handle, &append.block, append.height)
+        .await;
+      if res.is_err() {
+        self.release_tenant_usage(tenant, 0, append.num_bytes).await;
+        self.commit_intent(handle, append.height).await;
+      }
+      let (actual_height, nonces) = match res {
+        Ok(appended) => appended,
+        Err(LedgerStoreError::LedgerError(StorageError::IncorrectConditionalData)) => {
+          results[index] = Err(self.condition_failed(handle).await);
+          break;
+        },
+        Err(error) => {
+          warn!(
+            "Failed to append to the ledger in the ledger store {:?}",
+            error
+          );
+          results[index] = Err(CoordinatorError::FailedToAppendLedger);
+          break;
+        },
+      };
+      assert!(actual_height == append.height);
+
+      let hash_nonces = nonces.hash();
+      let block_hash =
+        compute_aggregated_block_hash(&append.block.hash().to_bytes(), &hash_nonces.to_bytes());
+      appends.push(BatchedAppend {
+        index,
+        handle: *handle,
+        block_hash,
+        hash_nonces,
+        height: actual_height,
+        request: endorser_proto::AppendReq {
+          handle: handle.to_bytes().into(),
+          block_hash: block_hash.to_bytes().into(),
+          expected_height: actual_height as u64,
+          block: append.block.to_bytes().into(),
+          nonces: nonces.to_bytes().into(),
+          expected_tail: expected_tail.to_bytes().into(),
+        },
       });
+      expected_tail = block_hash;
     }
+    let mut persist_time = persist_start.elapsed();
 
-    let num_verified_endorsers = self
-      .endorser_verify_view_change(
-        new_endorsers,
-        view_ledger_entry.get_block().clone(),
-        view_ledger_genesis_block.clone(),
-        ledger_tail_maps,
-        ledger_chunks,
-        &receipts,
-      )
+    let endorsers = self.get_endorser_pks();
+    let endorse_start = Instant::now();
+    let all_receipts = self
+      .endorser_append_batch(&endorsers, &appends, deadline)
       .await;
-    if num_verified_endorsers * 2 <= new_endorsers.len() {
-      eprintln!(
-        "insufficient verified endorsers {} * 2 <= {}",
-        num_verified_endorsers,
-        new_endorsers.len()
-      );
+    self
+      .metrics
+      .observe_append_stage("endorse", endorse_start.elapsed());
+
+    let attach_start = Instant::now();
+    for (append, receipts) in appends.iter().zip(all_receipts) {
+      let item = &run[append.index];
+      if let Err(error) = self.check_receipts_quorum(&receipts) {
+        self.metrics.record_quorum_shortfall("append");
+        results[append.index] = Err(item.deadline.exceeded_or(error, WriteStage::Persisted));
+        break;
+      }
+      if let Err(error) = item.deadline.check(WriteStage::Endorsed) {
+        results[append.index] = Err(error);
+        break;
+      }
+      let res = self
+        .ledger_store
+        .attach_ledger_receipts(handle, append.height, &receipts)
+        .await;
+      if let Err(error) = res {
+        warn!(
+          "Failed to attach ledger receipt to the ledger store ({:?})",
+          error
+        );
+        results[append.index] = Err(CoordinatorError::FailedToAttachReceipt);
+        break;
+      }
+      self.commit_intent(handle, append.height).await;
+      self.watchers.publish(handle, append.height);
+      results[append.index] = Ok((append.hash_nonces, receipts));
     }
+    persist_time += attach_start.elapsed();
+    self.metrics.observe_append_stage("persist", persist_time);
 
-    // Apply view change to the verifier state
-    if let Ok(mut vs) = self.verifier_state.write() {
-      if let Err(e) = vs.apply_view_change(
-        &view_ledger_genesis_block.to_bytes(),
-        &receipts.to_bytes(),
-        Some(ATTESTATION_STR.as_bytes()),
-      ) {
-        eprintln!("Failed to apply view change: {:?}", e);
-      }
-    } else {
-      return Err(CoordinatorError::FailedToAcquireWriteLock);
+    for (append, res) in run.into_iter().zip(results) {
+      append.answer(res);
     }
+    left
+  }
 
-    // Disconnect existing endorsers
-    self.disconnect_endorsers(existing_endorsers).await;
+  /// seals a ledger by appending a seal block, so that the receipts of the final entry attest
+  /// that the ledger was closed; later appends fail with `LedgerSealed`. Sealing a sealed ledger
+  /// returns the entry that sealed it. Returns the height, block, nonces hash and receipts of
+  /// that entry
+  pub async fn seal_ledger(
+    &self,
+    handle_bytes: &[u8],
+    deadline: Deadline,
+  ) -> Result<(usize, Block, NimbleDigest, Receipts), CoordinatorError> {
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    loop {
+      deadline.check(WriteStage::NotStarted)?;
+      let (tail_entry, height) = match self.ledger_store.read_ledger_tail(&handle).await {
+        Ok(tail) => tail,
+        Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+          return Err(CoordinatorError::InvalidHandle);
+        },
+        Err(error) => {
+          warn!(
+            "Failed to read the tail of the ledger from the ledger store {:?}",
+            error
+          );
+          return Err(CoordinatorError::FailedToReadLedger);
+        },
+      };
 
-    Ok(())
-  }
+      // a seal at the tail is appended again, which returns its receipts
+      let (seal_block, seal_height) =
+        if parse_seal_block(&tail_entry.get_block().to_bytes()).is_some() {
+          (tail_entry.get_block().clone(), height)
+        } else {
+          let sealed_at = self.clock.now_ms();
+          (compute_seal_block(sealed_at), height + 1)
+        };
 
-  pub async fn reset_ledger_store(&self) {
-    let res = self.ledger_store.reset_store().await;
-    assert!(res.is_ok());
+      match self
+        .append_ledger_with_deadline(
+          None,
+          handle_bytes,
+          &seal_block.to_bytes(),
+          seal_height,
+          deadline,
+        )
+        .await
+      {
+        Ok((hash_nonces, receipts)) => return Ok((seal_height, seal_block, hash_nonces, receipts)),
+        // another append or seal got there first
+        Err(CoordinatorError::ConditionFailed { .. }) | Err(CoordinatorError::LedgerSealed) => {},
+        Err(e) => return Err(e),
+      }
+    }
   }
 
-  pub async fn create_ledger(
+  /// deletes the contents of the blocks of a ledger below `before_height`, keeping their hashes,
+  /// nonces and receipts so that proofs over them still verify; reads of the purged entries fail
+  /// with `ContentPurged`. The tail is never purged
+  pub async fn purge_ledger_blocks(
     &self,
-    endorsers_opt: Option<Vec<Vec<u8>>>,
     handle_bytes: &[u8],
-    block_bytes: &[u8],
-  ) -> Result<Receipts, CoordinatorError> {
+    before_height: usize,
+  ) -> Result<(), CoordinatorError> {
+    self.check_serving()?;
     let handle = NimbleDigest::digest(handle_bytes);
-    let genesis_block = Block::new(block_bytes);
-
-    let hash_block = genesis_block.hash();
-    let hash_nonces = Nonces::new().hash();
-    let block_hash = compute_aggregated_block_hash(&hash_block.to_bytes(), &hash_nonces.to_bytes());
+    telemetry::record_handle(&handle);
+    let height = match self.ledger_store.read_ledger_tail(&handle).await {
+      Ok((_, height)) => height,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        return Err(CoordinatorError::InvalidHandle);
+      },
+      Err(error) => {
+        warn!(
+          "Failed to read the tail of the ledger from the ledger store {:?}",
+          error
+        );
+        return Err(CoordinatorError::FailedToReadLedger);
+      },
+    };
+    if before_height > height {
+      return Err(CoordinatorError::OutOfRange {
+        current_height: height,
+      });
+    }
 
-    let res = self
+    self
       .ledger_store
-      .create_ledger(&handle, genesis_block.clone())
-      .await;
-    if res.is_err() {
-      eprintln!(
-        "Failed to create ledger in the ledger store ({:?})",
-        res.unwrap_err()
-      );
-      return Err(CoordinatorError::FailedToCreateLedger);
+      .purge_ledger_blocks(&handle, before_height)
+      .await
+      .map_err(|error| {
+        warn!(
+          "Failed to purge the blocks of the ledger in the ledger store {:?}",
+          error
+        );
+        CoordinatorError::FailedToCallLedgerStore
+      })
+  }
+
+  /// appends a block to each ledger of a batch, sending the appends to the endorsers together;
+  /// the batch is not atomic: each item is appended or fails on its own, and the result of each
+  /// item is returned in the order of the items. The items that the deadline cuts short report
+  /// how far they got
+  pub async fn append_ledger_batch(
+    &self,
+    items: &[AppendBatchItem],
+    deadline: Deadline,
+  ) -> Result<Vec<Result<(NimbleDigest, Receipts), CoordinatorError>>, CoordinatorError> {
+    if items.len() > MAX_APPEND_BATCH_SIZE {
+      return Err(CoordinatorError::BatchTooLarge);
     }
 
-    // Make a request to the endorsers for NewLedger using the handle which returns a signature.
-    let receipts = {
-      let endorsers = match endorsers_opt {
-        Some(ref endorsers) => endorsers.clone(),
-        None => self.get_endorser_pks(),
+    let _view = self.hold_view()?;
+    let handles = items
+      .iter()
+      .map(|item| NimbleDigest::digest(&item.handle_bytes))
+      .collect::<Vec<_>>();
+    let mut num_items_per_handle = HashMap::new();
+    for handle in &handles {
+      *num_items_per_handle.entry(*handle).or_insert(0) += 1;
+    }
+
+    let mut results = items
+      .iter()
+      .map(|_| Err(CoordinatorError::FailedToAppendLedger))
+      .collect::<Vec<_>>();
+    let mut blocks = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+      let res = if item.expected_height == 0 {
+        Err(CoordinatorError::InvalidHeight)
+      } else if num_items_per_handle[&handles[index]] > 1 {
+        // the appends of an item are conditioned on the tail of its ledger before the batch
+        Err(CoordinatorError::DuplicateHandleInBatch)
+      } else {
+        Block::try_new_with_max_size(&item.block_bytes, self.max_block_size)
+          .map_err(|_e| CoordinatorError::BlockTooLarge)
+      };
+      match res {
+        Ok(block) => blocks.push((index, block)),
+        Err(error) => results[index] = Err(error),
+      }
+    }
+
+    // the ledgers are locked in the order of their handles, so batches that share ledgers cannot
+    // wait for each other in a cycle
+    blocks.sort_by_key(|(index, _block)| handles[*index]);
+    let mut _ledgers = Vec::with_capacity(blocks.len());
+    for (index, _block) in &blocks {
+      let ledger = deadline
+        .run(self.lock_ledger(&handles[*index]))
+        .await
+        .ok_or(CoordinatorError::DeadlineExceeded(WriteStage::NotStarted))??;
+      _ledgers.push(ledger);
+    }
+
+    let mut appends = Vec::with_capacity(blocks.len());
+    for (index, data_block) in blocks {
+      let handle = handles[index];
+      let expected_height = items[index].expected_height;
+      if let Err(error) = deadline.check(WriteStage::NotStarted) {
+        results[index] = Err(error);
+        continue;
+      }
+      let res = self
+        .read_append_base(&handle, &data_block, expected_height, true, deadline)
+        .await;
+      let expected_tail = match res {
+        Ok(AppendBase::Tail(expected_tail)) => expected_tail,
+        Ok(AppendBase::Retried(hash_nonces, receipts)) => {
+          results[index] = Ok((hash_nonces, receipts));
+          continue;
+        },
+        Err(error) => {
+          results[index] = Err(error);
+          continue;
+        },
+      };
+      let num_bytes = items[index].block_bytes.len() as u64;
+      let tenant = match self
+        .reserve_tenant_usage(&items[index].handle_bytes, 0, 1, num_bytes)
+        .await
+      {
+        Ok(tenant) => tenant,
+        Err(error) => {
+          results[index] = Err(error);
+          continue;
+        },
       };
+
       let res = self
-        .endorser_create_ledger(&endorsers, &handle, &block_hash, genesis_block)
+        .ledger_store
+        .append_ledger(&handle, &data_block, expected_height)
         .await;
       if res.is_err() {
-        eprintln!("Failed to create ledger in endorsers ({:?})", res);
-        return Err(res.unwrap_err());
+        self.release_tenant_usage(tenant, 0, num_bytes).await;
       }
-      res.unwrap()
-    };
+      let (actual_height, nonces) = match res {
+        Ok(appended) => appended,
+        Err(LedgerStoreError::LedgerError(StorageError::IncorrectConditionalData)) => {
+          results[index] = Err(self.condition_failed(&handle).await);
+          continue;
+        },
+        Err(error) => {
+          warn!(
+            "Failed to append to the ledger in the ledger store {:?}",
+            error
+          );
+          continue;
+        },
+      };
+      assert!(actual_height == expected_height);
+
+      let hash_nonces = nonces.hash();
+      let block_hash =
+        compute_aggregated_block_hash(&data_block.hash().to_bytes(), &hash_nonces.to_bytes());
+      appends.push(BatchedAppend {
+        index,
+        handle,
+        block_hash,
+        hash_nonces,
+        height: actual_height,
+        request: endorser_proto::AppendReq {
+          handle: handle.to_bytes().into(),
+          block_hash: block_hash.to_bytes().into(),
+          expected_height: actual_height as u64,
+          block: data_block.to_bytes().into(),
+          nonces: nonces.to_bytes().into(),
+          expected_tail: expected_tail.to_bytes().into(),
+        },
+      });
+    }
 
-    // Store the receipt
-    let res = self
-      .ledger_store
-      .attach_ledger_receipts(&handle, 0, &receipts)
+    let endorsers = self.get_endorser_pks();
+    let all_receipts = self
+      .endorser_append_batch(&endorsers, &appends, deadline)
       .await;
-    if res.is_err() {
-      eprintln!(
-        "Failed to attach ledger receipt to the ledger store ({:?})",
-        res
-      );
-      return Err(CoordinatorError::FailedToAttachReceipt);
+    for (append, receipts) in appends.iter().zip(all_receipts) {
+      if let Err(error) = self.check_receipts_quorum(&receipts) {
+        self.metrics.record_quorum_shortfall("append");
+        results[append.index] = Err(deadline.exceeded_or(error, WriteStage::Persisted));
+        continue;
+      }
+      if let Err(error) = deadline.check(WriteStage::Endorsed) {
+        results[append.index] = Err(error);
+        continue;
+      }
+      let res = self
+        .ledger_store
+        .attach_ledger_receipts(&append.handle, append.height, &receipts)
+        .await;
+      results[append.index] = match res {
+        Ok(()) => {
+          self.watchers.publish(&append.handle, append.height);
+          Ok((append.hash_nonces, receipts))
+        },
+        Err(error) => {
+          warn!(
+            "Failed to attach ledger receipt to the ledger store ({:?})",
+            error
+          );
+          Err(CoordinatorError::FailedToAttachReceipt)
+        },
+      };
     }
 
-    Ok(receipts)
+    Ok(results)
   }
 
-  pub async fn append_ledger(
+  /// reads the tail of a ledger that an append at `expected_height` builds on. An append extends
+  /// only an endorsed tail, so a tail without a quorum of receipts is reconciled first if
+  /// `reconcile` is set; a retried append whose block the store already holds is completed
+  async fn read_append_base(
     &self,
-    endorsers_opt: Option<Vec<Vec<u8>>>,
-    handle_bytes: &[u8],
-    block_bytes: &[u8],
+    handle: &Handle,
+    data_block: &Block,
     expected_height: usize,
-  ) -> Result<(NimbleDigest, Receipts), CoordinatorError> {
-    if expected_height == 0 {
-      return Err(CoordinatorError::InvalidHeight);
-    }
-
-    let handle = NimbleDigest::digest(handle_bytes);
-    let data_block = Block::new(block_bytes);
-
-    let res = self
-      .ledger_store
-      .append_ledger(&handle, &data_block, expected_height)
-      .await;
-    if res.is_err() {
-      eprintln!(
-        "Failed to append to the ledger in the ledger store {:?}",
-        res.unwrap_err()
-      );
-      return Err(CoordinatorError::FailedToAppendLedger);
+    reconcile: bool,
+    deadline: Deadline,
+  ) -> Result<AppendBase, CoordinatorError> {
+    let (ledger_entry, height) = match self.ledger_store.read_ledger_tail(handle).await {
+      Ok(tail) => tail,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        return Err(CoordinatorError::InvalidHandle);
+      },
+      Err(error) => {
+        warn!(
+          "Failed to read the tail of the ledger in the ledger store {:?}",
+          error
+        );
+        return Err(CoordinatorError::FailedToAppendLedger);
+      },
+    };
+
+    if height == expected_height && ledger_entry.get_block().to_bytes() == data_block.to_bytes() {
+      let hash_nonces = ledger_entry.get_nonces().hash();
+      let receipts = if self
+        .check_receipts_quorum(ledger_entry.get_receipts())
+        .is_ok()
+      {
+        ledger_entry.get_receipts().clone()
+      } else {
+        self
+          .reconcile_ledger_tail(handle, height, ledger_entry, deadline)
+          .await
+          .map_err(|e| deadline.exceeded_or(e, WriteStage::Persisted))?
+      };
+      return Ok(AppendBase::Retried(hash_nonces, receipts));
     }
 
-    let (actual_height, nonces) = res.unwrap();
-    assert!(actual_height == expected_height);
+    if parse_seal_block(&ledger_entry.get_block().to_bytes()).is_some() {
+      return Err(CoordinatorError::LedgerSealed);
+    }
 
-    let hash_block = data_block.hash();
-    let hash_nonces = nonces.hash();
-    let block_hash = compute_aggregated_block_hash(&hash_block.to_bytes(), &hash_nonces.to_bytes());
+    if height + 1 != expected_height {
+      return Err(CoordinatorError::ConditionFailed {
+        current_height: height,
+        current_tail: ledger_entry.get_block_hash(),
+      });
+    }
 
-    let receipts = {
-      let endorsers = match endorsers_opt {
-        Some(endorsers) => endorsers,
-        None => self.get_endorser_pks(),
-      };
-      let res = self
-        .endorser_append_ledger(
-          &endorsers,
-          &handle,
-          &block_hash,
-          actual_height,
-          data_block,
-          nonces,
-        )
-        .await;
-      if res.is_err() {
-        eprintln!("Failed to append to the ledger in endorsers {:?}", res);
-        return Err(res.unwrap_err());
-      }
-      res.unwrap()
-    };
+    let tail = ledger_entry.get_block_hash();
+    if reconcile
+      && self
+        .check_receipts_quorum(ledger_entry.get_receipts())
+        .is_err()
+    {
+      self
+        .reconcile_ledger_tail(handle, height, ledger_entry, deadline)
+        .await
+        .map_err(|e| deadline.exceeded_or(e, WriteStage::NotStarted))?;
+    }
+    Ok(AppendBase::Tail(tail))
+  }
 
-    let res = self
-      .ledger_store
-      .attach_ledger_receipts(&handle, expected_height, &receipts)
-      .await;
-    if res.is_err() {
-      eprintln!(
-        "Failed to attach ledger receipt to the ledger store ({:?})",
-        res.unwrap_err()
-      );
-      return Err(CoordinatorError::FailedToAttachReceipt);
+  /// reports the current tail of a ledger after a conditional append failed to extend it
+  async fn condition_failed(&self, handle: &Handle) -> CoordinatorError {
+    match self.ledger_store.read_ledger_tail(handle).await {
+      Ok((ledger_entry, _)) if parse_seal_block(&ledger_entry.get_block().to_bytes()).is_some() => {
+        CoordinatorError::LedgerSealed
+      },
+      Ok((ledger_entry, current_height)) => CoordinatorError::ConditionFailed {
+        current_height,
+        current_tail: ledger_entry.get_block_hash(),
+      },
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        CoordinatorError::InvalidHandle
+      },
+      Err(error) => {
+        warn!(
+          "Failed to read the tail of the ledger in the ledger store {:?}",
+          error
+        );
+        CoordinatorError::FailedToAppendLedger
+      },
     }
+  }
 
-    Ok((hash_nonces, receipts))
+  /// a nonce for a read of the coordinator's own
+  fn new_nonce(&self) -> Nonce {
+    let bytes = match self.rng.lock() {
+      Ok(mut rng) => rng.gen::<[u8; 16]>(),
+      Err(_) => random::<[u8; 16]>(),
+    };
+    Nonce::try_from_bytes(&bytes).unwrap()
   }
 
   async fn read_ledger_tail_internal(
@@ -1867,15 +5323,16 @@ impl CoordinatorState {
     nonce_bytes: &[u8],
   ) -> Result<LedgerEntry, CoordinatorError> {
     let nonce = {
-      let nonce_op = Nonce::new(nonce_bytes);
+      let nonce_op = Nonce::try_from_bytes(nonce_bytes);
       if nonce_op.is_err() {
-        eprintln!("Nonce is invalide");
+        warn!("Nonce is invalide");
         return Err(CoordinatorError::InvalidNonce);
       }
       nonce_op.unwrap().to_owned()
     };
 
     let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
 
     let mut nonce_attached = false;
     let mut nonce_attached_height = 0;
@@ -1886,9 +5343,10 @@ impl CoordinatorState {
         Err(error) => match error {
           CoordinatorError::FailedToObtainQuorum => {
             if !nonce_attached {
+              self.metrics.record_quorum_shortfall("read");
               let res = self.ledger_store.attach_ledger_nonce(&handle, &nonce).await;
               if res.is_err() {
-                eprintln!(
+                warn!(
                   "Failed to attach the nonce for reading ledger tail {:?}",
                   res.unwrap_err()
                 );
@@ -1901,7 +5359,18 @@ impl CoordinatorState {
               .read_ledger_by_index_internal(&handle, nonce_attached_height)
               .await
             {
-              Ok(ledger_entry) => return Ok(ledger_entry),
+              // the receipts of this entry cover its nonces, so they are fresh only if the
+              // client's nonce is among them
+              Ok(ledger_entry) => {
+                if !ledger_entry.get_nonces().contains(&nonce) {
+                  warn!(
+                    "The nonce is missing from the entry at height {} of ledger {}",
+                    nonce_attached_height, handle
+                  );
+                  return Err(CoordinatorError::FailedToAttachNonce);
+                }
+                return Ok(ledger_entry);
+              },
               Err(error) => match error {
                 CoordinatorError::FailedToObtainQuorum | CoordinatorError::InvalidHeight => {
                   continue;
@@ -1920,17 +5389,620 @@ impl CoordinatorState {
     }
   }
 
+  /// reads the tails of the ledgers `handles_bytes` as of one moment, each attested by the
+  /// endorsers with the same nonce. The locks of the ledgers are taken in the order of their
+  /// handles, like those of a batch, so snapshots and batches that share ledgers cannot wait for
+  /// each other in a cycle, and they are held until the last tail is read, so no append lands on
+  /// any of the ledgers in between. Unlike `read_ledger_tail`, a tail whose receipts lack a quorum
+  /// fails the read rather than waiting for the next append, which the locks hold back
+  pub async fn snapshot_read(
+    &self,
+    handles_bytes: &[Vec<u8>],
+    nonce_bytes: &[u8],
+  ) -> Result<Snapshot, CoordinatorError> {
+    if handles_bytes.len() > MAX_SNAPSHOT_READ_SIZE {
+      return Err(CoordinatorError::SnapshotTooLarge);
+    }
+    let nonce = Nonce::try_from_bytes(nonce_bytes).map_err(|_e| CoordinatorError::InvalidNonce)?;
+    let handles = handles_bytes
+      .iter()
+      .map(|handle_bytes| NimbleDigest::digest(handle_bytes))
+      .collect::<Vec<_>>();
+    let mut order = (0..handles.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| handles[*index]);
+    if order.windows(2).any(|w| handles[w[0]] == handles[w[1]]) {
+      return Err(CoordinatorError::DuplicateHandleInSnapshot);
+    }
+
+    let deadline = Deadline::after(SNAPSHOT_LOCK_TIMEOUT);
+    let mut ledgers = Vec::with_capacity(order.len());
+    for index in &order {
+      let ledger = deadline
+        .run(self.lock_ledger(&handles[*index]))
+        .await
+        .ok_or(CoordinatorError::SnapshotLocksUnavailable)??;
+      ledgers.push(ledger);
+    }
+    let locked_at = self.clock.now_ms();
+
+    let mut entries = Vec::with_capacity(handles.len());
+    let mut tails = Vec::with_capacity(handles.len());
+    for handle in &handles {
+      let captured_at = self.clock.now_ms();
+      let ledger_entry = self.read_ledger_tail_internal(handle, &nonce).await?;
+      // all receipts of a tail cover the same metablock, which carries the height
+      let metablock = ledger_entry
+        .get_receipts()
+        .get_metablock()
+        .map_err(|_e| CoordinatorError::FailedToReadLedger)?;
+      tails.push((*handle, metablock.hash(), metablock.get_height()));
+      entries.push(SnapshotEntry {
+        ledger_entry,
+        height: metablock.get_height(),
+        captured_at,
+      });
+    }
+    let released_at = self.clock.now_ms();
+    drop(ledgers);
+
+    Ok(Snapshot {
+      entries,
+      digest: compute_snapshot_digest(&nonce, &tails),
+      locked_at,
+      released_at,
+    })
+  }
+
+  /// reads the latest entry of a ledger whose append completed from the ledger store, without
+  /// contacting the endorsers; a tail that an append persisted but has not collected receipts for
+  /// yet is skipped, so the entry carries a quorum of receipts. Nothing attests that the entry is
+  /// still the tail. Returns the entry and its height
+  pub async fn read_cached_ledger_tail(
+    &self,
+    handle_bytes: &[u8],
+  ) -> Result<(LedgerEntry, usize), CoordinatorError> {
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    let (ledger_entry, height) = match self.ledger_store.read_ledger_tail(&handle).await {
+      Ok(tail) => tail,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        return Err(CoordinatorError::LedgerNotFound);
+      },
+      Err(error) => {
+        warn!("Failed to read the ledger {} ({:?})", handle, error);
+        return Err(CoordinatorError::FailedToCallLedgerStore);
+      },
+    };
+    if self
+      .check_receipts_quorum(ledger_entry.get_receipts())
+      .is_ok()
+    {
+      return Ok((ledger_entry, height));
+    }
+
+    // appends to a ledger are serialized, so only the tail can lack its receipts
+    if height == 0 {
+      return Err(CoordinatorError::FailedToObtainQuorum);
+    }
+    let ledger_entry = self
+      .read_ledger_by_index_internal(&handle, height - 1)
+      .await?;
+    self.check_receipts_quorum(ledger_entry.get_receipts())?;
+    Ok((ledger_entry, height - 1))
+  }
+
+  /// reads the height, tail hash, and info of a ledger from the ledger store without contacting
+  /// the endorsers, so the answer is unattested
+  pub async fn read_ledger_summary(
+    &self,
+    handle_bytes: &[u8],
+  ) -> Result<LedgerSummary, CoordinatorError> {
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    self.read_ledger_summary_internal(&handle).await
+  }
+
+  async fn read_ledger_summary_internal(
+    &self,
+    handle: &Handle,
+  ) -> Result<LedgerSummary, CoordinatorError> {
+    let map_error = |error: LedgerStoreError| match error {
+      LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => {
+        CoordinatorError::LedgerNotFound
+      },
+      _ => {
+        warn!("Failed to read the ledger {} ({:?})", handle, error);
+        CoordinatorError::FailedToCallLedgerStore
+      },
+    };
+
+    let info = self
+      .ledger_store
+      .read_ledger_info(handle)
+      .await
+      .map_err(map_error)?;
+    let (tail, height) = self
+      .ledger_store
+      .read_ledger_tail(handle)
+      .await
+      .map_err(map_error)?;
+
+    Ok(LedgerSummary {
+      height,
+      tail_hash: tail.get_block_hash(),
+      info,
+    })
+  }
+
+  /// lists up to `page_size` ledgers that sort after `start_after` in the ledger store, keeping
+  /// only those whose handle bytes start with `handle_prefix` and whose handles were derived from
+  /// app bytes starting with `app_prefix` (if it is not empty); a tenant's listing counts only
+  /// the ledgers of the tenant. The canary ledgers are left out unless `handle_prefix` is in their
+  /// namespace. Like `read_ledger_summary`, none of the answer is attested. Ledgers created
+  /// concurrently show up on a later page if they sort after the current position and are
+  /// skipped otherwise, but a listing never repeats or skips a ledger that existed when it began.
+  pub async fn list_ledgers(
+    &self,
+    start_after: Option<Handle>,
+    page_size: usize,
+    handle_prefix: &[u8],
+    app_prefix: &[u8],
+  ) -> Result<LedgerPage, CoordinatorError> {
+    let mut ledgers = Vec::new();
+    let mut cursor = start_after;
+    let mut num_scanned = 0;
+    let mut exhausted = false;
+
+    // with a prefix, a page may need to look at many ledgers, so the scan is bounded and a page
+    // may come back short of page_size even if more ledgers follow
+    while ledgers.len() < page_size && num_scanned < LIST_LEDGERS_MAX_SCAN {
+      let limit = std::cmp::min(
+        page_size - ledgers.len(),
+        LIST_LEDGERS_MAX_SCAN - num_scanned,
+      );
+      let handles = self
+        .ledger_store
+        .list_handles(cursor.as_ref(), limit)
+        .await
+        .map_err(|e| {
+          warn!("Failed to list the handles in the ledger store {:?}", e);
+          CoordinatorError::FailedToCallLedgerStore
+        })?;
+
+      for handle in &handles {
+        let summary = self.read_ledger_summary_internal(handle).await?;
+        let canary = summary.info.handle_bytes.starts_with(CANARY_PREFIX)
+          && !handle_prefix.starts_with(CANARY_PREFIX);
+        if summary.info.handle_bytes.starts_with(handle_prefix)
+          && summary.info.app_bytes.starts_with(app_prefix)
+          && !canary
+        {
+          ledgers.push(summary);
+        }
+      }
+      num_scanned += handles.len();
+
+      if handles.len() < limit {
+        exhausted = true;
+        break;
+      }
+      cursor = handles.last().cloned();
+    }
+
+    let tenant = self.tenants.lock().await.find(handle_prefix);
+    let num_ledgers_estimate = match tenant {
+      Some(tenant) => Some(self.read_tenant(&tenant).await?.num_ledgers as usize),
+      None => self
+        .ledger_store
+        .estimate_num_ledgers()
+        .await
+        .map_err(|e| {
+          warn!("Failed to estimate the number of ledgers {:?}", e);
+          CoordinatorError::FailedToCallLedgerStore
+        })?,
+    };
+
+    Ok(LedgerPage {
+      ledgers,
+      next_start_after: if exhausted { None } else { cursor },
+      num_ledgers_estimate,
+    })
+  }
+
+  /// reads the entries at heights `from..=to` of a ledger along with a proof that chains them to
+  /// the attested tail of the ledger
+  pub async fn read_ledger_range(
+    &self,
+    handle_bytes: &[u8],
+    from: usize,
+    to: usize,
+  ) -> Result<LedgerRange, CoordinatorError> {
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    let (tail_entry, height) = match self.ledger_store.read_ledger_tail(&handle).await {
+      Ok(tail) => tail,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        return Err(CoordinatorError::InvalidHandle);
+      },
+      Err(error) => {
+        warn!(
+          "Failed to read the tail of the ledger from the ledger store {:?}",
+          error
+        );
+        return Err(CoordinatorError::FailedToReadLedger);
+      },
+    };
+    if from > to || to > height {
+      return Err(CoordinatorError::OutOfRange {
+        current_height: height,
+      });
+    }
+
+    // the latest appends may still await their receipts, so the attested tail can be below the
+    // tail of the store
+    let mut block_hashes = Vec::new();
+    let mut tail_receipts = None;
+    let mut ledger_entry = tail_entry;
+    let mut index = height;
+    while index > to {
+      if tail_receipts.is_none()
+        && self
+          .check_receipts_quorum(ledger_entry.get_receipts())
+          .is_ok()
+      {
+        tail_receipts = Some(ledger_entry.get_receipts().clone());
+      }
+      if tail_receipts.is_some() {
+        block_hashes.push(ledger_entry.get_block_hash());
+      }
+      index -= 1;
+      ledger_entry = self.read_ledger_entry(&handle, index).await?;
+    }
+    block_hashes.reverse();
+
+    let mut entries = vec![ledger_entry];
+    for index in (from..to).rev() {
+      entries.push(self.read_ledger_entry(&handle, index).await?);
+    }
+    entries.reverse();
+    for (index, entry) in (from..).zip(entries.iter()) {
+      check_not_purged(index, entry)?;
+    }
+
+    let tail_receipts = match tail_receipts {
+      Some(receipts) => receipts,
+      None => {
+        let receipts = entries.last().unwrap().get_receipts();
+        self.check_receipts_quorum(receipts)?;
+        receipts.clone()
+      },
+    };
+
+    let checkpoint = if from == 0 {
+      None
+    } else {
+      Some(self.read_ledger_metablock(&handle, from - 1).await?)
+    };
+
+    Ok(LedgerRange {
+      entries,
+      checkpoint,
+      block_hashes,
+      tail_receipts,
+    })
+  }
+
+  /// checks the entries of `range`, which start at height `from` of ledger `handle_bytes`, against
+  /// the receipts of its attested tail with the current verifier state, as a client checks the
+  /// proof of a read; returns the height of the attested tail
+  pub fn verify_ledger_range(
+    &self,
+    handle_bytes: &[u8],
+    from: usize,
+    range: &LedgerRange,
+  ) -> Result<usize, CoordinatorError> {
+    let entries = range
+      .entries
+      .iter()
+      .map(|entry| (entry.get_block().to_bytes(), entry.get_nonces().to_bytes()))
+      .collect::<Vec<_>>();
+    let checkpoint = range
+      .checkpoint
+      .as_ref()
+      .map(|checkpoint| checkpoint.to_bytes())
+      .unwrap_or_default();
+    let block_hashes = range
+      .block_hashes
+      .iter()
+      .map(|block_hash| block_hash.to_bytes())
+      .collect::<Vec<_>>();
+    let vs = self
+      .verifier_state
+      .read()
+      .map_err(|_e| CoordinatorError::FailedToAcquireReadLock)?;
+    let height = vs.verify_read_range(
+      handle_bytes,
+      from,
+      &entries,
+      &checkpoint,
+      &block_hashes,
+      &range.tail_receipts.to_bytes(),
+    )?;
+    Ok(height)
+  }
+
+  /// streams the entries of ledger `handle_bytes` from `from_height` on into `tx`: the committed
+  /// ones first, and then each one as it commits, with a heartbeat whenever none commits for
+  /// `WATCH_HEARTBEAT_INTERVAL`. The entries are read from the ledger store at the pace of the
+  /// client, so appends never wait for it. Returns once the client is gone, or after queueing the
+  /// error that ends the stream
+  pub async fn watch_ledger(
+    &self,
+    handle_bytes: &[u8],
+    from_height: usize,
+    include_blocks: bool,
+    tx: mpsc::Sender<Result<WatchEvent, CoordinatorError>>,
+  ) {
+    // a slot of the queue is kept for the error that ends the stream, which a client that fell
+    // behind would otherwise never receive
+    let permit = match tx.clone().reserve_owned().await {
+      Ok(permit) => permit,
+      Err(_closed) => return,
+    };
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    let res = self
+      .stream_ledger(&handle, from_height, include_blocks, &tx)
+      .await;
+    if let Err(error) = res {
+      permit.send(Err(error));
+    }
+  }
+
+  async fn stream_ledger(
+    &self,
+    handle: &Handle,
+    from_height: usize,
+    include_blocks: bool,
+    tx: &mpsc::Sender<Result<WatchEvent, CoordinatorError>>,
+  ) -> Result<(), CoordinatorError> {
+    let mut shutdown = self.shutdown.subscribe();
+    if *shutdown.borrow_and_update() != ShutdownPhase::Running {
+      return Err(CoordinatorError::ShuttingDown);
+    }
+    // subscribed before the store is read, so that no entry commits unnoticed in between
+    let mut published = self.watchers.subscribe(handle);
+    let mut committed = self.read_committed_height(handle).await?;
+    if from_height > committed + 1 {
+      return Err(CoordinatorError::OutOfRange {
+        current_height: committed,
+      });
+    }
+
+    let mut next_height = from_height;
+    loop {
+      committed = committed.max(*published.borrow_and_update());
+      while next_height <= committed {
+        let ledger_entry = self.read_ledger_entry(handle, next_height).await?;
+        let event = WatchEvent::Entry {
+          height: next_height,
+          block_hash: ledger_entry.get_block_hash(),
+          entry: if include_blocks {
+            Some(ledger_entry)
+          } else {
+            None
+          },
+        };
+        if !push_watch_event(tx, event, next_height).await? {
+          return Ok(());
+        }
+        next_height += 1;
+      }
+
+      tokio::select! {
+        res = published.changed() => {
+          // the sender of a ledger is kept while it has receivers
+          res.map_err(|_e| CoordinatorError::UnexpectedError)?;
+        },
+        // the phase only moves away from running, and streams end with it so that the server
+        // stops without waiting for them
+        _ = shutdown.changed() => return Err(CoordinatorError::ShuttingDown),
+        () = tokio::time::sleep(WATCH_HEARTBEAT_INTERVAL) => {
+          let event = WatchEvent::Heartbeat { height: committed };
+          if !push_watch_event(tx, event, next_height).await? {
+            return Ok(());
+          }
+        },
+        () = tx.closed() => return Ok(()),
+      }
+    }
+  }
+
+  /// the height of the highest entry of the ledger with a quorum of receipts; the latest appends
+  /// may still await their receipts
+  async fn read_committed_height(&self, handle: &Handle) -> Result<usize, CoordinatorError> {
+    let (mut ledger_entry, mut height) = match self.ledger_store.read_ledger_tail(handle).await {
+      Ok(tail) => tail,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)) => {
+        return Err(CoordinatorError::InvalidHandle);
+      },
+      Err(error) => {
+        warn!(
+          "Failed to read the tail of the ledger from the ledger store {:?}",
+          error
+        );
+        return Err(CoordinatorError::FailedToReadLedger);
+      },
+    };
+    loop {
+      if self
+        .check_receipts_quorum(ledger_entry.get_receipts())
+        .is_ok()
+      {
+        return Ok(height);
+      }
+      // a ledger whose genesis entry lacks a quorum is still being created
+      if height == 0 {
+        return Err(CoordinatorError::FailedToObtainQuorum);
+      }
+      height -= 1;
+      ledger_entry = self.read_ledger_entry(handle, height).await?;
+    }
+  }
+
+  /// streams the export bundle of ledger `handle_bytes` into `tx` (see `ledger::bundle`): the
+  /// entries up to the attested tail with their metablocks, the receipts of the tail, and the
+  /// entries of the view ledger, which are read after the tail so that they reach the view of its
+  /// receipts. The entries are read at the pace of the client. Returns once the client is gone, or
+  /// after queueing the error that ends the stream
+  pub async fn export_ledger(
+    &self,
+    handle_bytes: &[u8],
+    tx: mpsc::Sender<Result<Vec<u8>, CoordinatorError>>,
+  ) {
+    let permit = match tx.clone().reserve_owned().await {
+      Ok(permit) => permit,
+      Err(_closed) => return,
+    };
+    let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    if let Err(error) = self.write_bundle(handle_bytes, &handle, &tx).await {
+      permit.send(Err(error));
+    }
+  }
+
+  async fn write_bundle(
+    &self,
+    handle_bytes: &[u8],
+    handle: &Handle,
+    tx: &mpsc::Sender<Result<Vec<u8>, CoordinatorError>>,
+  ) -> Result<(), CoordinatorError> {
+    let height = self.read_committed_height(handle).await?;
+    if tx.send(Ok(bundle_header())).await.is_err() {
+      return Ok(());
+    }
+    let mut writer = BundleWriter { tx, num_records: 0 };
+    let record = BundleRecord::Ledger {
+      handle: handle_bytes.to_vec(),
+      height: height as u64,
+    };
+    if !writer.write(record).await {
+      return Ok(());
+    }
+
+    let mut metablock: Option<MetaBlock> = None;
+    let mut tail_receipts = Receipts::new();
+    for index in 0..=height {
+      let ledger_entry = self.read_ledger_entry(handle, index).await?;
+      check_not_purged(index, &ledger_entry)?;
+      let block_hash = ledger_entry.get_block_hash();
+      let next = match &metablock {
+        Some(metablock) => metablock
+          .next(&block_hash)
+          .ok_or(CoordinatorError::InvalidHeight)?,
+        None => MetaBlock::genesis(&block_hash),
+      };
+      if index == height {
+        tail_receipts = ledger_entry.get_receipts().clone();
+      }
+      let record = BundleRecord::Entry {
+        height: index as u64,
+        block: ledger_entry.get_block().to_bytes(),
+        nonces: ledger_entry.get_nonces().to_bytes(),
+        metablock: next.to_bytes(),
+      };
+      if !writer.write(record).await {
+        return Ok(());
+      }
+      metablock = Some(next);
+    }
+    let record = BundleRecord::Tail {
+      height: height as u64,
+      receipts: tail_receipts.to_bytes(),
+    };
+    if !writer.write(record).await {
+      return Ok(());
+    }
+
+    let (_view_tail, view_height, _attestations) = self.read_view_tail().await?;
+    for index in 1..=view_height {
+      let view_entry = self.read_view_by_index(index).await?;
+      let record = BundleRecord::View {
+        index: index as u64,
+        block: view_entry.get_block().to_bytes(),
+        receipts: view_entry.get_receipts().to_bytes(),
+      };
+      if !writer.write(record).await {
+        return Ok(());
+      }
+    }
+    let num_records = writer.num_records;
+    writer.write(BundleRecord::End { num_records }).await;
+    Ok(())
+  }
+
+  async fn read_ledger_entry(
+    &self,
+    handle: &Handle,
+    index: usize,
+  ) -> Result<LedgerEntry, CoordinatorError> {
+    self
+      .ledger_store
+      .read_ledger_by_index(handle, index)
+      .await
+      .map_err(|error| {
+        warn!(
+          "Failed to read ledger by index from the ledger store {:?}",
+          error,
+        );
+        CoordinatorError::FailedToReadLedger
+      })
+  }
+
+  /// returns the metablock of the entry at `index`, taken from the receipts of the closest entry
+  /// at or below `index` that has receipts and extended over the block hashes above it
+  async fn read_ledger_metablock(
+    &self,
+    handle: &Handle,
+    index: usize,
+  ) -> Result<MetaBlock, CoordinatorError> {
+    let mut block_hashes = Vec::new();
+    let mut index = index;
+    let mut metablock = loop {
+      let ledger_entry = self.read_ledger_entry(handle, index).await?;
+      if let Ok(metablock) = ledger_entry.get_receipts().get_metablock() {
+        break metablock;
+      }
+      if index == 0 {
+        break MetaBlock::genesis(&ledger_entry.get_block_hash());
+      }
+      block_hashes.push(ledger_entry.get_block_hash());
+      index -= 1;
+    };
+    for block_hash in block_hashes.iter().rev() {
+      metablock = metablock
+        .next(block_hash)
+        .ok_or(CoordinatorError::InvalidHeight)?;
+    }
+    Ok(metablock)
+  }
+
   pub async fn read_ledger_by_index(
     &self,
     handle_bytes: &[u8],
     index: usize,
   ) -> Result<LedgerEntry, CoordinatorError> {
     let handle = NimbleDigest::digest(handle_bytes);
+    telemetry::record_handle(&handle);
+    telemetry::record_height(index);
 
     match self.ledger_store.read_ledger_by_index(&handle, index).await {
-      Ok(ledger_entry) => Ok(ledger_entry),
+      Ok(ledger_entry) => {
+        check_not_purged(index, &ledger_entry)?;
+        Ok(ledger_entry)
+      },
       Err(error) => {
-        eprintln!(
+        warn!(
           "Failed to read ledger by index from the ledger store {:?}",
           error,
         );
@@ -1940,21 +6012,31 @@ impl CoordinatorState {
   }
 
   pub async fn read_view_by_index(&self, index: usize) -> Result<LedgerEntry, CoordinatorError> {
-    let ledger_entry = {
-      let res = self.ledger_store.read_view_ledger_by_index(index).await;
-      if res.is_err() {
-        return Err(CoordinatorError::FailedToReadViewLedger);
-      }
-      res.unwrap()
-    };
-
-    Ok(ledger_entry)
+    let res = self.ledger_store.read_view_ledger_by_index(index).await;
+    match res {
+      Ok(ledger_entry) => Ok(ledger_entry),
+      Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex)) => {
+        match self.ledger_store.read_view_ledger_tail().await {
+          Ok((_ledger_entry, current_height)) => {
+            Err(CoordinatorError::OutOfRange { current_height })
+          },
+          Err(_) => Err(CoordinatorError::FailedToReadViewLedger),
+        }
+      },
+      Err(error) => {
+        warn!(
+          "Failed to read the view ledger from the ledger store {:?}",
+          error,
+        );
+        Err(CoordinatorError::FailedToReadViewLedger)
+      },
+    }
   }
 
   pub async fn read_view_tail(&self) -> Result<(LedgerEntry, usize, Vec<u8>), CoordinatorError> {
     let res = self.ledger_store.read_view_ledger_tail().await;
     if let Err(error) = res {
-      eprintln!(
+      warn!(
         "Failed to read the view ledger tail from the ledger store {:?}",
         error,
       );
@@ -1964,4 +6046,17 @@ impl CoordinatorState {
     let (ledger_entry, height) = res.unwrap();
     Ok((ledger_entry, height, ATTESTATION_STR.as_bytes().to_vec()))
   }
+
+  /// obtains a quorum of receipts of the endorsers of the current view on the tail of the view
+  /// ledger together with a client's nonce, which show the client that the view is current
+  pub async fn read_view_tail_receipts(
+    &self,
+    nonce_bytes: &[u8],
+  ) -> Result<Receipts, CoordinatorError> {
+    let nonce = Nonce::try_from_bytes(nonce_bytes).map_err(|_e| CoordinatorError::InvalidNonce)?;
+    let endorsers = self.get_endorser_pks();
+    let receipts = self.endorser_read_view_tail(&endorsers, &nonce).await;
+    self.check_receipts_quorum(&receipts)?;
+    Ok(receipts)
+  }
 }
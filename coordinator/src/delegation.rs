@@ -0,0 +1,437 @@
+//! Delegation tokens, with which HDFS daemons authenticate to the coordinator as a tenant the way
+//! they authenticate to the NameNode. The admin service issues a token to a daemon, which sends it
+//! as `authorization: Delegation <token>` and renews it before it expires.
+//!
+//! As in Hadoop, a token is an identifier and a password, which is the HMAC of the identifier
+//! under a secret that the coordinators share; the coordinator keeps no state per token. Since the
+//! expiry is in the identifier, renewing a token issues a new one with a later expiry, up to the
+//! max date of the first one; the old token stays valid until its own expiry.
+use crate::tenant::{ClientAuth, Tenant, TENANT_SEPARATOR};
+use hmac::{Hmac, Mac};
+use ledger::NimbleDigest;
+use sha2::Sha256;
+use std::{
+  convert::{TryFrom, TryInto},
+  sync::atomic::{AtomicU64, Ordering},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tonic::Status;
+
+/// the scheme of the `authorization` headers that carry delegation tokens
+pub const DELEGATION_SCHEME: &str = "Delegation";
+/// the fewest bytes of a secret
+pub const MIN_SECRET_SIZE: usize = 32;
+/// how long a token lasts before it must be renewed, as in Hadoop
+pub const DEFAULT_RENEW_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// how long a token can be renewed for, as in Hadoop
+pub const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// the longest owner or renewer, in bytes
+pub const MAX_PRINCIPAL_SIZE: usize = 256;
+
+const TOKEN_VERSION: u8 = 1;
+const PASSWORD_SIZE: usize = 32;
+
+/// the MAC of the passwords, HMAC-SHA256 as in Hadoop
+type HmacSha256 = Hmac<Sha256>;
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+/// the HMAC of `message` under `key`, to sign or verify a password
+fn hmac(key: &[u8], message: &[u8]) -> HmacSha256 {
+  let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+  mac.update(message);
+  mac
+}
+
+/// the identifier of a delegation token, which its password signs; times are in milliseconds
+/// since the Unix epoch, as in Hadoop
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenIdentifier {
+  pub tenant: String,
+  /// the daemon that the token was issued to, such as the Kerberos principal of a NameNode
+  pub owner: String,
+  /// the only daemon that may renew the token; any holder of the token may if empty
+  pub renewer: String,
+  pub issue_date: u64,
+  /// when the token expires unless it is renewed
+  pub expiry: u64,
+  /// when the token expires for good
+  pub max_date: u64,
+  pub sequence_number: u64,
+  /// identifies the secret that signs the token
+  pub key_id: u32,
+}
+
+impl TokenIdentifier {
+  fn encode(&self) -> Result<Vec<u8>, TokenError> {
+    let mut bytes = vec![TOKEN_VERSION];
+    bytes.extend_from_slice(&self.key_id.to_be_bytes());
+    for field in [&self.tenant, &self.owner, &self.renewer] {
+      // the length prefix of a field has two bytes
+      let len = u16::try_from(field.len()).map_err(|_e| TokenError::FieldTooLarge)?;
+      bytes.extend_from_slice(&len.to_be_bytes());
+      bytes.extend_from_slice(field.as_bytes());
+    }
+    for time in [
+      self.issue_date,
+      self.expiry,
+      self.max_date,
+      self.sequence_number,
+    ] {
+      bytes.extend_from_slice(&time.to_be_bytes());
+    }
+    Ok(bytes)
+  }
+
+  fn decode(bytes: &[u8]) -> Option<Self> {
+    let (version, mut rest) = bytes.split_first()?;
+    if *version != TOKEN_VERSION {
+      return None;
+    }
+    let mut take = |n: usize| -> Option<&[u8]> {
+      if rest.len() < n {
+        return None;
+      }
+      let (field, tail) = rest.split_at(n);
+      rest = tail;
+      Some(field)
+    };
+    let key_id = u32::from_be_bytes(take(4)?.try_into().ok()?);
+    let mut strings = Vec::new();
+    for _ in 0..3 {
+      let len = u16::from_be_bytes(take(2)?.try_into().ok()?) as usize;
+      strings.push(String::from_utf8(take(len)?.to_vec()).ok()?);
+    }
+    let mut numbers = Vec::new();
+    for _ in 0..4 {
+      numbers.push(u64::from_be_bytes(take(8)?.try_into().ok()?));
+    }
+    if !rest.is_empty() {
+      return None;
+    }
+    let renewer = strings.pop()?;
+    let owner = strings.pop()?;
+    let tenant = strings.pop()?;
+    Some(TokenIdentifier {
+      tenant,
+      owner,
+      renewer,
+      issue_date: numbers[0],
+      expiry: numbers[1],
+      max_date: numbers[2],
+      sequence_number: numbers[3],
+      key_id,
+    })
+  }
+}
+
+/// why a delegation token is refused
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenError {
+  /// the token is malformed, or was not signed with the secret of the coordinator
+  Invalid,
+  Expired,
+  /// the token names another renewer
+  WrongRenewer(String),
+  /// the tenant, the owner, or the renewer is longer than a token can hold
+  FieldTooLarge,
+}
+
+impl TokenError {
+  /// the status of a call that authenticates with the token
+  pub fn to_status(&self) -> Status {
+    match self {
+      TokenError::Invalid => Status::unauthenticated("Invalid delegation token"),
+      TokenError::Expired => Status::unauthenticated("Expired delegation token"),
+      TokenError::WrongRenewer(renewer) => Status::permission_denied(format!(
+        "The delegation token can only be renewed by {}",
+        renewer
+      )),
+      TokenError::FieldTooLarge => Status::invalid_argument(format!(
+        "The tenant, the owner and the renewer of a delegation token must be at most {} bytes",
+        u16::MAX
+      )),
+    }
+  }
+}
+
+/// issues, renews, and verifies the delegation tokens of the coordinator
+pub struct DelegationTokens {
+  secret: Vec<u8>,
+  key_id: u32,
+  renew_interval: Duration,
+  max_lifetime: Duration,
+  next_sequence_number: AtomicU64,
+}
+
+impl DelegationTokens {
+  /// signs tokens with `secret`, which must have at least `MIN_SECRET_SIZE` bytes; the ID of the
+  /// key is derived from it, so tokens signed with another secret are told apart
+  pub fn new(secret: Vec<u8>) -> Self {
+    let digest = NimbleDigest::digest_parts(&[b"NimbleDelegationKey/v1", &secret]).to_bytes();
+    DelegationTokens {
+      key_id: u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]),
+      secret,
+      renew_interval: DEFAULT_RENEW_INTERVAL,
+      max_lifetime: DEFAULT_MAX_LIFETIME,
+      // the sequence numbers of tokens issued by coordinators that share the secret should not
+      // collide, and the coordinator does not keep them across restarts
+      next_sequence_number: AtomicU64::new(rand::random::<u32>() as u64),
+    }
+  }
+
+  pub fn with_renew_interval(mut self, renew_interval: Duration) -> Self {
+    self.renew_interval = renew_interval;
+    self
+  }
+
+  pub fn with_max_lifetime(mut self, max_lifetime: Duration) -> Self {
+    self.max_lifetime = max_lifetime;
+    self
+  }
+
+  fn sign(&self, identifier: &TokenIdentifier) -> Result<String, TokenError> {
+    let bytes = identifier.encode()?;
+    let password = hmac(&self.secret, &bytes).finalize().into_bytes();
+    Ok(base64_url::encode(&[&bytes[..], &password[..]].concat()))
+  }
+
+  /// issues a token that authenticates its holder as `tenant`
+  pub fn issue(
+    &self,
+    tenant: &str,
+    owner: &str,
+    renewer: &str,
+  ) -> Result<(String, TokenIdentifier), TokenError> {
+    self.issue_at(tenant, owner, renewer, now_ms())
+  }
+
+  fn issue_at(
+    &self,
+    tenant: &str,
+    owner: &str,
+    renewer: &str,
+    now: u64,
+  ) -> Result<(String, TokenIdentifier), TokenError> {
+    let max_date = now.saturating_add(self.max_lifetime.as_millis() as u64);
+    let identifier = TokenIdentifier {
+      tenant: tenant.to_string(),
+      owner: owner.to_string(),
+      renewer: renewer.to_string(),
+      issue_date: now,
+      expiry: max_date.min(now.saturating_add(self.renew_interval.as_millis() as u64)),
+      max_date,
+      sequence_number: self.next_sequence_number.fetch_add(1, Ordering::Relaxed),
+      key_id: self.key_id,
+    };
+    Ok((self.sign(&identifier)?, identifier))
+  }
+
+  /// the identifier of a token that the coordinator issued and that has not expired
+  pub fn verify(&self, token: &str) -> Result<TokenIdentifier, TokenError> {
+    self.verify_at(token, now_ms())
+  }
+
+  fn verify_at(&self, token: &str, now: u64) -> Result<TokenIdentifier, TokenError> {
+    let bytes = base64_url::decode(token).map_err(|_e| TokenError::Invalid)?;
+    if bytes.len() < PASSWORD_SIZE {
+      return Err(TokenError::Invalid);
+    }
+    let (identifier_bytes, password) = bytes.split_at(bytes.len() - PASSWORD_SIZE);
+    let identifier = TokenIdentifier::decode(identifier_bytes).ok_or(TokenError::Invalid)?;
+    if identifier.key_id != self.key_id {
+      return Err(TokenError::Invalid);
+    }
+    // the password is compared in constant time
+    hmac(&self.secret, identifier_bytes)
+      .verify_slice(password)
+      .map_err(|_e| TokenError::Invalid)?;
+    if now >= identifier.expiry {
+      return Err(TokenError::Expired);
+    }
+    Ok(identifier)
+  }
+
+  /// issues a token like `token`, which must not have expired, whose expiry is a renew interval
+  /// from now but not past its max date; `renewer` must be the renewer that the token names, if
+  /// any
+  pub fn renew(&self, token: &str, renewer: &str) -> Result<(String, TokenIdentifier), TokenError> {
+    self.renew_at(token, renewer, now_ms())
+  }
+
+  fn renew_at(
+    &self,
+    token: &str,
+    renewer: &str,
+    now: u64,
+  ) -> Result<(String, TokenIdentifier), TokenError> {
+    let mut identifier = self.verify_at(token, now)?;
+    if !identifier.renewer.is_empty() && identifier.renewer != renewer {
+      return Err(TokenError::WrongRenewer(identifier.renewer));
+    }
+    identifier.expiry = identifier
+      .max_date
+      .min(now.saturating_add(self.renew_interval.as_millis() as u64));
+    Ok((self.sign(&identifier)?, identifier))
+  }
+}
+
+impl ClientAuth for DelegationTokens {
+  fn scheme(&self) -> &'static str {
+    DELEGATION_SCHEME
+  }
+
+  fn authenticate(&self, credentials: &str) -> Result<Tenant, Status> {
+    let identifier = self.verify(credentials).map_err(|e| e.to_status())?;
+    // the admin service issues tokens only for valid tenants, but a token outlives its checks
+    if identifier.tenant.is_empty() || identifier.tenant.as_bytes().contains(&TENANT_SEPARATOR) {
+      return Err(TokenError::Invalid.to_status());
+    }
+    Ok(Tenant(identifier.tenant))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HOUR: u64 = 60 * 60 * 1000;
+
+  fn tokens() -> DelegationTokens {
+    DelegationTokens::new(vec![7u8; MIN_SECRET_SIZE])
+      .with_renew_interval(Duration::from_secs(60 * 60))
+      .with_max_lifetime(Duration::from_secs(3 * 60 * 60))
+  }
+
+  #[test]
+  fn test_hmac() {
+    // RFC 4231, test cases 2 and 6
+    let hex_mac =
+      |key: &[u8], message: &[u8]| hex::encode(hmac(key, message).finalize().into_bytes());
+    assert_eq!(
+      hex_mac(b"Jefe", b"what do ya want for nothing?"),
+      "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+      hex_mac(
+        &[0xaa; 131],
+        b"Test Using Larger Than Block-Size Key - Hash Key First"
+      ),
+      "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+  }
+
+  #[test]
+  fn test_expiry_and_renewal() {
+    let tokens = tokens();
+    let now = 1_700_000_000_000;
+    let (token, identifier) = tokens
+      .issue_at("hdfs-a", "nn/host@REALM", "yarn", now)
+      .unwrap();
+    assert_eq!(identifier.expiry, now + HOUR);
+    assert_eq!(identifier.max_date, now + 3 * HOUR);
+    assert_eq!(tokens.verify_at(&token, now), Ok(identifier.clone()));
+    assert_eq!(
+      tokens.verify_at(&token, now + HOUR),
+      Err(TokenError::Expired)
+    );
+
+    // only the renewer renews, which extends the expiry up to the max date
+    assert_eq!(
+      tokens.renew_at(&token, "hdfs", now + HOUR / 2),
+      Err(TokenError::WrongRenewer("yarn".to_string()))
+    );
+    let (renewed, renewed_identifier) = tokens.renew_at(&token, "yarn", now + HOUR / 2).unwrap();
+    assert_eq!(renewed_identifier.expiry, now + 3 * HOUR / 2);
+    assert_eq!(
+      renewed_identifier.sequence_number,
+      identifier.sequence_number
+    );
+    assert!(tokens.verify_at(&renewed, now + HOUR).is_ok());
+    assert_eq!(
+      tokens.verify_at(&token, now + HOUR),
+      Err(TokenError::Expired)
+    );
+    let (renewed, renewed_identifier) = tokens
+      .renew_at(&renewed, "yarn", now + 5 * HOUR / 2)
+      .unwrap();
+    assert_eq!(renewed_identifier.expiry, now + 3 * HOUR);
+    assert_eq!(
+      tokens.renew_at(&renewed, "yarn", now + 3 * HOUR),
+      Err(TokenError::Expired)
+    );
+    // an expired token is not renewed
+    assert_eq!(
+      tokens.renew_at(&token, "yarn", now + 2 * HOUR),
+      Err(TokenError::Expired)
+    );
+
+    // tokens without a renewer are renewed by any holder
+    let (token, _identifier) = tokens.issue_at("hdfs-a", "nn/host@REALM", "", now).unwrap();
+    assert!(tokens.renew_at(&token, "anyone", now).is_ok());
+  }
+
+  #[test]
+  fn test_forgery() {
+    let tokens = tokens();
+    let (token, _identifier) = tokens.issue("hdfs-a", "nn/host@REALM", "").unwrap();
+    assert_eq!(
+      tokens.authenticate(&token).unwrap(),
+      Tenant("hdfs-a".to_string())
+    );
+
+    // a token with another tenant, a later expiry, or a flipped bit does not verify
+    let bytes = base64_url::decode(&token).unwrap();
+    let (identifier_bytes, password) = bytes.split_at(bytes.len() - PASSWORD_SIZE);
+    let mut identifier = TokenIdentifier::decode(identifier_bytes).unwrap();
+    identifier.tenant = "hdfs-b".to_string();
+    let forged = base64_url::encode(&[identifier.encode().unwrap(), password.to_vec()].concat());
+    assert_eq!(tokens.verify(&forged), Err(TokenError::Invalid));
+    let mut identifier = TokenIdentifier::decode(identifier_bytes).unwrap();
+    identifier.expiry = identifier.max_date;
+    let forged = base64_url::encode(&[identifier.encode().unwrap(), password.to_vec()].concat());
+    assert_eq!(tokens.verify(&forged), Err(TokenError::Invalid));
+    for i in 0..bytes.len() {
+      let mut tampered = bytes.clone();
+      tampered[i] ^= 1;
+      assert!(tokens.verify(&base64_url::encode(&tampered)).is_err());
+    }
+    assert_eq!(
+      tokens.verify(&base64_url::encode(&bytes[1..])),
+      Err(TokenError::Invalid)
+    );
+    assert_eq!(tokens.verify("not a token"), Err(TokenError::Invalid));
+
+    // a token signed with another secret does not verify, nor does its identifier
+    let other = DelegationTokens::new(vec![8u8; MIN_SECRET_SIZE]);
+    let (token, _identifier) = other.issue("hdfs-a", "nn/host@REALM", "").unwrap();
+    assert_eq!(tokens.verify(&token), Err(TokenError::Invalid));
+    let status = tokens.authenticate(&token).unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+  }
+
+  #[test]
+  fn test_field_size() {
+    let tokens = tokens();
+    // the length prefix of a field holds up to u16::MAX bytes
+    let longest = "a".repeat(u16::MAX as usize);
+    let (token, identifier) = tokens.issue(&longest, &longest, &longest).unwrap();
+    assert_eq!(identifier.tenant, longest);
+    assert_eq!(tokens.verify(&token), Ok(identifier));
+
+    let too_long = "a".repeat(u16::MAX as usize + 1);
+    assert_eq!(
+      tokens.issue(&too_long, "nn/host@REALM", ""),
+      Err(TokenError::FieldTooLarge)
+    );
+    assert_eq!(
+      tokens.issue("hdfs-a", "nn/host@REALM", &too_long),
+      Err(TokenError::FieldTooLarge)
+    );
+  }
+}
@@ -0,0 +1,221 @@
+//! The calls that the coordinator makes to an endorser, behind a trait so that the coordinator does
+//! not depend on how it reaches the endorser. The coordinator reaches endorsers over gRPC, through
+//! the tonic client; its tests reach in-memory endorsers, which answer without a network.
+use crate::errors::CoordinatorError;
+use ledger::endorser_proto::{
+  endorser_call_client::EndorserCallClient, ActivateReq, ActivateResp, AppendBatchReq,
+  AppendBatchResp, AppendReq, AppendResp, FinalizeStateReq, FinalizeStateResp, GetPublicKeyReq,
+  GetPublicKeyResp, GetStatusReq, GetStatusResp, HeartbeatReq, HeartbeatResp, InitializeStateReq,
+  InitializeStateResp, NewLedgerReq, NewLedgerResp, ReadLatestReq, ReadLatestResp, ReadStateReq,
+  ReadStateResp, ReadViewTailReq, ReadViewTailResp, RotateKeyReq, RotateKeyResp,
+};
+use std::{sync::Arc, time::Duration};
+use tonic::{
+  transport::{Channel, Endpoint},
+  Request, Response, Status,
+};
+use tracing::warn;
+
+const ENDORSER_CONNECT_TIMEOUT: u64 = 10; // seconds: the connect timeout to endorsers
+
+/// a connection to an endorser; the calls take the request with its metadata and timeout, as the
+/// tonic client does
+#[tonic::async_trait]
+pub trait EndorserConnection: Send + Sync {
+  async fn get_public_key(
+    &self,
+    request: Request<GetPublicKeyReq>,
+  ) -> Result<Response<GetPublicKeyResp>, Status>;
+
+  async fn new_ledger(
+    &self,
+    request: Request<NewLedgerReq>,
+  ) -> Result<Response<NewLedgerResp>, Status>;
+
+  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status>;
+
+  async fn append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status>;
+
+  async fn read_latest(
+    &self,
+    request: Request<ReadLatestReq>,
+  ) -> Result<Response<ReadLatestResp>, Status>;
+
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status>;
+
+  async fn initialize_state(
+    &self,
+    request: Request<InitializeStateReq>,
+  ) -> Result<Response<InitializeStateResp>, Status>;
+
+  async fn finalize_state(
+    &self,
+    request: Request<FinalizeStateReq>,
+  ) -> Result<Response<FinalizeStateResp>, Status>;
+
+  async fn read_state(
+    &self,
+    request: Request<ReadStateReq>,
+  ) -> Result<Response<ReadStateResp>, Status>;
+
+  async fn activate(&self, request: Request<ActivateReq>)
+    -> Result<Response<ActivateResp>, Status>;
+
+  async fn rotate_key(
+    &self,
+    request: Request<RotateKeyReq>,
+  ) -> Result<Response<RotateKeyResp>, Status>;
+
+  async fn get_status(
+    &self,
+    request: Request<GetStatusReq>,
+  ) -> Result<Response<GetStatusResp>, Status>;
+
+  async fn heartbeat(
+    &self,
+    request: Request<HeartbeatReq>,
+  ) -> Result<Response<HeartbeatResp>, Status>;
+}
+
+/// a connection to an endorser that the coordinator shares between its calls
+pub type EndorserClient = Arc<dyn EndorserConnection>;
+
+// the tonic client takes itself mutably, but a clone shares the channel; its methods are called by
+// path, since the methods of the trait, which take the client by reference, would be found first
+#[tonic::async_trait]
+impl EndorserConnection for EndorserCallClient<Channel> {
+  async fn get_public_key(
+    &self,
+    request: Request<GetPublicKeyReq>,
+  ) -> Result<Response<GetPublicKeyResp>, Status> {
+    EndorserCallClient::get_public_key(&mut self.clone(), request).await
+  }
+
+  async fn new_ledger(
+    &self,
+    request: Request<NewLedgerReq>,
+  ) -> Result<Response<NewLedgerResp>, Status> {
+    EndorserCallClient::new_ledger(&mut self.clone(), request).await
+  }
+
+  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+    EndorserCallClient::append(&mut self.clone(), request).await
+  }
+
+  async fn append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    EndorserCallClient::append_batch(&mut self.clone(), request).await
+  }
+
+  async fn read_latest(
+    &self,
+    request: Request<ReadLatestReq>,
+  ) -> Result<Response<ReadLatestResp>, Status> {
+    EndorserCallClient::read_latest(&mut self.clone(), request).await
+  }
+
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status> {
+    EndorserCallClient::read_view_tail(&mut self.clone(), request).await
+  }
+
+  async fn initialize_state(
+    &self,
+    request: Request<InitializeStateReq>,
+  ) -> Result<Response<InitializeStateResp>, Status> {
+    EndorserCallClient::initialize_state(&mut self.clone(), request).await
+  }
+
+  async fn finalize_state(
+    &self,
+    request: Request<FinalizeStateReq>,
+  ) -> Result<Response<FinalizeStateResp>, Status> {
+    EndorserCallClient::finalize_state(&mut self.clone(), request).await
+  }
+
+  async fn read_state(
+    &self,
+    request: Request<ReadStateReq>,
+  ) -> Result<Response<ReadStateResp>, Status> {
+    EndorserCallClient::read_state(&mut self.clone(), request).await
+  }
+
+  async fn activate(
+    &self,
+    request: Request<ActivateReq>,
+  ) -> Result<Response<ActivateResp>, Status> {
+    EndorserCallClient::activate(&mut self.clone(), request).await
+  }
+
+  async fn rotate_key(
+    &self,
+    request: Request<RotateKeyReq>,
+  ) -> Result<Response<RotateKeyResp>, Status> {
+    EndorserCallClient::rotate_key(&mut self.clone(), request).await
+  }
+
+  async fn get_status(
+    &self,
+    request: Request<GetStatusReq>,
+  ) -> Result<Response<GetStatusResp>, Status> {
+    EndorserCallClient::get_status(&mut self.clone(), request).await
+  }
+
+  async fn heartbeat(
+    &self,
+    request: Request<HeartbeatReq>,
+  ) -> Result<Response<HeartbeatResp>, Status> {
+    EndorserCallClient::heartbeat(&mut self.clone(), request).await
+  }
+}
+
+/// opens connections to the endorsers at the URIs of a view
+#[tonic::async_trait]
+pub trait EndorserConnector: Send + Sync {
+  async fn connect(&self, uri: &str) -> Result<EndorserClient, CoordinatorError>;
+}
+
+/// connects to endorsers over gRPC, with a timeout on every call
+pub struct GrpcConnector {
+  /// seconds
+  timeout: u64,
+}
+
+impl GrpcConnector {
+  pub fn new(timeout: u64) -> Self {
+    GrpcConnector { timeout }
+  }
+}
+
+#[tonic::async_trait]
+impl EndorserConnector for GrpcConnector {
+  async fn connect(&self, uri: &str) -> Result<EndorserClient, CoordinatorError> {
+    let endpoint = match Endpoint::from_shared(uri.to_string()) {
+      Ok(endpoint) => endpoint,
+      Err(error) => {
+        warn!("Failed to resolve the endorser host name: {:?}", error);
+        return Err(CoordinatorError::CannotResolveHostName);
+      },
+    };
+    let endpoint = endpoint
+      .connect_timeout(Duration::from_secs(ENDORSER_CONNECT_TIMEOUT))
+      .timeout(Duration::from_secs(self.timeout));
+    match endpoint.connect().await {
+      Ok(channel) => Ok(Arc::new(EndorserCallClient::new(channel))),
+      Err(error) => {
+        warn!("Failed to connect to the endorser {}: {:?}", uri, error);
+        Err(CoordinatorError::FailedToConnectToEndorser)
+      },
+    }
+  }
+}
@@ -0,0 +1,369 @@
+//! The signed heartbeats of the endorsers, which show the coordinator that every endorser is alive
+//! and signing without ledger traffic. Every interval the coordinator sends each connected endorser
+//! a fresh nonce, and the endorser signs it with its heartbeat counter and the time of its clock
+//! (see `ledger::compute_endorser_heartbeat_message`). The coordinator keeps the last heartbeat of
+//! each endorser that verified: once heartbeats run, the health checks count only the endorsers
+//! whose last heartbeat is recent, and the times that the endorsers signed show how far apart
+//! their clocks are.
+use crate::{
+  config::EndorsersConfig, coordinator_state::CoordinatorState,
+  endorser_connection::EndorserClient, endorser_key::EndorserKey, telemetry,
+};
+use ledger::{
+  compute_endorser_heartbeat_message, endorser_proto::HeartbeatReq, CustomSerde, IdSig, Nonce,
+};
+use std::{
+  collections::{HashMap, HashSet},
+  future::Future,
+  sync::{Arc, RwLock},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::Instant;
+use tonic::Code;
+use tracing::{info, warn};
+
+/// the heartbeats in a row that an endorser may miss before the health checks stop counting it
+const MISSED_HEARTBEATS: u32 = 3;
+/// the skew between the clocks of the endorsers above which the monitor warns
+const SKEW_WARNING: Duration = Duration::from_secs(5);
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+/// the last heartbeat of an endorser that verified
+#[derive(Clone, Debug)]
+pub struct SignedHeartbeat {
+  pub uri: String,
+  pub counter: u64,
+  /// the time that the endorser signed, in milliseconds since the Unix epoch
+  pub timestamp_ms: u64,
+  /// when the coordinator received it
+  pub received: Instant,
+  /// how far the clock of the endorser is ahead of the clock of the coordinator, in milliseconds,
+  /// taking the endorser to have signed halfway through the call
+  pub offset_ms: i64,
+}
+
+/// the heartbeats of the endorsers, by public key
+#[derive(Default)]
+pub struct EndorserHeartbeats {
+  /// how old the last heartbeat of an endorser may be for the health checks to count the endorser;
+  /// the health checks leave heartbeats out if not set
+  max_age: RwLock<Option<Duration>>,
+  last: RwLock<HashMap<EndorserKey, SignedHeartbeat>>,
+  /// the endorsers that predate heartbeats, which the health checks count while they are connected
+  unsupported: RwLock<HashSet<EndorserKey>>,
+}
+
+impl EndorserHeartbeats {
+  /// makes the health checks count only the endorsers whose last heartbeat is at most `max_age` old
+  pub fn require(&self, max_age: Duration) {
+    if let Ok(mut required) = self.max_age.write() {
+      *required = Some(max_age);
+    }
+  }
+
+  /// records the heartbeat of the endorser `pk`, which verified; returns whether its counter went
+  /// back, as it does when the endorser restarts
+  pub fn record(&self, pk: EndorserKey, heartbeat: SignedHeartbeat) -> bool {
+    if let Ok(mut unsupported) = self.unsupported.write() {
+      unsupported.remove(&pk);
+    }
+    match self.last.write() {
+      Ok(mut last) => match last.insert(pk, heartbeat.clone()) {
+        Some(previous) => heartbeat.counter <= previous.counter,
+        None => false,
+      },
+      Err(_) => false,
+    }
+  }
+
+  /// records that the endorser `pk` does not implement heartbeats
+  pub fn record_unsupported(&self, pk: EndorserKey) {
+    if let Ok(mut unsupported) = self.unsupported.write() {
+      unsupported.insert(pk);
+    }
+  }
+
+  /// forgets the endorsers other than `pks`, which left the view
+  pub fn retain(&self, pks: &[EndorserKey]) {
+    if let Ok(mut last) = self.last.write() {
+      last.retain(|pk, _heartbeat| pks.contains(pk));
+    }
+    if let Ok(mut unsupported) = self.unsupported.write() {
+      unsupported.retain(|pk| pks.contains(pk));
+    }
+  }
+
+  /// the last heartbeats of the endorsers
+  pub fn snapshot(&self) -> Vec<(EndorserKey, SignedHeartbeat)> {
+    match self.last.read() {
+      Ok(last) => last
+        .iter()
+        .map(|(pk, heartbeat)| (*pk, heartbeat.clone()))
+        .collect(),
+      Err(_) => Vec::new(),
+    }
+  }
+
+  fn is_recent(&self, heartbeat: &SignedHeartbeat) -> bool {
+    match self.max_age.read().ok().and_then(|max_age| *max_age) {
+      Some(max_age) => heartbeat.received.elapsed() <= max_age,
+      None => true,
+    }
+  }
+
+  /// whether the health checks count the endorser `pk`, which is connected: without heartbeats,
+  /// and for an endorser that predates them, they do; otherwise, only if its last heartbeat is
+  /// recent
+  pub fn is_alive(&self, pk: &[u8]) -> bool {
+    if self
+      .max_age
+      .read()
+      .ok()
+      .and_then(|max_age| *max_age)
+      .is_none()
+    {
+      return true;
+    }
+    if let Ok(unsupported) = self.unsupported.read() {
+      if unsupported.contains(pk) {
+        return true;
+      }
+    }
+    match self.last.read() {
+      Ok(last) => last
+        .get(pk)
+        .map_or(false, |heartbeat| self.is_recent(heartbeat)),
+      Err(_) => false,
+    }
+  }
+
+  /// the spread of the clocks of the endorsers with a recent heartbeat, from the one furthest
+  /// behind to the one furthest ahead; `None` without heartbeats
+  pub fn clock_skew(&self) -> Option<Duration> {
+    let offsets = self
+      .snapshot()
+      .into_iter()
+      .filter(|(_pk, heartbeat)| self.is_recent(heartbeat))
+      .map(|(_pk, heartbeat)| heartbeat.offset_ms)
+      .collect::<Vec<_>>();
+    let min = offsets.iter().min()?;
+    let max = offsets.iter().max()?;
+    Some(Duration::from_millis((max - min) as u64))
+  }
+}
+
+/// what came of a heartbeat sent to an endorser
+enum Beat {
+  Signed(SignedHeartbeat),
+  /// the endorser predates heartbeats
+  Unsupported,
+  Failed(String),
+}
+
+/// sends a heartbeat to the endorser `pk` at `uri`, and verifies that the endorser signed it
+async fn beat(endorser_client: EndorserClient, pk: EndorserKey, uri: String) -> Beat {
+  let nonce = Nonce::new();
+  let sent = Instant::now();
+  let sent_ms = now_ms();
+  let req = HeartbeatReq {
+    nonce: nonce.to_bytes().into(),
+  };
+  let resp = match endorser_client.heartbeat(telemetry::outgoing(req)).await {
+    Ok(resp) => resp.into_inner(),
+    Err(status) if status.code() == Code::Unimplemented => return Beat::Unsupported,
+    Err(status) => return Beat::Failed(format!("{:?}", status)),
+  };
+  let received = Instant::now();
+  if resp.pk != pk.as_bytes() {
+    return Beat::Failed("the heartbeat is signed with another key".to_string());
+  }
+  let message = compute_endorser_heartbeat_message(&nonce, resp.counter, resp.timestamp_ms);
+  let verified = IdSig::from_raw(&resp.pk, &resp.signature)
+    .map_err(|e| format!("{:?}", e))
+    .and_then(|id_sig| {
+      id_sig
+        .verify(&message.to_bytes())
+        .map_err(|e| format!("{:?}", e))
+    });
+  if let Err(e) = verified {
+    return Beat::Failed(format!("the signature of the heartbeat is invalid ({})", e));
+  }
+  let midpoint_ms = sent_ms + (received - sent).as_millis() as u64 / 2;
+  Beat::Signed(SignedHeartbeat {
+    uri,
+    counter: resp.counter,
+    timestamp_ms: resp.timestamp_ms,
+    received,
+    offset_ms: resp.timestamp_ms as i64 - midpoint_ms as i64,
+  })
+}
+
+/// sends heartbeats to the connected endorsers at a fixed interval, and records the ones that
+/// verify in the heartbeats of the coordinator
+pub struct EndorserHeartbeatMonitor {
+  state: Arc<CoordinatorState>,
+  interval: Duration,
+}
+
+impl EndorserHeartbeatMonitor {
+  pub fn new(state: Arc<CoordinatorState>, interval: Duration) -> Self {
+    EndorserHeartbeatMonitor { state, interval }
+  }
+
+  /// makes the health checks count only the endorsers that answered one of their last
+  /// `MISSED_HEARTBEATS` heartbeats
+  pub fn require(&self) {
+    self
+      .state
+      .endorser_heartbeats()
+      .require(self.interval * MISSED_HEARTBEATS);
+  }
+
+  /// sends a heartbeat to every connected endorser at once, and records the ones that verify;
+  /// returns the number that did
+  pub async fn beat_all(&self) -> usize {
+    let heartbeats = self.state.endorser_heartbeats();
+    let pks = self.state.get_endorser_pks();
+    heartbeats.retain(&pks);
+    let jobs = pks
+      .iter()
+      .filter_map(|pk| {
+        let (endorser_client, uri) = self.state.get_endorser_client(pk)?;
+        Some((
+          *pk,
+          uri.clone(),
+          tokio::spawn(beat(endorser_client, *pk, uri)),
+        ))
+      })
+      .collect::<Vec<_>>();
+
+    let mut num_signed = 0;
+    for (pk, uri, job) in jobs {
+      match job.await {
+        Ok(Beat::Signed(heartbeat)) => {
+          num_signed += 1;
+          if heartbeats.record(pk, heartbeat) {
+            info!(
+              "The heartbeat counter of endorser {} went back; it restarted",
+              uri
+            );
+          }
+        },
+        Ok(Beat::Unsupported) => heartbeats.record_unsupported(pk),
+        Ok(Beat::Failed(error)) => {
+          warn!("The heartbeat of endorser {} failed: {}", uri, error);
+        },
+        Err(error) => warn!("The heartbeat of endorser {} panicked: {}", uri, error),
+      }
+    }
+    if let Some(skew) = heartbeats.clock_skew() {
+      if skew > SKEW_WARNING {
+        warn!(
+          skew_ms = skew.as_millis() as u64,
+          "The clocks of the endorsers are far apart"
+        );
+      }
+    }
+    num_signed
+  }
+
+  /// sends heartbeats every interval until `stopped` completes
+  pub async fn run(self, stopped: impl Future<Output = ()>) {
+    tokio::pin!(stopped);
+    loop {
+      tokio::select! {
+        () = &mut stopped => return,
+        () = tokio::time::sleep(self.interval) => {},
+      }
+      self.beat_all().await;
+    }
+  }
+}
+
+/// the monitor that the heartbeat interval of `config` enables; the health checks then count only
+/// the endorsers that answer it, so it is made before the coordinator reports that it recovered
+pub fn monitor_from_config(
+  state: Arc<CoordinatorState>,
+  config: &EndorsersConfig,
+) -> Option<EndorserHeartbeatMonitor> {
+  config.heartbeat_interval.map(|secs| {
+    let monitor = EndorserHeartbeatMonitor::new(state, Duration::from_secs(secs));
+    monitor.require();
+    monitor
+  })
+}
+
+/// sends `monitor` heartbeats to every endorser once, and then every interval until `stopped`
+/// completes
+pub async fn start_monitor(
+  monitor: EndorserHeartbeatMonitor,
+  stopped: impl Future<Output = ()> + Send + 'static,
+) {
+  monitor.beat_all().await;
+  info!(
+    "Sending the endorsers heartbeats every {}s",
+    monitor.interval.as_secs()
+  );
+  let _job = tokio::spawn(monitor.run(stopped));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::signature::{PrivateKey, PrivateKeyTrait, PublicKeyTrait};
+
+  fn key() -> EndorserKey {
+    let pk = PrivateKey::new().get_public_key().unwrap();
+    EndorserKey::from_bytes(&pk.to_bytes()).unwrap()
+  }
+
+  fn heartbeat(counter: u64, offset_ms: i64) -> SignedHeartbeat {
+    SignedHeartbeat {
+      uri: "http://endorser:9090".to_string(),
+      counter,
+      timestamp_ms: 1_700_000_000_000,
+      received: Instant::now(),
+      offset_ms,
+    }
+  }
+
+  #[test]
+  pub fn test_endorser_heartbeats() {
+    let heartbeats = EndorserHeartbeats::default();
+    let (a, b, c) = (key(), key(), key());
+    // until heartbeats are required, every connected endorser counts
+    assert!(heartbeats.is_alive(&a));
+    assert_eq!(heartbeats.clock_skew(), None);
+
+    heartbeats.require(Duration::from_secs(60));
+    assert!(!heartbeats.is_alive(&a));
+    assert!(!heartbeats.record(a, heartbeat(1, -200)));
+    assert!(!heartbeats.record(a, heartbeat(2, -200)));
+    assert!(!heartbeats.record(b, heartbeat(7, 300)));
+    assert!(heartbeats.is_alive(&a) && heartbeats.is_alive(&b));
+    assert_eq!(heartbeats.clock_skew(), Some(Duration::from_millis(500)));
+
+    // a counter that goes back is a restart
+    assert!(heartbeats.record(b, heartbeat(1, 300)));
+
+    // an endorser that predates heartbeats counts while it is connected
+    heartbeats.record_unsupported(c);
+    assert!(heartbeats.is_alive(&c));
+
+    // a heartbeat that is too old does not count
+    heartbeats.require(Duration::ZERO);
+    std::thread::sleep(Duration::from_millis(1));
+    assert!(!heartbeats.is_alive(&a));
+    assert_eq!(heartbeats.clock_skew(), None);
+
+    heartbeats.retain(&[b]);
+    assert_eq!(heartbeats.snapshot().len(), 1);
+    assert!(!heartbeats.is_alive(&c));
+  }
+}
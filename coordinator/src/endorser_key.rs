@@ -0,0 +1,174 @@
+//! The key under which the coordinator keeps the connections to an endorser: the compressed
+//! public key of the endorser in a fixed-size array, so that the fan-out of a request to the
+//! endorsers copies the keys rather than allocating them for every call. The array is private and
+//! the only way to make a key is from bytes that parse as a public key, so an unvalidated key does
+//! not type-check as a key of the connections.
+use ledger::signature::{PublicKey, PublicKeyTrait};
+use std::{
+  borrow::Borrow,
+  convert::TryInto,
+  hash::{Hash, Hasher},
+  ops::Deref,
+};
+
+/// bytes: the length of a compressed public key of an endorser
+pub const PUBLIC_KEY_LEN: usize = 33;
+
+/// the public key of a connected endorser
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EndorserKey([u8; PUBLIC_KEY_LEN]);
+
+impl EndorserKey {
+  /// the key of the endorser with the public key `bytes`, or `None` if they are not a public key
+  pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    let key = bytes.try_into().ok()?;
+    PublicKey::from_bytes(bytes).ok()?;
+    Some(EndorserKey(key))
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.0
+  }
+}
+
+impl Deref for EndorserKey {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.0
+  }
+}
+
+// a key hashes as its bytes, so that the connections can be looked up by a slice
+impl Hash for EndorserKey {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.as_bytes().hash(state)
+  }
+}
+
+impl Borrow<[u8]> for EndorserKey {
+  fn borrow(&self) -> &[u8] {
+    &self.0
+  }
+}
+
+impl std::fmt::Debug for EndorserKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "EndorserKey({})", hex::encode(self.0))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::signature::{PrivateKey, PrivateKeyTrait};
+  use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::HashMap,
+    time::Instant,
+  };
+
+  // counts the allocations of the current thread, so that tests running next to each other do
+  // not count each other's allocations
+  struct CountingAllocator;
+
+  thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+  }
+
+  unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+      let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+      System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+      System.dealloc(ptr, layout)
+    }
+  }
+
+  #[global_allocator]
+  static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+  fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+  }
+
+  #[test]
+  pub fn test_endorser_key() {
+    assert_eq!(PUBLIC_KEY_LEN, PublicKey::num_bytes());
+    let pk = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    let key = EndorserKey::from_bytes(&pk).unwrap();
+    assert_eq!(key.as_bytes(), pk.as_slice());
+    assert_eq!(key.to_vec(), pk);
+
+    // only public keys make keys
+    assert!(EndorserKey::from_bytes(&pk[1..]).is_none());
+    assert!(EndorserKey::from_bytes(&[0u8; PUBLIC_KEY_LEN]).is_none());
+    assert!(EndorserKey::from_bytes(&[pk.as_slice(), &[0]].concat()).is_none());
+
+    // the connections are looked up by the bytes of a key as well
+    let mut map = HashMap::new();
+    map.insert(key, "http://[::1]:9090");
+    assert_eq!(map.get(pk.as_slice()), Some(&"http://[::1]:9090"));
+    assert_eq!(map.get(&key), Some(&"http://[::1]:9090"));
+  }
+
+  // the setup of a fan-out: every job looks up the connection of its endorser and takes the key
+  // of the endorser along, to attribute the response
+  fn fan_out<K: Clone + Eq + std::hash::Hash>(
+    map: &HashMap<K, usize>,
+    keys: &[K],
+    jobs: &mut Vec<(K, usize)>,
+  ) {
+    jobs.clear();
+    for key in keys {
+      if let Some(client) = map.get(key) {
+        jobs.push((key.clone(), *client));
+      }
+    }
+  }
+
+  #[test]
+  pub fn test_fan_out_allocations() {
+    const NUM_ENDORSERS: usize = 7;
+    const NUM_FAN_OUTS: usize = 10_000;
+    let pks = (0..NUM_ENDORSERS)
+      .map(|_| PrivateKey::new().get_public_key().unwrap().to_bytes())
+      .collect::<Vec<Vec<u8>>>();
+    let keys = pks
+      .iter()
+      .map(|pk| EndorserKey::from_bytes(pk).unwrap())
+      .collect::<Vec<EndorserKey>>();
+    let vec_map = pks.iter().cloned().zip(0..).collect::<HashMap<_, _>>();
+    let key_map = keys.iter().copied().zip(0..).collect::<HashMap<_, _>>();
+
+    let mut vec_jobs = Vec::with_capacity(NUM_ENDORSERS);
+    let (start, before) = (Instant::now(), allocations());
+    for _ in 0..NUM_FAN_OUTS {
+      fan_out(&vec_map, &pks, &mut vec_jobs);
+    }
+    let (vec_elapsed, vec_allocations) = (start.elapsed(), allocations() - before);
+
+    let mut key_jobs = Vec::with_capacity(NUM_ENDORSERS);
+    let (start, before) = (Instant::now(), allocations());
+    for _ in 0..NUM_FAN_OUTS {
+      fan_out(&key_map, &keys, &mut key_jobs);
+    }
+    let (key_elapsed, key_allocations) = (start.elapsed(), allocations() - before);
+
+    println!(
+      "fan-out setup to {} endorsers: Vec<u8> keys {:?} and {} allocations per fan-out, \
+       EndorserKey {:?} and {} allocations per fan-out",
+      NUM_ENDORSERS,
+      vec_elapsed / NUM_FAN_OUTS as u32,
+      vec_allocations / NUM_FAN_OUTS,
+      key_elapsed / NUM_FAN_OUTS as u32,
+      key_allocations / NUM_FAN_OUTS,
+    );
+    assert_eq!(vec_allocations, NUM_ENDORSERS * NUM_FAN_OUTS);
+    assert_eq!(key_allocations, 0);
+    assert_eq!(key_jobs.len(), NUM_ENDORSERS);
+  }
+}
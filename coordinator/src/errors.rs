@@ -1,3 +1,55 @@
+use ledger::{
+  errors::{LedgerError, VerificationError},
+  signature::CryptoError,
+  CustomSerdeError, NimbleDigest,
+};
+use std::fmt;
+use store::errors::{LedgerStoreError, StorageError};
+use tonic::Code;
+
+/// how far a write to a ledger got; the block of a write is persisted before it is endorsed, and
+/// the receipts of the endorsers are persisted last
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WriteStage {
+  /// nothing is written for the request
+  NotStarted,
+  /// the block is in the ledger store, but without a quorum of receipts
+  Persisted,
+  /// a quorum of endorsers signed the block, but their receipts are not in the ledger store
+  Endorsed,
+}
+
+impl fmt::Display for WriteStage {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      WriteStage::NotStarted => write!(f, "not started"),
+      WriteStage::Persisted => write!(f, "persisted but not endorsed"),
+      WriteStage::Endorsed => write!(f, "endorsed but the receipts are not persisted"),
+    }
+  }
+}
+
+/// a limit that the coordinator enforces on the ledgers of a tenant
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TenantQuota {
+  /// the number of ledgers the tenant created
+  Ledgers,
+  /// the number of appends of the tenant in a second
+  AppendRate,
+  /// the bytes of the blocks the tenant stored
+  StoredBytes,
+}
+
+impl fmt::Display for TenantQuota {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TenantQuota::Ledgers => write!(f, "ledgers"),
+      TenantQuota::AppendRate => write!(f, "appends per second"),
+      TenantQuota::StoredBytes => write!(f, "stored bytes"),
+    }
+  }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum CoordinatorError {
   /// returned if the connection clients to the endorser cannot be made by the coordinator
@@ -30,7 +82,7 @@ pub enum CoordinatorError {
   FailedToAcquireWriteLock,
   /// returned if the call to read latest state fails
   FailedToReadLatestState,
-  /// returned if the cooordinator cannot assemble a receipt
+  /// returned if the coordinator cannot assemble a receipt
   EndorsersNotInSync,
   /// returned if the returned receipt is invalid
   InvalidReceipt,
@@ -64,4 +116,290 @@ pub enum CoordinatorError {
   FailedToObtainQuorum,
   /// returned if failed to verify view change
   FailedToActivate,
+  /// returned if a block exceeds the maximum block size
+  BlockTooLarge,
+  /// returned if the metadata of a new ledger exceeds the maximum metadata size
+  MetadataTooLarge,
+  /// returned if the ledger does not exist
+  LedgerNotFound,
+  /// returned if a create finds its handle taken by a ledger with other genesis parameters, with
+  /// what that ledger was created with
+  LedgerConflict { created_at: u64, metadata: Vec<u8> },
+  /// returned if a batch has more items than the maximum batch size
+  BatchTooLarge,
+  /// returned for items of a batch that append to the same ledger as another item
+  DuplicateHandleInBatch,
+  /// returned if a snapshot read names more ledgers than the most it reads at once
+  SnapshotTooLarge,
+  /// returned if a snapshot read names the same ledger more than once
+  DuplicateHandleInSnapshot,
+  /// returned if a snapshot read cannot take the locks of all its ledgers in time
+  SnapshotLocksUnavailable,
+  /// returned if an endorser's ledger state cannot be explained by lagging behind the ledger store
+  EndorserDivergedFromStore,
+  /// returned if a client write arrives while the view of endorsers is changing
+  ViewChangeInProgress,
+  /// returned if a view change would leave fewer endorsers than the configured minimum
+  TooFewEndorsers,
+  /// returned if a view would need fewer endorsers than a majority of its endorsers, or more
+  /// than all of them
+  InvalidQuorum { quorum: usize, num_endorsers: usize },
+  /// returned if the public key of an endorser is not on the allowlist of endorsers
+  EndorserNotAllowlisted,
+  /// returned if the allowlist of endorsers cannot be loaded, or none is configured
+  InvalidEndorserAllowlist(String),
+  /// returned if a conditional append does not find the ledger at the expected height
+  ConditionFailed {
+    current_height: usize,
+    current_tail: NimbleDigest,
+  },
+  /// returned if a read asks for entries beyond the current height of a ledger
+  OutOfRange { current_height: usize },
+  /// returned if the deadline of a client's write expires, with how far the write got
+  DeadlineExceeded(WriteStage),
+  /// returned if a write would take a tenant beyond one of its quotas
+  QuotaExceeded(TenantQuota),
+  /// returned if the coordinator does not serve the tenant
+  UnknownTenant,
+  /// returned if an append targets a ledger whose tail is a seal block
+  LedgerSealed,
+  /// returned if the request ID of an append names an earlier append of another block, which
+  /// left the entry at `height`
+  RequestIdReused { height: usize },
+  /// returned if an append of a pipeline is not acknowledged because an append below it failed;
+  /// its block may be in the ledger store
+  PipelineAborted,
+  /// returned if a read asks for an entry whose block contents were purged, with what the
+  /// entry keeps of the block so that proofs over it still verify
+  ContentPurged {
+    index: usize,
+    block_hash: NimbleDigest,
+    hash_nonces: NimbleDigest,
+  },
+  /// returned if the coordinator runs with a lease and does not hold it, so another coordinator
+  /// may be serving writes
+  LeaseNotHeld,
+  /// returned if the coordinator is shutting down and no longer starts writes
+  ShuttingDown,
+  /// returned if the client of a watch does not keep up with the entries of the ledger, with the
+  /// height that resumes the watch
+  WatchLagged { next_height: usize },
+  /// returned if an endorser answered a call with an error status
+  EndorserStatus(Code, String),
+  /// returned if the gRPC transport to an endorser fails
+  Transport(String),
+  /// returned if a ledger operation (verification, (de)serialization, crypto) fails
+  Ledger(LedgerError),
+}
+
+impl fmt::Display for CoordinatorError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CoordinatorError::FailedToConnectToEndorser => write!(
+        f,
+        "the connection clients to the endorser cannot be made by the coordinator"
+      ),
+      CoordinatorError::CannotResolveHostName => write!(f, "the host name is not correct"),
+      CoordinatorError::UnableToRetrievePublicKey => {
+        write!(f, "the public key returned is invalid")
+      },
+      CoordinatorError::FailedToInitializeEndorser => {
+        write!(f, "the call to initialize the endorser state fails")
+      },
+      CoordinatorError::FailedToCreateLedger => write!(f, "the call to create ledger fails"),
+      CoordinatorError::FailedToAppendLedger => write!(f, "the call to append ledger fails"),
+      CoordinatorError::FailedToReadLedger => write!(f, "the call to read ledger fails"),
+      CoordinatorError::FailedToAppendViewLedger => {
+        write!(f, "the call to append view ledger fails")
+      },
+      CoordinatorError::FailedToReadViewLedger => write!(f, "the call to read view ledger fails"),
+      CoordinatorError::FailedToCallLedgerStore => write!(f, "a call to the ledger store fails"),
+      CoordinatorError::InvalidEndorserPublicKey => {
+        write!(f, "the endorser public key does not exist")
+      },
+      CoordinatorError::InvalidEndorserUri => write!(f, "the endorser uri does not exist"),
+      CoordinatorError::FailedToAcquireReadLock => write!(f, "the read lock cannot be acquired"),
+      CoordinatorError::FailedToAcquireWriteLock => write!(f, "the write lock cannot be acquired"),
+      CoordinatorError::FailedToReadLatestState => write!(f, "the call to read latest state fails"),
+      CoordinatorError::EndorsersNotInSync => {
+        write!(f, "the coordinator cannot assemble a receipt")
+      },
+      CoordinatorError::InvalidReceipt => write!(f, "the returned receipt is invalid"),
+      CoordinatorError::FailedToUnlock => write!(f, "the call to unlock fails"),
+      CoordinatorError::NonUniqueViews => write!(f, "the views of endorsers are different"),
+      CoordinatorError::EmptyLedgerViews => write!(f, "the ledger views are empty"),
+      CoordinatorError::FailedToAttachReceipt => write!(f, "failed to attach receipt"),
+      CoordinatorError::FailedToCreateGenesis => write!(f, "failed to create the genesis entry"),
+      CoordinatorError::InvalidHandle => write!(f, "the provided handle is invalid"),
+      CoordinatorError::InvalidHeight => write!(f, "the provided next height is invalid"),
+      CoordinatorError::FailedToSerde => write!(f, "failed to (de)serialize endorser hostnames"),
+      CoordinatorError::InvalidNonce => write!(f, "the provided nonce is invalid"),
+      CoordinatorError::NoNewEndorsers => write!(f, "no new endorsers added"),
+      CoordinatorError::LedgerAlreadyExists => write!(f, "a ledger or an entry already exists"),
+      CoordinatorError::UnexpectedError => write!(f, "unexpected error"),
+      CoordinatorError::FailedToAttachNonce => {
+        write!(f, "failed to attach nonce into the ledger store")
+      },
+      CoordinatorError::FailedToObtainQuorum => write!(f, "failed to obtain a quorum"),
+      CoordinatorError::FailedToActivate => write!(f, "failed to verify view change"),
+      CoordinatorError::BlockTooLarge => write!(f, "a block exceeds the maximum block size"),
+      CoordinatorError::MetadataTooLarge => {
+        write!(
+          f,
+          "the metadata of a new ledger exceeds the maximum metadata size"
+        )
+      },
+      CoordinatorError::LedgerNotFound => write!(f, "the ledger does not exist"),
+      CoordinatorError::LedgerConflict { created_at, .. } => write!(
+        f,
+        "the handle belongs to a ledger created at {} with other genesis parameters",
+        created_at
+      ),
+      CoordinatorError::BatchTooLarge => write!(f, "a batch exceeds the maximum batch size"),
+      CoordinatorError::DuplicateHandleInBatch => {
+        write!(f, "a batch appends to the same ledger more than once")
+      },
+      CoordinatorError::SnapshotTooLarge => {
+        write!(f, "a snapshot read names more ledgers than the maximum")
+      },
+      CoordinatorError::DuplicateHandleInSnapshot => {
+        write!(f, "a snapshot read names the same ledger more than once")
+      },
+      CoordinatorError::SnapshotLocksUnavailable => {
+        write!(f, "the ledgers of a snapshot read stayed locked too long")
+      },
+      CoordinatorError::EndorserDivergedFromStore => write!(
+        f,
+        "an endorser's ledger state cannot be explained by lagging behind the ledger store"
+      ),
+      CoordinatorError::ViewChangeInProgress => {
+        write!(f, "the view of endorsers is changing")
+      },
+      CoordinatorError::TooFewEndorsers => {
+        write!(
+          f,
+          "a view change would leave fewer endorsers than the minimum"
+        )
+      },
+      CoordinatorError::InvalidQuorum {
+        quorum,
+        num_endorsers,
+      } => write!(
+        f,
+        "a quorum of {} is not a majority of the {} endorsers of the view, or exceeds them",
+        quorum, num_endorsers
+      ),
+      CoordinatorError::EndorserNotAllowlisted => {
+        write!(f, "the endorser is not on the allowlist of endorsers")
+      },
+      CoordinatorError::InvalidEndorserAllowlist(msg) => write!(f, "{}", msg),
+      CoordinatorError::ConditionFailed {
+        current_height,
+        current_tail,
+      } => write!(
+        f,
+        "the ledger is at height {} with tail {}",
+        current_height, current_tail
+      ),
+      CoordinatorError::OutOfRange { current_height } => write!(
+        f,
+        "the read is beyond the current height {} of the ledger",
+        current_height
+      ),
+      CoordinatorError::DeadlineExceeded(stage) => {
+        write!(f, "the deadline of the request expired ({})", stage)
+      },
+      CoordinatorError::QuotaExceeded(quota) => {
+        write!(f, "the write exceeds the quota of the tenant on {}", quota)
+      },
+      CoordinatorError::UnknownTenant => write!(f, "the coordinator does not serve the tenant"),
+      CoordinatorError::LedgerSealed => write!(f, "the ledger is sealed"),
+      CoordinatorError::RequestIdReused { height } => write!(
+        f,
+        "the request ID names the append of another block at height {}",
+        height
+      ),
+      CoordinatorError::PipelineAborted => {
+        write!(
+          f,
+          "an earlier append to the ledger in the same pipeline failed"
+        )
+      },
+      CoordinatorError::ContentPurged {
+        index, block_hash, ..
+      } => write!(
+        f,
+        "the contents of the block at index {} were purged; its hash is {}",
+        index, block_hash
+      ),
+      CoordinatorError::LeaseNotHeld => write!(f, "the coordinator does not hold the lease"),
+      CoordinatorError::ShuttingDown => write!(f, "the coordinator is shutting down"),
+      CoordinatorError::WatchLagged { next_height } => write!(
+        f,
+        "the client of the watch fell behind; it resumes at height {}",
+        next_height
+      ),
+      CoordinatorError::EndorserStatus(code, msg) => {
+        write!(f, "endorser returned {:?}: {}", code, msg)
+      },
+      CoordinatorError::Transport(msg) => write!(f, "transport error: {}", msg),
+      CoordinatorError::Ledger(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for CoordinatorError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      CoordinatorError::Ledger(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<tonic::Status> for CoordinatorError {
+  fn from(status: tonic::Status) -> Self {
+    CoordinatorError::EndorserStatus(status.code(), status.message().to_string())
+  }
+}
+
+impl From<tonic::transport::Error> for CoordinatorError {
+  fn from(e: tonic::transport::Error) -> Self {
+    CoordinatorError::Transport(e.to_string())
+  }
+}
+
+impl From<LedgerError> for CoordinatorError {
+  fn from(e: LedgerError) -> Self {
+    CoordinatorError::Ledger(e)
+  }
+}
+
+impl From<VerificationError> for CoordinatorError {
+  fn from(e: VerificationError) -> Self {
+    CoordinatorError::Ledger(e.into())
+  }
+}
+
+impl From<CustomSerdeError> for CoordinatorError {
+  fn from(e: CustomSerdeError) -> Self {
+    CoordinatorError::Ledger(e.into())
+  }
+}
+
+impl From<CryptoError> for CoordinatorError {
+  fn from(e: CryptoError) -> Self {
+    CoordinatorError::Ledger(e.into())
+  }
+}
+
+impl From<LedgerStoreError> for CoordinatorError {
+  fn from(e: LedgerStoreError) -> Self {
+    match e {
+      LedgerStoreError::LedgerError(StorageError::DuplicateKey) => {
+        CoordinatorError::LedgerAlreadyExists
+      },
+      _ => CoordinatorError::FailedToCallLedgerStore,
+    }
+  }
 }
@@ -0,0 +1,682 @@
+//! An HTTP/JSON gateway to the client service, for clients that do not speak gRPC.
+//!
+//! Each route builds the request of a gRPC method and calls the client service with it, so that
+//! both serve clients alike. Handles, nonces and digests are hex strings, and blocks, metadata
+//! and the other opaque bytes are base64url strings. Receipts are returned in their canonical
+//! encoding, which clients verify, along with a decoded view for reading.
+use crate::{
+  authz::{authorize, Role},
+  coordinator_proto::{
+    call_server::Call, AppendConditionFailed, AppendReq, AppendResp, ContentPurged,
+    IndexOutOfRange, LedgerExists, NewLedgerReq, NewLedgerResp, ReadByIndexReq, ReadByIndexResp,
+    ReadConsistency, ReadLatestReq, ReadLatestResp, WriteDeadlineExceeded,
+  },
+  rate_limit::{rate_limited, Budget, RateLimiter},
+  spnego::NEGOTIATE_SCHEME,
+  telemetry::REQUEST_ID_KEY,
+  tenant::{Authenticator, Tenant},
+  CoordinatorServiceState,
+};
+use axum::{
+  extract::{ConnectInfo, Extension, Path, Query},
+  http::{HeaderMap, HeaderValue, StatusCode},
+  response::{IntoResponse, Response},
+  routing::{get, post},
+  Json, Router,
+};
+use ledger::{signature::SignatureTrait, CustomSerde, Receipts};
+use prost::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{convert::TryFrom, net::SocketAddr, sync::Arc};
+use tonic::{metadata::MetadataValue, Code, Request, Status};
+
+/// what the routes of the gateway share
+pub struct GatewayState {
+  service: Arc<CoordinatorServiceState>,
+  /// authenticates the tenants, if the coordinator serves tenants
+  auth: Option<Authenticator>,
+  /// the limiter of the client service, which the calls through the gateway count against too
+  rate_limiter: Arc<RateLimiter>,
+}
+
+impl GatewayState {
+  pub fn new(service: Arc<CoordinatorServiceState>) -> Self {
+    GatewayState {
+      service,
+      auth: None,
+      rate_limiter: Arc::new(RateLimiter::default()),
+    }
+  }
+
+  /// makes the gateway authenticate clients as tenants
+  pub fn with_auth(mut self, auth: Option<Authenticator>) -> Self {
+    self.auth = auth;
+    self
+  }
+
+  /// makes the calls through the gateway count against the budgets of `rate_limiter`
+  pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+    self.rate_limiter = rate_limiter;
+    self
+  }
+
+  /// the request that the client service gets for a call through the gateway: it carries the
+  /// tenant that the call authenticates as, if its role allows the call, and is charged to the
+  /// budget of its client like the gRPC calls are
+  #[allow(clippy::result_large_err)]
+  fn request<T>(
+    &self,
+    headers: &HeaderMap,
+    remote: SocketAddr,
+    method: &str,
+    budget: Budget,
+    message: T,
+  ) -> Result<Request<T>, Response> {
+    let identity = match &self.auth {
+      Some(auth) => {
+        let authorization = headers
+          .get("authorization")
+          .and_then(|value| value.to_str().ok());
+        let role = Role::of_budget(budget);
+        let identity = authorize(auth, authorization, method, role).map_err(|status| {
+          let unauthenticated = status.code() == Code::Unauthenticated;
+          let mut response = error_response(status);
+          // challenges the clients that can negotiate, as WebHDFS does
+          if unauthenticated && auth.supports(NEGOTIATE_SCHEME) {
+            response.headers_mut().insert(
+              "www-authenticate",
+              HeaderValue::from_static(NEGOTIATE_SCHEME),
+            );
+          }
+          response
+        })?;
+        Some(identity)
+      },
+      None => None,
+    };
+    let tenant = identity
+      .as_ref()
+      .and_then(|identity| identity.tenant.clone());
+    let key = match &tenant {
+      Some(Tenant(id)) => id.clone(),
+      None => remote.ip().to_string(),
+    };
+    if let Err(retry_after) = self.rate_limiter.check(&key, budget) {
+      return Err(error_response(rate_limited(retry_after)));
+    }
+
+    let mut request = Request::new(message);
+    if let Some(tenant) = tenant {
+      request.extensions_mut().insert(tenant);
+    }
+    // the audit log records who made the call
+    if let Some(identity) = identity {
+      request.extensions_mut().insert(identity);
+    }
+    // the service traces the request under the ID that the client gave it, if any
+    if let Some(request_id) = headers
+      .get(REQUEST_ID_KEY)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| MetadataValue::try_from(value).ok())
+    {
+      request.metadata_mut().insert(REQUEST_ID_KEY, request_id);
+    }
+    Ok(request)
+  }
+}
+
+/// the routes of the gateway
+pub fn router(gateway: GatewayState) -> Router {
+  Router::new()
+    .route("/ledgers", post(new_ledger))
+    .route("/ledgers/:handle/blocks", post(append))
+    .route("/ledgers/:handle/blocks/:height", get(read_block))
+    .route("/ledgers/:handle/tail", get(read_tail))
+    .layer(Extension(Arc::new(gateway)))
+}
+
+/// the HTTP status of a gRPC code; the client service reports internal failures as aborted
+fn http_status(code: Code) -> StatusCode {
+  match code {
+    Code::Ok => StatusCode::OK,
+    Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+    Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+    Code::PermissionDenied => StatusCode::FORBIDDEN,
+    Code::NotFound => StatusCode::NOT_FOUND,
+    Code::AlreadyExists => StatusCode::CONFLICT,
+    Code::FailedPrecondition => StatusCode::PRECONDITION_FAILED,
+    Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+    Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+    Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+    _ => StatusCode::INTERNAL_SERVER_ERROR,
+  }
+}
+
+/// the details that the client service attaches to a status, decoded
+fn status_details(status: &Status) -> Value {
+  let details = status.details();
+  match status.code() {
+    Code::FailedPrecondition => match AppendConditionFailed::decode(details) {
+      Ok(d) if !details.is_empty() => json!({
+        "current_height": d.current_height,
+        "current_tail": hex::encode(d.current_tail),
+      }),
+      _ => Value::Null,
+    },
+    Code::OutOfRange => match IndexOutOfRange::decode(details) {
+      Ok(d) if !details.is_empty() => json!({ "current_height": d.current_height }),
+      _ => Value::Null,
+    },
+    Code::AlreadyExists => match LedgerExists::decode(details) {
+      Ok(d) if !details.is_empty() => json!({
+        "created_at": d.created_at,
+        "metadata": base64_url::encode(&d.metadata),
+      }),
+      _ => Value::Null,
+    },
+    Code::NotFound => match ContentPurged::decode(details) {
+      Ok(d) if !details.is_empty() => json!({
+        "index": d.index,
+        "block_hash": hex::encode(d.block_hash),
+        "hash_nonces": hex::encode(d.hash_nonces),
+      }),
+      _ => Value::Null,
+    },
+    Code::DeadlineExceeded => match WriteDeadlineExceeded::decode(details) {
+      Ok(d) if !details.is_empty() => json!({ "stage": format!("{:?}", d.stage()) }),
+      _ => Value::Null,
+    },
+    _ => Value::Null,
+  }
+}
+
+/// the response to a call that the client service or the gateway rejected
+fn error_response(status: Status) -> Response {
+  let body = json!({
+    "code": format!("{:?}", status.code()),
+    "error": status.message(),
+    "details": status_details(&status),
+  });
+  let mut response = (http_status(status.code()), Json(body)).into_response();
+  if let Some(retry_after) = status
+    .metadata()
+    .get("retry-after")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| HeaderValue::from_str(value).ok())
+  {
+    response.headers_mut().insert("retry-after", retry_after);
+  }
+  response
+}
+
+#[allow(clippy::result_large_err)]
+fn decode_hex(value: &str, name: &str) -> Result<Vec<u8>, Response> {
+  hex::decode(value)
+    .map_err(|_e| error_response(Status::invalid_argument(format!("Invalid {}", name))))
+}
+
+#[allow(clippy::result_large_err)]
+fn decode_base64(value: &str, name: &str) -> Result<Vec<u8>, Response> {
+  base64_url::decode(value)
+    .map_err(|_e| error_response(Status::invalid_argument(format!("Invalid {}", name))))
+}
+
+/// a view of receipts for reading: the metablocks they cover and who signed each one; receipts
+/// that do not decode have no view
+fn decoded_receipts(bytes: &[u8]) -> Value {
+  let receipts = match Receipts::from_bytes(bytes) {
+    Ok(receipts) => receipts,
+    Err(_) => return Value::Null,
+  };
+  receipts
+    .get()
+    .iter()
+    .map(|(ex_meta_block, id_sigs)| {
+      let metablock = ex_meta_block.get_metablock();
+      json!({
+        "view": hex::encode(ex_meta_block.get_view().to_bytes()),
+        "height": metablock.get_height(),
+        "prev": hex::encode(metablock.get_prev().to_bytes()),
+        "block_hash": hex::encode(metablock.get_block_hash().to_bytes()),
+        "signatures": id_sigs
+          .iter()
+          .map(|id_sig| json!({
+            "public_key": hex::encode(id_sig.get_id()),
+            "signature": hex::encode(id_sig.get_sig().to_bytes()),
+          }))
+          .collect::<Vec<_>>(),
+      })
+    })
+    .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewLedgerBody {
+  /// hex; empty if the handle is derived from `app_bytes` and `nonce`
+  #[serde(default)]
+  handle: String,
+  /// base64url
+  #[serde(default)]
+  block: String,
+  /// base64url
+  #[serde(default)]
+  app_bytes: String,
+  /// hex
+  #[serde(default)]
+  nonce: String,
+  /// base64url
+  #[serde(default)]
+  metadata: String,
+}
+
+async fn new_ledger(
+  Extension(gateway): Extension<Arc<GatewayState>>,
+  ConnectInfo(remote): ConnectInfo<SocketAddr>,
+  headers: HeaderMap,
+  Json(body): Json<NewLedgerBody>,
+) -> Result<Json<Value>, Response> {
+  let message = NewLedgerReq {
+    handle: decode_hex(&body.handle, "handle")?,
+    block: decode_base64(&body.block, "block")?,
+    app_bytes: decode_base64(&body.app_bytes, "app_bytes")?,
+    nonce: decode_hex(&body.nonce, "nonce")?,
+    metadata: decode_base64(&body.metadata, "metadata")?,
+  };
+  let method = "/coordinator_proto.Call/NewLedger";
+  let request = gateway.request(&headers, remote, method, Budget::Appends, message)?;
+  let NewLedgerResp { receipts, handle } = gateway
+    .service
+    .new_ledger(request)
+    .await
+    .map_err(error_response)?
+    .into_inner();
+  Ok(Json(json!({
+    "handle": hex::encode(handle),
+    "receipts": base64_url::encode(&receipts),
+    "decoded_receipts": decoded_receipts(&receipts),
+  })))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AppendBody {
+  /// base64url
+  block: String,
+  /// the height of the tail that the block is appended to
+  expected_height: u64,
+  /// names the append, so that a retry with the same ID returns the entry it left
+  #[serde(default)]
+  request_id: String,
+}
+
+async fn append(
+  Path(handle): Path<String>,
+  Extension(gateway): Extension<Arc<GatewayState>>,
+  ConnectInfo(remote): ConnectInfo<SocketAddr>,
+  headers: HeaderMap,
+  Json(body): Json<AppendBody>,
+) -> Result<Json<Value>, Response> {
+  let message = AppendReq {
+    handle: decode_hex(&handle, "handle")?,
+    block: decode_base64(&body.block, "block")?,
+    expected_height: body.expected_height,
+    request_id: body.request_id,
+  };
+  let method = "/coordinator_proto.Call/Append";
+  let request = gateway.request(&headers, remote, method, Budget::Appends, message)?;
+  let AppendResp {
+    hash_nonces,
+    receipts,
+    height,
+  } = gateway
+    .service
+    .append(request)
+    .await
+    .map_err(error_response)?
+    .into_inner();
+  Ok(Json(json!({
+    "height": height,
+    "hash_nonces": hex::encode(hash_nonces),
+    "receipts": base64_url::encode(&receipts),
+    "decoded_receipts": decoded_receipts(&receipts),
+  })))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReadTailQuery {
+  /// hex; ignored if the read is cached
+  #[serde(default)]
+  nonce: String,
+  /// `attested` by default, or `cached`
+  #[serde(default)]
+  consistency: Option<String>,
+}
+
+async fn read_tail(
+  Path(handle): Path<String>,
+  Query(query): Query<ReadTailQuery>,
+  Extension(gateway): Extension<Arc<GatewayState>>,
+  ConnectInfo(remote): ConnectInfo<SocketAddr>,
+  headers: HeaderMap,
+) -> Result<Json<Value>, Response> {
+  let consistency = match query.consistency.as_deref() {
+    None | Some("attested") => ReadConsistency::Attested,
+    Some("cached") => ReadConsistency::Cached,
+    Some(_) => {
+      return Err(error_response(Status::invalid_argument(
+        "Invalid consistency",
+      )))
+    },
+  };
+  let message = ReadLatestReq {
+    handle: decode_hex(&handle, "handle")?,
+    nonce: decode_hex(&query.nonce, "nonce")?,
+    consistency: consistency as i32,
+  };
+  let method = "/coordinator_proto.Call/ReadLatest";
+  let request = gateway.request(&headers, remote, method, Budget::Reads, message)?;
+  let resp = gateway
+    .service
+    .read_latest(request)
+    .await
+    .map_err(error_response)?
+    .into_inner();
+  let ReadLatestResp {
+    block,
+    nonces,
+    receipts,
+    height,
+    nonce,
+    ..
+  } = &resp;
+  let consistency = match resp.consistency() {
+    ReadConsistency::Attested => "attested",
+    ReadConsistency::Cached => "cached",
+  };
+  Ok(Json(json!({
+    "block": base64_url::encode(block),
+    "nonces": base64_url::encode(nonces),
+    "height": height,
+    "nonce": hex::encode(nonce),
+    "consistency": consistency,
+    "receipts": base64_url::encode(receipts),
+    "decoded_receipts": decoded_receipts(receipts),
+  })))
+}
+
+async fn read_block(
+  Path((handle, height)): Path<(String, u64)>,
+  Extension(gateway): Extension<Arc<GatewayState>>,
+  ConnectInfo(remote): ConnectInfo<SocketAddr>,
+  headers: HeaderMap,
+) -> Result<Json<Value>, Response> {
+  let message = ReadByIndexReq {
+    handle: decode_hex(&handle, "handle")?,
+    index: height,
+  };
+  let method = "/coordinator_proto.Call/ReadByIndex";
+  let request = gateway.request(&headers, remote, method, Budget::Reads, message)?;
+  let ReadByIndexResp {
+    block,
+    nonces,
+    receipts,
+    metablock_hash,
+    checkpoint,
+    block_hashes,
+    tail_receipts,
+  } = gateway
+    .service
+    .read_by_index(request)
+    .await
+    .map_err(error_response)?
+    .into_inner();
+  Ok(Json(json!({
+    "block": base64_url::encode(&block),
+    "nonces": base64_url::encode(&nonces),
+    "metablock_hash": hex::encode(metablock_hash),
+    "checkpoint": base64_url::encode(&checkpoint),
+    "block_hashes": block_hashes.iter().map(hex::encode).collect::<Vec<_>>(),
+    "receipts": base64_url::encode(&receipts),
+    "decoded_receipts": decoded_receipts(&receipts),
+    "tail_receipts": base64_url::encode(&tail_receipts),
+    "decoded_tail_receipts": decoded_receipts(&tail_receipts),
+  })))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{router, GatewayState};
+  use crate::{
+    authz::{api_key_hash, ApiKeyConfig, ApiKeys, Role},
+    coordinator_state::CoordinatorState,
+    rate_limit::RateLimiter,
+    spnego::{Acceptor, Spnego},
+    tenant::Authenticator,
+    CoordinatorServiceState,
+  };
+  use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+  };
+  use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::Arc,
+  };
+  use tower::ServiceExt;
+
+  fn request(method: &str, uri: &str, token: Option<&str>, body: &str) -> Request<Body> {
+    let mut builder = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header("content-type", "application/json");
+    if let Some(token) = token {
+      builder = builder.header("authorization", format!("Bearer {}", token));
+    }
+    let mut req = builder.body(Body::from(body.to_string())).unwrap();
+    let remote: SocketAddr = "[::1]:5000".parse().unwrap();
+    req.extensions_mut().insert(ConnectInfo(remote));
+    req
+  }
+
+  #[tokio::test]
+  async fn test_gateway_errors() {
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let service = Arc::new(CoordinatorServiceState::new(state));
+    let tenants = HashMap::from([("token-a".to_string(), "hdfs-a".to_string())]);
+    let app = router(
+      GatewayState::new(service)
+        .with_auth(Some(tenants.into()))
+        .with_rate_limiter(Arc::new(RateLimiter::default())),
+    );
+
+    // errors of the client service map to HTTP statuses
+    let res = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        "/ledgers/0a0b/tail?consistency=cached",
+        Some("token-a"),
+        "",
+      ))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        "/ledgers/0a0b/blocks/0",
+        Some("token-a"),
+        "",
+      ))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // the gateway rejects what the client service would not accept
+    let res = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        "/ledgers/0a0b/tail?consistency=cached",
+        Some("other"),
+        "",
+      ))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = app
+      .clone()
+      .oneshot(request("GET", "/ledgers/zz/tail", Some("token-a"), ""))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        "/ledgers/0a0b/tail?consistency=eventual",
+        Some("token-a"),
+        "",
+      ))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = app
+      .oneshot(request(
+        "POST",
+        "/ledgers/0a0b/blocks",
+        Some("token-a"),
+        r#"{"block": "!!", "expected_height": 0}"#,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+  }
+
+  /// accepts every token as the principal that it spells
+  struct PlainAcceptor;
+
+  impl Acceptor for PlainAcceptor {
+    fn accept(&self, token: &[u8]) -> Result<String, String> {
+      String::from_utf8(token.to_vec()).map_err(|e| e.to_string())
+    }
+  }
+
+  #[tokio::test]
+  async fn test_gateway_negotiate() {
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let service = Arc::new(CoordinatorServiceState::new(state));
+    let tenants = HashMap::from([("token-a".to_string(), "hdfs".to_string())]);
+    let spnego = Spnego::new(
+      Box::new(PlainAcceptor),
+      vec!["EXAMPLE.COM".to_string()],
+      tenants.values().cloned().collect(),
+    );
+    let auth = Authenticator::from(tenants).with_auth(Arc::new(spnego));
+    let app = router(GatewayState::new(service.clone()).with_auth(Some(auth)));
+    let negotiate = |principal: &str| {
+      let mut req = request("GET", "/ledgers/0a0b/tail?consistency=cached", None, "");
+      let value = format!("Negotiate {}", base64_url::encode(principal));
+      req
+        .headers_mut()
+        .insert("authorization", value.parse().unwrap());
+      req
+    };
+
+    // clients that do not authenticate are challenged to negotiate
+    let res = app
+      .clone()
+      .oneshot(request("GET", "/ledgers/0a0b/tail", None, ""))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers()["www-authenticate"], "Negotiate");
+    let res = app
+      .clone()
+      .oneshot(negotiate("hdfs@OTHER.COM"))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers()["www-authenticate"], "Negotiate");
+
+    // a principal of a tenant reaches the service, which does not know the ledger
+    let res = app
+      .clone()
+      .oneshot(negotiate("hdfs/nn1.example.com@EXAMPLE.COM"))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = app.oneshot(negotiate("yarn@EXAMPLE.COM")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(res.headers().get("www-authenticate").is_none());
+
+    // without SPNEGO, clients are not challenged
+    let tenants = HashMap::from([("token-a".to_string(), "hdfs".to_string())]);
+    let app = router(GatewayState::new(service).with_auth(Some(tenants.into())));
+    let res = app
+      .oneshot(request("GET", "/ledgers/0a0b/tail", None, ""))
+      .await
+      .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(res.headers().get("www-authenticate").is_none());
+  }
+
+  #[tokio::test]
+  async fn test_gateway_roles() {
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let service = Arc::new(CoordinatorServiceState::new(state));
+    let keys = BTreeMap::from([(
+      "dashboard".to_string(),
+      ApiKeyConfig {
+        hash: api_key_hash("key-r"),
+        role: Role::Reader,
+        tenant: "hdfs".to_string(),
+      },
+    )]);
+    let tenants = HashMap::from([("token-a".to_string(), "hdfs".to_string())]);
+    let auth = Authenticator::from(tenants).with_auth(Arc::new(ApiKeys::new(&keys)));
+    let app = router(GatewayState::new(service).with_auth(Some(auth)));
+    let with_key = |mut req: Request<Body>| {
+      req
+        .headers_mut()
+        .insert("authorization", "ApiKey key-r".parse().unwrap());
+      req
+    };
+    let append = || {
+      let body = r#"{"block": "YWJj", "expected_height": 0}"#;
+      request("POST", "/ledgers/0a0b/blocks", None, body)
+    };
+
+    // a reader reads, but does not append; a tenant gets to append
+    let read = request("GET", "/ledgers/0a0b/tail?consistency=cached", None, "");
+    let res = app.clone().oneshot(with_key(read)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = app.clone().oneshot(with_key(append())).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let mut req = append();
+    req
+      .headers_mut()
+      .insert("authorization", "Bearer token-a".parse().unwrap());
+    let res = app.oneshot(req).await.unwrap();
+    assert_ne!(res.status(), StatusCode::FORBIDDEN);
+  }
+}
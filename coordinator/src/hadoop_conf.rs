@@ -0,0 +1,336 @@
+//! Hadoop configuration files (`*-site.xml`), which the coordinator and the endorser read so that
+//! the operators of HDFS configure Nimble where they configure HDFS.
+//!
+//! Such a file is a `<configuration>` of `<property>` elements, each with a `<name>`, a `<value>`,
+//! and optionally a `<final>`, which are read from the events of `quick-xml`. Attributes and other
+//! elements, such as `<description>`, are skipped; a document type declaration is an error, as in
+//! Hadoop. It depends on nothing of the coordinator, so that the endorser and `fuzz/` compile it
+//! in.
+use quick_xml::{events::Event, Reader};
+use std::collections::{BTreeMap, HashSet};
+
+/// the properties of one or more Hadoop configuration files, read in order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HadoopConf {
+  values: BTreeMap<String, String>,
+  finals: HashSet<String>,
+}
+
+/// a `<property>` as it is read, with the trimmed text of the first of each of its elements
+#[derive(Default)]
+struct Property {
+  line: usize,
+  name: Option<String>,
+  value: Option<String>,
+  is_final: Option<String>,
+}
+
+impl Property {
+  fn set(&mut self, element: &str, text: &str) {
+    let field = match element {
+      "name" => &mut self.name,
+      "value" => &mut self.value,
+      "final" => &mut self.is_final,
+      _ => return,
+    };
+    if field.is_none() {
+      *field = Some(text.trim().to_string());
+    }
+  }
+}
+
+impl HadoopConf {
+  /// parses a single configuration file
+  pub fn parse(xml: &str) -> Result<Self, String> {
+    let mut conf = HadoopConf::default();
+    conf.add_resource(xml)?;
+    Ok(conf)
+  }
+
+  /// adds the properties of a configuration file, which override those added before unless those
+  /// are final, as in Hadoop; returns a warning per final property that the file tries to change
+  pub fn add_resource(&mut self, xml: &str) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    for property in read_properties(xml)? {
+      let name = match property.name {
+        Some(name) if !name.is_empty() => name,
+        _ => return Err(format!("line {}: <property> without <name>", property.line)),
+      };
+      // a property without a value is unset, as in Hadoop
+      let value = match property.value {
+        Some(value) => value,
+        None => continue,
+      };
+      if self.finals.contains(&name) {
+        if self.values.get(&name) != Some(&value) {
+          warnings.push(format!(
+            "line {}: {} is final and keeps its earlier value",
+            property.line, name
+          ));
+        }
+        continue;
+      }
+      if property.is_final.as_deref() == Some("true") {
+        self.finals.insert(name.clone());
+      }
+      self.values.insert(name, value);
+    }
+    Ok(warnings)
+  }
+
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.values.get(name).map(|value| value.as_str())
+  }
+
+  /// the names of the properties that start with `prefix`, in order
+  pub fn names_with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    self
+      .values
+      .keys()
+      .filter(move |name| name.starts_with(prefix))
+      .map(|name| name.as_str())
+  }
+}
+
+/// renders `properties` as a configuration file that `HadoopConf::parse` reads back
+pub fn to_xml<'a>(properties: impl IntoIterator<Item = (&'a str, String)>) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\"?>\n<configuration>\n");
+  for (name, value) in properties {
+    xml.push_str(&format!(
+      "  <property>\n    <name>{}</name>\n    <value>{}</value>\n  </property>\n",
+      quick_xml::escape::escape(name),
+      quick_xml::escape::escape(&value)
+    ));
+  }
+  xml.push_str("</configuration>\n");
+  xml
+}
+
+/// the line of the byte at `pos` of `xml`
+fn line_at(xml: &str, pos: u64) -> usize {
+  let pos = (pos as usize).min(xml.len());
+  xml.as_bytes()[..pos]
+    .iter()
+    .filter(|&&b| b == b'\n')
+    .count()
+    + 1
+}
+
+/// reads the properties of the `<configuration>` of `xml`, in order
+fn read_properties(xml: &str) -> Result<Vec<Property>, String> {
+  let mut reader = Reader::from_str(xml);
+  let mut properties: Vec<Property> = Vec::new();
+  // the elements that are open, from the root, and the text of an element of a property
+  let mut open: Vec<String> = Vec::new();
+  let mut text = String::new();
+  let mut has_root = false;
+  loop {
+    let event = reader
+      .read_event()
+      .map_err(|e| format!("line {}: {}", line_at(xml, reader.error_position()), e))?;
+    let error = |msg: &str| format!("line {}: {}", line_at(xml, reader.buffer_position()), msg);
+    // whether the event is within an element of a property, such as its <name>
+    let in_property = open.len() == 3 && open[1] == "property";
+    match event {
+      Event::Start(ref e) | Event::Empty(ref e) => {
+        let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+        if open.is_empty() {
+          if has_root {
+            return Err(error("unexpected content after the root element"));
+          }
+          if name != "configuration" {
+            return Err(error(&format!(
+              "expected <configuration>, found <{}>",
+              name
+            )));
+          }
+          has_root = true;
+        } else if open.len() == 1 && name == "property" {
+          properties.push(Property {
+            line: line_at(xml, reader.buffer_position()),
+            ..Property::default()
+          });
+        }
+        if matches!(event, Event::Empty(_)) {
+          // an empty element of a property, such as <value/>, has no text
+          if open.len() == 2 && open[1] == "property" {
+            if let Some(property) = properties.last_mut() {
+              property.set(&name, "");
+            }
+          }
+        } else {
+          if open.len() == 2 {
+            text.clear();
+          }
+          open.push(name);
+        }
+      },
+      Event::End(_e) => {
+        if in_property {
+          if let Some(property) = properties.last_mut() {
+            property.set(&open[2], &text);
+          }
+        }
+        open.pop();
+      },
+      Event::Text(e) => {
+        let unescaped = e.unescape().map_err(|e| error(&e.to_string()))?;
+        if open.is_empty() && !unescaped.trim().is_empty() {
+          return Err(error("unexpected content outside of the root element"));
+        }
+        if in_property {
+          text.push_str(&unescaped);
+        }
+      },
+      Event::CData(e) => {
+        if open.is_empty() {
+          return Err(error("unexpected content outside of the root element"));
+        }
+        if in_property {
+          text.push_str(&String::from_utf8_lossy(&e));
+        }
+      },
+      Event::DocType(_e) => {
+        return Err(error("document type declarations are not supported"));
+      },
+      Event::Decl(_) | Event::PI(_) | Event::Comment(_) => {},
+      Event::Eof => {
+        if let Some(name) = open.last() {
+          return Err(error(&format!("unterminated <{}>", name)));
+        }
+        if !has_root {
+          return Err(error("expected an element"));
+        }
+        return Ok(properties);
+      },
+    }
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_configuration() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?xml-stylesheet type="text/xsl" href="configuration.xsl"?>
+<!-- the site configuration -->
+<configuration>
+  <property>
+    <name>dfs.replication</name>
+    <value>3</value>
+    <description>not <b>read</b></description>
+  </property>
+  <property>
+    <name> nimble.coordinator.endorsers </name>
+    <value>http://a:9090,http://b:9090</value>
+    <final>true</final>
+  </property>
+  <property name="ignored">
+    <name>nimble.coordinator.admin.token</name>
+    <value>a&amp;b&#x3c;&#62;<![CDATA[<&>]]></value>
+  </property>
+  <property><name>unset</name></property>
+  <property><name>empty</name><value/></property>
+</configuration>
+"#;
+    let mut conf = HadoopConf::parse(xml).unwrap();
+    assert_eq!(conf.get("dfs.replication"), Some("3"));
+    assert_eq!(
+      conf.get("nimble.coordinator.endorsers"),
+      Some("http://a:9090,http://b:9090")
+    );
+    assert_eq!(conf.get("nimble.coordinator.admin.token"), Some("a&b<><&>"));
+    assert_eq!(conf.get("unset"), None);
+    assert_eq!(conf.get("empty"), Some(""));
+    let names = conf.names_with_prefix("nimble.").collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      vec![
+        "nimble.coordinator.admin.token",
+        "nimble.coordinator.endorsers"
+      ]
+    );
+
+    // a later file overrides the properties that are not final
+    let later = to_xml(vec![
+      ("dfs.replication", "2".to_string()),
+      ("nimble.coordinator.endorsers", "http://c:9090".to_string()),
+    ]);
+    let warnings = conf.add_resource(&later).unwrap();
+    assert_eq!(conf.get("dfs.replication"), Some("2"));
+    assert_eq!(
+      conf.get("nimble.coordinator.endorsers"),
+      Some("http://a:9090,http://b:9090")
+    );
+    assert_eq!(
+      warnings,
+      vec!["line 7: nimble.coordinator.endorsers is final and keeps its earlier value"]
+    );
+
+    // what is rendered reads back
+    let rendered = to_xml(vec![("a<b", "x & <y>".to_string())]);
+    assert_eq!(
+      HadoopConf::parse(&rendered).unwrap().get("a<b"),
+      Some("x & <y>")
+    );
+  }
+
+  #[test]
+  fn test_malformed_configuration() {
+    let error = |xml: &str| HadoopConf::parse(xml).unwrap_err();
+    assert_eq!(error(""), "line 1: expected an element");
+    assert_eq!(
+      error("<configuration>\n<property>\n</configuration>"),
+      "line 3: ill-formed document: expected `</property>`, but `</configuration>` was found"
+    );
+    assert_eq!(
+      error("<configuration>\n<property><name>a</name>"),
+      "line 2: unterminated <property>"
+    );
+    assert_eq!(
+      error("<conf></conf>"),
+      "line 1: expected <configuration>, found <conf>"
+    );
+    assert_eq!(
+      error("<configuration><property><value>1</value></property></configuration>"),
+      "line 1: <property> without <name>"
+    );
+    assert!(error("<configuration>&nbsp;</configuration>").contains("unrecognized entity `nbsp`"));
+    assert_eq!(
+      error("<!DOCTYPE x [<!ENTITY a \"b\">]><configuration/>"),
+      "line 1: document type declarations are not supported"
+    );
+    assert_eq!(
+      error("<configuration/>\n<configuration/>"),
+      "line 2: unexpected content after the root element"
+    );
+    assert_eq!(
+      error("<configuration/>\ntext"),
+      "line 2: unexpected content outside of the root element"
+    );
+    assert!(error("<configuration><!-- x").contains("comment not closed"));
+
+    // the events are read without recursion, so a deep document is an error like any other
+    let nested = "<a>".repeat(100_000);
+    assert_eq!(
+      error(&format!("<configuration>{}", nested)),
+      "line 1: unterminated <a>"
+    );
+  }
+
+  fn check_hadoop_conf(data: &[u8]) {
+    if let Ok(xml) = std::str::from_utf8(data) {
+      let _ = HadoopConf::parse(xml);
+    }
+  }
+
+  #[test]
+  fn test_configuration_mutations() {
+    let xml = to_xml(vec![
+      ("nimble.endpoint.url", "http://localhost:8082".to_string()),
+      ("nimble.fs.ledger-prefix", "a&b<c>".to_string()),
+    ]);
+    ledger::fuzz::run_bounded("hadoop_conf", check_hadoop_conf, &[xml.into_bytes()]);
+  }
+}
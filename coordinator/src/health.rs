@@ -0,0 +1,403 @@
+//! Whether the coordinator is ready to serve clients, as reported to orchestrators by the gRPC
+//! health service and by `/readyz` and `/livez` on the metrics port.
+//!
+//! The coordinator is ready once it recovered at startup, while it is connected to a quorum of
+//! the endorsers of its view and its ledger store does not fail writes, and, if it runs a canary,
+//! while its last canary passed. Readiness is checked
+//! periodically and changes only after the same outcome in several checks in a row, so that a
+//! single slow endorser or failed write does not make it flap. Until it recovered, `/readyz`
+//! reports how far recovery is in its scan of the ledgers.
+use std::{
+  fmt,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+  },
+  time::Duration,
+};
+use store::errors::{LedgerStoreError, StorageError};
+use tokio::time::Instant;
+
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const UNHEALTHY_CHECKS: usize = 3; // failed checks in a row after which it is not ready
+const HEALTHY_CHECKS: usize = 5; // passed checks in a row after which it is ready again
+const FAILED_STORE_WRITES: usize = 3; // failed writes in a row after which the store is failing
+const STORE_FAILURE_WINDOW: Duration = Duration::from_secs(30); // how long a failing store counts
+const LIVENESS_TIMEOUT: Duration = Duration::from_secs(10); // the longest a check may be overdue
+
+/// what a health check looks at
+#[derive(Clone, Copy, Debug)]
+pub struct HealthInputs {
+  /// the number of endorsers that the coordinator is connected to, and that answer their
+  /// heartbeats if it sends them
+  pub num_connected: usize,
+  /// the number of endorsers in the current view
+  pub view_size: usize,
+  /// the number of endorsers whose receipts the current view needs, as its view block records it
+  pub quorum: usize,
+  /// whether the coordinator accepts writes, i.e., it is not shutting down and holds its lease
+  pub accepts_writes: bool,
+}
+
+#[derive(Default)]
+struct Checks {
+  /// when the last check ran
+  last: Option<Instant>,
+  /// the number of checks in a row whose outcome differs from the readiness
+  streak: usize,
+  /// whether the coordinator was ever ready
+  was_ready: bool,
+}
+
+#[derive(Default)]
+struct StoreWrites {
+  /// the number of failed writes since the last write that succeeded
+  num_failed: usize,
+  last_failure: Option<Instant>,
+}
+
+/// how far the startup recovery is in a scan of the ledgers in the store
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecoveryProgress {
+  /// what recovery scans the ledgers for
+  pub phase: &'static str,
+  /// the number of ledgers that the scan went through
+  pub scanned: usize,
+  /// the estimated number of ledgers in the store, if the store estimates it
+  pub total: Option<usize>,
+}
+
+impl fmt::Display for RecoveryProgress {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.total {
+      Some(total) => write!(f, "{}: {}/{} ledgers", self.phase, self.scanned, total),
+      None => write!(f, "{}: {} ledgers", self.phase, self.scanned),
+    }
+  }
+}
+
+/// the outcome of a canary, the self-test of the pipeline in `canary.rs`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanaryOutcome {
+  /// the phase that failed and why, if the canary failed
+  pub failure: Option<String>,
+  /// how long the canary took, up to the phase that failed
+  pub latency: Duration,
+}
+
+#[derive(Default)]
+pub struct Health {
+  recovered: AtomicBool,
+  ready: AtomicBool,
+  /// whether the coordinator is ready only once a canary passed
+  canary_required: AtomicBool,
+  /// the outcome of the last canary
+  canary: Mutex<Option<CanaryOutcome>>,
+  /// the scan of the ledgers that recovery runs, if any
+  recovery: Mutex<Option<RecoveryProgress>>,
+  store_writes: Mutex<StoreWrites>,
+  checks: Mutex<Checks>,
+}
+
+/// whether an error of the ledger store means that the store failed rather than that it refused
+/// the write, e.g., because the entry already exists
+fn is_store_failure(error: &LedgerStoreError) -> bool {
+  match error {
+    LedgerStoreError::MongoDBError(_) => true,
+    LedgerStoreError::LedgerError(error) => matches!(
+      error,
+      StorageError::UnhandledError
+        | StorageError::CorruptedLedger
+        | StorageError::ViewLedgerReadLockFailed
+        | StorageError::ViewLedgerWriteLockFailed
+        | StorageError::LedgerMapReadLockFailed
+        | StorageError::LedgerMapWriteLockFailed
+        | StorageError::LedgerReadLockFailed
+        | StorageError::LedgerWriteLockFailed
+    ),
+  }
+}
+
+impl Health {
+  pub fn new() -> Self {
+    Health::default()
+  }
+
+  /// marks the startup recovery as done; the coordinator is not ready before
+  pub fn set_recovered(&self) {
+    self.recovered.store(true, Ordering::SeqCst);
+    if let Ok(mut recovery) = self.recovery.lock() {
+      *recovery = None;
+    }
+  }
+
+  /// starts a scan of the ledgers in the store for `phase` of recovery, which goes through about
+  /// `total` ledgers; scans after recovery are not tracked
+  pub fn start_recovery_scan(&self, phase: &'static str, total: Option<usize>) {
+    if self.recovered.load(Ordering::SeqCst) {
+      return;
+    }
+    if let Ok(mut recovery) = self.recovery.lock() {
+      *recovery = Some(RecoveryProgress {
+        phase,
+        scanned: 0,
+        total,
+      });
+    }
+  }
+
+  /// records that the scan of recovery went through `scanned` more ledgers
+  pub fn record_recovery_scan(&self, scanned: usize) {
+    if let Ok(mut recovery) = self.recovery.lock() {
+      if let Some(progress) = recovery.as_mut() {
+        progress.scanned += scanned;
+      }
+    }
+  }
+
+  /// how far recovery is in its scan of the ledgers, while it runs one
+  pub fn recovery_progress(&self) -> Option<RecoveryProgress> {
+    self.recovery.lock().ok()?.clone()
+  }
+
+  /// keeps the coordinator from being ready until a canary passed
+  pub fn require_canary(&self) {
+    self.canary_required.store(true, Ordering::SeqCst);
+  }
+
+  pub fn record_canary(&self, outcome: CanaryOutcome) {
+    if let Ok(mut canary) = self.canary.lock() {
+      *canary = Some(outcome);
+    }
+  }
+
+  /// the outcome of the last canary, if one ran
+  pub fn last_canary(&self) -> Option<CanaryOutcome> {
+    self.canary.lock().ok()?.clone()
+  }
+
+  fn is_canary_passing(&self) -> bool {
+    if !self.canary_required.load(Ordering::SeqCst) {
+      return true;
+    }
+    matches!(self.last_canary(), Some(outcome) if outcome.failure.is_none())
+  }
+
+  pub fn is_ready(&self) -> bool {
+    self.ready.load(Ordering::SeqCst)
+  }
+
+  /// whether the checks keep running, which they do on the runtime that serves clients
+  pub fn is_live(&self) -> bool {
+    match self.checks.lock() {
+      Ok(checks) => match checks.last {
+        Some(last) => last.elapsed() < LIVENESS_TIMEOUT,
+        // the checks start with the service
+        None => true,
+      },
+      Err(_) => false,
+    }
+  }
+
+  /// records the outcome of a write to the ledger store
+  pub fn record_store_write(&self, res: Result<(), &LedgerStoreError>) {
+    if let Ok(mut writes) = self.store_writes.lock() {
+      match res {
+        Ok(()) => writes.num_failed = 0,
+        Err(error) if is_store_failure(error) => {
+          writes.num_failed += 1;
+          writes.last_failure = Some(Instant::now());
+        },
+        // the store answered the write
+        Err(_) => writes.num_failed = 0,
+      }
+    }
+  }
+
+  fn is_store_failing(&self) -> bool {
+    match self.store_writes.lock() {
+      Ok(writes) => {
+        writes.num_failed >= FAILED_STORE_WRITES
+          && matches!(writes.last_failure, Some(last) if last.elapsed() < STORE_FAILURE_WINDOW)
+      },
+      Err(_) => true,
+    }
+  }
+
+  /// checks the health of the coordinator, and returns the new readiness if it changed
+  pub fn check(&self, inputs: HealthInputs) -> Option<bool> {
+    let healthy = self.recovered.load(Ordering::SeqCst)
+      && inputs.accepts_writes
+      && inputs.view_size > 0
+      && inputs.num_connected >= inputs.quorum.max(1)
+      && !self.is_store_failing()
+      && self.is_canary_passing();
+
+    let mut checks = self.checks.lock().ok()?;
+    checks.last = Some(Instant::now());
+    let ready = self.is_ready();
+    if healthy == ready {
+      checks.streak = 0;
+      return None;
+    }
+    checks.streak += 1;
+    let needed = match (ready, checks.was_ready) {
+      (true, _) => UNHEALTHY_CHECKS,
+      // the coordinator is ready as soon as it recovered
+      (false, false) => 1,
+      (false, true) => HEALTHY_CHECKS,
+    };
+    if checks.streak < needed {
+      return None;
+    }
+    checks.streak = 0;
+    checks.was_ready |= healthy;
+    self.ready.store(healthy, Ordering::SeqCst);
+    Some(healthy)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_health_hysteresis() {
+    let quorum = HealthInputs {
+      num_connected: 2,
+      view_size: 3,
+      quorum: 2,
+      accepts_writes: true,
+    };
+    let minority = HealthInputs {
+      num_connected: 1,
+      ..quorum
+    };
+
+    // not ready before recovery, and ready right after it
+    let health = Health::new();
+    assert_eq!(health.check(quorum), None);
+    assert!(!health.is_ready());
+    health.set_recovered();
+    assert_eq!(health.check(quorum), Some(true));
+    // a view that needs all of its endorsers is not served by two of three
+    let all = HealthInputs {
+      quorum: 3,
+      ..quorum
+    };
+    for _ in 1..UNHEALTHY_CHECKS {
+      assert_eq!(health.check(all), None);
+    }
+    assert_eq!(health.check(all), Some(false));
+    for _ in 0..HEALTHY_CHECKS {
+      health.check(quorum);
+    }
+    assert!(health.is_ready());
+
+    // losing the quorum for a single check does not change readiness
+    assert_eq!(health.check(minority), None);
+    assert_eq!(health.check(quorum), None);
+    for _ in 1..UNHEALTHY_CHECKS {
+      assert_eq!(health.check(minority), None);
+    }
+    assert_eq!(health.check(minority), Some(false));
+
+    // being ready again takes more checks
+    for _ in 1..HEALTHY_CHECKS {
+      assert_eq!(health.check(quorum), None);
+    }
+    assert_eq!(health.check(quorum), Some(true));
+
+    // writes that the store refuses do not count against it, failed ones do
+    let refused = LedgerStoreError::LedgerError(StorageError::DuplicateKey);
+    let failed = LedgerStoreError::LedgerError(StorageError::UnhandledError);
+    for _ in 0..FAILED_STORE_WRITES {
+      health.record_store_write(Err(&refused));
+    }
+    assert!(!health.is_store_failing());
+    for _ in 0..FAILED_STORE_WRITES {
+      health.record_store_write(Err(&failed));
+    }
+    assert!(health.is_store_failing());
+    for _ in 0..UNHEALTHY_CHECKS {
+      health.check(quorum);
+    }
+    assert!(!health.is_ready());
+    health.record_store_write(Ok(()));
+    assert!(!health.is_store_failing());
+    assert!(health.is_live());
+  }
+
+  #[test]
+  fn test_canary() {
+    let quorum = HealthInputs {
+      num_connected: 2,
+      view_size: 3,
+      quorum: 2,
+      accepts_writes: true,
+    };
+    let outcome = |failure: Option<&str>| CanaryOutcome {
+      failure: failure.map(str::to_string),
+      latency: Duration::from_millis(20),
+    };
+
+    // a coordinator that runs a canary is not ready before one passed
+    let health = Health::new();
+    health.require_canary();
+    health.set_recovered();
+    assert_eq!(health.check(quorum), None);
+    health.record_canary(outcome(Some("read: failed to obtain a quorum")));
+    assert_eq!(health.check(quorum), None);
+    health.record_canary(outcome(None));
+    assert_eq!(health.check(quorum), Some(true));
+
+    // a canary that fails later makes it not ready like any other failed check
+    health.record_canary(outcome(Some("verify: bad signature")));
+    for _ in 1..UNHEALTHY_CHECKS {
+      assert_eq!(health.check(quorum), None);
+    }
+    assert_eq!(health.check(quorum), Some(false));
+    assert_eq!(
+      health.last_canary().unwrap().failure.as_deref(),
+      Some("verify: bad signature")
+    );
+
+    // without a canary, its outcomes do not count
+    let health = Health::new();
+    health.set_recovered();
+    health.record_canary(outcome(Some("create: timed out")));
+    assert_eq!(health.check(quorum), Some(true));
+  }
+
+  #[test]
+  fn test_recovery_progress() {
+    let health = Health::new();
+    assert_eq!(health.recovery_progress(), None);
+    health.record_recovery_scan(10);
+    assert_eq!(health.recovery_progress(), None);
+
+    health.start_recovery_scan("repairing the endorsers", Some(2000));
+    health.record_recovery_scan(1000);
+    health.record_recovery_scan(500);
+    let progress = health.recovery_progress().unwrap();
+    assert_eq!(progress.scanned, 1500);
+    assert_eq!(
+      progress.to_string(),
+      "repairing the endorsers: 1500/2000 ledgers"
+    );
+
+    // a new scan starts over
+    health.start_recovery_scan("reconciling the ledgers", None);
+    health.record_recovery_scan(7);
+    assert_eq!(
+      health.recovery_progress().unwrap().to_string(),
+      "reconciling the ledgers: 7 ledgers"
+    );
+
+    // scans are not tracked once the coordinator recovered
+    health.set_recovered();
+    assert_eq!(health.recovery_progress(), None);
+    health.start_recovery_scan("repairing the endorsers", None);
+    assert_eq!(health.recovery_progress(), None);
+  }
+}
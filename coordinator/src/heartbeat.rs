@@ -0,0 +1,179 @@
+//! The heartbeats of the coordinator, which show clients that it is alive and that the ledgers
+//! they read are fresh. Every interval the coordinator appends a heartbeat block with the time of
+//! its clock to a liveness ledger, and optionally a heartbeat record that repeats the latest
+//! checkpoint to the checkpoint ledgers of some namespaces. A client reads the tail of the
+//! liveness ledger with a fresh nonce and checks its time against its own clock, within the skew
+//! that it tolerates, with `verifier::check_freshness`.
+use crate::{
+  checkpoint::{checkpoint_handle, CheckpointServiceState},
+  config::HeartbeatConfig,
+  coordinator_state::{CoordinatorState, Deadline},
+  errors::CoordinatorError,
+  tenant::Tenant,
+};
+use ledger::{compute_heartbeat_block, parse_heartbeat_block, CustomSerde};
+use std::{
+  cmp,
+  future::Future,
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+
+/// the handle of the liveness ledger unless one is configured
+pub const DEFAULT_LIVENESS_LEDGER: &str = "nimble-liveness";
+/// how long the scheduler waits after the first heartbeat that fails; the wait doubles with every
+/// failure after it, up to the interval
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+/// appends the heartbeats of the coordinator at a fixed interval. The time of a heartbeat is that
+/// of the clock of the coordinator, and never below the time of the previous heartbeat, so the
+/// heartbeats of a ledger do not go back in time when the clock is set back
+pub struct HeartbeatScheduler {
+  state: Arc<CoordinatorState>,
+  checkpoints: CheckpointServiceState,
+  ledger: Vec<u8>,
+  interval: Duration,
+  checkpoint_ledgers: Vec<Vec<u8>>,
+  last_timestamp_ms: u64,
+}
+
+impl HeartbeatScheduler {
+  pub fn new(state: Arc<CoordinatorState>, ledger: &[u8], interval: Duration) -> Self {
+    HeartbeatScheduler {
+      checkpoints: CheckpointServiceState::new(state.clone()),
+      state,
+      ledger: ledger.to_vec(),
+      interval,
+      checkpoint_ledgers: Vec::new(),
+      last_timestamp_ms: 0,
+    }
+  }
+
+  /// also appends heartbeats to the checkpoint ledgers `handles`, which are scoped to their
+  /// tenants already
+  pub fn with_checkpoint_ledgers(mut self, handles: Vec<Vec<u8>>) -> Self {
+    self.checkpoint_ledgers = handles;
+    self
+  }
+
+  /// the time of the next heartbeat
+  fn next_timestamp(&mut self, now_ms: u64) -> u64 {
+    self.last_timestamp_ms = cmp::max(now_ms, self.last_timestamp_ms);
+    self.last_timestamp_ms
+  }
+
+  /// appends one heartbeat to the liveness ledger, which is created with the first one, and to
+  /// the checkpoint ledgers. An append that another coordinator raced is skipped, since the tail
+  /// is fresh then; the appends to the checkpoint ledgers are attempted even if one fails, and the
+  /// first error is returned
+  pub async fn beat(&mut self) -> Result<(), CoordinatorError> {
+    let timestamp_ms = self.next_timestamp(now_ms());
+    let interval_ms = self.interval.as_millis() as u64;
+    let block = compute_heartbeat_block(timestamp_ms, interval_ms).to_bytes();
+    // a heartbeat that misses its interval is pointless, so it gives up then
+    let deadline = Deadline::after(self.interval);
+
+    let mut result = match self.state.read_cached_ledger_tail(&self.ledger).await {
+      Ok((entry, height)) => {
+        if parse_heartbeat_block(&entry.get_block().to_bytes()).is_none() {
+          warn!("The tail of the liveness ledger is not a heartbeat");
+        }
+        let res = self
+          .state
+          .append_ledger_with_request_id(&self.ledger, &block, height + 1, "", deadline)
+          .await;
+        match res {
+          Ok(_) | Err(CoordinatorError::ConditionFailed { .. }) => Ok(()),
+          Err(e) => Err(e),
+        }
+      },
+      Err(CoordinatorError::LedgerNotFound) => self
+        .state
+        .create_ledger_with_deadline(None, &self.ledger, &block, &[], &[], deadline)
+        .await
+        .map(|_receipts| ()),
+      Err(e) => Err(e),
+    };
+
+    for handle in &self.checkpoint_ledgers {
+      let res = self
+        .checkpoints
+        .append_heartbeat(handle, timestamp_ms, deadline)
+        .await;
+      if let (Err(e), Ok(())) = (res, &result) {
+        result = Err(e);
+      }
+    }
+    result
+  }
+
+  /// appends a heartbeat every interval until `stopped` completes. While heartbeats fail, e.g.,
+  /// because too few endorsers are available, the scheduler backs off from `MIN_BACKOFF` up to the
+  /// interval, and logs only when the failures start and when they stop
+  pub async fn run(mut self, stopped: impl Future<Output = ()>) {
+    tokio::pin!(stopped);
+    let mut backoff: Option<Duration> = None;
+    loop {
+      let wait = match self.beat().await {
+        Ok(()) => {
+          if backoff.take().is_some() {
+            info!("The heartbeats of the coordinator recovered");
+          }
+          self.interval
+        },
+        Err(e) => {
+          let wait = match backoff {
+            None => {
+              warn!(error = %e, "Failed to append a heartbeat; backing off");
+              cmp::min(MIN_BACKOFF, self.interval)
+            },
+            Some(wait) => cmp::min(wait * 2, self.interval),
+          };
+          backoff = Some(wait);
+          wait
+        },
+      };
+      tokio::select! {
+        () = &mut stopped => return,
+        () = tokio::time::sleep(wait) => {},
+      }
+    }
+  }
+}
+
+/// appends the heartbeats that the interval of `config` enables until `stopped` completes. The
+/// checkpoint namespaces of `config` are `tenant/namespace` if the coordinator has `tenants`
+pub fn start_heartbeats(
+  state: Arc<CoordinatorState>,
+  config: &HeartbeatConfig,
+  tenants: bool,
+  stopped: impl Future<Output = ()> + Send + 'static,
+) {
+  let secs = match config.interval {
+    Some(secs) => secs,
+    None => return,
+  };
+  let ledger = config.ledger.as_deref().unwrap_or(DEFAULT_LIVENESS_LEDGER);
+  let checkpoint_ledgers = config
+    .checkpoint_namespaces
+    .iter()
+    .map(|namespace| match namespace.split_once('/') {
+      Some((tenant, namespace_id)) if tenants => {
+        Tenant(tenant.to_string()).scope(checkpoint_handle(namespace_id))
+      },
+      _ => checkpoint_handle(namespace),
+    })
+    .collect::<Vec<_>>();
+  let scheduler = HeartbeatScheduler::new(state, ledger.as_bytes(), Duration::from_secs(secs))
+    .with_checkpoint_ledgers(checkpoint_ledgers);
+  info!("Appending heartbeats to {} every {}s", ledger, secs);
+  let _job = tokio::spawn(scheduler.run(stopped));
+}
@@ -0,0 +1,128 @@
+use crate::clock::{Clock, SystemClock};
+use std::{
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+use store::{
+  errors::{LedgerStoreError, StorageError},
+  ledger::{LeaseRecord, LedgerStore},
+};
+use tokio::time::Instant;
+
+/// how far apart the clocks of coordinators may be; a standby waits this long past the expiry of
+/// a lease before it takes the lease over
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(1);
+
+/// the lease that a coordinator must hold in the ledger store to serve writes, so that a standby
+/// can take over when the coordinator dies without both of them driving the endorsers. The lease
+/// changes hands only through `LedgerStore::swap_lease`, and the holder stops writing once the
+/// lease may have expired by its own clock, which is before any standby can take it
+pub struct Lease {
+  holder: String,
+  duration: Duration,
+  /// the clock that the expiry of the lease in the store is written and read by
+  clock: Arc<dyn Clock>,
+  state: Mutex<LeaseState>,
+}
+
+#[derive(Default)]
+struct LeaseState {
+  /// the lease as this coordinator last wrote it, if it holds the lease
+  record: Option<LeaseRecord>,
+  /// until when this coordinator holds the lease, counted from before the write that took or
+  /// renewed it
+  valid_until: Option<Instant>,
+}
+
+impl Lease {
+  pub fn new(holder: &str, duration: Duration) -> Self {
+    Lease {
+      holder: holder.to_string(),
+      duration,
+      clock: Arc::new(SystemClock),
+      state: Mutex::new(LeaseState::default()),
+    }
+  }
+
+  /// makes the lease expire by `clock` rather than by the clock of the system
+  #[cfg(any(test, feature = "testing"))]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  pub fn holder(&self) -> &str {
+    &self.holder
+  }
+
+  /// how often the holder renews the lease and a standby checks whether it expired
+  pub fn renew_interval(&self) -> Duration {
+    self.duration / 3
+  }
+
+  /// whether this coordinator holds the lease now
+  pub fn is_held(&self) -> bool {
+    match self.state.lock() {
+      Ok(state) => matches!(state.valid_until, Some(valid_until) if Instant::now() < valid_until),
+      Err(_) => false,
+    }
+  }
+
+  fn set_state(&self, record: Option<LeaseRecord>, valid_until: Option<Instant>) {
+    if let Ok(mut state) = self.state.lock() {
+      state.record = record;
+      state.valid_until = valid_until;
+    }
+  }
+
+  /// renews the lease if this coordinator holds it, or takes it over if no coordinator ever held
+  /// it or it expired; returns whether this coordinator holds the lease afterwards
+  pub async fn acquire(
+    &self,
+    store: &(dyn LedgerStore + Send + Sync),
+  ) -> Result<bool, LedgerStoreError> {
+    let current = store.read_lease().await?;
+    let held = match self.state.lock() {
+      Ok(state) => state.record.clone(),
+      Err(_) => None,
+    };
+    let ours = held.as_ref() == Some(&current);
+    if !ours {
+      // another coordinator took the lease if this one held it; a coordinator that restarts
+      // under the same name waits for its old lease to expire like any other
+      self.set_state(None, None);
+      let expired = self.clock.now_ms()
+        > current
+          .expires_at
+          .saturating_add(MAX_CLOCK_SKEW.as_millis() as u64);
+      if !current.holder.is_empty() && !expired {
+        return Ok(false);
+      }
+    }
+
+    let sent_at = Instant::now();
+    let new = LeaseRecord {
+      holder: self.holder.clone(),
+      expires_at: self
+        .clock
+        .now_ms()
+        .saturating_add(self.duration.as_millis() as u64),
+      epoch: if ours {
+        current.epoch
+      } else {
+        current.epoch + 1
+      },
+    };
+    match store.swap_lease(&current, &new).await {
+      Ok(()) => {
+        self.set_state(Some(new), Some(sent_at + self.duration));
+        Ok(true)
+      },
+      Err(LedgerStoreError::LedgerError(StorageError::IncorrectConditionalData)) => {
+        self.set_state(None, None);
+        Ok(false)
+      },
+      Err(error) => Err(error),
+    }
+  }
+}
@@ -1,27 +1,120 @@
+mod admin;
+mod allowlist;
+mod audit;
+mod authz;
+mod canary;
+mod checkpoint;
+mod clock;
+mod config;
 mod coordinator_state;
+mod delegation;
+mod endorser_connection;
+mod endorser_heartbeat;
+mod endorser_key;
 mod errors;
-
-use crate::coordinator_state::CoordinatorState;
-use ledger::CustomSerde;
-use std::{collections::HashMap, sync::Arc};
-use tonic::{transport::Server, Request, Response, Status};
+mod gateway;
+mod hadoop_conf;
+mod health;
+mod heartbeat;
+mod lease;
+mod metrics;
+#[cfg(any(test, feature = "testing"))]
+mod mock_endorser;
+mod rate_limit;
+mod replay;
+#[cfg(any(test, feature = "testing"))]
+mod simulation;
+mod spnego;
+mod telemetry;
+mod tenant;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+mod tls;
+mod validate;
+mod watchers;
+
+use crate::{
+  admin::{AdminServiceState, AdminToken},
+  allowlist::EndorserAllowlist,
+  audit::{audited, is_call_mutation, AuditLog, CALL_SERVICE},
+  authz::{ApiKeys, AuthLayer},
+  checkpoint::CheckpointServiceState,
+  config::CoordinatorConfig,
+  coordinator_state::{
+    AppendBatchItem, CoordinatorState, Deadline, WatchEvent, DEFAULT_REQUEST_ID_RETENTION,
+    WATCH_STREAM_BUFFER,
+  },
+  delegation::DelegationTokens,
+  errors::{CoordinatorError, WriteStage},
+  gateway::GatewayState,
+  hadoop_conf::HadoopConf,
+  health::HEALTH_CHECK_INTERVAL,
+  lease::Lease,
+  metrics::render_labeled_gauge,
+  rate_limit::{RateLimitLayer, RateLimiter},
+  replay::{
+    request_client, NonceCache, NonceKey, ReplayDetector, DEFAULT_NONCE_CAPACITY,
+    DEFAULT_NONCE_WINDOW,
+  },
+  tenant::{request_tenant, scope_handle, Authenticator, Tenant},
+};
+use ledger::{
+  hash::HASH_ALGORITHM, retrieve_quorum_from_config, CustomSerde, Handle, MetaBlock,
+  NimbleHashTrait, Nonce,
+};
+use prost::Message;
+use std::{
+  collections::HashMap, convert::TryInto, future::Future, net::SocketAddr, pin::Pin, sync::Arc,
+  time::Duration,
+};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tonic::{server::NamedService, Code, Request, Response, Status};
+use tonic_health::ServingStatus;
+use tracing::{debug, error, field, info, info_span, warn, Instrument};
+
+const DEFAULT_READ_RANGE_PAGE_SIZE: u64 = 64; // entries: the page size of ReadRange by default
+const DEFAULT_LIST_LEDGERS_PAGE_SIZE: u64 = 100; // ledgers: the page size of ListLedgers by default
+const DEADLINE_MARGIN: Duration = Duration::from_millis(20); // the time left to answer a client
+const DEFAULT_SHUTDOWN_GRACE: u64 = 10; // seconds: the time writes in flight get on shutdown
+const SERVER_STOP_TIMEOUT: Duration = Duration::from_secs(1); // the time servers get to stop
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 pub mod coordinator_proto {
   tonic::include_proto!("coordinator_proto");
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod coordinator_admin_proto {
+  tonic::include_proto!("coordinator_admin_proto");
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod checkpoint_proto {
+  tonic::include_proto!("checkpoint_proto");
+}
+
+use checkpoint_proto::checkpoint_server::CheckpointServer;
 use clap::{App, Arg};
+use coordinator_admin_proto::admin_server::AdminServer;
 use coordinator_proto::{
   call_server::{Call, CallServer},
-  AppendReq, AppendResp, NewLedgerReq, NewLedgerResp, ReadByIndexReq, ReadByIndexResp,
-  ReadLatestReq, ReadLatestResp, ReadViewByIndexReq, ReadViewByIndexResp, ReadViewTailReq,
-  ReadViewTailResp,
+  write_deadline_exceeded::Stage,
+  AppendBatchReq, AppendBatchResp, AppendBatchResult, AppendConditionFailed, AppendReq, AppendResp,
+  ContentPurged, ExportLedgerReq, ExportLedgerResp, GetLedgerInfoReq, GetLedgerInfoResp,
+  IndexOutOfRange, LedgerExists, LedgerListing, ListLedgersReq, ListLedgersResp, NewLedgerReq,
+  NewLedgerResp, ReadByIndexReq, ReadByIndexResp, ReadConsistency, ReadLatestReq, ReadLatestResp,
+  ReadRangeEntry, ReadRangeReq, ReadRangeResp, ReadViewByIndexReq, ReadViewByIndexResp,
+  ReadViewTailReq, ReadViewTailResp, SealLedgerReq, SealLedgerResp, SnapshotReadEntry,
+  SnapshotReadReq, SnapshotReadResp, WatchLagged, WatchReq, WatchResp, WriteDeadlineExceeded,
 };
 
+type WatchStream = Pin<Box<dyn Stream<Item = Result<WatchResp, Status>> + Send>>;
+type ExportLedgerStream = Pin<Box<dyn Stream<Item = Result<ExportLedgerResp, Status>> + Send>>;
+
 use axum::{
   extract::{Extension, Path},
-  http::StatusCode,
+  http::{header, StatusCode},
   response::IntoResponse,
   routing::get,
   Json, Router,
@@ -32,11 +125,31 @@ use tower::ServiceBuilder;
 
 pub struct CoordinatorServiceState {
   state: Arc<CoordinatorState>,
+  /// records the calls that change ledgers, if set
+  audit: Option<Arc<AuditLog>>,
+  /// flags the attested reads that reuse a nonce, if set
+  replay: Option<Arc<ReplayDetector>>,
 }
 
 impl CoordinatorServiceState {
   pub fn new(coordinator: Arc<CoordinatorState>) -> Self {
-    CoordinatorServiceState { state: coordinator }
+    CoordinatorServiceState {
+      state: coordinator,
+      audit: None,
+      replay: None,
+    }
+  }
+
+  /// records the calls that change ledgers in `audit`
+  pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+    self.audit = Some(audit);
+    self
+  }
+
+  /// flags the attested reads that reuse a nonce of their client with `replay`
+  pub fn with_replay_detector(mut self, replay: Arc<ReplayDetector>) -> Self {
+    self.replay = Some(replay);
+    self
   }
 
   #[cfg(test)]
@@ -45,170 +158,1125 @@ impl CoordinatorServiceState {
   }
 }
 
-#[tonic::async_trait]
-impl Call for CoordinatorServiceState {
-  async fn new_ledger(
+/// parses the value of a `grpc-timeout` header: at most 8 digits followed by a unit
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+  if value.len() < 2 || value.len() > 9 || !value.is_ascii() {
+    return None;
+  }
+  let (amount, unit) = value.split_at(value.len() - 1);
+  if !amount.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+  let amount = amount.parse::<u64>().ok()?;
+  match unit {
+    "H" => Some(Duration::from_secs(amount * 60 * 60)),
+    "M" => Some(Duration::from_secs(amount * 60)),
+    "S" => Some(Duration::from_secs(amount)),
+    "m" => Some(Duration::from_millis(amount)),
+    "u" => Some(Duration::from_micros(amount)),
+    "n" => Some(Duration::from_nanos(amount)),
+    _ => None,
+  }
+}
+
+/// the deadline that a client set on its request, if any; tonic drops a handler whose deadline
+/// expired without an answer, so the handler keeps a margin to report how far it got
+fn request_deadline<T>(request: &Request<T>) -> Deadline {
+  let timeout = request
+    .metadata()
+    .get("grpc-timeout")
+    .and_then(|value| value.to_str().ok())
+    .and_then(parse_grpc_timeout);
+  match timeout {
+    Some(timeout) => Deadline::after(timeout.saturating_sub(DEADLINE_MARGIN)),
+    None => Deadline::none(),
+  }
+}
+
+fn watch_resp(event: WatchEvent) -> WatchResp {
+  match event {
+    WatchEvent::Entry {
+      height,
+      block_hash,
+      entry,
+    } => {
+      let (block, nonces) = match entry {
+        Some(ledger_entry) => {
+          let block = match ledger_entry.get_purged_block_hash() {
+            Some(_) => vec![],
+            None => ledger_entry.get_block().to_bytes(),
+          };
+          (block, ledger_entry.get_nonces().to_bytes())
+        },
+        None => (vec![], vec![]),
+      };
+      WatchResp {
+        height: height as u64,
+        block_hash: block_hash.to_bytes(),
+        block,
+        nonces,
+        heartbeat: false,
+      }
+    },
+    WatchEvent::Heartbeat { height } => WatchResp {
+      height: height as u64,
+      heartbeat: true,
+      ..Default::default()
+    },
+  }
+}
+
+/// maps an error of the coordinator to the status returned to the client
+fn process_error(error: CoordinatorError, default_msg: &str) -> Status {
+  match error {
+    CoordinatorError::LedgerAlreadyExists => Status::already_exists("Ledger already exists"),
+    CoordinatorError::BlockTooLarge => Status::invalid_argument("Block is too large"),
+    CoordinatorError::MetadataTooLarge => Status::invalid_argument("Metadata is too large"),
+    CoordinatorError::LedgerNotFound => Status::not_found("Ledger does not exist"),
+    CoordinatorError::LedgerConflict {
+      created_at,
+      metadata,
+    } => {
+      let details = LedgerExists {
+        created_at,
+        metadata,
+      };
+      Status::with_details(
+        Code::AlreadyExists,
+        "A ledger with another genesis block, metadata, or app bytes has the handle",
+        details.encode_to_vec().into(),
+      )
+    },
+    CoordinatorError::InvalidHandle => Status::invalid_argument("Invalid handle"),
+    CoordinatorError::BatchTooLarge => Status::invalid_argument("Batch is too large"),
+    CoordinatorError::DuplicateHandleInBatch => {
+      Status::invalid_argument("Another item of the batch appends to the same ledger")
+    },
+    CoordinatorError::SnapshotTooLarge => Status::invalid_argument("Snapshot is too large"),
+    CoordinatorError::DuplicateHandleInSnapshot => {
+      Status::invalid_argument("The snapshot names the same ledger more than once")
+    },
+    CoordinatorError::SnapshotLocksUnavailable => {
+      Status::unavailable("The ledgers of the snapshot are busy; retry later")
+    },
+    CoordinatorError::InvalidHeight => Status::invalid_argument("Invalid expected height"),
+    CoordinatorError::InvalidNonce => Status::invalid_argument("Invalid nonce"),
+    CoordinatorError::FailedToObtainQuorum | CoordinatorError::EndorsersNotInSync => {
+      Status::unavailable("Failed to obtain a quorum of endorsers")
+    },
+    CoordinatorError::ViewChangeInProgress => {
+      Status::unavailable("The view of endorsers is changing; retry later")
+    },
+    CoordinatorError::LeaseNotHeld => {
+      Status::unavailable("The coordinator does not hold the lease; retry at the active one")
+    },
+    CoordinatorError::ShuttingDown => {
+      Status::unavailable("The coordinator is shutting down; retry at another one")
+    },
+    CoordinatorError::TooFewEndorsers => {
+      Status::failed_precondition("Too few endorsers would remain in the view")
+    },
+    CoordinatorError::InvalidQuorum {
+      quorum,
+      num_endorsers,
+    } => Status::invalid_argument(format!(
+      "A quorum of {} is not a majority of the {} endorsers of the view, or exceeds them",
+      quorum, num_endorsers
+    )),
+    CoordinatorError::EndorserNotAllowlisted => {
+      Status::permission_denied("The endorser is not on the allowlist of endorsers")
+    },
+    CoordinatorError::InvalidEndorserAllowlist(msg) => Status::failed_precondition(msg),
+    CoordinatorError::ConditionFailed {
+      current_height,
+      current_tail,
+    } => {
+      let details = AppendConditionFailed {
+        current_height: current_height as u64,
+        current_tail: current_tail.to_bytes(),
+      };
+      Status::with_details(
+        Code::FailedPrecondition,
+        "The ledger is not at the expected height",
+        details.encode_to_vec().into(),
+      )
+    },
+    CoordinatorError::OutOfRange { current_height } => {
+      let details = IndexOutOfRange {
+        current_height: current_height as u64,
+      };
+      Status::with_details(
+        Code::OutOfRange,
+        "The read is beyond the current height of the ledger",
+        details.encode_to_vec().into(),
+      )
+    },
+    CoordinatorError::LedgerSealed => Status::failed_precondition("The ledger is sealed"),
+    CoordinatorError::RequestIdReused { .. } => {
+      Status::already_exists("The request ID names the append of another block to the ledger")
+    },
+    CoordinatorError::PipelineAborted => {
+      Status::aborted("An earlier append to the ledger failed; read the tail and retry")
+    },
+    CoordinatorError::ContentPurged {
+      index,
+      block_hash,
+      hash_nonces,
+    } => {
+      let details = ContentPurged {
+        index: index as u64,
+        block_hash: block_hash.to_bytes(),
+        hash_nonces: hash_nonces.to_bytes(),
+      };
+      Status::with_details(
+        Code::NotFound,
+        "The contents of the block were purged",
+        details.encode_to_vec().into(),
+      )
+    },
+    CoordinatorError::WatchLagged { next_height } => {
+      let details = WatchLagged {
+        next_height: next_height as u64,
+      };
+      Status::with_details(
+        Code::ResourceExhausted,
+        "The client fell behind the watch; resume it at the next height",
+        details.encode_to_vec().into(),
+      )
+    },
+    CoordinatorError::QuotaExceeded(quota) => Status::resource_exhausted(format!(
+      "The write exceeds the quota of the tenant on {}",
+      quota
+    )),
+    CoordinatorError::DeadlineExceeded(stage) => {
+      let details = WriteDeadlineExceeded {
+        stage: match stage {
+          WriteStage::NotStarted => Stage::NotStarted,
+          WriteStage::Persisted => Stage::Persisted,
+          WriteStage::Endorsed => Stage::Endorsed,
+        } as i32,
+      };
+      Status::with_details(
+        Code::DeadlineExceeded,
+        format!("The deadline expired; the write is {}", stage),
+        details.encode_to_vec().into(),
+      )
+    },
+    _ => Status::aborted(default_msg),
+  }
+}
+
+// the handlers of the client service; the gRPC service and the HTTP gateway call them through
+// `Call`, which traces them and records their metrics
+impl CoordinatorServiceState {
+  /// runs a handler of the client service in a span of its own, recording its latency and the
+  /// code it returns, and records the call in the audit log if it changes a ledger
+  async fn metered<R, T, F>(
+    &self,
+    method: &'static str,
+    request: Request<R>,
+    handler: impl FnOnce(Request<R>) -> F,
+  ) -> Result<Response<T>, Status>
+  where
+    R: Message,
+    F: Future<Output = Result<Response<T>, Status>>,
+  {
+    let request_id = telemetry::request_id_of(request.metadata());
+    let span = info_span!(
+      "request",
+      method,
+      request_id = %request_id,
+      handle = field::Empty,
+      height = field::Empty,
+    );
+    let audit = if is_call_mutation(method) {
+      self.audit.clone()
+    } else {
+      None
+    };
+    let start = std::time::Instant::now();
+    let res = telemetry::with_request_id(
+      request_id,
+      audited(&audit, CALL_SERVICE, method, request, handler),
+    )
+    .instrument(span.clone())
+    .await;
+    let elapsed = start.elapsed();
+    let code = match &res {
+      Ok(_) => Code::Ok,
+      Err(status) => status.code(),
+    };
+    match code {
+      Code::Ok => debug!(parent: &span, ?elapsed, "request served"),
+      // the coordinator, its store, or its endorsers failed rather than the client
+      Code::Internal | Code::Unknown | Code::Unavailable | Code::DeadlineExceeded => {
+        warn!(parent: &span, ?code, ?elapsed, "request failed")
+      },
+      _ => info!(parent: &span, ?code, ?elapsed, "request rejected"),
+    }
+    self.state.metrics().observe_request(method, code, elapsed);
+    res
+  }
+
+  /// looks up the nonce of an attested read in the nonce cache, if there is one. A read that
+  /// reuses a nonce that its client sent for the handle within the window is logged and counted,
+  /// and rejected if the tenant of the client is strict; otherwise, the key to remember the
+  /// nonce by once the read succeeded is returned
+  fn check_nonce(
+    &self,
+    method: &'static str,
+    client: &str,
+    tenant: &Option<Tenant>,
+    handle: &[u8],
+    nonce: &[u8],
+  ) -> Result<Option<NonceKey>, Status> {
+    let replay = match &self.replay {
+      Some(replay) => replay,
+      None => return Ok(None),
+    };
+    let key = replay.cache().key(client, handle, nonce);
+    let reused = replay
+      .cache()
+      .contains(&key)
+      .map_err(|e| process_error(e, "Failed to look up the nonce"))?;
+    if !reused {
+      return Ok(Some(key));
+    }
+    let rejected = replay.is_strict(tenant);
+    warn!(
+      method,
+      client,
+      handle = %hex::encode(handle),
+      rejected,
+      "a read reused a nonce of its client"
+    );
+    self.state.metrics().record_nonce_reuse(method, rejected);
+    if rejected {
+      return Err(Status::invalid_argument(
+        "The nonce was used for a read of the ledger before; send a fresh nonce",
+      ));
+    }
+    Ok(None)
+  }
+
+  /// remembers the nonce of a read that succeeded
+  fn remember_nonce(&self, key: Option<NonceKey>) {
+    if let (Some(replay), Some(key)) = (&self.replay, key) {
+      if let Err(e) = replay.cache().insert(&key) {
+        warn!("Failed to remember the nonce of a read ({:?})", e);
+      }
+    }
+  }
+
+  async fn serve_new_ledger(
     &self,
     req: Request<NewLedgerReq>,
   ) -> Result<Response<NewLedgerResp>, Status> {
+    validate::new_ledger(req.get_ref(), self.state.max_block_size())?;
+    let deadline = request_deadline(&req);
+    let tenant = request_tenant(&req);
     let NewLedgerReq {
       handle: handle_bytes,
       block: block_bytes,
+      app_bytes,
+      nonce,
+      metadata,
     } = req.into_inner();
 
+    // either the client supplies a handle or the handle is derived from (app_bytes, nonce); either
+    // way, the handle of a tenant's ledger is in the namespace of the tenant
+    let handle_bytes = if handle_bytes.is_empty() {
+      let nonce = match Nonce::try_from_bytes(&nonce) {
+        Ok(nonce) => nonce,
+        Err(_) => return Err(Status::invalid_argument("Invalid nonce")),
+      };
+      scope_handle(&tenant, Handle::derive(&app_bytes, &nonce).to_bytes())
+    } else {
+      scope_handle(&tenant, handle_bytes)
+    };
+
     let res = self
       .state
-      .create_ledger(None, &handle_bytes, &block_bytes)
+      .create_ledger_with_deadline(
+        None,
+        &handle_bytes,
+        &block_bytes,
+        &app_bytes,
+        &metadata,
+        deadline,
+      )
       .await;
-    if res.is_err() {
-      return Err(Status::aborted("Failed to create a new ledger"));
-    }
+    let receipts = res.map_err(|e| process_error(e, "Failed to create a new ledger"))?;
 
-    let receipts = res.unwrap();
     let reply = NewLedgerResp {
       receipts: receipts.to_bytes(),
+      handle: handle_bytes,
     };
     Ok(Response::new(reply))
   }
 
-  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+  async fn serve_append(
+    &self,
+    request: Request<AppendReq>,
+  ) -> Result<Response<AppendResp>, Status> {
+    validate::append(request.get_ref(), self.state.max_block_size())?;
+    let deadline = request_deadline(&request);
+    let tenant = request_tenant(&request);
     let AppendReq {
       handle: handle_bytes,
       block: block_bytes,
       expected_height,
+      request_id,
     } = request.into_inner();
 
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    // the block is appended right after the tail the client expects
+    let height = match (expected_height as usize).checked_add(1) {
+      Some(height) => height,
+      None => return Err(Status::invalid_argument("Invalid expected height")),
+    };
+
+    // a retry with the request ID of an earlier append returns the height that it appended at
     let res = self
       .state
-      .append_ledger(None, &handle_bytes, &block_bytes, expected_height as usize)
+      .append_ledger_with_request_id(&handle_bytes, &block_bytes, height, &request_id, deadline)
       .await;
-    if res.is_err() {
-      return Err(Status::aborted("Failed to append to a ledger"));
+    let (height, hash_nonces, receipts) =
+      res.map_err(|e| process_error(e, "Failed to append to a ledger"))?;
+    let reply = AppendResp {
+      hash_nonces: hash_nonces.to_bytes(),
+      receipts: receipts.to_bytes(),
+      height: height as u64,
+    };
+
+    Ok(Response::new(reply))
+  }
+
+  async fn serve_append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    validate::append_batch(request.get_ref())?;
+    let deadline = request_deadline(&request);
+    let tenant = request_tenant(&request);
+    let AppendBatchReq { items } = request.into_inner();
+
+    // the items that are malformed fail on their own, like the items that fail in the state
+    let mut results = Vec::with_capacity(items.len());
+    let mut batch = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+      let checked =
+        validate::append_batch_item(&item, self.state.max_block_size()).map_err(|status| {
+          Status::invalid_argument(format!("items[{}].{}", index, status.message()))
+        });
+      let AppendReq {
+        handle: handle_bytes,
+        block: block_bytes,
+        expected_height,
+        ..
+      } = item;
+      let res = if let Err(status) = checked {
+        Err(status)
+      } else {
+        match (expected_height as usize).checked_add(1) {
+          Some(height) => {
+            batch.push(AppendBatchItem {
+              handle_bytes: scope_handle(&tenant, handle_bytes),
+              block_bytes,
+              expected_height: height,
+            });
+            Ok(height)
+          },
+          None => Err(Status::invalid_argument("Invalid expected height")),
+        }
+      };
+      results.push(res);
     }
 
-    let (hash_nonces, receipts) = res.unwrap();
-    let reply = AppendResp {
+    let res = self.state.append_ledger_batch(&batch, deadline).await;
+    let mut appended = res
+      .map_err(|e| process_error(e, "Failed to append a batch"))?
+      .into_iter();
+    let results = results
+      .into_iter()
+      .map(|res| {
+        let res = match res {
+          Ok(height) => match appended.next() {
+            Some(Ok((hash_nonces, receipts))) => Ok(AppendResp {
+              hash_nonces: hash_nonces.to_bytes(),
+              receipts: receipts.to_bytes(),
+              height: height as u64,
+            }),
+            Some(Err(e)) => Err(process_error(e, "Failed to append to a ledger")),
+            None => Err(Status::internal("Missing the result of an item")),
+          },
+          Err(status) => Err(status),
+        };
+        match res {
+          Ok(resp) => AppendBatchResult {
+            resp: Some(resp),
+            code: Code::Ok as i32,
+            message: String::new(),
+            details: Vec::new(),
+          },
+          Err(status) => AppendBatchResult {
+            resp: None,
+            code: status.code() as i32,
+            message: status.message().to_string(),
+            details: status.details().to_vec(),
+          },
+        }
+      })
+      .collect();
+
+    Ok(Response::new(AppendBatchResp { results }))
+  }
+
+  async fn serve_seal_ledger(
+    &self,
+    request: Request<SealLedgerReq>,
+  ) -> Result<Response<SealLedgerResp>, Status> {
+    validate::seal_ledger(request.get_ref())?;
+    let deadline = request_deadline(&request);
+    let tenant = request_tenant(&request);
+    let SealLedgerReq {
+      handle: handle_bytes,
+    } = request.into_inner();
+
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    let res = self.state.seal_ledger(&handle_bytes, deadline).await;
+    let (height, block, hash_nonces, receipts) =
+      res.map_err(|e| process_error(e, "Failed to seal a ledger"))?;
+    let reply = SealLedgerResp {
+      block: block.to_bytes(),
       hash_nonces: hash_nonces.to_bytes(),
       receipts: receipts.to_bytes(),
+      height: height as u64,
     };
 
     Ok(Response::new(reply))
   }
 
-  async fn read_latest(
+  async fn serve_read_latest(
     &self,
     request: Request<ReadLatestReq>,
   ) -> Result<Response<ReadLatestResp>, Status> {
+    validate::read_latest(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let client = request_client(&request);
     let ReadLatestReq {
       handle: handle_bytes,
       nonce: nonce_bytes,
+      consistency,
     } = request.into_inner();
 
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    if consistency == ReadConsistency::Cached as i32 {
+      let (ledger_entry, height) = self
+        .state
+        .read_cached_ledger_tail(&handle_bytes)
+        .await
+        .map_err(|e| process_error(e, "Failed to read a ledger tail"))?;
+      return Ok(Response::new(ReadLatestResp {
+        block: ledger_entry.get_block().to_bytes(),
+        nonces: ledger_entry.get_nonces().to_bytes(),
+        receipts: ledger_entry.get_receipts().to_bytes(),
+        height: height as u64,
+        nonce: vec![],
+        consistency: ReadConsistency::Cached as i32,
+      }));
+    }
+
+    let nonce_key =
+      self.check_nonce("ReadLatest", &client, &tenant, &handle_bytes, &nonce_bytes)?;
     let res = self
       .state
       .read_ledger_tail(&handle_bytes, &nonce_bytes)
       .await;
-    if res.is_err() {
-      return Err(Status::aborted("Failed to read a ledger tail"));
-    }
-
-    let ledger_entry = res.unwrap();
+    let ledger_entry = res.map_err(|e| process_error(e, "Failed to read a ledger tail"))?;
+    self.remember_nonce(nonce_key);
+
+    // all receipts of a tail cover the same metablock, which carries the height
+    let height = ledger_entry
+      .get_receipts()
+      .get_metablock()
+      .map_err(|_| Status::internal("Receipts of the ledger tail are inconsistent"))?
+      .get_height();
     let reply = ReadLatestResp {
       block: ledger_entry.get_block().to_bytes(),
       nonces: ledger_entry.get_nonces().to_bytes(),
       receipts: ledger_entry.get_receipts().to_bytes(),
+      height: height as u64,
+      nonce: nonce_bytes,
+      consistency: ReadConsistency::Attested as i32,
     };
 
     Ok(Response::new(reply))
   }
 
-  async fn read_by_index(
+  async fn serve_snapshot_read(
+    &self,
+    request: Request<SnapshotReadReq>,
+  ) -> Result<Response<SnapshotReadResp>, Status> {
+    validate::snapshot_read(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let client = request_client(&request);
+    let SnapshotReadReq {
+      handles,
+      nonce: nonce_bytes,
+    } = request.into_inner();
+
+    let scoped = handles
+      .iter()
+      .map(|handle_bytes| scope_handle(&tenant, handle_bytes.clone()))
+      .collect::<Vec<_>>();
+    let mut nonce_keys = Vec::with_capacity(scoped.len());
+    for handle_bytes in &scoped {
+      nonce_keys.push(self.check_nonce(
+        "SnapshotRead",
+        &client,
+        &tenant,
+        handle_bytes,
+        &nonce_bytes,
+      )?);
+    }
+    let snapshot = self
+      .state
+      .snapshot_read(&scoped, &nonce_bytes)
+      .await
+      .map_err(|e| process_error(e, "Failed to read a snapshot"))?;
+    for nonce_key in nonce_keys {
+      self.remember_nonce(nonce_key);
+    }
+
+    let entries = handles
+      .into_iter()
+      .zip(snapshot.entries)
+      .map(|(handle, entry)| SnapshotReadEntry {
+        handle,
+        block: entry.ledger_entry.get_block().to_bytes(),
+        nonces: entry.ledger_entry.get_nonces().to_bytes(),
+        receipts: entry.ledger_entry.get_receipts().to_bytes(),
+        height: entry.height as u64,
+        captured_at: entry.captured_at,
+      })
+      .collect();
+    let reply = SnapshotReadResp {
+      entries,
+      nonce: nonce_bytes,
+      digest: snapshot.digest.to_bytes(),
+      locked_at: snapshot.locked_at,
+      released_at: snapshot.released_at,
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn serve_read_by_index(
     &self,
     request: Request<ReadByIndexReq>,
   ) -> Result<Response<ReadByIndexResp>, Status> {
+    validate::read_by_index(request.get_ref())?;
+    let tenant = request_tenant(&request);
     let ReadByIndexReq {
       handle: handle_bytes,
       index,
     } = request.into_inner();
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
 
-    match self
+    let index = index as usize;
+    let res = self
       .state
-      .read_ledger_by_index(&handle_bytes, index as usize)
-      .await
-    {
-      Ok(ledger_entry) => {
-        let reply = ReadByIndexResp {
+      .read_ledger_range(&handle_bytes, index, index)
+      .await;
+    let range = res.map_err(|e| process_error(e, "Failed to read a ledger"))?;
+    let ledger_entry = &range.entries[0];
+    let metablock = match &range.checkpoint {
+      None => Some(MetaBlock::genesis(&ledger_entry.get_block_hash())),
+      Some(checkpoint) => checkpoint.next(&ledger_entry.get_block_hash()),
+    }
+    .ok_or_else(|| Status::internal("The ledger is too long"))?;
+
+    let reply = ReadByIndexResp {
+      block: ledger_entry.get_block().to_bytes(),
+      nonces: ledger_entry.get_nonces().to_bytes(),
+      receipts: ledger_entry.get_receipts().to_bytes(),
+      metablock_hash: metablock.hash().to_bytes(),
+      checkpoint: range
+        .checkpoint
+        .map(|checkpoint| checkpoint.to_bytes())
+        .unwrap_or_default(),
+      block_hashes: range.block_hashes.iter().map(|h| h.to_bytes()).collect(),
+      tail_receipts: range.tail_receipts.to_bytes(),
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn serve_read_range(
+    &self,
+    request: Request<ReadRangeReq>,
+  ) -> Result<Response<ReadRangeResp>, Status> {
+    validate::read_range(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let ReadRangeReq {
+      handle: handle_bytes,
+      from,
+      to,
+      page_size,
+      page_token,
+    } = request.into_inner();
+
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    // a page token is the index of the first entry of the page
+    let start = if page_token.is_empty() {
+      from
+    } else {
+      let token: [u8; 8] = page_token
+        .as_slice()
+        .try_into()
+        .map_err(|_e| Status::invalid_argument("Invalid page token"))?;
+      let start = u64::from_le_bytes(token);
+      if start < from || start > to {
+        return Err(Status::invalid_argument("Invalid page token"));
+      }
+      start
+    };
+    let page_size = if page_size == 0 {
+      DEFAULT_READ_RANGE_PAGE_SIZE
+    } else {
+      page_size
+    };
+    let end = std::cmp::min(to, start.saturating_add(page_size - 1));
+
+    let res = self
+      .state
+      .read_ledger_range(&handle_bytes, start as usize, end as usize)
+      .await;
+    let range = res.map_err(|e| process_error(e, "Failed to read a ledger range"))?;
+
+    let reply = ReadRangeResp {
+      entries: range
+        .entries
+        .iter()
+        .zip(start..)
+        .map(|(ledger_entry, index)| ReadRangeEntry {
+          index,
           block: ledger_entry.get_block().to_bytes(),
           nonces: ledger_entry.get_nonces().to_bytes(),
-          receipts: ledger_entry.get_receipts().to_bytes(),
-        };
-        Ok(Response::new(reply))
+        })
+        .collect(),
+      checkpoint: range
+        .checkpoint
+        .map(|checkpoint| checkpoint.to_bytes())
+        .unwrap_or_default(),
+      block_hashes: range.block_hashes.iter().map(|h| h.to_bytes()).collect(),
+      tail_receipts: range.tail_receipts.to_bytes(),
+      next_page_token: if end < to {
+        (end + 1).to_le_bytes().to_vec()
+      } else {
+        vec![]
       },
-      Err(_) => return Err(Status::aborted("Failed to read a ledger")),
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn serve_watch(&self, request: Request<WatchReq>) -> Result<Response<WatchStream>, Status> {
+    validate::watch(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let WatchReq {
+      handle: handle_bytes,
+      from_height,
+      include_blocks,
+    } = request.into_inner();
+
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    // the entries are read into a bounded queue by a task of the stream, which ends with the
+    // stream; an error, including an unknown ledger, is the last item of the stream
+    let (tx, rx) = mpsc::channel(WATCH_STREAM_BUFFER);
+    let state = self.state.clone();
+    tokio::spawn(
+      async move {
+        state
+          .watch_ledger(&handle_bytes, from_height as usize, include_blocks, tx)
+          .await
+      }
+      .in_current_span(),
+    );
+    let stream = ReceiverStream::new(rx).map(|res| match res {
+      Ok(event) => Ok(watch_resp(event)),
+      Err(e) => Err(process_error(e, "Failed to watch the ledger")),
+    });
+    Ok(Response::new(Box::pin(stream)))
+  }
+
+  async fn serve_export_ledger(
+    &self,
+    request: Request<ExportLedgerReq>,
+  ) -> Result<Response<ExportLedgerStream>, Status> {
+    validate::export_ledger(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let ExportLedgerReq {
+      handle: handle_bytes,
+    } = request.into_inner();
+
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    // the bundle is written into a bounded queue by a task of the stream, like the entries of a
+    // watch, so the ledger is read at the pace of the client
+    let (tx, rx) = mpsc::channel(WATCH_STREAM_BUFFER);
+    let state = self.state.clone();
+    tokio::spawn(async move { state.export_ledger(&handle_bytes, tx).await }.in_current_span());
+    let stream = ReceiverStream::new(rx).map(|res| match res {
+      Ok(chunk) => Ok(ExportLedgerResp { chunk }),
+      Err(e) => Err(process_error(e, "Failed to export the ledger")),
+    });
+    Ok(Response::new(Box::pin(stream)))
+  }
+
+  async fn serve_get_ledger_info(
+    &self,
+    request: Request<GetLedgerInfoReq>,
+  ) -> Result<Response<GetLedgerInfoResp>, Status> {
+    validate::get_ledger_info(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let client = request_client(&request);
+    let GetLedgerInfoReq {
+      handle: handle_bytes,
+      attested,
+      nonce: nonce_bytes,
+    } = request.into_inner();
+
+    let handle_bytes = scope_handle(&tenant, handle_bytes);
+
+    // an unknown handle is reported from the store, even if the caller asked for a signed read
+    let res = self.state.read_ledger_summary(&handle_bytes).await;
+    let summary = res.map_err(|e| process_error(e, "Failed to read the info of a ledger"))?;
+    let mut reply = GetLedgerInfoResp {
+      height: summary.height as u64,
+      tail_hash: summary.tail_hash.to_bytes(),
+      created_at: summary.info.created_at,
+      metadata: summary.info.metadata,
+      receipts: vec![],
+      nonces: vec![],
+    };
+
+    if attested {
+      let nonce_key = self.check_nonce(
+        "GetLedgerInfo",
+        &client,
+        &tenant,
+        &handle_bytes,
+        &nonce_bytes,
+      )?;
+      let res = self
+        .state
+        .read_ledger_tail(&handle_bytes, &nonce_bytes)
+        .await;
+      let ledger_entry = res.map_err(|e| process_error(e, "Failed to read a ledger tail"))?;
+      self.remember_nonce(nonce_key);
+      let height = ledger_entry
+        .get_receipts()
+        .get_metablock()
+        .map_err(|_| Status::internal("Receipts of the ledger tail are inconsistent"))?
+        .get_height();
+      reply.height = height as u64;
+      reply.tail_hash = ledger_entry.get_block_hash().to_bytes();
+      reply.receipts = ledger_entry.get_receipts().to_bytes();
+      reply.nonces = ledger_entry.get_nonces().to_bytes();
     }
+
+    Ok(Response::new(reply))
   }
 
-  async fn read_view_by_index(
+  async fn serve_list_ledgers(
+    &self,
+    request: Request<ListLedgersReq>,
+  ) -> Result<Response<ListLedgersResp>, Status> {
+    validate::list_ledgers(request.get_ref())?;
+    // a tenant lists only the ledgers in its namespace
+    let handle_prefix = request_tenant(&request)
+      .map(|tenant| tenant.prefix())
+      .unwrap_or_default();
+    let ListLedgersReq {
+      page_token,
+      page_size,
+      app_prefix,
+    } = request.into_inner();
+
+    // a page token is the key in the store of the last ledger the previous page looked at
+    let start_after = if page_token.is_empty() {
+      None
+    } else {
+      let handle = Handle::from_bytes(&page_token)
+        .map_err(|_e| Status::invalid_argument("Invalid page token"))?;
+      Some(handle)
+    };
+    let page_size = if page_size == 0 {
+      DEFAULT_LIST_LEDGERS_PAGE_SIZE
+    } else {
+      page_size
+    };
+
+    let res = self
+      .state
+      .list_ledgers(start_after, page_size as usize, &handle_prefix, &app_prefix)
+      .await;
+    let page = res.map_err(|e| process_error(e, "Failed to list ledgers"))?;
+
+    let reply = ListLedgersResp {
+      ledgers: page
+        .ledgers
+        .into_iter()
+        .map(|summary| LedgerListing {
+          handle: summary.info.handle_bytes,
+          height: summary.height as u64,
+          metadata: summary.info.metadata,
+          created_at: summary.info.created_at,
+        })
+        .collect(),
+      next_page_token: page
+        .next_start_after
+        .map(|handle| handle.to_bytes())
+        .unwrap_or_default(),
+      num_ledgers_estimate: page.num_ledgers_estimate.unwrap_or(0) as u64,
+    };
+    Ok(Response::new(reply))
+  }
+
+  async fn serve_read_view_by_index(
     &self,
     request: Request<ReadViewByIndexReq>,
   ) -> Result<Response<ReadViewByIndexResp>, Status> {
+    validate::read_view_by_index(request.get_ref())?;
     let ReadViewByIndexReq { index } = request.into_inner();
 
     let res = self.state.read_view_by_index(index as usize).await;
-    if res.is_err() {
-      return Err(Status::aborted("Failed to read the view ledger"));
-    }
-
-    let ledger_entry = res.unwrap();
+    let ledger_entry = res.map_err(|e| process_error(e, "Failed to read the view ledger"))?;
+    let block = ledger_entry.get_block().to_bytes();
+    let quorum = retrieve_quorum_from_config(&block)
+      .map_err(|_e| Status::internal("The view block is malformed"))?;
     let reply = ReadViewByIndexResp {
-      block: ledger_entry.get_block().to_bytes(),
+      block,
       receipts: ledger_entry.get_receipts().to_bytes(),
+      quorum: quorum as u32,
     };
 
     Ok(Response::new(reply))
   }
 
-  async fn read_view_tail(
+  async fn serve_read_view_tail(
     &self,
-    _request: Request<ReadViewTailReq>,
+    request: Request<ReadViewTailReq>,
   ) -> Result<Response<ReadViewTailResp>, Status> {
+    validate::read_view_tail(request.get_ref())?;
+    let tenant = request_tenant(&request);
+    let client = request_client(&request);
+    let ReadViewTailReq { nonce } = request.into_inner();
+
     let res = self.state.read_view_tail().await;
     if res.is_err() {
       return Err(Status::aborted("Failed to read the view ledger tail"));
     }
 
     let (ledger_entry, height, attestation_reports) = res.unwrap();
+    let nonce_receipts = if nonce.is_empty() {
+      Vec::new()
+    } else {
+      // the view ledger has no handle
+      let nonce_key = self.check_nonce("ReadViewTail", &client, &tenant, &[], &nonce)?;
+      let res = self.state.read_view_tail_receipts(&nonce).await;
+      let receipts = res.map_err(|e| process_error(e, "Failed to read the view ledger tail"))?;
+      self.remember_nonce(nonce_key);
+      receipts.to_bytes()
+    };
     let reply = ReadViewTailResp {
       block: ledger_entry.get_block().to_bytes(),
       receipts: ledger_entry.get_receipts().to_bytes(),
       height: height as u64,
       attestations: attestation_reports,
+      hash_algorithm: HASH_ALGORITHM.id(),
+      nonce_receipts,
     };
 
     Ok(Response::new(reply))
   }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct EndorserOpResponse {
-  #[serde(rename = "PublicKey")]
-  pub pk: String,
-}
+#[tonic::async_trait]
+impl Call for CoordinatorServiceState {
+  async fn new_ledger(
+    &self,
+    request: Request<NewLedgerReq>,
+  ) -> Result<Response<NewLedgerResp>, Status> {
+    self
+      .metered("NewLedger", request, |request| {
+        self.serve_new_ledger(request)
+      })
+      .await
+  }
 
-async fn get_endorser(
-  Path(uri): Path<String>,
-  Extension(state): Extension<Arc<CoordinatorState>>,
-) -> impl IntoResponse {
-  let res = base64_url::decode(&uri);
-  if res.is_err() {
-    eprintln!("received a bad endorser uri {:?}", res);
-    return (StatusCode::BAD_REQUEST, Json(json!({})));
+  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+    self
+      .metered("Append", request, |request| self.serve_append(request))
+      .await
   }
-  let endorser_uri = res.unwrap();
+
+  async fn append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    self
+      .metered("AppendBatch", request, |request| {
+        self.serve_append_batch(request)
+      })
+      .await
+  }
+
+  async fn seal_ledger(
+    &self,
+    request: Request<SealLedgerReq>,
+  ) -> Result<Response<SealLedgerResp>, Status> {
+    self
+      .metered("SealLedger", request, |request| {
+        self.serve_seal_ledger(request)
+      })
+      .await
+  }
+
+  async fn read_latest(
+    &self,
+    request: Request<ReadLatestReq>,
+  ) -> Result<Response<ReadLatestResp>, Status> {
+    self
+      .metered("ReadLatest", request, |request| {
+        self.serve_read_latest(request)
+      })
+      .await
+  }
+
+  async fn snapshot_read(
+    &self,
+    request: Request<SnapshotReadReq>,
+  ) -> Result<Response<SnapshotReadResp>, Status> {
+    self
+      .metered("SnapshotRead", request, |request| {
+        self.serve_snapshot_read(request)
+      })
+      .await
+  }
+
+  async fn read_by_index(
+    &self,
+    request: Request<ReadByIndexReq>,
+  ) -> Result<Response<ReadByIndexResp>, Status> {
+    self
+      .metered("ReadByIndex", request, |request| {
+        self.serve_read_by_index(request)
+      })
+      .await
+  }
+
+  async fn read_range(
+    &self,
+    request: Request<ReadRangeReq>,
+  ) -> Result<Response<ReadRangeResp>, Status> {
+    self
+      .metered("ReadRange", request, |request| {
+        self.serve_read_range(request)
+      })
+      .await
+  }
+
+  async fn get_ledger_info(
+    &self,
+    request: Request<GetLedgerInfoReq>,
+  ) -> Result<Response<GetLedgerInfoResp>, Status> {
+    self
+      .metered("GetLedgerInfo", request, |request| {
+        self.serve_get_ledger_info(request)
+      })
+      .await
+  }
+
+  async fn list_ledgers(
+    &self,
+    request: Request<ListLedgersReq>,
+  ) -> Result<Response<ListLedgersResp>, Status> {
+    self
+      .metered("ListLedgers", request, |request| {
+        self.serve_list_ledgers(request)
+      })
+      .await
+  }
+
+  async fn read_view_by_index(
+    &self,
+    request: Request<ReadViewByIndexReq>,
+  ) -> Result<Response<ReadViewByIndexResp>, Status> {
+    self
+      .metered("ReadViewByIndex", request, |request| {
+        self.serve_read_view_by_index(request)
+      })
+      .await
+  }
+
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status> {
+    self
+      .metered("ReadViewTail", request, |request| {
+        self.serve_read_view_tail(request)
+      })
+      .await
+  }
+
+  type WatchStream = WatchStream;
+
+  async fn watch(&self, request: Request<WatchReq>) -> Result<Response<WatchStream>, Status> {
+    self
+      .metered("Watch", request, |request| self.serve_watch(request))
+      .await
+  }
+
+  type ExportLedgerStream = ExportLedgerStream;
+
+  async fn export_ledger(
+    &self,
+    request: Request<ExportLedgerReq>,
+  ) -> Result<Response<ExportLedgerStream>, Status> {
+    self
+      .metered("ExportLedger", request, |request| {
+        self.serve_export_ledger(request)
+      })
+      .await
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EndorserOpResponse {
+  #[serde(rename = "PublicKey")]
+  pub pk: String,
+}
+
+async fn get_endorser(
+  Path(uri): Path<String>,
+  Extension(state): Extension<Arc<CoordinatorState>>,
+) -> impl IntoResponse {
+  let res = base64_url::decode(&uri);
+  if res.is_err() {
+    warn!("received a bad endorser uri {:?}", res);
+    return (StatusCode::BAD_REQUEST, Json(json!({})));
+  }
+  let endorser_uri = res.unwrap();
 
   let res = std::str::from_utf8(&endorser_uri);
   if res.is_err() {
-    eprintln!(
+    warn!(
       "cannot convert the endorser uri {:?} to string {:?}",
       endorser_uri, res
     );
@@ -219,7 +1287,7 @@ async fn get_endorser(
   let res = state.get_endorser_pk(endorser_uri_str);
   match res {
     None => {
-      eprintln!(
+      warn!(
         "failed to delete the endorser {} ({:?})",
         endorser_uri_str, res
       );
@@ -240,14 +1308,14 @@ async fn new_endorser(
 ) -> impl IntoResponse {
   let res = base64_url::decode(&uri);
   if res.is_err() {
-    eprintln!("received a bad endorser uri {:?}", res);
+    warn!("received a bad endorser uri {:?}", res);
     return (StatusCode::BAD_REQUEST, Json(json!({})));
   }
   let endorser_uri = res.unwrap();
 
   let res = String::from_utf8(endorser_uri.clone());
   if res.is_err() {
-    eprintln!(
+    warn!(
       "cannot convert the endorser uri {:?} to string {:?}",
       endorser_uri, res
     );
@@ -261,16 +1329,16 @@ async fn new_endorser(
     .map(|e| e.to_string())
     .collect::<Vec<String>>();
 
-  let res = state.replace_endorsers(&endorsers).await;
+  let res = state.add_endorsers(&endorsers).await;
   if res.is_err() {
-    eprintln!("failed to add the endorser ({:?})", res);
+    warn!("failed to add the endorser ({:?})", res);
     return (StatusCode::BAD_REQUEST, Json(json!({})));
   }
 
   let pks = state.get_endorser_pks();
   let mut pks_vec = Vec::new();
   for pk in pks {
-    pks_vec.extend(pk);
+    pks_vec.extend_from_slice(&pk);
   }
   let resp = EndorserOpResponse {
     pk: base64_url::encode(&pks_vec),
@@ -284,14 +1352,14 @@ async fn delete_endorser(
 ) -> impl IntoResponse {
   let res = base64_url::decode(&uri);
   if res.is_err() {
-    eprintln!("received a bad endorser uri {:?}", res);
+    warn!("received a bad endorser uri {:?}", res);
     return (StatusCode::BAD_REQUEST, Json(json!({})));
   }
   let endorser_uri = res.unwrap();
 
   let res = std::str::from_utf8(&endorser_uri);
   if res.is_err() {
-    eprintln!(
+    warn!(
       "cannot convert the endorser uri {:?} to string {:?}",
       endorser_uri, res
     );
@@ -299,32 +1367,250 @@ async fn delete_endorser(
   }
   let endorser_uri_str = res.unwrap();
 
-  let res = state.get_endorser_pk(endorser_uri_str);
-  let pk = match res {
-    None => {
-      eprintln!(
-        "failed to find the endorser {} ({:?})",
-        endorser_uri_str, res
+  let res = state
+    .remove_endorsers(&[endorser_uri_str.to_string()])
+    .await;
+  let removed_endorsers = match res {
+    Ok(endorsers) => endorsers,
+    Err(error) => {
+      warn!(
+        "failed to remove the endorser {} ({:?})",
+        endorser_uri_str, error
       );
       return (StatusCode::BAD_REQUEST, Json(json!({})));
     },
-    Some(pk) => pk,
   };
 
+  let mut pks_vec = Vec::new();
+  for (pk, _uri) in removed_endorsers {
+    pks_vec.extend(pk);
+  }
   let resp = EndorserOpResponse {
-    pk: base64_url::encode(&pk),
+    pk: base64_url::encode(&pks_vec),
   };
+  (StatusCode::OK, Json(json!(resp)))
+}
 
-  state
-    .disconnect_endorsers(&vec![(pk, endorser_uri_str.to_string())])
-    .await;
+/// renders the metrics of the coordinator for Prometheus
+async fn get_metrics(
+  Extension(state): Extension<Arc<CoordinatorState>>,
+  Extension(replay): Extension<Option<Arc<ReplayDetector>>>,
+) -> impl IntoResponse {
+  let mut gauges = vec![
+    (
+      "nimble_ledger_lock_waiters",
+      "The appends waiting for the lock of their ledger.",
+      state.ledger_locks.num_waiting() as f64,
+    ),
+    (
+      "nimble_connected_endorsers",
+      "The endorsers that the coordinator sends requests to.",
+      state.get_endorser_pks().len() as f64,
+    ),
+    (
+      "nimble_view_quorum",
+      "The endorsers whose receipts the current view needs.",
+      state.get_quorum() as f64,
+    ),
+    (
+      "nimble_watched_ledgers",
+      "The ledgers that clients watch.",
+      state.watchers.num_watched() as f64,
+    ),
+    (
+      "nimble_ledger_locks",
+      "The locks of ledgers that the coordinator holds in memory.",
+      state.ledger_locks.num_locks() as f64,
+    ),
+    (
+      "nimble_pending_intents",
+      "The appends whose intents were recorded and not yet committed.",
+      state.num_pending_intents() as f64,
+    ),
+  ];
+  if let Some(replay) = &replay {
+    gauges.push((
+      "nimble_nonce_cache_nonces",
+      "The nonces that the nonce cache remembers.",
+      replay.cache().num_nonces().unwrap_or_default() as f64,
+    ));
+    gauges.push((
+      "nimble_nonce_cache_bytes",
+      "The bytes of the bloom filters of the nonce cache.",
+      replay.cache().size_bytes() as f64,
+    ));
+  }
+  gauges.push((
+    "nimble_endorser_cpu_seconds",
+    "The CPU time that the connected endorsers report having used, summed.",
+    state.get_endorser_cpu_time().await.as_secs_f64(),
+  ));
+  if let Some(skew) = state.endorser_heartbeats().clock_skew() {
+    gauges.push((
+      "nimble_endorser_clock_skew_seconds",
+      "How far apart the clocks of the endorsers are, from their recent signed heartbeats.",
+      skew.as_secs_f64(),
+    ));
+  }
+  if let Some(canary) = state.health().last_canary() {
+    gauges.push((
+      "nimble_canary_success",
+      "Whether the last canary passed.",
+      if canary.failure.is_none() { 1.0 } else { 0.0 },
+    ));
+    gauges.push((
+      "nimble_canary_latency_seconds",
+      "How long the last canary took, up to the phase that failed.",
+      canary.latency.as_secs_f64(),
+    ));
+  }
+  // the backends that cannot estimate their size cheaply leave the gauges out
+  if let Ok(Some(num_ledgers)) = state.ledger_store.estimate_num_ledgers().await {
+    gauges.push((
+      "nimble_store_ledgers",
+      "The ledgers in the ledger store, as the store estimates them.",
+      num_ledgers as f64,
+    ));
+  }
+  if let Ok(Some(num_bytes)) = state.ledger_store.estimate_size_bytes().await {
+    gauges.push((
+      "nimble_store_bytes",
+      "The bytes of data in the ledger store, as the store estimates them.",
+      num_bytes as f64,
+    ));
+  }
+  let mut text = state.metrics().render(&gauges);
+  let heartbeats = state.endorser_heartbeats().snapshot();
+  if !heartbeats.is_empty() {
+    let ages = heartbeats
+      .iter()
+      .map(|(_pk, heartbeat)| {
+        (
+          heartbeat.uri.clone(),
+          heartbeat.received.elapsed().as_secs_f64(),
+        )
+      })
+      .collect::<Vec<_>>();
+    render_labeled_gauge(
+      &mut text,
+      "nimble_endorser_heartbeat_age_seconds",
+      "How long ago the coordinator received the last heartbeat of each endorser that verified.",
+      "endorser",
+      &ages,
+    );
+    let offsets = heartbeats
+      .iter()
+      .map(|(_pk, heartbeat)| (heartbeat.uri.clone(), heartbeat.offset_ms as f64 / 1000.0))
+      .collect::<Vec<_>>();
+    render_labeled_gauge(
+      &mut text,
+      "nimble_endorser_clock_offset_seconds",
+      "How far the clock of each endorser is ahead of the clock of the coordinator.",
+      "endorser",
+      &offsets,
+    );
+  }
+  ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text)
+}
 
-  (StatusCode::OK, Json(json!(resp)))
+/// answers whether the coordinator is ready to serve clients, and how far it is in recovery
+/// while it recovers
+async fn get_readyz(Extension(state): Extension<Arc<CoordinatorState>>) -> impl IntoResponse {
+  let health = state.health();
+  if health.is_ready() {
+    (StatusCode::OK, "ready".to_string())
+  } else if let Some(progress) = health.recovery_progress() {
+    (
+      StatusCode::SERVICE_UNAVAILABLE,
+      format!("not ready: recovering, {}", progress),
+    )
+  } else if let Some(failure) = health.last_canary().and_then(|canary| canary.failure) {
+    (
+      StatusCode::SERVICE_UNAVAILABLE,
+      format!("not ready: the canary failed at {}", failure),
+    )
+  } else {
+    (StatusCode::SERVICE_UNAVAILABLE, "not ready".to_string())
+  }
+}
+
+/// answers whether the runtime that serves clients still runs the health checks
+async fn get_livez(Extension(state): Extension<Arc<CoordinatorState>>) -> impl IntoResponse {
+  if state.health().is_live() {
+    (StatusCode::OK, "live")
+  } else {
+    (StatusCode::SERVICE_UNAVAILABLE, "not live")
+  }
+}
+
+/// parses a file listing endorser URIs, one per line; blank lines and `#` comments are ignored
+fn parse_endorser_file(contents: &str) -> Vec<String> {
+  contents
+    .lines()
+    .map(|line| line.split('#').next().unwrap_or("").trim())
+    .filter(|line| !line.is_empty())
+    .map(|line| line.to_string())
+    .collect()
+}
+
+/// waits for SIGTERM, or for Ctrl-C on platforms without signals
+async fn stop_signal() {
+  #[cfg(unix)]
+  {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+      Ok(mut sigterm) => {
+        tokio::select! {
+          _ = sigterm.recv() => {},
+          _ = tokio::signal::ctrl_c() => {},
+        }
+      },
+      Err(_) => {
+        let _ = tokio::signal::ctrl_c().await;
+      },
+    }
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = tokio::signal::ctrl_c().await;
+  }
+}
+
+/// checks that `dir` exists (creating it if needed) and that the coordinator can write to it
+fn check_writable_dir(dir: &str) -> Result<(), String> {
+  std::fs::create_dir_all(dir).map_err(|e| format!("cannot create --fstore-dir {}: {}", dir, e))?;
+  let probe = std::path::Path::new(dir).join(".nimble_write_probe");
+  std::fs::write(&probe, b"")
+    .and_then(|_| std::fs::remove_file(&probe))
+    .map_err(|e| format!("--fstore-dir {} is not writable: {}", dir, e))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-  let config = App::new("coordinator")
+  let app = App::new("coordinator")
+    .arg(
+      Arg::with_name("config")
+        .long("config")
+        .takes_value(true)
+        .help("A TOML file configuring the coordinator; flags override its values"),
+    )
+    .arg(
+      Arg::with_name("hadoop_conf")
+        .long("hadoop-conf")
+        .help(
+          "Hadoop configuration files (*-site.xml) whose nimble.coordinator.* keys override the \
+           TOML file; later files override earlier ones, and flags override both",
+        )
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true)
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("print_config")
+        .long("print-config")
+        .help("Prints the effective configuration, with secrets redacted, and exits"),
+    )
     .arg(
       Arg::with_name("nimbledb")
         .short("n")
@@ -381,71 +1667,487 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .help("The port number to run the coordinator control service on.")
         .default_value("8090"),
     )
+    .arg(
+      Arg::with_name("admin")
+        .long("admin")
+        .takes_value(true)
+        .help("The port number to run the coordinator admin service on; disabled if not set."),
+    )
+    .arg(
+      Arg::with_name("admin_token")
+        .long("admin-token")
+        .takes_value(true)
+        .help("The token that admin clients must present; defaults to NIMBLE_ADMIN_TOKEN"),
+    )
+    .arg(
+      Arg::with_name("http")
+        .long("http")
+        .takes_value(true)
+        .help("The port number to run the HTTP/JSON gateway to the coordinator service on; disabled if not set."),
+    )
+    .arg(
+      Arg::with_name("metrics")
+        .long("metrics")
+        .takes_value(true)
+        .help("The port number to serve Prometheus metrics on at /metrics; disabled if not set."),
+    )
+    .arg(
+      Arg::with_name("listen")
+        .long("listen")
+        .takes_value(true)
+        .help("The address to run the coordinator service on; overrides --host and --port."),
+    )
     .arg(
       Arg::with_name("endorser")
         .short("e")
         .long("endorser")
-        .help("List of URLs to Endorser Services")
+        .help("List of URLs to Endorser Services; may be repeated")
+        .multiple(true)
+        .number_of_values(1)
         .use_delimiter(true)
         .default_value("http://[::1]:9090"),
     )
+    .arg(
+      Arg::with_name("endorser_file")
+        .long("endorser-file")
+        .takes_value(true)
+        .help("A file listing the URLs of Endorser Services, one per line"),
+    )
+    .arg(
+      Arg::with_name("fstore_dir")
+        .long("fstore-dir")
+        .takes_value(true)
+        .help("The directory used by the filestore backend"),
+    )
+    .arg(
+      Arg::with_name("rocksdb_dir")
+        .long("rocksdb-dir")
+        .takes_value(true)
+        .help("The directory used by the rocksdb backend"),
+    )
+    .arg(
+      Arg::with_name("rocksdb_cache_mb")
+        .long("rocksdb-cache-mb")
+        .takes_value(true)
+        .help("The size in MiB of the block cache of the rocksdb backend"),
+    )
+    .arg(
+      Arg::with_name("rocksdb_compression")
+        .long("rocksdb-compression")
+        .takes_value(true)
+        .possible_values(&["none", "snappy", "lz4", "zstd"])
+        .help("The compression used by the rocksdb backend"),
+    )
     .arg(
       Arg::with_name("channels")
         .short("l")
         .long("channels")
         .takes_value(true)
         .help("The number of grpc channels"),
+    )
+    .arg(
+      Arg::with_name("timeout")
+        .long("timeout")
+        .takes_value(true)
+        .help("The timeout in seconds for requests to endorsers"),
+    )
+    .arg(
+      Arg::with_name("min_endorsers")
+        .long("min-endorsers")
+        .takes_value(true)
+        .help("The minimum number of endorsers that removing endorsers must leave in the view"),
+    )
+    .arg(
+      Arg::with_name("quorum")
+        .long("quorum")
+        .takes_value(true)
+        .help("The number of endorsers whose receipts the first view needs; a majority if not set. The view ledger records it, and SetQuorum changes it"),
+    )
+    .arg(
+      Arg::with_name("max_block_size")
+        .long("max-block-size")
+        .takes_value(true)
+        .help("The maximum size in bytes of a block that clients create or append"),
+    )
+    .arg(
+      Arg::with_name("pipeline_depth")
+        .long("pipeline-depth")
+        .takes_value(true)
+        .help("The maximum number of consecutive appends to a ledger that are sent to the endorsers together; appends are not pipelined if not set"),
+    )
+    .arg(
+      Arg::with_name("request_id_retention")
+        .long("request-id-retention")
+        .takes_value(true)
+        .help("The number of the last appends to a ledger whose request IDs are kept for retries"),
+    )
+    .arg(
+      Arg::with_name("lease")
+        .long("lease")
+        .takes_value(true)
+        .help("The duration in seconds of the lease in the store that the active coordinator holds; standbys wait for it to lapse"),
+    )
+    .arg(
+      Arg::with_name("lease_holder")
+        .long("lease-holder")
+        .takes_value(true)
+        .help("The name under which the coordinator holds the lease; defaults to its address and a random suffix"),
+    )
+    .arg(
+      Arg::with_name("shutdown_grace")
+        .long("shutdown-grace")
+        .takes_value(true)
+        .help("The time in seconds that writes in flight get to finish when the coordinator is stopped"),
+    )
+    .arg(
+      Arg::with_name("log_format")
+        .long("log-format")
+        .takes_value(true)
+        .possible_values(&telemetry::LOG_FORMATS)
+        .help("The format of the logs, which RUST_LOG filters; defaults to text"),
+    )
+    .arg(
+      Arg::with_name("tenants")
+        .long("tenants")
+        .takes_value(true)
+        .help("A file listing a tenant and its token per line; clients must then authenticate"),
     );
 
-  let cli_matches = config.get_matches();
-  let hostname = cli_matches.value_of("host").unwrap();
-  let port_number = cli_matches.value_of("port").unwrap();
-  let ctrl_port = cli_matches.value_of("ctrl").unwrap();
-  let store = cli_matches.value_of("store").unwrap();
-  let addr = format!("{}:{}", hostname, port_number).parse()?;
-  let str_vec: Vec<&str> = cli_matches.values_of("endorser").unwrap().collect();
-  let endorser_hostnames = str_vec
-    .iter()
-    .filter(|e| !e.is_empty())
-    .map(|e| e.to_string())
-    .collect::<Vec<String>>();
+  let cli_matches = app.get_matches();
+
+  // the file configures the coordinator, and flags given on the command line override it
+  let mut config = match cli_matches.value_of("config") {
+    Some(path) => {
+      let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read --config {}: {}", path, e))?;
+      CoordinatorConfig::from_toml(&contents)
+        .map_err(|e| format!("invalid --config {}: {}", path, e))?
+    },
+    None => CoordinatorConfig::default(),
+  };
+  let mut config_warnings = Vec::new();
+  if let Some(paths) = cli_matches.values_of("hadoop_conf") {
+    let mut conf = HadoopConf::default();
+    for path in paths {
+      let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read --hadoop-conf {}: {}", path, e))?;
+      let warnings = conf
+        .add_resource(&contents)
+        .map_err(|e| format!("invalid --hadoop-conf {}: {}", path, e))?;
+      config_warnings.extend(warnings.into_iter().map(|w| format!("{}: {}", path, w)));
+    }
+    let warnings = config
+      .apply_hadoop_conf(&conf)
+      .map_err(|e| format!("invalid --hadoop-conf: {}", e))?;
+    config_warnings.extend(warnings);
+  }
+  config.apply_flags(&cli_matches)?;
+  config.apply_defaults();
+  if cli_matches.is_present("print_config") {
+    print!("{}", config.redacted().to_toml());
+    return Ok(());
+  }
+
+  // the secrets are fetched from their providers, and the whole configuration is validated,
+  // before connecting to anything
+  config.load_secrets()?;
+  config.validate()?;
+  telemetry::init(config.service.log_format.as_deref().unwrap_or("text"));
+  for warning in &config_warnings {
+    warn!("{}", warning);
+  }
+  let store = config.store.kind.as_str();
+  let addr = config.addr()?;
+  let ctrl_addr = SocketAddr::new(addr.ip(), config.service.ctrl);
+  let admin_addr = config
+    .service
+    .admin
+    .map(|admin_port| SocketAddr::new(addr.ip(), admin_port));
+  let admin_token = config.service.admin_token.clone();
+  let http_addr = config
+    .service
+    .http
+    .map(|http_port| SocketAddr::new(addr.ip(), http_port));
+  let metrics_addr = config
+    .service
+    .metrics
+    .map(|metrics_port| SocketAddr::new(addr.ip(), metrics_port));
+  let endorser_hostnames = config.endorser_uris()?;
+  // the certificate and its key are checked before the coordinator connects to anything
+  let tls = tls::server_tls(&config.tls)?;
+  // and so is the signature of the allowlist of endorsers, which applies to the endorsers of the
+  // recovered view too
+  let endorser_allowlist = match config.endorser_allowlist()? {
+    Some((path, signature_path, operator_key)) => {
+      let allowlist = EndorserAllowlist::load(&path, &signature_path, operator_key)?;
+      info!(
+        "Connecting only to the {} endorsers of the allowlist {}",
+        allowlist.num_keys(),
+        path
+      );
+      Some(allowlist)
+    },
+    None => None,
+  };
+  let with_allowlist = |coordinator: CoordinatorState| match endorser_allowlist {
+    Some(allowlist) => coordinator.with_endorser_allowlist(allowlist),
+    None => coordinator,
+  };
+
+  let tenants = tenant::load_tenants(&config.service)?;
+  let delegation_tokens = config.delegation_secret().map(|secret| {
+    let mut tokens = DelegationTokens::new(secret);
+    if let Some(secs) = config.delegation_tokens.renew_interval {
+      tokens = tokens.with_renew_interval(Duration::from_secs(secs));
+    }
+    if let Some(secs) = config.delegation_tokens.max_lifetime {
+      tokens = tokens.with_max_lifetime(Duration::from_secs(secs));
+    }
+    Arc::new(tokens)
+  });
+  // clients authenticate with the tokens of the tenant file, with delegation tokens if the
+  // coordinator issues them, and with API keys
+  let api_keys = if config.api_keys.is_empty() {
+    None
+  } else {
+    Some(Arc::new(ApiKeys::new(&config.api_keys)))
+  };
+  let auth = tenant::client_authenticator(&tenants, &delegation_tokens, &api_keys);
+  // the gateway also authenticates the Kerberos principals of the tenants if SPNEGO is
+  // configured, which requires tenants
+  let gateway_auth = spnego::gateway_authenticator(&auth, &tenants, &config)?;
 
   let mut ledger_store_args = HashMap::<String, String>::new();
-  if let Some(x) = cli_matches.value_of("cosmosurl") {
+  if let Some(x) = &config.store.cosmosurl {
     ledger_store_args.insert(String::from("COSMOS_URL"), x.to_string());
   }
-  if let Some(x) = cli_matches.value_of("nimbledb") {
-    ledger_store_args.insert(String::from("NIMBLE_DB"), x.to_string());
-  }
-  if let Some(x) = cli_matches.value_of("storage_account") {
+  ledger_store_args.insert(String::from("NIMBLE_DB"), config.store.nimbledb.clone());
+  if let Some(x) = &config.store.storage_account {
     ledger_store_args.insert(String::from("STORAGE_ACCOUNT"), x.to_string());
   }
-  if let Some(x) = cli_matches.value_of("storage_master_key") {
+  if let Some(x) = &config.store.storage_master_key {
     ledger_store_args.insert(String::from("STORAGE_MASTER_KEY"), x.to_string());
   }
-  let num_grpc_channels: Option<usize> = if let Some(x) = cli_matches.value_of("channels") {
-    match x.to_string().parse() {
-      Ok(v) => Some(v),
-      Err(_) => panic!("Failed to parse the number of grpc channels"),
+  if let Some(dir) = config
+    .store
+    .fstore_dir
+    .as_deref()
+    .filter(|_| store == "filestore")
+  {
+    check_writable_dir(dir)?;
+    ledger_store_args.insert(String::from("NIMBLE_FSTORE_DIR"), dir.to_string());
+  }
+  #[cfg(feature = "rocksdb")]
+  if store == "rocksdb" {
+    if let Some(dir) = &config.store.rocksdb_dir {
+      ledger_store_args.insert(String::from("NIMBLE_ROCKSDB_DIR"), dir.to_string());
+    }
+    if let Some(x) = config.store.rocksdb_cache_mb {
+      ledger_store_args.insert(String::from("NIMBLE_ROCKSDB_CACHE_MB"), x.to_string());
+    }
+    if let Some(x) = &config.store.rocksdb_compression {
+      ledger_store_args.insert(String::from("NIMBLE_ROCKSDB_COMPRESSION"), x.to_string());
+    }
+  }
+
+  let num_grpc_channels = config.endorsers.channels;
+  let endorser_timeout = config.endorsers.timeout;
+  let min_num_endorsers = config.endorsers.min_endorsers;
+  let quorum = config.endorsers.quorum;
+  let max_block_size = config.service.max_block_size;
+  let pipeline_depth = config.service.pipeline_depth.unwrap_or(1);
+  let request_id_retention = config
+    .service
+    .request_id_retention
+    .unwrap_or(DEFAULT_REQUEST_ID_RETENTION);
+  let shutdown_grace = Duration::from_secs(
+    config
+      .service
+      .shutdown_grace
+      .unwrap_or(DEFAULT_SHUTDOWN_GRACE),
+  );
+
+  let lease = config.lease.duration.map(|secs| {
+    let holder = match &config.lease.holder {
+      Some(holder) => holder.to_string(),
+      None => format!("{}-{:08x}", addr, rand::random::<u32>()),
+    };
+    Lease::new(&holder, Duration::from_secs(secs))
+  });
+
+  // an empty store creates the view ledger with the given endorsers; otherwise the coordinator
+  // recovers the current view from the store and reconnects to its endorsers. With a lease, the
+  // coordinator is a standby until the lease is free, and recovers only once it holds the lease
+  let start_error = |e: CoordinatorError| {
+    format!(
+      "failed to start the coordinator with --store {}: {}",
+      store, e
+    )
+  };
+  let holder = lease.as_ref().map(|lease| lease.holder().to_string());
+  let coordinator = CoordinatorState::open(
+    store,
+    &ledger_store_args,
+    num_grpc_channels,
+    endorser_timeout,
+    min_num_endorsers,
+    max_block_size,
+  )
+  .await
+  .map_err(start_error)?
+  .with_pipeline_depth(pipeline_depth)
+  .with_request_id_retention(request_id_retention)
+  .with_quorum(quorum);
+  let coordinator = match lease {
+    Some(lease) => coordinator.with_lease(lease),
+    None => coordinator,
+  };
+  let coordinator = Arc::new(with_allowlist(coordinator));
+
+  // the servers stop accepting connections once the coordinator starts shutting down
+  let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+  let stopped = |mut stop_rx: tokio::sync::watch::Receiver<bool>| async move {
+    while !*stop_rx.borrow_and_update() {
+      if stop_rx.changed().await.is_err() {
+        break;
+      }
     }
+  };
+
+  let replay = if config.nonce_replay.enabled {
+    let window = config.nonce_replay.window.unwrap_or(DEFAULT_NONCE_WINDOW);
+    let capacity = config
+      .nonce_replay
+      .capacity
+      .unwrap_or(DEFAULT_NONCE_CAPACITY);
+    let cache = NonceCache::new(Duration::from_secs(window), capacity);
+    info!(
+      window,
+      capacity,
+      size_bytes = cache.size_bytes(),
+      "flagging reads that reuse a nonce"
+    );
+    let replay = ReplayDetector::new(cache)
+      .with_strict(config.nonce_replay.strict)
+      .with_strict_tenants(&config.nonce_replay.strict_tenants);
+    Some(Arc::new(replay))
   } else {
     None
   };
-  let res = CoordinatorState::new(store, &ledger_store_args, num_grpc_channels).await;
-  assert!(res.is_ok());
-  let coordinator = res.unwrap();
 
+  // the metrics and the probes of orchestrators are served by a thread of their own, so that they
+  // are answered even while the client service keeps every worker of the runtime busy; they are
+  // served during recovery too, so that `/readyz` reports how far it is
+  if let Some(metrics_addr) = metrics_addr {
+    let metrics_server = Router::new()
+      .route("/metrics", get(get_metrics))
+      .route("/readyz", get(get_readyz))
+      .route("/livez", get(get_livez))
+      .layer(Extension(coordinator.clone()))
+      .layer(Extension(replay.clone()));
+    let metrics_stopped = stopped(stop_rx.clone());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+      .enable_all()
+      .build()?;
+    std::thread::spawn(move || {
+      info!("Running metrics service at {}", metrics_addr);
+      let _res = runtime.block_on(
+        axum::Server::bind(&metrics_addr)
+          .serve(metrics_server.into_make_service())
+          .with_graceful_shutdown(metrics_stopped),
+      );
+    });
+  }
+
+  if let Some(holder) = holder {
+    info!("Coordinator {} is waiting for the lease", holder);
+    coordinator.take_lease().await;
+    info!("Coordinator {} holds the lease", holder);
+
+    // the lease is renewed from now on, since recovery can take longer than the lease; once it
+    // is lost, another coordinator may be active, so this one stops
+    let lease_holder = coordinator.clone();
+    tokio::spawn(async move {
+      lease_holder.keep_lease().await;
+      error!(holder = %holder, "the coordinator lost the lease; exiting");
+      std::process::exit(1);
+    });
+  }
+  coordinator.recover().await.map_err(start_error)?;
+
+  // endorsers that are already part of the recovered view yield NoNewEndorsers, which is fine
   if !endorser_hostnames.is_empty() {
-    let _ = coordinator.replace_endorsers(&endorser_hostnames).await;
+    match coordinator.replace_endorsers(&endorser_hostnames).await {
+      Ok(()) | Err(CoordinatorError::NoNewEndorsers) => {},
+      Err(e) => {
+        return Err(
+          format!(
+            "failed to set up the endorsers {:?}: {}",
+            endorser_hostnames, e
+          )
+          .into(),
+        )
+      },
+    }
+  }
+  // the view ledger, rather than the configuration, holds the quorum of a view
+  if let Some(quorum) = quorum {
+    if quorum != coordinator.get_quorum() {
+      warn!(
+        configured = quorum,
+        recorded = coordinator.get_quorum(),
+        "the view ledger records another quorum than the configured one; SetQuorum changes it"
+      );
+    }
   }
   if coordinator.get_endorser_pks().is_empty() {
-    panic!("No endorsers are available!");
+    return Err(
+      format!(
+        "no endorsers are available (tried {:?}); pass a reachable --endorser or --endorser-file",
+        endorser_hostnames
+      )
+      .into(),
+    );
+  }
+  // with a canary, the coordinator is not ready until it proved the whole pipeline works, and
+  // with endorser heartbeats, it counts only the endorsers that answer them
+  let canary = canary::canary_from_config(coordinator.clone(), &config.canary);
+  let endorser_monitor =
+    endorser_heartbeat::monitor_from_config(coordinator.clone(), &config.endorsers);
+  coordinator.health().set_recovered();
+  if let Some(monitor) = endorser_monitor {
+    endorser_heartbeat::start_monitor(monitor, stopped(stop_rx.clone())).await;
+  }
+  if let Some(canary) = canary {
+    canary::start_canary(canary, &config.canary, stopped(stop_rx.clone())).await;
+  }
+  info!(
+    "Coordinator listening on {} (control {}), store {}, endorsers {:?}",
+    addr,
+    ctrl_addr,
+    store,
+    coordinator.get_endorser_uris(),
+  );
+
+  if let Some(tenants) = &tenants {
+    tenant::register_tenants(&coordinator, tenants).await;
   }
-  println!("Endorser URIs: {:?}", coordinator.get_endorser_uris());
 
-  let coordinator_ref = Arc::new(coordinator);
+  let coordinator_ref = coordinator;
 
-  let server = CoordinatorServiceState::new(coordinator_ref.clone());
+  // the services queue a record of every call that changes state for the writer of the audit
+  // ledger, which starts with the servers below
+  let (audit, audit_queue) = audit::audit_from_config(&config.audit);
+
+  // the gRPC service and the HTTP gateway serve clients with the same handlers
+  let mut server = CoordinatorServiceState::new(coordinator_ref.clone());
+  if let Some(audit) = &audit {
+    server = server.with_audit_log(audit.clone());
+  }
+  if let Some(replay) = replay {
+    server = server.with_replay_detector(replay);
+  }
+  let server = Arc::new(server);
 
   // Start the REST server for management
   let control_server = Router::new()
@@ -458,23 +2160,160 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
               .into_inner(),
       );
 
-  let ctrl_addr = format!("{}:{}", hostname, ctrl_port).parse()?;
+  // calls to the client service are limited per client; the admin service changes the limits
+  let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+  let rate_limit = RateLimitLayer::new(rate_limiter.clone(), auth.clone());
+
+  let ctrl_stopped = stopped(stop_rx.clone());
   let _job = tokio::spawn(async move {
-    println!("Running control service at {}", ctrl_addr);
+    info!("Running control service at {}", ctrl_addr);
     let _res = axum::Server::bind(&ctrl_addr)
       .serve(control_server.into_make_service())
+      .with_graceful_shutdown(ctrl_stopped)
       .await;
   });
 
-  let job2 = tokio::spawn(async move {
-    println!("Running gRPC Coordinator Service at {:?}", addr);
-    let _ = Server::builder()
-      .add_service(CallServer::new(server))
-      .serve(addr)
+  // the operators authenticate with the admin token or with the API keys of admins
+  let admin_auth = Authenticator::default();
+  let admin_auth = match admin_token.filter(|token| !token.is_empty()) {
+    Some(token) => admin_auth.with_auth(Arc::new(AdminToken::new(token))),
+    None => admin_auth,
+  };
+  let admin_auth = match &api_keys {
+    Some(keys) => admin_auth.with_auth(keys.clone()),
+    None => admin_auth,
+  };
+  if let Some(admin_addr) = admin_addr {
+    let mut admin_server =
+      AdminServiceState::new(coordinator_ref.clone()).with_rate_limiter(rate_limiter.clone());
+    if let Some(tokens) = &delegation_tokens {
+      admin_server = admin_server.with_delegation_tokens(tokens.clone());
+    }
+    if let Some(audit) = &audit {
+      admin_server = admin_server.with_audit_log(audit.clone());
+    }
+    let admin_stopped = stopped(stop_rx.clone());
+    let admin_builder = tls::server_builder(&tls)?;
+    let _job = tokio::spawn(async move {
+      info!("Running admin service at {}", admin_addr);
+      let _ = admin_builder
+        .layer(AuthLayer::new(Some(admin_auth)))
+        .add_service(AdminServer::new(admin_server))
+        .serve_with_shutdown(admin_addr, admin_stopped)
+        .await;
+    });
+  }
+
+  // orchestrators watch the readiness of the coordinator through the gRPC health service, which
+  // reports NOT_SERVING until the first health check after recovery passes
+  let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+  health_reporter
+    .set_service_status("", ServingStatus::NotServing)
+    .await;
+  health_reporter
+    .set_not_serving::<CallServer<CoordinatorServiceState>>()
+    .await;
+  let health_state = coordinator_ref.clone();
+  let _job = tokio::spawn(async move {
+    loop {
+      if let Some(ready) = health_state.check_health() {
+        info!(ready, "the readiness of the coordinator changed");
+        let status = if ready {
+          ServingStatus::Serving
+        } else {
+          ServingStatus::NotServing
+        };
+        health_reporter.set_service_status("", status).await;
+        health_reporter
+          .set_service_status(<CallServer<CoordinatorServiceState>>::NAME, status)
+          .await;
+      }
+      tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+    }
+  });
+
+  if let Some(http_addr) = http_addr {
+    let gateway = gateway::router(
+      GatewayState::new(server.clone())
+        .with_auth(gateway_auth)
+        .with_rate_limiter(rate_limiter.clone()),
+    );
+    let http_stopped = stopped(stop_rx.clone());
+    let _job = tokio::spawn(async move {
+      info!("Running HTTP gateway at {}", http_addr);
+      let _res = axum::Server::bind(&http_addr)
+        .serve(gateway.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(http_stopped)
+        .await;
+    });
+  }
+
+  // the heartbeats and the audit writer stop with the servers, before the writes in flight are
+  // drained
+  heartbeat::start_heartbeats(
+    coordinator_ref.clone(),
+    &config.heartbeat,
+    tenants.is_some(),
+    stopped(stop_rx.clone()),
+  );
+  if let Some(queue) = audit_queue {
+    audit::start_audit_writer(coordinator_ref.clone(), queue, stopped(stop_rx.clone()));
+  }
+
+  // SIGHUP reloads the allowlist of endorsers, and is recorded like a call to the admin service
+  #[cfg(unix)]
+  {
+    if config.endorsers.allowlist.is_some() {
+      let state = coordinator_ref.clone();
+      let _job = tokio::spawn(admin::reload_endorser_allowlist_on_hangup(
+        state,
+        audit.clone(),
+      ));
+    }
+  }
+
+  // the checkpoint service shares the port, the tenants and the limits of the client service
+  let mut checkpoint_server = CheckpointServiceState::new(coordinator_ref.clone());
+  if let Some(audit) = &audit {
+    checkpoint_server = checkpoint_server.with_audit_log(audit.clone());
+  }
+  let client_stopped = stopped(stop_rx);
+  let client_builder = tls::server_builder(&tls)?;
+  let mut job2 = tokio::spawn(async move {
+    info!("Running gRPC Coordinator Service at {:?}", addr);
+    // calls are authorized before they draw from the budgets of their clients
+    let _ = client_builder
+      .layer(AuthLayer::new(auth))
+      .layer(rate_limit)
+      .add_service(CallServer::from_arc(server))
+      .add_service(CheckpointServer::new(checkpoint_server))
+      .add_service(health_service)
+      .serve_with_shutdown(addr, client_stopped)
       .await;
   });
 
-  job2.await?;
+  tokio::select! {
+    res = &mut job2 => {
+      res?;
+      return Ok(());
+    },
+    () = stop_signal() => {},
+  }
+
+  // new writes are rejected with a retryable error while those in flight finish; whatever is cut
+  // off after the grace period is reconciled by recovery at the next start
+  info!(
+    "Coordinator is shutting down; waiting up to {:?} for writes in flight",
+    shutdown_grace
+  );
+  let _ = stop_tx.send(true);
+  if coordinator_ref.shutdown(shutdown_grace).await {
+    info!("Coordinator finished the writes in flight");
+  } else {
+    warn!("Coordinator cut off the writes still in flight after the grace period");
+  }
+  let _ = tokio::time::timeout(SERVER_STOP_TIMEOUT, job2).await;
+  info!("Coordinator stopped");
 
   Ok(())
 }
@@ -482,13 +2321,71 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
   use crate::{
+    admin::AdminServiceState,
+    allowlist::{self, EndorserAllowlist},
+    audit::{AuditLog, AuditWriter, DEFAULT_AUDIT_LEDGER},
+    canary::{Canary, Phase, CANARY_PREFIX},
+    check_writable_dir,
+    checkpoint::{
+      checkpoint_handle, decode_record, encode_record, CheckpointServiceState,
+      CHECKPOINT_HEARTBEAT_VERSION,
+    },
+    checkpoint_proto::{
+      checkpoint_server::Checkpoint, CheckpointConflict, CheckpointRecord, EditSegment,
+      GetLatestCheckpointReq, RecordCheckpointReq, RecordCheckpointResp, RecordEditSegmentReq,
+      VerifyCheckpointReq, VerifyEditRangeReq,
+    },
+    config::TlsConfig,
+    coordinator_admin_proto::{
+      admin_server::Admin, AddEndorserReq, GetOperationStatusReq, GetOperationStatusResp,
+      GetRateLimitReq, GetTenantReq, GetViewHistoryReq, GetViewHistoryResp,
+      IssueDelegationTokenReq, ListEndorsersReq, ListEndorsersResp, OperationResp, OperationState,
+      PurgeBlocksReq, RateLimitResp, ReadAuditReq, ReadAuditResp, ReloadEndorserAllowlistReq,
+      ReloadEndorserAllowlistResp, RenewDelegationTokenReq, SetRateLimitReq, SetTenantQuotaReq,
+      TenantResp, TriggerRepairReq,
+    },
     coordinator_proto::{
-      call_server::Call, AppendReq, AppendResp, NewLedgerReq, NewLedgerResp, ReadByIndexReq,
-      ReadByIndexResp, ReadLatestReq, ReadLatestResp, ReadViewTailReq, ReadViewTailResp,
+      call_client::CallClient,
+      call_server::{Call, CallServer},
+      write_deadline_exceeded::Stage,
+      AppendBatchReq, AppendBatchResp, AppendConditionFailed, AppendReq, AppendResp, ContentPurged,
+      ExportLedgerReq, GetLedgerInfoReq, GetLedgerInfoResp, IndexOutOfRange, LedgerExists,
+      ListLedgersReq, ListLedgersResp, NewLedgerReq, NewLedgerResp, ReadByIndexReq,
+      ReadByIndexResp, ReadConsistency, ReadLatestReq, ReadLatestResp, ReadRangeReq, ReadRangeResp,
+      ReadViewByIndexReq, ReadViewTailReq, ReadViewTailResp, SealLedgerReq, SnapshotReadReq,
+      WatchLagged, WatchReq, WriteDeadlineExceeded,
+    },
+    coordinator_state::{
+      AppendBatchItem, Deadline, EndorserStatus, LedgerLocks, WatchEvent, MAX_APPEND_BATCH_SIZE,
+      MAX_LEDGER_METADATA_SIZE, MAX_SNAPSHOT_READ_SIZE,
     },
-    CoordinatorServiceState, CoordinatorState,
+    delegation::{DelegationTokens, MIN_SECRET_SIZE},
+    endorser_heartbeat::EndorserHeartbeatMonitor,
+    heartbeat::HeartbeatScheduler,
+    lease::Lease,
+    mock_endorser::{MockEndorser, MockEndorsers},
+    parse_endorser_file, parse_grpc_timeout, process_error,
+    rate_limit::{RateLimitConfig, RateLimitLayer, RateLimiter},
+    replay::{NonceCache, ReplayDetector},
+    simulation::{simulate, Step},
+    tenant::{Authenticator, Tenant},
+    testing::{StoreKind, TestCluster},
+    tls, validate, CoordinatorError, CoordinatorServiceState, CoordinatorState,
   };
-  use ledger::{Block, CustomSerde, NimbleDigest, VerifierState};
+  use ledger::{
+    bundle::{BundleReader, BundleRecord},
+    compute_aggregated_block_hash, compute_genesis_block, compute_seal_block,
+    compute_snapshot_digest, compute_view_block_hash,
+    hash::{NimbleHasher, Sha256Hasher, HASH_ALGORITHM},
+    parse_genesis_block, Block, CustomSerde, Handle, MetaBlock, NimbleDigest, NimbleHashTrait,
+    Nonce, Nonces, VerifierState, MAX_BLOCK_SIZE,
+  };
+  use ledger::{
+    errors::VerificationError,
+    signature::{PrivateKey, PrivateKeyTrait, SignatureTrait},
+    Receipt, Receipts,
+  };
+  use prost::Message;
   use rand::Rng;
   use std::{
     collections::HashMap,
@@ -496,7 +2393,12 @@ mod tests {
     io::{BufRead, BufReader},
     process::{Child, Command, Stdio},
     sync::Arc,
+    time::Duration,
   };
+  use store::ledger::IntentRecord;
+  use tokio::sync::mpsc;
+  use tokio_stream::StreamExt;
+  use tonic::{Request, Response, Status};
 
   struct BoxChild {
     pub child: Child,
@@ -614,7 +2516,7 @@ mod tests {
 
     // Create the coordinator
     let coordinator = Arc::new(
-      CoordinatorState::new(&store, &ledger_store_args, None)
+      CoordinatorState::new(&store, &ledger_store_args, None, None, None, None)
         .await
         .unwrap(),
     );
@@ -629,7 +2531,10 @@ mod tests {
     // Initialization: Fetch view ledger to build VerifierState
     let mut vs = VerifierState::new();
 
-    let req = tonic::Request::new(ReadViewTailReq {});
+    let view_nonce = rand::thread_rng().gen::<[u8; 16]>();
+    let req = tonic::Request::new(ReadViewTailReq {
+      nonce: view_nonce.to_vec(),
+    });
     let res = server.read_view_tail(req).await;
     assert!(res.is_ok());
     let ReadViewTailResp {
@@ -637,14 +2542,32 @@ mod tests {
       receipts,
       height: view_height,
       attestations,
+      hash_algorithm,
+      nonce_receipts,
     } = res.unwrap().into_inner();
 
     assert!(view_height == 1);
-    vs.set_group_identity(NimbleDigest::digest(&block));
+    assert_eq!(hash_algorithm, HASH_ALGORITHM.id());
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
 
     let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
     assert!(res.is_ok());
 
+    // the endorsers vouch that the tail is the current view for this nonce and no other
+    assert_eq!(vs.verify_view_tail(&view_nonce, &nonce_receipts), Ok(1));
+    assert!(vs.verify_view_tail(&[0u8; 16], &nonce_receipts).is_err());
+    let res = server
+      .read_view_by_index(tonic::Request::new(ReadViewByIndexReq { index: 2 }))
+      .await;
+    let status = res.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange);
+    assert_eq!(
+      IndexOutOfRange::decode(status.details())
+        .unwrap()
+        .current_height,
+      1
+    );
+
     // Step 0: Create some app data
     let block_bytes: Vec<u8> = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
 
@@ -653,12 +2576,104 @@ mod tests {
     let request = tonic::Request::new(NewLedgerReq {
       handle: handle_bytes.to_vec(),
       block: block_bytes.to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: b"app=test".to_vec(),
     });
-    let NewLedgerResp { receipts } = server.new_ledger(request).await.unwrap().into_inner();
+    let NewLedgerResp { receipts, handle } = server.new_ledger(request).await.unwrap().into_inner();
+    assert_eq!(handle, handle_bytes.to_vec());
     let res = vs.verify_new_ledger(&handle_bytes, block_bytes.as_ref(), &receipts);
+    assert!(res.is_err());
+    let res = vs.verify_new_ledger_with_metadata(
+      &handle_bytes,
+      block_bytes.as_ref(),
+      b"app=test",
+      &receipts,
+    );
     println!("NewLedger (WithAppData) : {:?}", res);
     assert!(res.is_ok());
 
+    // the metadata cannot change after creation
+    let request = tonic::Request::new(NewLedgerReq {
+      handle: handle_bytes.to_vec(),
+      block: block_bytes.to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: b"app=other".to_vec(),
+    });
+    let res = server.new_ledger(request).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+    // Step 1a: NewLedger with a handle derived by the coordinator from (app_bytes, nonce)
+    let app_bytes = b"namespace-1".to_vec();
+    let nonce = Nonce::new();
+    let new_ledger_derived = || {
+      tonic::Request::new(NewLedgerReq {
+        handle: vec![],
+        block: block_bytes.to_vec(),
+        app_bytes: app_bytes.clone(),
+        nonce: nonce.to_bytes(),
+        metadata: vec![],
+      })
+    };
+    let NewLedgerResp {
+      receipts,
+      handle: derived_handle,
+    } = server
+      .new_ledger(new_ledger_derived())
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(
+      derived_handle,
+      Handle::derive(&app_bytes, &nonce).to_bytes()
+    );
+    let res = vs.verify_new_ledger(&derived_handle, block_bytes.as_ref(), &receipts);
+    assert!(res.is_ok());
+
+    // retrying the create returns the stored receipts of the existing ledger
+    let NewLedgerResp {
+      receipts: retried_receipts,
+      handle: retried_handle,
+    } = server
+      .new_ledger(new_ledger_derived())
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(retried_handle, derived_handle);
+    assert_eq!(retried_receipts, receipts);
+
+    // deriving the same handle with another genesis block must not overwrite the existing ledger
+    let req = tonic::Request::new(NewLedgerReq {
+      handle: vec![],
+      block: b"another genesis block".to_vec(),
+      app_bytes: app_bytes.clone(),
+      nonce: nonce.to_bytes(),
+      metadata: vec![],
+    });
+    let res = server.new_ledger(req).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::AlreadyExists);
+
+    // duplicate creates racing through two tasks both succeed with receipts for the same ledger
+    let race_handle = Handle::random().to_bytes();
+    let tasks = (0..2)
+      .map(|_| {
+        let state = server.state.clone();
+        let race_handle = race_handle.clone();
+        let block_bytes = block_bytes.clone();
+        tokio::spawn(async move {
+          state
+            .create_ledger(None, &race_handle, &block_bytes, &[], &[])
+            .await
+        })
+      })
+      .collect::<Vec<_>>();
+    for task in tasks {
+      let receipts = task.await.unwrap().unwrap();
+      let res = vs.verify_new_ledger(&race_handle, &block_bytes, &receipts.to_bytes());
+      assert!(res.is_ok());
+    }
+
     let handle = handle_bytes.to_vec();
 
     // Step 2: Read At Index
@@ -671,29 +2686,58 @@ mod tests {
       block,
       nonces,
       receipts,
+      ..
     } = server.read_by_index(req).await.unwrap().into_inner();
 
     let res = vs.verify_read_by_index(&handle, &block, &nonces, 0, &receipts);
     println!("ReadByIndex: {:?}", res.is_ok());
     assert!(res.is_ok());
+    assert_eq!(
+      parse_genesis_block(&block).unwrap(),
+      (&b"app=test"[..], &block_bytes[..])
+    );
 
     // Step 3: Read Latest with the Nonce generated
     let nonce = rand::thread_rng().gen::<[u8; 16]>();
     let req = tonic::Request::new(ReadLatestReq {
       handle: handle.clone(),
       nonce: nonce.to_vec(),
+      consistency: ReadConsistency::Attested as i32,
     });
 
     let ReadLatestResp {
       block,
       nonces,
       receipts,
+      height,
+      nonce: echoed_nonce,
+      consistency,
     } = server.read_latest(req).await.unwrap().into_inner();
+    assert_eq!(height, 0);
+    assert_eq!(consistency, ReadConsistency::Attested as i32);
+    assert_eq!(echoed_nonce, nonce.to_vec());
 
     let res = vs.verify_read_latest(&handle, &block, &nonces, nonce.as_ref(), &receipts);
     println!("Read Latest : {:?}", res.is_ok());
     assert!(res.is_ok());
 
+    // malformed requests are rejected with InvalidArgument
+    let req = tonic::Request::new(ReadLatestReq {
+      handle: handle.clone(),
+      nonce: vec![0u8; 15],
+      consistency: ReadConsistency::Attested as i32,
+    });
+    let res = server.read_latest(req).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    let req = tonic::Request::new(AppendReq {
+      handle: handle.clone(),
+      block: b"data_block_example_0".to_vec(),
+      expected_height: u64::MAX,
+      request_id: String::new(),
+    });
+    let res = server.append(req).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
     // Step 4: Append
     let b1: Vec<u8> = "data_block_example_1".as_bytes().to_vec();
     let b2: Vec<u8> = "data_block_example_2".as_bytes().to_vec();
@@ -702,17 +2746,21 @@ mod tests {
 
     let mut expected_height = 0;
     for block_to_append in blocks {
-      expected_height += 1;
+      // the first append expects a fresh ledger
       let req = tonic::Request::new(AppendReq {
         handle: handle.clone(),
         block: block_to_append.to_vec(),
         expected_height: expected_height as u64,
+        request_id: String::new(),
       });
+      expected_height += 1;
 
       let AppendResp {
         hash_nonces,
         receipts,
+        height,
       } = server.append(req).await.unwrap().into_inner();
+      assert_eq!(height, expected_height as u64);
 
       let res = vs.verify_append(
         &handle,
@@ -725,23 +2773,39 @@ mod tests {
       assert!(res.is_ok());
     }
 
+    // an oversized block is rejected before it reaches the endorsers
+    let req = tonic::Request::new(AppendReq {
+      handle: handle.clone(),
+      block: vec![0u8; MAX_BLOCK_SIZE + 1],
+      expected_height: expected_height as u64,
+      request_id: String::new(),
+    });
+    let res = server.append(req).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
     // Step 4: Read Latest with the Nonce generated and check for new data
     let nonce = rand::thread_rng().gen::<[u8; 16]>();
     let latest_state_query = tonic::Request::new(ReadLatestReq {
       handle: handle.clone(),
       nonce: nonce.to_vec(),
+      consistency: ReadConsistency::Attested as i32,
     });
 
     let ReadLatestResp {
       block,
       nonces,
       receipts,
+      height,
+      nonce: echoed_nonce,
+      ..
     } = server
       .read_latest(latest_state_query)
       .await
       .unwrap()
       .into_inner();
     assert_eq!(block, b3.clone());
+    assert_eq!(height, 3);
+    assert_eq!(echoed_nonce, nonce.to_vec());
 
     let is_latest_valid =
       vs.verify_read_latest(&handle, &block, &nonces, nonce.as_ref(), &receipts);
@@ -761,6 +2825,7 @@ mod tests {
       block,
       nonces,
       receipts,
+      ..
     } = server.read_by_index(req).await.unwrap().into_inner();
     assert_eq!(block, b1.clone());
 
@@ -768,6 +2833,126 @@ mod tests {
     println!("Verifying ReadByIndex Response: {:?}", res.is_ok());
     assert!(res.is_ok());
 
+    // Step 5c: a batch appends to several ledgers at once; the conflict on the last item does
+    // not keep the others from being appended and endorsed
+    let batch_handles = (0..3)
+      .map(|_| Handle::random().to_bytes())
+      .collect::<Vec<_>>();
+    for batch_handle in &batch_handles {
+      let res = server
+        .state
+        .create_ledger(None, batch_handle, &block_bytes, &[], &[])
+        .await;
+      assert!(res.is_ok());
+    }
+    let batch_items = [
+      (batch_handles[0].clone(), 0),
+      (batch_handles[1].clone(), 0),
+      (handle.clone(), expected_height as u64),
+      (batch_handles[2].clone(), 5),
+    ];
+    let AppendBatchResp { results } = server
+      .append_batch(tonic::Request::new(AppendBatchReq {
+        items: batch_items
+          .iter()
+          .map(|(batch_handle, expected_height)| AppendReq {
+            handle: batch_handle.clone(),
+            block: b"batch_block".to_vec(),
+            expected_height: *expected_height,
+            request_id: String::new(),
+          })
+          .collect(),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(results.len(), batch_items.len());
+    for ((batch_handle, expected_height), result) in batch_items.iter().zip(&results).take(3) {
+      assert_eq!(result.code, tonic::Code::Ok as i32);
+      let resp = result.resp.as_ref().unwrap();
+      assert_eq!(resp.height, expected_height + 1);
+      let res = vs.verify_append(
+        batch_handle,
+        b"batch_block",
+        &resp.hash_nonces,
+        resp.height as usize,
+        &resp.receipts,
+      );
+      assert!(res.is_ok());
+    }
+    assert_eq!(results[3].code, tonic::Code::FailedPrecondition as i32);
+    let AppendConditionFailed { current_height, .. } =
+      AppendConditionFailed::decode(results[3].details.as_slice()).unwrap();
+    assert_eq!(current_height, 0);
+    expected_height += 1;
+
+    // Step 5d: a coordinator that persisted a block but crashed before the endorsers signed it
+    // leaves an unendorsed tail; the next append reconciles it before extending the ledger, and
+    // a client retrying an append the store already holds gets its receipts rather than a conflict
+    let crash_handle = Handle::random().to_bytes();
+    let res = server
+      .state
+      .create_ledger(None, &crash_handle, &block_bytes, &[], &[])
+      .await;
+    assert!(res.is_ok());
+    let crash_block = Block::new(b"persisted_before_crash");
+    let res = server
+      .state
+      .ledger_store
+      .append_ledger(&NimbleDigest::digest(&crash_handle), &crash_block, 1usize)
+      .await;
+    assert!(res.is_ok());
+
+    let append = |expected_height: u64| {
+      server.append(tonic::Request::new(AppendReq {
+        handle: crash_handle.clone(),
+        block: b"after_crash".to_vec(),
+        expected_height,
+        request_id: String::new(),
+      }))
+    };
+    let resp = append(1).await.unwrap().into_inner();
+    assert_eq!(resp.height, 2);
+    let res = vs.verify_append(
+      &crash_handle,
+      b"after_crash",
+      &resp.hash_nonces,
+      resp.height as usize,
+      &resp.receipts,
+    );
+    assert!(res.is_ok());
+
+    let res = server
+      .state
+      .read_ledger_by_index(&crash_handle, 1usize)
+      .await;
+    let ledger_entry = res.unwrap();
+    assert_eq!(ledger_entry.get_block().to_bytes(), crash_block.to_bytes());
+    let res = vs.verify_append(
+      &crash_handle,
+      &crash_block.to_bytes(),
+      &ledger_entry.get_nonces().hash().to_bytes(),
+      1usize,
+      &ledger_entry.get_receipts().to_bytes(),
+    );
+    assert!(res.is_ok());
+
+    let retried = append(1).await.unwrap().into_inner();
+    assert_eq!(retried.height, 2);
+    assert_eq!(retried.hash_nonces, resp.hash_nonces);
+    let res = vs.verify_append(
+      &crash_handle,
+      b"after_crash",
+      &retried.hash_nonces,
+      retried.height as usize,
+      &retried.receipts,
+    );
+    assert!(res.is_ok());
+    assert_eq!(
+      append(0).await.unwrap_err().code(),
+      tonic::Code::FailedPrecondition
+    );
+
     // Step 6: change the view by adding two new endorsers
     let endorser_args2 = endorser_args.clone() + " -p 9092";
     let endorser2 = launch_endorser(&endorser_cmd, endorser_args2);
@@ -784,7 +2969,9 @@ mod tests {
     println!("new config with 2 endorsers: {:?}", res);
     assert!(res.is_ok());
 
-    let req = tonic::Request::new(ReadViewTailReq {});
+    let req = tonic::Request::new(ReadViewTailReq {
+      nonce: view_nonce.to_vec(),
+    });
     let res = server.read_view_tail(req).await;
     assert!(res.is_ok());
     let ReadViewTailResp {
@@ -792,25 +2979,34 @@ mod tests {
       receipts,
       height: _view_height,
       attestations,
+      nonce_receipts,
+      ..
     } = res.unwrap().into_inner();
 
+    // the receipts on the nonce from before the view change do not show the new view is current
+    assert_eq!(
+      vs.verify_view_tail(&view_nonce, &nonce_receipts),
+      Err(ledger::errors::VerificationError::ViewInMetaBlockNotLatest)
+    );
     let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
     println!("Applying ReadViewByIndexResp Response: {:?}", res);
     assert!(res.is_ok());
+    assert!(vs.verify_view_tail(&view_nonce, &nonce_receipts).is_ok());
 
     // Step 7: Append after view change
-    expected_height += 1;
-
     let message = "data_block_append".as_bytes();
     let req = tonic::Request::new(AppendReq {
       handle: handle.clone(),
       block: message.to_vec(),
       expected_height: expected_height as u64,
+      request_id: String::new(),
     });
+    expected_height += 1;
 
     let AppendResp {
       hash_nonces,
       receipts,
+      ..
     } = server.append(req).await.unwrap().into_inner();
 
     let res = vs.verify_append(&handle, message, &hash_nonces, expected_height, &receipts);
@@ -822,12 +3018,14 @@ mod tests {
     let latest_state_query = tonic::Request::new(ReadLatestReq {
       handle: handle.clone(),
       nonce: nonce.to_vec(),
+      consistency: ReadConsistency::Attested as i32,
     });
 
     let ReadLatestResp {
       block,
       nonces,
       receipts,
+      ..
     } = server
       .read_latest(latest_state_query)
       .await
@@ -850,7 +3048,13 @@ mod tests {
     let handle_bytes = rand::thread_rng().gen::<[u8; 16]>();
     let res = server
       .get_state()
-      .create_ledger(Some(endorsers.clone()), handle_bytes.as_ref(), &[])
+      .create_ledger(
+        Some(endorsers.clone()),
+        handle_bytes.as_ref(),
+        &[],
+        &[],
+        &[],
+      )
       .await;
     println!("create_ledger with first endorser: {:?}", res);
     assert!(res.is_ok());
@@ -873,7 +3077,7 @@ mod tests {
     let handle2_bytes = rand::thread_rng().gen::<[u8; 16]>();
     let res = server
       .get_state()
-      .create_ledger(None, handle2_bytes.as_ref(), &[])
+      .create_ledger(None, handle2_bytes.as_ref(), &[], &[], &[])
       .await;
     println!("create_ledger with first endorser: {:?}", res);
     assert!(res.is_ok());
@@ -975,7 +3179,7 @@ mod tests {
     println!("new config with 3 endorsers: {:?}", res);
     assert!(res.is_ok());
 
-    let req = tonic::Request::new(ReadViewTailReq {});
+    let req = tonic::Request::new(ReadViewTailReq { nonce: vec![] });
     let res = server.read_view_tail(req).await;
     assert!(res.is_ok());
     let ReadViewTailResp {
@@ -983,6 +3187,7 @@ mod tests {
       receipts,
       height: _view_height,
       attestations,
+      ..
     } = res.unwrap().into_inner();
 
     let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
@@ -994,12 +3199,14 @@ mod tests {
     let latest_state_query = tonic::Request::new(ReadLatestReq {
       handle: new_handle.clone(),
       nonce: nonce.to_vec(),
+      consistency: ReadConsistency::Attested as i32,
     });
 
     let ReadLatestResp {
       block,
       nonces,
       receipts,
+      ..
     } = server
       .read_latest(latest_state_query)
       .await
@@ -1017,18 +3224,163 @@ mod tests {
     let req = tonic::Request::new(AppendReq {
       handle: new_handle.clone(),
       block: message.to_vec(),
-      expected_height: 2_u64,
+      expected_height: 1_u64,
+      request_id: String::new(),
     });
 
     let AppendResp {
       hash_nonces,
       receipts,
+      ..
     } = server.append(req).await.unwrap().into_inner();
 
     let res = vs.verify_append(&new_handle, message, &hash_nonces, 2, &receipts);
     println!("Append verification: {:?}", res.is_ok());
     assert!(res.is_ok());
 
+    // Step 12a: grow the view with a fourth endorser
+    let endorser_args10 = endorser_args.clone() + " -p 9100";
+    let endorser10 = launch_endorser(&endorser_cmd, endorser_args10);
+
+    let res = server
+      .get_state()
+      .add_endorsers(&["http://[::1]:9100".to_string()])
+      .await;
+    println!("new config with 4 endorsers: {:?}", res);
+    assert!(res.is_ok());
+    assert_eq!(server.get_state().get_endorser_pks().len(), 4);
+
+    let req = tonic::Request::new(ReadViewTailReq { nonce: vec![] });
+    let res = server.read_view_tail(req).await;
+    assert!(res.is_ok());
+    let ReadViewTailResp {
+      block,
+      receipts,
+      height: _view_height,
+      attestations,
+      ..
+    } = res.unwrap().into_inner();
+
+    let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
+    println!("Applying ReadViewByIndexResp Response: {:?}", res);
+    assert!(res.is_ok());
+
+    // the endorsers that stayed and the new one endorse appends
+    let message = "data_block_append 3a".as_bytes();
+    let req = tonic::Request::new(AppendReq {
+      handle: new_handle.clone(),
+      block: message.to_vec(),
+      expected_height: 2_u64,
+      request_id: String::new(),
+    });
+
+    let AppendResp {
+      hash_nonces,
+      receipts,
+      ..
+    } = server.append(req).await.unwrap().into_inner();
+
+    let res = vs.verify_append(&new_handle, message, &hash_nonces, 3, &receipts);
+    println!("Append verification: {:?}", res.is_ok());
+    assert!(res.is_ok());
+
+    // Step 12b: remove the fourth endorser after it died
+    drop(endorser10);
+
+    let res = server
+      .get_state()
+      .remove_endorsers(&["http://[::1]:9100".to_string()])
+      .await;
+    println!("new config without the dead endorser: {:?}", res);
+    assert_eq!(res.unwrap().len(), 1);
+    assert_eq!(server.get_state().get_endorser_pks().len(), 3);
+
+    let req = tonic::Request::new(ReadViewTailReq { nonce: vec![] });
+    let res = server.read_view_tail(req).await;
+    assert!(res.is_ok());
+    let ReadViewTailResp {
+      block,
+      receipts,
+      height: _view_height,
+      attestations,
+      ..
+    } = res.unwrap().into_inner();
+
+    let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
+    println!("Applying ReadViewByIndexResp Response: {:?}", res);
+    assert!(res.is_ok());
+
+    // receipts are collected from the remaining endorsers only
+    let message = "data_block_append 3b".as_bytes();
+    let req = tonic::Request::new(AppendReq {
+      handle: new_handle.clone(),
+      block: message.to_vec(),
+      expected_height: 3_u64,
+      request_id: String::new(),
+    });
+
+    let AppendResp {
+      hash_nonces,
+      receipts,
+      ..
+    } = server.append(req).await.unwrap().into_inner();
+
+    let res = vs.verify_append(&new_handle, message, &hash_nonces, 4, &receipts);
+    println!("Append verification: {:?}", res.is_ok());
+    assert!(res.is_ok());
+
+    // removing every endorser would leave the view empty
+    let res = server
+      .get_state()
+      .remove_endorsers(&server.get_state().get_endorser_uris())
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::TooFewEndorsers);
+
+    // Step 12c: inspect the endorsers and the views through the admin service
+    let admin = AdminServiceState::new(server.state.clone());
+    let ListEndorsersResp { endorsers } = admin
+      .list_endorsers(tonic::Request::new(ListEndorsersReq {}))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(endorsers.len(), 3);
+    assert!(endorsers
+      .iter()
+      .all(|e| e.connected && e.healthy && e.lag == 0));
+
+    let GetViewHistoryResp { views } = admin
+      .get_view_history(tonic::Request::new(GetViewHistoryReq {}))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(views.len(), 5);
+    assert_eq!(views[3].endorsers.len(), 4);
+    assert_eq!(views[4].endorsers.len(), 3);
+    assert_eq!(views[4].quorum, 2);
+
+    let OperationResp { operation_id } = admin
+      .trigger_repair(tonic::Request::new(TriggerRepairReq {
+        pk: endorsers[0].pk.clone(),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    loop {
+      let GetOperationStatusResp { state, error } = admin
+        .get_operation_status(tonic::Request::new(GetOperationStatusReq {
+          operation_id: operation_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+      if state == OperationState::Running as i32 {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        continue;
+      }
+      assert_eq!(state, OperationState::Succeeded as i32, "{}", error);
+      break;
+    }
+
     if store != "memory" {
       // set up the endorsers to be at different heights
       let mut endorsers = server.get_state().get_endorser_pks();
@@ -1037,7 +3389,13 @@ mod tests {
       let handle_bytes = rand::thread_rng().gen::<[u8; 16]>();
       let res = server
         .get_state()
-        .create_ledger(Some(endorsers.clone()), handle_bytes.as_ref(), &[])
+        .create_ledger(
+          Some(endorsers.clone()),
+          handle_bytes.as_ref(),
+          &[],
+          &[],
+          &[],
+        )
         .await;
       println!("create_ledger with the first two endorser: {:?}", res);
       assert!(res.is_ok());
@@ -1063,7 +3421,7 @@ mod tests {
       let handle2_bytes = rand::thread_rng().gen::<[u8; 16]>();
       let res = server
         .get_state()
-        .create_ledger(None, handle2_bytes.as_ref(), &[])
+        .create_ledger(None, handle2_bytes.as_ref(), &[], &[], &[])
         .await;
       println!("create_ledger with all three endorser: {:?}", res);
       assert!(res.is_ok());
@@ -1112,7 +3470,7 @@ mod tests {
       let res = server
         .state
         .ledger_store
-        .append_view_ledger(&Block::new(&view_ledger_genesis_block), 4usize)
+        .append_view_ledger(&Block::new(&view_ledger_genesis_block), 6usize)
         .await;
       assert!(res.is_ok());
 
@@ -1120,7 +3478,7 @@ mod tests {
       drop(server);
 
       let coordinator2 = Arc::new(
-        CoordinatorState::new(&store, &ledger_store_args, None)
+        CoordinatorState::new(&store, &ledger_store_args, None, None, None, None)
           .await
           .unwrap(),
       );
@@ -1128,7 +3486,7 @@ mod tests {
       let server2 = CoordinatorServiceState::new(coordinator2);
       println!("Started a new coordinator");
 
-      let req = tonic::Request::new(ReadViewTailReq {});
+      let req = tonic::Request::new(ReadViewTailReq { nonce: vec![] });
       let res = server2.read_view_tail(req).await;
       assert!(res.is_ok());
       let ReadViewTailResp {
@@ -1136,6 +3494,7 @@ mod tests {
         receipts,
         height: _view_height,
         attestations,
+        ..
       } = res.unwrap().into_inner();
 
       let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
@@ -1147,12 +3506,14 @@ mod tests {
       let req = tonic::Request::new(AppendReq {
         handle: new_handle.clone(),
         block: message.to_vec(),
-        expected_height: 2_u64,
+        expected_height: 1_u64,
+        request_id: String::new(),
       });
 
       let AppendResp {
         hash_nonces,
         receipts,
+        ..
       } = server2.append(req).await.unwrap().into_inner();
       let res = vs.verify_append(&new_handle, message, &hash_nonces, 2, &receipts);
       println!("Append verification: {:?}", res.is_ok());
@@ -1163,12 +3524,14 @@ mod tests {
       let req = tonic::Request::new(AppendReq {
         handle: new_handle2.clone(),
         block: message.to_vec(),
-        expected_height: 2_u64,
+        expected_height: 1_u64,
+        request_id: String::new(),
       });
 
       let AppendResp {
         hash_nonces,
         receipts,
+        ..
       } = server2.append(req).await.unwrap().into_inner();
       let res = vs.verify_append(&new_handle2, message, &hash_nonces, 2, &receipts);
       println!("Append verification: {:?}", res.is_ok());
@@ -1190,4 +3553,4274 @@ mod tests {
     println!("endorser5 process ID is {}", endorser5.child.id());
     println!("endorser6 process ID is {}", endorser6.child.id());
   }
+
+  /// a coordinator with an in-memory store whose view is `num_endorsers` mock endorsers, with the
+  /// mock endorsers and the connector to them
+  async fn mock_coordinator(
+    num_endorsers: usize,
+  ) -> (CoordinatorState, Arc<MockEndorsers>, Vec<Arc<MockEndorser>>) {
+    let mocks = MockEndorsers::new();
+    let uris = (0..num_endorsers)
+      .map(|i| format!("mock://endorser{}", i))
+      .collect::<Vec<_>>();
+    let endorsers = uris.iter().map(|uri| mocks.start(uri)).collect();
+    let state = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap()
+      .with_endorser_connector(mocks.clone());
+    let res = state.replace_endorsers(&uris).await;
+    assert!(res.is_ok(), "{:?}", res);
+    (state, mocks, endorsers)
+  }
+
+  /// waits for what the endorsers do after the coordinator has its quorum
+  async fn eventually(done: impl Fn() -> bool) {
+    for _ in 0..100 {
+      if done() {
+        return;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the endorsers did not catch up");
+  }
+
+  #[tokio::test]
+  async fn test_byzantine_endorser() {
+    // two honest endorsers and a third one that forges its signatures
+    let (coordinator, _mocks, endorsers) = mock_coordinator(3).await;
+    let byzantine = &endorsers[2];
+    byzantine.set_byzantine(true);
+    let byzantine_pk = byzantine.public_key();
+    let server = CoordinatorServiceState::new(Arc::new(coordinator));
+
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = server
+      .read_view_tail(tonic::Request::new(ReadViewTailReq { nonce: vec![] }))
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    // whichever two endorsers answer first, the coordinator returns only valid signatures
+    let handle = Handle::random().to_bytes();
+    let genesis = b"genesis".to_vec();
+    let NewLedgerResp { receipts, .. } = server
+      .new_ledger(tonic::Request::new(NewLedgerReq {
+        handle: handle.clone(),
+        block: genesis.clone(),
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: vec![],
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs.verify_new_ledger(&handle, &genesis, &receipts).is_ok());
+
+    for height in 1..=3usize {
+      let block = format!("block_{}", height).into_bytes();
+      let AppendResp {
+        hash_nonces,
+        receipts,
+        ..
+      } = server
+        .append(tonic::Request::new(AppendReq {
+          handle: handle.clone(),
+          block: block.clone(),
+          expected_height: height as u64 - 1,
+          request_id: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+      let res = vs.verify_append(&handle, &block, &hash_nonces, height, &receipts);
+      assert!(res.is_ok());
+    }
+
+    let first_nonce = Nonce::new().to_bytes();
+    let ReadLatestResp {
+      receipts: first_receipts,
+      nonce: echoed_nonce,
+      ..
+    } = server
+      .read_latest(tonic::Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: first_nonce.clone(),
+        consistency: ReadConsistency::Attested as i32,
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(echoed_nonce, first_nonce);
+    eventually(|| byzantine.num_calls("read_latest") > 0).await;
+
+    // the byzantine endorser replays its receipt for the first nonce, which the coordinator
+    // rejects, so the client gets receipts that cover its new nonce
+    let nonce = Nonce::new().to_bytes();
+    let ReadLatestResp {
+      block,
+      nonces,
+      receipts,
+      nonce: echoed_nonce,
+      ..
+    } = server
+      .read_latest(tonic::Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: nonce.clone(),
+        consistency: ReadConsistency::Attested as i32,
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(echoed_nonce, nonce);
+    assert!(vs
+      .verify_read_latest(&handle, &block, &nonces, &nonce, &receipts)
+      .is_ok());
+    assert!(!Receipts::from_bytes(&receipts)
+      .unwrap()
+      .contains(&byzantine_pk));
+
+    // a client also rejects receipts that cover a previous nonce
+    assert!(vs
+      .verify_read_latest(&handle, &block, &nonces, &nonce, &first_receipts)
+      .is_err());
+
+    // without the second honest endorser, the forged signatures cannot make up a quorum: the
+    // append fails and only the honest receipt is kept for a later reconciliation
+    endorsers[1].set_down(true);
+    let block = b"block_4".to_vec();
+    let res = server
+      .append(tonic::Request::new(AppendReq {
+        handle: handle.clone(),
+        block,
+        expected_height: 3,
+        request_id: String::new(),
+      }))
+      .await;
+    assert!(res.is_err());
+    let ledger_entry = server
+      .state
+      .ledger_store
+      .read_ledger_by_index(&NimbleDigest::digest(&handle), 4)
+      .await
+      .unwrap();
+    assert!(!ledger_entry.get_receipts().contains(&byzantine_pk));
+    assert!(vs
+      .verify_append(
+        &handle,
+        b"block_4",
+        &ledger_entry.get_nonces().hash().to_bytes(),
+        4,
+        &ledger_entry.get_receipts().to_bytes(),
+      )
+      .is_err());
+
+    let statuses = server.state.get_endorser_statuses().await.unwrap();
+    for status in statuses {
+      if status.pk == byzantine_pk {
+        assert!(status.invalid_signatures > 0);
+      } else {
+        assert_eq!(status.invalid_signatures, 0);
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_endorser_retries() {
+    let (state, _mocks, endorsers) = mock_coordinator(3).await;
+    let handle = Handle::random().to_bytes();
+    let res = state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok(), "{:?}", res);
+    let digest = NimbleDigest::digest(&handle);
+
+    // an endorser that is out of resources is asked again until it endorses the append
+    endorsers[0].fail_next("append", tonic::Code::ResourceExhausted, 3);
+    let res = state.append_ledger(None, &handle, b"block_1", 1).await;
+    assert!(res.is_ok(), "{:?}", res);
+    eventually(|| endorsers[0].height(&digest) == Some(1)).await;
+    assert_eq!(endorsers[0].num_calls("append"), 4);
+
+    // an endorser that was down while the ledger grew is brought up to date by the next append
+    endorsers[2].set_down(true);
+    for height in 2..=3 {
+      let block = format!("block_{}", height).into_bytes();
+      let res = state.append_ledger(None, &handle, &block, height).await;
+      assert!(res.is_ok(), "{:?}", res);
+    }
+    assert_eq!(endorsers[2].height(&digest), Some(1));
+    endorsers[2].set_down(false);
+    let res = state.append_ledger(None, &handle, b"block_4", 4).await;
+    assert!(res.is_ok(), "{:?}", res);
+    eventually(|| endorsers[2].height(&digest) == Some(4)).await;
+    let ledger_entry = state
+      .ledger_store
+      .read_ledger_by_index(&digest, 2)
+      .await
+      .unwrap();
+    assert!(ledger_entry
+      .get_receipts()
+      .contains(&endorsers[2].public_key()));
+
+    // a slow endorser does not hold up an append that the others make a quorum for
+    endorsers[1].set_latency(Duration::from_secs(10));
+    let start = std::time::Instant::now();
+    let res = state.append_ledger(None, &handle, b"block_5", 5).await;
+    assert!(res.is_ok(), "{:?}", res);
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // with two of the three endorsers down, an append has no quorum
+    endorsers[1].set_down(true);
+    endorsers[2].set_down(true);
+    let res = state.append_ledger(None, &handle, b"block_6", 6).await;
+    assert!(res.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_cluster() {
+    // the clients reach the coordinator, and the coordinator the endorsers, over the network
+    let mut cluster = TestCluster::start(3, StoreKind::TempDir).await;
+    let mut client = cluster.client();
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = client
+      .read_view_tail(ReadViewTailReq { nonce: vec![] })
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    let handle = Handle::random().to_bytes();
+    let NewLedgerResp { receipts, .. } = client
+      .new_ledger(NewLedgerReq {
+        handle: handle.clone(),
+        block: b"genesis".to_vec(),
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: vec![],
+      })
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs.verify_new_ledger(&handle, b"genesis", &receipts).is_ok());
+    let append = |height: usize| AppendReq {
+      handle: handle.clone(),
+      block: format!("block_{}", height).into_bytes(),
+      expected_height: height as u64 - 1,
+      request_id: String::new(),
+    };
+
+    // a killed endorser does not hold up the appends, and the first append after its restart
+    // brings it up to date
+    cluster.kill_endorser(0).await;
+    for height in 1..=3usize {
+      if height == 3 {
+        cluster.restart_endorser(0).await;
+      }
+      let AppendResp {
+        hash_nonces,
+        receipts,
+        ..
+      } = client.append(append(height)).await.unwrap().into_inner();
+      let block = format!("block_{}", height).into_bytes();
+      let res = vs.verify_append(&handle, &block, &hash_nonces, height, &receipts);
+      assert!(res.is_ok());
+    }
+    let digest = NimbleDigest::digest(&handle);
+    eventually(|| cluster.endorser(0).height(&digest) == Some(3)).await;
+
+    // nor does a partitioned endorser, but without two of the three there is no quorum
+    cluster.partition(1);
+    let res = client.append(append(4)).await;
+    assert!(res.is_ok(), "{:?}", res);
+    cluster.partition(2);
+    let res = client.append(append(5)).await;
+    assert!(res.is_err());
+    cluster.heal(1);
+    cluster.heal(2);
+
+    // the cluster leaves no server behind
+    let uri = cluster.coordinator_uri();
+    cluster.stop().await;
+    let res = tonic::transport::Endpoint::from_shared(uri)
+      .unwrap()
+      .connect()
+      .await;
+    assert!(res.is_err());
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_coordinator_failover() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9120");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9121");
+
+    // the primary takes the free lease at once, and creates the view once it holds it
+    let lease_duration = Duration::from_millis(600);
+    let primary = Arc::new(
+      CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap()
+        .with_lease(Lease::new("primary", lease_duration)),
+    );
+    primary.take_lease().await;
+    let primary_lease = {
+      let primary = primary.clone();
+      tokio::spawn(async move { primary.keep_lease().await })
+    };
+    primary.recover().await.unwrap();
+    let res = primary
+      .replace_endorsers(&[
+        "http://[::1]:9120".to_string(),
+        "http://[::1]:9121".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let server = CoordinatorServiceState::new(primary.clone());
+
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = server
+      .read_view_tail(tonic::Request::new(ReadViewTailReq { nonce: vec![] }))
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    let handle = Handle::random().to_bytes();
+    let NewLedgerResp { receipts, .. } = server
+      .new_ledger(tonic::Request::new(NewLedgerReq {
+        handle: handle.clone(),
+        block: b"genesis".to_vec(),
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: vec![],
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs.verify_new_ledger(&handle, b"genesis", &receipts).is_ok());
+    let append = |block: &[u8], expected_height: u64| {
+      tonic::Request::new(AppendReq {
+        handle: handle.clone(),
+        block: block.to_vec(),
+        expected_height,
+        request_id: String::new(),
+      })
+    };
+    let AppendResp {
+      hash_nonces,
+      receipts,
+      ..
+    } = server
+      .append(append(b"block_1", 0))
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs
+      .verify_append(&handle, b"block_1", &hash_nonces, 1, &receipts)
+      .is_ok());
+
+    // a standby over the same store waits while the primary renews the lease
+    let mut standby = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap()
+      .with_lease(Lease::new("standby", lease_duration));
+    standby.ledger_store = primary.ledger_store.clone();
+    let standby = Arc::new(standby);
+    let res = tokio::time::timeout(lease_duration * 3, standby.take_lease()).await;
+    assert!(res.is_err());
+
+    // the primary dies in the middle of an append: the store holds block_2, which no endorser
+    // has seen
+    primary
+      .ledger_store
+      .append_ledger(&NimbleDigest::digest(&handle), &Block::new(b"block_2"), 2)
+      .await
+      .unwrap();
+    primary_lease.abort();
+
+    // the standby takes over once the lease lapses, and its recovery endorses block_2
+    let res = tokio::time::timeout(Duration::from_secs(10), standby.take_lease()).await;
+    assert!(res.is_ok());
+    let standby_lease = {
+      let standby = standby.clone();
+      tokio::spawn(async move { standby.keep_lease().await })
+    };
+    standby.recover().await.unwrap();
+    let server2 = CoordinatorServiceState::new(standby.clone());
+
+    // what is left of the primary may not write anymore
+    let res = server.append(append(b"block_3", 2)).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::Unavailable);
+
+    let ReadByIndexResp {
+      block,
+      nonces,
+      receipts,
+      ..
+    } = server2
+      .read_by_index(tonic::Request::new(ReadByIndexReq {
+        handle: handle.clone(),
+        index: 2,
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(block, b"block_2".to_vec());
+    assert!(vs
+      .verify_read_by_index(&handle, &block, &nonces, 2, &receipts)
+      .is_ok());
+    let AppendResp {
+      hash_nonces,
+      receipts,
+      ..
+    } = server2
+      .append(append(b"block_3", 2))
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs
+      .verify_append(&handle, b"block_3", &hash_nonces, 3, &receipts)
+      .is_ok());
+
+    let lease = standby.ledger_store.read_lease().await.unwrap();
+    assert_eq!(lease.holder, "standby");
+    assert_eq!(lease.epoch, 2);
+    standby_lease.abort();
+  }
+
+  #[tokio::test]
+  async fn test_ledger_locks() {
+    let locks = LedgerLocks::new();
+    let handle1 = Handle::random();
+    let handle2 = Handle::random();
+
+    let guard1 = locks.lock(&handle1).await.unwrap();
+    // another ledger does not wait for the held lock
+    let res = tokio::time::timeout(Duration::from_secs(1), locks.lock(&handle2)).await;
+    assert!(res.is_ok());
+    // the same ledger waits until the lock is released
+    let res = tokio::time::timeout(Duration::from_millis(100), locks.lock(&handle1)).await;
+    assert!(res.is_err());
+    drop(guard1);
+    let res = tokio::time::timeout(Duration::from_secs(1), locks.lock(&handle1)).await;
+    assert!(res.is_ok());
+    // the lock of a ledger is counted once, however often it is taken
+    assert_eq!(locks.num_locks(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_append_race() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let server = CoordinatorServiceState::new(cluster.state().clone());
+    let vs = view_verifier(&server).await;
+    let block_bytes = b"genesis".to_vec();
+
+    // 100 tasks race to append to one ledger, retrying at the height they lost to; the
+    // appends are serialized, so each task gets a distinct height and a receipt that verifies
+    let stress_handle = Handle::random().to_bytes();
+    let res = server
+      .state
+      .create_ledger(None, &stress_handle, &block_bytes, &[], &[])
+      .await;
+    assert!(res.is_ok());
+    let tasks = (0..100)
+      .map(|i| {
+        let state = server.state.clone();
+        let stress_handle = stress_handle.clone();
+        tokio::spawn(async move {
+          let block = format!("stress_block_{}", i).into_bytes();
+          let mut height = 1;
+          loop {
+            match state
+              .append_ledger(None, &stress_handle, &block, height)
+              .await
+            {
+              Ok((hash_nonces, receipts)) => return (block, height, hash_nonces, receipts),
+              Err(CoordinatorError::ConditionFailed { current_height, .. }) => {
+                height = current_height + 1;
+              },
+              Err(error) => panic!("append failed: {:?}", error),
+            }
+          }
+        })
+      })
+      .collect::<Vec<_>>();
+    let mut heights = Vec::new();
+    for task in tasks {
+      let (block, height, hash_nonces, receipts) = task.await.unwrap();
+      let res = vs.verify_append(
+        &stress_handle,
+        &block,
+        &hash_nonces.to_bytes(),
+        height,
+        &receipts.to_bytes(),
+      );
+      assert!(res.is_ok());
+      heights.push(height);
+    }
+    heights.sort_unstable();
+    assert_eq!(heights, (1..=100).collect::<Vec<usize>>());
+    let (_tail, tail_height) = server
+      .state
+      .ledger_store
+      .read_ledger_tail(&NimbleDigest::digest(&stress_handle))
+      .await
+      .unwrap();
+    assert_eq!(tail_height, heights.len());
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_read_range() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let server = CoordinatorServiceState::new(cluster.state().clone());
+    let vs = view_verifier(&server).await;
+    let handle = Handle::random().to_bytes();
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    let b1 = b"data_block_example_1".to_vec();
+    let b2 = b"data_block_example_2".to_vec();
+    let b3 = b"data_block_example_3".to_vec();
+    for (height, block) in [&b1, &b2, &b3].iter().enumerate() {
+      let res = server
+        .state
+        .append_ledger(None, &handle, block, height + 1)
+        .await;
+      assert!(res.is_ok());
+    }
+
+    let ReadByIndexResp {
+      block,
+      nonces,
+      receipts,
+      metablock_hash,
+      checkpoint,
+      block_hashes,
+      tail_receipts,
+    } = server
+      .read_by_index(tonic::Request::new(ReadByIndexReq {
+        handle: handle.clone(),
+        index: 1,
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(block, b1);
+    let res = vs.verify_read_by_index(&handle, &block, &nonces, 1, &receipts);
+    assert!(res.is_ok());
+
+    // the entry chains to the attested tail at height 3
+    assert_eq!(block_hashes.len(), 2);
+    let entries = vec![(block.clone(), nonces.clone())];
+    let res = vs.verify_read_range(
+      &handle,
+      1,
+      &entries,
+      &checkpoint,
+      &block_hashes,
+      &tail_receipts,
+    );
+    assert_eq!(res, Ok(3));
+    let metablock = MetaBlock::from_bytes(&checkpoint)
+      .unwrap()
+      .next(&compute_aggregated_block_hash(
+        &NimbleDigest::digest(&block).to_bytes(),
+        &NimbleDigest::digest(&nonces).to_bytes(),
+      ))
+      .unwrap();
+    assert_eq!(metablock_hash, metablock.hash().to_bytes());
+
+    // reads beyond the current height report it
+    let req = tonic::Request::new(ReadByIndexReq {
+      handle: handle.clone(),
+      index: 4,
+    });
+    let status = server.read_by_index(req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange);
+    let details = IndexOutOfRange::decode(status.details()).unwrap();
+    assert_eq!(details.current_height, 3);
+
+    // ReadRange pages through the history, each page chaining to the attested tail
+    let mut page_token = vec![];
+    let mut pages = 0;
+    let mut blocks = vec![];
+    loop {
+      let req = tonic::Request::new(ReadRangeReq {
+        handle: handle.clone(),
+        from: 0,
+        to: 3,
+        page_size: 3,
+        page_token,
+      });
+      let ReadRangeResp {
+        entries,
+        checkpoint,
+        block_hashes,
+        tail_receipts,
+        next_page_token,
+      } = server.read_range(req).await.unwrap().into_inner();
+      let from = entries[0].index as usize;
+      let entries = entries
+        .into_iter()
+        .map(|entry| (entry.block, entry.nonces))
+        .collect::<Vec<(Vec<u8>, Vec<u8>)>>();
+      let res = vs.verify_read_range(
+        &handle,
+        from,
+        &entries,
+        &checkpoint,
+        &block_hashes,
+        &tail_receipts,
+      );
+      assert_eq!(res, Ok(3));
+      blocks.extend(entries.into_iter().map(|(block, _nonces)| block));
+      pages += 1;
+      if next_page_token.is_empty() {
+        break;
+      }
+      page_token = next_page_token;
+    }
+    assert_eq!(pages, 2);
+    assert_eq!(blocks.len(), 4);
+    assert_eq!(blocks[3], b3);
+    let req = tonic::Request::new(ReadRangeReq {
+      handle: handle.clone(),
+      from: 2,
+      to: 7,
+      page_size: 0,
+      page_token: vec![],
+    });
+    let status = server.read_range(req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange);
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_cached_read_latest() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let server = CoordinatorServiceState::new(cluster.state().clone());
+    let vs = view_verifier(&server).await;
+    let handle = Handle::random().to_bytes();
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    let b1 = b"data_block_example_1".to_vec();
+    let b2 = b"data_block_example_2".to_vec();
+    let b3 = b"data_block_example_3".to_vec();
+    for (height, block) in [&b1, &b2, &b3].iter().enumerate() {
+      let res = server
+        .state
+        .append_ledger(None, &handle, block, height + 1)
+        .await;
+      assert!(res.is_ok());
+    }
+    let nonce = rand::thread_rng().gen::<[u8; 16]>();
+
+    // a cached read answers from the store and is marked unattested: its receipts verify like
+    // those of a read by index, but not like those of an attested read
+    let ReadLatestResp {
+      block,
+      nonces,
+      receipts,
+      height,
+      nonce: echoed_nonce,
+      consistency,
+    } = server
+      .read_latest(tonic::Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: nonce.to_vec(),
+        consistency: ReadConsistency::Cached as i32,
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(block, b3);
+    assert_eq!(height, 3);
+    assert!(echoed_nonce.is_empty());
+    assert_eq!(consistency, ReadConsistency::Cached as i32);
+    assert!(vs
+      .verify_read_latest(&handle, &block, &nonces, nonce.as_ref(), &receipts)
+      .is_err());
+    assert!(vs
+      .verify_read_by_index(&handle, &block, &nonces, 3, &receipts)
+      .is_ok());
+    let res = server
+      .read_latest(tonic::Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: vec![],
+        consistency: 7,
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_new_ledger_conflict() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let server = CoordinatorServiceState::new(cluster.state().clone());
+    let vs = view_verifier(&server).await;
+    let handle = Handle::random().to_bytes();
+    let new_ledger = |block: &[u8]| {
+      tonic::Request::new(NewLedgerReq {
+        handle: handle.clone(),
+        block: block.to_vec(),
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: b"owner=alice".to_vec(),
+      })
+    };
+    let NewLedgerResp { receipts, .. } = server
+      .new_ledger(new_ledger(b"genesis"))
+      .await
+      .unwrap()
+      .into_inner();
+    let res = vs.verify_new_ledger_with_metadata(&handle, b"genesis", b"owner=alice", &receipts);
+    assert!(res.is_ok());
+
+    // another genesis block must not overwrite the ledger; the conflict describes the ledger that
+    // holds the handle
+    let status = server
+      .new_ledger(new_ledger(b"another genesis block"))
+      .await
+      .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let LedgerExists {
+      created_at,
+      metadata,
+    } = LedgerExists::decode(status.details()).unwrap();
+    assert!(created_at > 0);
+    assert_eq!(metadata, b"owner=alice".to_vec());
+    cluster.stop().await;
+  }
+
+  /// a verifier of the current view of the coordinator
+  async fn view_verifier(server: &CoordinatorServiceState) -> VerifierState {
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = server
+      .read_view_tail(tonic::Request::new(ReadViewTailReq { nonce: vec![] }))
+      .await
+      .unwrap()
+      .into_inner();
+    let mut vs = VerifierState::new();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+    vs
+  }
+
+  #[tokio::test]
+  async fn test_conditional_append() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let server = CoordinatorServiceState::new(cluster.state().clone());
+    let vs = view_verifier(&server).await;
+    let handle = Handle::random().to_bytes();
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+
+    // an append expecting a height the ledger has not reached reports the current tail
+    let req = tonic::Request::new(AppendReq {
+      handle: handle.clone(),
+      block: b"data_block_example_0".to_vec(),
+      expected_height: 2,
+      request_id: String::new(),
+    });
+    let status = server.append(req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    let AppendConditionFailed {
+      current_height,
+      current_tail,
+    } = AppendConditionFailed::decode(status.details()).unwrap();
+    assert_eq!(current_height, 0);
+    assert_eq!(
+      current_tail,
+      compute_aggregated_block_hash(
+        &compute_genesis_block(b"genesis", &[]).hash().to_bytes(),
+        &Nonces::new().hash().to_bytes()
+      )
+      .to_bytes()
+    );
+
+    // two writers that both read height 0 race to append; exactly one wins and the other learns
+    // the tail it lost to instead of overwriting it
+    let b1a = b"data_block_example_1a".to_vec();
+    let b1b = b"data_block_example_1b".to_vec();
+    let req_a = tonic::Request::new(AppendReq {
+      handle: handle.clone(),
+      block: b1a.clone(),
+      expected_height: 0,
+      request_id: String::new(),
+    });
+    let req_b = tonic::Request::new(AppendReq {
+      handle: handle.clone(),
+      block: b1b.clone(),
+      expected_height: 0,
+      request_id: String::new(),
+    });
+    let (winner, resp, status) = match tokio::join!(server.append(req_a), server.append(req_b)) {
+      (Ok(resp), Err(status)) => (b1a, resp.into_inner(), status),
+      (Err(status), Ok(resp)) => (b1b, resp.into_inner(), status),
+      res => panic!("exactly one of the competing appends must win: {:?}", res),
+    };
+    assert_eq!(resp.height, 1);
+    let res = vs.verify_append(&handle, &winner, &resp.hash_nonces, 1, &resp.receipts);
+    assert!(res.is_ok());
+
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    let AppendConditionFailed {
+      current_height,
+      current_tail,
+    } = AppendConditionFailed::decode(status.details()).unwrap();
+    assert_eq!(current_height, 1);
+    assert_eq!(
+      current_tail,
+      compute_aggregated_block_hash(&Block::new(&winner).hash().to_bytes(), &resp.hash_nonces)
+        .to_bytes()
+    );
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_max_block_size() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, Some(16))
+      .await
+      .unwrap();
+    let server = CoordinatorServiceState::new(Arc::new(coordinator));
+    let handle = Handle::random().to_bytes();
+
+    // a block over the configured size never reaches the store
+    let res = server
+      .new_ledger(tonic::Request::new(NewLedgerReq {
+        handle: handle.clone(),
+        block: vec![0u8; 17],
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: vec![],
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    let res = server
+      .state
+      .ledger_store
+      .read_ledger_tail(&NimbleDigest::digest(&handle))
+      .await;
+    assert!(res.is_err());
+
+    // blocks of the configured size pass the check; without endorsers, they get no receipts
+    let res = server
+      .state
+      .create_ledger(None, &handle, &[0u8; 16], &[], &[])
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+    let res = server
+      .state
+      .append_ledger(None, &handle, &[0u8; 17], 1)
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::BlockTooLarge);
+    let res = server
+      .state
+      .append_ledger(None, &handle, &[0u8; 16], 1)
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+  }
+
+  #[tokio::test]
+  async fn test_request_deadline() {
+    assert_eq!(parse_grpc_timeout("5S"), Some(Duration::from_secs(5)));
+    assert_eq!(parse_grpc_timeout("100m"), Some(Duration::from_millis(100)));
+    assert_eq!(parse_grpc_timeout("1H"), Some(Duration::from_secs(3600)));
+    assert_eq!(
+      parse_grpc_timeout("99999999n"),
+      Some(Duration::from_nanos(99999999))
+    );
+    for value in &["", "S", "5", "5x", "+5S", "-5S", "123456789S"] {
+      assert_eq!(parse_grpc_timeout(value), None);
+    }
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let server = CoordinatorServiceState::new(Arc::new(coordinator));
+    fn with_timeout<T>(message: T, timeout: &str) -> tonic::Request<T> {
+      let mut request = tonic::Request::new(message);
+      request
+        .metadata_mut()
+        .insert("grpc-timeout", timeout.parse().unwrap());
+      request
+    }
+    let stage = |status: Status| {
+      assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+      WriteDeadlineExceeded::decode(status.details())
+        .unwrap()
+        .stage
+    };
+
+    // a deadline shorter than the margin to answer expires before anything is written
+    let handle = Handle::random().to_bytes();
+    let req = NewLedgerReq {
+      handle: handle.clone(),
+      block: b"genesis".to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: vec![],
+    };
+    let res = server.new_ledger(with_timeout(req, "1m")).await;
+    assert_eq!(stage(res.unwrap_err()), Stage::NotStarted as i32);
+    let res = server
+      .state
+      .ledger_store
+      .read_ledger_tail(&NimbleDigest::digest(&handle))
+      .await;
+    assert!(res.is_err());
+
+    // an append that waits for the lock of its ledger gives up the wait at its deadline
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+    let ledger = server
+      .state
+      .ledger_locks
+      .lock(&NimbleDigest::digest(&handle))
+      .await
+      .unwrap();
+    let req = AppendReq {
+      handle: handle.clone(),
+      block: b"block".to_vec(),
+      expected_height: 0,
+      request_id: String::new(),
+    };
+    let res = server.append(with_timeout(req.clone(), "100m")).await;
+    assert_eq!(stage(res.unwrap_err()), Stage::NotStarted as i32);
+    let res = server
+      .append_batch(with_timeout(AppendBatchReq { items: vec![req] }, "100m"))
+      .await;
+    assert_eq!(stage(res.unwrap_err()), Stage::NotStarted as i32);
+    drop(ledger);
+    let (_entry, height) = server
+      .state
+      .ledger_store
+      .read_ledger_tail(&NimbleDigest::digest(&handle))
+      .await
+      .unwrap();
+    assert_eq!(height, 0);
+  }
+
+  #[tokio::test]
+  async fn test_get_ledger_info() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let server = CoordinatorServiceState::new(Arc::new(coordinator));
+    let handle = Handle::random().to_bytes();
+    let info_req = |handle: &[u8]| {
+      tonic::Request::new(GetLedgerInfoReq {
+        handle: handle.to_vec(),
+        attested: false,
+        nonce: vec![],
+      })
+    };
+
+    let res = server.get_ledger_info(info_req(&handle)).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+
+    // oversized metadata is rejected before the ledger reaches the store
+    let res = server
+      .state
+      .create_ledger(
+        None,
+        &handle,
+        b"genesis",
+        &[],
+        &[0u8; MAX_LEDGER_METADATA_SIZE + 1],
+      )
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::MetadataTooLarge);
+    let res = server.get_ledger_info(info_req(&handle)).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+
+    // without endorsers the create gets no receipts, but the store already holds the ledger,
+    // which is all the unattested query looks at
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], b"owner=alice")
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+    let GetLedgerInfoResp {
+      height,
+      tail_hash,
+      created_at,
+      metadata,
+      receipts,
+      nonces,
+    } = server
+      .get_ledger_info(info_req(&handle))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(height, 0);
+    let genesis_hash = compute_aggregated_block_hash(
+      &compute_genesis_block(b"genesis", b"owner=alice")
+        .hash()
+        .to_bytes(),
+      &Nonces::new().hash().to_bytes(),
+    );
+    assert_eq!(tail_hash, genesis_hash.to_bytes());
+    assert!(created_at > 0);
+    assert_eq!(metadata, b"owner=alice".to_vec());
+    assert!(receipts.is_empty());
+    assert!(nonces.is_empty());
+
+    // the metadata is immutable; retrying with the same metadata is a retry of the create
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], b"owner=bob")
+      .await;
+    assert_eq!(
+      res.unwrap_err(),
+      CoordinatorError::LedgerConflict {
+        created_at,
+        metadata: b"owner=alice".to_vec(),
+      }
+    );
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], b"owner=alice")
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+
+    let res = server
+      .get_ledger_info(tonic::Request::new(GetLedgerInfoReq {
+        handle: vec![],
+        attested: false,
+        nonce: vec![],
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+  }
+
+  #[tokio::test]
+  async fn test_attested_ledger_info() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let server = CoordinatorServiceState::new(cluster.state().clone());
+    let vs = view_verifier(&server).await;
+    let handle = Handle::random().to_bytes();
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], b"app=test")
+      .await;
+    assert!(res.is_ok());
+
+    // The unattested info comes from the store alone; the attested one is a signed read of the tail
+    let info = server
+      .get_ledger_info(tonic::Request::new(GetLedgerInfoReq {
+        handle: handle.clone(),
+        attested: false,
+        nonce: vec![],
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(info.height, 0);
+    assert_eq!(info.metadata, b"app=test".to_vec());
+    assert!(info.created_at > 0);
+    assert!(info.receipts.is_empty());
+    let info_nonce = rand::thread_rng().gen::<[u8; 16]>();
+    let attested_info = server
+      .get_ledger_info(tonic::Request::new(GetLedgerInfoReq {
+        handle: handle.clone(),
+        attested: true,
+        nonce: info_nonce.to_vec(),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(attested_info.height, 0);
+    assert_eq!(attested_info.tail_hash, info.tail_hash);
+    assert_eq!(attested_info.metadata, info.metadata);
+    let tail_hash = NimbleDigest::from_bytes(&attested_info.tail_hash).unwrap();
+    let res = Receipts::from_bytes(&attested_info.receipts)
+      .unwrap()
+      .verify_block_hash(&vs, &handle, &tail_hash, Some(0), Some(&info_nonce));
+    assert!(res.is_ok());
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_nonce_replay() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let replay = ReplayDetector::new(NonceCache::new(Duration::from_secs(60), 1000))
+      .with_strict_tenants(&["hdfs".to_string()]);
+    let server =
+      CoordinatorServiceState::new(Arc::new(coordinator)).with_replay_detector(Arc::new(replay));
+    let hdfs = Some(Tenant("hdfs".to_string()));
+
+    // a nonce is remembered once its read succeeded, so that a failed read can be retried
+    let key = server
+      .check_nonce("ReadLatest", "ApiKey:app", &None, b"handle", b"nonce-1")
+      .unwrap();
+    assert!(key.is_some());
+    let retry = server
+      .check_nonce("ReadLatest", "ApiKey:app", &None, b"handle", b"nonce-1")
+      .unwrap();
+    assert_eq!(retry, key);
+    server.remember_nonce(key);
+    // by default a reuse is only logged and counted
+    let res = server.check_nonce("ReadLatest", "ApiKey:app", &None, b"handle", b"nonce-1");
+    assert_eq!(res.unwrap(), None);
+    // the same nonce is fresh for another client or another ledger
+    for (client, handle) in [
+      ("ApiKey:other", &b"handle"[..]),
+      ("ApiKey:app", &b"other"[..]),
+    ] {
+      let res = server.check_nonce("ReadLatest", client, &None, handle, b"nonce-1");
+      assert!(res.unwrap().is_some());
+    }
+
+    // the reads of a strict tenant are rejected
+    let key = server
+      .check_nonce(
+        "GetLedgerInfo",
+        "Token:hdfs",
+        &hdfs,
+        b"hdfs/handle",
+        b"nonce-2",
+      )
+      .unwrap();
+    server.remember_nonce(key);
+    let res = server.check_nonce(
+      "GetLedgerInfo",
+      "Token:hdfs",
+      &hdfs,
+      b"hdfs/handle",
+      b"nonce-2",
+    );
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+    // a read that fails, here without endorsers, does not remember its nonce
+    let nonce = vec![3; Nonce::num_bytes()];
+    let handle = Handle::random().to_bytes();
+    for _ in 0..2 {
+      let mut req = tonic::Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: nonce.clone(),
+        consistency: ReadConsistency::Attested as i32,
+      });
+      req.extensions_mut().insert(Tenant("hdfs".to_string()));
+      let res = server.read_latest(req).await;
+      assert_ne!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+    let scoped = Tenant("hdfs".to_string()).scope(handle);
+    let res = server.check_nonce("ReadLatest", "hdfs", &hdfs, &scoped, &nonce);
+    assert!(res.unwrap().is_some());
+
+    let text = server.get_state().metrics().render(&[]);
+    assert!(text.contains("nimble_nonce_reuses_total{action=\"logged\",method=\"ReadLatest\"} 1\n"));
+    assert!(
+      text.contains("nimble_nonce_reuses_total{action=\"rejected\",method=\"GetLedgerInfo\"} 1\n")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_list_ledgers() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let server = CoordinatorServiceState::new(Arc::new(coordinator));
+
+    // without endorsers the creates get no receipts, but the store holds the ledgers
+    let mut created = Vec::new();
+    for i in 0..8u8 {
+      let app_bytes = if i < 5 {
+        format!("app-a/{}", i).into_bytes()
+      } else {
+        vec![]
+      };
+      let handle = vec![i; 16];
+      let res = server
+        .state
+        .create_ledger(None, &handle, b"genesis", &app_bytes, &[i])
+        .await;
+      assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+      created.push(handle);
+    }
+
+    let list_all = |app_prefix: &[u8], created_midway: Option<Vec<u8>>| {
+      let server = &server;
+      let app_prefix = app_prefix.to_vec();
+      async move {
+        let mut listed = Vec::new();
+        let mut page_token = vec![];
+        loop {
+          let ListLedgersResp {
+            ledgers,
+            next_page_token,
+            num_ledgers_estimate,
+          } = server
+            .list_ledgers(tonic::Request::new(ListLedgersReq {
+              page_token,
+              page_size: 3,
+              app_prefix: app_prefix.clone(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+          assert!(ledgers.len() <= 3);
+          assert!(num_ledgers_estimate >= 8);
+          for ledger in ledgers {
+            assert_eq!(ledger.height, 0);
+            assert_eq!(ledger.metadata, vec![ledger.handle[0]]);
+            listed.push(ledger.handle);
+          }
+          if next_page_token.is_empty() {
+            break;
+          }
+          page_token = next_page_token;
+
+          // a ledger created mid-listing must not disturb the rest of it
+          if let Some(handle) = created_midway.as_ref() {
+            if listed.len() == 3 {
+              let res = server
+                .state
+                .create_ledger(None, handle, b"genesis", &[], &[handle[0]])
+                .await;
+              assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+            }
+          }
+        }
+        listed
+      }
+    };
+
+    let mut listed = list_all(&[], None).await;
+    listed.sort();
+    assert_eq!(listed, created);
+
+    let mut listed = list_all(b"app-a", None).await;
+    listed.sort();
+    assert_eq!(listed, created[..5].to_vec());
+    assert!(list_all(b"app-b", None).await.is_empty());
+
+    let listed = list_all(&[], Some(vec![42u8; 16])).await;
+    let mut deduped = listed.clone();
+    deduped.sort();
+    deduped.dedup();
+    assert_eq!(deduped.len(), listed.len());
+    for handle in &created {
+      assert!(listed.contains(handle));
+    }
+
+    let res = server
+      .list_ledgers(tonic::Request::new(ListLedgersReq {
+        page_token: vec![1, 2, 3],
+        page_size: 0,
+        app_prefix: vec![],
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+  }
+
+  #[tokio::test]
+  async fn test_tenants() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    coordinator
+      .register_tenants(&["hdfs-a".to_string(), "hdfs-b".to_string()])
+      .await;
+    let coordinator_ref = Arc::new(coordinator);
+    let server = CoordinatorServiceState::new(coordinator_ref.clone());
+    let admin = AdminServiceState::new(coordinator_ref);
+
+    fn as_tenant<T>(tenant: &str, message: T) -> tonic::Request<T> {
+      let mut request = tonic::Request::new(message);
+      request.extensions_mut().insert(Tenant(tenant.to_string()));
+      request
+    }
+    let new_ledger = |tenant: &str, handle: &[u8]| {
+      as_tenant(
+        tenant,
+        NewLedgerReq {
+          handle: handle.to_vec(),
+          block: b"genesis".to_vec(),
+          app_bytes: vec![],
+          nonce: vec![],
+          metadata: vec![],
+        },
+      )
+    };
+    let set_quota = |max_ledgers: u64, max_stored_bytes: u64| {
+      tonic::Request::new(SetTenantQuotaReq {
+        tenant: "hdfs-a".to_string(),
+        max_ledgers,
+        max_appends_per_sec: 0,
+        max_stored_bytes,
+      })
+    };
+
+    admin.set_tenant_quota(set_quota(2, 0)).await.unwrap();
+    let res = admin
+      .set_tenant_quota(tonic::Request::new(SetTenantQuotaReq {
+        tenant: "hdfs-c".to_string(),
+        max_ledgers: 1,
+        max_appends_per_sec: 0,
+        max_stored_bytes: 0,
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+
+    // without endorsers the creates get no receipts, but the store holds the ledgers, and the
+    // tenant is charged for them, but not again for a retry
+    for handle in &[b"x", b"y", b"x"] {
+      let res = server.new_ledger(new_ledger("hdfs-a", *handle)).await;
+      assert_eq!(res.unwrap_err().code(), tonic::Code::Unavailable);
+    }
+    let res = server.new_ledger(new_ledger("hdfs-a", b"z")).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::ResourceExhausted);
+    let TenantResp {
+      num_ledgers,
+      stored_bytes,
+      ..
+    } = admin
+      .get_tenant(tonic::Request::new(GetTenantReq {
+        tenant: "hdfs-a".to_string(),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(num_ledgers, 2);
+    assert_eq!(stored_bytes, 2 * b"genesis".len() as u64);
+
+    // the stored bytes are limited on their own, and a limit of 0 lifts the limit on ledgers
+    admin.set_tenant_quota(set_quota(0, 20)).await.unwrap();
+    let res = server.new_ledger(new_ledger("hdfs-a", b"z")).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::ResourceExhausted);
+
+    // the tenants have separate namespaces with the same handles
+    let res = server.new_ledger(new_ledger("hdfs-b", b"x")).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::Unavailable);
+    let res = server
+      .get_ledger_info(as_tenant(
+        "hdfs-b",
+        GetLedgerInfoReq {
+          handle: b"y".to_vec(),
+          attested: false,
+          nonce: vec![],
+        },
+      ))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+    let res = server
+      .get_ledger_info(as_tenant(
+        "hdfs-a",
+        GetLedgerInfoReq {
+          handle: b"hdfs-a/y".to_vec(),
+          attested: false,
+          nonce: vec![],
+        },
+      ))
+      .await;
+    assert_eq!(res.unwrap().into_inner().height, 0);
+
+    let list = |tenant: &str| {
+      server.list_ledgers(as_tenant(
+        tenant,
+        ListLedgersReq {
+          page_token: vec![],
+          page_size: 0,
+          app_prefix: vec![],
+        },
+      ))
+    };
+    let ListLedgersResp {
+      ledgers,
+      num_ledgers_estimate,
+      ..
+    } = list("hdfs-a").await.unwrap().into_inner();
+    let mut handles = ledgers
+      .into_iter()
+      .map(|ledger| ledger.handle)
+      .collect::<Vec<_>>();
+    handles.sort();
+    assert_eq!(handles, vec![b"hdfs-a/x".to_vec(), b"hdfs-a/y".to_vec()]);
+    assert_eq!(num_ledgers_estimate, 2);
+    let ListLedgersResp { ledgers, .. } = list("hdfs-b").await.unwrap().into_inner();
+    assert_eq!(ledgers.len(), 1);
+    assert_eq!(ledgers[0].handle, b"hdfs-b/x".to_vec());
+  }
+
+  #[tokio::test]
+  async fn test_delegation_tokens() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    coordinator.register_tenants(&["hdfs-a".to_string()]).await;
+    let coordinator_ref = Arc::new(coordinator);
+    let issue = |tenant: &str| {
+      tonic::Request::new(IssueDelegationTokenReq {
+        tenant: tenant.to_string(),
+        owner: "nn/host@REALM".to_string(),
+        renewer: "nn/host@REALM".to_string(),
+      })
+    };
+
+    // the admin service issues tokens only if it has a secret, and only for known tenants
+    let admin = AdminServiceState::new(coordinator_ref.clone());
+    let res = admin.issue_delegation_token(issue("hdfs-a")).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::FailedPrecondition);
+    let tokens = Arc::new(DelegationTokens::new(vec![1u8; MIN_SECRET_SIZE]));
+    let admin = AdminServiceState::new(coordinator_ref).with_delegation_tokens(tokens.clone());
+    let res = admin.issue_delegation_token(issue("hdfs-b")).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+    let issued = admin
+      .issue_delegation_token(issue("hdfs-a"))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(issued.tenant, "hdfs-a");
+    assert!(issued.expiry > issued.issue_date && issued.expiry <= issued.max_date);
+
+    // the client service authenticates the holder of a token as its tenant, next to the tokens of
+    // the tenant file
+    let tenants = HashMap::from([("token-b".to_string(), "hdfs-b".to_string())]);
+    let auth = Authenticator::from(tenants).with_auth(tokens);
+    let check = |authorization: String| auth.authenticate(Some(&authorization));
+    let tenant = check(format!("Delegation {}", issued.token)).unwrap();
+    assert_eq!(tenant, Tenant("hdfs-a".to_string()));
+    let tenant = check("Bearer token-b".to_string()).unwrap();
+    assert_eq!(tenant, Tenant("hdfs-b".to_string()));
+    let res = check(format!("Bearer {}", issued.token));
+    assert_eq!(res.unwrap_err().code(), tonic::Code::Unauthenticated);
+    let forged = format!("Delegation {}A", issued.token);
+    assert_eq!(
+      check(forged).unwrap_err().code(),
+      tonic::Code::Unauthenticated
+    );
+
+    // the renewer renews the token before it expires, and no one else does
+    let renew = |token: &str, renewer: &str| {
+      tonic::Request::new(RenewDelegationTokenReq {
+        token: token.to_string(),
+        renewer: renewer.to_string(),
+      })
+    };
+    let res = admin
+      .renew_delegation_token(renew(&issued.token, "other"))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::PermissionDenied);
+    let res = admin
+      .renew_delegation_token(renew("not a token", "nn/host@REALM"))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    let renewed = admin
+      .renew_delegation_token(renew(&issued.token, "nn/host@REALM"))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(renewed.sequence_number, issued.sequence_number);
+    assert!(renewed.expiry >= issued.expiry);
+    assert!(check(format!("Delegation {}", renewed.token)).is_ok());
+  }
+
+  #[tokio::test]
+  async fn test_append_batch() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let server = CoordinatorServiceState::new(Arc::new(coordinator));
+
+    // without endorsers the creates get no receipts, but the store holds the ledgers
+    let (handle_a, handle_b, handle_c) = (vec![1u8; 16], vec![2u8; 16], vec![3u8; 16]);
+    for handle in &[&handle_a, &handle_b, &handle_c] {
+      let res = server
+        .state
+        .create_ledger(None, handle, b"genesis", &[], &[])
+        .await;
+      assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+    }
+
+    let item = |handle: &[u8], expected_height: u64| AppendReq {
+      handle: handle.to_vec(),
+      block: b"block".to_vec(),
+      expected_height,
+      request_id: String::new(),
+    };
+    let AppendBatchResp { results } = server
+      .append_batch(tonic::Request::new(AppendBatchReq {
+        items: vec![
+          item(&handle_a, 0),
+          item(&handle_b, 5),
+          item(&[], 0),
+          item(&handle_c, 0),
+          item(&handle_c, 0),
+          item(&[4u8; 16], 0),
+        ],
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    let codes = results
+      .iter()
+      .map(|result| tonic::Code::from_i32(result.code))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      codes,
+      vec![
+        tonic::Code::Unavailable,
+        tonic::Code::FailedPrecondition,
+        tonic::Code::InvalidArgument,
+        tonic::Code::InvalidArgument,
+        tonic::Code::InvalidArgument,
+        tonic::Code::InvalidArgument,
+      ]
+    );
+    assert!(results.iter().all(|result| result.resp.is_none()));
+    let details = AppendConditionFailed::decode(results[1].details.as_slice()).unwrap();
+    assert_eq!(details.current_height, 0);
+
+    // an append only extends an endorsed tail, so a is left at its unendorsed genesis, which
+    // still cannot be reconciled without endorsers
+    let height = |handle: &[u8]| {
+      let handle = NimbleDigest::digest(handle);
+      let server = &server;
+      async move {
+        let (_entry, height) = server
+          .state
+          .ledger_store
+          .read_ledger_tail(&handle)
+          .await
+          .unwrap();
+        height
+      }
+    };
+    assert_eq!(height(&handle_a).await, 0);
+    assert_eq!(height(&handle_b).await, 0);
+    assert_eq!(height(&handle_c).await, 0);
+
+    let res = server
+      .append_batch(tonic::Request::new(AppendBatchReq {
+        items: (0..=MAX_APPEND_BATCH_SIZE)
+          .map(|i| item(&(i as u64).to_le_bytes(), 0))
+          .collect(),
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+  }
+
+  #[tokio::test]
+  async fn test_seal_and_purge() {
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let coordinator_ref = Arc::new(coordinator);
+    let server = CoordinatorServiceState::new(coordinator_ref.clone());
+    let admin = AdminServiceState::new(coordinator_ref);
+
+    // without endorsers the create gets no receipts, but the store holds the ledger, which is
+    // extended directly
+    let handle = vec![5u8; 16];
+    let res = server
+      .state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+    let digest = NimbleDigest::digest(&handle);
+    for (height, block) in [b"block1", b"block2"].iter().enumerate() {
+      server
+        .state
+        .ledger_store
+        .append_ledger(&digest, &Block::new(*block), height + 1)
+        .await
+        .unwrap();
+    }
+
+    let res = server.state.purge_ledger_blocks(&handle, 3).await;
+    assert_eq!(
+      res.unwrap_err(),
+      CoordinatorError::OutOfRange { current_height: 2 }
+    );
+    let OperationResp { operation_id } = admin
+      .purge_blocks(tonic::Request::new(PurgeBlocksReq {
+        handle: handle.clone(),
+        before_height: 2,
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    loop {
+      let GetOperationStatusResp { state, error } = admin
+        .get_operation_status(tonic::Request::new(GetOperationStatusReq {
+          operation_id: operation_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+      if state == OperationState::Running as i32 {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        continue;
+      }
+      assert_eq!(state, OperationState::Succeeded as i32, "{}", error);
+      break;
+    }
+
+    // the purged entries keep their hashes, which the error reports in place of the block
+    let status = server
+      .read_by_index(tonic::Request::new(ReadByIndexReq {
+        handle: handle.clone(),
+        index: 1,
+      }))
+      .await
+      .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+    let details = ContentPurged::decode(status.details()).unwrap();
+    assert_eq!(details.index, 1);
+    assert_eq!(details.block_hash, Block::new(b"block1").hash().to_bytes());
+    assert_eq!(details.hash_nonces, Nonces::new().hash().to_bytes());
+
+    let status = server
+      .read_range(tonic::Request::new(ReadRangeReq {
+        handle: handle.clone(),
+        from: 0,
+        to: 2,
+        page_size: 0,
+        page_token: vec![],
+      }))
+      .await
+      .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+    let details = ContentPurged::decode(status.details()).unwrap();
+    assert_eq!(details.index, 0);
+    assert_eq!(
+      details.block_hash,
+      compute_genesis_block(b"genesis", &[]).hash().to_bytes()
+    );
+
+    // a sealed ledger refuses appends at any height
+    server
+      .state
+      .ledger_store
+      .append_ledger(&digest, &compute_seal_block(1), 3)
+      .await
+      .unwrap();
+    for expected_height in &[2, 3] {
+      let status = server
+        .append(tonic::Request::new(AppendReq {
+          handle: handle.clone(),
+          block: b"block4".to_vec(),
+          expected_height: *expected_height,
+          request_id: String::new(),
+        }))
+        .await
+        .unwrap_err();
+      assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+      assert!(status.details().is_empty());
+    }
+
+    // sealing again returns the receipts of the seal, which cannot be obtained without endorsers
+    let res = server
+      .seal_ledger(tonic::Request::new(SealLedgerReq {
+        handle: handle.clone(),
+      }))
+      .await;
+    assert!(res.is_err());
+    let (_entry, height) = server
+      .state
+      .ledger_store
+      .read_ledger_tail(&digest)
+      .await
+      .unwrap();
+    assert_eq!(height, 3);
+  }
+
+  #[tokio::test]
+  async fn test_lease() {
+    let lease_duration = Duration::from_millis(300);
+    let primary = Arc::new(
+      CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap()
+        .with_lease(Lease::new("primary", lease_duration)),
+    );
+    let mut standby = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap()
+      .with_lease(Lease::new("standby", lease_duration));
+    standby.ledger_store = primary.ledger_store.clone();
+    let standby = Arc::new(standby);
+
+    // neither coordinator writes before it holds the lease
+    let handle = vec![6u8; 16];
+    let res = primary
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::LeaseNotHeld);
+
+    primary.take_lease().await;
+    let primary_lease = {
+      let primary = primary.clone();
+      tokio::spawn(async move { primary.keep_lease().await })
+    };
+    let res = tokio::time::timeout(lease_duration * 3, standby.take_lease()).await;
+    assert!(res.is_err());
+    let res = standby
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::LeaseNotHeld);
+
+    // without endorsers the create gets no receipts, but the primary gets to the store
+    let res = primary
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+
+    // once the primary stops renewing, its lease lapses, so it stops writing before the standby
+    // takes over
+    primary_lease.abort();
+    let res = tokio::time::timeout(Duration::from_secs(10), standby.take_lease()).await;
+    assert!(res.is_ok());
+    let res = primary.append_ledger(None, &handle, b"block1", 1).await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::LeaseNotHeld);
+    let res = standby.append_ledger(None, &handle, b"block1", 1).await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::FailedToObtainQuorum);
+
+    // and the primary waits in turn while the standby renews the lease
+    let standby_lease = {
+      let standby = standby.clone();
+      tokio::spawn(async move { standby.keep_lease().await })
+    };
+    let res = tokio::time::timeout(lease_duration * 3, primary.take_lease()).await;
+    assert!(res.is_err());
+    let lease = standby.ledger_store.read_lease().await.unwrap();
+    assert_eq!(lease.holder, "standby");
+    assert_eq!(lease.epoch, 2);
+    standby_lease.abort();
+  }
+
+  /// whether the trace has `line`, past its time stamp
+  fn traced(trace: &[String], line: &str) -> bool {
+    trace.iter().any(|traced| traced.ends_with(line))
+  }
+
+  #[test]
+  fn test_simulate_partial_append_crash() {
+    let slow = Duration::from_secs(1);
+    let script = [
+      Step::Create {
+        coordinator: 0,
+        ledger: 0,
+      },
+      Step::Append {
+        coordinator: 0,
+        ledger: 0,
+      },
+      // block 2 reaches the store and endorser 0, and the coordinator dies before it reaches the
+      // other endorsers
+      Step::Delay {
+        endorser: 1,
+        latency: slow,
+      },
+      Step::Delay {
+        endorser: 2,
+        latency: slow,
+      },
+      Step::StartAppend {
+        coordinator: 0,
+        ledger: 0,
+      },
+      Step::Run(Duration::from_millis(100)),
+      Step::Crash(0),
+      Step::Run(slow * 2),
+      Step::Check,
+      // the next coordinator takes over once the lease lapses, and its recovery completes block 2
+      Step::Delay {
+        endorser: 1,
+        latency: Duration::ZERO,
+      },
+      Step::Delay {
+        endorser: 2,
+        latency: Duration::ZERO,
+      },
+      Step::Start,
+      Step::Run(slow),
+      Step::Check,
+      Step::Append {
+        coordinator: 1,
+        ledger: 0,
+      },
+      Step::Run(slow),
+      Step::Check,
+    ];
+    for seed in 0..4 {
+      let trace = simulate(seed, &script);
+      for line in [
+        "ledger 0 is at 2 in the store, at 2 1 1 in the endorsers",
+        "coordinator 1 took the lease and recovered: ok",
+        "ledger 0 is at 2 in the store, at 2 2 2 in the endorsers",
+        "coordinator 1 appended block 3 to ledger 0: ok",
+        "ledger 0 is at 3 in the store, at 3 3 3 in the endorsers",
+      ] {
+        assert!(traced(&trace, line), "{} is not in {:#?}", line, trace);
+      }
+      // the append died with its coordinator, so its client never heard back
+      assert!(!trace.iter().any(|line| line.contains("appended block 2")));
+      assert_eq!(trace, simulate(seed, &script));
+    }
+  }
+
+  #[test]
+  fn test_simulate_lease_failover() {
+    let slow = Duration::from_secs(8);
+    let script = [
+      Step::Create {
+        coordinator: 0,
+        ledger: 0,
+      },
+      Step::Append {
+        coordinator: 0,
+        ledger: 0,
+      },
+      // coordinator 0 hangs: it stops renewing its lease, and block 2 is slow to reach the
+      // endorsers
+      Step::StopRenewing(0),
+      Step::Delay {
+        endorser: 0,
+        latency: slow,
+      },
+      Step::Delay {
+        endorser: 1,
+        latency: slow,
+      },
+      Step::Delay {
+        endorser: 2,
+        latency: slow,
+      },
+      Step::StartAppend {
+        coordinator: 0,
+        ledger: 0,
+      },
+      Step::Run(Duration::from_millis(100)),
+      Step::Delay {
+        endorser: 0,
+        latency: Duration::ZERO,
+      },
+      Step::Delay {
+        endorser: 1,
+        latency: Duration::ZERO,
+      },
+      Step::Delay {
+        endorser: 2,
+        latency: Duration::ZERO,
+      },
+      // coordinator 1 takes over before block 2 reaches the endorsers, and its recovery endorses
+      // block 2
+      Step::Start,
+      Step::Run(Duration::from_secs(1)),
+      Step::Check,
+      // coordinator 0 stopped writing when its lease lapsed
+      Step::Append {
+        coordinator: 0,
+        ledger: 0,
+      },
+      Step::Append {
+        coordinator: 1,
+        ledger: 0,
+      },
+      // the late appends of block 2 do not move the endorsers off the tail of the store
+      Step::Run(slow),
+      Step::Append {
+        coordinator: 1,
+        ledger: 0,
+      },
+      Step::Run(Duration::from_secs(1)),
+      Step::Check,
+    ];
+    for seed in 0..4 {
+      let trace = simulate(seed, &script);
+      for line in [
+        "coordinator 1 took the lease and recovered: ok",
+        "ledger 0 is at 2 in the store, at 2 2 2 in the endorsers",
+        "coordinator 0 appended block 3 to ledger 0: LeaseNotHeld",
+        "coordinator 1 appended block 3 to ledger 0: ok",
+        "coordinator 1 appended block 4 to ledger 0: ok",
+        "ledger 0 is at 4 in the store, at 4 4 4 in the endorsers",
+      ] {
+        assert!(traced(&trace, line), "{} is not in {:#?}", line, trace);
+      }
+      assert_eq!(trace, simulate(seed, &script));
+    }
+  }
+
+  #[test]
+  fn test_simulate_replays_from_seed() {
+    let append = Step::Append {
+      coordinator: 0,
+      ledger: 0,
+    };
+    let script = [
+      Step::Create {
+        coordinator: 0,
+        ledger: 0,
+      },
+      Step::Partition(2),
+      Step::Loss { percent: 20 },
+      append.clone(),
+      append.clone(),
+      Step::Drop {
+        endorser: None,
+        call: "append",
+        times: 2,
+      },
+      append.clone(),
+      append.clone(),
+      Step::Heal(2),
+      Step::Loss { percent: 0 },
+      append,
+      Step::Run(Duration::from_secs(1)),
+      Step::Check,
+    ];
+    for seed in 0..8 {
+      assert_eq!(simulate(seed, &script), simulate(seed, &script));
+    }
+  }
+
+  #[tokio::test]
+  async fn test_shutdown() {
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let handle = vec![7u8; 16];
+    let append = |state: &Arc<CoordinatorState>, height: usize| {
+      let state = state.clone();
+      let handle = handle.clone();
+      tokio::spawn(async move { state.append_ledger(None, &handle, b"block", height).await })
+    };
+
+    // an append in flight finishes within the grace period, and the shutdown waits for it
+    let ledger = state
+      .ledger_locks
+      .lock(&NimbleDigest::digest(&handle))
+      .await
+      .unwrap();
+    let in_flight = append(&state, 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let shutdown = {
+      let state = state.clone();
+      tokio::spawn(async move { state.shutdown(Duration::from_secs(10)).await })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let res = state.append_ledger(None, &handle, b"block", 1).await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::ShuttingDown);
+    drop(ledger);
+    assert_ne!(
+      in_flight.await.unwrap().unwrap_err(),
+      CoordinatorError::ShuttingDown
+    );
+    assert!(shutdown.await.unwrap());
+
+    // appends still queued behind the lock of their ledger at the end of the grace period are
+    // rejected, and clients see an error they retry
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let _ledger = state
+      .ledger_locks
+      .lock(&NimbleDigest::digest(&handle))
+      .await
+      .unwrap();
+    let queued = (1..=3)
+      .map(|height| append(&state, height))
+      .collect::<Vec<_>>();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!state.shutdown(Duration::from_millis(200)).await);
+    for append in queued {
+      assert_eq!(
+        append.await.unwrap().unwrap_err(),
+        CoordinatorError::ShuttingDown
+      );
+    }
+    let server = CoordinatorServiceState::new(state);
+    let res = server
+      .append(Request::new(AppendReq {
+        handle,
+        block: b"block".to_vec(),
+        expected_height: 0,
+        request_id: String::new(),
+      }))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::Unavailable);
+  }
+
+  #[tokio::test]
+  async fn test_rate_limit() {
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let mut config = RateLimitConfig::default();
+    config.default.reads_per_sec = 1;
+    let rate_limiter = Arc::new(RateLimiter::new(config));
+    let admin = AdminServiceState::new(state.clone()).with_rate_limiter(rate_limiter.clone());
+    let _server = tokio::spawn(
+      tonic::transport::Server::builder()
+        .layer(RateLimitLayer::new(rate_limiter, None))
+        .add_service(CallServer::new(CoordinatorServiceState::new(state)))
+        .serve("[::1]:8150".parse().unwrap()),
+    );
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut client = CallClient::connect("http://[::1]:8150").await.unwrap();
+    let read = || {
+      Request::new(GetLedgerInfoReq {
+        handle: b"ledger".to_vec(),
+        ..Default::default()
+      })
+    };
+
+    // clients are keyed by their address; past its budget, a client is told when to retry
+    let res = client.get_ledger_info(read()).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+    let status = client.get_ledger_info(read()).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "1");
+    let RateLimitResp {
+      reads_allowed,
+      reads_rejected,
+      ..
+    } = admin
+      .get_rate_limit(Request::new(GetRateLimitReq {
+        key: "::1".to_string(),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!((reads_allowed, reads_rejected), (1, 1));
+
+    // the admin service lifts the limit of the client without a restart
+    let res = admin
+      .set_rate_limit(Request::new(SetRateLimitReq {
+        key: "::1".to_string(),
+        reads_per_sec: 1000,
+        ..Default::default()
+      }))
+      .await;
+    assert_eq!(res.unwrap().into_inner().reads_per_sec, 1000);
+    for _i in 0..10 {
+      let res = client.get_ledger_info(read()).await;
+      assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_tls() {
+    use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity};
+    // the certificates that `scripts/gen-test-certs.sh` generates
+    let testdata = |name: &str| format!("{}/testdata/tls/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let pem = |name: &str| std::fs::read(testdata(name)).unwrap();
+    let config = TlsConfig {
+      cert: Some(testdata("server.pem")),
+      key: Some(testdata("server.key")),
+      client_ca: Some(testdata("ca.pem")),
+      require_client_auth: true,
+    };
+
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let tls = tls::server_tls(&config).unwrap();
+    let _server = tokio::spawn(
+      tls::server_builder(&tls)
+        .unwrap()
+        .add_service(CallServer::new(CoordinatorServiceState::new(state)))
+        .serve("[::1]:8160".parse().unwrap()),
+    );
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // whether a client reaches the client service, which does not know the ledger
+    let reaches = |tls: Option<ClientTlsConfig>| async move {
+      let endpoint = match tls {
+        Some(tls) => Endpoint::from_static("https://[::1]:8160")
+          .tls_config(tls)
+          .unwrap(),
+        None => Endpoint::from_static("http://[::1]:8160"),
+      };
+      let channel = match endpoint.connect().await {
+        Ok(channel) => channel,
+        Err(_e) => return false,
+      };
+      let req = Request::new(GetLedgerInfoReq {
+        handle: b"ledger".to_vec(),
+        ..Default::default()
+      });
+      let res = CallClient::new(channel).get_ledger_info(req).await;
+      matches!(res, Err(status) if status.code() == tonic::Code::NotFound)
+    };
+    let client_tls = ClientTlsConfig::new()
+      .ca_certificate(Certificate::from_pem(pem("ca.pem")))
+      .domain_name("localhost");
+
+    // plaintext clients are rejected, and so are clients without a certificate when the
+    // coordinator requires one
+    assert!(!reaches(None).await);
+    assert!(!reaches(Some(client_tls.clone())).await);
+    let identity = Identity::from_pem(pem("client.pem"), pem("client.key"));
+    assert!(reaches(Some(client_tls.identity(identity))).await);
+  }
+
+  #[tokio::test]
+  async fn test_canary() {
+    let cluster = TestCluster::start(3, StoreKind::Memory).await;
+    let state = cluster.state();
+    let health = state.health();
+    health.require_canary();
+    health.set_recovered();
+    assert_eq!(state.check_health(), None);
+
+    // the canary verifies what it wrote and read, and seals its ledger, which listings leave out
+    let canary = Canary::new(state.clone()).with_seal(true);
+    let handle = canary.probe().await.unwrap();
+    assert!(handle.starts_with(CANARY_PREFIX));
+    let res = state.append_ledger(None, &handle, b"block", 3).await;
+    assert!(matches!(res, Err(CoordinatorError::LedgerSealed)));
+    let page = state.list_ledgers(None, 10, &[], &[]).await.unwrap();
+    assert!(page.ledgers.is_empty());
+    let page = state
+      .list_ledgers(None, 10, CANARY_PREFIX, &[])
+      .await
+      .unwrap();
+    assert_eq!(page.ledgers.len(), 1);
+
+    assert!(canary.run_once().await);
+    assert_eq!(state.check_health(), Some(true));
+
+    // without a quorum, the canary fails and names the phase that failed
+    cluster.partition(1);
+    cluster.partition(2);
+    assert_eq!(canary.probe().await.unwrap_err().phase, Phase::Create);
+    assert!(!canary.run_once().await);
+    let failure = health.last_canary().unwrap().failure.unwrap();
+    assert!(failure.starts_with("create: "), "{}", failure);
+    cluster.heal(1);
+    cluster.heal(2);
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_endorser_heartbeats() {
+    let cluster = TestCluster::start(3, StoreKind::Memory).await;
+    let state = cluster.state();
+    let heartbeats = state.endorser_heartbeats();
+    state.health().set_recovered();
+    let monitor = EndorserHeartbeatMonitor::new(state.clone(), Duration::from_millis(100));
+    monitor.require();
+    // the endorsers count once they answer their heartbeats
+    assert_eq!(state.check_health(), None);
+    cluster.endorser(0).set_clock_offset(5_000);
+    assert_eq!(monitor.beat_all().await, 3);
+    assert_eq!(state.check_health(), Some(true));
+    assert_eq!(cluster.endorser(1).num_calls("heartbeat"), 1);
+
+    // the clock of the first endorser is ahead of the others
+    let skew = heartbeats.clock_skew().unwrap();
+    assert!(
+      skew > Duration::from_millis(4_500) && skew < Duration::from_millis(5_500),
+      "{:?}",
+      skew
+    );
+    let offsets = heartbeats
+      .snapshot()
+      .into_iter()
+      .filter(|(_pk, heartbeat)| heartbeat.offset_ms > 4_500)
+      .map(|(pk, _heartbeat)| pk.to_vec())
+      .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![cluster.endorser(0).public_key()]);
+
+    // an endorser that stops answering stops counting once its last heartbeat is too old
+    cluster.partition(1);
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert_eq!(monitor.beat_all().await, 2);
+    assert!(heartbeats.is_alive(&cluster.endorser(0).public_key()));
+    assert!(!heartbeats.is_alive(&cluster.endorser(1).public_key()));
+    cluster.heal(1);
+    assert_eq!(monitor.beat_all().await, 3);
+    assert!(heartbeats.is_alive(&cluster.endorser(1).public_key()));
+
+    // an endorser without heartbeats, such as the Open Enclave one, counts while it is connected
+    cluster
+      .endorser(2)
+      .fail_next("heartbeat", tonic::Code::Unimplemented, 1);
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert_eq!(monitor.beat_all().await, 2);
+    assert!(heartbeats.is_alive(&cluster.endorser(2).public_key()));
+    assert_eq!(state.check_health(), Some(true));
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_change_quorum() {
+    let cluster = TestCluster::start(3, StoreKind::Memory).await;
+    let state = cluster.state();
+    let mut client = cluster.client();
+    assert_eq!(state.get_quorum(), 2);
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = client
+      .read_view_tail(ReadViewTailReq { nonce: vec![] })
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    let handle = Handle::random().to_bytes();
+    let res = state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    // the receipts of two of the three endorsers make an append of the first view
+    let (old_hash_nonces, old_receipts) = state
+      .append_ledger(None, &handle, b"block_1", 1)
+      .await
+      .unwrap();
+    let two_of = |receipts: &Receipts| {
+      let mut two = Receipts::new();
+      for (ex_meta_block, id_sigs) in receipts.get() {
+        for id_sig in &id_sigs[..2] {
+          two.add(&Receipt::new(
+            *ex_meta_block.get_view(),
+            ex_meta_block.get_metablock().clone(),
+            id_sig.clone(),
+          ));
+        }
+      }
+      two.to_bytes()
+    };
+    let old_hash_nonces = old_hash_nonces.to_bytes();
+    let verify_old = |vs: &VerifierState| {
+      vs.verify_append(
+        &handle,
+        b"block_1",
+        &old_hash_nonces,
+        1,
+        &two_of(&old_receipts),
+      )
+    };
+    assert!(verify_old(&vs).is_ok());
+
+    // the quorum changes with a view change, which the view ledger records
+    let res = state.change_quorum(4).await;
+    assert!(matches!(
+      res,
+      Err(CoordinatorError::InvalidQuorum {
+        quorum: 4,
+        num_endorsers: 3
+      })
+    ));
+    assert!(state.change_quorum(3).await.is_ok());
+    assert_eq!(state.get_quorum(), 3);
+    let quorum = |index: u64| {
+      let mut client = client.clone();
+      async move {
+        client
+          .read_view_by_index(ReadViewByIndexReq { index })
+          .await
+          .unwrap()
+          .into_inner()
+          .quorum
+      }
+    };
+    assert_eq!((quorum(1).await, quorum(2).await), (2, 3));
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = client
+      .read_view_tail(ReadViewTailReq { nonce: vec![] })
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    // the appends of the new view need all three endorsers, while those of the first view still
+    // verify with two
+    let (hash_nonces, receipts) = state
+      .append_ledger(None, &handle, b"block_2", 2)
+      .await
+      .unwrap();
+    let hash_nonces = hash_nonces.to_bytes();
+    assert!(vs
+      .verify_append(&handle, b"block_2", &hash_nonces, 2, &receipts.to_bytes())
+      .is_ok());
+    assert_eq!(
+      vs.verify_append(&handle, b"block_2", &hash_nonces, 2, &two_of(&receipts)),
+      Err(VerificationError::InvalidReceipt)
+    );
+    assert!(verify_old(&vs).is_ok());
+
+    // the verifier of clients follows the quorum too, and a view may not shrink below it
+    assert!(Canary::new(state.clone()).probe().await.is_ok());
+    assert!(matches!(
+      state
+        .remove_endorsers(&state.get_endorser_uris()[..1])
+        .await,
+      Err(CoordinatorError::InvalidQuorum { .. })
+    ));
+    cluster.partition(2);
+    let res = state.append_ledger(None, &handle, b"block_3", 3).await;
+    assert!(res.is_err());
+    cluster.heal(2);
+    cluster.stop().await;
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_read_latest_cached() {
+    let (state, _mocks, endorsers) = mock_coordinator(2).await;
+    let state = Arc::new(state);
+    let server = CoordinatorServiceState::new(state.clone());
+    let handle = Handle::random().to_bytes();
+    let res = state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    for height in 1..=3 {
+      let res = state.append_ledger(None, &handle, b"block", height).await;
+      assert!(res.is_ok());
+    }
+    let read = |consistency: ReadConsistency| {
+      Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: rand::thread_rng().gen::<[u8; 16]>().to_vec(),
+        consistency: consistency as i32,
+      })
+    };
+
+    // a cached read skips the round trips to the endorsers, which the paused clock of the test
+    // only lets pass once the endorsers answer
+    const NUM_READS: u32 = 20;
+    const LATENCY: Duration = Duration::from_millis(10);
+    for endorser in &endorsers {
+      endorser.set_latency(LATENCY);
+    }
+    let num_calls = || {
+      endorsers
+        .iter()
+        .map(|endorser| endorser.num_calls("read_latest"))
+        .sum::<usize>()
+    };
+    let mut elapsed = Vec::new();
+    let mut calls = Vec::new();
+    for consistency in [ReadConsistency::Attested, ReadConsistency::Cached] {
+      let calls_before = num_calls();
+      let start = tokio::time::Instant::now();
+      for _i in 0..NUM_READS {
+        let ReadLatestResp { height, .. } = server
+          .read_latest(read(consistency))
+          .await
+          .unwrap()
+          .into_inner();
+        assert_eq!(height, 3);
+      }
+      elapsed.push(start.elapsed());
+      calls.push(num_calls() - calls_before);
+    }
+    assert!(calls[0] >= NUM_READS as usize);
+    assert_eq!(calls[1], 0);
+    assert!(elapsed[0] >= LATENCY * NUM_READS);
+    assert!(elapsed[1] < LATENCY);
+
+    // an append that persisted its block but has not collected its receipts is not visible
+    state
+      .ledger_store
+      .append_ledger(&NimbleDigest::digest(&handle), &Block::new(b"block_4"), 4)
+      .await
+      .unwrap();
+    let ReadLatestResp { block, height, .. } = server
+      .read_latest(read(ReadConsistency::Cached))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!((block, height), (b"block".to_vec(), 3));
+  }
+
+  #[tokio::test]
+  async fn test_snapshot_read() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let state = cluster.state();
+    let mut client = cluster.client();
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = client
+      .read_view_tail(ReadViewTailReq { nonce: vec![] })
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    // ledger i is at height i + 1
+    let handles = (0..3)
+      .map(|_| Handle::random().to_bytes())
+      .collect::<Vec<_>>();
+    for (i, handle) in handles.iter().enumerate() {
+      let res = state
+        .create_ledger(None, handle, b"genesis", &[], &[])
+        .await;
+      assert!(res.is_ok());
+      for height in 1..=i + 1 {
+        let res = state.append_ledger(None, handle, b"block", height).await;
+        assert!(res.is_ok());
+      }
+    }
+
+    // every tail is attested with the nonce, and the digest covers them all
+    let nonce = Nonce::new();
+    let resp = client
+      .snapshot_read(SnapshotReadReq {
+        handles: handles.clone(),
+        nonce: nonce.to_bytes(),
+      })
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(resp.entries.len(), handles.len());
+    assert!(resp.locked_at <= resp.released_at);
+    let mut tails = Vec::new();
+    for (i, entry) in resp.entries.iter().enumerate() {
+      assert_eq!(entry.handle, handles[i]);
+      assert_eq!(entry.height, i as u64 + 1);
+      assert!(resp.locked_at <= entry.captured_at && entry.captured_at <= resp.released_at);
+      let height = vs
+        .verify_read_latest(
+          &entry.handle,
+          &entry.block,
+          &entry.nonces,
+          &nonce.to_bytes(),
+          &entry.receipts,
+        )
+        .unwrap();
+      assert_eq!(height, i + 1);
+      let tail = Receipts::from_bytes(&entry.receipts)
+        .unwrap()
+        .get_metablock()
+        .unwrap()
+        .hash();
+      tails.push((NimbleDigest::digest(&entry.handle), tail, height));
+    }
+    assert_eq!(
+      resp.digest,
+      compute_snapshot_digest(&nonce, &tails).to_bytes()
+    );
+
+    // a snapshot names each ledger once, and a bounded number of them
+    let read = |handles: Vec<Vec<u8>>| SnapshotReadReq {
+      handles,
+      nonce: Nonce::new().to_bytes(),
+    };
+    let res = client
+      .snapshot_read(read(vec![handles[0].clone(), handles[0].clone()]))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    let res = client
+      .snapshot_read(read(
+        (0..=MAX_SNAPSHOT_READ_SIZE)
+          .map(|i| (i as u64).to_le_bytes().to_vec())
+          .collect(),
+      ))
+      .await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+    // a snapshot and a batch lock the ledgers in the same order, whatever order they name them in,
+    // so neither waits for the other forever, and the snapshot sees all of the batch or none of it
+    let reversed = handles.iter().rev().cloned().collect::<Vec<_>>();
+    let batch = handles
+      .iter()
+      .enumerate()
+      .map(|(i, handle)| AppendBatchItem {
+        handle_bytes: handle.clone(),
+        block_bytes: b"block".to_vec(),
+        expected_height: i + 2,
+      })
+      .collect::<Vec<_>>();
+    let nonce = Nonce::new().to_bytes();
+    let (appended, snapshot) = tokio::time::timeout(Duration::from_secs(10), async {
+      tokio::join!(
+        state.append_ledger_batch(&batch, Deadline::none()),
+        state.snapshot_read(&reversed, &nonce)
+      )
+    })
+    .await
+    .unwrap();
+    assert!(appended.unwrap().iter().all(|res| res.is_ok()));
+    let heights = snapshot
+      .unwrap()
+      .entries
+      .iter()
+      .map(|entry| entry.height)
+      .collect::<Vec<_>>();
+    assert!(heights == vec![3, 2, 1] || heights == vec![4, 3, 2]);
+    cluster.stop().await;
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_append_pipelining() {
+    const NUM_APPENDS: usize = 64;
+    const NUM_WRITERS: usize = 8;
+    const LATENCY: Duration = Duration::from_millis(10);
+    let mut elapsed = Vec::new();
+    let mut round_trips = Vec::new();
+    for pipeline_depth in [1, NUM_WRITERS] {
+      let (state, _mocks, endorsers) = mock_coordinator(2).await;
+      let state = Arc::new(state.with_pipeline_depth(pipeline_depth));
+      let handle = Handle::random().to_bytes();
+      let res = state
+        .create_ledger(None, &handle, b"genesis", &[], &[])
+        .await;
+      assert!(res.is_ok());
+      for endorser in &endorsers {
+        endorser.set_latency(LATENCY);
+      }
+
+      // without pipelining, appends to a ledger are only acknowledged one at a time, so a single
+      // writer appends; with it, the writers take turns at the heights and append concurrently
+      let num_writers = if pipeline_depth == 1 { 1 } else { NUM_WRITERS };
+      let start = tokio::time::Instant::now();
+      let mut writers = Vec::new();
+      for writer in 0..num_writers {
+        let state = state.clone();
+        let handle = handle.clone();
+        writers.push(tokio::spawn(async move {
+          for height in (writer + 1..=NUM_APPENDS).step_by(num_writers) {
+            let block = format!("block_{}", height).into_bytes();
+            let res = state.append_ledger(None, &handle, &block, height).await;
+            assert!(res.is_ok(), "append at height {} failed: {:?}", height, res);
+          }
+        }));
+      }
+      for writer in writers {
+        writer.await.unwrap();
+      }
+      elapsed.push(start.elapsed());
+      round_trips.push(endorsers[0].num_calls("append") + endorsers[0].num_calls("append_batch"));
+
+      // the ledger holds the blocks in order, each with a quorum of receipts
+      let handle = NimbleDigest::digest(&handle);
+      for height in 1..=NUM_APPENDS {
+        let entry = state
+          .ledger_store
+          .read_ledger_by_index(&handle, height)
+          .await
+          .unwrap();
+        assert_eq!(
+          entry.get_block().to_bytes(),
+          format!("block_{}", height).into_bytes()
+        );
+        assert_eq!(entry.get_receipts().len(), 2);
+      }
+    }
+
+    // each append is a round trip to the endorsers of its own, unless the pipeline batches the
+    // appends that overlap, which the paused clock of the test lets pass only once per batch
+    assert_eq!(round_trips[0], NUM_APPENDS);
+    assert!(round_trips[1] < NUM_APPENDS);
+    assert!(elapsed[0] >= LATENCY * NUM_APPENDS as u32);
+    assert!(elapsed[1] < elapsed[0]);
+  }
+
+  #[tokio::test]
+  async fn test_shutdown_under_load() {
+    let (state, mocks, _endorsers) = mock_coordinator(2).await;
+    let state = Arc::new(state);
+
+    // two writers per ledger keep appending, so appends also queue behind the lock of a ledger
+    let handles = (0..4u8).map(|i| vec![i; 16]).collect::<Vec<_>>();
+    for handle in &handles {
+      let res = state
+        .create_ledger(None, handle, b"genesis", &[], &[])
+        .await;
+      assert!(res.is_ok());
+    }
+    let mut writers = Vec::new();
+    for handle in handles.iter().chain(handles.iter()) {
+      let state = state.clone();
+      let handle = handle.clone();
+      writers.push(tokio::spawn(async move {
+        let mut height = 1;
+        let mut acknowledged = 0;
+        loop {
+          match state.append_ledger(None, &handle, b"block", height).await {
+            Ok(_) => {
+              acknowledged = height;
+              height += 1;
+            },
+            Err(CoordinatorError::ConditionFailed { current_height, .. }) => {
+              height = current_height + 1;
+            },
+            Err(CoordinatorError::ShuttingDown) => return acknowledged,
+            Err(error) => panic!("unexpected error {:?}", error),
+          }
+        }
+      }));
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // the writers see a retryable error once the coordinator stops taking writes; what is in
+    // flight after the grace period is cut off, as if the process exited
+    state.shutdown(Duration::from_millis(50)).await;
+    let mut acknowledged = vec![0; handles.len()];
+    for (i, writer) in writers.iter_mut().enumerate() {
+      let res = tokio::time::timeout(Duration::from_secs(1), writer).await;
+      if let Ok(res) = res {
+        let i = i % handles.len();
+        acknowledged[i] = acknowledged[i].max(res.unwrap());
+      }
+    }
+    for writer in writers {
+      writer.abort();
+    }
+    let res = state
+      .append_ledger(None, &handles[0], b"block", acknowledged[0] + 1)
+      .await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::ShuttingDown);
+
+    // the next coordinator recovers over the same store and endorsers, keeps every
+    // acknowledged append, and continues every ledger from its tail
+    let mut next = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap()
+      .with_endorser_connector(mocks);
+    next.ledger_store = state.ledger_store.clone();
+    next.recover().await.unwrap();
+    for (handle, acknowledged) in handles.iter().zip(acknowledged) {
+      assert!(acknowledged > 0);
+      let (_entry, height) = next
+        .ledger_store
+        .read_ledger_tail(&NimbleDigest::digest(handle))
+        .await
+        .unwrap();
+      assert!(height >= acknowledged);
+      let res = next
+        .append_ledger(None, handle, b"after restart", height + 1)
+        .await;
+      assert!(res.is_ok(), "{:?}", res);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_create_collisions() {
+    let (state, _mocks, endorsers) = mock_coordinator(2).await;
+    let num_new_ledgers = || endorsers[1].num_calls("new_ledger");
+    let state = Arc::new(state);
+    let server = CoordinatorServiceState::new(state.clone());
+
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = server
+      .read_view_tail(tonic::Request::new(ReadViewTailReq { nonce: vec![] }))
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    // creates of a handle with the same genesis parameters race through many tasks; they all
+    // return the receipts of the one create that reaches the endorsers
+    let handle = Handle::random().to_bytes();
+    let tasks = (0..8)
+      .map(|_| {
+        let state = state.clone();
+        let handle = handle.clone();
+        tokio::spawn(async move {
+          state
+            .create_ledger(None, &handle, b"genesis", &[], b"owner=alice")
+            .await
+        })
+      })
+      .collect::<Vec<_>>();
+    for task in tasks {
+      let receipts = task.await.unwrap().unwrap();
+      let res = vs.verify_new_ledger_with_metadata(
+        &handle,
+        b"genesis",
+        b"owner=alice",
+        &receipts.to_bytes(),
+      );
+      assert!(res.is_ok());
+    }
+    assert_eq!(num_new_ledgers(), 1);
+
+    // creates of a handle with other genesis parameters race: one of them creates the ledger, and
+    // the others conflict with it without reaching the endorsers
+    let handle = Handle::random().to_bytes();
+    let tasks = (0..8u8)
+      .map(|i| {
+        let state = state.clone();
+        let handle = handle.clone();
+        tokio::spawn(async move {
+          let res = state
+            .create_ledger(None, &handle, b"genesis", &[], &[i])
+            .await;
+          (i, res)
+        })
+      })
+      .collect::<Vec<_>>();
+    let mut winner = None;
+    let mut conflicts = Vec::new();
+    for task in tasks {
+      match task.await.unwrap() {
+        (i, Ok(receipts)) => {
+          assert!(winner.is_none());
+          let res =
+            vs.verify_new_ledger_with_metadata(&handle, b"genesis", &[i], &receipts.to_bytes());
+          assert!(res.is_ok());
+          winner = Some(i);
+        },
+        (_i, Err(CoordinatorError::LedgerConflict { metadata, .. })) => conflicts.push(metadata),
+        (_i, Err(error)) => panic!("unexpected error {:?}", error),
+      }
+    }
+    let winner = winner.unwrap();
+    assert_eq!(conflicts, vec![vec![winner]; 7]);
+    assert_eq!(num_new_ledgers(), 2);
+
+    // the conflict reaches the client with the metadata of the existing ledger
+    let status = server
+      .new_ledger(tonic::Request::new(NewLedgerReq {
+        handle: handle.clone(),
+        block: b"another genesis".to_vec(),
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: vec![winner],
+      }))
+      .await
+      .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let LedgerExists { metadata, .. } = LedgerExists::decode(status.details()).unwrap();
+    assert_eq!(metadata, vec![winner]);
+    let res = state
+      .create_ledger(None, &handle, b"genesis", b"another app", &[winner])
+      .await;
+    assert!(matches!(res, Err(CoordinatorError::LedgerConflict { .. })));
+    assert_eq!(num_new_ledgers(), 2);
+  }
+
+  /// bytes of a random length, which is often at the edge of what a field holds
+  fn fuzz_bytes<R: Rng>(rng: &mut R) -> Vec<u8> {
+    const LENGTHS: [usize; 11] = [0, 1, 15, 16, 17, 31, 32, 33, 256, 257, 5000];
+    let len = if rng.gen_bool(0.8) {
+      LENGTHS[rng.gen_range(0..LENGTHS.len())]
+    } else {
+      rng.gen_range(0..2048)
+    };
+    (0..len).map(|_| rng.gen()).collect()
+  }
+
+  /// a random number, which is often at the edge of what a field holds
+  fn fuzz_u64<R: Rng>(rng: &mut R) -> u64 {
+    let values = [
+      0,
+      1,
+      validate::MAX_HEIGHT - 1,
+      validate::MAX_HEIGHT,
+      u64::MAX,
+      validate::MAX_READ_RANGE_PAGE_SIZE + 1,
+      validate::MAX_LIST_LEDGERS_PAGE_SIZE + 1,
+    ];
+    if rng.gen_bool(0.7) {
+      values[rng.gen_range(0..values.len())]
+    } else {
+      rng.gen()
+    }
+  }
+
+  /// the handle of an existing ledger, or random bytes
+  fn fuzz_handle<R: Rng>(rng: &mut R, handle: &[u8]) -> Vec<u8> {
+    if rng.gen_bool(0.3) {
+      handle.to_vec()
+    } else {
+      fuzz_bytes(rng)
+    }
+  }
+
+  /// a message decoded from random bytes if they decode, and one built from random fields otherwise
+  fn fuzz_message<R: Rng, T: Message + Default>(rng: &mut R, build: impl FnOnce(&mut R) -> T) -> T {
+    if rng.gen_bool(0.25) {
+      if let Ok(message) = T::decode(&fuzz_bytes(rng)[..]) {
+        return message;
+      }
+    }
+    build(rng)
+  }
+
+  fn assert_invalid<T>(res: Result<Response<T>, Status>) {
+    match res {
+      Ok(_) => panic!("a malformed request succeeded"),
+      Err(status) => assert_eq!(status.code(), tonic::Code::InvalidArgument, "{:?}", status),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_malformed_requests() {
+    let (state, _mocks, endorsers) = mock_coordinator(2).await;
+    let num_calls = || endorsers[1].total_calls();
+    let state = Arc::new(state);
+    let server = CoordinatorServiceState::new(state.clone());
+    let handle = Handle::random().to_bytes();
+    let res = server
+      .new_ledger(Request::new(NewLedgerReq {
+        handle: handle.clone(),
+        block: b"genesis".to_vec(),
+        app_bytes: vec![],
+        nonce: vec![],
+        metadata: vec![],
+      }))
+      .await;
+    assert!(res.is_ok());
+    let calls_before = num_calls();
+
+    // every RPC is thrown requests that its checks reject, built from random fields or decoded
+    // from random bytes; they fail with InvalidArgument and none of them reaches an endorser
+    const ROUNDS: usize = 200;
+    let max_block_size = state.max_block_size();
+    let mut rng = rand::thread_rng();
+    let mut thrown = [0usize; 11];
+    for _ in 0..ROUNDS {
+      let req = fuzz_message(&mut rng, |rng| NewLedgerReq {
+        handle: if rng.gen() {
+          vec![]
+        } else {
+          fuzz_handle(rng, &handle)
+        },
+        block: fuzz_bytes(rng),
+        app_bytes: fuzz_bytes(rng),
+        nonce: fuzz_bytes(rng),
+        metadata: fuzz_bytes(rng),
+      });
+      if validate::new_ledger(&req, max_block_size).is_err() {
+        assert_invalid(server.new_ledger(Request::new(req)).await);
+        thrown[0] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| AppendReq {
+        handle: fuzz_handle(rng, &handle),
+        block: fuzz_bytes(rng),
+        expected_height: fuzz_u64(rng),
+        request_id: String::new(),
+      });
+      if validate::append(&req, max_block_size).is_err() {
+        assert_invalid(server.append(Request::new(req)).await);
+        thrown[1] += 1;
+      }
+
+      // the items of a batch fail on their own, so a batch whose items all fail succeeds
+      let req = fuzz_message(&mut rng, |rng| {
+        let num_items = if rng.gen_bool(0.1) {
+          MAX_APPEND_BATCH_SIZE + 1
+        } else {
+          rng.gen_range(0..4)
+        };
+        AppendBatchReq {
+          items: (0..num_items)
+            .map(|_| AppendReq {
+              handle: fuzz_handle(rng, &handle),
+              block: fuzz_bytes(rng),
+              expected_height: fuzz_u64(rng),
+              request_id: String::new(),
+            })
+            .collect(),
+        }
+      });
+      if validate::append_batch(&req).is_err() {
+        assert_invalid(server.append_batch(Request::new(req)).await);
+        thrown[2] += 1;
+      } else if req
+        .items
+        .iter()
+        .all(|item| validate::append(item, max_block_size).is_err())
+      {
+        let num_items = req.items.len();
+        let AppendBatchResp { results } = server
+          .append_batch(Request::new(req))
+          .await
+          .unwrap()
+          .into_inner();
+        assert_eq!(results.len(), num_items);
+        for result in results {
+          assert_eq!(result.code, tonic::Code::InvalidArgument as i32);
+          assert!(result.message.starts_with("items["));
+        }
+        thrown[2] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| SealLedgerReq {
+        handle: fuzz_bytes(rng),
+      });
+      if validate::seal_ledger(&req).is_err() {
+        assert_invalid(server.seal_ledger(Request::new(req)).await);
+        thrown[3] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| ReadLatestReq {
+        handle: fuzz_handle(rng, &handle),
+        nonce: fuzz_bytes(rng),
+        consistency: if rng.gen() {
+          rng.gen_range(-1..3)
+        } else {
+          rng.gen()
+        },
+      });
+      if validate::read_latest(&req).is_err() {
+        assert_invalid(server.read_latest(Request::new(req)).await);
+        thrown[4] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| ReadByIndexReq {
+        handle: fuzz_handle(rng, &handle),
+        index: fuzz_u64(rng),
+      });
+      if validate::read_by_index(&req).is_err() {
+        assert_invalid(server.read_by_index(Request::new(req)).await);
+        thrown[5] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| ReadRangeReq {
+        handle: fuzz_handle(rng, &handle),
+        from: fuzz_u64(rng),
+        to: fuzz_u64(rng),
+        page_size: fuzz_u64(rng),
+        page_token: fuzz_bytes(rng),
+      });
+      if validate::read_range(&req).is_err() {
+        assert_invalid(server.read_range(Request::new(req)).await);
+        thrown[6] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| GetLedgerInfoReq {
+        handle: fuzz_handle(rng, &handle),
+        attested: rng.gen(),
+        nonce: fuzz_bytes(rng),
+      });
+      if validate::get_ledger_info(&req).is_err() {
+        assert_invalid(server.get_ledger_info(Request::new(req)).await);
+        thrown[7] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| ListLedgersReq {
+        page_token: fuzz_bytes(rng),
+        page_size: fuzz_u64(rng),
+        app_prefix: fuzz_bytes(rng),
+      });
+      if validate::list_ledgers(&req).is_err() {
+        assert_invalid(server.list_ledgers(Request::new(req)).await);
+        thrown[8] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| ReadViewByIndexReq {
+        index: fuzz_u64(rng),
+      });
+      if validate::read_view_by_index(&req).is_err() {
+        assert_invalid(server.read_view_by_index(Request::new(req)).await);
+        thrown[9] += 1;
+      }
+
+      let req = fuzz_message(&mut rng, |rng| ReadViewTailReq {
+        nonce: fuzz_bytes(rng),
+      });
+      if validate::read_view_tail(&req).is_err() {
+        assert_invalid(server.read_view_tail(Request::new(req)).await);
+        thrown[10] += 1;
+      }
+    }
+    assert!(thrown.iter().all(|&n| n > 0), "{:?}", thrown);
+    assert_eq!(num_calls(), calls_before);
+
+    // the ledger is as it was
+    let res = server
+      .append(Request::new(AppendReq {
+        handle,
+        block: b"block1".to_vec(),
+        expected_height: 0,
+        request_id: String::new(),
+      }))
+      .await;
+    assert!(res.is_ok());
+  }
+
+  #[tokio::test]
+  async fn test_append_intents() {
+    let (state, mocks, _endorsers) = mock_coordinator(3).await;
+    // every ledger holds an acknowledged append, and the append after it leaves its intent
+    // behind as the coordinator is killed at a different point: before the store, before the
+    // fan-out to the endorsers, after the fan-out reached one of them, and after it reached all
+    // of them but before the receipts were stored
+    let handles = (0..4u8).map(|i| vec![i; 16]).collect::<Vec<_>>();
+    for handle in &handles {
+      let res = state
+        .create_ledger(None, handle, b"genesis", &[], &[])
+        .await;
+      assert!(res.is_ok());
+      let res = state.append_ledger(None, handle, b"block_1", 1).await;
+      assert!(res.is_ok());
+    }
+    let endorsers = state.get_endorser_pks();
+    for (i, handle) in handles.iter().enumerate() {
+      let handle = NimbleDigest::digest(handle);
+      let block = Block::new(b"block_2");
+      let intent = IntentRecord {
+        handle,
+        height: 2,
+        block_hash: block.hash(),
+        request_id: format!("crash-{}", i),
+      };
+      state.ledger_store.write_intent(&intent).await.unwrap();
+      if i == 0 {
+        continue;
+      }
+      let (_height, nonces) = state
+        .ledger_store
+        .append_ledger(&handle, &block, 2)
+        .await
+        .unwrap();
+      let fan_out = match i {
+        1 => 0,
+        2 => 1,
+        _ => endorsers.len(),
+      };
+      if fan_out > 0 {
+        let prev = state
+          .ledger_store
+          .read_ledger_by_index(&handle, 1)
+          .await
+          .unwrap();
+        let res = state
+          .endorser_append_ledger(
+            &endorsers[..fan_out],
+            &handle,
+            2,
+            Some(&prev.get_block_hash()),
+            &block,
+            &nonces,
+            Deadline::none(),
+          )
+          .await;
+        assert!(res.is_ok());
+      }
+    }
+    assert_eq!(state.ledger_store.list_intents().await.unwrap().len(), 4);
+
+    // the next coordinator resolves every intent: the append that never reached the store is
+    // discarded, and the others are completed with a quorum of receipts
+    let mut next = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap()
+      .with_endorser_connector(mocks);
+    next.ledger_store = state.ledger_store.clone();
+    next.recover().await.unwrap();
+    assert!(next.ledger_store.list_intents().await.unwrap().is_empty());
+    let next = Arc::new(next);
+    let server = CoordinatorServiceState::new(next.clone());
+
+    let mut vs = VerifierState::new();
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = server
+      .read_view_tail(tonic::Request::new(ReadViewTailReq { nonce: vec![] }))
+      .await
+      .unwrap()
+      .into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    for (i, handle) in handles.iter().enumerate() {
+      let (_entry, height) = next
+        .ledger_store
+        .read_ledger_tail(&NimbleDigest::digest(handle))
+        .await
+        .unwrap();
+      assert_eq!(height, if i == 0 { 1 } else { 2 });
+      for index in 1..=height {
+        let ReadByIndexResp {
+          block,
+          nonces,
+          receipts,
+          ..
+        } = server
+          .read_by_index(tonic::Request::new(ReadByIndexReq {
+            handle: handle.clone(),
+            index: index as u64,
+          }))
+          .await
+          .unwrap()
+          .into_inner();
+        assert_eq!(block, format!("block_{}", index).into_bytes());
+        assert!(vs
+          .verify_read_by_index(handle, &block, &nonces, index, &receipts)
+          .is_ok());
+      }
+      let res = next
+        .append_ledger(None, handle, b"block_3", height + 1)
+        .await;
+      assert!(res.is_ok(), "{:?}", res);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_append_request_ids() {
+    let cluster = TestCluster::start_with(2, StoreKind::Memory, |state| {
+      state.with_request_id_retention(2)
+    })
+    .await;
+    let coordinator = cluster.state();
+    let client = cluster.client();
+    let handle = rand::thread_rng().gen::<[u8; 16]>().to_vec();
+    let res = coordinator
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    let append = |block: &[u8], expected_height: u64, request_id: &str| {
+      let mut client = client.clone();
+      let req = AppendReq {
+        handle: handle.clone(),
+        block: block.to_vec(),
+        expected_height,
+        request_id: request_id.to_string(),
+      };
+      async move { client.append(req).await }
+    };
+
+    // a retry of a done append returns its entry even though another writer moved the tail
+    let first = append(b"block_1", 0, "writer-a-1")
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(first.height, 1);
+    let res = append(b"block_2", 1, "").await;
+    assert!(res.is_ok());
+    let retried = append(b"block_1", 0, "writer-a-1")
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(retried, first);
+    let (_entry, height) = coordinator
+      .ledger_store
+      .read_ledger_tail(&NimbleDigest::digest(&handle))
+      .await
+      .unwrap();
+    assert_eq!(height, 2);
+
+    // an append that fails its condition leaves no ID behind, so its retry appends
+    let status = append(b"block_3", 0, "writer-a-2").await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    let resp = append(b"block_3", 2, "writer-a-2")
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(resp.height, 3);
+
+    // an ID names one block
+    let status = append(b"block_4", 3, "writer-a-2").await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+
+    // only the IDs of the latest appends are kept; an older ID is a new append
+    let res = append(b"block_4", 3, "writer-a-3").await;
+    assert!(res.is_ok());
+    let status = append(b"block_1", 0, "writer-a-1").await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    let tokens = coordinator
+      .ledger_store
+      .read_append_tokens(&NimbleDigest::digest(&handle))
+      .await
+      .unwrap();
+    assert_eq!(
+      tokens
+        .iter()
+        .map(|t| (t.request_id.as_str(), t.height))
+        .collect::<Vec<_>>(),
+      vec![("writer-a-2", 3), ("writer-a-3", 4)]
+    );
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  async fn test_watch() {
+    let cluster = TestCluster::start(2, StoreKind::Memory).await;
+    let coordinator = cluster.state();
+    let client = cluster.client();
+    let handle = rand::thread_rng().gen::<[u8; 16]>().to_vec();
+    let res = coordinator
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    let append = |block: &[u8], expected_height: u64| {
+      let mut client = client.clone();
+      let req = AppendReq {
+        handle: handle.clone(),
+        block: block.to_vec(),
+        expected_height,
+        request_id: String::new(),
+      };
+      async move { client.append(req).await }
+    };
+    for height in 1..=2u64 {
+      let res = append(format!("block_{}", height).as_bytes(), height - 1).await;
+      assert!(res.is_ok());
+    }
+    let watch = |from_height: u64| {
+      let mut client = client.clone();
+      let req = WatchReq {
+        handle: handle.clone(),
+        from_height,
+        include_blocks: true,
+      };
+      async move { client.watch(req).await }
+    };
+
+    // the committed entries come first, and then each entry as it commits
+    let mut stream = watch(1).await.unwrap().into_inner();
+    for height in 1..=3u64 {
+      if height == 3 {
+        let res = append(b"block_3", 2).await;
+        assert!(res.is_ok());
+      }
+      let resp = stream.next().await.unwrap().unwrap();
+      assert_eq!(resp.height, height);
+      assert!(!resp.heartbeat);
+      let block = format!("block_{}", height).into_bytes();
+      assert_eq!(resp.block, block);
+      let hash_nonces = NimbleDigest::digest(&resp.nonces);
+      let block_hash = compute_aggregated_block_hash(
+        &NimbleDigest::digest(&block).to_bytes(),
+        &hash_nonces.to_bytes(),
+      );
+      assert_eq!(resp.block_hash, block_hash.to_bytes());
+    }
+    drop(stream);
+
+    // a watch may start right after the committed height, but not beyond it
+    let mut stream = watch(4).await.unwrap().into_inner();
+    let res = append(b"block_4", 3).await;
+    assert!(res.is_ok());
+    assert_eq!(stream.next().await.unwrap().unwrap().height, 4);
+    let mut stream = watch(6).await.unwrap().into_inner();
+    let status = stream.next().await.unwrap().unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange);
+    assert!(stream.next().await.is_none());
+
+    // a client that leaves the queue full is dropped with the height that resumes it
+    let (tx, mut rx) = mpsc::channel(2);
+    coordinator.watch_ledger(&handle, 0, false, tx).await;
+    match rx.recv().await.unwrap() {
+      Ok(WatchEvent::Entry { height, entry, .. }) => {
+        assert_eq!(height, 0);
+        assert!(entry.is_none());
+      },
+      _ => panic!("the watch did not start at genesis"),
+    }
+    let error = rx.recv().await.unwrap().err().unwrap();
+    assert_eq!(error, CoordinatorError::WatchLagged { next_height: 1 });
+    let status = process_error(error, "");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    let details = WatchLagged::decode(status.details()).unwrap();
+    assert_eq!(details.next_height, 1);
+    assert!(rx.recv().await.is_none());
+    cluster.stop().await;
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_checkpoint() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9183");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9184");
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let res = coordinator
+      .replace_endorsers(&[
+        "http://[::1]:9183".to_string(),
+        "http://[::1]:9184".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let coordinator = Arc::new(coordinator);
+    let server = CheckpointServiceState::new(coordinator.clone());
+    let namespace_id = format!("ns-{}", rand::random::<u64>());
+    let record = |txid: u64, digest: u8| {
+      server.record_checkpoint(Request::new(RecordCheckpointReq {
+        namespace_id: namespace_id.clone(),
+        txid,
+        fsimage_digest: vec![digest; 32],
+        md5: vec![digest; 16],
+        metadata: b"nn1.example.com".to_vec(),
+      }))
+    };
+    let verify = |txid: u64, digest: u8| {
+      server.verify_checkpoint(Request::new(VerifyCheckpointReq {
+        namespace_id: namespace_id.clone(),
+        txid,
+        fsimage_digest: vec![digest; 32],
+      }))
+    };
+    let latest = || {
+      server.get_latest_checkpoint(Request::new(GetLatestCheckpointReq {
+        namespace_id: namespace_id.clone(),
+        nonce: rand::thread_rng().gen::<[u8; 16]>().to_vec(),
+      }))
+    };
+    let status = latest().await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    // the first checkpoint creates the ledger of the namespace, and each one is the next entry
+    let first = record(100, 1).await.unwrap().into_inner();
+    assert_eq!(first.height, 1);
+    let RecordCheckpointResp {
+      handle,
+      height,
+      block,
+      receipts,
+      ..
+    } = record(200, 2).await.unwrap().into_inner();
+    assert_eq!(height, 2);
+    assert_eq!(decode_record(&block).unwrap().txid, 200);
+    let genesis = coordinator.read_ledger_by_index(&handle, 0).await.unwrap();
+    let genesis_bytes = genesis.get_block().to_bytes();
+    let (genesis_metadata, _block) = parse_genesis_block(&genesis_bytes).unwrap();
+    assert_eq!(genesis_metadata, namespace_id.as_bytes());
+
+    // a retry returns the entry of the latest checkpoint, and a txid that is not above it fails
+    let retry = record(200, 2).await.unwrap().into_inner();
+    assert_eq!((retry.height, retry.receipts), (2, receipts.clone()));
+    for (txid, digest) in [(200, 3), (150, 3)] {
+      let status = record(txid, digest).await.unwrap_err();
+      assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+      let conflict = CheckpointConflict::decode(status.details()).unwrap();
+      assert_eq!((conflict.latest_txid, conflict.latest_height), (200, 2));
+    }
+
+    let resp = latest().await.unwrap().into_inner();
+    assert_eq!(resp.height, 2);
+    assert_eq!(resp.record.unwrap().fsimage_digest, vec![2u8; 32]);
+
+    // both checkpoints verify against the attested ledger, and a tampered digest does not match
+    for (txid, digest) in [(100, 1), (200, 2)] {
+      let resp = verify(txid, digest).await.unwrap().into_inner();
+      assert!(resp.matches);
+      assert_eq!(resp.block_hashes.len(), if txid == 100 { 1 } else { 0 });
+    }
+    assert!(!verify(200, 1).await.unwrap().into_inner().matches);
+    let status = verify(150, 1).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    // a checkpoint written to the ledger store behind the coordinator, with the receipts of the
+    // previous one, is found but fails verification
+    let forged = encode_record(&CheckpointRecord {
+      txid: 300,
+      ..decode_record(&block).unwrap()
+    });
+    let ledger = NimbleDigest::digest(&handle);
+    let res = coordinator
+      .ledger_store
+      .append_ledger(&ledger, &Block::new(&forged), 3)
+      .await;
+    assert!(res.is_ok());
+    let res = coordinator
+      .ledger_store
+      .attach_ledger_receipts(&ledger, 3, &Receipts::from_bytes(&receipts).unwrap())
+      .await;
+    assert!(res.is_ok());
+    let status = verify(300, 2).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::DataLoss);
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_edit_segments() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9185");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9186");
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let res = coordinator
+      .replace_endorsers(&[
+        "http://[::1]:9185".to_string(),
+        "http://[::1]:9186".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let server = CheckpointServiceState::new(Arc::new(coordinator));
+    let namespace_id = format!("ns-{}", rand::random::<u64>());
+    let record = |start_txid: u64, end_txid: u64, digest: u8| {
+      server.record_edit_segment(Request::new(RecordEditSegmentReq {
+        namespace_id: namespace_id.clone(),
+        start_txid,
+        end_txid,
+        segment_digest: vec![digest; 32],
+      }))
+    };
+    let verify = |start_txid: u64, end_txid: u64, segments: Vec<EditSegment>| {
+      server.verify_edit_range(Request::new(VerifyEditRangeReq {
+        namespace_id: namespace_id.clone(),
+        start_txid,
+        end_txid,
+        segments,
+      }))
+    };
+
+    // a namespace without segments has a single gap
+    let resp = verify(1, 100, vec![]).await.unwrap().into_inner();
+    assert!(!resp.covered);
+    assert_eq!(resp.gaps.len(), 1);
+
+    // the segments are recorded out of order, and a retry returns the entry of the segment
+    for (height, (start_txid, end_txid)) in [(101, 200), (1, 100), (301, 400)].iter().enumerate() {
+      let resp = record(*start_txid, *end_txid, (*start_txid / 100) as u8)
+        .await
+        .unwrap()
+        .into_inner();
+      assert_eq!(resp.height, height as u64 + 1);
+    }
+    let retry = record(1, 100, 0).await.unwrap().into_inner();
+    assert_eq!(retry.height, 2);
+
+    // the missing segment is a gap until it is recorded
+    let resp = verify(1, 400, vec![]).await.unwrap().into_inner();
+    assert!(!resp.covered);
+    let gaps = resp
+      .gaps
+      .iter()
+      .map(|gap| (gap.start_txid, gap.end_txid))
+      .collect::<Vec<_>>();
+    assert_eq!(gaps, vec![(201, 300)]);
+    assert!(record(201, 300, 2).await.is_ok());
+    let supplied = vec![EditSegment {
+      start_txid: 201,
+      end_txid: 300,
+      segment_digest: vec![2u8; 32],
+      height: 0,
+    }];
+    let resp = verify(1, 400, supplied.clone()).await.unwrap().into_inner();
+    assert!(resp.covered);
+    assert_eq!(resp.height, 4);
+    let starts = resp
+      .segments
+      .iter()
+      .map(|segment| segment.start_txid)
+      .collect::<Vec<_>>();
+    assert_eq!(starts, vec![1, 101, 201, 301]);
+
+    // a tampered digest is a mismatch, and a segment that overlaps others is reported with both
+    let mut tampered = supplied;
+    tampered[0].segment_digest = vec![7u8; 32];
+    let resp = verify(1, 400, tampered).await.unwrap().into_inner();
+    assert!(!resp.covered);
+    assert_eq!(resp.mismatches[0].recorded_digest, vec![2u8; 32]);
+    assert_eq!(resp.mismatches[0].height, 4);
+    assert!(record(350, 450, 5).await.is_ok());
+    let resp = verify(1, 450, vec![]).await.unwrap().into_inner();
+    assert!(!resp.covered);
+    assert!(resp.gaps.is_empty());
+    let overlap = &resp.overlaps[0];
+    assert_eq!(
+      (
+        overlap.start_txid,
+        overlap.end_txid,
+        overlap.first_height,
+        overlap.second_height
+      ),
+      (350, 400, 3, 5)
+    );
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_heartbeats() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9187");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9188");
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let res = coordinator
+      .replace_endorsers(&[
+        "http://[::1]:9187".to_string(),
+        "http://[::1]:9188".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let coordinator = Arc::new(coordinator);
+    let server = CheckpointServiceState::new(coordinator.clone());
+    let namespace_id = format!("ns-{}", rand::random::<u64>());
+    let record = || {
+      server.record_checkpoint(Request::new(RecordCheckpointReq {
+        namespace_id: namespace_id.clone(),
+        txid: 100,
+        fsimage_digest: vec![1u8; 32],
+        md5: vec![1u8; 16],
+        metadata: vec![],
+      }))
+    };
+    assert!(record().await.is_ok());
+
+    // the liveness ledger is created with the first heartbeat, and the times never go back
+    let liveness = format!("liveness-{}", rand::random::<u64>());
+    let mut scheduler = HeartbeatScheduler::new(
+      coordinator.clone(),
+      liveness.as_bytes(),
+      Duration::from_secs(5),
+    )
+    .with_checkpoint_ledgers(vec![checkpoint_handle(&namespace_id)]);
+    let mut timestamps = Vec::new();
+    for height in 0..3 {
+      assert!(scheduler.beat().await.is_ok());
+      let (entry, tail_height) = coordinator
+        .read_cached_ledger_tail(liveness.as_bytes())
+        .await
+        .unwrap();
+      assert_eq!(tail_height, height);
+      let (timestamp_ms, interval_ms) =
+        ledger::parse_heartbeat_block(&entry.get_block().to_bytes()).unwrap();
+      assert_eq!(interval_ms, 5_000);
+      timestamps.push(timestamp_ms);
+    }
+    assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // the checkpoint ledger has a heartbeat per beat, which repeats the checkpoint; recording the
+    // checkpoint again returns the latest heartbeat, and the checkpoint still verifies
+    let retry = record().await.unwrap().into_inner();
+    assert_eq!(retry.height, 4);
+    let heartbeat = decode_record(&retry.block).unwrap();
+    assert_eq!(heartbeat.version, CHECKPOINT_HEARTBEAT_VERSION);
+    assert_eq!(
+      (heartbeat.txid, heartbeat.heartbeat_ms),
+      (100, timestamps[2])
+    );
+    let resp = server
+      .verify_checkpoint(Request::new(VerifyCheckpointReq {
+        namespace_id: namespace_id.clone(),
+        txid: 100,
+        fsimage_digest: vec![1u8; 32],
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(resp.matches);
+
+    // a namespace without checkpoints gets no heartbeats
+    let mut scheduler = HeartbeatScheduler::new(
+      coordinator.clone(),
+      liveness.as_bytes(),
+      Duration::from_secs(5),
+    )
+    .with_checkpoint_ledgers(vec![checkpoint_handle("ns-empty")]);
+    assert!(scheduler.beat().await.is_ok());
+    let res = coordinator
+      .read_cached_ledger_tail(&checkpoint_handle("ns-empty"))
+      .await;
+    assert!(matches!(res, Err(CoordinatorError::LedgerNotFound)));
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_rotate_endorser_key() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9195");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9196");
+
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let res = state
+      .replace_endorsers(&[
+        "http://[::1]:9195".to_string(),
+        "http://[::1]:9196".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let server = CoordinatorServiceState::new(state.clone());
+    let mut vs = VerifierState::new();
+    let read_view_tail = || server.read_view_tail(Request::new(ReadViewTailReq { nonce: vec![] }));
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = read_view_tail().await.unwrap().into_inner();
+    vs.set_group_identity(compute_view_block_hash(&block).unwrap());
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+
+    let handle = Handle::random().to_bytes();
+    let res = state
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+
+    // appends keep going while the key rotates; they are turned away during the view change only
+    const NUM_APPENDS: usize = 50;
+    let appender = {
+      let state = state.clone();
+      let handle = handle.clone();
+      tokio::spawn(async move {
+        let mut height = 1;
+        while height <= NUM_APPENDS {
+          let block = format!("block_{}", height).into_bytes();
+          match state.append_ledger(None, &handle, &block, height).await {
+            Ok(_res) => height += 1,
+            Err(CoordinatorError::ViewChangeInProgress) => {
+              tokio::time::sleep(Duration::from_millis(1)).await;
+            },
+            Err(e) => panic!("append at height {} failed: {:?}", height, e),
+          }
+        }
+      })
+    };
+
+    let old_pk = state.get_endorser_pks()[0].to_vec();
+    let new_pk = state.rotate_endorser_key(&old_pk).await.unwrap();
+    appender.await.unwrap();
+    let pks = state
+      .get_endorser_pks()
+      .iter()
+      .map(|pk| pk.to_vec())
+      .collect::<Vec<_>>();
+    assert_eq!(pks.len(), 2);
+    assert!(pks.contains(&new_pk) && !pks.contains(&old_pk));
+
+    // the view ledger records the handover, so the new key is attributed to the endorser of the
+    // old one
+    let ReadViewTailResp {
+      block,
+      receipts,
+      attestations,
+      ..
+    } = read_view_tail().await.unwrap().into_inner();
+    assert!(vs
+      .apply_view_change(&block, &receipts, Some(&attestations))
+      .is_ok());
+    assert_eq!(vs.get_rotated_key(&new_pk), Some(&old_pk));
+    assert_eq!(vs.get_original_key(&new_pk), old_pk);
+
+    // the endorser signs under its new key, and the old key can no longer be rotated
+    let message = b"block_after_rotation".to_vec();
+    let AppendResp {
+      hash_nonces,
+      receipts,
+      ..
+    } = server
+      .append(Request::new(AppendReq {
+        handle: handle.clone(),
+        block: message.clone(),
+        expected_height: NUM_APPENDS as u64 + 1,
+        request_id: String::new(),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+    assert!(vs
+      .verify_append(&handle, &message, &hash_nonces, NUM_APPENDS + 1, &receipts)
+      .is_ok());
+    let res = state.rotate_endorser_key(&old_pk).await;
+    assert!(matches!(
+      res,
+      Err(CoordinatorError::InvalidEndorserPublicKey)
+    ));
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_endorser_allowlist() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9199");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9200");
+    let uri1 = "http://[::1]:9199".to_string();
+    let uri2 = "http://[::1]:9200".to_string();
+
+    // the keys of the endorsers, as the operator learns them
+    let probe = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let pk = |uri: &String| {
+      let probe = &probe;
+      let uri = uri.clone();
+      async move { probe.connect_endorsers(&[uri]).await[0].0.clone() }
+    };
+    let (pk1, pk2) = (pk(&uri1).await, pk(&uri2).await);
+
+    let operator = PrivateKey::new();
+    let dir = std::env::temp_dir().join(format!("nimble-allowlist-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("endorsers.allow").to_str().unwrap().to_string();
+    let signature_path = allowlist::default_signature_path(&path);
+    let write = |keys: &[&Vec<u8>]| {
+      let contents = keys
+        .iter()
+        .map(|key| format!("{}\n", hex::encode(key)))
+        .collect::<String>();
+      let signature = operator
+        .sign(&allowlist::signed_message(contents.as_bytes()))
+        .unwrap();
+      std::fs::write(&path, &contents).unwrap();
+      std::fs::write(&signature_path, hex::encode(signature.to_bytes())).unwrap();
+    };
+    write(&[&pk1]);
+    let allowlist =
+      EndorserAllowlist::load(&path, &signature_path, operator.get_public_key().unwrap()).unwrap();
+
+    let state = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap()
+      .with_endorser_allowlist(allowlist);
+    state.recover().await.unwrap();
+    let state = Arc::new(state);
+    assert!(state.replace_endorsers(&[uri1.clone()]).await.is_ok());
+    let res = state.add_endorsers(&[uri2.clone()]).await;
+    assert_eq!(res.unwrap_err(), CoordinatorError::EndorserNotAllowlisted);
+    let pks = || {
+      let mut pks = state
+        .get_endorser_pks()
+        .iter()
+        .map(|pk| pk.to_vec())
+        .collect::<Vec<_>>();
+      pks.sort();
+      pks
+    };
+    assert_eq!(pks(), vec![pk1.clone()]);
+
+    // the admin service enforces the allowlist as well, and reloads it
+    let admin = AdminServiceState::new(state.clone());
+    let add_endorser = || async {
+      let OperationResp { operation_id } = admin
+        .add_endorser(Request::new(AddEndorserReq { uri: uri2.clone() }))
+        .await
+        .unwrap()
+        .into_inner();
+      loop {
+        let GetOperationStatusResp { state, error } = admin
+          .get_operation_status(Request::new(GetOperationStatusReq {
+            operation_id: operation_id.clone(),
+          }))
+          .await
+          .unwrap()
+          .into_inner();
+        if state != OperationState::Running as i32 {
+          return (state, error);
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+      }
+    };
+    let (op_state, error) = add_endorser().await;
+    assert_eq!(op_state, OperationState::Failed as i32);
+    assert!(error.contains("allowlist"));
+
+    // a file changed without the operator key is refused, and the allowlist stays as it was
+    std::fs::write(&path, format!("{}\n", hex::encode(&pk2))).unwrap();
+    let reload = || admin.reload_endorser_allowlist(Request::new(ReloadEndorserAllowlistReq {}));
+    let status = reload().await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    assert!(status.message().contains("does not verify"));
+    let (op_state, _error) = add_endorser().await;
+    assert_eq!(op_state, OperationState::Failed as i32);
+
+    write(&[&pk1, &pk2]);
+    let ReloadEndorserAllowlistResp { num_keys } = reload().await.unwrap().into_inner();
+    assert_eq!(num_keys, 2);
+    let (op_state, error) = add_endorser().await;
+    assert_eq!(op_state, OperationState::Succeeded as i32, "{}", error);
+    let mut expected = vec![pk1, pk2];
+    expected.sort();
+    assert_eq!(pks(), expected);
+
+    // without an allowlist, there is nothing to reload
+    let admin = AdminServiceState::new(Arc::new(probe));
+    let status = admin
+      .reload_endorser_allowlist(Request::new(ReloadEndorserAllowlistReq {}))
+      .await
+      .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_audit_log() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9197");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9198");
+
+    let state = Arc::new(
+      CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+        .await
+        .unwrap(),
+    );
+    let res = state
+      .replace_endorsers(&[
+        "http://[::1]:9197".to_string(),
+        "http://[::1]:9198".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let (audit, queue) = AuditLog::new(DEFAULT_AUDIT_LEDGER.as_bytes(), 16);
+    let audit = Arc::new(audit);
+    let server = CoordinatorServiceState::new(state.clone()).with_audit_log(audit.clone());
+    let admin = AdminServiceState::new(state.clone()).with_audit_log(audit);
+    let read_audit =
+      |from: u64, page_size: u64| admin.read_audit(Request::new(ReadAuditReq { from, page_size }));
+
+    // the audit ledger is created with the first record
+    let ReadAuditResp {
+      entries,
+      tail_height,
+      ..
+    } = read_audit(0, 0).await.unwrap().into_inner();
+    assert!(entries.is_empty());
+    assert_eq!(tail_height, 0);
+    let _writer = tokio::spawn(AuditWriter::new(state.clone(), queue).run(std::future::pending()));
+
+    let handle = Handle::random().to_bytes();
+    let new_ledger = NewLedgerReq {
+      handle: handle.clone(),
+      block: b"genesis".to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: vec![],
+    };
+    assert!(server
+      .new_ledger(Request::new(new_ledger.clone()))
+      .await
+      .is_ok());
+    let append = |expected_height: u64| AppendReq {
+      handle: handle.clone(),
+      block: b"block_1".to_vec(),
+      expected_height,
+      request_id: String::new(),
+    };
+    assert!(server.append(Request::new(append(0))).await.is_ok());
+    // failed calls are recorded with their code, and reads are not recorded
+    let res = server.append(Request::new(append(0))).await;
+    assert_eq!(res.unwrap_err().code(), tonic::Code::FailedPrecondition);
+    let res = server
+      .read_latest(Request::new(ReadLatestReq {
+        handle: handle.clone(),
+        nonce: vec![],
+        consistency: ReadConsistency::Attested as i32,
+      }))
+      .await;
+    assert!(res.is_ok());
+
+    // the records follow the genesis block of the audit ledger
+    let mut tail_height = 0;
+    for _ in 0..100 {
+      tail_height = read_audit(0, 0).await.unwrap().into_inner().tail_height;
+      if tail_height == 3 {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(tail_height, 3);
+    let ReadAuditResp {
+      entries, next_from, ..
+    } = read_audit(1, 2).await.unwrap().into_inner();
+    assert_eq!(next_from, 3);
+    let ReadAuditResp {
+      entries: rest,
+      next_from,
+      ..
+    } = read_audit(next_from, 2).await.unwrap().into_inner();
+    assert_eq!(next_from, 0);
+    let records = entries
+      .into_iter()
+      .chain(rest)
+      .map(|entry| entry.record.unwrap())
+      .collect::<Vec<_>>();
+    let methods = records
+      .iter()
+      .map(|record| record.method.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      methods,
+      vec![
+        "coordinator_proto.Call/NewLedger",
+        "coordinator_proto.Call/Append",
+        "coordinator_proto.Call/Append",
+      ]
+    );
+    let codes = records.iter().map(|record| record.code).collect::<Vec<_>>();
+    assert_eq!(codes, vec![0, 0, tonic::Code::FailedPrecondition as u32]);
+    assert_eq!(
+      records[0].params_digest,
+      Sha256Hasher::digest(&new_ledger.encode_to_vec()).to_vec()
+    );
+  }
+
+  #[tokio::test]
+  #[ignore]
+  async fn test_export_ledger() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9193");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9194");
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let res = coordinator
+      .replace_endorsers(&[
+        "http://[::1]:9193".to_string(),
+        "http://[::1]:9194".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let coordinator = Arc::new(coordinator);
+    let server = CoordinatorServiceState::new(coordinator.clone());
+    let handle = rand::thread_rng().gen::<[u8; 16]>().to_vec();
+    let res = coordinator
+      .create_ledger(None, &handle, b"genesis", &[], &[])
+      .await;
+    assert!(res.is_ok());
+    for height in 1..=2u64 {
+      let res = server
+        .append(Request::new(AppendReq {
+          handle: handle.clone(),
+          block: format!("block_{}", height).into_bytes(),
+          expected_height: height - 1,
+          request_id: String::new(),
+        }))
+        .await;
+      assert!(res.is_ok());
+    }
+    let export = |handle: Vec<u8>| server.export_ledger(Request::new(ExportLedgerReq { handle }));
+
+    // the bundle holds the ledger, its entries, the tail, the views and the end, in that order
+    let mut stream = export(handle.clone()).await.unwrap().into_inner();
+    let mut bundle = Vec::new();
+    while let Some(resp) = stream.next().await {
+      bundle.extend(resp.unwrap().chunk);
+    }
+    let records = BundleReader::new(&bundle)
+      .unwrap()
+      .map(|res| res.unwrap().1)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      records[0],
+      BundleRecord::Ledger {
+        handle: handle.clone(),
+        height: 2
+      }
+    );
+    for height in 0..=2 {
+      match &records[1 + height] {
+        BundleRecord::Entry {
+          height: entry_height,
+          block,
+          ..
+        } => {
+          assert_eq!(*entry_height, height as u64);
+          if height > 0 {
+            assert_eq!(*block, format!("block_{}", height).into_bytes());
+          }
+        },
+        record => panic!("unexpected record {:?}", record),
+      }
+    }
+    assert!(matches!(records[4], BundleRecord::Tail { height: 2, .. }));
+    let (_view_tail, view_height, _) = coordinator.read_view_tail().await.unwrap();
+    assert_eq!(records.len(), 4 + view_height + 2);
+    assert!(matches!(records[5], BundleRecord::View { index: 1, .. }));
+
+    // an unknown ledger ends the stream with an error
+    let mut stream = export(b"unknown".to_vec()).await.unwrap().into_inner();
+    let status = stream.next().await.unwrap().unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+  }
+
+  /// times the recovery of a coordinator over a store of `NIMBLE_RECOVERY_LEDGERS` ledgers (10000
+  /// by default) whose endorsers are up to date, which scans the store twice: once to repair the
+  /// endorsers and once to reconcile the ledgers
+  #[tokio::test(flavor = "multi_thread")]
+  #[ignore]
+  async fn test_recovery_benchmark() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let num_ledgers = match std::env::var("NIMBLE_RECOVERY_LEDGERS") {
+      Ok(x) => x.parse::<usize>().unwrap(),
+      Err(_) => 10_000,
+    };
+    let _endorser1 = launch_endorser(&endorser_cmd, endorser_args.clone() + " -p 9210");
+    let _endorser2 = launch_endorser(&endorser_cmd, endorser_args + " -p 9211");
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let res = coordinator
+      .replace_endorsers(&[
+        "http://[::1]:9210".to_string(),
+        "http://[::1]:9211".to_string(),
+      ])
+      .await;
+    assert!(res.is_ok());
+    let coordinator = Arc::new(coordinator);
+
+    let start = std::time::Instant::now();
+    let num_tasks = 64;
+    let tasks = (0..num_tasks)
+      .map(|task| {
+        let coordinator = coordinator.clone();
+        tokio::spawn(async move {
+          for _ in (task..num_ledgers).step_by(num_tasks) {
+            let handle = rand::random::<[u8; 16]>();
+            let res = coordinator
+              .create_ledger(None, &handle, b"genesis", &[], &[])
+              .await;
+            assert!(res.is_ok());
+          }
+        })
+      })
+      .collect::<Vec<_>>();
+    for task in tasks {
+      task.await.unwrap();
+    }
+    println!("created {} ledgers in {:?}", num_ledgers, start.elapsed());
+
+    // a second coordinator recovers from the same store
+    let mut recovered = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    recovered.ledger_store = coordinator.ledger_store.clone();
+    let start = std::time::Instant::now();
+    recovered.recover().await.unwrap();
+    println!("recovered {} ledgers in {:?}", num_ledgers, start.elapsed());
+
+    // the progress of the last scan covers every ledger
+    let progress = recovered.health().recovery_progress().unwrap();
+    assert_eq!(progress.scanned, num_ledgers);
+    assert_eq!(progress.total, Some(num_ledgers));
+  }
+
+  /// checks that the memory that the coordinator and an endorser report moves as ledgers are
+  /// created and sealed
+  #[tokio::test]
+  #[ignore]
+  async fn test_memory_accounting() {
+    let endorser_cmd = match std::env::var_os("ENDORSER_CMD") {
+      None => panic!("The ENDORSER_CMD environment variable is not specified"),
+      Some(x) => x,
+    };
+    let endorser_args = match std::env::var_os("ENDORSER_ARGS") {
+      None => String::from(""),
+      Some(x) => x.into_string().unwrap(),
+    };
+    let _endorser = launch_endorser(&endorser_cmd, endorser_args + " -p 9212");
+
+    let coordinator = CoordinatorState::new("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .unwrap();
+    let res = coordinator
+      .replace_endorsers(&["http://[::1]:9212".to_string()])
+      .await;
+    assert!(res.is_ok());
+    let endorser_usage = |statuses: Vec<EndorserStatus>| {
+      assert_eq!(statuses.len(), 1);
+      (
+        statuses[0].num_ledgers.unwrap(),
+        statuses[0].tail_map_bytes.unwrap(),
+      )
+    };
+    let (num_ledgers, empty_bytes) =
+      endorser_usage(coordinator.get_endorser_statuses().await.unwrap());
+    assert_eq!(num_ledgers, 0);
+
+    // every ledger created takes a tail in the endorser and a lock in the coordinator
+    let handles = (0..10u8).map(|i| vec![i; 16]).collect::<Vec<_>>();
+    for handle in &handles {
+      let res = coordinator
+        .create_ledger(None, handle, b"genesis", &[], &[])
+        .await;
+      assert!(res.is_ok());
+    }
+    let (num_ledgers, created_bytes) =
+      endorser_usage(coordinator.get_endorser_statuses().await.unwrap());
+    assert_eq!(num_ledgers, handles.len());
+    assert!(created_bytes > empty_bytes);
+    assert_eq!(
+      coordinator
+        .ledger_store
+        .estimate_num_ledgers()
+        .await
+        .unwrap(),
+      Some(handles.len())
+    );
+    let num_locks = coordinator.ledger_locks.num_locks();
+    assert!(num_locks > 0 && num_locks <= handles.len());
+
+    // a seal replaces the tail in the endorser, and its intent is committed once it is endorsed
+    let res = coordinator.seal_ledger(&handles[0], Deadline::none()).await;
+    assert!(res.is_ok());
+    let (num_ledgers, sealed_bytes) =
+      endorser_usage(coordinator.get_endorser_statuses().await.unwrap());
+    assert_eq!(num_ledgers, handles.len());
+    assert_ne!(sealed_bytes, created_bytes);
+    assert_eq!(coordinator.num_pending_intents(), 0);
+    assert!(coordinator.ledger_locks.num_locks() >= 1);
+  }
+
+  #[test]
+  pub fn test_endorser_file_and_store_dir_checks() {
+    let contents = "# endorsers\nhttp://[::1]:9090\n\n  http://[::1]:9091  # second\n";
+    assert_eq!(
+      parse_endorser_file(contents),
+      vec![
+        "http://[::1]:9090".to_string(),
+        "http://[::1]:9091".to_string()
+      ]
+    );
+    assert!(parse_endorser_file("# nothing here\n").is_empty());
+
+    let dir = std::env::temp_dir().join(format!("nimble-cli-{}", rand::random::<u64>()));
+    assert!(check_writable_dir(dir.to_str().unwrap()).is_ok());
+    let file = dir.join("file");
+    std::fs::write(&file, b"").unwrap();
+    assert!(check_writable_dir(file.to_str().unwrap()).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
 }
@@ -0,0 +1,674 @@
+//! Prometheus metrics of the coordinator, kept in a registry of the `prometheus` crate and
+//! rendered with its text encoder.
+//!
+//! The values of labels are names that the coordinator picks (gRPC methods and codes, stages of
+//! an append, operations of the ledger store), never handles or other values that clients pick,
+//! so the number of series is bounded.
+use crate::health::Health;
+use ledger::{Block, Handle, NimbleDigest, Nonce, Nonces, Receipts};
+use prometheus::{
+  core::Collector, proto::MetricFamily, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec,
+  IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::{
+  future::Future,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+use store::{
+  errors::LedgerStoreError,
+  ledger::{
+    AppendToken, IntentRecord, LeaseRecord, LedgerEntry, LedgerInfo, LedgerStore, TenantRecord,
+  },
+};
+use tonic::Code;
+
+/// the operations of the ledger store that write to it
+const STORE_WRITES: &[&str] = &[
+  "create_ledger",
+  "append_ledger",
+  "attach_ledger_receipts",
+  "attach_ledger_nonce",
+  "append_view_ledger",
+  "attach_view_ledger_receipts",
+  "purge_ledger_blocks",
+  "write_tenant",
+  "write_intent",
+  "commit_intent",
+  "write_append_tokens",
+];
+
+/// seconds: the upper bounds of the buckets of latency histograms
+const LATENCY_BUCKETS: [f64; 14] = [
+  0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// the name of a gRPC code as gRPC spells it
+fn code_name(code: Code) -> &'static str {
+  match code {
+    Code::Ok => "OK",
+    Code::Cancelled => "CANCELLED",
+    Code::Unknown => "UNKNOWN",
+    Code::InvalidArgument => "INVALID_ARGUMENT",
+    Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+    Code::NotFound => "NOT_FOUND",
+    Code::AlreadyExists => "ALREADY_EXISTS",
+    Code::PermissionDenied => "PERMISSION_DENIED",
+    Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+    Code::FailedPrecondition => "FAILED_PRECONDITION",
+    Code::Aborted => "ABORTED",
+    Code::OutOfRange => "OUT_OF_RANGE",
+    Code::Unimplemented => "UNIMPLEMENTED",
+    Code::Internal => "INTERNAL",
+    Code::Unavailable => "UNAVAILABLE",
+    Code::DataLoss => "DATA_LOSS",
+    Code::Unauthenticated => "UNAUTHENTICATED",
+  }
+}
+
+/// registers `metric` with `registry`; the metrics of the coordinator have fixed names that are
+/// valid and distinct, so registering them cannot fail
+fn register<M: Collector + Clone + 'static>(registry: &Registry, metric: M) -> M {
+  registry
+    .register(Box::new(metric.clone()))
+    .expect("the metrics of the coordinator have valid and distinct names");
+  metric
+}
+
+fn counters(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+  let counters = IntCounterVec::new(Opts::new(name, help), labels).expect("a valid counter");
+  register(registry, counters)
+}
+
+fn histograms(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+  let opts = HistogramOpts::new(name, help).buckets(LATENCY_BUCKETS.to_vec());
+  register(
+    registry,
+    HistogramVec::new(opts, labels).expect("a valid histogram"),
+  )
+}
+
+fn histogram(registry: &Registry, name: &str, help: &str) -> Histogram {
+  let opts = HistogramOpts::new(name, help).buckets(LATENCY_BUCKETS.to_vec());
+  register(
+    registry,
+    Histogram::with_opts(opts).expect("a valid histogram"),
+  )
+}
+
+/// encodes `families` in the text exposition format, appending them to `out`
+fn encode(families: &[MetricFamily], out: &mut String) {
+  if let Err(e) = TextEncoder::new().encode_utf8(families, out) {
+    tracing::warn!(error = %e, "failed to encode metrics");
+  }
+}
+
+/// the metrics of the coordinator
+pub struct Metrics {
+  registry: Registry,
+  requests: IntCounterVec,
+  request_duration: HistogramVec,
+  append_duration: HistogramVec,
+  receipt_failures: IntCounter,
+  quorum_shortfalls: IntCounterVec,
+  store_duration: HistogramVec,
+  store_errors: IntCounterVec,
+  ledger_lock_wait: Histogram,
+  view_change_duration: Histogram,
+  nonce_reuses: IntCounterVec,
+}
+
+impl Default for Metrics {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Metrics {
+  pub fn new() -> Self {
+    let registry = Registry::new();
+    let requests = counters(
+      &registry,
+      "nimble_requests_total",
+      "The calls to the client service, by method and gRPC code.",
+      &["method", "code"],
+    );
+    let request_duration = histograms(
+      &registry,
+      "nimble_request_duration_seconds",
+      "The latency of calls to the client service.",
+      &["method"],
+    );
+    let append_duration = histograms(
+      &registry,
+      "nimble_append_duration_seconds",
+      "The latency of the stages of appends: the endorser fan-out and the ledger store writes.",
+      &["stage"],
+    );
+    let receipt_failures = register(
+      &registry,
+      IntCounter::new(
+        "nimble_receipt_failures_total",
+        "The receipts of endorsers that failed to parse or verify.",
+      )
+      .expect("a valid counter"),
+    );
+    let quorum_shortfalls = counters(
+      &registry,
+      "nimble_quorum_shortfalls_total",
+      "The fan-outs to the endorsers that returned fewer receipts than a quorum.",
+      &["op"],
+    );
+    let store_duration = histograms(
+      &registry,
+      "nimble_store_duration_seconds",
+      "The latency of calls to the ledger store, by backend and operation.",
+      &["backend", "op"],
+    );
+    let store_errors = counters(
+      &registry,
+      "nimble_store_errors_total",
+      "The calls to the ledger store that failed, including conditional writes that lost.",
+      &["backend", "op"],
+    );
+    let ledger_lock_wait = histogram(
+      &registry,
+      "nimble_ledger_lock_wait_seconds",
+      "The time that appends waited for the lock of their ledger.",
+    );
+    let view_change_duration = histogram(
+      &registry,
+      "nimble_view_change_duration_seconds",
+      "The duration of view changes.",
+    );
+    let nonce_reuses = counters(
+      &registry,
+      "nimble_nonce_reuses_total",
+      "The reads that reused a nonce of their client, by method and whether they were rejected.",
+      &["method", "action"],
+    );
+    Metrics {
+      registry,
+      requests,
+      request_duration,
+      append_duration,
+      receipt_failures,
+      quorum_shortfalls,
+      store_duration,
+      store_errors,
+      ledger_lock_wait,
+      view_change_duration,
+      nonce_reuses,
+    }
+  }
+
+  /// records a call to the client service
+  pub fn observe_request(&self, method: &'static str, code: Code, elapsed: Duration) {
+    self
+      .requests
+      .with_label_values(&[method, code_name(code)])
+      .inc();
+    self
+      .request_duration
+      .with_label_values(&[method])
+      .observe(elapsed.as_secs_f64());
+  }
+
+  /// records how long a stage of an append took: `endorse` for the fan-out to the endorsers,
+  /// and `persist` for writing the block and its receipts to the ledger store
+  pub fn observe_append_stage(&self, stage: &'static str, elapsed: Duration) {
+    self
+      .append_duration
+      .with_label_values(&[stage])
+      .observe(elapsed.as_secs_f64());
+  }
+
+  /// records a receipt of an endorser that was rejected because it did not parse or verify
+  pub fn record_receipt_failure(&self) {
+    self.receipt_failures.inc();
+  }
+
+  /// records a write or read whose fan-out to the endorsers returned fewer receipts than a quorum
+  pub fn record_quorum_shortfall(&self, op: &'static str) {
+    self.quorum_shortfalls.with_label_values(&[op]).inc();
+  }
+
+  /// records a call to the ledger store
+  pub fn observe_store(
+    &self,
+    backend: &'static str,
+    op: &'static str,
+    elapsed: Duration,
+    ok: bool,
+  ) {
+    self
+      .store_duration
+      .with_label_values(&[backend, op])
+      .observe(elapsed.as_secs_f64());
+    if !ok {
+      self.store_errors.with_label_values(&[backend, op]).inc();
+    }
+  }
+
+  /// records how long an append waited for the lock of its ledger
+  pub fn observe_ledger_lock_wait(&self, elapsed: Duration) {
+    self.ledger_lock_wait.observe(elapsed.as_secs_f64());
+  }
+
+  pub fn observe_view_change(&self, elapsed: Duration) {
+    self.view_change_duration.observe(elapsed.as_secs_f64());
+  }
+
+  /// records a read that reused a nonce of its client, which was rejected or only logged
+  pub fn record_nonce_reuse(&self, method: &'static str, rejected: bool) {
+    let action = if rejected { "rejected" } else { "logged" };
+    self.nonce_reuses.with_label_values(&[method, action]).inc();
+  }
+
+  /// renders the metrics, along with gauges that the caller reads at the time of the scrape,
+  /// given as (name, help, value)
+  pub fn render(&self, gauges: &[(&str, &str, f64)]) -> String {
+    let mut families = self.registry.gather();
+    // the gauges live only for the scrape, in a registry of their own
+    let scrape = Registry::new();
+    for (name, help, value) in gauges {
+      match Gauge::new(*name, *help) {
+        Ok(gauge) => {
+          gauge.set(*value);
+          if let Err(e) = scrape.register(Box::new(gauge)) {
+            tracing::warn!(name = %name, error = %e, "failed to register a gauge");
+          }
+        },
+        Err(e) => tracing::warn!(name = %name, error = %e, "failed to create a gauge"),
+      }
+    }
+    families.extend(scrape.gather());
+    let mut out = String::new();
+    encode(&families, &mut out);
+    out
+  }
+}
+
+/// renders a gauge with a series per value of `label`, given as (label value, value), which the
+/// caller reads at the time of the scrape
+pub fn render_labeled_gauge(
+  out: &mut String,
+  name: &str,
+  help: &str,
+  label: &str,
+  series: &[(String, f64)],
+) {
+  let gauges = match GaugeVec::new(Opts::new(name, help), &[label]) {
+    Ok(gauges) => gauges,
+    Err(e) => {
+      tracing::warn!(name = %name, error = %e, "failed to create a gauge");
+      return;
+    },
+  };
+  for (value, gauge) in series {
+    gauges.with_label_values(&[value.as_str()]).set(*gauge);
+  }
+  encode(&gauges.collect(), out);
+}
+
+/// the name of a backend of the ledger store as a label value
+pub fn backend_label(ledger_store_type: &str) -> &'static str {
+  match ledger_store_type {
+    "mongodb_cosmos" => "mongodb_cosmos",
+    "table" => "table",
+    "filestore" => "filestore",
+    "rocksdb" => "rocksdb",
+    _ => "memory",
+  }
+}
+
+/// a ledger store that records the latency and failures of the calls to the store it wraps
+pub struct MeteredLedgerStore {
+  inner: Box<dyn LedgerStore + Send + Sync>,
+  backend: &'static str,
+  metrics: Arc<Metrics>,
+  /// told whether the writes to the store fail, if set
+  health: Option<Arc<Health>>,
+}
+
+impl MeteredLedgerStore {
+  pub fn new(
+    inner: Box<dyn LedgerStore + Send + Sync>,
+    backend: &'static str,
+    metrics: Arc<Metrics>,
+  ) -> Self {
+    MeteredLedgerStore {
+      inner,
+      backend,
+      metrics,
+      health: None,
+    }
+  }
+
+  pub fn with_health(mut self, health: Arc<Health>) -> Self {
+    self.health = Some(health);
+    self
+  }
+
+  async fn timed<T, F>(&self, op: &'static str, call: F) -> Result<T, LedgerStoreError>
+  where
+    F: Future<Output = Result<T, LedgerStoreError>>,
+  {
+    let start = Instant::now();
+    let res = call.await;
+    self
+      .metrics
+      .observe_store(self.backend, op, start.elapsed(), res.is_ok());
+    if let Some(health) = &self.health {
+      if STORE_WRITES.contains(&op) {
+        health.record_store_write(res.as_ref().map(|_| ()));
+      }
+    }
+    res
+  }
+}
+
+#[tonic::async_trait]
+impl LedgerStore for MeteredLedgerStore {
+  async fn create_ledger(
+    &self,
+    handle: &NimbleDigest,
+    genesis_block: Block,
+    info: &LedgerInfo,
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed(
+        "create_ledger",
+        self.inner.create_ledger(handle, genesis_block, info),
+      )
+      .await
+  }
+
+  async fn append_ledger(
+    &self,
+    handle: &Handle,
+    block: &Block,
+    expected_height: usize,
+  ) -> Result<(usize, Nonces), LedgerStoreError> {
+    self
+      .timed(
+        "append_ledger",
+        self.inner.append_ledger(handle, block, expected_height),
+      )
+      .await
+  }
+
+  async fn attach_ledger_receipts(
+    &self,
+    handle: &Handle,
+    idx: usize,
+    receipt: &Receipts,
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed(
+        "attach_ledger_receipts",
+        self.inner.attach_ledger_receipts(handle, idx, receipt),
+      )
+      .await
+  }
+
+  async fn attach_ledger_nonce(
+    &self,
+    handle: &Handle,
+    nonce: &Nonce,
+  ) -> Result<usize, LedgerStoreError> {
+    self
+      .timed(
+        "attach_ledger_nonce",
+        self.inner.attach_ledger_nonce(handle, nonce),
+      )
+      .await
+  }
+
+  async fn read_ledger_tail(
+    &self,
+    handle: &Handle,
+  ) -> Result<(LedgerEntry, usize), LedgerStoreError> {
+    self
+      .timed("read_ledger_tail", self.inner.read_ledger_tail(handle))
+      .await
+  }
+
+  async fn read_ledger_by_index(
+    &self,
+    handle: &Handle,
+    idx: usize,
+  ) -> Result<LedgerEntry, LedgerStoreError> {
+    self
+      .timed(
+        "read_ledger_by_index",
+        self.inner.read_ledger_by_index(handle, idx),
+      )
+      .await
+  }
+
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError> {
+    self
+      .timed("read_ledger_info", self.inner.read_ledger_info(handle))
+      .await
+  }
+
+  async fn append_view_ledger(
+    &self,
+    block: &Block,
+    expected_height: usize,
+  ) -> Result<usize, LedgerStoreError> {
+    self
+      .timed(
+        "append_view_ledger",
+        self.inner.append_view_ledger(block, expected_height),
+      )
+      .await
+  }
+
+  async fn attach_view_ledger_receipts(
+    &self,
+    idx: usize,
+    receipt: &Receipts,
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed(
+        "attach_view_ledger_receipts",
+        self.inner.attach_view_ledger_receipts(idx, receipt),
+      )
+      .await
+  }
+
+  async fn read_view_ledger_tail(&self) -> Result<(LedgerEntry, usize), LedgerStoreError> {
+    self
+      .timed("read_view_ledger_tail", self.inner.read_view_ledger_tail())
+      .await
+  }
+
+  async fn read_view_ledger_by_index(&self, idx: usize) -> Result<LedgerEntry, LedgerStoreError> {
+    self
+      .timed(
+        "read_view_ledger_by_index",
+        self.inner.read_view_ledger_by_index(idx),
+      )
+      .await
+  }
+
+  async fn list_handles(
+    &self,
+    start_after: Option<&Handle>,
+    limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError> {
+    self
+      .timed("list_handles", self.inner.list_handles(start_after, limit))
+      .await
+  }
+
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError> {
+    self
+      .timed("estimate_num_ledgers", self.inner.estimate_num_ledgers())
+      .await
+  }
+
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError> {
+    self
+      .timed("estimate_size_bytes", self.inner.estimate_size_bytes())
+      .await
+  }
+
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed(
+        "purge_ledger_blocks",
+        self.inner.purge_ledger_blocks(handle, before_height),
+      )
+      .await
+  }
+
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError> {
+    self
+      .timed("read_tenant", self.inner.read_tenant(tenant))
+      .await
+  }
+
+  async fn write_tenant(
+    &self,
+    tenant: &str,
+    record: &TenantRecord,
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed("write_tenant", self.inner.write_tenant(tenant, record))
+      .await
+  }
+
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    self.timed("read_lease", self.inner.read_lease()).await
+  }
+
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed("swap_lease", self.inner.swap_lease(expected, new))
+      .await
+  }
+
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError> {
+    self
+      .timed("write_intent", self.inner.write_intent(intent))
+      .await
+  }
+
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError> {
+    self
+      .timed("commit_intent", self.inner.commit_intent(handle, height))
+      .await
+  }
+
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError> {
+    self.timed("list_intents", self.inner.list_intents()).await
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, LedgerStoreError> {
+    self
+      .timed("read_append_tokens", self.inner.read_append_tokens(handle))
+      .await
+  }
+
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError> {
+    self
+      .timed(
+        "write_append_tokens",
+        self.inner.write_append_tokens(handle, tokens),
+      )
+      .await
+  }
+
+  async fn reset_store(&self) -> Result<(), LedgerStoreError> {
+    self.timed("reset_store", self.inner.reset_store()).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{backend_label, render_labeled_gauge, MeteredLedgerStore, Metrics};
+  use ledger::{Block, NimbleDigest};
+  use std::{sync::Arc, time::Duration};
+  use store::ledger::{in_memory::InMemoryLedgerStore, LedgerInfo, LedgerStore};
+  use tonic::Code;
+
+  #[tokio::test]
+  async fn test_metrics_render() {
+    let metrics = Arc::new(Metrics::new());
+    metrics.observe_request("Append", Code::Ok, Duration::from_millis(3));
+    metrics.observe_request("Append", Code::Ok, Duration::from_millis(30));
+    metrics.observe_request("Append", Code::Unavailable, Duration::from_millis(1));
+    metrics.record_quorum_shortfall("append");
+    metrics.record_receipt_failure();
+    metrics.record_nonce_reuse("ReadLatest", false);
+
+    // the calls to the wrapped store are timed by backend and operation
+    let store = MeteredLedgerStore::new(
+      Box::new(InMemoryLedgerStore::new()),
+      backend_label("memory"),
+      metrics.clone(),
+    );
+    let handle = NimbleDigest::digest(b"handle");
+    let res = store
+      .create_ledger(&handle, Block::new(b"genesis"), &LedgerInfo::default())
+      .await;
+    assert!(res.is_ok());
+    assert!(store
+      .read_ledger_tail(&NimbleDigest::digest(b"other"))
+      .await
+      .is_err());
+
+    let text = metrics.render(&[("nimble_ledger_lock_waiters", "Appends waiting.", 2.0)]);
+    assert!(text.contains("nimble_requests_total{code=\"OK\",method=\"Append\"} 2\n"));
+    assert!(text.contains("nimble_requests_total{code=\"UNAVAILABLE\",method=\"Append\"} 1\n"));
+    assert!(
+      text.contains("nimble_request_duration_seconds_bucket{method=\"Append\",le=\"0.005\"} 2\n")
+    );
+    assert!(
+      text.contains("nimble_request_duration_seconds_bucket{method=\"Append\",le=\"+Inf\"} 3\n")
+    );
+    assert!(text.contains("nimble_request_duration_seconds_count{method=\"Append\"} 3\n"));
+    assert!(text.contains("nimble_quorum_shortfalls_total{op=\"append\"} 1\n"));
+    assert!(text.contains("nimble_receipt_failures_total 1\n"));
+    assert!(text.contains("nimble_nonce_reuses_total{action=\"logged\",method=\"ReadLatest\"} 1\n"));
+    assert!(text.contains(
+      "nimble_store_duration_seconds_count{backend=\"memory\",op=\"create_ledger\"} 1\n"
+    ));
+    assert!(
+      text.contains("nimble_store_errors_total{backend=\"memory\",op=\"read_ledger_tail\"} 1\n")
+    );
+    assert!(text.contains("nimble_ledger_lock_wait_seconds_count 0\n"));
+    assert!(
+      text.contains("# TYPE nimble_ledger_lock_waiters gauge\nnimble_ledger_lock_waiters 2\n")
+    );
+
+    // the values of labels that the coordinator does not pick, such as URIs, are escaped
+    let mut text = String::new();
+    render_labeled_gauge(
+      &mut text,
+      "nimble_endorser_clock_offset_seconds",
+      "Offsets.",
+      "endorser",
+      &[("http://a\"b".to_string(), 0.5)],
+    );
+    assert!(text.contains("nimble_endorser_clock_offset_seconds{endorser=\"http://a\\\"b\"} 0.5\n"));
+  }
+}
@@ -0,0 +1,853 @@
+//! In-memory endorsers for the tests of the coordinator. A mock endorser keeps the state that an
+//! endorser keeps and signs its receipts with a key of its own, so the coordinator and a verifier
+//! check them as they check those of an endorser. What goes wrong with it is up to the test: it
+//! answers late, fails the calls it is told to, is down, or forges its receipts, always in the
+//! same way, so the tests of quorums, retries and reconciliation need neither endorser processes
+//! nor timing. Key rotation is left to the tests with endorser processes.
+use crate::{
+  endorser_connection::{EndorserClient, EndorserConnection, EndorserConnector},
+  errors::CoordinatorError,
+};
+use ledger::{
+  compute_endorser_heartbeat_message, compute_ledger_tail_message,
+  endorser_proto::{
+    endorser_call_server::EndorserCall, ActivateReq, ActivateResp, AppendBatchReq, AppendBatchResp,
+    AppendBatchResult, AppendReq, AppendResp, EndorserMode, FinalizeStateReq, FinalizeStateResp,
+    GetPublicKeyReq, GetPublicKeyResp, GetStatusReq, GetStatusResp, HeartbeatReq, HeartbeatResp,
+    InitializeStateReq, InitializeStateResp, LedgerTailMapEntry, NewLedgerReq, NewLedgerResp,
+    ReadLatestReq, ReadLatestResp, ReadStateReq, ReadStateResp, ReadViewTailReq, ReadViewTailResp,
+    RotateKeyReq, RotateKeyResp,
+  },
+  produce_hash_of_state,
+  signature::{PrivateKey, PrivateKeyTrait, PublicKey, PublicKeyTrait, SignatureTrait},
+  view_ledger_handle, Block, CustomSerde, Handle, IdSig, MetaBlock, NimbleDigest, NimbleHashTrait,
+  Nonce, Nonces, Receipt, Receipts,
+};
+use prost::bytes::Bytes;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+  collections::{BTreeMap, HashMap, VecDeque},
+  mem,
+  sync::{Arc, Mutex},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tonic::{Code, Request, Response, Status};
+
+/// the metablock of the tail of a ledger, with the block and the nonces of the tail entry
+type Tail = (MetaBlock, Block, Nonces);
+
+struct MockState {
+  mode: EndorserMode,
+  group_identity: NimbleDigest,
+  view_tail_metablock: MetaBlock,
+  view_tail_hash: NimbleDigest,
+  view_prev_metablock: MetaBlock,
+  tails: BTreeMap<Handle, Tail>,
+  /// the heartbeats that the endorser signed
+  heartbeats: u64,
+}
+
+#[derive(Default)]
+struct Faults {
+  latency: Duration,
+  down: bool,
+  /// the codes that the next calls of each kind fail with, by the name of the call
+  failures: HashMap<&'static str, VecDeque<Code>>,
+  byzantine: bool,
+  /// the first read that a byzantine endorser answered, which it answers every read with
+  first_read: Option<ReadLatestResp>,
+  /// how far the clock of the endorser is ahead of the system clock, in milliseconds
+  clock_offset_ms: i64,
+}
+
+pub struct MockEndorser {
+  private_key: PrivateKey,
+  public_key: PublicKey,
+  state: Mutex<MockState>,
+  faults: Mutex<Faults>,
+  /// the calls that reached the endorser, by name, including the ones that failed
+  calls: Mutex<HashMap<&'static str, usize>>,
+}
+
+impl MockEndorser {
+  pub fn new() -> Arc<Self> {
+    MockEndorser::with_key(PrivateKey::new())
+  }
+
+  /// an endorser that signs with `private_key`
+  pub fn with_key(private_key: PrivateKey) -> Arc<Self> {
+    let public_key = private_key.get_public_key().unwrap();
+    Arc::new(MockEndorser {
+      private_key,
+      public_key,
+      state: Mutex::new(MockState {
+        mode: EndorserMode::Uninitialized,
+        group_identity: NimbleDigest::default(),
+        view_tail_metablock: MetaBlock::default(),
+        view_tail_hash: MetaBlock::default().hash(),
+        view_prev_metablock: MetaBlock::default(),
+        tails: BTreeMap::new(),
+        heartbeats: 0,
+      }),
+      faults: Mutex::new(Faults::default()),
+      calls: Mutex::new(HashMap::new()),
+    })
+  }
+
+  pub fn public_key(&self) -> Vec<u8> {
+    self.public_key.to_bytes()
+  }
+
+  /// makes every call wait for `latency` before it is answered
+  pub fn set_latency(&self, latency: Duration) {
+    self.faults.lock().unwrap().latency = latency;
+  }
+
+  /// makes every call fail as it does when the endorser cannot be reached
+  pub fn set_down(&self, down: bool) {
+    self.faults.lock().unwrap().down = down;
+  }
+
+  /// makes the next `times` calls named `call` fail with `code`
+  pub fn fail_next(&self, call: &'static str, code: Code, times: usize) {
+    let mut faults = self.faults.lock().unwrap();
+    let failures = faults.failures.entry(call).or_default();
+    failures.resize(failures.len() + times, code);
+  }
+
+  /// makes the endorser forge the signatures of its ledger receipts, and answer every read with
+  /// the first read it answered
+  pub fn set_byzantine(&self, byzantine: bool) {
+    self.faults.lock().unwrap().byzantine = byzantine;
+  }
+
+  /// makes the clock of the endorser, which it signs its heartbeats with, run `offset_ms` ahead of
+  /// the system clock, or behind it if negative
+  pub fn set_clock_offset(&self, offset_ms: i64) {
+    self.faults.lock().unwrap().clock_offset_ms = offset_ms;
+  }
+
+  /// the calls named `call` that reached the endorser
+  pub fn num_calls(&self, call: &str) -> usize {
+    self.calls.lock().unwrap().get(call).copied().unwrap_or(0)
+  }
+
+  /// all the calls that reached the endorser
+  pub fn total_calls(&self) -> usize {
+    self.calls.lock().unwrap().values().sum()
+  }
+
+  /// the height of the ledger `handle` in the endorser, if it has the ledger
+  pub fn height(&self, handle: &Handle) -> Option<usize> {
+    let state = self.state.lock().unwrap();
+    state.tails.get(handle).map(|tail| tail.0.get_height())
+  }
+
+  /// counts a call, and waits or fails it as the faults of the endorser say
+  async fn enter(&self, call: &'static str) -> Result<(), Status> {
+    *self.calls.lock().unwrap().entry(call).or_default() += 1;
+    let (latency, failure) = {
+      let mut faults = self.faults.lock().unwrap();
+      if faults.down {
+        return Err(Status::unavailable("the endorser is down"));
+      }
+      let failure = faults.failures.get_mut(call).and_then(VecDeque::pop_front);
+      (faults.latency, failure)
+    };
+    if !latency.is_zero() {
+      tokio::time::sleep(latency).await;
+    }
+    match failure {
+      Some(code) => Err(Status::new(
+        code,
+        format!("{} failed as the test asked", call),
+      )),
+      None => Ok(()),
+    }
+  }
+
+  fn is_byzantine(&self) -> bool {
+    self.faults.lock().unwrap().byzantine
+  }
+
+  fn sign(&self, message: &NimbleDigest) -> IdSig {
+    let signature = self.private_key.sign(&message.to_bytes()).unwrap();
+    IdSig::new(self.public_key.clone(), signature)
+  }
+
+  /// a receipt on `metablock`, the tail of the ledger `handle`, which signs `tail_hash`; a
+  /// byzantine endorser signs another message
+  fn sign_tail(
+    &self,
+    state: &MockState,
+    handle: &Handle,
+    tail_hash: &NimbleDigest,
+    metablock: MetaBlock,
+  ) -> Receipt {
+    let id_sig = if self.is_byzantine() {
+      let forged = PrivateKey::new().sign(b"forged").unwrap();
+      IdSig::new(self.public_key.clone(), forged)
+    } else {
+      let message = compute_ledger_tail_message(
+        &state.group_identity,
+        &state.view_tail_hash,
+        handle,
+        tail_hash,
+      );
+      self.sign(&message)
+    };
+    Receipt::new(state.view_tail_hash, metablock, id_sig)
+  }
+
+  /// a receipt on the tail of the view ledger, with the ledger tails as the view
+  fn sign_view(&self, state: &MockState, ledger_tail_map: &Vec<LedgerTailMapEntry>) -> Receipt {
+    let view = produce_hash_of_state(ledger_tail_map);
+    let message = state
+      .group_identity
+      .digest_with(&view.digest_with(&state.view_tail_hash));
+    Receipt::new(view, state.view_tail_metablock.clone(), self.sign(&message))
+  }
+
+  fn append_view(
+    &self,
+    state: &mut MockState,
+    ledger_tail_map: &Vec<LedgerTailMapEntry>,
+    block_hash: &NimbleDigest,
+    expected_height: usize,
+  ) -> Result<Receipt, Status> {
+    let metablock = state
+      .view_tail_metablock
+      .next(block_hash)
+      .ok_or_else(|| Status::out_of_range("Ledger height overflow"))?;
+    if expected_height < metablock.get_height() {
+      return Err(Status::invalid_argument("Invalid ledger height"));
+    }
+    if expected_height > metablock.get_height() {
+      return Err(Status::failed_precondition(
+        "View ledger height is out of order",
+      ));
+    }
+    state.view_prev_metablock = mem::replace(&mut state.view_tail_metablock, metablock);
+    state.view_tail_hash = state.view_tail_metablock.hash();
+    Ok(self.sign_view(state, ledger_tail_map))
+  }
+
+  fn check_active(state: &MockState) -> Result<(), Status> {
+    match state.mode {
+      EndorserMode::Uninitialized | EndorserMode::Initialized => {
+        Err(Status::internal("Endorser is not active"))
+      },
+      EndorserMode::Finalized => Err(Status::unavailable("Endorser is already finalized")),
+      _ => Ok(()),
+    }
+  }
+
+  fn ledger_tail_map(state: &MockState) -> Vec<LedgerTailMapEntry> {
+    state
+      .tails
+      .iter()
+      .map(|(handle, (metablock, block, nonces))| LedgerTailMapEntry {
+        handle: handle.to_bytes().into(),
+        height: metablock.get_height() as u64,
+        metablock: metablock.to_bytes().into(),
+        block: block.to_bytes().into(),
+        nonces: nonces.to_bytes().into(),
+      })
+      .collect()
+  }
+
+  fn do_initialize_state(&self, req: InitializeStateReq) -> Result<InitializeStateResp, Status> {
+    let invalid = |_e| Status::invalid_argument("Invalid initial state");
+    let group_identity = NimbleDigest::from_bytes(&req.group_identity).map_err(invalid)?;
+    let view_tail_metablock = MetaBlock::from_bytes(&req.view_tail_metablock).map_err(invalid)?;
+    let block_hash = NimbleDigest::from_bytes(&req.block_hash).map_err(invalid)?;
+    let expected_height = req.expected_height as usize;
+    if !req.public_key.is_empty() && req.public_key[..] != self.public_key.to_bytes()[..] {
+      return Err(Status::invalid_argument("Unknown endorser key"));
+    }
+    let mut tails = Vec::with_capacity(req.ledger_tail_map.len());
+    for entry in &req.ledger_tail_map {
+      let tail = (
+        MetaBlock::from_bytes(&entry.metablock).map_err(invalid)?,
+        Block::from_bytes(&entry.block).map_err(invalid)?,
+        Nonces::from_bytes(&entry.nonces).map_err(invalid)?,
+      );
+      tails.push((
+        NimbleDigest::from_bytes(&entry.handle).map_err(invalid)?,
+        tail,
+      ));
+    }
+
+    let mut state = self.state.lock().unwrap();
+    // an endorser that finalized the previous view into this very view entry joins it as well
+    let rejoins = match state.mode {
+      EndorserMode::Uninitialized => false,
+      EndorserMode::Initialized | EndorserMode::Finalized => {
+        if state.group_identity != group_identity
+          || state.view_prev_metablock != view_tail_metablock
+          || state.view_tail_metablock.get_height() != expected_height
+          || *state.view_tail_metablock.get_block_hash() != block_hash
+        {
+          return Err(Status::already_exists("Endorser is already initialized"));
+        }
+        true
+      },
+      _ => return Err(Status::already_exists("Endorser is already initialized")),
+    };
+    if rejoins {
+      state.tails.clear();
+    }
+    state.tails.extend(tails);
+    state.mode = EndorserMode::Initialized;
+
+    let receipt = if rejoins {
+      self.sign_view(&state, &req.ledger_tail_map)
+    } else {
+      state.view_prev_metablock = mem::replace(&mut state.view_tail_metablock, view_tail_metablock);
+      state.view_tail_hash = state.view_tail_metablock.hash();
+      state.group_identity = group_identity;
+      self.append_view(
+        &mut state,
+        &req.ledger_tail_map,
+        &block_hash,
+        expected_height,
+      )?
+    };
+    Ok(InitializeStateResp {
+      receipt: receipt.to_bytes().into(),
+    })
+  }
+
+  fn do_new_ledger(&self, req: NewLedgerReq) -> Result<NewLedgerResp, Status> {
+    let invalid = |_e| Status::invalid_argument("Invalid input sizes");
+    let handle = NimbleDigest::from_bytes(&req.handle).map_err(invalid)?;
+    let block_hash = NimbleDigest::from_bytes(&req.block_hash).map_err(invalid)?;
+    let block = Block::from_bytes(&req.block).map_err(invalid)?;
+
+    let mut state = self.state.lock().unwrap();
+    Self::check_active(&state)?;
+    // a retried create of a ledger that has not grown since gets the same receipt again
+    let metablock = MetaBlock::genesis(&block_hash);
+    match state.tails.get(&handle) {
+      Some(tail) if tail.0 != metablock => return Err(Status::already_exists("Ledger exists")),
+      Some(_tail) => {},
+      None => {
+        state
+          .tails
+          .insert(handle, (metablock.clone(), block, Nonces::new()));
+      },
+    }
+    let receipt = self.sign_tail(&state, &handle, &metablock.hash(), metablock);
+    Ok(NewLedgerResp {
+      receipt: receipt.to_bytes().into(),
+    })
+  }
+
+  fn do_append(&self, req: AppendReq) -> Result<AppendResp, Status> {
+    let invalid = |_e| Status::invalid_argument("Invalid input sizes");
+    let handle = NimbleDigest::from_bytes(&req.handle).map_err(invalid)?;
+    let block_hash = NimbleDigest::from_bytes(&req.block_hash).map_err(invalid)?;
+    let block = Block::from_bytes(&req.block).map_err(invalid)?;
+    let nonces = Nonces::from_bytes(&req.nonces).map_err(invalid)?;
+    let expected_tail = if req.expected_tail.is_empty() {
+      None
+    } else {
+      Some(NimbleDigest::from_bytes(&req.expected_tail).map_err(invalid)?)
+    };
+    let expected_height = req.expected_height as usize;
+    if expected_height == 0 {
+      return Err(Status::invalid_argument("Invalid expected height"));
+    }
+
+    let mut state = self.state.lock().unwrap();
+    Self::check_active(&state)?;
+    let tail = state
+      .tails
+      .get_mut(&handle)
+      .ok_or_else(|| Status::not_found("Ledger handle not found"))?;
+    let next = tail
+      .0
+      .next(&block_hash)
+      .ok_or_else(|| Status::out_of_range("Ledger height overflow"))?;
+    // a retried append of the tail is endorsed again
+    let metablock =
+      if expected_height == tail.0.get_height() && *tail.0.get_block_hash() == block_hash {
+        tail.0.clone()
+      } else {
+        if expected_height < next.get_height() {
+          return Err(Status::already_exists("Ledger exists"));
+        }
+        if expected_height > next.get_height() {
+          // the coordinator brings the endorser up to date from the height in the details
+          let height = tail.0.get_height() as u64;
+          return Err(Status::with_details(
+            Code::FailedPrecondition,
+            "Out of order",
+            Bytes::copy_from_slice(&height.to_le_bytes()),
+          ));
+        }
+        if let Some(expected_tail) = expected_tail {
+          if *tail.0.get_block_hash() != expected_tail {
+            return Err(Status::aborted(
+              "Ledger tail does not match the expected tail",
+            ));
+          }
+        }
+        *tail = (next.clone(), block, nonces);
+        next
+      };
+    let receipt = self.sign_tail(&state, &handle, &metablock.hash(), metablock);
+    Ok(AppendResp {
+      receipt: receipt.to_bytes().into(),
+    })
+  }
+
+  fn do_read_latest(&self, req: ReadLatestReq) -> Result<ReadLatestResp, Status> {
+    let handle = NimbleDigest::from_bytes(&req.handle)
+      .map_err(|_e| Status::invalid_argument("Invalid handle size"))?;
+    let nonce =
+      Nonce::try_from_bytes(&req.nonce).map_err(|_e| Status::invalid_argument("Invalid nonce"))?;
+
+    let state = self.state.lock().unwrap();
+    Self::check_active(&state)?;
+    let (metablock, block, nonces) = state
+      .tails
+      .get(&handle)
+      .cloned()
+      .ok_or_else(|| Status::not_found("Ledger handle not found"))?;
+    let tail_hash = metablock.hash().digest_with_bytes(&nonce.to_bytes());
+    let receipt = self.sign_tail(&state, &handle, &tail_hash, metablock);
+    let resp = ReadLatestResp {
+      receipt: receipt.to_bytes().into(),
+      block: block.to_bytes().into(),
+      nonces: nonces.to_bytes().into(),
+    };
+
+    let mut faults = self.faults.lock().unwrap();
+    if faults.byzantine {
+      return Ok(faults.first_read.get_or_insert(resp).clone());
+    }
+    Ok(resp)
+  }
+
+  fn do_read_view_tail(&self, req: ReadViewTailReq) -> Result<ReadViewTailResp, Status> {
+    let nonce =
+      Nonce::try_from_bytes(&req.nonce).map_err(|_e| Status::invalid_argument("Invalid nonce"))?;
+
+    let state = self.state.lock().unwrap();
+    Self::check_active(&state)?;
+    let tail_hash = state.view_tail_hash.digest_with_bytes(&nonce.to_bytes());
+    let message = compute_ledger_tail_message(
+      &state.group_identity,
+      &state.view_tail_hash,
+      &view_ledger_handle(),
+      &tail_hash,
+    );
+    let receipt = Receipt::new(
+      state.view_tail_hash,
+      state.view_tail_metablock.clone(),
+      self.sign(&message),
+    );
+    Ok(ReadViewTailResp {
+      receipt: receipt.to_bytes().into(),
+    })
+  }
+
+  fn do_finalize_state(&self, req: FinalizeStateReq) -> Result<FinalizeStateResp, Status> {
+    let block_hash = NimbleDigest::from_bytes(&req.block_hash)
+      .map_err(|_e| Status::invalid_argument("Invalid input sizes"))?;
+    let expected_height = req.expected_height as usize;
+
+    let mut state = self.state.lock().unwrap();
+    // an endorser that already joined the view it is asked to finalize into only re-signs
+    let joined_view = state.mode == EndorserMode::Initialized
+      && state.view_tail_metablock.get_height() == expected_height
+      && *state.view_tail_metablock.get_block_hash() == block_hash;
+    if !joined_view
+      && (state.mode == EndorserMode::Uninitialized || state.mode == EndorserMode::Initialized)
+    {
+      return Err(Status::internal("Endorser is not active"));
+    }
+
+    let ledger_tail_map = Self::ledger_tail_map(&state);
+    let receipt = if joined_view || state.mode == EndorserMode::Finalized {
+      self.sign_view(&state, &ledger_tail_map)
+    } else {
+      state.mode = EndorserMode::Finalized;
+      self.append_view(&mut state, &ledger_tail_map, &block_hash, expected_height)?
+    };
+    Ok(FinalizeStateResp {
+      receipt: receipt.to_bytes().into(),
+      ledger_tail_map,
+    })
+  }
+
+  fn do_read_state(&self) -> ReadStateResp {
+    let state = self.state.lock().unwrap();
+    let ledger_tail_map = Self::ledger_tail_map(&state);
+    ReadStateResp {
+      receipt: self.sign_view(&state, &ledger_tail_map).to_bytes().into(),
+      mode: state.mode as i32,
+      ledger_tail_map,
+    }
+  }
+
+  fn do_activate(&self, req: ActivateReq) -> Result<ActivateResp, Status> {
+    let receipts = Receipts::from_bytes(&req.receipts)
+      .map_err(|_e| Status::invalid_argument("Invalid receipts"))?;
+
+    let mut state = self.state.lock().unwrap();
+    match state.mode {
+      EndorserMode::Uninitialized => {
+        return Err(Status::unimplemented("Endorser is not initialized"));
+      },
+      EndorserMode::Active => return Err(Status::internal("Endorser is already active")),
+      EndorserMode::Finalized => {
+        return Err(Status::unavailable("Endorser is already finalized"));
+      },
+      _ => {},
+    }
+    let res = receipts.verify_view_change(
+      &req.old_config,
+      &req.new_config,
+      &self.public_key,
+      &state.group_identity,
+      &state.view_prev_metablock,
+      &state.view_tail_metablock,
+      &req.ledger_tail_maps,
+      &req.ledger_chunks,
+    );
+    if res.is_err() {
+      return Err(Status::internal("Failed to verify the view change"));
+    }
+    state.mode = EndorserMode::Active;
+    Ok(ActivateResp {})
+  }
+
+  fn do_heartbeat(&self, req: HeartbeatReq) -> Result<HeartbeatResp, Status> {
+    let nonce =
+      Nonce::try_from_bytes(&req.nonce).map_err(|_e| Status::invalid_argument("Invalid nonce"))?;
+    let counter = {
+      let mut state = self.state.lock().unwrap();
+      state.heartbeats += 1;
+      state.heartbeats
+    };
+    let now_ms = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_millis() as i64)
+      .unwrap_or_default();
+    let timestamp_ms = (now_ms + self.faults.lock().unwrap().clock_offset_ms).max(0) as u64;
+    let message = compute_endorser_heartbeat_message(&nonce, counter, timestamp_ms);
+    Ok(HeartbeatResp {
+      counter,
+      timestamp_ms,
+      pk: self.public_key().into(),
+      signature: self.sign(&message).get_sig().to_bytes().into(),
+    })
+  }
+
+  fn do_get_status(&self) -> GetStatusResp {
+    let state = self.state.lock().unwrap();
+    let tail_map_bytes = state
+      .tails
+      .values()
+      .map(|(_metablock, block, nonces)| {
+        mem::size_of::<(Handle, Tail)>() + block.len() + nonces.len() * Nonce::num_bytes()
+      })
+      .sum::<usize>();
+    GetStatusResp {
+      mode: state.mode as i32,
+      num_ledgers: state.tails.len() as u64,
+      tail_map_bytes: tail_map_bytes as u64,
+      cpu_micros: 0,
+    }
+  }
+}
+
+#[tonic::async_trait]
+impl EndorserConnection for MockEndorser {
+  async fn get_public_key(
+    &self,
+    _request: Request<GetPublicKeyReq>,
+  ) -> Result<Response<GetPublicKeyResp>, Status> {
+    self.enter("get_public_key").await?;
+    Ok(Response::new(GetPublicKeyResp {
+      pk: self.public_key().into(),
+    }))
+  }
+
+  async fn new_ledger(
+    &self,
+    request: Request<NewLedgerReq>,
+  ) -> Result<Response<NewLedgerResp>, Status> {
+    self.enter("new_ledger").await?;
+    self.do_new_ledger(request.into_inner()).map(Response::new)
+  }
+
+  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+    self.enter("append").await?;
+    self.do_append(request.into_inner()).map(Response::new)
+  }
+
+  async fn append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    self.enter("append_batch").await?;
+    let results = request
+      .into_inner()
+      .items
+      .into_iter()
+      .map(|item| match self.do_append(item) {
+        Ok(AppendResp { receipt }) => AppendBatchResult {
+          receipt,
+          code: Code::Ok as i32,
+          message: String::new(),
+          details: Bytes::new(),
+        },
+        Err(status) => AppendBatchResult {
+          receipt: Bytes::new(),
+          code: status.code() as i32,
+          message: status.message().to_string(),
+          details: Bytes::copy_from_slice(status.details()),
+        },
+      })
+      .collect();
+    Ok(Response::new(AppendBatchResp { results }))
+  }
+
+  async fn read_latest(
+    &self,
+    request: Request<ReadLatestReq>,
+  ) -> Result<Response<ReadLatestResp>, Status> {
+    self.enter("read_latest").await?;
+    self.do_read_latest(request.into_inner()).map(Response::new)
+  }
+
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status> {
+    self.enter("read_view_tail").await?;
+    self
+      .do_read_view_tail(request.into_inner())
+      .map(Response::new)
+  }
+
+  async fn initialize_state(
+    &self,
+    request: Request<InitializeStateReq>,
+  ) -> Result<Response<InitializeStateResp>, Status> {
+    self.enter("initialize_state").await?;
+    self
+      .do_initialize_state(request.into_inner())
+      .map(Response::new)
+  }
+
+  async fn finalize_state(
+    &self,
+    request: Request<FinalizeStateReq>,
+  ) -> Result<Response<FinalizeStateResp>, Status> {
+    self.enter("finalize_state").await?;
+    self
+      .do_finalize_state(request.into_inner())
+      .map(Response::new)
+  }
+
+  async fn read_state(
+    &self,
+    _request: Request<ReadStateReq>,
+  ) -> Result<Response<ReadStateResp>, Status> {
+    self.enter("read_state").await?;
+    Ok(Response::new(self.do_read_state()))
+  }
+
+  async fn activate(
+    &self,
+    request: Request<ActivateReq>,
+  ) -> Result<Response<ActivateResp>, Status> {
+    self.enter("activate").await?;
+    self.do_activate(request.into_inner()).map(Response::new)
+  }
+
+  async fn rotate_key(
+    &self,
+    _request: Request<RotateKeyReq>,
+  ) -> Result<Response<RotateKeyResp>, Status> {
+    self.enter("rotate_key").await?;
+    Err(Status::unimplemented(
+      "a mock endorser does not rotate its key",
+    ))
+  }
+
+  async fn get_status(
+    &self,
+    _request: Request<GetStatusReq>,
+  ) -> Result<Response<GetStatusResp>, Status> {
+    self.enter("get_status").await?;
+    Ok(Response::new(self.do_get_status()))
+  }
+
+  async fn heartbeat(
+    &self,
+    request: Request<HeartbeatReq>,
+  ) -> Result<Response<HeartbeatResp>, Status> {
+    self.enter("heartbeat").await?;
+    self.do_heartbeat(request.into_inner()).map(Response::new)
+  }
+}
+
+/// the mock endorsers of a test by URI, which the coordinators of the test connect to
+#[derive(Default)]
+pub struct MockEndorsers {
+  endorsers: Mutex<HashMap<String, Arc<MockEndorser>>>,
+  /// draws the keys of the endorsers, if they are seeded
+  keys: Mutex<Option<StdRng>>,
+}
+
+impl MockEndorsers {
+  pub fn new() -> Arc<Self> {
+    Arc::new(MockEndorsers::default())
+  }
+
+  /// endorsers whose keys are drawn from `seed`, so that they are the same whenever a test runs
+  pub fn with_seed(seed: u64) -> Arc<Self> {
+    Arc::new(MockEndorsers {
+      endorsers: Mutex::new(HashMap::new()),
+      keys: Mutex::new(Some(StdRng::seed_from_u64(seed))),
+    })
+  }
+
+  /// starts a mock endorser at `uri`
+  pub fn start(&self, uri: &str) -> Arc<MockEndorser> {
+    let endorser = match self.keys.lock().unwrap().as_mut() {
+      Some(keys) => MockEndorser::with_key(seeded_key(keys)),
+      None => MockEndorser::new(),
+    };
+    self
+      .endorsers
+      .lock()
+      .unwrap()
+      .insert(uri.to_string(), endorser.clone());
+    endorser
+  }
+}
+
+#[tonic::async_trait]
+impl EndorserConnector for MockEndorsers {
+  async fn connect(&self, uri: &str) -> Result<EndorserClient, CoordinatorError> {
+    let endorser = self.endorsers.lock().unwrap().get(uri).cloned();
+    match endorser {
+      Some(endorser) if !endorser.faults.lock().unwrap().down => {
+        let client: EndorserClient = endorser;
+        Ok(client)
+      },
+      _ => Err(CoordinatorError::FailedToConnectToEndorser),
+    }
+  }
+}
+
+/// a private key drawn from `rng`
+fn seeded_key(rng: &mut StdRng) -> PrivateKey {
+  loop {
+    // the rare draws that are not valid scalars are drawn again
+    if let Ok(key) = PrivateKey::from_bytes(&rng.gen::<[u8; 32]>()) {
+      return key;
+    }
+  }
+}
+
+/// serves a mock endorser over gRPC, as an endorser process does
+#[derive(Clone)]
+pub struct MockEndorserService(pub Arc<MockEndorser>);
+
+#[tonic::async_trait]
+impl EndorserCall for MockEndorserService {
+  async fn get_public_key(
+    &self,
+    request: Request<GetPublicKeyReq>,
+  ) -> Result<Response<GetPublicKeyResp>, Status> {
+    EndorserConnection::get_public_key(&*self.0, request).await
+  }
+
+  async fn new_ledger(
+    &self,
+    request: Request<NewLedgerReq>,
+  ) -> Result<Response<NewLedgerResp>, Status> {
+    EndorserConnection::new_ledger(&*self.0, request).await
+  }
+
+  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+    EndorserConnection::append(&*self.0, request).await
+  }
+
+  async fn append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    EndorserConnection::append_batch(&*self.0, request).await
+  }
+
+  async fn read_latest(
+    &self,
+    request: Request<ReadLatestReq>,
+  ) -> Result<Response<ReadLatestResp>, Status> {
+    EndorserConnection::read_latest(&*self.0, request).await
+  }
+
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status> {
+    EndorserConnection::read_view_tail(&*self.0, request).await
+  }
+
+  async fn initialize_state(
+    &self,
+    request: Request<InitializeStateReq>,
+  ) -> Result<Response<InitializeStateResp>, Status> {
+    EndorserConnection::initialize_state(&*self.0, request).await
+  }
+
+  async fn finalize_state(
+    &self,
+    request: Request<FinalizeStateReq>,
+  ) -> Result<Response<FinalizeStateResp>, Status> {
+    EndorserConnection::finalize_state(&*self.0, request).await
+  }
+
+  async fn read_state(
+    &self,
+    request: Request<ReadStateReq>,
+  ) -> Result<Response<ReadStateResp>, Status> {
+    EndorserConnection::read_state(&*self.0, request).await
+  }
+
+  async fn activate(
+    &self,
+    request: Request<ActivateReq>,
+  ) -> Result<Response<ActivateResp>, Status> {
+    EndorserConnection::activate(&*self.0, request).await
+  }
+
+  async fn rotate_key(
+    &self,
+    request: Request<RotateKeyReq>,
+  ) -> Result<Response<RotateKeyResp>, Status> {
+    EndorserConnection::rotate_key(&*self.0, request).await
+  }
+
+  async fn get_status(
+    &self,
+    request: Request<GetStatusReq>,
+  ) -> Result<Response<GetStatusResp>, Status> {
+    EndorserConnection::get_status(&*self.0, request).await
+  }
+
+  async fn heartbeat(
+    &self,
+    request: Request<HeartbeatReq>,
+  ) -> Result<Response<HeartbeatResp>, Status> {
+    EndorserConnection::heartbeat(&*self.0, request).await
+  }
+}
@@ -0,0 +1,417 @@
+use crate::tenant::Authenticator;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{BTreeMap, HashMap},
+  convert::TryFrom,
+  sync::{Arc, Mutex},
+  task::{Context, Poll},
+  time::Duration,
+};
+use tokio::time::Instant;
+use tonic::{
+  body::BoxBody,
+  codegen::{
+    http::{Request, Response},
+    BoxFuture,
+  },
+  metadata::MetadataValue,
+  transport::server::TcpConnectInfo,
+  Status,
+};
+use tower::{Layer, Service};
+
+const APPEND_METHODS: [&str; 6] = [
+  "NewLedger",
+  "Append",
+  "AppendBatch",
+  "SealLedger",
+  "RecordCheckpoint",
+  "RecordEditSegment",
+];
+/// the path of the probes of orchestrators, which are not limited
+pub const HEALTH_SERVICE_PATH: &str = "/grpc.health.v1.Health/";
+const BUCKETS_PRUNE_LEN: usize = 4096; // the buckets are pruned of full ones at this size
+
+/// which budget of a client a call draws from: calls that write ledgers go to the endorsers, so
+/// they are budgeted apart from reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Budget {
+  Reads,
+  Appends,
+}
+
+impl Budget {
+  /// the budget of a call to the client service, from the path of its gRPC method; an
+  /// AppendBatch draws a single token however many appends it carries
+  pub fn of_path(path: &str) -> Self {
+    match path.rsplit('/').next() {
+      Some(method) if APPEND_METHODS.contains(&method) => Budget::Appends,
+      _ => Budget::Reads,
+    }
+  }
+}
+
+/// the rates of the calls of a client; a rate of 0 means that the client is not limited, and a
+/// burst of 0 means a burst of one second at the rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+  pub reads_per_sec: u64,
+  pub read_burst: u64,
+  pub appends_per_sec: u64,
+  pub append_burst: u64,
+}
+
+impl RateLimits {
+  /// the rate and burst of a budget
+  fn of(&self, budget: Budget) -> (u64, u64) {
+    let (rate, burst) = match budget {
+      Budget::Reads => (self.reads_per_sec, self.read_burst),
+      Budget::Appends => (self.appends_per_sec, self.append_burst),
+    };
+    (rate, if burst == 0 { rate } else { burst })
+  }
+}
+
+/// the limits of every client and those of clients with limits of their own, keyed like
+/// `RateLimiter::check`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+  pub default: RateLimits,
+  pub keys: BTreeMap<String, RateLimits>,
+}
+
+/// the calls of a client that were let through and rejected, per budget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateCounters {
+  pub reads_allowed: u64,
+  pub reads_rejected: u64,
+  pub appends_allowed: u64,
+  pub appends_rejected: u64,
+}
+
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+}
+
+#[derive(Default)]
+struct LimiterState {
+  config: RateLimitConfig,
+  buckets: HashMap<(String, Budget), Bucket>,
+  counters: HashMap<String, RateCounters>,
+}
+
+/// token buckets that limit the rate of the calls of each client of the coordinator, so that one
+/// client cannot take the endorsers from all others; the limits change at runtime
+#[derive(Default)]
+pub struct RateLimiter {
+  state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+  pub fn new(config: RateLimitConfig) -> Self {
+    RateLimiter {
+      state: Mutex::new(LimiterState {
+        config,
+        ..Default::default()
+      }),
+    }
+  }
+
+  /// takes a token from the budget of a client, or returns how long until the budget has one
+  pub fn check(&self, key: &str, budget: Budget) -> Result<(), Duration> {
+    let mut state = match self.state.lock() {
+      Ok(state) => state,
+      Err(_) => return Ok(()),
+    };
+    let limits = *state.config.keys.get(key).unwrap_or(&state.config.default);
+    let (rate, burst) = limits.of(budget);
+    let res = if rate == 0 {
+      Ok(())
+    } else {
+      if state.buckets.len() >= BUCKETS_PRUNE_LEN {
+        // a bucket that refilled holds nothing that a new one would not
+        let now = Instant::now();
+        state.buckets.retain(|_key, bucket| {
+          now.duration_since(bucket.updated) < Duration::from_secs(1)
+            || bucket.tokens < burst as f64
+        });
+      }
+      let now = Instant::now();
+      let bucket = state
+        .buckets
+        .entry((key.to_string(), budget))
+        .or_insert(Bucket {
+          tokens: burst as f64,
+          updated: now,
+        });
+      let refill = now.duration_since(bucket.updated).as_secs_f64() * rate as f64;
+      bucket.tokens = (bucket.tokens + refill).min(burst as f64);
+      bucket.updated = now;
+      if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        Ok(())
+      } else {
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate as f64))
+      }
+    };
+
+    let counters = state.counters.entry(key.to_string()).or_default();
+    let counter = match (budget, res.is_ok()) {
+      (Budget::Reads, true) => &mut counters.reads_allowed,
+      (Budget::Reads, false) => &mut counters.reads_rejected,
+      (Budget::Appends, true) => &mut counters.appends_allowed,
+      (Budget::Appends, false) => &mut counters.appends_rejected,
+    };
+    *counter += 1;
+    res
+  }
+
+  /// the limits of a client, and of every client without limits of its own if the key is empty
+  pub fn limits(&self, key: &str) -> RateLimits {
+    match self.state.lock() {
+      Ok(state) if key.is_empty() => state.config.default,
+      Ok(state) => *state.config.keys.get(key).unwrap_or(&state.config.default),
+      Err(_) => RateLimits::default(),
+    }
+  }
+
+  /// sets the limits of a client, or of every client without limits of its own if the key is
+  /// empty; `None` makes a client fall back to the limits of every client. The buckets that the
+  /// change affects start full
+  pub fn set_limits(&self, key: &str, limits: Option<RateLimits>) {
+    if let Ok(mut state) = self.state.lock() {
+      match (key.is_empty(), limits) {
+        (true, limits) => state.config.default = limits.unwrap_or_default(),
+        (false, Some(limits)) => {
+          state.config.keys.insert(key.to_string(), limits);
+        },
+        (false, None) => {
+          state.config.keys.remove(key);
+        },
+      }
+      if key.is_empty() {
+        state.buckets.clear();
+      } else {
+        state
+          .buckets
+          .retain(|(bucket_key, _budget), _bucket| bucket_key != key);
+      }
+    }
+  }
+
+  pub fn counters(&self, key: &str) -> RateCounters {
+    match self.state.lock() {
+      Ok(state) => state.counters.get(key).copied().unwrap_or_default(),
+      Err(_) => RateCounters::default(),
+    }
+  }
+}
+
+/// applies a `RateLimiter` to the client service; calls are keyed by the tenant that they
+/// authenticate as if the coordinator serves tenants, and by the IP address of the client otherwise
+#[derive(Clone)]
+pub struct RateLimitLayer {
+  limiter: Arc<RateLimiter>,
+  /// authenticates the tenants, if the coordinator serves tenants
+  auth: Option<Authenticator>,
+}
+
+impl RateLimitLayer {
+  pub fn new(limiter: Arc<RateLimiter>, auth: Option<Authenticator>) -> Self {
+    RateLimitLayer { limiter, auth }
+  }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+  type Service = RateLimitService<S>;
+
+  fn layer(&self, inner: S) -> Self::Service {
+    RateLimitService {
+      inner,
+      layer: self.clone(),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+  inner: S,
+  layer: RateLimitLayer,
+}
+
+impl<S> RateLimitService<S> {
+  /// the client a call counts against; calls with a token of no tenant are let through, since
+  /// the service rejects them anyway
+  fn key<B>(&self, req: &Request<B>) -> Option<String> {
+    match &self.layer.auth {
+      Some(auth) => {
+        let authorization = req
+          .headers()
+          .get("authorization")
+          .and_then(|value| value.to_str().ok());
+        auth.authenticate(authorization).ok().map(|tenant| tenant.0)
+      },
+      None => Some(
+        req
+          .extensions()
+          .get::<TcpConnectInfo>()
+          .and_then(TcpConnectInfo::remote_addr)
+          .map(|addr| addr.ip().to_string())
+          .unwrap_or_default(),
+      ),
+    }
+  }
+}
+
+/// the status of a call that exceeds the budget of its client, with when to retry it
+pub fn rate_limited(retry_after: Duration) -> Status {
+  let mut status = Status::resource_exhausted("The client exceeded its rate limit; retry later");
+  let millis = retry_after.as_millis().max(1);
+  let secs = (millis + 999) / 1000;
+  let metadata = status.metadata_mut();
+  if let Ok(value) = MetadataValue::try_from(secs.to_string()) {
+    metadata.insert("retry-after", value);
+  }
+  if let Ok(value) = MetadataValue::try_from(millis.to_string()) {
+    metadata.insert("grpc-retry-pushback-ms", value);
+  }
+  status
+}
+
+impl<S, B> Service<Request<B>> for RateLimitService<S>
+where
+  S: Service<Request<B>, Response = Response<BoxBody>>,
+  S::Future: Send + 'static,
+{
+  type Response = S::Response;
+  type Error = S::Error;
+  type Future = BoxFuture<Self::Response, Self::Error>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, req: Request<B>) -> Self::Future {
+    if req.uri().path().starts_with(HEALTH_SERVICE_PATH) {
+      return Box::pin(self.inner.call(req));
+    }
+    if let Some(key) = self.key(&req) {
+      let budget = Budget::of_path(req.uri().path());
+      if let Err(retry_after) = self.layer.limiter.check(&key, budget) {
+        let response = rate_limited(retry_after).to_http();
+        return Box::pin(async move { Ok(response) });
+      }
+    }
+    Box::pin(self.inner.call(req))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::convert::Infallible;
+  use tower::ServiceExt;
+
+  #[tokio::test]
+  async fn test_rate_limiter() {
+    let mut config = RateLimitConfig::default();
+    config.default.appends_per_sec = 10;
+    config.default.append_burst = 2;
+    config.keys.insert(
+      "hdfs-b".to_string(),
+      RateLimits {
+        reads_per_sec: 1,
+        ..Default::default()
+      },
+    );
+    let limiter = Arc::new(RateLimiter::new(config));
+
+    // a burst is let through, and then a token a tenth of a second; reads are not limited
+    assert!(limiter.check("hdfs-a", Budget::Appends).is_ok());
+    assert!(limiter.check("hdfs-a", Budget::Appends).is_ok());
+    let retry_after = limiter.check("hdfs-a", Budget::Appends).unwrap_err();
+    assert!(retry_after <= Duration::from_millis(100));
+    for _i in 0..100 {
+      assert!(limiter.check("hdfs-a", Budget::Reads).is_ok());
+    }
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    assert!(limiter.check("hdfs-a", Budget::Appends).is_ok());
+    assert_eq!(
+      limiter.counters("hdfs-a"),
+      RateCounters {
+        reads_allowed: 100,
+        reads_rejected: 0,
+        appends_allowed: 3,
+        appends_rejected: 1,
+      }
+    );
+
+    // a client with limits of its own has only those
+    assert!(limiter.check("hdfs-b", Budget::Reads).is_ok());
+    assert!(limiter.check("hdfs-b", Budget::Reads).is_err());
+    for _i in 0..10 {
+      assert!(limiter.check("hdfs-b", Budget::Appends).is_ok());
+    }
+
+    // limits change at runtime
+    limiter.set_limits("hdfs-b", None);
+    assert_eq!(limiter.limits("hdfs-b"), limiter.limits(""));
+    assert!(limiter.check("hdfs-b", Budget::Reads).is_ok());
+    limiter.set_limits("", None);
+    for _i in 0..10 {
+      assert!(limiter.check("hdfs-a", Budget::Appends).is_ok());
+    }
+
+    // the layer keys calls by tenant, and rejects those over budget with a hint to retry
+    limiter.set_limits(
+      "hdfs-a",
+      Some(RateLimits {
+        appends_per_sec: 1,
+        ..Default::default()
+      }),
+    );
+    let tenants = vec![("token-a".to_string(), "hdfs-a".to_string())]
+      .into_iter()
+      .collect::<HashMap<_, _>>();
+    let service =
+      RateLimitLayer::new(limiter.clone(), Some(tenants.into())).layer(tower::service_fn(
+        |_req: Request<()>| async { Ok::<_, Infallible>(Response::new(BoxBody::default())) },
+      ));
+    let call = |path: &str, token: &str| {
+      Request::builder()
+        .uri(path)
+        .header("authorization", format!("Bearer {}", token))
+        .body(())
+        .unwrap()
+    };
+    let res = service
+      .clone()
+      .oneshot(call("/coordinator_proto.Call/Append", "token-a"))
+      .await
+      .unwrap();
+    assert!(res.headers().get("grpc-status").is_none());
+    let res = service
+      .clone()
+      .oneshot(call("/coordinator_proto.Call/AppendBatch", "token-a"))
+      .await
+      .unwrap();
+    let status = Status::from_header_map(res.headers()).unwrap();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "1");
+    let res = service
+      .clone()
+      .oneshot(call("/coordinator_proto.Call/ReadLatest", "token-a"))
+      .await
+      .unwrap();
+    assert!(res.headers().get("grpc-status").is_none());
+    let res = service
+      .oneshot(call("/coordinator_proto.Call/Append", "other"))
+      .await
+      .unwrap();
+    assert!(res.headers().get("grpc-status").is_none());
+    assert_eq!(limiter.counters("hdfs-a").appends_rejected, 2);
+  }
+}
@@ -0,0 +1,378 @@
+//! Detection of reads that reuse a nonce. The nonce of an attested read is what makes its
+//! receipts fresh, so a client whose library sends the same nonce twice silently accepts a reply
+//! that could have been recorded earlier. The coordinator remembers the (client, handle, nonce)
+//! of the attested reads of a window and flags a read whose nonce it remembers: by default the
+//! reuse is logged and counted, and the reads of strict clients are rejected.
+//!
+//! The cache is a ring of `NUM_BUCKETS` bloom filters, each of which takes the nonces of a span of
+//! `window / (NUM_BUCKETS - 1)`; the oldest one is cleared to take the next span, so a nonce is
+//! remembered for at least the window and at most a span longer, and the memory of the cache is
+//! fixed when it is created, about 27 bits for each nonce of its capacity.
+//!
+//! A bloom filter forgets nothing that it took, so a reuse within the window is always flagged,
+//! except between reads that are served at the same time, since a nonce is remembered once its
+//! read succeeded. It may flag a nonce that was never used, though: with the nonces of its
+//! capacity spread over the window, a bucket flags a fresh nonce with a probability of about
+//! (1 - e^(-14/20))^14 ≈ 7 × 10^-5, and a fresh nonce is checked against the 3 buckets that are
+//! full, so about 2 in 10,000 fresh nonces are flagged. The rate grows quickly beyond the
+//! capacity, to about 6 in 100 at twice the capacity, so a strict mode needs a capacity above the
+//! attested reads that the coordinator serves in a window.
+use crate::{
+  authz::Identity,
+  errors::CoordinatorError,
+  tenant::{request_tenant, Tenant},
+};
+use ledger::hash::{NimbleHasher, Sha256Hasher};
+use std::{
+  collections::HashSet,
+  sync::{Mutex, MutexGuard},
+  time::{Duration, Instant},
+};
+use tonic::Request;
+
+/// the bloom filters of the cache
+const NUM_BUCKETS: usize = 4;
+/// the bits of a bloom filter for each nonce of its capacity
+const BITS_PER_NONCE: usize = 20;
+/// the bits that a nonce sets in a bloom filter, which flags the fewest fresh nonces for 20 bits
+/// per nonce (20 ln 2 ≈ 14)
+const NUM_HASHES: u64 = 14;
+
+/// the seconds that nonces are remembered for unless configured
+pub const DEFAULT_NONCE_WINDOW: u64 = 600;
+/// the nonces of a window that the cache holds at the documented rate of false positives, unless
+/// configured
+pub const DEFAULT_NONCE_CAPACITY: usize = 1 << 20;
+
+/// the name of the client of a request: the principal it authenticated as, or its tenant
+pub fn request_client<T>(request: &Request<T>) -> String {
+  match request.extensions().get::<Identity>() {
+    Some(identity) => identity.principal.clone(),
+    None => request_tenant(request)
+      .map(|tenant| tenant.0)
+      .unwrap_or_default(),
+  }
+}
+
+/// the positions of a (client, handle, nonce) in the bloom filters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonceKey {
+  h1: u64,
+  h2: u64,
+}
+
+struct Buckets {
+  bits: Vec<Vec<u64>>,
+  /// the nonces that each bucket took since it was cleared
+  counts: [usize; NUM_BUCKETS],
+  /// the bucket that takes the nonces of the current span
+  current: usize,
+  /// when the current span started
+  started: Instant,
+}
+
+impl Buckets {
+  /// moves on to the span of `now`, clearing the buckets of the spans that ended
+  fn advance(&mut self, now: Instant, span: Duration) {
+    let elapsed = now.saturating_duration_since(self.started);
+    let spans = elapsed.as_nanos() / span.as_nanos();
+    if spans == 0 {
+      return;
+    }
+    for _ in 0..spans.min(NUM_BUCKETS as u128) {
+      self.current = (self.current + 1) % NUM_BUCKETS;
+      self.bits[self.current]
+        .iter_mut()
+        .for_each(|word| *word = 0);
+      self.counts[self.current] = 0;
+    }
+    let into_span = elapsed.as_nanos() % span.as_nanos();
+    self.started = now - Duration::from_nanos(into_span as u64);
+  }
+}
+
+/// the nonces of the attested reads of a window, in time-bucketed bloom filters
+pub struct NonceCache {
+  window: Duration,
+  span: Duration,
+  /// the bits of each bucket
+  num_bits: u64,
+  /// keys the hashes of the cache, so that clients cannot pick nonces that collide
+  salt: [u8; 32],
+  buckets: Mutex<Buckets>,
+}
+
+impl NonceCache {
+  /// a cache that remembers nonces for `window` and holds `capacity` nonces in a window
+  pub fn new(window: Duration, capacity: usize) -> Self {
+    NonceCache::new_at(window, capacity, rand::random(), Instant::now())
+  }
+
+  fn new_at(window: Duration, capacity: usize, salt: [u8; 32], now: Instant) -> Self {
+    let span = (window / (NUM_BUCKETS as u32 - 1)).max(Duration::from_millis(1));
+    let bucket_capacity = capacity / (NUM_BUCKETS - 1) + 1;
+    let num_words = bucket_capacity * BITS_PER_NONCE / 64 + 1;
+    NonceCache {
+      window,
+      span,
+      num_bits: num_words as u64 * 64,
+      salt,
+      buckets: Mutex::new(Buckets {
+        bits: vec![vec![0; num_words]; NUM_BUCKETS],
+        counts: [0; NUM_BUCKETS],
+        current: 0,
+        started: now,
+      }),
+    }
+  }
+
+  pub fn window(&self) -> Duration {
+    self.window
+  }
+
+  /// the bytes of the bloom filters, which do not grow
+  pub fn size_bytes(&self) -> usize {
+    NUM_BUCKETS * self.num_bits as usize / 8
+  }
+
+  /// the nonces that the cache remembers, counting a nonce taken twice twice
+  pub fn num_nonces(&self) -> Result<usize, CoordinatorError> {
+    self.num_nonces_at(Instant::now())
+  }
+
+  fn num_nonces_at(&self, now: Instant) -> Result<usize, CoordinatorError> {
+    let buckets = self.buckets_at(now)?;
+    Ok(buckets.counts.iter().sum())
+  }
+
+  /// the buckets, moved on to the span of `now`
+  fn buckets_at(&self, now: Instant) -> Result<MutexGuard<'_, Buckets>, CoordinatorError> {
+    let mut buckets = self
+      .buckets
+      .lock()
+      .map_err(|_e| CoordinatorError::FailedToAcquireWriteLock)?;
+    buckets.advance(now, self.span);
+    Ok(buckets)
+  }
+
+  pub fn key(&self, client: &str, handle: &[u8], nonce: &[u8]) -> NonceKey {
+    let mut hasher = Sha256Hasher::new();
+    hasher.update(&self.salt);
+    for part in [client.as_bytes(), handle, nonce] {
+      hasher.update(&(part.len() as u64).to_le_bytes());
+      hasher.update(part);
+    }
+    let digest = hasher.finalize();
+    let word = |i: usize| {
+      let mut bytes = [0u8; 8];
+      bytes.copy_from_slice(&digest[i * 8..(i + 1) * 8]);
+      u64::from_le_bytes(bytes)
+    };
+    // the bits of a nonce are h1 + i * h2, as in double hashing; an odd step rarely repeats one
+    NonceKey {
+      h1: word(0),
+      h2: word(1) | 1,
+    }
+  }
+
+  fn bits(&self, key: NonceKey) -> impl Iterator<Item = (usize, u64)> {
+    let num_bits = self.num_bits;
+    (0..NUM_HASHES).map(move |i| {
+      let bit = key.h1.wrapping_add(i.wrapping_mul(key.h2)) % num_bits;
+      ((bit / 64) as usize, 1u64 << (bit % 64))
+    })
+  }
+
+  /// whether the cache remembers `key`
+  pub fn contains(&self, key: &NonceKey) -> Result<bool, CoordinatorError> {
+    self.contains_at(key, Instant::now())
+  }
+
+  fn contains_at(&self, key: &NonceKey, now: Instant) -> Result<bool, CoordinatorError> {
+    let buckets = self.buckets_at(now)?;
+    Ok(
+      buckets
+        .bits
+        .iter()
+        .any(|bucket| self.bits(*key).all(|(word, mask)| bucket[word] & mask != 0)),
+    )
+  }
+
+  /// remembers `key` for the window
+  pub fn insert(&self, key: &NonceKey) -> Result<(), CoordinatorError> {
+    self.insert_at(key, Instant::now())
+  }
+
+  fn insert_at(&self, key: &NonceKey, now: Instant) -> Result<(), CoordinatorError> {
+    let mut buckets = self.buckets_at(now)?;
+    let current = buckets.current;
+    for (word, mask) in self.bits(*key) {
+      buckets.bits[current][word] |= mask;
+    }
+    buckets.counts[current] += 1;
+    Ok(())
+  }
+}
+
+/// the nonce cache of the coordinator, and the clients whose reuses it rejects
+pub struct ReplayDetector {
+  cache: NonceCache,
+  /// rejects the reuses of every client
+  strict: bool,
+  /// rejects the reuses of the clients of these tenants
+  strict_tenants: HashSet<String>,
+}
+
+impl ReplayDetector {
+  pub fn new(cache: NonceCache) -> Self {
+    ReplayDetector {
+      cache,
+      strict: false,
+      strict_tenants: HashSet::new(),
+    }
+  }
+
+  pub fn with_strict(mut self, strict: bool) -> Self {
+    self.strict = strict;
+    self
+  }
+
+  pub fn with_strict_tenants(mut self, tenants: &[String]) -> Self {
+    self.strict_tenants = tenants.iter().cloned().collect();
+    self
+  }
+
+  pub fn cache(&self) -> &NonceCache {
+    &self.cache
+  }
+
+  /// whether a read of `tenant` that reuses a nonce is rejected rather than only logged
+  pub fn is_strict(&self, tenant: &Option<Tenant>) -> bool {
+    self.strict
+      || tenant
+        .as_ref()
+        .map_or(false, |tenant| self.strict_tenants.contains(&tenant.0))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SALT: [u8; 32] = [7; 32];
+
+  #[test]
+  pub fn test_nonce_cache() {
+    let start = Instant::now();
+    let window = Duration::from_secs(600);
+    let cache = NonceCache::new_at(window, 1000, SALT, start);
+    let key = cache.key("ApiKey:app", b"handle", b"nonce-1");
+    assert!(!cache.contains_at(&key, start).unwrap());
+    cache.insert_at(&key, start).unwrap();
+    assert!(cache.contains_at(&key, start).unwrap());
+
+    // the client, the handle and the nonce all tell reads apart, without ambiguity
+    assert_ne!(key, cache.key("ApiKey:other", b"handle", b"nonce-1"));
+    assert_ne!(key, cache.key("ApiKey:app", b"other", b"nonce-1"));
+    assert_ne!(key, cache.key("ApiKey:app", b"handle", b"nonce-2"));
+    assert_ne!(cache.key("a", b"bc", b""), cache.key("ab", b"c", b""),);
+    let other = cache.key("ApiKey:other", b"handle", b"nonce-1");
+    assert!(!cache.contains_at(&other, start).unwrap());
+    // another coordinator, or a restarted one, hashes with another salt
+    let restarted = NonceCache::new_at(window, 1000, [8; 32], start);
+    assert_ne!(key, restarted.key("ApiKey:app", b"handle", b"nonce-1"));
+
+    // a nonce is remembered for the window, and forgotten a span later
+    assert!(cache.contains_at(&key, start + window).unwrap());
+    assert!(cache
+      .contains_at(&key, start + Duration::from_secs(799))
+      .unwrap());
+    assert_eq!(
+      cache
+        .num_nonces_at(start + Duration::from_secs(799))
+        .unwrap(),
+      1
+    );
+    assert!(!cache
+      .contains_at(&key, start + Duration::from_secs(800))
+      .unwrap());
+    assert_eq!(
+      cache
+        .num_nonces_at(start + Duration::from_secs(800))
+        .unwrap(),
+      0
+    );
+    let later = start + Duration::from_secs(10_000);
+    cache.insert_at(&key, later).unwrap();
+    assert!(cache.contains_at(&key, later + window).unwrap());
+    // after a long quiet time, every bucket is cleared at once
+    assert!(!cache
+      .contains_at(&key, later + Duration::from_secs(100_000))
+      .unwrap());
+
+    // the filters do not grow with the nonces they take
+    let size = cache.size_bytes();
+    assert_eq!(size, NUM_BUCKETS * 105 * 8); // 334 nonces of 20 bits in each
+    for i in 0..10_000u32 {
+      cache
+        .insert_at(&cache.key("c", b"h", &i.to_le_bytes()), later)
+        .unwrap();
+    }
+    assert_eq!(cache.size_bytes(), size);
+    assert_eq!(cache.num_nonces_at(later).unwrap(), 10_001);
+  }
+
+  #[test]
+  pub fn test_nonce_cache_false_positives() {
+    let start = Instant::now();
+    let window = Duration::from_secs(600);
+    let capacity = 30_000;
+    let cache = NonceCache::new_at(window, capacity, SALT, start);
+    assert_eq!(cache.size_bytes(), NUM_BUCKETS * 3126 * 8);
+
+    // the nonces of the capacity, spread over the window
+    for i in 0..capacity as u64 {
+      let at = start + Duration::from_millis(i * 600_000 / capacity as u64);
+      let key = cache.key("ApiKey:app", b"handle", &i.to_le_bytes());
+      cache.insert_at(&key, at).unwrap();
+    }
+    let end = start + Duration::from_millis(599_999);
+    for i in (0..capacity as u64).step_by(97) {
+      let key = cache.key("ApiKey:app", b"handle", &i.to_le_bytes());
+      assert!(cache.contains_at(&key, end).unwrap());
+    }
+
+    // about 2 in 10,000 fresh nonces are flagged; fewer than 5 in 10,000 pass here
+    let probes = 100_000u64;
+    let flagged = (0..probes)
+      .filter(|i| {
+        let key = cache.key(
+          "ApiKey:app",
+          b"handle",
+          &(capacity as u64 + i).to_le_bytes(),
+        );
+        cache.contains_at(&key, end).unwrap()
+      })
+      .count();
+    assert!(
+      flagged < 50,
+      "{} of {} fresh nonces flagged",
+      flagged,
+      probes
+    );
+  }
+
+  #[test]
+  pub fn test_replay_detector() {
+    let detector = ReplayDetector::new(NonceCache::new(Duration::from_secs(60), 100));
+    let tenant = Some(Tenant("hdfs".to_string()));
+    assert!(!detector.is_strict(&tenant) && !detector.is_strict(&None));
+    let detector = detector.with_strict_tenants(&["hdfs".to_string()]);
+    assert!(detector.is_strict(&tenant));
+    assert!(!detector.is_strict(&Some(Tenant("hbase".to_string()))));
+    assert!(!detector.is_strict(&None));
+    let detector = detector.with_strict(true);
+    assert!(detector.is_strict(&None));
+    assert_eq!(detector.cache().window(), Duration::from_secs(60));
+  }
+}
@@ -0,0 +1,632 @@
+//! Deterministic simulations of coordinators and their endorsers, for the races of reconciliation
+//! and failover that real timers and networks make nearly impossible to reproduce. A simulation
+//! runs on a runtime of its own, with one thread and a paused clock. The wall clocks of the
+//! coordinators follow that clock, so timeouts, waits and leases move only as the script runs the
+//! simulation, and time jumps ahead whenever every task waits on a timer. The keys of the
+//! endorsers, the nonces of the coordinators and the messages that the script leaves to chance
+//! are drawn from the seed. A simulation returns a trace of what the clients and the store saw,
+//! which is the same whenever the script runs from the same seed; the order in which the calls of
+//! a fan-out reach the endorsers is not traced, as it follows the iteration of hash maps.
+use crate::{
+  clock::{Clock, SimulatedClock},
+  endorser_connection::{EndorserClient, EndorserConnection, EndorserConnector},
+  errors::CoordinatorError,
+  lease::Lease,
+  mock_endorser::{MockEndorser, MockEndorsers},
+  CoordinatorState,
+};
+use ledger::{
+  endorser_proto::{
+    ActivateReq, ActivateResp, AppendBatchReq, AppendBatchResp, AppendReq, AppendResp,
+    FinalizeStateReq, FinalizeStateResp, GetPublicKeyReq, GetPublicKeyResp, GetStatusReq,
+    GetStatusResp, HeartbeatReq, HeartbeatResp, InitializeStateReq, InitializeStateResp,
+    NewLedgerReq, NewLedgerResp, ReadLatestReq, ReadLatestResp, ReadStateReq, ReadStateResp,
+    ReadViewTailReq, ReadViewTailResp, RotateKeyReq, RotateKeyResp,
+  },
+  NimbleDigest,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+  collections::{BTreeSet, HashMap},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+  },
+  time::Duration,
+};
+use tokio::{
+  task::JoinHandle,
+  time::{error::Elapsed, Instant},
+};
+use tonic::{Request, Response, Status};
+
+const NUM_ENDORSERS: usize = 3; // the endorsers of a simulation
+const START_MS: u64 = 1_600_000_000_000; // ms since the Unix epoch: when every simulation starts
+const LEASE_DURATION: Duration = Duration::from_secs(3); // the lease of the coordinators
+const STEP_TIMEOUT: Duration = Duration::from_secs(60); // simulated: the longest wait of a step
+
+/// a step of a simulation. The coordinators are numbered from 0 in the order they started, and
+/// the endorsers and the ledgers from 0
+#[derive(Clone, Debug)]
+pub enum Step {
+  /// creates the ledger through the coordinator, and waits for the outcome
+  Create { coordinator: usize, ledger: usize },
+  /// appends the next block of the store to the ledger through the coordinator, and waits for
+  /// the outcome
+  Append { coordinator: usize, ledger: usize },
+  /// appends like `Append` without waiting; the outcome is traced once it comes
+  StartAppend { coordinator: usize, ledger: usize },
+  /// delays the messages sent to the endorser from now on by `latency`
+  Delay { endorser: usize, latency: Duration },
+  /// drops the next `times` messages of `call` to the endorser, or to an endorser drawn from the
+  /// seed
+  Drop {
+    endorser: Option<usize>,
+    call: &'static str,
+    times: usize,
+  },
+  /// drops each message to an endorser with the chance `percent` in 100, drawn from the seed
+  Loss { percent: u32 },
+  /// cuts the endorser off from the coordinators
+  Partition(usize),
+  /// reconnects the endorser to the coordinators
+  Heal(usize),
+  /// lets the simulation run for a while
+  Run(Duration),
+  /// makes the coordinator stop renewing its lease, as a coordinator that hangs does; it serves
+  /// until the lease lapses
+  StopRenewing(usize),
+  /// kills the coordinator; the messages that it sent and that were not delivered are lost
+  Crash(usize),
+  /// starts another coordinator over the store, which serves once it takes over the lease and
+  /// recovers
+  Start,
+  /// traces the height of each ledger in the store and in each endorser
+  Check,
+}
+
+/// runs `script` from `seed`, and returns its trace
+pub fn simulate(seed: u64, script: &[Step]) -> Vec<String> {
+  let runtime = tokio::runtime::Builder::new_current_thread()
+    .enable_all()
+    .start_paused(true)
+    .build()
+    .expect("failed to build the runtime of the simulation");
+  runtime.block_on(async {
+    let mut simulation = Simulation::new(seed).await;
+    for step in script {
+      simulation.step(step).await;
+    }
+    simulation.finish()
+  })
+}
+
+/// what the network does with the messages to the endorsers
+struct Links {
+  latency: Vec<Duration>,
+  partitioned: Vec<bool>,
+  /// the messages still to drop, by endorser and call
+  drops: HashMap<(usize, &'static str), usize>,
+  loss_percent: u32,
+  /// draws the lost messages; each endorser has its own, so that what is lost on the way to one
+  /// endorser does not depend on the order of the calls to the others
+  loss: Vec<StdRng>,
+}
+
+/// the network between the coordinators and the endorsers of a simulation
+struct Network {
+  endorsers: Vec<Arc<MockEndorser>>,
+  uris: Vec<String>,
+  links: Mutex<Links>,
+}
+
+impl Network {
+  /// how long a message of `call` takes to get to the endorser, unless it is lost; a lost message
+  /// fails its call at once, as a refused connection does
+  fn route(&self, endorser: usize, call: &'static str) -> Result<Duration, Status> {
+    let mut links = self.links.lock().unwrap();
+    if links.partitioned[endorser] {
+      return Err(Status::unavailable("the endorser is partitioned"));
+    }
+    if let Some(times) = links.drops.get_mut(&(endorser, call)) {
+      if *times > 0 {
+        *times -= 1;
+        return Err(Status::unavailable("the message was dropped"));
+      }
+    }
+    let loss_percent = links.loss_percent;
+    if loss_percent > 0 && links.loss[endorser].gen_range(0..100) < loss_percent {
+      return Err(Status::unavailable("the message was lost"));
+    }
+    Ok(links.latency[endorser])
+  }
+}
+
+/// the end of the network at a coordinator, which goes down with the coordinator
+#[derive(Clone)]
+struct Link {
+  network: Arc<Network>,
+  alive: Arc<AtomicBool>,
+}
+
+#[tonic::async_trait]
+impl EndorserConnector for Link {
+  async fn connect(&self, uri: &str) -> Result<EndorserClient, CoordinatorError> {
+    let endorser = self
+      .network
+      .uris
+      .iter()
+      .position(|endorser_uri| endorser_uri == uri)
+      .ok_or(CoordinatorError::FailedToConnectToEndorser)?;
+    let unreachable = self.network.links.lock().unwrap().partitioned[endorser];
+    if unreachable || !self.alive.load(Ordering::SeqCst) {
+      return Err(CoordinatorError::FailedToConnectToEndorser);
+    }
+    let client: EndorserClient = Arc::new(Wire {
+      link: self.clone(),
+      endorser,
+    });
+    Ok(client)
+  }
+}
+
+/// a connection of a coordinator to an endorser over the network of the simulation
+struct Wire {
+  link: Link,
+  endorser: usize,
+}
+
+impl Wire {
+  /// carries a message of `call` to the endorser, or fails as the network says
+  async fn deliver(&self, call: &'static str) -> Result<&MockEndorser, Status> {
+    let crashed = || Status::unavailable("the coordinator crashed");
+    if !self.link.alive.load(Ordering::SeqCst) {
+      return Err(crashed());
+    }
+    let latency = self.link.network.route(self.endorser, call)?;
+    if !latency.is_zero() {
+      tokio::time::sleep(latency).await;
+    }
+    // a message still on its way when its coordinator dies is lost with it
+    if !self.link.alive.load(Ordering::SeqCst) {
+      return Err(crashed());
+    }
+    Ok(&*self.link.network.endorsers[self.endorser])
+  }
+}
+
+#[tonic::async_trait]
+impl EndorserConnection for Wire {
+  async fn get_public_key(
+    &self,
+    request: Request<GetPublicKeyReq>,
+  ) -> Result<Response<GetPublicKeyResp>, Status> {
+    let endorser = self.deliver("get_public_key").await?;
+    EndorserConnection::get_public_key(endorser, request).await
+  }
+
+  async fn new_ledger(
+    &self,
+    request: Request<NewLedgerReq>,
+  ) -> Result<Response<NewLedgerResp>, Status> {
+    let endorser = self.deliver("new_ledger").await?;
+    EndorserConnection::new_ledger(endorser, request).await
+  }
+
+  async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+    let endorser = self.deliver("append").await?;
+    EndorserConnection::append(endorser, request).await
+  }
+
+  async fn append_batch(
+    &self,
+    request: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    let endorser = self.deliver("append_batch").await?;
+    EndorserConnection::append_batch(endorser, request).await
+  }
+
+  async fn read_latest(
+    &self,
+    request: Request<ReadLatestReq>,
+  ) -> Result<Response<ReadLatestResp>, Status> {
+    let endorser = self.deliver("read_latest").await?;
+    EndorserConnection::read_latest(endorser, request).await
+  }
+
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status> {
+    let endorser = self.deliver("read_view_tail").await?;
+    EndorserConnection::read_view_tail(endorser, request).await
+  }
+
+  async fn initialize_state(
+    &self,
+    request: Request<InitializeStateReq>,
+  ) -> Result<Response<InitializeStateResp>, Status> {
+    let endorser = self.deliver("initialize_state").await?;
+    EndorserConnection::initialize_state(endorser, request).await
+  }
+
+  async fn finalize_state(
+    &self,
+    request: Request<FinalizeStateReq>,
+  ) -> Result<Response<FinalizeStateResp>, Status> {
+    let endorser = self.deliver("finalize_state").await?;
+    EndorserConnection::finalize_state(endorser, request).await
+  }
+
+  async fn read_state(
+    &self,
+    request: Request<ReadStateReq>,
+  ) -> Result<Response<ReadStateResp>, Status> {
+    let endorser = self.deliver("read_state").await?;
+    EndorserConnection::read_state(endorser, request).await
+  }
+
+  async fn activate(
+    &self,
+    request: Request<ActivateReq>,
+  ) -> Result<Response<ActivateResp>, Status> {
+    let endorser = self.deliver("activate").await?;
+    EndorserConnection::activate(endorser, request).await
+  }
+
+  async fn rotate_key(
+    &self,
+    request: Request<RotateKeyReq>,
+  ) -> Result<Response<RotateKeyResp>, Status> {
+    let endorser = self.deliver("rotate_key").await?;
+    EndorserConnection::rotate_key(endorser, request).await
+  }
+
+  async fn get_status(
+    &self,
+    request: Request<GetStatusReq>,
+  ) -> Result<Response<GetStatusResp>, Status> {
+    let endorser = self.deliver("get_status").await?;
+    EndorserConnection::get_status(endorser, request).await
+  }
+
+  async fn heartbeat(
+    &self,
+    request: Request<HeartbeatReq>,
+  ) -> Result<Response<HeartbeatResp>, Status> {
+    let endorser = self.deliver("heartbeat").await?;
+    EndorserConnection::heartbeat(endorser, request).await
+  }
+}
+
+/// a coordinator of a simulation
+struct Node {
+  state: Arc<CoordinatorState>,
+  alive: Arc<AtomicBool>,
+  /// renews the lease of the coordinator once it holds it
+  renewal: Option<JoinHandle<()>>,
+  /// the appends of the coordinator that no step waits for
+  appends: Vec<JoinHandle<()>>,
+}
+
+/// a trace that the appends in flight add to
+type Trace = Arc<Mutex<Vec<String>>>;
+
+struct Simulation {
+  rng: StdRng,
+  clock: Arc<dyn Clock>,
+  start: Instant,
+  network: Arc<Network>,
+  nodes: Vec<Node>,
+  ledgers: BTreeSet<usize>,
+  trace: Trace,
+}
+
+/// the handle of the ledger `ledger` of a script
+fn handle_bytes(ledger: usize) -> Vec<u8> {
+  format!("simulated ledger {}", ledger).into_bytes()
+}
+
+/// how a call that a step waited for came out
+fn outcome<T>(res: Result<Result<T, CoordinatorError>, Elapsed>) -> String {
+  match res {
+    Ok(Ok(_)) => "ok".to_string(),
+    Ok(Err(error)) => format!("{:?}", error),
+    Err(_) => "timed out".to_string(),
+  }
+}
+
+/// `line`, stamped with the simulated time since `start`
+fn stamped(start: Instant, line: String) -> String {
+  format!("{:>7}ms {}", start.elapsed().as_millis(), line)
+}
+
+/// appends the block at `height` to the ledger through the coordinator, and describes how it went
+async fn append(
+  state: &CoordinatorState,
+  coordinator: usize,
+  ledger: usize,
+  height: usize,
+) -> String {
+  let block = format!("block {} of ledger {}", height, ledger);
+  let res = tokio::time::timeout(
+    STEP_TIMEOUT,
+    state.append_ledger(None, &handle_bytes(ledger), block.as_bytes(), height),
+  )
+  .await;
+  format!(
+    "coordinator {} appended block {} to ledger {}: {}",
+    coordinator,
+    height,
+    ledger,
+    outcome(res)
+  )
+}
+
+impl Simulation {
+  /// the endorsers and the first coordinator, which creates the view of the endorsers
+  async fn new(seed: u64) -> Simulation {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mocks = MockEndorsers::with_seed(rng.gen());
+    let uris = (0..NUM_ENDORSERS)
+      .map(|i| format!("sim://endorser{}", i))
+      .collect::<Vec<_>>();
+    let endorsers = uris.iter().map(|uri| mocks.start(uri)).collect();
+    let links = Links {
+      latency: vec![Duration::ZERO; NUM_ENDORSERS],
+      partitioned: vec![false; NUM_ENDORSERS],
+      drops: HashMap::new(),
+      loss_percent: 0,
+      loss: (0..NUM_ENDORSERS)
+        .map(|_| StdRng::seed_from_u64(rng.gen()))
+        .collect(),
+    };
+    let network = Arc::new(Network {
+      endorsers,
+      uris,
+      links: Mutex::new(links),
+    });
+
+    let mut simulation = Simulation {
+      rng,
+      clock: Arc::new(SimulatedClock::new(START_MS)),
+      start: Instant::now(),
+      network,
+      nodes: Vec::new(),
+      ledgers: BTreeSet::new(),
+      trace: Arc::new(Mutex::new(Vec::new())),
+    };
+    simulation.start_coordinator().await;
+    let res = tokio::time::timeout(
+      STEP_TIMEOUT,
+      simulation.nodes[0]
+        .state
+        .replace_endorsers(&simulation.network.uris),
+    )
+    .await;
+    simulation.record(format!("coordinator 0 created the view: {}", outcome(res)));
+    simulation
+  }
+
+  fn record(&self, line: String) {
+    self.trace.lock().unwrap().push(stamped(self.start, line));
+  }
+
+  /// the coordinator `coordinator`, unless it crashed
+  fn serving(&self, coordinator: usize) -> Option<Arc<CoordinatorState>> {
+    let node = &self.nodes[coordinator];
+    if node.alive.load(Ordering::SeqCst) {
+      Some(node.state.clone())
+    } else {
+      self.record(format!("coordinator {} is down", coordinator));
+      None
+    }
+  }
+
+  /// the height after the tail of the ledger in the store
+  async fn next_height(&self, ledger: usize) -> usize {
+    let handle = NimbleDigest::digest(&handle_bytes(ledger));
+    match self.nodes[0]
+      .state
+      .ledger_store
+      .read_ledger_tail(&handle)
+      .await
+    {
+      Ok((_entry, height)) => height + 1,
+      Err(_) => 1,
+    }
+  }
+
+  /// starts a coordinator over the store of the first one, and waits until it serves
+  async fn start_coordinator(&mut self) {
+    let id = self.nodes.len();
+    let alive = Arc::new(AtomicBool::new(true));
+    let link = Link {
+      network: self.network.clone(),
+      alive: alive.clone(),
+    };
+    let lease =
+      Lease::new(&format!("coordinator-{}", id), LEASE_DURATION).with_clock(self.clock.clone());
+    let mut state = CoordinatorState::open("memory", &HashMap::new(), None, None, None, None)
+      .await
+      .expect("failed to open a coordinator of the simulation")
+      .with_lease(lease)
+      .with_clock(self.clock.clone())
+      .with_rng_seed(self.rng.gen())
+      .with_endorser_connector(Arc::new(link));
+    if let Some(first) = self.nodes.first() {
+      state.ledger_store = first.state.ledger_store.clone();
+    }
+    let state = Arc::new(state);
+    let mut node = Node {
+      state: state.clone(),
+      alive,
+      renewal: None,
+      appends: Vec::new(),
+    };
+
+    if tokio::time::timeout(STEP_TIMEOUT, state.take_lease())
+      .await
+      .is_err()
+    {
+      self.record(format!("coordinator {} did not take the lease", id));
+      self.nodes.push(node);
+      return;
+    }
+    node.renewal = Some({
+      let state = state.clone();
+      tokio::spawn(async move { state.keep_lease().await })
+    });
+    self.nodes.push(node);
+    let res = tokio::time::timeout(STEP_TIMEOUT, state.recover()).await;
+    self.record(format!(
+      "coordinator {} took the lease and recovered: {}",
+      id,
+      outcome(res)
+    ));
+  }
+
+  async fn step(&mut self, step: &Step) {
+    match *step {
+      Step::Create {
+        coordinator,
+        ledger,
+      } => {
+        if let Some(state) = self.serving(coordinator) {
+          self.ledgers.insert(ledger);
+          let res = tokio::time::timeout(
+            STEP_TIMEOUT,
+            state.create_ledger(None, &handle_bytes(ledger), b"genesis", &[], &[]),
+          )
+          .await;
+          self.record(format!(
+            "coordinator {} created ledger {}: {}",
+            coordinator,
+            ledger,
+            outcome(res)
+          ));
+        }
+      },
+      Step::Append {
+        coordinator,
+        ledger,
+      } => {
+        if let Some(state) = self.serving(coordinator) {
+          let height = self.next_height(ledger).await;
+          let line = append(&state, coordinator, ledger, height).await;
+          self.record(line);
+        }
+      },
+      Step::StartAppend {
+        coordinator,
+        ledger,
+      } => {
+        if let Some(state) = self.serving(coordinator) {
+          let height = self.next_height(ledger).await;
+          let (start, trace) = (self.start, self.trace.clone());
+          let task = tokio::spawn(async move {
+            let line = append(&state, coordinator, ledger, height).await;
+            trace.lock().unwrap().push(stamped(start, line));
+          });
+          self.nodes[coordinator].appends.push(task);
+        }
+      },
+      Step::Delay { endorser, latency } => {
+        self.network.links.lock().unwrap().latency[endorser] = latency;
+      },
+      Step::Drop {
+        endorser,
+        call,
+        times,
+      } => {
+        let endorser = endorser.unwrap_or_else(|| self.rng.gen_range(0..NUM_ENDORSERS));
+        *self
+          .network
+          .links
+          .lock()
+          .unwrap()
+          .drops
+          .entry((endorser, call))
+          .or_default() += times;
+        self.record(format!(
+          "the network drops the next {} {} calls to endorser {}",
+          times, call, endorser
+        ));
+      },
+      Step::Loss { percent } => {
+        self.network.links.lock().unwrap().loss_percent = percent;
+      },
+      Step::Partition(endorser) => {
+        self.network.links.lock().unwrap().partitioned[endorser] = true;
+      },
+      Step::Heal(endorser) => {
+        self.network.links.lock().unwrap().partitioned[endorser] = false;
+      },
+      Step::Run(duration) => {
+        tokio::time::sleep(duration).await;
+      },
+      Step::StopRenewing(coordinator) => {
+        if let Some(renewal) = self.nodes[coordinator].renewal.take() {
+          renewal.abort();
+        }
+      },
+      Step::Crash(coordinator) => {
+        let node = &mut self.nodes[coordinator];
+        node.alive.store(false, Ordering::SeqCst);
+        if let Some(renewal) = node.renewal.take() {
+          renewal.abort();
+        }
+        for task in node.appends.drain(..) {
+          task.abort();
+        }
+        self.record(format!("coordinator {} crashed", coordinator));
+      },
+      Step::Start => self.start_coordinator().await,
+      Step::Check => {
+        for &ledger in &self.ledgers {
+          let handle = NimbleDigest::digest(&handle_bytes(ledger));
+          let stored = match self.nodes[0]
+            .state
+            .ledger_store
+            .read_ledger_tail(&handle)
+            .await
+          {
+            Ok((_entry, height)) => height.to_string(),
+            Err(_) => "-".to_string(),
+          };
+          let endorsed = self
+            .network
+            .endorsers
+            .iter()
+            .map(|endorser| {
+              endorser
+                .height(&handle)
+                .map_or("-".to_string(), |height| height.to_string())
+            })
+            .collect::<Vec<_>>();
+          self.record(format!(
+            "ledger {} is at {} in the store, at {} in the endorsers",
+            ledger,
+            stored,
+            endorsed.join(" ")
+          ));
+        }
+      },
+    }
+  }
+
+  /// stops what is still running, and returns the trace
+  fn finish(self) -> Vec<String> {
+    for node in &self.nodes {
+      node.alive.store(false, Ordering::SeqCst);
+      if let Some(renewal) = &node.renewal {
+        renewal.abort();
+      }
+      for task in &node.appends {
+        task.abort();
+      }
+    }
+    let trace = self.trace.lock().unwrap();
+    trace.clone()
+  }
+}
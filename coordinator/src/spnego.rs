@@ -0,0 +1,332 @@
+//! SPNEGO authentication for the HTTP gateway, as WebHDFS does it (RFC 4559): a client sends
+//! `authorization: Negotiate <base64 token>` with a Kerberos ticket for the service principal of
+//! the coordinator, and the gateway accepts the token with the keytab of that principal.
+//!
+//! The principal that the ticket authenticates maps to a tenant like the default `auth_to_local`
+//! rule of Hadoop maps it to a user: `primary[/instance]@REALM` is the tenant `primary` if the
+//! realm is accepted and `primary` is a tenant of the tenant file, so that the NameNodes of a
+//! cluster, `hdfs/nn1.example.com@EXAMPLE.COM` and `hdfs/nn2.example.com@EXAMPLE.COM`, share the
+//! namespace and the quotas of the tenant `hdfs`. The gateway completes the context with the
+//! first token and does not return the token of the acceptor, so clients must not require
+//! mutual authentication.
+use crate::{
+  config::CoordinatorConfig,
+  tenant::{Authenticator, ClientAuth, Tenant},
+};
+use std::{
+  collections::{HashMap, HashSet},
+  sync::Arc,
+};
+use tonic::Status;
+
+/// the scheme of the `authorization` headers that carry SPNEGO tokens
+pub const NEGOTIATE_SCHEME: &str = "Negotiate";
+
+/// accepts the token of a client, and returns the principal that it authenticates
+pub trait Acceptor: Send + Sync {
+  fn accept(&self, token: &[u8]) -> Result<String, String>;
+}
+
+/// authenticates the clients of the gateway as the tenants their Kerberos principals map to
+pub struct Spnego {
+  acceptor: Box<dyn Acceptor>,
+  /// the realms whose principals are accepted
+  realms: Vec<String>,
+  /// the tenants of the tenant file
+  tenants: HashSet<String>,
+}
+
+impl Spnego {
+  pub fn new(acceptor: Box<dyn Acceptor>, realms: Vec<String>, tenants: HashSet<String>) -> Self {
+    Spnego {
+      acceptor,
+      realms,
+      tenants,
+    }
+  }
+}
+
+/// the tenant that a principal `primary[/instance]@REALM` maps to
+#[allow(clippy::result_large_err)]
+pub fn principal_tenant(
+  principal: &str,
+  realms: &[String],
+  tenants: &HashSet<String>,
+) -> Result<Tenant, Status> {
+  let (name, realm) = principal
+    .rsplit_once('@')
+    .ok_or_else(|| Status::unauthenticated(format!("Principal {} has no realm", principal)))?;
+  if !realms.iter().any(|r| r == realm) {
+    return Err(Status::unauthenticated(format!(
+      "Principal {} is not of an accepted realm",
+      principal
+    )));
+  }
+  let primary = name.split('/').next().unwrap_or("");
+  if primary.is_empty() || !tenants.contains(primary) {
+    return Err(Status::permission_denied(format!(
+      "Principal {} is not a tenant",
+      principal
+    )));
+  }
+  Ok(Tenant(primary.to_string()))
+}
+
+/// decodes the base64 of a token, which clients pad and encode with the standard alphabet
+fn decode_token(credentials: &str) -> Option<Vec<u8>> {
+  let url_safe = credentials
+    .trim()
+    .trim_end_matches('=')
+    .replace('+', "-")
+    .replace('/', "_");
+  base64_url::decode(&url_safe).ok().filter(|t| !t.is_empty())
+}
+
+impl ClientAuth for Spnego {
+  fn scheme(&self) -> &'static str {
+    NEGOTIATE_SCHEME
+  }
+
+  fn authenticate(&self, credentials: &str) -> Result<Tenant, Status> {
+    let token =
+      decode_token(credentials).ok_or_else(|| Status::unauthenticated("Invalid SPNEGO token"))?;
+    let principal = self
+      .acceptor
+      .accept(&token)
+      .map_err(|e| Status::unauthenticated(format!("Invalid SPNEGO token: {}", e)))?;
+    principal_tenant(&principal, &self.realms, &self.tenants)
+  }
+}
+
+/// accepts tokens with GSSAPI and the keytab of the service principal of the gateway
+#[cfg(feature = "spnego")]
+pub struct GssAcceptor {
+  principal: String,
+}
+
+#[cfg(feature = "spnego")]
+impl GssAcceptor {
+  /// an acceptor for `principal`, whose key is in `keytab`. GSSAPI reads the keytab of acceptors
+  /// from KRB5_KTNAME, so the acceptor is made at startup, before the servers run, and sets it
+  /// for the process
+  pub fn new(keytab: &str, principal: &str) -> Result<Self, String> {
+    std::env::set_var("KRB5_KTNAME", keytab);
+    let acceptor = GssAcceptor {
+      principal: principal.to_string(),
+    };
+    // fails with the credentials, rather than with the first client
+    acceptor
+      .credentials()
+      .map_err(|e| format!("cannot acquire the credentials of {}: {}", principal, e))?;
+    Ok(acceptor)
+  }
+
+  fn credentials(&self) -> Result<libgssapi::credential::Cred, libgssapi::error::Error> {
+    use libgssapi::{
+      credential::{Cred, CredUsage},
+      name::Name,
+      oid::{OidSet, GSS_MECH_KRB5, GSS_MECH_SPNEGO, GSS_NT_KRB5_PRINCIPAL},
+    };
+    let name = Name::new(self.principal.as_bytes(), Some(&GSS_NT_KRB5_PRINCIPAL))?;
+    let mut mechs = OidSet::new()?;
+    mechs.add(&GSS_MECH_SPNEGO)?;
+    mechs.add(&GSS_MECH_KRB5)?;
+    Cred::acquire(Some(&name), None, CredUsage::Accept, Some(&mechs))
+  }
+}
+
+#[cfg(feature = "spnego")]
+impl Acceptor for GssAcceptor {
+  fn accept(&self, token: &[u8]) -> Result<String, String> {
+    use libgssapi::context::{SecurityContext, ServerCtx};
+    let mut ctx = ServerCtx::new(self.credentials().map_err(|e| e.to_string())?);
+    ctx.step(token).map_err(|e| e.to_string())?;
+    if !ctx.is_complete() {
+      return Err("the context needs another round trip".to_string());
+    }
+    ctx
+      .source_name()
+      .map(|name| name.to_string())
+      .map_err(|e| e.to_string())
+  }
+}
+
+/// the authenticator of the gateway, which also accepts the Kerberos principals of `tenants` if
+/// `config` sets the keytab and the principal of SPNEGO; without clients that authenticate, the
+/// gateway takes `auth` as it is
+pub fn gateway_authenticator(
+  auth: &Option<Authenticator>,
+  tenants: &Option<HashMap<String, String>>,
+  config: &CoordinatorConfig,
+) -> Result<Option<Authenticator>, String> {
+  match (
+    auth,
+    tenants,
+    &config.spnego.keytab,
+    &config.spnego.principal,
+  ) {
+    (Some(auth), Some(tenants), Some(keytab), Some(principal)) => {
+      let spnego = Spnego::new(
+        acceptor(keytab, principal)?,
+        config.spnego_realms(),
+        tenants.values().cloned().collect(),
+      );
+      Ok(Some(auth.clone().with_auth(Arc::new(spnego))))
+    },
+    _ => Ok(auth.clone()),
+  }
+}
+
+/// the acceptor of the SPNEGO tokens of the gateway, which the coordinator has only if it is built
+/// with the spnego feature; the configuration is rejected without it
+#[cfg(feature = "spnego")]
+fn acceptor(keytab: &str, principal: &str) -> Result<Box<dyn Acceptor>, String> {
+  Ok(Box::new(GssAcceptor::new(keytab, principal)?))
+}
+
+#[cfg(not(feature = "spnego"))]
+fn acceptor(_keytab: &str, _principal: &str) -> Result<Box<dyn Acceptor>, String> {
+  Err("SPNEGO requires a coordinator built with the spnego feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tonic::Code;
+
+  /// accepts the tokens `principal:<name>` as that principal, ignoring what follows a NUL
+  struct FakeAcceptor;
+
+  impl Acceptor for FakeAcceptor {
+    fn accept(&self, token: &[u8]) -> Result<String, String> {
+      let token = token.split(|b| *b == 0).next().unwrap_or_default();
+      std::str::from_utf8(token)
+        .ok()
+        .and_then(|token| token.strip_prefix("principal:"))
+        .map(|principal| principal.to_string())
+        .ok_or_else(|| "defective token".to_string())
+    }
+  }
+
+  fn tenants() -> HashSet<String> {
+    ["hdfs", "hbase"].iter().map(|t| t.to_string()).collect()
+  }
+
+  #[test]
+  pub fn test_principal_tenant() {
+    let realms = vec!["EXAMPLE.COM".to_string(), "OTHER.COM".to_string()];
+    let tenant = |principal: &str| principal_tenant(principal, &realms, &tenants());
+    assert_eq!(
+      tenant("hdfs/nn1.example.com@EXAMPLE.COM").unwrap(),
+      Tenant("hdfs".to_string())
+    );
+    assert_eq!(
+      tenant("hbase@OTHER.COM").unwrap(),
+      Tenant("hbase".to_string())
+    );
+    assert_eq!(tenant("hdfs").unwrap_err().code(), Code::Unauthenticated);
+    assert_eq!(
+      tenant("hdfs/nn1.example.com@EVIL.COM").unwrap_err().code(),
+      Code::Unauthenticated
+    );
+    assert_eq!(
+      tenant("yarn/rm.example.com@EXAMPLE.COM")
+        .unwrap_err()
+        .code(),
+      Code::PermissionDenied
+    );
+    assert_eq!(
+      tenant("/nn1.example.com@EXAMPLE.COM").unwrap_err().code(),
+      Code::PermissionDenied
+    );
+  }
+
+  #[test]
+  pub fn test_negotiate_header() {
+    let spnego = Spnego::new(
+      Box::new(FakeAcceptor),
+      vec!["EXAMPLE.COM".to_string()],
+      tenants(),
+    );
+    let bearer = HashMap::from([("token-a".to_string(), "hbase".to_string())]);
+    let auth = Authenticator::from(bearer).with_auth(Arc::new(spnego));
+    assert!(auth.supports(NEGOTIATE_SCHEME));
+
+    // clients pad their tokens and encode them with the standard alphabet
+    let header = |token: &[u8]| {
+      let encoded = base64_url::encode(token)
+        .replace('-', "+")
+        .replace('_', "/");
+      let padding = "=".repeat((4 - encoded.len() % 4) % 4);
+      format!("Negotiate {}{}", encoded, padding)
+    };
+    let principal = b"principal:hdfs/nn1.example.com@EXAMPLE.COM\0\xff\xfe\0";
+    let res = auth.authenticate(Some(&header(principal)));
+    assert_eq!(res.unwrap(), Tenant("hdfs".to_string()));
+    let res = auth.authenticate(Some(&header(b"principal:hdfs@EXAMPLE.COM")));
+    assert_eq!(res.unwrap(), Tenant("hdfs".to_string()));
+    // token auth is unaffected
+    assert_eq!(
+      auth.authenticate(Some("Bearer token-a")).unwrap(),
+      Tenant("hbase".to_string())
+    );
+
+    let res = auth.authenticate(Some("Negotiate !!"));
+    assert_eq!(res.unwrap_err().code(), Code::Unauthenticated);
+    let res = auth.authenticate(Some("Negotiate "));
+    assert_eq!(res.unwrap_err().code(), Code::Unauthenticated);
+    let res = auth.authenticate(Some(&header(b"ticket")));
+    assert_eq!(res.unwrap_err().code(), Code::Unauthenticated);
+    let res = auth.authenticate(Some(&header(b"principal:hdfs@EVIL.COM")));
+    assert_eq!(res.unwrap_err().code(), Code::Unauthenticated);
+    let res = auth.authenticate(Some(&header(b"principal:yarn@EXAMPLE.COM")));
+    assert_eq!(res.unwrap_err().code(), Code::PermissionDenied);
+
+    // without SPNEGO, the scheme is not supported
+    let auth = Authenticator::from(HashMap::new());
+    assert!(!auth.supports(NEGOTIATE_SCHEME));
+    let res = auth.authenticate(Some(&header(b"principal:hdfs@EXAMPLE.COM")));
+    assert_eq!(res.unwrap_err().code(), Code::Unauthenticated);
+  }
+
+  /// accepts a ticket from a KDC: the service principal NIMBLE_KDC_PRINCIPAL must have its key in
+  /// NIMBLE_KDC_KEYTAB, and the credential cache of the test must hold a ticket-granting ticket of
+  /// a principal of the realm named like a tenant, e.g., from a containerized KDC with
+  /// `kinit hdfs@EXAMPLE.COM`
+  #[cfg(feature = "spnego")]
+  #[test]
+  #[ignore]
+  pub fn test_kdc() {
+    use libgssapi::{
+      context::{ClientCtx, CtxFlags},
+      name::Name,
+      oid::{GSS_MECH_SPNEGO, GSS_NT_KRB5_PRINCIPAL},
+    };
+    let keytab = match std::env::var("NIMBLE_KDC_KEYTAB") {
+      Ok(keytab) => keytab,
+      Err(_) => panic!("The NIMBLE_KDC_KEYTAB environment variable is not specified"),
+    };
+    let principal = match std::env::var("NIMBLE_KDC_PRINCIPAL") {
+      Ok(principal) => principal,
+      Err(_) => panic!("The NIMBLE_KDC_PRINCIPAL environment variable is not specified"),
+    };
+    let realm = principal.rsplit_once('@').unwrap().1.to_string();
+
+    let acceptor = GssAcceptor::new(&keytab, &principal).unwrap();
+    let target = Name::new(principal.as_bytes(), Some(&GSS_NT_KRB5_PRINCIPAL)).unwrap();
+    let mut client = ClientCtx::new(None, target, CtxFlags::empty(), Some(&GSS_MECH_SPNEGO));
+    let token = client.step(None, None).unwrap().unwrap();
+    let header = format!("Negotiate {}", base64_url::encode(&*token));
+
+    let spnego = Spnego::new(Box::new(acceptor), vec![realm], tenants());
+    let auth = Authenticator::default().with_auth(Arc::new(spnego));
+    assert!(auth.authenticate(Some(&header)).is_ok());
+    assert_eq!(
+      auth
+        .authenticate(Some("Negotiate YWJj"))
+        .unwrap_err()
+        .code(),
+      Code::Unauthenticated
+    );
+  }
+}
@@ -0,0 +1,134 @@
+//! Logs and spans of the coordinator. Each call to the client service runs in a span that carries
+//! its method and request ID, and the calls that it makes to endorsers run in child spans that
+//! carry the endorser. The request ID is taken from the `x-request-id` metadata of the call, or
+//! made up if the client did not set one, and is forwarded to the endorsers in the same metadata
+//! so that their logs can be correlated with the coordinator's.
+use ledger::Handle;
+use std::{convert::TryFrom, future::Future};
+use tokio::task::JoinHandle;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tracing::{Instrument, Span};
+use tracing_subscriber::EnvFilter;
+
+/// the metadata key, and HTTP header, that carries the ID of a request
+pub const REQUEST_ID_KEY: &str = "x-request-id";
+const MAX_REQUEST_ID_LEN: usize = 128; // bytes: longer IDs from clients are replaced
+pub const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+tokio::task_local! {
+  /// the ID of the request that the task works on
+  static REQUEST_ID: String;
+}
+
+/// sets up the logs, filtered by `RUST_LOG` and at the info level by default; `json` emits one
+/// JSON object per event, with the fields of the spans that the event is in
+pub fn init(format: &str) {
+  let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+  let builder = tracing_subscriber::fmt().with_env_filter(filter);
+  if format == "json" {
+    builder
+      .json()
+      .with_current_span(true)
+      .with_span_list(true)
+      .init();
+  } else {
+    builder.init();
+  }
+}
+
+/// the ID of a request from its metadata, or a new one if it has none that is printable
+pub fn request_id_of(metadata: &MetadataMap) -> String {
+  match metadata.get(REQUEST_ID_KEY).and_then(|v| v.to_str().ok()) {
+    Some(id) if !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN => id.to_string(),
+    _ => uuid::Uuid::new_v4().to_string(),
+  }
+}
+
+/// the ID of the request that the current task works on, if any
+pub fn request_id() -> Option<String> {
+  REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// runs the future as part of the request with the given ID
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+  REQUEST_ID.scope(request_id, future).await
+}
+
+/// a request to an endorser that carries the ID of the request that the current task works on
+pub fn outgoing<T>(message: T) -> tonic::Request<T> {
+  let mut request = tonic::Request::new(message);
+  if let Some(value) = request_id().and_then(|id| MetadataValue::try_from(id.as_str()).ok()) {
+    request.metadata_mut().insert(REQUEST_ID_KEY, value);
+  }
+  request
+}
+
+/// the span of a call to an endorser, as a child of the current span
+pub fn endorser_span(call: &'static str, pk: &[u8], uri: &str) -> Span {
+  tracing::info_span!(
+    "endorser_call",
+    call,
+    endorser.pk = %hex::encode(pk),
+    endorser.uri = %uri,
+  )
+}
+
+/// spawns a task in the span, which keeps the request ID of the current task
+pub fn spawn<F>(span: Span, future: F) -> JoinHandle<F::Output>
+where
+  F: Future + Send + 'static,
+  F::Output: Send + 'static,
+{
+  match request_id() {
+    Some(id) => tokio::spawn(REQUEST_ID.scope(id, future).instrument(span)),
+    None => tokio::spawn(future.instrument(span)),
+  }
+}
+
+/// records the ledger that the current request is about on its span
+pub fn record_handle(handle: &Handle) {
+  Span::current().record("handle", &tracing::field::display(handle));
+}
+
+/// records the height that the current request reads or writes on its span
+pub fn record_height(height: usize) {
+  Span::current().record("height", &height);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_request_id() {
+    let mut metadata = MetadataMap::new();
+    metadata.insert(REQUEST_ID_KEY, MetadataValue::from_static("req-1"));
+    assert_eq!(request_id_of(&metadata), "req-1");
+
+    // a missing ID is made up
+    let id = request_id_of(&MetadataMap::new());
+    assert!(uuid::Uuid::parse_str(&id).is_ok());
+    assert_eq!(request_id(), None);
+
+    // the ID is forwarded to the endorsers, also from the tasks that the request spawns
+    let request = with_request_id("req-1".to_string(), async {
+      spawn(
+        endorser_span("append", &[1, 2], "http://[::1]:9090"),
+        async { outgoing(()) },
+      )
+      .await
+      .unwrap()
+    })
+    .await;
+    assert_eq!(
+      request
+        .metadata()
+        .get(REQUEST_ID_KEY)
+        .unwrap()
+        .to_str()
+        .unwrap(),
+      "req-1"
+    );
+    assert!(outgoing(()).metadata().get(REQUEST_ID_KEY).is_none());
+  }
+}
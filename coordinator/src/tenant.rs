@@ -0,0 +1,270 @@
+use crate::{
+  authz::{ApiKeys, Identity, Role},
+  canary::CANARY_PREFIX,
+  config::ServiceConfig,
+  coordinator_state::CoordinatorState,
+  delegation::DelegationTokens,
+};
+use std::{collections::HashMap, sync::Arc};
+use tonic::{Request, Status};
+
+/// separates the id of a tenant from the rest of the handle bytes of its ledgers
+pub const TENANT_SEPARATOR: u8 = b'/';
+
+/// a tenant of the coordinator, as authenticated by `AuthLayer`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tenant(pub String);
+
+impl Tenant {
+  /// the prefix of the handle bytes of every ledger of the tenant
+  pub fn prefix(&self) -> Vec<u8> {
+    let mut prefix = self.0.as_bytes().to_vec();
+    prefix.push(TENANT_SEPARATOR);
+    prefix
+  }
+
+  /// moves handle bytes into the namespace of the tenant; handle bytes that are already in it are
+  /// kept, so that clients can pass back the handles the coordinator returned to them
+  pub fn scope(&self, handle_bytes: Vec<u8>) -> Vec<u8> {
+    let prefix = self.prefix();
+    if handle_bytes.is_empty() || handle_bytes.starts_with(&prefix) {
+      handle_bytes
+    } else {
+      [prefix, handle_bytes].concat()
+    }
+  }
+}
+
+/// the tenant that sent a request, if the coordinator serves tenants
+pub fn request_tenant<T>(request: &Request<T>) -> Option<Tenant> {
+  request.extensions().get::<Tenant>().cloned()
+}
+
+/// moves handle bytes into the namespace of the tenant that sent the request, if any
+pub fn scope_handle(tenant: &Option<Tenant>, handle_bytes: Vec<u8>) -> Vec<u8> {
+  match tenant {
+    Some(tenant) => tenant.scope(handle_bytes),
+    None => handle_bytes,
+  }
+}
+
+/// parses a file listing a tenant and its token per line, separated by whitespace; blank lines
+/// and `#` comments are ignored. Returns the tenants keyed by their tokens
+pub fn parse_tenant_file(contents: &str) -> Result<HashMap<String, String>, String> {
+  let mut tenants = HashMap::new();
+  for (number, line) in contents.lines().enumerate() {
+    let line = line.split('#').next().unwrap_or("").trim();
+    if line.is_empty() {
+      continue;
+    }
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let (tenant, token) = match fields.as_slice() {
+      [tenant, token] => (tenant.to_string(), token.to_string()),
+      _ => {
+        return Err(format!(
+          "line {}: expected a tenant and a token",
+          number + 1
+        ))
+      },
+    };
+    if tenant.as_bytes().contains(&TENANT_SEPARATOR) {
+      return Err(format!(
+        "line {}: tenant {} contains a '/'",
+        number + 1,
+        tenant
+      ));
+    }
+    // the canary ledgers stay out of the namespaces of the tenants
+    if Tenant(tenant.clone()).prefix() == CANARY_PREFIX {
+      return Err(format!(
+        "line {}: tenant {} is reserved for the canary",
+        number + 1,
+        tenant
+      ));
+    }
+    if tenants.insert(token, tenant).is_some() {
+      return Err(format!("line {}: the token is used twice", number + 1));
+    }
+  }
+  Ok(tenants)
+}
+
+/// a way for clients to authenticate as a tenant, with the credentials of an
+/// `authorization: <scheme> <credentials>` header
+pub trait ClientAuth: Send + Sync {
+  fn scheme(&self) -> &'static str;
+
+  #[allow(clippy::result_large_err)]
+  fn authenticate(&self, credentials: &str) -> Result<Tenant, Status>;
+
+  /// the identity that the credentials authenticate; the tenants of the ways that know nothing
+  /// of roles are writers
+  #[allow(clippy::result_large_err)]
+  fn identify(&self, credentials: &str) -> Result<Identity, Status> {
+    let tenant = self.authenticate(credentials)?;
+    let name = tenant.0.clone();
+    Ok(Identity::new(
+      self.scheme(),
+      &name,
+      Some(tenant),
+      Role::Writer,
+    ))
+  }
+}
+
+/// the tenants of the tenant file keyed by their tokens, which clients send as
+/// `authorization: Bearer <token>`
+impl ClientAuth for HashMap<String, String> {
+  fn scheme(&self) -> &'static str {
+    "Bearer"
+  }
+
+  fn authenticate(&self, credentials: &str) -> Result<Tenant, Status> {
+    self
+      .get(credentials)
+      .map(|tenant| Tenant(tenant.clone()))
+      .ok_or_else(|| Status::unauthenticated("Invalid tenant token"))
+  }
+}
+
+/// the ways in which clients authenticate as tenants, told apart by the scheme of their
+/// `authorization` header
+#[derive(Clone, Default)]
+pub struct Authenticator {
+  auths: Vec<Arc<dyn ClientAuth>>,
+}
+
+impl Authenticator {
+  pub fn with_auth(mut self, auth: Arc<dyn ClientAuth>) -> Self {
+    self.auths.push(auth);
+    self
+  }
+
+  /// whether clients can authenticate with `scheme`
+  pub fn supports(&self, scheme: &str) -> bool {
+    self.auths.iter().any(|auth| auth.scheme() == scheme)
+  }
+
+  /// the tenant that an `authorization` header authenticates
+  #[allow(clippy::result_large_err)]
+  pub fn authenticate(&self, authorization: Option<&str>) -> Result<Tenant, Status> {
+    let (auth, credentials) = self.auth_of(authorization)?;
+    auth.authenticate(credentials)
+  }
+
+  /// the identity that an `authorization` header authenticates
+  #[allow(clippy::result_large_err)]
+  pub fn identify(&self, authorization: Option<&str>) -> Result<Identity, Status> {
+    let (auth, credentials) = self.auth_of(authorization)?;
+    auth.identify(credentials)
+  }
+
+  /// the way that an `authorization` header authenticates with, and its credentials
+  #[allow(clippy::result_large_err)]
+  fn auth_of<'a>(
+    &self,
+    authorization: Option<&'a str>,
+  ) -> Result<(&Arc<dyn ClientAuth>, &'a str), Status> {
+    let (scheme, credentials) = authorization
+      .and_then(|value| value.split_once(' '))
+      .ok_or_else(|| Status::unauthenticated("Invalid tenant token"))?;
+    match self.auths.iter().find(|auth| auth.scheme() == scheme) {
+      Some(auth) => Ok((auth, credentials)),
+      None => Err(Status::unauthenticated(format!(
+        "Unsupported authorization scheme {}",
+        scheme
+      ))),
+    }
+  }
+}
+
+impl From<HashMap<String, String>> for Authenticator {
+  fn from(tenants: HashMap<String, String>) -> Self {
+    Authenticator::default().with_auth(Arc::new(tenants))
+  }
+}
+
+/// reads the tenant file of `config`, if one is set
+pub fn load_tenants(config: &ServiceConfig) -> Result<Option<HashMap<String, String>>, String> {
+  let path = match &config.tenants {
+    Some(path) => path,
+    None => return Ok(None),
+  };
+  let contents =
+    std::fs::read_to_string(path).map_err(|e| format!("cannot read --tenants {}: {}", path, e))?;
+  parse_tenant_file(&contents)
+    .map(Some)
+    .map_err(|e| format!("invalid --tenants {}: {}", path, e))
+}
+
+/// the authenticator of the clients, who authenticate with the tokens of `tenants`, with
+/// delegation `tokens` if the coordinator issues them, and with `api_keys`; clients need not
+/// authenticate if there are none of these
+pub fn client_authenticator(
+  tenants: &Option<HashMap<String, String>>,
+  tokens: &Option<Arc<DelegationTokens>>,
+  api_keys: &Option<Arc<ApiKeys>>,
+) -> Option<Authenticator> {
+  let auth = tenants.clone().map(|tenants| {
+    let auth = Authenticator::from(tenants);
+    match tokens {
+      Some(tokens) => auth.with_auth(tokens.clone()),
+      None => auth,
+    }
+  });
+  match api_keys {
+    Some(keys) => Some(auth.unwrap_or_default().with_auth(keys.clone())),
+    None => auth,
+  }
+}
+
+/// registers the tenants of the tenant file with the coordinator
+pub async fn register_tenants(state: &CoordinatorState, tenants: &HashMap<String, String>) {
+  let ids = tenants.values().cloned().collect::<Vec<_>>();
+  state.register_tenants(&ids).await;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{parse_tenant_file, Authenticator, Tenant};
+  use tonic::Code;
+
+  #[test]
+  pub fn test_tenant_file_and_token() {
+    let tenants = parse_tenant_file("# tenants\nhdfs-a token-a\n\nhdfs-b  token-b # second\n");
+    let tenants = tenants.unwrap();
+    assert_eq!(tenants.len(), 2);
+    assert_eq!(tenants["token-b"], "hdfs-b");
+    assert!(parse_tenant_file("hdfs-a\n").is_err());
+    assert!(parse_tenant_file("hdfs/a token\n").is_err());
+    assert!(parse_tenant_file("nimble-canary token\n").is_err());
+    assert!(parse_tenant_file("hdfs-a token\nhdfs-b token\n").is_err());
+
+    let auth = Authenticator::from(tenants);
+    let check = |authorization: Option<&str>| auth.authenticate(authorization);
+    assert_eq!(
+      check(Some("Bearer token-a")).unwrap(),
+      Tenant("hdfs-a".to_string())
+    );
+
+    assert_eq!(
+      check(Some("Bearer other")).unwrap_err().code(),
+      Code::Unauthenticated
+    );
+    assert_eq!(check(None).unwrap_err().code(), Code::Unauthenticated);
+    assert_eq!(
+      check(Some("Basic token-a")).unwrap_err().code(),
+      Code::Unauthenticated
+    );
+
+    // handles are scoped once, so the scoped handles that clients pass back stay the same
+    let tenant = Tenant("hdfs-a".to_string());
+    let scoped = tenant.scope(b"ledger".to_vec());
+    assert_eq!(scoped, b"hdfs-a/ledger".to_vec());
+    assert_eq!(tenant.scope(scoped.clone()), scoped);
+    assert_eq!(
+      Tenant("hdfs-b".to_string()).scope(scoped),
+      b"hdfs-b/hdfs-a/ledger".to_vec()
+    );
+  }
+}
@@ -0,0 +1,231 @@
+//! A cluster for end-to-end tests that runs inside the test: mock endorsers and a coordinator that
+//! serve gRPC on ephemeral local ports, as their processes would, and that the coordinator and the
+//! clients reach over the network. A test kills, restarts and partitions the endorsers through
+//! the cluster. Stopping the cluster closes its ports and waits for its servers, so many tests can
+//! run clusters in one process.
+use crate::{
+  coordinator_proto::{call_client::CallClient, call_server::CallServer},
+  mock_endorser::{MockEndorser, MockEndorserService},
+  CoordinatorServiceState, CoordinatorState,
+};
+use ledger::endorser_proto::endorser_call_server::EndorserCallServer;
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{server::Router, Channel, Endpoint};
+
+/// how long a server is given to drain its connections before it is aborted
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the ledger store of a cluster
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StoreKind {
+  Memory,
+  /// a filestore in a directory of its own, which is removed with the cluster
+  TempDir,
+}
+
+/// a gRPC server that the cluster runs
+struct Served {
+  addr: SocketAddr,
+  shutdown: Option<oneshot::Sender<()>>,
+  task: Option<JoinHandle<()>>,
+}
+
+impl Served {
+  /// serves `router` at `addr`; port 0 picks an ephemeral port
+  async fn start(router: Router, addr: SocketAddr) -> Served {
+    let listener = TcpListener::bind(addr)
+      .await
+      .expect("failed to bind a port for the cluster");
+    let addr = listener.local_addr().unwrap();
+    let (shutdown, stopped) = oneshot::channel::<()>();
+    let task = tokio::spawn(async move {
+      let incoming = TcpListenerStream::new(listener);
+      let _ = router
+        .serve_with_incoming_shutdown(incoming, async {
+          let _ = stopped.await;
+        })
+        .await;
+    });
+    Served {
+      addr,
+      shutdown: Some(shutdown),
+      task: Some(task),
+    }
+  }
+
+  fn uri(&self) -> String {
+    format!("http://{}", self.addr)
+  }
+
+  fn is_running(&self) -> bool {
+    self.task.is_some()
+  }
+
+  /// stops accepting calls, and waits for the calls in flight and the connections to close
+  async fn stop(&mut self) {
+    if let Some(shutdown) = self.shutdown.take() {
+      let _ = shutdown.send(());
+    }
+    if let Some(mut task) = self.task.take() {
+      if tokio::time::timeout(STOP_TIMEOUT, &mut task).await.is_err() {
+        task.abort();
+      }
+    }
+  }
+}
+
+impl Drop for Served {
+  fn drop(&mut self) {
+    if let Some(shutdown) = self.shutdown.take() {
+      let _ = shutdown.send(());
+    }
+  }
+}
+
+fn endorser_router(endorser: &Arc<MockEndorser>) -> Router {
+  tonic::transport::Server::builder().add_service(EndorserCallServer::new(MockEndorserService(
+    endorser.clone(),
+  )))
+}
+
+pub struct TestCluster {
+  state: Arc<CoordinatorState>,
+  coordinator: Served,
+  channel: Channel,
+  endorsers: Vec<(Arc<MockEndorser>, Served)>,
+  store_dir: Option<PathBuf>,
+}
+
+impl TestCluster {
+  /// starts `num_endorsers` endorsers, and a coordinator over a store of `store_kind` whose view
+  /// is made of them, once they all serve
+  pub async fn start(num_endorsers: usize, store_kind: StoreKind) -> TestCluster {
+    TestCluster::start_with(num_endorsers, store_kind, |state| state).await
+  }
+
+  /// starts a cluster whose coordinator is set up by `configure` before its view is created
+  pub async fn start_with(
+    num_endorsers: usize,
+    store_kind: StoreKind,
+    configure: impl FnOnce(CoordinatorState) -> CoordinatorState,
+  ) -> TestCluster {
+    let any_port: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let mut endorsers = Vec::with_capacity(num_endorsers);
+    for _ in 0..num_endorsers {
+      let endorser = MockEndorser::new();
+      let server = Served::start(endorser_router(&endorser), any_port).await;
+      endorsers.push((endorser, server));
+    }
+
+    let mut args = HashMap::new();
+    let store_dir = match store_kind {
+      StoreKind::Memory => None,
+      StoreKind::TempDir => {
+        let dir = std::env::temp_dir().join(format!("nimble-cluster-{}", rand::random::<u64>()));
+        args.insert(
+          "NIMBLE_FSTORE_DIR".to_string(),
+          dir.to_str().unwrap().to_string(),
+        );
+        Some(dir)
+      },
+    };
+    let store_type = match store_kind {
+      StoreKind::Memory => "memory",
+      StoreKind::TempDir => "filestore",
+    };
+    let state = CoordinatorState::new(store_type, &args, None, None, None, None)
+      .await
+      .expect("failed to start the coordinator");
+    let state = configure(state);
+    let uris = endorsers
+      .iter()
+      .map(|(_endorser, server)| server.uri())
+      .collect::<Vec<_>>();
+    state
+      .replace_endorsers(&uris)
+      .await
+      .expect("failed to create the view of the cluster");
+    let state = Arc::new(state);
+
+    let router = tonic::transport::Server::builder()
+      .add_service(CallServer::new(CoordinatorServiceState::new(state.clone())));
+    let coordinator = Served::start(router, any_port).await;
+    // the port is bound before the server runs, so the coordinator is ready once it connects
+    let channel = Endpoint::from_shared(coordinator.uri())
+      .unwrap()
+      .connect()
+      .await
+      .expect("failed to connect to the coordinator");
+
+    TestCluster {
+      state,
+      coordinator,
+      channel,
+      endorsers,
+      store_dir,
+    }
+  }
+
+  /// the coordinator, for what the clients cannot do or see
+  pub fn state(&self) -> &Arc<CoordinatorState> {
+    &self.state
+  }
+
+  /// a client of the coordinator; the clients share a connection
+  pub fn client(&self) -> CallClient<Channel> {
+    CallClient::new(self.channel.clone())
+  }
+
+  pub fn coordinator_uri(&self) -> String {
+    self.coordinator.uri()
+  }
+
+  /// the endorser `i`, to inject faults that the cluster does not model, or to look into it
+  pub fn endorser(&self, i: usize) -> &Arc<MockEndorser> {
+    &self.endorsers[i].0
+  }
+
+  /// stops the server of the endorser `i`, which closes its connections; it keeps its state
+  pub async fn kill_endorser(&mut self, i: usize) {
+    self.endorsers[i].1.stop().await;
+  }
+
+  /// serves the endorser `i` again at its address, with the state and the key it had
+  pub async fn restart_endorser(&mut self, i: usize) {
+    let (endorser, server) = &mut self.endorsers[i];
+    if server.is_running() {
+      server.stop().await;
+    }
+    *server = Served::start(endorser_router(endorser), server.addr).await;
+  }
+
+  /// cuts the endorser `i` off from the coordinator: it stays up, but its calls fail as those to
+  /// an unreachable endorser do
+  pub fn partition(&self, i: usize) {
+    self.endorsers[i].0.set_down(true);
+  }
+
+  /// undoes the partition of the endorser `i`
+  pub fn heal(&self, i: usize) {
+    self.endorsers[i].0.set_down(false);
+  }
+
+  /// stops the coordinator and the endorsers, and removes the store directory
+  pub async fn stop(mut self) {
+    self.coordinator.stop().await;
+    for (_endorser, server) in &mut self.endorsers {
+      server.stop().await;
+    }
+  }
+}
+
+impl Drop for TestCluster {
+  fn drop(&mut self) {
+    // the servers stop on their own once they are dropped
+    if let Some(dir) = self.store_dir.take() {
+      let _ = std::fs::remove_dir_all(dir);
+    }
+  }
+}
@@ -0,0 +1,174 @@
+//! TLS on the gRPC services of the coordinator. The certificate, its key and the CAs of clients
+//! are read and checked when the coordinator starts, so that a broken configuration fails then
+//! rather than at the first handshake: the key must sign a message that the public key of the
+//! certificate verifies. The key is a secret, which may come from any provider of
+//! `ledger::secret`.
+use crate::config::TlsConfig;
+use ledger::secret::load_secret;
+use rustls::{sign, PrivateKey, SignatureScheme};
+use rustls_pemfile::Item;
+use std::convert::TryFrom;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+
+/// what the key signs to show that it is the key of the certificate
+const KEY_CHECK_MESSAGE: &[u8] = b"NimbleTlsKeyCheck";
+
+/// the signature schemes of the keys that the coordinator serves with, and how the certificate
+/// verifies their signatures
+static KEY_CHECK_SCHEMES: [(SignatureScheme, &webpki::SignatureAlgorithm); 5] = [
+  (
+    SignatureScheme::ECDSA_NISTP256_SHA256,
+    &webpki::ECDSA_P256_SHA256,
+  ),
+  (
+    SignatureScheme::ECDSA_NISTP384_SHA384,
+    &webpki::ECDSA_P384_SHA384,
+  ),
+  (SignatureScheme::ED25519, &webpki::ED25519),
+  (
+    SignatureScheme::RSA_PSS_SHA256,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+  ),
+  (
+    SignatureScheme::RSA_PKCS1_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+  ),
+];
+
+fn read(path: &str, what: &str) -> Result<Vec<u8>, String> {
+  std::fs::read(path).map_err(|e| format!("cannot read the TLS {} {}: {}", what, path, e))
+}
+
+/// the DER certificates of a PEM file
+fn pem_certs(pem: &[u8]) -> Vec<Vec<u8>> {
+  rustls_pemfile::certs(&mut &*pem).unwrap_or_default()
+}
+
+/// the first DER private key of a PEM file
+fn pem_key(pem: &[u8]) -> Option<Vec<u8>> {
+  rustls_pemfile::read_all(&mut &*pem)
+    .unwrap_or_default()
+    .into_iter()
+    .find_map(|item| match item {
+      Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(key),
+      _ => None,
+    })
+}
+
+/// checks that `key_pem` holds the key of the first certificate of `cert_pem`
+pub fn check_key_pair(cert_pem: &[u8], key_pem: &[u8]) -> Result<(), String> {
+  let certs = pem_certs(cert_pem);
+  let cert = certs
+    .first()
+    .ok_or("the certificate file holds no certificate")?;
+  let key = pem_key(key_pem).ok_or("the key file holds no private key")?;
+  let key = sign::any_supported_type(&PrivateKey(key)).map_err(|_e| "unsupported private key")?;
+  let offered = KEY_CHECK_SCHEMES
+    .iter()
+    .map(|(scheme, _alg)| *scheme)
+    .collect::<Vec<_>>();
+  let signer = key
+    .choose_scheme(&offered)
+    .ok_or("unsupported private key")?;
+  let alg = KEY_CHECK_SCHEMES
+    .iter()
+    .find(|(scheme, _alg)| *scheme == signer.scheme())
+    .map(|(_scheme, alg)| *alg)
+    .ok_or("unsupported private key")?;
+  let signature = signer
+    .sign(KEY_CHECK_MESSAGE)
+    .map_err(|e| format!("the key cannot sign: {}", e))?;
+  let cert = webpki::EndEntityCert::try_from(cert.as_slice())
+    .map_err(|e| format!("invalid certificate: {:?}", e))?;
+  cert
+    .verify_signature(alg, KEY_CHECK_MESSAGE, &signature)
+    .map_err(|_e| "the key does not match the certificate".to_string())
+}
+
+/// the TLS settings of the gRPC servers, if TLS is configured
+pub fn server_tls(config: &TlsConfig) -> Result<Option<ServerTlsConfig>, String> {
+  let (cert_path, key_path) = match (&config.cert, &config.key) {
+    (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+    _ => return Ok(None),
+  };
+  let cert = read(cert_path, "certificate")?;
+  let key = load_secret(key_path).map_err(|e| format!("cannot load the TLS key: {}", e))?;
+  check_key_pair(&cert, &key).map_err(|e| {
+    format!(
+      "invalid TLS certificate {} and key {}: {}",
+      cert_path, key_path, e
+    )
+  })?;
+
+  // the servers verify client certificates only if they require them, since tonic 0.8 cannot
+  // verify the certificates that clients present optionally
+  let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(cert, key.as_slice()));
+  if let (Some(path), true) = (&config.client_ca, config.require_client_auth) {
+    let ca = read(path, "client CA")?;
+    if pem_certs(&ca).is_empty() {
+      return Err(format!("the TLS client CA {} holds no certificate", path));
+    }
+    tls = tls.client_ca_root(Certificate::from_pem(ca));
+  }
+  Ok(Some(tls))
+}
+
+/// a builder of a gRPC server that serves over `tls`, if it is set
+pub fn server_builder(tls: &Option<ServerTlsConfig>) -> Result<Server, String> {
+  match tls {
+    Some(tls) => Server::builder()
+      .tls_config(tls.clone())
+      .map_err(|e| format!("invalid TLS settings: {}", e)),
+    None => Ok(Server::builder()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// a file of the certificates that `scripts/gen-test-certs.sh` generates
+  fn testdata(name: &str) -> String {
+    format!("{}/testdata/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+  }
+
+  #[test]
+  pub fn test_server_tls() {
+    let config = |cert: &str, key: &str| TlsConfig {
+      cert: Some(testdata(cert)),
+      key: Some(testdata(key)),
+      client_ca: Some(testdata("ca.pem")),
+      require_client_auth: true,
+    };
+    assert!(server_tls(&config("server.pem", "server.key"))
+      .unwrap()
+      .is_some());
+    assert!(server_tls(&TlsConfig::default()).unwrap().is_none());
+
+    // a coordinator does not start with a key that is not the key of its certificate
+    let res = server_tls(&config("server.pem", "other.key"));
+    assert!(res.unwrap_err().contains("does not match"));
+    let res = server_tls(&config("server.pem", "client.key"));
+    assert!(res.unwrap_err().contains("does not match"));
+    // nor with files that it cannot read, or that hold something else
+    let res = server_tls(&config("server.pem", "missing.key"));
+    assert!(res.unwrap_err().starts_with("cannot load the TLS key"));
+    // the key may come from another provider of secrets
+    let mut from_env = config("server.pem", "server.key");
+    let env = format!("NIMBLE_TEST_TLS_KEY_{}", rand::random::<u32>());
+    std::env::set_var(
+      &env,
+      std::fs::read_to_string(testdata("server.key")).unwrap(),
+    );
+    from_env.key = Some(format!("env:{}", env));
+    assert!(server_tls(&from_env).unwrap().is_some());
+    std::env::remove_var(&env);
+    let res = server_tls(&config("server.key", "server.key"));
+    assert!(res.unwrap_err().contains("no certificate"));
+    let res = server_tls(&config("server.pem", "server.pem"));
+    assert!(res.unwrap_err().contains("no private key"));
+    let mut bad_ca = config("server.pem", "server.key");
+    bad_ca.client_ca = Some(testdata("other.key"));
+    assert!(server_tls(&bad_ca).unwrap_err().contains("no certificate"));
+  }
+}
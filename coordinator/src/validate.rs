@@ -0,0 +1,511 @@
+//! Checks of the requests of the client service, which each handler runs before anything of the
+//! request reaches the ledger store or the endorsers. A request that fails a check is rejected
+//! with `InvalidArgument`, whose message names the field and the constraint that it violates.
+//!
+//! Clients supply handles of their own choosing, which the coordinator hashes into the digest
+//! that identifies the ledger, so a handle is bounded in size rather than required to be a
+//! digest; a derived handle is bounded through its inputs.
+use crate::{
+  checkpoint::{CHECKPOINT_HANDLE_PREFIX, EDITS_HANDLE_PREFIX},
+  checkpoint_proto::{
+    EditSegment, GetLatestCheckpointReq, RecordCheckpointReq, RecordEditSegmentReq,
+    VerifyCheckpointReq, VerifyEditRangeReq,
+  },
+  coordinator_proto::{
+    AppendBatchReq, AppendReq, ExportLedgerReq, GetLedgerInfoReq, ListLedgersReq, NewLedgerReq,
+    ReadByIndexReq, ReadConsistency, ReadLatestReq, ReadRangeReq, ReadViewByIndexReq,
+    ReadViewTailReq, SealLedgerReq, SnapshotReadReq, WatchReq,
+  },
+  coordinator_state::{MAX_APPEND_BATCH_SIZE, MAX_LEDGER_METADATA_SIZE, MAX_SNAPSHOT_READ_SIZE},
+};
+use ledger::{NimbleDigest, Nonce};
+use std::fmt::Display;
+use tonic::Status;
+
+pub const MAX_HANDLE_SIZE: usize = 256; // bytes: the longest handle that a client supplies
+pub const MAX_APP_BYTES_SIZE: usize = 1024; // bytes: the longest app_bytes, and app_prefix
+pub const MAX_HEIGHT: u64 = i64::MAX as u64; // the highest height that the ledger stores hold
+pub const MAX_READ_RANGE_PAGE_SIZE: u64 = 1024; // entries: the largest page size of ReadRange
+pub const MAX_REQUEST_ID_SIZE: usize = 128; // bytes: the longest request_id of an append
+pub const MAX_LIST_LEDGERS_PAGE_SIZE: u64 = 1000; // ledgers: the largest page size of ListLedgers
+pub const MAX_CHECKPOINT_METADATA_SIZE: usize = 4096; // bytes: the longest checkpoint metadata
+const FSIMAGE_DIGEST_SIZE: usize = 32; // bytes: the SHA-256 of an fsimage
+const FSIMAGE_MD5_SIZE: usize = 16; // bytes: the MD5 of an fsimage
+const EDIT_SEGMENT_DIGEST_SIZE: usize = 32; // bytes: the SHA-256 of an edit-log segment
+pub const MAX_VERIFY_EDIT_SEGMENTS: usize = 1024; // segments: the most that VerifyEditRange checks
+const READ_RANGE_PAGE_TOKEN_SIZE: usize = 8; // bytes: the index that the next page starts at
+
+fn invalid(field: &str, constraint: impl Display) -> Status {
+  Status::invalid_argument(format!("{} {}", field, constraint))
+}
+
+fn check_handle(field: &str, handle: &[u8]) -> Result<(), Status> {
+  if handle.is_empty() || handle.len() > MAX_HANDLE_SIZE {
+    return Err(invalid(
+      field,
+      format_args!("must be 1 to {} bytes", MAX_HANDLE_SIZE),
+    ));
+  }
+  Ok(())
+}
+
+/// a namespace ID is part of the handles of the ledgers of the namespace, after `prefix`
+fn check_namespace_id(field: &str, namespace_id: &str, prefix: &[u8]) -> Result<(), Status> {
+  let max_size = MAX_HANDLE_SIZE - prefix.len();
+  if namespace_id.is_empty() || namespace_id.len() > max_size {
+    return Err(invalid(
+      field,
+      format_args!("must be 1 to {} bytes", max_size),
+    ));
+  }
+  Ok(())
+}
+
+fn check_exact_size(field: &str, bytes: &[u8], size: usize) -> Result<(), Status> {
+  if bytes.len() != size {
+    return Err(invalid(
+      field,
+      format_args!("must be exactly {} bytes", size),
+    ));
+  }
+  Ok(())
+}
+
+fn check_txid_range(field: &str, start_txid: u64, end_txid: u64) -> Result<(), Status> {
+  if start_txid == 0 || start_txid > end_txid {
+    return Err(invalid(
+      field,
+      "must start at 1 or above and end at or after its start",
+    ));
+  }
+  Ok(())
+}
+
+fn check_nonce(field: &str, nonce: &[u8]) -> Result<(), Status> {
+  if nonce.len() != Nonce::num_bytes() {
+    return Err(invalid(
+      field,
+      format_args!("must be exactly {} bytes", Nonce::num_bytes()),
+    ));
+  }
+  Ok(())
+}
+
+fn check_size(field: &str, bytes: &[u8], max_size: usize) -> Result<(), Status> {
+  if bytes.len() > max_size {
+    return Err(invalid(
+      field,
+      format_args!("must be at most {} bytes", max_size),
+    ));
+  }
+  Ok(())
+}
+
+fn check_at_most(field: &str, value: u64, max: u64) -> Result<(), Status> {
+  if value > max {
+    return Err(invalid(field, format_args!("must be at most {}", max)));
+  }
+  Ok(())
+}
+
+pub fn new_ledger(req: &NewLedgerReq, max_block_size: usize) -> Result<(), Status> {
+  // either the client supplies a handle or the handle is derived from (app_bytes, nonce)
+  if req.handle.is_empty() {
+    check_size("app_bytes", &req.app_bytes, MAX_APP_BYTES_SIZE)?;
+    check_nonce("nonce", &req.nonce)?;
+  } else {
+    check_handle("handle", &req.handle)?;
+    if !req.app_bytes.is_empty() || !req.nonce.is_empty() {
+      return Err(invalid(
+        "app_bytes and nonce",
+        "must be empty if handle is set",
+      ));
+    }
+  }
+  check_size("block", &req.block, max_block_size)?;
+  check_size("metadata", &req.metadata, MAX_LEDGER_METADATA_SIZE)
+}
+
+pub fn append(req: &AppendReq, max_block_size: usize) -> Result<(), Status> {
+  check_handle("handle", &req.handle)?;
+  check_size("block", &req.block, max_block_size)?;
+  check_size("request_id", req.request_id.as_bytes(), MAX_REQUEST_ID_SIZE)?;
+  // the block is appended at expected_height + 1
+  check_at_most("expected_height", req.expected_height, MAX_HEIGHT - 1)
+}
+
+/// checks an item of a batch, which fails on its own
+pub fn append_batch_item(req: &AppendReq, max_block_size: usize) -> Result<(), Status> {
+  append(req, max_block_size)?;
+  // an item is not retried on its own, so it is not named either
+  if !req.request_id.is_empty() {
+    return Err(invalid("request_id", "must be empty in a batch"));
+  }
+  Ok(())
+}
+
+/// checks the batch as a whole; its items are checked with `append_batch_item`
+pub fn append_batch(req: &AppendBatchReq) -> Result<(), Status> {
+  if req.items.len() > MAX_APPEND_BATCH_SIZE {
+    return Err(invalid(
+      "items",
+      format_args!("must hold at most {} appends", MAX_APPEND_BATCH_SIZE),
+    ));
+  }
+  Ok(())
+}
+
+pub fn seal_ledger(req: &SealLedgerReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)
+}
+
+pub fn read_latest(req: &ReadLatestReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)?;
+  match ReadConsistency::from_i32(req.consistency) {
+    Some(ReadConsistency::Attested) => check_nonce("nonce", &req.nonce),
+    Some(ReadConsistency::Cached) => Ok(()),
+    None => Err(invalid("consistency", "must be ATTESTED or CACHED")),
+  }
+}
+
+pub fn snapshot_read(req: &SnapshotReadReq) -> Result<(), Status> {
+  if req.handles.is_empty() || req.handles.len() > MAX_SNAPSHOT_READ_SIZE {
+    return Err(invalid(
+      "handles",
+      format_args!("must hold 1 to {} handles", MAX_SNAPSHOT_READ_SIZE),
+    ));
+  }
+  req
+    .handles
+    .iter()
+    .try_for_each(|handle| check_handle("handles", handle))?;
+  check_nonce("nonce", &req.nonce)
+}
+
+pub fn read_by_index(req: &ReadByIndexReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)?;
+  check_at_most("index", req.index, MAX_HEIGHT)
+}
+
+pub fn read_range(req: &ReadRangeReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)?;
+  check_at_most("to", req.to, MAX_HEIGHT)?;
+  if req.from > req.to {
+    return Err(invalid("from", "must be at most to"));
+  }
+  check_at_most("page_size", req.page_size, MAX_READ_RANGE_PAGE_SIZE)?;
+  if !req.page_token.is_empty() && req.page_token.len() != READ_RANGE_PAGE_TOKEN_SIZE {
+    return Err(invalid(
+      "page_token",
+      "must be empty or the next_page_token of the previous page",
+    ));
+  }
+  Ok(())
+}
+
+pub fn watch(req: &WatchReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)?;
+  check_at_most("from_height", req.from_height, MAX_HEIGHT)
+}
+
+pub fn export_ledger(req: &ExportLedgerReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)
+}
+
+pub fn get_ledger_info(req: &GetLedgerInfoReq) -> Result<(), Status> {
+  check_handle("handle", &req.handle)?;
+  if req.attested {
+    check_nonce("nonce", &req.nonce)?;
+  }
+  Ok(())
+}
+
+pub fn list_ledgers(req: &ListLedgersReq) -> Result<(), Status> {
+  if !req.page_token.is_empty() && req.page_token.len() != NimbleDigest::num_bytes() {
+    return Err(invalid(
+      "page_token",
+      "must be empty or the next_page_token of the previous page",
+    ));
+  }
+  check_at_most("page_size", req.page_size, MAX_LIST_LEDGERS_PAGE_SIZE)?;
+  check_size("app_prefix", &req.app_prefix, MAX_APP_BYTES_SIZE)
+}
+
+pub fn read_view_by_index(req: &ReadViewByIndexReq) -> Result<(), Status> {
+  check_at_most("index", req.index, MAX_HEIGHT)
+}
+
+pub fn read_view_tail(req: &ReadViewTailReq) -> Result<(), Status> {
+  if !req.nonce.is_empty() {
+    check_nonce("nonce", &req.nonce)?;
+  }
+  Ok(())
+}
+
+pub fn record_checkpoint(req: &RecordCheckpointReq) -> Result<(), Status> {
+  check_namespace_id("namespace_id", &req.namespace_id, CHECKPOINT_HANDLE_PREFIX)?;
+  check_exact_size("fsimage_digest", &req.fsimage_digest, FSIMAGE_DIGEST_SIZE)?;
+  check_exact_size("md5", &req.md5, FSIMAGE_MD5_SIZE)?;
+  check_size("metadata", &req.metadata, MAX_CHECKPOINT_METADATA_SIZE)
+}
+
+pub fn get_latest_checkpoint(req: &GetLatestCheckpointReq) -> Result<(), Status> {
+  check_namespace_id("namespace_id", &req.namespace_id, CHECKPOINT_HANDLE_PREFIX)?;
+  check_nonce("nonce", &req.nonce)
+}
+
+pub fn verify_checkpoint(req: &VerifyCheckpointReq) -> Result<(), Status> {
+  check_namespace_id("namespace_id", &req.namespace_id, CHECKPOINT_HANDLE_PREFIX)?;
+  check_exact_size("fsimage_digest", &req.fsimage_digest, FSIMAGE_DIGEST_SIZE)
+}
+
+pub fn record_edit_segment(req: &RecordEditSegmentReq) -> Result<(), Status> {
+  check_namespace_id("namespace_id", &req.namespace_id, EDITS_HANDLE_PREFIX)?;
+  check_txid_range("start_txid..end_txid", req.start_txid, req.end_txid)?;
+  check_exact_size(
+    "segment_digest",
+    &req.segment_digest,
+    EDIT_SEGMENT_DIGEST_SIZE,
+  )
+}
+
+fn check_edit_segment(segment: &EditSegment) -> Result<(), Status> {
+  check_txid_range(
+    "segments.start_txid..end_txid",
+    segment.start_txid,
+    segment.end_txid,
+  )?;
+  check_exact_size(
+    "segments.segment_digest",
+    &segment.segment_digest,
+    EDIT_SEGMENT_DIGEST_SIZE,
+  )
+}
+
+pub fn verify_edit_range(req: &VerifyEditRangeReq) -> Result<(), Status> {
+  check_namespace_id("namespace_id", &req.namespace_id, EDITS_HANDLE_PREFIX)?;
+  check_txid_range("start_txid..end_txid", req.start_txid, req.end_txid)?;
+  if req.segments.len() > MAX_VERIFY_EDIT_SEGMENTS {
+    return Err(invalid(
+      "segments",
+      format_args!("must hold at most {} segments", MAX_VERIFY_EDIT_SEGMENTS),
+    ));
+  }
+  req.segments.iter().try_for_each(check_edit_segment)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tonic::Code;
+
+  #[test]
+  fn test_validate() {
+    let handle = vec![1u8; 16];
+    let nonce = vec![2u8; Nonce::num_bytes()];
+    let new_ledger_req = NewLedgerReq {
+      handle: handle.clone(),
+      block: b"genesis".to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: vec![],
+    };
+    assert!(new_ledger(&new_ledger_req, 16).is_ok());
+
+    // the message names the field and the constraint
+    let status = new_ledger(&new_ledger_req, 4).unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "block must be at most 4 bytes");
+    let derived = NewLedgerReq {
+      handle: vec![],
+      app_bytes: b"app".to_vec(),
+      nonce: nonce[1..].to_vec(),
+      ..new_ledger_req.clone()
+    };
+    assert_eq!(
+      new_ledger(&derived, 16).unwrap_err().message(),
+      "nonce must be exactly 16 bytes"
+    );
+    let both = NewLedgerReq {
+      nonce: nonce.clone(),
+      ..new_ledger_req.clone()
+    };
+    assert!(new_ledger(&both, 16).is_err());
+    let long_handle = NewLedgerReq {
+      handle: vec![0u8; MAX_HANDLE_SIZE + 1],
+      ..new_ledger_req
+    };
+    assert!(new_ledger(&long_handle, 16).is_err());
+
+    // heights are bounded by what the stores hold
+    let append_req = AppendReq {
+      handle: handle.clone(),
+      block: vec![],
+      expected_height: MAX_HEIGHT - 1,
+      request_id: String::new(),
+    };
+    assert!(append(&append_req, 16).is_ok());
+    let append_req = AppendReq {
+      expected_height: u64::MAX,
+      ..append_req
+    };
+    assert_eq!(
+      append(&append_req, 16).unwrap_err().message(),
+      format!("expected_height must be at most {}", MAX_HEIGHT - 1)
+    );
+    let append_req = AppendReq {
+      expected_height: 1,
+      request_id: "a".repeat(MAX_REQUEST_ID_SIZE + 1),
+      ..append_req
+    };
+    assert!(append(&append_req, 16).is_err());
+    let append_req = AppendReq {
+      request_id: "request-1".to_string(),
+      ..append_req
+    };
+    assert!(append(&append_req, 16).is_ok());
+    assert_eq!(
+      append_batch_item(&append_req, 16).unwrap_err().message(),
+      "request_id must be empty in a batch"
+    );
+
+    // a nonce is needed only where the endorsers sign it
+    let read_req = ReadLatestReq {
+      handle: handle.clone(),
+      nonce: vec![],
+      consistency: ReadConsistency::Cached as i32,
+    };
+    assert!(read_latest(&read_req).is_ok());
+    let read_req = ReadLatestReq {
+      consistency: ReadConsistency::Attested as i32,
+      ..read_req
+    };
+    assert!(read_latest(&read_req).is_err());
+    let read_req = ReadLatestReq {
+      consistency: 7,
+      nonce,
+      ..read_req
+    };
+    assert!(read_latest(&read_req).is_err());
+
+    // a snapshot reads a bounded number of ledgers with one nonce
+    let snapshot_req = SnapshotReadReq {
+      handles: vec![handle.clone(), b"ledger".to_vec()],
+      nonce: vec![2u8; Nonce::num_bytes()],
+    };
+    assert!(snapshot_read(&snapshot_req).is_ok());
+    let too_many = SnapshotReadReq {
+      handles: vec![handle.clone(); MAX_SNAPSHOT_READ_SIZE + 1],
+      ..snapshot_req.clone()
+    };
+    assert_eq!(
+      snapshot_read(&too_many).unwrap_err().message(),
+      format!("handles must hold 1 to {} handles", MAX_SNAPSHOT_READ_SIZE)
+    );
+    let empty = SnapshotReadReq {
+      handles: vec![],
+      ..snapshot_req.clone()
+    };
+    assert!(snapshot_read(&empty).is_err());
+    let no_nonce = SnapshotReadReq {
+      nonce: vec![],
+      ..snapshot_req
+    };
+    assert!(snapshot_read(&no_nonce).is_err());
+
+    let range_req = ReadRangeReq {
+      handle,
+      from: 2,
+      to: 1,
+      page_size: 0,
+      page_token: vec![],
+    };
+    assert!(read_range(&range_req).is_err());
+    let range_req = ReadRangeReq { to: 2, ..range_req };
+    assert!(read_range(&range_req).is_ok());
+    let range_req = ReadRangeReq {
+      page_size: MAX_READ_RANGE_PAGE_SIZE + 1,
+      ..range_req
+    };
+    assert!(read_range(&range_req).is_err());
+    let watch_req = WatchReq {
+      handle: vec![],
+      from_height: 0,
+      include_blocks: false,
+    };
+    assert!(watch(&watch_req).is_err());
+    let watch_req = WatchReq {
+      handle: b"ledger".to_vec(),
+      from_height: MAX_HEIGHT + 1,
+      ..watch_req
+    };
+    assert!(watch(&watch_req).is_err());
+    let list_req = ListLedgersReq {
+      page_token: vec![0u8; 3],
+      page_size: 0,
+      app_prefix: vec![],
+    };
+    assert!(list_ledgers(&list_req).is_err());
+
+    // the namespace ID of a checkpoint fits in a handle, and its digests have their sizes
+    let checkpoint_req = RecordCheckpointReq {
+      namespace_id: "ns-1".to_string(),
+      txid: 7,
+      fsimage_digest: vec![1u8; FSIMAGE_DIGEST_SIZE],
+      md5: vec![2u8; FSIMAGE_MD5_SIZE],
+      metadata: vec![],
+    };
+    assert!(record_checkpoint(&checkpoint_req).is_ok());
+    let checkpoint_req = RecordCheckpointReq {
+      md5: vec![2u8; FSIMAGE_DIGEST_SIZE],
+      ..checkpoint_req
+    };
+    assert_eq!(
+      record_checkpoint(&checkpoint_req).unwrap_err().message(),
+      "md5 must be exactly 16 bytes"
+    );
+    let verify_req = VerifyCheckpointReq {
+      namespace_id: "n".repeat(MAX_HANDLE_SIZE),
+      txid: 7,
+      fsimage_digest: vec![1u8; FSIMAGE_DIGEST_SIZE],
+    };
+    assert!(verify_checkpoint(&verify_req).is_err());
+    let latest_req = GetLatestCheckpointReq {
+      namespace_id: "ns-1".to_string(),
+      nonce: vec![],
+    };
+    assert!(get_latest_checkpoint(&latest_req).is_err());
+
+    // a segment covers at least one txid, and txids start at 1
+    let segment_req = RecordEditSegmentReq {
+      namespace_id: "ns-1".to_string(),
+      start_txid: 101,
+      end_txid: 101,
+      segment_digest: vec![3u8; EDIT_SEGMENT_DIGEST_SIZE],
+    };
+    assert!(record_edit_segment(&segment_req).is_ok());
+    for (start_txid, end_txid) in [(0, 100), (101, 100)] {
+      let segment_req = RecordEditSegmentReq {
+        start_txid,
+        end_txid,
+        ..segment_req.clone()
+      };
+      assert!(record_edit_segment(&segment_req).is_err());
+    }
+    let range_req = VerifyEditRangeReq {
+      namespace_id: "ns-1".to_string(),
+      start_txid: 1,
+      end_txid: 200,
+      segments: vec![EditSegment {
+        start_txid: 1,
+        end_txid: 100,
+        segment_digest: vec![3u8; 16],
+        height: 0,
+      }],
+    };
+    assert_eq!(
+      verify_edit_range(&range_req).unwrap_err().message(),
+      "segments.segment_digest must be exactly 32 bytes"
+    );
+  }
+}
@@ -0,0 +1,82 @@
+//! The committed heights of the ledgers that clients watch. An append publishes the height of its
+//! entry once the receipts are in the ledger store, which never waits for the watches: each watch
+//! reads the entries up to the latest height from the ledger store at the pace of its client.
+use ledger::Handle;
+use std::{
+  collections::HashMap,
+  sync::{Mutex, PoisonError},
+};
+use tokio::sync::watch;
+
+#[derive(Default)]
+pub struct Watchers {
+  ledgers: Mutex<HashMap<Handle, watch::Sender<usize>>>,
+}
+
+impl Watchers {
+  /// follows the committed height of ledger `handle`; the receiver sees the heights published
+  /// after the call, so a watch subscribes before it reads what is committed already
+  pub fn subscribe(&self, handle: &Handle) -> watch::Receiver<usize> {
+    let mut ledgers = self.ledgers.lock().unwrap_or_else(PoisonError::into_inner);
+    // the ledgers whose watches all ended are dropped here, since watches start rarely
+    ledgers.retain(|_handle, sender| sender.receiver_count() > 0);
+    ledgers
+      .entry(*handle)
+      .or_insert_with(|| watch::channel(0).0)
+      .subscribe()
+  }
+
+  /// publishes that the entry at `height` of ledger `handle` committed; a height below one that
+  /// was published already is ignored, since appends to a ledger can finish out of order
+  pub fn publish(&self, handle: &Handle, height: usize) {
+    let ledgers = self.ledgers.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(sender) = ledgers.get(handle) {
+      sender.send_if_modified(|committed| {
+        let modified = height > *committed;
+        if modified {
+          *committed = height;
+        }
+        modified
+      });
+    }
+  }
+
+  /// the number of ledgers with watches
+  pub fn num_watched(&self) -> usize {
+    let ledgers = self.ledgers.lock().unwrap_or_else(PoisonError::into_inner);
+    ledgers
+      .values()
+      .filter(|sender| sender.receiver_count() > 0)
+      .count()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::NimbleDigest;
+
+  #[test]
+  fn test_publish() {
+    let watchers = Watchers::default();
+    let handle = NimbleDigest::digest(b"ledger");
+    // nothing is kept for a ledger without watches
+    watchers.publish(&handle, 1);
+    assert_eq!(watchers.num_watched(), 0);
+
+    let mut rx = watchers.subscribe(&handle);
+    assert!(!rx.has_changed().unwrap());
+    watchers.publish(&handle, 3);
+    watchers.publish(&handle, 2);
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(*rx.borrow_and_update(), 3);
+    watchers.publish(&handle, 3);
+    assert!(!rx.has_changed().unwrap());
+    assert_eq!(watchers.num_watched(), 1);
+
+    drop(rx);
+    assert_eq!(watchers.num_watched(), 0);
+    let _rx = watchers.subscribe(&NimbleDigest::digest(b"other"));
+    assert_eq!(watchers.ledgers.lock().unwrap().len(), 1);
+  }
+}
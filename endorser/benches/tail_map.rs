@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ledger::{Block, MetaBlock, NimbleDigest, Nonces};
+use std::{sync::Arc, thread};
+
+// the endorser is a binary, so the map is compiled in from its source
+#[path = "../src/errors.rs"]
+#[allow(dead_code)]
+mod errors;
+#[path = "../src/tail_map.rs"]
+#[allow(dead_code)]
+mod tail_map;
+
+use tail_map::{Tail, TailMap};
+
+const NUM_THREADS: usize = 4;
+const APPENDS_PER_THREAD: usize = 1_000;
+
+fn handle(i: usize) -> NimbleDigest {
+  NimbleDigest::digest(&i.to_le_bytes())
+}
+
+fn tail(i: usize) -> Tail {
+  (
+    MetaBlock::genesis(&handle(i)),
+    Block::new(&i.to_le_bytes()),
+    Nonces::new(),
+  )
+}
+
+fn bench_get_or_insert(c: &mut Criterion) {
+  let mut group = c.benchmark_group("tail_map_get_or_insert");
+  for num_tails in [1_000usize, 10_000] {
+    let handles = (0..num_tails).map(handle).collect::<Vec<_>>();
+    group.bench_with_input(
+      BenchmarkId::from_parameter(num_tails),
+      &handles,
+      |b, handles| {
+        b.iter(|| {
+          let map = TailMap::new();
+          for (i, handle) in handles.iter().enumerate() {
+            map.get_or_insert(handle, || tail(i)).unwrap();
+          }
+          map.num_bytes()
+        })
+      },
+    );
+  }
+  group.finish();
+}
+
+// appends of threads to ledgers of their own, which contend on the counters of the map, and on its
+// shards unless there are enough of them
+fn bench_concurrent_appends(c: &mut Criterion) {
+  let mut group = c.benchmark_group("tail_map_concurrent_appends");
+  for num_shards in [1usize, 64] {
+    let map = Arc::new(TailMap::with_shards(num_shards));
+    for i in 0..NUM_THREADS {
+      map.get_or_insert(&handle(i), || tail(i)).unwrap();
+    }
+    group.bench_with_input(BenchmarkId::from_parameter(num_shards), &map, |b, map| {
+      b.iter(|| {
+        let threads = (0..NUM_THREADS)
+          .map(|i| {
+            let map = map.clone();
+            thread::spawn(move || {
+              let block = Block::new(&i.to_le_bytes());
+              for _ in 0..APPENDS_PER_THREAD {
+                let protected = map.get(&handle(i)).unwrap().unwrap();
+                let mut tail = protected.write().unwrap();
+                let metablock = tail.0.next(&handle(i)).unwrap();
+                map.replace(&mut tail, (metablock, block.clone(), Nonces::new()));
+              }
+            })
+          })
+          .collect::<Vec<_>>();
+        for thread in threads {
+          thread.join().unwrap();
+        }
+      })
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, bench_get_or_insert, bench_concurrent_appends);
+criterion_main!(benches);
@@ -1,21 +1,68 @@
-use crate::errors::EndorserError;
-
-use itertools::Itertools;
+use crate::{
+  errors::EndorserError,
+  tail_map::{Tail, TailMap},
+};
 
 use ledger::endorser_proto::{EndorserMode, LedgerChunkEntry, LedgerTailMap, LedgerTailMapEntry};
 
 use ledger::{
+  compute_endorser_heartbeat_message, compute_key_rotation_message, compute_ledger_tail_message,
   produce_hash_of_state,
-  signature::{PrivateKey, PrivateKeyTrait, PublicKey},
-  Block, CustomSerde, Handle, IdSig, MetaBlock, NimbleDigest, NimbleHashTrait, Nonces, Receipt,
-  Receipts,
+  signature::{PrivateKey, PrivateKeyTrait, PublicKey, PublicKeyTrait},
+  view_ledger_handle, Block, CustomSerde, Handle, IdSig, KeyRotation, MetaBlock, NimbleDigest,
+  NimbleHashTrait, Nonce, Nonces, Receipt, Receipts,
 };
 use std::{
-  collections::{hash_map, HashMap},
   ops::{Deref, DerefMut},
-  sync::{Arc, RwLock},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock, RwLockReadGuard,
+  },
+  time::{SystemTime, UNIX_EPOCH},
 };
 
+/// a key pair in a digital signature scheme
+struct KeyPair {
+  private_key: PrivateKey,
+  public_key: PublicKey,
+}
+
+impl KeyPair {
+  fn new() -> Self {
+    let private_key = PrivateKey::new();
+    let public_key = private_key.get_public_key().unwrap();
+    KeyPair {
+      private_key,
+      public_key,
+    }
+  }
+
+  fn from_private_key(private_key: PrivateKey) -> Result<Self, EndorserError> {
+    let public_key = private_key.get_public_key()?;
+    Ok(KeyPair {
+      private_key,
+      public_key,
+    })
+  }
+
+  fn sign(&self, message: &NimbleDigest) -> Result<IdSig, EndorserError> {
+    let signature = self.private_key.sign(&message.to_bytes())?;
+    Ok(IdSig::new(self.public_key.clone(), signature))
+  }
+}
+
+/// the keys of an endorser, which change with the views: an endorser joins a view with the key
+/// that it rotates to, and retires its old key once that view is activated
+struct EndorserKeys {
+  /// the key that the endorser signs with
+  current: Arc<KeyPair>,
+  /// the key that the endorser rotates to, until it joins a view with it
+  next: Option<KeyPair>,
+  /// the key that the endorser rotated from, which signs the state of the view that it left until
+  /// the view that it joined is activated
+  previous: Option<Arc<KeyPair>>,
+}
+
 struct ViewLedgerState {
   view_ledger_tail_metablock: MetaBlock,
 
@@ -28,38 +75,103 @@ struct ViewLedgerState {
 
   /// Endorser's group identity
   group_identity: NimbleDigest,
+
+  /// the keys are switched together with the mode, under the same lock
+  keys: EndorserKeys,
 }
 
-type ProtectedMetaBlock = Arc<RwLock<(MetaBlock, Block, Nonces)>>;
+/// what an endorser signs the tails of ledgers with in a view, which it takes from the view
+/// ledger state under its lock, so that it signs after releasing the lock
+struct TailSigner {
+  group_identity: NimbleDigest,
+  view: NimbleDigest,
+  key: Arc<KeyPair>,
+}
+
+impl TailSigner {
+  /// a receipt on `metablock`, the tail of the ledger `handle`, which signs `tail_hash`
+  fn sign(
+    &self,
+    handle: &Handle,
+    tail_hash: &NimbleDigest,
+    metablock: MetaBlock,
+  ) -> Result<Receipt, EndorserError> {
+    let message = compute_ledger_tail_message(&self.group_identity, &self.view, handle, tail_hash);
+    Ok(Receipt::new(self.view, metablock, self.key.sign(&message)?))
+  }
+}
 
 /// Endorser's internal state
+///
+/// The locks are taken in order: the view ledger state, a shard of the ledger tails, and the tail
+/// of a ledger. The tails of the ledgers are updated under a read lock of the view ledger state, so
+/// a view change, which write-locks it, sees all of them or none, but they are signed after the
+/// locks are released.
 pub struct EndorserState {
-  /// a key pair in a digital signature scheme
-  private_key: PrivateKey,
-  public_key: PublicKey,
+  /// the tails of the ledgers
+  ledger_tail_map: TailMap,
 
-  /// a map from fixed-sized labels to a tail hash and a counter
-  ledger_tail_map: Arc<RwLock<HashMap<Handle, ProtectedMetaBlock>>>,
+  view_ledger_state: RwLock<ViewLedgerState>,
 
-  view_ledger_state: Arc<RwLock<ViewLedgerState>>,
+  /// the heartbeats that the endorser signed since it started
+  heartbeat_counter: AtomicU64,
 }
 
 impl EndorserState {
   pub fn new() -> Self {
-    let private_key = PrivateKey::new();
-    let public_key = private_key.get_public_key().unwrap();
+    EndorserState::with_key_pair(KeyPair::new())
+  }
+
+  /// an endorser that signs with `private_key` rather than with a key that it generates, so that
+  /// it keeps its identity across restarts; a key that it rotates to is not written back
+  pub fn with_private_key(private_key: PrivateKey) -> Result<Self, EndorserError> {
+    let key_pair = KeyPair::from_private_key(private_key)?;
+    Ok(EndorserState::with_key_pair(key_pair))
+  }
+
+  fn with_key_pair(current: KeyPair) -> Self {
     EndorserState {
-      private_key,
-      public_key,
-      ledger_tail_map: Arc::new(RwLock::new(HashMap::new())),
-      view_ledger_state: Arc::new(RwLock::new(ViewLedgerState {
+      ledger_tail_map: TailMap::new(),
+      view_ledger_state: RwLock::new(ViewLedgerState {
         view_ledger_tail_metablock: MetaBlock::default(),
         view_ledger_tail_hash: MetaBlock::default().hash(),
         view_ledger_prev_metablock: MetaBlock::default(),
         endorser_mode: EndorserMode::Uninitialized,
         group_identity: NimbleDigest::default(),
-      })),
+        keys: EndorserKeys {
+          current: Arc::new(current),
+          next: None,
+          previous: None,
+        },
+      }),
+      heartbeat_counter: AtomicU64::new(0),
+    }
+  }
+
+  /// read-locks the view ledger state of an active endorser, and takes what it signs the tails of
+  /// ledgers with
+  fn read_active(
+    &self,
+  ) -> Result<(RwLockReadGuard<'_, ViewLedgerState>, TailSigner), EndorserError> {
+    let view_ledger_state = self
+      .view_ledger_state
+      .read()
+      .map_err(|_e| EndorserError::FailedToAcquireViewLedgerReadLock)?;
+    match view_ledger_state.endorser_mode {
+      EndorserMode::Uninitialized | EndorserMode::Initialized => {
+        return Err(EndorserError::NotActive);
+      },
+      EndorserMode::Finalized => {
+        return Err(EndorserError::AlreadyFinalized);
+      },
+      _ => {},
     }
+    let signer = TailSigner {
+      group_identity: view_ledger_state.group_identity,
+      view: view_ledger_state.view_ledger_tail_hash,
+      key: view_ledger_state.keys.current.clone(),
+    };
+    Ok((view_ledger_state, signer))
   }
 
   pub fn initialize_state(
@@ -69,30 +181,80 @@ impl EndorserState {
     view_ledger_tail_metablock: &MetaBlock,
     block_hash: &NimbleDigest,
     expected_height: usize,
+    public_key: Option<&PublicKey>,
   ) -> Result<Receipt, EndorserError> {
     if let Ok(mut view_ledger_state) = self.view_ledger_state.write() {
-      if view_ledger_state.endorser_mode != EndorserMode::Uninitialized {
-        return Err(EndorserError::AlreadyInitialized);
+      // an endorser that finalized the previous view into this very view entry joins the new view
+      // as well; an endorser already initialized for it re-signs so that a view change can resume
+      let rejoins = match view_ledger_state.endorser_mode {
+        EndorserMode::Uninitialized => false,
+        EndorserMode::Initialized | EndorserMode::Finalized => {
+          if view_ledger_state.group_identity != *group_identity
+            || view_ledger_state.view_ledger_prev_metablock != *view_ledger_tail_metablock
+            || view_ledger_state.view_ledger_tail_metablock.get_height() != expected_height
+            || view_ledger_state
+              .view_ledger_tail_metablock
+              .get_block_hash()
+              != block_hash
+          {
+            return Err(EndorserError::AlreadyInitialized);
+          }
+          true
+        },
+        _ => return Err(EndorserError::AlreadyInitialized),
+      };
+
+      // the view lists the endorser under its current key, or under the key that it rotates to
+      let keys = &view_ledger_state.keys;
+      let is_next = |pk: &Vec<u8>| {
+        keys
+          .next
+          .as_ref()
+          .map_or(false, |next| next.public_key.to_bytes() == *pk)
+      };
+      let rotates = match public_key.map(|pk| pk.to_bytes()) {
+        None => false,
+        Some(pk) if pk == keys.current.public_key.to_bytes() => false,
+        Some(pk) if is_next(&pk) => true,
+        Some(_) => return Err(EndorserError::UnknownKey),
+      };
+
+      // parse every entry before touching the state so that a malformed map leaves it unchanged
+      let mut entries: Vec<(Handle, Tail)> = Vec::with_capacity(ledger_tail_map.len());
+      for entry in ledger_tail_map {
+        entries.push((
+          NimbleDigest::from_bytes(&entry.handle)?,
+          (
+            MetaBlock::from_bytes(&entry.metablock)?,
+            Block::from_bytes(&entry.block)?,
+            Nonces::from_bytes(&entry.nonces)?,
+          ),
+        ));
       }
 
-      if let Ok(mut ledger_tail_map_wr) = self.ledger_tail_map.write() {
-        for entry in ledger_tail_map {
-          ledger_tail_map_wr.insert(
-            NimbleDigest::from_bytes(&entry.handle).unwrap(),
-            Arc::new(RwLock::new((
-              MetaBlock::from_bytes(&entry.metablock).unwrap(),
-              Block::from_bytes(&entry.block).unwrap(),
-              Nonces::from_bytes(&entry.nonces).unwrap(),
-            ))),
-          );
-        }
+      self.ledger_tail_map.insert_all(entries, rejoins)?;
+
+      if rotates {
+        let keys = &mut view_ledger_state.keys;
+        let next = keys.next.take().unwrap();
+        keys.previous = Some(std::mem::replace(&mut keys.current, Arc::new(next)));
+      }
+
+      view_ledger_state.endorser_mode = EndorserMode::Initialized;
+      if rejoins {
+        // the view ledger already holds the entry of the new view
+        let view_ledger_state = view_ledger_state.deref();
+        return self.sign_view_ledger(
+          view_ledger_state,
+          ledger_tail_map,
+          &view_ledger_state.keys.current,
+        );
       }
 
       view_ledger_state.view_ledger_prev_metablock =
         view_ledger_state.view_ledger_tail_metablock.clone();
       view_ledger_state.view_ledger_tail_metablock = view_ledger_tail_metablock.clone();
       view_ledger_state.view_ledger_tail_hash = view_ledger_state.view_ledger_tail_metablock.hash();
-      view_ledger_state.endorser_mode = EndorserMode::Initialized;
       view_ledger_state.group_identity = *group_identity;
 
       self.append_view_ledger(
@@ -112,47 +274,27 @@ impl EndorserState {
     block_hash: &NimbleDigest,
     block: &Block,
   ) -> Result<Receipt, EndorserError> {
-    if let Ok(view_ledger_state) = self.view_ledger_state.read() {
-      match view_ledger_state.endorser_mode {
-        EndorserMode::Uninitialized | EndorserMode::Initialized => {
-          return Err(EndorserError::NotActive);
-        },
-        EndorserMode::Finalized => {
-          return Err(EndorserError::AlreadyFinalized);
-        },
-        _ => {},
-      }
+    let (view_ledger_state, signer) = self.read_active()?;
 
-      // create a genesis metablock that embeds the current tail of the view/membership ledger
-      let view = view_ledger_state.view_ledger_tail_hash;
-      let metablock = MetaBlock::genesis(block_hash);
-      let message = view_ledger_state
-        .group_identity
-        .digest_with(&view.digest_with(&handle.digest_with(&metablock.hash())));
-      let signature = self.private_key.sign(&message.to_bytes()).unwrap();
-
-      // check if the handle already exists, if so, return an error
-      if let Ok(mut ledger_tail_map) = self.ledger_tail_map.write() {
-        if let hash_map::Entry::Vacant(e) = ledger_tail_map.entry(*handle) {
-          e.insert(Arc::new(RwLock::new((
-            metablock.clone(),
-            block.clone(),
-            Nonces::new(),
-          ))));
-          Ok(Receipt::new(
-            view,
-            metablock,
-            IdSig::new(self.public_key.clone(), signature),
-          ))
-        } else {
-          Err(EndorserError::LedgerExists)
-        }
-      } else {
-        Err(EndorserError::FailedToAcquireLedgerMapWriteLock)
+    // create a genesis metablock; a retried create of a ledger that has not grown since gets the
+    // same receipt again, any other create of an existing ledger returns an error
+    let metablock = MetaBlock::genesis(block_hash);
+    let (tail, inserted) = self
+      .ledger_tail_map
+      .get_or_insert(handle, || (metablock.clone(), block.clone(), Nonces::new()))?;
+    if !inserted {
+      let is_same_genesis = match tail.read() {
+        Ok(tail) => tail.0 == metablock,
+        Err(_) => return Err(EndorserError::FailedToAcquireLedgerEntryReadLock),
+      };
+      if !is_same_genesis {
+        return Err(EndorserError::LedgerExists);
       }
-    } else {
-      Err(EndorserError::FailedToAcquireViewLedgerReadLock)
     }
+    drop(view_ledger_state);
+
+    // the genesis metablock embeds the current tail of the view/membership ledger
+    signer.sign(handle, &metablock.hash(), metablock)
   }
 
   pub fn read_latest(
@@ -160,81 +302,47 @@ impl EndorserState {
     handle: &NimbleDigest,
     nonce: &[u8],
   ) -> Result<(Receipt, Block, Nonces), EndorserError> {
-    if let Ok(view_ledger_state) = self.view_ledger_state.read() {
-      match view_ledger_state.endorser_mode {
-        EndorserMode::Uninitialized | EndorserMode::Initialized => {
-          return Err(EndorserError::NotActive);
-        },
-        EndorserMode::Finalized => {
-          return Err(EndorserError::AlreadyFinalized);
-        },
-        _ => {},
-      }
+    // reject malformed nonces before signing anything
+    let nonce = Nonce::try_from_bytes(nonce).map_err(|_e| EndorserError::InvalidNonce)?;
 
-      if let Ok(ledger_tail_map) = self.ledger_tail_map.read() {
-        match ledger_tail_map.get(handle) {
-          None => Err(EndorserError::InvalidLedgerName),
-          Some(protected_metablock) => {
-            if let Ok(e) = protected_metablock.read() {
-              let view = view_ledger_state.view_ledger_tail_hash;
-              let metablock = &e.0;
-              let tail_hash = metablock.hash();
-              let message = view_ledger_state.group_identity.digest_with(
-                &view.digest_with(&handle.digest_with(&tail_hash.digest_with_bytes(nonce))),
-              );
-              let signature = self.private_key.sign(&message.to_bytes()).unwrap();
-
-              Ok((
-                Receipt::new(
-                  view,
-                  metablock.clone(),
-                  IdSig::new(self.public_key.clone(), signature),
-                ),
-                e.1.clone(),
-                e.2.clone(),
-              ))
-            } else {
-              Err(EndorserError::FailedToAcquireLedgerEntryReadLock)
-            }
-          },
-        }
-      } else {
-        Err(EndorserError::FailedToAcquireLedgerMapReadLock)
-      }
-    } else {
-      Err(EndorserError::FailedToAcquireViewLedgerReadLock)
-    }
+    let (view_ledger_state, signer) = self.read_active()?;
+    let tail = self
+      .ledger_tail_map
+      .get(handle)?
+      .ok_or(EndorserError::InvalidLedgerName)?;
+    let (metablock, block, nonces) = match tail.read() {
+      Ok(tail) => tail.clone(),
+      Err(_) => return Err(EndorserError::FailedToAcquireLedgerEntryReadLock),
+    };
+    drop(view_ledger_state);
+
+    let tail_hash = metablock.hash().digest_with_bytes(&nonce.to_bytes());
+    Ok((signer.sign(handle, &tail_hash, metablock)?, block, nonces))
   }
 
-  pub fn get_height(&self, handle: &NimbleDigest) -> Result<usize, EndorserError> {
-    if let Ok(view_ledger_state) = self.view_ledger_state.read() {
-      match view_ledger_state.endorser_mode {
-        EndorserMode::Uninitialized | EndorserMode::Initialized => {
-          return Err(EndorserError::NotActive);
-        },
-        EndorserMode::Finalized => {
-          return Err(EndorserError::AlreadyFinalized);
-        },
-        _ => {},
-      }
+  /// signs the tail of the view ledger with a reader's nonce, vouching that it is the current view
+  pub fn read_view_tail(&self, nonce: &[u8]) -> Result<Receipt, EndorserError> {
+    let nonce = Nonce::try_from_bytes(nonce).map_err(|_e| EndorserError::InvalidNonce)?;
 
-      if let Ok(ledger_tail_map) = self.ledger_tail_map.read() {
-        match ledger_tail_map.get(handle) {
-          None => Err(EndorserError::InvalidLedgerName),
-          Some(protected_metablock) => {
-            if let Ok(e) = protected_metablock.read() {
-              Ok(e.0.get_height())
-            } else {
-              Err(EndorserError::FailedToAcquireLedgerEntryReadLock)
-            }
-          },
-        }
-      } else {
-        Err(EndorserError::FailedToAcquireLedgerMapReadLock)
-      }
-    } else {
-      Err(EndorserError::FailedToAcquireViewLedgerReadLock)
-    }
+    let (view_ledger_state, signer) = self.read_active()?;
+    let metablock = view_ledger_state.view_ledger_tail_metablock.clone();
+    drop(view_ledger_state);
+
+    let tail_hash = signer.view.digest_with_bytes(&nonce.to_bytes());
+    signer.sign(&view_ledger_handle(), &tail_hash, metablock)
+  }
+
+  pub fn get_height(&self, handle: &NimbleDigest) -> Result<usize, EndorserError> {
+    let (_view_ledger_state, _signer) = self.read_active()?;
+    let tail = self
+      .ledger_tail_map
+      .get(handle)?
+      .ok_or(EndorserError::InvalidLedgerName)?;
+    let height = match tail.read() {
+      Ok(tail) => tail.0.get_height(),
+      Err(_) => return Err(EndorserError::FailedToAcquireLedgerEntryReadLock),
+    };
+    Ok(height)
   }
 
   pub fn append(
@@ -242,10 +350,69 @@ impl EndorserState {
     handle: &NimbleDigest,
     block_hash: &NimbleDigest,
     expected_height: usize,
+    expected_tail: Option<&NimbleDigest>,
     block: &Block,
     nonces: &Nonces,
   ) -> Result<Receipt, EndorserError> {
+    let (view_ledger_state, signer) = self.read_active()?;
+    let tail = self
+      .ledger_tail_map
+      .get(handle)?
+      .ok_or(EndorserError::InvalidLedgerName)?;
+
+    let metablock = if let Ok(mut e) = tail.write() {
+      // extend the tail, returning an error in case the height overflows
+      let new_metablock = match e.0.next(block_hash) {
+        Some(metablock) => metablock,
+        None => return Err(EndorserError::LedgerHeightOverflow),
+      };
+
+      // a retried append of the tail is endorsed again, so that the coordinator can collect the
+      // receipts of an append that it persisted but failed to complete
+      if expected_height == e.0.get_height() && e.0.get_block_hash() == block_hash {
+        e.0.clone()
+      } else {
+        if expected_height < new_metablock.get_height() {
+          return Err(EndorserError::LedgerExists);
+        }
+
+        if expected_height > new_metablock.get_height() {
+          return Err(EndorserError::OutOfOrder);
+        }
+
+        if let Some(tail) = expected_tail {
+          if e.0.get_block_hash() != tail {
+            return Err(EndorserError::TailMismatch);
+          }
+        }
+
+        self.ledger_tail_map.replace(
+          &mut e,
+          (new_metablock.clone(), block.clone(), nonces.clone()),
+        );
+        new_metablock
+      }
+    } else {
+      return Err(EndorserError::FailedToAcquireLedgerEntryWriteLock);
+    };
+    drop(view_ledger_state);
+
+    signer.sign(handle, &metablock.hash(), metablock)
+  }
+
+  pub fn get_public_key(&self) -> Result<PublicKey, EndorserError> {
     if let Ok(view_ledger_state) = self.view_ledger_state.read() {
+      Ok(view_ledger_state.keys.current.public_key.clone())
+    } else {
+      Err(EndorserError::FailedToAcquireViewLedgerReadLock)
+    }
+  }
+
+  /// generates the key that the endorser rotates to, unless it generated one before, and signs
+  /// the handover to it with the current key; the endorser keeps signing with the current key
+  /// until it is initialized for a view that lists the new key
+  pub fn rotate_key(&self) -> Result<KeyRotation, EndorserError> {
+    if let Ok(mut view_ledger_state) = self.view_ledger_state.write() {
       match view_ledger_state.endorser_mode {
         EndorserMode::Uninitialized | EndorserMode::Initialized => {
           return Err(EndorserError::NotActive);
@@ -256,61 +423,25 @@ impl EndorserState {
         _ => {},
       }
 
-      if let Ok(ledger_tail_map) = self.ledger_tail_map.read() {
-        match ledger_tail_map.get(handle) {
-          None => Err(EndorserError::InvalidLedgerName),
-          Some(protected_metablock) => {
-            if let Ok(mut e) = protected_metablock.write() {
-              let metablock = &e.0;
-              // increment height and returning an error in case of overflow
-              let height_plus_one = {
-                let res = metablock.get_height().checked_add(1);
-                if res.is_none() {
-                  return Err(EndorserError::LedgerHeightOverflow);
-                }
-                res.unwrap()
-              };
-
-              if expected_height < height_plus_one {
-                return Err(EndorserError::LedgerExists);
-              }
-
-              if expected_height > height_plus_one {
-                return Err(EndorserError::OutOfOrder);
-              }
-
-              let new_metablock = MetaBlock::new(&metablock.hash(), block_hash, height_plus_one);
-
-              let view = view_ledger_state.view_ledger_tail_hash;
-              let message = view_ledger_state
-                .group_identity
-                .digest_with(&view.digest_with(&handle.digest_with(&new_metablock.hash())));
-
-              let signature = self.private_key.sign(&message.to_bytes()).unwrap();
-
-              *e = (new_metablock.clone(), block.clone(), nonces.clone());
-              Ok(Receipt::new(
-                view,
-                new_metablock,
-                IdSig::new(self.public_key.clone(), signature),
-              ))
-            } else {
-              Err(EndorserError::FailedToAcquireLedgerEntryWriteLock)
-            }
-          },
-        }
-      } else {
-        Err(EndorserError::FailedToAcquireLedgerMapReadLock)
-      }
+      let group_identity = view_ledger_state.group_identity;
+      let keys = &mut view_ledger_state.keys;
+      let next = keys.next.get_or_insert_with(KeyPair::new);
+      let message = compute_key_rotation_message(
+        &group_identity,
+        &keys.current.public_key.to_bytes(),
+        &next.public_key.to_bytes(),
+      );
+      let signature = keys.current.private_key.sign(&message.to_bytes())?;
+      Ok(KeyRotation::new(
+        &keys.current.public_key,
+        &next.public_key,
+        &signature,
+      ))
     } else {
-      Err(EndorserError::FailedToAcquireViewLedgerReadLock)
+      Err(EndorserError::FailedToAcquireViewLedgerWriteLock)
     }
   }
 
-  pub fn get_public_key(&self) -> PublicKey {
-    self.public_key.clone()
-  }
-
   fn append_view_ledger(
     &self,
     view_ledger_state: &mut ViewLedgerState,
@@ -318,76 +449,72 @@ impl EndorserState {
     block_hash: &NimbleDigest,
     expected_height: usize,
   ) -> Result<Receipt, EndorserError> {
-    let metablock = &view_ledger_state.view_ledger_tail_metablock;
-
-    // perform a checked addition of height with 1
-    let height_plus_one = {
-      let res = metablock.get_height().checked_add(1);
-      if res.is_none() {
-        return Err(EndorserError::LedgerHeightOverflow);
-      }
-      res.unwrap()
+    // formulate a metablock for the new entry on the view ledger; and hash it to get the updated tail hash
+    let new_metablock = match view_ledger_state
+      .view_ledger_tail_metablock
+      .next(block_hash)
+    {
+      Some(metablock) => metablock,
+      None => return Err(EndorserError::LedgerHeightOverflow),
     };
 
     assert!(expected_height != 0);
-    if expected_height < height_plus_one {
+    if expected_height < new_metablock.get_height() {
       return Err(EndorserError::InvalidTailHeight);
     }
 
-    if expected_height > height_plus_one {
+    if expected_height > new_metablock.get_height() {
       return Err(EndorserError::OutOfOrder);
     }
 
-    // formulate a metablock for the new entry on the view ledger; and hash it to get the updated tail hash
-    let prev = view_ledger_state.view_ledger_tail_hash;
-    let new_metablock = MetaBlock::new(&prev, block_hash, height_plus_one);
-
     // update the internal state
     view_ledger_state.view_ledger_prev_metablock =
       view_ledger_state.view_ledger_tail_metablock.clone();
     view_ledger_state.view_ledger_tail_metablock = new_metablock;
     view_ledger_state.view_ledger_tail_hash = view_ledger_state.view_ledger_tail_metablock.hash();
 
-    Ok(self.sign_view_ledger(view_ledger_state, ledger_tail_map))
+    let view_ledger_state = &*view_ledger_state;
+    self.sign_view_ledger(
+      view_ledger_state,
+      ledger_tail_map,
+      &view_ledger_state.keys.current,
+    )
   }
 
   fn sign_view_ledger(
     &self,
     view_ledger_state: &ViewLedgerState,
     ledger_tail_map: &Vec<LedgerTailMapEntry>,
-  ) -> Receipt {
+    key: &KeyPair,
+  ) -> Result<Receipt, EndorserError> {
     // the view embedded in the view ledger is the hash of the current state of the endorser
     let view = produce_hash_of_state(ledger_tail_map);
     let message = view_ledger_state
       .group_identity
       .digest_with(&view.digest_with(&view_ledger_state.view_ledger_tail_hash));
-    let signature = self.private_key.sign(&message.to_bytes()).unwrap();
 
-    Receipt::new(
+    Ok(Receipt::new(
       view,
       view_ledger_state.view_ledger_tail_metablock.clone(),
-      IdSig::new(self.public_key.clone(), signature),
-    )
+      key.sign(&message)?,
+    ))
   }
 
   fn construct_ledger_tail_map(&self) -> Result<Vec<LedgerTailMapEntry>, EndorserError> {
-    let mut ledger_tail_map = Vec::new();
-    if let Ok(ledger_tail_map_rd) = self.ledger_tail_map.read() {
-      for (handle, value) in ledger_tail_map_rd.deref().iter().sorted_by_key(|x| x.0) {
-        if let Ok(e) = value.read() {
-          ledger_tail_map.push(LedgerTailMapEntry {
-            handle: handle.to_bytes(),
-            height: e.0.get_height() as u64,
-            metablock: e.0.to_bytes(),
-            block: e.1.to_bytes(),
-            nonces: e.2.to_bytes(),
-          });
-        } else {
-          return Err(EndorserError::FailedToAcquireLedgerEntryReadLock);
-        }
+    let tails = self.ledger_tail_map.sorted()?;
+    let mut ledger_tail_map = Vec::with_capacity(tails.len());
+    for (handle, tail) in tails {
+      if let Ok(e) = tail.read() {
+        ledger_tail_map.push(LedgerTailMapEntry {
+          handle: handle.to_bytes().into(),
+          height: e.0.get_height() as u64,
+          metablock: e.0.to_bytes().into(),
+          block: e.1.to_bytes().into(),
+          nonces: e.2.to_bytes().into(),
+        });
+      } else {
+        return Err(EndorserError::FailedToAcquireLedgerEntryReadLock);
       }
-    } else {
-      return Err(EndorserError::FailedToAcquireLedgerMapReadLock);
     }
 
     Ok(ledger_tail_map)
@@ -399,16 +526,33 @@ impl EndorserState {
     expected_height: usize,
   ) -> Result<(Receipt, Vec<LedgerTailMapEntry>), EndorserError> {
     if let Ok(mut view_ledger_state) = self.view_ledger_state.write() {
-      if view_ledger_state.endorser_mode == EndorserMode::Uninitialized
-        || view_ledger_state.endorser_mode == EndorserMode::Initialized
+      // an endorser that already joined the view it is asked to finalize into only re-signs its
+      // state, which lets an interrupted view change resume
+      let joined_view = view_ledger_state.endorser_mode == EndorserMode::Initialized
+        && view_ledger_state.view_ledger_tail_metablock.get_height() == expected_height
+        && view_ledger_state
+          .view_ledger_tail_metablock
+          .get_block_hash()
+          == block_hash;
+
+      if !joined_view
+        && (view_ledger_state.endorser_mode == EndorserMode::Uninitialized
+          || view_ledger_state.endorser_mode == EndorserMode::Initialized)
       {
         return Err(EndorserError::NotActive);
       };
 
       let ledger_tail_map = self.construct_ledger_tail_map()?;
 
-      let receipt = if view_ledger_state.endorser_mode == EndorserMode::Finalized {
-        self.sign_view_ledger(view_ledger_state.deref(), &ledger_tail_map)
+      let receipt = if joined_view || view_ledger_state.endorser_mode == EndorserMode::Finalized {
+        // an endorser that joined the view with a new key finalized the old view with its old key
+        let view_ledger_state = view_ledger_state.deref();
+        let keys = &view_ledger_state.keys;
+        let key = match (joined_view, &keys.previous) {
+          (true, Some(previous)) => previous,
+          _ => &keys.current,
+        };
+        self.sign_view_ledger(view_ledger_state, &ledger_tail_map, key)?
       } else {
         view_ledger_state.endorser_mode = EndorserMode::Finalized;
 
@@ -426,6 +570,42 @@ impl EndorserState {
     }
   }
 
+  /// signs a heartbeat with `nonce`, in any mode, with the current key: the next value of the
+  /// heartbeat counter, and the time of the clock of the endorser. Returns the counter, the time in
+  /// milliseconds since the Unix epoch, and the signature
+  pub fn heartbeat(&self, nonce: &[u8]) -> Result<(u64, u64, IdSig), EndorserError> {
+    let nonce = Nonce::try_from_bytes(nonce).map_err(|_e| EndorserError::InvalidNonce)?;
+    let key = self
+      .view_ledger_state
+      .read()
+      .map_err(|_e| EndorserError::FailedToAcquireViewLedgerReadLock)?
+      .keys
+      .current
+      .clone();
+    let counter = self.heartbeat_counter.fetch_add(1, Ordering::SeqCst) + 1;
+    let timestamp_ms = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_millis() as u64)
+      .unwrap_or_default();
+    let message = compute_endorser_heartbeat_message(&nonce, counter, timestamp_ms);
+    Ok((counter, timestamp_ms, key.sign(&message)?))
+  }
+
+  /// the mode of the endorser, the number of ledgers it holds the tails of, and the approximate
+  /// bytes of memory that the tails take
+  pub fn get_status(&self) -> Result<(EndorserMode, usize, usize), EndorserError> {
+    let endorser_mode = self
+      .view_ledger_state
+      .read()
+      .map_err(|_e| EndorserError::FailedToAcquireViewLedgerReadLock)?
+      .endorser_mode;
+    Ok((
+      endorser_mode,
+      self.ledger_tail_map.num_tails(),
+      self.ledger_tail_map.num_bytes(),
+    ))
+  }
+
   pub fn read_state(
     &self,
   ) -> Result<(Receipt, EndorserMode, Vec<LedgerTailMapEntry>), EndorserError> {
@@ -433,7 +613,11 @@ impl EndorserState {
       let ledger_tail_map = self.construct_ledger_tail_map()?;
 
       Ok((
-        self.sign_view_ledger(view_ledger_state.deref(), &ledger_tail_map),
+        self.sign_view_ledger(
+          view_ledger_state.deref(),
+          &ledger_tail_map,
+          &view_ledger_state.keys.current,
+        )?,
         view_ledger_state.endorser_mode,
         ledger_tail_map,
       ))
@@ -467,7 +651,7 @@ impl EndorserState {
       let res = receipts.verify_view_change(
         old_config,
         new_config,
-        &self.public_key,
+        &view_ledger_state.keys.current.public_key,
         &view_ledger_state.group_identity,
         &view_ledger_state.view_ledger_prev_metablock,
         &view_ledger_state.view_ledger_tail_metablock,
@@ -479,6 +663,8 @@ impl EndorserState {
         Err(EndorserError::FailedToActivate)
       } else {
         view_ledger_state.endorser_mode = EndorserMode::Active;
+        // the view that the endorser joined with its new key is committed
+        view_ledger_state.keys.previous = None;
         Ok(())
       }
     } else {
@@ -490,7 +676,19 @@ impl EndorserState {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use ledger::{
+    compute_aggregated_block_hash,
+    hash::{HashAlgorithm, HASH_ALGORITHM},
+  };
   use rand::Rng;
+  use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Instant,
+  };
+
+  include!("../../ledger/testdata/receipts.rs");
+  use golden::*;
 
   #[test]
   pub fn check_endorser_new_ledger_and_get_tail() {
@@ -525,6 +723,7 @@ mod tests {
       &MetaBlock::default(),
       &view_block_hash,
       height_plus_one,
+      None,
     );
     assert!(res.is_ok());
 
@@ -564,7 +763,7 @@ mod tests {
     assert!(receipt
       .get_id_sig()
       .verify_with_id(
-        &endorser_state.public_key,
+        &endorser_state.get_public_key().unwrap(),
         &view_block_hash
           .digest_with(
             &receipt
@@ -575,18 +774,65 @@ mod tests {
       )
       .is_ok());
 
+    // A retried create is endorsed again, while a create with another genesis block is rejected.
+    let retried = endorser_state
+      .new_ledger(&handle, &block_hash, &block)
+      .unwrap();
+    assert_eq!(retried.get_metablock(), receipt.get_metablock());
+    assert_eq!(retried.get_view(), receipt.get_view());
+    let other_block = Block::new(&rand::thread_rng().gen::<[u8; 32]>());
+    assert_eq!(
+      endorser_state
+        .new_ledger(&handle, &other_block.hash(), &other_block)
+        .unwrap_err(),
+      EndorserError::LedgerExists
+    );
+
     // Fetch the value currently in the tail.
-    let tail_result = endorser_state.read_latest(&handle, &[0]);
+    let tail_result = endorser_state.read_latest(&handle, &Nonce::new().to_bytes());
     assert!(tail_result.is_ok());
 
-    let ledger_tail_map = endorser_state.ledger_tail_map.read().expect("failed");
+    // Short and empty nonces are rejected before anything is signed.
+    for nonce in [&[0u8][..], &[]] {
+      assert_eq!(
+        endorser_state.read_latest(&handle, nonce).unwrap_err(),
+        EndorserError::InvalidNonce
+      );
+    }
+
+    // The view ledger tail is signed together with the reader's nonce.
+    let nonce = Nonce::new().to_bytes();
+    let view_receipt = endorser_state.read_view_tail(&nonce).unwrap();
+    assert_eq!(view_receipt.get_view(), receipt.get_view());
+    assert_eq!(view_receipt.get_metablock().hash(), *receipt.get_view());
+    let view = *view_receipt.get_view();
+    assert!(view_receipt
+      .get_id_sig()
+      .verify_with_id(
+        &endorser_state.get_public_key().unwrap(),
+        &compute_ledger_tail_message(
+          &view_block_hash,
+          &view,
+          &view_ledger_handle(),
+          &view.digest_with_bytes(&nonce),
+        )
+        .to_bytes(),
+      )
+      .is_ok());
+    assert_eq!(
+      endorser_state.read_view_tail(&[0u8]).unwrap_err(),
+      EndorserError::InvalidNonce
+    );
 
-    let metablock = &ledger_tail_map
+    let metablock = endorser_state
+      .ledger_tail_map
       .get(&handle)
       .unwrap()
+      .unwrap()
       .read()
       .expect("failed")
-      .0;
+      .0
+      .clone();
     assert_eq!(metablock.get_height(), 0usize);
     assert_eq!(metablock.hash(), genesis_tail_hash);
   }
@@ -624,6 +870,7 @@ mod tests {
       &MetaBlock::default(),
       &view_block_hash,
       height_plus_one,
+      None,
     );
     assert!(res.is_ok());
 
@@ -644,10 +891,9 @@ mod tests {
     // Fetch the value currently in the tail.
     let prev_tail = endorser_state
       .ledger_tail_map
-      .read()
-      .expect("failed")
       .get(&handle)
       .unwrap()
+      .unwrap()
       .read()
       .expect("failed")
       .0
@@ -658,10 +904,9 @@ mod tests {
     let height_plus_one = {
       let height = endorser_state
         .ledger_tail_map
-        .read()
-        .expect("failed")
         .get(&handle)
         .unwrap()
+        .unwrap()
         .read()
         .expect("failed")
         .0
@@ -673,21 +918,32 @@ mod tests {
       res.unwrap()
     };
 
+    // an append conditioned on a different tail is rejected
+    let res = endorser_state.append(
+      &handle,
+      &block_hash_to_append,
+      height_plus_one,
+      Some(&block_hash_to_append),
+      &block_hash_to_append_data,
+      &Nonces::new(),
+    );
+    assert_eq!(res.unwrap_err(), EndorserError::TailMismatch);
+
     let receipt = endorser_state
       .append(
         &handle,
         &block_hash_to_append,
         height_plus_one,
+        Some(&block_hash),
         &block_hash_to_append_data,
         &Nonces::new(),
       )
       .unwrap();
     let new_ledger_height = endorser_state
       .ledger_tail_map
-      .read()
-      .expect("failed")
       .get(&handle)
       .unwrap()
+      .unwrap()
       .read()
       .expect("failed")
       .0
@@ -703,12 +959,48 @@ mod tests {
     assert_eq!(*receipt.get_prev(), prev_tail);
     assert_eq!(new_ledger_height, height_plus_one);
 
+    // a retried append of the tail is endorsed again without growing the ledger, while an append
+    // of another block at that height is rejected
+    let retried = endorser_state
+      .append(
+        &handle,
+        &block_hash_to_append,
+        height_plus_one,
+        Some(&block_hash),
+        &block_hash_to_append_data,
+        &Nonces::new(),
+      )
+      .unwrap();
+    assert_eq!(retried.get_metablock(), receipt.get_metablock());
+    let other_block = Block::new(&rand::thread_rng().gen::<[u8; 32]>());
+    assert_eq!(
+      endorser_state
+        .append(
+          &handle,
+          &other_block.hash(),
+          height_plus_one,
+          Some(&block_hash),
+          &other_block,
+          &Nonces::new(),
+        )
+        .unwrap_err(),
+      EndorserError::LedgerExists
+    );
+
+    // once the ledger has grown, its genesis is not endorsed again
+    assert_eq!(
+      endorser_state
+        .new_ledger(&handle, &block_hash, &block)
+        .unwrap_err(),
+      EndorserError::LedgerExists
+    );
+
     let metadata = MetaBlock::new(&prev_tail, &block_hash_to_append, new_ledger_height);
 
     let endorser_tail_expectation = metadata.hash();
     let message = handle.digest_with(&endorser_tail_expectation);
     let tail_signature_verification = receipt.get_id_sig().verify_with_id(
-      &endorser_state.public_key,
+      &endorser_state.get_public_key().unwrap(),
       &view_block_hash
         .digest_with(&receipt.get_view().digest_with_bytes(&message.to_bytes()))
         .to_bytes(),
@@ -716,10 +1008,11 @@ mod tests {
 
     if tail_signature_verification.is_ok() {
       println!("Verification Passed. Checking Updated Tail");
-      let ledger_tail_map = endorser_state.ledger_tail_map.read().expect("failed");
-      let metablock_hash = ledger_tail_map
+      let metablock_hash = endorser_state
+        .ledger_tail_map
         .get(&handle)
         .unwrap()
+        .unwrap()
         .read()
         .expect("failed")
         .0
@@ -729,4 +1022,556 @@ mod tests {
       panic!("Signature verification failed when it should not have failed");
     }
   }
+
+  #[test]
+  pub fn check_endorser_rejects_malformed_ledger_tail_map() {
+    let endorser_state = EndorserState::new();
+    let view_block_hash = NimbleDigest::from_bytes(&rand::thread_rng().gen::<[u8; 32]>()).unwrap();
+
+    let ledger_tail_map = vec![LedgerTailMapEntry {
+      handle: vec![1u8; 31].into(),
+      height: 0,
+      metablock: MetaBlock::default().to_bytes().into(),
+      block: Default::default(),
+      nonces: Default::default(),
+    }];
+    let res = endorser_state.initialize_state(
+      &view_block_hash,
+      &ledger_tail_map,
+      &MetaBlock::default(),
+      &view_block_hash,
+      1,
+      None,
+    );
+    let err = res.unwrap_err();
+    assert_eq!(
+      err,
+      EndorserError::Ledger(ledger::errors::LedgerError::Serde(
+        ledger::CustomSerdeError::IncorrectLength
+      ))
+    );
+    assert!(std::error::Error::source(&err).is_some());
+
+    // the endorser is left untouched and can still be initialized
+    assert!(endorser_state.ledger_tail_map.is_empty());
+    assert_eq!(
+      endorser_state
+        .view_ledger_state
+        .read()
+        .unwrap()
+        .endorser_mode,
+      EndorserMode::Uninitialized
+    );
+  }
+
+  #[test]
+  pub fn check_endorser_rejoins_the_next_view() {
+    let endorser_state = EndorserState::new();
+
+    let view_block_hash = NimbleDigest::digest(&[1u8; 32]);
+    let receipt = endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &Vec::new(),
+        &MetaBlock::default(),
+        &view_block_hash,
+        1,
+        None,
+      )
+      .unwrap();
+    let view_tail_metablock = receipt.get_metablock().clone();
+    endorser_state
+      .view_ledger_state
+      .write()
+      .unwrap()
+      .endorser_mode = EndorserMode::Active;
+
+    let handle = NimbleDigest::digest(&[2u8; 32]);
+    let block = Block::new(&[3u8; 32]);
+    assert!(endorser_state
+      .new_ledger(&handle, &block.hash(), &block)
+      .is_ok());
+
+    // finalize the current view into the next view entry
+    let next_view_block_hash = NimbleDigest::digest(&[4u8; 32]);
+    let (finalize_receipt, ledger_tail_map) = endorser_state
+      .finalize_state(&next_view_block_hash, 2)
+      .unwrap();
+
+    // joining a view entry other than the one it finalized into is rejected
+    let res = endorser_state.initialize_state(
+      &view_block_hash,
+      &ledger_tail_map,
+      &view_tail_metablock,
+      &NimbleDigest::digest(&[5u8; 32]),
+      2,
+      None,
+    );
+    assert_eq!(res.unwrap_err(), EndorserError::AlreadyInitialized);
+
+    // the finalized endorser joins the next view with the same view ledger tail
+    let receipt = endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &ledger_tail_map,
+        &view_tail_metablock,
+        &next_view_block_hash,
+        2,
+        None,
+      )
+      .unwrap();
+    assert_eq!(receipt.get_metablock(), finalize_receipt.get_metablock());
+    assert_eq!(
+      endorser_state
+        .view_ledger_state
+        .read()
+        .unwrap()
+        .endorser_mode,
+      EndorserMode::Initialized
+    );
+
+    // resuming the view change repeats both steps without moving the view ledger
+    let (receipt, _) = endorser_state
+      .finalize_state(&next_view_block_hash, 2)
+      .unwrap();
+    assert_eq!(receipt.get_metablock(), finalize_receipt.get_metablock());
+    let receipt = endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &ledger_tail_map,
+        &view_tail_metablock,
+        &next_view_block_hash,
+        2,
+        None,
+      )
+      .unwrap();
+    assert_eq!(receipt.get_metablock(), finalize_receipt.get_metablock());
+  }
+
+  #[test]
+  pub fn check_endorser_rotates_its_key() {
+    let endorser_state = EndorserState::new();
+    let old_pk = endorser_state.get_public_key().unwrap();
+
+    // only an active endorser rotates its key
+    assert_eq!(
+      endorser_state.rotate_key().unwrap_err(),
+      EndorserError::NotActive
+    );
+    let view_block_hash = NimbleDigest::digest(&[1u8; 32]);
+    let receipt = endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &Vec::new(),
+        &MetaBlock::default(),
+        &view_block_hash,
+        1,
+        Some(&old_pk),
+      )
+      .unwrap();
+    let view_tail_metablock = receipt.get_metablock().clone();
+    endorser_state
+      .view_ledger_state
+      .write()
+      .unwrap()
+      .endorser_mode = EndorserMode::Active;
+
+    // the old key signs the handover to the new key, which a retry returns again
+    let rotation = endorser_state.rotate_key().unwrap();
+    assert_eq!(*rotation.get_old_pk(), old_pk.to_bytes());
+    assert!(rotation.verify(&view_block_hash).is_ok());
+    assert_eq!(
+      endorser_state.rotate_key().unwrap().get_new_pk(),
+      rotation.get_new_pk()
+    );
+    let new_pk = PublicKey::from_bytes(rotation.get_new_pk()).unwrap();
+
+    // the endorser signs with its old key until it joins a view with the new one
+    let handle = NimbleDigest::digest(&[2u8; 32]);
+    let block = Block::new(&[3u8; 32]);
+    let receipt = endorser_state
+      .new_ledger(&handle, &block.hash(), &block)
+      .unwrap();
+    assert_eq!(*receipt.get_id_sig().get_id(), old_pk.to_bytes());
+    let next_view_block_hash = NimbleDigest::digest(&[4u8; 32]);
+    let (finalize_receipt, ledger_tail_map) = endorser_state
+      .finalize_state(&next_view_block_hash, 2)
+      .unwrap();
+    assert_eq!(*finalize_receipt.get_id_sig().get_id(), old_pk.to_bytes());
+
+    // a view cannot list the endorser under a key that it does not hold
+    let other_pk = PrivateKey::new().get_public_key().unwrap();
+    let res = endorser_state.initialize_state(
+      &view_block_hash,
+      &ledger_tail_map,
+      &view_tail_metablock,
+      &next_view_block_hash,
+      2,
+      Some(&other_pk),
+    );
+    assert_eq!(res.unwrap_err(), EndorserError::UnknownKey);
+
+    // the endorser joins the next view with its new key, and still finalizes the old view with
+    // its old key until the next view is activated
+    let receipt = endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &ledger_tail_map,
+        &view_tail_metablock,
+        &next_view_block_hash,
+        2,
+        Some(&new_pk),
+      )
+      .unwrap();
+    assert_eq!(*receipt.get_id_sig().get_id(), new_pk.to_bytes());
+    assert_eq!(endorser_state.get_public_key().unwrap(), new_pk);
+    let (receipt, _) = endorser_state
+      .finalize_state(&next_view_block_hash, 2)
+      .unwrap();
+    assert_eq!(*receipt.get_id_sig().get_id(), old_pk.to_bytes());
+    let receipt = endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &ledger_tail_map,
+        &view_tail_metablock,
+        &next_view_block_hash,
+        2,
+        Some(&new_pk),
+      )
+      .unwrap();
+    assert_eq!(*receipt.get_id_sig().get_id(), new_pk.to_bytes());
+    let view_ledger_state = endorser_state.view_ledger_state.read().unwrap();
+    assert!(view_ledger_state.keys.next.is_none());
+    assert_eq!(
+      view_ledger_state.keys.previous.as_ref().unwrap().public_key,
+      old_pk
+    );
+  }
+
+  #[test]
+  pub fn check_endorser_signs_golden_payloads() {
+    // the vectors are of SHA-256 digests
+    if HASH_ALGORITHM != HashAlgorithm::Sha256 {
+      return;
+    }
+    let digest = |hex: &str| NimbleDigest::from_bytes(&hex::decode(hex).unwrap()).unwrap();
+    let endorser_state = EndorserState::new();
+    let group_identity = digest(GOLDEN_GROUP_IDENTITY);
+    let receipt = endorser_state
+      .initialize_state(
+        &group_identity,
+        &Vec::new(),
+        &MetaBlock::default(),
+        &group_identity,
+        1,
+        None,
+      )
+      .unwrap();
+    assert_eq!(receipt.get_metablock().hash(), digest(GOLDEN_VIEW));
+    endorser_state
+      .view_ledger_state
+      .write()
+      .unwrap()
+      .endorser_mode = EndorserMode::Active;
+
+    // the endorser signs the payloads of the vectors, under its own key, in the golden view
+    let check = |receipt: &Receipt, vector: &ReceiptVector| {
+      assert_eq!(*receipt.get_view(), digest(GOLDEN_VIEW));
+      assert_eq!(
+        *receipt.get_metablock(),
+        MetaBlock::new(
+          &digest(vector.prev),
+          &digest(vector.block_hash),
+          vector.height
+        )
+      );
+      assert!(receipt
+        .get_id_sig()
+        .verify_with_id(
+          &endorser_state.get_public_key().unwrap(),
+          &hex::decode(vector.message).unwrap()
+        )
+        .is_ok());
+    };
+    let handle = NimbleDigest::digest(GOLDEN_HANDLE);
+    let genesis_hash = digest(GOLDEN_NEW_LEDGER.block_hash);
+    let genesis_block =
+      ledger::compute_genesis_block(GOLDEN_GENESIS_BLOCK, GOLDEN_GENESIS_METADATA);
+    let receipt = endorser_state
+      .new_ledger(&handle, &genesis_hash, &genesis_block)
+      .unwrap();
+    check(&receipt, &GOLDEN_NEW_LEDGER);
+    let receipt = endorser_state
+      .append(
+        &handle,
+        &digest(GOLDEN_APPEND.block_hash),
+        GOLDEN_APPEND.height,
+        Some(&genesis_hash),
+        &Block::new(GOLDEN_BLOCK),
+        &Nonces::new(),
+      )
+      .unwrap();
+    check(&receipt, &GOLDEN_APPEND);
+    let (receipt, _block, _nonces) = endorser_state
+      .read_latest(&handle, &hex::decode(GOLDEN_READ_LATEST.nonce).unwrap())
+      .unwrap();
+    check(&receipt, &GOLDEN_READ_LATEST);
+  }
+
+  #[test]
+  pub fn test_endorser_with_private_key() {
+    let private_key = PrivateKey::new();
+    let public_key = private_key.get_public_key().unwrap();
+    let restored = PrivateKey::from_bytes(&private_key.to_bytes()).unwrap();
+    let endorser_state = EndorserState::with_private_key(restored).unwrap();
+    assert_eq!(endorser_state.get_public_key().unwrap(), public_key);
+    assert_ne!(EndorserState::new().get_public_key().unwrap(), public_key);
+  }
+
+  /// an active endorser whose ledger tails are in `ledger_tail_map`
+  fn active_endorser(ledger_tail_map: TailMap) -> EndorserState {
+    let endorser_state = EndorserState {
+      ledger_tail_map,
+      ..EndorserState::new()
+    };
+    let view_block_hash = NimbleDigest::digest(&[1u8; 32]);
+    endorser_state
+      .initialize_state(
+        &view_block_hash,
+        &Vec::new(),
+        &MetaBlock::default(),
+        &view_block_hash,
+        1,
+        None,
+      )
+      .unwrap();
+    endorser_state
+      .view_ledger_state
+      .write()
+      .unwrap()
+      .endorser_mode = EndorserMode::Active;
+    endorser_state
+  }
+
+  /// the block hash of a ledger entry, which binds its block and its nonces
+  fn entry_hash(block: &Block, nonces: &Nonces) -> NimbleDigest {
+    compute_aggregated_block_hash(&block.hash().to_bytes(), &nonces.hash().to_bytes())
+  }
+
+  #[test]
+  pub fn check_endorser_concurrent_appends() {
+    let endorser_state = Arc::new(active_endorser(TailMap::with_shards(4)));
+    let handles = (0..8u8)
+      .map(|i| NimbleDigest::digest(&[i]))
+      .collect::<Vec<_>>();
+    for handle in &handles {
+      let block = Block::new(&handle.to_bytes());
+      endorser_state
+        .new_ledger(handle, &entry_hash(&block, &Nonces::new()), &block)
+        .unwrap();
+    }
+    let done = Arc::new(AtomicBool::new(false));
+
+    // the writers race to append to the same ledgers, so that all but one of the appends at a
+    // height fail
+    let writers = (0..8)
+      .map(|_| {
+        let endorser_state = endorser_state.clone();
+        let handles = handles.clone();
+        thread::spawn(move || {
+          let mut rng = rand::thread_rng();
+          let mut appended = vec![0usize; handles.len()];
+          for _ in 0..200 {
+            let i = rng.gen_range(0, handles.len());
+            let height = endorser_state.get_height(&handles[i]).unwrap();
+            let block = Block::new(&rng.gen::<[u8; 32]>());
+            let nonces = Nonces::from_vec(vec![Nonce::new()]);
+            let res = endorser_state.append(
+              &handles[i],
+              &entry_hash(&block, &nonces),
+              height + 1,
+              None,
+              &block,
+              &nonces,
+            );
+            match res {
+              Ok(receipt) => {
+                assert_eq!(receipt.get_metablock().get_height(), height + 1);
+                appended[i] += 1;
+              },
+              Err(e) => assert_eq!(e, EndorserError::LedgerExists),
+            }
+          }
+          appended
+        })
+      })
+      .collect::<Vec<_>>();
+
+    // the readers check that the height of a ledger never goes down, and that its tail is never
+    // torn: the metablock is always that of the block and the nonces returned with it
+    let readers = (0..4)
+      .map(|_| {
+        let endorser_state = endorser_state.clone();
+        let handles = handles.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+          let mut rng = rand::thread_rng();
+          let mut heights = vec![0usize; handles.len()];
+          while !done.load(Ordering::SeqCst) {
+            let i = rng.gen_range(0, handles.len());
+            let nonce = rng.gen::<[u8; 16]>();
+            let (receipt, block, nonces) = endorser_state.read_latest(&handles[i], &nonce).unwrap();
+            let metablock = receipt.get_metablock();
+            assert!(metablock.get_height() >= heights[i]);
+            assert_eq!(*metablock.get_block_hash(), entry_hash(&block, &nonces));
+            heights[i] = metablock.get_height();
+          }
+        })
+      })
+      .collect::<Vec<_>>();
+
+    let mut appended = vec![0usize; handles.len()];
+    for writer in writers {
+      for (total, count) in appended.iter_mut().zip(writer.join().unwrap()) {
+        *total += count;
+      }
+    }
+    done.store(true, Ordering::SeqCst);
+    for reader in readers {
+      reader.join().unwrap();
+    }
+
+    // every append that succeeded extended its ledger by one
+    for (handle, count) in handles.iter().zip(appended) {
+      assert_eq!(endorser_state.get_height(handle).unwrap(), count);
+    }
+  }
+
+  #[test]
+  pub fn check_endorser_status() {
+    let endorser_state = active_endorser(TailMap::new());
+    assert_eq!(
+      endorser_state.get_status().unwrap(),
+      (EndorserMode::Active, 0, 0)
+    );
+
+    let handle = NimbleDigest::digest(b"ledger");
+    let genesis = Block::new(b"genesis");
+    endorser_state
+      .new_ledger(&handle, &entry_hash(&genesis, &Nonces::new()), &genesis)
+      .unwrap();
+    let (_mode, num_ledgers, created_bytes) = endorser_state.get_status().unwrap();
+    assert_eq!(num_ledgers, 1);
+    assert!(created_bytes > genesis.len());
+
+    // a bigger tail takes more memory, but the number of ledgers stays
+    let block = Block::new(&[1u8; 1000]);
+    let nonces = Nonces::from_vec(vec![Nonce::new()]);
+    endorser_state
+      .append(
+        &handle,
+        &entry_hash(&block, &nonces),
+        1,
+        None,
+        &block,
+        &nonces,
+      )
+      .unwrap();
+    let (_mode, num_ledgers, appended_bytes) = endorser_state.get_status().unwrap();
+    assert_eq!(num_ledgers, 1);
+    assert_eq!(
+      appended_bytes,
+      created_bytes + block.len() - genesis.len() + Nonce::num_bytes()
+    );
+  }
+
+  #[test]
+  pub fn check_endorser_heartbeat() {
+    let endorser_state = EndorserState::new();
+    let nonce = Nonce::new();
+    let (counter, timestamp_ms, id_sig) = endorser_state.heartbeat(&nonce.to_bytes()).unwrap();
+    assert_eq!(counter, 1);
+    assert_eq!(
+      id_sig.get_id(),
+      &endorser_state.get_public_key().unwrap().to_bytes()
+    );
+    let message = compute_endorser_heartbeat_message(&nonce, counter, timestamp_ms);
+    assert!(id_sig.verify(&message.to_bytes()).is_ok());
+
+    // the counter goes up with every heartbeat, and the signature covers it
+    let (next, next_ms, next_sig) = endorser_state.heartbeat(&nonce.to_bytes()).unwrap();
+    assert_eq!(next, 2);
+    assert!(next_ms >= timestamp_ms);
+    assert!(next_sig.verify(&message.to_bytes()).is_err());
+    assert_eq!(
+      endorser_state.heartbeat(&[1, 2, 3]).unwrap_err(),
+      EndorserError::InvalidNonce
+    );
+  }
+
+  /// compares the throughput of concurrent creates, appends and reads with the ledger tails in one
+  /// shard, as they were behind a single lock, and in the default shards; run it with
+  /// `cargo test --release -p endorser -- --ignored bench_endorser_contention --nocapture`
+  #[test]
+  #[ignore]
+  pub fn bench_endorser_contention() {
+    const THREADS: usize = 16;
+    const OPS: usize = 20_000;
+    for (name, ledger_tail_map) in [
+      ("one shard", TailMap::with_shards(1)),
+      ("sharded", TailMap::new()),
+    ] {
+      let endorser_state = Arc::new(active_endorser(ledger_tail_map));
+      let start = Instant::now();
+      let threads = (0..THREADS)
+        .map(|_| {
+          let endorser_state = endorser_state.clone();
+          thread::spawn(move || {
+            let mut rng = rand::thread_rng();
+            // each thread appends to ledgers of its own, so that its appends do not conflict
+            let mut ledgers: Vec<(NimbleDigest, usize)> = Vec::new();
+            for op in 0..OPS {
+              let block = Block::new(&rng.gen::<[u8; 32]>());
+              if ledgers.is_empty() || op % 10 == 0 {
+                let handle = NimbleDigest::digest(&rng.gen::<[u8; 32]>());
+                endorser_state
+                  .new_ledger(&handle, &entry_hash(&block, &Nonces::new()), &block)
+                  .unwrap();
+                ledgers.push((handle, 0));
+              } else if op % 2 == 0 {
+                let i = rng.gen_range(0, ledgers.len());
+                let (handle, height) = &mut ledgers[i];
+                *height += 1;
+                endorser_state
+                  .append(
+                    handle,
+                    &entry_hash(&block, &Nonces::new()),
+                    *height,
+                    None,
+                    &block,
+                    &Nonces::new(),
+                  )
+                  .unwrap();
+              } else {
+                let i = rng.gen_range(0, ledgers.len());
+                let (handle, _height) = &ledgers[i];
+                endorser_state
+                  .read_latest(handle, &rng.gen::<[u8; 16]>())
+                  .unwrap();
+              }
+            }
+          })
+        })
+        .collect::<Vec<_>>();
+      for thread in threads {
+        thread.join().unwrap();
+      }
+      let elapsed = start.elapsed();
+      println!(
+        "{}: {:.0} ops/s",
+        name,
+        (THREADS * OPS) as f64 / elapsed.as_secs_f64()
+      );
+    }
+  }
 }
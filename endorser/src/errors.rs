@@ -1,3 +1,10 @@
+use ledger::{
+  errors::{LedgerError, VerificationError},
+  signature::CryptoError,
+  CustomSerdeError,
+};
+use std::fmt;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum EndorserError {
   /// returned if the supplied ledger name is invalid
@@ -14,6 +21,8 @@ pub enum EndorserError {
   InvalidTailHeight,
   /// returned if the requested tail height is more than the expected height
   OutOfOrder,
+  /// returned if the tail of the ledger does not have the expected block hash
+  TailMismatch,
   /// returned if failed to acquire view ledger read lock
   FailedToAcquireViewLedgerReadLock,
   /// returned if failed to acquire view ledger write lock
@@ -34,4 +43,97 @@ pub enum EndorserError {
   NotActive,
   /// returned if the endorser is already activated
   AlreadyActivated,
+  /// returned if the supplied nonce is malformed
+  InvalidNonce,
+  /// returned if the endorser holds neither the current nor the next key that it is asked for
+  UnknownKey,
+  /// returned if a ledger operation ((de)serialization, crypto, verification) fails
+  Ledger(LedgerError),
+}
+
+impl fmt::Display for EndorserError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EndorserError::InvalidLedgerName => write!(f, "the supplied ledger name is invalid"),
+      EndorserError::LedgerExists => {
+        write!(f, "the ledger already exists")
+      },
+      EndorserError::LedgerHeightOverflow => {
+        write!(f, "the ledger height overflows")
+      },
+      EndorserError::NotInitialized => write!(f, "the state of the endorser is not initialized"),
+      EndorserError::AlreadyInitialized => {
+        write!(f, "the state of the endorser is already initialized")
+      },
+      EndorserError::InvalidTailHeight => write!(
+        f,
+        "the requested tail height is less than the expected height"
+      ),
+      EndorserError::OutOfOrder => write!(
+        f,
+        "the requested tail height is more than the expected height"
+      ),
+      EndorserError::TailMismatch => {
+        write!(f, "the ledger tail does not have the expected block hash")
+      },
+      EndorserError::FailedToAcquireViewLedgerReadLock => {
+        write!(f, "failed to acquire view ledger read lock")
+      },
+      EndorserError::FailedToAcquireViewLedgerWriteLock => {
+        write!(f, "failed to acquire view ledger write lock")
+      },
+      EndorserError::FailedToAcquireLedgerMapReadLock => {
+        write!(f, "failed to acquire ledger map read lock")
+      },
+      EndorserError::FailedToAcquireLedgerMapWriteLock => {
+        write!(f, "failed to acquire ledger map write lock")
+      },
+      EndorserError::FailedToAcquireLedgerEntryReadLock => {
+        write!(f, "failed to acquire ledger entry read lock")
+      },
+      EndorserError::FailedToAcquireLedgerEntryWriteLock => {
+        write!(f, "failed to acquire ledger entry write lock")
+      },
+      EndorserError::AlreadyFinalized => write!(f, "the endorser is already finalized"),
+      EndorserError::FailedToActivate => write!(f, "failed to verify the view change"),
+      EndorserError::NotActive => write!(f, "the endorser is not active"),
+      EndorserError::AlreadyActivated => write!(f, "the endorser is already activated"),
+      EndorserError::InvalidNonce => write!(f, "the supplied nonce is malformed"),
+      EndorserError::UnknownKey => write!(f, "the endorser does not hold the supplied key"),
+      EndorserError::Ledger(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for EndorserError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      EndorserError::Ledger(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<LedgerError> for EndorserError {
+  fn from(e: LedgerError) -> Self {
+    EndorserError::Ledger(e)
+  }
+}
+
+impl From<VerificationError> for EndorserError {
+  fn from(e: VerificationError) -> Self {
+    EndorserError::Ledger(e.into())
+  }
+}
+
+impl From<CustomSerdeError> for EndorserError {
+  fn from(e: CustomSerdeError) -> Self {
+    EndorserError::Ledger(e.into())
+  }
+}
+
+impl From<CryptoError> for EndorserError {
+  fn from(e: CryptoError) -> Self {
+    EndorserError::Ledger(e.into())
+  }
 }
@@ -1,20 +1,119 @@
-use crate::{endorser_state::EndorserState, errors::EndorserError};
+use crate::{endorser_state::EndorserState, errors::EndorserError, hadoop_conf::HadoopConf};
 use clap::{App, Arg};
 use ledger::{
-  signature::PublicKeyTrait, Block, CustomSerde, MetaBlock, NimbleDigest, Nonces, Receipts,
+  errors::LedgerError,
+  secret::load_secret,
+  signature::{PrivateKey, PublicKey, PublicKeyTrait, SignatureTrait},
+  Block, CustomSerde, MetaBlock, NimbleDigest, Nonces, Receipts,
 };
-use tonic::{transport::Server, Code, Request, Response, Status};
+use std::time::Duration;
+use tonic::{codegen::http, transport::Server, Code, Request, Response, Status};
+use tracing::{debug, error, field, info, info_span, warn, Span};
+use tracing_subscriber::EnvFilter;
 
 mod endorser_state;
 mod errors;
+// the coordinator reads the same files, and owns their reader
+#[path = "../../coordinator/src/hadoop_conf.rs"]
+#[allow(dead_code)]
+mod hadoop_conf;
+mod tail_map;
 
 use ledger::endorser_proto::{
   endorser_call_server::{EndorserCall, EndorserCallServer},
-  ActivateReq, ActivateResp, AppendReq, AppendResp, FinalizeStateReq, FinalizeStateResp,
-  GetPublicKeyReq, GetPublicKeyResp, InitializeStateReq, InitializeStateResp, NewLedgerReq,
-  NewLedgerResp, ReadLatestReq, ReadLatestResp, ReadStateReq, ReadStateResp,
+  ActivateReq, ActivateResp, AppendBatchReq, AppendBatchResp, AppendBatchResult, AppendReq,
+  AppendResp, FinalizeStateReq, FinalizeStateResp, GetPublicKeyReq, GetPublicKeyResp, GetStatusReq,
+  GetStatusResp, HeartbeatReq, HeartbeatResp, InitializeStateReq, InitializeStateResp,
+  NewLedgerReq, NewLedgerResp, ReadLatestReq, ReadLatestResp, ReadStateReq, ReadStateResp,
+  ReadViewTailReq, ReadViewTailResp, RotateKeyReq, RotateKeyResp,
 };
 
+/// the metadata key in which the coordinator forwards the ID of the client request that a call is
+/// made for
+const REQUEST_ID_KEY: &str = "x-request-id";
+const LOG_FORMATS: [&str; 2] = ["text", "json"];
+const HADOOP_PREFIX: &str = "nimble."; // the keys of Hadoop configuration files that Nimble reads
+const HADOOP_COORDINATOR_PREFIX: &str = "nimble.coordinator."; // the keys the coordinator reads
+
+/// the keys of Hadoop configuration files that configure the endorser, and the flags that they
+/// stand for
+const HADOOP_KEYS: [(&str, &str); 4] = [
+  ("nimble.endorser.host", "host"),
+  ("nimble.endorser.port", "port"),
+  ("nimble.endorser.log-format", "log_format"),
+  ("nimble.endorser.key-file", "key_file"),
+];
+
+/// the values that the `nimble.endorser.*` properties of `conf` give the flags, and a warning per
+/// unknown `nimble.*` property, other than those of the coordinator
+fn hadoop_settings(conf: &HadoopConf) -> (Vec<(&'static str, String)>, Vec<String>) {
+  let settings = HADOOP_KEYS
+    .iter()
+    .filter_map(|(name, flag)| conf.get(name).map(|value| (*flag, value.to_string())))
+    .collect();
+  let warnings = conf
+    .names_with_prefix(HADOOP_PREFIX)
+    .filter(|name| !name.starts_with(HADOOP_COORDINATOR_PREFIX))
+    .filter(|name| !HADOOP_KEYS.iter().any(|(key, _flag)| key == name))
+    .map(|name| format!("unknown key {} in --hadoop-conf", name))
+    .collect();
+  (settings, warnings)
+}
+
+/// sets up the logs, filtered by `RUST_LOG` and at the info level by default; `json` emits one
+/// JSON object per event, with the fields of the spans that the event is in
+fn init_tracing(format: &str) {
+  let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+  let builder = tracing_subscriber::fmt().with_env_filter(filter);
+  if format == "json" {
+    builder
+      .json()
+      .with_current_span(true)
+      .with_span_list(true)
+      .init();
+  } else {
+    builder.init();
+  }
+}
+
+/// the span of a call from the coordinator, with the ID of the client request it is made for;
+/// the handlers record the ledger and height that the call is about
+fn call_span(request: &http::Request<()>) -> Span {
+  let request_id = request
+    .headers()
+    .get(REQUEST_ID_KEY)
+    .and_then(|value| value.to_str().ok());
+  info_span!(
+    "endorser_request",
+    method = request.uri().path(),
+    request_id,
+    handle = field::Empty,
+    height = field::Empty,
+  )
+}
+
+/// the CPU time that the endorser process has used, in user and system mode
+#[cfg(unix)]
+fn cpu_time() -> Duration {
+  let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+  // SAFETY: getrusage fills in `usage` when it succeeds
+  if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+    return Duration::ZERO;
+  }
+  let usage = unsafe { usage.assume_init() };
+  let micros = |time: libc::timeval| time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64;
+  Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Duration {
+  Duration::ZERO
+}
+
+fn record_handle(handle: &NimbleDigest) {
+  Span::current().record("handle", &field::display(handle));
+}
+
 pub struct EndorserServiceState {
   state: EndorserState,
 }
@@ -26,16 +125,27 @@ impl EndorserServiceState {
     }
   }
 
+  /// an endorser that signs with the PEM private key `pem`
+  pub fn with_private_key_pem(pem: &[u8]) -> Result<Self, String> {
+    let private_key = PrivateKey::from_pem(pem).map_err(|e| e.to_string())?;
+    let state = EndorserState::with_private_key(private_key).map_err(|e| e.to_string())?;
+    Ok(EndorserServiceState { state })
+  }
+
   fn process_error(
     &self,
     error: EndorserError,
     handle: Option<&NimbleDigest>,
     default_msg: impl Into<String>,
   ) -> Status {
+    debug!(?error, "the request failed");
     match error {
       EndorserError::OutOfOrder => {
         if let Some(h) = handle {
-          let height = self.state.get_height(h).unwrap();
+          let height = match self.state.get_height(h) {
+            Ok(height) => height,
+            Err(e) => return Status::internal(e.to_string()),
+          };
           Status::with_details(
             Code::FailedPrecondition,
             "Out of order",
@@ -46,6 +156,9 @@ impl EndorserServiceState {
         }
       },
       EndorserError::LedgerExists => Status::already_exists("Ledger exists"),
+      EndorserError::TailMismatch => {
+        Status::aborted("Ledger tail does not match the expected tail")
+      },
       EndorserError::InvalidLedgerName => Status::not_found("Ledger handle not found"),
       EndorserError::LedgerHeightOverflow => Status::out_of_range("Ledger height overflow"),
       EndorserError::InvalidTailHeight => Status::invalid_argument("Invalid ledger height"),
@@ -54,7 +167,85 @@ impl EndorserServiceState {
       },
       EndorserError::NotInitialized => Status::unimplemented("Endorser is not initialized"),
       EndorserError::AlreadyFinalized => Status::unavailable("Endorser is already finalized"),
-      _ => Status::internal(default_msg),
+      EndorserError::InvalidNonce => Status::invalid_argument("Invalid nonce"),
+      EndorserError::UnknownKey => Status::invalid_argument("Unknown endorser key"),
+      EndorserError::Ledger(LedgerError::Serde(e)) => Status::invalid_argument(e.to_string()),
+      _ => {
+        let msg = default_msg.into();
+        error!(?error, "{}", msg);
+        Status::internal(msg)
+      },
+    }
+  }
+
+  /// appends a block to a ledger; shared by `append` and the items of `append_batch`
+  #[allow(clippy::result_large_err)]
+  fn append_item(&self, req: AppendReq) -> Result<AppendResp, Status> {
+    let AppendReq {
+      handle,
+      block_hash,
+      expected_height,
+      block,
+      nonces,
+      expected_tail,
+    } = req;
+
+    let handle_instance = NimbleDigest::from_bytes(&handle);
+    let block_hash_instance = NimbleDigest::from_bytes(&block_hash);
+    let block_instance = Block::from_bytes(&block);
+    let nonces_instance = Nonces::from_bytes(&nonces);
+    let expected_tail_instance = if expected_tail.is_empty() {
+      Ok(None)
+    } else {
+      NimbleDigest::from_bytes(&expected_tail).map(Some)
+    };
+
+    if handle_instance.is_err()
+      || block_hash_instance.is_err()
+      || block_instance.is_err()
+      || nonces_instance.is_err()
+      || expected_tail_instance.is_err()
+    {
+      return Err(Status::invalid_argument("Invalid input sizes"));
+    }
+
+    if expected_height == 0 {
+      return Err(Status::invalid_argument("Invalid expected height"));
+    }
+
+    let handle = handle_instance.unwrap();
+    record_handle(&handle);
+    Span::current().record("height", &expected_height);
+    let block_hash = block_hash_instance.unwrap();
+    let block = block_instance.unwrap();
+    let nonces = nonces_instance.unwrap();
+    let expected_tail = expected_tail_instance.unwrap();
+
+    let res = self.state.append(
+      &handle,
+      &block_hash,
+      expected_height as usize,
+      expected_tail.as_ref(),
+      &block,
+      &nonces,
+    );
+
+    match res {
+      Ok(receipt) => {
+        let reply = AppendResp {
+          receipt: receipt.to_bytes().into(),
+        };
+        Ok(reply)
+      },
+
+      Err(error) => {
+        let status = self.process_error(
+          error,
+          Some(&handle),
+          "Failed to append to a ledger due to an internal error",
+        );
+        Err(status)
+      },
     }
   }
 }
@@ -71,10 +262,13 @@ impl EndorserCall for EndorserServiceState {
     &self,
     _req: Request<GetPublicKeyReq>,
   ) -> Result<Response<GetPublicKeyResp>, Status> {
-    let pk = self.state.get_public_key();
+    let pk = self
+      .state
+      .get_public_key()
+      .map_err(|error| self.process_error(error, None, "Failed to read the public key"))?;
 
     let reply = GetPublicKeyResp {
-      pk: pk.to_bytes().to_vec(),
+      pk: pk.to_bytes().into(),
     };
 
     Ok(Response::new(reply))
@@ -96,6 +290,7 @@ impl EndorserCall for EndorserServiceState {
       }
       res.unwrap()
     };
+    record_handle(&handle);
 
     let block_hash = {
       let res = NimbleDigest::from_bytes(&block_hash);
@@ -118,7 +313,7 @@ impl EndorserCall for EndorserServiceState {
     match res {
       Ok(receipt) => {
         let reply = NewLedgerResp {
-          receipt: receipt.to_bytes().to_vec(),
+          receipt: receipt.to_bytes().into(),
         };
         Ok(Response::new(reply))
       },
@@ -134,61 +329,40 @@ impl EndorserCall for EndorserServiceState {
   }
 
   async fn append(&self, req: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
-    let AppendReq {
-      handle,
-      block_hash,
-      expected_height,
-      block,
-      nonces,
-    } = req.into_inner();
-
-    let handle_instance = NimbleDigest::from_bytes(&handle);
-    let block_hash_instance = NimbleDigest::from_bytes(&block_hash);
-    let block_instance = Block::from_bytes(&block);
-    let nonces_instance = Nonces::from_bytes(&nonces);
-
-    if handle_instance.is_err()
-      || block_hash_instance.is_err()
-      || block_instance.is_err()
-      || nonces_instance.is_err()
-    {
-      return Err(Status::invalid_argument("Invalid input sizes"));
-    }
-
-    if expected_height == 0 {
-      return Err(Status::invalid_argument("Invalid expected height"));
-    }
-
-    let handle = handle_instance.unwrap();
-    let block_hash = block_hash_instance.unwrap();
-    let block = block_instance.unwrap();
-    let nonces = nonces_instance.unwrap();
-
-    let res = self.state.append(
-      &handle,
-      &block_hash,
-      expected_height as usize,
-      &block,
-      &nonces,
-    );
-
-    match res {
-      Ok(receipt) => {
-        let reply = AppendResp {
-          receipt: receipt.to_bytes().to_vec(),
-        };
-        Ok(Response::new(reply))
-      },
+    self.append_item(req.into_inner()).map(Response::new)
+  }
 
-      Err(error) => {
-        let status = self.process_error(
-          error,
-          Some(&handle),
-          "Failed to append to a ledger due to an internal error",
-        );
-        Err(status)
-      },
-    }
+  async fn append_batch(
+    &self,
+    req: Request<AppendBatchReq>,
+  ) -> Result<Response<AppendBatchResp>, Status> {
+    let AppendBatchReq { items } = req.into_inner();
+
+    // items are independent: one that fails does not keep the others from being appended; each
+    // is traced in a span of its own
+    let results = items
+      .into_iter()
+      .map(|item| {
+        let span = info_span!("append_item", handle = field::Empty, height = field::Empty);
+        span.in_scope(|| self.append_item(item))
+      })
+      .map(|res| match res {
+        Ok(AppendResp { receipt }) => AppendBatchResult {
+          receipt,
+          code: Code::Ok as i32,
+          message: String::new(),
+          details: bytes::Bytes::new(),
+        },
+        Err(status) => AppendBatchResult {
+          receipt: bytes::Bytes::new(),
+          code: status.code() as i32,
+          message: status.message().to_string(),
+          details: bytes::Bytes::copy_from_slice(status.details()),
+        },
+      })
+      .collect();
+
+    Ok(Response::new(AppendBatchResp { results }))
   }
 
   async fn read_latest(
@@ -203,14 +377,15 @@ impl EndorserCall for EndorserServiceState {
       }
       res.unwrap()
     };
+    record_handle(&handle);
     let res = self.state.read_latest(&handle, &nonce);
 
     match res {
       Ok((receipt, block, nonces)) => {
         let reply = ReadLatestResp {
-          receipt: receipt.to_bytes().to_vec(),
-          block: block.to_bytes().to_vec(),
-          nonces: nonces.to_bytes().to_vec(),
+          receipt: receipt.to_bytes().into(),
+          block: block.to_bytes().into(),
+          nonces: nonces.to_bytes().into(),
         };
         Ok(Response::new(reply))
       },
@@ -225,6 +400,31 @@ impl EndorserCall for EndorserServiceState {
     }
   }
 
+  async fn read_view_tail(
+    &self,
+    request: Request<ReadViewTailReq>,
+  ) -> Result<Response<ReadViewTailResp>, Status> {
+    let ReadViewTailReq { nonce } = request.into_inner();
+    let res = self.state.read_view_tail(&nonce);
+
+    match res {
+      Ok(receipt) => {
+        let reply = ReadViewTailResp {
+          receipt: receipt.to_bytes().into(),
+        };
+        Ok(Response::new(reply))
+      },
+      Err(error) => {
+        let status = self.process_error(
+          error,
+          None,
+          "Failed to read the view ledger due to an internal error",
+        );
+        Err(status)
+      },
+    }
+  }
+
   async fn finalize_state(
     &self,
     req: Request<FinalizeStateReq>,
@@ -247,7 +447,7 @@ impl EndorserCall for EndorserServiceState {
     match res {
       Ok((receipt, ledger_tail_map)) => {
         let reply = FinalizeStateResp {
-          receipt: receipt.to_bytes().to_vec(),
+          receipt: receipt.to_bytes().into(),
           ledger_tail_map,
         };
         Ok(Response::new(reply))
@@ -273,22 +473,35 @@ impl EndorserCall for EndorserServiceState {
       view_tail_metablock,
       block_hash,
       expected_height,
+      public_key,
     } = req.into_inner();
-    let group_identity_rs = NimbleDigest::from_bytes(&group_identity).unwrap();
-    let view_tail_metablock_rs = MetaBlock::from_bytes(&view_tail_metablock).unwrap();
-    let block_hash_rs = NimbleDigest::from_bytes(&block_hash).unwrap();
+    let group_identity_rs = NimbleDigest::from_bytes(&group_identity)
+      .map_err(|_| Status::invalid_argument("Invalid group identity"))?;
+    let view_tail_metablock_rs = MetaBlock::from_bytes(&view_tail_metablock)
+      .map_err(|_| Status::invalid_argument("Invalid view tail metablock"))?;
+    let block_hash_rs = NimbleDigest::from_bytes(&block_hash)
+      .map_err(|_| Status::invalid_argument("Invalid block hash"))?;
+    let public_key_rs = if public_key.is_empty() {
+      None
+    } else {
+      Some(
+        PublicKey::from_bytes(&public_key)
+          .map_err(|_| Status::invalid_argument("Invalid public key"))?,
+      )
+    };
     let res = self.state.initialize_state(
       &group_identity_rs,
       &ledger_tail_map,
       &view_tail_metablock_rs,
       &block_hash_rs,
       expected_height as usize,
+      public_key_rs.as_ref(),
     );
 
     match res {
       Ok(receipt) => {
         let reply = InitializeStateResp {
-          receipt: receipt.to_bytes().to_vec(),
+          receipt: receipt.to_bytes().into(),
         };
         Ok(Response::new(reply))
       },
@@ -312,7 +525,7 @@ impl EndorserCall for EndorserServiceState {
     match res {
       Ok((receipt, endorser_mode, ledger_tail_map)) => {
         let reply = ReadStateResp {
-          receipt: receipt.to_bytes().to_vec(),
+          receipt: receipt.to_bytes().into(),
           mode: endorser_mode as i32,
           ledger_tail_map,
         };
@@ -337,7 +550,8 @@ impl EndorserCall for EndorserServiceState {
       ledger_chunks,
       receipts,
     } = req.into_inner();
-    let receipts_rs = Receipts::from_bytes(&receipts).unwrap();
+    let receipts_rs = Receipts::from_bytes(&receipts)
+      .map_err(|e| Status::invalid_argument(format!("Invalid receipts: {}", e)))?;
     let res = self.state.activate(
       &old_config,
       &new_config,
@@ -361,6 +575,71 @@ impl EndorserCall for EndorserServiceState {
       },
     }
   }
+
+  async fn rotate_key(
+    &self,
+    _req: Request<RotateKeyReq>,
+  ) -> Result<Response<RotateKeyResp>, Status> {
+    match self.state.rotate_key() {
+      Ok(rotation) => {
+        info!(
+          old_pk = %hex::encode(rotation.get_old_pk()),
+          new_pk = %hex::encode(rotation.get_new_pk()),
+          "generated the key to rotate to"
+        );
+        let reply = RotateKeyResp {
+          old_pk: rotation.get_old_pk().clone().into(),
+          new_pk: rotation.get_new_pk().clone().into(),
+          signature: rotation.get_signature().clone().into(),
+        };
+        Ok(Response::new(reply))
+      },
+      Err(error) => {
+        let status = self.process_error(
+          error,
+          None,
+          "Failed to rotate the key due to an internal error",
+        );
+        Err(status)
+      },
+    }
+  }
+
+  async fn get_status(
+    &self,
+    _req: Request<GetStatusReq>,
+  ) -> Result<Response<GetStatusResp>, Status> {
+    let (endorser_mode, num_ledgers, tail_map_bytes) = self
+      .state
+      .get_status()
+      .map_err(|error| self.process_error(error, None, "Failed to read the status"))?;
+
+    let reply = GetStatusResp {
+      mode: endorser_mode as i32,
+      num_ledgers: num_ledgers as u64,
+      tail_map_bytes: tail_map_bytes as u64,
+      cpu_micros: cpu_time().as_micros() as u64,
+    };
+
+    Ok(Response::new(reply))
+  }
+
+  async fn heartbeat(&self, req: Request<HeartbeatReq>) -> Result<Response<HeartbeatResp>, Status> {
+    let HeartbeatReq { nonce } = req.into_inner();
+    let (counter, timestamp_ms, id_sig) = self
+      .state
+      .heartbeat(&nonce)
+      .map_err(|error| self.process_error(error, None, "Failed to sign a heartbeat"))?;
+
+    let reply = HeartbeatResp {
+      counter,
+      timestamp_ms,
+      pk: id_sig.get_id().clone().into(),
+      signature: id_sig.get_sig().to_bytes().into(),
+    };
+
+    Ok(Response::new(reply))
+  }
 }
 
 #[tokio::main]
@@ -379,17 +658,89 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .long("port")
         .help("The port number to run the Service On. Default: 9096")
         .default_value("9090"),
+    )
+    .arg(
+      Arg::with_name("log_format")
+        .long("log-format")
+        .possible_values(&LOG_FORMATS)
+        .help("The format of the logs, which RUST_LOG filters")
+        .default_value("text"),
+    )
+    .arg(
+      Arg::with_name("hadoop_conf")
+        .long("hadoop-conf")
+        .help(
+          "Hadoop configuration files (*-site.xml) whose nimble.endorser.* keys set the flags \
+           that are not given; later files override earlier ones",
+        )
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true)
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("key_file")
+        .long("key-file")
+        .help(
+          "The PEM private key that the endorser signs with, as a file or as env:<NAME> or \
+           exec:<command>; a key is generated if not set",
+        )
+        .takes_value(true),
     );
   let cli_matches = config.get_matches();
-  let hostname = cli_matches.value_of("host").unwrap();
-  let port_number = cli_matches.value_of("port").unwrap();
-  let addr = format!("{}:{}", hostname, port_number).parse()?;
-  let server = EndorserServiceState::new();
+
+  // flags given on the command line override the Hadoop configuration files, which override the
+  // defaults of the flags
+  let mut conf = HadoopConf::default();
+  let mut warnings = Vec::new();
+  for path in cli_matches.values_of("hadoop_conf").into_iter().flatten() {
+    let contents = std::fs::read_to_string(path)
+      .map_err(|e| format!("cannot read --hadoop-conf {}: {}", path, e))?;
+    let final_warnings = conf
+      .add_resource(&contents)
+      .map_err(|e| format!("invalid --hadoop-conf {}: {}", path, e))?;
+    warnings.extend(
+      final_warnings
+        .into_iter()
+        .map(|w| format!("{}: {}", path, w)),
+    );
+  }
+  let (settings, unknown_keys) = hadoop_settings(&conf);
+  warnings.extend(unknown_keys);
+  let optional_setting = |flag: &str| -> Option<String> {
+    match settings.iter().find(|(name, _value)| *name == flag) {
+      Some((_name, value)) if cli_matches.occurrences_of(flag) == 0 => Some(value.clone()),
+      _ => cli_matches.value_of(flag).map(|value| value.to_string()),
+    }
+  };
+  let setting = |flag: &str| -> String { optional_setting(flag).unwrap() };
+  let log_format = setting("log_format");
+  if !LOG_FORMATS.contains(&log_format.as_str()) {
+    return Err(format!("invalid nimble.endorser.log-format {}", log_format).into());
+  }
+  init_tracing(&log_format);
+  for warning in &warnings {
+    warn!("{}", warning);
+  }
+  let addr = format!("{}:{}", setting("host"), setting("port")).parse()?;
+  // the key is parsed from the secret, which is zeroed when it is dropped
+  let server = match optional_setting("key_file") {
+    Some(reference) => {
+      let pem = load_secret(&reference)
+        .map_err(|e| format!("cannot load the endorser key {}: {}", reference, e))?;
+      let server = EndorserServiceState::with_private_key_pem(&pem)
+        .map_err(|e| format!("invalid endorser key {}: {}", reference, e))?;
+      info!("Endorser signs with the key of {}", reference);
+      server
+    },
+    None => EndorserServiceState::new(),
+  };
 
   let job = tokio::spawn(async move {
-    println!("Endorser host listening on {:?}", addr);
+    info!("Endorser host listening on {}", addr);
 
     let _ = Server::builder()
+      .trace_fn(call_span)
       .add_service(EndorserCallServer::new(server))
       .serve(addr)
       .await;
@@ -399,3 +750,57 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hadoop_settings() {
+    // every key of a Hadoop configuration file sets its flag
+    let values = [
+      "0.0.0.0",
+      "9191",
+      "json",
+      "exec:/usr/bin/fetch-key nimble-endorser-1",
+    ];
+    let xml = hadoop_conf::to_xml(
+      HADOOP_KEYS
+        .iter()
+        .zip(values.iter())
+        .map(|((name, _flag), value)| (*name, value.to_string())),
+    );
+    let (settings, warnings) = hadoop_settings(&HadoopConf::parse(&xml).unwrap());
+    assert!(warnings.is_empty());
+    let expected = HADOOP_KEYS
+      .iter()
+      .zip(values.iter())
+      .map(|((_name, flag), value)| (*flag, value.to_string()))
+      .collect::<Vec<_>>();
+    assert_eq!(settings, expected);
+
+    // the keys of the coordinator and of the rest of Hadoop are ignored silently
+    let xml = hadoop_conf::to_xml(vec![
+      ("nimble.coordinator.port", "8080".to_string()),
+      ("dfs.replication", "3".to_string()),
+      ("nimble.endorser.prot", "9090".to_string()),
+    ]);
+    let (settings, warnings) = hadoop_settings(&HadoopConf::parse(&xml).unwrap());
+    assert!(settings.is_empty());
+    assert_eq!(
+      warnings,
+      vec!["unknown key nimble.endorser.prot in --hadoop-conf"]
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_cpu_time() {
+    let before = cpu_time();
+    let mut digest = NimbleDigest::digest(b"spin");
+    while cpu_time() == before {
+      digest = NimbleDigest::digest(&digest.to_bytes());
+    }
+    assert!(cpu_time() > before);
+  }
+}
@@ -0,0 +1,349 @@
+//! The tails of the ledgers that an endorser endorses, sharded by handle. A create write-locks the
+//! shard of its handle alone, so it does not block the appends to and the reads of the ledgers of
+//! the other shards, and an append or a read locks its shard only to look up the tail of its
+//! ledger, which it then locks on its own. The map keeps count of its tails and of the memory
+//! they take as they change, so that reporting them does not walk the map.
+use crate::errors::EndorserError;
+use ledger::{Block, Handle, MetaBlock, Nonce, Nonces};
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap},
+  hash::{Hash, Hasher},
+  mem,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, RwLock,
+  },
+};
+
+/// the metablock of the tail of a ledger, with the block and the nonces of the tail entry
+pub type Tail = (MetaBlock, Block, Nonces);
+
+pub type ProtectedTail = Arc<RwLock<Tail>>;
+
+const NUM_SHARDS: usize = 64;
+
+/// the approximate bytes of memory that the tail of a ledger takes: its entry in the map, the
+/// counts of its `Arc`, and the tail with the contents of its block and its nonces
+fn tail_size(tail: &Tail) -> usize {
+  mem::size_of::<(Handle, ProtectedTail)>()
+    + 2 * mem::size_of::<usize>()
+    + mem::size_of::<RwLock<Tail>>()
+    + tail.1.len()
+    + tail.2.len() * Nonce::num_bytes()
+}
+
+pub struct TailMap {
+  shards: Vec<RwLock<HashMap<Handle, ProtectedTail>>>,
+  num_tails: AtomicUsize,
+  num_bytes: AtomicUsize,
+}
+
+impl TailMap {
+  pub fn new() -> Self {
+    TailMap::with_shards(NUM_SHARDS)
+  }
+
+  pub fn with_shards(num_shards: usize) -> Self {
+    assert!(num_shards > 0);
+    TailMap {
+      shards: (0..num_shards)
+        .map(|_| RwLock::new(HashMap::new()))
+        .collect(),
+      num_tails: AtomicUsize::new(0),
+      num_bytes: AtomicUsize::new(0),
+    }
+  }
+
+  /// the number of ledgers in the map
+  pub fn num_tails(&self) -> usize {
+    self.num_tails.load(Ordering::SeqCst)
+  }
+
+  /// the approximate bytes of memory that the tails in the map take
+  pub fn num_bytes(&self) -> usize {
+    self.num_bytes.load(Ordering::SeqCst)
+  }
+
+  fn add(&self, tail: &Tail) {
+    self.num_tails.fetch_add(1, Ordering::SeqCst);
+    self.num_bytes.fetch_add(tail_size(tail), Ordering::SeqCst);
+  }
+
+  fn remove(&self, tail: &Tail) {
+    self.num_tails.fetch_sub(1, Ordering::SeqCst);
+    self.num_bytes.fetch_sub(tail_size(tail), Ordering::SeqCst);
+  }
+
+  fn shard_index(&self, handle: &Handle) -> usize {
+    let mut hasher = DefaultHasher::new();
+    handle.hash(&mut hasher);
+    hasher.finish() as usize % self.shards.len()
+  }
+
+  fn shard(&self, handle: &Handle) -> &RwLock<HashMap<Handle, ProtectedTail>> {
+    &self.shards[self.shard_index(handle)]
+  }
+
+  /// the tail of the ledger `handle`, if the endorser has it
+  pub fn get(&self, handle: &Handle) -> Result<Option<ProtectedTail>, EndorserError> {
+    let shard = self
+      .shard(handle)
+      .read()
+      .map_err(|_e| EndorserError::FailedToAcquireLedgerMapReadLock)?;
+    Ok(shard.get(handle).cloned())
+  }
+
+  /// the tail of the ledger `handle`, which `tail` makes if the endorser does not have it yet;
+  /// returns whether it was made
+  pub fn get_or_insert(
+    &self,
+    handle: &Handle,
+    tail: impl FnOnce() -> Tail,
+  ) -> Result<(ProtectedTail, bool), EndorserError> {
+    let mut shard = self
+      .shard(handle)
+      .write()
+      .map_err(|_e| EndorserError::FailedToAcquireLedgerMapWriteLock)?;
+    if let Some(existing) = shard.get(handle) {
+      return Ok((existing.clone(), false));
+    }
+    let tail = tail();
+    self.add(&tail);
+    let inserted = Arc::new(RwLock::new(tail));
+    shard.insert(*handle, inserted.clone());
+    Ok((inserted, true))
+  }
+
+  /// replaces `tail`, a tail in the map whose lock the caller holds, with `new`; the caller keeps
+  /// `insert_all` from running meanwhile, as the endorser does with the lock of its view ledger
+  /// state, since the counters would otherwise count a tail that `insert_all` took out of the map
+  pub fn replace(&self, tail: &mut Tail, new: Tail) {
+    let (old_size, new_size) = (tail_size(tail), tail_size(&new));
+    if new_size >= old_size {
+      self
+        .num_bytes
+        .fetch_add(new_size - old_size, Ordering::SeqCst);
+    } else {
+      self
+        .num_bytes
+        .fetch_sub(old_size - new_size, Ordering::SeqCst);
+    }
+    *tail = new;
+  }
+
+  /// inserts the tails of `entries`, after removing all the others if `clear`, as one update
+  pub fn insert_all(&self, entries: Vec<(Handle, Tail)>, clear: bool) -> Result<(), EndorserError> {
+    let mut shards = Vec::with_capacity(self.shards.len());
+    for shard in &self.shards {
+      shards.push(
+        shard
+          .write()
+          .map_err(|_e| EndorserError::FailedToAcquireLedgerMapWriteLock)?,
+      );
+    }
+    if clear {
+      shards.iter_mut().for_each(|shard| shard.clear());
+      self.num_tails.store(0, Ordering::SeqCst);
+      self.num_bytes.store(0, Ordering::SeqCst);
+    }
+    for (handle, tail) in entries {
+      self.add(&tail);
+      let shard = &mut shards[self.shard_index(&handle)];
+      if let Some(replaced) = shard.insert(handle, Arc::new(RwLock::new(tail))) {
+        let replaced = replaced
+          .read()
+          .map_err(|_e| EndorserError::FailedToAcquireLedgerEntryReadLock)?;
+        self.remove(&replaced);
+      }
+    }
+    Ok(())
+  }
+
+  /// the tails of all the ledgers, sorted by handle
+  pub fn sorted(&self) -> Result<Vec<(Handle, ProtectedTail)>, EndorserError> {
+    let mut tails = Vec::new();
+    for shard in &self.shards {
+      let shard = shard
+        .read()
+        .map_err(|_e| EndorserError::FailedToAcquireLedgerMapReadLock)?;
+      tails.extend(shard.iter().map(|(handle, tail)| (*handle, tail.clone())));
+    }
+    tails.sort_unstable_by_key(|(handle, _tail)| *handle);
+    Ok(tails)
+  }
+
+  #[cfg(test)]
+  pub fn is_empty(&self) -> bool {
+    self
+      .shards
+      .iter()
+      .all(|shard| shard.read().map_or(true, |shard| shard.is_empty()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::NimbleDigest;
+  use proptest::prelude::*;
+
+  fn tail(height: u8) -> Tail {
+    let block = Block::new(&[height]);
+    (
+      MetaBlock::genesis(&NimbleDigest::digest(&[height])),
+      block,
+      Nonces::new(),
+    )
+  }
+
+  #[test]
+  pub fn check_tail_map() {
+    use ledger::CustomSerde;
+
+    for num_shards in [1, NUM_SHARDS] {
+      let map = TailMap::with_shards(num_shards);
+      assert!(map.is_empty());
+      assert_eq!((map.num_tails(), map.num_bytes()), (0, 0));
+      let handles = (0..100u8)
+        .map(|i| NimbleDigest::digest(&[i]))
+        .collect::<Vec<_>>();
+      for (i, handle) in handles.iter().enumerate() {
+        let (_tail, inserted) = map.get_or_insert(handle, || tail(i as u8)).unwrap();
+        assert!(inserted);
+      }
+      assert_eq!(map.num_tails(), handles.len());
+      assert_eq!(map.num_bytes(), handles.len() * tail_size(&tail(0)));
+
+      // an existing tail is kept
+      let (existing, inserted) = map.get_or_insert(&handles[7], || tail(0)).unwrap();
+      assert!(!inserted);
+      assert_eq!(existing.read().unwrap().1.to_bytes(), vec![7]);
+      assert!(Arc::ptr_eq(
+        &existing,
+        &map.get(&handles[7]).unwrap().unwrap()
+      ));
+      assert!(map
+        .get(&NimbleDigest::digest(b"missing"))
+        .unwrap()
+        .is_none());
+      assert_eq!(map.num_tails(), handles.len());
+
+      // a tail that grows or shrinks changes the bytes, but not the number of tails
+      let before = map.num_bytes();
+      let nonces = Nonces::from_vec(vec![Nonce::new(), Nonce::new()]);
+      let metablock = existing.read().unwrap().0.clone();
+      let bigger = (metablock, Block::new(&[7; 100]), nonces);
+      let growth = tail_size(&bigger) - tail_size(&tail(7));
+      map.replace(&mut existing.write().unwrap(), bigger);
+      assert_eq!(map.num_bytes(), before + growth);
+      map.replace(&mut existing.write().unwrap(), tail(7));
+      assert_eq!(map.num_bytes(), before);
+      assert_eq!(map.num_tails(), handles.len());
+
+      let sorted = map.sorted().unwrap();
+      assert_eq!(sorted.len(), handles.len());
+      assert!(sorted.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+      // a reset replaces all the tails at once
+      map
+        .insert_all(vec![(handles[0], tail(1)), (handles[1], tail(2))], true)
+        .unwrap();
+      assert_eq!(map.sorted().unwrap().len(), 2);
+      assert_eq!(map.num_tails(), 2);
+      map
+        .insert_all(vec![(handles[2], tail(3)), (handles[0], tail(4))], false)
+        .unwrap();
+      assert_eq!(map.sorted().unwrap().len(), 3);
+      assert_eq!(map.num_tails(), 3);
+      assert_eq!(map.num_bytes(), 3 * tail_size(&tail(0)));
+      map.insert_all(Vec::new(), true).unwrap();
+      assert!(map.is_empty());
+      assert_eq!((map.num_tails(), map.num_bytes()), (0, 0));
+    }
+  }
+
+  #[derive(Clone, Debug)]
+  enum Op {
+    /// creates the ledger with the given handle and block length, unless it exists
+    Insert(u8, usize),
+    /// replaces the tail of the ledger, if it exists, with one of the given block length
+    Replace(u8, usize),
+    /// inserts the given tails, after removing all the others if asked to
+    InsertAll(Vec<(u8, usize)>, bool),
+  }
+
+  fn sized_tail(handle: u8, len: usize) -> Tail {
+    (
+      MetaBlock::genesis(&NimbleDigest::digest(&[handle])),
+      Block::new(&vec![handle; len]),
+      Nonces::new(),
+    )
+  }
+
+  fn op() -> impl Strategy<Value = Op> {
+    let tail = || (0..16u8, 0..64usize);
+    prop_oneof![
+      4 => tail().prop_map(|(handle, len)| Op::Insert(handle, len)),
+      4 => tail().prop_map(|(handle, len)| Op::Replace(handle, len)),
+      1 => (prop::collection::vec(tail(), 0..4), any::<bool>())
+        .prop_map(|(tails, clear)| Op::InsertAll(tails, clear)),
+    ]
+  }
+
+  proptest! {
+    // the counters match the tails in the map once threads inserted, replaced, and removed them
+    // concurrently; like the endorser, the threads exclude `insert_all` from the other updates
+    #[test]
+    fn check_tail_map_counters(threads in prop::collection::vec(
+      prop::collection::vec(op(), 1..32),
+      1..4,
+    )) {
+      let map = Arc::new(TailMap::with_shards(4));
+      let state = Arc::new(RwLock::new(()));
+      let handles = threads
+        .into_iter()
+        .map(|ops| {
+          let (map, state) = (map.clone(), state.clone());
+          std::thread::spawn(move || {
+            for op in ops {
+              match op {
+                Op::Insert(handle, len) => {
+                  let _state = state.read().unwrap();
+                  let digest = NimbleDigest::digest(&[handle]);
+                  map.get_or_insert(&digest, || sized_tail(handle, len)).unwrap();
+                },
+                Op::Replace(handle, len) => {
+                  let _state = state.read().unwrap();
+                  if let Some(tail) = map.get(&NimbleDigest::digest(&[handle])).unwrap() {
+                    map.replace(&mut tail.write().unwrap(), sized_tail(handle, len));
+                  }
+                },
+                Op::InsertAll(tails, clear) => {
+                  let _state = state.write().unwrap();
+                  let entries = tails
+                    .into_iter()
+                    .map(|(handle, len)| (NimbleDigest::digest(&[handle]), sized_tail(handle, len)))
+                    .collect();
+                  map.insert_all(entries, clear).unwrap();
+                },
+              }
+            }
+          })
+        })
+        .collect::<Vec<_>>();
+      for handle in handles {
+        handle.join().unwrap();
+      }
+
+      let tails = map.sorted().unwrap();
+      prop_assert_eq!(map.num_tails(), tails.len());
+      prop_assert_eq!(
+        map.num_bytes(),
+        tails
+          .iter()
+          .map(|(_handle, tail)| tail_size(&tail.read().unwrap()))
+          .sum::<usize>()
+      );
+    }
+  }
+}
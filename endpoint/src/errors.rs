@@ -18,6 +18,8 @@ pub enum EndpointError {
   FailedToReadCounter,
   /// returned if the endpoint fails to verify the read counter
   FaieldToVerifyReadCounter,
+  /// returned if the coordinator answers a read with a nonce other than the one sent
+  MismatchedNonce,
   /// returned if the endpoint fails to read the view ledger
   FailedToReadViewLedger,
   /// returned if the endpoint fails to acquire the read lock
@@ -26,4 +28,8 @@ pub enum EndpointError {
   FailedToAcquireWriteLock,
   /// returned if the endpoint fails to apply view change
   FailedToApplyViewChange,
+  /// returned if the endorsers do not vouch that the view ledger tail is the current view
+  FailedToVerifyViewTail,
+  /// returned if the coordinator uses a different hash algorithm than the endpoint
+  MismatchedHashAlgorithm,
 }
@@ -12,13 +12,16 @@ pub mod coordinator_proto {
 
 use crate::errors::EndpointError;
 use coordinator_proto::{
-  call_client::CallClient, AppendReq, AppendResp, NewLedgerReq, NewLedgerResp, ReadLatestReq,
-  ReadLatestResp, ReadViewByIndexReq, ReadViewByIndexResp, ReadViewTailReq, ReadViewTailResp,
+  call_client::CallClient, AppendReq, AppendResp, NewLedgerReq, NewLedgerResp, ReadConsistency,
+  ReadLatestReq, ReadLatestResp, ReadViewByIndexReq, ReadViewByIndexResp, ReadViewTailReq,
+  ReadViewTailResp,
 };
 use ledger::{
+  compute_view_block_hash,
   errors::VerificationError,
+  hash::{HashAlgorithm, HASH_ALGORITHM},
   signature::{PrivateKey, PrivateKeyTrait, PublicKey, PublicKeyTrait, Signature, SignatureTrait},
-  Block, CustomSerde, NimbleDigest, NimbleHashTrait, VerifierState,
+  CustomSerde, NimbleDigest, Nonce, VerifierState,
 };
 use rand::random;
 use std::{
@@ -70,12 +73,22 @@ impl Connection {
     })
   }
 
-  pub async fn new_ledger(&self, handle: &[u8], block: &[u8]) -> Result<Vec<u8>, EndpointError> {
+  /// creates a ledger whose genesis receipts also cover `metadata` (at most 4 KiB, and fixed for
+  /// the life of the ledger)
+  pub async fn new_ledger(
+    &self,
+    handle: &[u8],
+    block: &[u8],
+    metadata: &[u8],
+  ) -> Result<Vec<u8>, EndpointError> {
     let req = Request::new(NewLedgerReq {
       handle: handle.to_vec(),
       block: block.to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: metadata.to_vec(),
     });
-    let NewLedgerResp { receipts } = self.clients[random::<usize>() % self.num_grpc_channels]
+    let NewLedgerResp { receipts, .. } = self.clients[random::<usize>() % self.num_grpc_channels]
       .clone()
       .new_ledger(req)
       .await
@@ -97,10 +110,12 @@ impl Connection {
       handle: handle.to_vec(),
       block: block.to_vec(),
       expected_height,
+      request_id: String::new(),
     });
     let AppendResp {
       hash_nonces,
       receipts,
+      ..
     } = self.clients[random::<usize>() % self.num_grpc_channels]
       .clone()
       .append(req)
@@ -122,11 +137,14 @@ impl Connection {
       block,
       nonces,
       receipts,
+      nonce: echoed_nonce,
+      ..
     } = self.clients[random::<usize>() % self.num_grpc_channels]
       .clone()
       .read_latest(ReadLatestReq {
         handle: handle.to_vec(),
         nonce: nonce.to_vec(),
+        consistency: ReadConsistency::Attested as i32,
       })
       .await
       .map_err(|e| {
@@ -134,6 +152,9 @@ impl Connection {
         EndpointError::FailedToReadCounter
       })?
       .into_inner();
+    if echoed_nonce != nonce {
+      return Err(EndpointError::MismatchedNonce);
+    }
     Ok((block, nonces, receipts))
   }
 
@@ -141,8 +162,9 @@ impl Connection {
     &self,
     index: usize,
   ) -> Result<(Vec<u8>, Vec<u8>), EndpointError> {
-    let ReadViewByIndexResp { block, receipts } = self.clients
-      [random::<usize>() % self.num_grpc_channels]
+    let ReadViewByIndexResp {
+      block, receipts, ..
+    } = self.clients[random::<usize>() % self.num_grpc_channels]
       .clone()
       .read_view_by_index(ReadViewByIndexReq {
         index: index as u64,
@@ -153,19 +175,43 @@ impl Connection {
     Ok((block, receipts))
   }
 
-  pub async fn read_view_tail(&self) -> Result<(Vec<u8>, Vec<u8>, usize, Vec<u8>), EndpointError> {
+  /// reads the tail of the view ledger; the returned receipts on `nonce` show that the tail is the
+  /// current view
+  pub async fn read_view_tail(
+    &self,
+    nonce: &[u8],
+  ) -> Result<(Vec<u8>, Vec<u8>, usize, Vec<u8>, Vec<u8>), EndpointError> {
     let ReadViewTailResp {
       block,
       receipts,
       height,
       attestations,
+      hash_algorithm,
+      nonce_receipts,
     } = self.clients[random::<usize>() % self.num_grpc_channels]
       .clone()
-      .read_view_tail(ReadViewTailReq {})
+      .read_view_tail(ReadViewTailReq {
+        nonce: nonce.to_vec(),
+      })
       .await
       .map_err(|_e| EndpointError::FailedToReadViewLedger)?
       .into_inner();
-    Ok((block, receipts, height as usize, attestations))
+    // digests computed with a different hash function can never verify, so fail early
+    if hash_algorithm != HASH_ALGORITHM.id() {
+      eprintln!(
+        "the coordinator uses hash algorithm {:?} but the endpoint uses {:?}",
+        HashAlgorithm::from_id(hash_algorithm),
+        HASH_ALGORITHM
+      );
+      return Err(EndpointError::MismatchedHashAlgorithm);
+    }
+    Ok((
+      block,
+      receipts,
+      height as usize,
+      attestations,
+      nonce_receipts,
+    ))
   }
 }
 
@@ -215,12 +261,16 @@ impl EndpointState {
       let (block, _r) = conn.read_view_by_index(1usize).await.unwrap();
 
       // the hash of the genesis block of the view ledger uniquely identifies a particular instance of NimbleLedger
-      let id = Block::from_bytes(&block).unwrap().hash();
+      let id = compute_view_block_hash(&block).unwrap();
       vs.set_group_identity(id);
 
-      let (block, receipts, height, attestations) = conn.read_view_tail().await.unwrap();
+      let nonce = Nonce::new().to_bytes();
+      let (block, receipts, height, attestations, nonce_receipts) =
+        conn.read_view_tail(&nonce).await.unwrap();
       let res = vs.apply_view_change(&block, &receipts, Some(&attestations));
       assert!(res.is_ok());
+      let res = vs.verify_view_tail(&nonce, &nonce_receipts);
+      assert!(res.is_ok());
 
       for index in (1..height).rev() {
         let (block, receipts) = conn.read_view_by_index(index).await.unwrap();
@@ -277,18 +327,25 @@ impl EndpointState {
       }
     };
 
-    let (block, receipts, height, attestations) = self.conn.read_view_tail().await.unwrap();
+    // the nonce keeps the coordinator from passing off a view that has since changed as the tail
+    let nonce = Nonce::new().to_bytes();
+    let (block, receipts, height, attestations, nonce_receipts) =
+      self.conn.read_view_tail(&nonce).await?;
     if let Ok(mut vs_wr) = self.vs.write() {
       let res = vs_wr.apply_view_change(&block, &receipts, Some(&attestations));
       if res.is_err() {
         return Err(EndpointError::FailedToApplyViewChange);
       }
+      let res = vs_wr.verify_view_tail(&nonce, &nonce_receipts);
+      if res.is_err() {
+        return Err(EndpointError::FailedToVerifyViewTail);
+      }
     } else {
       return Err(EndpointError::FailedToAcquireWriteLock);
     }
 
     for index in (start_height..height).rev() {
-      let (block, receipts) = self.conn.read_view_by_index(index).await.unwrap();
+      let (block, receipts) = self.conn.read_view_by_index(index).await?;
       if let Ok(mut vs_wr) = self.vs.write() {
         let res = vs_wr.apply_view_change(&block, &receipts, None);
         if res.is_err() {
@@ -330,7 +387,7 @@ impl EndpointState {
 
     // issue a request to the coordinator and receive a response
     let receipts = {
-      let res = self.conn.new_ledger(handle, &block).await;
+      let res = self.conn.new_ledger(handle, &block, &[]).await;
       if res.is_err() {
         return Err(EndpointError::FailedToCreateNewCounter);
       }
@@ -426,7 +483,12 @@ impl EndpointState {
 
     // issue a request to the coordinator and receive a response
     let (hash_nonces, receipts) = {
-      let res = self.conn.append(handle, &block, expected_counter).await;
+      // the coordinator appends only if the counter currently has the preceding value
+      let current_counter = match expected_counter.checked_sub(1) {
+        Some(counter) => counter,
+        None => return Err(EndpointError::FailedToConvertCounter),
+      };
+      let res = self.conn.append(handle, &block, current_counter).await;
 
       if res.is_err() {
         return Err(EndpointError::FailedToIncrementCounter);
@@ -492,14 +554,7 @@ impl EndpointState {
     sigformat: SignatureFormat,
   ) -> Result<(Vec<u8>, u64, Vec<u8>), EndpointError> {
     // issue a request to the coordinator and receive a response
-    let (block, nonces, receipts) = {
-      let res = self.conn.read_latest(handle, nonce).await;
-
-      if res.is_err() {
-        return Err(EndpointError::FailedToReadCounter);
-      }
-      res.unwrap()
-    };
+    let (block, nonces, receipts) = self.conn.read_latest(handle, nonce).await?;
 
     // verify the response received from the coordinator
     let res = {
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// the coordinator is a binary, so its reader is compiled in from its source
+#[path = "../../coordinator/src/hadoop_conf.rs"]
+#[allow(dead_code)]
+mod hadoop_conf;
+
+fuzz_target!(|data: &[u8]| {
+  if let Ok(xml) = std::str::from_utf8(data) {
+    let _ = hadoop_conf::HadoopConf::parse(xml);
+  }
+});
@@ -0,0 +1,87 @@
+package com.microsoft.nimble;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+import java.nio.charset.StandardCharsets;
+import java.security.MessageDigest;
+import java.security.NoSuchAlgorithmException;
+
+/**
+ * The digests that endorsers sign, computed as the ledger crate computes them with its default
+ * hash, SHA-256. The digest of an empty message is the zero digest rather than its SHA-256.
+ */
+public final class Payloads {
+  public static final int DIGEST_LEN = 32;
+
+  private static final byte[] GENESIS_METADATA_DOMAIN_TAG =
+      "NimbleGenesisMetadata".getBytes(StandardCharsets.US_ASCII);
+
+  private Payloads() {}
+
+  /** the digest of the concatenation of {@code parts} */
+  public static byte[] digest(byte[]... parts) {
+    MessageDigest sha256;
+    try {
+      sha256 = MessageDigest.getInstance("SHA-256");
+    } catch (NoSuchAlgorithmException e) {
+      throw new IllegalStateException(e);
+    }
+    int len = 0;
+    for (byte[] part : parts) {
+      sha256.update(part);
+      len += part.length;
+    }
+    return len == 0 ? new byte[DIGEST_LEN] : sha256.digest();
+  }
+
+  /** the block of the genesis entry of a ledger created with {@code block} and {@code metadata} */
+  public static byte[] genesisBlock(byte[] block, byte[] metadata) {
+    if (metadata.length == 0 && !startsWith(block, GENESIS_METADATA_DOMAIN_TAG)) {
+      return block;
+    }
+    ByteBuffer buf =
+        ByteBuffer.allocate(GENESIS_METADATA_DOMAIN_TAG.length + 4 + metadata.length + block.length)
+            .order(ByteOrder.LITTLE_ENDIAN);
+    buf.put(GENESIS_METADATA_DOMAIN_TAG).putInt(metadata.length).put(metadata).put(block);
+    return buf.array();
+  }
+
+  /**
+   * the block hash of the metablock of an entry with {@code block}, whose nonces have the digest
+   * {@code noncesHash}, which is the zero digest if it has none
+   */
+  public static byte[] aggregatedBlockHash(byte[] block, byte[] noncesHash) {
+    return digest(digest(digest(block)), noncesHash);
+  }
+
+  /** the hash of a metablock, which is the tail of a ledger that ends with its entry */
+  public static byte[] metablockHash(byte[] prev, byte[] blockHash, long height) {
+    return digest(prev, blockHash, u64(height));
+  }
+
+  /**
+   * the message that an endorser of {@code view} signs to endorse {@code tail} as the tail of the
+   * ledger with {@code handle}, together with the {@code nonce} of a read unless it is empty
+   */
+  public static byte[] tailMessage(
+      byte[] groupIdentity, byte[] view, byte[] handle, byte[] tail, byte[] nonce) {
+    byte[] tailHash = nonce.length == 0 ? tail : digest(tail, nonce);
+    return digest(groupIdentity, digest(view, digest(digest(handle), tailHash)));
+  }
+
+  static byte[] u64(long value) {
+    return ByteBuffer.allocate(8).order(ByteOrder.LITTLE_ENDIAN).putLong(value).array();
+  }
+
+  private static boolean startsWith(byte[] bytes, byte[] prefix) {
+    if (bytes.length < prefix.length) {
+      return false;
+    }
+    for (int i = 0; i < prefix.length; i++) {
+      if (bytes[i] != prefix[i]) {
+        return false;
+      }
+    }
+    return true;
+  }
+}
@@ -0,0 +1,124 @@
+package com.microsoft.nimble;
+
+import java.io.ByteArrayOutputStream;
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+import java.util.ArrayList;
+import java.util.Collections;
+import java.util.List;
+
+/**
+ * Receipts in the encoding of {@code Receipts::to_bytes} of the ledger crate: a version byte, the
+ * number of groups, and, for each group, the view, the metablock (the previous tail, the block
+ * hash, and the height as u64), the number of signatures, and each signature as the length and
+ * bytes of the endorser's public key and the length and bytes of the signature. Numbers are u32
+ * little-endian unless noted.
+ */
+public final class Receipts {
+  public static final int ENCODING_VERSION = 1;
+
+  /** A metablock that endorsers signed in a view, and their signatures. */
+  public static final class Group {
+    public final byte[] view;
+    public final byte[] prev;
+    public final byte[] blockHash;
+    public final long height;
+    public final List<Signature> signatures;
+
+    public Group(byte[] view, byte[] prev, byte[] blockHash, long height, List<Signature> signatures) {
+      this.view = view;
+      this.prev = prev;
+      this.blockHash = blockHash;
+      this.height = height;
+      this.signatures = Collections.unmodifiableList(signatures);
+    }
+
+    /** the hash of the metablock, which is the tail that the signatures endorse */
+    public byte[] tail() {
+      return Payloads.metablockHash(prev, blockHash, height);
+    }
+  }
+
+  /** The signature of an endorser, which is known by its public key. */
+  public static final class Signature {
+    public final byte[] publicKey;
+    public final byte[] signature;
+
+    public Signature(byte[] publicKey, byte[] signature) {
+      this.publicKey = publicKey;
+      this.signature = signature;
+    }
+  }
+
+  private Receipts() {}
+
+  /** parses receipts; throws {@link IllegalArgumentException} if they are malformed */
+  public static List<Group> parse(byte[] bytes) {
+    ByteBuffer buf = ByteBuffer.wrap(bytes).order(ByteOrder.LITTLE_ENDIAN);
+    try {
+      if (buf.get() != ENCODING_VERSION) {
+        throw new IllegalArgumentException("unsupported receipts encoding version");
+      }
+      int numGroups = buf.getInt();
+      List<Group> groups = new ArrayList<>();
+      for (int i = 0; i < numGroups; i++) {
+        byte[] view = take(buf, Payloads.DIGEST_LEN);
+        byte[] prev = take(buf, Payloads.DIGEST_LEN);
+        byte[] blockHash = take(buf, Payloads.DIGEST_LEN);
+        long height = buf.getLong();
+        int numSignatures = buf.getInt();
+        List<Signature> signatures = new ArrayList<>();
+        for (int j = 0; j < numSignatures; j++) {
+          byte[] publicKey = take(buf, buf.getInt());
+          byte[] signature = take(buf, buf.getInt());
+          signatures.add(new Signature(publicKey, signature));
+        }
+        groups.add(new Group(view, prev, blockHash, height, signatures));
+      }
+      if (buf.hasRemaining()) {
+        throw new IllegalArgumentException("trailing bytes after the receipts");
+      }
+      return groups;
+    } catch (java.nio.BufferUnderflowException e) {
+      throw new IllegalArgumentException("the receipts are truncated", e);
+    }
+  }
+
+  /**
+   * encodes receipts as {@link #parse} reads them; the groups and the signatures in them must be
+   * in the order of the Rust encoding, which sorts them by their bytes, to encode identically
+   */
+  public static byte[] encode(List<Group> groups) {
+    ByteArrayOutputStream out = new ByteArrayOutputStream();
+    out.write(ENCODING_VERSION);
+    writeInt(out, groups.size());
+    for (Group group : groups) {
+      out.write(group.view, 0, group.view.length);
+      out.write(group.prev, 0, group.prev.length);
+      out.write(group.blockHash, 0, group.blockHash.length);
+      out.write(Payloads.u64(group.height), 0, 8);
+      writeInt(out, group.signatures.size());
+      for (Signature signature : group.signatures) {
+        writeInt(out, signature.publicKey.length);
+        out.write(signature.publicKey, 0, signature.publicKey.length);
+        writeInt(out, signature.signature.length);
+        out.write(signature.signature, 0, signature.signature.length);
+      }
+    }
+    return out.toByteArray();
+  }
+
+  private static byte[] take(ByteBuffer buf, int len) {
+    if (len < 0 || len > buf.remaining()) {
+      throw new IllegalArgumentException("the receipts are truncated");
+    }
+    byte[] bytes = new byte[len];
+    buf.get(bytes);
+    return bytes;
+  }
+
+  private static void writeInt(ByteArrayOutputStream out, int value) {
+    byte[] bytes = ByteBuffer.allocate(4).order(ByteOrder.LITTLE_ENDIAN).putInt(value).array();
+    out.write(bytes, 0, bytes.length);
+  }
+}
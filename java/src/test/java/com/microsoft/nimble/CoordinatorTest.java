@@ -0,0 +1,96 @@
+package com.microsoft.nimble;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assume.assumeTrue;
+
+import com.google.protobuf.ByteString;
+import com.microsoft.nimble.coordinator.AppendReq;
+import com.microsoft.nimble.coordinator.AppendResp;
+import com.microsoft.nimble.coordinator.CallGrpc;
+import com.microsoft.nimble.coordinator.NewLedgerReq;
+import com.microsoft.nimble.coordinator.NewLedgerResp;
+import com.microsoft.nimble.coordinator.ReadLatestReq;
+import com.microsoft.nimble.coordinator.ReadLatestResp;
+import io.grpc.ManagedChannel;
+import io.grpc.ManagedChannelBuilder;
+import java.nio.charset.StandardCharsets;
+import java.security.SecureRandom;
+import java.util.List;
+import java.util.UUID;
+import java.util.concurrent.TimeUnit;
+import org.junit.After;
+import org.junit.Before;
+import org.junit.Test;
+
+/**
+ * Drives a running coordinator through the generated stubs; the test is skipped unless
+ * NIMBLE_COORDINATOR is set to its address, e.g., [::1]:8080.
+ */
+public class CoordinatorTest {
+  private ManagedChannel channel;
+  private CallGrpc.CallBlockingStub stub;
+
+  @Before
+  public void connect() {
+    String target = System.getenv("NIMBLE_COORDINATOR");
+    assumeTrue("NIMBLE_COORDINATOR is not set", target != null && !target.isEmpty());
+    channel = ManagedChannelBuilder.forTarget(target).usePlaintext().build();
+    stub = CallGrpc.newBlockingStub(channel).withDeadlineAfter(30, TimeUnit.SECONDS);
+  }
+
+  @After
+  public void disconnect() throws InterruptedException {
+    if (channel != null) {
+      channel.shutdownNow().awaitTermination(5, TimeUnit.SECONDS);
+    }
+  }
+
+  @Test
+  public void testAppendAndRead() {
+    byte[] handle = ("java-" + UUID.randomUUID()).getBytes(StandardCharsets.UTF_8);
+    NewLedgerResp created =
+        stub.newLedger(
+            NewLedgerReq.newBuilder()
+                .setHandle(ByteString.copyFrom(handle))
+                .setBlock(ByteString.copyFromUtf8("genesis"))
+                .build());
+    Receipts.Group genesis = Receipts.parse(created.getReceipts().toByteArray()).get(0);
+    assertEquals(0, genesis.height);
+    assertArrayEquals(
+        Payloads.aggregatedBlockHash(
+            Payloads.genesisBlock("genesis".getBytes(StandardCharsets.UTF_8), new byte[0]),
+            new byte[Payloads.DIGEST_LEN]),
+        genesis.blockHash);
+
+    byte[] block = "block1".getBytes(StandardCharsets.UTF_8);
+    AppendResp appended =
+        stub.append(
+            AppendReq.newBuilder()
+                .setHandle(ByteString.copyFrom(handle))
+                .setBlock(ByteString.copyFrom(block))
+                .setExpectedHeight(0)
+                .build());
+    assertEquals(1, appended.getHeight());
+    List<Receipts.Group> groups = Receipts.parse(appended.getReceipts().toByteArray());
+    for (Receipts.Group group : groups) {
+      assertEquals(1, group.height);
+      assertArrayEquals(genesis.tail(), group.prev);
+      assertArrayEquals(
+          Payloads.aggregatedBlockHash(block, appended.getHashNonces().toByteArray()),
+          group.blockHash);
+    }
+
+    byte[] nonce = new byte[16];
+    new SecureRandom().nextBytes(nonce);
+    ReadLatestResp read =
+        stub.readLatest(
+            ReadLatestReq.newBuilder()
+                .setHandle(ByteString.copyFrom(handle))
+                .setNonce(ByteString.copyFrom(nonce))
+                .build());
+    assertEquals(1, read.getHeight());
+    assertArrayEquals(block, read.getBlock().toByteArray());
+    assertEquals(1, Receipts.parse(read.getReceipts().toByteArray()).get(0).height);
+  }
+}
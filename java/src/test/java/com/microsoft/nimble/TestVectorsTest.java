@@ -0,0 +1,97 @@
+package com.microsoft.nimble;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+
+import com.google.gson.JsonArray;
+import com.google.gson.JsonElement;
+import com.google.gson.JsonObject;
+import com.google.gson.JsonParser;
+import java.io.Reader;
+import java.nio.charset.StandardCharsets;
+import java.nio.file.Files;
+import java.nio.file.Paths;
+import java.util.List;
+import org.junit.BeforeClass;
+import org.junit.Test;
+
+/**
+ * Checks the Java payloads and receipts against the golden vectors that the verifier crate
+ * exports to ledger/testdata/receipts.json, byte for byte.
+ */
+public class TestVectorsTest {
+  private static JsonObject vectors;
+
+  @BeforeClass
+  public static void load() throws Exception {
+    String path = System.getProperty("nimble.testVectors", "../ledger/testdata/receipts.json");
+    try (Reader reader = Files.newBufferedReader(Paths.get(path), StandardCharsets.UTF_8)) {
+      vectors = JsonParser.parseReader(reader).getAsJsonObject();
+    }
+    assertEquals("sha256", vectors.get("hash_algorithm").getAsString());
+  }
+
+  @Test
+  public void testPayloads() {
+    byte[] groupIdentity = hex(vectors, "group_identity");
+    byte[] view = hex(vectors, "view");
+    byte[] handle = hex(vectors, "handle");
+    for (JsonElement element : vectors.getAsJsonArray("vectors")) {
+      JsonObject vector = element.getAsJsonObject();
+      String name = vector.get("name").getAsString();
+      long height = vector.get("height").getAsLong();
+      byte[] block = hex(vector, "block");
+      if (height == 0) {
+        block = Payloads.genesisBlock(block, hex(vector, "metadata"));
+      }
+
+      byte[] blockHash = Payloads.aggregatedBlockHash(block, new byte[Payloads.DIGEST_LEN]);
+      assertArrayEquals(name, hex(vector, "block_hash"), blockHash);
+      byte[] tail = Payloads.metablockHash(hex(vector, "prev"), blockHash, height);
+      assertArrayEquals(name, hex(vector, "tail"), tail);
+      byte[] message =
+          Payloads.tailMessage(groupIdentity, view, handle, tail, hex(vector, "nonce"));
+      assertArrayEquals(name, hex(vector, "message"), message);
+    }
+  }
+
+  @Test
+  public void testReceipts() {
+    byte[] view = hex(vectors, "view");
+    byte[] publicKey = hex(vectors, "public_key");
+    JsonArray array = vectors.getAsJsonArray("vectors");
+    for (JsonElement element : array) {
+      JsonObject vector = element.getAsJsonObject();
+      String name = vector.get("name").getAsString();
+      byte[] bytes = hex(vector, "receipts");
+      List<Receipts.Group> groups = Receipts.parse(bytes);
+      assertEquals(name, 1, groups.size());
+
+      Receipts.Group group = groups.get(0);
+      assertArrayEquals(name, view, group.view);
+      assertArrayEquals(name, hex(vector, "prev"), group.prev);
+      assertArrayEquals(name, hex(vector, "block_hash"), group.blockHash);
+      assertEquals(name, vector.get("height").getAsLong(), group.height);
+      assertArrayEquals(name, hex(vector, "tail"), group.tail());
+      assertEquals(name, 1, group.signatures.size());
+      assertArrayEquals(name, publicKey, group.signatures.get(0).publicKey);
+
+      assertArrayEquals(name, bytes, Receipts.encode(groups));
+    }
+  }
+
+  @Test(expected = IllegalArgumentException.class)
+  public void testTruncatedReceipts() {
+    byte[] bytes = hex(vectors.getAsJsonArray("vectors").get(0).getAsJsonObject(), "receipts");
+    Receipts.parse(java.util.Arrays.copyOf(bytes, bytes.length - 1));
+  }
+
+  static byte[] hex(JsonObject object, String field) {
+    String s = object.get(field).getAsString();
+    byte[] bytes = new byte[s.length() / 2];
+    for (int i = 0; i < bytes.length; i++) {
+      bytes[i] = (byte) Integer.parseInt(s.substring(2 * i, 2 * i + 2), 16);
+    }
+    return bytes;
+  }
+}
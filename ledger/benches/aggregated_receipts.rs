@@ -0,0 +1,74 @@
+//! The size and the verification time of the receipts of views that sign with BLS, in which the
+//! signatures of the endorsers aggregate into one, against the receipts of views that sign with
+//! P-256, which carry a key and a signature per endorser. Run with `--features bls`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ledger::{
+  signature::{bls, PrivateKey, PrivateKeyTrait, PublicKeyTrait, SignatureSchemeId},
+  CustomSerde, IdSig, MetaBlock, NimbleDigest, Receipt, Receipts, SchemeKeys,
+};
+
+fn bench_aggregated_receipts(c: &mut Criterion) {
+  let view = NimbleDigest::digest(b"view");
+  let metablock = MetaBlock::genesis(&NimbleDigest::digest(b"block"));
+  let message = NimbleDigest::digest(b"message").to_bytes();
+  let mut group = c.benchmark_group("aggregated_receipts");
+  for num_endorsers in [3usize, 7, 15, 31] {
+    let keys = (0..num_endorsers)
+      .map(|_| (PrivateKey::new(), bls::PrivateKey::new()))
+      .collect::<Vec<_>>();
+
+    let mut receipts = Receipts::new();
+    for (key, _bls_key) in &keys {
+      let id_sig = IdSig::new(key.get_public_key().unwrap(), key.sign(&message).unwrap());
+      receipts.add(&Receipt::new(view, metablock.clone(), id_sig));
+    }
+    let id_sigs = receipts.get().values().next().unwrap().clone();
+
+    let scheme_keys = SchemeKeys::new(
+      SignatureSchemeId::Bls12381,
+      keys
+        .iter()
+        .map(|(key, bls_key)| {
+          (
+            key.get_public_key().unwrap().to_bytes(),
+            bls_key.get_public_key().to_bytes(),
+            bls_key.prove_possession().to_bytes(),
+          )
+        })
+        .collect(),
+    );
+    let signatures = keys
+      .iter()
+      .map(|(key, bls_key)| {
+        let position = scheme_keys
+          .position(&key.get_public_key().unwrap().to_bytes())
+          .unwrap();
+        (position, bls_key.sign(&message))
+      })
+      .collect::<Vec<_>>();
+    let aggregated =
+      Receipt::aggregate::<bls::Bls>(&view, &metablock, num_endorsers, &signatures).unwrap();
+
+    println!(
+      "{} endorsers: P-256 receipts are {} bytes, the aggregated receipt is {} bytes",
+      num_endorsers,
+      receipts.to_bytes().len(),
+      aggregated.to_bytes().len()
+    );
+
+    group.bench_with_input(
+      BenchmarkId::new("p256", num_endorsers),
+      &id_sigs,
+      |b, id_sigs| b.iter(|| IdSig::verify_batch(id_sigs, &message).unwrap()),
+    );
+    group.bench_with_input(
+      BenchmarkId::new("bls_aggregated", num_endorsers),
+      &aggregated,
+      |b, aggregated| b.iter(|| aggregated.verify(&scheme_keys, &message).unwrap()),
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, bench_aggregated_receipts);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+#[cfg(feature = "blake3")]
+use ledger::hash::Blake3Hasher;
+use ledger::{
+  endorser_proto::LedgerTailMapEntry,
+  hash::{NimbleHasher, Sha256Hasher},
+  produce_hash_of_state, NimbleDigest,
+};
+use prost::bytes::Bytes;
+
+fn bench_hasher<H: NimbleHasher>(c: &mut Criterion, name: &str) {
+  let mut group = c.benchmark_group(format!("hash/{}", name));
+  // a metablock, a tail hash concatenated with a nonce, and a large block
+  for size in [72usize, 48, 4096] {
+    let bytes = vec![7u8; size];
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(size), &bytes, |b, bytes| {
+      b.iter(|| H::digest(bytes))
+    });
+  }
+  group.finish();
+}
+
+fn bench_hash_algorithms(c: &mut Criterion) {
+  bench_hasher::<Sha256Hasher>(c, "sha256");
+  #[cfg(feature = "blake3")]
+  bench_hasher::<Blake3Hasher>(c, "blake3");
+}
+
+fn bench_hash_of_state(c: &mut Criterion) {
+  // hashing the ledger tail map dominates the cost of initializing an endorser's state
+  let mut group = c.benchmark_group("produce_hash_of_state");
+  group.sample_size(10);
+  for num_ledgers in [1024usize, 1024 * 1024] {
+    let ledger_tail_map = (0..num_ledgers)
+      .map(|i| LedgerTailMapEntry {
+        handle: NimbleDigest::digest(&i.to_le_bytes()).to_bytes().into(),
+        metablock: vec![0u8; 72].into(),
+        height: 0,
+        block: Bytes::new(),
+        nonces: Bytes::new(),
+      })
+      .collect::<Vec<LedgerTailMapEntry>>();
+    group.bench_with_input(
+      BenchmarkId::from_parameter(num_ledgers),
+      &ledger_tail_map,
+      |b, ledger_tail_map| b.iter(|| produce_hash_of_state(ledger_tail_map)),
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, bench_hash_algorithms, bench_hash_of_state);
+criterion_main!(benches);
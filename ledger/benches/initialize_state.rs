@@ -0,0 +1,124 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ledger::{endorser_proto::InitializeStateReq, NimbleDigest};
+use prost::{bytes::Bytes, Message};
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+// counts the allocations, to compare the requests with byte fields in `Vec`s with the ones with
+// byte fields in `Bytes`
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// the messages as they were generated before their byte fields became `Bytes`
+#[derive(Clone, PartialEq, Message)]
+struct VecLedgerTailMapEntry {
+  #[prost(bytes = "vec", tag = "1")]
+  handle: Vec<u8>,
+  #[prost(uint64, tag = "2")]
+  height: u64,
+  #[prost(bytes = "vec", tag = "3")]
+  metablock: Vec<u8>,
+  #[prost(bytes = "vec", tag = "4")]
+  block: Vec<u8>,
+  #[prost(bytes = "vec", tag = "5")]
+  nonces: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct VecInitializeStateReq {
+  #[prost(bytes = "vec", tag = "1")]
+  group_identity: Vec<u8>,
+  #[prost(message, repeated, tag = "2")]
+  ledger_tail_map: Vec<VecLedgerTailMapEntry>,
+  #[prost(bytes = "vec", tag = "3")]
+  view_tail_metablock: Vec<u8>,
+  #[prost(bytes = "vec", tag = "4")]
+  block_hash: Vec<u8>,
+  #[prost(uint64, tag = "5")]
+  expected_height: u64,
+  #[prost(bytes = "vec", tag = "6")]
+  public_key: Vec<u8>,
+}
+
+const NUM_LEDGERS: usize = 1024 * 1024;
+
+fn requests() -> (VecInitializeStateReq, InitializeStateReq) {
+  let entries = (0..NUM_LEDGERS)
+    .map(|i| VecLedgerTailMapEntry {
+      handle: NimbleDigest::digest(&i.to_le_bytes()).to_bytes(),
+      height: 1,
+      metablock: vec![1u8; 72],
+      block: vec![2u8; 64],
+      nonces: vec![3u8; 16],
+    })
+    .collect::<Vec<_>>();
+  let vec_request = VecInitializeStateReq {
+    group_identity: vec![4u8; 32],
+    ledger_tail_map: entries,
+    view_tail_metablock: vec![5u8; 72],
+    block_hash: vec![6u8; 32],
+    expected_height: 2,
+    public_key: vec![7u8; 33],
+  };
+  let bytes_request = InitializeStateReq::decode(vec_request.encode_to_vec().as_slice()).unwrap();
+  (vec_request, bytes_request)
+}
+
+// what the coordinator and an endorser do with the request: the coordinator clones the shared
+// ledger tail map into a request for every endorser and encodes it, and the endorser decodes it
+fn send<M: Message + Clone + Default>(request: &M) -> M {
+  let encoded = Bytes::from(request.clone().encode_to_vec());
+  M::decode(encoded).unwrap()
+}
+
+fn count_allocations<M: Message + Clone + Default>(request: &M) -> usize {
+  let before = ALLOCATIONS.load(Ordering::Relaxed);
+  let received = send(request);
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+  drop(received);
+  allocations
+}
+
+fn bench_initialize_state(c: &mut Criterion) {
+  let (vec_request, bytes_request) = requests();
+  println!(
+    "initialize_state with {} ledgers: {} allocations with Vec<u8>, {} with Bytes",
+    NUM_LEDGERS,
+    count_allocations(&vec_request),
+    count_allocations(&bytes_request)
+  );
+
+  let mut group = c.benchmark_group("initialize_state");
+  group.sample_size(10);
+  group.bench_with_input(
+    BenchmarkId::new("vec", NUM_LEDGERS),
+    &vec_request,
+    |b, request| b.iter(|| send(request)),
+  );
+  group.bench_with_input(
+    BenchmarkId::new("bytes", NUM_LEDGERS),
+    &bytes_request,
+    |b, request| b.iter(|| send(request)),
+  );
+  group.finish();
+}
+
+criterion_group!(benches, bench_initialize_state);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ledger::{
+  signature::{PrivateKey, PrivateKeyTrait},
+  IdSig, NimbleDigest,
+};
+
+fn bench_verify_receipt_signatures(c: &mut Criterion) {
+  let message = NimbleDigest::digest(b"message").to_bytes();
+  let mut group = c.benchmark_group("verify_receipt_signatures");
+  for num_sigs in [3usize, 7, 15] {
+    let id_sigs = (0..num_sigs)
+      .map(|_| {
+        let sk = PrivateKey::new();
+        IdSig::new(sk.get_public_key().unwrap(), sk.sign(&message).unwrap())
+      })
+      .collect::<Vec<IdSig>>();
+
+    group.bench_with_input(
+      BenchmarkId::new("individual", num_sigs),
+      &id_sigs,
+      |b, id_sigs| {
+        b.iter(|| {
+          for id_sig in id_sigs {
+            id_sig.verify(&message).unwrap();
+          }
+        })
+      },
+    );
+    group.bench_with_input(
+      BenchmarkId::new("batch", num_sigs),
+      &id_sigs,
+      |b, id_sigs| b.iter(|| IdSig::verify_batch(id_sigs, &message).unwrap()),
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, bench_verify_receipt_signatures);
+criterion_main!(benches);
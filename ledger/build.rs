@@ -1,4 +1,13 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-  tonic_build::compile_protos("../proto/endorser.proto")?;
+  // without the `grpc` feature, only the messages are generated, so tonic is not needed
+  let grpc = std::env::var_os("CARGO_FEATURE_GRPC").is_some();
+  // the byte fields are `Bytes`, so that the requests to the endorsers share their blocks and
+  // ledger tail maps with each other and with the wire rather than copying them
+  let mut config = prost_build::Config::new();
+  config.bytes(["."]);
+  tonic_build::configure()
+    .build_client(grpc)
+    .build_server(grpc)
+    .compile_with_config(config, &["../proto/endorser.proto"], &["../proto"])?;
   Ok(())
 }
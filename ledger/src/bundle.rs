@@ -0,0 +1,430 @@
+//! The export bundle of a ledger, a single file from which an auditor checks the ledger offline:
+//! every entry of the ledger with its metablock, the receipts of its attested tail, and the entries
+//! of the view ledger that lead from the group identity to the endorsers of those receipts.
+//!
+//! A bundle is `magic || u32 LE version || u32 LE hash algorithm`, followed by records, each
+//! `u8 kind || u32 LE len || payload || checksum`, where the checksum is the digest of the kind,
+//! the length and the payload. Integers in a payload are u64 LE and byte strings are prefixed with
+//! their u32 LE length. The records are a `Ledger`, the `Entry` of every height from genesis on, a
+//! `Tail`, the `View` of every index from 1 on, and an `End` with the number of records before it,
+//! so a truncated bundle is told apart from a complete one.
+use crate::{hash::HASH_ALGORITHM, NimbleDigest};
+use std::{convert::TryInto, fmt};
+
+pub const BUNDLE_MAGIC: &[u8] = b"NimbleBundle";
+pub const BUNDLE_VERSION: u32 = 1;
+/// the size of the kind and the length that precede the payload of a record
+const RECORD_HEADER_SIZE: usize = 5;
+
+const KIND_LEDGER: u8 = 1;
+const KIND_ENTRY: u8 = 2;
+const KIND_TAIL: u8 = 3;
+const KIND_VIEW: u8 = 4;
+const KIND_END: u8 = 5;
+
+/// a record of a bundle
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BundleRecord {
+  /// the ledger that the bundle exports, and the height of its attested tail
+  Ledger { handle: Vec<u8>, height: u64 },
+  /// the entry of the ledger at `height`, with its metablock
+  Entry {
+    height: u64,
+    block: Vec<u8>,
+    nonces: Vec<u8>,
+    metablock: Vec<u8>,
+  },
+  /// the receipts of the attested tail, at `height`
+  Tail { height: u64, receipts: Vec<u8> },
+  /// the entry of the view ledger at `index`
+  View {
+    index: u64,
+    block: Vec<u8>,
+    receipts: Vec<u8>,
+  },
+  /// the number of records before it
+  End { num_records: u64 },
+}
+
+/// returned if a bundle is not well formed; `offset` is that of the record at fault, or of the end
+/// of the bundle if it is truncated
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BundleError {
+  pub offset: usize,
+  pub reason: &'static str,
+}
+
+impl fmt::Display for BundleError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "at offset {}: {}", self.offset, self.reason)
+  }
+}
+
+impl std::error::Error for BundleError {}
+
+/// the header of a bundle with the hash algorithm of this build
+pub fn bundle_header() -> Vec<u8> {
+  let mut bytes = BUNDLE_MAGIC.to_vec();
+  bytes.extend_from_slice(&BUNDLE_VERSION.to_le_bytes());
+  bytes.extend_from_slice(&HASH_ALGORITHM.id().to_le_bytes());
+  bytes
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+  bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+  bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+  bytes.extend_from_slice(value);
+}
+
+fn checksum(kind: u8, len: &[u8], payload: &[u8]) -> NimbleDigest {
+  NimbleDigest::digest_parts(&[&[kind], len, payload])
+}
+
+/// the fields of a payload, read in order
+struct Fields<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+  fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+    if self.bytes.len() < len {
+      return None;
+    }
+    let (value, rest) = self.bytes.split_at(len);
+    self.bytes = rest;
+    Some(value)
+  }
+
+  fn u64(&mut self) -> Option<u64> {
+    Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+  }
+
+  fn bytes(&mut self) -> Option<Vec<u8>> {
+    let len = u32::from_le_bytes(self.take(4)?.try_into().ok()?);
+    Some(self.take(len as usize)?.to_vec())
+  }
+
+  /// checks that no bytes follow the last field
+  fn end(self) -> Option<()> {
+    if self.bytes.is_empty() {
+      Some(())
+    } else {
+      None
+    }
+  }
+}
+
+impl BundleRecord {
+  fn kind(&self) -> u8 {
+    match self {
+      BundleRecord::Ledger { .. } => KIND_LEDGER,
+      BundleRecord::Entry { .. } => KIND_ENTRY,
+      BundleRecord::Tail { .. } => KIND_TAIL,
+      BundleRecord::View { .. } => KIND_VIEW,
+      BundleRecord::End { .. } => KIND_END,
+    }
+  }
+
+  fn payload(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match self {
+      BundleRecord::Ledger { handle, height } => {
+        put_bytes(&mut bytes, handle);
+        put_u64(&mut bytes, *height);
+      },
+      BundleRecord::Entry {
+        height,
+        block,
+        nonces,
+        metablock,
+      } => {
+        put_u64(&mut bytes, *height);
+        put_bytes(&mut bytes, block);
+        put_bytes(&mut bytes, nonces);
+        put_bytes(&mut bytes, metablock);
+      },
+      BundleRecord::Tail { height, receipts } => {
+        put_u64(&mut bytes, *height);
+        put_bytes(&mut bytes, receipts);
+      },
+      BundleRecord::View {
+        index,
+        block,
+        receipts,
+      } => {
+        put_u64(&mut bytes, *index);
+        put_bytes(&mut bytes, block);
+        put_bytes(&mut bytes, receipts);
+      },
+      BundleRecord::End { num_records } => put_u64(&mut bytes, *num_records),
+    }
+    bytes
+  }
+
+  fn from_payload(kind: u8, payload: &[u8]) -> Option<BundleRecord> {
+    let mut fields = Fields { bytes: payload };
+    let record = match kind {
+      KIND_LEDGER => BundleRecord::Ledger {
+        handle: fields.bytes()?,
+        height: fields.u64()?,
+      },
+      KIND_ENTRY => BundleRecord::Entry {
+        height: fields.u64()?,
+        block: fields.bytes()?,
+        nonces: fields.bytes()?,
+        metablock: fields.bytes()?,
+      },
+      KIND_TAIL => BundleRecord::Tail {
+        height: fields.u64()?,
+        receipts: fields.bytes()?,
+      },
+      KIND_VIEW => BundleRecord::View {
+        index: fields.u64()?,
+        block: fields.bytes()?,
+        receipts: fields.bytes()?,
+      },
+      KIND_END => BundleRecord::End {
+        num_records: fields.u64()?,
+      },
+      _ => return None,
+    };
+    fields.end()?;
+    Some(record)
+  }
+
+  /// the record as it is written to a bundle, with its checksum
+  pub fn encode(&self) -> Vec<u8> {
+    let kind = self.kind();
+    let payload = self.payload();
+    let len = (payload.len() as u32).to_le_bytes();
+    let mut bytes =
+      Vec::with_capacity(RECORD_HEADER_SIZE + payload.len() + NimbleDigest::num_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(&len);
+    bytes.extend_from_slice(&payload);
+    bytes.extend_from_slice(&checksum(kind, &len, &payload).to_bytes());
+    bytes
+  }
+}
+
+/// reads the records of a bundle in order, each with its offset. It stops after the first error,
+/// and reports a bundle that ends before its `End` record, or has bytes after it
+pub struct BundleReader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+  num_records: u64,
+  done: bool,
+}
+
+impl<'a> BundleReader<'a> {
+  /// checks the header of `bytes`, which must be of a bundle of this version and hash algorithm
+  pub fn new(bytes: &'a [u8]) -> Result<Self, BundleError> {
+    let header_size = BUNDLE_MAGIC.len() + 8;
+    let fail = |reason| Err(BundleError { offset: 0, reason });
+    if bytes.len() < header_size || !bytes.starts_with(BUNDLE_MAGIC) {
+      return fail("not a bundle");
+    }
+    let field = |i: usize| {
+      let start = BUNDLE_MAGIC.len() + 4 * i;
+      u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap())
+    };
+    if field(0) != BUNDLE_VERSION {
+      return fail("unsupported bundle version");
+    }
+    if field(1) != HASH_ALGORITHM.id() {
+      return fail("the bundle uses another hash algorithm");
+    }
+    Ok(BundleReader {
+      bytes,
+      pos: header_size,
+      num_records: 0,
+      done: false,
+    })
+  }
+
+  fn read_record(&mut self) -> Result<(usize, BundleRecord), BundleError> {
+    let offset = self.pos;
+    let fail = |reason| Err(BundleError { offset, reason });
+    let rest = &self.bytes[offset..];
+    if rest.is_empty() {
+      return fail("the bundle ends before its end record");
+    }
+    if rest.len() < RECORD_HEADER_SIZE {
+      return fail("truncated record");
+    }
+    let kind = rest[0];
+    let len = &rest[1..RECORD_HEADER_SIZE];
+    let payload_len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    let checksum_size = NimbleDigest::num_bytes();
+    if rest.len() - RECORD_HEADER_SIZE < payload_len.saturating_add(checksum_size) {
+      return fail("truncated record");
+    }
+    let payload = &rest[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + payload_len];
+    let stored = &rest[RECORD_HEADER_SIZE + payload_len..][..checksum_size];
+    if checksum(kind, len, payload).to_bytes() != stored {
+      return fail("checksum mismatch");
+    }
+    let record = match BundleRecord::from_payload(kind, payload) {
+      Some(record) => record,
+      None => return fail("malformed record"),
+    };
+    self.pos += RECORD_HEADER_SIZE + payload_len + checksum_size;
+
+    if let BundleRecord::End { num_records } = record {
+      self.done = true;
+      if num_records != self.num_records {
+        return fail("the end record does not match the number of records");
+      }
+      if self.pos != self.bytes.len() {
+        return Err(BundleError {
+          offset: self.pos,
+          reason: "bytes after the end record",
+        });
+      }
+    }
+    self.num_records += 1;
+    Ok((offset, record))
+  }
+}
+
+impl<'a> Iterator for BundleReader<'a> {
+  type Item = Result<(usize, BundleRecord), BundleError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let res = self.read_record();
+    if res.is_err() {
+      self.done = true;
+    }
+    Some(res)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn records() -> Vec<BundleRecord> {
+    vec![
+      BundleRecord::Ledger {
+        handle: b"handle".to_vec(),
+        height: 1,
+      },
+      BundleRecord::Entry {
+        height: 0,
+        block: b"genesis".to_vec(),
+        nonces: Vec::new(),
+        metablock: vec![1; 72],
+      },
+      BundleRecord::Entry {
+        height: 1,
+        block: Vec::new(),
+        nonces: vec![2; 16],
+        metablock: vec![3; 72],
+      },
+      BundleRecord::Tail {
+        height: 1,
+        receipts: vec![4; 10],
+      },
+      BundleRecord::View {
+        index: 1,
+        block: b"view".to_vec(),
+        receipts: vec![5; 10],
+      },
+      BundleRecord::End { num_records: 5 },
+    ]
+  }
+
+  fn encode(records: &[BundleRecord]) -> Vec<u8> {
+    let mut bytes = bundle_header();
+    for record in records {
+      bytes.extend(record.encode());
+    }
+    bytes
+  }
+
+  fn read(bytes: &[u8]) -> Result<Vec<(usize, BundleRecord)>, BundleError> {
+    BundleReader::new(bytes)?.collect()
+  }
+
+  #[test]
+  pub fn test_bundle_records() {
+    let records = records();
+    let bytes = encode(&records);
+    let read_records = read(&bytes).unwrap();
+    assert_eq!(
+      read_records
+        .iter()
+        .map(|(_offset, record)| record.clone())
+        .collect::<Vec<_>>(),
+      records
+    );
+    assert_eq!(read_records[0].0, bundle_header().len());
+    assert_eq!(
+      read_records[1].0,
+      bundle_header().len() + records[0].encode().len()
+    );
+
+    // a flipped byte fails the checksum of its record, wherever it is in the record
+    let entry_offset = read_records[2].0;
+    let entry_len = records[2].encode().len();
+    for pos in entry_offset..entry_offset + entry_len {
+      let mut corrupt = bytes.clone();
+      corrupt[pos] ^= 1;
+      let err = read(&corrupt).unwrap_err();
+      assert_eq!(err.offset, entry_offset, "byte {}", pos);
+    }
+    let mut corrupt = bytes.clone();
+    corrupt[entry_offset + RECORD_HEADER_SIZE + 8] ^= 1;
+    assert_eq!(
+      read(&corrupt),
+      Err(BundleError {
+        offset: entry_offset,
+        reason: "checksum mismatch"
+      })
+    );
+
+    // the header, the end and the length of the bundle are checked
+    let mut corrupt = bytes.clone();
+    corrupt[0] ^= 1;
+    assert_eq!(read(&corrupt).unwrap_err().reason, "not a bundle");
+    let mut corrupt = bytes.clone();
+    corrupt[BUNDLE_MAGIC.len()] = 2;
+    assert_eq!(
+      read(&corrupt).unwrap_err().reason,
+      "unsupported bundle version"
+    );
+    let end_offset = read_records[5].0;
+    assert_eq!(
+      read(&bytes[..end_offset]),
+      Err(BundleError {
+        offset: end_offset,
+        reason: "the bundle ends before its end record"
+      })
+    );
+    assert_eq!(
+      read(&bytes[..bytes.len() - 1]).unwrap_err().offset,
+      end_offset
+    );
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(
+      read(&longer),
+      Err(BundleError {
+        offset: bytes.len(),
+        reason: "bytes after the end record"
+      })
+    );
+    let mut dropped = records;
+    dropped.remove(4);
+    assert_eq!(
+      read(&encode(&dropped)).unwrap_err().reason,
+      "the end record does not match the number of records"
+    );
+  }
+}
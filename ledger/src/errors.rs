@@ -1,3 +1,6 @@
+use crate::{signature::CryptoError, CustomSerdeError};
+use std::fmt;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum VerificationError {
   /// returned if the supplied genesis block is not well formed
@@ -56,4 +59,118 @@ pub enum VerificationError {
   InsufficentEndorsers,
   /// returned if the ledger tail maps are inconsistent
   InconsistentLedgerTailMaps,
+  /// returned if a public key signed the same metablock with different signatures
+  ConflictingReceipts,
+  /// returned if a sequence of entries does not form a hash chain; carries the first height
+  /// at which the chain breaks
+  BrokenChain(usize),
+  /// returned if a view change rotates the key of an endorser without a valid handover
+  InvalidKeyRotation,
+  /// returned if the endorsers of a view do not all sign with the scheme that the view records
+  MixedSignatureSchemes,
+  /// returned if a view signs with a scheme that the ledger was built without, or if a receipt
+  /// needs aggregation from a scheme whose signatures do not aggregate
+  UnsupportedSignatureScheme,
+  /// returned if a view block records another hash algorithm than the one the ledger was built
+  /// with
+  HashAlgorithmMismatch,
+}
+
+impl fmt::Display for VerificationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let msg = match self {
+      VerificationError::InvalidGenesisBlock => "genesis block is not well formed",
+      VerificationError::InvalidEndorserAttestation => "endorser's attestation is invalid",
+      VerificationError::IncorrectLength => "byte array has an incorrect length",
+      VerificationError::InvalidReceipt => "receipt is invalid",
+      VerificationError::InvalidSignature => "signature is invalid",
+      VerificationError::IndexOutofBounds => "index is out of bounds",
+      VerificationError::DuplicateIds => "identities are not unique",
+      VerificationError::InvalidView => "view is not well formed",
+      VerificationError::InsufficientReceipts => "insufficient receipts",
+      VerificationError::InvalidViewChangeReceipt => "view change receipt is invalid",
+      VerificationError::ViewNotFound => "view is not in the verifier's state",
+      VerificationError::ViewInMetaBlockNotLatest => "view in the metablock is not the latest",
+      VerificationError::InvalidPublicKey => "public key is not found in the receipt",
+      VerificationError::InvalidBlockHash => "block hash does not match the block",
+      VerificationError::InvalidHeight => "height does not match the expected height",
+      VerificationError::InvalidHandle => "handle is invalid",
+      VerificationError::InvalidNonces => "nonces are invalid",
+      VerificationError::InvalidNonce => "nonce is invalid",
+      VerificationError::InvalidNoncesHash => "hash of nonces is invalid",
+      VerificationError::InvalidGroupIdentity => "group identity does not match the config",
+      VerificationError::InvalidMetaBlock => "metablock does not match",
+      VerificationError::InvalidMaxCut => "max cut is incorrect",
+      VerificationError::InvalidLedgerTailMap => "ledger tail map is incorrect",
+      VerificationError::MissingLedgerTailMap => "ledger tail map is missing",
+      VerificationError::RedundantLedgerTailMap => "redundant ledger tail map",
+      VerificationError::InvalidConfig => "config is invalid",
+      VerificationError::InsufficentEndorsers => "too few endorsers",
+      VerificationError::InconsistentLedgerTailMaps => "ledger tail maps are inconsistent",
+      VerificationError::ConflictingReceipts => {
+        "a public key signed the same metablock with different signatures"
+      },
+      VerificationError::BrokenChain(height) => {
+        return write!(f, "hash chain breaks at height {}", height);
+      },
+      VerificationError::InvalidKeyRotation => "key rotation is invalid",
+      VerificationError::MixedSignatureSchemes => {
+        "endorsers of a view sign with different signature schemes"
+      },
+      VerificationError::UnsupportedSignatureScheme => "signature scheme is not supported",
+      VerificationError::HashAlgorithmMismatch => "view was created with another hash algorithm",
+    };
+    write!(f, "{}", msg)
+  }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// an error from any of the ledger's building blocks, so that callers can propagate them with `?`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LedgerError {
+  /// returned if verifying a receipt, a view change or a chain fails
+  Verification(VerificationError),
+  /// returned if (de)serializing a ledger type fails
+  Serde(CustomSerdeError),
+  /// returned if a key or a signature operation fails
+  Crypto(CryptoError),
+}
+
+impl fmt::Display for LedgerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      LedgerError::Verification(e) => write!(f, "verification failed: {}", e),
+      LedgerError::Serde(e) => write!(f, "serialization failed: {}", e),
+      LedgerError::Crypto(e) => write!(f, "crypto operation failed: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for LedgerError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      LedgerError::Verification(e) => Some(e),
+      LedgerError::Serde(e) => Some(e),
+      LedgerError::Crypto(e) => Some(e),
+    }
+  }
+}
+
+impl From<VerificationError> for LedgerError {
+  fn from(e: VerificationError) -> Self {
+    LedgerError::Verification(e)
+  }
+}
+
+impl From<CustomSerdeError> for LedgerError {
+  fn from(e: CustomSerdeError) -> Self {
+    LedgerError::Serde(e)
+  }
+}
+
+impl From<CryptoError> for LedgerError {
+  fn from(e: CryptoError) -> Self {
+    LedgerError::Crypto(e)
+  }
 }
@@ -0,0 +1,194 @@
+//! The checks that the targets of `fuzz/` run on arbitrary bytes, for the parsers that read what
+//! an attacker controls: they return an error rather than panic, and what they accept encodes back
+//! to what they read. The tests run the checks on the mutations of valid inputs and on the corpus
+//! in `fuzz/corpus`, so that a regression shows without a fuzzer.
+use crate::{
+  bundle::BundleReader,
+  compute_view_block_hash, decode_view_config,
+  signature::{PublicKey, PublicKeyTrait, Signature, SignatureTrait},
+  CustomSerde, Receipts,
+};
+use std::path::Path;
+
+/// parses `data` as receipts, and checks that the receipts it accepts encode canonically
+pub fn check_receipts(data: &[u8]) {
+  if let Ok(receipts) = Receipts::try_from_bytes(data) {
+    let bytes = receipts.to_bytes();
+    let again = Receipts::try_from_bytes(&bytes).expect("the encoding of receipts reads back");
+    assert_eq!(again.to_bytes(), bytes);
+  }
+}
+
+/// reads `data` as an export bundle, and checks that every record it accepts encodes to the bytes
+/// it was read from
+pub fn check_bundle(data: &[u8]) {
+  if let Ok(reader) = BundleReader::new(data) {
+    for (offset, record) in reader.flatten() {
+      let encoded = record.encode();
+      assert_eq!(&data[offset..offset + encoded.len()], &encoded[..]);
+    }
+  }
+}
+
+/// parses `data` as a public key, a signature and the message it signs, and verifies it
+pub fn check_signature(data: &[u8]) {
+  let (pk, rest) = data.split_at(data.len().min(PublicKey::num_bytes()));
+  let (sig, msg) = rest.split_at(rest.len().min(Signature::num_bytes()));
+  if let (Ok(pk), Ok(sig)) = (PublicKey::from_bytes(pk), Signature::from_bytes(sig)) {
+    let _ = sig.verify(&pk, msg);
+  }
+}
+
+/// parses `data` as the configuration of a view: its endorsers and their key rotations
+pub fn check_view_config(data: &[u8]) {
+  let _ = decode_view_config(data);
+  let _ = compute_view_block_hash(data);
+}
+
+/// the inputs of a bounded run from `seed`: the seed, each of its prefixes, and, at each
+/// position, the byte flipped, the byte set to 0xff, and four bytes of 0xff, which is how a length
+/// prefix overflows
+pub fn mutations(seed: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+  let prefixes = (0..seed.len()).map(move |len| seed[..len].to_vec());
+  let positions = (0..seed.len()).flat_map(move |pos| {
+    let mut flipped = seed.to_vec();
+    flipped[pos] ^= 1;
+    let mut max = seed.to_vec();
+    max[pos] = 0xff;
+    let mut overflow = seed.to_vec();
+    let end = (pos + 4).min(seed.len());
+    overflow[pos..end].iter_mut().for_each(|b| *b = 0xff);
+    vec![flipped, max, overflow]
+  });
+  std::iter::once(seed.to_vec())
+    .chain(prefixes)
+    .chain(positions)
+}
+
+/// the inputs in `fuzz/corpus/<target>`, where the crashes that fuzzing found are kept once they
+/// are fixed
+pub fn corpus(target: &str) -> Vec<Vec<u8>> {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("../fuzz/corpus")
+    .join(target);
+  match std::fs::read_dir(dir) {
+    Ok(entries) => entries
+      .map(|entry| std::fs::read(entry.unwrap().path()).unwrap())
+      .collect(),
+    Err(_) => Vec::new(),
+  }
+}
+
+/// runs `check` on the mutations of `seeds` and of the corpus of `target`
+pub fn run_bounded(target: &str, check: fn(&[u8]), seeds: &[Vec<u8>]) {
+  for seed in seeds.iter().chain(corpus(target).iter()) {
+    for input in mutations(seed) {
+      check(&input);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    bundle::{bundle_header, BundleRecord},
+    compute_key_rotation_message, encode_view_config,
+    signature::{PrivateKey, PrivateKeyTrait},
+    EndorserHostnames, IdSig, KeyRotation, MetaBlock, NimbleDigest, NimbleHashTrait, Receipt,
+  };
+
+  fn keys() -> Vec<PrivateKey> {
+    (0..2).map(|_| PrivateKey::new()).collect()
+  }
+
+  #[test]
+  pub fn test_mutations() {
+    let seed = [1u8, 2, 3, 4, 5];
+    let inputs = mutations(&seed).collect::<Vec<_>>();
+    assert_eq!(inputs.len(), 1 + 5 + 3 * 5);
+    assert_eq!(inputs[0], seed);
+    assert!(inputs.contains(&vec![1, 0xff, 0xff, 0xff, 0xff]));
+    assert!(inputs.contains(&vec![1, 2, 3, 4, 0xff]));
+  }
+
+  #[test]
+  pub fn test_fuzz_receipts() {
+    let keys = keys();
+    let mut receipts = Receipts::new();
+    for view in [b"first view", b"other view"] {
+      let view = NimbleDigest::digest(view);
+      let metablock = MetaBlock::genesis(&NimbleDigest::digest(b"block"));
+      for key in &keys {
+        let id_sig = IdSig::new(
+          key.get_public_key().unwrap(),
+          key.sign(&metablock.hash().to_bytes()).unwrap(),
+        );
+        receipts.add(&Receipt::new(view, metablock.clone(), id_sig));
+      }
+    }
+    run_bounded("receipts", check_receipts, &[receipts.to_bytes()]);
+  }
+
+  #[test]
+  pub fn test_fuzz_bundle() {
+    let records = vec![
+      BundleRecord::Ledger {
+        handle: b"ledger".to_vec(),
+        height: 0,
+      },
+      BundleRecord::Entry {
+        height: 0,
+        block: b"genesis".to_vec(),
+        nonces: vec![7; 16],
+        metablock: MetaBlock::default().to_bytes(),
+      },
+      BundleRecord::Tail {
+        height: 0,
+        receipts: Receipts::new().to_bytes(),
+      },
+      BundleRecord::End { num_records: 3 },
+    ];
+    let mut bundle = bundle_header();
+    for record in &records {
+      bundle.extend(record.encode());
+    }
+    run_bounded("bundle", check_bundle, &[bundle]);
+  }
+
+  #[test]
+  pub fn test_fuzz_signature() {
+    let key = PrivateKey::new();
+    let mut input = key.get_public_key().unwrap().to_bytes();
+    input.extend(key.sign(b"message").unwrap().to_bytes());
+    input.extend(b"message");
+    run_bounded("signature", check_signature, &[input]);
+  }
+
+  #[test]
+  pub fn test_fuzz_view_config() {
+    let keys = keys();
+    let endorsers = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let pk = key.get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    let (old, new) = (keys[0].get_public_key().unwrap(), PrivateKey::new());
+    let new = new.get_public_key().unwrap();
+    let message = compute_key_rotation_message(
+      &NimbleDigest::digest(b"group"),
+      &old.to_bytes(),
+      &new.to_bytes(),
+    );
+    let signature = keys[0].sign(&message.to_bytes()).unwrap();
+    let rotation = KeyRotation::new(&old, &new, &signature);
+    run_bounded(
+      "view_config",
+      check_view_config,
+      &[encode_view_config(&endorsers, None, &[rotation])],
+    );
+  }
+}
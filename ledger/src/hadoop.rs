@@ -0,0 +1,150 @@
+//! The handles of the ledgers of the HDFS integration. A ledger is named by the cluster ID and the
+//! namespace ID of HDFS and the kind of what it records, so that the NameNode, the JournalNodes and
+//! auditors derive the same handle from what they know without asking anyone.
+//!
+//! The identifiers are encoded as
+//! `tag || u32 LE len || cluster_id || u32 LE len || namespace_id || u32 LE len || kind`, where
+//! `kind` is the name of the `LedgerKind`, so that no two triples share an encoding, and the
+//! handle is `Handle::derive` of the encoding with the all-zero nonce. The mapping is fixed: the
+//! golden vectors of the tests must never change, or the ledgers that exist are lost.
+use crate::{Handle, Nonce};
+
+/// domain separation tag for the encoding of HDFS identifiers, which versions the mapping
+const HADOOP_HANDLE_DOMAIN_TAG: &[u8] = b"NimbleHadoopHandle/v1";
+
+/// the kinds of ledgers that the HDFS integration keeps per namespace
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LedgerKind {
+  /// the fsimage checkpoints
+  Fsimage,
+  /// the finalized edit-log segments
+  Edits,
+  /// the heartbeats of the coordinator
+  Liveness,
+  /// the block reports of the DataNodes
+  BlockReport,
+}
+
+impl LedgerKind {
+  pub const ALL: [LedgerKind; 4] = [
+    LedgerKind::Fsimage,
+    LedgerKind::Edits,
+    LedgerKind::Liveness,
+    LedgerKind::BlockReport,
+  ];
+
+  /// the name of the kind, which is part of the encoding of the handle
+  pub fn name(&self) -> &'static str {
+    match self {
+      LedgerKind::Fsimage => "fsimage",
+      LedgerKind::Edits => "edits",
+      LedgerKind::Liveness => "liveness",
+      LedgerKind::BlockReport => "block-report",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<LedgerKind> {
+    LedgerKind::ALL
+      .iter()
+      .copied()
+      .find(|kind| kind.name() == name)
+  }
+}
+
+/// encodes the identifiers of a ledger, each prefixed with its length
+fn encode_identifiers(cluster_id: &str, namespace_id: &str, kind: LedgerKind) -> Vec<u8> {
+  let fields = [
+    cluster_id.as_bytes(),
+    namespace_id.as_bytes(),
+    kind.name().as_bytes(),
+  ];
+  let mut bytes = Vec::with_capacity(
+    HADOOP_HANDLE_DOMAIN_TAG.len() + fields.iter().map(|f| 4 + f.len()).sum::<usize>(),
+  );
+  bytes.extend_from_slice(HADOOP_HANDLE_DOMAIN_TAG);
+  for field in fields.iter() {
+    bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(field);
+  }
+  bytes
+}
+
+/// the handle of the ledger of `kind` of namespace `namespace_id` in cluster `cluster_id`; its
+/// bytes are the handle bytes that clients pass to the coordinator
+pub fn handle_for(cluster_id: &str, namespace_id: &str, kind: LedgerKind) -> Handle {
+  let nonce = Nonce { data: [0u8; 16] };
+  Handle::derive(&encode_identifiers(cluster_id, namespace_id, kind), &nonce)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hash::{HashAlgorithm, HASH_ALGORITHM};
+  use std::collections::HashSet;
+
+  #[test]
+  pub fn test_handle_for() {
+    // golden vectors, which pin the mapping
+    if HASH_ALGORITHM == HashAlgorithm::Sha256 {
+      let vectors = [
+        (
+          "CID-1",
+          "ns-1",
+          LedgerKind::Fsimage,
+          "1ca3defa67db358cb14e03199e4b9b8f75194094e159a7e962677a9ec7d3dd8b",
+        ),
+        (
+          "CID-1",
+          "ns-1",
+          LedgerKind::Edits,
+          "14d5aa3328bf1dbbd036ab7ce7fe98df2b7686c1db3ce4f1b09c1af3ab2f68dd",
+        ),
+        (
+          "CID-1",
+          "ns-1",
+          LedgerKind::Liveness,
+          "05fa3fdf753b8d8be990d923e0db9b03a15e704b2d83ee50fde11d3ce44d62f6",
+        ),
+        (
+          "CID-1",
+          "ns-1",
+          LedgerKind::BlockReport,
+          "4047a71cb52b98d728e0c26afcf490d70511a72c0aec786d09754425248a4423",
+        ),
+        (
+          "",
+          "",
+          LedgerKind::Fsimage,
+          "51bece1d37b0db8d4a76bd55252156edbb0fe66d6068043171703a2966d1f668",
+        ),
+      ];
+      for (cluster_id, namespace_id, kind, handle) in vectors.iter() {
+        assert_eq!(
+          hex::encode(handle_for(cluster_id, namespace_id, *kind).to_bytes()),
+          *handle,
+          "{} {} {}",
+          cluster_id,
+          namespace_id,
+          kind.name()
+        );
+      }
+    }
+
+    // the identifiers are delimited by their lengths, so moving bytes between them changes the
+    // handle, and every kind has a handle of its own
+    let handles = [("ab", "c"), ("a", "bc"), ("abc", ""), ("", "abc")]
+      .iter()
+      .flat_map(|(c, n)| LedgerKind::ALL.iter().map(move |k| handle_for(c, n, *k)))
+      .collect::<HashSet<_>>();
+    assert_eq!(handles.len(), 16);
+    assert_eq!(
+      handle_for("CID-1", "ns-1", LedgerKind::Edits),
+      handle_for("CID-1", "ns-1", LedgerKind::Edits)
+    );
+
+    for kind in LedgerKind::ALL.iter() {
+      assert_eq!(LedgerKind::from_name(kind.name()), Some(*kind));
+    }
+    assert_eq!(LedgerKind::from_name("blockreport"), None);
+  }
+}
@@ -0,0 +1,122 @@
+use sha2::{Digest, Sha256};
+
+/// Identifies the hash function used to compute every `NimbleDigest` in a deployment
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HashAlgorithm {
+  Sha256 = 1,
+  Blake3 = 2,
+}
+
+impl HashAlgorithm {
+  pub fn id(&self) -> u32 {
+    *self as u32
+  }
+
+  pub fn from_id(id: u32) -> Option<HashAlgorithm> {
+    match id {
+      1 => Some(HashAlgorithm::Sha256),
+      2 => Some(HashAlgorithm::Blake3),
+      _ => None,
+    }
+  }
+}
+
+/// An incremental hash function that produces 32-byte digests
+pub trait NimbleHasher
+where
+  Self: Sized,
+{
+  const ALGORITHM: HashAlgorithm;
+  fn new() -> Self;
+  fn update(&mut self, bytes: &[u8]);
+  fn finalize(self) -> [u8; 32];
+
+  fn digest(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Self::new();
+    hasher.update(bytes);
+    hasher.finalize()
+  }
+}
+
+pub struct Sha256Hasher {
+  hasher: Sha256,
+}
+
+impl NimbleHasher for Sha256Hasher {
+  const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+
+  fn new() -> Self {
+    Sha256Hasher {
+      hasher: Sha256::new(),
+    }
+  }
+
+  fn update(&mut self, bytes: &[u8]) {
+    self.hasher.update(bytes);
+  }
+
+  fn finalize(self) -> [u8; 32] {
+    self.hasher.finalize().into()
+  }
+}
+
+#[cfg(feature = "blake3")]
+pub struct Blake3Hasher {
+  hasher: blake3::Hasher,
+}
+
+#[cfg(feature = "blake3")]
+impl NimbleHasher for Blake3Hasher {
+  const ALGORITHM: HashAlgorithm = HashAlgorithm::Blake3;
+
+  fn new() -> Self {
+    Blake3Hasher {
+      hasher: blake3::Hasher::new(),
+    }
+  }
+
+  fn update(&mut self, bytes: &[u8]) {
+    self.hasher.update(bytes);
+  }
+
+  fn finalize(self) -> [u8; 32] {
+    *self.hasher.finalize().as_bytes()
+  }
+}
+
+// The algorithm is fixed at compile time so that a deployment built from this workspace
+// (cargo unifies features across it) cannot mix algorithms between its components.
+#[cfg(not(feature = "blake3"))]
+pub type DefaultHasher = Sha256Hasher;
+#[cfg(feature = "blake3")]
+pub type DefaultHasher = Blake3Hasher;
+
+/// The hash function used by this build
+pub const HASH_ALGORITHM: HashAlgorithm = <DefaultHasher as NimbleHasher>::ALGORITHM;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_incremental_matches_one_shot() {
+    let mut hasher = DefaultHasher::new();
+    hasher.update(b"hello ");
+    hasher.update(b"world");
+    assert_eq!(hasher.finalize(), DefaultHasher::digest(b"hello world"));
+  }
+
+  #[test]
+  fn test_algorithm_ids() {
+    for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+      assert_eq!(HashAlgorithm::from_id(algorithm.id()), Some(algorithm));
+    }
+    assert_eq!(HashAlgorithm::from_id(0), None);
+  }
+
+  #[cfg(feature = "blake3")]
+  #[test]
+  fn test_algorithms_differ() {
+    assert_ne!(Sha256Hasher::digest(b"1"), Blake3Hasher::digest(b"1"));
+  }
+}
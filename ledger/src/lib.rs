@@ -1,37 +1,74 @@
+pub mod bundle;
 pub mod errors;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
+pub mod hadoop;
+pub mod hash;
+pub mod secret;
+#[cfg(feature = "serde")]
+mod serde_impls;
 pub mod signature;
-use crate::signature::{PublicKey, PublicKeyTrait, Signature, SignatureTrait};
-use digest::Output;
-use errors::VerificationError;
+use crate::{
+  hash::{DefaultHasher, HashAlgorithm, NimbleHasher, HASH_ALGORITHM},
+  signature::{
+    CryptoError, PublicKey, PublicKeyTrait, Signature, SignatureScheme, SignatureSchemeId,
+    SignatureTrait, P256,
+  },
+};
+use bincode::Options;
+use errors::{LedgerError, VerificationError};
 use generic_array::{typenum::U32, GenericArray};
+use rand::{rngs::OsRng, RngCore};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use sha2::{Digest, Sha256};
 use std::{
   cmp::Ordering,
   collections::{hash_map, HashMap, HashSet},
-  convert::TryInto,
+  convert::{TryFrom, TryInto},
+  fmt,
+  str::FromStr,
 };
+use subtle::ConstantTimeEq;
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 pub mod endorser_proto {
+  #[cfg(feature = "grpc")]
   tonic::include_proto!("endorser_proto");
+  #[cfg(not(feature = "grpc"))]
+  include!(concat!(env!("OUT_DIR"), "/endorser_proto.rs"));
 }
 
 use endorser_proto::{LedgerChunkEntry, LedgerTailMap, LedgerTailMapEntry};
+use prost::bytes::Bytes;
 
 /// A cryptographic digest
-#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Copy, Ord, PartialOrd)]
+#[derive(Clone, Default, Copy, Ord, PartialOrd)]
 pub struct NimbleDigest {
-  digest: Output<Sha256>,
+  digest: GenericArray<u8, U32>,
+}
+
+// Digests are compared in constant time; `Hash` must agree with the manual `PartialEq`.
+impl PartialEq for NimbleDigest {
+  fn eq(&self, other: &Self) -> bool {
+    self.digest.as_slice().ct_eq(other.digest.as_slice()).into()
+  }
+}
+
+impl Eq for NimbleDigest {}
+
+impl std::hash::Hash for NimbleDigest {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.digest.hash(state);
+  }
 }
 
 impl NimbleDigest {
-  pub fn new(d: Output<Sha256>) -> Self {
+  pub fn new(d: GenericArray<u8, U32>) -> Self {
     NimbleDigest { digest: d }
   }
 
   pub fn num_bytes() -> usize {
-    <Sha256 as Digest>::output_size()
+    32
   }
 
   pub fn to_bytes(self) -> Vec<u8> {
@@ -53,24 +90,123 @@ impl NimbleDigest {
       NimbleDigest::default()
     } else {
       NimbleDigest {
-        digest: Sha256::digest(bytes),
+        digest: DefaultHasher::digest(bytes).into(),
       }
     }
   }
 
+  /// computes a hash of the concatenation of `parts` without materializing it
+  pub fn digest_parts(parts: &[&[u8]]) -> Self {
+    let mut builder = DigestBuilder::new();
+    for part in parts {
+      builder.update(part);
+    }
+    builder.finalize()
+  }
+
   /// concatenates `self` and `other` and computes a hash of the two
   pub fn digest_with(&self, other: &NimbleDigest) -> Self {
-    NimbleDigest::digest(&[self.to_bytes(), other.to_bytes()].concat())
+    NimbleDigest::digest_parts(&[self.digest.as_slice(), other.digest.as_slice()])
   }
 
   /// concatenates `self` and `other` bytes and computes a hash of the two
   pub fn digest_with_bytes(&self, other: &[u8]) -> Self {
-    NimbleDigest::digest(&[self.to_bytes(), other.to_vec()].concat())
+    NimbleDigest::digest_parts(&[self.digest.as_slice(), other])
+  }
+}
+
+/// Feeds the components of a message to a streaming hasher, producing the same digest as
+/// `NimbleDigest::digest` on their concatenation
+pub struct DigestBuilder {
+  hasher: DefaultHasher,
+  len: usize,
+}
+
+impl DigestBuilder {
+  pub fn new() -> Self {
+    DigestBuilder {
+      hasher: DefaultHasher::new(),
+      len: 0,
+    }
+  }
+
+  pub fn update(&mut self, bytes: &[u8]) -> &mut Self {
+    self.hasher.update(bytes);
+    self.len += bytes.len();
+    self
+  }
+
+  pub fn finalize(self) -> NimbleDigest {
+    // match `NimbleDigest::digest`, which maps the empty message to the zero digest
+    if self.len == 0 {
+      NimbleDigest::default()
+    } else {
+      NimbleDigest {
+        digest: self.hasher.finalize().into(),
+      }
+    }
+  }
+}
+
+impl Default for DigestBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl fmt::Display for NimbleDigest {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", hex::encode(self.digest.as_slice()))
+  }
+}
+
+impl fmt::Debug for NimbleDigest {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "NimbleDigest({}…)",
+      &hex::encode(self.digest.as_slice())[..DEBUG_HEX_PREFIX_LEN]
+    )
+  }
+}
+
+impl FromStr for NimbleDigest {
+  type Err = CustomSerdeError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    NimbleDigest::from_bytes(&decode_hex(s)?)
   }
 }
 
 pub type Handle = NimbleDigest;
 
+impl Handle {
+  /// derives the handle of a ledger as `H(app_bytes || nonce)`, so the same application object
+  /// and nonce always name the same ledger
+  pub fn derive(app_bytes: &[u8], nonce: &Nonce) -> Handle {
+    NimbleDigest::digest_parts(&[app_bytes, &nonce.data])
+  }
+
+  /// a handle drawn from the operating system's randomness source
+  pub fn random() -> Handle {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    NimbleDigest::from_bytes(&bytes).unwrap()
+  }
+}
+
+/// the number of hex characters shown by the `Debug` output of digests and nonces
+const DEBUG_HEX_PREFIX_LEN: usize = 8;
+
+/// decodes a hex string with an optional `0x` prefix
+fn decode_hex(s: &str) -> Result<Vec<u8>, CustomSerdeError> {
+  let s = s
+    .strip_prefix("0x")
+    .or_else(|| s.strip_prefix("0X"))
+    .unwrap_or(s);
+  hex::decode(s).map_err(|_| CustomSerdeError::InvalidHex)
+}
+
 // this function assumes the provided vector is sorted by handles
 pub fn produce_hash_of_state(ledger_tail_map: &Vec<LedgerTailMapEntry>) -> NimbleDigest {
   // for empty state, hash is a vector of zeros
@@ -78,21 +214,23 @@ pub fn produce_hash_of_state(ledger_tail_map: &Vec<LedgerTailMapEntry>) -> Nimbl
     NimbleDigest::default()
   } else {
     let hash_inner = |ledger_tail_map_slice: &[LedgerTailMapEntry]| -> NimbleDigest {
-      let mut sha256 = Sha256::new();
+      let mut hasher = DefaultHasher::new();
       for entry in ledger_tail_map_slice {
-        sha256.update(&entry.handle);
-        sha256.update(&entry.metablock);
+        hasher.update(&entry.handle);
+        hasher.update(&entry.metablock);
       }
-      NimbleDigest::new(sha256.finalize())
+      NimbleDigest::new(hasher.finalize().into())
     };
 
     let num_leaves = 32;
     // we ceil the slice size so the last slice contains fewer entries.
     let slice_size = (ledger_tail_map.len() as f64 / num_leaves as f64).ceil() as usize;
-    let leaf_hashes = (0..num_leaves)
-      .into_iter()
-      .collect::<Vec<usize>>()
-      .par_iter()
+    let leaves = (0..num_leaves).into_iter().collect::<Vec<usize>>();
+    #[cfg(feature = "parallel")]
+    let leaves = leaves.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let leaves = leaves.iter();
+    let leaf_hashes = leaves
       .map(|&i| {
         if i < ledger_tail_map.len() {
           let start = i * slice_size;
@@ -108,23 +246,45 @@ pub fn produce_hash_of_state(ledger_tail_map: &Vec<LedgerTailMapEntry>) -> Nimbl
       })
       .collect::<Vec<NimbleDigest>>();
 
-    let mut sha256 = Sha256::new();
+    let mut hasher = DefaultHasher::new();
     for entry in leaf_hashes {
-      sha256.update(&entry.to_bytes());
+      hasher.update(&entry.to_bytes());
     }
-    NimbleDigest::new(sha256.finalize())
+    NimbleDigest::new(hasher.finalize().into())
   }
 }
 
 /// A cryptographic Nonce
-#[derive(Clone, Debug, Copy, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Default)]
 pub struct Nonce {
   data: [u8; 16],
 }
 
+impl PartialEq for Nonce {
+  fn eq(&self, other: &Self) -> bool {
+    self.data.ct_eq(&other.data).into()
+  }
+}
+
+impl Eq for Nonce {}
+
+impl std::hash::Hash for Nonce {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.data.hash(state);
+  }
+}
+
 impl Nonce {
-  pub fn new(nonce: &[u8]) -> Result<Nonce, CustomSerdeError> {
-    if nonce.len() != 16 {
+  /// a fresh nonce drawn from the operating system's randomness source
+  pub fn new() -> Nonce {
+    let mut data = [0u8; 16];
+    OsRng.fill_bytes(&mut data);
+    Nonce { data }
+  }
+
+  /// parses a nonce, rejecting inputs that are not exactly `Nonce::num_bytes()` long
+  pub fn try_from_bytes(nonce: &[u8]) -> Result<Nonce, CustomSerdeError> {
+    if nonce.len() != Nonce::num_bytes() {
       Err(CustomSerdeError::IncorrectLength)
     } else {
       Ok(Nonce {
@@ -138,6 +298,30 @@ impl Nonce {
   }
 }
 
+impl fmt::Display for Nonce {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", hex::encode(self.data))
+  }
+}
+
+impl fmt::Debug for Nonce {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "Nonce({}…)",
+      &hex::encode(self.data)[..DEBUG_HEX_PREFIX_LEN]
+    )
+  }
+}
+
+impl FromStr for Nonce {
+  type Err = CustomSerdeError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Nonce::try_from_bytes(&decode_hex(s)?)
+  }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Nonces {
   nonces: Vec<Nonce>,
@@ -173,6 +357,9 @@ impl Nonces {
   }
 }
 
+/// the default maximum number of bytes in a block supplied by a client
+pub const MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
 /// A block in a ledger is a byte array
 #[derive(Clone, Debug, Default)]
 pub struct Block {
@@ -186,6 +373,22 @@ impl Block {
     }
   }
 
+  /// creates a block from client-supplied contents, rejecting contents over `MAX_BLOCK_SIZE`
+  pub fn try_new(bytes: &[u8]) -> Result<Self, CustomSerdeError> {
+    Block::try_new_with_max_size(bytes, MAX_BLOCK_SIZE)
+  }
+
+  /// creates a block from client-supplied contents, rejecting contents over `max_block_size`
+  pub fn try_new_with_max_size(
+    bytes: &[u8],
+    max_block_size: usize,
+  ) -> Result<Self, CustomSerdeError> {
+    if bytes.len() > max_block_size {
+      return Err(CustomSerdeError::BlockTooLarge);
+    }
+    Ok(Block::new(bytes))
+  }
+
   pub fn len(&self) -> usize {
     self.block.len()
   }
@@ -218,6 +421,7 @@ impl MetaBlock {
     NimbleDigest::num_bytes() * 2 + 0_u64.to_le_bytes().to_vec().len()
   }
 
+  /// the first metablock of a ledger: a zero `prev` and height 0
   pub fn genesis(block_hash: &NimbleDigest) -> Self {
     MetaBlock {
       prev: NimbleDigest::default(),
@@ -226,6 +430,17 @@ impl MetaBlock {
     }
   }
 
+  /// the metablock that extends `self` with `block_hash`, i.e.,
+  /// `(hash(self), block_hash, height + 1)`; returns `None` if the height overflows
+  pub fn next(&self, block_hash: &NimbleDigest) -> Option<Self> {
+    let height = self.height.checked_add(1)?;
+    Some(MetaBlock {
+      prev: self.hash(),
+      block_hash: *block_hash,
+      height,
+    })
+  }
+
   pub fn get_height(&self) -> usize {
     self.height
   }
@@ -262,41 +477,52 @@ impl ExtendedMetaBlock {
   }
 }
 
-// We store id and sig in raw form and convert them to
-// appropriate types only when verifying signatures.
-// This reduces the CPU work on the coordinator since
-// the coordinator only needs to perform a simple quorum check
-// and does not have to incur CPU cycles to convert compressed
-// elliptic curve points into uncompressed form
+// We parse the public key and the signature when an `IdSig` is constructed, so malformed
+// entries are rejected up front rather than when a signature is verified. The compressed
+// encoding of the public key is kept alongside the parsed key since the quorum checks on the
+// coordinator only compare keys and should not have to re-encode elliptic curve points.
 #[derive(Debug, Clone)]
 pub struct IdSig {
   id: Vec<u8>,
-  sig: Vec<u8>,
+  pk: PublicKey,
+  sig: Signature,
 }
 
 impl IdSig {
   pub fn new(id: PublicKey, sig: Signature) -> Self {
     Self {
       id: id.to_bytes(),
-      sig: sig.to_bytes(),
+      pk: id,
+      sig,
     }
   }
 
+  /// parses a raw (public key, signature) pair
+  pub fn from_raw(id: &[u8], sig: &[u8]) -> Result<Self, CustomSerdeError> {
+    let pk = PublicKey::from_bytes(id).map_err(CustomSerdeError::InvalidIdSig)?;
+    let sig = Signature::from_bytes(sig).map_err(CustomSerdeError::InvalidIdSig)?;
+    Ok(IdSig::new(pk, sig))
+  }
+
   pub fn get_id(&self) -> &Vec<u8> {
     &self.id
   }
 
+  pub fn get_pk(&self) -> &PublicKey {
+    &self.pk
+  }
+
+  pub fn get_sig(&self) -> &Signature {
+    &self.sig
+  }
+
   pub fn verify(&self, message: &[u8]) -> Result<(), VerificationError> {
-    let id = PublicKey::from_bytes(&self.id).map_err(|_| VerificationError::InvalidPublicKey)?;
-    let sig = Signature::from_bytes(&self.sig).map_err(|_| VerificationError::InvalidSignature)?;
-    sig
-      .verify(&id, message)
-      .map_err(|_| VerificationError::InvalidSignature)
+    self.verify_with_id(&self.pk, message)
   }
 
   pub fn verify_with_id(&self, id: &PublicKey, message: &[u8]) -> Result<(), VerificationError> {
-    let sig = Signature::from_bytes(&self.sig).map_err(|_| VerificationError::InvalidSignature)?;
-    sig
+    self
+      .sig
       .verify(id, message)
       .map_err(|_| VerificationError::InvalidSignature)
   }
@@ -304,6 +530,26 @@ impl IdSig {
   pub fn num_bytes() -> usize {
     PublicKey::num_bytes() + Signature::num_bytes()
   }
+
+  /// verifies that every signature in `id_sigs` is valid on `message`, or returns the index of the
+  /// first one that is not. ECDSA has no algebraic batch verification, so the signatures of a
+  /// batch of more than two are verified in parallel; smaller batches are verified in place, as
+  /// handing them to the thread pool costs more than it saves
+  pub fn verify_batch(id_sigs: &[IdSig], message: &[u8]) -> Result<(), usize> {
+    let is_invalid = |id_sig: &IdSig| id_sig.verify(message).is_err();
+    #[cfg(feature = "parallel")]
+    let culprit = if id_sigs.len() > 2 {
+      id_sigs.par_iter().position_first(is_invalid)
+    } else {
+      id_sigs.iter().position(is_invalid)
+    };
+    #[cfg(not(feature = "parallel"))]
+    let culprit = id_sigs.iter().position(is_invalid);
+    match culprit {
+      Some(index) => Err(index),
+      None => Ok(()),
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -353,10 +599,204 @@ impl Receipt {
   pub fn num_bytes() -> usize {
     NimbleDigest::num_bytes() + MetaBlock::num_bytes() + IdSig::num_bytes()
   }
+
+  /// verifies that the receipt is signed by an endorser of its view on its metablock as the tail
+  /// of ledger `handle`; `nonce` is set for receipts of reads of the latest state
+  pub fn verify_ledger_tail(
+    &self,
+    verifier_state: &VerifierState,
+    handle: &NimbleDigest,
+    nonce: Option<&[u8]>,
+  ) -> Result<(), VerificationError> {
+    let pks = verifier_state.get_pks_for_view(&self.view)?;
+    if !pks.contains(self.id_sig.get_id()) {
+      return Err(VerificationError::InvalidPublicKey);
+    }
+
+    let tail_hash = match nonce {
+      Some(n) => self.metablock.hash().digest_with_bytes(n),
+      None => self.metablock.hash(),
+    };
+    let message = compute_ledger_tail_message(
+      verifier_state.get_group_identity(),
+      &self.view,
+      handle,
+      &tail_hash,
+    );
+    self.id_sig.verify(&message.to_bytes())
+  }
+
+  /// aggregates the signatures of endorsers of `view` on `metablock` into one receipt, for a view
+  /// of `num_endorsers` endorsers that signs with scheme `S`; each signature comes with the position
+  /// of its endorser in the view (see `SchemeKeys::position`), and an endorser listed twice counts
+  /// once. Fails with `CryptoError::AggregationUnsupported` if the signatures of `S` do not
+  /// aggregate
+  pub fn aggregate<S: SignatureScheme>(
+    view: &NimbleDigest,
+    metablock: &MetaBlock,
+    num_endorsers: usize,
+    signatures: &[(usize, S::Signature)],
+  ) -> Result<AggregatedReceipt, LedgerError> {
+    let mut signers = vec![0u8; (num_endorsers + 7) / 8];
+    let mut sigs = Vec::new();
+    for (position, sig) in signatures {
+      if *position >= num_endorsers {
+        return Err(VerificationError::IndexOutofBounds.into());
+      }
+      let (byte, bit) = (position / 8, 1 << (position % 8));
+      if signers[byte] & bit == 0 {
+        signers[byte] |= bit;
+        sigs.push(sig);
+      }
+    }
+    let signature = S::aggregate(&sigs)?;
+    Ok(AggregatedReceipt {
+      view: *view,
+      metablock: metablock.clone(),
+      signers,
+      signature: S::signature_to_bytes(&signature),
+    })
+  }
+}
+
+/// a receipt in which the endorsers of a view that signs with an aggregating scheme endorse a
+/// metablock with one signature, along with a bitmap of who signed: bit `i % 8` of byte `i / 8` is
+/// set if the endorser at position `i` of the `SchemeKeys` of the view did. Where `Receipts` carry
+/// a key and a signature per signer, only the bitmap grows with the number of endorsers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AggregatedReceipt {
+  view: NimbleDigest,
+  metablock: MetaBlock,
+  signers: Vec<u8>,
+  signature: Vec<u8>,
+}
+
+impl AggregatedReceipt {
+  pub fn get_view(&self) -> &NimbleDigest {
+    &self.view
+  }
+
+  pub fn get_metablock(&self) -> &MetaBlock {
+    &self.metablock
+  }
+
+  pub fn get_signers(&self) -> &Vec<u8> {
+    &self.signers
+  }
+
+  pub fn get_signature(&self) -> &Vec<u8> {
+    &self.signature
+  }
+
+  /// the positions of the endorsers that signed, in a view of `num_endorsers` endorsers; the
+  /// bitmap must have a bit for each of them, and no other bit set
+  pub fn signer_positions(&self, num_endorsers: usize) -> Result<Vec<usize>, VerificationError> {
+    if self.signers.len() != (num_endorsers + 7) / 8 {
+      return Err(VerificationError::InvalidReceipt);
+    }
+    let positions = (0..self.signers.len() * 8)
+      .filter(|i| self.signers[i / 8] & (1 << (i % 8)) != 0)
+      .collect::<Vec<usize>>();
+    if positions.iter().any(|position| *position >= num_endorsers) {
+      return Err(VerificationError::InvalidReceipt);
+    }
+    Ok(positions)
+  }
+
+  /// verifies that the endorsers in the bitmap signed `message` with `keys`, the keys of the view
+  /// of the receipt; returns the number of signers, which the caller compares with the quorum
+  pub fn verify(&self, keys: &SchemeKeys, message: &[u8]) -> Result<usize, VerificationError> {
+    let positions = self.signer_positions(keys.len())?;
+    match keys.get_scheme() {
+      SignatureSchemeId::P256 => {
+        verify_aggregate::<P256>(keys, &positions, &self.signature, message)
+      },
+      SignatureSchemeId::Bls12381 => {
+        #[cfg(feature = "bls")]
+        {
+          verify_aggregate::<signature::bls::Bls>(keys, &positions, &self.signature, message)
+        }
+        #[cfg(not(feature = "bls"))]
+        {
+          Err(VerificationError::UnsupportedSignatureScheme)
+        }
+      },
+    }?;
+    Ok(positions.len())
+  }
+}
+
+/// verifies an aggregate of signatures of scheme `S` by the endorsers at `positions` of `keys`
+fn verify_aggregate<S: SignatureScheme>(
+  keys: &SchemeKeys,
+  positions: &[usize],
+  signature: &[u8],
+  message: &[u8],
+) -> Result<(), VerificationError> {
+  if !S::supports_aggregation() {
+    return Err(VerificationError::UnsupportedSignatureScheme);
+  }
+  let pks = positions
+    .iter()
+    .map(|position| {
+      S::public_key_from_bytes(&keys.keys[*position].1)
+        .map_err(|_e| VerificationError::InvalidPublicKey)
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  let signature =
+    S::signature_from_bytes(signature).map_err(|_e| VerificationError::InvalidSignature)?;
+  S::verify_aggregate(&pks.iter().collect::<Vec<_>>(), &signature, message)
+    .map_err(|_e| VerificationError::InvalidSignature)
 }
 
 const MIN_NUM_ENDORSERS: usize = 1;
 
+/// recomputes the metablock chain over `entries`, given as (block hash, height) pairs, and checks
+/// that it ends at `expected_tail`. The chain starts at the genesis metablock when `checkpoint` is
+/// `None` and extends the checkpoint metablock otherwise. A height that does not follow its
+/// predecessor is reported at that height; a tail mismatch is reported at the last height.
+pub fn verify_chain(
+  checkpoint: Option<&MetaBlock>,
+  entries: &[(NimbleDigest, usize)],
+  expected_tail: &NimbleDigest,
+) -> Result<(), VerificationError> {
+  let mut metablock = checkpoint.cloned();
+  for (block_hash, height) in entries {
+    let next = match &metablock {
+      None => MetaBlock::genesis(block_hash),
+      Some(prev) => prev
+        .next(block_hash)
+        .ok_or(VerificationError::BrokenChain(*height))?,
+    };
+    if next.get_height() != *height {
+      return Err(VerificationError::BrokenChain(next.get_height()));
+    }
+    metablock = Some(next);
+  }
+
+  match metablock {
+    Some(tail) if tail.hash() == *expected_tail => Ok(()),
+    Some(tail) => Err(VerificationError::BrokenChain(tail.get_height())),
+    None => Err(VerificationError::BrokenChain(0)),
+  }
+}
+
+/// the message an endorser signs to endorse `tail_hash` as the tail of ledger `handle` in `view`
+pub fn compute_ledger_tail_message(
+  group_identity: &NimbleDigest,
+  view: &NimbleDigest,
+  handle: &NimbleDigest,
+  tail_hash: &NimbleDigest,
+) -> NimbleDigest {
+  group_identity.digest_with(&view.digest_with(&handle.digest_with(tail_hash)))
+}
+
+/// the handle under which endorsers sign the tail of the view ledger together with a reader's
+/// nonce; ledger handles are digests, so no ledger has it
+pub fn view_ledger_handle() -> NimbleDigest {
+  NimbleDigest::default()
+}
+
 pub fn compute_aggregated_block_hash(
   hash_block_bytes: &[u8],
   hash_nonces_bytes: &[u8],
@@ -364,117 +804,881 @@ pub fn compute_aggregated_block_hash(
   NimbleDigest::digest(hash_block_bytes).digest_with_bytes(hash_nonces_bytes)
 }
 
-pub fn retrieve_public_keys_from_config(
-  config: &[u8],
-) -> Result<HashSet<Vec<u8>>, VerificationError> {
-  let endorsers: EndorserHostnames = bincode::deserialize(config).map_err(|e| {
+/// the options of `bincode::deserialize` with a limit, so that untrusted `bytes` fail to
+/// deserialize rather than make it allocate more than they could hold
+fn bounded_bincode(bytes: &[u8]) -> impl Options {
+  bincode::DefaultOptions::new()
+    .with_fixint_encoding()
+    .allow_trailing_bytes()
+    .with_limit(bytes.len() as u64)
+}
+
+/// splits a view ledger block into its endorsers and the encoding of its key rotations
+fn split_view_config(config: &[u8]) -> Result<(EndorserHostnames, &[u8]), VerificationError> {
+  let endorsers: EndorserHostnames = bounded_bincode(config).deserialize(config).map_err(|e| {
     eprintln!("Failed to deserialize the view genesis block {:?}", e);
     VerificationError::InvalidGenesisBlock
   })?;
-  let mut pks = HashSet::new();
-  for (pk_bytes, _uri) in &endorsers {
-    let pk = PublicKey::from_bytes(pk_bytes).map_err(|_e| VerificationError::InvalidPublicKey)?;
-    pks.insert(pk.to_bytes());
+  let len = bincode::serialized_size(&endorsers).map_err(|_e| VerificationError::InvalidConfig)?;
+  Ok((endorsers, &config[len as usize..]))
+}
+
+/// the signatures that the receipts of a view with `num_endorsers` endorsers need unless its view
+/// block records a quorum: a majority
+pub fn majority_quorum(num_endorsers: usize) -> usize {
+  num_endorsers / 2 + 1
+}
+
+/// the number of distinct keys among `endorsers`
+fn count_endorsers(endorsers: &EndorserHostnames) -> usize {
+  endorsers
+    .iter()
+    .map(|(pk, _uri)| pk)
+    .collect::<HashSet<&Vec<u8>>>()
+    .len()
+}
+
+/// checks the hash algorithm that follows the endorsers in a view ledger block, if any, and
+/// returns it with what follows it. Blocks written before views recorded their algorithm have none;
+/// a block that records another algorithm than `HASH_ALGORITHM` is rejected, since its digests
+/// would not match any computed here
+fn split_view_hash(bytes: &[u8]) -> Result<(Option<HashAlgorithm>, &[u8]), VerificationError> {
+  let encoded = match bytes.strip_prefix(VIEW_HASH_DOMAIN_TAG) {
+    Some(encoded) => encoded,
+    None => return Ok((None, bytes)),
+  };
+  if encoded.len() < 4 {
+    return Err(VerificationError::InvalidConfig);
   }
+  let (id, rest) = encoded.split_at(4);
+  match HashAlgorithm::from_id(u32::from_le_bytes(id.try_into().unwrap())) {
+    Some(algorithm) if algorithm == HASH_ALGORITHM => Ok((Some(algorithm), rest)),
+    _ => Err(VerificationError::HashAlgorithmMismatch),
+  }
+}
 
-  Ok(pks)
+/// splits what follows the hash algorithm in a view ledger block into the quorum that it records,
+/// if any, and the encoding of its key rotations. A recorded quorum is at least a majority of the
+/// `num_endorsers` endorsers of the view, so that any two quorums share an endorser, and at most
+/// all of them
+fn split_view_quorum(
+  bytes: &[u8],
+  num_endorsers: usize,
+) -> Result<(Option<usize>, &[u8]), VerificationError> {
+  let encoded = match bytes.strip_prefix(VIEW_QUORUM_DOMAIN_TAG) {
+    Some(encoded) => encoded,
+    None => return Ok((None, bytes)),
+  };
+  if encoded.len() < 4 {
+    return Err(VerificationError::InvalidConfig);
+  }
+  let (quorum, rest) = encoded.split_at(4);
+  let quorum = u32::from_le_bytes(quorum.try_into().unwrap()) as usize;
+  if quorum < majority_quorum(num_endorsers) || quorum > num_endorsers {
+    return Err(VerificationError::InvalidConfig);
+  }
+  Ok((Some(quorum), rest))
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct Receipts {
-  receipts: HashMap<ExtendedMetaBlock, Vec<IdSig>>,
+/// the keys with which the endorsers of a view sign receipts if the view records a scheme other
+/// than P-256, the scheme of their identity keys. Each comes with the identity key of its endorser
+/// and a proof that the endorser knows the private key; the keys are kept in ascending order of
+/// the identity keys, which is the order of the signers of an `AggregatedReceipt`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SchemeKeys {
+  scheme: SignatureSchemeId,
+  keys: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>,
 }
 
-impl Receipts {
-  pub fn new() -> Self {
-    Receipts {
-      receipts: HashMap::new(),
-    }
+impl SchemeKeys {
+  /// the keys of `scheme`, given as (identity key, key, proof of possession)
+  pub fn new(scheme: SignatureSchemeId, mut keys: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>) -> Self {
+    keys.sort();
+    SchemeKeys { scheme, keys }
   }
 
-  pub fn is_empty(&self) -> bool {
-    self.receipts.is_empty()
+  pub fn get_scheme(&self) -> SignatureSchemeId {
+    self.scheme
   }
 
-  pub fn get_metablock(&self) -> Result<MetaBlock, VerificationError> {
-    let mut metablocks = HashSet::<MetaBlock>::new();
-    for ex_meta_block in self.receipts.keys() {
-      metablocks.insert(ex_meta_block.get_metablock().clone());
-    }
-    if metablocks.len() != 1 {
-      eprintln!("#metablocks: {}", metablocks.len());
-      for metablock in &metablocks {
-        eprintln!("metablock: {:?}", metablock);
-      }
-      Err(VerificationError::InvalidViewChangeReceipt)
-    } else {
-      Ok(metablocks.iter().next().unwrap().clone())
-    }
+  pub fn len(&self) -> usize {
+    self.keys.len()
   }
 
-  pub fn get(&self) -> &HashMap<ExtendedMetaBlock, Vec<IdSig>> {
-    &self.receipts
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
   }
 
-  pub fn add(&mut self, receipt: &Receipt) {
-    let ex_meta_block = ExtendedMetaBlock::new(receipt.get_view(), receipt.get_metablock());
-    if let hash_map::Entry::Occupied(mut e) = self.receipts.entry(ex_meta_block.clone()) {
-      let new_id_sig = receipt.get_id_sig();
-      let id_sig = e
-        .get()
-        .iter()
-        .find(|existing_id_sig| existing_id_sig.get_id() == new_id_sig.get_id());
-      if id_sig.is_none() {
-        e.get_mut().push(receipt.get_id_sig().clone());
-      }
-    } else {
-      self
-        .receipts
-        .insert(ex_meta_block, vec![receipt.get_id_sig().clone()]);
-    }
+  /// the key of the endorser at `position`
+  pub fn get_key(&self, position: usize) -> Option<&Vec<u8>> {
+    self.keys.get(position).map(|(_id, key, _proof)| key)
   }
 
-  pub fn merge_receipts(&mut self, receipts: &Receipts) {
-    for (ex_meta_block, id_sigs) in receipts.get() {
-      for id_sig in id_sigs {
-        let receipt = Receipt::new(
-          *ex_meta_block.get_view(),
-          ex_meta_block.get_metablock().clone(),
-          id_sig.clone(),
-        );
-        self.add(&receipt);
-      }
-    }
+  /// the position of the endorser with `identity_pk` among the endorsers of the view
+  pub fn position(&self, identity_pk: &[u8]) -> Option<usize> {
+    self
+      .keys
+      .binary_search_by(|(id, _key, _proof)| id.as_slice().cmp(identity_pk))
+      .ok()
   }
 
-  pub fn check_quorum(&self, verifier_state: &VerifierState) -> Result<usize, VerificationError> {
-    for (ex_meta_block, id_sigs) in &self.receipts {
-      let view = ex_meta_block.get_view();
-      let pks = verifier_state.get_pks_for_view(view)?;
-      if id_sigs.len() < pks.len() / 2 + 1 {
-        continue;
+  /// checks that all the keys are of the scheme, and that there is one for each of `endorsers`
+  /// and for no one else
+  fn check_endorsers(&self, endorsers: &EndorserHostnames) -> Result<(), VerificationError> {
+    // the endorsers of a P-256 view sign with their identity keys
+    if self.scheme == SignatureSchemeId::P256 {
+      return Err(VerificationError::InvalidConfig);
+    }
+    for (_id, key, proof) in &self.keys {
+      if key.len() != self.scheme.public_key_len() || proof.len() != self.scheme.signature_len() {
+        return Err(VerificationError::MixedSignatureSchemes);
       }
+    }
+    let ids = self
+      .keys
+      .iter()
+      .map(|(id, _key, _proof)| id)
+      .collect::<HashSet<&Vec<u8>>>();
+    let pks = endorsers
+      .iter()
+      .map(|(pk, _uri)| pk)
+      .collect::<HashSet<&Vec<u8>>>();
+    if ids != pks {
+      return Err(VerificationError::MixedSignatureSchemes);
+    }
+    Ok(())
+  }
 
-      let mut num_receipts = 0;
-      for id_sig in id_sigs {
-        let id = id_sig.get_id();
-        if pks.contains(id) {
-          num_receipts += 1;
+  /// checks the proof of possession of every key, which a verifier does once, when it applies the
+  /// view that records them
+  pub fn verify_possession(&self) -> Result<(), VerificationError> {
+    match self.scheme {
+      SignatureSchemeId::P256 => Err(VerificationError::UnsupportedSignatureScheme),
+      SignatureSchemeId::Bls12381 => {
+        #[cfg(feature = "bls")]
+        {
+          use signature::bls;
+          for (_id, key, proof) in &self.keys {
+            let key =
+              bls::PublicKey::from_bytes(key).map_err(|_e| VerificationError::InvalidPublicKey)?;
+            let proof = bls::Signature::from_bytes(proof)
+              .map_err(|_e| VerificationError::InvalidSignature)?;
+            key
+              .verify_possession(&proof)
+              .map_err(|_e| VerificationError::InvalidSignature)?;
+          }
+          Ok(())
         }
-      }
-
-      if num_receipts > pks.len() / 2 {
-        return Ok(ex_meta_block.get_metablock().get_height());
-      }
+        #[cfg(not(feature = "bls"))]
+        {
+          Err(VerificationError::UnsupportedSignatureScheme)
+        }
+      },
     }
+  }
+}
 
-    Err(VerificationError::InsufficientReceipts)
+/// parses `u8 scheme || bincode keys` off the front of `bytes`; the keys must be sorted by their
+/// identity keys, with no identity key twice, so that every set of keys has one encoding
+fn split_scheme_keys(bytes: &[u8]) -> Result<(SchemeKeys, &[u8]), CustomSerdeError> {
+  let (scheme, encoded) = bytes
+    .split_first()
+    .ok_or(CustomSerdeError::IncorrectLength)?;
+  let scheme = SignatureSchemeId::from_u8(*scheme).ok_or(CustomSerdeError::InternalError)?;
+  let keys: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> = bounded_bincode(encoded)
+    .deserialize(encoded)
+    .map_err(|_e| CustomSerdeError::InternalError)?;
+  if keys.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+    return Err(CustomSerdeError::DuplicateEntry);
   }
+  let len = bincode::serialized_size(&keys).map_err(|_e| CustomSerdeError::InternalError)?;
+  Ok((SchemeKeys { scheme, keys }, &encoded[len as usize..]))
+}
 
-  pub fn verify_read_latest(
-    &self,
-    verifier_state: &VerifierState,
-    handle_bytes: &[u8],
+/// splits what follows the quorum in a view ledger block into the keys of the scheme that it
+/// records, if any, and the encoding of its key rotations; the keys must be of one scheme, and
+/// there must be one for each of the `endorsers` of the view
+fn split_view_scheme<'a>(
+  bytes: &'a [u8],
+  endorsers: &EndorserHostnames,
+) -> Result<(Option<SchemeKeys>, &'a [u8]), VerificationError> {
+  let encoded = match bytes.strip_prefix(VIEW_SCHEME_DOMAIN_TAG) {
+    Some(encoded) => encoded,
+    None => return Ok((None, bytes)),
+  };
+  let (keys, rest) = split_scheme_keys(encoded).map_err(|_e| VerificationError::InvalidConfig)?;
+  keys.check_endorsers(endorsers)?;
+  Ok((Some(keys), rest))
+}
+
+fn decode_key_rotations(bytes: &[u8]) -> Result<Vec<KeyRotation>, VerificationError> {
+  if bytes.is_empty() {
+    return Ok(Vec::new());
+  }
+  let encoded = bytes
+    .strip_prefix(VIEW_ROTATIONS_DOMAIN_TAG)
+    .ok_or(VerificationError::InvalidConfig)?;
+  let rotations: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> =
+    bounded_bincode(encoded)
+      .deserialize(encoded)
+      .map_err(|_e| VerificationError::InvalidConfig)?;
+  // trailing bytes would not be covered by the digest of the rotations
+  if bincode::serialized_size(&rotations).ok() != Some(encoded.len() as u64) {
+    return Err(VerificationError::InvalidConfig);
+  }
+  rotations
+    .iter()
+    .map(|(old_pk, new_pk, signature)| {
+      KeyRotation::from_raw(old_pk, new_pk, signature)
+        .map_err(|_e| VerificationError::InvalidKeyRotation)
+    })
+    .collect()
+}
+
+fn decode_public_keys(endorsers: &EndorserHostnames) -> Result<Vec<PublicKey>, VerificationError> {
+  endorsers
+    .iter()
+    .map(|(pk_bytes, _uri)| {
+      PublicKey::from_bytes(pk_bytes).map_err(|_e| VerificationError::InvalidPublicKey)
+    })
+    .collect()
+}
+
+pub fn retrieve_public_keys_from_config(
+  config: &[u8],
+) -> Result<HashSet<Vec<u8>>, VerificationError> {
+  let (endorsers, _rotations) = split_view_config(config)?;
+  let pks = decode_public_keys(&endorsers)?;
+  Ok(pks.iter().map(|pk| pk.to_bytes()).collect())
+}
+
+/// the quorum of the view held by a view ledger block encoded by `encode_view_config`: the one that
+/// the block records, or a majority of its endorsers
+pub fn retrieve_quorum_from_config(config: &[u8]) -> Result<usize, VerificationError> {
+  let (endorsers, rest) = split_view_config(config)?;
+  let (_algorithm, rest) = split_view_hash(rest)?;
+  let num_endorsers = count_endorsers(&endorsers);
+  let (quorum, _rotations) = split_view_quorum(rest, num_endorsers)?;
+  Ok(quorum.unwrap_or_else(|| majority_quorum(num_endorsers)))
+}
+
+/// the keys of the signature scheme that a view ledger block encoded by `encode_view_config`
+/// records, or `None` if the endorsers of the view sign with their P-256 identity keys
+pub fn retrieve_scheme_keys_from_config(
+  config: &[u8],
+) -> Result<Option<SchemeKeys>, VerificationError> {
+  let (endorsers, rest) = split_view_config(config)?;
+  let (_algorithm, rest) = split_view_hash(rest)?;
+  let (_quorum, rest) = split_view_quorum(rest, count_endorsers(&endorsers))?;
+  let (keys, _rotations) = split_view_scheme(rest, &endorsers)?;
+  Ok(keys)
+}
+
+/// encodes the block of a view ledger entry: the endorsers of the view, followed by
+/// `tag || u32 LE id` of `HASH_ALGORITHM`, by `tag || u32 LE quorum` if the view needs another
+/// quorum than a majority, and by `tag || bincode rotations` if the view change rotates the keys
+/// of endorsers
+pub fn encode_view_config(
+  endorsers: &EndorserHostnames,
+  quorum: Option<usize>,
+  rotations: &[KeyRotation],
+) -> Vec<u8> {
+  encode_view_config_with_scheme(endorsers, quorum, None, rotations)
+}
+
+/// like `encode_view_config`, but for a view whose endorsers sign with `scheme_keys`, which follow
+/// the quorum as `tag || u8 scheme || bincode keys`
+pub fn encode_view_config_with_scheme(
+  endorsers: &EndorserHostnames,
+  quorum: Option<usize>,
+  scheme_keys: Option<&SchemeKeys>,
+  rotations: &[KeyRotation],
+) -> Vec<u8> {
+  let mut bytes = bincode::serialize(endorsers).unwrap();
+  bytes.extend_from_slice(VIEW_HASH_DOMAIN_TAG);
+  bytes.extend(&HASH_ALGORITHM.id().to_le_bytes());
+  if let Some(quorum) = quorum {
+    bytes.extend_from_slice(VIEW_QUORUM_DOMAIN_TAG);
+    bytes.extend(&(quorum as u32).to_le_bytes());
+  }
+  if let Some(scheme_keys) = scheme_keys {
+    bytes.extend_from_slice(VIEW_SCHEME_DOMAIN_TAG);
+    bytes.extend(scheme_keys.to_bytes());
+  }
+  if !rotations.is_empty() {
+    let rotations = rotations
+      .iter()
+      .map(|r| (r.old_pk.clone(), r.new_pk.clone(), r.signature.clone()))
+      .collect::<Vec<_>>();
+    bytes.extend_from_slice(VIEW_ROTATIONS_DOMAIN_TAG);
+    bytes.extend(bincode::serialize(&rotations).unwrap());
+  }
+  bytes
+}
+
+/// splits a block encoded by `encode_view_config` into the endorsers and the key rotations
+pub fn decode_view_config(
+  config: &[u8],
+) -> Result<(EndorserHostnames, Vec<KeyRotation>), VerificationError> {
+  let (endorsers, rest) = split_view_config(config)?;
+  let (_algorithm, rest) = split_view_hash(rest)?;
+  let (_quorum, rest) = split_view_quorum(rest, count_endorsers(&endorsers))?;
+  let (_keys, rotations) = split_view_scheme(rest, &endorsers)?;
+  Ok((endorsers, decode_key_rotations(rotations)?))
+}
+
+/// domain separation tag for the digests of view ledger blocks
+const VIEW_BLOCK_DOMAIN_TAG: &[u8] = b"NimbleViewBlock";
+
+/// domain separation tag for the hash algorithm that follows the endorsers in a view ledger block
+const VIEW_HASH_DOMAIN_TAG: &[u8] = b"NimbleViewHash";
+
+/// domain separation tag for the quorum that follows the hash algorithm in a view ledger block
+const VIEW_QUORUM_DOMAIN_TAG: &[u8] = b"NimbleViewQuorum";
+
+/// domain separation tag for the keys of the signature scheme that follow the quorum in a view
+/// ledger block
+const VIEW_SCHEME_DOMAIN_TAG: &[u8] = b"NimbleViewScheme";
+
+/// domain separation tag for the key rotations that follow the endorsers in a view ledger block
+const VIEW_ROTATIONS_DOMAIN_TAG: &[u8] = b"NimbleViewRotations";
+
+/// domain separation tag for the messages that hand the place of an endorser over to a new key
+const KEY_ROTATION_DOMAIN_TAG: &[u8] = b"NimbleKeyRotation";
+
+/// computes the message that an endorser of the group with `group_identity` signs with its old
+/// key, `old_pk`, to hand its place in the group over to its new key, `new_pk`
+pub fn compute_key_rotation_message(
+  group_identity: &NimbleDigest,
+  old_pk: &[u8],
+  new_pk: &[u8],
+) -> NimbleDigest {
+  let mut builder = DigestBuilder::new();
+  builder
+    .update(KEY_ROTATION_DOMAIN_TAG)
+    .update(&group_identity.digest)
+    .update(&(old_pk.len() as u32).to_le_bytes())
+    .update(old_pk)
+    .update(&(new_pk.len() as u32).to_le_bytes())
+    .update(new_pk);
+  builder.finalize()
+}
+
+/// the handover of an endorser from its old key to a new key, signed with the old key; a view
+/// change that swaps the keys records it in the view ledger, so that the receipts signed under
+/// either key are attributed to the same endorser
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyRotation {
+  old_pk: Vec<u8>,
+  new_pk: Vec<u8>,
+  signature: Vec<u8>,
+}
+
+impl KeyRotation {
+  pub fn new(old_pk: &PublicKey, new_pk: &PublicKey, signature: &Signature) -> Self {
+    KeyRotation {
+      old_pk: old_pk.to_bytes(),
+      new_pk: new_pk.to_bytes(),
+      signature: signature.to_bytes(),
+    }
+  }
+
+  /// parses a raw (old public key, new public key, signature) triple
+  pub fn from_raw(
+    old_pk: &[u8],
+    new_pk: &[u8],
+    signature: &[u8],
+  ) -> Result<Self, CustomSerdeError> {
+    let old_pk = PublicKey::from_bytes(old_pk).map_err(CustomSerdeError::InvalidIdSig)?;
+    let new_pk = PublicKey::from_bytes(new_pk).map_err(CustomSerdeError::InvalidIdSig)?;
+    let signature = Signature::from_bytes(signature).map_err(CustomSerdeError::InvalidIdSig)?;
+    Ok(KeyRotation::new(&old_pk, &new_pk, &signature))
+  }
+
+  pub fn get_old_pk(&self) -> &Vec<u8> {
+    &self.old_pk
+  }
+
+  pub fn get_new_pk(&self) -> &Vec<u8> {
+    &self.new_pk
+  }
+
+  pub fn get_signature(&self) -> &Vec<u8> {
+    &self.signature
+  }
+
+  /// checks that the old key signed the handover to the new key in the group with `group_identity`
+  pub fn verify(&self, group_identity: &NimbleDigest) -> Result<(), VerificationError> {
+    if self.old_pk == self.new_pk {
+      return Err(VerificationError::InvalidKeyRotation);
+    }
+    let old_pk =
+      PublicKey::from_bytes(&self.old_pk).map_err(|_e| VerificationError::InvalidPublicKey)?;
+    let signature =
+      Signature::from_bytes(&self.signature).map_err(|_e| VerificationError::InvalidSignature)?;
+    let message = compute_key_rotation_message(group_identity, &self.old_pk, &self.new_pk);
+    signature
+      .verify(&old_pk, &message.to_bytes())
+      .map_err(|_e| VerificationError::InvalidKeyRotation)
+  }
+}
+
+/// checks the key rotations of a view change from the endorsers `old_pks` to `new_pks`: each hands
+/// a key that leaves the view over to a key that joins it, with a signature of the key that leaves,
+/// and no key is handed over or taken over twice. Without `old_pks`, as for a verifier that does
+/// not know the old view, only the new keys and the signatures are checked
+pub fn verify_key_rotations(
+  group_identity: &NimbleDigest,
+  rotations: &[KeyRotation],
+  old_pks: Option<&HashSet<Vec<u8>>>,
+  new_pks: &HashSet<Vec<u8>>,
+) -> Result<(), VerificationError> {
+  let mut rotated = HashSet::new();
+  for rotation in rotations {
+    let (old_pk, new_pk) = (rotation.get_old_pk(), rotation.get_new_pk());
+    let leaves = old_pks.map_or(true, |pks| pks.contains(old_pk) && !pks.contains(new_pk));
+    if !leaves
+      || new_pks.contains(old_pk)
+      || !new_pks.contains(new_pk)
+      || !rotated.insert(old_pk)
+      || !rotated.insert(new_pk)
+    {
+      return Err(VerificationError::InvalidKeyRotation);
+    }
+    rotation.verify(group_identity)?;
+  }
+  Ok(())
+}
+
+/// computes the digest of a view ledger entry from the public keys of the endorsers in the view
+/// and arbitrary metadata; keys are sorted and deduplicated, so the order of `pks` does not matter
+pub fn compute_view_block(pks: &[PublicKey], metadata: &[u8]) -> NimbleDigest {
+  let pks = pks
+    .iter()
+    .map(|pk| pk.to_bytes())
+    .collect::<std::collections::BTreeSet<Vec<u8>>>();
+
+  let mut builder = DigestBuilder::new();
+  builder
+    .update(VIEW_BLOCK_DOMAIN_TAG)
+    .update(&(pks.len() as u32).to_le_bytes());
+  for pk in &pks {
+    builder.update(&(pk.len() as u32).to_le_bytes()).update(pk);
+  }
+  builder
+    .update(&(metadata.len() as u32).to_le_bytes())
+    .update(metadata);
+  builder.finalize()
+}
+
+/// computes the digest of a view ledger block encoded by `encode_view_config`, whose hash algorithm,
+/// quorum, scheme keys, and key rotations are its metadata; the empty config that precedes the first view maps to the zero
+/// digest
+pub fn compute_view_block_hash(config: &[u8]) -> Result<NimbleDigest, VerificationError> {
+  if config.is_empty() {
+    return Ok(NimbleDigest::default());
+  }
+  let (endorsers, metadata) = split_view_config(config)?;
+  let (_algorithm, rest) = split_view_hash(metadata)?;
+  let (_quorum, rest) = split_view_quorum(rest, count_endorsers(&endorsers))?;
+  let (_keys, rotations) = split_view_scheme(rest, &endorsers)?;
+  decode_key_rotations(rotations)?;
+  let pks = decode_public_keys(&endorsers)?;
+  Ok(compute_view_block(&pks, metadata))
+}
+
+/// domain separation tag for the digests of ledger tail maps
+const TAIL_MAP_DOMAIN_TAG: &[u8] = b"NimbleTailMap";
+
+/// domain separation tag for the blocks appended to the view ledger on a view change
+const VIEW_CHANGE_DOMAIN_TAG: &[u8] = b"NimbleViewChange";
+
+/// domain separation tag for the genesis blocks that carry the metadata of their ledger
+const GENESIS_METADATA_DOMAIN_TAG: &[u8] = b"NimbleGenesisMetadata";
+
+/// domain separation tag for the final block of a sealed ledger
+const SEAL_DOMAIN_TAG: &[u8] = b"NimbleSealedLedger";
+
+/// domain separation tag for the blocks that the coordinator appends to liveness ledgers
+const HEARTBEAT_DOMAIN_TAG: &[u8] = b"NimbleHeartbeat";
+
+/// computes the digest of a map from ledger handles to their (tail hash, height); entries are
+/// encoded in ascending order of handle as `handle || tail hash || u64 LE height`, so every party
+/// that holds the same map obtains the same digest regardless of iteration order
+pub fn tail_map_digest(tail_map: &HashMap<NimbleDigest, (NimbleDigest, usize)>) -> NimbleDigest {
+  let mut entries = tail_map.iter().collect::<Vec<_>>();
+  entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+  let mut builder = DigestBuilder::new();
+  builder
+    .update(TAIL_MAP_DOMAIN_TAG)
+    .update(&(entries.len() as u64).to_le_bytes());
+  for (handle, (tail_hash, height)) in entries {
+    builder
+      .update(&handle.digest)
+      .update(&tail_hash.digest)
+      .update(&(*height as u64).to_le_bytes());
+  }
+  builder.finalize()
+}
+
+/// constructs the view ledger block that records a view change: it binds the tail of the old
+/// view ledger, the digest of the new endorser set and the digest of the ledger tail map that the
+/// new endorsers are initialized with; the view ledger entry commits to `hash()` of this block
+pub fn view_change_block(
+  tail_map_digest: &NimbleDigest,
+  new_endorser_set_digest: &NimbleDigest,
+  old_view_tail: &NimbleDigest,
+) -> Block {
+  let mut bytes = Vec::with_capacity(VIEW_CHANGE_DOMAIN_TAG.len() + 3 * NimbleDigest::num_bytes());
+  bytes.extend_from_slice(VIEW_CHANGE_DOMAIN_TAG);
+  bytes.extend_from_slice(&old_view_tail.to_bytes());
+  bytes.extend_from_slice(&new_endorser_set_digest.to_bytes());
+  bytes.extend_from_slice(&tail_map_digest.to_bytes());
+  Block::new(&bytes)
+}
+
+/// constructs the genesis block of a ledger created with the client's `block_bytes` and
+/// `metadata`, so that the receipts of the genesis entry cover the metadata; the block is encoded
+/// as `tag || u32 LE metadata length || metadata || block_bytes`, except that without metadata it
+/// is `block_bytes` itself unless those would parse as such an encoding
+pub fn compute_genesis_block(block_bytes: &[u8], metadata: &[u8]) -> Block {
+  if metadata.is_empty() && !block_bytes.starts_with(GENESIS_METADATA_DOMAIN_TAG) {
+    return Block::new(block_bytes);
+  }
+  let mut bytes =
+    Vec::with_capacity(GENESIS_METADATA_DOMAIN_TAG.len() + 4 + metadata.len() + block_bytes.len());
+  bytes.extend_from_slice(GENESIS_METADATA_DOMAIN_TAG);
+  bytes.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+  bytes.extend_from_slice(metadata);
+  bytes.extend_from_slice(block_bytes);
+  Block::new(&bytes)
+}
+
+/// splits a genesis block constructed by `compute_genesis_block` into the metadata and the
+/// client's block
+pub fn parse_genesis_block(genesis_bytes: &[u8]) -> Result<(&[u8], &[u8]), VerificationError> {
+  let framed = match genesis_bytes.strip_prefix(GENESIS_METADATA_DOMAIN_TAG) {
+    Some(framed) => framed,
+    None => return Ok((&[], genesis_bytes)),
+  };
+  if framed.len() < 4 {
+    return Err(VerificationError::InvalidGenesisBlock);
+  }
+  let (len_bytes, rest) = framed.split_at(4);
+  let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+  if rest.len() < len {
+    return Err(VerificationError::InvalidGenesisBlock);
+  }
+  Ok(rest.split_at(len))
+}
+
+/// constructs the final block of a ledger that is sealed at `sealed_at` (milliseconds since the
+/// Unix epoch); the block is encoded as `tag || u64 LE sealed_at`, so that the receipts of the
+/// entry attest that the ledger was closed
+pub fn compute_seal_block(sealed_at: u64) -> Block {
+  let mut bytes = Vec::with_capacity(SEAL_DOMAIN_TAG.len() + 8);
+  bytes.extend_from_slice(SEAL_DOMAIN_TAG);
+  bytes.extend_from_slice(&sealed_at.to_le_bytes());
+  Block::new(&bytes)
+}
+
+/// returns when the ledger was sealed if `block_bytes` is a block constructed by
+/// `compute_seal_block`
+pub fn parse_seal_block(block_bytes: &[u8]) -> Option<u64> {
+  let sealed_at = block_bytes.strip_prefix(SEAL_DOMAIN_TAG)?;
+  Some(u64::from_le_bytes(sealed_at.try_into().ok()?))
+}
+
+/// constructs a heartbeat block, which the coordinator appends to a liveness ledger every
+/// `interval_ms` at `timestamp_ms` by its clock (milliseconds since the Unix epoch); the block is
+/// encoded as `tag || u64 LE timestamp_ms || u64 LE interval_ms`, so that a read of the tail of the
+/// ledger with a fresh nonce shows how recently the coordinator appended to it
+pub fn compute_heartbeat_block(timestamp_ms: u64, interval_ms: u64) -> Block {
+  let mut bytes = Vec::with_capacity(HEARTBEAT_DOMAIN_TAG.len() + 16);
+  bytes.extend_from_slice(HEARTBEAT_DOMAIN_TAG);
+  bytes.extend_from_slice(&timestamp_ms.to_le_bytes());
+  bytes.extend_from_slice(&interval_ms.to_le_bytes());
+  Block::new(&bytes)
+}
+
+/// returns the timestamp and the interval of a block constructed by `compute_heartbeat_block`
+pub fn parse_heartbeat_block(block_bytes: &[u8]) -> Option<(u64, u64)> {
+  let fields = block_bytes.strip_prefix(HEARTBEAT_DOMAIN_TAG)?;
+  if fields.len() != 16 {
+    return None;
+  }
+  let (timestamp_ms, interval_ms) = fields.split_at(8);
+  Some((
+    u64::from_le_bytes(timestamp_ms.try_into().ok()?),
+    u64::from_le_bytes(interval_ms.try_into().ok()?),
+  ))
+}
+
+/// domain separation tag for the digests of snapshot reads
+const SNAPSHOT_DOMAIN_TAG: &[u8] = b"NimbleSnapshot";
+
+/// computes the digest of a snapshot read of several ledgers with `nonce`, from the handle of
+/// each ledger and the (tail hash, height) that the read attested for it; entries are encoded in
+/// ascending order of handle as `handle || tail hash || u64 LE height` after the nonce, like those
+/// of `tail_map_digest`, so the digest does not depend on the order the ledgers were read in
+pub fn compute_snapshot_digest(
+  nonce: &Nonce,
+  entries: &[(NimbleDigest, NimbleDigest, usize)],
+) -> NimbleDigest {
+  let mut entries = entries.iter().collect::<Vec<_>>();
+  entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+  let mut builder = DigestBuilder::new();
+  builder
+    .update(SNAPSHOT_DOMAIN_TAG)
+    .update(&nonce.to_bytes())
+    .update(&(entries.len() as u64).to_le_bytes());
+  for (handle, tail_hash, height) in entries {
+    builder
+      .update(&handle.digest)
+      .update(&tail_hash.digest)
+      .update(&(*height as u64).to_le_bytes());
+  }
+  builder.finalize()
+}
+
+/// domain separation tag for the heartbeats that endorsers sign for the coordinator
+const ENDORSER_HEARTBEAT_DOMAIN_TAG: &[u8] = b"NimbleEndorserHeartbeat";
+
+/// computes the message that an endorser signs to answer a heartbeat with `nonce`: its heartbeat
+/// `counter`, which goes up with every heartbeat while the endorser runs, and the time of its
+/// clock, `timestamp_ms` (milliseconds since the Unix epoch). The tag sets it apart from the
+/// messages that endorse ledgers, so a heartbeat never passes for an attestation
+pub fn compute_endorser_heartbeat_message(
+  nonce: &Nonce,
+  counter: u64,
+  timestamp_ms: u64,
+) -> NimbleDigest {
+  let mut builder = DigestBuilder::new();
+  builder
+    .update(ENDORSER_HEARTBEAT_DOMAIN_TAG)
+    .update(&nonce.to_bytes())
+    .update(&counter.to_le_bytes())
+    .update(&timestamp_ms.to_le_bytes());
+  builder.finalize()
+}
+
+/// counts the distinct keys in `pks` that appear in `id_sigs`
+fn count_distinct_signers(id_sigs: &[IdSig], pks: &HashSet<Vec<u8>>) -> usize {
+  id_sigs
+    .iter()
+    .map(|id_sig| id_sig.get_id())
+    .filter(|id| pks.contains(*id))
+    .collect::<HashSet<&Vec<u8>>>()
+    .len()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Receipts {
+  receipts: HashMap<ExtendedMetaBlock, Vec<IdSig>>,
+}
+
+impl Receipts {
+  pub fn new() -> Self {
+    Receipts {
+      receipts: HashMap::new(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.receipts.is_empty()
+  }
+
+  pub fn get_metablock(&self) -> Result<MetaBlock, VerificationError> {
+    let mut metablocks = HashSet::<MetaBlock>::new();
+    for ex_meta_block in self.receipts.keys() {
+      metablocks.insert(ex_meta_block.get_metablock().clone());
+    }
+    if metablocks.len() != 1 {
+      eprintln!("#metablocks: {}", metablocks.len());
+      for metablock in &metablocks {
+        eprintln!("metablock: {:?}", metablock);
+      }
+      Err(VerificationError::InvalidViewChangeReceipt)
+    } else {
+      Ok(metablocks.iter().next().unwrap().clone())
+    }
+  }
+
+  pub fn get(&self) -> &HashMap<ExtendedMetaBlock, Vec<IdSig>> {
+    &self.receipts
+  }
+
+  pub fn add(&mut self, receipt: &Receipt) {
+    let ex_meta_block = ExtendedMetaBlock::new(receipt.get_view(), receipt.get_metablock());
+    if let hash_map::Entry::Occupied(mut e) = self.receipts.entry(ex_meta_block.clone()) {
+      let new_id_sig = receipt.get_id_sig();
+      let id_sig = e
+        .get()
+        .iter()
+        .find(|existing_id_sig| existing_id_sig.get_id() == new_id_sig.get_id());
+      if id_sig.is_none() {
+        e.get_mut().push(receipt.get_id_sig().clone());
+      }
+    } else {
+      self
+        .receipts
+        .insert(ex_meta_block, vec![receipt.get_id_sig().clone()]);
+    }
+  }
+
+  pub fn merge_receipts(&mut self, receipts: &Receipts) {
+    for (ex_meta_block, id_sigs) in receipts.get() {
+      for id_sig in id_sigs {
+        let receipt = Receipt::new(
+          *ex_meta_block.get_view(),
+          ex_meta_block.get_metablock().clone(),
+          id_sig.clone(),
+        );
+        self.add(&receipt);
+      }
+    }
+  }
+
+  /// returns the union of `self` and `other`, rejecting a public key that signed the same
+  /// (view, metablock) with two different signatures
+  pub fn merge(&self, other: &Receipts) -> Result<Receipts, VerificationError> {
+    let mut merged = self.clone();
+    for (ex_meta_block, id_sigs) in other.get() {
+      let entry = merged
+        .receipts
+        .entry(ex_meta_block.clone())
+        .or_insert_with(Vec::new);
+      for id_sig in id_sigs {
+        match entry
+          .iter()
+          .find(|existing_id_sig| existing_id_sig.get_id() == id_sig.get_id())
+        {
+          Some(existing_id_sig) => {
+            if existing_id_sig.sig != id_sig.sig {
+              return Err(VerificationError::ConflictingReceipts);
+            }
+          },
+          None => entry.push(id_sig.clone()),
+        }
+      }
+    }
+    Ok(merged)
+  }
+
+  /// returns true if any of the receipts is signed by the public key `pk`
+  pub fn contains(&self, pk: &[u8]) -> bool {
+    self
+      .receipts
+      .values()
+      .any(|id_sigs| id_sigs.iter().any(|id_sig| id_sig.get_id() == pk))
+  }
+
+  /// returns the total number of signatures across all receipts
+  pub fn len(&self) -> usize {
+    self.receipts.values().map(|id_sigs| id_sigs.len()).sum()
+  }
+
+  /// iterates over the (public key, signature) pairs of all receipts
+  pub fn id_sigs(&self) -> impl Iterator<Item = (&PublicKey, &Signature)> {
+    self
+      .receipts
+      .values()
+      .flatten()
+      .map(|id_sig| (id_sig.get_pk(), id_sig.get_sig()))
+  }
+
+  /// returns the distinct public keys that signed any of the receipts
+  pub fn signers(&self) -> Vec<PublicKey> {
+    let mut seen = HashSet::new();
+    self
+      .receipts
+      .values()
+      .flatten()
+      .filter(|id_sig| seen.insert(id_sig.get_id()))
+      .map(|id_sig| id_sig.get_pk().clone())
+      .collect()
+  }
+
+  /// returns a signature by `pk` on any of the receipts
+  pub fn get_sig(&self, pk: &PublicKey) -> Option<&Signature> {
+    let id = pk.to_bytes();
+    self
+      .receipts
+      .values()
+      .flatten()
+      .find(|id_sig| *id_sig.get_id() == id)
+      .map(|id_sig| id_sig.get_sig())
+  }
+
+  pub fn check_quorum(&self, verifier_state: &VerifierState) -> Result<usize, VerificationError> {
+    for (ex_meta_block, id_sigs) in &self.receipts {
+      let view = ex_meta_block.get_view();
+      let pks = verifier_state.get_pks_for_view(view)?;
+      let quorum = verifier_state.get_quorum_for_view(view)?;
+      if id_sigs.len() < quorum {
+        continue;
+      }
+
+      let num_receipts = count_distinct_signers(id_sigs, pks);
+      if num_receipts >= quorum {
+        return Ok(ex_meta_block.get_metablock().get_height());
+      }
+    }
+
+    Err(VerificationError::InsufficientReceipts)
+  }
+
+  /// verifies that a quorum of the endorsers of the latest view in `verifier_state` signed the
+  /// tail of the view ledger together with `nonce_bytes`, i.e., that the view was still current
+  /// when they saw the nonce; returns the height of the view ledger tail
+  pub fn verify_view_tail(
+    &self,
+    verifier_state: &VerifierState,
+    nonce_bytes: &[u8],
+  ) -> Result<usize, VerificationError> {
+    let mut stale = false;
+    for (ex_meta_block, id_sigs) in &self.receipts {
+      let view = ex_meta_block.get_view();
+      let metablock = ex_meta_block.get_metablock();
+      if metablock.hash() != *view {
+        continue;
+      }
+      if metablock.get_height() != verifier_state.get_view_ledger_height() {
+        stale = true;
+        continue;
+      }
+      let pks = verifier_state.get_pks_for_view(view)?;
+      let quorum = verifier_state.get_quorum_for_view(view)?;
+      if id_sigs.len() < quorum {
+        continue;
+      }
+
+      let message = compute_ledger_tail_message(
+        verifier_state.get_group_identity(),
+        view,
+        &view_ledger_handle(),
+        &view.digest_with_bytes(nonce_bytes),
+      );
+      IdSig::verify_batch(id_sigs, &message.to_bytes())
+        .map_err(|_e| VerificationError::InvalidSignature)?;
+      if count_distinct_signers(id_sigs, pks) >= quorum {
+        return Ok(metablock.get_height());
+      }
+    }
+
+    if stale {
+      Err(VerificationError::ViewInMetaBlockNotLatest)
+    } else {
+      Err(VerificationError::InsufficientReceipts)
+    }
+  }
+
+  pub fn verify_read_latest(
+    &self,
+    verifier_state: &VerifierState,
+    handle_bytes: &[u8],
     block_bytes: &[u8],
     nonces_bytes: &[u8],
     nonce_bytes: &[u8],
@@ -525,15 +1729,33 @@ impl Receipts {
       &NimbleDigest::digest(block_bytes).to_bytes(),
       hash_nonces_bytes,
     );
+    self.verify_block_hash(
+      verifier_state,
+      handle_bytes,
+      &block_hash,
+      expected_height,
+      nonce_bytes,
+    )
+  }
 
+  /// like `verify`, but for an entry known only by its aggregated block hash
+  pub fn verify_block_hash(
+    &self,
+    verifier_state: &VerifierState,
+    handle_bytes: &[u8],
+    block_hash: &NimbleDigest,
+    expected_height: Option<usize>,
+    nonce_bytes: Option<&[u8]>,
+  ) -> Result<usize, VerificationError> {
     for (ex_meta_block, id_sigs) in &self.receipts {
       let pks = verifier_state.get_pks_for_view(ex_meta_block.get_view())?;
-      if id_sigs.len() < pks.len() / 2 + 1 {
+      let quorum = verifier_state.get_quorum_for_view(ex_meta_block.get_view())?;
+      if id_sigs.len() < quorum {
         continue;
       }
 
       // check the block hash matches with the block
-      if block_hash != *ex_meta_block.get_metablock().get_block_hash() {
+      if block_hash != ex_meta_block.get_metablock().get_block_hash() {
         return Err(VerificationError::InvalidBlockHash);
       }
       // check the height matches with the expected height
@@ -548,23 +1770,18 @@ impl Receipts {
         None => ex_meta_block.get_metablock().hash(),
       };
 
-      let message = verifier_state.get_group_identity().digest_with(
-        &ex_meta_block
-          .get_view()
-          .digest_with(&NimbleDigest::digest(handle_bytes).digest_with(&tail_hash)),
+      let message = compute_ledger_tail_message(
+        verifier_state.get_group_identity(),
+        ex_meta_block.get_view(),
+        &NimbleDigest::digest(handle_bytes),
+        &tail_hash,
       );
 
-      let mut num_receipts = 0;
-      for id_sig in id_sigs {
-        id_sig
-          .verify(&message.to_bytes())
-          .map_err(|_e| VerificationError::InvalidSignature)?;
-        if pks.contains(id_sig.get_id()) {
-          num_receipts += 1;
-        }
-      }
+      IdSig::verify_batch(id_sigs, &message.to_bytes())
+        .map_err(|_e| VerificationError::InvalidSignature)?;
+      let num_receipts = count_distinct_signers(id_sigs, pks);
 
-      if num_receipts > pks.len() / 2 {
+      if num_receipts >= quorum {
         return Ok(ex_meta_block.get_metablock().get_height());
       }
     }
@@ -606,10 +1823,14 @@ impl Receipts {
 
     // retrieve public keys of endorsers in the configuration
     let new_pks = retrieve_public_keys_from_config(new_config)?;
-    let old_pks = if old_metablock.get_height() > 0 {
-      retrieve_public_keys_from_config(old_config)?
+    let new_quorum = retrieve_quorum_from_config(new_config)?;
+    let (old_pks, old_quorum) = if old_metablock.get_height() > 0 {
+      (
+        retrieve_public_keys_from_config(old_config)?,
+        retrieve_quorum_from_config(old_config)?,
+      )
     } else {
-      HashSet::new()
+      (HashSet::new(), 0)
     };
 
     if new_pks.len() < MIN_NUM_ENDORSERS {
@@ -623,19 +1844,27 @@ impl Receipts {
     }
 
     // check the configs match with block hash
-    if NimbleDigest::digest(old_config) != *old_metablock.get_block_hash()
-      || NimbleDigest::digest(new_config) != *new_metablock.get_block_hash()
+    let new_config_hash = compute_view_block_hash(new_config)?;
+    if compute_view_block_hash(old_config)? != *old_metablock.get_block_hash()
+      || new_config_hash != *new_metablock.get_block_hash()
     {
       eprintln!("config doesn't match block hash");
       return Err(VerificationError::InvalidBlockHash);
     }
 
     // check group identity
-    if old_metablock.get_height() == 0 && NimbleDigest::digest(new_config) != *group_identity {
+    if old_metablock.get_height() == 0 && new_config_hash != *group_identity {
       eprintln!("group identity doesn't match with the config");
       return Err(VerificationError::InvalidGroupIdentity);
     }
 
+    // check that the old keys of rotated endorsers handed their places over to the new keys
+    let (_endorsers, rotations) = decode_view_config(new_config)?;
+    verify_key_rotations(group_identity, &rotations, Some(&old_pks), &new_pks).map_err(|e| {
+      eprintln!("key rotation is invalid");
+      e
+    })?;
+
     // compute max cut
     let max_cut_hash = if ledger_tail_maps.len() == 1 {
       produce_hash_of_state(&ledger_tail_maps[0].entries)
@@ -655,12 +1884,13 @@ impl Receipts {
       }
     }
 
-    let mut ledger_entries: HashMap<(Vec<u8>, u64), Vec<u8>> = HashMap::new();
+    let mut ledger_entries: HashMap<(Bytes, u64), Vec<u8>> = HashMap::new();
     let cut_diffs = compute_cut_diffs(ledger_tail_maps);
     let mut i: usize = 0;
     let mut j: usize = 0;
     while i < cut_diffs.len() && j < ledger_chunks.len() {
       if cut_diffs[i].low == cut_diffs[i].high {
+        i += 1;
         continue;
       }
       if cut_diffs[i].handle.cmp(&ledger_chunks[j].handle) != Ordering::Equal
@@ -696,6 +1926,9 @@ impl Receipts {
       j += 1;
     }
 
+    while i < cut_diffs.len() && cut_diffs[i].low == cut_diffs[i].high {
+      i += 1;
+    }
     if i != cut_diffs.len() || j != ledger_chunks.len() {
       eprintln!("incorrect information for comparing cuts");
       return Err(VerificationError::InconsistentLedgerTailMaps);
@@ -705,7 +1938,7 @@ impl Receipts {
       for entry in &ledger_tail_map.entries {
         let res = ledger_entries.get(&(entry.handle.clone(), entry.height));
         if let Some(metablock) = res {
-          if entry.metablock.cmp(metablock) != Ordering::Equal {
+          if entry.metablock[..].cmp(&metablock[..]) != Ordering::Equal {
             eprintln!("metablock1={:?}", entry.metablock);
             eprintln!("metablock2={:?}", metablock);
             return Err(VerificationError::InconsistentLedgerTailMaps);
@@ -714,8 +1947,9 @@ impl Receipts {
       }
     }
 
-    let mut num_receipts_for_old_pks = 0;
-    let mut num_receipts_for_new_pks = 0;
+    // count distinct keys so that a key that signed more than once is counted once
+    let mut old_signers = HashSet::<&Vec<u8>>::new();
+    let mut new_signers = HashSet::<&Vec<u8>>::new();
     let mut used_ledger_tail_maps = HashSet::<NimbleDigest>::new();
 
     let new_metablock_hash = new_metablock.hash();
@@ -736,22 +1970,28 @@ impl Receipts {
           VerificationError::InvalidSignature
         })?;
 
-        if new_pks.contains(id_sig.get_id()) {
-          if *ex_meta_block.get_view() != max_cut_hash {
+        // an endorser that stays across the view change signs twice: once over its own state when
+        // it finalizes the old view, and once over the max cut when it joins the new view
+        let is_new = new_pks.contains(id_sig.get_id());
+        let is_old = old_pks.contains(id_sig.get_id());
+
+        if is_new {
+          if *ex_meta_block.get_view() == max_cut_hash {
+            new_signers.insert(id_sig.get_id());
+          } else if !is_old {
             eprintln!("the hashed state is invalid");
             return Err(VerificationError::InvalidView);
           }
-          num_receipts_for_new_pks += 1;
         }
 
-        if old_pks.contains(id_sig.get_id()) {
+        if is_old {
           if state_hashes.contains(ex_meta_block.get_view()) {
             used_ledger_tail_maps.insert(*ex_meta_block.get_view());
-          } else {
+            old_signers.insert(id_sig.get_id());
+          } else if !is_new {
             eprintln!("ledger tail map is missing");
             return Err(VerificationError::MissingLedgerTailMap);
           }
-          num_receipts_for_old_pks += 1;
         }
       }
     }
@@ -761,12 +2001,12 @@ impl Receipts {
       return Err(VerificationError::RedundantLedgerTailMap);
     }
 
-    if old_metablock.get_height() > 0 && num_receipts_for_old_pks < old_pks.len() / 2 + 1 {
+    if old_metablock.get_height() > 0 && old_signers.len() < old_quorum {
       eprintln!("insufficent receipts from old config");
       return Err(VerificationError::InsufficientReceipts);
     }
 
-    if num_receipts_for_new_pks < new_pks.len() / 2 + 1 {
+    if new_signers.len() < new_quorum {
       eprintln!("insufficent receipts from new config");
       return Err(VerificationError::InsufficientReceipts);
     }
@@ -784,9 +2024,10 @@ impl Receipts {
       return Err(VerificationError::InsufficientReceipts);
     }
 
-    let config_hash = NimbleDigest::digest(config);
+    let config_hash = compute_view_block_hash(config)?;
 
     let pks = retrieve_public_keys_from_config(config)?;
+    let quorum = retrieve_quorum_from_config(config)?;
 
     for (ex_meta_block, id_sigs) in &self.receipts {
       if config_hash != *ex_meta_block.get_metablock().get_block_hash() {
@@ -799,7 +2040,7 @@ impl Receipts {
           .digest_with(&ex_meta_block.get_metablock().hash()),
       );
 
-      let mut num_receipts = 0;
+      let mut signers = HashSet::new();
       for id_sig in id_sigs {
         let id = id_sig.get_id();
 
@@ -811,10 +2052,11 @@ impl Receipts {
           continue;
         }
 
-        num_receipts += 1;
+        signers.insert(id);
       }
+      let num_receipts = signers.len();
 
-      if num_receipts * 2 > pks.len() {
+      if num_receipts >= quorum {
         let is_verified = if let Some(attestation_reports) = attestations {
           attestation_reports == "THIS IS A PLACE HOLDER FOR ATTESTATION".as_bytes().to_vec()
         } else {
@@ -838,18 +2080,24 @@ pub struct VerifierState {
   // In our context, we don't need views to be ordered, so we use a HashMap
   // However, we require that a new view is "authorized" by the latest view, so we keep track of the latest_view in a separate variable
   vk_map: HashMap<NimbleDigest, HashSet<Vec<u8>>>,
+  /// the quorum of each view that was applied, as its view block records it
+  quorums: HashMap<NimbleDigest, usize>,
   group_identity: NimbleDigest,
   view_ledger_height: usize,
   verified_views: HashSet<NimbleDigest>,
+  /// a map from the new key of a rotated endorser to its old key
+  rotated_keys: HashMap<Vec<u8>, Vec<u8>>,
 }
 
 impl VerifierState {
   pub fn new() -> Self {
     VerifierState {
       vk_map: HashMap::new(),
+      quorums: HashMap::new(),
       group_identity: NimbleDigest::default(),
       view_ledger_height: 0,
       verified_views: HashSet::new(),
+      rotated_keys: HashMap::new(),
     }
   }
 
@@ -868,6 +2116,16 @@ impl VerifierState {
     }
   }
 
+  /// the number of endorsers of `view` whose signatures make a receipt valid: the quorum that the
+  /// view block of `view` records, or a majority of its endorsers
+  pub fn get_quorum_for_view(&self, view: &NimbleDigest) -> Result<usize, VerificationError> {
+    let pks = self.get_pks_for_view(view)?;
+    Ok(match self.quorums.get(view) {
+      Some(quorum) => *quorum,
+      None => majority_quorum(pks.len()),
+    })
+  }
+
   pub fn get_group_identity(&self) -> &NimbleDigest {
     &self.group_identity
   }
@@ -880,6 +2138,25 @@ impl VerifierState {
     self.verified_views.contains(view)
   }
 
+  /// the key that an applied view change rotated the endorser with `pk` from, if any
+  pub fn get_rotated_key(&self, pk: &[u8]) -> Option<&Vec<u8>> {
+    self.rotated_keys.get(pk)
+  }
+
+  /// the first key of the endorser with `pk`, following its rotations back, so that the receipts
+  /// that it signed under any of its keys are attributed to the same endorser
+  pub fn get_original_key(&self, pk: &[u8]) -> Vec<u8> {
+    let mut key = pk.to_vec();
+    // bounded, in case the endorsers rotated back to a key they held before
+    for _ in 0..self.rotated_keys.len() {
+      match self.rotated_keys.get(&key) {
+        Some(old_pk) => key = old_pk.clone(),
+        None => break,
+      }
+    }
+    key
+  }
+
   pub fn apply_view_change(
     &mut self,
     config: &[u8],
@@ -889,18 +2166,26 @@ impl VerifierState {
     let receipts =
       Receipts::from_bytes(receipts_bytes).map_err(|_e| VerificationError::InvalidReceipt)?;
 
-    let res = receipts.verify_view_change_receipts(self, config, attestations);
-    match res {
-      Ok((meta_block, pks)) => {
-        self.verified_views.insert(*meta_block.get_prev());
-        self.vk_map.insert(meta_block.hash(), pks);
-        if self.view_ledger_height < meta_block.get_height() {
-          self.view_ledger_height = meta_block.get_height();
-        }
-        Ok(())
-      },
-      Err(e) => Err(e),
+    let (meta_block, pks) = receipts.verify_view_change_receipts(self, config, attestations)?;
+    let (_endorsers, rotations) = decode_view_config(config)?;
+    let quorum = retrieve_quorum_from_config(config)?;
+    verify_key_rotations(
+      &self.group_identity,
+      &rotations,
+      self.vk_map.get(meta_block.get_prev()),
+      &pks,
+    )?;
+
+    self.verified_views.insert(*meta_block.get_prev());
+    self.vk_map.insert(meta_block.hash(), pks);
+    self.quorums.insert(meta_block.hash(), quorum);
+    for rotation in rotations {
+      self.rotated_keys.insert(rotation.new_pk, rotation.old_pk);
     }
+    if self.view_ledger_height < meta_block.get_height() {
+      self.view_ledger_height = meta_block.get_height();
+    }
+    Ok(())
   }
 
   pub fn verify_new_ledger(
@@ -908,13 +2193,26 @@ impl VerifierState {
     handle_bytes: &[u8],
     block_bytes: &[u8],
     receipts_bytes: &[u8],
+  ) -> Result<(), VerificationError> {
+    self.verify_new_ledger_with_metadata(handle_bytes, block_bytes, &[], receipts_bytes)
+  }
+
+  /// verifies the receipts of a ledger created with `metadata`, which they cover through the
+  /// genesis block (see `compute_genesis_block`)
+  pub fn verify_new_ledger_with_metadata(
+    &self,
+    handle_bytes: &[u8],
+    block_bytes: &[u8],
+    metadata: &[u8],
+    receipts_bytes: &[u8],
   ) -> Result<(), VerificationError> {
     let receipts =
       Receipts::from_bytes(receipts_bytes).map_err(|_e| VerificationError::InvalidReceipt)?;
+    let genesis_block = compute_genesis_block(block_bytes, metadata);
     let res = receipts.verify(
       self,
       handle_bytes,
-      block_bytes,
+      &genesis_block.to_bytes(),
       &NimbleDigest::default().to_bytes(),
       Some(0),
       None,
@@ -962,6 +2260,18 @@ impl VerifierState {
     receipts.verify_read_latest(self, handle_bytes, block_bytes, nonces_bytes, nonce_bytes)
   }
 
+  /// verifies the receipts of a read of the view ledger tail with `nonce_bytes`; the tail must
+  /// already have been applied with `apply_view_change`
+  pub fn verify_view_tail(
+    &self,
+    nonce_bytes: &[u8],
+    receipts_bytes: &[u8],
+  ) -> Result<usize, VerificationError> {
+    let receipts =
+      Receipts::from_bytes(receipts_bytes).map_err(|_e| VerificationError::InvalidReceipt)?;
+    receipts.verify_view_tail(self, nonce_bytes)
+  }
+
   pub fn verify_read_by_index(
     &self,
     handle_bytes: &[u8],
@@ -986,6 +2296,68 @@ impl VerifierState {
       Err(e) => Err(e),
     }
   }
+
+  /// verifies the entries at heights `from..from + entries.len()` of a ledger, given as (block,
+  /// nonces) pairs: the metablock chain is recomputed from `checkpoint_bytes`, the metablock at
+  /// `from - 1` (empty if `from` is 0), over the entries and then over `block_hashes`, the block
+  /// hashes of the later entries, up to the tail that `tail_receipts_bytes` endorse. Returns the
+  /// height of that tail.
+  pub fn verify_read_range(
+    &self,
+    handle_bytes: &[u8],
+    from: usize,
+    entries: &[(Vec<u8>, Vec<u8>)],
+    checkpoint_bytes: &[u8],
+    block_hashes: &[Vec<u8>],
+    tail_receipts_bytes: &[u8],
+  ) -> Result<usize, VerificationError> {
+    let checkpoint = if from == 0 {
+      if !checkpoint_bytes.is_empty() {
+        return Err(VerificationError::InvalidMetaBlock);
+      }
+      None
+    } else {
+      let metablock = MetaBlock::from_bytes(checkpoint_bytes)
+        .map_err(|_e| VerificationError::InvalidMetaBlock)?;
+      Some(metablock)
+    };
+
+    let mut chain = Vec::with_capacity(entries.len() + block_hashes.len());
+    for (block_bytes, nonces_bytes) in entries {
+      chain.push(compute_aggregated_block_hash(
+        &NimbleDigest::digest(block_bytes).to_bytes(),
+        &NimbleDigest::digest(nonces_bytes).to_bytes(),
+      ));
+    }
+    for block_hash_bytes in block_hashes {
+      let block_hash = NimbleDigest::from_bytes(block_hash_bytes)
+        .map_err(|_e| VerificationError::InvalidBlockHash)?;
+      chain.push(block_hash);
+    }
+    let tail_block_hash = *chain.last().ok_or(VerificationError::InvalidBlockHash)?;
+    let tail_height = from
+      .checked_add(chain.len() - 1)
+      .ok_or(VerificationError::InvalidHeight)?;
+
+    let tail_receipts =
+      Receipts::from_bytes(tail_receipts_bytes).map_err(|_e| VerificationError::InvalidReceipt)?;
+    tail_receipts.verify_block_hash(
+      self,
+      handle_bytes,
+      &tail_block_hash,
+      Some(tail_height),
+      None,
+    )?;
+    let tail_metablock = tail_receipts.get_metablock()?;
+
+    let chain = chain
+      .into_iter()
+      .enumerate()
+      .map(|(i, block_hash)| (block_hash, from + i))
+      .collect::<Vec<(NimbleDigest, usize)>>();
+    verify_chain(checkpoint.as_ref(), &chain, &tail_metablock.hash())?;
+    Ok(tail_height)
+  }
 }
 
 pub fn compute_max_cut(ledger_tail_maps: &Vec<LedgerTailMap>) -> Vec<LedgerTailMapEntry> {
@@ -1030,7 +2402,7 @@ pub fn compute_max_cut(ledger_tail_maps: &Vec<LedgerTailMap>) -> Vec<LedgerTailM
 }
 
 pub struct CutDiff {
-  pub handle: Vec<u8>,
+  pub handle: Bytes,
   pub hash: NimbleDigest,
   pub low: usize,
   pub high: usize,
@@ -1061,6 +2433,8 @@ pub fn compute_cut_diffs(ledger_tail_maps: &Vec<LedgerTailMap>) -> Vec<CutDiff>
             } else if (ledger_tail_map.entries[j].height as usize) > cut_diffs[i].high {
               cut_diffs[i].high = ledger_tail_map.entries[j].height as usize;
             }
+            i += 1;
+            j += 1;
           },
           Ordering::Greater => {
             cut_diffs.insert(
@@ -1102,6 +2476,47 @@ pub enum CustomSerdeError {
   IncorrectLength,
   /// returned if deserializing any byte entry into the Rust type fails
   InternalError,
+  /// returned if the encoding carries a version tag that is not supported
+  UnsupportedVersion,
+  /// returned if the encoding contains the same entry more than once
+  DuplicateEntry,
+  /// returned if the encoding contains two different signatures by the same public key
+  ConflictingEntry,
+  /// returned if a string is not a valid (even-length) hex encoding
+  InvalidHex,
+  /// returned if a block exceeds `MAX_BLOCK_SIZE`
+  BlockTooLarge,
+  /// returned if a public key or a signature in a receipt cannot be parsed
+  InvalidIdSig(CryptoError),
+}
+
+impl fmt::Display for CustomSerdeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CustomSerdeError::IncorrectLength => write!(f, "byte array has an incorrect length"),
+      CustomSerdeError::InternalError => write!(f, "failed to deserialize a byte entry"),
+      CustomSerdeError::UnsupportedVersion => write!(f, "unsupported encoding version"),
+      CustomSerdeError::DuplicateEntry => write!(f, "encoding contains a duplicate entry"),
+      CustomSerdeError::ConflictingEntry => {
+        write!(
+          f,
+          "encoding contains conflicting signatures by the same key"
+        )
+      },
+      CustomSerdeError::InvalidHex => write!(f, "invalid hex encoding"),
+      CustomSerdeError::BlockTooLarge => write!(f, "block exceeds the maximum block size"),
+      CustomSerdeError::InvalidIdSig(e) => write!(f, "invalid signer in receipt: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for CustomSerdeError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      CustomSerdeError::InvalidIdSig(e) => Some(e),
+      _ => None,
+    }
+  }
 }
 
 pub trait CustomSerde
@@ -1118,10 +2533,7 @@ impl CustomSerde for Nonce {
   }
 
   fn from_bytes(bytes: &[u8]) -> Result<Nonce, CustomSerdeError> {
-    match Nonce::new(bytes) {
-      Ok(nonce) => Ok(nonce),
-      Err(_) => Err(CustomSerdeError::IncorrectLength),
-    }
+    Nonce::try_from_bytes(bytes)
   }
 }
 impl CustomSerde for Nonces {
@@ -1204,7 +2616,9 @@ impl CustomSerde for MetaBlock {
         bytes[2 * digest_len..]
           .try_into()
           .map_err(|_| CustomSerdeError::IncorrectLength)?,
-      ) as usize;
+      );
+      // a truncated height would decode two encodings to the same metablock on 32-bit targets
+      let height = usize::try_from(height).map_err(|_| CustomSerdeError::InternalError)?;
       Ok(MetaBlock {
         prev,
         block_hash,
@@ -1218,7 +2632,7 @@ impl CustomSerde for IdSig {
   fn to_bytes(&self) -> Vec<u8> {
     let mut bytes = Vec::new();
     bytes.extend(&self.id);
-    bytes.extend(&self.sig);
+    bytes.extend(&self.sig.to_bytes());
     bytes
   }
 
@@ -1231,10 +2645,10 @@ impl CustomSerde for IdSig {
       );
       return Err(CustomSerdeError::IncorrectLength);
     }
-    let id = bytes[0..PublicKey::num_bytes()].to_vec();
-    let sig = bytes[PublicKey::num_bytes()..].to_vec();
-
-    Ok(IdSig { id, sig })
+    IdSig::from_raw(
+      &bytes[0..PublicKey::num_bytes()],
+      &bytes[PublicKey::num_bytes()..],
+    )
   }
 }
 
@@ -1270,39 +2684,208 @@ impl CustomSerde for Receipt {
   }
 }
 
-impl CustomSerde for Receipts {
+/// The layout is `u8 scheme || bincode keys`, with the keys as (identity key, key, proof of
+/// possession) in ascending order of the identity keys
+impl CustomSerde for SchemeKeys {
+  fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = vec![self.scheme.to_u8()];
+    bytes.extend(bincode::serialize(&self.keys).unwrap());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<SchemeKeys, CustomSerdeError> {
+    let (keys, rest) = split_scheme_keys(bytes)?;
+    if !rest.is_empty() {
+      return Err(CustomSerdeError::IncorrectLength);
+    }
+    Ok(keys)
+  }
+}
+
+/// The layout is the view, the metablock, the length of the signer bitmap as u32 LE, the bitmap,
+/// and the aggregated signature
+impl CustomSerde for AggregatedReceipt {
   fn to_bytes(&self) -> Vec<u8> {
     let mut bytes = Vec::new();
-    for (ex_meta_block, id_sigs) in &self.receipts {
-      for id_sig in id_sigs {
-        bytes.extend(
-          Receipt::new(
-            *ex_meta_block.get_view(),
-            ex_meta_block.get_metablock().clone(),
-            id_sig.clone(),
-          )
-          .to_bytes(),
-        );
+    bytes.extend(&self.view.to_bytes());
+    bytes.extend(&self.metablock.to_bytes());
+    bytes.extend(&(self.signers.len() as u32).to_le_bytes());
+    bytes.extend(&self.signers);
+    bytes.extend(&self.signature);
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<AggregatedReceipt, CustomSerdeError> {
+    let mut pos = 0;
+    let view = NimbleDigest::from_bytes(read_slice(bytes, &mut pos, NimbleDigest::num_bytes())?)?;
+    let metablock = MetaBlock::from_bytes(read_slice(bytes, &mut pos, MetaBlock::num_bytes())?)?;
+    let num_signers = read_u32_le(bytes, &mut pos)? as usize;
+    let signers = read_slice(bytes, &mut pos, num_signers)?.to_vec();
+    let signature = bytes[pos..].to_vec();
+    if signers.is_empty() || signature.is_empty() {
+      return Err(CustomSerdeError::IncorrectLength);
+    }
+    Ok(AggregatedReceipt {
+      view,
+      metablock,
+      signers,
+      signature,
+    })
+  }
+}
+
+/// Version tag prefixed to the canonical encoding of `Receipts`
+const RECEIPTS_ENCODING_VERSION: u8 = 1;
+
+fn read_u32_le(bytes: &[u8], pos: &mut usize) -> Result<u32, CustomSerdeError> {
+  let slice = read_slice(bytes, pos, std::mem::size_of::<u32>())?;
+  Ok(u32::from_le_bytes(
+    slice
+      .try_into()
+      .map_err(|_| CustomSerdeError::IncorrectLength)?,
+  ))
+}
+
+fn read_slice<'a>(
+  bytes: &'a [u8],
+  pos: &mut usize,
+  len: usize,
+) -> Result<&'a [u8], CustomSerdeError> {
+  let end = pos
+    .checked_add(len)
+    .ok_or(CustomSerdeError::IncorrectLength)?;
+  if end > bytes.len() {
+    return Err(CustomSerdeError::IncorrectLength);
+  }
+  let slice = &bytes[*pos..end];
+  *pos = end;
+  Ok(slice)
+}
+
+impl Receipts {
+  /// Parses the canonical encoding produced by `to_bytes`. The layout is a version byte,
+  /// the number of (view, metablock) groups, and for each group the view, the metablock,
+  /// the number of signatures, and length-prefixed (public key, signature) pairs.
+  /// Truncated or trailing data, unknown versions, duplicate groups, and conflicting signatures
+  /// by the same public key are rejected; a repeated (public key, signature) pair is kept once.
+  /// Receipts that stores hold from before the encoding was versioned are not accepted here;
+  /// they are decoded explicitly with `from_legacy_bytes`.
+  pub fn try_from_bytes(bytes: &[u8]) -> Result<Receipts, CustomSerdeError> {
+    let mut pos = 0;
+    let version = read_slice(bytes, &mut pos, 1)?[0];
+    if version != RECEIPTS_ENCODING_VERSION {
+      return Err(CustomSerdeError::UnsupportedVersion);
+    }
+
+    let num_groups = read_u32_le(bytes, &mut pos)?;
+    let mut receipts = Receipts::new();
+    for _ in 0..num_groups {
+      let view = NimbleDigest::from_bytes(read_slice(bytes, &mut pos, NimbleDigest::num_bytes())?)?;
+      let metablock = MetaBlock::from_bytes(read_slice(bytes, &mut pos, MetaBlock::num_bytes())?)?;
+      let ex_meta_block = ExtendedMetaBlock::new(&view, &metablock);
+      if receipts.receipts.contains_key(&ex_meta_block) {
+        return Err(CustomSerdeError::DuplicateEntry);
+      }
+
+      let num_id_sigs = read_u32_le(bytes, &mut pos)?;
+      let mut id_sigs: Vec<IdSig> = Vec::new();
+      // the index of the signature of each key, so that a group of many signatures is not checked
+      // for duplicates in quadratic time
+      let mut signed: HashMap<Vec<u8>, usize> = HashMap::new();
+      for _ in 0..num_id_sigs {
+        let id_len = read_u32_le(bytes, &mut pos)? as usize;
+        let id = read_slice(bytes, &mut pos, id_len)?;
+        let sig_len = read_u32_le(bytes, &mut pos)? as usize;
+        let sig = read_slice(bytes, &mut pos, sig_len)?;
+        let id_sig = IdSig::from_raw(id, sig)?;
+        // an identical pair is kept once; two signatures by the same key indicate equivocation
+        match signed.entry(id_sig.id.clone()) {
+          hash_map::Entry::Occupied(e) if id_sigs[*e.get()].sig == id_sig.sig => {},
+          hash_map::Entry::Occupied(_) => return Err(CustomSerdeError::ConflictingEntry),
+          hash_map::Entry::Vacant(e) => {
+            e.insert(id_sigs.len());
+            id_sigs.push(id_sig);
+          },
+        }
       }
+      receipts.receipts.insert(ex_meta_block, id_sigs);
     }
-    bytes
+
+    if pos != bytes.len() {
+      return Err(CustomSerdeError::IncorrectLength);
+    }
+
+    Ok(receipts)
   }
 
-  fn from_bytes(bytes: &[u8]) -> Result<Receipts, CustomSerdeError> {
+  /// Parses the encoding that preceded the versioned one: receipts of `Receipt::num_bytes()`
+  /// bytes each, back to back, in no particular order, so that no receipts encode as no bytes.
+  /// Conflicting signatures by the same public key are rejected as in the versioned encoding.
+  pub fn from_legacy_bytes(bytes: &[u8]) -> Result<Receipts, CustomSerdeError> {
     if bytes.len() % Receipt::num_bytes() != 0 {
       return Err(CustomSerdeError::IncorrectLength);
     }
-    let mut pos = 0;
     let mut receipts = Receipts::new();
-    while pos < bytes.len() {
-      let receipt = Receipt::from_bytes(&bytes[pos..pos + Receipt::num_bytes()])?;
+    for receipt_bytes in bytes.chunks(Receipt::num_bytes()) {
+      let receipt = Receipt::from_bytes(receipt_bytes)?;
+      let ex_meta_block = ExtendedMetaBlock::new(receipt.get_view(), receipt.get_metablock());
+      let id_sig = receipt.get_id_sig();
+      if let Some(id_sigs) = receipts.receipts.get(&ex_meta_block) {
+        if id_sigs
+          .iter()
+          .any(|existing| existing.id == id_sig.id && existing.sig != id_sig.sig)
+        {
+          return Err(CustomSerdeError::ConflictingEntry);
+        }
+      }
       receipts.add(&receipt);
-      pos += Receipt::num_bytes();
     }
     Ok(receipts)
   }
 }
 
+impl CustomSerde for Receipts {
+  fn to_bytes(&self) -> Vec<u8> {
+    // sort groups and signatures so that equal sets of receipts encode identically
+    let mut groups = self
+      .receipts
+      .iter()
+      .map(|(ex_meta_block, id_sigs)| {
+        let mut id_sigs = id_sigs.iter().collect::<Vec<&IdSig>>();
+        id_sigs.sort_by(|a, b| a.id.cmp(&b.id));
+        (
+          [
+            ex_meta_block.get_view().to_bytes(),
+            ex_meta_block.get_metablock().to_bytes(),
+          ]
+          .concat(),
+          id_sigs,
+        )
+      })
+      .collect::<Vec<(Vec<u8>, Vec<&IdSig>)>>();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut bytes = vec![RECEIPTS_ENCODING_VERSION];
+    bytes.extend(&(groups.len() as u32).to_le_bytes());
+    for (ex_meta_block_bytes, id_sigs) in groups {
+      bytes.extend(&ex_meta_block_bytes);
+      bytes.extend(&(id_sigs.len() as u32).to_le_bytes());
+      for id_sig in id_sigs {
+        bytes.extend(&(id_sig.id.len() as u32).to_le_bytes());
+        bytes.extend(&id_sig.id);
+        let sig = id_sig.sig.to_bytes();
+        bytes.extend(&(sig.len() as u32).to_le_bytes());
+        bytes.extend(&sig);
+      }
+    }
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Receipts, CustomSerdeError> {
+    Receipts::try_from_bytes(bytes)
+  }
+}
+
 pub trait NimbleHashTrait
 where
   Self: Sized,
@@ -1318,7 +2901,11 @@ impl NimbleHashTrait for Block {
 
 impl NimbleHashTrait for MetaBlock {
   fn hash(&self) -> NimbleDigest {
-    NimbleDigest::digest(&self.to_bytes())
+    NimbleDigest::digest_parts(&[
+      self.prev.digest.as_slice(),
+      self.block_hash.digest.as_slice(),
+      &(self.height as u64).to_le_bytes(),
+    ])
   }
 }
 
@@ -1331,7 +2918,7 @@ impl NimbleHashTrait for Nonces {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use rand::Rng;
+  use rand::{seq::SliceRandom, Rng};
 
   #[test]
   pub fn test_nimble_digest_equality() {
@@ -1345,6 +2932,8 @@ mod tests {
     assert_eq!(nimble_digest_1, nimble_digest_1_dupe);
   }
 
+  // the expected values below are SHA-256 test vectors
+  #[cfg(not(feature = "blake3"))]
   #[test]
   pub fn test_nimble_digest_hash_correctness_and_equality() {
     let message_1 = "1".as_bytes();
@@ -1373,6 +2962,8 @@ mod tests {
     );
   }
 
+  // the expected values below are SHA-256 test vectors
+  #[cfg(not(feature = "blake3"))]
   #[test]
   pub fn test_block_hash_results() {
     let message_1 = "1".as_bytes();
@@ -1389,22 +2980,1492 @@ mod tests {
     assert_eq!(block_1_hash.to_bytes(), expected_hash_message_1_op.unwrap());
   }
 
+  // a valid public key with a random (and hence invalid, but well-formed) signature
+  fn random_id_sig() -> IdSig {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let mut rng = rand::thread_rng();
+    let sig = (0..Signature::num_bytes())
+      .map(|_| rng.gen::<u8>())
+      .collect::<Vec<u8>>();
+    IdSig::new(
+      PrivateKey::new().get_public_key().unwrap(),
+      Signature::from_bytes(&sig).unwrap(),
+    )
+  }
+
+  fn random_receipts(num_groups: usize, num_id_sigs: usize) -> Receipts {
+    let mut receipts = Receipts::new();
+    for _ in 0..num_groups {
+      let view = NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>());
+      let metablock = MetaBlock::new(
+        &NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>()),
+        &NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>()),
+        rand::thread_rng().gen::<u32>() as usize,
+      );
+      for _ in 0..num_id_sigs {
+        receipts.add(&Receipt::new(view, metablock.clone(), random_id_sig()));
+      }
+    }
+    receipts
+  }
+
   #[test]
-  pub fn test_hash_of_state() {
-    let map = (0..1024 * 1023)
-      .map(|i: usize| {
-        let handle = NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>());
-        let metablock = NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>());
-        LedgerTailMapEntry {
-          handle: handle.to_bytes(),
-          metablock: metablock.to_bytes(),
-          height: i as u64,
-          block: vec![],
-          nonces: vec![],
-        }
+  pub fn test_receipts_round_trip() {
+    for _ in 0..64 {
+      let num_groups = rand::thread_rng().gen_range(0..4);
+      let num_id_sigs = rand::thread_rng().gen_range(1..8);
+      let receipts = random_receipts(num_groups, num_id_sigs);
+      let bytes = receipts.to_bytes();
+      let decoded = Receipts::try_from_bytes(&bytes).unwrap();
+      assert_eq!(decoded.to_bytes(), bytes);
+      assert_eq!(decoded.get().len(), num_groups);
+    }
+  }
+
+  // the expected values below are SHA-256 test vectors
+  #[cfg(not(feature = "blake3"))]
+  #[test]
+  pub fn test_receipts_golden_encoding() {
+    let view = NimbleDigest::from_bytes(&[1u8; 32]).unwrap();
+    let metablock = MetaBlock::new(
+      &NimbleDigest::from_bytes(&[2u8; 32]).unwrap(),
+      &NimbleDigest::from_bytes(&[3u8; 32]).unwrap(),
+      5,
+    );
+    let mut receipts = Receipts::new();
+    // insert out of order to check that the encoding sorts by public key
+    for (pk, sig) in golden_public_keys().iter().rev().zip([5u8, 7u8]) {
+      let id_sig = IdSig::new(pk.clone(), Signature::from_bytes(&[sig; 64]).unwrap());
+      receipts.add(&Receipt::new(view, metablock.clone(), id_sig));
+    }
+
+    let bytes = receipts.to_bytes();
+    assert_eq!(bytes.len(), 323);
+    assert_eq!(bytes[0..5], [1u8, 1, 0, 0, 0]);
+    assert_eq!(
+      NimbleDigest::digest(&bytes).to_bytes(),
+      hex::decode("124c279362e78d62d120eab266dbfcc2c329a79cfcd38cc7a758f6ee659b505a").unwrap()
+    );
+
+    let empty = Receipts::new().to_bytes();
+    assert_eq!(empty, vec![1u8, 0, 0, 0, 0]);
+    assert!(Receipts::try_from_bytes(&empty).unwrap().is_empty());
+  }
+
+  #[test]
+  pub fn test_receipts_malformed_encoding() {
+    let bytes = random_receipts(2, 3).to_bytes();
+
+    // truncated data is rejected at every length
+    for len in 0..bytes.len() {
+      assert_eq!(
+        Receipts::try_from_bytes(&bytes[0..len]).unwrap_err(),
+        CustomSerdeError::IncorrectLength
+      );
+    }
+
+    // trailing data is rejected
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+      Receipts::try_from_bytes(&trailing).unwrap_err(),
+      CustomSerdeError::IncorrectLength
+    );
+
+    // unknown versions are rejected
+    let mut unknown_version = bytes;
+    unknown_version[0] = 2;
+    assert_eq!(
+      Receipts::try_from_bytes(&unknown_version).unwrap_err(),
+      CustomSerdeError::UnsupportedVersion
+    );
+
+    // duplicate groups are rejected
+    let receipts = random_receipts(1, 1);
+    let mut duplicate_group = receipts.to_bytes();
+    let group_bytes = duplicate_group[5..].to_vec();
+    duplicate_group.extend(&group_bytes);
+    duplicate_group[1] = 2;
+    assert_eq!(
+      Receipts::try_from_bytes(&duplicate_group).unwrap_err(),
+      CustomSerdeError::DuplicateEntry
+    );
+  }
+
+  #[test]
+  pub fn test_receipts_legacy_encoding() {
+    let legacy = |receipts: &[Receipt]| {
+      receipts
+        .iter()
+        .flat_map(|receipt| receipt.to_bytes())
+        .collect::<Vec<u8>>()
+    };
+    let receipts = random_receipts(2, 3);
+    let flattened = receipts
+      .get()
+      .iter()
+      .flat_map(|(ex_meta_block, id_sigs)| {
+        id_sigs.iter().map(move |id_sig| {
+          Receipt::new(
+            *ex_meta_block.get_view(),
+            ex_meta_block.get_metablock().clone(),
+            id_sig.clone(),
+          )
+        })
       })
-      .collect::<Vec<LedgerTailMapEntry>>();
-    let hash = produce_hash_of_state(&map);
-    assert_ne!(hash, NimbleDigest::default());
+      .collect::<Vec<Receipt>>();
+    let decoded = Receipts::from_legacy_bytes(&legacy(&flattened)).unwrap();
+    assert_eq!(decoded.to_bytes(), receipts.to_bytes());
+
+    // no receipts were no bytes
+    assert!(Receipts::from_legacy_bytes(&[]).unwrap().is_empty());
+
+    // the versioned decoding does not fall back to the legacy one
+    assert!(Receipts::try_from_bytes(&legacy(&flattened)).is_err());
+
+    // a legacy encoding may start with the version, as the view of its first receipt does
+    let view = NimbleDigest::from_bytes(&[RECEIPTS_ENCODING_VERSION; 32]).unwrap();
+    let metablock = MetaBlock::new(
+      &NimbleDigest::from_bytes(&[2u8; 32]).unwrap(),
+      &NimbleDigest::from_bytes(&[3u8; 32]).unwrap(),
+      5,
+    );
+    let receipt = Receipt::new(view, metablock.clone(), random_id_sig());
+    let decoded = Receipts::from_legacy_bytes(&legacy(&[receipt.clone()])).unwrap();
+    let mut expected = Receipts::new();
+    expected.add(&receipt);
+    assert_eq!(decoded.to_bytes(), expected.to_bytes());
+
+    // the same public key with a different signature is rejected
+    let conflicting = Receipt::new(
+      view,
+      metablock,
+      IdSig::new(
+        receipt.get_id_sig().get_pk().clone(),
+        random_id_sig().get_sig().clone(),
+      ),
+    );
+    assert_eq!(
+      Receipts::from_legacy_bytes(&legacy(&[receipt, conflicting])).unwrap_err(),
+      CustomSerdeError::ConflictingEntry
+    );
+  }
+
+  #[test]
+  pub fn test_receipts_duplicate_signers() {
+    let num_id_sigs_pos = 5 + NimbleDigest::num_bytes() + MetaBlock::num_bytes();
+    let receipts = random_receipts(1, 1);
+
+    // a duplicate identical (public key, signature) pair is accepted once
+    let mut duplicate = receipts.to_bytes();
+    let id_sig_bytes = duplicate[num_id_sigs_pos + 4..].to_vec();
+    duplicate.extend(&id_sig_bytes);
+    duplicate[num_id_sigs_pos] = 2;
+    let decoded = Receipts::try_from_bytes(&duplicate).unwrap();
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded.to_bytes(), receipts.to_bytes());
+
+    // the same public key with a different signature is rejected
+    let mut conflicting = receipts.to_bytes();
+    let mut id_sig_bytes = id_sig_bytes;
+    let last = id_sig_bytes.len() - 1;
+    id_sig_bytes[last] ^= 1;
+    conflicting.extend(&id_sig_bytes);
+    conflicting[num_id_sigs_pos] = 2;
+    assert_eq!(
+      Receipts::try_from_bytes(&conflicting).unwrap_err(),
+      CustomSerdeError::ConflictingEntry
+    );
+
+    // receipts built from any shuffle of a multiset of receipts are identical
+    let pool = random_receipts(2, 3);
+    let mut multiset = pool
+      .get()
+      .iter()
+      .flat_map(|(ex_meta_block, id_sigs)| {
+        id_sigs.iter().map(move |id_sig| {
+          Receipt::new(
+            *ex_meta_block.get_view(),
+            ex_meta_block.get_metablock().clone(),
+            id_sig.clone(),
+          )
+        })
+      })
+      .collect::<Vec<Receipt>>();
+    multiset.extend(multiset.clone());
+    for _ in 0..16 {
+      multiset.shuffle(&mut rand::thread_rng());
+      let mut shuffled = Receipts::new();
+      for receipt in &multiset {
+        shuffled.add(receipt);
+      }
+      assert_eq!(shuffled.len(), 6);
+      assert_eq!(shuffled.to_bytes(), pool.to_bytes());
+    }
+  }
+
+  #[test]
+  pub fn test_receipts_typed_accessors() {
+    let receipts = random_receipts(2, 3);
+    assert_eq!(receipts.id_sigs().count(), 6);
+
+    let signers = receipts.signers();
+    assert_eq!(signers.len(), 6);
+    for (pk, sig) in receipts.id_sigs() {
+      assert!(signers.contains(pk));
+      assert_eq!(receipts.get_sig(pk), Some(sig));
+    }
+    let pk = golden_public_keys().remove(0);
+    assert!(receipts.get_sig(&pk).is_none());
+
+    // malformed public keys are rejected when a receipt is parsed
+    let sig = [1u8; 64];
+    assert_eq!(
+      IdSig::from_raw(&[0u8; 33], &sig).unwrap_err(),
+      CustomSerdeError::InvalidIdSig(CryptoError::NonCanonicalPublicKey)
+    );
+    assert!(IdSig::from_raw(&pk.to_bytes(), &sig).is_ok());
+    assert_eq!(
+      IdSig::from_raw(&pk.to_bytes(), &sig[1..]).unwrap_err(),
+      CustomSerdeError::InvalidIdSig(CryptoError::InvalidSignatureLength)
+    );
+    assert_eq!(
+      IdSig::from_bytes(&[vec![0u8; 33], sig.to_vec()].concat()).unwrap_err(),
+      CustomSerdeError::InvalidIdSig(CryptoError::NonCanonicalPublicKey)
+    );
+  }
+
+  #[test]
+  pub fn test_receipts_merge() {
+    // draw receipts from a small shared pool so that the merged sets overlap
+    let pool = random_receipts(3, 4);
+    let pool_receipts = pool
+      .get()
+      .iter()
+      .flat_map(|(ex_meta_block, id_sigs)| {
+        id_sigs.iter().map(move |id_sig| {
+          Receipt::new(
+            *ex_meta_block.get_view(),
+            ex_meta_block.get_metablock().clone(),
+            id_sig.clone(),
+          )
+        })
+      })
+      .collect::<Vec<Receipt>>();
+    let random_subset = || {
+      let mut receipts = Receipts::new();
+      for receipt in &pool_receipts {
+        if rand::thread_rng().gen::<bool>() {
+          receipts.add(receipt);
+        }
+      }
+      receipts
+    };
+
+    for _ in 0..64 {
+      let (a, b, c) = (random_subset(), random_subset(), random_subset());
+
+      let ab = a.merge(&b).unwrap();
+      let ba = b.merge(&a).unwrap();
+      assert_eq!(ab.to_bytes(), ba.to_bytes());
+
+      let ab_c = ab.merge(&c).unwrap();
+      let a_bc = a.merge(&b.merge(&c).unwrap()).unwrap();
+      assert_eq!(ab_c.to_bytes(), a_bc.to_bytes());
+
+      assert!(ab.len() >= a.len() && ab.len() >= b.len());
+      assert!(ab.len() <= a.len() + b.len());
+      for receipt in &pool_receipts {
+        let pk = receipt.get_id_sig().get_id();
+        assert_eq!(ab.contains(pk), a.contains(pk) || b.contains(pk));
+      }
+    }
+
+    assert_eq!(pool.len(), 12);
+    assert!(!Receipts::new().contains(pool_receipts[0].get_id_sig().get_id()));
+  }
+
+  #[test]
+  pub fn test_receipts_merge_conflicting_signatures() {
+    let receipts = random_receipts(1, 1);
+    let (ex_meta_block, id_sigs) = receipts.get().iter().next().unwrap();
+    let conflicting_id_sig = IdSig::new(id_sigs[0].get_pk().clone(), random_id_sig().sig);
+
+    let mut conflicting = Receipts::new();
+    conflicting.add(&Receipt::new(
+      *ex_meta_block.get_view(),
+      ex_meta_block.get_metablock().clone(),
+      conflicting_id_sig,
+    ));
+    assert_eq!(
+      receipts.merge(&conflicting).unwrap_err(),
+      VerificationError::ConflictingReceipts
+    );
+    assert_eq!(receipts.merge(&receipts).unwrap().len(), 1);
+  }
+
+  // the expected values below are SHA-256 test vectors
+  #[cfg(not(feature = "blake3"))]
+  #[test]
+  pub fn test_hex_display_and_from_str() {
+    let digest = NimbleDigest::digest("1".as_bytes());
+    let hex_str = "6b86b273ff34fce19d6b804eff5a3f5747ada4eaa22f1d49c01e52ddb7875b4b";
+    assert_eq!(digest.to_string(), hex_str);
+    assert_eq!(format!("{:?}", digest), "NimbleDigest(6b86b273…)");
+    assert_eq!(hex_str.parse::<Handle>().unwrap(), digest);
+    assert_eq!(format!("0x{}", hex_str).parse::<Handle>().unwrap(), digest);
+    assert_eq!(
+      hex_str.to_uppercase().parse::<NimbleDigest>().unwrap(),
+      digest
+    );
+
+    assert_eq!(
+      hex_str[1..].parse::<NimbleDigest>().unwrap_err(),
+      CustomSerdeError::InvalidHex
+    );
+    assert_eq!(
+      hex_str
+        .replace('6', "g")
+        .parse::<NimbleDigest>()
+        .unwrap_err(),
+      CustomSerdeError::InvalidHex
+    );
+    assert_eq!(
+      hex_str[2..].parse::<NimbleDigest>().unwrap_err(),
+      CustomSerdeError::IncorrectLength
+    );
+
+    let nonce = Nonce::try_from_bytes(&[0xab; 16]).unwrap();
+    assert_eq!(nonce.to_string(), "ab".repeat(16));
+    assert_eq!(format!("{:?}", nonce), "Nonce(abababab…)");
+    assert_eq!(nonce.to_string().parse::<Nonce>().unwrap(), nonce);
+    assert_eq!(
+      "0xabab".parse::<Nonce>().unwrap_err(),
+      CustomSerdeError::IncorrectLength
+    );
+  }
+
+  #[test]
+  pub fn test_verify_batch_identifies_corrupted_signature() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let message = NimbleDigest::digest(b"message").to_bytes();
+    for num_sigs in [1, 2, 3, 7, 15] {
+      let mut id_sigs = (0..num_sigs)
+        .map(|_| {
+          let sk = PrivateKey::new();
+          IdSig::new(sk.get_public_key().unwrap(), sk.sign(&message).unwrap())
+        })
+        .collect::<Vec<IdSig>>();
+      assert!(IdSig::verify_batch(&id_sigs, &message).is_ok());
+
+      // corrupt exactly one signature by replacing it with a signature on a different message
+      let culprit = num_sigs / 2;
+      let sk = PrivateKey::new();
+      id_sigs[culprit] = IdSig::new(
+        id_sigs[culprit].get_pk().clone(),
+        sk.sign(b"another message").unwrap(),
+      );
+      assert_eq!(IdSig::verify_batch(&id_sigs, &message), Err(culprit));
+      assert!(IdSig::verify_batch(&id_sigs[..culprit], &message).is_ok());
+      assert!(IdSig::verify_batch(&id_sigs[culprit + 1..], &message).is_ok());
+
+      // of two corrupted signatures, the batch reports the first, whichever fails first
+      if culprit + 1 < num_sigs {
+        id_sigs[num_sigs - 1] = IdSig::new(
+          id_sigs[num_sigs - 1].get_pk().clone(),
+          sk.sign(b"another message").unwrap(),
+        );
+        assert_eq!(IdSig::verify_batch(&id_sigs, &message), Err(culprit));
+      }
+    }
+  }
+
+  #[test]
+  pub fn test_receipt_verify_ledger_tail() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let sk = PrivateKey::new();
+    let pk = sk.get_public_key().unwrap();
+    let view = NimbleDigest::digest(b"view");
+    let handle = NimbleDigest::digest(b"handle");
+    let mut vs = VerifierState::new();
+    vs.set_group_identity(NimbleDigest::digest(b"group"));
+    vs.vk_map
+      .insert(view, [pk.to_bytes()].iter().cloned().collect());
+
+    let metablock = MetaBlock::genesis(&NimbleDigest::digest(b"block"));
+    let sign = |tail_hash: &NimbleDigest| {
+      let message = compute_ledger_tail_message(vs.get_group_identity(), &view, &handle, tail_hash);
+      IdSig::new(pk.clone(), sk.sign(&message.to_bytes()).unwrap())
+    };
+
+    let receipt = Receipt::new(view, metablock.clone(), sign(&metablock.hash()));
+    assert!(receipt.verify_ledger_tail(&vs, &handle, None).is_ok());
+    assert_eq!(
+      receipt.verify_ledger_tail(&vs, &NimbleDigest::digest(b"other"), None),
+      Err(VerificationError::InvalidSignature)
+    );
+
+    // receipts of reads are bound to the nonce
+    let nonce = Nonce::new().to_bytes();
+    let receipt = Receipt::new(
+      view,
+      metablock.clone(),
+      sign(&metablock.hash().digest_with_bytes(&nonce)),
+    );
+    assert!(receipt
+      .verify_ledger_tail(&vs, &handle, Some(&nonce))
+      .is_ok());
+    assert!(receipt.verify_ledger_tail(&vs, &handle, None).is_err());
+    let replayed = receipt.verify_ledger_tail(&vs, &handle, Some(&Nonce::new().to_bytes()));
+    assert_eq!(replayed, Err(VerificationError::InvalidSignature));
+
+    // a signature on another metablock or by a key outside the view is rejected
+    let next = metablock.next(&NimbleDigest::digest(b"next")).unwrap();
+    let receipt = Receipt::new(view, next, sign(&metablock.hash()));
+    assert_eq!(
+      receipt.verify_ledger_tail(&vs, &handle, None),
+      Err(VerificationError::InvalidSignature)
+    );
+    let outsider = PrivateKey::new();
+    let receipt = Receipt::new(
+      view,
+      metablock.clone(),
+      IdSig::new(
+        outsider.get_public_key().unwrap(),
+        outsider.sign(&metablock.hash().to_bytes()).unwrap(),
+      ),
+    );
+    assert_eq!(
+      receipt.verify_ledger_tail(&vs, &handle, None),
+      Err(VerificationError::InvalidPublicKey)
+    );
+    let receipt = Receipt::new(
+      NimbleDigest::digest(b"unknown view"),
+      metablock.clone(),
+      sign(&metablock.hash()),
+    );
+    assert_eq!(
+      receipt.verify_ledger_tail(&vs, &handle, None),
+      Err(VerificationError::ViewNotFound)
+    );
+  }
+
+  fn golden_public_keys() -> Vec<PublicKey> {
+    [
+      "03A60909370C9CCB5DD3B909654AE158E21C4EE35C7A291C7197F38E22CA95B858",
+      // the generator of P-256
+      "036B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296",
+    ]
+    .iter()
+    .map(|pk| PublicKey::from_bytes(&hex::decode(pk).unwrap()).unwrap())
+    .collect()
+  }
+
+  #[cfg(not(feature = "blake3"))]
+  #[test]
+  pub fn test_view_block_golden_vectors() {
+    let pks = golden_public_keys();
+    assert_eq!(
+      compute_view_block(&pks, &[]).to_string(),
+      "3ba7c0d0367558a4ce6ca648b950cbbd0a80109b342ac3cfa20bf55859f05fd8"
+    );
+    assert_eq!(
+      compute_view_block(&pks, b"metadata").to_string(),
+      "0fb2c35816c4a92fe508bc6ce2d6789252cafa0982fc5c91e68e9643856a0070"
+    );
+    assert_eq!(
+      compute_view_block(&pks[..1], &[]).to_string(),
+      "97c435ef1cdd92d70b8d60dd94bc6046a73f42ebbff23dcf7428502f3ff79e25"
+    );
+  }
+
+  #[test]
+  pub fn test_view_block_is_order_independent() {
+    let pks = golden_public_keys();
+    let reversed = pks.iter().rev().cloned().collect::<Vec<PublicKey>>();
+    let duplicated = [pks.clone(), pks.clone()].concat();
+    assert_eq!(
+      compute_view_block(&pks, &[]),
+      compute_view_block(&reversed, &[])
+    );
+    assert_eq!(
+      compute_view_block(&pks, &[]),
+      compute_view_block(&duplicated, &[])
+    );
+    assert_ne!(
+      compute_view_block(&pks, &[]),
+      compute_view_block(&pks[..1], &[])
+    );
+    assert_ne!(
+      compute_view_block(&pks, &[]),
+      compute_view_block(&pks, b"metadata")
+    );
+
+    // configs that list the same endorsers in a different order yield the same view block
+    let config = |pks: &[PublicKey]| {
+      let hostnames = pks
+        .iter()
+        .enumerate()
+        .map(|(i, pk)| (pk.to_bytes(), format!("http://endorser{}:9090", i)))
+        .collect::<EndorserHostnames>();
+      bincode::serialize(&hostnames).unwrap()
+    };
+    assert_eq!(
+      compute_view_block_hash(&config(&pks)).unwrap(),
+      compute_view_block_hash(&config(&reversed)).unwrap()
+    );
+    assert_eq!(
+      compute_view_block_hash(&config(&pks)).unwrap(),
+      compute_view_block(&pks, &[])
+    );
+    assert_eq!(
+      compute_view_block_hash(&[]).unwrap(),
+      NimbleDigest::default()
+    );
+    assert!(compute_view_block_hash(&[1, 2, 3]).is_err());
+  }
+
+  #[test]
+  pub fn test_key_rotation() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let group_identity = NimbleDigest::digest(b"group");
+    let keys = (0..4).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let pk = |i: usize| keys[i].get_public_key().unwrap();
+    let pks = |ids: &[usize]| {
+      ids
+        .iter()
+        .map(|i| pk(*i).to_bytes())
+        .collect::<HashSet<Vec<u8>>>()
+    };
+    let sign = |signer: usize, old: usize, new: usize| {
+      let message =
+        compute_key_rotation_message(&group_identity, &pk(old).to_bytes(), &pk(new).to_bytes());
+      KeyRotation::new(
+        &pk(old),
+        &pk(new),
+        &keys[signer].sign(&message.to_bytes()).unwrap(),
+      )
+    };
+
+    // the endorser with key 0 hands its place over to key 3
+    let rotation = sign(0, 0, 3);
+    let rotations = [rotation.clone()];
+    let (old_pks, new_pks) = (pks(&[0, 1, 2]), pks(&[3, 1, 2]));
+    assert!(rotation.verify(&group_identity).is_ok());
+    assert!(verify_key_rotations(&group_identity, &rotations, Some(&old_pks), &new_pks).is_ok());
+    assert!(verify_key_rotations(&group_identity, &rotations, None, &new_pks).is_ok());
+    assert_eq!(
+      rotation.verify(&NimbleDigest::digest(b"other group")),
+      Err(VerificationError::InvalidKeyRotation)
+    );
+    // no other key can hand that place over
+    assert_eq!(
+      sign(1, 0, 3).verify(&group_identity),
+      Err(VerificationError::InvalidKeyRotation)
+    );
+    // the old key leaves the view and the new key joins it, once
+    let invalid =
+      |rotations: &[KeyRotation], old_pks: &HashSet<Vec<u8>>, new_pks: &HashSet<Vec<u8>>| {
+        verify_key_rotations(&group_identity, rotations, Some(old_pks), new_pks)
+          == Err(VerificationError::InvalidKeyRotation)
+      };
+    assert!(invalid(&rotations, &old_pks, &pks(&[0, 1, 2, 3])));
+    assert!(invalid(&rotations, &pks(&[1, 2]), &new_pks));
+    assert!(invalid(&rotations, &old_pks, &pks(&[1, 2])));
+    assert!(invalid(&[sign(3, 3, 0)], &old_pks, &new_pks));
+    assert!(invalid(
+      &[rotation.clone(), rotation.clone()],
+      &old_pks,
+      &new_pks
+    ));
+
+    // a view block without rotations is the serialized endorsers and the hash algorithm
+    let endorsers = [3, 1, 2]
+      .iter()
+      .map(|i| (pk(*i).to_bytes(), format!("http://endorser{}:9090", i)))
+      .collect::<EndorserHostnames>();
+    let plain = encode_view_config(&endorsers, None, &[]);
+    let serialized = bincode::serialize(&endorsers).unwrap();
+    let algorithm = [VIEW_HASH_DOMAIN_TAG, &HASH_ALGORITHM.id().to_le_bytes()].concat();
+    assert_eq!(plain, [&serialized[..], &algorithm[..]].concat());
+    assert_eq!(
+      decode_view_config(&plain).unwrap(),
+      (endorsers.clone(), Vec::new())
+    );
+    assert_eq!(
+      compute_view_block_hash(&plain).unwrap(),
+      compute_view_block(&[pk(3), pk(1), pk(2)], &algorithm)
+    );
+
+    // the rotations follow the endorsers, where readers of the endorsers ignore them, and the
+    // digest of the block covers them
+    let config = encode_view_config(&endorsers, None, &rotations);
+    assert_eq!(
+      decode_view_config(&config).unwrap(),
+      (endorsers.clone(), rotations.to_vec())
+    );
+    let decoded: EndorserHostnames = bincode::deserialize(&config).unwrap();
+    assert_eq!(decoded, endorsers);
+    assert_eq!(retrieve_public_keys_from_config(&config).unwrap(), new_pks);
+    assert_ne!(
+      compute_view_block_hash(&config).unwrap(),
+      compute_view_block_hash(&plain).unwrap()
+    );
+    // trailing bytes are rejected rather than left out of the digest
+    assert_eq!(
+      compute_view_block_hash(&[&config[..], &[0]].concat()),
+      Err(VerificationError::InvalidConfig)
+    );
+    assert_eq!(
+      compute_view_block_hash(&[&plain[..], &[0]].concat()),
+      Err(VerificationError::InvalidConfig)
+    );
+  }
+
+  #[test]
+  pub fn test_view_hash_algorithm() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let endorsers = (0..3)
+      .map(|i| {
+        let pk = PrivateKey::new().get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    let serialized = bincode::serialize(&endorsers).unwrap();
+
+    // a view block written before views recorded their hash algorithm is still accepted
+    let config = encode_view_config(&endorsers, None, &[]);
+    assert_eq!(retrieve_quorum_from_config(&serialized), Ok(2));
+    assert_eq!(
+      decode_view_config(&serialized).unwrap(),
+      (endorsers.clone(), Vec::new())
+    );
+    assert_ne!(
+      compute_view_block_hash(&serialized).unwrap(),
+      compute_view_block_hash(&config).unwrap()
+    );
+
+    // a view of another or an unknown algorithm is rejected as such
+    let other = match HASH_ALGORITHM {
+      HashAlgorithm::Sha256 => HashAlgorithm::Blake3,
+      HashAlgorithm::Blake3 => HashAlgorithm::Sha256,
+    };
+    for id in [other.id(), 0, 3] {
+      let config = [&serialized[..], VIEW_HASH_DOMAIN_TAG, &id.to_le_bytes()].concat();
+      assert_eq!(
+        compute_view_block_hash(&config),
+        Err(VerificationError::HashAlgorithmMismatch)
+      );
+      assert_eq!(
+        retrieve_quorum_from_config(&config),
+        Err(VerificationError::HashAlgorithmMismatch)
+      );
+      assert_eq!(
+        decode_view_config(&config),
+        Err(VerificationError::HashAlgorithmMismatch)
+      );
+    }
+    assert_eq!(
+      compute_view_block_hash(&config[..config.len() - 1]),
+      Err(VerificationError::InvalidConfig)
+    );
+  }
+
+  #[test]
+  pub fn test_view_quorum() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let keys = (0..3).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let endorsers = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let pk = key.get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+
+    // a view block without a quorum needs a majority
+    let plain = encode_view_config(&endorsers, None, &[]);
+    assert_eq!(retrieve_quorum_from_config(&plain), Ok(2));
+    let all = encode_view_config(&endorsers, Some(3), &[]);
+    assert_eq!(retrieve_quorum_from_config(&all), Ok(3));
+    assert_eq!(
+      decode_view_config(&all).unwrap(),
+      (endorsers.clone(), Vec::new())
+    );
+    let decoded: EndorserHostnames = bincode::deserialize(&all).unwrap();
+    assert_eq!(decoded, endorsers);
+    assert_ne!(
+      compute_view_block_hash(&all).unwrap(),
+      compute_view_block_hash(&plain).unwrap()
+    );
+    // a quorum must be a majority of the endorsers, and no more than all of them
+    for quorum in [0, 1, 4] {
+      let config = encode_view_config(&endorsers, Some(quorum), &[]);
+      assert_eq!(
+        retrieve_quorum_from_config(&config),
+        Err(VerificationError::InvalidConfig)
+      );
+      assert_eq!(
+        compute_view_block_hash(&config),
+        Err(VerificationError::InvalidConfig)
+      );
+    }
+    assert_eq!(
+      compute_view_block_hash(&all[..all.len() - 1]),
+      Err(VerificationError::InvalidConfig)
+    );
+
+    // the quorum changes from a majority to all endorsers in the second view
+    let group_identity = compute_view_block_hash(&plain).unwrap();
+    let mut vs = VerifierState::new();
+    vs.set_group_identity(group_identity);
+    let attestations = "THIS IS A PLACE HOLDER FOR ATTESTATION".as_bytes();
+    let first = MetaBlock::default().next(&group_identity).unwrap();
+    let second = first.next(&compute_view_block_hash(&all).unwrap()).unwrap();
+    let state_hash = NimbleDigest::digest(b"state");
+    for (config, metablock) in [(&plain, &first), (&all, &second)] {
+      let message = group_identity.digest_with(&state_hash.digest_with(&metablock.hash()));
+      let mut receipts = Receipts::new();
+      for key in &keys {
+        let id_sig = IdSig::new(
+          key.get_public_key().unwrap(),
+          key.sign(&message.to_bytes()).unwrap(),
+        );
+        receipts.add(&Receipt::new(state_hash, metablock.clone(), id_sig));
+      }
+      vs.apply_view_change(config, &receipts.to_bytes(), Some(attestations))
+        .unwrap();
+    }
+    assert_eq!(vs.get_quorum_for_view(&first.hash()), Ok(2));
+    assert_eq!(vs.get_quorum_for_view(&second.hash()), Ok(3));
+
+    // receipts are held to the quorum of the view they were signed in
+    let (handle, block) = (b"handle", b"block");
+    let metablock = MetaBlock::default()
+      .next(&compute_aggregated_block_hash(
+        &NimbleDigest::digest(block).to_bytes(),
+        &[],
+      ))
+      .unwrap();
+    let receipts = |view: &MetaBlock, signers: usize| {
+      let message = compute_ledger_tail_message(
+        &group_identity,
+        &view.hash(),
+        &NimbleDigest::digest(handle),
+        &metablock.hash(),
+      );
+      let mut receipts = Receipts::new();
+      for key in &keys[..signers] {
+        let id_sig = IdSig::new(
+          key.get_public_key().unwrap(),
+          key.sign(&message.to_bytes()).unwrap(),
+        );
+        receipts.add(&Receipt::new(view.hash(), metablock.clone(), id_sig));
+      }
+      receipts.to_bytes()
+    };
+    assert!(vs
+      .verify_append(handle, block, &[], 1, &receipts(&first, 2))
+      .is_ok());
+    assert_eq!(
+      vs.verify_append(handle, block, &[], 1, &receipts(&second, 2)),
+      Err(VerificationError::InvalidReceipt)
+    );
+    assert!(vs
+      .verify_append(handle, block, &[], 1, &receipts(&second, 3))
+      .is_ok());
+    let two = Receipts::from_bytes(&receipts(&second, 2)).unwrap();
+    assert_eq!(
+      two.check_quorum(&vs),
+      Err(VerificationError::InsufficientReceipts)
+    );
+  }
+
+  #[test]
+  pub fn test_view_scheme_keys() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let endorsers = (0..3)
+      .map(|i| {
+        let pk = PrivateKey::new().get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    let key_of = |i: u8, scheme: SignatureSchemeId| {
+      (
+        endorsers[i as usize].0.clone(),
+        vec![i; scheme.public_key_len()],
+        vec![i; scheme.signature_len()],
+      )
+    };
+    let keys = SchemeKeys::new(
+      SignatureSchemeId::Bls12381,
+      (0..3)
+        .map(|i| key_of(i, SignatureSchemeId::Bls12381))
+        .collect(),
+    );
+    assert_eq!(SchemeKeys::from_bytes(&keys.to_bytes()), Ok(keys.clone()));
+    let mut ids = endorsers.iter().map(|(pk, _uri)| pk).collect::<Vec<_>>();
+    ids.sort();
+    for (position, id) in ids.iter().enumerate() {
+      assert_eq!(keys.position(id), Some(position));
+    }
+
+    // the keys follow the quorum, are covered by the view, and leave the key rotations decodable
+    let plain = encode_view_config(&endorsers, Some(2), &[]);
+    let config = encode_view_config_with_scheme(&endorsers, Some(2), Some(&keys), &[]);
+    assert_eq!(retrieve_scheme_keys_from_config(&plain), Ok(None));
+    assert_eq!(retrieve_scheme_keys_from_config(&config), Ok(Some(keys)));
+    assert_eq!(retrieve_quorum_from_config(&config), Ok(2));
+    assert_eq!(
+      decode_view_config(&config).unwrap(),
+      (endorsers.clone(), Vec::new())
+    );
+    assert_ne!(
+      compute_view_block_hash(&config).unwrap(),
+      compute_view_block_hash(&plain).unwrap()
+    );
+
+    // every endorser of the view signs with the scheme of the view, and only they do
+    let mixed = SchemeKeys::new(
+      SignatureSchemeId::Bls12381,
+      vec![
+        key_of(0, SignatureSchemeId::Bls12381),
+        key_of(1, SignatureSchemeId::Bls12381),
+        key_of(2, SignatureSchemeId::P256),
+      ],
+    );
+    let missing = SchemeKeys::new(
+      SignatureSchemeId::Bls12381,
+      (0..2)
+        .map(|i| key_of(i, SignatureSchemeId::Bls12381))
+        .collect(),
+    );
+    for keys in [mixed, missing] {
+      let config = encode_view_config_with_scheme(&endorsers, None, Some(&keys), &[]);
+      assert_eq!(
+        compute_view_block_hash(&config),
+        Err(VerificationError::MixedSignatureSchemes)
+      );
+      assert_eq!(
+        retrieve_scheme_keys_from_config(&config),
+        Err(VerificationError::MixedSignatureSchemes)
+      );
+    }
+    // a P-256 view records no keys
+    let p256 = SchemeKeys::new(
+      SignatureSchemeId::P256,
+      (0..3).map(|i| key_of(i, SignatureSchemeId::P256)).collect(),
+    );
+    let config = encode_view_config_with_scheme(&endorsers, None, Some(&p256), &[]);
+    assert_eq!(
+      compute_view_block_hash(&config),
+      Err(VerificationError::InvalidConfig)
+    );
+
+    // aggregation needs a scheme whose signatures aggregate
+    let sig = PrivateKey::new().sign(b"message").unwrap();
+    assert_eq!(
+      Receipt::aggregate::<P256>(
+        &NimbleDigest::default(),
+        &MetaBlock::default(),
+        3,
+        &[(0, sig)]
+      )
+      .unwrap_err(),
+      LedgerError::Crypto(CryptoError::AggregationUnsupported)
+    );
+  }
+
+  #[cfg(feature = "bls")]
+  #[test]
+  pub fn test_aggregated_receipt() {
+    use crate::signature::{bls, PrivateKey, PrivateKeyTrait};
+
+    let keys = (0..4)
+      .map(|_| (PrivateKey::new(), bls::PrivateKey::new()))
+      .collect::<Vec<_>>();
+    let scheme_keys = SchemeKeys::new(
+      SignatureSchemeId::Bls12381,
+      keys
+        .iter()
+        .map(|(identity, key)| {
+          (
+            identity.get_public_key().unwrap().to_bytes(),
+            key.get_public_key().to_bytes(),
+            key.prove_possession().to_bytes(),
+          )
+        })
+        .collect(),
+    );
+    assert!(scheme_keys.verify_possession().is_ok());
+    let position = |i: usize| {
+      scheme_keys
+        .position(&keys[i].0.get_public_key().unwrap().to_bytes())
+        .unwrap()
+    };
+
+    let view = NimbleDigest::digest(b"view");
+    let metablock = MetaBlock::genesis(&NimbleDigest::digest(b"block"));
+    let message = NimbleDigest::digest(b"message").to_bytes();
+    let signatures = [0, 1, 3, 1]
+      .iter()
+      .map(|i| (position(*i), keys[*i].1.sign(&message)))
+      .collect::<Vec<_>>();
+    let receipt = Receipt::aggregate::<bls::Bls>(&view, &metablock, 4, &signatures).unwrap();
+    assert_eq!(
+      AggregatedReceipt::from_bytes(&receipt.to_bytes()),
+      Ok(receipt.clone())
+    );
+    let mut positions = vec![position(0), position(1), position(3)];
+    positions.sort_unstable();
+    assert_eq!(receipt.signer_positions(4), Ok(positions));
+    assert_eq!(receipt.verify(&scheme_keys, &message), Ok(3));
+    assert_eq!(
+      receipt.verify(&scheme_keys, b"another message"),
+      Err(VerificationError::InvalidSignature)
+    );
+
+    // the bitmap must name the signers of the aggregate, for the number of endorsers of the view
+    let mut forged = receipt.clone();
+    forged.signers[0] ^= 1 << position(2);
+    assert_eq!(
+      forged.verify(&scheme_keys, &message),
+      Err(VerificationError::InvalidSignature)
+    );
+    forged.signers[0] = 1 << 5;
+    assert_eq!(
+      forged.signer_positions(4),
+      Err(VerificationError::InvalidReceipt)
+    );
+    assert_eq!(
+      receipt.signer_positions(9),
+      Err(VerificationError::InvalidReceipt)
+    );
+    assert_eq!(
+      Receipt::aggregate::<bls::Bls>(&view, &metablock, 4, &[(4, keys[0].1.sign(&message))])
+        .unwrap_err(),
+      LedgerError::Verification(VerificationError::IndexOutofBounds)
+    );
+
+    // a key without a proof that its holder knows the private key is rejected
+    let mut rogue = scheme_keys.clone();
+    rogue.keys[0].2 = bls::PrivateKey::new().prove_possession().to_bytes();
+    assert_eq!(
+      rogue.verify_possession(),
+      Err(VerificationError::InvalidSignature)
+    );
+  }
+
+  #[cfg(not(feature = "blake3"))]
+  #[test]
+  pub fn test_view_change_block_golden_vectors() {
+    let digest_of = |b: u8| NimbleDigest::from_bytes(&[b; 32]).unwrap();
+
+    assert_eq!(
+      tail_map_digest(&HashMap::new()).to_bytes(),
+      decode_hex("4d63080a179840c8a85719c121fdb1772d428a89ee33058954d286f056227526").unwrap()
+    );
+
+    let mut tail_map = HashMap::new();
+    tail_map.insert(digest_of(2), (digest_of(3), 5));
+    tail_map.insert(digest_of(1), (digest_of(4), 1));
+    let tail = tail_map_digest(&tail_map);
+    assert_eq!(
+      tail.to_bytes(),
+      decode_hex("e390352ef5c8cb14fe6e5b5989bd9b3f5e1f6625e4586359de1fbfd50fda996b").unwrap()
+    );
+
+    // the digest depends on the heights, not only on the handles and tail hashes
+    tail_map.insert(digest_of(2), (digest_of(3), 6));
+    assert_ne!(tail_map_digest(&tail_map), tail);
+
+    let block = view_change_block(&tail, &digest_of(5), &digest_of(6));
+    assert_eq!(block.to_bytes().len(), 112);
+    assert_eq!(
+      block.hash().to_bytes(),
+      decode_hex("06117297c41161706da54727c183c2cbc0557f2c1f4cc18ca8ba80adf0ed3fe3").unwrap()
+    );
+    assert_ne!(
+      view_change_block(&tail, &digest_of(6), &digest_of(5)).hash(),
+      block.hash()
+    );
+  }
+
+  #[test]
+  pub fn test_block_size_limit() {
+    let block = Block::try_new(&vec![7u8; MAX_BLOCK_SIZE]).unwrap();
+    assert_eq!(block.len(), MAX_BLOCK_SIZE);
+    assert_eq!(block.hash(), NimbleDigest::digest(&block.to_bytes()));
+    assert_eq!(
+      Block::try_new(&vec![7u8; MAX_BLOCK_SIZE + 1]).unwrap_err(),
+      CustomSerdeError::BlockTooLarge
+    );
+    assert!(Block::try_new(&[]).unwrap().is_empty());
+    assert!(Block::try_new_with_max_size(b"block", 5).is_ok());
+    assert_eq!(
+      Block::try_new_with_max_size(b"block", 4).unwrap_err(),
+      CustomSerdeError::BlockTooLarge
+    );
+  }
+
+  #[test]
+  pub fn test_genesis_block_metadata() {
+    // without metadata, the genesis block is the client's block
+    let genesis = compute_genesis_block(b"block", &[]);
+    assert_eq!(genesis.to_bytes(), b"block".to_vec());
+    assert_eq!(
+      parse_genesis_block(&genesis.to_bytes()).unwrap(),
+      (&b""[..], &b"block"[..])
+    );
+
+    // metadata and blocks that look framed are framed, and parse back
+    let genesis = compute_genesis_block(b"block", b"{\"kind\":\"fsimage\"}");
+    assert_ne!(
+      genesis.hash(),
+      compute_genesis_block(b"block", b"other").hash()
+    );
+    assert_eq!(
+      parse_genesis_block(&genesis.to_bytes()).unwrap(),
+      (&b"{\"kind\":\"fsimage\"}"[..], &b"block"[..])
+    );
+    let framed_looking = [GENESIS_METADATA_DOMAIN_TAG, &[1, 0, 0, 0, 7]].concat();
+    let genesis = compute_genesis_block(&framed_looking, &[]);
+    assert_ne!(genesis.to_bytes(), framed_looking);
+    assert_eq!(
+      parse_genesis_block(&genesis.to_bytes()).unwrap(),
+      (&b""[..], &framed_looking[..])
+    );
+
+    let truncated = [GENESIS_METADATA_DOMAIN_TAG, &[9, 0, 0, 0, 7]].concat();
+    assert_eq!(
+      parse_genesis_block(&truncated).unwrap_err(),
+      VerificationError::InvalidGenesisBlock
+    );
+  }
+
+  #[test]
+  pub fn test_seal_block() {
+    let seal = compute_seal_block(1_700_000_000_000);
+    assert_eq!(parse_seal_block(&seal.to_bytes()), Some(1_700_000_000_000));
+    assert_ne!(seal.hash(), compute_seal_block(1).hash());
+    assert_eq!(parse_seal_block(b"block"), None);
+    assert_eq!(parse_seal_block(&[SEAL_DOMAIN_TAG, &[1, 2]].concat()), None);
+  }
+
+  #[test]
+  pub fn test_heartbeat_block() {
+    let heartbeat = compute_heartbeat_block(1_700_000_000_000, 30_000);
+    assert_eq!(
+      parse_heartbeat_block(&heartbeat.to_bytes()),
+      Some((1_700_000_000_000, 30_000))
+    );
+    assert_eq!(parse_seal_block(&heartbeat.to_bytes()), None);
+    assert_eq!(
+      parse_heartbeat_block(&compute_seal_block(1).to_bytes()),
+      None
+    );
+    let truncated = &heartbeat.to_bytes()[..HEARTBEAT_DOMAIN_TAG.len() + 8];
+    assert_eq!(parse_heartbeat_block(truncated), None);
+  }
+
+  #[test]
+  pub fn test_snapshot_digest() {
+    let nonce = Nonce::try_from_bytes(&[7u8; 16]).unwrap();
+    let a = (
+      NimbleDigest::digest(b"a"),
+      NimbleDigest::digest(b"tail a"),
+      3,
+    );
+    let b = (
+      NimbleDigest::digest(b"b"),
+      NimbleDigest::digest(b"tail b"),
+      5,
+    );
+    let digest = compute_snapshot_digest(&nonce, &[a, b]);
+    // the order the ledgers were read in does not matter
+    assert_eq!(digest, compute_snapshot_digest(&nonce, &[b, a]));
+    // every ledger, its tail and its height are covered, and so is the nonce
+    assert_ne!(digest, compute_snapshot_digest(&nonce, &[a]));
+    assert_ne!(digest, compute_snapshot_digest(&nonce, &[a, (b.0, b.1, 6)]));
+    assert_ne!(digest, compute_snapshot_digest(&nonce, &[a, (b.0, a.1, 5)]));
+    let other_nonce = Nonce::try_from_bytes(&[8u8; 16]).unwrap();
+    assert_ne!(digest, compute_snapshot_digest(&other_nonce, &[a, b]));
+  }
+
+  #[test]
+  pub fn test_endorser_heartbeat_message() {
+    let nonce = Nonce::try_from_bytes(&[7u8; 16]).unwrap();
+    let message = compute_endorser_heartbeat_message(&nonce, 3, 1_700_000_000_000);
+    assert_eq!(
+      message,
+      compute_endorser_heartbeat_message(&nonce, 3, 1_700_000_000_000)
+    );
+    // every field is signed
+    let other_nonce = Nonce::try_from_bytes(&[8u8; 16]).unwrap();
+    assert_ne!(
+      message,
+      compute_endorser_heartbeat_message(&other_nonce, 3, 1_700_000_000_000)
+    );
+    assert_ne!(
+      message,
+      compute_endorser_heartbeat_message(&nonce, 4, 1_700_000_000_000)
+    );
+    assert_ne!(
+      message,
+      compute_endorser_heartbeat_message(&nonce, 3, 1_700_000_000_001)
+    );
+    // the tag keeps the fields from passing for the digests of a ledger tail message
+    let mut payload = nonce.to_bytes();
+    payload.extend_from_slice(&3u64.to_le_bytes());
+    payload.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+    assert_ne!(message, NimbleDigest::digest(&payload));
+  }
+
+  #[test]
+  pub fn test_constant_time_equality() {
+    let digest = NimbleDigest::digest(b"1");
+    assert_eq!(
+      digest,
+      NimbleDigest::from_bytes(&digest.to_bytes()).unwrap()
+    );
+    assert_ne!(digest, NimbleDigest::digest(b"2"));
+    // digests that differ only in their last byte
+    let mut bytes = digest.to_bytes();
+    bytes[31] ^= 1;
+    assert_ne!(digest, NimbleDigest::from_bytes(&bytes).unwrap());
+    let set = [digest, digest]
+      .iter()
+      .copied()
+      .collect::<HashSet<NimbleDigest>>();
+    assert_eq!(set.len(), 1);
+
+    let nonce = Nonce::new();
+    assert_eq!(nonce, Nonce::try_from_bytes(&nonce.to_bytes()).unwrap());
+    let mut bytes = nonce.to_bytes();
+    bytes[0] ^= 1;
+    assert_ne!(nonce, Nonce::try_from_bytes(&bytes).unwrap());
+  }
+
+  #[test]
+  pub fn test_handle_derivation() {
+    let nonce = Nonce::new();
+    let handle = Handle::derive(b"namespace-1", &nonce);
+    assert_eq!(handle, Handle::derive(b"namespace-1", &nonce));
+    assert_eq!(
+      handle,
+      NimbleDigest::digest(&[b"namespace-1".to_vec(), nonce.to_bytes()].concat())
+    );
+    assert_ne!(handle, Handle::derive(b"namespace-2", &nonce));
+    assert_ne!(handle, Handle::derive(b"namespace-1", &Nonce::new()));
+    assert_ne!(Handle::random(), Handle::random());
+  }
+
+  #[test]
+  pub fn test_nonce_generation_and_parsing() {
+    let nonces = (0..10_000)
+      .map(|_| Nonce::new())
+      .collect::<HashSet<Nonce>>();
+    assert_eq!(nonces.len(), 10_000);
+
+    let nonce = Nonce::new();
+    assert_eq!(Nonce::try_from_bytes(&nonce.to_bytes()).unwrap(), nonce);
+    for len in [0, 1, 15, 17, 32] {
+      assert_eq!(
+        Nonce::try_from_bytes(&vec![0u8; len]).unwrap_err(),
+        CustomSerdeError::IncorrectLength
+      );
+    }
+  }
+
+  #[test]
+  pub fn test_digest_parts_matches_concatenation() {
+    let handle = NimbleDigest::digest(b"handle");
+    let view = NimbleDigest::digest(b"view");
+    let group_identity = NimbleDigest::digest(b"group identity");
+    let metablock = MetaBlock::new(&view, &handle, 7);
+    let nonce = Nonce::new();
+
+    // the messages signed by endorsers, computed by concatenating and then hashing
+    let concat =
+      |a: &NimbleDigest, b: &[u8]| NimbleDigest::digest(&[a.to_bytes(), b.to_vec()].concat());
+    let tail_hash = NimbleDigest::digest(&metablock.to_bytes());
+    let expected = concat(
+      &group_identity,
+      &concat(&view, &concat(&handle, &tail_hash.to_bytes()).to_bytes()).to_bytes(),
+    );
+    assert_eq!(metablock.hash(), tail_hash);
+    assert_eq!(
+      group_identity.digest_with(&view.digest_with(&handle.digest_with(&metablock.hash()))),
+      expected
+    );
+    assert_eq!(
+      metablock.hash().digest_with_bytes(&nonce.to_bytes()),
+      concat(&tail_hash, &nonce.to_bytes())
+    );
+    assert_eq!(
+      Handle::derive(b"app", &nonce),
+      NimbleDigest::digest(&[b"app".to_vec(), nonce.to_bytes()].concat())
+    );
+
+    // the builder agrees with one-shot hashing, including for the empty message
+    let parts: [&[u8]; 3] = [b"a", b"", b"bc"];
+    let mut builder = DigestBuilder::new();
+    for part in parts {
+      builder.update(part);
+    }
+    assert_eq!(builder.finalize(), NimbleDigest::digest(b"abc"));
+    assert_eq!(
+      NimbleDigest::digest_parts(&parts),
+      NimbleDigest::digest(b"abc")
+    );
+    assert_eq!(NimbleDigest::digest_parts(&[]), NimbleDigest::digest(&[]));
+    assert_eq!(DigestBuilder::new().finalize(), NimbleDigest::default());
+  }
+
+  #[test]
+  pub fn test_verify_chain() {
+    let block_hashes = (0..5u8)
+      .map(|i| NimbleDigest::digest(&[i]))
+      .collect::<Vec<NimbleDigest>>();
+    let mut metablocks = vec![MetaBlock::genesis(&block_hashes[0])];
+    for block_hash in &block_hashes[1..] {
+      let next = metablocks.last().unwrap().next(block_hash).unwrap();
+      metablocks.push(next);
+    }
+    let tail = metablocks.last().unwrap().hash();
+    let entries = block_hashes
+      .iter()
+      .enumerate()
+      .map(|(height, block_hash)| (*block_hash, height))
+      .collect::<Vec<(NimbleDigest, usize)>>();
+
+    // from genesis and from a checkpoint
+    assert!(verify_chain(None, &entries, &tail).is_ok());
+    assert!(verify_chain(Some(&metablocks[1]), &entries[2..], &tail).is_ok());
+    assert!(verify_chain(Some(&metablocks[4]), &[], &tail).is_ok());
+
+    // a tampered block is detected at the tail
+    let mut tampered = entries.clone();
+    tampered[2].0 = NimbleDigest::digest(b"tampered");
+    assert_eq!(
+      verify_chain(None, &tampered, &tail),
+      Err(VerificationError::BrokenChain(4))
+    );
+
+    // a gap in the heights is reported at the first height that breaks the chain
+    let mut gap = entries.clone();
+    gap.remove(2);
+    assert_eq!(
+      verify_chain(None, &gap, &tail),
+      Err(VerificationError::BrokenChain(2))
+    );
+
+    // a wrong tail or an empty chain is rejected
+    assert_eq!(
+      verify_chain(None, &entries[..4], &tail),
+      Err(VerificationError::BrokenChain(3))
+    );
+    assert_eq!(
+      verify_chain(None, &[], &tail),
+      Err(VerificationError::BrokenChain(0))
+    );
+  }
+
+  #[test]
+  pub fn test_verify_read_range() {
+    use crate::signature::{PrivateKey, PrivateKeyTrait};
+
+    let sk = PrivateKey::new();
+    let pk = sk.get_public_key().unwrap();
+    let view = NimbleDigest::digest(b"view");
+    let handle_bytes = b"handle";
+    let mut vs = VerifierState::new();
+    vs.set_group_identity(NimbleDigest::digest(b"group"));
+    vs.vk_map
+      .insert(view, [pk.to_bytes()].iter().cloned().collect());
+
+    // a ledger of five entries whose tail is endorsed
+    let entries = (0..5u8)
+      .map(|i| (vec![i], Nonces::new().to_bytes()))
+      .collect::<Vec<(Vec<u8>, Vec<u8>)>>();
+    let block_hashes = entries
+      .iter()
+      .map(|(block, nonces)| {
+        compute_aggregated_block_hash(
+          &NimbleDigest::digest(block).to_bytes(),
+          &NimbleDigest::digest(nonces).to_bytes(),
+        )
+      })
+      .collect::<Vec<NimbleDigest>>();
+    let mut metablocks = vec![MetaBlock::genesis(&block_hashes[0])];
+    for block_hash in &block_hashes[1..] {
+      let next = metablocks.last().unwrap().next(block_hash).unwrap();
+      metablocks.push(next);
+    }
+    let message = compute_ledger_tail_message(
+      vs.get_group_identity(),
+      &view,
+      &NimbleDigest::digest(handle_bytes),
+      &metablocks[4].hash(),
+    );
+    let mut receipts = Receipts::new();
+    receipts.add(&Receipt::new(
+      view,
+      metablocks[4].clone(),
+      IdSig::new(pk, sk.sign(&message.to_bytes()).unwrap()),
+    ));
+    let tail_receipts = receipts.to_bytes();
+    let hashes = |from: usize| {
+      block_hashes[from..]
+        .iter()
+        .map(|block_hash| block_hash.to_bytes())
+        .collect::<Vec<Vec<u8>>>()
+    };
+
+    // the first entry, a page in the middle, and the tail itself
+    let res = vs.verify_read_range(
+      handle_bytes,
+      0,
+      &entries[..1],
+      &[],
+      &hashes(1),
+      &tail_receipts,
+    );
+    assert_eq!(res, Ok(4));
+    let checkpoint = metablocks[1].to_bytes();
+    let res = vs.verify_read_range(
+      handle_bytes,
+      2,
+      &entries[2..4],
+      &checkpoint,
+      &hashes(4),
+      &tail_receipts,
+    );
+    assert_eq!(res, Ok(4));
+    let res = vs.verify_read_range(
+      handle_bytes,
+      4,
+      &entries[4..],
+      &metablocks[3].to_bytes(),
+      &[],
+      &tail_receipts,
+    );
+    assert_eq!(res, Ok(4));
+
+    // a tampered entry or a wrong checkpoint breaks the chain to the tail
+    let mut tampered = entries[2..4].to_vec();
+    tampered[0].0 = b"tampered".to_vec();
+    let res = vs.verify_read_range(
+      handle_bytes,
+      2,
+      &tampered,
+      &checkpoint,
+      &hashes(4),
+      &tail_receipts,
+    );
+    assert_eq!(res, Err(VerificationError::BrokenChain(4)));
+    let res = vs.verify_read_range(
+      handle_bytes,
+      2,
+      &entries[2..4],
+      &metablocks[0].to_bytes(),
+      &hashes(4),
+      &tail_receipts,
+    );
+    assert_eq!(res, Err(VerificationError::BrokenChain(1)));
+
+    // a missing block hash misses the endorsed tail, as do receipts of another ledger
+    let res = vs.verify_read_range(
+      handle_bytes,
+      0,
+      &entries[..1],
+      &[],
+      &hashes(2),
+      &tail_receipts,
+    );
+    assert_eq!(res, Err(VerificationError::InvalidHeight));
+    let res = vs.verify_read_range(b"other", 0, &entries[..1], &[], &hashes(1), &tail_receipts);
+    assert_eq!(res, Err(VerificationError::InvalidSignature));
+  }
+
+  #[test]
+  pub fn test_metablock_encoding_and_chaining() {
+    let block_hash = NimbleDigest::digest("1".as_bytes());
+    let genesis = MetaBlock::genesis(&block_hash);
+    assert_eq!(
+      genesis.to_bytes(),
+      [
+        vec![0u8; 32],
+        block_hash.to_bytes(),
+        0u64.to_le_bytes().to_vec()
+      ]
+      .concat()
+    );
+    assert_eq!(MetaBlock::from_bytes(&genesis.to_bytes()).unwrap(), genesis);
+    assert_eq!(genesis.hash(), NimbleDigest::digest(&genesis.to_bytes()));
+
+    let next_block_hash = NimbleDigest::digest("2".as_bytes());
+    let next = genesis.next(&next_block_hash).unwrap();
+    assert_eq!(next, MetaBlock::new(&genesis.hash(), &next_block_hash, 1));
+    assert_eq!(
+      next.to_bytes()[2 * NimbleDigest::num_bytes()..],
+      1u64.to_le_bytes()
+    );
+
+    let last = MetaBlock::new(&genesis.hash(), &next_block_hash, usize::MAX);
+    assert!(last.next(&block_hash).is_none());
+    assert!(MetaBlock::from_bytes(&genesis.to_bytes()[1..]).is_err());
+  }
+
+  #[test]
+  pub fn test_hash_of_state() {
+    let map = (0..1024 * 1023)
+      .map(|i: usize| {
+        let handle = NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>());
+        let metablock = NimbleDigest::digest(&rand::thread_rng().gen::<[u8; 32]>());
+        LedgerTailMapEntry {
+          handle: handle.to_bytes().into(),
+          metablock: metablock.to_bytes().into(),
+          height: i as u64,
+          block: Bytes::new(),
+          nonces: Bytes::new(),
+        }
+      })
+      .collect::<Vec<LedgerTailMapEntry>>();
+    let hash = produce_hash_of_state(&map);
+    assert_ne!(hash, NimbleDigest::default());
+  }
+
+  #[test]
+  pub fn test_error_conversions() {
+    use crate::errors::LedgerError;
+    use std::error::Error;
+
+    let e: LedgerError = CustomSerdeError::InvalidIdSig(CryptoError::WeakPublicKey).into();
+    assert_eq!(
+      e,
+      LedgerError::Serde(CustomSerdeError::InvalidIdSig(CryptoError::WeakPublicKey))
+    );
+    assert_eq!(
+      e.to_string(),
+      "serialization failed: invalid signer in receipt: public key is the identity point"
+    );
+    // the chain of causes leads down to the crypto error
+    let serde_err = e.source().unwrap();
+    assert!(serde_err.source().unwrap().is::<CryptoError>());
+
+    let e: LedgerError = VerificationError::BrokenChain(7).into();
+    assert_eq!(
+      e.to_string(),
+      "verification failed: hash chain breaks at height 7"
+    );
   }
 }
@@ -0,0 +1,201 @@
+//! Secrets that the coordinator and the endorser load, such as private keys and tokens. The
+//! configuration names where each secret comes from, with a provider before a colon:
+//!
+//! - `file:<path>`, or a bare path where the configuration takes a file: the contents of the file
+//! - `env:<NAME>`: the value of an environment variable
+//! - `exec:<command> [<arg>...]`: what a command prints, so that operators fetch secrets from
+//!   Vault or a KMS with their own tools; the arguments are split on whitespace, without quoting
+//!
+//! A secret is zeroed when it is dropped, so callers parse it and drop it; the errors name the
+//! reference to the secret, never its value.
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// the bytes of a secret, zeroed when dropped
+pub type Secret = Zeroizing<Vec<u8>>;
+
+const PROVIDERS: [&str; 3] = ["file:", "env:", "exec:"];
+
+/// where a secret comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretSource {
+  File(String),
+  Env(String),
+  Exec(Vec<String>),
+}
+
+/// whether `value` names the provider of a secret rather than being a value itself
+pub fn is_reference(value: &str) -> bool {
+  PROVIDERS.iter().any(|provider| value.starts_with(provider))
+}
+
+impl SecretSource {
+  /// parses a reference to a secret; one that names no provider is the path of a file
+  pub fn parse(reference: &str) -> Result<Self, String> {
+    let source = if let Some(path) = reference.strip_prefix("file:") {
+      SecretSource::File(path.to_string())
+    } else if let Some(name) = reference.strip_prefix("env:") {
+      SecretSource::Env(name.to_string())
+    } else if let Some(command) = reference.strip_prefix("exec:") {
+      SecretSource::Exec(command.split_whitespace().map(str::to_string).collect())
+    } else {
+      SecretSource::File(reference.to_string())
+    };
+    let named = match &source {
+      SecretSource::File(path) => !path.is_empty(),
+      SecretSource::Env(name) => !name.is_empty(),
+      SecretSource::Exec(args) => !args.is_empty(),
+    };
+    if !named {
+      return Err(format!(
+        "the secret reference {:?} names no secret",
+        reference
+      ));
+    }
+    Ok(source)
+  }
+
+  /// fetches the secret from its provider
+  pub fn load(&self) -> Result<Secret, String> {
+    let secret = match self {
+      SecretSource::File(path) => Zeroizing::new(
+        std::fs::read(path).map_err(|e| format!("cannot read the secret file {}: {}", path, e))?,
+      ),
+      SecretSource::Env(name) => {
+        let value = std::env::var(name)
+          .map_err(|_e| format!("the environment variable {} is not set", name))?;
+        Zeroizing::new(value.into_bytes())
+      },
+      SecretSource::Exec(args) => {
+        let (program, args) = args.split_first().ok_or("the secret command is empty")?;
+        let output = Command::new(program)
+          .args(args)
+          .stdin(Stdio::null())
+          .output()
+          .map_err(|e| format!("cannot run the secret command {}: {}", program, e))?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+          let stderr = String::from_utf8_lossy(&output.stderr);
+          return Err(format!(
+            "the secret command {} failed with {}: {}",
+            program,
+            output.status,
+            stderr.trim()
+          ));
+        }
+        stdout
+      },
+    };
+    if secret.iter().all(|b| b.is_ascii_whitespace()) {
+      return Err(format!("the secret of {} is empty", self));
+    }
+    Ok(secret)
+  }
+}
+
+impl std::fmt::Display for SecretSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      SecretSource::File(path) => write!(f, "file:{}", path),
+      SecretSource::Env(name) => write!(f, "env:{}", name),
+      SecretSource::Exec(args) => write!(f, "exec:{}", args.join(" ")),
+    }
+  }
+}
+
+/// fetches the secret that `reference` names
+pub fn load_secret(reference: &str) -> Result<Secret, String> {
+  SecretSource::parse(reference)?.load()
+}
+
+/// the text of a secret, without the whitespace around it, such as the newline that ends a file
+/// or the output of a command
+pub fn secret_text(secret: &Secret) -> Result<&str, String> {
+  std::str::from_utf8(secret)
+    .map(str::trim)
+    .map_err(|_e| "the secret is not UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  pub fn test_secret_sources() {
+    assert_eq!(
+      SecretSource::parse("/etc/nimble/key.pem"),
+      Ok(SecretSource::File("/etc/nimble/key.pem".to_string()))
+    );
+    assert_eq!(
+      SecretSource::parse("file:key.pem"),
+      Ok(SecretSource::File("key.pem".to_string()))
+    );
+    assert_eq!(
+      SecretSource::parse("env:NIMBLE_KEY"),
+      Ok(SecretSource::Env("NIMBLE_KEY".to_string()))
+    );
+    let exec = SecretSource::parse("exec:/usr/bin/fetch-key  nimble-endorser-1").unwrap();
+    assert_eq!(
+      exec,
+      SecretSource::Exec(vec![
+        "/usr/bin/fetch-key".to_string(),
+        "nimble-endorser-1".to_string()
+      ])
+    );
+    assert_eq!(
+      exec.to_string(),
+      "exec:/usr/bin/fetch-key nimble-endorser-1"
+    );
+    assert!(SecretSource::parse("exec: ").is_err());
+    assert!(SecretSource::parse("env:").is_err());
+    assert!(SecretSource::parse("")
+      .unwrap_err()
+      .contains("names no secret"));
+
+    assert!(is_reference("env:NIMBLE_KEY") && is_reference("exec:fetch-key"));
+    assert!(!is_reference("0123abcd") && !is_reference("/etc/nimble/key.pem"));
+  }
+
+  #[test]
+  pub fn test_load_secret() {
+    let path = std::env::temp_dir().join(format!("nimble-secret-{}", rand::random::<u64>()));
+    let path = path.to_str().unwrap().to_string();
+    std::fs::write(&path, b"s3cret\n").unwrap();
+    let secret = load_secret(&path).unwrap();
+    assert_eq!(secret.as_slice(), b"s3cret\n");
+    assert_eq!(secret_text(&secret), Ok("s3cret"));
+    assert_eq!(*load_secret(&format!("file:{}", path)).unwrap(), *secret);
+    std::fs::write(&path, b"\n").unwrap();
+    assert!(load_secret(&path).unwrap_err().contains("is empty"));
+    std::fs::remove_file(&path).unwrap();
+    let res = load_secret(&path);
+    assert!(res.unwrap_err().starts_with("cannot read the secret file"));
+
+    let name = format!("NIMBLE_TEST_SECRET_{}", rand::random::<u32>());
+    std::env::set_var(&name, "t0ken");
+    let secret = load_secret(&format!("env:{}", name)).unwrap();
+    assert_eq!(secret_text(&secret), Ok("t0ken"));
+    std::env::remove_var(&name);
+    let res = load_secret(&format!("env:{}", name));
+    assert!(res.unwrap_err().contains("is not set"));
+
+    let res = load_secret("exec:/nonexistent/fetch-key");
+    assert!(res
+      .unwrap_err()
+      .starts_with("cannot run the secret command"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  pub fn test_exec_secret() {
+    let secret = load_secret("exec:echo s3cret").unwrap();
+    assert_eq!(secret.as_slice(), b"s3cret\n");
+    assert_eq!(secret_text(&secret), Ok("s3cret"));
+    let res = load_secret("exec:false");
+    assert!(res
+      .unwrap_err()
+      .starts_with("the secret command false failed"));
+    let res = load_secret("exec:true");
+    assert!(res.unwrap_err().contains("is empty"));
+  }
+}
@@ -0,0 +1,306 @@
+use crate::{
+  signature::{PublicKey, PublicKeyTrait},
+  Block, CustomSerde, NimbleDigest, Nonce, Receipt, Receipts,
+};
+use serde::{
+  de::{self, Deserializer, SeqAccess, Visitor},
+  ser::{SerializeTuple, Serializer},
+  Deserialize, Serialize,
+};
+use std::fmt;
+
+// Binary formats encode fixed-size types as tuples of bytes (no length prefix), while
+// human-readable formats such as JSON encode every type as a lowercase hex string.
+
+fn serialize_fixed_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+  if serializer.is_human_readable() {
+    serializer.serialize_str(&hex::encode(bytes))
+  } else {
+    let mut tuple = serializer.serialize_tuple(bytes.len())?;
+    for byte in bytes {
+      tuple.serialize_element(byte)?;
+    }
+    tuple.end()
+  }
+}
+
+fn serialize_var_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+  if serializer.is_human_readable() {
+    serializer.serialize_str(&hex::encode(bytes))
+  } else {
+    serializer.serialize_bytes(bytes)
+  }
+}
+
+struct FixedBytesVisitor {
+  len: usize,
+}
+
+impl<'de> Visitor<'de> for FixedBytesVisitor {
+  type Value = Vec<u8>;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "an array of {} bytes", self.len)
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+    let mut bytes = Vec::with_capacity(self.len);
+    for i in 0..self.len {
+      match seq.next_element::<u8>()? {
+        Some(byte) => bytes.push(byte),
+        None => return Err(de::Error::invalid_length(i, &self)),
+      }
+    }
+    Ok(bytes)
+  }
+}
+
+struct VarBytesVisitor;
+
+impl<'de> Visitor<'de> for VarBytesVisitor {
+  type Value = Vec<u8>;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "a byte array")
+  }
+
+  fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+    Ok(v.to_vec())
+  }
+
+  fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+    Ok(v)
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+    let mut bytes = Vec::new();
+    while let Some(byte) = seq.next_element::<u8>()? {
+      bytes.push(byte);
+    }
+    Ok(bytes)
+  }
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+  let s = String::deserialize(deserializer)?;
+  hex::decode(&s).map_err(|e| de::Error::custom(format!("invalid hex string: {}", e)))
+}
+
+fn deserialize_fixed_bytes<'de, D: Deserializer<'de>>(
+  deserializer: D,
+  len: usize,
+) -> Result<Vec<u8>, D::Error> {
+  if deserializer.is_human_readable() {
+    let bytes = deserialize_hex(deserializer)?;
+    if bytes.len() != len {
+      return Err(de::Error::invalid_length(
+        bytes.len(),
+        &FixedBytesVisitor { len },
+      ));
+    }
+    Ok(bytes)
+  } else {
+    deserializer.deserialize_tuple(len, FixedBytesVisitor { len })
+  }
+}
+
+fn deserialize_var_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+  if deserializer.is_human_readable() {
+    deserialize_hex(deserializer)
+  } else {
+    deserializer.deserialize_byte_buf(VarBytesVisitor)
+  }
+}
+
+impl Serialize for NimbleDigest {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_fixed_bytes(&self.to_bytes(), serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for NimbleDigest {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let bytes = deserialize_fixed_bytes(deserializer, NimbleDigest::num_bytes())?;
+    NimbleDigest::from_bytes(&bytes).map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+}
+
+impl Serialize for Nonce {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_fixed_bytes(&self.to_bytes(), serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Nonce {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let bytes = deserialize_fixed_bytes(deserializer, Nonce::num_bytes())?;
+    Nonce::from_bytes(&bytes).map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+}
+
+impl Serialize for PublicKey {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_fixed_bytes(&self.to_bytes(), serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let bytes = deserialize_fixed_bytes(deserializer, PublicKey::num_bytes())?;
+    PublicKey::from_bytes(&bytes).map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+}
+
+impl Serialize for Receipt {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_fixed_bytes(&self.to_bytes(), serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Receipt {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let bytes = deserialize_fixed_bytes(deserializer, Receipt::num_bytes())?;
+    Receipt::from_bytes(&bytes).map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+}
+
+impl Serialize for Block {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_var_bytes(&self.to_bytes(), serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Block {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let bytes = deserialize_var_bytes(deserializer)?;
+    Block::try_new(&bytes).map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+}
+
+impl Serialize for Receipts {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_var_bytes(&self.to_bytes(), serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Receipts {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let bytes = deserialize_var_bytes(deserializer)?;
+    Receipts::try_from_bytes(&bytes).map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    signature::{PrivateKey, PrivateKeyTrait},
+    IdSig, MetaBlock,
+  };
+
+  fn sample_receipts() -> Receipts {
+    let sk = PrivateKey::new();
+    let view = NimbleDigest::digest(b"view");
+    let metablock = MetaBlock::genesis(&NimbleDigest::digest(b"block"));
+    let sig = sk.sign(b"message").unwrap();
+    let mut receipts = Receipts::new();
+    receipts.add(&Receipt::new(
+      view,
+      metablock,
+      IdSig::new(sk.get_public_key().unwrap(), sig),
+    ));
+    receipts
+  }
+
+  #[cfg(not(feature = "blake3"))]
+  #[test]
+  fn test_json_round_trip() {
+    let digest = NimbleDigest::digest(b"1");
+    let json = serde_json::to_string(&digest).unwrap();
+    assert_eq!(
+      json,
+      "\"6b86b273ff34fce19d6b804eff5a3f5747ada4eaa22f1d49c01e52ddb7875b4b\""
+    );
+    assert_eq!(serde_json::from_str::<NimbleDigest>(&json).unwrap(), digest);
+
+    let nonce = Nonce::try_from_bytes(&[7u8; 16]).unwrap();
+    let json = serde_json::to_string(&nonce).unwrap();
+    assert_eq!(serde_json::from_str::<Nonce>(&json).unwrap(), nonce);
+
+    let pk = PrivateKey::new().get_public_key().unwrap();
+    let json = serde_json::to_string(&pk).unwrap();
+    let decoded = serde_json::from_str::<PublicKey>(&json).unwrap();
+    assert_eq!(decoded.to_bytes(), pk.to_bytes());
+
+    let receipts = sample_receipts();
+    let json = serde_json::to_string(&receipts).unwrap();
+    let decoded = serde_json::from_str::<Receipts>(&json).unwrap();
+    assert_eq!(decoded.to_bytes(), receipts.to_bytes());
+
+    let (ex_meta_block, id_sigs) = receipts.get().iter().next().unwrap();
+    let receipt = Receipt::new(
+      *ex_meta_block.get_view(),
+      ex_meta_block.get_metablock().clone(),
+      id_sigs[0].clone(),
+    );
+    let json = serde_json::to_string(&receipt).unwrap();
+    let decoded = serde_json::from_str::<Receipt>(&json).unwrap();
+    assert_eq!(decoded.to_bytes(), receipt.to_bytes());
+  }
+
+  #[test]
+  fn test_bincode_round_trip() {
+    let digest = NimbleDigest::digest(b"1");
+    let bytes = bincode::serialize(&digest).unwrap();
+    assert_eq!(bytes, digest.to_bytes());
+    assert_eq!(
+      bincode::deserialize::<NimbleDigest>(&bytes).unwrap(),
+      digest
+    );
+
+    let nonce = Nonce::try_from_bytes(&[7u8; 16]).unwrap();
+    let bytes = bincode::serialize(&nonce).unwrap();
+    assert_eq!(bytes.len(), Nonce::num_bytes());
+    assert_eq!(bincode::deserialize::<Nonce>(&bytes).unwrap(), nonce);
+
+    let pk = PrivateKey::new().get_public_key().unwrap();
+    let bytes = bincode::serialize(&pk).unwrap();
+    assert_eq!(bytes, pk.to_bytes());
+    let decoded = bincode::deserialize::<PublicKey>(&bytes).unwrap();
+    assert_eq!(decoded.to_bytes(), pk.to_bytes());
+
+    let receipts = sample_receipts();
+    let bytes = bincode::serialize(&receipts).unwrap();
+    let decoded = bincode::deserialize::<Receipts>(&bytes).unwrap();
+    assert_eq!(decoded.to_bytes(), receipts.to_bytes());
+
+    let block = Block::new(b"block contents");
+    let bytes = bincode::serialize(&block).unwrap();
+    let decoded = bincode::deserialize::<Block>(&bytes).unwrap();
+    assert_eq!(decoded.to_bytes(), block.to_bytes());
+    let json = serde_json::to_string(&block).unwrap();
+    assert_eq!(json, format!("\"{}\"", hex::encode(b"block contents")));
+  }
+
+  #[test]
+  fn test_malformed_inputs() {
+    // wrong length
+    let json = format!("\"{}\"", hex::encode([1u8; 31]));
+    assert!(serde_json::from_str::<NimbleDigest>(&json).is_err());
+    assert!(bincode::deserialize::<NimbleDigest>(&[1u8; 31]).is_err());
+
+    // malformed hex
+    let json = format!("\"{}zz\"", hex::encode([1u8; 31]));
+    assert!(serde_json::from_str::<NimbleDigest>(&json).is_err());
+    assert!(serde_json::from_str::<Nonce>("\"0123456789abcdef0123456789abcde\"").is_err());
+
+    // invalid public key and receipts
+    let json = format!("\"{}\"", hex::encode([0u8; 33]));
+    assert!(serde_json::from_str::<PublicKey>(&json).is_err());
+    assert!(serde_json::from_str::<Receipts>("\"02\"").is_err());
+
+    // oversized block
+    let json = format!("\"{}\"", hex::encode(vec![0u8; crate::MAX_BLOCK_SIZE + 1]));
+    assert!(serde_json::from_str::<Block>(&json).is_err());
+  }
+}
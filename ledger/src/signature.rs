@@ -1,17 +1,35 @@
 use core::fmt::Debug;
-use itertools::concat;
-use openssl::{
-  bn::{BigNum, BigNumContext},
-  ec::*,
-  ecdsa::EcdsaSig,
-  nid::Nid,
-  pkey::{Private, Public},
-};
+use subtle::ConstantTimeEq;
+
+#[cfg(feature = "bls")]
+pub mod bls;
+#[cfg(feature = "openssl")]
+mod openssl_backend;
+#[cfg(feature = "openssl")]
+pub use openssl_backend::{PrivateKey, PublicKey, Signature};
+
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+mod p256_backend;
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+pub use p256_backend::{PrivateKey, PublicKey, Signature};
+
+#[cfg(not(any(feature = "openssl", feature = "rustcrypto")))]
+compile_error!("the ledger needs a signature backend: enable `openssl` or `rustcrypto`");
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum CryptoError {
   /// returned if the supplied byte array cannot be parsed as a valid public key
   InvalidPublicKeyBytes,
+  /// returned if a public key is not `PublicKey::num_bytes()` long
+  InvalidPublicKeyLength,
+  /// returned if a public key is not a compressed point on the curve in canonical form
+  NonCanonicalPublicKey,
+  /// returned if a public key is the identity point, which any signature would verify under
+  WeakPublicKey,
+  /// returned if a signature is not `Signature::num_bytes()` long
+  InvalidSignatureLength,
+  /// returned if a signature component is zero or not less than the order of the group
+  SignatureScalarOutOfRange,
   /// returned if the provided signature is invalid when verifying
   InvalidSignature,
   /// returned if there's an error when signing
@@ -20,8 +38,37 @@ pub enum CryptoError {
   InvalidPrivateKeyPem,
   /// returned if there is an error when deriving a signature from DER
   FailedToGetSigFromDER,
+  /// returned if the supplied byte array cannot be parsed as a valid private key
+  InvalidPrivateKeyBytes,
+  /// returned if a scheme whose signatures do not aggregate is asked to aggregate them
+  AggregationUnsupported,
+  /// returned if a key does not come with a valid proof that its holder knows its private key
+  InvalidProofOfPossession,
+}
+
+impl std::fmt::Display for CryptoError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let msg = match self {
+      CryptoError::InvalidPublicKeyBytes => "invalid public key",
+      CryptoError::InvalidPublicKeyLength => "public key has an incorrect length",
+      CryptoError::NonCanonicalPublicKey => "public key is not a canonical compressed point",
+      CryptoError::WeakPublicKey => "public key is the identity point",
+      CryptoError::InvalidSignatureLength => "signature has an incorrect length",
+      CryptoError::SignatureScalarOutOfRange => "signature scalar is out of range",
+      CryptoError::InvalidSignature => "invalid signature",
+      CryptoError::SignatureGenerationError => "failed to generate a signature",
+      CryptoError::InvalidPrivateKeyPem => "invalid private key PEM",
+      CryptoError::FailedToGetSigFromDER => "invalid DER-encoded signature",
+      CryptoError::InvalidPrivateKeyBytes => "invalid private key",
+      CryptoError::AggregationUnsupported => "signatures of the scheme do not aggregate",
+      CryptoError::InvalidProofOfPossession => "invalid proof of possession",
+    };
+    write!(f, "{}", msg)
+  }
 }
 
+impl std::error::Error for CryptoError {}
+
 pub trait PublicKeyTrait {
   fn num_bytes() -> usize;
   fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError>
@@ -53,186 +100,128 @@ pub trait SignatureTrait {
   fn to_bytes(&self) -> Vec<u8>;
 }
 
-/// Types and concrete implementations of types for ECDSA algorithm with P-256 using OpenSSL
-pub struct PublicKey {
-  key: EcKey<Public>,
+/// the schemes that endorsers sign receipts with; every endorser of a view signs with the scheme
+/// that the view records, which is P-256 unless it records another
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SignatureSchemeId {
+  /// ECDSA over P-256, the scheme of the identity keys of endorsers
+  P256,
+  /// BLS over BLS12-381 with public keys in G1, whose signatures aggregate
+  Bls12381,
 }
 
-pub struct PrivateKey {
-  key: EcKey<Private>,
-}
-
-pub struct Signature {
-  sig: EcdsaSig,
-}
-
-impl PublicKeyTrait for PublicKey {
-  fn num_bytes() -> usize {
-    33
-  }
-
-  fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
-    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
-    let point = {
-      let mut ctx = BigNumContext::new().unwrap();
-      let res = EcPoint::from_bytes(&group, bytes, &mut ctx);
-      if res.is_err() {
-        return Err(CryptoError::InvalidPublicKeyBytes);
-      }
-      res.unwrap()
-    };
-
-    let res = EcKey::from_public_key(&group, &point);
-    if let Ok(key) = res {
-      Ok(PublicKey { key })
-    } else {
-      Err(CryptoError::InvalidPublicKeyBytes)
+impl SignatureSchemeId {
+  pub fn to_u8(self) -> u8 {
+    match self {
+      SignatureSchemeId::P256 => 0,
+      SignatureSchemeId::Bls12381 => 1,
     }
   }
 
-  fn to_bytes(&self) -> Vec<u8> {
-    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
-    let mut ctx = BigNumContext::new().unwrap();
-    self
-      .key
-      .public_key()
-      .to_bytes(&group, PointConversionForm::COMPRESSED, &mut ctx)
-      .unwrap()
+  pub fn from_u8(id: u8) -> Option<Self> {
+    match id {
+      0 => Some(SignatureSchemeId::P256),
+      1 => Some(SignatureSchemeId::Bls12381),
+      _ => None,
+    }
   }
-}
 
-impl PublicKey {
-  pub fn to_der(&self) -> Vec<u8> {
-    self.key.public_key_to_der().unwrap()
+  pub fn public_key_len(self) -> usize {
+    match self {
+      SignatureSchemeId::P256 => 33,
+      SignatureSchemeId::Bls12381 => 48,
+    }
   }
 
-  pub fn to_uncompressed(&self) -> Vec<u8> {
-    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
-    let mut ctx = BigNumContext::new().unwrap();
-    self
-      .key
-      .public_key()
-      .to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)
-      .unwrap()
+  pub fn signature_len(self) -> usize {
+    match self {
+      SignatureSchemeId::P256 => 64,
+      SignatureSchemeId::Bls12381 => 96,
+    }
   }
 }
 
-impl PrivateKeyTrait for PrivateKey {
-  fn new() -> Self {
-    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
-    let key = EcKey::generate(&group).unwrap();
-    PrivateKey { key }
+impl std::fmt::Display for SignatureSchemeId {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      SignatureSchemeId::P256 => write!(f, "p256"),
+      SignatureSchemeId::Bls12381 => write!(f, "bls12-381"),
+    }
   }
+}
 
-  fn get_public_key(&self) -> Result<PublicKey, CryptoError> {
-    let key = {
-      let point = self.key.public_key();
-      let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
-      let res = EcKey::from_public_key(&group, point);
-      if res.is_err() {
-        return Err(CryptoError::InvalidPublicKeyBytes);
-      }
-      res.unwrap()
-    };
-    Ok(PublicKey { key })
+/// a scheme that endorsers sign receipts with. Aggregation is optional: the schemes whose
+/// signatures do not aggregate keep the defaults, which fail with
+/// `CryptoError::AggregationUnsupported`
+pub trait SignatureScheme {
+  type PublicKey;
+  type Signature;
+
+  fn id() -> SignatureSchemeId;
+  fn public_key_from_bytes(bytes: &[u8]) -> Result<Self::PublicKey, CryptoError>;
+  fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, CryptoError>;
+  fn signature_to_bytes(sig: &Self::Signature) -> Vec<u8>;
+  fn verify(pk: &Self::PublicKey, sig: &Self::Signature, msg: &[u8]) -> Result<(), CryptoError>;
+
+  fn supports_aggregation() -> bool {
+    false
   }
 
-  fn sign(&self, msg: &[u8]) -> Result<Signature, CryptoError> {
-    let sig = {
-      let res = EcdsaSig::sign(msg, &self.key);
-      if res.is_err() {
-        return Err(CryptoError::SignatureGenerationError);
-      }
-      res.unwrap()
-    };
-    Ok(Signature { sig })
+  /// aggregates signatures on the same message into one
+  fn aggregate(_sigs: &[&Self::Signature]) -> Result<Self::Signature, CryptoError> {
+    Err(CryptoError::AggregationUnsupported)
   }
-}
 
-impl PrivateKey {
-  pub fn from_pem(pem: &[u8]) -> Result<PrivateKey, CryptoError> {
-    let res = EcKey::private_key_from_pem(pem);
-    if res.is_err() {
-      return Err(CryptoError::InvalidPrivateKeyPem);
-    }
-    let key = res.unwrap();
-    Ok(PrivateKey { key })
+  /// verifies that an aggregate of signatures on `msg` holds a signature by each of `pks`; the keys
+  /// must have been checked for proofs of possession, or one of them could cancel out the others
+  fn verify_aggregate(
+    _pks: &[&Self::PublicKey],
+    _sig: &Self::Signature,
+    _msg: &[u8],
+  ) -> Result<(), CryptoError> {
+    Err(CryptoError::AggregationUnsupported)
   }
 }
 
-impl SignatureTrait for Signature {
-  fn num_bytes() -> usize {
-    64
-  }
-
-  fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
-    if bytes.len() != Self::num_bytes() {
-      return Err(CryptoError::InvalidSignature);
-    }
+/// ECDSA over P-256 with the keys of the backend that the ledger was built with
+pub struct P256;
 
-    let r = {
-      let res = BigNum::from_slice(&bytes[0..Self::num_bytes() / 2]);
-      if res.is_err() {
-        return Err(CryptoError::InvalidSignature);
-      }
-      res.unwrap()
-    };
-    let s = {
-      let res = BigNum::from_slice(&bytes[Self::num_bytes() / 2..]);
-      if res.is_err() {
-        return Err(CryptoError::InvalidSignature);
-      }
-      res.unwrap()
-    };
-
-    let sig = {
-      let res = EcdsaSig::from_private_components(r, s);
-      if res.is_err() {
-        return Err(CryptoError::InvalidSignature);
-      }
-      res.unwrap()
-    };
+impl SignatureScheme for P256 {
+  type PublicKey = PublicKey;
+  type Signature = Signature;
 
-    Ok(Signature { sig })
+  fn id() -> SignatureSchemeId {
+    SignatureSchemeId::P256
   }
 
-  fn verify(&self, pk: &PublicKey, msg: &[u8]) -> Result<(), CryptoError> {
-    let res = self.sig.verify(msg, &pk.key);
-    if let Ok(true) = res {
-      Ok(())
-    } else {
-      Err(CryptoError::InvalidSignature)
-    }
+  fn public_key_from_bytes(bytes: &[u8]) -> Result<PublicKey, CryptoError> {
+    PublicKey::from_bytes(bytes)
   }
 
-  fn to_bytes(&self) -> Vec<u8> {
-    let r = self
-      .sig
-      .r()
-      .to_vec_padded((Self::num_bytes() / 2) as i32)
-      .unwrap();
-    let s = self
-      .sig
-      .s()
-      .to_vec_padded((Self::num_bytes() / 2) as i32)
-      .unwrap();
-    concat(vec![r, s]).to_vec()
+  fn signature_from_bytes(bytes: &[u8]) -> Result<Signature, CryptoError> {
+    Signature::from_bytes(bytes)
   }
-}
 
-impl Signature {
-  pub fn to_der(&self) -> Vec<u8> {
-    self.sig.to_der().unwrap()
+  fn signature_to_bytes(sig: &Signature) -> Vec<u8> {
+    sig.to_bytes()
   }
 
-  pub fn from_der(der: &[u8]) -> Result<Self, CryptoError> {
-    match EcdsaSig::from_der(der) {
-      Ok(sig) => Ok(Signature { sig }),
-      Err(_) => Err(CryptoError::FailedToGetSigFromDER),
-    }
+  fn verify(pk: &PublicKey, sig: &Signature, msg: &[u8]) -> Result<(), CryptoError> {
+    sig.verify(pk, msg)
   }
 }
 
+// fails to compile if `PrivateKey` ever implements `Debug`
+const _: fn() = || {
+  trait AmbiguousIfDebug<A> {
+    fn some_item() {}
+  }
+  impl<T: ?Sized> AmbiguousIfDebug<()> for T {}
+  struct Invalid;
+  impl<T: ?Sized + Debug> AmbiguousIfDebug<Invalid> for T {}
+  let _ = <PrivateKey as AmbiguousIfDebug<_>>::some_item;
+};
+
 impl Clone for PublicKey {
   fn clone(&self) -> Self {
     PublicKey::from_bytes(&self.to_bytes()).unwrap()
@@ -251,6 +240,22 @@ impl Clone for Signature {
   }
 }
 
+impl PartialEq for Signature {
+  fn eq(&self, other: &Self) -> bool {
+    self.to_bytes().ct_eq(&other.to_bytes()).into()
+  }
+}
+
+impl Eq for Signature {}
+
+impl PartialEq for PublicKey {
+  fn eq(&self, other: &Self) -> bool {
+    self.to_bytes().ct_eq(&other.to_bytes()).into()
+  }
+}
+
+impl Eq for PublicKey {}
+
 impl Debug for Signature {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     write!(f, "Signature({:?})", self.to_bytes())
@@ -279,6 +284,139 @@ mod tests {
     assert!(res.is_err());
   }
 
+  #[test]
+  fn test_signature_and_public_key_equality() {
+    let sk = PrivateKey::new();
+    let sig = sk.sign(b"hello world").unwrap();
+    assert_eq!(sig, Signature::from_bytes(&sig.to_bytes()).unwrap());
+    assert_ne!(sig, sk.sign(b"hello world2").unwrap());
+
+    let pk = sk.get_public_key().unwrap();
+    assert_eq!(pk, PublicKey::from_bytes(&pk.to_bytes()).unwrap());
+    assert_ne!(pk, PrivateKey::new().get_public_key().unwrap());
+  }
+
+  #[test]
+  fn test_private_key_bytes_round_trip() {
+    let sk = PrivateKey::new();
+    let bytes = sk.to_bytes();
+    assert_eq!(bytes.len(), PrivateKey::num_bytes());
+    let sk2 = PrivateKey::from_bytes(&bytes).unwrap();
+    assert_eq!(sk2.get_public_key().unwrap(), sk.get_public_key().unwrap());
+
+    let sig = sk2.sign(b"hello world").unwrap();
+    assert!(sig
+      .verify(&sk.get_public_key().unwrap(), b"hello world")
+      .is_ok());
+
+    assert!(PrivateKey::from_bytes(&bytes[1..]).is_err());
+    assert!(PrivateKey::from_bytes(&[0u8; 32]).is_err());
+  }
+
+  #[test]
+  fn test_malformed_public_keys() {
+    let pk = PrivateKey::new().get_public_key().unwrap().to_bytes();
+
+    assert_eq!(
+      PublicKey::from_bytes(&pk[1..]).unwrap_err(),
+      CryptoError::InvalidPublicKeyLength
+    );
+    assert_eq!(
+      PublicKey::from_bytes(&[]).unwrap_err(),
+      CryptoError::InvalidPublicKeyLength
+    );
+
+    // the uncompressed encoding is not canonical
+    let uncompressed = PrivateKey::new()
+      .get_public_key()
+      .unwrap()
+      .to_uncompressed();
+    assert_eq!(
+      PublicKey::from_bytes(&uncompressed).unwrap_err(),
+      CryptoError::InvalidPublicKeyLength
+    );
+
+    // a bad prefix, an x-coordinate not on the curve, and an unreduced x-coordinate
+    let mut bad_prefix = pk.clone();
+    bad_prefix[0] = 0x04;
+    assert_eq!(
+      PublicKey::from_bytes(&bad_prefix).unwrap_err(),
+      CryptoError::NonCanonicalPublicKey
+    );
+    // x = 1 is not the x-coordinate of any point on P-256
+    let mut off_curve = vec![0x02];
+    off_curve.extend(vec![0u8; 31]);
+    off_curve.push(1);
+    assert_eq!(
+      PublicKey::from_bytes(&off_curve).unwrap_err(),
+      CryptoError::NonCanonicalPublicKey
+    );
+    let unreduced = [vec![0x02], vec![0xff; 32]].concat();
+    assert_eq!(
+      PublicKey::from_bytes(&unreduced).unwrap_err(),
+      CryptoError::NonCanonicalPublicKey
+    );
+
+    // the identity point has no compressed encoding, so its encoding is rejected as well
+    assert_eq!(
+      PublicKey::from_bytes(&[0u8; 33]).unwrap_err(),
+      CryptoError::NonCanonicalPublicKey
+    );
+  }
+
+  #[test]
+  fn test_malformed_signatures() {
+    let sig = PrivateKey::new().sign(b"hello world").unwrap().to_bytes();
+    assert_eq!(
+      Signature::from_bytes(&sig[1..]).unwrap_err(),
+      CryptoError::InvalidSignatureLength
+    );
+
+    // zero and unreduced components are rejected
+    let zero_r = [vec![0u8; 32], sig[32..].to_vec()].concat();
+    assert_eq!(
+      Signature::from_bytes(&zero_r).unwrap_err(),
+      CryptoError::SignatureScalarOutOfRange
+    );
+    let unreduced_s = [sig[..32].to_vec(), vec![0xff; 32]].concat();
+    assert_eq!(
+      Signature::from_bytes(&unreduced_s).unwrap_err(),
+      CryptoError::SignatureScalarOutOfRange
+    );
+    assert!(Signature::from_bytes(&sig).is_ok());
+    assert_eq!(
+      CryptoError::SignatureScalarOutOfRange.to_string(),
+      "signature scalar is out of range"
+    );
+  }
+
+  #[test]
+  fn test_p256_scheme() {
+    let sk = PrivateKey::new();
+    let pk = sk.get_public_key().unwrap();
+    let sig = sk.sign(b"hello world").unwrap();
+    assert_eq!(P256::id().public_key_len(), PublicKey::num_bytes());
+    assert_eq!(P256::id().signature_len(), Signature::num_bytes());
+    assert!(P256::verify(&pk, &sig, b"hello world").is_ok());
+    assert!(P256::verify(&pk, &sig, b"hello world2").is_err());
+
+    // ECDSA signatures do not aggregate
+    assert!(!P256::supports_aggregation());
+    assert_eq!(
+      P256::aggregate(&[&sig]).unwrap_err(),
+      CryptoError::AggregationUnsupported
+    );
+    assert_eq!(
+      P256::verify_aggregate(&[&pk], &sig, b"hello world").unwrap_err(),
+      CryptoError::AggregationUnsupported
+    );
+
+    for id in [SignatureSchemeId::P256, SignatureSchemeId::Bls12381] {
+      assert_eq!(SignatureSchemeId::from_u8(id.to_u8()), Some(id));
+    }
+    assert_eq!(SignatureSchemeId::from_u8(2), None);
+  }
+
   #[test]
   fn test_compressed_pk_and_raw_signature_encoding() {
     let pk_bytes =
@@ -287,7 +425,7 @@ mod tests {
       hex::decode("3341835E0BA33047E0B472F5622B157ED5879085213A1777963571220E48BF0F").unwrap();
     let s_bytes =
       hex::decode("8B630A0251F157CAB579FD3D589969A92CCC75C9B5058E2BF77F7038D352DF10").unwrap();
-    let sig_bytes = concat(vec![r_bytes, s_bytes]).to_vec();
+    let sig_bytes = [r_bytes, s_bytes].concat();
     let m =
       hex::decode("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
 
@@ -0,0 +1,286 @@
+//! BLS signatures over BLS12-381 with `blst`, with public keys in G1 and signatures in G2, in the
+//! proof-of-possession ciphersuite: the signatures of the endorsers of a view on one message
+//! aggregate into a single signature, which verifies against the sum of the keys of its signers.
+//! A view takes a key only with a proof that its holder knows the private key (see
+//! `PrivateKey::prove_possession`), since a key chosen as a function of the keys of others could
+//! otherwise forge an aggregate on their behalf.
+use super::{CryptoError, SignatureScheme, SignatureSchemeId};
+use blst::{min_pk, BLST_ERROR};
+use rand::{rngs::OsRng, RngCore};
+use zeroize::Zeroizing;
+
+/// the domain separation tag of the signatures on messages
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// the domain separation tag of the proofs of possession, so that no signature on a message doubles
+/// as a proof for a key
+const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublicKey {
+  key: min_pk::PublicKey,
+}
+
+/// Deliberately does not implement `Debug`, like the P-256 `PrivateKey`
+pub struct PrivateKey {
+  key: min_pk::SecretKey,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signature {
+  sig: min_pk::Signature,
+}
+
+fn check(res: BLST_ERROR, err: CryptoError) -> Result<(), CryptoError> {
+  match res {
+    BLST_ERROR::BLST_SUCCESS => Ok(()),
+    _ => Err(err),
+  }
+}
+
+impl PrivateKey {
+  pub fn new() -> Self {
+    let mut ikm = Zeroizing::new([0u8; 32]);
+    OsRng.fill_bytes(ikm.as_mut());
+    let key = min_pk::SecretKey::key_gen(ikm.as_ref(), &[])
+      .expect("32 bytes of key material are enough to derive a key");
+    PrivateKey { key }
+  }
+
+  pub fn num_bytes() -> usize {
+    32
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != PrivateKey::num_bytes() {
+      return Err(CryptoError::InvalidPrivateKeyBytes);
+    }
+    let key =
+      min_pk::SecretKey::from_bytes(bytes).map_err(|_e| CryptoError::InvalidPrivateKeyBytes)?;
+    Ok(PrivateKey { key })
+  }
+
+  pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(self.key.to_bytes().to_vec())
+  }
+
+  pub fn get_public_key(&self) -> PublicKey {
+    PublicKey {
+      key: self.key.sk_to_pk(),
+    }
+  }
+
+  pub fn sign(&self, msg: &[u8]) -> Signature {
+    Signature {
+      sig: self.key.sign(msg, SIGNATURE_DST, &[]),
+    }
+  }
+
+  /// proves that the holder of the public key of this key knows it
+  pub fn prove_possession(&self) -> Signature {
+    Signature {
+      sig: self
+        .key
+        .sign(&self.get_public_key().to_bytes(), POP_DST, &[]),
+    }
+  }
+}
+
+impl Default for PrivateKey {
+  fn default() -> Self {
+    PrivateKey::new()
+  }
+}
+
+impl PublicKey {
+  pub fn num_bytes() -> usize {
+    SignatureSchemeId::Bls12381.public_key_len()
+  }
+
+  /// parses a compressed point, which must be in the group of prime order and not the identity
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != PublicKey::num_bytes() {
+      return Err(CryptoError::InvalidPublicKeyLength);
+    }
+    let key = min_pk::PublicKey::key_validate(bytes).map_err(|e| match e {
+      BLST_ERROR::BLST_PK_IS_INFINITY => CryptoError::WeakPublicKey,
+      _ => CryptoError::NonCanonicalPublicKey,
+    })?;
+    Ok(PublicKey { key })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    self.key.compress().to_vec()
+  }
+
+  /// checks a proof that the holder of this key knows its private key
+  pub fn verify_possession(&self, proof: &Signature) -> Result<(), CryptoError> {
+    check(
+      proof
+        .sig
+        .verify(true, &self.to_bytes(), POP_DST, &[], &self.key, false),
+      CryptoError::InvalidProofOfPossession,
+    )
+  }
+}
+
+impl Signature {
+  pub fn num_bytes() -> usize {
+    SignatureSchemeId::Bls12381.signature_len()
+  }
+
+  /// parses a compressed point, which must be in the group of prime order and not the identity
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != Signature::num_bytes() {
+      return Err(CryptoError::InvalidSignatureLength);
+    }
+    let sig =
+      min_pk::Signature::sig_validate(bytes, true).map_err(|_e| CryptoError::InvalidSignature)?;
+    Ok(Signature { sig })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    self.sig.compress().to_vec()
+  }
+
+  pub fn verify(&self, pk: &PublicKey, msg: &[u8]) -> Result<(), CryptoError> {
+    // the key and the signature were checked to be in their groups when they were parsed
+    check(
+      self
+        .sig
+        .verify(false, msg, SIGNATURE_DST, &[], &pk.key, false),
+      CryptoError::InvalidSignature,
+    )
+  }
+}
+
+/// BLS over BLS12-381, whose signatures on one message aggregate
+pub struct Bls;
+
+impl SignatureScheme for Bls {
+  type PublicKey = PublicKey;
+  type Signature = Signature;
+
+  fn id() -> SignatureSchemeId {
+    SignatureSchemeId::Bls12381
+  }
+
+  fn public_key_from_bytes(bytes: &[u8]) -> Result<PublicKey, CryptoError> {
+    PublicKey::from_bytes(bytes)
+  }
+
+  fn signature_from_bytes(bytes: &[u8]) -> Result<Signature, CryptoError> {
+    Signature::from_bytes(bytes)
+  }
+
+  fn signature_to_bytes(sig: &Signature) -> Vec<u8> {
+    sig.to_bytes()
+  }
+
+  fn verify(pk: &PublicKey, sig: &Signature, msg: &[u8]) -> Result<(), CryptoError> {
+    sig.verify(pk, msg)
+  }
+
+  fn supports_aggregation() -> bool {
+    true
+  }
+
+  fn aggregate(sigs: &[&Signature]) -> Result<Signature, CryptoError> {
+    if sigs.is_empty() {
+      return Err(CryptoError::InvalidSignature);
+    }
+    let sigs = sigs.iter().map(|sig| &sig.sig).collect::<Vec<_>>();
+    let aggregate = min_pk::AggregateSignature::aggregate(&sigs, false)
+      .map_err(|_e| CryptoError::InvalidSignature)?;
+    Ok(Signature {
+      sig: aggregate.to_signature(),
+    })
+  }
+
+  fn verify_aggregate(pks: &[&PublicKey], sig: &Signature, msg: &[u8]) -> Result<(), CryptoError> {
+    if pks.is_empty() {
+      return Err(CryptoError::InvalidSignature);
+    }
+    let pks = pks.iter().map(|pk| &pk.key).collect::<Vec<_>>();
+    check(
+      sig
+        .sig
+        .fast_aggregate_verify(false, msg, SIGNATURE_DST, &pks),
+      CryptoError::InvalidSignature,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_bls_sign_verify() {
+    let sk = PrivateKey::new();
+    let pk = sk.get_public_key();
+    let sig = sk.sign(b"hello world");
+    assert!(sig.verify(&pk, b"hello world").is_ok());
+    assert!(sig.verify(&pk, b"hello world2").is_err());
+
+    assert_eq!(pk, PublicKey::from_bytes(&pk.to_bytes()).unwrap());
+    assert_eq!(sig, Signature::from_bytes(&sig.to_bytes()).unwrap());
+    let sk2 = PrivateKey::from_bytes(&sk.to_bytes()).unwrap();
+    assert_eq!(sk2.get_public_key(), pk);
+
+    assert_eq!(
+      PublicKey::from_bytes(&pk.to_bytes()[1..]).unwrap_err(),
+      CryptoError::InvalidPublicKeyLength
+    );
+    // the compressed identity point
+    let mut identity = vec![0u8; PublicKey::num_bytes()];
+    identity[0] = 0xc0;
+    assert_eq!(
+      PublicKey::from_bytes(&identity).unwrap_err(),
+      CryptoError::WeakPublicKey
+    );
+    assert_eq!(
+      Signature::from_bytes(&[0u8; 64]).unwrap_err(),
+      CryptoError::InvalidSignatureLength
+    );
+  }
+
+  #[test]
+  fn test_bls_proof_of_possession() {
+    let sk = PrivateKey::new();
+    let pk = sk.get_public_key();
+    assert!(pk.verify_possession(&sk.prove_possession()).is_ok());
+
+    // neither the proof of another key nor a signature on the key itself proves possession
+    let other = PrivateKey::new();
+    assert_eq!(
+      pk.verify_possession(&other.prove_possession()),
+      Err(CryptoError::InvalidProofOfPossession)
+    );
+    assert_eq!(
+      pk.verify_possession(&sk.sign(&pk.to_bytes())),
+      Err(CryptoError::InvalidProofOfPossession)
+    );
+  }
+
+  #[test]
+  fn test_bls_aggregation() {
+    let msg = b"hello world";
+    let sks = (0..4).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let pks = sks.iter().map(|sk| sk.get_public_key()).collect::<Vec<_>>();
+    let sigs = sks.iter().map(|sk| sk.sign(msg)).collect::<Vec<_>>();
+
+    let aggregate = Bls::aggregate(&sigs.iter().collect::<Vec<_>>()).unwrap();
+    let all = pks.iter().collect::<Vec<_>>();
+    assert!(Bls::supports_aggregation());
+    assert!(Bls::verify_aggregate(&all, &aggregate, msg).is_ok());
+    assert!(Bls::verify_aggregate(&all, &aggregate, b"hello world2").is_err());
+    // the aggregate holds a signature by each of the keys, and by no others
+    assert!(Bls::verify_aggregate(&all[..3], &aggregate, msg).is_err());
+    let partial = Bls::aggregate(&sigs[..3].iter().collect::<Vec<_>>()).unwrap();
+    assert!(Bls::verify_aggregate(&all[..3], &partial, msg).is_ok());
+    assert!(Bls::verify_aggregate(&all, &partial, msg).is_err());
+
+    assert!(Bls::aggregate(&[]).is_err());
+    assert!(Bls::verify_aggregate(&[], &aggregate, msg).is_err());
+  }
+}
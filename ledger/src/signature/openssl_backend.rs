@@ -0,0 +1,269 @@
+//! ECDSA with P-256 using OpenSSL
+use super::{CryptoError, PrivateKeyTrait, PublicKeyTrait, SignatureTrait};
+use itertools::concat;
+use openssl::{
+  bn::{BigNum, BigNumContext},
+  ec::*,
+  ecdsa::EcdsaSig,
+  nid::Nid,
+  pkey::{Private, Public},
+};
+use zeroize::Zeroizing;
+
+pub struct PublicKey {
+  key: EcKey<Public>,
+}
+
+/// Deliberately does not implement `Debug` (see the assertion in `signature`) so that secret keys
+/// cannot be printed by accident
+pub struct PrivateKey {
+  key: EcKey<Private>,
+}
+
+pub struct Signature {
+  sig: EcdsaSig,
+}
+
+impl PublicKeyTrait for PublicKey {
+  fn num_bytes() -> usize {
+    33
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != Self::num_bytes() {
+      return Err(CryptoError::InvalidPublicKeyLength);
+    }
+    // only the compressed encoding is canonical
+    if bytes[0] != 0x02 && bytes[0] != 0x03 {
+      return Err(CryptoError::NonCanonicalPublicKey);
+    }
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let point = {
+      let res = EcPoint::from_bytes(&group, bytes, &mut ctx);
+      if res.is_err() {
+        return Err(CryptoError::NonCanonicalPublicKey);
+      }
+      res.unwrap()
+    };
+
+    // P-256 has a prime order, so the identity is the only point of low order
+    if point.is_infinity(&group) {
+      return Err(CryptoError::WeakPublicKey);
+    }
+
+    let res = EcKey::from_public_key(&group, &point);
+    let key = match res {
+      Ok(key) => key,
+      Err(_) => return Err(CryptoError::InvalidPublicKeyBytes),
+    };
+    if key.check_key().is_err() {
+      return Err(CryptoError::InvalidPublicKeyBytes);
+    }
+
+    // the x-coordinate must be reduced modulo the field prime
+    let encoded = point
+      .to_bytes(&group, PointConversionForm::COMPRESSED, &mut ctx)
+      .map_err(|_| CryptoError::InvalidPublicKeyBytes)?;
+    if encoded != bytes {
+      return Err(CryptoError::NonCanonicalPublicKey);
+    }
+
+    Ok(PublicKey { key })
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    self
+      .key
+      .public_key()
+      .to_bytes(&group, PointConversionForm::COMPRESSED, &mut ctx)
+      .unwrap()
+  }
+}
+
+impl PublicKey {
+  pub fn to_der(&self) -> Vec<u8> {
+    self.key.public_key_to_der().unwrap()
+  }
+
+  pub fn to_uncompressed(&self) -> Vec<u8> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    self
+      .key
+      .public_key()
+      .to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)
+      .unwrap()
+  }
+}
+
+impl PrivateKeyTrait for PrivateKey {
+  fn new() -> Self {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = EcKey::generate(&group).unwrap();
+    PrivateKey { key }
+  }
+
+  fn get_public_key(&self) -> Result<PublicKey, CryptoError> {
+    let key = {
+      let point = self.key.public_key();
+      let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+      let res = EcKey::from_public_key(&group, point);
+      if res.is_err() {
+        return Err(CryptoError::InvalidPublicKeyBytes);
+      }
+      res.unwrap()
+    };
+    Ok(PublicKey { key })
+  }
+
+  fn sign(&self, msg: &[u8]) -> Result<Signature, CryptoError> {
+    let sig = {
+      let res = EcdsaSig::sign(msg, &self.key);
+      if res.is_err() {
+        return Err(CryptoError::SignatureGenerationError);
+      }
+      res.unwrap()
+    };
+    Ok(Signature { sig })
+  }
+}
+
+impl PrivateKey {
+  pub fn num_bytes() -> usize {
+    32
+  }
+
+  pub fn from_pem(pem: &[u8]) -> Result<PrivateKey, CryptoError> {
+    let res = EcKey::private_key_from_pem(pem);
+    if res.is_err() {
+      return Err(CryptoError::InvalidPrivateKeyPem);
+    }
+    let key = res.unwrap();
+    Ok(PrivateKey { key })
+  }
+
+  /// parses the raw big-endian scalar of a private key
+  pub fn from_bytes(bytes: &[u8]) -> Result<PrivateKey, CryptoError> {
+    if bytes.len() != Self::num_bytes() {
+      return Err(CryptoError::InvalidPrivateKeyBytes);
+    }
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let scalar = BigNum::from_slice(bytes).map_err(|_| CryptoError::InvalidPrivateKeyBytes)?;
+    let point = {
+      let ctx = BigNumContext::new().unwrap();
+      let mut point = EcPoint::new(&group).unwrap();
+      point
+        .mul_generator(&group, &scalar, &ctx)
+        .map_err(|_| CryptoError::InvalidPrivateKeyBytes)?;
+      point
+    };
+    let key = EcKey::from_private_components(&group, &scalar, &point)
+      .map_err(|_| CryptoError::InvalidPrivateKeyBytes)?;
+    key
+      .check_key()
+      .map_err(|_| CryptoError::InvalidPrivateKeyBytes)?;
+    Ok(PrivateKey { key })
+  }
+
+  /// the raw big-endian scalar of the private key, scrubbed from memory when dropped
+  pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(
+      self
+        .key
+        .private_key()
+        .to_vec_padded(Self::num_bytes() as i32)
+        .unwrap(),
+    )
+  }
+}
+
+impl SignatureTrait for Signature {
+  fn num_bytes() -> usize {
+    64
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != Self::num_bytes() {
+      return Err(CryptoError::InvalidSignatureLength);
+    }
+
+    let r = {
+      let res = BigNum::from_slice(&bytes[0..Self::num_bytes() / 2]);
+      if res.is_err() {
+        return Err(CryptoError::InvalidSignature);
+      }
+      res.unwrap()
+    };
+    let s = {
+      let res = BigNum::from_slice(&bytes[Self::num_bytes() / 2..]);
+      if res.is_err() {
+        return Err(CryptoError::InvalidSignature);
+      }
+      res.unwrap()
+    };
+
+    // both components must lie in [1, n - 1], where n is the order of the group
+    let order = {
+      let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+      let mut ctx = BigNumContext::new().unwrap();
+      let mut order = BigNum::new().unwrap();
+      group.order(&mut order, &mut ctx).unwrap();
+      order
+    };
+    for scalar in [&r, &s] {
+      if scalar.num_bits() == 0 || scalar.ucmp(&order) != std::cmp::Ordering::Less {
+        return Err(CryptoError::SignatureScalarOutOfRange);
+      }
+    }
+
+    let sig = {
+      let res = EcdsaSig::from_private_components(r, s);
+      if res.is_err() {
+        return Err(CryptoError::InvalidSignature);
+      }
+      res.unwrap()
+    };
+
+    Ok(Signature { sig })
+  }
+
+  fn verify(&self, pk: &PublicKey, msg: &[u8]) -> Result<(), CryptoError> {
+    let res = self.sig.verify(msg, &pk.key);
+    if let Ok(true) = res {
+      Ok(())
+    } else {
+      Err(CryptoError::InvalidSignature)
+    }
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    let r = self
+      .sig
+      .r()
+      .to_vec_padded((Self::num_bytes() / 2) as i32)
+      .unwrap();
+    let s = self
+      .sig
+      .s()
+      .to_vec_padded((Self::num_bytes() / 2) as i32)
+      .unwrap();
+    concat(vec![r, s]).to_vec()
+  }
+}
+
+impl Signature {
+  pub fn to_der(&self) -> Vec<u8> {
+    self.sig.to_der().unwrap()
+  }
+
+  pub fn from_der(der: &[u8]) -> Result<Self, CryptoError> {
+    match EcdsaSig::from_der(der) {
+      Ok(sig) => Ok(Signature { sig }),
+      Err(_) => Err(CryptoError::FailedToGetSigFromDER),
+    }
+  }
+}
@@ -0,0 +1,166 @@
+//! ECDSA with P-256 using the pure-Rust `p256` crate, which builds without a C toolchain, e.g.,
+//! for wasm32. Keys, signatures, and their encodings are the same as with OpenSSL.
+use super::{CryptoError, PrivateKeyTrait, PublicKeyTrait, SignatureTrait};
+use p256::{
+  ecdsa::{
+    signature::hazmat::{PrehashSigner, PrehashVerifier},
+    Signature as EcdsaSignature, SigningKey, VerifyingKey,
+  },
+  pkcs8::{DecodePrivateKey, EncodePublicKey},
+  SecretKey,
+};
+use rand::rngs::OsRng;
+use zeroize::Zeroizing;
+
+pub struct PublicKey {
+  key: VerifyingKey,
+}
+
+/// Deliberately does not implement `Debug` (see the assertion in `signature`) so that secret keys
+/// cannot be printed by accident
+pub struct PrivateKey {
+  key: SigningKey,
+}
+
+pub struct Signature {
+  sig: EcdsaSignature,
+}
+
+/// OpenSSL signs a message as the digest that it is given: a message longer than the order of the
+/// group is truncated to its first 32 bytes, and a shorter one is read as a smaller number
+fn prehash(msg: &[u8]) -> [u8; 32] {
+  let mut prehash = [0u8; 32];
+  if msg.len() >= 32 {
+    prehash.copy_from_slice(&msg[..32]);
+  } else {
+    prehash[32 - msg.len()..].copy_from_slice(msg);
+  }
+  prehash
+}
+
+impl PublicKeyTrait for PublicKey {
+  fn num_bytes() -> usize {
+    33
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != Self::num_bytes() {
+      return Err(CryptoError::InvalidPublicKeyLength);
+    }
+    // only the compressed encoding is canonical
+    if bytes[0] != 0x02 && bytes[0] != 0x03 {
+      return Err(CryptoError::NonCanonicalPublicKey);
+    }
+
+    // rejects x-coordinates that are not reduced or not on the curve; the identity has no
+    // compressed encoding
+    let key =
+      VerifyingKey::from_sec1_bytes(bytes).map_err(|_| CryptoError::NonCanonicalPublicKey)?;
+    Ok(PublicKey { key })
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    self.key.to_encoded_point(true).as_bytes().to_vec()
+  }
+}
+
+impl PublicKey {
+  pub fn to_der(&self) -> Vec<u8> {
+    self.key.to_public_key_der().unwrap().as_bytes().to_vec()
+  }
+
+  pub fn to_uncompressed(&self) -> Vec<u8> {
+    self.key.to_encoded_point(false).as_bytes().to_vec()
+  }
+}
+
+impl PrivateKeyTrait for PrivateKey {
+  fn new() -> Self {
+    let key = SigningKey::random(&mut OsRng);
+    PrivateKey { key }
+  }
+
+  fn get_public_key(&self) -> Result<PublicKey, CryptoError> {
+    let key = VerifyingKey::from(&self.key);
+    Ok(PublicKey { key })
+  }
+
+  fn sign(&self, msg: &[u8]) -> Result<Signature, CryptoError> {
+    let sig = self
+      .key
+      .sign_prehash(&prehash(msg))
+      .map_err(|_| CryptoError::SignatureGenerationError)?;
+    Ok(Signature { sig })
+  }
+}
+
+impl PrivateKey {
+  pub fn num_bytes() -> usize {
+    32
+  }
+
+  /// parses a private key in SEC1 (`EC PRIVATE KEY`) or PKCS#8 (`PRIVATE KEY`) PEM
+  pub fn from_pem(pem: &[u8]) -> Result<PrivateKey, CryptoError> {
+    let pem = std::str::from_utf8(pem).map_err(|_| CryptoError::InvalidPrivateKeyPem)?;
+    let secret = SecretKey::from_sec1_pem(pem)
+      .or_else(|_| SecretKey::from_pkcs8_pem(pem))
+      .map_err(|_| CryptoError::InvalidPrivateKeyPem)?;
+    let key = SigningKey::from(secret);
+    Ok(PrivateKey { key })
+  }
+
+  /// parses the raw big-endian scalar of a private key
+  pub fn from_bytes(bytes: &[u8]) -> Result<PrivateKey, CryptoError> {
+    if bytes.len() != Self::num_bytes() {
+      return Err(CryptoError::InvalidPrivateKeyBytes);
+    }
+    // rejects zero and scalars that are not less than the order of the group
+    let key = SigningKey::from_slice(bytes).map_err(|_| CryptoError::InvalidPrivateKeyBytes)?;
+    Ok(PrivateKey { key })
+  }
+
+  /// the raw big-endian scalar of the private key, scrubbed from memory when dropped
+  pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(self.key.to_bytes().to_vec())
+  }
+}
+
+impl SignatureTrait for Signature {
+  fn num_bytes() -> usize {
+    64
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+    if bytes.len() != Self::num_bytes() {
+      return Err(CryptoError::InvalidSignatureLength);
+    }
+
+    // both components must lie in [1, n - 1], where n is the order of the group
+    let sig =
+      EcdsaSignature::from_slice(bytes).map_err(|_| CryptoError::SignatureScalarOutOfRange)?;
+    Ok(Signature { sig })
+  }
+
+  fn verify(&self, pk: &PublicKey, msg: &[u8]) -> Result<(), CryptoError> {
+    pk.key
+      .verify_prehash(&prehash(msg), &self.sig)
+      .map_err(|_| CryptoError::InvalidSignature)
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    self.sig.to_bytes().to_vec()
+  }
+}
+
+impl Signature {
+  pub fn to_der(&self) -> Vec<u8> {
+    self.sig.to_der().as_bytes().to_vec()
+  }
+
+  pub fn from_der(der: &[u8]) -> Result<Self, CryptoError> {
+    match EcdsaSignature::from_der(der) {
+      Ok(sig) => Ok(Signature { sig }),
+      Err(_) => Err(CryptoError::FailedToGetSigFromDER),
+    }
+  }
+}
@@ -0,0 +1,80 @@
+// Golden vectors of the receipts that endorsers return, shared by the tests of the endorser, which
+// check that it signs these payloads, and of the verifier, which check that it reconstructs them.
+// The digests are SHA-256 digests. The receipts are signed by the endorser with the public key
+// `GOLDEN_PUBLIC_KEY`, which was initialized with `GOLDEN_GROUP_IDENTITY` as both the group identity
+// and the block hash of the first view, and so signs in view `GOLDEN_VIEW`.
+#[allow(dead_code)]
+mod golden {
+  pub struct ReceiptVector {
+    /// the metablock of the entry that the receipts endorse
+    pub prev: &'static str,
+    pub block_hash: &'static str,
+    pub height: usize,
+    /// the nonce of a read of the tail; empty for writes
+    pub nonce: &'static str,
+    /// the payload that the endorser signs
+    pub message: &'static str,
+    pub receipts: &'static str,
+  }
+
+  pub const GOLDEN_PUBLIC_KEY: &str =
+    "02c1af9a5a14375c21d42cd451e948afe2e03be15fe8256434b2dad1b80905b28c";
+  pub const GOLDEN_GROUP_IDENTITY: &str =
+    "9c00ae256e55635a9bd056d34fc792f3d5e7f7933bdc1d4380c12010442a1ee3";
+  pub const GOLDEN_VIEW: &str = "f7a5f5df5a2a39ffb756e291a0349fc5d953f49119d834680862eff33b230c0b";
+  pub const GOLDEN_HANDLE: &[u8] = b"golden-ledger";
+  pub const GOLDEN_GENESIS_BLOCK: &[u8] = b"genesis";
+  pub const GOLDEN_GENESIS_METADATA: &[u8] = b"owner=alice";
+  pub const GOLDEN_BLOCK: &[u8] = b"block1";
+
+  /// the creation of the ledger with `GOLDEN_GENESIS_BLOCK` and `GOLDEN_GENESIS_METADATA`
+  pub const GOLDEN_NEW_LEDGER: ReceiptVector = ReceiptVector {
+    prev: "0000000000000000000000000000000000000000000000000000000000000000",
+    block_hash: "53c6dd31734ea5801520db6feff93a1293ada087aed309e10ec2a53f5485a8ed",
+    height: 0,
+    nonce: "",
+    message: "00850ce4e0ddb3dd470fd336f8707ca6162f82f9ab9b83e540456c7ef68f0305",
+    receipts: concat!(
+      "0101000000f7a5f5df5a2a39ffb756e291a0349fc5d953f49119d834680862eff33b230c0b000000",
+      "000000000000000000000000000000000000000000000000000000000053c6dd31734ea5801520db",
+      "6feff93a1293ada087aed309e10ec2a53f5485a8ed0000000000000000010000002100000002c1af",
+      "9a5a14375c21d42cd451e948afe2e03be15fe8256434b2dad1b80905b28c40000000657b4e8b7a3c",
+      "0eef8e7a996e9a0be68d6bcaa31203e1c6475a6b8f52ef255d6d1e858c45d698f845a3b94ab51bad",
+      "48549d436bc31d6e4db5357865e5d2220160",
+    ),
+  };
+
+  /// the append of `GOLDEN_BLOCK` with no nonces
+  pub const GOLDEN_APPEND: ReceiptVector = ReceiptVector {
+    prev: "627432973f707e04b4f4d763558abeb16e897f6d21b1e3bb7e407a889cb7f3a3",
+    block_hash: "1d6c13c0f60f81519764ce7fa51e9ffd3be7124f9d3dcc5760ebf4769068efbc",
+    height: 1,
+    nonce: "",
+    message: "210d394c5c5a58027f1a8421f6530fe0eb5e437438e08141462805c4b482c3ec",
+    receipts: concat!(
+      "0101000000f7a5f5df5a2a39ffb756e291a0349fc5d953f49119d834680862eff33b230c0b627432",
+      "973f707e04b4f4d763558abeb16e897f6d21b1e3bb7e407a889cb7f3a31d6c13c0f60f81519764ce",
+      "7fa51e9ffd3be7124f9d3dcc5760ebf4769068efbc0100000000000000010000002100000002c1af",
+      "9a5a14375c21d42cd451e948afe2e03be15fe8256434b2dad1b80905b28c400000007d28528ea1e7",
+      "d43afcc2c3f10516bfd96cb83742caa4230491f3dfbb0a147e69835d10f0acce330b72d1952b7323",
+      "3bd67a1d411ff6b2d5fdd31bcc451ceaf3c9",
+    ),
+  };
+
+  /// a read of the tail after the append
+  pub const GOLDEN_READ_LATEST: ReceiptVector = ReceiptVector {
+    prev: "627432973f707e04b4f4d763558abeb16e897f6d21b1e3bb7e407a889cb7f3a3",
+    block_hash: "1d6c13c0f60f81519764ce7fa51e9ffd3be7124f9d3dcc5760ebf4769068efbc",
+    height: 1,
+    nonce: "000102030405060708090a0b0c0d0e0f",
+    message: "35234503897c75035b2200cf947e26a0c8cff9a7f2b5fa71a907b5a55f353ea1",
+    receipts: concat!(
+      "0101000000f7a5f5df5a2a39ffb756e291a0349fc5d953f49119d834680862eff33b230c0b627432",
+      "973f707e04b4f4d763558abeb16e897f6d21b1e3bb7e407a889cb7f3a31d6c13c0f60f81519764ce",
+      "7fa51e9ffd3be7124f9d3dcc5760ebf4769068efbc0100000000000000010000002100000002c1af",
+      "9a5a14375c21d42cd451e948afe2e03be15fe8256434b2dad1b80905b28c400000008c72ef107341",
+      "e8cd58bc8fec09199a99fc93cf254f3a55d51281e411bd5124122541b70de83ff9853a20eb52a23d",
+      "11320da965e17e46cec500061d93b081decd",
+    ),
+  };
+}
@@ -0,0 +1,94 @@
+//! Launches the endorsers and the coordinator that a benchmark runs against, so that a full
+//! benchmark runs with one command. The binaries are ENDORSER_CMD and COORDINATOR_CMD, as in the
+//! tests, or else the `endorser` and `coordinator` next to the benchmark, where cargo builds them.
+use std::{
+  env,
+  ffi::OsString,
+  io::{BufRead, BufReader},
+  process::{Child, Command, Stdio},
+  thread,
+};
+
+/// a launched process, which is killed when dropped
+pub struct BoxChild {
+  child: Child,
+}
+
+impl Drop for BoxChild {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn binary(var: &str, name: &str) -> Result<OsString, String> {
+  if let Some(cmd) = env::var_os(var) {
+    return Ok(cmd);
+  }
+  let exe = env::current_exe().map_err(|e| format!("cannot locate nimble-bench: {}", e))?;
+  let path = exe.with_file_name(format!("{}{}", name, env::consts::EXE_SUFFIX));
+  if !path.exists() {
+    return Err(format!(
+      "cannot find the {} binary at {}; build it or set {}",
+      name,
+      path.display(),
+      var
+    ));
+  }
+  Ok(path.into_os_string())
+}
+
+/// starts `cmd` with `args`, and waits until it logs that it is listening; the rest of its output
+/// is discarded
+fn launch(name: &str, cmd: &OsString, args: &[String]) -> Result<BoxChild, String> {
+  let mut child = Command::new(cmd)
+    .args(args)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .map_err(|e| format!("the {} failed to start: {}", name, e))?;
+  let stdout = child.stdout.take().unwrap();
+  let process = BoxChild { child };
+
+  let mut lines = BufReader::new(stdout).lines();
+  loop {
+    match lines.next() {
+      Some(Ok(line)) if line.contains("listening on") => break,
+      Some(Ok(_line)) => continue,
+      _ => return Err(format!("the {} exited before it listened", name)),
+    }
+  }
+  // keeps reading the output, so that the process does not fail to write it
+  thread::spawn(move || lines.for_each(drop));
+  Ok(process)
+}
+
+/// launches an endorser on `port`
+pub fn launch_endorser(port: u16) -> Result<BoxChild, String> {
+  let cmd = binary("ENDORSER_CMD", "endorser")?;
+  launch("endorser", &cmd, &["-p".to_string(), port.to_string()])
+}
+
+/// launches a coordinator with an in-memory store on `port` and `ctrl_port`, with its metrics on
+/// `metrics_port`, in a view of the endorsers at `endorsers`
+pub fn launch_coordinator(
+  port: u16,
+  ctrl_port: u16,
+  metrics_port: u16,
+  endorsers: &[String],
+) -> Result<BoxChild, String> {
+  let cmd = binary("COORDINATOR_CMD", "coordinator")?;
+  let args = [
+    "-p".to_string(),
+    port.to_string(),
+    "-r".to_string(),
+    ctrl_port.to_string(),
+    "-s".to_string(),
+    "memory".to_string(),
+    "--metrics".to_string(),
+    metrics_port.to_string(),
+    "-e".to_string(),
+    endorsers.join(","),
+  ];
+  launch("coordinator", &cmd, &args)
+}
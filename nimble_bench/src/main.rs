@@ -0,0 +1,781 @@
+//! A benchmark of the throughput and the latency of appends and reads. Concurrent workers append
+//! to and read the tails of ledgers that the benchmark creates first, through a coordinator or,
+//! with `--direct`, on the endorsers themselves. After a warm-up, it reports the throughput, the
+//! p50, p95, and p99 latencies, and the errors of each operation, as text or, with `--json`, as
+//! JSON.
+//!
+//! With `--launch N`, the benchmark launches N endorsers and, unless `--direct`, a coordinator
+//! with an in-memory store in front of them, so a full benchmark runs with one command.
+//! `--min-throughput` fails the benchmark below an expected throughput, to catch regressions.
+//!
+//! With `--schedule` or `--profile`, it runs a schedule of profiles shaped like HDFS instead (see
+//! `schedule`), and reports the latencies of each profile and, with `--metrics`, the CPU time of
+//! the endorsers that the coordinator reports.
+mod launch;
+mod metrics;
+mod schedule;
+mod stats;
+mod target;
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod coordinator_proto {
+  tonic::include_proto!("coordinator_proto");
+}
+
+use crate::{
+  launch::{launch_coordinator, launch_endorser, BoxChild},
+  metrics::{sample_cpu, sample_cpu_every, CpuReport, CpuSample},
+  schedule::{Event, Schedule, PROFILES},
+  stats::{millis, OpStats, Summary},
+  target::{current_height, Target},
+};
+use clap::{App, Arg, ArgMatches};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+use std::{
+  str::FromStr,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+const DEFAULT_COORDINATOR: &str = "http://[::1]:8080";
+
+#[derive(Debug)]
+struct Workload {
+  ledgers: usize,
+  concurrency: usize,
+  /// the fraction of the operations that are reads rather than appends
+  read_ratio: f64,
+  block_size: usize,
+  warmup: Duration,
+  duration: Duration,
+}
+
+/// a ledger of the benchmark with its height, which one append at a time extends
+struct Ledger {
+  handle: Vec<u8>,
+  height: Mutex<u64>,
+}
+
+impl Ledger {
+  /// appends `block` after the last entry of the ledger, and returns how long the append took and
+  /// whether it succeeded
+  async fn append(&self, target: &Target, block: &[u8]) -> (Duration, bool) {
+    let mut height = self.height.lock().await;
+    let start = Instant::now();
+    let res = target.append(&self.handle, *height + 1, block).await;
+    let latency = start.elapsed();
+    match &res {
+      Ok(()) => *height += 1,
+      // another client appended to the ledger, or an append that failed was applied after all
+      Err(status) => {
+        if let Some(current) = current_height(status) {
+          *height = current;
+        }
+      },
+    }
+    (latency, res.is_ok())
+  }
+}
+
+fn parse<T: FromStr>(args: &ArgMatches, name: &str) -> Result<T, String> {
+  let value = args.value_of(name).unwrap();
+  value.parse::<T>().map_err(|_e| {
+    format!(
+      "--{} has an invalid value {}",
+      name.replace('_', "-"),
+      value
+    )
+  })
+}
+
+/// a number of seconds, which `Duration::from_secs_f64` would panic on if negative
+fn parse_secs(args: &ArgMatches, name: &str) -> Result<Duration, String> {
+  let secs = parse::<f64>(args, name)?;
+  if !secs.is_finite() || secs < 0.0 {
+    return Err(format!(
+      "--{} must be a number of seconds",
+      name.replace('_', "-")
+    ));
+  }
+  Ok(Duration::from_secs_f64(secs))
+}
+
+fn parse_workload(args: &ArgMatches) -> Result<Workload, String> {
+  let workload = Workload {
+    ledgers: parse(args, "ledgers")?,
+    concurrency: parse(args, "concurrency")?,
+    read_ratio: parse(args, "read_ratio")?,
+    block_size: parse(args, "block_size")?,
+    warmup: parse_secs(args, "warmup")?,
+    duration: parse_secs(args, "duration")?,
+  };
+  if workload.ledgers == 0 || workload.concurrency == 0 {
+    return Err("--ledgers and --concurrency must be positive".to_string());
+  }
+  if !(0.0..=1.0).contains(&workload.read_ratio) {
+    return Err("--read-ratio must be between 0 and 1".to_string());
+  }
+  if workload.duration.is_zero() {
+    return Err("--duration must be positive".to_string());
+  }
+  Ok(workload)
+}
+
+/// the schedule of `--schedule` or `--profile`, if either is set
+fn parse_schedule(args: &ArgMatches) -> Result<Option<Schedule>, String> {
+  let mut schedule = if let Some(path) = args.value_of("schedule") {
+    let contents = std::fs::read_to_string(path)
+      .map_err(|e| format!("cannot read the schedule {}: {}", path, e))?;
+    Schedule::parse(&contents).map_err(|e| format!("{}: {}", path, e))?
+  } else if let Some(name) = args.value_of("profile") {
+    Schedule::builtin(name).unwrap()
+  } else {
+    return Ok(None);
+  };
+  if args.is_present("time_scale") {
+    schedule.time_scale = parse(args, "time_scale")?;
+    if schedule.time_scale == 0 {
+      return Err("--time-scale must be positive".to_string());
+    }
+  }
+  Ok(Some(schedule))
+}
+
+/// the block that a worker creates and appends ledgers with
+fn random_block(rng: &mut StdRng, size: usize) -> Vec<u8> {
+  (0..size).map(|_| rng.gen()).collect()
+}
+
+/// creates `num_ledgers` ledgers with genesis blocks of `block_size` bytes, with `concurrency`
+/// workers
+async fn create_ledgers(
+  target: &Target,
+  num_ledgers: usize,
+  concurrency: usize,
+  block_size: usize,
+) -> Result<(Vec<Ledger>, Summary), String> {
+  let next = Arc::new(AtomicUsize::new(0));
+  let start = Instant::now();
+  let mut jobs = Vec::with_capacity(concurrency);
+  for _ in 0..concurrency {
+    let (target, next) = (target.clone(), next.clone());
+    let block = random_block(&mut StdRng::from_entropy(), block_size);
+    jobs.push(tokio::spawn(async move {
+      let mut handles = Vec::new();
+      let mut stats = OpStats::default();
+      while next.fetch_add(1, Ordering::SeqCst) < num_ledgers {
+        let start = Instant::now();
+        let res = target.create(&block).await;
+        stats.record(start.elapsed(), res.is_ok());
+        match res {
+          Ok(handle) => handles.push(handle),
+          Err(status) => return Err(format!("cannot create a ledger: {}", status.message())),
+        }
+      }
+      Ok((handles, stats))
+    }));
+  }
+
+  let mut ledgers = Vec::with_capacity(num_ledgers);
+  let mut stats = OpStats::default();
+  for job in jobs {
+    let (handles, job_stats) = job.await.map_err(|e| e.to_string())??;
+    ledgers.extend(handles.into_iter().map(|handle| Ledger {
+      handle,
+      height: Mutex::new(0),
+    }));
+    stats.merge(job_stats);
+  }
+  Ok((ledgers, stats.summary(start.elapsed())))
+}
+
+/// runs appends and reads on random ledgers until `end`, recording those that start after
+/// `measure_from`
+async fn run_worker(
+  target: Target,
+  ledgers: Arc<Vec<Ledger>>,
+  read_ratio: f64,
+  block: Vec<u8>,
+  measure_from: Instant,
+  end: Instant,
+) -> (OpStats, OpStats) {
+  let mut rng = StdRng::from_entropy();
+  let (mut appends, mut reads) = (OpStats::default(), OpStats::default());
+  loop {
+    let ledger = &ledgers[rng.gen_range(0..ledgers.len())];
+    if rng.gen_bool(read_ratio) {
+      let start = Instant::now();
+      if start >= end {
+        break;
+      }
+      let res = target.read(&ledger.handle).await;
+      if start >= measure_from {
+        reads.record(start.elapsed(), res.is_ok());
+      }
+    } else {
+      let start = Instant::now();
+      if start >= end {
+        break;
+      }
+      let (latency, ok) = ledger.append(&target, &block).await;
+      if start >= measure_from {
+        appends.record(latency, ok);
+      }
+    }
+  }
+  (appends, reads)
+}
+
+/// runs the workload after a warm-up, and returns the appends and reads that it measured
+async fn run_workload(
+  target: &Target,
+  ledgers: Vec<Ledger>,
+  workload: &Workload,
+) -> Result<(Summary, Summary), String> {
+  let ledgers = Arc::new(ledgers);
+  let measure_from = Instant::now() + workload.warmup;
+  let end = measure_from + workload.duration;
+  let jobs = (0..workload.concurrency)
+    .map(|_| {
+      let block = random_block(&mut StdRng::from_entropy(), workload.block_size);
+      tokio::spawn(run_worker(
+        target.clone(),
+        ledgers.clone(),
+        workload.read_ratio,
+        block,
+        measure_from,
+        end,
+      ))
+    })
+    .collect::<Vec<_>>();
+
+  let (mut appends, mut reads) = (OpStats::default(), OpStats::default());
+  for job in jobs {
+    let (job_appends, job_reads) = job.await.map_err(|e| e.to_string())?;
+    appends.merge(job_appends);
+    reads.merge(job_reads);
+  }
+  Ok((
+    appends.summary(workload.duration),
+    reads.summary(workload.duration),
+  ))
+}
+
+/// the ledgers that the operations of a profile go to: its own, or, for an audit, those of the
+/// profiles that it scans
+enum Ledgers {
+  Own(Arc<Vec<Ledger>>),
+  Scanned(Vec<Arc<Vec<Ledger>>>),
+}
+
+/// a profile of a schedule, whose workers take its operations in order
+struct ProfileRun {
+  events: Vec<Event>,
+  next: AtomicUsize,
+  ledgers: Ledgers,
+  block: Vec<u8>,
+  time_scale: u32,
+}
+
+/// runs the operations of `run` that no other worker took, each at its time after `start`, and
+/// returns their latencies and how far behind its time an operation started at most
+async fn run_profile_worker(
+  target: Target,
+  run: Arc<ProfileRun>,
+  start: Instant,
+) -> (OpStats, Duration) {
+  let mut rng = StdRng::from_entropy();
+  let (mut ops, mut max_lag) = (OpStats::default(), Duration::ZERO);
+  while let Some(event) = run.events.get(run.next.fetch_add(1, Ordering::SeqCst)) {
+    let due = start + event.at / run.time_scale;
+    tokio::time::sleep_until(due.into()).await;
+    max_lag = max_lag.max(Instant::now().saturating_duration_since(due));
+    let (latency, ok) = match &run.ledgers {
+      Ledgers::Own(ledgers) => ledgers[event.ledger].append(&target, &run.block).await,
+      Ledgers::Scanned(profiles) => {
+        let num_ledgers = profiles.iter().map(|ledgers| ledgers.len()).sum::<usize>();
+        let index = rng.gen_range(0..num_ledgers);
+        let ledger = profiles
+          .iter()
+          .flat_map(|ledgers| ledgers.iter())
+          .nth(index)
+          // an audit scans only profiles that append, which have ledgers
+          .unwrap();
+        let height = *ledger.height.lock().await;
+        let start = Instant::now();
+        let res = target.read_history(&ledger.handle, height).await;
+        (start.elapsed(), res.is_ok())
+      },
+    };
+    ops.record(latency, ok);
+  }
+  (ops, max_lag)
+}
+
+/// runs `schedule` on the coordinator at `coordinator`, sampling the CPU time of its endorsers
+/// from the metrics at `metrics`, if set
+async fn run_schedule(
+  args: &ArgMatches<'_>,
+  target: &Target,
+  coordinator: &str,
+  metrics: Option<&str>,
+  schedule: &Schedule,
+) -> Result<Value, String> {
+  let sample_interval = parse_secs(args, "metrics_interval")?;
+  if sample_interval.is_zero() {
+    return Err("--metrics-interval must be positive".to_string());
+  }
+
+  // the ledgers of the profiles that append are created before the schedule starts
+  let mut own = Vec::with_capacity(schedule.profiles.len());
+  for profile in &schedule.profiles {
+    own.push(if profile.kind.appends() {
+      let (ledgers, creates) = create_ledgers(
+        target,
+        profile.ledgers,
+        profile.concurrency,
+        profile.block_size,
+      )
+      .await?;
+      Some((Arc::new(ledgers), creates))
+    } else {
+      None
+    });
+  }
+  let mut rng = StdRng::seed_from_u64(schedule.seed);
+  let runs = schedule
+    .profiles
+    .iter()
+    .zip(&own)
+    .map(|(profile, created)| {
+      let ledgers = match created {
+        Some((ledgers, _creates)) => Ledgers::Own(ledgers.clone()),
+        None => Ledgers::Scanned(
+          schedule
+            .scanned(profile)
+            .filter_map(|scanned| {
+              let index = schedule
+                .profiles
+                .iter()
+                .position(|p| p.name == scanned.name)?;
+              own[index]
+                .as_ref()
+                .map(|(ledgers, _creates)| ledgers.clone())
+            })
+            .collect(),
+        ),
+      };
+      Arc::new(ProfileRun {
+        events: schedule.events(profile, &mut rng),
+        next: AtomicUsize::new(0),
+        ledgers,
+        block: random_block(&mut rng, profile.block_size),
+        time_scale: schedule.time_scale,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let first = match metrics {
+    Some(url) => Some(sample_cpu(url, Duration::ZERO).await?),
+    None => None,
+  };
+  let start = Instant::now();
+  let elapsed = schedule.wall(schedule.duration);
+  let sampler = metrics.zip(first).map(|(url, first)| {
+    tokio::spawn(sample_cpu_every(
+      url.to_string(),
+      first,
+      start,
+      elapsed,
+      sample_interval,
+    ))
+  });
+  let jobs = schedule
+    .profiles
+    .iter()
+    .zip(&runs)
+    .map(|(profile, run)| {
+      (0..profile.concurrency)
+        .map(|_| tokio::spawn(run_profile_worker(target.clone(), run.clone(), start)))
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+  let mut results = Vec::with_capacity(jobs.len());
+  for workers in jobs {
+    let (mut ops, mut max_lag) = (OpStats::default(), Duration::ZERO);
+    for worker in workers {
+      let (worker_ops, worker_lag) = worker.await.map_err(|e| e.to_string())?;
+      ops.merge(worker_ops);
+      max_lag = max_lag.max(worker_lag);
+    }
+    results.push((ops.summary(elapsed), max_lag));
+  }
+  let cpu = match sampler {
+    Some(sampler) => {
+      let samples: Vec<CpuSample> = sampler.await.map_err(|e| e.to_string())?;
+      Some(CpuReport::new(&samples, schedule.time_scale))
+    },
+    None => None,
+  };
+
+  let profiles = schedule
+    .profiles
+    .iter()
+    .zip(&own)
+    .zip(&results)
+    .map(|((profile, created), (ops, max_lag))| {
+      let mut output = json!({
+        "name": profile.name,
+        "kind": profile.kind.name(),
+        "ledgers": profile.ledgers,
+        "ops": ops.to_json(),
+        "max_lag_us": max_lag.as_micros() as u64,
+      });
+      if let Some((_ledgers, creates)) = created {
+        output["create"] = creates.to_json();
+      }
+      output
+    })
+    .collect::<Vec<_>>();
+  let mut output = json!({
+    "target": { "coordinator": coordinator },
+    "duration_secs": schedule.duration.as_secs_f64(),
+    "time_scale": schedule.time_scale,
+    "seed": schedule.seed,
+    "profiles": profiles,
+  });
+  if let Some(cpu) = &cpu {
+    output["endorser_cpu"] = cpu.to_json();
+  }
+  if args.is_present("json") {
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+  } else {
+    println!(
+      "{} profiles over {:?} of schedule time in {:?} on the coordinator at {}",
+      schedule.profiles.len(),
+      schedule.duration,
+      elapsed,
+      coordinator
+    );
+    for (profile, (ops, max_lag)) in schedule.profiles.iter().zip(&results) {
+      println!(
+        "{} ({}, {} ledgers): {}, {:.2} ms late at most",
+        profile.name,
+        profile.kind.name(),
+        profile.ledgers,
+        ops,
+        millis(*max_lag)
+      );
+    }
+    if let Some(cpu) = &cpu {
+      println!("endorser CPU: {}", cpu);
+    }
+  }
+  Ok(output)
+}
+
+async fn run(args: &ArgMatches<'_>) -> Result<Value, String> {
+  let schedule = parse_schedule(args)?;
+  let workload = parse_workload(args)?;
+  let direct = args.is_present("direct");
+
+  // the launched processes are killed when the benchmark ends
+  let mut processes: Vec<BoxChild> = Vec::new();
+  let mut endorsers = args
+    .values_of("endorser")
+    .map(|uris| uris.map(str::to_string).collect::<Vec<_>>())
+    .unwrap_or_default();
+  let mut coordinator = args
+    .value_of("coordinator")
+    .unwrap_or(DEFAULT_COORDINATOR)
+    .to_string();
+  let mut metrics = args.value_of("metrics").map(str::to_string);
+  if args.is_present("launch") {
+    let num_endorsers = parse::<u16>(args, "launch")?;
+    if num_endorsers == 0 {
+      return Err("--launch must be positive".to_string());
+    }
+    let base_port = parse::<u16>(args, "base_port")?;
+    endorsers.clear();
+    for i in 0..num_endorsers {
+      let port = base_port + i;
+      processes.push(launch_endorser(port)?);
+      endorsers.push(format!("http://[::1]:{}", port));
+    }
+    if !direct {
+      let port = base_port + num_endorsers;
+      processes.push(launch_coordinator(port, port + 1, port + 2, &endorsers)?);
+      coordinator = format!("http://[::1]:{}", port);
+      metrics = Some(format!("http://[::1]:{}/metrics", port + 2));
+    }
+  }
+
+  let target = if direct {
+    if endorsers.is_empty() {
+      return Err("--direct needs --endorser or --launch".to_string());
+    }
+    Target::connect_endorsers(&endorsers).await?
+  } else {
+    Target::connect_coordinator(&coordinator).await?
+  };
+  if let Some(schedule) = &schedule {
+    return run_schedule(args, &target, &coordinator, metrics.as_deref(), schedule).await;
+  }
+
+  let (ledgers, creates) = create_ledgers(
+    &target,
+    workload.ledgers,
+    workload.concurrency,
+    workload.block_size,
+  )
+  .await?;
+  let (appends, reads) = run_workload(&target, ledgers, &workload).await?;
+  let throughput = appends.throughput + reads.throughput;
+
+  let target = if direct {
+    json!({ "endorsers": endorsers })
+  } else {
+    json!({ "coordinator": coordinator })
+  };
+  let output = json!({
+    "target": target,
+    "ledgers": workload.ledgers,
+    "concurrency": workload.concurrency,
+    "read_ratio": workload.read_ratio,
+    "block_size": workload.block_size,
+    "warmup_secs": workload.warmup.as_secs_f64(),
+    "duration_secs": workload.duration.as_secs_f64(),
+    "create": creates.to_json(),
+    "append": appends.to_json(),
+    "read": reads.to_json(),
+    "throughput": throughput,
+  });
+  if args.is_present("json") {
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+  } else {
+    let target = if direct {
+      format!("{} endorsers", endorsers.len())
+    } else {
+      format!("the coordinator at {}", coordinator)
+    };
+    println!(
+      "{} workers on {} ledgers of {} with {}-byte blocks and {:.0}% reads for {:?}",
+      workload.concurrency,
+      workload.ledgers,
+      target,
+      workload.block_size,
+      workload.read_ratio * 100.0,
+      workload.duration
+    );
+    println!("create: {}", creates);
+    println!("append: {}", appends);
+    println!("read:   {}", reads);
+    println!("total:  {:.1} ops/s", throughput);
+  }
+
+  if args.is_present("min_throughput") {
+    let min_throughput = parse::<f64>(args, "min_throughput")?;
+    if throughput < min_throughput {
+      return Err(format!(
+        "the throughput {:.1} ops/s is below {:.1} ops/s",
+        throughput, min_throughput
+      ));
+    }
+  }
+  Ok(output)
+}
+
+fn app() -> App<'static, 'static> {
+  App::new("nimble-bench")
+    .about("Measures the throughput and the latency of appends and reads")
+    .arg(
+      Arg::with_name("coordinator")
+        .short("c")
+        .long("coordinator")
+        .takes_value(true)
+        .help("The URI of the coordinator to drive, if not launched"),
+    )
+    .arg(
+      Arg::with_name("direct")
+        .long("direct")
+        .help("Drives the endorsers directly, without a coordinator"),
+    )
+    .arg(
+      Arg::with_name("endorser")
+        .short("e")
+        .long("endorser")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true)
+        .help("The URIs of fresh endorsers to drive with --direct, if not launched"),
+    )
+    .arg(
+      Arg::with_name("launch")
+        .long("launch")
+        .takes_value(true)
+        .help("Launches this many endorsers and, without --direct, a coordinator in front of them"),
+    )
+    .arg(
+      Arg::with_name("base_port")
+        .long("base-port")
+        .takes_value(true)
+        .default_value("9700")
+        .help("The first port of the launched endorsers, which the coordinator follows"),
+    )
+    .arg(
+      Arg::with_name("ledgers")
+        .short("l")
+        .long("ledgers")
+        .takes_value(true)
+        .default_value("16")
+        .help("The number of ledgers to create and run the workload on"),
+    )
+    .arg(
+      Arg::with_name("concurrency")
+        .short("n")
+        .long("concurrency")
+        .takes_value(true)
+        .default_value("16")
+        .help("The number of concurrent workers"),
+    )
+    .arg(
+      Arg::with_name("read_ratio")
+        .long("read-ratio")
+        .takes_value(true)
+        .default_value("0")
+        .help("The fraction of the operations that are reads of a tail rather than appends"),
+    )
+    .arg(
+      Arg::with_name("block_size")
+        .short("b")
+        .long("block-size")
+        .takes_value(true)
+        .default_value("1024")
+        .help("The size of the blocks in bytes"),
+    )
+    .arg(
+      Arg::with_name("warmup")
+        .long("warmup")
+        .takes_value(true)
+        .default_value("2")
+        .help("The seconds to run the workload for before measuring it"),
+    )
+    .arg(
+      Arg::with_name("duration")
+        .short("d")
+        .long("duration")
+        .takes_value(true)
+        .default_value("10")
+        .help("The seconds to measure the workload for"),
+    )
+    .arg(
+      Arg::with_name("min_throughput")
+        .long("min-throughput")
+        .takes_value(true)
+        .help("Fails if the appends and reads per second are fewer"),
+    )
+    .arg(
+      Arg::with_name("schedule")
+        .long("schedule")
+        .takes_value(true)
+        .conflicts_with_all(&["profile", "direct", "min_throughput"])
+        .help("Runs the schedule of workload profiles in this TOML file on a coordinator"),
+    )
+    .arg(
+      Arg::with_name("profile")
+        .long("profile")
+        .takes_value(true)
+        .possible_values(&PROFILES)
+        .conflicts_with_all(&["direct", "min_throughput"])
+        .help("Runs the built-in schedule of this workload profile on a coordinator"),
+    )
+    .arg(
+      Arg::with_name("time_scale")
+        .long("time-scale")
+        .takes_value(true)
+        .help("The seconds of schedule time per second of the run; overrides that of the schedule"),
+    )
+    .arg(
+      Arg::with_name("metrics")
+        .long("metrics")
+        .takes_value(true)
+        .help("The URL of the metrics of the coordinator, to sample the CPU time of the endorsers"),
+    )
+    .arg(
+      Arg::with_name("metrics_interval")
+        .long("metrics-interval")
+        .takes_value(true)
+        .default_value("1")
+        .help("The seconds between the samples of the CPU time of the endorsers"),
+    )
+    .arg(Arg::with_name("json").long("json").help("Prints JSON"))
+}
+
+#[tokio::main]
+async fn main() {
+  let args = app().get_matches();
+  if let Err(error) = run(&args).await {
+    eprintln!("error: {}", error);
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  pub fn test_parse_workload() {
+    let workload = |args: &[&str]| {
+      let args = app().get_matches_from_safe([&["nimble-bench"][..], args].concat())?;
+      Ok::<_, clap::Error>(parse_workload(&args))
+    };
+    let defaults = workload(&[]).unwrap().unwrap();
+    assert_eq!((defaults.ledgers, defaults.concurrency), (16, 16));
+    assert_eq!(defaults.read_ratio, 0.0);
+    assert_eq!(defaults.duration, Duration::from_secs(10));
+
+    let mixed = workload(&["--read-ratio", "0.25", "--warmup", "0.5", "-b", "64"])
+      .unwrap()
+      .unwrap();
+    assert_eq!(mixed.read_ratio, 0.25);
+    assert_eq!(mixed.warmup, Duration::from_millis(500));
+    assert_eq!(mixed.block_size, 64);
+
+    assert!(workload(&["--read-ratio", "1.5"]).unwrap().is_err());
+    assert!(workload(&["--ledgers", "0"]).unwrap().is_err());
+    assert!(workload(&["--duration", "0"]).unwrap().is_err());
+    assert!(workload(&["--warmup=-1"]).unwrap().is_err());
+    assert!(workload(&["--concurrency", "many"])
+      .unwrap()
+      .unwrap_err()
+      .contains("--concurrency"));
+  }
+
+  #[test]
+  pub fn test_parse_schedule() {
+    let args = |args: &[&str]| app().get_matches_from_safe([&["nimble-bench"][..], args].concat());
+    assert_eq!(parse_schedule(&args(&[]).unwrap()), Ok(None));
+    let schedule = parse_schedule(&args(&["--profile", "mixed", "--time-scale", "60"]).unwrap())
+      .unwrap()
+      .unwrap();
+    assert_eq!(schedule.time_scale, 60);
+    assert_eq!(schedule.profiles.len(), 3);
+
+    assert!(parse_schedule(&args(&["--profile", "mixed", "--time-scale", "0"]).unwrap()).is_err());
+    assert!(args(&["--profile", "uniform"]).is_err());
+    assert!(args(&["--profile", "mixed", "--direct"]).is_err());
+    assert!(args(&["--schedule", "day.toml", "--profile", "mixed"]).is_err());
+    assert!(
+      parse_schedule(&args(&["--schedule", "/nonexistent/day.toml"]).unwrap())
+        .unwrap_err()
+        .starts_with("cannot read the schedule")
+    );
+  }
+}
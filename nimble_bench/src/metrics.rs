@@ -0,0 +1,218 @@
+//! The CPU time of the endorsers during a schedule, which the benchmark samples from the metrics of
+//! the coordinator, and its report: the CPU time and the utilization of the endorsers in each
+//! window between samples, on average, and at their peak.
+use serde_json::{json, Value};
+use std::{
+  fmt,
+  time::{Duration, Instant},
+};
+
+/// the CPU time that the connected endorsers report having used, summed
+const CPU_GAUGE: &str = "nimble_endorser_cpu_seconds";
+const ENDORSERS_GAUGE: &str = "nimble_connected_endorsers";
+
+/// fetches the metrics that a coordinator serves at `url`
+async fn scrape(url: &str) -> Result<String, String> {
+  let uri = url
+    .parse::<hyper::Uri>()
+    .map_err(|e| format!("invalid --metrics {}: {}", url, e))?;
+  let resp = hyper::Client::new()
+    .get(uri)
+    .await
+    .map_err(|e| format!("cannot read the metrics at {}: {}", url, e))?;
+  if !resp.status().is_success() {
+    return Err(format!("the metrics at {} answered {}", url, resp.status()));
+  }
+  let body = hyper::body::to_bytes(resp.into_body())
+    .await
+    .map_err(|e| format!("cannot read the metrics at {}: {}", url, e))?;
+  String::from_utf8(body.to_vec()).map_err(|_e| format!("the metrics at {} are not text", url))
+}
+
+/// the value of the series `name` without labels in the metrics `text`
+fn gauge(text: &str, name: &str) -> Option<f64> {
+  text.lines().find_map(|line| {
+    line
+      .strip_prefix(name)?
+      .strip_prefix(' ')?
+      .trim()
+      .parse()
+      .ok()
+  })
+}
+
+/// the CPU time of the endorsers at a time of the run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuSample {
+  pub at: Duration,
+  pub cpu_seconds: f64,
+  pub endorsers: f64,
+}
+
+/// samples the CPU time of the endorsers from the metrics at `url`, at `at` into the run
+pub async fn sample_cpu(url: &str, at: Duration) -> Result<CpuSample, String> {
+  let text = scrape(url).await?;
+  match (gauge(&text, CPU_GAUGE), gauge(&text, ENDORSERS_GAUGE)) {
+    (Some(cpu_seconds), Some(endorsers)) => Ok(CpuSample {
+      at,
+      cpu_seconds,
+      endorsers,
+    }),
+    _ => Err(format!(
+      "the metrics at {} do not report the CPU time of the endorsers",
+      url
+    )),
+  }
+}
+
+/// samples the CPU time of the endorsers from the metrics at `url` every `interval` after `start`
+/// until `duration` has passed, following `first`; the samples that fail are left out
+pub async fn sample_cpu_every(
+  url: String,
+  first: CpuSample,
+  start: Instant,
+  duration: Duration,
+  interval: Duration,
+) -> Vec<CpuSample> {
+  let mut samples = vec![first];
+  let mut at = Duration::ZERO;
+  while at < duration {
+    at = (at + interval).min(duration);
+    tokio::time::sleep_until((start + at).into()).await;
+    if let Ok(sample) = sample_cpu(&url, start.elapsed()).await {
+      samples.push(sample);
+    }
+  }
+  samples
+}
+
+/// the CPU time of the endorsers over a run
+pub struct CpuReport {
+  pub cpu_seconds: f64,
+  /// the CPU time per endorser and second of the run
+  pub utilization: f64,
+  pub peak_utilization: f64,
+  /// the windows between samples: the schedule time they end at, their CPU time, and their
+  /// utilization
+  pub windows: Vec<(Duration, f64, f64)>,
+}
+
+impl CpuReport {
+  /// reports the CPU time between `samples`, in the order taken, of a run whose schedule time
+  /// passes `time_scale` times as fast
+  pub fn new(samples: &[CpuSample], time_scale: u32) -> Self {
+    let utilization = |cpu_seconds: f64, from: &CpuSample, to: &CpuSample| {
+      let endorser_secs = (to.at - from.at).as_secs_f64() * to.endorsers;
+      if endorser_secs > 0.0 {
+        cpu_seconds / endorser_secs
+      } else {
+        0.0
+      }
+    };
+    let windows = samples
+      .windows(2)
+      .map(|pair| {
+        // the CPU time goes back when an endorser restarts, and counts from zero again
+        let cpu_seconds = match pair[1].cpu_seconds - pair[0].cpu_seconds {
+          delta if delta < 0.0 => pair[1].cpu_seconds,
+          delta => delta,
+        };
+        (
+          pair[1].at * time_scale,
+          cpu_seconds,
+          utilization(cpu_seconds, &pair[0], &pair[1]),
+        )
+      })
+      .collect::<Vec<_>>();
+    let cpu_seconds = windows.iter().map(|window| window.1).sum();
+    let utilization = match (samples.first(), samples.last()) {
+      (Some(first), Some(last)) => utilization(cpu_seconds, first, last),
+      _ => 0.0,
+    };
+    CpuReport {
+      cpu_seconds,
+      utilization,
+      peak_utilization: windows.iter().map(|window| window.2).fold(0.0, f64::max),
+      windows,
+    }
+  }
+
+  pub fn to_json(&self) -> Value {
+    let windows = self
+      .windows
+      .iter()
+      .map(|(at, cpu_seconds, utilization)| {
+        json!({
+          "at_secs": at.as_secs_f64(),
+          "cpu_seconds": cpu_seconds,
+          "utilization": utilization,
+        })
+      })
+      .collect::<Vec<_>>();
+    json!({
+      "cpu_seconds": self.cpu_seconds,
+      "utilization": self.utilization,
+      "peak_utilization": self.peak_utilization,
+      "windows": windows,
+    })
+  }
+}
+
+impl fmt::Display for CpuReport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{:.2} s, {:.1}% of an endorser on average, {:.1}% at peak",
+      self.cpu_seconds,
+      self.utilization * 100.0,
+      self.peak_utilization * 100.0
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  pub fn test_cpu_report() {
+    let text = "# HELP nimble_endorser_cpu_seconds The CPU time.\n\
+                # TYPE nimble_endorser_cpu_seconds gauge\n\
+                nimble_endorser_cpu_seconds 1.5\n\
+                nimble_endorser_cpu_seconds_total 9\n\
+                nimble_requests_total{code=\"OK\",method=\"Append\"} 2\n";
+    assert_eq!(gauge(text, CPU_GAUGE), Some(1.5));
+    assert_eq!(gauge(text, "nimble_requests_total"), None);
+    assert_eq!(gauge(text, ENDORSERS_GAUGE), None);
+
+    let sample = |secs: u64, cpu_seconds: f64| CpuSample {
+      at: Duration::from_secs(secs),
+      cpu_seconds,
+      endorsers: 2.0,
+    };
+    let report = CpuReport::new(
+      &[
+        sample(0, 10.0),
+        sample(1, 10.5),
+        sample(3, 12.5),
+        sample(4, 0.5),
+      ],
+      60,
+    );
+    assert_eq!(
+      report.windows,
+      vec![
+        (Duration::from_secs(60), 0.5, 0.25),
+        (Duration::from_secs(180), 2.0, 0.5),
+        (Duration::from_secs(240), 0.5, 0.25),
+      ]
+    );
+    assert_eq!(report.cpu_seconds, 3.0);
+    assert_eq!(report.utilization, 3.0 / 8.0);
+    assert_eq!(report.peak_utilization, 0.5);
+    assert_eq!(report.to_json()["windows"][1]["at_secs"], 180.0);
+
+    let report = CpuReport::new(&[], 1);
+    assert_eq!((report.cpu_seconds, report.utilization), (0.0, 0.0));
+  }
+}
@@ -0,0 +1,607 @@
+//! Schedules of workloads shaped like those of HDFS: a few liveness ledgers that are appended
+//! every few seconds, many namespace ledgers that are checkpointed in bursts, and audits that read
+//! the whole history of ledgers now and then. A schedule is a small TOML file of profiles, which
+//! run side by side in schedule time, compressed into the time of the run by its time scale:
+//!
+//! ```toml
+//! duration = 86400 # seconds of schedule time
+//! time_scale = 240 # seconds of schedule time per second of the run
+//!
+//! [profiles.namespace]
+//! kind = "checkpoint-burst"
+//! ledgers = 1000
+//! interval = 3600
+//! ```
+//!
+//! Every key of a profile but `kind` defaults to that of its kind, and `--profile` runs the
+//! built-in schedule of a kind, or `mixed`, a day of a cluster with the three side by side.
+use rand::{rngs::StdRng, Rng};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// the built-in schedules that `--profile` runs
+pub const PROFILES: [&str; 4] = [
+  "checkpoint-burst",
+  "steady-heartbeat",
+  "audit-scan",
+  "mixed",
+];
+
+const CHECKPOINT_BURST: &str = r#"
+duration = 14400
+time_scale = 240
+
+[profiles.namespace]
+kind = "checkpoint-burst"
+"#;
+
+const STEADY_HEARTBEAT: &str = r#"
+duration = 600
+
+[profiles.liveness]
+kind = "steady-heartbeat"
+"#;
+
+const AUDIT_SCAN: &str = r#"
+duration = 3600
+time_scale = 60
+
+[profiles.namespace]
+kind = "checkpoint-burst"
+ledgers = 64
+interval = 60
+spread = 10
+
+[profiles.audit]
+kind = "audit-scan"
+interval = 600
+start = 300
+"#;
+
+const MIXED: &str = r#"
+duration = 86400
+time_scale = 240
+
+[profiles.liveness]
+kind = "steady-heartbeat"
+
+[profiles.namespace]
+kind = "checkpoint-burst"
+
+[profiles.audit]
+kind = "audit-scan"
+start = 3600
+"#;
+
+/// a schedule whose profiles would run more operations than this is rejected, rather than held in
+/// memory
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+const PROFILE_KEYS: [&str; 9] = [
+  "kind",
+  "ledgers",
+  "interval",
+  "spread",
+  "block_size",
+  "concurrency",
+  "start",
+  "end",
+  "scans",
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+  /// appends to every ledger once per interval, at a random time within the spread of the burst
+  CheckpointBurst,
+  /// appends to every ledger once per interval, the ledgers evenly apart
+  SteadyHeartbeat,
+  /// reads the whole history of random ledgers of other profiles once per interval, evenly apart
+  /// within the spread of the audit
+  AuditScan,
+}
+
+impl Kind {
+  pub fn name(self) -> &'static str {
+    match self {
+      Kind::CheckpointBurst => "checkpoint-burst",
+      Kind::SteadyHeartbeat => "steady-heartbeat",
+      Kind::AuditScan => "audit-scan",
+    }
+  }
+
+  fn from_name(name: &str) -> Option<Kind> {
+    [
+      Kind::CheckpointBurst,
+      Kind::SteadyHeartbeat,
+      Kind::AuditScan,
+    ]
+    .iter()
+    .copied()
+    .find(|kind| kind.name() == name)
+  }
+
+  /// whether the profile appends to ledgers of its own, rather than reading those of others
+  pub fn appends(self) -> bool {
+    self != Kind::AuditScan
+  }
+
+  /// the profile of the kind with its default settings
+  fn profile(self, name: &str) -> Profile {
+    let (ledgers, interval, spread, block_size, concurrency) = match self {
+      Kind::CheckpointBurst => (1000, 3600, 300, 4096, 16),
+      Kind::SteadyHeartbeat => (4, 5, 0, 64, 4),
+      Kind::AuditScan => (4, 21600, 600, 0, 2),
+    };
+    Profile {
+      name: name.to_string(),
+      kind: self,
+      ledgers,
+      interval: Duration::from_secs(interval),
+      spread: Duration::from_secs(spread),
+      block_size,
+      concurrency,
+      start: Duration::ZERO,
+      end: None,
+      scans: Vec::new(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+  pub name: String,
+  pub kind: Kind,
+  /// the ledgers that the profile appends to, or that an audit reads
+  pub ledgers: usize,
+  /// the schedule time between the appends to a ledger, or between bursts or audits
+  pub interval: Duration,
+  /// the schedule time that a burst or an audit is spread over
+  pub spread: Duration,
+  pub block_size: usize,
+  /// the concurrent workers of the profile
+  pub concurrency: usize,
+  /// the schedule time that the profile starts at, and ends at if before the schedule
+  pub start: Duration,
+  pub end: Option<Duration>,
+  /// the profiles whose ledgers an audit reads; all those that append if empty
+  pub scans: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+  /// the schedule time that the schedule runs for
+  pub duration: Duration,
+  /// the seconds of schedule time per second of the run
+  pub time_scale: u32,
+  /// the seed of the times of the appends within bursts
+  pub seed: u64,
+  pub profiles: Vec<Profile>,
+}
+
+/// an operation of a profile at a schedule time, on the ledger at an index of the ledgers of the
+/// profile or, for an audit, on a random ledger
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Event {
+  pub at: Duration,
+  pub ledger: usize,
+}
+
+fn uint(table: &Map<String, Value>, key: &str, what: &str) -> Result<Option<u64>, String> {
+  match table.get(key) {
+    None => Ok(None),
+    Some(value) => value
+      .as_u64()
+      .map(Some)
+      .ok_or_else(|| format!("{} must be a non-negative integer", what)),
+  }
+}
+
+fn secs(table: &Map<String, Value>, key: &str, what: &str) -> Result<Option<Duration>, String> {
+  Ok(uint(table, key, what)?.map(Duration::from_secs))
+}
+
+fn parse_profile(name: &str, value: &Value) -> Result<Profile, String> {
+  let what = |key: &str| format!("profiles.{}.{}", name, key);
+  let table = value
+    .as_object()
+    .ok_or_else(|| format!("profiles.{} must be a table", name))?;
+  if let Some(key) = table
+    .keys()
+    .find(|key| !PROFILE_KEYS.contains(&key.as_str()))
+  {
+    return Err(format!("unknown key {}", what(key)));
+  }
+  let kind = match table.get("kind").map(Value::as_str) {
+    Some(Some(kind)) => Kind::from_name(kind)
+      .ok_or_else(|| format!("{} is not a kind of profile: {}", what("kind"), kind))?,
+    _ => {
+      return Err(format!(
+        "{} must be set to the kind of the profile",
+        what("kind")
+      ))
+    },
+  };
+
+  let mut profile = kind.profile(name);
+  if let Some(ledgers) = uint(table, "ledgers", &what("ledgers"))? {
+    profile.ledgers = ledgers as usize;
+  }
+  if let Some(interval) = secs(table, "interval", &what("interval"))? {
+    profile.interval = interval;
+  }
+  if let Some(spread) = secs(table, "spread", &what("spread"))? {
+    profile.spread = spread;
+  }
+  if let Some(block_size) = uint(table, "block_size", &what("block_size"))? {
+    profile.block_size = block_size as usize;
+  }
+  if let Some(concurrency) = uint(table, "concurrency", &what("concurrency"))? {
+    profile.concurrency = concurrency as usize;
+  }
+  if let Some(start) = secs(table, "start", &what("start"))? {
+    profile.start = start;
+  }
+  profile.end = secs(table, "end", &what("end"))?;
+  if let Some(scans) = table.get("scans") {
+    if kind.appends() {
+      return Err(format!("{} is only for audits", what("scans")));
+    }
+    profile.scans = scans
+      .as_array()
+      .and_then(|scans| {
+        scans
+          .iter()
+          .map(|scan| scan.as_str().map(str::to_string))
+          .collect()
+      })
+      .ok_or_else(|| {
+        format!(
+          "{} must be an array of the names of profiles",
+          what("scans")
+        )
+      })?;
+  }
+
+  if profile.ledgers == 0 || profile.concurrency == 0 {
+    return Err(format!(
+      "{} and {} must be positive",
+      what("ledgers"),
+      what("concurrency")
+    ));
+  }
+  if profile.interval.is_zero() {
+    return Err(format!("{} must be positive", what("interval")));
+  }
+  if profile.spread > profile.interval {
+    return Err(format!(
+      "{} must not be longer than {}",
+      what("spread"),
+      what("interval")
+    ));
+  }
+  if matches!(profile.end, Some(end) if end <= profile.start) {
+    return Err(format!("{} must be after {}", what("end"), what("start")));
+  }
+  Ok(profile)
+}
+
+impl Schedule {
+  /// parses and checks a schedule
+  pub fn parse(contents: &str) -> Result<Schedule, String> {
+    let document: Map<String, Value> = toml::from_str(contents).map_err(|e| e.to_string())?;
+    if let Some(key) = document
+      .keys()
+      .find(|key| !["duration", "time_scale", "seed", "profiles"].contains(&key.as_str()))
+    {
+      return Err(format!("unknown key {}", key));
+    }
+    let duration = secs(&document, "duration", "duration")?
+      .filter(|duration| !duration.is_zero())
+      .ok_or("duration must be set to a positive number of seconds")?;
+    let time_scale = uint(&document, "time_scale", "time_scale")?.unwrap_or(1);
+    if time_scale == 0 || time_scale > u32::MAX as u64 {
+      return Err("time_scale must be positive".to_string());
+    }
+    let schedule = Schedule {
+      duration,
+      time_scale: time_scale as u32,
+      seed: uint(&document, "seed", "seed")?.unwrap_or(0),
+      profiles: match document.get("profiles").map(Value::as_object) {
+        Some(Some(profiles)) => profiles
+          .iter()
+          .map(|(name, profile)| parse_profile(name, profile))
+          .collect::<Result<Vec<_>, _>>()?,
+        _ => Vec::new(),
+      },
+    };
+    schedule.check()?;
+    Ok(schedule)
+  }
+
+  /// the built-in schedule that `name` of `PROFILES` names
+  pub fn builtin(name: &str) -> Option<Schedule> {
+    let contents = match name {
+      "checkpoint-burst" => CHECKPOINT_BURST,
+      "steady-heartbeat" => STEADY_HEARTBEAT,
+      "audit-scan" => AUDIT_SCAN,
+      "mixed" => MIXED,
+      _ => return None,
+    };
+    Some(Schedule::parse(contents).unwrap())
+  }
+
+  fn check(&self) -> Result<(), String> {
+    if self.profiles.is_empty() {
+      return Err("the schedule has no profiles".to_string());
+    }
+    for profile in &self.profiles {
+      if profile.start >= self.duration {
+        return Err(format!(
+          "profiles.{} starts after the schedule ends",
+          profile.name
+        ));
+      }
+      let span = self.end(profile) - profile.start;
+      let rounds = (span.as_nanos() / profile.interval.as_nanos()) as u64 + 1;
+      if rounds.saturating_mul(profile.ledgers as u64) > MAX_OPERATIONS {
+        return Err(format!(
+          "profiles.{} runs more than {} operations",
+          profile.name, MAX_OPERATIONS
+        ));
+      }
+      if profile.kind.appends() {
+        continue;
+      }
+      for scan in &profile.scans {
+        match self.profiles.iter().find(|other| &other.name == scan) {
+          Some(other) if other.kind.appends() => {},
+          _ => {
+            return Err(format!(
+              "profiles.{}.scans names {}, which is not a profile that appends",
+              profile.name, scan
+            ))
+          },
+        }
+      }
+      if !self.profiles.iter().any(|other| other.kind.appends()) {
+        return Err(format!("profiles.{} has no ledgers to audit", profile.name));
+      }
+    }
+    Ok(())
+  }
+
+  /// the schedule time that `profile` ends at
+  pub fn end(&self, profile: &Profile) -> Duration {
+    profile.end.unwrap_or(self.duration).min(self.duration)
+  }
+
+  /// the time of the run that the schedule time `at` falls at
+  pub fn wall(&self, at: Duration) -> Duration {
+    at / self.time_scale
+  }
+
+  /// the profiles whose ledgers the audit `profile` reads
+  pub fn scanned<'a>(&'a self, profile: &'a Profile) -> impl Iterator<Item = &'a Profile> {
+    self.profiles.iter().filter(move |other| {
+      other.kind.appends() && (profile.scans.is_empty() || profile.scans.contains(&other.name))
+    })
+  }
+
+  /// the operations of `profile` in the order of their schedule times; the appends within a burst
+  /// fall at times that `rng` draws
+  pub fn events(&self, profile: &Profile, rng: &mut StdRng) -> Vec<Event> {
+    let end = self.end(profile);
+    let mut events = Vec::new();
+    let mut round = profile.start;
+    while round < end {
+      for ledger in 0..profile.ledgers {
+        let offset = match profile.kind {
+          Kind::CheckpointBurst => profile.spread.mul_f64(rng.gen::<f64>()),
+          Kind::SteadyHeartbeat => profile
+            .interval
+            .mul_f64(ledger as f64 / profile.ledgers as f64),
+          Kind::AuditScan => profile
+            .spread
+            .mul_f64(ledger as f64 / profile.ledgers as f64),
+        };
+        if round + offset < end {
+          events.push(Event {
+            at: round + offset,
+            ledger,
+          });
+        }
+      }
+      round += profile.interval;
+    }
+    events.sort_by_key(|event| event.at);
+    events
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::SeedableRng;
+
+  #[test]
+  pub fn test_builtin_schedules() {
+    for name in PROFILES {
+      assert!(Schedule::builtin(name).is_some(), "{}", name);
+    }
+    let mixed = Schedule::builtin("mixed").unwrap();
+    assert_eq!(mixed.duration, Duration::from_secs(86400));
+    assert_eq!(
+      mixed.wall(mixed.duration),
+      Duration::from_secs(6 * 60),
+      "a day runs in six minutes"
+    );
+    let kinds = mixed
+      .profiles
+      .iter()
+      .map(|profile| profile.kind)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      kinds,
+      vec![
+        Kind::SteadyHeartbeat,
+        Kind::CheckpointBurst,
+        Kind::AuditScan
+      ]
+    );
+    let audit = &mixed.profiles[2];
+    let scanned = mixed.scanned(audit).map(|profile| &profile.name[..]);
+    assert_eq!(scanned.collect::<Vec<_>>(), vec!["liveness", "namespace"]);
+    assert_eq!(audit.start, Duration::from_secs(3600));
+    assert_eq!(audit.interval, Duration::from_secs(21600));
+  }
+
+  #[test]
+  pub fn test_parse_schedule() {
+    let schedule = Schedule::parse(
+      r#"
+duration = 7200
+time_scale = 60
+seed = 7
+
+[profiles.namespace]
+kind = "checkpoint-burst"
+ledgers = 10
+block_size = 512
+
+[profiles.hot]
+kind = "steady-heartbeat"
+interval = 10
+end = 3600
+
+[profiles.audit]
+kind = "audit-scan"
+scans = ["namespace"]
+"#,
+    )
+    .unwrap();
+    assert_eq!((schedule.time_scale, schedule.seed), (60, 7));
+    let namespace = &schedule.profiles[0];
+    assert_eq!((namespace.ledgers, namespace.block_size), (10, 512));
+    assert_eq!(namespace.interval, Duration::from_secs(3600));
+    assert_eq!(
+      schedule.end(&schedule.profiles[1]),
+      Duration::from_secs(3600)
+    );
+    let audit = &schedule.profiles[2];
+    let scanned = schedule.scanned(audit).map(|profile| &profile.name[..]);
+    assert_eq!(scanned.collect::<Vec<_>>(), vec!["namespace"]);
+
+    let error = |contents: &str| Schedule::parse(contents).unwrap_err();
+    let profile = |settings: &str| {
+      error(&format!(
+        "duration = 60\n[profiles.p]\nkind = \"steady-heartbeat\"\n{}",
+        settings
+      ))
+    };
+    assert_eq!(
+      error(""),
+      "duration must be set to a positive number of seconds"
+    );
+    assert_eq!(error("duration = 60"), "the schedule has no profiles");
+    assert_eq!(
+      error("duration = 60\ntime_scale = 0"),
+      "time_scale must be positive"
+    );
+    assert_eq!(profile("ledger = 1"), "unknown key profiles.p.ledger");
+    assert_eq!(
+      profile("ledgers = -1"),
+      "profiles.p.ledgers must be a non-negative integer"
+    );
+    assert_eq!(
+      profile("interval = 0"),
+      "profiles.p.interval must be positive"
+    );
+    assert_eq!(
+      profile("spread = 10"),
+      "profiles.p.spread must not be longer than profiles.p.interval"
+    );
+    assert_eq!(
+      profile("start = 60"),
+      "profiles.p starts after the schedule ends"
+    );
+    assert_eq!(profile("scans = []"), "profiles.p.scans is only for audits");
+    assert_eq!(
+      profile("ledgers = 1000000"),
+      "profiles.p runs more than 10000000 operations"
+    );
+    assert_eq!(
+      error("duration = 60\n[profiles.a]\nkind = \"audit-scan\"\nspread = 0"),
+      "profiles.a has no ledgers to audit"
+    );
+    assert_eq!(
+      error("duration = 60\n[profiles.a]\nkind = \"audit-scan\"\nscans = [\"b\"]"),
+      "profiles.a.scans names b, which is not a profile that appends"
+    );
+    assert_eq!(
+      error("duration = 60\n[profiles.p]\nkind = \"burst\""),
+      "profiles.p.kind is not a kind of profile: burst"
+    );
+  }
+
+  #[test]
+  pub fn test_events() {
+    let schedule = Schedule::parse(
+      r#"
+duration = 100
+
+[profiles.hot]
+kind = "steady-heartbeat"
+ledgers = 2
+interval = 10
+start = 5
+
+[profiles.burst]
+kind = "checkpoint-burst"
+ledgers = 3
+interval = 40
+spread = 4
+"#,
+    )
+    .unwrap();
+    let mut rng = StdRng::seed_from_u64(schedule.seed);
+
+    // the ledgers of a heartbeat are appended evenly apart
+    let events = schedule.events(&schedule.profiles[0], &mut rng);
+    assert_eq!(events.len(), 2 * 10 - 1);
+    assert_eq!(
+      events[..3],
+      [
+        Event {
+          at: Duration::from_secs(5),
+          ledger: 0
+        },
+        Event {
+          at: Duration::from_secs(10),
+          ledger: 1
+        },
+        Event {
+          at: Duration::from_secs(15),
+          ledger: 0
+        },
+      ]
+    );
+
+    // every ledger is appended once in each burst, within its spread
+    let events = schedule.events(&schedule.profiles[1], &mut rng);
+    assert_eq!(events.len(), 3 * 3);
+    for (i, burst) in events.chunks(3).enumerate() {
+      let start = Duration::from_secs(40 * i as u64);
+      assert!(burst
+        .iter()
+        .all(|event| event.at >= start && event.at < start + Duration::from_secs(4)));
+      let mut ledgers = burst.iter().map(|event| event.ledger).collect::<Vec<_>>();
+      ledgers.sort_unstable();
+      assert_eq!(ledgers, vec![0, 1, 2]);
+    }
+
+    // the same seed draws the same times
+    let mut again = StdRng::seed_from_u64(schedule.seed);
+    schedule.events(&schedule.profiles[0], &mut again);
+    assert_eq!(schedule.events(&schedule.profiles[1], &mut again), events);
+  }
+}
@@ -0,0 +1,131 @@
+//! The latencies and errors of the operations of a benchmark, and their summary: the throughput
+//! and the latency percentiles of the operations that succeeded, and the number that failed.
+use serde_json::{json, Value};
+use std::{fmt, time::Duration};
+
+/// the operations of one kind that a worker ran
+#[derive(Default)]
+pub struct OpStats {
+  latencies: Vec<Duration>,
+  errors: usize,
+}
+
+impl OpStats {
+  pub fn record(&mut self, latency: Duration, ok: bool) {
+    if ok {
+      self.latencies.push(latency);
+    } else {
+      self.errors += 1;
+    }
+  }
+
+  pub fn merge(&mut self, other: OpStats) {
+    self.latencies.extend(other.latencies);
+    self.errors += other.errors;
+  }
+
+  /// summarizes the operations that ran within `elapsed`
+  pub fn summary(mut self, elapsed: Duration) -> Summary {
+    self.latencies.sort_unstable();
+    let count = self.latencies.len();
+    let throughput = if elapsed.is_zero() {
+      0.0
+    } else {
+      count as f64 / elapsed.as_secs_f64()
+    };
+    Summary {
+      count,
+      errors: self.errors,
+      throughput,
+      p50: percentile(&self.latencies, 0.50),
+      p95: percentile(&self.latencies, 0.95),
+      p99: percentile(&self.latencies, 0.99),
+      max: self.latencies.last().copied().unwrap_or_default(),
+    }
+  }
+}
+
+/// the latency under which a fraction `p` of the sorted `latencies` fall, by the nearest rank
+fn percentile(latencies: &[Duration], p: f64) -> Duration {
+  if latencies.is_empty() {
+    return Duration::ZERO;
+  }
+  let rank = (p * latencies.len() as f64).ceil() as usize;
+  latencies[rank.clamp(1, latencies.len()) - 1]
+}
+
+pub struct Summary {
+  pub count: usize,
+  pub errors: usize,
+  /// operations per second
+  pub throughput: f64,
+  pub p50: Duration,
+  pub p95: Duration,
+  pub p99: Duration,
+  pub max: Duration,
+}
+
+impl Summary {
+  pub fn to_json(&self) -> Value {
+    json!({
+      "count": self.count,
+      "errors": self.errors,
+      "throughput": self.throughput,
+      "p50_us": self.p50.as_micros() as u64,
+      "p95_us": self.p95.as_micros() as u64,
+      "p99_us": self.p99.as_micros() as u64,
+      "max_us": self.max.as_micros() as u64,
+    })
+  }
+}
+
+pub fn millis(latency: Duration) -> f64 {
+  latency.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Summary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} ops, {:.1} ops/s, p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms, {} errors",
+      self.count,
+      self.throughput,
+      millis(self.p50),
+      millis(self.p95),
+      millis(self.p99),
+      millis(self.max),
+      self.errors
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  pub fn test_summary() {
+    let mut stats = OpStats::default();
+    for ms in (1..=100).rev() {
+      stats.record(Duration::from_millis(ms), true);
+    }
+    let mut failed = OpStats::default();
+    failed.record(Duration::from_millis(1000), false);
+    failed.record(Duration::from_millis(1000), false);
+    stats.merge(failed);
+
+    let summary = stats.summary(Duration::from_secs(4));
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.errors, 2);
+    assert_eq!(summary.throughput, 25.0);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p95, Duration::from_millis(95));
+    assert_eq!(summary.p99, Duration::from_millis(99));
+    assert_eq!(summary.max, Duration::from_millis(100));
+    assert_eq!(summary.to_json()["p99_us"], 99_000);
+
+    let summary = OpStats::default().summary(Duration::ZERO);
+    assert_eq!((summary.count, summary.throughput), (0, 0.0));
+    assert_eq!(summary.p99, Duration::ZERO);
+  }
+}
@@ -0,0 +1,255 @@
+//! What a benchmark drives: a coordinator through its client service, or, without a coordinator,
+//! the endorsers themselves, which get every write the way the coordinator fans it out to them.
+//! The latter measures the endorsers and the connections to them without the ledger store.
+use crate::coordinator_proto::{self, call_client::CallClient, AppendConditionFailed};
+use ledger::{
+  compute_aggregated_block_hash, compute_genesis_block, compute_view_block_hash,
+  encode_view_config,
+  endorser_proto::{self, endorser_call_client::EndorserCallClient},
+  CustomSerde, EndorserHostnames, MetaBlock, NimbleDigest, NimbleHashTrait, Nonce, Nonces, Receipt,
+  Receipts,
+};
+use prost::{bytes::Bytes, Message};
+use std::future::Future;
+use tonic::{transport::Channel, Code, Status};
+
+/// the app bytes of the ledgers that a benchmark creates through a coordinator
+const APP_BYTES: &[u8] = b"nimble-bench";
+/// the entries of a ledger that a page of an audit holds at most
+const AUDIT_PAGE_SIZE: u64 = 256;
+
+#[derive(Clone)]
+pub enum Target {
+  Coordinator(CallClient<Channel>),
+  Endorsers(Vec<EndorserCallClient<Channel>>),
+}
+
+fn status_error(what: &str, status: Status) -> String {
+  format!("{} failed: {}", what, status.message())
+}
+
+impl Target {
+  pub async fn connect_coordinator(uri: &str) -> Result<Self, String> {
+    let client = CallClient::connect(uri.to_string())
+      .await
+      .map_err(|e| format!("cannot connect to the coordinator at {}: {}", uri, e))?;
+    Ok(Target::Coordinator(client))
+  }
+
+  /// connects to the endorsers at `uris` and activates them in a first view of their own, as a
+  /// coordinator does with the endorsers of a new group
+  pub async fn connect_endorsers(uris: &[String]) -> Result<Self, String> {
+    let mut clients = Vec::with_capacity(uris.len());
+    let mut endorsers = EndorserHostnames::new();
+    for uri in uris {
+      let mut client = EndorserCallClient::connect(uri.clone())
+        .await
+        .map_err(|e| format!("cannot connect to the endorser at {}: {}", uri, e))?;
+      let endorser_proto::GetPublicKeyResp { pk } = client
+        .get_public_key(endorser_proto::GetPublicKeyReq {})
+        .await
+        .map_err(|status| status_error("get_public_key", status))?
+        .into_inner();
+      endorsers.push((pk.to_vec(), uri.clone()));
+      clients.push(client);
+    }
+
+    let config = encode_view_config(&endorsers, None, &[]);
+    let view_block_hash =
+      compute_view_block_hash(&config).map_err(|e| format!("invalid view: {:?}", e))?;
+    let request = endorser_proto::InitializeStateReq {
+      group_identity: view_block_hash.to_bytes().into(),
+      ledger_tail_map: Vec::new(),
+      view_tail_metablock: MetaBlock::default().to_bytes().into(),
+      block_hash: view_block_hash.to_bytes().into(),
+      expected_height: 1,
+      public_key: Bytes::new(),
+    };
+    let mut receipts = Receipts::new();
+    for client in &mut clients {
+      let endorser_proto::InitializeStateResp { receipt } = client
+        .initialize_state(request.clone())
+        .await
+        .map_err(|status| status_error("initialize_state", status))?
+        .into_inner();
+      let receipt =
+        Receipt::from_bytes(&receipt).map_err(|e| format!("invalid receipt: {:?}", e))?;
+      receipts.add(&receipt);
+    }
+
+    let request = endorser_proto::ActivateReq {
+      old_config: Bytes::new(),
+      new_config: config.into(),
+      ledger_tail_maps: Vec::new(),
+      ledger_chunks: Vec::new(),
+      receipts: receipts.to_bytes().into(),
+    };
+    for client in &mut clients {
+      client
+        .activate(request.clone())
+        .await
+        .map_err(|status| status_error("activate", status))?;
+    }
+    Ok(Target::Endorsers(clients))
+  }
+
+  /// creates a ledger whose genesis block holds `block`, and returns its handle
+  pub async fn create(&self, block: &[u8]) -> Result<Vec<u8>, Status> {
+    match self {
+      Target::Coordinator(client) => {
+        let request = coordinator_proto::NewLedgerReq {
+          handle: Vec::new(),
+          block: block.to_vec(),
+          app_bytes: APP_BYTES.to_vec(),
+          nonce: Nonce::new().to_bytes(),
+          metadata: Vec::new(),
+        };
+        let resp = client.clone().new_ledger(request).await?.into_inner();
+        Ok(resp.handle)
+      },
+      Target::Endorsers(clients) => {
+        let handle = NimbleDigest::digest(&Nonce::new().to_bytes());
+        let genesis_block = compute_genesis_block(block, &[]);
+        let block_hash = compute_aggregated_block_hash(
+          &genesis_block.hash().to_bytes(),
+          &Nonces::new().hash().to_bytes(),
+        );
+        let request = endorser_proto::NewLedgerReq {
+          handle: handle.to_bytes().into(),
+          block_hash: block_hash.to_bytes().into(),
+          block: genesis_block.to_bytes().into(),
+        };
+        fan_out(clients, move |mut client| {
+          let request = request.clone();
+          async move { client.new_ledger(request).await }
+        })
+        .await?;
+        Ok(handle.to_bytes())
+      },
+    }
+  }
+
+  /// appends `block` to the ledger `handle` at `height`, which is the current height plus one
+  pub async fn append(&self, handle: &[u8], height: u64, block: &[u8]) -> Result<(), Status> {
+    match self {
+      Target::Coordinator(client) => {
+        let request = coordinator_proto::AppendReq {
+          handle: handle.to_vec(),
+          block: block.to_vec(),
+          expected_height: height - 1,
+          request_id: String::new(),
+        };
+        client.clone().append(request).await?;
+        Ok(())
+      },
+      Target::Endorsers(clients) => {
+        let nonces = Nonces::new();
+        let block_hash = compute_aggregated_block_hash(
+          &NimbleDigest::digest(block).to_bytes(),
+          &nonces.hash().to_bytes(),
+        );
+        let request = endorser_proto::AppendReq {
+          handle: Bytes::copy_from_slice(handle),
+          block_hash: block_hash.to_bytes().into(),
+          expected_height: height,
+          block: Bytes::copy_from_slice(block),
+          nonces: nonces.to_bytes().into(),
+          expected_tail: Bytes::new(),
+        };
+        fan_out(clients, move |mut client| {
+          let request = request.clone();
+          async move { client.append(request).await }
+        })
+        .await
+      },
+    }
+  }
+
+  /// reads the tail of the ledger `handle` that the endorsers attest with a fresh nonce
+  pub async fn read(&self, handle: &[u8]) -> Result<(), Status> {
+    match self {
+      Target::Coordinator(client) => {
+        let request = coordinator_proto::ReadLatestReq {
+          handle: handle.to_vec(),
+          nonce: Nonce::new().to_bytes(),
+          consistency: coordinator_proto::ReadConsistency::Attested as i32,
+        };
+        client.clone().read_latest(request).await?;
+        Ok(())
+      },
+      Target::Endorsers(clients) => {
+        let request = endorser_proto::ReadLatestReq {
+          handle: Bytes::copy_from_slice(handle),
+          nonce: Nonce::new().to_bytes().into(),
+        };
+        fan_out(clients, move |mut client| {
+          let request = request.clone();
+          async move { client.read_latest(request).await }
+        })
+        .await
+      },
+    }
+  }
+
+  /// reads the entries of the ledger `handle` from its genesis up to `height` a page at a time, as
+  /// an audit does; the endorsers do not hold the entries
+  pub async fn read_history(&self, handle: &[u8], height: u64) -> Result<(), Status> {
+    let client = match self {
+      Target::Coordinator(client) => client,
+      Target::Endorsers(_clients) => {
+        return Err(Status::unimplemented(
+          "the endorsers do not hold the entries of ledgers",
+        ))
+      },
+    };
+    let mut page_token = Vec::new();
+    loop {
+      let request = coordinator_proto::ReadRangeReq {
+        handle: handle.to_vec(),
+        from: 0,
+        to: height,
+        page_size: AUDIT_PAGE_SIZE,
+        page_token,
+      };
+      let resp = client.clone().read_range(request).await?.into_inner();
+      if resp.next_page_token.is_empty() {
+        return Ok(());
+      }
+      page_token = resp.next_page_token;
+    }
+  }
+}
+
+/// calls every endorser at once, and waits for all of them, so that the calls to an endorser stay
+/// in order; fails with the status of a failed call, if any
+async fn fan_out<F, Fut, T>(clients: &[EndorserCallClient<Channel>], call: F) -> Result<(), Status>
+where
+  F: Fn(EndorserCallClient<Channel>) -> Fut,
+  Fut: Future<Output = Result<T, Status>> + Send + 'static,
+  T: Send + 'static,
+{
+  let jobs = clients
+    .iter()
+    .map(|client| tokio::spawn(call(client.clone())))
+    .collect::<Vec<_>>();
+  let mut res = Ok(());
+  for job in jobs {
+    match job.await {
+      Ok(Ok(_resp)) => {},
+      Ok(Err(status)) => res = Err(status),
+      Err(e) => res = Err(Status::internal(e.to_string())),
+    }
+  }
+  res
+}
+
+/// the current height of the ledger that an append failed on because it was not the height the
+/// append expected, if the coordinator says so
+pub fn current_height(status: &Status) -> Option<u64> {
+  if status.code() != Code::FailedPrecondition {
+    return None;
+  }
+  AppendConditionFailed::decode(status.details())
+    .ok()
+    .map(|details| details.current_height)
+}
@@ -0,0 +1,176 @@
+//! A connection to the coordinator together with the verifier state that its responses are
+//! checked against. The state is kept in a file, so the CLI follows the view changes of the
+//! coordinator from one run to the next rather than trusting whatever view it is shown.
+use crate::{
+  coordinator_proto::{call_client::CallClient, ReadViewByIndexReq},
+  errors::CliError,
+};
+use ledger::{compute_view_block_hash, CustomSerde, NimbleDigest};
+use nimble_client::{
+  tls::{self, TlsOptions},
+  ClientError,
+};
+use std::path::{Path, PathBuf};
+use tonic::{
+  transport::{Channel, Endpoint},
+  Code,
+};
+use verifier::{VerifierError, VerifierState};
+
+pub struct Client {
+  conn: CallClient<Channel>,
+  state: VerifierState,
+  state_path: PathBuf,
+  /// whether the state differs from the one in the file
+  changed: bool,
+}
+
+impl Client {
+  /// connects to the coordinator at `uri`, with `tls` if it is an `https` URI, and loads the
+  /// verifier state from `state_path`. Without a state file, the client trusts the first view of
+  /// the group with `group_identity`, or, if none is given, of the group that the coordinator
+  /// serves.
+  pub async fn connect(
+    uri: &str,
+    tls: &TlsOptions,
+    state_path: &Path,
+    group_identity: Option<NimbleDigest>,
+  ) -> Result<Self, CliError> {
+    Endpoint::from_shared(uri.to_string())
+      .map_err(|_e| CliError::InvalidArgument(format!("invalid coordinator uri {}", uri)))?;
+    let conn = tls::connect(uri, tls).await.map_err(|e| match e {
+      ClientError::Transport(e) => CliError::Connection(e),
+      e => CliError::InvalidArgument(e.to_string()),
+    })?;
+    let conn = CallClient::new(conn);
+
+    let (state, changed) = match std::fs::read(state_path) {
+      Ok(bytes) => {
+        let state = VerifierState::from_bytes(&bytes)
+          .map_err(|e| CliError::State(format!("{} is corrupt: {:?}", state_path.display(), e)))?;
+        match group_identity {
+          Some(group_identity) if *state.get_group_identity() != group_identity => {
+            return Err(CliError::State(format!(
+              "{} belongs to group {}, not {}",
+              state_path.display(),
+              state.get_group_identity(),
+              group_identity
+            )));
+          },
+          _ => (state, false),
+        }
+      },
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        let (block, receipts) = read_view(&conn, 1)
+          .await?
+          .ok_or(CliError::MalformedResponse("the view ledger is empty"))?;
+        let group_identity = match group_identity {
+          Some(group_identity) => group_identity,
+          None => {
+            let group_identity =
+              compute_view_block_hash(&block).map_err(VerifierError::from_view_block)?;
+            eprintln!(
+              "warning: trusting the coordinator's group {}; pass --group-identity to pin it",
+              group_identity
+            );
+            group_identity
+          },
+        };
+        let state = VerifierState::from_first_view(&group_identity, &block, &receipts)?;
+        (state, true)
+      },
+      Err(e) => return Err(CliError::Io(state_path.display().to_string(), e)),
+    };
+    Ok(Client {
+      conn,
+      state,
+      state_path: state_path.to_path_buf(),
+      changed,
+    })
+  }
+
+  pub fn get_state(&self) -> &VerifierState {
+    &self.state
+  }
+
+  /// replaces the state, e.g., with one that was rebuilt from the first view
+  pub fn set_state(&mut self, state: VerifierState) {
+    self.changed |= state != self.state;
+    self.state = state;
+  }
+
+  /// a handle on the client service of the coordinator
+  pub fn call(&self) -> CallClient<Channel> {
+    self.conn.clone()
+  }
+
+  /// reads the entry of the view ledger at `index`; returns `None` if it is beyond the tail
+  pub async fn read_view(&self, index: usize) -> Result<Option<(Vec<u8>, Vec<u8>)>, CliError> {
+    read_view(&self.conn, index).await
+  }
+
+  /// applies the entries of the view ledger from `next_index` on until the state reaches `view`
+  /// or the tail of the view ledger
+  async fn catch_up(&mut self, next_index: usize, view: &NimbleDigest) -> Result<(), CliError> {
+    let mut index = next_index;
+    while self.state.current_view() != *view {
+      let (block, receipts) = match self.read_view(index).await? {
+        Some(entry) => entry,
+        None => break,
+      };
+      self.state.apply_view_change(&block, &receipts)?;
+      self.changed = true;
+      index += 1;
+    }
+    Ok(())
+  }
+
+  /// runs `verify` against the state; if the receipts are from a view that the state does not
+  /// know yet, applies the view changes up to it and runs `verify` again
+  pub async fn verify<T>(
+    &mut self,
+    verify: impl Fn(&VerifierState) -> Result<T, VerifierError>,
+  ) -> Result<T, CliError> {
+    match verify(&self.state) {
+      Err(VerifierError::StaleVerifier { view, next_index }) => {
+        self.catch_up(next_index, &view).await?;
+        Ok(verify(&self.state)?)
+      },
+      res => Ok(res?),
+    }
+  }
+
+  /// writes the state back to its file if it changed; the file is replaced as a whole, so a crash
+  /// leaves either the old or the new state
+  pub fn save(&mut self) -> Result<(), CliError> {
+    if !self.changed {
+      return Ok(());
+    }
+    let tmp_path = self.state_path.with_extension("tmp");
+    let io_error = |path: &Path, e| CliError::Io(path.display().to_string(), e);
+    std::fs::write(&tmp_path, self.state.to_bytes()).map_err(|e| io_error(&tmp_path, e))?;
+    std::fs::rename(&tmp_path, &self.state_path).map_err(|e| io_error(&self.state_path, e))?;
+    self.changed = false;
+    Ok(())
+  }
+}
+
+async fn read_view(
+  conn: &CallClient<Channel>,
+  index: usize,
+) -> Result<Option<(Vec<u8>, Vec<u8>)>, CliError> {
+  let res = conn
+    .clone()
+    .read_view_by_index(ReadViewByIndexReq {
+      index: index as u64,
+    })
+    .await;
+  match res {
+    Ok(resp) => {
+      let resp = resp.into_inner();
+      Ok(Some((resp.block, resp.receipts)))
+    },
+    Err(status) if status.code() == Code::OutOfRange => Ok(None),
+    Err(status) => Err(CliError::Rpc(status)),
+  }
+}
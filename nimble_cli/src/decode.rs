@@ -0,0 +1,701 @@
+//! Decoders for receipts that fail to verify. They lay out the canonical encoding of receipts and
+//! the payloads that endorsers sign field by field, check every signature on its own rather than
+//! stopping at the first bad one as the verifier does, and diff a payload against the expected
+//! one. A malformed input is decoded as far as it goes, and the field where it breaks is named.
+use ledger::{
+  compute_key_rotation_message,
+  signature::{PublicKey, PublicKeyTrait},
+  CustomSerde, IdSig, MetaBlock, NimbleDigest, NimbleHashTrait, Nonce, Receipts,
+};
+use serde_json::{json, Value};
+use std::{collections::HashSet, convert::TryInto, ops::Range};
+use verifier::{ledger_tail_message, view_entry_message, VerifierState};
+
+/// the version byte of the canonical encoding of `Receipts`
+const RECEIPTS_ENCODING_VERSION: u8 = 1;
+
+/// the length of the domain tag that prefixes a key rotation payload, "NimbleKeyRotation"
+const KEY_ROTATION_TAG_LEN: usize = 17;
+
+/// the endorsers whose signatures count
+pub enum Endorsers {
+  /// any key; signatures are only checked to be valid
+  Unknown,
+  Keys(HashSet<Vec<u8>>),
+  /// the endorsers of the view that a verifier state trusts
+  Trusted(VerifierState),
+}
+
+impl Endorsers {
+  fn contains(&self, pk: &PublicKey) -> Option<bool> {
+    match self {
+      Endorsers::Unknown => None,
+      Endorsers::Keys(pks) => Some(pks.contains(&pk.to_bytes())),
+      Endorsers::Trusted(state) => Some(state.is_endorser(pk)),
+    }
+  }
+}
+
+/// what the signatures of receipts are checked against; a signature is checked only if the
+/// group identity and either the handle or `view_ledger` are known
+pub struct Context {
+  pub group_identity: Option<NimbleDigest>,
+  pub handle: Option<Vec<u8>>,
+  /// the nonce of a read, which the endorsers sign together with the tail
+  pub nonce: Option<Nonce>,
+  /// whether the receipts are of an entry of the view ledger
+  pub view_ledger: bool,
+  pub endorsers: Endorsers,
+}
+
+impl Context {
+  /// the message that the endorsers sign for the entry with `metablock` in `view`, if known
+  fn message(
+    &self,
+    view: &NimbleDigest,
+    metablock: &MetaBlock,
+    nonce: Option<&Nonce>,
+  ) -> Option<Vec<u8>> {
+    let group_identity = self.group_identity.as_ref()?;
+    if self.view_ledger {
+      return Some(view_entry_message(group_identity, view, &metablock.hash()));
+    }
+    let handle = NimbleDigest::digest(self.handle.as_ref()?);
+    Some(ledger_tail_message(
+      group_identity,
+      view,
+      &handle,
+      &metablock.hash(),
+      nonce,
+    ))
+  }
+
+  fn verdict(&self, view: &NimbleDigest, metablock: &MetaBlock, id_sig: &IdSig) -> &'static str {
+    let message = match self.message(view, metablock, self.nonce.as_ref()) {
+      Some(message) => message,
+      None => return "unchecked",
+    };
+    if id_sig.verify(&message).is_ok() {
+      return "valid";
+    }
+    // as the verifier does, tells a signature of the tail alone apart from a bad one
+    match self.nonce {
+      Some(_) if !self.view_ledger => match self.message(view, metablock, None) {
+        Some(message) if id_sig.verify(&message).is_ok() => "valid without the nonce",
+        _ => "invalid",
+      },
+      _ => "invalid",
+    }
+  }
+}
+
+/// walks an encoding, naming the field that runs past its end
+struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn new(bytes: &'a [u8]) -> Self {
+    Reader { bytes, pos: 0 }
+  }
+
+  fn take(&mut self, field: &str, len: usize) -> Result<&'a [u8], String> {
+    let left = self.bytes.len() - self.pos;
+    if len > left {
+      return Err(format!(
+        "{} needs {} bytes at byte {}, but {} are left",
+        field, len, self.pos, left
+      ));
+    }
+    let slice = &self.bytes[self.pos..self.pos + len];
+    self.pos += len;
+    Ok(slice)
+  }
+
+  fn take_u32(&mut self, field: &str) -> Result<u32, String> {
+    let bytes = self.take(field, std::mem::size_of::<u32>())?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+  }
+
+  /// reads a u32 LE length followed by that many bytes
+  fn take_prefixed(&mut self, field: &str) -> Result<&'a [u8], String> {
+    let len = self.take_u32(&format!("the length of {}", field))?;
+    self.take(field, len as usize)
+  }
+
+  fn finish(&self) -> Result<(), String> {
+    match self.bytes.len() - self.pos {
+      0 => Ok(()),
+      left => Err(format!("{} trailing bytes at byte {}", left, self.pos)),
+    }
+  }
+}
+
+/// decodes the canonical encoding of receipts, checking each signature against `context`
+pub fn decode_receipts(bytes: &[u8], context: &Context) -> Value {
+  let mut groups = Vec::new();
+  let res = read_groups(&mut Reader::new(bytes), context, &mut groups);
+  let mut output = json!({
+    "bytes": bytes.len(),
+    // the verifier accepts only what the canonical decoder accepts, which also rejects duplicates
+    "canonical": match Receipts::try_from_bytes(bytes) {
+      Ok(_) => "ok".to_string(),
+      Err(e) => e.to_string(),
+    },
+    "groups": groups,
+  });
+  if let Err(e) = res {
+    output["malformed"] = json!(e);
+  }
+  output
+}
+
+fn read_groups(
+  reader: &mut Reader,
+  context: &Context,
+  groups: &mut Vec<Value>,
+) -> Result<(), String> {
+  let version = reader.take("the version", 1)?[0];
+  if version != RECEIPTS_ENCODING_VERSION {
+    return Err(format!("unsupported version {}", version));
+  }
+  let num_groups = reader.take_u32("the number of groups")?;
+  for i in 0..num_groups {
+    let view = reader.take(
+      &format!("the view of group {}", i),
+      NimbleDigest::num_bytes(),
+    )?;
+    let view = NimbleDigest::from_bytes(view).unwrap();
+    let metablock = reader.take(
+      &format!("the metablock of group {}", i),
+      MetaBlock::num_bytes(),
+    )?;
+    let metablock = MetaBlock::from_bytes(metablock).unwrap();
+
+    let mut group = json!({
+      "view": view.to_string(),
+      "height": metablock.get_height(),
+      "prev": metablock.get_prev().to_string(),
+      "block_hash": metablock.get_block_hash().to_string(),
+      "tail": metablock.hash().to_string(),
+    });
+    // laid out as a payload of `decode payload`, of a read if there is a nonce
+    let mut payload = [view.to_bytes(), metablock.to_bytes()].concat();
+    if let (Some(nonce), false) = (&context.nonce, context.view_ledger) {
+      payload.extend(nonce.to_bytes());
+    }
+    group["payload"] = json!(hex::encode(payload));
+    if let Endorsers::Trusted(state) = &context.endorsers {
+      group["trusted_view"] = json!(view == state.current_view());
+    }
+    if let Some(message) = context.message(&view, &metablock, context.nonce.as_ref()) {
+      group["message"] = json!(hex::encode(message));
+    }
+
+    let mut signers = Vec::new();
+    let res = read_signers(reader, i, &view, &metablock, context, &mut signers);
+    group["signers"] = json!(signers);
+    groups.push(group);
+    res?;
+  }
+  reader.finish()
+}
+
+fn read_signers(
+  reader: &mut Reader,
+  group: u32,
+  view: &NimbleDigest,
+  metablock: &MetaBlock,
+  context: &Context,
+  signers: &mut Vec<Value>,
+) -> Result<(), String> {
+  let num_signers = reader.take_u32(&format!("the number of signers of group {}", group))?;
+  for i in 0..num_signers {
+    let pk = reader.take_prefixed(&format!(
+      "the public key of signer {} of group {}",
+      i, group
+    ))?;
+    let sig = reader.take_prefixed(&format!("the signature of signer {} of group {}", i, group))?;
+    let mut signer = json!({
+      "public_key": hex::encode(pk),
+      "signature": hex::encode(sig),
+    });
+    match IdSig::from_raw(pk, sig) {
+      Ok(id_sig) => {
+        if let Some(endorser) = context.endorsers.contains(id_sig.get_pk()) {
+          signer["endorser"] = json!(endorser);
+        }
+        signer["verdict"] = json!(context.verdict(view, metablock, &id_sig));
+      },
+      Err(e) => signer["verdict"] = json!(format!("malformed: {}", e)),
+    }
+    signers.push(signer);
+  }
+  Ok(())
+}
+
+/// the payloads that endorsers sign, in the layout that `decode receipt` prints them in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PayloadKind {
+  /// the view and the metablock of an entry of a ledger
+  Append,
+  /// the view and the metablock of the tail of a ledger, and the nonce of the read
+  Read,
+  /// the hash of the state of the endorser and the metablock of an entry of the view ledger
+  ViewChange,
+  /// the preimage of the message that hands the place of an endorser over to a new key
+  KeyRotation,
+}
+
+pub const PAYLOAD_KINDS: &[&str] = &["append", "read", "view-change", "key-rotation"];
+
+impl PayloadKind {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "append" => Some(PayloadKind::Append),
+      "read" => Some(PayloadKind::Read),
+      "view-change" => Some(PayloadKind::ViewChange),
+      "key-rotation" => Some(PayloadKind::KeyRotation),
+      _ => None,
+    }
+  }
+}
+
+/// a field of a payload: where it sits in the payload and its value in readable form
+struct Field {
+  name: &'static str,
+  range: Range<usize>,
+  value: String,
+}
+
+impl Field {
+  fn to_json(&self) -> Value {
+    json!({
+      "field": self.name,
+      "bytes": format!("{}..{}", self.range.start, self.range.end),
+      "value": self.value,
+    })
+  }
+}
+
+fn read_field(
+  reader: &mut Reader,
+  fields: &mut Vec<Field>,
+  name: &'static str,
+  len: usize,
+  render: impl Fn(&[u8]) -> String,
+) -> Result<(), String> {
+  let start = reader.pos;
+  let bytes = reader.take(&format!("the {}", name), len)?;
+  fields.push(Field {
+    name,
+    range: start..reader.pos,
+    value: render(bytes),
+  });
+  Ok(())
+}
+
+fn read_u32_field(
+  reader: &mut Reader,
+  fields: &mut Vec<Field>,
+  name: &'static str,
+) -> Result<usize, String> {
+  let start = reader.pos;
+  let len = reader.take_u32(&format!("the {}", name))?;
+  fields.push(Field {
+    name,
+    range: start..reader.pos,
+    value: len.to_string(),
+  });
+  Ok(len as usize)
+}
+
+fn render_hex(bytes: &[u8]) -> String {
+  hex::encode(bytes)
+}
+
+fn render_u64(bytes: &[u8]) -> String {
+  u64::from_le_bytes(bytes.try_into().unwrap()).to_string()
+}
+
+/// decodes `bytes` as a payload of `kind` into `fields`, as far as it goes
+fn read_payload(kind: PayloadKind, bytes: &[u8], fields: &mut Vec<Field>) -> Result<(), String> {
+  let mut reader = Reader::new(bytes);
+  let digest_len = NimbleDigest::num_bytes();
+  match kind {
+    PayloadKind::Append | PayloadKind::Read | PayloadKind::ViewChange => {
+      read_field(&mut reader, fields, "view", digest_len, render_hex)?;
+      read_field(&mut reader, fields, "prev", digest_len, render_hex)?;
+      read_field(&mut reader, fields, "block_hash", digest_len, render_hex)?;
+      read_field(&mut reader, fields, "height", 8, render_u64)?;
+      if kind == PayloadKind::Read {
+        read_field(&mut reader, fields, "nonce", Nonce::num_bytes(), render_hex)?;
+      }
+    },
+    PayloadKind::KeyRotation => {
+      read_field(&mut reader, fields, "tag", KEY_ROTATION_TAG_LEN, |tag| {
+        String::from_utf8_lossy(tag).into_owned()
+      })?;
+      read_field(
+        &mut reader,
+        fields,
+        "group_identity",
+        digest_len,
+        render_hex,
+      )?;
+      let len = read_u32_field(&mut reader, fields, "old_pk_len")?;
+      read_field(&mut reader, fields, "old_pk", len, render_hex)?;
+      let len = read_u32_field(&mut reader, fields, "new_pk_len")?;
+      read_field(&mut reader, fields, "new_pk", len, render_hex)?;
+    },
+  }
+  reader.finish()
+}
+
+/// the message that the endorsers sign for a well-formed payload, if it can be computed; that of
+/// a key rotation is recomputed from its keys, so a corrupt tag does not carry over
+fn payload_message(
+  kind: PayloadKind,
+  bytes: &[u8],
+  fields: &[Field],
+  context: &Context,
+) -> Option<Vec<u8>> {
+  let field = |name: &str| {
+    fields
+      .iter()
+      .find(|field| field.name == name)
+      .map(|field| &bytes[field.range.clone()])
+  };
+  let digest = |name: &str| NimbleDigest::from_bytes(field(name)?).ok();
+  if kind == PayloadKind::KeyRotation {
+    let message = compute_key_rotation_message(
+      &digest("group_identity")?,
+      field("old_pk")?,
+      field("new_pk")?,
+    );
+    return Some(message.to_bytes());
+  }
+
+  let group_identity = context.group_identity.as_ref()?;
+  let view = digest("view")?;
+  let metablock = MetaBlock::new(
+    &digest("prev")?,
+    &digest("block_hash")?,
+    u64::from_le_bytes(field("height")?.try_into().ok()?) as usize,
+  );
+  if kind == PayloadKind::ViewChange {
+    return Some(view_entry_message(group_identity, &view, &metablock.hash()));
+  }
+  let handle = NimbleDigest::digest(context.handle.as_ref()?);
+  let nonce = match field("nonce") {
+    Some(nonce) => Some(Nonce::try_from_bytes(nonce).ok()?),
+    None => None,
+  };
+  Some(ledger_tail_message(
+    group_identity,
+    &view,
+    &handle,
+    &metablock.hash(),
+    nonce.as_ref(),
+  ))
+}
+
+/// decodes the payload `actual` of `kind` field by field, and lists the fields in which it differs
+/// from `expected`, if given
+pub fn decode_payload(
+  kind: PayloadKind,
+  actual: &[u8],
+  expected: Option<&[u8]>,
+  context: &Context,
+) -> Value {
+  let mut fields = Vec::new();
+  let res = read_payload(kind, actual, &mut fields);
+  let mut output = json!({
+    "bytes": actual.len(),
+    "fields": fields.iter().map(Field::to_json).collect::<Vec<Value>>(),
+  });
+  match res {
+    Ok(()) => {
+      if let Some(message) = payload_message(kind, actual, &fields, context) {
+        output["message"] = json!(hex::encode(message));
+      }
+    },
+    Err(e) => output["malformed"] = json!(e),
+  }
+
+  if let Some(expected) = expected {
+    let mut expected_fields = Vec::new();
+    if let Err(e) = read_payload(kind, expected, &mut expected_fields) {
+      output["expected_malformed"] = json!(e);
+    }
+    // the fields of a kind come in a fixed order, so they pair up by position
+    let diff = (0..fields.len().max(expected_fields.len()))
+      .filter_map(|i| match (expected_fields.get(i), fields.get(i)) {
+        (Some(expected), Some(actual)) if expected.value == actual.value => None,
+        (expected, actual) => Some(json!({
+          "field": expected.or(actual).unwrap().name,
+          "expected": expected.map_or("missing", |field| field.value.as_str()),
+          "actual": actual.map_or("missing", |field| field.value.as_str()),
+        })),
+      })
+      .collect::<Vec<Value>>();
+    output["matches"] = json!(diff.is_empty() && expected == actual);
+    output["diff"] = json!(diff);
+  }
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::{
+    signature::{PrivateKey, PrivateKeyTrait},
+    Receipt,
+  };
+
+  struct Fixture {
+    context: Context,
+    view: NimbleDigest,
+    metablock: MetaBlock,
+  }
+
+  /// an entry of a ledger endorsed by two of three endorsers and by an outsider
+  fn signed_receipts(nonce: Option<Nonce>) -> (Fixture, Vec<u8>) {
+    let keys = (0..4).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let context = Context {
+      group_identity: Some(NimbleDigest::digest(b"group")),
+      handle: Some(b"handle".to_vec()),
+      nonce,
+      view_ledger: false,
+      endorsers: Endorsers::Keys(
+        keys[..3]
+          .iter()
+          .map(|key| key.get_public_key().unwrap().to_bytes())
+          .collect(),
+      ),
+    };
+    let view = NimbleDigest::digest(b"view");
+    let metablock = MetaBlock::genesis(&NimbleDigest::digest(b"block"))
+      .next(&NimbleDigest::digest(b"next"))
+      .unwrap();
+    let message = context.message(&view, &metablock, nonce.as_ref()).unwrap();
+    let mut receipts = Receipts::new();
+    for key in [&keys[0], &keys[1], &keys[3]] {
+      let id_sig = IdSig::new(key.get_public_key().unwrap(), key.sign(&message).unwrap());
+      receipts.add(&Receipt::new(view, metablock.clone(), id_sig));
+    }
+    let bytes = receipts.to_bytes();
+    let fixture = Fixture {
+      context,
+      view,
+      metablock,
+    };
+    (fixture, bytes)
+  }
+
+  fn verdicts(output: &Value) -> Vec<(bool, String)> {
+    output["groups"][0]["signers"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|signer| {
+        (
+          signer["endorser"].as_bool().unwrap(),
+          signer["verdict"].as_str().unwrap().to_string(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_decode_receipts() {
+    let (fixture, bytes) = signed_receipts(None);
+    let output = decode_receipts(&bytes, &fixture.context);
+    assert_eq!(output["canonical"], "ok");
+    assert!(output.get("malformed").is_none());
+    let group = &output["groups"][0];
+    assert_eq!(group["view"], fixture.view.to_string());
+    assert_eq!(group["height"], 1);
+    assert_eq!(group["tail"], fixture.metablock.hash().to_string());
+    let mut sorted = verdicts(&output);
+    sorted.sort();
+    assert_eq!(
+      sorted,
+      vec![
+        (false, "valid".to_string()),
+        (true, "valid".to_string()),
+        (true, "valid".to_string()),
+      ]
+    );
+
+    // signatures under another group are bad, and none can be checked without the handle
+    let context = Context {
+      group_identity: Some(NimbleDigest::digest(b"other group")),
+      ..fixture.context
+    };
+    let output = decode_receipts(&bytes, &context);
+    assert!(verdicts(&output).iter().all(|(_, v)| v == "invalid"));
+    let context = Context {
+      handle: None,
+      ..context
+    };
+    let output = decode_receipts(&bytes, &context);
+    assert!(verdicts(&output).iter().all(|(_, v)| v == "unchecked"));
+    assert!(output["groups"][0].get("message").is_none());
+  }
+
+  #[test]
+  fn test_decode_corrupt_receipts() {
+    let (fixture, bytes) = signed_receipts(None);
+
+    // a flipped bit in the last signature fails that signature alone
+    let mut corrupt = bytes.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 1;
+    let output = decode_receipts(&corrupt, &fixture.context);
+    assert!(output.get("malformed").is_none());
+    let verdicts = verdicts(&output);
+    assert_eq!(verdicts.iter().filter(|(_, v)| v == "valid").count(), 2);
+    assert_ne!(verdicts[2].1, "valid");
+
+    // truncated receipts decode up to the field that runs past the end
+    let output = decode_receipts(&bytes[..bytes.len() - 10], &fixture.context);
+    assert_ne!(output["canonical"], "ok");
+    assert!(output["malformed"]
+      .as_str()
+      .unwrap()
+      .starts_with("the signature of signer 2 of group 0"));
+    assert_eq!(output["groups"][0]["signers"].as_array().unwrap().len(), 2);
+    let output = decode_receipts(&bytes[..20], &fixture.context);
+    assert!(output["malformed"]
+      .as_str()
+      .unwrap()
+      .starts_with("the view of group 0"));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    let output = decode_receipts(&trailing, &fixture.context);
+    assert_eq!(
+      output["malformed"],
+      format!("1 trailing bytes at byte {}", bytes.len())
+    );
+    let mut version = bytes;
+    version[0] = 2;
+    let output = decode_receipts(&version, &fixture.context);
+    assert_eq!(output["malformed"], "unsupported version 2");
+  }
+
+  #[test]
+  fn test_decode_read_receipts() {
+    // the endorsers signed the tail without the nonce that the client sent
+    let (fixture, bytes) = signed_receipts(None);
+    let context = Context {
+      nonce: Some(Nonce::new()),
+      ..fixture.context
+    };
+    let output = decode_receipts(&bytes, &context);
+    assert!(verdicts(&output)
+      .iter()
+      .all(|(_, v)| v == "valid without the nonce"));
+
+    let (fixture, bytes) = signed_receipts(Some(Nonce::new()));
+    let output = decode_receipts(&bytes, &fixture.context);
+    assert!(verdicts(&output).iter().all(|(_, v)| v == "valid"));
+  }
+
+  #[test]
+  fn test_decode_payload() {
+    let (fixture, bytes) = signed_receipts(None);
+    let receipts = decode_receipts(&bytes, &fixture.context);
+    let payload = hex::decode(receipts["groups"][0]["payload"].as_str().unwrap()).unwrap();
+
+    // the payload of a receipt decodes to the message that its signatures verify against
+    let output = decode_payload(PayloadKind::Append, &payload, None, &fixture.context);
+    assert!(output.get("malformed").is_none());
+    assert_eq!(output["message"], receipts["groups"][0]["message"]);
+    assert_eq!(output["fields"][3]["field"], "height");
+    assert_eq!(output["fields"][3]["value"], "1");
+    assert_eq!(output["fields"][3]["bytes"], "96..104");
+
+    // a payload with another height differs in that field alone
+    let other = MetaBlock::new(
+      fixture.metablock.get_prev(),
+      fixture.metablock.get_block_hash(),
+      2,
+    );
+    let expected = [fixture.view.to_bytes(), other.to_bytes()].concat();
+    let output = decode_payload(
+      PayloadKind::Append,
+      &payload,
+      Some(&expected),
+      &fixture.context,
+    );
+    assert_eq!(output["matches"], false);
+    assert_eq!(
+      output["diff"],
+      json!([{ "field": "height", "expected": "2", "actual": "1" }])
+    );
+    let output = decode_payload(
+      PayloadKind::Append,
+      &payload,
+      Some(&payload),
+      &fixture.context,
+    );
+    assert_eq!(output["matches"], true);
+
+    // a read payload without its nonce is cut short
+    let output = decode_payload(
+      PayloadKind::Read,
+      &payload,
+      Some(&[payload.clone(), Nonce::new().to_bytes()].concat()),
+      &fixture.context,
+    );
+    assert!(output["malformed"]
+      .as_str()
+      .unwrap()
+      .starts_with("the nonce needs 16 bytes"));
+    assert!(output.get("message").is_none());
+    assert_eq!(output["diff"][0]["field"], "nonce");
+    assert_eq!(output["diff"][0]["actual"], "missing");
+  }
+
+  #[test]
+  fn test_decode_key_rotation_payload() {
+    let group_identity = NimbleDigest::digest(b"group");
+    let old_pk = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    let new_pk = PrivateKey::new().get_public_key().unwrap().to_bytes();
+    let mut payload = b"NimbleKeyRotation".to_vec();
+    payload.extend(&group_identity.to_bytes());
+    for pk in [&old_pk, &new_pk] {
+      payload.extend(&(pk.len() as u32).to_le_bytes());
+      payload.extend(pk.iter());
+    }
+    let context = Context {
+      group_identity: None,
+      handle: None,
+      nonce: None,
+      view_ledger: false,
+      endorsers: Endorsers::Unknown,
+    };
+    let output = decode_payload(PayloadKind::KeyRotation, &payload, None, &context);
+    assert!(output.get("malformed").is_none());
+    assert_eq!(
+      output["message"],
+      hex::encode(compute_key_rotation_message(&group_identity, &old_pk, &new_pk).to_bytes())
+    );
+
+    // a corrupt tag shows in the diff, but not in the message, which is recomputed
+    let mut corrupt = payload.clone();
+    corrupt[0] = b'X';
+    let output = decode_payload(PayloadKind::KeyRotation, &corrupt, Some(&payload), &context);
+    assert_eq!(
+      output["diff"],
+      json!([{ "field": "tag", "expected": "NimbleKeyRotation", "actual": "XimbleKeyRotation" }])
+    );
+    assert_eq!(
+      output["message"],
+      hex::encode(compute_key_rotation_message(&group_identity, &old_pk, &new_pk).to_bytes())
+    );
+  }
+}
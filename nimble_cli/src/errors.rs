@@ -0,0 +1,76 @@
+use std::fmt;
+use tonic::Status;
+use verifier::{audit::AuditError, VerifierError};
+
+#[derive(Debug)]
+pub enum CliError {
+  /// returned if an argument is missing or malformed
+  InvalidArgument(String),
+  /// returned if a local file cannot be read or written; carries its path
+  Io(String, std::io::Error),
+  /// returned if the coordinator cannot be reached
+  Connection(tonic::transport::Error),
+  /// returned if the coordinator fails the request
+  Rpc(Status),
+  /// returned if a response of the coordinator is not well formed
+  MalformedResponse(&'static str),
+  /// returned if the verifier state on disk cannot be used
+  State(String),
+  /// returned if the receipts in a response do not verify, i.e., the coordinator or the endorsers
+  /// misbehaved
+  Verification(VerifierError),
+  /// returned if an export bundle fails its audit
+  Audit(AuditError),
+}
+
+impl CliError {
+  /// the exit code of the CLI, which sets failed verifications apart from other failures
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      CliError::Verification(_) | CliError::Audit(_) => 2,
+      _ => 1,
+    }
+  }
+}
+
+impl fmt::Display for CliError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CliError::InvalidArgument(message) => write!(f, "{}", message),
+      CliError::Io(path, error) => write!(f, "{}: {}", path, error),
+      CliError::Connection(error) => write!(f, "cannot connect to the coordinator: {}", error),
+      CliError::Rpc(status) => write!(
+        f,
+        "the coordinator failed the request: {:?}: {}",
+        status.code(),
+        status.message()
+      ),
+      CliError::MalformedResponse(what) => {
+        write!(f, "the coordinator returned a malformed response: {}", what)
+      },
+      CliError::State(message) => write!(f, "verifier state: {}", message),
+      CliError::Verification(error) => write!(f, "verification failed: {}", error),
+      CliError::Audit(error) => write!(f, "audit failed {}", error),
+    }
+  }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<Status> for CliError {
+  fn from(status: Status) -> Self {
+    CliError::Rpc(status)
+  }
+}
+
+impl From<VerifierError> for CliError {
+  fn from(error: VerifierError) -> Self {
+    CliError::Verification(error)
+  }
+}
+
+impl From<AuditError> for CliError {
+  fn from(error: AuditError) -> Self {
+    CliError::Audit(error)
+  }
+}
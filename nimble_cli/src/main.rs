@@ -0,0 +1,945 @@
+//! A command-line client of the coordinator's client service. Every command verifies the receipts
+//! of what the coordinator returns with the verifier crate before it reports success, so a
+//! misbehaving coordinator makes the command fail rather than return data that no quorum of
+//! endorsers vouched for. Failed verifications exit with code 2, other failures with code 1.
+mod client;
+mod decode;
+mod errors;
+mod receipt;
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod coordinator_proto {
+  tonic::include_proto!("coordinator_proto");
+}
+
+use crate::{
+  client::Client,
+  decode::{decode_payload, decode_receipts, Context, Endorsers, PayloadKind, PAYLOAD_KINDS},
+  errors::CliError,
+  receipt::{Attests, ReceiptFile},
+};
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use coordinator_proto::{
+  AppendReq, ExportLedgerReq, NewLedgerReq, ReadConsistency, ReadLatestReq, ReadRangeReq,
+  ReadRangeResp,
+};
+use ledger::{
+  compute_aggregated_block_hash, compute_genesis_block, CustomSerde, EndorserHostnames, Handle,
+  MetaBlock, NimbleDigest, NimbleHashTrait, Nonce,
+};
+use nimble_client::TlsOptions;
+use serde_json::{json, Value};
+use std::{convert::TryFrom, path::Path, str::FromStr};
+use verifier::{audit::audit_bundle, VerifierError, VerifierState};
+
+const DEFAULT_COORDINATOR: &str = "http://[::1]:8080";
+const DEFAULT_STATE_FILE: &str = ".nimble-verifier-state";
+
+fn parse_hex(flag: &str, value: &str) -> Result<Vec<u8>, CliError> {
+  hex::decode(value).map_err(|_e| CliError::InvalidArgument(format!("{} must be hex", flag)))
+}
+
+fn parse_u64(flag: &str, value: &str) -> Result<u64, CliError> {
+  value
+    .parse::<u64>()
+    .map_err(|_e| CliError::InvalidArgument(format!("{} must be a non-negative integer", flag)))
+}
+
+fn to_height(height: u64) -> Result<usize, CliError> {
+  usize::try_from(height).map_err(|_e| CliError::MalformedResponse("the height overflows"))
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, CliError> {
+  std::fs::read(path).map_err(|e| CliError::Io(path.to_string(), e))
+}
+
+/// the bytes of `value`, which names a file or holds them in hex
+fn read_input(value: &str) -> Result<Vec<u8>, CliError> {
+  if Path::new(value).is_file() {
+    return read_file(value);
+  }
+  hex::decode(value.trim())
+    .map_err(|_e| CliError::InvalidArgument(format!("{} is neither a file nor hex", value)))
+}
+
+fn write_file(path: &str, bytes: &[u8]) -> Result<(), CliError> {
+  std::fs::write(path, bytes).map_err(|e| CliError::Io(path.to_string(), e))
+}
+
+/// writes `receipt` to the file named by `--receipt-out`, if any
+fn write_receipt(args: &ArgMatches, receipt: &ReceiptFile) -> Result<(), CliError> {
+  match args.value_of("receipt_out") {
+    Some(path) => write_file(path, &receipt.to_bytes()),
+    None => Ok(()),
+  }
+}
+
+async fn create(client: &mut Client, args: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let block = match args.value_of("file") {
+    Some(path) => read_file(path)?,
+    None => Vec::new(),
+  };
+  let metadata = args.value_of("metadata").unwrap_or("").as_bytes().to_vec();
+  let (handle, app_bytes, nonce) = match args.value_of("handle") {
+    Some(handle) => (parse_hex("--handle", handle)?, Vec::new(), None),
+    None => {
+      let app_bytes = args.value_of("app_bytes").unwrap_or("").as_bytes().to_vec();
+      (Vec::new(), app_bytes, Some(Nonce::new()))
+    },
+  };
+
+  let resp = client
+    .call()
+    .new_ledger(NewLedgerReq {
+      handle: handle.clone(),
+      block: block.clone(),
+      app_bytes: app_bytes.clone(),
+      nonce: nonce.map(|nonce| nonce.to_bytes()).unwrap_or_default(),
+      metadata: metadata.clone(),
+    })
+    .await?
+    .into_inner();
+  // a derived handle is checked like the rest of the response
+  let handle = match nonce {
+    Some(nonce) => {
+      let handle = Handle::derive(&app_bytes, &nonce).to_bytes();
+      if resp.handle != handle {
+        return Err(CliError::MalformedResponse(
+          "the handle is not derived from --app-bytes",
+        ));
+      }
+      handle
+    },
+    None => handle,
+  };
+
+  let tail = client
+    .verify(|state| state.verify_new_ledger(&handle, &block, &metadata, &resp.receipts))
+    .await?;
+  let block_hash = compute_aggregated_block_hash(
+    &compute_genesis_block(&block, &metadata).hash().to_bytes(),
+    &NimbleDigest::default().to_bytes(),
+  );
+  write_receipt(
+    args,
+    &ReceiptFile {
+      handle: handle.clone(),
+      height: 0,
+      attests: Attests::Entry { block_hash },
+      receipts: resp.receipts,
+    },
+  )?;
+  Ok(json!({
+    "handle": hex::encode(&handle),
+    "height": 0,
+    "tail": tail.to_string(),
+  }))
+}
+
+async fn append(client: &mut Client, args: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let handle = parse_hex("--handle", args.value_of("handle").unwrap())?;
+  let block = read_file(args.value_of("file").unwrap())?;
+  let expected_height = parse_u64(
+    "--expected-height",
+    args.value_of("expected_height").unwrap(),
+  )?;
+
+  let resp = client
+    .call()
+    .append(AppendReq {
+      handle: handle.clone(),
+      block: block.clone(),
+      expected_height,
+      request_id: String::new(),
+    })
+    .await?
+    .into_inner();
+  // the block is appended right after the expected height
+  if Some(resp.height) != expected_height.checked_add(1) {
+    return Err(CliError::MalformedResponse(
+      "the block is not at --expected-height + 1",
+    ));
+  }
+  let hash_nonces = NimbleDigest::from_bytes(&resp.hash_nonces)
+    .map_err(|_e| CliError::MalformedResponse("hash_nonces is not a digest"))?;
+  let receipt = ReceiptFile {
+    handle,
+    height: to_height(resp.height)?,
+    attests: Attests::Entry {
+      block_hash: compute_aggregated_block_hash(
+        &NimbleDigest::digest(&block).to_bytes(),
+        &hash_nonces.to_bytes(),
+      ),
+    },
+    receipts: resp.receipts,
+  };
+
+  let tail = client.verify(|state| receipt.verify(state)).await?;
+  write_receipt(args, &receipt)?;
+  Ok(json!({
+    "handle": hex::encode(&receipt.handle),
+    "height": receipt.height,
+    "tail": tail.to_string(),
+  }))
+}
+
+async fn read(client: &mut Client, args: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let handle = parse_hex("--handle", args.value_of("handle").unwrap())?;
+  let cached = args.is_present("cached");
+  let nonce = Nonce::new();
+  let (consistency, nonce_bytes) = if cached {
+    (ReadConsistency::Cached, Vec::new())
+  } else {
+    (ReadConsistency::Attested, nonce.to_bytes())
+  };
+
+  let resp = client
+    .call()
+    .read_latest(ReadLatestReq {
+      handle: handle.clone(),
+      nonce: nonce_bytes.clone(),
+      consistency: consistency as i32,
+    })
+    .await?
+    .into_inner();
+  if resp.consistency != consistency as i32 {
+    return Err(CliError::MalformedResponse(
+      "the read does not have the requested consistency",
+    ));
+  }
+  if resp.nonce != nonce_bytes {
+    return Err(CliError::MalformedResponse("the nonce is not the one sent"));
+  }
+  // a cached read attests the entry alone, and not that it is still the tail
+  let hash_block = NimbleDigest::digest(&resp.block);
+  let attests = if cached {
+    Attests::Entry {
+      block_hash: compute_aggregated_block_hash(
+        &hash_block.to_bytes(),
+        &NimbleDigest::digest(&resp.nonces).to_bytes(),
+      ),
+    }
+  } else {
+    Attests::Read {
+      hash_block,
+      nonces: resp.nonces,
+      nonce,
+    }
+  };
+  let receipt = ReceiptFile {
+    handle,
+    height: to_height(resp.height)?,
+    attests,
+    receipts: resp.receipts,
+  };
+
+  let tail = client.verify(|state| receipt.verify(state)).await?;
+  write_receipt(args, &receipt)?;
+  let mut output = json!({
+    "handle": hex::encode(&receipt.handle),
+    "height": receipt.height,
+    "tail": tail.to_string(),
+    "attested": !cached,
+  });
+  match args.value_of("out") {
+    Some(path) => write_file(path, &resp.block)?,
+    None => output["block"] = json!(hex::encode(&resp.block)),
+  }
+  Ok(output)
+}
+
+/// verifies a page of `ReadRange` whose first entry is at `from` through the receipts of the tail
+/// that the block hashes after the entries lead to
+async fn verify_range_page(
+  client: &mut Client,
+  handle: &[u8],
+  from: usize,
+  page: &ReadRangeResp,
+) -> Result<(), CliError> {
+  if page.entries.is_empty() {
+    return Err(CliError::MalformedResponse("a page holds no entries"));
+  }
+  let checkpoint = if from == 0 {
+    if !page.checkpoint.is_empty() {
+      return Err(CliError::MalformedResponse(
+        "the genesis entry has a checkpoint",
+      ));
+    }
+    None
+  } else {
+    let checkpoint = MetaBlock::from_bytes(&page.checkpoint)
+      .map_err(|_e| CliError::MalformedResponse("the checkpoint is not a metablock"))?;
+    if checkpoint.get_height() + 1 != from {
+      return Err(CliError::MalformedResponse(
+        "the checkpoint does not precede the page",
+      ));
+    }
+    Some(checkpoint)
+  };
+
+  let mut block_hashes = Vec::new();
+  for (i, entry) in page.entries.iter().enumerate() {
+    if entry.index != (from + i) as u64 {
+      return Err(CliError::MalformedResponse(
+        "the entries are not consecutive",
+      ));
+    }
+    block_hashes.push(compute_aggregated_block_hash(
+      &NimbleDigest::digest(&entry.block).to_bytes(),
+      &NimbleDigest::digest(&entry.nonces).to_bytes(),
+    ));
+  }
+  for block_hash in &page.block_hashes {
+    block_hashes.push(
+      NimbleDigest::from_bytes(block_hash)
+        .map_err(|_e| CliError::MalformedResponse("a block hash is not a digest"))?,
+    );
+  }
+  client
+    .verify(|state| {
+      state.verify_chain(
+        handle,
+        checkpoint.as_ref(),
+        &block_hashes,
+        &page.tail_receipts,
+      )
+    })
+    .await?;
+  Ok(())
+}
+
+async fn history(client: &mut Client, args: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let handle = parse_hex("--handle", args.value_of("handle").unwrap())?;
+  let from = parse_u64("--from", args.value_of("from").unwrap())?;
+  let to = parse_u64("--to", args.value_of("to").unwrap())?;
+  if from > to {
+    return Err(CliError::InvalidArgument(
+      "--from must be at most --to".to_string(),
+    ));
+  }
+
+  let mut entries = Vec::new();
+  let mut page_token = Vec::new();
+  loop {
+    let page = client
+      .call()
+      .read_range(ReadRangeReq {
+        handle: handle.clone(),
+        from,
+        to,
+        page_size: 0,
+        page_token,
+      })
+      .await?
+      .into_inner();
+    verify_range_page(client, &handle, to_height(from)? + entries.len(), &page).await?;
+    for entry in &page.entries {
+      entries.push(json!({
+        "index": entry.index,
+        "block": hex::encode(&entry.block),
+      }));
+    }
+    if page.next_page_token.is_empty() {
+      break;
+    }
+    page_token = page.next_page_token;
+  }
+  if entries.len() as u64 != (to - from).saturating_add(1) {
+    return Err(CliError::MalformedResponse(
+      "the pages do not cover the range",
+    ));
+  }
+  Ok(json!({
+    "handle": hex::encode(&handle),
+    "entries": entries,
+  }))
+}
+
+async fn verify_receipt(client: &mut Client, args: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let path = args.value_of("file").unwrap();
+  let receipt = ReceiptFile::from_bytes(&read_file(path)?)
+    .map_err(|e| CliError::InvalidArgument(format!("{} is not a receipt file: {:?}", path, e)))?;
+
+  let tail = client.verify(|state| receipt.verify(state)).await?;
+  let mut output = json!({
+    "handle": hex::encode(&receipt.handle),
+    "height": receipt.height,
+    "tail": tail.to_string(),
+    "view": client.get_state().current_view().to_string(),
+  });
+  if let Attests::Read { nonce, .. } = &receipt.attests {
+    output["nonce"] = json!(nonce.to_string());
+  }
+  Ok(output)
+}
+
+/// streams the export bundle of a ledger into a file, once it passed the audit against the group
+/// of the verifier state
+async fn export(client: &mut Client, args: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let handle = parse_hex("--handle", args.value_of("handle").unwrap())?;
+  let out = args.value_of("out").unwrap();
+  let mut stream = client
+    .call()
+    .export_ledger(ExportLedgerReq {
+      handle: handle.clone(),
+    })
+    .await?
+    .into_inner();
+  let mut bundle = Vec::new();
+  while let Some(resp) = stream.message().await? {
+    bundle.extend(resp.chunk);
+  }
+  let report = audit_bundle(&bundle, Some(client.get_state().get_group_identity()))?;
+  write_file(out, &bundle)?;
+  Ok(json!({
+    "handle": hex::encode(&report.handle),
+    "height": report.height,
+    "tail": report.tail.to_string(),
+    "bytes": bundle.len(),
+  }))
+}
+
+/// the verifier state in the state file, if there is one
+fn load_state(state_path: &Path) -> Result<Option<VerifierState>, CliError> {
+  match std::fs::read(state_path) {
+    Ok(bytes) => VerifierState::from_bytes(&bytes)
+      .map(Some)
+      .map_err(|e| CliError::State(format!("{} is corrupt: {:?}", state_path.display(), e))),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(CliError::Io(state_path.display().to_string(), e)),
+  }
+}
+
+/// audits an export bundle offline. The group is pinned by `group_identity`, or by the state file
+/// if there is one; otherwise the audit trusts the group of the bundle
+fn audit(
+  args: &ArgMatches<'_>,
+  state_path: &Path,
+  group_identity: Option<NimbleDigest>,
+) -> Result<Value, CliError> {
+  let bundle = read_file(args.value_of("bundle").unwrap())?;
+  let group_identity = match group_identity {
+    Some(group_identity) => Some(group_identity),
+    None => match load_state(state_path)? {
+      Some(state) => Some(*state.get_group_identity()),
+      None => {
+        eprintln!("warning: trusting the group of the bundle; pass --group-identity to pin it");
+        None
+      },
+    },
+  };
+  let report = audit_bundle(&bundle, group_identity.as_ref())?;
+  Ok(json!({
+    "verdict": "valid",
+    "handle": hex::encode(&report.handle),
+    "height": report.height,
+    "tail": report.tail.to_string(),
+    "group_identity": report.group_identity.to_string(),
+    "views": report.num_views,
+    "metadata": hex::encode(&report.metadata),
+  }))
+}
+
+/// decodes receipts or a payload that endorsers sign, offline. Signatures are checked against the
+/// group of `group_identity` or of the state file, and attributed to the endorsers of --endorsers
+/// or to those that the state file trusts
+fn decode(
+  args: &ArgMatches<'_>,
+  state_path: &Path,
+  group_identity: Option<NimbleDigest>,
+) -> Result<Value, CliError> {
+  let (command, args) = match args.subcommand() {
+    (command, Some(args)) => (command, args),
+    _ => {
+      return Err(CliError::InvalidArgument(
+        "missing decode command".to_string(),
+      ))
+    },
+  };
+  let state = load_state(state_path)?;
+  let group_identity =
+    group_identity.or_else(|| state.as_ref().map(|state| *state.get_group_identity()));
+  let endorsers = match (args.value_of("endorsers"), state) {
+    (Some(pks), _) => Endorsers::Keys(
+      pks
+        .split(',')
+        .map(|pk| parse_hex("--endorsers", pk))
+        .collect::<Result<_, _>>()?,
+    ),
+    (None, Some(state)) => Endorsers::Trusted(state),
+    (None, None) => Endorsers::Unknown,
+  };
+  let nonce = match args.value_of("nonce") {
+    Some(nonce) => Some(
+      Nonce::try_from_bytes(&parse_hex("--nonce", nonce)?)
+        .map_err(|_e| CliError::InvalidArgument("--nonce must be 16 bytes".to_string()))?,
+    ),
+    None => None,
+  };
+  let mut context = Context {
+    group_identity,
+    handle: match args.value_of("handle") {
+      Some(handle) => Some(parse_hex("--handle", handle)?),
+      None => None,
+    },
+    nonce,
+    view_ledger: args.is_present("view_ledger"),
+    endorsers,
+  };
+  let input = read_input(args.value_of("input").unwrap())?;
+
+  match command {
+    "receipt" => {
+      // a receipt file written with --receipt-out names the handle and the nonce of its receipts
+      let receipts = match ReceiptFile::from_bytes(&input) {
+        Ok(file) => {
+          context.handle = context.handle.or(Some(file.handle));
+          if let Attests::Read { nonce, .. } = file.attests {
+            context.nonce = context.nonce.or(Some(nonce));
+          }
+          file.receipts
+        },
+        Err(_) => input,
+      };
+      Ok(decode_receipts(&receipts, &context))
+    },
+    "payload" => {
+      let kind = PayloadKind::from_name(args.value_of("kind").unwrap()).unwrap();
+      let expected = match args.value_of("expected") {
+        Some(expected) => Some(read_input(expected)?),
+        None => None,
+      };
+      Ok(decode_payload(kind, &input, expected.as_deref(), &context))
+    },
+    _ => Err(CliError::InvalidArgument(format!(
+      "unknown decode command {}",
+      command
+    ))),
+  }
+}
+
+/// describes the view that `state` just applied from `view_block`
+fn describe_view(state: &VerifierState, view_block: &[u8]) -> Result<Value, CliError> {
+  let endorsers: EndorserHostnames =
+    bincode::deserialize(view_block).map_err(|_e| VerifierError::MalformedViewBlock)?;
+  let endorsers = endorsers
+    .iter()
+    .map(|(pk, uri)| json!({ "public_key": hex::encode(pk), "uri": uri }))
+    .collect::<Vec<Value>>();
+  Ok(json!({
+    "index": state.current_view_index(),
+    "view": state.current_view().to_string(),
+    "endorsers": endorsers,
+  }))
+}
+
+/// replays the view ledger from its first entry, verifying each view change; the view ledger must
+/// extend the one that the state followed so far
+async fn view_history(client: &mut Client) -> Result<Value, CliError> {
+  let known_index = client.get_state().current_view_index();
+  let known_view = client.get_state().current_view();
+  let group_identity = *client.get_state().get_group_identity();
+
+  let (block, receipts) = client
+    .read_view(1)
+    .await?
+    .ok_or(CliError::MalformedResponse("the view ledger is empty"))?;
+  let mut state = VerifierState::from_first_view(&group_identity, &block, &receipts)?;
+  let mut views = vec![describe_view(&state, &block)?];
+  let mut forked = state.current_view_index() == known_index && state.current_view() != known_view;
+  while let Some((block, receipts)) = client.read_view(state.current_view_index() + 1).await? {
+    state.apply_view_change(&block, &receipts)?;
+    views.push(describe_view(&state, &block)?);
+    forked |= state.current_view_index() == known_index && state.current_view() != known_view;
+  }
+  if forked {
+    return Err(CliError::Verification(VerifierError::WrongEntry));
+  }
+  if state.current_view_index() < known_index {
+    return Err(CliError::MalformedResponse(
+      "the view ledger is shorter than the one the verifier state follows",
+    ));
+  }
+  client.set_state(state);
+  Ok(json!({
+    "group_identity": group_identity.to_string(),
+    "views": views,
+  }))
+}
+
+/// renders a value of the output on a single line, e.g., an entry of a list
+fn render_inline(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    Value::Object(fields) => fields
+      .iter()
+      .map(|(key, value)| format!("{}={}", key, render_inline(value)))
+      .collect::<Vec<String>>()
+      .join(" "),
+    Value::Array(items) => format!(
+      "[{}]",
+      items
+        .iter()
+        .map(render_inline)
+        .collect::<Vec<String>>()
+        .join(", ")
+    ),
+    other => other.to_string(),
+  }
+}
+
+fn print_output(output: &Value, json: bool) {
+  if json {
+    println!("{}", output);
+    return;
+  }
+  if let Value::Object(fields) = output {
+    for (key, value) in fields {
+      match value {
+        Value::Array(items) => {
+          println!("{}:", key);
+          for item in items {
+            println!("  {}", render_inline(item));
+          }
+        },
+        value => println!("{}: {}", key, render_inline(value)),
+      }
+    }
+  }
+}
+
+fn handle_arg() -> Arg<'static, 'static> {
+  Arg::with_name("handle")
+    .long("handle")
+    .takes_value(true)
+    .help("The handle of the ledger, in hex")
+}
+
+fn receipt_out_arg() -> Arg<'static, 'static> {
+  Arg::with_name("receipt_out")
+    .long("receipt-out")
+    .takes_value(true)
+    .help("A file to write the verified receipts to, for verify-receipt")
+}
+
+fn app() -> App<'static, 'static> {
+  App::new("nimble-cli")
+    .about("A client of the Nimble coordinator that verifies what it returns")
+    .setting(AppSettings::SubcommandRequiredElseHelp)
+    .arg(
+      Arg::with_name("coordinator")
+        .short("c")
+        .long("coordinator")
+        .takes_value(true)
+        .global(true)
+        .help("The URL of the coordinator's client service [default: http://[::1]:8080]"),
+    )
+    .arg(
+      Arg::with_name("state")
+        .long("state")
+        .takes_value(true)
+        .global(true)
+        .help("The file that keeps the verifier state [default: .nimble-verifier-state]"),
+    )
+    .arg(
+      Arg::with_name("group_identity")
+        .long("group-identity")
+        .takes_value(true)
+        .global(true)
+        .help("The group identity to trust, in hex; checked against the verifier state"),
+    )
+    .arg(
+      Arg::with_name("ca_cert")
+        .long("ca-cert")
+        .takes_value(true)
+        .global(true)
+        .help("A PEM bundle of the CAs to verify an https coordinator against"),
+    )
+    .arg(
+      Arg::with_name("client_cert")
+        .long("client-cert")
+        .takes_value(true)
+        .global(true)
+        .help("A PEM certificate to present to a coordinator that verifies its clients"),
+    )
+    .arg(
+      Arg::with_name("client_key")
+        .long("client-key")
+        .takes_value(true)
+        .global(true)
+        .help("The PEM private key of --client-cert"),
+    )
+    .arg(
+      Arg::with_name("insecure")
+        .long("insecure")
+        .global(true)
+        .help("Does not verify the certificate of an https coordinator; receipts still verify"),
+    )
+    .arg(
+      Arg::with_name("json")
+        .long("json")
+        .global(true)
+        .help("Prints the output as JSON"),
+    )
+    .subcommand(
+      SubCommand::with_name("create")
+        .about("Creates a ledger")
+        .arg(handle_arg())
+        .arg(
+          Arg::with_name("app_bytes")
+            .long("app-bytes")
+            .takes_value(true)
+            .help(
+              "Application bytes that the coordinator derives the handle from with a fresh nonce",
+            ),
+        )
+        .group(
+          ArgGroup::with_name("name")
+            .args(&["handle", "app_bytes"])
+            .required(true),
+        )
+        .arg(
+          Arg::with_name("file")
+            .long("file")
+            .takes_value(true)
+            .help("A file holding the genesis block; empty if not set"),
+        )
+        .arg(
+          Arg::with_name("metadata")
+            .long("metadata")
+            .takes_value(true)
+            .help("The immutable metadata of the ledger"),
+        )
+        .arg(receipt_out_arg()),
+    )
+    .subcommand(
+      SubCommand::with_name("append")
+        .about("Appends a block to a ledger")
+        .arg(handle_arg().required(true))
+        .arg(
+          Arg::with_name("file")
+            .long("file")
+            .takes_value(true)
+            .required(true)
+            .help("A file holding the block"),
+        )
+        .arg(
+          Arg::with_name("expected_height")
+            .long("expected-height")
+            .takes_value(true)
+            .required(true)
+            .help("The current height of the ledger; the block is appended after it"),
+        )
+        .arg(receipt_out_arg()),
+    )
+    .subcommand(
+      SubCommand::with_name("read")
+        .about("Reads the tail of a ledger, which the endorsers attest together with a fresh nonce")
+        .arg(handle_arg().required(true))
+        .arg(Arg::with_name("cached").long("cached").help(
+          "Reads the latest entry from the ledger store, which is not attested to be the tail",
+        ))
+        .arg(
+          Arg::with_name("out")
+            .long("out")
+            .takes_value(true)
+            .help("A file to write the block to rather than printing it"),
+        )
+        .arg(receipt_out_arg()),
+    )
+    .subcommand(
+      SubCommand::with_name("history")
+        .about("Reads the entries of a ledger in a range of heights")
+        .arg(handle_arg().required(true))
+        .arg(
+          Arg::with_name("from")
+            .long("from")
+            .takes_value(true)
+            .default_value("0"),
+        )
+        .arg(
+          Arg::with_name("to")
+            .long("to")
+            .takes_value(true)
+            .required(true)
+            .help("The last height of the range, inclusive"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("verify-receipt")
+        .about("Verifies a receipt file written with --receipt-out")
+        .arg(
+          Arg::with_name("file")
+            .long("file")
+            .takes_value(true)
+            .required(true),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("view-history")
+        .about("Lists the views of the view ledger, verifying every view change"),
+    )
+    .subcommand(
+      SubCommand::with_name("export")
+        .about("Exports a ledger up to its attested tail into a bundle for an offline audit")
+        .arg(handle_arg().required(true))
+        .arg(
+          Arg::with_name("out")
+            .long("out")
+            .takes_value(true)
+            .required(true)
+            .help("The file to write the bundle to"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("audit")
+        .about("Audits a bundle written by export, without the coordinator")
+        .arg(
+          Arg::with_name("bundle")
+            .required(true)
+            .help("The file holding the bundle"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("decode")
+        .about("Decodes receipts or a signing payload field by field, without the coordinator")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+          SubCommand::with_name("receipt")
+            .about("Decodes receipts and checks each signature on its own")
+            .arg(
+              Arg::with_name("input")
+                .required(true)
+                .help("A receipt file, a file of receipts, or receipts in hex"),
+            )
+            .arg(handle_arg())
+            .arg(
+              Arg::with_name("nonce")
+                .long("nonce")
+                .takes_value(true)
+                .help("The nonce of a read, in hex"),
+            )
+            .arg(
+              Arg::with_name("view_ledger")
+                .long("view-ledger")
+                .conflicts_with("handle")
+                .help("The receipts are of an entry of the view ledger"),
+            )
+            .arg(
+              Arg::with_name("endorsers")
+                .long("endorsers")
+                .takes_value(true)
+                .help("The public keys of the endorsers, in hex and separated by commas"),
+            ),
+        )
+        .subcommand(
+          SubCommand::with_name("payload")
+            .about("Decodes a payload that endorsers sign, as printed by decode receipt")
+            .arg(
+              Arg::with_name("input")
+                .required(true)
+                .help("A file holding the payload, or the payload in hex"),
+            )
+            .arg(
+              Arg::with_name("kind")
+                .long("kind")
+                .takes_value(true)
+                .required(true)
+                .possible_values(PAYLOAD_KINDS),
+            )
+            .arg(
+              Arg::with_name("expected")
+                .long("expected")
+                .takes_value(true)
+                .help("The expected payload, in a file or in hex, to diff against"),
+            )
+            .arg(handle_arg()),
+        ),
+    )
+}
+
+/// the value of a global flag, which may be given before or after any subcommand; the innermost
+/// one wins
+fn global<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+  let inner = match matches.subcommand() {
+    (_, Some(args)) => global(args, name),
+    _ => None,
+  };
+  inner.or_else(|| matches.value_of(name))
+}
+
+/// whether a global switch is given, before or after any subcommand
+fn is_global(matches: &ArgMatches, name: &str) -> bool {
+  matches.is_present(name)
+    || matches!(matches.subcommand(), (_, Some(args)) if is_global(args, name))
+}
+
+async fn run(matches: &ArgMatches<'_>) -> Result<Value, CliError> {
+  let (command, args) = match matches.subcommand() {
+    (command, Some(args)) => (command, args),
+    _ => return Err(CliError::InvalidArgument("missing command".to_string())),
+  };
+  let coordinator = global(matches, "coordinator").unwrap_or(DEFAULT_COORDINATOR);
+  let state_path = global(matches, "state").unwrap_or(DEFAULT_STATE_FILE);
+  let group_identity = match global(matches, "group_identity") {
+    Some(group_identity) => Some(NimbleDigest::from_str(group_identity).map_err(|_e| {
+      CliError::InvalidArgument("--group-identity must be a hex digest".to_string())
+    })?),
+    None => None,
+  };
+
+  // an audit and a decode are offline, so they need no coordinator
+  match command {
+    "audit" => return audit(args, Path::new(state_path), group_identity),
+    "decode" => return decode(args, Path::new(state_path), group_identity),
+    _ => {},
+  }
+
+  let mut tls = TlsOptions::default().with_insecure(is_global(matches, "insecure"));
+  if let Some(path) = global(matches, "ca_cert") {
+    tls = tls.with_ca_bundle(read_file(path)?);
+  }
+  match (
+    global(matches, "client_cert"),
+    global(matches, "client_key"),
+  ) {
+    (Some(cert), Some(key)) => tls = tls.with_identity(read_file(cert)?, read_file(key)?),
+    (None, None) => {},
+    _ => {
+      return Err(CliError::InvalidArgument(
+        "--client-cert and --client-key go together".to_string(),
+      ))
+    },
+  }
+  let mut client =
+    Client::connect(coordinator, &tls, Path::new(state_path), group_identity).await?;
+  let res = match command {
+    "create" => create(&mut client, args).await,
+    "append" => append(&mut client, args).await,
+    "read" => read(&mut client, args).await,
+    "history" => history(&mut client, args).await,
+    "verify-receipt" => verify_receipt(&mut client, args).await,
+    "view-history" => view_history(&mut client).await,
+    "export" => export(&mut client, args).await,
+    _ => Err(CliError::InvalidArgument(format!(
+      "unknown command {}",
+      command
+    ))),
+  };
+  // the view changes that the command verified stay trusted even if the command failed
+  client.save()?;
+  res
+}
+
+#[tokio::main]
+async fn main() {
+  let matches = app().get_matches();
+  let json = is_global(&matches, "json");
+  match run(&matches).await {
+    Ok(output) => print_output(&output, json),
+    Err(error) => {
+      eprintln!("error: {}", error);
+      std::process::exit(error.exit_code());
+    },
+  }
+}
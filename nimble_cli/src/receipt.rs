@@ -0,0 +1,216 @@
+//! The receipt files that `--receipt-out` writes and `verify-receipt` checks. A file holds the
+//! receipts of an entry together with what is needed to verify them later without the block, so
+//! it can be archived or handed to a third party.
+use ledger::{
+  compute_aggregated_block_hash, CustomSerde, CustomSerdeError, NimbleDigest, Nonce, Nonces,
+};
+use std::convert::{TryFrom, TryInto};
+use verifier::{VerifierError, VerifierState};
+
+/// what the receipts of a file attest
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Attests {
+  /// the entry with `block_hash`, the aggregated hash of its block and nonces
+  Entry { block_hash: NimbleDigest },
+  /// that the entry whose block hashes to `hash_block` was the tail when the endorsers saw
+  /// `nonce`, which they signed or which is among the `nonces` of the entry
+  Read {
+    hash_block: NimbleDigest,
+    nonces: Vec<u8>,
+    nonce: Nonce,
+  },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReceiptFile {
+  pub handle: Vec<u8>,
+  pub height: usize,
+  pub attests: Attests,
+  pub receipts: Vec<u8>,
+}
+
+impl ReceiptFile {
+  /// verifies the receipts against `state`; returns the hash of the tail that they endorse
+  pub fn verify(&self, state: &VerifierState) -> Result<NimbleDigest, VerifierError> {
+    match &self.attests {
+      Attests::Entry { block_hash } => {
+        state.verify_append(&self.handle, block_hash, self.height, None, &self.receipts)
+      },
+      Attests::Read {
+        hash_block,
+        nonces,
+        nonce,
+      } => {
+        let block_hash = compute_aggregated_block_hash(
+          &hash_block.to_bytes(),
+          &NimbleDigest::digest(nonces).to_bytes(),
+        );
+        match state.verify_read_latest(
+          &self.handle,
+          nonce,
+          &block_hash,
+          self.height,
+          &self.receipts,
+        ) {
+          // the endorsers signed the tail before the nonce arrived, which then joined its nonces
+          Err(VerifierError::NonceMismatch) => {
+            let nonces = Nonces::from_bytes(nonces).map_err(|_e| VerifierError::NonceMismatch)?;
+            if !nonces.contains(nonce) {
+              return Err(VerifierError::NonceMismatch);
+            }
+            state.verify_append(&self.handle, &block_hash, self.height, None, &self.receipts)
+          },
+          res => res,
+        }
+      },
+    }
+  }
+}
+
+/// Version tag prefixed to the encoding of `ReceiptFile`
+const RECEIPT_FILE_ENCODING_VERSION: u8 = 1;
+const ATTESTS_ENTRY: u8 = 0;
+const ATTESTS_READ: u8 = 1;
+
+fn read_slice<'a>(
+  bytes: &'a [u8],
+  pos: &mut usize,
+  len: usize,
+) -> Result<&'a [u8], CustomSerdeError> {
+  let end = pos
+    .checked_add(len)
+    .ok_or(CustomSerdeError::IncorrectLength)?;
+  if end > bytes.len() {
+    return Err(CustomSerdeError::IncorrectLength);
+  }
+  let slice = &bytes[*pos..end];
+  *pos = end;
+  Ok(slice)
+}
+
+/// reads a u32 LE length followed by that many bytes
+fn read_prefixed<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a [u8], CustomSerdeError> {
+  let len = read_slice(bytes, pos, std::mem::size_of::<u32>())?;
+  let len = u32::from_le_bytes(
+    len
+      .try_into()
+      .map_err(|_| CustomSerdeError::IncorrectLength)?,
+  );
+  read_slice(bytes, pos, len as usize)
+}
+
+fn write_prefixed(bytes: &mut Vec<u8>, data: &[u8]) {
+  bytes.extend(&(data.len() as u32).to_le_bytes());
+  bytes.extend(data);
+}
+
+/// The layout is a version byte, the handle, the height as u64 LE, a tag byte followed by the
+/// block hash for an entry or by the hash of the block, the nonce and the nonces for a read, and
+/// the receipts, with the handle, the nonces and the receipts prefixed by their length as u32 LE.
+impl CustomSerde for ReceiptFile {
+  fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = vec![RECEIPT_FILE_ENCODING_VERSION];
+    write_prefixed(&mut bytes, &self.handle);
+    bytes.extend(&(self.height as u64).to_le_bytes());
+    match &self.attests {
+      Attests::Entry { block_hash } => {
+        bytes.push(ATTESTS_ENTRY);
+        bytes.extend(&block_hash.to_bytes());
+      },
+      Attests::Read {
+        hash_block,
+        nonces,
+        nonce,
+      } => {
+        bytes.push(ATTESTS_READ);
+        bytes.extend(&hash_block.to_bytes());
+        bytes.extend(&nonce.to_bytes());
+        write_prefixed(&mut bytes, nonces);
+      },
+    }
+    write_prefixed(&mut bytes, &self.receipts);
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, CustomSerdeError> {
+    let mut pos = 0;
+    if read_slice(bytes, &mut pos, 1)?[0] != RECEIPT_FILE_ENCODING_VERSION {
+      return Err(CustomSerdeError::UnsupportedVersion);
+    }
+    let handle = read_prefixed(bytes, &mut pos)?.to_vec();
+    let height = u64::from_le_bytes(
+      read_slice(bytes, &mut pos, std::mem::size_of::<u64>())?
+        .try_into()
+        .map_err(|_| CustomSerdeError::IncorrectLength)?,
+    );
+    let height = usize::try_from(height).map_err(|_| CustomSerdeError::IncorrectLength)?;
+    let attests = match read_slice(bytes, &mut pos, 1)?[0] {
+      ATTESTS_ENTRY => Attests::Entry {
+        block_hash: NimbleDigest::from_bytes(read_slice(
+          bytes,
+          &mut pos,
+          NimbleDigest::num_bytes(),
+        )?)?,
+      },
+      ATTESTS_READ => Attests::Read {
+        hash_block: NimbleDigest::from_bytes(read_slice(
+          bytes,
+          &mut pos,
+          NimbleDigest::num_bytes(),
+        )?)?,
+        nonce: Nonce::from_bytes(read_slice(bytes, &mut pos, Nonce::num_bytes())?)?,
+        nonces: read_prefixed(bytes, &mut pos)?.to_vec(),
+      },
+      _ => return Err(CustomSerdeError::InternalError),
+    };
+    let receipts = read_prefixed(bytes, &mut pos)?.to_vec();
+    if pos != bytes.len() {
+      return Err(CustomSerdeError::IncorrectLength);
+    }
+    Ok(ReceiptFile {
+      handle,
+      height,
+      attests,
+      receipts,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_receipt_file_encoding() {
+    let entry = ReceiptFile {
+      handle: b"handle".to_vec(),
+      height: 3,
+      attests: Attests::Entry {
+        block_hash: NimbleDigest::digest(b"block"),
+      },
+      receipts: vec![7u8; 10],
+    };
+    let nonce = Nonce::new();
+    let read = ReceiptFile {
+      attests: Attests::Read {
+        hash_block: NimbleDigest::digest(b"block"),
+        nonces: nonce.to_bytes(),
+        nonce,
+      },
+      ..entry.clone()
+    };
+    for file in &[entry, read] {
+      let bytes = file.to_bytes();
+      assert_eq!(ReceiptFile::from_bytes(&bytes).as_ref(), Ok(file));
+      for len in 0..bytes.len() {
+        assert!(ReceiptFile::from_bytes(&bytes[..len]).is_err());
+      }
+      let mut trailing = bytes.clone();
+      trailing.push(0);
+      assert_eq!(
+        ReceiptFile::from_bytes(&trailing),
+        Err(CustomSerdeError::IncorrectLength)
+      );
+    }
+  }
+}
@@ -0,0 +1,269 @@
+//! Runs nimble-cli against a coordinator and endorsers that the test launches from COORDINATOR_CMD
+//! and ENDORSER_CMD, so it is ignored by default, like the tests of the coordinator.
+use serde_json::Value;
+use std::{
+  ffi::OsString,
+  io::{BufRead, BufReader, Read, Write},
+  net::TcpStream,
+  path::{Path, PathBuf},
+  process::{Child, Command, Output, Stdio},
+  thread,
+  time::Duration,
+};
+
+struct BoxChild {
+  pub child: Child,
+}
+
+impl Drop for BoxChild {
+  fn drop(&mut self) {
+    self.child.kill().expect("failed to kill a child process");
+  }
+}
+
+fn env_cmd(name: &str) -> OsString {
+  match std::env::var_os(name) {
+    None => panic!("The {} environment variable is not specified", name),
+    Some(x) => x,
+  }
+}
+
+fn launch_endorser(cmd: &OsString, port: u16) -> BoxChild {
+  let mut endorser = BoxChild {
+    child: Command::new(cmd)
+      .args(&["-p", &port.to_string()])
+      .stdout(Stdio::piped())
+      .spawn()
+      .expect("endorser failed to start"),
+  };
+
+  let mut buf_reader = BufReader::new(endorser.child.stdout.take().unwrap());
+  let mut endorser_output = String::new();
+  while let Ok(buflen) = buf_reader.read_line(&mut endorser_output) {
+    if buflen == 0 {
+      break;
+    }
+    if endorser_output.contains("listening on") {
+      break;
+    }
+  }
+
+  endorser
+}
+
+struct Cli {
+  coordinator: String,
+  state: PathBuf,
+}
+
+impl Cli {
+  fn run(&self, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_nimble-cli"))
+      .arg("--coordinator")
+      .arg(&self.coordinator)
+      .arg("--state")
+      .arg(&self.state)
+      .arg("--json")
+      .args(args)
+      .output()
+      .expect("nimble-cli failed to start")
+  }
+
+  /// runs a command that must succeed, and returns its output
+  fn ok(&self, args: &[&str]) -> Value {
+    let output = self.run(args);
+    assert!(
+      output.status.success(),
+      "nimble-cli {:?} failed: {}",
+      args,
+      String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+  }
+
+  /// runs a command that must fail, and returns its exit code
+  fn fails(&self, args: &[&str]) -> i32 {
+    let output = self.run(args);
+    assert!(!output.status.success(), "nimble-cli {:?} succeeded", args);
+    output.status.code().unwrap()
+  }
+}
+
+/// asks the control service of the coordinator to add the endorser at `uri` to its view
+fn add_endorser(ctrl_port: u16, uri: &str) {
+  let mut stream = TcpStream::connect(("::1", ctrl_port)).unwrap();
+  write!(
+    stream,
+    "PUT /endorsers/{} HTTP/1.1\r\nHost: [::1]:{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    base64_url::encode(uri),
+    ctrl_port
+  )
+  .unwrap();
+  let mut resp = String::new();
+  stream.read_to_string(&mut resp).unwrap();
+  assert!(resp.starts_with("HTTP/1.1 200"), "{}", resp);
+}
+
+fn write_block(dir: &Path, name: &str, block: &[u8]) -> String {
+  let path = dir.join(name);
+  std::fs::write(&path, block).unwrap();
+  path.to_str().unwrap().to_string()
+}
+
+#[test]
+#[ignore]
+fn test_cli() {
+  let endorser_cmd = env_cmd("ENDORSER_CMD");
+  let coordinator_cmd = env_cmd("COORDINATOR_CMD");
+  let _endorsers = vec![
+    launch_endorser(&endorser_cmd, 9290),
+    launch_endorser(&endorser_cmd, 9291),
+  ];
+  let _coordinator = BoxChild {
+    child: Command::new(&coordinator_cmd)
+      .args(&["-p", "9292", "-r", "9293", "-s", "memory"])
+      .args(&["-e", "http://[::1]:9290,http://[::1]:9291"])
+      .stdout(Stdio::null())
+      .spawn()
+      .expect("coordinator failed to start"),
+  };
+
+  let dir = std::env::temp_dir().join(format!("nimble-cli-test-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let cli = Cli {
+    coordinator: "http://[::1]:9292".to_string(),
+    state: dir.join("verifier-state"),
+  };
+
+  // the first command trusts the group of the coordinator once it serves
+  let mut views = None;
+  for _ in 0..50 {
+    let output = cli.run(&["view-history"]);
+    if output.status.success() {
+      views = Some(serde_json::from_slice::<Value>(&output.stdout).unwrap());
+      break;
+    }
+    thread::sleep(Duration::from_millis(200));
+  }
+  let views = views.expect("the coordinator did not start");
+  let group_identity = views["group_identity"].as_str().unwrap().to_string();
+  assert_eq!(views["views"].as_array().unwrap().len(), 1);
+  assert_eq!(views["views"][0]["endorsers"].as_array().unwrap().len(), 2);
+
+  // create, append, and read a ledger
+  let receipt0 = dir.join("receipt0").to_str().unwrap().to_string();
+  let created = cli.ok(&[
+    "create",
+    "--app-bytes",
+    "app",
+    "--metadata",
+    "owner=alice",
+    "--receipt-out",
+    &receipt0,
+  ]);
+  let handle = created["handle"].as_str().unwrap().to_string();
+  assert_eq!(created["height"], 0);
+
+  let receipt1 = dir.join("receipt1").to_str().unwrap().to_string();
+  let block1 = write_block(&dir, "block1", b"first block");
+  let appended = cli.ok(&[
+    "append",
+    "--handle",
+    &handle,
+    "--file",
+    &block1,
+    "--expected-height",
+    "0",
+    "--receipt-out",
+    &receipt1,
+  ]);
+  assert_eq!(appended["height"], 1);
+  let block2 = write_block(&dir, "block2", b"second block");
+  cli.ok(&[
+    "append",
+    "--handle",
+    &handle,
+    "--file",
+    &block2,
+    "--expected-height",
+    "1",
+  ]);
+  // a conflicting append is refused by the coordinator, which is not a failed verification
+  assert_eq!(
+    cli.fails(&[
+      "append",
+      "--handle",
+      &handle,
+      "--file",
+      &block2,
+      "--expected-height",
+      "1",
+    ]),
+    1
+  );
+
+  let read = cli.ok(&["read", "--handle", &handle]);
+  assert_eq!(read["height"], 2);
+  assert_eq!(read["attested"], true);
+  assert_eq!(read["block"], hex::encode(b"second block"));
+  let cached = cli.ok(&["read", "--handle", &handle, "--cached"]);
+  assert_eq!(cached["height"], 2);
+  assert_eq!(cached["attested"], false);
+
+  let history = cli.ok(&["history", "--handle", &handle, "--from", "1", "--to", "2"]);
+  let entries = history["entries"].as_array().unwrap();
+  assert_eq!(entries.len(), 2);
+  assert_eq!(entries[0]["index"], 1);
+  assert_eq!(entries[0]["block"], hex::encode(b"first block"));
+  assert_eq!(entries[1]["block"], hex::encode(b"second block"));
+  assert_eq!(
+    cli.ok(&["history", "--handle", &handle, "--to", "2"])["entries"]
+      .as_array()
+      .unwrap()
+      .len(),
+    3
+  );
+
+  // receipt files verify, and tampered ones fail verification
+  for receipt in &[&receipt0, &receipt1] {
+    cli.ok(&["verify-receipt", "--file", receipt]);
+  }
+  let mut tampered = std::fs::read(&receipt1).unwrap();
+  let last = tampered.len() - 1;
+  tampered[last] ^= 1;
+  let tampered_path = write_block(&dir, "tampered", &tampered);
+  assert_eq!(cli.fails(&["verify-receipt", "--file", &tampered_path]), 2);
+
+  // a state is tied to its group
+  let other = Cli {
+    coordinator: cli.coordinator.clone(),
+    state: dir.join("other-state"),
+  };
+  other.ok(&["view-history", "--group-identity", &group_identity]);
+  let wrong_group = hex::encode([7u8; 32]);
+  assert_eq!(
+    other.fails(&["view-history", "--group-identity", &wrong_group]),
+    1
+  );
+
+  // the CLI follows a view change when it sees receipts of the next view
+  let _endorser = launch_endorser(&endorser_cmd, 9294);
+  add_endorser(9293, "http://[::1]:9294");
+  let block3 = write_block(&dir, "block3", b"third block");
+  cli.ok(&[
+    "append",
+    "--handle",
+    &handle,
+    "--file",
+    &block3,
+    "--expected-height",
+    "2",
+  ]);
+  let views = cli.ok(&["view-history"]);
+  let views = views["views"].as_array().unwrap();
+  assert_eq!(views.len(), 2);
+  assert_eq!(views[1]["endorsers"].as_array().unwrap().len(), 3);
+  assert_eq!(cli.ok(&["read", "--handle", &handle])["height"], 3);
+
+  std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,92 @@
+use std::{fmt, time::Duration};
+use tonic::Status;
+use verifier::VerifierError;
+
+#[derive(Debug)]
+pub enum ClientError {
+  /// returned if the coordinator cannot be reached
+  Transport(tonic::transport::Error),
+  /// returned if the TLS options of the client cannot be used
+  Tls(String),
+  /// returned if the coordinator fails the request, after the retries if the failure is transient
+  Rpc(Status),
+  /// returned if an append does not apply because the ledger is not at the expected height
+  ConditionFailed { current_height: u64 },
+  /// returned if a response of the coordinator is not well formed
+  MalformedResponse(&'static str),
+  /// returned if the receipts of a response do not verify
+  Verification(VerifierError),
+  /// returned if a read attests a tail of the ledger at `height` that does not extend the tail at
+  /// `verified_height` that the client verified before, e.g., a ledger that was rolled back
+  RollbackDetected { verified_height: u64, height: u64 },
+  /// returned if the coordinator held the ledgers of a snapshot read for longer than the client
+  /// tolerates while it read their tails
+  SnapshotWindowExceeded {
+    window: Duration,
+    max_window: Duration,
+  },
+}
+
+impl ClientError {
+  /// whether the coordinator or the endorsers misbehaved, rather than failed; applications alarm
+  /// on these, since retrying does not help. A heartbeat that is not fresh is not one of them: it
+  /// shows that the coordinator stopped, or that the clocks are further apart than tolerated; nor
+  /// is a view of another hash algorithm, which shows that the client was built for another
+  /// deployment
+  pub fn is_integrity_violation(&self) -> bool {
+    match self {
+      ClientError::Verification(
+        VerifierError::StaleHeartbeat { .. }
+        | VerifierError::FutureHeartbeat { .. }
+        | VerifierError::HashAlgorithmMismatch,
+      ) => false,
+      ClientError::MalformedResponse(_)
+      | ClientError::Verification(_)
+      | ClientError::RollbackDetected { .. } => true,
+      _ => false,
+    }
+  }
+}
+
+impl fmt::Display for ClientError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ClientError::Transport(error) => write!(f, "cannot connect to the coordinator: {}", error),
+      ClientError::Tls(message) => write!(f, "TLS: {}", message),
+      ClientError::Rpc(status) => write!(
+        f,
+        "the coordinator failed the request: {:?}: {}",
+        status.code(),
+        status.message()
+      ),
+      ClientError::ConditionFailed { current_height } => {
+        write!(f, "the ledger is at height {}", current_height)
+      },
+      ClientError::MalformedResponse(what) => {
+        write!(f, "the coordinator returned a malformed response: {}", what)
+      },
+      ClientError::Verification(error) => write!(f, "verification failed: {}", error),
+      ClientError::RollbackDetected {
+        verified_height,
+        height,
+      } => write!(
+        f,
+        "the tail at height {} does not extend the tail at height {} that was verified before",
+        height, verified_height
+      ),
+      ClientError::SnapshotWindowExceeded { window, max_window } => write!(
+        f,
+        "the snapshot was read over {:?}, longer than the {:?} tolerated",
+        window, max_window
+      ),
+    }
+  }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<VerifierError> for ClientError {
+  fn from(error: VerifierError) -> Self {
+    ClientError::Verification(error)
+  }
+}
@@ -0,0 +1,1753 @@
+//! An asynchronous client of the coordinator's client service for applications that embed Nimble.
+//! Every call verifies the receipts of the response against a `VerifierState` before it returns,
+//! and follows the view changes of the coordinator as their receipts show up.
+//!
+//! Calls that fail transiently are retried. The attempts of a call carry the same request ID, and
+//! a write is idempotent in its arguments, so a retried write that already applied returns the
+//! entry that the first attempt wrote rather than writing it twice.
+//!
+//! The client records the latest tail of each ledger that a read attested, and fails a later read
+//! whose tail does not extend it, which a rollback of the ledger would show up as.
+//!
+//! A watch streams the entries of a ledger as they commit, without receipts; an application
+//! attests the entries that it acts on with `NimbleClient::attest`.
+//!
+//! The coordinator can append heartbeats to a liveness ledger with the time of its clock; an
+//! application checks that they are recent with `NimbleClient::assert_freshness`, which compares
+//! the time with the clock of the client.
+//!
+//! A snapshot read attests the tails of several ledgers as of one moment, during which the
+//! coordinator holds back the appends to all of them; the client checks the digest of the
+//! snapshot against the tails and that the coordinator read them within a bounded window.
+//!
+//! A client connects to an `https` URI over TLS with `NimbleClient::connect_with_tls`.
+mod errors;
+pub mod tls;
+
+pub use errors::ClientError;
+pub use ledger::hadoop::{handle_for, LedgerKind};
+pub use tls::TlsOptions;
+pub use verifier::{VerifierError, VerifierState};
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod coordinator_proto {
+  tonic::include_proto!("coordinator_proto");
+}
+
+use coordinator_proto::{
+  call_client::CallClient, AppendConditionFailed, AppendReq, NewLedgerReq, ReadByIndexReq,
+  ReadConsistency, ReadLatestReq, ReadViewByIndexReq, SnapshotReadReq, WatchReq, WatchResp,
+};
+use ledger::{
+  compute_aggregated_block_hash, compute_snapshot_digest, parse_heartbeat_block, CustomSerde,
+  MetaBlock, NimbleDigest, NimbleHashTrait, Nonce, Nonces,
+};
+use prost::Message;
+use std::{
+  convert::TryFrom,
+  future::Future,
+  sync::{Arc, PoisonError, RwLock},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{
+  metadata::MetadataValue, transport::Channel, Code, Request, Response, Status, Streaming,
+};
+
+/// the metadata key that carries the ID of a request, which the coordinator logs and records
+pub const REQUEST_ID_KEY: &str = "x-request-id";
+const DEFAULT_MAX_ATTEMPTS: usize = 3;
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100); // doubles with every retry
+const WATCH_BUFFER: usize = 16; // the entries of a watch that the application has yet to take
+const WATCH_IDLE_TIMEOUT: Duration = Duration::from_secs(30); // a silent watch is resumed after it
+/// how far apart the clocks of the client and the coordinator may be unless the application says
+const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(1);
+/// how long the coordinator may hold the ledgers of a snapshot read unless the application says
+const DEFAULT_MAX_SNAPSHOT_WINDOW: Duration = Duration::from_secs(2);
+
+/// an entry of a ledger whose receipts verified
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LedgerEntry {
+  pub block: Vec<u8>,
+  pub height: usize,
+  /// the hash of the metablock of the entry, which the entry after it extends
+  pub hash: NimbleDigest,
+}
+
+/// the tails of several ledgers as of one moment, whose receipts verified
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Snapshot {
+  /// the tail of each ledger, in the order of the handles of the read
+  pub entries: Vec<LedgerEntry>,
+  /// the digest of the snapshot, which the client computed from the tails
+  pub digest: NimbleDigest,
+  /// how long the coordinator held the ledgers while it read their tails, by its clock
+  pub window: Duration,
+}
+
+/// an entry that a watch of a ledger pushed; nothing about it is verified until it is attested
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WatchedEntry {
+  pub height: usize,
+  /// the aggregated hash of the block and nonces of the entry
+  pub block_hash: NimbleDigest,
+  /// the block, if the watch asked for the blocks and the contents of the block were not purged
+  pub block: Option<Vec<u8>>,
+}
+
+/// whether a call that failed with `status` may succeed if it is made again
+fn is_transient(status: &Status) -> bool {
+  matches!(
+    status.code(),
+    Code::Unavailable | Code::DeadlineExceeded | Code::Aborted | Code::ResourceExhausted
+  )
+}
+
+/// the height of the ledger if an append failed because it is not at the expected height
+fn condition_failed(status: &Status) -> Option<u64> {
+  if status.code() != Code::FailedPrecondition || status.details().is_empty() {
+    return None;
+  }
+  AppendConditionFailed::decode(status.details())
+    .ok()
+    .map(|details| details.current_height)
+}
+
+/// the entry in `resp`, which must be at `next_height`
+fn watched_entry(
+  resp: WatchResp,
+  next_height: usize,
+  include_blocks: bool,
+) -> Result<WatchedEntry, ClientError> {
+  if to_height(resp.height)? != next_height {
+    return Err(ClientError::MalformedResponse(
+      "the watch skipped or repeated an entry",
+    ));
+  }
+  let block_hash = NimbleDigest::from_bytes(&resp.block_hash)
+    .map_err(|_e| ClientError::MalformedResponse("a block hash is not a digest"))?;
+  let block = if include_blocks {
+    let hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(&resp.block).to_bytes(),
+      &NimbleDigest::digest(&resp.nonces).to_bytes(),
+    );
+    if hash == block_hash {
+      Some(resp.block)
+    } else if resp.block.is_empty() {
+      // the contents of the block were purged
+      None
+    } else {
+      return Err(ClientError::MalformedResponse(
+        "a block does not match its hash",
+      ));
+    }
+  } else {
+    None
+  };
+  Ok(WatchedEntry {
+    height: next_height,
+    block_hash,
+    block,
+  })
+}
+
+fn to_height(height: u64) -> Result<usize, ClientError> {
+  usize::try_from(height).map_err(|_e| ClientError::MalformedResponse("the height overflows"))
+}
+
+/// milliseconds since the Unix epoch by the clock of the client
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+fn nonces_contain(nonces: &[u8], nonce: &Nonce) -> bool {
+  match Nonces::from_bytes(nonces) {
+    Ok(nonces) => nonces.contains(nonce),
+    Err(_) => false,
+  }
+}
+
+#[derive(Clone)]
+pub struct NimbleClient {
+  conn: CallClient<Channel>,
+  state: Arc<RwLock<VerifierState>>,
+  max_attempts: usize,
+  retry_backoff: Duration,
+  check_gaps: bool,
+  max_clock_skew: Duration,
+  max_snapshot_window: Duration,
+}
+
+impl NimbleClient {
+  /// connects to the coordinator at `uri`, whose responses are verified against `verifier_state`
+  pub async fn connect(uri: &str, verifier_state: VerifierState) -> Result<Self, ClientError> {
+    Self::connect_with_tls(uri, verifier_state, &TlsOptions::default()).await
+  }
+
+  /// connects like `connect`, with `tls` if the scheme of `uri` is `https`
+  pub async fn connect_with_tls(
+    uri: &str,
+    verifier_state: VerifierState,
+    tls: &TlsOptions,
+  ) -> Result<Self, ClientError> {
+    let channel = tls::connect(uri, tls).await?;
+    Ok(NimbleClient {
+      conn: CallClient::new(channel),
+      state: Arc::new(RwLock::new(verifier_state)),
+      max_attempts: DEFAULT_MAX_ATTEMPTS,
+      retry_backoff: DEFAULT_RETRY_BACKOFF,
+      check_gaps: false,
+      max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+      max_snapshot_window: DEFAULT_MAX_SNAPSHOT_WINDOW,
+    })
+  }
+
+  /// makes up to `max_attempts` attempts at a call that fails transiently, waiting `backoff`
+  /// before the first retry and twice as long before each one after it
+  pub fn with_retries(mut self, max_attempts: usize, backoff: Duration) -> Self {
+    self.max_attempts = max_attempts.max(1);
+    self.retry_backoff = backoff;
+    self
+  }
+
+  /// makes a read whose tail is more than one entry past the tail that the client verified before
+  /// read the entries in between, and fail unless they link the two tails; without it, only a
+  /// tail at most one entry past is checked to extend it
+  pub fn with_gap_checking(mut self, check_gaps: bool) -> Self {
+    self.check_gaps = check_gaps;
+    self
+  }
+
+  /// tolerates clocks of the client and the coordinator that are up to `max_clock_skew` apart when
+  /// it checks the freshness of heartbeats and the windows of snapshots
+  pub fn with_max_clock_skew(mut self, max_clock_skew: Duration) -> Self {
+    self.max_clock_skew = max_clock_skew;
+    self
+  }
+
+  /// fails a snapshot read whose ledgers the coordinator held for longer than
+  /// `max_snapshot_window`, by its clock, while it read their tails
+  pub fn with_max_snapshot_window(mut self, max_snapshot_window: Duration) -> Self {
+    self.max_snapshot_window = max_snapshot_window;
+    self
+  }
+
+  /// the verifier state, which follows the view changes and the tails of the ledgers that the
+  /// client verified; applications persist it with `CustomSerde::to_bytes` to connect with it
+  /// again later
+  pub fn get_verifier_state(&self) -> VerifierState {
+    self
+      .state
+      .read()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone()
+  }
+
+  /// makes `rpc` with `message` until it succeeds, fails with a status that is not transient, or
+  /// runs out of attempts; all attempts carry the same request ID. On failure, returns the last
+  /// status and the number of attempts made.
+  async fn call<M, T, F, Fut>(&self, message: M, rpc: F) -> Result<T, (Status, usize)>
+  where
+    M: Clone,
+    F: Fn(CallClient<Channel>, Request<M>) -> Fut,
+    Fut: Future<Output = Result<Response<T>, Status>>,
+  {
+    let request_id = MetadataValue::try_from(uuid::Uuid::new_v4().to_string().as_str()).ok();
+    let mut backoff = self.retry_backoff;
+    let mut attempts = 0;
+    loop {
+      attempts += 1;
+      let mut request = Request::new(message.clone());
+      if let Some(request_id) = &request_id {
+        request
+          .metadata_mut()
+          .insert(REQUEST_ID_KEY, request_id.clone());
+      }
+      match rpc(self.conn.clone(), request).await {
+        Ok(resp) => return Ok(resp.into_inner()),
+        Err(status) if is_transient(&status) && attempts < self.max_attempts => {
+          tokio::time::sleep(backoff).await;
+          backoff *= 2;
+        },
+        Err(status) => return Err((status, attempts)),
+      }
+    }
+  }
+
+  /// applies the entries of the view ledger after the current view until the state reaches
+  /// `view` or the tail of the view ledger
+  async fn catch_up(&self, view: &NimbleDigest) -> Result<(), ClientError> {
+    let mut state = self.get_verifier_state();
+    let mut entries = Vec::new();
+    while state.current_view() != *view {
+      let index = state.current_view_index() + 1;
+      let req = ReadViewByIndexReq {
+        index: index as u64,
+      };
+      let res = self
+        .call(req, |mut conn, request| async move {
+          conn.read_view_by_index(request).await
+        })
+        .await;
+      let resp = match res {
+        Ok(resp) => resp,
+        Err((status, _attempts)) if status.code() == Code::OutOfRange => break,
+        Err((status, _attempts)) => return Err(ClientError::Rpc(status)),
+      };
+      state.apply_view_change(&resp.block, &resp.receipts)?;
+      entries.push((index, resp));
+    }
+
+    // calls that run at the same time may catch up too, or record tails; the state only gets the
+    // entries that it does not have yet
+    let mut current = self.state.write().unwrap_or_else(PoisonError::into_inner);
+    for (index, resp) in entries {
+      if index > current.current_view_index() {
+        current.apply_view_change(&resp.block, &resp.receipts)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// runs `verify` against the verifier state; if the receipts are from a view that the state
+  /// does not know yet, applies the view changes up to it and runs `verify` again
+  async fn verify<T>(
+    &self,
+    verify: impl Fn(&VerifierState) -> Result<T, VerifierError>,
+  ) -> Result<T, ClientError> {
+    let res = verify(&self.get_verifier_state());
+    match res {
+      Err(VerifierError::StaleVerifier { view, .. }) => {
+        self.catch_up(&view).await?;
+        Ok(verify(&self.get_verifier_state())?)
+      },
+      res => Ok(res?),
+    }
+  }
+
+  /// creates ledger `handle` with the genesis `block` and immutable `metadata`; returns the hash
+  /// of the genesis entry
+  pub async fn new_ledger(
+    &self,
+    handle: &[u8],
+    block: &[u8],
+    metadata: &[u8],
+  ) -> Result<NimbleDigest, ClientError> {
+    let req = NewLedgerReq {
+      handle: handle.to_vec(),
+      block: block.to_vec(),
+      app_bytes: vec![],
+      nonce: vec![],
+      metadata: metadata.to_vec(),
+    };
+    // a create with the same block and metadata as an earlier attempt returns its receipts
+    let resp = self
+      .call(req, |mut conn, request| async move {
+        conn.new_ledger(request).await
+      })
+      .await
+      .map_err(|(status, _attempts)| ClientError::Rpc(status))?;
+    self
+      .verify(|state| state.verify_new_ledger(handle, block, metadata, &resp.receipts))
+      .await
+  }
+
+  /// appends `block` to ledger `handle`, which must be at `expected_height`
+  pub async fn append(
+    &self,
+    handle: &[u8],
+    block: &[u8],
+    expected_height: u64,
+  ) -> Result<LedgerEntry, ClientError> {
+    let req = AppendReq {
+      handle: handle.to_vec(),
+      block: block.to_vec(),
+      expected_height,
+      // names the append on every attempt, so that a retry after a lost response returns the
+      // entry of the append wherever the tail is by then
+      request_id: uuid::Uuid::new_v4().to_string(),
+    };
+    let res = self
+      .call(req, |mut conn, request| async move {
+        conn.append(request).await
+      })
+      .await;
+    let resp = match res {
+      Ok(resp) => resp,
+      Err((status, attempts)) => {
+        return match condition_failed(&status) {
+          // an earlier attempt may have appended the block before more entries followed it, and a
+          // coordinator that does not keep request IDs fails the condition of the retry
+          Some(current_height) if attempts > 1 && current_height > expected_height => {
+            let entry = self
+              .read_by_index(handle, to_height(expected_height + 1)?)
+              .await?;
+            if entry.block == block {
+              Ok(entry)
+            } else {
+              Err(ClientError::ConditionFailed { current_height })
+            }
+          },
+          Some(current_height) => Err(ClientError::ConditionFailed { current_height }),
+          None => Err(ClientError::Rpc(status)),
+        };
+      },
+    };
+
+    if Some(resp.height) != expected_height.checked_add(1) {
+      return Err(ClientError::MalformedResponse(
+        "the block is not right after the expected height",
+      ));
+    }
+    let height = to_height(resp.height)?;
+    let hash_nonces = NimbleDigest::from_bytes(&resp.hash_nonces)
+      .map_err(|_e| ClientError::MalformedResponse("hash_nonces is not a digest"))?;
+    let block_hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(block).to_bytes(),
+      &hash_nonces.to_bytes(),
+    );
+    let hash = self
+      .verify(|state| state.verify_append(handle, &block_hash, height, None, &resp.receipts))
+      .await?;
+    Ok(LedgerEntry {
+      block: block.to_vec(),
+      height,
+      hash,
+    })
+  }
+
+  /// reads the tail of ledger `handle`, which the endorsers attest together with a fresh nonce; the
+  /// tail must extend the latest one that the client verified before
+  pub async fn read_latest(&self, handle: &[u8]) -> Result<LedgerEntry, ClientError> {
+    // a read that runs at the same time may see a later tail, so the tail is checked against the
+    // one verified before the read started
+    let verified = self.get_verifier_state().get_ledger_tail(handle);
+    let nonce = Nonce::new();
+    let req = ReadLatestReq {
+      handle: handle.to_vec(),
+      nonce: nonce.to_bytes(),
+      consistency: ReadConsistency::Attested as i32,
+    };
+    let resp = self
+      .call(req, |mut conn, request| async move {
+        conn.read_latest(request).await
+      })
+      .await
+      .map_err(|(status, _attempts)| ClientError::Rpc(status))?;
+    if resp.consistency != ReadConsistency::Attested as i32 || resp.nonce != nonce.to_bytes() {
+      return Err(ClientError::MalformedResponse(
+        "the read is not attested with the nonce sent",
+      ));
+    }
+
+    let height = to_height(resp.height)?;
+    let block_hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(&resp.block).to_bytes(),
+      &NimbleDigest::digest(&resp.nonces).to_bytes(),
+    );
+    let hash = self
+      .verify(|state| {
+        match state.verify_read_latest(handle, &nonce, &block_hash, height, &resp.receipts) {
+          // the endorsers signed the tail before the nonce arrived, which then joined its nonces
+          Err(VerifierError::NonceMismatch) if nonces_contain(&resp.nonces, &nonce) => {
+            state.verify_append(handle, &block_hash, height, None, &resp.receipts)
+          },
+          res => res,
+        }
+      })
+      .await?;
+    self
+      .check_tail(handle, verified, height, &block_hash, &hash)
+      .await?;
+    Ok(LedgerEntry {
+      block: resp.block,
+      height,
+      hash,
+    })
+  }
+
+  /// reads the tails of the ledgers `handles` as of one moment: the coordinator holds back the
+  /// appends to all of them while it reads their tails, which the endorsers attest with one fresh
+  /// nonce. Each tail must extend the latest one of its ledger that the client verified before, as
+  /// with `read_latest`, the digest of the coordinator must match the tails, and the tails must
+  /// have been read within one window, while the read was in flight and no longer than the client
+  /// tolerates
+  pub async fn snapshot_read(&self, handles: &[Vec<u8>]) -> Result<Snapshot, ClientError> {
+    let verified = {
+      let state = self.get_verifier_state();
+      handles
+        .iter()
+        .map(|handle| state.get_ledger_tail(handle))
+        .collect::<Vec<_>>()
+    };
+    let nonce = Nonce::new();
+    let req = SnapshotReadReq {
+      handles: handles.to_vec(),
+      nonce: nonce.to_bytes(),
+    };
+    let sent_ms = now_ms();
+    let resp = self
+      .call(req, |mut conn, request| async move {
+        conn.snapshot_read(request).await
+      })
+      .await
+      .map_err(|(status, _attempts)| ClientError::Rpc(status))?;
+    let received_ms = now_ms();
+    if resp.nonce != nonce.to_bytes() {
+      return Err(ClientError::MalformedResponse(
+        "the snapshot is not attested with the nonce sent",
+      ));
+    }
+    if resp.entries.len() != handles.len()
+      || resp
+        .entries
+        .iter()
+        .zip(handles)
+        .any(|(entry, handle)| entry.handle != *handle)
+    {
+      return Err(ClientError::MalformedResponse(
+        "the snapshot does not hold the ledgers asked for",
+      ));
+    }
+
+    // the window is by the clock of the coordinator, which may be off by the skew tolerated
+    let skew_ms = self.max_clock_skew.as_millis() as u64;
+    if resp.locked_at > resp.released_at
+      || resp.locked_at.saturating_add(skew_ms) < sent_ms
+      || resp.released_at > received_ms.saturating_add(skew_ms)
+      || resp
+        .entries
+        .iter()
+        .any(|entry| entry.captured_at < resp.locked_at || entry.captured_at > resp.released_at)
+    {
+      return Err(ClientError::MalformedResponse(
+        "the tails were not read within the window of the snapshot",
+      ));
+    }
+    let window = Duration::from_millis(resp.released_at - resp.locked_at);
+    if window > self.max_snapshot_window {
+      return Err(ClientError::SnapshotWindowExceeded {
+        window,
+        max_window: self.max_snapshot_window,
+      });
+    }
+
+    let mut entries = Vec::with_capacity(handles.len());
+    let mut tails = Vec::with_capacity(handles.len());
+    for ((handle, entry), verified) in handles.iter().zip(resp.entries).zip(verified) {
+      let height = to_height(entry.height)?;
+      let block_hash = compute_aggregated_block_hash(
+        &NimbleDigest::digest(&entry.block).to_bytes(),
+        &NimbleDigest::digest(&entry.nonces).to_bytes(),
+      );
+      // no append lands on the ledgers of a snapshot, so the endorsers sign the nonce itself
+      let hash = self
+        .verify(|state| {
+          state.verify_read_latest(handle, &nonce, &block_hash, height, &entry.receipts)
+        })
+        .await?;
+      self
+        .check_tail(handle, verified, height, &block_hash, &hash)
+        .await?;
+      tails.push((NimbleDigest::digest(handle), hash, height));
+      entries.push(LedgerEntry {
+        block: entry.block,
+        height,
+        hash,
+      });
+    }
+    let digest = compute_snapshot_digest(&nonce, &tails);
+    if digest.to_bytes() != resp.digest {
+      return Err(ClientError::MalformedResponse(
+        "the digest of the snapshot does not match its tails",
+      ));
+    }
+    Ok(Snapshot {
+      entries,
+      digest,
+      window,
+    })
+  }
+
+  /// checks that the coordinator appended a heartbeat to the liveness ledger `handle` at most
+  /// `max_age` ago. The tail of the ledger is read as by `read_latest`, so it is the tail as of
+  /// now, and the time of its heartbeat, by the clock of the coordinator, is compared with the
+  /// clock of the client within the skew that the client tolerates. Returns the age of the
+  /// heartbeat; a stale heartbeat fails with `VerifierError::StaleHeartbeat`
+  pub async fn assert_freshness(
+    &self,
+    handle: &[u8],
+    max_age: Duration,
+  ) -> Result<Duration, ClientError> {
+    let tail = self.read_latest(handle).await?;
+    let (timestamp_ms, _interval_ms) = parse_heartbeat_block(&tail.block).ok_or(
+      ClientError::MalformedResponse("the tail of the ledger is not a heartbeat"),
+    )?;
+    let age = verifier::check_freshness(timestamp_ms, now_ms(), max_age, self.max_clock_skew)?;
+    Ok(age)
+  }
+
+  /// checks that `tail`, the entry with `block_hash` at `height`, which a read of ledger `handle`
+  /// verified, extends `verified`, the tail that the client verified before the read, and records
+  /// it as the latest tail of the ledger
+  async fn check_tail(
+    &self,
+    handle: &[u8],
+    verified: Option<(usize, NimbleDigest)>,
+    height: usize,
+    block_hash: &NimbleDigest,
+    tail: &NimbleDigest,
+  ) -> Result<(), ClientError> {
+    let rollback = |verified_height: usize| ClientError::RollbackDetected {
+      verified_height: verified_height as u64,
+      height: height as u64,
+    };
+    if let Some((verified_height, verified_tail)) = verified {
+      let extends = if height <= verified_height {
+        height == verified_height && *tail == verified_tail
+      } else if height == verified_height + 1 {
+        MetaBlock::new(&verified_tail, block_hash, height).hash() == *tail
+      } else if self.check_gaps {
+        self
+          .links(handle, verified_height, &verified_tail, height, tail)
+          .await?
+      } else {
+        true
+      };
+      if !extends {
+        return Err(rollback(verified_height));
+      }
+    }
+
+    // two tails at the same height are a fork, whichever read saw the other first
+    let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+    match state.get_ledger_tail(handle) {
+      Some((recorded_height, recorded_tail))
+        if recorded_height == height && recorded_tail != *tail =>
+      {
+        Err(rollback(recorded_height))
+      },
+      _ => {
+        state.set_ledger_tail(handle, height, tail);
+        Ok(())
+      },
+    }
+  }
+
+  /// whether the entries of ledger `handle` after `verified_tail` at `verified_height` lead to
+  /// `tail` at `height`; they are read with the receipts of the current tail, which verify them
+  async fn links(
+    &self,
+    handle: &[u8],
+    verified_height: usize,
+    verified_tail: &NimbleDigest,
+    height: usize,
+    tail: &NimbleDigest,
+  ) -> Result<bool, ClientError> {
+    let (_block, checkpoint, chain) = match self.read_chain(handle, verified_height + 1).await {
+      Ok(read) => read,
+      // the ledger is not even past the tail that was verified
+      Err(ClientError::Rpc(status)) if status.code() == Code::OutOfRange => return Ok(false),
+      Err(error) => return Err(error),
+    };
+    Ok(
+      checkpoint.map(|checkpoint| checkpoint.hash()) == Some(*verified_tail)
+        && chain.get(height - verified_height - 1).map(|m| m.hash()) == Some(*tail),
+    )
+  }
+
+  /// watches ledger `handle` from `from_height` on: the stream yields the committed entries first
+  /// and then each entry as it commits, in order and without gaps, and with their blocks if
+  /// `include_blocks` is set. A watch that breaks, that the coordinator drops because the
+  /// application fell behind, or that stays silent past `WATCH_IDLE_TIMEOUT` is resumed at the
+  /// next height, up to `max_attempts` times in a row; the stream ends after an error
+  pub async fn watch(
+    &self,
+    handle: &[u8],
+    from_height: usize,
+    include_blocks: bool,
+  ) -> Result<impl Stream<Item = Result<WatchedEntry, ClientError>> + Unpin, ClientError> {
+    let stream = self
+      .open_watch(handle, from_height, include_blocks)
+      .await
+      .map_err(|(status, _attempts)| ClientError::Rpc(status))?;
+    let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+    let client = self.clone();
+    let handle = handle.to_vec();
+    tokio::spawn(async move {
+      client
+        .follow_watch(&handle, from_height, include_blocks, stream, tx)
+        .await
+    });
+    Ok(ReceiverStream::new(rx))
+  }
+
+  async fn open_watch(
+    &self,
+    handle: &[u8],
+    from_height: usize,
+    include_blocks: bool,
+  ) -> Result<Streaming<WatchResp>, (Status, usize)> {
+    let req = WatchReq {
+      handle: handle.to_vec(),
+      from_height: from_height as u64,
+      include_blocks,
+    };
+    self
+      .call(
+        req,
+        |mut conn, request| async move { conn.watch(request).await },
+      )
+      .await
+  }
+
+  /// passes the entries of `stream` on to `tx`, and resumes the watch when it fails transiently
+  async fn follow_watch(
+    &self,
+    handle: &[u8],
+    mut next_height: usize,
+    include_blocks: bool,
+    mut stream: Streaming<WatchResp>,
+    tx: mpsc::Sender<Result<WatchedEntry, ClientError>>,
+  ) {
+    let mut failures = 0;
+    loop {
+      let status = match tokio::time::timeout(WATCH_IDLE_TIMEOUT, stream.message()).await {
+        // the coordinator sends heartbeats while no entry commits
+        Ok(Ok(Some(resp))) if resp.heartbeat => continue,
+        Ok(Ok(Some(resp))) => {
+          let res = watched_entry(resp, next_height, include_blocks);
+          let failed = res.is_err();
+          if tx.send(res).await.is_err() || failed {
+            return;
+          }
+          next_height += 1;
+          failures = 0;
+          continue;
+        },
+        Ok(Ok(None)) => Status::unavailable("the coordinator ended the watch"),
+        // a client that fell behind resumes at its next height, which the status carries too
+        Ok(Err(status)) => status,
+        Err(_elapsed) => Status::deadline_exceeded("the watch stayed silent"),
+      };
+
+      failures += 1;
+      if !is_transient(&status) || failures >= self.max_attempts {
+        let _ = tx.send(Err(ClientError::Rpc(status))).await;
+        return;
+      }
+      stream = match self.open_watch(handle, next_height, include_blocks).await {
+        Ok(stream) => stream,
+        Err((status, _attempts)) => {
+          let _ = tx.send(Err(ClientError::Rpc(status))).await;
+          return;
+        },
+      };
+    }
+  }
+
+  /// attests an entry that a watch of ledger `handle` pushed with a read of the entry, which the
+  /// receipts of the tail verify; fails if the entry is not the one that the read attests
+  pub async fn attest(
+    &self,
+    handle: &[u8],
+    entry: &WatchedEntry,
+  ) -> Result<LedgerEntry, ClientError> {
+    let (block, _checkpoint, chain) = self.read_chain(handle, entry.height).await?;
+    if *chain[0].get_block_hash() != entry.block_hash {
+      return Err(ClientError::MalformedResponse(
+        "the watch pushed another entry than the read attests",
+      ));
+    }
+    Ok(LedgerEntry {
+      block,
+      height: entry.height,
+      hash: chain[0].hash(),
+    })
+  }
+
+  /// reads the entry at `index` of ledger `handle`, which is verified through the receipts of the
+  /// tail that the entries after it lead to
+  pub async fn read_by_index(
+    &self,
+    handle: &[u8],
+    index: usize,
+  ) -> Result<LedgerEntry, ClientError> {
+    let (block, _checkpoint, chain) = self.read_chain(handle, index).await?;
+    Ok(LedgerEntry {
+      block,
+      height: index,
+      hash: chain[0].hash(),
+    })
+  }
+
+  /// reads the entry at `index` of ledger `handle`; returns its block, the metablock before it,
+  /// unless it is the genesis entry, and the metablocks from the entry to the tail, which the
+  /// receipts of the tail verify
+  async fn read_chain(
+    &self,
+    handle: &[u8],
+    index: usize,
+  ) -> Result<(Vec<u8>, Option<MetaBlock>, Vec<MetaBlock>), ClientError> {
+    let req = ReadByIndexReq {
+      handle: handle.to_vec(),
+      index: index as u64,
+    };
+    let resp = self
+      .call(req, |mut conn, request| async move {
+        conn.read_by_index(request).await
+      })
+      .await
+      .map_err(|(status, _attempts)| ClientError::Rpc(status))?;
+
+    let checkpoint = if index == 0 {
+      if !resp.checkpoint.is_empty() {
+        return Err(ClientError::MalformedResponse(
+          "the genesis entry has a checkpoint",
+        ));
+      }
+      None
+    } else {
+      let checkpoint = MetaBlock::from_bytes(&resp.checkpoint)
+        .map_err(|_e| ClientError::MalformedResponse("the checkpoint is not a metablock"))?;
+      if checkpoint.get_height() + 1 != index {
+        return Err(ClientError::MalformedResponse(
+          "the checkpoint does not precede the entry",
+        ));
+      }
+      Some(checkpoint)
+    };
+    let block_hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(&resp.block).to_bytes(),
+      &NimbleDigest::digest(&resp.nonces).to_bytes(),
+    );
+    let mut block_hashes = vec![block_hash];
+    for bytes in &resp.block_hashes {
+      block_hashes.push(
+        NimbleDigest::from_bytes(bytes)
+          .map_err(|_e| ClientError::MalformedResponse("a block hash is not a digest"))?,
+      );
+    }
+    self
+      .verify(|state| {
+        state.verify_chain(
+          handle,
+          checkpoint.as_ref(),
+          &block_hashes,
+          &resp.tail_receipts,
+        )
+      })
+      .await?;
+
+    let mut chain: Vec<MetaBlock> = Vec::with_capacity(block_hashes.len());
+    for block_hash in &block_hashes {
+      let metablock = match chain.last().or_else(|| checkpoint.as_ref()) {
+        Some(prev) => prev
+          .next(block_hash)
+          .ok_or(ClientError::MalformedResponse("the height overflows"))?,
+        None => MetaBlock::genesis(block_hash),
+      };
+      chain.push(metablock);
+    }
+    Ok((resp.block, checkpoint, chain))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use coordinator_proto::{
+    call_server::{Call, CallServer},
+    AppendBatchReq, AppendBatchResp, AppendResp, ExportLedgerReq, ExportLedgerResp,
+    GetLedgerInfoReq, GetLedgerInfoResp, ListLedgersReq, ListLedgersResp, NewLedgerResp,
+    ReadByIndexResp, ReadLatestResp, ReadRangeReq, ReadRangeResp, ReadViewByIndexResp,
+    ReadViewTailReq, ReadViewTailResp, SealLedgerReq, SealLedgerResp, SnapshotReadEntry,
+    SnapshotReadResp, WatchLagged,
+  };
+  use ledger::{
+    compute_genesis_block, compute_heartbeat_block, compute_ledger_tail_message,
+    compute_view_block_hash, retrieve_quorum_from_config,
+    signature::{PrivateKey, PrivateKeyTrait, PublicKeyTrait},
+    EndorserHostnames, IdSig, Receipt, Receipts,
+  };
+  use std::{
+    collections::HashMap,
+    sync::{
+      atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+      Mutex,
+    },
+  };
+  use tokio_stream::StreamExt;
+
+  fn view_block(keys: &[PrivateKey]) -> Vec<u8> {
+    let hostnames = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let pk = key.get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    bincode::serialize(&hostnames).unwrap()
+  }
+
+  /// adds the receipts of `keys` on `metablock`, which they sign with `message` in `view`
+  fn sign(
+    receipts: &mut Receipts,
+    keys: &[PrivateKey],
+    view: &NimbleDigest,
+    metablock: &MetaBlock,
+    message: &NimbleDigest,
+  ) {
+    for key in keys {
+      let id_sig = IdSig::new(
+        key.get_public_key().unwrap(),
+        key.sign(&message.to_bytes()).unwrap(),
+      );
+      receipts.add(&Receipt::new(*view, metablock.clone(), id_sig));
+    }
+  }
+
+  struct Entry {
+    block: Vec<u8>,
+    metablock: MetaBlock,
+    receipts: Vec<u8>,
+  }
+
+  /// the state of a coordinator whose endorsers are keys that it holds itself
+  struct Ledgers {
+    group_identity: NimbleDigest,
+    keys: Vec<PrivateKey>,
+    views: Vec<(Vec<u8>, Vec<u8>, MetaBlock)>,
+    ledgers: HashMap<Vec<u8>, Vec<Entry>>,
+    request_ids: Vec<String>,
+  }
+
+  impl Ledgers {
+    /// moves to a view with `keys`; its entry is endorsed by the endorsers of both views
+    fn change_view(&mut self, keys: Vec<PrivateKey>) {
+      let block = view_block(&keys);
+      let block_hash = compute_view_block_hash(&block).unwrap();
+      let metablock = match self.views.last() {
+        Some((_, _, prev)) => prev.next(&block_hash).unwrap(),
+        None => {
+          self.group_identity = block_hash;
+          MetaBlock::default().next(&block_hash).unwrap()
+        },
+      };
+      let state_hash = NimbleDigest::digest(b"state");
+      let message = self
+        .group_identity
+        .digest_with(&state_hash.digest_with(&metablock.hash()));
+      let mut receipts = Receipts::new();
+      sign(&mut receipts, &self.keys, &state_hash, &metablock, &message);
+      sign(&mut receipts, &keys, &state_hash, &metablock, &message);
+      self.views.push((block, receipts.to_bytes(), metablock));
+      self.keys = keys;
+    }
+
+    /// the receipts of the endorsers of the current view on `metablock` as the tail of `handle`
+    fn endorse(&self, handle: &[u8], metablock: &MetaBlock, nonce: Option<&Nonce>) -> Vec<u8> {
+      let view = self.views.last().unwrap().2.hash();
+      let tail_hash = match nonce {
+        Some(nonce) => metablock.hash().digest_with_bytes(&nonce.to_bytes()),
+        None => metablock.hash(),
+      };
+      let message = compute_ledger_tail_message(
+        &self.group_identity,
+        &view,
+        &NimbleDigest::digest(handle),
+        &tail_hash,
+      );
+      let mut receipts = Receipts::new();
+      sign(&mut receipts, &self.keys, &view, metablock, &message);
+      receipts.to_bytes()
+    }
+
+    fn append(&mut self, handle: &[u8], block: &[u8]) -> Vec<u8> {
+      let block_hash = compute_aggregated_block_hash(
+        &NimbleDigest::digest(block).to_bytes(),
+        &NimbleDigest::default().to_bytes(),
+      );
+      let metablock = self.ledgers[handle]
+        .last()
+        .unwrap()
+        .metablock
+        .next(&block_hash)
+        .unwrap();
+      let receipts = self.endorse(handle, &metablock, None);
+      self.ledgers.get_mut(handle).unwrap().push(Entry {
+        block: block.to_vec(),
+        metablock,
+        receipts: receipts.clone(),
+      });
+      receipts
+    }
+  }
+
+  /// a coordinator that can lose the responses of the writes it applies, interleave the append of
+  /// another client with a lost one, tamper with the receipts it returns, drop a watch as if its
+  /// client fell behind, and hold the ledgers of a snapshot for a while before it reads them; its
+  /// endorsers can attest the entry before the tail as the tail, as byzantine endorsers that hold
+  /// a stale tail
+  struct FakeCoordinator {
+    ledgers: Arc<Mutex<Ledgers>>,
+    lost_responses: AtomicUsize,
+    interleave: AtomicBool,
+    tamper: AtomicBool,
+    stale_tail: AtomicBool,
+    lag_watch: AtomicBool,
+    snapshot_delay_ms: AtomicU64,
+  }
+
+  impl FakeCoordinator {
+    fn new(keys: Vec<PrivateKey>) -> Self {
+      let mut ledgers = Ledgers {
+        group_identity: NimbleDigest::default(),
+        keys: vec![],
+        views: vec![],
+        ledgers: HashMap::new(),
+        request_ids: vec![],
+      };
+      ledgers.change_view(keys);
+      FakeCoordinator {
+        ledgers: Arc::new(Mutex::new(ledgers)),
+        lost_responses: AtomicUsize::new(0),
+        interleave: AtomicBool::new(false),
+        tamper: AtomicBool::new(false),
+        stale_tail: AtomicBool::new(false),
+        lag_watch: AtomicBool::new(false),
+        snapshot_delay_ms: AtomicU64::new(0),
+      }
+    }
+
+    fn record<M>(&self, request: &Request<M>) {
+      if let Some(request_id) = request.metadata().get(REQUEST_ID_KEY) {
+        let request_id = request_id.to_str().unwrap().to_string();
+        self.ledgers.lock().unwrap().request_ids.push(request_id);
+      }
+    }
+
+    /// fails a write that was applied as if its response were lost
+    fn lose_response(&self) -> Result<(), Status> {
+      let lost = self
+        .lost_responses
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok();
+      if lost {
+        Err(Status::unavailable("the response was lost"))
+      } else {
+        Ok(())
+      }
+    }
+
+    fn receipts(&self, mut receipts: Vec<u8>) -> Vec<u8> {
+      if self.tamper.load(Ordering::SeqCst) {
+        let last = receipts.len() - 1;
+        receipts[last] ^= 1;
+      }
+      receipts
+    }
+  }
+
+  #[tonic::async_trait]
+  impl Call for FakeCoordinator {
+    async fn new_ledger(
+      &self,
+      request: Request<NewLedgerReq>,
+    ) -> Result<Response<NewLedgerResp>, Status> {
+      self.record(&request);
+      let NewLedgerReq {
+        handle,
+        block,
+        metadata,
+        ..
+      } = request.into_inner();
+      let genesis = compute_genesis_block(&block, &metadata);
+      let block_hash = compute_aggregated_block_hash(
+        &genesis.hash().to_bytes(),
+        &NimbleDigest::default().to_bytes(),
+      );
+      let metablock = MetaBlock::genesis(&block_hash);
+      let receipts = {
+        let mut ledgers = self.ledgers.lock().unwrap();
+        match ledgers.ledgers.get(&handle) {
+          Some(entries) if entries[0].metablock == metablock => entries[0].receipts.clone(),
+          Some(_) => return Err(Status::already_exists("the ledger exists")),
+          None => {
+            let receipts = ledgers.endorse(&handle, &metablock, None);
+            let entry = Entry {
+              block: genesis.to_bytes(),
+              metablock,
+              receipts: receipts.clone(),
+            };
+            ledgers.ledgers.insert(handle.clone(), vec![entry]);
+            receipts
+          },
+        }
+      };
+      self.lose_response()?;
+      Ok(Response::new(NewLedgerResp {
+        receipts: self.receipts(receipts),
+        handle,
+      }))
+    }
+
+    async fn append(&self, request: Request<AppendReq>) -> Result<Response<AppendResp>, Status> {
+      self.record(&request);
+      let AppendReq {
+        handle,
+        block,
+        expected_height,
+        ..
+      } = request.into_inner();
+      let receipts = {
+        let mut ledgers = self.ledgers.lock().unwrap();
+        let entries = ledgers
+          .ledgers
+          .get(&handle)
+          .ok_or_else(|| Status::not_found("no such ledger"))?;
+        let height = (entries.len() - 1) as u64;
+        let tail = entries.last().unwrap();
+        if height == expected_height {
+          ledgers.append(&handle, &block)
+        } else if height == expected_height + 1 && tail.block == block {
+          // a retry of the append of the tail
+          tail.receipts.clone()
+        } else {
+          let details = AppendConditionFailed {
+            current_height: height,
+            current_tail: tail.metablock.get_block_hash().to_bytes(),
+          };
+          return Err(Status::with_details(
+            Code::FailedPrecondition,
+            "the ledger is not at the expected height",
+            details.encode_to_vec().into(),
+          ));
+        }
+      };
+      if self.lose_response().is_err() {
+        if self.interleave.swap(false, Ordering::SeqCst) {
+          self
+            .ledgers
+            .lock()
+            .unwrap()
+            .append(&handle, b"another client");
+        }
+        return Err(Status::unavailable("the response was lost"));
+      }
+      Ok(Response::new(AppendResp {
+        hash_nonces: NimbleDigest::default().to_bytes(),
+        receipts: self.receipts(receipts),
+        height: expected_height + 1,
+      }))
+    }
+
+    async fn read_latest(
+      &self,
+      request: Request<ReadLatestReq>,
+    ) -> Result<Response<ReadLatestResp>, Status> {
+      self.record(&request);
+      let req = request.into_inner();
+      let nonce = Nonce::from_bytes(&req.nonce).unwrap();
+      let ledgers = self.ledgers.lock().unwrap();
+      let entries = ledgers
+        .ledgers
+        .get(&req.handle)
+        .ok_or_else(|| Status::not_found("no such ledger"))?;
+      let height = if self.stale_tail.load(Ordering::SeqCst) {
+        entries.len() - 2
+      } else {
+        entries.len() - 1
+      };
+      let tail = &entries[height];
+      Ok(Response::new(ReadLatestResp {
+        block: tail.block.clone(),
+        nonces: vec![],
+        receipts: self.receipts(ledgers.endorse(&req.handle, &tail.metablock, Some(&nonce))),
+        height: height as u64,
+        nonce: req.nonce,
+        consistency: ReadConsistency::Attested as i32,
+      }))
+    }
+
+    async fn snapshot_read(
+      &self,
+      request: Request<SnapshotReadReq>,
+    ) -> Result<Response<SnapshotReadResp>, Status> {
+      self.record(&request);
+      let req = request.into_inner();
+      let nonce = Nonce::from_bytes(&req.nonce).unwrap();
+      let locked_at = now_ms();
+      let delay = self.snapshot_delay_ms.load(Ordering::SeqCst);
+      tokio::time::sleep(Duration::from_millis(delay)).await;
+      let ledgers = self.ledgers.lock().unwrap();
+      let mut entries = Vec::new();
+      let mut tails = Vec::new();
+      for handle in req.handles {
+        let tail = ledgers
+          .ledgers
+          .get(&handle)
+          .and_then(|entries| entries.last())
+          .ok_or_else(|| Status::not_found("no such ledger"))?;
+        let height = tail.metablock.get_height();
+        tails.push((NimbleDigest::digest(&handle), tail.metablock.hash(), height));
+        entries.push(SnapshotReadEntry {
+          block: tail.block.clone(),
+          nonces: vec![],
+          receipts: self.receipts(ledgers.endorse(&handle, &tail.metablock, Some(&nonce))),
+          height: height as u64,
+          captured_at: now_ms(),
+          handle,
+        });
+      }
+      Ok(Response::new(SnapshotReadResp {
+        entries,
+        nonce: req.nonce,
+        digest: compute_snapshot_digest(&nonce, &tails).to_bytes(),
+        locked_at,
+        released_at: now_ms(),
+      }))
+    }
+
+    async fn read_by_index(
+      &self,
+      request: Request<ReadByIndexReq>,
+    ) -> Result<Response<ReadByIndexResp>, Status> {
+      self.record(&request);
+      let req = request.into_inner();
+      let ledgers = self.ledgers.lock().unwrap();
+      let entries = ledgers
+        .ledgers
+        .get(&req.handle)
+        .ok_or_else(|| Status::not_found("no such ledger"))?;
+      let index = req.index as usize;
+      let entry = entries
+        .get(index)
+        .ok_or_else(|| Status::out_of_range("beyond the tail"))?;
+      let tail = entries.last().unwrap();
+      Ok(Response::new(ReadByIndexResp {
+        block: entry.block.clone(),
+        nonces: vec![],
+        receipts: entry.receipts.clone(),
+        metablock_hash: entry.metablock.hash().to_bytes(),
+        checkpoint: match index {
+          0 => vec![],
+          _ => entries[index - 1].metablock.to_bytes(),
+        },
+        block_hashes: entries[index + 1..]
+          .iter()
+          .map(|entry| entry.metablock.get_block_hash().to_bytes())
+          .collect(),
+        tail_receipts: self.receipts(ledgers.endorse(&req.handle, &tail.metablock, None)),
+      }))
+    }
+
+    async fn read_view_by_index(
+      &self,
+      request: Request<ReadViewByIndexReq>,
+    ) -> Result<Response<ReadViewByIndexResp>, Status> {
+      self.record(&request);
+      let index = request.into_inner().index as usize;
+      let ledgers = self.ledgers.lock().unwrap();
+      match index.checked_sub(1).and_then(|i| ledgers.views.get(i)) {
+        Some((block, receipts, _)) => Ok(Response::new(ReadViewByIndexResp {
+          block: block.clone(),
+          receipts: receipts.clone(),
+          quorum: retrieve_quorum_from_config(block).unwrap() as u32,
+        })),
+        None => Err(Status::out_of_range("beyond the tail of the view ledger")),
+      }
+    }
+
+    async fn append_batch(
+      &self,
+      _request: Request<AppendBatchReq>,
+    ) -> Result<Response<AppendBatchResp>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+
+    async fn seal_ledger(
+      &self,
+      _request: Request<SealLedgerReq>,
+    ) -> Result<Response<SealLedgerResp>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+
+    async fn read_range(
+      &self,
+      _request: Request<ReadRangeReq>,
+    ) -> Result<Response<ReadRangeResp>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+
+    async fn get_ledger_info(
+      &self,
+      _request: Request<GetLedgerInfoReq>,
+    ) -> Result<Response<GetLedgerInfoResp>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+
+    async fn list_ledgers(
+      &self,
+      _request: Request<ListLedgersReq>,
+    ) -> Result<Response<ListLedgersResp>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+
+    async fn read_view_tail(
+      &self,
+      _request: Request<ReadViewTailReq>,
+    ) -> Result<Response<ReadViewTailResp>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+
+    type WatchStream = ReceiverStream<Result<WatchResp, Status>>;
+
+    /// streams the entries with their blocks, and looks for new ones every few milliseconds; a
+    /// lagging watch is dropped after its first entry
+    async fn watch(
+      &self,
+      request: Request<WatchReq>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+      self.record(&request);
+      let WatchReq {
+        handle,
+        from_height,
+        ..
+      } = request.into_inner();
+      let ledgers = self.ledgers.clone();
+      let lag = self.lag_watch.swap(false, Ordering::SeqCst);
+      let (tx, rx) = mpsc::channel(4);
+      tokio::spawn(async move {
+        let mut height = from_height as usize;
+        while !tx.is_closed() {
+          if lag && height > from_height as usize {
+            let details = WatchLagged {
+              next_height: height as u64,
+            };
+            let status = Status::with_details(
+              Code::ResourceExhausted,
+              "the client fell behind",
+              details.encode_to_vec().into(),
+            );
+            let _ = tx.send(Err(status)).await;
+            return;
+          }
+          let resp = ledgers.lock().unwrap().ledgers[&handle]
+            .get(height)
+            .map(|entry| WatchResp {
+              height: height as u64,
+              block_hash: entry.metablock.get_block_hash().to_bytes(),
+              block: entry.block.clone(),
+              nonces: vec![],
+              heartbeat: false,
+            });
+          match resp {
+            Some(resp) => {
+              let _ = tx.send(Ok(resp)).await;
+              height += 1;
+            },
+            None => tokio::time::sleep(Duration::from_millis(10)).await,
+          }
+        }
+      });
+      Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ExportLedgerStream = ReceiverStream<Result<ExportLedgerResp, Status>>;
+
+    async fn export_ledger(
+      &self,
+      _request: Request<ExportLedgerReq>,
+    ) -> Result<Response<Self::ExportLedgerStream>, Status> {
+      Err(Status::unimplemented("not used by the client"))
+    }
+  }
+
+  /// serves a fake coordinator at `port`, and connects a client that trusts its first view
+  async fn start(port: u16) -> (Arc<FakeCoordinator>, NimbleClient) {
+    let coordinator = Arc::new(FakeCoordinator::new(
+      (0..3).map(|_| PrivateKey::new()).collect(),
+    ));
+    let server = tonic::transport::Server::builder()
+      .add_service(CallServer::from_arc(coordinator.clone()))
+      .serve(format!("[::1]:{}", port).parse().unwrap());
+    tokio::spawn(server);
+
+    let verifier_state = {
+      let ledgers = coordinator.ledgers.lock().unwrap();
+      let (block, receipts, _) = &ledgers.views[0];
+      VerifierState::from_first_view(&ledgers.group_identity, block, receipts).unwrap()
+    };
+    let uri = format!("http://[::1]:{}", port);
+    let mut client = None;
+    for _ in 0..50 {
+      match NimbleClient::connect(&uri, verifier_state.clone()).await {
+        Ok(c) => {
+          client = Some(c);
+          break;
+        },
+        Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+      }
+    }
+    let client = client
+      .expect("the coordinator did not start")
+      .with_retries(3, Duration::from_millis(1));
+    (coordinator, client)
+  }
+
+  #[tokio::test]
+  async fn test_client() {
+    let (coordinator, client) = start(9310).await;
+    let handle = NimbleDigest::digest(b"ledger").to_bytes();
+
+    // a create whose response is lost is retried with the same request ID
+    coordinator.lost_responses.store(1, Ordering::SeqCst);
+    let genesis = client
+      .new_ledger(&handle, b"genesis", b"meta")
+      .await
+      .unwrap();
+    let request_ids = coordinator.ledgers.lock().unwrap().request_ids.clone();
+    assert_eq!(request_ids.len(), 2);
+    assert_eq!(request_ids[0], request_ids[1]);
+
+    // so is an append, which returns the entry the first attempt appended
+    coordinator.lost_responses.store(1, Ordering::SeqCst);
+    let first = client.append(&handle, b"first", 0).await.unwrap();
+    assert_eq!(first.height, 1);
+    assert_eq!(
+      coordinator.ledgers.lock().unwrap().ledgers[&handle].len(),
+      2
+    );
+
+    // even if another client appended after it before the retry
+    coordinator.lost_responses.store(1, Ordering::SeqCst);
+    coordinator.interleave.store(true, Ordering::SeqCst);
+    let second = client.append(&handle, b"second", 1).await.unwrap();
+    assert_eq!(second.height, 2);
+    assert_eq!(client.read_by_index(&handle, 2).await.unwrap(), second);
+
+    // an append at a stale height fails the condition, which is not an integrity violation
+    match client.append(&handle, b"third", 1).await {
+      Err(e @ ClientError::ConditionFailed { current_height: 3 }) => {
+        assert!(!e.is_integrity_violation())
+      },
+      res => panic!("unexpected result {:?}", res),
+    }
+
+    let latest = client.read_latest(&handle).await.unwrap();
+    assert_eq!(latest.height, 3);
+    assert_eq!(latest.block, b"another client".to_vec());
+    let entry = client.read_by_index(&handle, 0).await.unwrap();
+    assert_eq!(entry.hash, genesis);
+    assert_eq!(client.read_by_index(&handle, 1).await.unwrap(), first);
+
+    // the client follows a view change once receipts of the next view show up
+    coordinator
+      .ledgers
+      .lock()
+      .unwrap()
+      .change_view((0..3).map(|_| PrivateKey::new()).collect());
+    let fourth = client.append(&handle, b"fourth", 3).await.unwrap();
+    assert_eq!(fourth.height, 4);
+    assert_eq!(client.get_verifier_state().current_view_index(), 2);
+    assert_eq!(client.read_latest(&handle).await.unwrap(), fourth);
+
+    // receipts that do not verify are an integrity violation
+    coordinator.tamper.store(true, Ordering::SeqCst);
+    match client.read_latest(&handle).await {
+      Err(e @ ClientError::Verification(_)) => assert!(e.is_integrity_violation()),
+      res => panic!("unexpected result {:?}", res),
+    }
+    coordinator.tamper.store(false, Ordering::SeqCst);
+
+    // and transient failures past the last attempt are returned as they are
+    coordinator.lost_responses.store(3, Ordering::SeqCst);
+    match client.append(&handle, b"fifth", 4).await {
+      Err(ClientError::Rpc(status)) => assert_eq!(status.code(), Code::Unavailable),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_rollback_detection() {
+    let (coordinator, client) = start(9311).await;
+    let client = client.with_gap_checking(true);
+    let handle = NimbleDigest::digest(b"ledger").to_bytes();
+    client.new_ledger(&handle, b"genesis", b"").await.unwrap();
+    client.append(&handle, b"first", 0).await.unwrap();
+    client.append(&handle, b"second", 1).await.unwrap();
+    let latest = client.read_latest(&handle).await.unwrap();
+    assert_eq!(latest.height, 2);
+
+    // the tail of every read is attested, but one behind the tail that was verified is not the
+    // tail of the ledger
+    coordinator.stale_tail.store(true, Ordering::SeqCst);
+    match client.read_latest(&handle).await {
+      Err(
+        e @ ClientError::RollbackDetected {
+          verified_height: 2,
+          height: 1,
+        },
+      ) => assert!(e.is_integrity_violation()),
+      res => panic!("unexpected result {:?}", res),
+    }
+    coordinator.stale_tail.store(false, Ordering::SeqCst);
+    assert_eq!(client.read_latest(&handle).await.unwrap(), latest);
+
+    // a tail further ahead is linked to the verified one through the entries in between
+    {
+      let mut ledgers = coordinator.ledgers.lock().unwrap();
+      ledgers.append(&handle, b"third");
+      ledgers.append(&handle, b"fourth");
+    }
+    assert_eq!(client.read_latest(&handle).await.unwrap().height, 4);
+
+    // the verified tail is in the verifier state, which catches a fork of the ledger behind it
+    // once the client connects with the state again
+    let state = VerifierState::from_bytes(&client.get_verifier_state().to_bytes()).unwrap();
+    assert_eq!(
+      state.get_ledger_tail(&handle).map(|(height, _tail)| height),
+      Some(4)
+    );
+    let client = NimbleClient::connect("http://[::1]:9311", state)
+      .await
+      .unwrap()
+      .with_gap_checking(true);
+    {
+      let mut ledgers = coordinator.ledgers.lock().unwrap();
+      ledgers.ledgers.get_mut(&handle).unwrap().truncate(3);
+      ledgers.append(&handle, b"forked third");
+      ledgers.append(&handle, b"forked fourth");
+      ledgers.append(&handle, b"forked fifth");
+    }
+    assert!(matches!(
+      client.read_latest(&handle).await,
+      Err(ClientError::RollbackDetected {
+        verified_height: 4,
+        height: 5
+      })
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_watch() {
+    let (coordinator, client) = start(9312).await;
+    let handle = NimbleDigest::digest(b"ledger").to_bytes();
+    client.new_ledger(&handle, b"genesis", b"").await.unwrap();
+    let first = client.append(&handle, b"first", 0).await.unwrap();
+
+    // the committed entries come first, and then each entry as it commits
+    let mut stream = client.watch(&handle, 1, true).await.unwrap();
+    let watched = stream.next().await.unwrap().unwrap();
+    assert_eq!(watched.height, 1);
+    assert_eq!(watched.block, Some(b"first".to_vec()));
+    let second = client.append(&handle, b"second", 1).await.unwrap();
+    let watched = stream.next().await.unwrap().unwrap();
+    assert_eq!(watched.height, 2);
+
+    // an entry is attested on demand
+    assert_eq!(client.attest(&handle, &watched).await.unwrap(), second);
+
+    // a watch that the coordinator drops is resumed without a gap
+    coordinator.lag_watch.store(true, Ordering::SeqCst);
+    let mut stream = client.watch(&handle, 1, false).await.unwrap();
+    for entry in [&first, &second] {
+      let watched = stream.next().await.unwrap().unwrap();
+      assert_eq!(watched.height, entry.height);
+      assert_eq!(watched.block, None);
+      assert_eq!(client.attest(&handle, &watched).await.unwrap(), *entry);
+    }
+
+    // an entry that the ledger no longer has fails its attestation
+    {
+      let mut ledgers = coordinator.ledgers.lock().unwrap();
+      ledgers.ledgers.get_mut(&handle).unwrap().truncate(2);
+      ledgers.append(&handle, b"forked second");
+    }
+    match client.attest(&handle, &watched).await {
+      Err(e @ ClientError::MalformedResponse(_)) => assert!(e.is_integrity_violation()),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_snapshot_read() {
+    let (coordinator, client) = start(9315).await;
+    let handles = [b"ledger-a".to_vec(), b"ledger-b".to_vec()];
+    for handle in &handles {
+      client.new_ledger(handle, b"genesis", b"").await.unwrap();
+    }
+    let first = client.append(&handles[1], b"first", 0).await.unwrap();
+
+    // every tail verifies with the nonce of the snapshot, and the digest covers them all
+    let snapshot = client.snapshot_read(&handles).await.unwrap();
+    assert_eq!(snapshot.entries.len(), 2);
+    assert_eq!(snapshot.entries[0].height, 0);
+    assert_eq!(snapshot.entries[1], first);
+    assert_eq!(
+      client.get_verifier_state().get_ledger_tail(&handles[1]),
+      Some((1, first.hash))
+    );
+
+    // a coordinator that holds the ledgers for longer than the client tolerates fails the read,
+    // which is not an integrity violation
+    coordinator.snapshot_delay_ms.store(50, Ordering::SeqCst);
+    let strict = client
+      .clone()
+      .with_max_snapshot_window(Duration::from_millis(10));
+    match strict.snapshot_read(&handles).await {
+      Err(e @ ClientError::SnapshotWindowExceeded { .. }) => assert!(!e.is_integrity_violation()),
+      res => panic!("unexpected result {:?}", res),
+    }
+    assert!(client.snapshot_read(&handles).await.is_ok());
+    coordinator.snapshot_delay_ms.store(0, Ordering::SeqCst);
+
+    // receipts that do not verify are an integrity violation
+    coordinator.tamper.store(true, Ordering::SeqCst);
+    match client.snapshot_read(&handles).await {
+      Err(e @ ClientError::Verification(_)) => assert!(e.is_integrity_violation()),
+      res => panic!("unexpected result {:?}", res),
+    }
+    coordinator.tamper.store(false, Ordering::SeqCst);
+
+    // and so is a tail behind the one that the client verified before
+    coordinator
+      .ledgers
+      .lock()
+      .unwrap()
+      .ledgers
+      .get_mut(&handles[1])
+      .unwrap()
+      .truncate(1);
+    assert!(matches!(
+      client.snapshot_read(&handles).await,
+      Err(ClientError::RollbackDetected {
+        verified_height: 1,
+        height: 0
+      })
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_freshness() {
+    let (coordinator, client) = start(9313).await;
+    let client = client.with_max_clock_skew(Duration::from_secs(1));
+    let handle = b"nimble-liveness".to_vec();
+    let now_ms = || {
+      SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+    };
+    let heartbeat = |timestamp_ms: u64| compute_heartbeat_block(timestamp_ms, 30_000).to_bytes();
+    let minute = Duration::from_secs(60);
+
+    // the genesis block of the liveness ledger is its first heartbeat
+    client
+      .new_ledger(&handle, &heartbeat(now_ms() - 10_000), b"")
+      .await
+      .unwrap();
+    let age = client.assert_freshness(&handle, minute).await.unwrap();
+    assert!(age >= Duration::from_secs(10) && age < minute);
+
+    // a heartbeat older than the bound and the skew is stale, and one ahead of the clock of the
+    // client by more than the skew is from the future; neither is an integrity violation
+    let stale = heartbeat(now_ms() - 62_000);
+    coordinator.ledgers.lock().unwrap().append(&handle, &stale);
+    match client.assert_freshness(&handle, minute).await {
+      Err(e @ ClientError::Verification(VerifierError::StaleHeartbeat { .. })) => {
+        assert!(!e.is_integrity_violation())
+      },
+      res => panic!("unexpected result {:?}", res),
+    }
+    let ahead = heartbeat(now_ms() + 5_000);
+    coordinator.ledgers.lock().unwrap().append(&handle, &ahead);
+    assert!(matches!(
+      client.assert_freshness(&handle, minute).await,
+      Err(ClientError::Verification(
+        VerifierError::FutureHeartbeat { .. }
+      ))
+    ));
+    let fresh = heartbeat(now_ms());
+    coordinator.ledgers.lock().unwrap().append(&handle, &fresh);
+    assert!(client.assert_freshness(&handle, minute).await.is_ok());
+
+    // a tail that is not a heartbeat is malformed
+    coordinator
+      .ledgers
+      .lock()
+      .unwrap()
+      .append(&handle, b"block");
+    assert!(matches!(
+      client.assert_freshness(&handle, minute).await,
+      Err(ClientError::MalformedResponse(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_tls() {
+    use tonic::transport::{Identity, Server, ServerTlsConfig};
+    // the certificates that `scripts/gen-test-certs.sh` generates
+    let pem = |name: &str| {
+      let path = format!(
+        "{}/../coordinator/testdata/tls/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+      );
+      std::fs::read(path).unwrap()
+    };
+    let coordinator = Arc::new(FakeCoordinator::new(
+      (0..3).map(|_| PrivateKey::new()).collect(),
+    ));
+    let tls =
+      ServerTlsConfig::new().identity(Identity::from_pem(pem("server.pem"), pem("server.key")));
+    let server = Server::builder()
+      .tls_config(tls)
+      .unwrap()
+      .add_service(CallServer::from_arc(coordinator.clone()))
+      .serve("127.0.0.1:9314".parse().unwrap());
+    tokio::spawn(server);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let verifier_state = {
+      let ledgers = coordinator.ledgers.lock().unwrap();
+      let (block, receipts, _) = &ledgers.views[0];
+      VerifierState::from_first_view(&ledgers.group_identity, block, receipts).unwrap()
+    };
+    let handle = NimbleDigest::digest(b"ledger").to_bytes();
+    let connect = |uri: &'static str, tls: TlsOptions| {
+      let verifier_state = verifier_state.clone();
+      async move { NimbleClient::connect_with_tls(uri, verifier_state, &tls).await }
+    };
+
+    // a client that verifies the coordinator against the CA
+    let options = TlsOptions::default().with_ca_bundle(pem("ca.pem"));
+    let client = connect("https://localhost:9314", options).await.unwrap();
+    client
+      .new_ledger(&handle, b"genesis", b"meta")
+      .await
+      .unwrap();
+    // and one that does not verify it
+    let options = TlsOptions::default().with_insecure(true);
+    let client = connect("https://localhost:9314", options).await.unwrap();
+    assert_eq!(client.read_latest(&handle).await.unwrap().height, 0);
+
+    // a coordinator whose certificate the CAs of the client did not issue is not trusted, and
+    // a coordinator that serves TLS does not serve plaintext
+    let options = TlsOptions::default().with_ca_bundle(pem("client.pem"));
+    let res = connect("https://localhost:9314", options).await;
+    assert!(matches!(res, Err(ClientError::Transport(_))));
+    let res = connect("https://localhost:9314", TlsOptions::default()).await;
+    assert!(matches!(res, Err(ClientError::Tls(_))));
+    let res = match connect("http://localhost:9314", TlsOptions::default()).await {
+      Ok(client) => client.read_latest(&handle).await.map(|_entry| ()),
+      Err(e) => Err(e),
+    };
+    assert!(res.is_err());
+  }
+}
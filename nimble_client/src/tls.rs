@@ -0,0 +1,185 @@
+//! TLS to the coordinator. A client speaks TLS to `https` URIs, verifies the certificate of the
+//! coordinator against the CAs of a bundle, and presents a certificate of its own to a
+//! coordinator that verifies its clients. An insecure client skips the verification of the
+//! coordinator, for tests and first contact only: the receipts it reads still verify, but anyone
+//! on the path can read and fail its calls.
+use crate::ClientError;
+use rustls::{
+  client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+  Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName,
+};
+use rustls_pemfile::Item;
+use std::{convert::TryFrom, sync::Arc, time::SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tonic::transport::{Channel, Endpoint, Uri};
+
+const HTTPS_PORT: u16 = 443;
+
+/// how a client secures its connection to the coordinator
+#[derive(Clone, Default)]
+pub struct TlsOptions {
+  /// the PEM certificates of the CAs that the certificate of the coordinator chains to
+  ca_bundle: Option<Vec<u8>>,
+  /// the PEM certificate and key of the client
+  identity: Option<(Vec<u8>, Vec<u8>)>,
+  insecure: bool,
+}
+
+impl TlsOptions {
+  /// verifies the coordinator against the CAs of `pem`
+  pub fn with_ca_bundle(mut self, pem: Vec<u8>) -> Self {
+    self.ca_bundle = Some(pem);
+    self
+  }
+
+  /// presents the certificate `cert_pem` with its key `key_pem` to the coordinator
+  pub fn with_identity(mut self, cert_pem: Vec<u8>, key_pem: Vec<u8>) -> Self {
+    self.identity = Some((cert_pem, key_pem));
+    self
+  }
+
+  /// skips the verification of the certificate of the coordinator
+  pub fn with_insecure(mut self, insecure: bool) -> Self {
+    self.insecure = insecure;
+    self
+  }
+
+  fn client_config(&self) -> Result<ClientConfig, ClientError> {
+    let verifier: Arc<dyn ServerCertVerifier> = if self.insecure {
+      Arc::new(NoVerification)
+    } else {
+      let pem = self.ca_bundle.as_ref().ok_or_else(|| {
+        ClientError::Tls("https requires a CA bundle unless it is insecure".to_string())
+      })?;
+      let mut roots = RootCertStore::empty();
+      let (added, _ignored) = roots.add_parsable_certificates(&pem_certs(pem));
+      if added == 0 {
+        return Err(ClientError::Tls(
+          "the CA bundle holds no certificate".to_string(),
+        ));
+      }
+      Arc::new(WebPkiVerifier::new(roots, None))
+    };
+    let builder = ClientConfig::builder()
+      .with_safe_defaults()
+      .with_custom_certificate_verifier(verifier);
+    let mut config = match &self.identity {
+      Some((cert_pem, key_pem)) => {
+        let certs = pem_certs(cert_pem).into_iter().map(Certificate).collect();
+        let key = pem_key(key_pem)
+          .ok_or_else(|| ClientError::Tls("the client key holds no private key".to_string()))?;
+        builder
+          .with_single_cert(certs, PrivateKey(key))
+          .map_err(|e| ClientError::Tls(format!("invalid client certificate: {}", e)))?
+      },
+      None => builder.with_no_client_auth(),
+    };
+    // gRPC runs over HTTP/2
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(config)
+  }
+}
+
+/// accepts any certificate of the coordinator; the signatures of the handshake are still checked
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+  fn verify_server_cert(
+    &self,
+    _end_entity: &Certificate,
+    _intermediates: &[Certificate],
+    _server_name: &ServerName,
+    _scts: &mut dyn Iterator<Item = &[u8]>,
+    _ocsp_response: &[u8],
+    _now: SystemTime,
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    Ok(ServerCertVerified::assertion())
+  }
+}
+
+/// the DER certificates of a PEM file
+fn pem_certs(pem: &[u8]) -> Vec<Vec<u8>> {
+  rustls_pemfile::certs(&mut &*pem).unwrap_or_default()
+}
+
+/// the first DER private key of a PEM file
+fn pem_key(pem: &[u8]) -> Option<Vec<u8>> {
+  rustls_pemfile::read_all(&mut &*pem)
+    .unwrap_or_default()
+    .into_iter()
+    .find_map(|item| match item {
+      Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(key),
+      _ => None,
+    })
+}
+
+/// connects to the coordinator at `uri`, over TLS if its scheme is `https`. The channel runs over
+/// a connection that the client secures itself, so tonic sees a plaintext URI
+pub async fn connect(uri: &str, tls: &TlsOptions) -> Result<Channel, ClientError> {
+  let endpoint = Endpoint::from_shared(uri.to_string()).map_err(ClientError::Transport)?;
+  if endpoint.uri().scheme_str() != Some("https") {
+    return endpoint.connect().await.map_err(ClientError::Transport);
+  }
+
+  let config = Arc::new(tls.client_config()?);
+  let host = endpoint.uri().host().unwrap_or_default();
+  let port = endpoint.uri().port_u16().unwrap_or(HTTPS_PORT);
+  // the host of an IPv6 address is bracketed in the URI, but not in its server name
+  let name = host
+    .trim_start_matches('[')
+    .trim_end_matches(']')
+    .to_string();
+  let server_name = ServerName::try_from(name.as_str())
+    .map_err(|_e| ClientError::Tls(format!("invalid coordinator host {}", host)))?;
+  let plaintext = format!("http://{}:{}", host, port);
+  Endpoint::from_shared(plaintext)
+    .map_err(ClientError::Transport)?
+    .connect_with_connector(tower::service_fn(move |_uri: Uri| {
+      let (config, server_name, name) = (config.clone(), server_name.clone(), name.clone());
+      async move {
+        let tcp = TcpStream::connect((name.as_str(), port)).await?;
+        TlsConnector::from(config).connect(server_name, tcp).await
+      }
+    }))
+    .await
+    .map_err(ClientError::Transport)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn pem(name: &str) -> Vec<u8> {
+    let path = format!(
+      "{}/../coordinator/testdata/tls/{}",
+      env!("CARGO_MANIFEST_DIR"),
+      name
+    );
+    std::fs::read(path).unwrap()
+  }
+
+  #[test]
+  pub fn test_tls_options() {
+    let options = TlsOptions::default().with_ca_bundle(pem("ca.pem"));
+    assert!(options.client_config().is_ok());
+    let options = options.with_identity(pem("client.pem"), pem("client.key"));
+    assert!(options.client_config().is_ok());
+    assert!(TlsOptions::default()
+      .with_insecure(true)
+      .client_config()
+      .is_ok());
+
+    let res = TlsOptions::default().client_config();
+    assert!(matches!(res, Err(ClientError::Tls(_))));
+    let res = TlsOptions::default()
+      .with_ca_bundle(pem("server.key"))
+      .client_config();
+    assert!(matches!(res, Err(ClientError::Tls(_))));
+    let res = TlsOptions::default()
+      .with_insecure(true)
+      .with_identity(pem("client.pem"), pem("client.pem"))
+      .client_config();
+    assert!(matches!(res, Err(ClientError::Tls(_))));
+  }
+}
@@ -0,0 +1,36 @@
+#!/bin/bash
+# Generates the certificates of the TLS tests in coordinator/testdata/tls: a CA, a certificate of
+# the coordinator for localhost, [::1] and 127.0.0.1, a certificate of a client, and a key that
+# matches neither. They are valid for a hundred years, so they are only regenerated on purpose.
+set -e
+
+dir=$(dirname "$0")/../coordinator/testdata/tls
+mkdir -p "$dir"
+cd "$dir"
+
+key() {
+  openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1"
+}
+
+key ca.key
+openssl req -x509 -new -key ca.key -sha256 -days 36500 -subj "/CN=Nimble Test CA" \
+  -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign" \
+  -out ca.pem
+
+sign() {
+  local name=$1 subject=$2 ext=$3
+  key "$name.key"
+  openssl req -new -key "$name.key" -subj "$subject" -out "$name.csr"
+  printf '%s\n' "basicConstraints=critical,CA:FALSE" "keyUsage=critical,digitalSignature" "$ext" \
+    > "$name.ext"
+  openssl x509 -req -in "$name.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -sha256 -days 36500 \
+    -extfile "$name.ext" -out "$name.pem"
+  rm "$name.csr" "$name.ext"
+}
+
+sign server "/CN=localhost" \
+  "$(printf '%s\n%s' 'subjectAltName=DNS:localhost,IP:::1,IP:127.0.0.1' 'extendedKeyUsage=serverAuth')"
+sign client "/CN=hdfs" \
+  "$(printf '%s\n%s' 'subjectAltName=DNS:hdfs' 'extendedKeyUsage=clientAuth')"
+key other.key
+rm -f ca.key ca.srl
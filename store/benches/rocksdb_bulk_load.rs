@@ -0,0 +1,51 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ledger::{Block, NimbleHashTrait};
+use std::collections::HashMap;
+use store::ledger::{rocksdb_store::RocksDBLedgerStore, LedgerInfo, LedgerStore};
+
+fn bench_bulk_load(c: &mut Criterion) {
+  let dir = std::env::temp_dir().join(format!("nimble-rocksdb-bench-{}", std::process::id()));
+  let mut args = HashMap::<String, String>::new();
+  args.insert(
+    String::from("NIMBLE_ROCKSDB_DIR"),
+    dir.to_str().unwrap().to_string(),
+  );
+
+  let rt = tokio::runtime::Runtime::new().unwrap();
+  let store = rt.block_on(RocksDBLedgerStore::new(&args)).unwrap();
+  let block = Block::new(&[7u8; 1024]);
+
+  let mut next_ledger = 0u64;
+  let mut group = c.benchmark_group("rocksdb_bulk_load");
+  for num_entries in [10usize, 100, 1000] {
+    group.throughput(Throughput::Elements(num_entries as u64));
+    group.bench_with_input(
+      BenchmarkId::new("append", num_entries),
+      &num_entries,
+      |b, &num_entries| {
+        b.iter(|| {
+          // every iteration loads a fresh ledger
+          next_ledger += 1;
+          let genesis = Block::new(&next_ledger.to_be_bytes());
+          let handle = genesis.hash();
+          rt.block_on(async {
+            store
+              .create_ledger(&handle, genesis, &LedgerInfo::default())
+              .await
+              .unwrap();
+            for height in 1..=num_entries {
+              store.append_ledger(&handle, &block, height).await.unwrap();
+            }
+          })
+        })
+      },
+    );
+  }
+  group.finish();
+
+  drop(store);
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, bench_bulk_load);
+criterion_main!(benches);
@@ -50,6 +50,10 @@ pub enum StorageError {
   UnhandledError,
   /// return if the name for the nimble database is not acceptable for the store
   InvalidDBName,
+  /// return if the store does not support the requested operation
+  UnsupportedOperation,
+  /// return if a persisted ledger fails validation when the store is opened
+  CorruptedLedger,
 }
 
 use std::fmt::Display;
@@ -1,6 +1,9 @@
 use crate::{
   errors::{LedgerStoreError, StorageError},
-  ledger::{LedgerEntry, LedgerStore},
+  ledger::{
+    decode_stored_receipts, AppendToken, IntentRecord, LeaseRecord, LedgerEntry, LedgerInfo,
+    LedgerStore, TenantRecord,
+  },
 };
 use async_trait::async_trait;
 use azure_data_tables::{clients::TableClient, prelude::*};
@@ -8,23 +11,44 @@ use azure_data_tables::{clients::TableClient, prelude::*};
 use azure_core::Etag;
 use azure_storage::core::prelude::*;
 use base64_url;
-use ledger::{Block, CustomSerde, Handle, NimbleDigest, Nonce, Nonces, Receipts};
+use ledger::{Block, CustomSerde, Handle, NimbleDigest, NimbleHashTrait, Nonce, Nonces, Receipts};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
   cmp::Ordering,
   collections::HashMap,
   convert::TryFrom,
   fmt::Debug,
-  sync::{Arc, RwLock},
+  future::Future,
+  sync::{Arc, Mutex, RwLock},
+  time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use http::{self, StatusCode};
 
 const TAIL: &str = "TAIL";
+const INFO: &str = "INFO";
+const TENANT: &str = "TENANT";
+const LEASE: &str = "LEASE";
+const TOKENS: &str = "TOKENS";
+// partition key of the lease; like those of tenants, it is never a valid hex encoding
+const LEASE_PARTITION: &str = "coordinator-lease";
+// partition key of the intents of appends, with one row per ledger and height
+const INTENT_PARTITION: &str = "coordinator-intents";
+
+// requests throttled (429) or failed by the service (5xx) are retried with exponential backoff
+const MAX_RETRIES: u32 = 8;
+const INITIAL_BACKOFF_MS: u64 = 50;
+const MAX_BACKOFF_MS: u64 = 10_000;
+
+// managed identity tokens come from the instance metadata service and are refreshed ahead of expiry
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token?api-version=2018-02-01&resource=https%3A%2F%2Fstorage.azure.com%2F";
+const TOKEN_REFRESH_MARGIN_SECS: u64 = 300;
 
 enum AzureOp {
   Append,
-  Create,
+  // a ledger is created with its INFO row, except for the view ledger
+  Create(Option<DBInfoEntry>),
 }
 
 /*
@@ -77,6 +101,60 @@ macro_rules! get_error_status {
   };
 }
 
+type AzureResult<T> = Result<T, Box<dyn std::error::Error + Sync + Send>>;
+
+fn is_transient_error(err: &(dyn std::error::Error + Sync + Send + 'static)) -> bool {
+  match err.downcast_ref::<azure_core::HttpError>() {
+    Some(azure_core::HttpError::StatusCode { status, body: _ }) => {
+      *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    },
+    _ => false,
+  }
+}
+
+/// sends the request built by `request` until it succeeds, fails for a reason other than
+/// throttling or a server error, or runs out of retries
+async fn with_backoff<T, F, Fut>(mut request: F) -> AzureResult<T>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = AzureResult<T>>,
+{
+  let mut backoff_ms = INITIAL_BACKOFF_MS;
+  let mut retries = 0;
+  loop {
+    match request().await {
+      Err(err) if retries < MAX_RETRIES && is_transient_error(err.as_ref()) => {
+        // full jitter keeps concurrent coordinators from retrying in lockstep
+        let delay = rand::thread_rng().gen_range(0..=backoff_ms);
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        backoff_ms = std::cmp::min(backoff_ms * 2, MAX_BACKOFF_MS);
+        retries += 1;
+      },
+      res => return res,
+    }
+  }
+}
+
+/// partition key of a ledger
+fn partition_key(handle: &Handle) -> String {
+  hex::encode(handle.to_bytes())
+}
+
+/// partition key of a tenant; never a valid hex encoding, so it cannot collide with a ledger
+fn tenant_partition_key(tenant: &str) -> String {
+  format!("tenant-{}", hex::encode(tenant.as_bytes()))
+}
+
+/// row key of the entry at `height`; zero-padded so that rows sort by height
+fn row_key(height: u64) -> String {
+  format!("{:020}", height)
+}
+
+/// row key of the intent of an append to the ledger at `height`
+fn intent_row_key(handle: &Handle, height: u64) -> String {
+  format!("{}-{}", partition_key(handle), row_key(height))
+}
+
 fn parse_error_status(code: StatusCode) -> LedgerStoreError {
   match code {
     StatusCode::BAD_REQUEST => LedgerStoreError::LedgerError(StorageError::BadRequest),
@@ -128,6 +206,80 @@ struct DBEntry {
   pub block: String,
   pub receipts: String,
   pub nonces: String,
+  // the hash of the block if its contents were purged, in which case the block is empty
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub purged_hash: Option<String>,
+}
+
+// The creation time, metadata, and client handle of a ledger, kept in the INFO row of its
+// partition
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBInfoEntry {
+  #[serde(rename = "PartitionKey")]
+  pub handle: String,
+  #[serde(rename = "RowKey")]
+  pub row: String,
+  pub created_at: i64,
+  pub metadata: String,
+  pub handle_bytes: String,
+  pub app_bytes: String,
+}
+
+// The quota and usage of a tenant, kept in the TENANT row of its own partition
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBTenantEntry {
+  #[serde(rename = "PartitionKey")]
+  pub tenant: String,
+  #[serde(rename = "RowKey")]
+  pub row: String,
+  pub max_ledgers: i64,
+  pub max_appends_per_sec: i64,
+  pub max_stored_bytes: i64,
+  pub num_ledgers: i64,
+  pub stored_bytes: i64,
+}
+
+// The request IDs of the appends to a ledger, kept in the TOKENS row of its partition; the list
+// is bincode-encoded, since a row has no nested properties
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBAppendTokensEntry {
+  #[serde(rename = "PartitionKey")]
+  pub handle: String,
+  #[serde(rename = "RowKey")]
+  pub row: String,
+  pub tokens: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct AppendTokenEntry {
+  pub request_id: String,
+  pub height: u64,
+  pub block_hash: Vec<u8>,
+}
+
+// The lease of the coordinators, kept in the LEASE row of its own partition
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBLeaseEntry {
+  #[serde(rename = "PartitionKey")]
+  pub partition: String,
+  #[serde(rename = "RowKey")]
+  pub row: String,
+  pub holder: String,
+  pub expires_at: i64,
+  pub epoch: i64,
+}
+
+// The intent of an append, kept in a row of the partition of intents
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBIntentEntry {
+  #[serde(rename = "PartitionKey")]
+  pub partition: String,
+  #[serde(rename = "RowKey")]
+  pub row: String,
+  pub handle: String,
+  pub height: i64,
+  pub block_hash: String,
+  pub request_id: String,
 }
 
 // This is a projection so you only modify the receipt, not the rest
@@ -140,6 +292,17 @@ struct DBEntryReceiptProjection {
   pub receipts: String,
 }
 
+// This is a projection so you only drop the block and record its hash, not the rest
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBEntryPurgeProjection {
+  #[serde(rename = "PartitionKey")]
+  pub handle: String,
+  #[serde(rename = "RowKey")]
+  pub row: String,
+  pub block: String,
+  pub purged_hash: String,
+}
+
 // This is a projection so you only modify the nonces, not the rest
 #[derive(Clone, Serialize, Deserialize, Debug)]
 struct DBEntryNonceProjection {
@@ -150,23 +313,115 @@ struct DBEntryNonceProjection {
   pub nonces: String,
 }
 
+#[derive(Deserialize)]
+struct ManagedIdentityToken {
+  access_token: String,
+  expires_on: String,
+}
+
+#[derive(Debug)]
+struct ManagedIdentity {
+  account: String,
+  client_id: Option<String>,
+  // seconds since the Unix epoch
+  expires_on: u64,
+}
+
 #[derive(Debug)]
 pub struct TableLedgerStore {
-  client: Arc<TableClient>,
+  client: RwLock<Arc<TableClient>>,
+  table_name: String,
+  identity: Option<Mutex<ManagedIdentity>>,
   view_handle: Handle,
   cache: CacheMap,
 }
 
-impl TableLedgerStore {
-  pub async fn new(args: &HashMap<String, String>) -> Result<Self, LedgerStoreError> {
-    if !args.contains_key("STORAGE_ACCOUNT") || !args.contains_key("STORAGE_MASTER_KEY") {
+/// returns `args[key]`, falling back to the environment variable of the same name
+fn arg_or_env(args: &HashMap<String, String>, key: &str) -> Option<String> {
+  match args.get(key) {
+    Some(v) => Some(v.clone()),
+    None => std::env::var(key).ok(),
+  }
+}
+
+fn unix_now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+fn new_table_client(
+  storage_client: Arc<StorageAccountClient>,
+  table_name: &str,
+) -> Result<Arc<TableClient>, LedgerStoreError> {
+  match storage_client.as_storage_client().as_table_service_client() {
+    Ok(v) => Ok(v.as_table_client(table_name)),
+    Err(e) => {
+      eprintln!("Unable to convert to table service client: {:?}", e);
+      Err(LedgerStoreError::LedgerError(StorageError::InvalidDBUri))
+    },
+  }
+}
+
+/// fetches an access token for Azure Storage from the instance metadata service; returns the
+/// token and its expiry in seconds since the Unix epoch
+async fn fetch_managed_identity_token(
+  client_id: Option<&str>,
+) -> Result<(String, u64), LedgerStoreError> {
+  let mut url = IMDS_TOKEN_URL.to_string();
+  if let Some(id) = client_id {
+    url.push_str("&client_id=");
+    url.push_str(id);
+  }
+
+  let http_client = azure_core::new_http_client();
+  let response = with_backoff(|| {
+    let request = http::Request::get(url.as_str())
+      .header("Metadata", "true")
+      .body(bytes::Bytes::new());
+    let http_client = http_client.clone();
+    async move {
+      let response = http_client
+        .execute_request_check_status(request?, StatusCode::OK)
+        .await?;
+      Ok(response)
+    }
+  })
+  .await;
+
+  let response = match response {
+    Ok(r) => r,
+    Err(e) => {
+      eprintln!("Unable to get a managed identity token: {:?}", e);
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidDBUri));
+    },
+  };
+
+  let token: ManagedIdentityToken = match serde_json::from_slice(response.body()) {
+    Ok(t) => t,
+    Err(e) => {
+      eprintln!("Unable to parse the managed identity token: {:?}", e);
       return Err(LedgerStoreError::LedgerError(
-        StorageError::MissingArguments,
+        StorageError::DeserializationError,
       ));
-    }
-    let account = args["STORAGE_ACCOUNT"].clone();
-    let master_key = args["STORAGE_MASTER_KEY"].clone();
+    },
+  };
 
+  match token.expires_on.parse::<u64>() {
+    Ok(expires_on) => Ok((token.access_token, expires_on)),
+    Err(_) => Err(LedgerStoreError::LedgerError(
+      StorageError::DeserializationError,
+    )),
+  }
+}
+
+impl TableLedgerStore {
+  /// Connects with the first credential available in `args` or the environment:
+  /// `STORAGE_CONNECTION_STRING` (e.g., pointing at Azurite), `STORAGE_ACCOUNT` with
+  /// `STORAGE_MASTER_KEY`, or `STORAGE_ACCOUNT` alone to use the managed identity of the host
+  /// (optionally the user-assigned identity `AZURE_CLIENT_ID`).
+  pub async fn new(args: &HashMap<String, String>) -> Result<Self, LedgerStoreError> {
     // Below is the desired name of the container that will hold the blobs
     // (it can be anything initially, but afterwards, it needs to be the same
     // so you access the same container and recover the stored data)
@@ -176,17 +431,35 @@ impl TableLedgerStore {
     }
 
     let http_client = azure_core::new_http_client();
-    let storage_client =
-      StorageAccountClient::new_access_key(http_client.clone(), &account, &master_key);
-    let table_service = match storage_client.as_storage_client().as_table_service_client() {
-      Ok(v) => v,
-      Err(e) => {
-        eprintln!("Unable to convert to table service client: {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::InvalidDBUri));
-      },
+    let mut identity = None;
+    let storage_client = if let Some(conn_str) = arg_or_env(args, "STORAGE_CONNECTION_STRING") {
+      match StorageAccountClient::new_connection_string(http_client.clone(), &conn_str) {
+        Ok(c) => c,
+        Err(e) => {
+          eprintln!("Invalid storage connection string: {:?}", e);
+          return Err(LedgerStoreError::LedgerError(StorageError::InvalidDBUri));
+        },
+      }
+    } else if let Some(account) = arg_or_env(args, "STORAGE_ACCOUNT") {
+      if let Some(master_key) = arg_or_env(args, "STORAGE_MASTER_KEY") {
+        StorageAccountClient::new_access_key(http_client.clone(), &account, &master_key)
+      } else {
+        let client_id = arg_or_env(args, "AZURE_CLIENT_ID");
+        let (token, expires_on) = fetch_managed_identity_token(client_id.as_deref()).await?;
+        identity = Some(Mutex::new(ManagedIdentity {
+          account: account.clone(),
+          client_id,
+          expires_on,
+        }));
+        StorageAccountClient::new_bearer_token(http_client.clone(), &account, token)
+      }
+    } else {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::MissingArguments,
+      ));
     };
 
-    let table_client = table_service.as_table_client(nimble_db_name);
+    let table_client = new_table_client(storage_client, &nimble_db_name)?;
 
     let view_handle = match NimbleDigest::from_bytes(&vec![0u8; NimbleDigest::num_bytes()]) {
       Ok(e) => e,
@@ -200,13 +473,15 @@ impl TableLedgerStore {
     let cache = Arc::new(RwLock::new(HashMap::new()));
 
     let ledger_store = TableLedgerStore {
-      client: table_client,
+      client: RwLock::new(table_client.clone()),
+      table_name: nimble_db_name,
+      identity,
       view_handle,
       cache,
     };
 
     // Try to create table. If it exists that's fine.
-    let res = ledger_store.client.create().execute().await;
+    let res = with_backoff(|| async { table_client.create().execute().await }).await;
 
     if let Err(err) = res {
       eprintln!("Error trying to create table in the first place. {:?}", err);
@@ -220,10 +495,10 @@ impl TableLedgerStore {
       }
     }
 
-    let view_handle_string = base64_url::encode(&view_handle.to_bytes());
+    let view_handle_string = partition_key(&view_handle);
 
     // Check if the view ledger exists, if not, create a new one
-    let res = find_db_entry(ledger_store.client.clone(), &view_handle_string, TAIL).await;
+    let res = find_db_entry(table_client.clone(), &view_handle_string, TAIL).await;
     match res {
       Err(error) => {
         match error {
@@ -232,20 +507,21 @@ impl TableLedgerStore {
             // Initialize view ledger's entry
             let entry = DBEntry {
               handle: view_handle_string.clone(),
-              row: 0.to_string(),
+              row: row_key(0),
               height: 0,
               block: base64_url::encode(&Block::new(&[0; 0]).to_bytes()),
               receipts: base64_url::encode(&Receipts::new().to_bytes()),
               nonces: base64_url::encode(&Nonces::new().to_bytes()),
+              purged_hash: None,
             };
 
             azure_op(
-              ledger_store.client.clone(),
+              table_client.clone(),
               &view_handle_string,
               entry.clone(),
               entry,
               &ledger_store.cache,
-              AzureOp::Create,
+              AzureOp::Create(None),
               None,
             )
             .await?;
@@ -272,6 +548,50 @@ impl TableLedgerStore {
 
     Ok(ledger_store)
   }
+
+  /// returns the table client, first renewing the managed identity token if it is about to expire
+  async fn table_client(&self) -> Result<Arc<TableClient>, LedgerStoreError> {
+    if let Some(identity) = &self.identity {
+      let refresh = match identity.lock() {
+        Ok(i) if i.expires_on <= unix_now() + TOKEN_REFRESH_MARGIN_SECS => {
+          Some((i.account.clone(), i.client_id.clone()))
+        },
+        Ok(_) => None,
+        Err(_) => {
+          return Err(LedgerStoreError::LedgerError(
+            StorageError::LedgerWriteLockFailed,
+          ));
+        },
+      };
+
+      // concurrent callers may both renew the token, which is harmless
+      if let Some((account, client_id)) = refresh {
+        let (token, expires_on) = fetch_managed_identity_token(client_id.as_deref()).await?;
+        let storage_client =
+          StorageAccountClient::new_bearer_token(azure_core::new_http_client(), &account, token);
+        let table_client = new_table_client(storage_client, &self.table_name)?;
+
+        match (self.client.write(), identity.lock()) {
+          (Ok(mut client), Ok(mut i)) => {
+            *client = table_client;
+            i.expires_on = expires_on;
+          },
+          _ => {
+            return Err(LedgerStoreError::LedgerError(
+              StorageError::LedgerWriteLockFailed,
+            ));
+          },
+        }
+      }
+    }
+
+    match self.client.read() {
+      Ok(client) => Ok(client.clone()),
+      Err(_) => Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerReadLockFailed,
+      )),
+    }
+  }
 }
 
 fn decode_nonces_string(nonces: &str) -> Result<Nonces, LedgerStoreError> {
@@ -310,7 +630,7 @@ async fn azure_op(
   let mut transaction = Transaction::default();
 
   match op {
-    AzureOp::Create => {
+    AzureOp::Create(info_entry) => {
       // We are creating the ledger so we need to insert the TAIL entry instead of updating it
       let tail_create = match table_client.insert().to_transaction_operation(&tail_entry) {
         Ok(v) => v,
@@ -321,6 +641,19 @@ async fn azure_op(
       };
 
       transaction.add(tail_create);
+
+      // The INFO row is inserted in the same transaction, so a ledger never exists without it
+      if let Some(info_entry) = info_entry {
+        let info_insert = match table_client.insert().to_transaction_operation(&info_entry) {
+          Ok(v) => v,
+          Err(e) => {
+            eprintln!("Cannot create transaction operation due to error: {:?}", e);
+            return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+          },
+        };
+
+        transaction.add(info_insert);
+      }
     },
     AzureOp::Append => {
       assert!(etag.is_some()); // by definition if operaiton is Append and etag must be provided.
@@ -356,10 +689,13 @@ async fn azure_op(
 
   transaction.add(row_insert);
 
-  let res = partition_client
-    .submit_transaction()
-    .execute(&transaction)
-    .await;
+  let res = with_backoff(|| async {
+    partition_client
+      .submit_transaction()
+      .execute(&transaction)
+      .await
+  })
+  .await;
 
   // We need to perform 2 checks. The first check basically asks whether Azure was OK with the
   // way we constructed the transaction (a sort of well-formenedness check). If not, Azure will return
@@ -445,7 +781,7 @@ async fn find_db_entry(
     },
   };
 
-  let res = row_client.get().execute().await;
+  let res = with_backoff(|| async { row_client.get().execute::<DBEntry>().await }).await;
 
   if let Err(err) = res {
     let e = parse_error_status(get_error_status!(err));
@@ -520,20 +856,22 @@ async fn append_ledger_internal(
   // 3. Construct the new entry we are going to append to the ledger
   let tail_entry = DBEntry {
     handle: handle.to_owned(),
-    row: height_plus_one.to_string(),
+    row: row_key(checked_conversion!(height_plus_one, u64)),
     height: height_plus_one,
     block: base64_url::encode(&block.to_bytes()),
     receipts: base64_url::encode(&Receipts::new().to_bytes()),
     nonces: base64_url::encode(&Nonces::new().to_bytes()), // clear out the nonces in tail
+    purged_hash: None,
   };
 
   let indexed_entry = DBEntry {
     handle: handle.to_owned(),
-    row: height_plus_one.to_string(),
+    row: row_key(checked_conversion!(height_plus_one, u64)),
     height: height_plus_one,
     block: base64_url::encode(&block.to_bytes()),
     receipts: base64_url::encode(&Receipts::new().to_bytes()),
     nonces: base64_url::encode(&cache_entry.get_nonces().to_bytes()),
+    purged_hash: None,
   };
 
   // 4. Try to insert the new entry into the ledger and set the tail
@@ -581,10 +919,8 @@ async fn attach_ledger_nonce_internal(
     },
   };
 
-  let res = row_client
-    .merge()
-    .execute(&merge_entry, &IfMatchCondition::Etag(entry.etag))
-    .await;
+  let etag = IfMatchCondition::Etag(entry.etag);
+  let res = with_backoff(|| async { row_client.merge().execute(&merge_entry, &etag).await }).await;
 
   if let Err(err) = res {
     return Err(parse_error_status(get_error_status!(err)));
@@ -618,7 +954,7 @@ async fn attach_ledger_receipts_op(
   }
 
   // 2. Append the receipt to the fetched receipt
-  let mut fetched_receipts = match Receipts::from_bytes(&string_decode(&entry.receipts)?) {
+  let mut fetched_receipts = match decode_stored_receipts(&string_decode(&entry.receipts)?) {
     Ok(r) => r,
     Err(e) => {
       eprintln!("Unable to decode receipt bytes in attach_ledger_op {:?}", e);
@@ -646,10 +982,8 @@ async fn attach_ledger_receipts_op(
     },
   };
 
-  let res = row_client
-    .merge()
-    .execute(&merge_entry, &IfMatchCondition::Etag(etag))
-    .await;
+  let etag = IfMatchCondition::Etag(etag);
+  let res = with_backoff(|| async { row_client.merge().execute(&merge_entry, &etag).await }).await;
 
   if let Err(err) = res {
     return Err(parse_error_status(get_error_status!(err)));
@@ -669,7 +1003,7 @@ async fn read_ledger_internal(
     let (entry, _etag) = find_db_entry(ledger.clone(), handle, TAIL).await?;
     entry.height as usize
   };
-  let index = checked_conversion!(actual_idx, i64).to_string();
+  let index = row_key(checked_conversion!(actual_idx, u64));
 
   let (entry, _etag) = find_db_entry(ledger, handle, &index).await?;
   let ret_block = match Block::from_bytes(&string_decode(&entry.block)?) {
@@ -685,7 +1019,7 @@ async fn read_ledger_internal(
     },
   };
 
-  let ret_receipts = match Receipts::from_bytes(&string_decode(&entry.receipts)?) {
+  let ret_receipts = match decode_stored_receipts(&string_decode(&entry.receipts)?) {
     Ok(r) => r,
     Err(e) => {
       eprintln!("Unable to decode receipt bytes in read_ledger_op {:?}", e);
@@ -697,10 +1031,100 @@ async fn read_ledger_internal(
 
   let nonce_list = decode_nonces_string(&entry.nonces)?;
 
-  Ok((
-    LedgerEntry::new(ret_block, ret_receipts, Some(nonce_list)),
-    checked_conversion!(entry.height, usize),
-  ))
+  let ledger_entry = match &entry.purged_hash {
+    Some(purged_hash) => match NimbleDigest::from_bytes(&string_decode(purged_hash)?) {
+      Ok(block_hash) => LedgerEntry::new_purged(block_hash, ret_receipts, nonce_list),
+      Err(e) => {
+        eprintln!("Unable to decode purged hash in read_ledger_op {:?}", e);
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::DeserializationError,
+        ));
+      },
+    },
+    None => LedgerEntry::new(ret_block, ret_receipts, Some(nonce_list)),
+  };
+
+  Ok((ledger_entry, checked_conversion!(entry.height, usize)))
+}
+
+async fn purge_ledger_block_op(
+  handle: &str,
+  index: &str,
+  ledger: Arc<TableClient>,
+) -> Result<(), LedgerStoreError> {
+  // 1. Fetch the entry at this index; it keeps its hash if it was purged already
+  let (entry, etag) = find_db_entry(ledger.clone(), handle, index).await?;
+  if entry.purged_hash.is_some() {
+    return Ok(());
+  }
+  let block_hash = match Block::from_bytes(&string_decode(&entry.block)?) {
+    Ok(b) => b.hash(),
+    Err(e) => {
+      eprintln!(
+        "Unable to decode block bytes in purge_ledger_block_op {:?}",
+        e
+      );
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::DeserializationError,
+      ));
+    },
+  };
+
+  // 2. Drop the block and record its hash in the row
+  let merge_entry = DBEntryPurgeProjection {
+    handle: handle.to_owned(),
+    row: index.to_owned(),
+    block: base64_url::encode(&Block::new(&[]).to_bytes()),
+    purged_hash: base64_url::encode(&block_hash.to_bytes()),
+  };
+
+  let partition_client = ledger.as_partition_key_client(handle);
+  let row_client = match partition_client.as_entity_client(index) {
+    Ok(v) => v,
+    Err(e) => {
+      eprintln!("Unable to get row client in purge ledger block: {:?}", e);
+      return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+    },
+  };
+
+  let etag = IfMatchCondition::Etag(etag);
+  let res = with_backoff(|| async { row_client.merge().execute(&merge_entry, &etag).await }).await;
+
+  if let Err(err) = res {
+    return Err(parse_error_status(get_error_status!(err)));
+  }
+
+  Ok(())
+}
+
+/// reads the lease and its etag, or returns `None` if no lease was written
+async fn read_lease_entry(
+  ledger: Arc<TableClient>,
+) -> Result<Option<(LeaseRecord, Etag)>, LedgerStoreError> {
+  let partition_client = ledger.as_partition_key_client(LEASE_PARTITION);
+  let row_client = match partition_client.as_entity_client(LEASE) {
+    Ok(v) => v,
+    Err(e) => {
+      eprintln!("Error in read_lease_entry: {:?}", e);
+      return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+    },
+  };
+
+  let res = with_backoff(|| async { row_client.get().execute::<DBLeaseEntry>().await }).await;
+  match res {
+    Ok(res) => Ok(Some((
+      LeaseRecord {
+        holder: res.entity.holder,
+        expires_at: checked_conversion!(res.entity.expires_at, u64),
+        epoch: checked_conversion!(res.entity.epoch, u64),
+      },
+      res.etag,
+    ))),
+    Err(err) => match parse_error_status(get_error_status!(err)) {
+      LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => Ok(None),
+      e => Err(e),
+    },
+  }
 }
 
 async fn get_cached_entry(
@@ -802,18 +1226,29 @@ impl LedgerStore for TableLedgerStore {
     &self,
     handle: &Handle,
     genesis_block: Block,
+    info: &LedgerInfo,
   ) -> Result<(), LedgerStoreError> {
-    let ledger = self.client.clone();
-    let handle_string = base64_url::encode(&handle.to_bytes());
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
     let nonces = base64_url::encode(&Nonces::new().to_bytes());
 
+    let info_entry = DBInfoEntry {
+      handle: handle_string.clone(),
+      row: INFO.to_owned(),
+      created_at: checked_conversion!(info.created_at, i64),
+      metadata: base64_url::encode(&info.metadata),
+      handle_bytes: base64_url::encode(&info.handle_bytes),
+      app_bytes: base64_url::encode(&info.app_bytes),
+    };
+
     let entry = DBEntry {
       handle: handle_string.clone(),
-      row: 0.to_string(),
+      row: row_key(0),
       height: 0,
       block: base64_url::encode(&genesis_block.to_bytes()),
       receipts: base64_url::encode(&Receipts::new().to_bytes()),
       nonces,
+      purged_hash: None,
     };
 
     azure_op(
@@ -822,7 +1257,7 @@ impl LedgerStore for TableLedgerStore {
       entry.clone(),
       entry,
       &self.cache,
-      AzureOp::Create,
+      AzureOp::Create(Some(info_entry)),
       None,
     )
     .await
@@ -834,8 +1269,8 @@ impl LedgerStore for TableLedgerStore {
     block: &Block,
     expected_height: usize,
   ) -> Result<(usize, Nonces), LedgerStoreError> {
-    let ledger = self.client.clone();
-    let handle_string = base64_url::encode(&handle.to_bytes());
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
 
     loop {
       let res = append_ledger_internal(
@@ -870,9 +1305,9 @@ impl LedgerStore for TableLedgerStore {
     idx: usize,
     receipts: &Receipts,
   ) -> Result<(), LedgerStoreError> {
-    let ledger = self.client.clone();
-    let handle_string = base64_url::encode(&handle.to_bytes());
-    let index = idx.to_string();
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
+    let index = row_key(checked_conversion!(idx, u64));
 
     attach_ledger_receipts_internal(ledger, &handle_string, &self.cache, idx, receipts, &index)
       .await
@@ -883,8 +1318,8 @@ impl LedgerStore for TableLedgerStore {
     handle: &Handle,
     nonce: &Nonce,
   ) -> Result<usize, LedgerStoreError> {
-    let ledger = self.client.clone();
-    let handle_string = base64_url::encode(&handle.to_bytes());
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
 
     loop {
       let res =
@@ -914,8 +1349,8 @@ impl LedgerStore for TableLedgerStore {
     &self,
     handle: &Handle,
   ) -> Result<(LedgerEntry, usize), LedgerStoreError> {
-    let ledger = self.client.clone();
-    let handle_string = base64_url::encode(&handle.to_bytes());
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
     read_ledger_internal(&handle_string, None, ledger).await
   }
 
@@ -924,12 +1359,43 @@ impl LedgerStore for TableLedgerStore {
     handle: &Handle,
     index: usize,
   ) -> Result<LedgerEntry, LedgerStoreError> {
-    let ledger = self.client.clone();
-    let handle_string = base64_url::encode(&handle.to_bytes());
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
     let (ledger_entry, _height) = read_ledger_internal(&handle_string, Some(index), ledger).await?;
     Ok(ledger_entry)
   }
 
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
+    let partition_client = ledger.as_partition_key_client(&handle_string);
+    let row_client = match partition_client.as_entity_client(INFO) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in read_ledger_info: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let res = with_backoff(|| async { row_client.get().execute::<DBInfoEntry>().await }).await;
+    match res {
+      Ok(res) => Ok(LedgerInfo {
+        created_at: checked_conversion!(res.entity.created_at, u64),
+        metadata: string_decode(&res.entity.metadata)?,
+        handle_bytes: string_decode(&res.entity.handle_bytes)?,
+        app_bytes: string_decode(&res.entity.app_bytes)?,
+      }),
+      Err(err) => match parse_error_status(get_error_status!(err)) {
+        LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => {
+          // ledgers created before info was recorded have a TAIL row but no INFO row
+          find_db_entry(ledger, &handle_string, TAIL).await?;
+          Ok(LedgerInfo::default())
+        },
+        e => Err(e),
+      },
+    }
+  }
+
   async fn read_view_ledger_tail(&self) -> Result<(LedgerEntry, usize), LedgerStoreError> {
     self.read_ledger_tail(&self.view_handle).await
   }
@@ -959,13 +1425,348 @@ impl LedgerStore for TableLedgerStore {
     Ok(height)
   }
 
+  async fn list_handles(
+    &self,
+    _start_after: Option<&Handle>,
+    _limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError> {
+    // enumerating partition keys requires a full table scan, which this backend does not do
+    Err(LedgerStoreError::LedgerError(
+      StorageError::UnsupportedOperation,
+    ))
+  }
+
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError> {
+    // counting partitions requires a full table scan
+    Ok(None)
+  }
+
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError> {
+    // the table service does not report the size of a table
+    Ok(None)
+  }
+
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let handle_string = partition_key(handle);
+
+    // the TAIL row holds the tail, which is never purged
+    let (tail, _etag) = find_db_entry(ledger.clone(), &handle_string, TAIL).await?;
+    if checked_conversion!(before_height, i64) > tail.height {
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+    }
+
+    for idx in 0..before_height {
+      let index = row_key(checked_conversion!(idx, u64));
+      loop {
+        match purge_ledger_block_op(&handle_string, &index, ledger.clone()).await {
+          Ok(()) => break,
+          // a receipt was attached concurrently, so fetch the entry again
+          Err(LedgerStoreError::LedgerError(StorageError::ConcurrentOperation)) => {},
+          Err(e) => return Err(e),
+        }
+      }
+    }
+    Ok(())
+  }
+
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let partition_client = ledger.as_partition_key_client(tenant_partition_key(tenant));
+    let row_client = match partition_client.as_entity_client(TENANT) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in read_tenant: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let res = with_backoff(|| async { row_client.get().execute::<DBTenantEntry>().await }).await;
+    match res {
+      Ok(res) => Ok(TenantRecord {
+        max_ledgers: checked_conversion!(res.entity.max_ledgers, u64),
+        max_appends_per_sec: checked_conversion!(res.entity.max_appends_per_sec, u64),
+        max_stored_bytes: checked_conversion!(res.entity.max_stored_bytes, u64),
+        num_ledgers: checked_conversion!(res.entity.num_ledgers, u64),
+        stored_bytes: checked_conversion!(res.entity.stored_bytes, u64),
+      }),
+      Err(err) => match parse_error_status(get_error_status!(err)) {
+        LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => Ok(TenantRecord::default()),
+        e => Err(e),
+      },
+    }
+  }
+
+  async fn write_tenant(
+    &self,
+    tenant: &str,
+    record: &TenantRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let partition_client = ledger.as_partition_key_client(tenant_partition_key(tenant));
+    let row_client = match partition_client.as_entity_client(TENANT) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in write_tenant: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let entry = DBTenantEntry {
+      tenant: tenant_partition_key(tenant),
+      row: TENANT.to_owned(),
+      max_ledgers: checked_conversion!(record.max_ledgers, i64),
+      max_appends_per_sec: checked_conversion!(record.max_appends_per_sec, i64),
+      max_stored_bytes: checked_conversion!(record.max_stored_bytes, i64),
+      num_ledgers: checked_conversion!(record.num_ledgers, i64),
+      stored_bytes: checked_conversion!(record.stored_bytes, i64),
+    };
+    let res = with_backoff(|| async { row_client.insert_or_replace().execute(&entry).await }).await;
+    if let Err(err) = res {
+      return Err(parse_error_status(get_error_status!(err)));
+    }
+
+    Ok(())
+  }
+
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    Ok(
+      read_lease_entry(ledger)
+        .await?
+        .map(|(lease, _etag)| lease)
+        .unwrap_or_default(),
+    )
+  }
+
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let current = read_lease_entry(ledger.clone()).await?;
+    if current
+      .as_ref()
+      .map_or(*expected != LeaseRecord::default(), |(lease, _etag)| {
+        lease != expected
+      })
+    {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData,
+      ));
+    }
+
+    let entry = DBLeaseEntry {
+      partition: LEASE_PARTITION.to_owned(),
+      row: LEASE.to_owned(),
+      holder: new.holder.clone(),
+      expires_at: checked_conversion!(new.expires_at, i64),
+      epoch: checked_conversion!(new.epoch, i64),
+    };
+    // the etag of the lease that was read, or the absence of a lease, is the condition of the
+    // write, so a coordinator that swapped the lease in between makes it fail
+    let res = match current {
+      Some((_lease, etag)) => {
+        let partition_client = ledger.as_partition_key_client(LEASE_PARTITION);
+        let row_client = match partition_client.as_entity_client(LEASE) {
+          Ok(v) => v,
+          Err(e) => {
+            eprintln!("Error in swap_lease: {:?}", e);
+            return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+          },
+        };
+        let etag = IfMatchCondition::Etag(etag);
+        with_backoff(|| async { row_client.update().execute(&entry, &etag).await })
+          .await
+          .map(|_| ())
+      },
+      None => with_backoff(|| async { ledger.insert().execute(&entry).await })
+        .await
+        .map(|_| ()),
+    };
+    match res {
+      Ok(()) => Ok(()),
+      Err(err) => match parse_error_status(get_error_status!(err)) {
+        LedgerStoreError::LedgerError(StorageError::ConcurrentOperation)
+        | LedgerStoreError::LedgerError(StorageError::DuplicateKey) => Err(
+          LedgerStoreError::LedgerError(StorageError::IncorrectConditionalData),
+        ),
+        e => Err(e),
+      },
+    }
+  }
+
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let height = checked_conversion!(intent.height, u64);
+    let entry = DBIntentEntry {
+      partition: INTENT_PARTITION.to_owned(),
+      row: intent_row_key(&intent.handle, height),
+      handle: base64_url::encode(&intent.handle.to_bytes()),
+      height: checked_conversion!(height, i64),
+      block_hash: base64_url::encode(&intent.block_hash.to_bytes()),
+      request_id: intent.request_id.clone(),
+    };
+    let partition_client = ledger.as_partition_key_client(INTENT_PARTITION);
+    let row_client = match partition_client.as_entity_client(&entry.row) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in write_intent: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let res = with_backoff(|| async { row_client.insert_or_replace().execute(&entry).await }).await;
+    if let Err(err) = res {
+      return Err(parse_error_status(get_error_status!(err)));
+    }
+
+    Ok(())
+  }
+
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let row = intent_row_key(handle, checked_conversion!(height, u64));
+    let partition_client = ledger.as_partition_key_client(INTENT_PARTITION);
+    let row_client = match partition_client.as_entity_client(&row) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in commit_intent: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let res = with_backoff(|| async { row_client.delete().execute().await }).await;
+    match res {
+      Ok(_) => Ok(()),
+      Err(err) => match parse_error_status(get_error_status!(err)) {
+        LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => Ok(()),
+        e => Err(e),
+      },
+    }
+  }
+
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    // the intents are those of the appends in flight, which fit in the first page of the query
+    let filter = format!("PartitionKey eq '{}'", INTENT_PARTITION);
+    let res = with_backoff(|| async {
+      ledger
+        .query()
+        .filter(Filter::new(filter.as_str()))
+        .execute::<DBIntentEntry>()
+        .await
+    })
+    .await;
+    let entries = match res {
+      Ok(res) => res.entities,
+      Err(err) => return Err(parse_error_status(get_error_status!(err))),
+    };
+
+    let mut intents = Vec::with_capacity(entries.len());
+    for entry in entries {
+      let handle = Handle::from_bytes(&string_decode(&entry.handle)?)
+        .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?;
+      let block_hash = NimbleDigest::from_bytes(&string_decode(&entry.block_hash)?)
+        .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?;
+      intents.push(IntentRecord {
+        handle,
+        height: checked_conversion!(entry.height, usize),
+        block_hash,
+        request_id: entry.request_id,
+      });
+    }
+    Ok(intents)
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let partition_client = ledger.as_partition_key_client(partition_key(handle));
+    let row_client = match partition_client.as_entity_client(TOKENS) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in read_append_tokens: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let res =
+      with_backoff(|| async { row_client.get().execute::<DBAppendTokensEntry>().await }).await;
+    let entries: Vec<AppendTokenEntry> = match res {
+      Ok(res) => bincode::deserialize(&string_decode(&res.entity.tokens)?)
+        .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?,
+      Err(err) => {
+        return match parse_error_status(get_error_status!(err)) {
+          LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist) => Ok(vec![]),
+          e => Err(e),
+        }
+      },
+    };
+
+    let mut tokens = Vec::with_capacity(entries.len());
+    for entry in entries {
+      tokens.push(AppendToken {
+        request_id: entry.request_id,
+        height: checked_conversion!(entry.height, usize),
+        block_hash: NimbleDigest::from_bytes(&entry.block_hash)
+          .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?,
+      });
+    }
+    Ok(tokens)
+  }
+
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError> {
+    let ledger = self.table_client().await?;
+    let partition_client = ledger.as_partition_key_client(partition_key(handle));
+    let row_client = match partition_client.as_entity_client(TOKENS) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("Error in write_append_tokens: {:?}", e);
+        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+      },
+    };
+
+    let mut entries = Vec::with_capacity(tokens.len());
+    for token in tokens {
+      entries.push(AppendTokenEntry {
+        request_id: token.request_id.clone(),
+        height: checked_conversion!(token.height, u64),
+        block_hash: token.block_hash.to_bytes(),
+      });
+    }
+    let bytes = bincode::serialize(&entries)
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))?;
+    let entry = DBAppendTokensEntry {
+      handle: partition_key(handle),
+      row: TOKENS.to_owned(),
+      tokens: base64_url::encode(&bytes),
+    };
+    let res = with_backoff(|| async { row_client.insert_or_replace().execute(&entry).await }).await;
+    if let Err(err) = res {
+      return Err(parse_error_status(get_error_status!(err)));
+    }
+
+    Ok(())
+  }
+
   async fn reset_store(&self) -> Result<(), LedgerStoreError> {
-    let ledger = self.client.clone();
-    ledger
-      .delete()
-      .execute()
-      .await
-      .expect("failed to delete ledgers");
+    let ledger = self.table_client().await?;
+    let res = with_backoff(|| async { ledger.delete().execute().await }).await;
+    if let Err(err) = res {
+      return Err(parse_error_status(get_error_status!(err)));
+    }
 
     Ok(())
   }
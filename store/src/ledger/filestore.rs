@@ -1,25 +1,80 @@
+//! A `LedgerStore` for single-node deployments that keeps every ledger in a local directory.
+//!
+//! Directory layout (`<stem>` is the hex encoding of a handle, or `view` for the view ledger):
+//!
+//! * `LOCK` is held exclusively by the process that has the store open.
+//! * `<stem>.blocks` is an append-only log with one record (block and nonces) per entry.
+//! * `<stem>.receipts` is an append-only log of the receipts attached to entries.
+//! * `<stem>.tail` holds the committed height and the committed length of the blocks log.
+//! * `<stem>.info` holds the creation time, metadata, and client handle of the ledger; it is
+//!   written before the first tail file and never changes.
+//! * `<stem>.purged` holds the hashes of the blocks whose contents were purged, which are the
+//!   first entries of the ledger.
+//! * `<stem>.tokens` holds the request IDs that the coordinator retains for the appends to the
+//!   ledger.
+//! * `intent-<stem>-<height>.intent` holds the intent of an append at `<height>` of a ledger
+//!   until the append is committed.
+//! * `coordinator.lease` holds the lease of the coordinators; since `LOCK` keeps other processes
+//!   out of the directory, a standby coordinator cannot share the store with the coordinator
+//!   that holds the lease.
+//!
+//! Every log record is framed as `[payload length: u32 LE][SHA-256 of payload][payload]`, so a
+//! torn write shows up as a short frame or a checksum mismatch. An append is committed once the
+//! blocks log is synced and the new tail file has been renamed into place; when the store is
+//! opened, bytes past the committed length and torn final receipts are truncated, and any other
+//! inconsistency fails with `StorageError::CorruptedLedger`.
+//!
+//! Purging overwrites the blocks of the purged records with zeros in place, after the purged file
+//! that records their hashes has been renamed into place; the records keep their lengths, so the
+//! offsets and the tail stay valid, and recovery finishes the overwrite if it was interrupted.
 use crate::{
   errors::{LedgerStoreError, StorageError},
-  ledger::{LedgerEntry, LedgerStore},
+  ledger::{
+    decode_stored_receipts, paginate_handles, AppendToken, IntentRecord, LeaseRecord, LedgerEntry,
+    LedgerInfo, LedgerStore, TenantRecord,
+  },
 };
 use async_trait::async_trait;
-use bincode;
 use fs2::FileExt;
-use hex;
-use ledger::{Block, CustomSerde, Handle, NimbleDigest, Nonce, Nonces, Receipts};
+use ledger::{Block, CustomSerde, Handle, NimbleDigest, NimbleHashTrait, Nonce, Nonces, Receipts};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
-  collections::HashMap,
+  collections::{hash_map, HashMap},
   convert::TryFrom,
-  fmt::Debug,
   fs,
   fs::{File, OpenOptions},
   io::{prelude::*, SeekFrom},
   path::{Path, PathBuf},
-  sync::{Arc, RwLock},
+  sync::{Arc, Mutex, RwLock},
 };
 
-const ENTRY_SIZE: usize = 1024; // total bytes in a ledger entry
+const LOCK_FILE: &str = "LOCK";
+const VIEW_STEM: &str = "view";
+const BLOCKS_EXT: &str = "blocks";
+const RECEIPTS_EXT: &str = "receipts";
+const TAIL_EXT: &str = "tail";
+const TAIL_TMP_EXT: &str = "tail.tmp";
+const INFO_EXT: &str = "info";
+const TENANT_STEM_PREFIX: &str = "tenant-";
+const TENANT_EXT: &str = "tenant";
+const TENANT_TMP_EXT: &str = "tenant.tmp";
+const PURGED_EXT: &str = "purged";
+const PURGED_TMP_EXT: &str = "purged.tmp";
+const LEASE_STEM: &str = "coordinator";
+const LEASE_EXT: &str = "lease";
+const LEASE_TMP_EXT: &str = "lease.tmp";
+const INTENT_STEM_PREFIX: &str = "intent-";
+const INTENT_EXT: &str = "intent";
+const INTENT_TMP_EXT: &str = "intent.tmp";
+const TOKENS_EXT: &str = "tokens";
+const TOKENS_TMP_EXT: &str = "tokens.tmp";
+
+const LEN_SIZE: usize = 4;
+const CHECKSUM_SIZE: usize = 32;
+const RECORD_HEADER_SIZE: usize = LEN_SIZE + CHECKSUM_SIZE;
+// bincode prefixes the block of a `StoreEntry` with its length as a u64
+const BLOCK_LEN_SIZE: usize = 8;
 
 macro_rules! checked_conversion {
   ($x:expr, $type:tt) => {
@@ -32,20 +87,110 @@ macro_rules! checked_conversion {
   };
 }
 
-type FileLock = Arc<RwLock<File>>;
-type FileMap = Arc<RwLock<HashMap<Handle, FileLock>>>;
-
+/// a record of the blocks log
 #[derive(Clone, Serialize, Deserialize, Debug)]
 struct StoreEntry {
   pub block: Vec<u8>,
+  pub nonces: Vec<u8>,
+}
+
+/// a record of the receipts log
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct ReceiptsEntry {
+  pub idx: u64,
   pub receipts: Vec<u8>,
 }
 
+/// the contents of a tail file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct TailEntry {
+  pub height: u64,
+  pub blocks_len: u64,
+}
+
+/// the contents of an info file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct InfoEntry {
+  pub created_at: u64,
+  pub metadata: Vec<u8>,
+  pub handle_bytes: Vec<u8>,
+  pub app_bytes: Vec<u8>,
+}
+
+/// the contents of a tenant file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct TenantEntry {
+  pub max_ledgers: u64,
+  pub max_appends_per_sec: u64,
+  pub max_stored_bytes: u64,
+  pub num_ledgers: u64,
+  pub stored_bytes: u64,
+}
+
+/// the contents of a purged file
+/// the contents of the lease file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct LeaseEntry {
+  pub holder: String,
+  pub expires_at: u64,
+  pub epoch: u64,
+}
+
+/// the contents of an intent file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct IntentEntry {
+  pub handle: Vec<u8>,
+  pub height: u64,
+  pub block_hash: Vec<u8>,
+  pub request_id: String,
+}
+
+/// a request ID in a tokens file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct AppendTokenEntry {
+  pub request_id: String,
+  pub height: u64,
+  pub block_hash: Vec<u8>,
+}
+
+/// the contents of a tokens file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct AppendTokensEntry {
+  pub tokens: Vec<AppendTokenEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct PurgedEntry {
+  pub block_hashes: Vec<Vec<u8>>,
+}
+
+/// in-memory index of a ledger whose entries live on disk
+#[derive(Debug, Default)]
+struct LedgerState {
+  stem: String,
+  // offset of every committed entry in the blocks log
+  offsets: Vec<u64>,
+  blocks_len: u64,
+  receipts_len: u64,
+  receipts: Vec<Receipts>,
+  // nonces attached since the last append; they are persisted with the next entry
+  nonces: Vec<Nonce>,
+  info: LedgerInfo,
+  // hashes of the blocks of the first entries, whose contents were purged
+  purged: Vec<NimbleDigest>,
+}
+
+type LedgerLock = Arc<RwLock<LedgerState>>;
+
 #[derive(Debug)]
 pub struct FileStore {
   dir_path: PathBuf,
-  open_files: FileMap,
-  view_handle: Handle,
+  ledgers: Arc<RwLock<HashMap<Handle, LedgerLock>>>,
+  view_ledger: LedgerLock,
+  // held while the lease is read and swapped
+  lease: Mutex<()>,
+  // keeps the exclusive lock on the directory for the lifetime of the store
+  _dir_lock: File,
 }
 
 impl FileStore {
@@ -57,261 +202,741 @@ impl FileStore {
     }
     let dir_path = Path::new(&args["NIMBLE_FSTORE_DIR"]).to_path_buf();
 
-    let view_handle = match NimbleDigest::from_bytes(&vec![0u8; NimbleDigest::num_bytes()]) {
-      Ok(e) => e,
+    // Try to create directory. If it exists that's fine.
+    if let Err(e) = fs::create_dir_all(&dir_path) {
+      eprintln!("Unable to create path {:?}, error: {:?}", &dir_path, e);
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidDBName));
+    }
+
+    let dir_lock = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(false)
+      .open(dir_path.join(LOCK_FILE))
+      .map_err(io_error("open the lock file"))?;
+    if dir_lock.try_lock_exclusive().is_err() {
+      eprintln!("The directory {:?} is in use by another store", &dir_path);
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerWriteLockFailed,
+      ));
+    }
+
+    let (mut ledgers, view_ledger) = fsck(&dir_path)?;
+
+    // Initialize the view ledger on first use
+    let view_ledger = match view_ledger {
+      Some(v) => v,
+      None => create_ledger_files(
+        &dir_path,
+        VIEW_STEM,
+        &Block::new(&[0; 0]),
+        &LedgerInfo::default(),
+      )?,
+    };
+
+    Ok(FileStore {
+      dir_path,
+      ledgers: Arc::new(RwLock::new(
+        ledgers
+          .drain()
+          .map(|(handle, state)| (handle, Arc::new(RwLock::new(state))))
+          .collect(),
+      )),
+      view_ledger: Arc::new(RwLock::new(view_ledger)),
+      lease: Mutex::new(()),
+      _dir_lock: dir_lock,
+    })
+  }
+
+  fn get_ledger(&self, handle: &Handle) -> Result<LedgerLock, LedgerStoreError> {
+    let ledgers = match self.ledgers.read() {
+      Ok(m) => m,
       Err(_) => {
         return Err(LedgerStoreError::LedgerError(
-          StorageError::DeserializationError,
+          StorageError::LedgerMapReadLockFailed,
         ));
       },
     };
 
-    // Try to create directory. If it exists that's fine.
-    match fs::create_dir_all(&dir_path) {
-      Ok(()) => (),
-      Err(e) => {
-        eprintln!("Unable to create path {:?}, error: {:?}", &dir_path, e);
-        return Err(LedgerStoreError::LedgerError(StorageError::InvalidDBName));
+    match ledgers.get(handle) {
+      Some(ledger) => Ok(ledger.clone()),
+      None => Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)),
+    }
+  }
+
+  fn append_op(
+    &self,
+    ledger: &LedgerLock,
+    block: &Block,
+    expected_height: usize,
+  ) -> Result<(usize, Nonces), LedgerStoreError> {
+    let mut state = match ledger.write() {
+      Ok(s) => s,
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::LedgerWriteLockFailed,
+        ));
       },
     };
 
-    let open_files = Arc::new(RwLock::new(HashMap::new()));
+    // 1. check if condition holds
+    if expected_height != state.offsets.len() {
+      eprintln!(
+        "Expected height {};  Height-plus-one: {}",
+        expected_height,
+        state.offsets.len()
+      );
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData,
+      ));
+    }
+
+    // 2. make the new entry durable
+    let nonces = Nonces::from_vec(state.nonces.clone());
+    let record = frame_record(&serialize(&StoreEntry {
+      block: block.to_bytes(),
+      nonces: nonces.to_bytes(),
+    })?)?;
+    let blocks_path = file_path(&self.dir_path, &state.stem, BLOCKS_EXT);
+    let blocks_len = append_record(&blocks_path, state.blocks_len, &record)?;
+
+    // 3. commit it by moving the tail
+    write_tail(
+      &self.dir_path,
+      &state.stem,
+      &TailEntry {
+        height: checked_conversion!(expected_height, u64),
+        blocks_len,
+      },
+    )?;
 
-    // Check if the view ledger exists, if not, create a new one
-    let ledger_lock = open_and_lock(&view_handle, &dir_path, &open_files, true)?;
+    let offset = state.blocks_len;
+    state.offsets.push(offset);
+    state.blocks_len = blocks_len;
+    state.receipts.push(Receipts::new());
+    state.nonces.clear();
 
-    let mut view_ledger = match ledger_lock.write() {
-      Ok(v) => v,
+    Ok((expected_height, nonces))
+  }
+
+  fn attach_receipts_op(
+    &self,
+    ledger: &LedgerLock,
+    idx: usize,
+    receipts: &Receipts,
+  ) -> Result<(), LedgerStoreError> {
+    let mut state = match ledger.write() {
+      Ok(s) => s,
       Err(_) => {
         return Err(LedgerStoreError::LedgerError(
-          StorageError::ViewLedgerWriteLockFailed,
+          StorageError::LedgerWriteLockFailed,
         ));
       },
     };
 
-    let file_len = match view_ledger.metadata() {
-      Ok(m) => m.len(),
-      Err(e) => {
-        eprintln!("Failed to access file metadata {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+    if idx >= state.offsets.len() {
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+    }
+
+    let record = frame_record(&serialize(&ReceiptsEntry {
+      idx: checked_conversion!(idx, u64),
+      receipts: receipts.to_bytes(),
+    })?)?;
+    let receipts_path = file_path(&self.dir_path, &state.stem, RECEIPTS_EXT);
+    state.receipts_len = append_record(&receipts_path, state.receipts_len, &record)?;
+    state.receipts[idx].merge_receipts(receipts);
+
+    Ok(())
+  }
+
+  fn read_op(
+    &self,
+    ledger: &LedgerLock,
+    req_idx: Option<usize>,
+  ) -> Result<(LedgerEntry, usize), LedgerStoreError> {
+    let state = match ledger.read() {
+      Ok(s) => s,
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::LedgerReadLockFailed,
+        ));
       },
     };
 
-    // If file is empty
-    if file_len == 0 {
-      // Initialized view ledger's entry
-      let entry = StoreEntry {
-        block: Block::new(&[0; 0]).to_bytes(),
-        receipts: Receipts::new().to_bytes(),
-      };
+    let idx = req_idx.unwrap_or(state.offsets.len() - 1);
+    if idx >= state.offsets.len() {
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+    }
 
-      // Guaranteed to be the size of 1 file entry
-      let ser_entry = serialize_entry(&entry)?;
+    // the record of an entry ends where the next one starts
+    let start = state.offsets[idx];
+    let end = state
+      .offsets
+      .get(idx + 1)
+      .copied()
+      .unwrap_or(state.blocks_len);
+
+    let mut bytes = vec![0; checked_conversion!(end - start, usize)];
+    let blocks_path = file_path(&self.dir_path, &state.stem, BLOCKS_EXT);
+    let mut blocks = File::open(&blocks_path).map_err(io_error("open the blocks log"))?;
+    blocks
+      .seek(SeekFrom::Start(start))
+      .map_err(io_error("seek the blocks log"))?;
+    blocks
+      .read_exact(&mut bytes)
+      .map_err(io_error("read the blocks log"))?;
+
+    let (block, nonces) = match parse_records(&bytes).0.as_slice() {
+      [(_, payload)] => parse_store_entry(payload)?,
+      _ => {
+        eprintln!("Entry {} of {} fails its checksum", idx, state.stem);
+        return Err(LedgerStoreError::LedgerError(StorageError::CorruptedLedger));
+      },
+    };
 
-      write_at(SeekFrom::Start(0), &mut view_ledger, &ser_entry)?;
-    }
+    let entry = match state.purged.get(idx) {
+      Some(block_hash) => LedgerEntry::new_purged(*block_hash, state.receipts[idx].clone(), nonces),
+      None => LedgerEntry::new(block, state.receipts[idx].clone(), Some(nonces)),
+    };
+    Ok((entry, idx))
+  }
 
-    let file_store = FileStore {
-      dir_path,
-      open_files,
-      view_handle,
+  fn purge_op(&self, ledger: &LedgerLock, before_height: usize) -> Result<(), LedgerStoreError> {
+    let mut state = match ledger.write() {
+      Ok(s) => s,
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::LedgerWriteLockFailed,
+        ));
+      },
     };
 
-    Ok(file_store)
+    if before_height >= state.offsets.len() {
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+    }
+    if before_height <= state.purged.len() {
+      return Ok(());
+    }
+
+    // 1. record the hashes of the blocks to purge
+    let end = checked_conversion!(state.offsets[before_height], usize);
+    let blocks_path = file_path(&self.dir_path, &state.stem, BLOCKS_EXT);
+    let mut bytes = vec![0; end];
+    let mut blocks = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .open(&blocks_path)
+      .map_err(io_error("open the blocks log"))?;
+    blocks
+      .read_exact(&mut bytes)
+      .map_err(io_error("read the blocks log"))?;
+
+    let (records, _) = parse_records(&bytes);
+    if records.len() != before_height {
+      return Err(corrupted(&state.stem, "entry fails its checksum"));
+    }
+    let mut purged = state.purged.clone();
+    for (_, payload) in &records[purged.len()..] {
+      purged.push(parse_store_entry(payload)?.0.hash());
+    }
+    write_purged(&self.dir_path, &state.stem, &purged)?;
+    state.purged = purged;
+
+    // 2. drop the contents of the blocks
+    zero_purged_blocks(&mut bytes, before_height);
+    blocks
+      .seek(SeekFrom::Start(0))
+      .map_err(io_error("seek the blocks log"))?;
+    blocks
+      .write_all(&bytes)
+      .map_err(io_error("write the blocks log"))?;
+    blocks.sync_data().map_err(io_error("sync the blocks log"))
   }
 }
 
-fn serialize_entry(entry: &StoreEntry) -> Result<Vec<u8>, LedgerStoreError> {
-  match bincode::serialize(&entry) {
-    Ok(mut e) => {
-      if e.len() < ENTRY_SIZE {
-        e.resize(ENTRY_SIZE, 0);
-        Ok(e)
-      } else {
-        Err(LedgerStoreError::LedgerError(StorageError::DataTooLarge))
-      }
-    },
+fn io_error(what: &'static str) -> impl Fn(std::io::Error) -> LedgerStoreError {
+  move |e| {
+    eprintln!("Failed to {}: {:?}", what, e);
+    LedgerStoreError::LedgerError(StorageError::UnhandledError)
+  }
+}
 
-    Err(_) => Err(LedgerStoreError::LedgerError(
-      StorageError::SerializationError,
-    )),
+fn corrupted(stem: &str, reason: &str) -> LedgerStoreError {
+  eprintln!("Ledger {} is corrupted: {}", stem, reason);
+  LedgerStoreError::LedgerError(StorageError::CorruptedLedger)
+}
+
+/// tenants are named by the hex encoding of their id, which cannot be taken for a handle
+fn tenant_stem(tenant: &str) -> String {
+  format!("{}{}", TENANT_STEM_PREFIX, hex::encode(tenant))
+}
+
+/// intents are named by their ledger and height, which cannot be taken for a handle either
+fn intent_stem(handle: &Handle, height: usize) -> String {
+  format!(
+    "{}{}-{}",
+    INTENT_STEM_PREFIX,
+    hex::encode(handle.to_bytes()),
+    height
+  )
+}
+
+fn file_path(dir_path: &Path, stem: &str, ext: &str) -> PathBuf {
+  dir_path.join(format!("{}.{}", stem, ext))
+}
+
+fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, LedgerStoreError> {
+  bincode::serialize(value)
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))
+}
+
+fn deserialize<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, LedgerStoreError> {
+  bincode::deserialize(bytes)
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))
+}
+
+fn frame_record(payload: &[u8]) -> Result<Vec<u8>, LedgerStoreError> {
+  let len = match u32::try_from(payload.len()) {
+    Ok(l) => l,
+    Err(_) => return Err(LedgerStoreError::LedgerError(StorageError::DataTooLarge)),
+  };
+
+  let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+  record.extend_from_slice(&len.to_le_bytes());
+  record.extend_from_slice(&Sha256::digest(payload));
+  record.extend_from_slice(payload);
+  Ok(record)
+}
+
+/// splits `bytes` into records, stopping at the first short or corrupted one; returns the
+/// offset and payload of every valid record and the length of the valid prefix
+fn parse_records(bytes: &[u8]) -> (Vec<(u64, &[u8])>, usize) {
+  let mut records = Vec::new();
+  let mut pos = 0;
+  while bytes.len() - pos >= RECORD_HEADER_SIZE {
+    let mut len = [0u8; LEN_SIZE];
+    len.copy_from_slice(&bytes[pos..pos + LEN_SIZE]);
+    let len = u32::from_le_bytes(len) as usize;
+    let start = pos + RECORD_HEADER_SIZE;
+    if bytes.len() - start < len {
+      break;
+    }
+    let payload = &bytes[start..start + len];
+    if Sha256::digest(payload).as_slice() != &bytes[pos + LEN_SIZE..start] {
+      break;
+    }
+    records.push((pos as u64, payload));
+    pos = start + len;
   }
+  (records, pos)
 }
 
-// reads value into buf
-fn read_at(index: SeekFrom, ledger: &mut File, buf: &mut [u8]) -> Result<(), LedgerStoreError> {
-  match ledger.seek(index) {
-    Ok(_) => {},
-    Err(e) => {
-      eprintln!("Failed to seek {:?}", e);
-      return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-    },
+fn parse_store_entry(payload: &[u8]) -> Result<(Block, Nonces), LedgerStoreError> {
+  let entry: StoreEntry = deserialize(payload)?;
+  match (
+    Block::from_bytes(&entry.block),
+    Nonces::from_bytes(&entry.nonces),
+  ) {
+    (Ok(block), Ok(nonces)) => Ok((block, nonces)),
+    _ => Err(LedgerStoreError::LedgerError(
+      StorageError::DeserializationError,
+    )),
   }
+}
 
-  match ledger.read(buf) {
-    Ok(n) => {
-      if n != ENTRY_SIZE {
-        eprintln!("Read only {} bytes instead of {}", n, ENTRY_SIZE);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-      }
-    },
-    Err(e) => {
-      eprintln!("Failed to read {:?}", e);
-      return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-    },
+/// writes `record` at offset `len` of the log at `path`, dropping anything past `len` left by
+/// a failed write, and syncs it; returns the new length of the log
+fn append_record(path: &Path, len: u64, record: &[u8]) -> Result<u64, LedgerStoreError> {
+  let mut log = OpenOptions::new()
+    .write(true)
+    .open(path)
+    .map_err(io_error("open a log"))?;
+  log.set_len(len).map_err(io_error("truncate a log"))?;
+  log
+    .seek(SeekFrom::Start(len))
+    .map_err(io_error("seek a log"))?;
+  log.write_all(record).map_err(io_error("write a log"))?;
+  log.sync_data().map_err(io_error("sync a log"))?;
+
+  match len.checked_add(checked_conversion!(record.len(), u64)) {
+    Some(l) => Ok(l),
+    None => Err(LedgerStoreError::LedgerError(StorageError::IntegerOverflow)),
   }
+}
 
-  Ok(())
+fn sync_dir(dir_path: &Path) -> Result<(), LedgerStoreError> {
+  File::open(dir_path)
+    .and_then(|d| d.sync_all())
+    .map_err(io_error("sync the store directory"))
 }
 
-fn write_at(index: SeekFrom, ledger: &mut File, buf: &[u8]) -> Result<(), LedgerStoreError> {
-  match ledger.seek(index) {
-    Ok(_) => {},
-    Err(e) => {
-      eprintln!("Failed to seek {:?}", e);
-      return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-    },
+/// overwrites the blocks of the first `num_purged` records in `bytes` with zeros and recomputes
+/// their checksums; the lengths of the records do not change, and neither do records whose
+/// blocks are already zeros. Returns whether `bytes` changed
+fn zero_purged_blocks(bytes: &mut [u8], num_purged: usize) -> bool {
+  let mut changed = false;
+  let mut pos = 0;
+  for _ in 0..num_purged {
+    if bytes.len() - pos < RECORD_HEADER_SIZE {
+      break;
+    }
+    let mut len = [0u8; LEN_SIZE];
+    len.copy_from_slice(&bytes[pos..pos + LEN_SIZE]);
+    let len = u32::from_le_bytes(len) as usize;
+    let start = pos + RECORD_HEADER_SIZE;
+    if bytes.len() - start < len || len < BLOCK_LEN_SIZE {
+      break;
+    }
+    let mut block_len = [0u8; BLOCK_LEN_SIZE];
+    block_len.copy_from_slice(&bytes[start..start + BLOCK_LEN_SIZE]);
+    let block_len = u64::from_le_bytes(block_len) as usize;
+    if len - BLOCK_LEN_SIZE < block_len {
+      break;
+    }
+
+    let block = start + BLOCK_LEN_SIZE..start + BLOCK_LEN_SIZE + block_len;
+    if bytes[block.clone()].iter().any(|b| *b != 0)
+      || Sha256::digest(&bytes[start..start + len]).as_slice() != &bytes[pos + LEN_SIZE..start]
+    {
+      bytes[block].fill(0);
+      let checksum = Sha256::digest(&bytes[start..start + len]);
+      bytes[pos + LEN_SIZE..start].copy_from_slice(&checksum);
+      changed = true;
+    }
+    pos = start + len;
   }
+  changed
+}
 
-  match ledger.write(buf) {
-    Ok(n) => {
-      if n != ENTRY_SIZE {
-        eprintln!("Wrote only {} bytes instead of {}", n, ENTRY_SIZE);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-      }
-    },
-    Err(e) => {
-      eprintln!("Failed to write {:?}", e);
-      return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-    },
+/// replaces the purged file of a ledger atomically, like its tail file
+fn write_purged(
+  dir_path: &Path,
+  stem: &str,
+  purged: &[NimbleDigest],
+) -> Result<(), LedgerStoreError> {
+  let record = frame_record(&serialize(&PurgedEntry {
+    block_hashes: purged.iter().map(|h| h.to_bytes()).collect(),
+  })?)?;
+  let tmp_path = file_path(dir_path, stem, PURGED_TMP_EXT);
+
+  let mut tmp = File::create(&tmp_path).map_err(io_error("create a purged file"))?;
+  tmp
+    .write_all(&record)
+    .map_err(io_error("write a purged file"))?;
+  tmp.sync_all().map_err(io_error("sync a purged file"))?;
+  drop(tmp);
+
+  fs::rename(&tmp_path, file_path(dir_path, stem, PURGED_EXT))
+    .map_err(io_error("rename a purged file"))?;
+  sync_dir(dir_path)
+}
+
+/// reads the lease file, or returns the default lease if there is none
+fn read_lease_file(dir_path: &Path) -> Result<LeaseRecord, LedgerStoreError> {
+  let path = file_path(dir_path, LEASE_STEM, LEASE_EXT);
+  if !path.exists() {
+    return Ok(LeaseRecord::default());
   }
+  let bytes = fs::read(&path).map_err(io_error("read the lease file"))?;
+  let entry: LeaseEntry = match parse_records(&bytes) {
+    (records, len) if records.len() == 1 && len == bytes.len() => {
+      deserialize(records[0].1).map_err(|_| corrupted(LEASE_STEM, "unreadable lease"))?
+    },
+    _ => return Err(corrupted(LEASE_STEM, "lease fails its checksum")),
+  };
+  Ok(LeaseRecord {
+    holder: entry.holder,
+    expires_at: entry.expires_at,
+    epoch: entry.epoch,
+  })
+}
 
-  Ok(())
+/// replaces the tail file of a ledger atomically by writing a temporary file and renaming it
+fn write_tail(dir_path: &Path, stem: &str, tail: &TailEntry) -> Result<(), LedgerStoreError> {
+  let record = frame_record(&serialize(tail)?)?;
+  let tmp_path = file_path(dir_path, stem, TAIL_TMP_EXT);
+
+  let mut tmp = File::create(&tmp_path).map_err(io_error("create a tail file"))?;
+  tmp
+    .write_all(&record)
+    .map_err(io_error("write a tail file"))?;
+  tmp.sync_all().map_err(io_error("sync a tail file"))?;
+  drop(tmp);
+
+  fs::rename(&tmp_path, file_path(dir_path, stem, TAIL_EXT))
+    .map_err(io_error("rename a tail file"))?;
+  sync_dir(dir_path)
 }
 
-fn open_and_lock(
-  handle: &Handle,
+/// creates the files of a ledger holding only `genesis_block`; the ledger exists once its tail
+/// file does
+fn create_ledger_files(
   dir_path: &Path,
-  file_map: &FileMap,
-  create_flag: bool,
-) -> Result<FileLock, LedgerStoreError> {
-  let map = match file_map.read() {
-    Ok(m) => m,
-    Err(_) => {
-      return Err(LedgerStoreError::LedgerError(
-        StorageError::LedgerReadLockFailed,
-      ));
+  stem: &str,
+  genesis_block: &Block,
+  info: &LedgerInfo,
+) -> Result<LedgerState, LedgerStoreError> {
+  let record = frame_record(&serialize(&StoreEntry {
+    block: genesis_block.to_bytes(),
+    nonces: Nonces::new().to_bytes(),
+  })?)?;
+
+  let info_record = frame_record(&serialize(&InfoEntry {
+    created_at: info.created_at,
+    metadata: info.metadata.clone(),
+    handle_bytes: info.handle_bytes.clone(),
+    app_bytes: info.app_bytes.clone(),
+  })?)?;
+  let mut info_file =
+    File::create(file_path(dir_path, stem, INFO_EXT)).map_err(io_error("create an info file"))?;
+  info_file
+    .write_all(&info_record)
+    .map_err(io_error("write an info file"))?;
+  info_file
+    .sync_all()
+    .map_err(io_error("sync an info file"))?;
+
+  File::create(file_path(dir_path, stem, RECEIPTS_EXT))
+    .map_err(io_error("create a receipts log"))?;
+  File::create(file_path(dir_path, stem, BLOCKS_EXT)).map_err(io_error("create a blocks log"))?;
+  let blocks_len = append_record(&file_path(dir_path, stem, BLOCKS_EXT), 0, &record)?;
+
+  write_tail(
+    dir_path,
+    stem,
+    &TailEntry {
+      height: 0,
+      blocks_len,
     },
+  )?;
+
+  Ok(LedgerState {
+    stem: stem.to_string(),
+    offsets: vec![0],
+    blocks_len,
+    receipts_len: 0,
+    receipts: vec![Receipts::new()],
+    nonces: Vec::new(),
+    info: info.clone(),
+    purged: Vec::new(),
+  })
+}
+
+/// validates the files of a ledger against its tail, truncates uncommitted or torn data, and
+/// rebuilds its in-memory index
+fn recover_ledger(dir_path: &Path, stem: &str) -> Result<LedgerState, LedgerStoreError> {
+  // 1. Read the committed tail
+  let tail_bytes =
+    fs::read(file_path(dir_path, stem, TAIL_EXT)).map_err(io_error("read a tail file"))?;
+  let tail: TailEntry = match parse_records(&tail_bytes) {
+    (records, len) if records.len() == 1 && len == tail_bytes.len() => {
+      deserialize(records[0].1).map_err(|_| corrupted(stem, "unreadable tail"))?
+    },
+    _ => return Err(corrupted(stem, "tail fails its checksum")),
   };
 
-  if let Some(entry) = map.get(handle) {
-    Ok(entry.clone())
+  // 2. Read the hashes of the purged blocks and finish purging their contents
+  let purged_path = file_path(dir_path, stem, PURGED_EXT);
+  let purged = if purged_path.exists() {
+    let purged_bytes = fs::read(&purged_path).map_err(io_error("read a purged file"))?;
+    let entry: PurgedEntry = match parse_records(&purged_bytes) {
+      (records, len) if records.len() == 1 && len == purged_bytes.len() => {
+        deserialize(records[0].1).map_err(|_| corrupted(stem, "unreadable purged hashes"))?
+      },
+      _ => return Err(corrupted(stem, "purged hashes fail their checksum")),
+    };
+    entry
+      .block_hashes
+      .iter()
+      .map(|h| NimbleDigest::from_bytes(h))
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|_| corrupted(stem, "unreadable purged hashes"))?
   } else {
-    drop(map); // drops read lock on map
+    Vec::new()
+  };
 
-    // Check if the ledger exists.
-    let mut options = OpenOptions::new();
-    let file_name = dir_path.join(&hex::encode(&handle.to_bytes()));
-    let ledger = match options
-      .read(true)
+  let blocks_path = file_path(dir_path, stem, BLOCKS_EXT);
+  let mut blocks_bytes = fs::read(&blocks_path).map_err(io_error("read a blocks log"))?;
+  let blocks_len = checked_conversion!(tail.blocks_len, usize);
+  if blocks_bytes.len() < blocks_len {
+    return Err(corrupted(stem, "blocks log is shorter than its tail"));
+  }
+  if zero_purged_blocks(&mut blocks_bytes[..blocks_len], purged.len()) {
+    eprintln!("Finishing an interrupted purge of ledger {}", stem);
+    let mut blocks = OpenOptions::new()
       .write(true)
-      .create(create_flag)
-      .open(&file_name)
-    {
-      Ok(f) => f,
-      Err(e) => {
-        eprintln!("Error opening view file {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::InvalidKey));
-      },
-    };
+      .open(&blocks_path)
+      .map_err(io_error("open a blocks log"))?;
+    blocks
+      .write_all(&blocks_bytes[..blocks_len])
+      .map_err(io_error("write a blocks log"))?;
+    blocks.sync_data().map_err(io_error("sync a blocks log"))?;
+  }
 
-    // Acquire exclusive lock on file
-    if ledger.try_lock_exclusive().is_err() {
-      return Err(LedgerStoreError::LedgerError(
-        StorageError::LedgerWriteLockFailed,
-      ));
+  // 3. Check that every committed entry is intact and drop whatever follows them
+
+  let (records, valid_len) = parse_records(&blocks_bytes[..blocks_len]);
+  if valid_len != blocks_len {
+    return Err(corrupted(stem, "committed entry fails its checksum"));
+  }
+  if records.len() as u64 != tail.height + 1 {
+    return Err(corrupted(stem, "blocks log does not match the tail height"));
+  }
+  if purged.len() >= records.len() {
+    return Err(corrupted(stem, "purged hashes cover the tail"));
+  }
+  for (_, payload) in &records {
+    parse_store_entry(payload).map_err(|_| corrupted(stem, "unreadable entry"))?;
+  }
+
+  if blocks_bytes.len() > blocks_len {
+    eprintln!(
+      "Truncating {} uncommitted bytes of ledger {}",
+      blocks_bytes.len() - blocks_len,
+      stem
+    );
+    truncate(&blocks_path, tail.blocks_len)?;
+  }
+
+  // 4. Replay the receipts, dropping a torn final record
+  let receipts_path = file_path(dir_path, stem, RECEIPTS_EXT);
+  let receipts_bytes = fs::read(&receipts_path).map_err(io_error("read a receipts log"))?;
+  let (receipt_records, receipts_len) = parse_records(&receipts_bytes);
+
+  let mut receipts = vec![Receipts::new(); records.len()];
+  for (_, payload) in receipt_records {
+    let entry: ReceiptsEntry =
+      deserialize(payload).map_err(|_| corrupted(stem, "unreadable receipts"))?;
+    let idx = checked_conversion!(entry.idx, usize);
+    if idx >= receipts.len() {
+      return Err(corrupted(stem, "receipts for an uncommitted entry"));
     }
+    let r = decode_stored_receipts(&entry.receipts)
+      .map_err(|_| corrupted(stem, "unreadable receipts"))?;
+    receipts[idx].merge_receipts(&r);
+  }
 
-    let mut map = match file_map.write() {
-      Ok(v) => v,
-      Err(_) => {
-        return Err(LedgerStoreError::LedgerError(
-          StorageError::LedgerWriteLockFailed,
-        ));
+  if receipts_bytes.len() > receipts_len {
+    eprintln!(
+      "Truncating {} torn bytes of the receipts of ledger {}",
+      receipts_bytes.len() - receipts_len,
+      stem
+    );
+    truncate(&receipts_path, checked_conversion!(receipts_len, u64))?;
+  }
+
+  // 5. Read the info, which ledgers created before it was recorded do not have
+  let info_path = file_path(dir_path, stem, INFO_EXT);
+  let info = if info_path.exists() {
+    let info_bytes = fs::read(&info_path).map_err(io_error("read an info file"))?;
+    let entry: InfoEntry = match parse_records(&info_bytes) {
+      (records, len) if records.len() == 1 && len == info_bytes.len() => {
+        deserialize(records[0].1).map_err(|_| corrupted(stem, "unreadable info"))?
       },
+      _ => return Err(corrupted(stem, "info fails its checksum")),
     };
+    LedgerInfo {
+      created_at: entry.created_at,
+      metadata: entry.metadata,
+      handle_bytes: entry.handle_bytes,
+      app_bytes: entry.app_bytes,
+    }
+  } else {
+    LedgerInfo::default()
+  };
 
-    let ledger_arc = Arc::new(RwLock::new(ledger));
+  Ok(LedgerState {
+    stem: stem.to_string(),
+    offsets: records.iter().map(|(offset, _)| *offset).collect(),
+    blocks_len: tail.blocks_len,
+    receipts_len: checked_conversion!(receipts_len, u64),
+    receipts,
+    nonces: Vec::new(),
+    info,
+    purged,
+  })
+}
 
-    map.insert(*handle, ledger_arc.clone());
-    Ok(ledger_arc)
-  }
+fn truncate(path: &Path, len: u64) -> Result<(), LedgerStoreError> {
+  let file = OpenOptions::new()
+    .write(true)
+    .open(path)
+    .map_err(io_error("open a log"))?;
+  file.set_len(len).map_err(io_error("truncate a log"))?;
+  file.sync_all().map_err(io_error("sync a log"))
 }
 
-async fn read_ledger_op(
-  handle: &Handle,
-  req_idx: Option<usize>,
+/// checks every ledger in `dir_path` and recovers it to its last committed entry; returns the
+/// ledgers and, if it exists, the view ledger
+fn fsck(
   dir_path: &Path,
-  file_map: &FileMap,
-) -> Result<(LedgerEntry, usize), LedgerStoreError> {
-  let ledger_lock = open_and_lock(handle, dir_path, file_map, false)?;
+) -> Result<(HashMap<Handle, LedgerState>, Option<LedgerState>), LedgerStoreError> {
+  let dir = fs::read_dir(dir_path).map_err(io_error("list the store directory"))?;
+
+  let mut ledgers = HashMap::new();
+  let mut view_ledger = None;
+  for entry in dir.flatten() {
+    let name = match entry.file_name().into_string() {
+      Ok(n) => n,
+      Err(_) => continue,
+    };
 
-  let mut ledger = match ledger_lock.write() {
-    Ok(v) => v,
-    Err(_) => {
-      return Err(LedgerStoreError::LedgerError(
-        StorageError::LedgerWriteLockFailed,
-      ));
-    },
-  };
+    // a tail, tenant, purged, lease, intent or tokens update that never got renamed into place
+    if name.ends_with(&format!(".{}", TAIL_TMP_EXT))
+      || name.ends_with(&format!(".{}", TENANT_TMP_EXT))
+      || name.ends_with(&format!(".{}", PURGED_TMP_EXT))
+      || name.ends_with(&format!(".{}", LEASE_TMP_EXT))
+      || name.ends_with(&format!(".{}", INTENT_TMP_EXT))
+      || name.ends_with(&format!(".{}", TOKENS_TMP_EXT))
+    {
+      fs::remove_file(entry.path()).map_err(io_error("remove a temporary file"))?;
+      continue;
+    }
 
-  // Find where to seek
-  let index = match req_idx {
-    Some(idx) => idx,
-    None => match ledger.metadata() {
-      Ok(m) => {
-        if checked_conversion!(m.len(), usize) < ENTRY_SIZE {
-          eprintln!("Trying to read an empty file");
-          return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-        }
-
-        (checked_conversion!(m.len(), usize) / ENTRY_SIZE) - 1
-      },
-      Err(e) => {
-        eprintln!("Failed to access file metadata {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-      },
-    },
-  };
+    // every ledger has a blocks log; other files are either part of a ledger or not ours
+    let stem = match name.strip_suffix(&format!(".{}", BLOCKS_EXT)) {
+      Some(s) => s,
+      None => continue,
+    };
 
-  let offset = match index.checked_mul(ENTRY_SIZE) {
-    Some(v) => checked_conversion!(v, u64),
-    None => {
-      return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
-    },
-  };
+    let handle = if stem == VIEW_STEM {
+      None
+    } else {
+      match hex::decode(stem)
+        .ok()
+        .and_then(|bytes| NimbleDigest::from_bytes(&bytes).ok())
+      {
+        Some(h) => Some(h),
+        None => continue,
+      }
+    };
 
-  let mut serialized_entry = [0; ENTRY_SIZE];
-  read_at(SeekFrom::Start(offset), &mut ledger, &mut serialized_entry)?;
+    // a ledger whose creation was interrupted before its tail was written never existed
+    if !file_path(dir_path, stem, TAIL_EXT).exists() {
+      eprintln!("Removing the partially created ledger {}", stem);
+      fs::remove_file(entry.path()).map_err(io_error("remove a blocks log"))?;
+      let receipts_path = file_path(dir_path, stem, RECEIPTS_EXT);
+      if receipts_path.exists() {
+        fs::remove_file(receipts_path).map_err(io_error("remove a receipts log"))?;
+      }
+      let info_path = file_path(dir_path, stem, INFO_EXT);
+      if info_path.exists() {
+        fs::remove_file(info_path).map_err(io_error("remove an info file"))?;
+      }
+      continue;
+    }
 
-  let entry: StoreEntry = match bincode::deserialize(&serialized_entry) {
-    Ok(e) => e,
-    Err(_) => {
-      return Err(LedgerStoreError::LedgerError(
-        StorageError::DeserializationError,
-      ));
-    },
-  };
+    let state = recover_ledger(dir_path, stem)?;
+    match handle {
+      Some(h) => {
+        ledgers.insert(h, state);
+      },
+      None => view_ledger = Some(state),
+    }
+  }
 
-  // 3. Return ledger entry by deserializing its contents
-  Ok((
-    LedgerEntry::new(
-      Block::from_bytes(&entry.block).unwrap(),
-      Receipts::from_bytes(&entry.receipts).unwrap(),
-      None, //TODO
-    ),
-    index,
-  ))
+  Ok((ledgers, view_ledger))
 }
 
 #[async_trait]
@@ -320,43 +945,30 @@ impl LedgerStore for FileStore {
     &self,
     handle: &Handle,
     genesis_block: Block,
+    info: &LedgerInfo,
   ) -> Result<(), LedgerStoreError> {
-    // 1. Create and lock file
-    let ledger_lock = open_and_lock(handle, &self.dir_path, &self.open_files, true)?;
-
-    let mut ledger = match ledger_lock.write() {
-      Ok(v) => v,
+    // hold the map lock so that concurrent creations of the same handle are serialized
+    let mut ledgers = match self.ledgers.write() {
+      Ok(m) => m,
       Err(_) => {
         return Err(LedgerStoreError::LedgerError(
-          StorageError::LedgerWriteLockFailed,
+          StorageError::LedgerMapWriteLockFailed,
         ));
       },
     };
 
-    // 2. Check if non-empty file
-    match ledger.metadata() {
-      Ok(m) => {
-        if m.len() > 0 {
-          return Err(LedgerStoreError::LedgerError(StorageError::DuplicateKey));
-        }
-      },
-      Err(e) => {
-        eprintln!("Failed to access file metadata {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-      },
-    };
-
-    // 3. Create the ledger entry that we will add to the brand new ledger
-    let init_entry = StoreEntry {
-      block: genesis_block.to_bytes(),
-      receipts: Receipts::new().to_bytes(),
-    };
-
-    // Serialize the entry
-    let ser_entry = serialize_entry(&init_entry)?;
-    write_at(SeekFrom::Start(0), &mut ledger, &ser_entry)?;
-
-    Ok(())
+    if let hash_map::Entry::Vacant(e) = ledgers.entry(*handle) {
+      let state = create_ledger_files(
+        &self.dir_path,
+        &hex::encode(handle.to_bytes()),
+        &genesis_block,
+        info,
+      )?;
+      e.insert(Arc::new(RwLock::new(state)));
+      Ok(())
+    } else {
+      Err(LedgerStoreError::LedgerError(StorageError::DuplicateKey))
+    }
   }
 
   async fn append_ledger(
@@ -365,10 +977,18 @@ impl LedgerStore for FileStore {
     block: &Block,
     expected_height: usize,
   ) -> Result<(usize, Nonces), LedgerStoreError> {
-    let ledger_lock = open_and_lock(handle, &self.dir_path, &self.open_files, false)?;
+    let ledger = self.get_ledger(handle)?;
+    self.append_op(&ledger, block, expected_height)
+  }
 
-    let mut ledger = match ledger_lock.write() {
-      Ok(v) => v,
+  async fn attach_ledger_nonce(
+    &self,
+    handle: &Handle,
+    nonce: &Nonce,
+  ) -> Result<usize, LedgerStoreError> {
+    let ledger = self.get_ledger(handle)?;
+    let mut state = match ledger.write() {
+      Ok(s) => s,
       Err(_) => {
         return Err(LedgerStoreError::LedgerError(
           StorageError::LedgerWriteLockFailed,
@@ -376,45 +996,10 @@ impl LedgerStore for FileStore {
       },
     };
 
-    let next_index = match ledger.metadata() {
-      Ok(m) => checked_conversion!(m.len(), usize) / ENTRY_SIZE,
-      Err(e) => {
-        eprintln!("Failed to access file metadata {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
-      },
-    };
-
-    // 1. check if condition holds
-    if expected_height != next_index {
-      eprintln!(
-        "Expected height {};  Height-plus-one: {}",
-        expected_height, next_index
-      );
-
-      return Err(LedgerStoreError::LedgerError(
-        StorageError::IncorrectConditionalData,
-      ));
-    }
-
-    // 2. Construct the new entry we are going to append to the ledger
-    let new_entry = StoreEntry {
-      block: block.to_bytes(),
-      receipts: Receipts::new().to_bytes(),
-    };
-
-    let ser_entry = serialize_entry(&new_entry)?;
-
-    write_at(SeekFrom::End(0), &mut ledger, &ser_entry)?;
-    Ok((next_index, Nonces::new()))
-  }
-
-  #[allow(unused_variables)]
-  async fn attach_ledger_nonce(
-    &self,
-    handle: &Handle,
-    receipt: &Nonce,
-  ) -> Result<usize, LedgerStoreError> {
-    unimplemented!()
+    // add nonce to the nonces list of this ledger and return the next
+    // height at which it should be appended
+    state.nonces.push(nonce.to_owned());
+    Ok(state.offsets.len())
   }
 
   async fn attach_ledger_receipts(
@@ -423,64 +1008,16 @@ impl LedgerStore for FileStore {
     idx: usize,
     receipts: &Receipts,
   ) -> Result<(), LedgerStoreError> {
-    // 1. Get the desired offset
-    let offset = match idx.checked_mul(ENTRY_SIZE) {
-      Some(v) => checked_conversion!(v, u64),
-      None => {
-        return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
-      },
-    };
-
-    let ledger_lock = open_and_lock(handle, &self.dir_path, &self.open_files, false)?;
-
-    let mut ledger = match ledger_lock.write() {
-      Ok(v) => v,
-      Err(_) => {
-        return Err(LedgerStoreError::LedgerError(
-          StorageError::LedgerWriteLockFailed,
-        ));
-      },
-    };
-
-    let seek_from = SeekFrom::Start(offset);
-
-    // 2. Find the appropriate entry in the ledger
-    let mut serialized_entry = [0; ENTRY_SIZE];
-    read_at(seek_from, &mut ledger, &mut serialized_entry)?;
-
-    let mut ledger_entry: StoreEntry = match bincode::deserialize(&serialized_entry) {
-      Ok(e) => e,
-      Err(_) => {
-        return Err(LedgerStoreError::LedgerError(
-          StorageError::DeserializationError,
-        ));
-      },
-    };
-
-    // 3. Recover the contents of the ledger entry
-    let mut ledger_entry_receipts =
-      Receipts::from_bytes(&ledger_entry.receipts).expect("failed to deserialize receipt");
-
-    // 4. Update receipt
-    ledger_entry_receipts.merge_receipts(receipts);
-    ledger_entry.receipts = ledger_entry_receipts.to_bytes();
-
-    // 5. Re-serialize
-    let ser_entry = serialize_entry(&ledger_entry)?;
-
-    // 6. Update entry
-    write_at(seek_from, &mut ledger, &ser_entry)?;
-
-    Ok(())
+    let ledger = self.get_ledger(handle)?;
+    self.attach_receipts_op(&ledger, idx, receipts)
   }
 
   async fn read_ledger_tail(
     &self,
     handle: &Handle,
   ) -> Result<(LedgerEntry, usize), LedgerStoreError> {
-    let (ledger_entry, height) =
-      read_ledger_op(handle, None, &self.dir_path, &self.open_files).await?;
-    Ok((ledger_entry, height))
+    let ledger = self.get_ledger(handle)?;
+    self.read_op(&ledger, None)
   }
 
   async fn read_ledger_by_index(
@@ -488,17 +1025,31 @@ impl LedgerStore for FileStore {
     handle: &Handle,
     index: usize,
   ) -> Result<LedgerEntry, LedgerStoreError> {
-    let (ledger_entry, _height) =
-      read_ledger_op(handle, Some(index), &self.dir_path, &self.open_files).await?;
+    let ledger = self.get_ledger(handle)?;
+    let (ledger_entry, _height) = self.read_op(&ledger, Some(index))?;
     Ok(ledger_entry)
   }
 
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError> {
+    let ledger = self.get_ledger(handle)?;
+    let state = match ledger.read() {
+      Ok(s) => s,
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::LedgerReadLockFailed,
+        ));
+      },
+    };
+    Ok(state.info.clone())
+  }
+
   async fn read_view_ledger_tail(&self) -> Result<(LedgerEntry, usize), LedgerStoreError> {
-    self.read_ledger_tail(&self.view_handle).await
+    self.read_op(&self.view_ledger, None)
   }
 
   async fn read_view_ledger_by_index(&self, idx: usize) -> Result<LedgerEntry, LedgerStoreError> {
-    self.read_ledger_by_index(&self.view_handle, idx).await
+    let (ledger_entry, _height) = self.read_op(&self.view_ledger, Some(idx))?;
+    Ok(ledger_entry)
   }
 
   async fn attach_view_ledger_receipts(
@@ -506,9 +1057,7 @@ impl LedgerStore for FileStore {
     idx: usize,
     receipts: &Receipts,
   ) -> Result<(), LedgerStoreError> {
-    self
-      .attach_ledger_receipts(&self.view_handle, idx, receipts)
-      .await
+    self.attach_receipts_op(&self.view_ledger, idx, receipts)
   }
 
   async fn append_view_ledger(
@@ -516,19 +1065,558 @@ impl LedgerStore for FileStore {
     block: &Block,
     expected_height: usize,
   ) -> Result<usize, LedgerStoreError> {
-    let res = self
-      .append_ledger(&self.view_handle, block, expected_height)
-      .await?;
+    let res = self.append_op(&self.view_ledger, block, expected_height)?;
     Ok(res.0)
   }
 
-  async fn reset_store(&self) -> Result<(), LedgerStoreError> {
-    match fs::remove_dir_all(&self.dir_path) {
-      Ok(_) => Ok(()),
-      Err(e) => {
-        eprintln!("Error opening view file {:?}", e);
-        return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError));
+  async fn list_handles(
+    &self,
+    start_after: Option<&Handle>,
+    limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError> {
+    if let Ok(ledgers) = self.ledgers.read() {
+      Ok(paginate_handles(
+        ledgers.keys().cloned().collect(),
+        start_after,
+        limit,
+      ))
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError> {
+    if let Ok(ledgers) = self.ledgers.read() {
+      Ok(Some(ledgers.len()))
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError> {
+    // the file of every ledger would have to be looked up
+    Ok(None)
+  }
+
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError> {
+    let ledger = self.get_ledger(handle)?;
+    self.purge_op(&ledger, before_height)
+  }
+
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError> {
+    let stem = tenant_stem(tenant);
+    let path = file_path(&self.dir_path, &stem, TENANT_EXT);
+    if !path.exists() {
+      return Ok(TenantRecord::default());
+    }
+    let bytes = fs::read(&path).map_err(io_error("read a tenant file"))?;
+    let entry: TenantEntry = match parse_records(&bytes) {
+      (records, len) if records.len() == 1 && len == bytes.len() => {
+        deserialize(records[0].1).map_err(|_| corrupted(&stem, "unreadable tenant"))?
       },
+      _ => return Err(corrupted(&stem, "tenant fails its checksum")),
+    };
+    Ok(TenantRecord {
+      max_ledgers: entry.max_ledgers,
+      max_appends_per_sec: entry.max_appends_per_sec,
+      max_stored_bytes: entry.max_stored_bytes,
+      num_ledgers: entry.num_ledgers,
+      stored_bytes: entry.stored_bytes,
+    })
+  }
+
+  async fn write_tenant(
+    &self,
+    tenant: &str,
+    record: &TenantRecord,
+  ) -> Result<(), LedgerStoreError> {
+    // like a tail, the record is written aside and renamed into place
+    let record = frame_record(&serialize(&TenantEntry {
+      max_ledgers: record.max_ledgers,
+      max_appends_per_sec: record.max_appends_per_sec,
+      max_stored_bytes: record.max_stored_bytes,
+      num_ledgers: record.num_ledgers,
+      stored_bytes: record.stored_bytes,
+    })?)?;
+    let stem = tenant_stem(tenant);
+    let tmp_path = file_path(&self.dir_path, &stem, TENANT_TMP_EXT);
+
+    let mut tmp = File::create(&tmp_path).map_err(io_error("create a tenant file"))?;
+    tmp
+      .write_all(&record)
+      .map_err(io_error("write a tenant file"))?;
+    tmp.sync_all().map_err(io_error("sync a tenant file"))?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, file_path(&self.dir_path, &stem, TENANT_EXT))
+      .map_err(io_error("rename a tenant file"))?;
+    sync_dir(&self.dir_path)
+  }
+
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    let _guard = self
+      .lease
+      .lock()
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::LedgerReadLockFailed))?;
+    read_lease_file(&self.dir_path)
+  }
+
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let _guard = self
+      .lease
+      .lock()
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::LedgerWriteLockFailed))?;
+    if read_lease_file(&self.dir_path)? != *expected {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData,
+      ));
+    }
+
+    // like a tail, the lease is written aside and renamed into place
+    let record = frame_record(&serialize(&LeaseEntry {
+      holder: new.holder.clone(),
+      expires_at: new.expires_at,
+      epoch: new.epoch,
+    })?)?;
+    let tmp_path = file_path(&self.dir_path, LEASE_STEM, LEASE_TMP_EXT);
+
+    let mut tmp = File::create(&tmp_path).map_err(io_error("create a lease file"))?;
+    tmp
+      .write_all(&record)
+      .map_err(io_error("write a lease file"))?;
+    tmp.sync_all().map_err(io_error("sync a lease file"))?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, file_path(&self.dir_path, LEASE_STEM, LEASE_EXT))
+      .map_err(io_error("rename a lease file"))?;
+    sync_dir(&self.dir_path)
+  }
+
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError> {
+    // like a tail, the intent is written aside and renamed into place
+    let record = frame_record(&serialize(&IntentEntry {
+      handle: intent.handle.to_bytes(),
+      height: checked_conversion!(intent.height, u64),
+      block_hash: intent.block_hash.to_bytes(),
+      request_id: intent.request_id.clone(),
+    })?)?;
+    let stem = intent_stem(&intent.handle, intent.height);
+    let tmp_path = file_path(&self.dir_path, &stem, INTENT_TMP_EXT);
+
+    let mut tmp = File::create(&tmp_path).map_err(io_error("create an intent file"))?;
+    tmp
+      .write_all(&record)
+      .map_err(io_error("write an intent file"))?;
+    tmp.sync_all().map_err(io_error("sync an intent file"))?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, file_path(&self.dir_path, &stem, INTENT_EXT))
+      .map_err(io_error("rename an intent file"))?;
+    sync_dir(&self.dir_path)
+  }
+
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError> {
+    let path = file_path(&self.dir_path, &intent_stem(handle, height), INTENT_EXT);
+    match fs::remove_file(&path) {
+      Ok(()) => sync_dir(&self.dir_path),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+      Err(e) => Err(io_error("remove an intent file")(e)),
+    }
+  }
+
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError> {
+    let dir = fs::read_dir(&self.dir_path).map_err(io_error("list the store directory"))?;
+    let mut intents = Vec::new();
+    for entry in dir.flatten() {
+      let name = match entry.file_name().into_string() {
+        Ok(n) => n,
+        Err(_) => continue,
+      };
+      let stem = match name.strip_suffix(&format!(".{}", INTENT_EXT)) {
+        Some(stem) if stem.starts_with(INTENT_STEM_PREFIX) => stem,
+        _ => continue,
+      };
+      let bytes = fs::read(entry.path()).map_err(io_error("read an intent file"))?;
+      let entry: IntentEntry = match parse_records(&bytes) {
+        (records, len) if records.len() == 1 && len == bytes.len() => {
+          deserialize(records[0].1).map_err(|_| corrupted(stem, "unreadable intent"))?
+        },
+        _ => return Err(corrupted(stem, "intent fails its checksum")),
+      };
+      let handle =
+        NimbleDigest::from_bytes(&entry.handle).map_err(|_| corrupted(stem, "invalid handle"))?;
+      let block_hash = NimbleDigest::from_bytes(&entry.block_hash)
+        .map_err(|_| corrupted(stem, "invalid block hash"))?;
+      intents.push(IntentRecord {
+        handle,
+        height: checked_conversion!(entry.height, usize),
+        block_hash,
+        request_id: entry.request_id,
+      });
+    }
+    Ok(intents)
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, LedgerStoreError> {
+    let stem = hex::encode(handle.to_bytes());
+    let path = file_path(&self.dir_path, &stem, TOKENS_EXT);
+    if !path.exists() {
+      return Ok(vec![]);
+    }
+    let bytes = fs::read(&path).map_err(io_error("read a tokens file"))?;
+    let entry: AppendTokensEntry = match parse_records(&bytes) {
+      (records, len) if records.len() == 1 && len == bytes.len() => {
+        deserialize(records[0].1).map_err(|_| corrupted(&stem, "unreadable tokens"))?
+      },
+      _ => return Err(corrupted(&stem, "tokens fail their checksum")),
+    };
+    let mut tokens = Vec::with_capacity(entry.tokens.len());
+    for token in entry.tokens {
+      tokens.push(AppendToken {
+        request_id: token.request_id,
+        height: checked_conversion!(token.height, usize),
+        block_hash: NimbleDigest::from_bytes(&token.block_hash)
+          .map_err(|_| corrupted(&stem, "invalid block hash"))?,
+      });
+    }
+    Ok(tokens)
+  }
+
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError> {
+    let mut entry = AppendTokensEntry {
+      tokens: Vec::with_capacity(tokens.len()),
+    };
+    for token in tokens {
+      entry.tokens.push(AppendTokenEntry {
+        request_id: token.request_id.clone(),
+        height: checked_conversion!(token.height, u64),
+        block_hash: token.block_hash.to_bytes(),
+      });
+    }
+    // like a tail, the tokens are written aside and renamed into place
+    let record = frame_record(&serialize(&entry)?)?;
+    let stem = hex::encode(handle.to_bytes());
+    let tmp_path = file_path(&self.dir_path, &stem, TOKENS_TMP_EXT);
+
+    let mut tmp = File::create(&tmp_path).map_err(io_error("create a tokens file"))?;
+    tmp
+      .write_all(&record)
+      .map_err(io_error("write a tokens file"))?;
+    tmp.sync_all().map_err(io_error("sync a tokens file"))?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, file_path(&self.dir_path, &stem, TOKENS_EXT))
+      .map_err(io_error("rename a tokens file"))?;
+    sync_dir(&self.dir_path)
+  }
+
+  async fn reset_store(&self) -> Result<(), LedgerStoreError> {
+    if let Ok(mut ledgers) = self.ledgers.write() {
+      ledgers.clear();
+    }
+    fs::remove_dir_all(&self.dir_path).map_err(io_error("remove the store directory"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{
+    process::{Command, Stdio},
+    time::Duration,
+  };
+
+  const CHILD_DIR_VAR: &str = "NIMBLE_FSTORE_CHILD_DIR";
+
+  fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nimble-fstore-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+  }
+
+  fn args(dir: &Path) -> HashMap<String, String> {
+    let mut args = HashMap::new();
+    args.insert(
+      String::from("NIMBLE_FSTORE_DIR"),
+      dir.to_str().unwrap().to_string(),
+    );
+    args
+  }
+
+  fn genesis() -> Block {
+    Block::new(&[7u8; 32])
+  }
+
+  fn block_at(height: usize) -> Block {
+    Block::new(&vec![(height % 251) as u8; 16 * 1024])
+  }
+
+  fn blocks_path(dir: &Path) -> PathBuf {
+    file_path(dir, &hex::encode(genesis().hash().to_bytes()), BLOCKS_EXT)
+  }
+
+  async fn check_consistent(store: &FileStore, height: usize) {
+    let handle = genesis().hash();
+    let (tail, h) = store.read_ledger_tail(&handle).await.unwrap();
+    assert_eq!(h, height);
+    assert_eq!(tail.get_block().to_bytes(), block_at(height).to_bytes());
+    for i in 1..=height {
+      let entry = store.read_ledger_by_index(&handle, i).await.unwrap();
+      assert_eq!(entry.get_block().to_bytes(), block_at(i).to_bytes());
+    }
+    // the recovered ledger accepts the next append
+    let res = store
+      .append_ledger(&handle, &block_at(height + 1), height + 1)
+      .await;
+    assert_eq!(res.unwrap().0, height + 1);
+  }
+
+  #[tokio::test]
+  pub async fn check_filestore_truncates_torn_records() {
+    let dir = test_dir("torn");
+    let handle = genesis().hash();
+    let info = LedgerInfo {
+      created_at: 42,
+      metadata: b"torn".to_vec(),
+      ..Default::default()
+    };
+    {
+      let store = FileStore::new(&args(&dir)).await.unwrap();
+      store
+        .create_ledger(&handle, genesis(), &info)
+        .await
+        .unwrap();
+      for i in 1..=3 {
+        store.append_ledger(&handle, &block_at(i), i).await.unwrap();
+      }
+      store
+        .attach_ledger_receipts(&handle, 2, &Receipts::new())
+        .await
+        .unwrap();
+    }
+
+    // a torn append and a torn receipts record
+    let committed_len = fs::metadata(blocks_path(&dir)).unwrap().len();
+    let record = frame_record(&[1u8; 100]).unwrap();
+    let mut blocks = OpenOptions::new()
+      .append(true)
+      .open(blocks_path(&dir))
+      .unwrap();
+    blocks.write_all(&record[..60]).unwrap();
+    let receipts_path = blocks_path(&dir).with_extension(RECEIPTS_EXT);
+    let mut receipts = OpenOptions::new()
+      .append(true)
+      .open(&receipts_path)
+      .unwrap();
+    receipts.write_all(&record[..10]).unwrap();
+
+    let store = FileStore::new(&args(&dir)).await.unwrap();
+    assert_eq!(
+      fs::metadata(blocks_path(&dir)).unwrap().len(),
+      committed_len
+    );
+    check_consistent(&store, 3).await;
+    assert_eq!(store.read_ledger_info(&handle).await.unwrap(), info);
+    store.reset_store().await.unwrap();
+  }
+
+  #[tokio::test]
+  pub async fn check_filestore_detects_corruption() {
+    let dir = test_dir("corrupt");
+    {
+      let store = FileStore::new(&args(&dir)).await.unwrap();
+      let handle = genesis().hash();
+      store
+        .create_ledger(&handle, genesis(), &LedgerInfo::default())
+        .await
+        .unwrap();
+      store.append_ledger(&handle, &block_at(1), 1).await.unwrap();
+    }
+
+    // flip a byte inside the committed genesis entry
+    let mut bytes = fs::read(blocks_path(&dir)).unwrap();
+    bytes[RECORD_HEADER_SIZE + 4] ^= 1;
+    fs::write(blocks_path(&dir), &bytes).unwrap();
+
+    match FileStore::new(&args(&dir)).await {
+      Err(LedgerStoreError::LedgerError(StorageError::CorruptedLedger)) => {},
+      res => panic!("expected a corrupted ledger, got {:?}", res.map(|_| ())),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[tokio::test]
+  pub async fn check_filestore_keeps_tenants() {
+    let dir = test_dir("tenants");
+    let record = TenantRecord {
+      max_ledgers: 2,
+      max_appends_per_sec: 100,
+      max_stored_bytes: 0,
+      num_ledgers: 1,
+      stored_bytes: 32,
+    };
+    {
+      let store = FileStore::new(&args(&dir)).await.unwrap();
+      store.write_tenant("hdfs-a", &record).await.unwrap();
+    }
+
+    // a tenant update that did not get renamed into place is dropped by the next open
+    let tmp_path = file_path(&dir, &tenant_stem("hdfs-a"), TENANT_TMP_EXT);
+    fs::write(&tmp_path, b"torn").unwrap();
+    let store = FileStore::new(&args(&dir)).await.unwrap();
+    assert!(!tmp_path.exists());
+    assert_eq!(store.read_tenant("hdfs-a").await.unwrap(), record);
+    assert_eq!(
+      store.read_tenant("hdfs-b").await.unwrap(),
+      TenantRecord::default()
+    );
+    store.reset_store().await.unwrap();
+  }
+
+  #[tokio::test]
+  pub async fn check_filestore_keeps_lease() {
+    let dir = test_dir("lease");
+    let lease = LeaseRecord {
+      holder: "coordinator-a".to_string(),
+      expires_at: 1000,
+      epoch: 1,
+    };
+    {
+      let store = FileStore::new(&args(&dir)).await.unwrap();
+      store
+        .swap_lease(&LeaseRecord::default(), &lease)
+        .await
+        .unwrap();
+    }
+
+    // a lease update that did not get renamed into place is dropped by the next open
+    let tmp_path = file_path(&dir, LEASE_STEM, LEASE_TMP_EXT);
+    fs::write(&tmp_path, b"torn").unwrap();
+    let store = FileStore::new(&args(&dir)).await.unwrap();
+    assert!(!tmp_path.exists());
+    assert_eq!(store.read_lease().await.unwrap(), lease);
+    let res = store
+      .swap_lease(&LeaseRecord::default(), &LeaseRecord::default())
+      .await;
+    assert!(matches!(
+      res,
+      Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData
+      ))
+    ));
+    store.reset_store().await.unwrap();
+  }
+
+  #[tokio::test]
+  pub async fn check_filestore_purges_blocks() {
+    let dir = test_dir("purge");
+    let handle = genesis().hash();
+    let unpurged_bytes;
+    let block_hashes;
+    {
+      let store = FileStore::new(&args(&dir)).await.unwrap();
+      store
+        .create_ledger(&handle, genesis(), &LedgerInfo::default())
+        .await
+        .unwrap();
+      for i in 1..=3 {
+        store.append_ledger(&handle, &block_at(i), i).await.unwrap();
+      }
+      block_hashes = [genesis().hash(), block_at(1).hash(), block_at(2).hash()];
+      unpurged_bytes = fs::read(blocks_path(&dir)).unwrap();
+      store.purge_ledger_blocks(&handle, 3).await.unwrap();
+    }
+
+    // the purged blocks are gone from the log, whose length does not change
+    let purged_bytes = fs::read(blocks_path(&dir)).unwrap();
+    assert_eq!(purged_bytes.len(), unpurged_bytes.len());
+    let needle = block_at(1).to_bytes();
+    assert!(!purged_bytes.windows(needle.len()).any(|w| w == needle));
+
+    // a purge interrupted after its purged file was renamed into place is finished on open
+    fs::write(blocks_path(&dir), &unpurged_bytes).unwrap();
+    let store = FileStore::new(&args(&dir)).await.unwrap();
+    assert_eq!(fs::read(blocks_path(&dir)).unwrap(), purged_bytes);
+    for (i, block_hash) in block_hashes.iter().enumerate() {
+      let entry = store.read_ledger_by_index(&handle, i).await.unwrap();
+      assert!(entry.get_block().to_bytes().is_empty());
+      assert_eq!(entry.get_purged_block_hash(), Some(block_hash));
+    }
+    let (tail, height) = store.read_ledger_tail(&handle).await.unwrap();
+    assert_eq!(height, 3);
+    assert_eq!(tail.get_block().to_bytes(), block_at(3).to_bytes());
+    store.append_ledger(&handle, &block_at(4), 4).await.unwrap();
+    store.reset_store().await.unwrap();
+  }
+
+  /// appends to a ledger until it is killed; only runs as the child of
+  /// `check_filestore_recovers_after_kill`
+  #[tokio::test]
+  #[ignore]
+  pub async fn append_until_killed() {
+    let dir = match std::env::var_os(CHILD_DIR_VAR) {
+      Some(d) => PathBuf::from(d),
+      None => return,
+    };
+    let store = FileStore::new(&args(&dir)).await.unwrap();
+    let handle = genesis().hash();
+    store
+      .create_ledger(&handle, genesis(), &LedgerInfo::default())
+      .await
+      .unwrap();
+    for i in 1.. {
+      store.append_ledger(&handle, &block_at(i), i).await.unwrap();
+    }
+  }
+
+  #[tokio::test]
+  pub async fn check_filestore_recovers_after_kill() {
+    let dir = test_dir("kill");
+    let mut child = Command::new(std::env::current_exe().unwrap())
+      .args([
+        "ledger::filestore::tests::append_until_killed",
+        "--exact",
+        "--ignored",
+      ])
+      .env(CHILD_DIR_VAR, &dir)
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+      .unwrap();
+
+    // wait for the child to get well into its appends, then kill it mid-flight
+    for _ in 0..500 {
+      if fs::metadata(blocks_path(&dir)).map_or(0, |m| m.len()) > 64 * 16 * 1024 {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
     }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let store = FileStore::new(&args(&dir)).await.unwrap();
+    let (_, height) = store.read_ledger_tail(&genesis().hash()).await.unwrap();
+    assert!(height > 0);
+    check_consistent(&store, height).await;
+    store.reset_store().await.unwrap();
   }
 }
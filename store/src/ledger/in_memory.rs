@@ -1,7 +1,10 @@
 use super::{Block, Handle, NimbleDigest, Nonce, Nonces, Receipts};
 use crate::{
   errors::{LedgerStoreError, StorageError},
-  ledger::{LedgerEntry, LedgerStore},
+  ledger::{
+    paginate_handles, AppendToken, IntentRecord, LeaseRecord, LedgerEntry, LedgerInfo, LedgerStore,
+    TenantRecord,
+  },
 };
 use async_trait::async_trait;
 use std::{
@@ -16,6 +19,11 @@ type NonceArray = Arc<RwLock<Vec<Nonce>>>;
 pub struct InMemoryLedgerStore {
   ledgers: Arc<RwLock<HashMap<Handle, LedgerArray>>>,
   nonces: Arc<RwLock<HashMap<Handle, NonceArray>>>,
+  infos: Arc<RwLock<HashMap<Handle, LedgerInfo>>>,
+  tenants: Arc<RwLock<HashMap<String, TenantRecord>>>,
+  lease: Arc<RwLock<LeaseRecord>>,
+  intents: Arc<RwLock<HashMap<(Handle, usize), IntentRecord>>>,
+  append_tokens: Arc<RwLock<HashMap<Handle, Vec<AppendToken>>>>,
   view_ledger: Arc<RwLock<Vec<LedgerEntry>>>,
 }
 
@@ -30,6 +38,11 @@ impl InMemoryLedgerStore {
     InMemoryLedgerStore {
       ledgers: Arc::new(RwLock::new(ledgers)),
       nonces: Arc::new(RwLock::new(HashMap::new())),
+      infos: Arc::new(RwLock::new(HashMap::new())),
+      tenants: Arc::new(RwLock::new(HashMap::new())),
+      lease: Arc::new(RwLock::new(LeaseRecord::default())),
+      intents: Arc::new(RwLock::new(HashMap::new())),
+      append_tokens: Arc::new(RwLock::new(HashMap::new())),
       view_ledger: Arc::new(RwLock::new(view_ledger)),
     }
   }
@@ -62,6 +75,7 @@ impl LedgerStore for InMemoryLedgerStore {
     &self,
     handle: &NimbleDigest,
     genesis_block: Block,
+    info: &LedgerInfo,
   ) -> Result<(), LedgerStoreError> {
     let genesis_ledger_entry = LedgerEntry::new(genesis_block, Receipts::new(), None);
     if let Ok(mut ledgers_map) = self.ledgers.write() {
@@ -71,7 +85,16 @@ impl LedgerStore for InMemoryLedgerStore {
 
           if let hash_map::Entry::Vacant(n) = nonce_map.entry(*handle) {
             n.insert(Arc::new(RwLock::new(Vec::new())));
-            Ok(())
+            // the ledger map lock is still held, so no reader sees the ledger without its info
+            match self.infos.write() {
+              Ok(mut infos) => {
+                infos.insert(*handle, info.clone());
+                Ok(())
+              },
+              Err(_) => Err(LedgerStoreError::LedgerError(
+                StorageError::LedgerMapWriteLockFailed,
+              )),
+            }
           } else {
             Err(LedgerStoreError::LedgerError(StorageError::DuplicateKey))
           }
@@ -106,6 +129,7 @@ impl LedgerStore for InMemoryLedgerStore {
               block: block.clone(),
               receipts: Receipts::new(),
               nonces: nonces.clone(),
+              purged_block_hash: None,
             };
             ledgers.push(ledger_entry);
 
@@ -259,6 +283,19 @@ impl LedgerStore for InMemoryLedgerStore {
     }
   }
 
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError> {
+    if let Ok(infos) = self.infos.read() {
+      match infos.get(handle) {
+        Some(info) => Ok(info.clone()),
+        None => Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)),
+      }
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
   async fn append_view_ledger(
     &self,
     block: &Block,
@@ -326,6 +363,185 @@ impl LedgerStore for InMemoryLedgerStore {
     }
   }
 
+  async fn list_handles(
+    &self,
+    start_after: Option<&Handle>,
+    limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError> {
+    if let Ok(ledgers_map) = self.ledgers.read() {
+      Ok(paginate_handles(
+        ledgers_map.keys().cloned().collect(),
+        start_after,
+        limit,
+      ))
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError> {
+    if let Ok(ledgers_map) = self.ledgers.read() {
+      Ok(Some(ledgers_map.len()))
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError> {
+    // the entries of every ledger would have to be walked
+    Ok(None)
+  }
+
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError> {
+    if let Ok(ledgers_map) = self.ledgers.read() {
+      if ledgers_map.contains_key(handle) {
+        if let Ok(mut ledgers) = ledgers_map[handle].write() {
+          if before_height < ledgers.len() {
+            ledgers[..before_height]
+              .iter_mut()
+              .for_each(|entry| entry.purge());
+            Ok(())
+          } else {
+            Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex))
+          }
+        } else {
+          Err(LedgerStoreError::LedgerError(
+            StorageError::LedgerWriteLockFailed,
+          ))
+        }
+      } else {
+        Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist))
+      }
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError> {
+    if let Ok(tenants) = self.tenants.read() {
+      Ok(tenants.get(tenant).cloned().unwrap_or_default())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn write_tenant(
+    &self,
+    tenant: &str,
+    record: &TenantRecord,
+  ) -> Result<(), LedgerStoreError> {
+    if let Ok(mut tenants) = self.tenants.write() {
+      tenants.insert(tenant.to_string(), record.clone());
+      Ok(())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapWriteLockFailed,
+      ))
+    }
+  }
+
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    if let Ok(lease) = self.lease.read() {
+      Ok(lease.clone())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError> {
+    if let Ok(mut lease) = self.lease.write() {
+      if *lease != *expected {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::IncorrectConditionalData,
+        ));
+      }
+      *lease = new.clone();
+      Ok(())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapWriteLockFailed,
+      ))
+    }
+  }
+
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError> {
+    if let Ok(mut intents) = self.intents.write() {
+      intents.insert((intent.handle, intent.height), intent.clone());
+      Ok(())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapWriteLockFailed,
+      ))
+    }
+  }
+
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError> {
+    if let Ok(mut intents) = self.intents.write() {
+      intents.remove(&(*handle, height));
+      Ok(())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapWriteLockFailed,
+      ))
+    }
+  }
+
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError> {
+    if let Ok(intents) = self.intents.read() {
+      Ok(intents.values().cloned().collect())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, LedgerStoreError> {
+    if let Ok(append_tokens) = self.append_tokens.read() {
+      Ok(append_tokens.get(handle).cloned().unwrap_or_default())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapReadLockFailed,
+      ))
+    }
+  }
+
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError> {
+    if let Ok(mut append_tokens) = self.append_tokens.write() {
+      append_tokens.insert(*handle, tokens.to_vec());
+      Ok(())
+    } else {
+      Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerMapWriteLockFailed,
+      ))
+    }
+  }
+
   async fn reset_store(&self) -> Result<(), LedgerStoreError> {
     // not really needed for in-memory since state is already volatile.
     // this API is only for testing persistent storage services.
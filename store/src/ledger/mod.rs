@@ -1,10 +1,15 @@
 use async_trait::async_trait;
-use ledger::{Block, Handle, NimbleDigest, Nonce, Nonces, Receipts};
+use ledger::{
+  compute_aggregated_block_hash, Block, CustomSerde, CustomSerdeError, Handle, NimbleDigest,
+  NimbleHashTrait, Nonce, Nonces, Receipts,
+};
 
 pub mod azure_table;
 pub mod filestore;
 pub mod in_memory;
 pub mod mongodb_cosmos;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb_store;
 
 use crate::errors::LedgerStoreError;
 
@@ -13,6 +18,8 @@ pub struct LedgerEntry {
   block: Block,
   receipts: Receipts,
   nonces: Nonces,
+  /// the hash of the block if its contents were purged, in which case the block is empty
+  purged_block_hash: Option<NimbleDigest>,
 }
 
 impl LedgerEntry {
@@ -25,6 +32,25 @@ impl LedgerEntry {
       } else {
         Nonces::new()
       },
+      purged_block_hash: None,
+    }
+  }
+
+  /// an entry whose block contents were purged, which keeps the hash of the block
+  pub fn new_purged(block_hash: NimbleDigest, receipts: Receipts, nonces: Nonces) -> Self {
+    Self {
+      block: Block::new(&[]),
+      receipts,
+      nonces,
+      purged_block_hash: Some(block_hash),
+    }
+  }
+
+  /// drops the contents of the block, keeping its hash
+  pub fn purge(&mut self) {
+    if self.purged_block_hash.is_none() {
+      self.purged_block_hash = Some(self.block.hash());
+      self.block = Block::new(&[]);
     }
   }
 
@@ -43,14 +69,124 @@ impl LedgerEntry {
   pub fn get_nonces(&self) -> &Nonces {
     &self.nonces
   }
+
+  /// the hash of the block if its contents were purged
+  pub fn get_purged_block_hash(&self) -> Option<&NimbleDigest> {
+    self.purged_block_hash.as_ref()
+  }
+
+  /// the hash of the block and its nonces, which the metablock of the entry carries; it is kept
+  /// when the contents of the block are purged
+  pub fn get_block_hash(&self) -> NimbleDigest {
+    let block_hash = match &self.purged_block_hash {
+      Some(block_hash) => *block_hash,
+      None => self.block.hash(),
+    };
+    compute_aggregated_block_hash(&block_hash.to_bytes(), &self.nonces.hash().to_bytes())
+  }
+}
+
+/// what the store records about a ledger when it is created
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LedgerInfo {
+  /// the creation time of the ledger in milliseconds since the Unix epoch; 0 if unknown
+  pub created_at: u64,
+  /// opaque bytes that the creator of the ledger attached to it
+  pub metadata: Vec<u8>,
+  /// the handle the client named the ledger with, whose digest keys the ledger in the store;
+  /// empty if unknown
+  pub handle_bytes: Vec<u8>,
+  /// the application bytes the handle was derived from; empty if the client chose the handle
+  pub app_bytes: Vec<u8>,
+}
+
+/// the quota of a tenant of the coordinator and what the tenant uses of it; a limit of 0 means
+/// that the tenant is not limited
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TenantRecord {
+  pub max_ledgers: u64,
+  pub max_appends_per_sec: u64,
+  pub max_stored_bytes: u64,
+  /// the number of ledgers the tenant created
+  pub num_ledgers: u64,
+  /// the bytes of the blocks the tenant stored
+  pub stored_bytes: u64,
+}
+
+/// the lease that a coordinator holds while it serves writes; standby coordinators take it over
+/// once it expires
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LeaseRecord {
+  /// the id of the coordinator that holds or last held the lease; empty if none ever did
+  pub holder: String,
+  /// when the lease expires, in milliseconds since the Unix epoch
+  pub expires_at: u64,
+  /// the number of times the lease changed hands
+  pub epoch: u64,
+}
+
+/// an append that the coordinator started: it is recorded before the block is persisted and sent
+/// to the endorsers, and committed once the block and its receipts are persisted, so that the
+/// intents left by a coordinator that failed tell the next one which appends to resolve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntentRecord {
+  pub handle: Handle,
+  /// the height that the append extends the ledger to
+  pub height: usize,
+  /// the hash of the block of the append
+  pub block_hash: NimbleDigest,
+  /// the ID of the request that made the append; empty if unknown
+  pub request_id: String,
+}
+
+/// an append that a client named with a request ID, so that a retry of the append with the same
+/// ID is answered with the entry it appended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppendToken {
+  /// the ID that the client chose for the append
+  pub request_id: String,
+  /// the height that the append extends the ledger to
+  pub height: usize,
+  /// the hash of the block of the append
+  pub block_hash: NimbleDigest,
+}
+
+/// returns up to `limit` handles that sort strictly after `start_after`, in ascending order
+pub(crate) fn paginate_handles(
+  mut handles: Vec<Handle>,
+  start_after: Option<&Handle>,
+  limit: usize,
+) -> Vec<Handle> {
+  handles.sort_unstable();
+  handles
+    .into_iter()
+    .filter(|h| start_after.map_or(true, |s| h > s))
+    .take(limit)
+    .collect()
 }
 
+/// decodes the receipts of a stored entry; entries written before the encoding of receipts was
+/// versioned hold the legacy encoding, which is tried only if the versioned one does not parse
+pub(crate) fn decode_stored_receipts(bytes: &[u8]) -> Result<Receipts, CustomSerdeError> {
+  Receipts::from_bytes(bytes).or_else(|e| Receipts::from_legacy_bytes(bytes).map_err(|_| e))
+}
+
+/// Storage backend of the coordinator for ledgers and the view ledger.
+///
+/// Concurrency contract: every mutation is conditional. `append_ledger` and `append_view_ledger`
+/// succeed only if `expected_height` is the height of the new entry (i.e., one more than the
+/// current tail) and fail with `StorageError::IncorrectConditionalData` otherwise, so at most one
+/// of several concurrent appends at the same height wins. `create_ledger` fails with
+/// `StorageError::DuplicateKey` if the handle exists. Receipts and nonces may be attached
+/// concurrently with appends; nonces attached before an append are drained into that entry.
 #[async_trait]
 pub trait LedgerStore {
+  /// creates a ledger whose only entry is `genesis_block` and records `info` along with it
   async fn create_ledger(
     &self,
     handle: &NimbleDigest,
     genesis_block: Block,
+    info: &LedgerInfo,
   ) -> Result<(), LedgerStoreError>;
   async fn append_ledger(
     &self,
@@ -78,6 +214,9 @@ pub trait LedgerStore {
     handle: &Handle,
     idx: usize,
   ) -> Result<LedgerEntry, LedgerStoreError>;
+  /// returns the info recorded when the ledger was created, or the default info if the ledger
+  /// predates it; fails with `StorageError::KeyDoesNotExist` if there is no such ledger
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError>;
   async fn append_view_ledger(
     &self,
     block: &Block,
@@ -90,17 +229,86 @@ pub trait LedgerStore {
   ) -> Result<(), LedgerStoreError>;
   async fn read_view_ledger_tail(&self) -> Result<(LedgerEntry, usize), LedgerStoreError>;
   async fn read_view_ledger_by_index(&self, idx: usize) -> Result<LedgerEntry, LedgerStoreError>;
+  /// lists the handles of the ledgers (excluding the view ledger) in ascending order; pass the
+  /// last handle of a page as `start_after` to fetch the next page
+  async fn list_handles(
+    &self,
+    start_after: Option<&Handle>,
+    limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError>;
+
+  /// returns an estimate of the number of ledgers (excluding the view ledger), or `None` if the
+  /// store cannot estimate it without a scan
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError>;
+
+  /// returns an estimate of the bytes of data that the store holds, or `None` if the store cannot
+  /// estimate it without a scan
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError>;
+
+  /// deletes the contents of the blocks of the entries below `before_height`, keeping their
+  /// hashes, nonces and receipts so that reads return entries whose `get_block_hash` is
+  /// unchanged; purging is idempotent and fails with `StorageError::InvalidIndex` if
+  /// `before_height` is above the tail, so the tail itself is never purged
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError>;
+
+  /// returns the record of a tenant, or the default record if none was written
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError>;
+  /// replaces the record of a tenant
+  async fn write_tenant(&self, tenant: &str, record: &TenantRecord)
+    -> Result<(), LedgerStoreError>;
+
+  /// returns the lease of the coordinators, or the default lease if none was written
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError>;
+  /// replaces the lease with `new` if it still equals `expected`, and fails with
+  /// `StorageError::IncorrectConditionalData` otherwise; the swap must be atomic for every
+  /// process that shares the store, since it is all that keeps two coordinators from holding the
+  /// lease at once
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError>;
+
+  /// records the intent of an append, replacing an intent for the same ledger and height
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError>;
+  /// marks the intent of an append at `height` of a ledger as committed, which removes it;
+  /// committing an intent that is not recorded succeeds
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError>;
+  /// returns the intents that are not committed, in no particular order
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError>;
+
+  /// returns the tokens of the appends to a ledger, or none if none were written
+  async fn read_append_tokens(&self, handle: &Handle)
+    -> Result<Vec<AppendToken>, LedgerStoreError>;
+  /// replaces the tokens of the appends to a ledger
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError>;
 
   async fn reset_store(&self) -> Result<(), LedgerStoreError>; // only used for testing
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::ledger::{
-    azure_table::TableLedgerStore, filestore::FileStore, in_memory::InMemoryLedgerStore,
-    mongodb_cosmos::MongoCosmosLedgerStore, LedgerStore,
+  use crate::{
+    errors::{LedgerStoreError, StorageError},
+    ledger::{
+      azure_table::TableLedgerStore, decode_stored_receipts, filestore::FileStore,
+      in_memory::InMemoryLedgerStore, mongodb_cosmos::MongoCosmosLedgerStore, AppendToken,
+      IntentRecord, LeaseRecord, LedgerInfo, LedgerStore, TenantRecord,
+    },
+  };
+  use ledger::{
+    signature::{PrivateKey, PrivateKeyTrait},
+    Block, CustomSerde, CustomSerdeError, IdSig, MetaBlock, NimbleDigest, NimbleHashTrait, Receipt,
+    Receipts,
   };
-  use ledger::{Block, CustomSerde, NimbleHashTrait};
   use std::collections::HashMap;
 
   pub async fn check_store_creation_and_operations(state: &dyn LedgerStore) {
@@ -111,11 +319,35 @@ mod tests {
 
     let genesis_block = Block::new(&initial_value);
     let handle = genesis_block.hash();
+    let info = LedgerInfo {
+      created_at: 1_650_000_000_000,
+      metadata: b"metadata".to_vec(),
+      handle_bytes: b"client handle".to_vec(),
+      app_bytes: b"app".to_vec(),
+    };
 
     state
-      .create_ledger(&handle, genesis_block)
+      .create_ledger(&handle, genesis_block.clone(), &info)
       .await
       .expect("failed create ledger");
+    assert_eq!(state.read_ledger_info(&handle).await.unwrap(), info);
+
+    // a second creation neither succeeds nor changes the info
+    let res = state
+      .create_ledger(&handle, genesis_block, &LedgerInfo::default())
+      .await;
+    assert!(matches!(
+      res,
+      Err(LedgerStoreError::LedgerError(StorageError::DuplicateKey))
+    ));
+    assert_eq!(state.read_ledger_info(&handle).await.unwrap(), info);
+    let res = state
+      .read_ledger_info(&NimbleDigest::digest(b"missing"))
+      .await;
+    assert!(matches!(
+      res,
+      Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist))
+    ));
 
     let res = state.read_ledger_tail(&handle).await;
     assert!(res.is_ok());
@@ -145,10 +377,210 @@ mod tests {
     let data_at_index = res.unwrap();
     assert_eq!(data_at_index.block.to_bytes(), initial_value);
 
+    // a stale conditional append loses
+    let res = state.append_ledger(&handle, &new_block, height + 1).await;
+    assert!(res.is_err());
+
+    // list the handles page by page
+    let mut handles = vec![handle];
+    for i in 0..4u8 {
+      let block = Block::new(&[i; 8]);
+      state
+        .create_ledger(&block.hash(), block.clone(), &LedgerInfo::default())
+        .await
+        .expect("failed create ledger");
+      handles.push(block.hash());
+    }
+    handles.sort();
+    match state.list_handles(None, 3).await {
+      Err(LedgerStoreError::LedgerError(StorageError::UnsupportedOperation)) => {},
+      res => {
+        let page = res.unwrap();
+        assert_eq!(page, handles[..3].to_vec());
+        let page = state.list_handles(page.last(), 3).await.unwrap();
+        assert_eq!(page, handles[3..].to_vec());
+        let page = state.list_handles(page.last(), 3).await.unwrap();
+        assert!(page.is_empty());
+      },
+    }
+    if let Some(estimate) = state.estimate_num_ledgers().await.unwrap() {
+      assert!(estimate > 0);
+    }
+    // an estimate may lag the writes, so it is only required to be available
+    let _size = state.estimate_size_bytes().await.unwrap();
+
+    // tenants read as unlimited and unused until their record is written
+    assert_eq!(
+      state.read_tenant("tenant").await.unwrap(),
+      TenantRecord::default()
+    );
+    let record = TenantRecord {
+      max_ledgers: 10,
+      max_appends_per_sec: 0,
+      max_stored_bytes: 1 << 20,
+      num_ledgers: 2,
+      stored_bytes: 64,
+    };
+    state.write_tenant("tenant", &record).await.unwrap();
+    assert_eq!(state.read_tenant("tenant").await.unwrap(), record);
+    assert_eq!(
+      state.read_tenant("other").await.unwrap(),
+      TenantRecord::default()
+    );
+
+    // the lease only changes hands if the swap expects the lease that is stored
+    assert_eq!(state.read_lease().await.unwrap(), LeaseRecord::default());
+    let lease = LeaseRecord {
+      holder: "coordinator-a".to_string(),
+      expires_at: 1000,
+      epoch: 1,
+    };
+    state
+      .swap_lease(&LeaseRecord::default(), &lease)
+      .await
+      .unwrap();
+    let res = state
+      .swap_lease(
+        &LeaseRecord::default(),
+        &LeaseRecord {
+          holder: "coordinator-b".to_string(),
+          expires_at: 2000,
+          epoch: 1,
+        },
+      )
+      .await;
+    assert!(matches!(
+      res,
+      Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData
+      ))
+    ));
+    let renewed = LeaseRecord {
+      expires_at: 3000,
+      ..lease.clone()
+    };
+    state.swap_lease(&lease, &renewed).await.unwrap();
+    assert_eq!(state.read_lease().await.unwrap(), renewed);
+
+    // intents are listed until they are committed, and a rewritten intent replaces the old one
+    let intent = |height: usize, block: &[u8]| IntentRecord {
+      handle,
+      height,
+      block_hash: NimbleDigest::digest(block),
+      request_id: format!("request-{}", height),
+    };
+    state.write_intent(&intent(5, b"block")).await.unwrap();
+    state.write_intent(&intent(6, b"block")).await.unwrap();
+    state.write_intent(&intent(5, b"other")).await.unwrap();
+    let mut intents = state.list_intents().await.unwrap();
+    intents.sort_by_key(|intent| intent.height);
+    assert_eq!(intents, vec![intent(5, b"other"), intent(6, b"block")]);
+    state.commit_intent(&handle, 5).await.unwrap();
+    state.commit_intent(&handle, 5).await.unwrap();
+    assert_eq!(
+      state.list_intents().await.unwrap(),
+      vec![intent(6, b"block")]
+    );
+    state.commit_intent(&handle, 6).await.unwrap();
+    assert!(state.list_intents().await.unwrap().is_empty());
+
+    // the tokens of a ledger are replaced as a whole
+    assert!(state.read_append_tokens(&handle).await.unwrap().is_empty());
+    let token = |request_id: &str, height: usize| AppendToken {
+      request_id: request_id.to_string(),
+      height,
+      block_hash: NimbleDigest::digest(request_id.as_bytes()),
+    };
+    let tokens = vec![token("request-1", 1), token("request-2", 2)];
+    state.write_append_tokens(&handle, &tokens).await.unwrap();
+    assert_eq!(state.read_append_tokens(&handle).await.unwrap(), tokens);
+    state
+      .write_append_tokens(&handle, &tokens[1..])
+      .await
+      .unwrap();
+    assert_eq!(
+      state.read_append_tokens(&handle).await.unwrap(),
+      tokens[1..].to_vec()
+    );
+    assert!(state
+      .read_append_tokens(&NimbleDigest::digest(b"missing"))
+      .await
+      .unwrap()
+      .is_empty());
+
+    // purging keeps the hashes of the blocks but not their contents
+    let (tail, tail_height) = state.read_ledger_tail(&handle).await.unwrap();
+    let genesis_hash = state
+      .read_ledger_by_index(&handle, 0)
+      .await
+      .unwrap()
+      .get_block_hash();
+    let res = state.purge_ledger_blocks(&handle, tail_height + 1).await;
+    assert!(matches!(
+      res,
+      Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex))
+    ));
+    for _ in 0..2 {
+      state
+        .purge_ledger_blocks(&handle, tail_height)
+        .await
+        .unwrap();
+      let purged = state.read_ledger_by_index(&handle, 0).await.unwrap();
+      assert!(purged.get_block().to_bytes().is_empty());
+      assert_eq!(
+        purged.get_purged_block_hash(),
+        Some(&Block::new(&initial_value).hash())
+      );
+      assert_eq!(purged.get_block_hash(), genesis_hash);
+    }
+    let (entry, _) = state.read_ledger_tail(&handle).await.unwrap();
+    assert_eq!(entry.get_block().to_bytes(), tail.get_block().to_bytes());
+    assert!(entry.get_purged_block_hash().is_none());
+    state
+      .append_ledger(&handle, &Block::new(b"after purge"), tail_height + 1)
+      .await
+      .unwrap();
+
     let res = state.reset_store().await;
     assert!(res.is_ok());
   }
 
+  #[test]
+  pub fn check_decode_stored_receipts() {
+    let key = PrivateKey::new();
+    let receipt = Receipt::new(
+      NimbleDigest::digest(b"view"),
+      MetaBlock::new(
+        &NimbleDigest::digest(b"prev"),
+        &NimbleDigest::digest(b"block"),
+        1,
+      ),
+      IdSig::new(key.get_public_key().unwrap(), key.sign(b"message").unwrap()),
+    );
+    let mut receipts = Receipts::new();
+    receipts.add(&receipt);
+
+    // both the versioned and the legacy encoding of stored receipts are decoded
+    let versioned = receipts.to_bytes();
+    assert_eq!(
+      decode_stored_receipts(&versioned).unwrap().to_bytes(),
+      versioned
+    );
+    assert_eq!(
+      decode_stored_receipts(&receipt.to_bytes())
+        .unwrap()
+        .to_bytes(),
+      versioned
+    );
+    assert!(decode_stored_receipts(&[]).unwrap().is_empty());
+
+    // anything else is rejected with the error of the versioned encoding
+    assert_eq!(
+      decode_stored_receipts(&versioned[..versioned.len() - 1]).unwrap_err(),
+      CustomSerdeError::IncorrectLength
+    );
+  }
+
   #[tokio::test]
   pub async fn check_in_memory_store() {
     let state = InMemoryLedgerStore::new();
@@ -176,35 +608,43 @@ mod tests {
 
   #[tokio::test]
   pub async fn check_azure_table_store() {
-    if std::env::var_os("STORAGE_ACCOUNT").is_none()
-      || std::env::var_os("STORAGE_MASTER_KEY").is_none()
-      || std::env::var_os("LEDGER_STORE").is_none()
-    {
-      // The right env variables are not available so let's skip tests
-      return;
-    }
-
-    if std::env::var_os("LEDGER_STORE").unwrap() != "table" {
-      // The right env variable is not set so let's skip tests
-      return;
-    }
-
     let mut args = HashMap::<String, String>::new();
-    args.insert(
-      String::from("STORAGE_ACCOUNT"),
-      std::env::var_os("STORAGE_ACCOUNT")
-        .unwrap()
-        .into_string()
-        .unwrap(),
-    );
-
-    args.insert(
-      String::from("STORAGE_MASTER_KEY"),
-      std::env::var_os("STORAGE_MASTER_KEY")
-        .unwrap()
-        .into_string()
-        .unwrap(),
-    );
+    if let Some(conn_str) = std::env::var_os("STORAGE_CONNECTION_STRING") {
+      // e.g., the connection string of Azurite or of a real account
+      args.insert(
+        String::from("STORAGE_CONNECTION_STRING"),
+        conn_str.into_string().unwrap(),
+      );
+    } else {
+      if std::env::var_os("STORAGE_ACCOUNT").is_none()
+        || std::env::var_os("STORAGE_MASTER_KEY").is_none()
+        || std::env::var_os("LEDGER_STORE").is_none()
+      {
+        // The right env variables are not available so let's skip tests
+        return;
+      }
+
+      if std::env::var_os("LEDGER_STORE").unwrap() != "table" {
+        // The right env variable is not set so let's skip tests
+        return;
+      }
+
+      args.insert(
+        String::from("STORAGE_ACCOUNT"),
+        std::env::var_os("STORAGE_ACCOUNT")
+          .unwrap()
+          .into_string()
+          .unwrap(),
+      );
+
+      args.insert(
+        String::from("STORAGE_MASTER_KEY"),
+        std::env::var_os("STORAGE_MASTER_KEY")
+          .unwrap()
+          .into_string()
+          .unwrap(),
+      );
+    }
 
     let state = TableLedgerStore::new(&args).await.unwrap();
     check_store_creation_and_operations(&state).await;
@@ -229,4 +669,22 @@ mod tests {
     let state = FileStore::new(&args).await.unwrap();
     check_store_creation_and_operations(&state).await;
   }
+
+  #[cfg(feature = "rocksdb")]
+  #[tokio::test]
+  pub async fn check_rocksdb_store() {
+    let dir = std::env::temp_dir().join(format!("nimble-rocksdb-{}", std::process::id()));
+    let mut args = HashMap::<String, String>::new();
+    args.insert(
+      String::from("NIMBLE_ROCKSDB_DIR"),
+      dir.to_str().unwrap().to_string(),
+    );
+
+    let state = crate::ledger::rocksdb_store::RocksDBLedgerStore::new(&args)
+      .await
+      .unwrap();
+    check_store_creation_and_operations(&state).await;
+    drop(state);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
 }
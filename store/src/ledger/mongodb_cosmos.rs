@@ -1,14 +1,18 @@
 use crate::{
   errors::{LedgerStoreError, StorageError},
-  ledger::{LedgerEntry, LedgerStore},
+  ledger::{
+    decode_stored_receipts, paginate_handles, AppendToken, IntentRecord, LeaseRecord, LedgerEntry,
+    LedgerInfo, LedgerStore, TenantRecord,
+  },
 };
 use async_trait::async_trait;
 use bincode;
 use hex;
-use ledger::{Block, CustomSerde, Handle, NimbleDigest, Nonce, Nonces, Receipts};
+use ledger::{Block, CustomSerde, Handle, NimbleDigest, NimbleHashTrait, Nonce, Nonces, Receipts};
 use mongodb::{
-  bson::{doc, spec::BinarySubtype, Binary},
+  bson::{doc, spec::BinarySubtype, Binary, Bson},
   error::WriteFailure::WriteError,
+  options::ReplaceOptions,
   Client, Collection,
 };
 use serde::{Deserialize, Serialize};
@@ -117,6 +121,84 @@ struct DBEntry {
   #[serde(rename = "_id")]
   index: i64,
   value: Binary, // SerializedLedgerEntry
+  // the hash of the block if its contents were purged, in which case the block of `value` is empty
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  purged_hash: Option<Binary>,
+}
+
+// the info of every ledger lives in one collection whose name is not a valid handle
+const INFO_COLLECTION: &str = "ledger_info";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct InfoEntry {
+  #[serde(rename = "_id")]
+  handle: String,
+  created_at: i64,
+  metadata: Binary,
+  handle_bytes: Binary,
+  app_bytes: Binary,
+}
+
+// the records of tenants live in one collection whose name is not a valid handle either
+const TENANT_COLLECTION: &str = "tenants";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct TenantEntry {
+  #[serde(rename = "_id")]
+  tenant: String,
+  max_ledgers: i64,
+  max_appends_per_sec: i64,
+  max_stored_bytes: i64,
+  num_ledgers: i64,
+  stored_bytes: i64,
+}
+
+// the lease of the coordinators is the only document of its own collection
+const LEASE_COLLECTION: &str = "lease";
+const LEASE_ID: &str = "lease";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct LeaseEntry {
+  #[serde(rename = "_id")]
+  id: String,
+  holder: String,
+  expires_at: i64,
+  epoch: i64,
+}
+
+// the intents of appends live in one collection, keyed by the ledger and the height they extend
+// it to
+const INTENT_COLLECTION: &str = "intents";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct IntentEntry {
+  #[serde(rename = "_id")]
+  id: String,
+  handle: String,
+  height: i64,
+  block_hash: String,
+  request_id: String,
+}
+
+// the request IDs of the appends to a ledger are a document of one collection, keyed by the ledger
+const TOKENS_COLLECTION: &str = "append_tokens";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct AppendTokenEntry {
+  request_id: String,
+  height: i64,
+  block_hash: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct AppendTokensEntry {
+  #[serde(rename = "_id")]
+  id: String,
+  tokens: Vec<AppendTokenEntry>,
+}
+
+fn intent_id(handle: &Handle, height: usize) -> String {
+  format!("{}-{}", hex::encode(handle.to_bytes()), height)
 }
 
 #[derive(Debug)]
@@ -191,6 +273,7 @@ impl MongoCosmosLedgerStore {
           let tail_entry = DBEntry {
             index: 0_i64,
             value: bson_entry.clone(),
+            purged_hash: None,
           };
 
           ledger_store
@@ -278,6 +361,7 @@ async fn append_ledger_op(
   let new_entry = DBEntry {
     index: height_plus_one,
     value: bson_new_ledger_entry,
+    purged_hash: None,
   };
 
   // 4. Try to insert the new entry into the ledger.
@@ -306,7 +390,7 @@ async fn attach_ledger_receipts_op(
     .expect("failed to deserialize ledger entry");
 
   let mut ledger_entry_receipts =
-    Receipts::from_bytes(&ledger_entry.receipts).expect("failed to deserialize receipt");
+    decode_stored_receipts(&ledger_entry.receipts).expect("failed to deserialize receipt");
 
   // 4. Update receipt
   ledger_entry_receipts.merge_receipts(receipts);
@@ -332,10 +416,54 @@ async fn attach_ledger_receipts_op(
   Ok(())
 }
 
+async fn purge_ledger_blocks_op(
+  before_height: usize,
+  ledger: &Collection<DBEntry>,
+) -> Result<(), LedgerStoreError> {
+  let before_height = checked_conversion!(before_height, i64);
+  if before_height > find_ledger_height(ledger).await? {
+    return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+  }
+
+  // every entry is purged on its own, so an interrupted purge is finished by the next one
+  for index in 0..before_height {
+    let db_entry = find_db_entry(ledger, index).await?;
+    if db_entry.purged_hash.is_some() {
+      continue;
+    }
+    let mut entry: SerializedLedgerEntry =
+      bincode::deserialize(&db_entry.value.bytes).expect("failed to deserialize entry");
+    let block_hash = Block::from_bytes(&entry.block).unwrap().hash();
+    entry.block = Block::new(&[]).to_bytes();
+
+    let write_bson_ledger_entry: Binary = bincode::serialize(&entry)
+      .expect("failed to serialized ledger entry")
+      .to_bson_binary();
+    ledger
+      .update_one(
+        doc! {
+            "_id": index,
+        },
+        doc! {
+            "$set": {
+              "value": write_bson_ledger_entry,
+              "purged_hash": block_hash.to_bytes().to_bson_binary(),
+            },
+        },
+        None,
+      )
+      .await?;
+  }
+
+  Ok(())
+}
+
 async fn create_ledger_op(
   handle: &Handle,
   genesis_block: &Block,
+  info: &LedgerInfo,
   ledger: &Collection<DBEntry>,
+  infos: &Collection<InfoEntry>,
   cache: &CacheMap,
 ) -> Result<(), LedgerStoreError> {
   // 1. Create the ledger entry that we will add to the brand new ledger
@@ -352,6 +480,7 @@ async fn create_ledger_op(
   let genesis_entry = DBEntry {
     index: 0,
     value: bson_init_data_ledger_entry,
+    purged_hash: None,
   };
 
   ledger.insert_one(&genesis_entry, None).await?;
@@ -359,6 +488,26 @@ async fn create_ledger_op(
   // Update the ledger's cache height with the the latest height (which is 0)
   update_cache_entry(handle, cache, 0)?;
 
+  // 3. Record the ledger's info; a ledger whose creation stopped short of this reads as having
+  // the default info
+  let info_entry = InfoEntry {
+    handle: hex::encode(handle.to_bytes()),
+    created_at: checked_conversion!(info.created_at, i64),
+    metadata: Binary {
+      subtype: BinarySubtype::Generic,
+      bytes: info.metadata.clone(),
+    },
+    handle_bytes: Binary {
+      subtype: BinarySubtype::Generic,
+      bytes: info.handle_bytes.clone(),
+    },
+    app_bytes: Binary {
+      subtype: BinarySubtype::Generic,
+      bytes: info.app_bytes.clone(),
+    },
+  };
+  infos.insert_one(&info_entry, None).await?;
+
   Ok(())
 }
 
@@ -398,11 +547,19 @@ async fn read_ledger_op(
   let entry: SerializedLedgerEntry =
     bincode::deserialize(&bson_entry.bytes).expect("failed to deserialize entry");
 
-  let res = LedgerEntry::new(
-    Block::from_bytes(&entry.block).unwrap(),
-    Receipts::from_bytes(&entry.receipts).unwrap(),
-    None, //TODO
-  );
+  let res = match &ledger_entry.purged_hash {
+    Some(purged_hash) => LedgerEntry::new_purged(
+      NimbleDigest::from_bytes(&purged_hash.bytes)
+        .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?,
+      decode_stored_receipts(&entry.receipts).unwrap(),
+      Nonces::new(),
+    ),
+    None => LedgerEntry::new(
+      Block::from_bytes(&entry.block).unwrap(),
+      decode_stored_receipts(&entry.receipts).unwrap(),
+      None, //TODO
+    ),
+  };
 
   Ok((res, checked_conversion!(index, usize)))
 }
@@ -527,15 +684,19 @@ impl LedgerStore for MongoCosmosLedgerStore {
     &self,
     handle: &Handle,
     genesis_block: Block,
+    info: &LedgerInfo,
   ) -> Result<(), LedgerStoreError> {
     let client = self.client.clone();
     let ledger = client
       .database(&self.dbname)
       .collection::<DBEntry>(&hex::encode(&handle.to_bytes()));
+    let infos = client
+      .database(&self.dbname)
+      .collection::<InfoEntry>(INFO_COLLECTION);
 
     loop {
       with_retry!(
-        create_ledger_op(handle, &genesis_block, &ledger, &self.cache).await,
+        create_ledger_op(handle, &genesis_block, info, &ledger, &infos, &self.cache).await,
         handle,
         &self.cache,
         &ledger
@@ -620,6 +781,40 @@ impl LedgerStore for MongoCosmosLedgerStore {
     Ok(entry)
   }
 
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError> {
+    let client = self.client.clone();
+    let infos = client
+      .database(&self.dbname)
+      .collection::<InfoEntry>(INFO_COLLECTION);
+
+    let res = infos
+      .find_one(
+        doc! {
+            "_id": hex::encode(handle.to_bytes()),
+        },
+        None,
+      )
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+
+    match res {
+      Some(entry) => Ok(LedgerInfo {
+        created_at: checked_conversion!(entry.created_at, u64),
+        metadata: entry.metadata.bytes,
+        handle_bytes: entry.handle_bytes.bytes,
+        app_bytes: entry.app_bytes.bytes,
+      }),
+      None => {
+        // ledgers created before info was recorded exist without it
+        let ledger = client
+          .database(&self.dbname)
+          .collection::<DBEntry>(&hex::encode(handle.to_bytes()));
+        find_ledger_height(&ledger).await?;
+        Ok(LedgerInfo::default())
+      },
+    }
+  }
+
   async fn read_view_ledger_tail(&self) -> Result<(LedgerEntry, usize), LedgerStoreError> {
     self.read_ledger_tail(&self.view_handle).await
   }
@@ -649,6 +844,342 @@ impl LedgerStore for MongoCosmosLedgerStore {
     Ok(res.0)
   }
 
+  async fn list_handles(
+    &self,
+    start_after: Option<&Handle>,
+    limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError> {
+    // every ledger is a collection named by the hex encoding of its handle
+    let names = self
+      .client
+      .database(&self.dbname)
+      .list_collection_names(None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    let handles = names
+      .iter()
+      .filter_map(|name| hex::decode(name).ok())
+      .filter_map(|bytes| NimbleDigest::from_bytes(&bytes).ok())
+      .filter(|handle| *handle != self.view_handle)
+      .collect();
+    Ok(paginate_handles(handles, start_after, limit))
+  }
+
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError> {
+    // every ledger created since info was recorded has one info document, whose count mongodb
+    // keeps in the metadata of the collection
+    let client = self.client.clone();
+    let count = client
+      .database(&self.dbname)
+      .collection::<InfoEntry>(INFO_COLLECTION)
+      .estimated_document_count(None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    Ok(Some(checked_conversion!(count, usize)))
+  }
+
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError> {
+    // the size of the documents of the database, which mongodb keeps in its statistics
+    let client = self.client.clone();
+    let stats = client
+      .database(&self.dbname)
+      .run_command(doc! { "dbStats": 1 }, None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    match stats.get("dataSize") {
+      Some(Bson::Int32(n)) => Ok(Some(checked_conversion!(*n, u64))),
+      Some(Bson::Int64(n)) => Ok(Some(checked_conversion!(*n, u64))),
+      Some(Bson::Double(n)) if *n >= 0.0 => Ok(Some(*n as u64)),
+      _ => Ok(None),
+    }
+  }
+
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError> {
+    let client = self.client.clone();
+    let ledger = client
+      .database(&self.dbname)
+      .collection::<DBEntry>(&hex::encode(handle.to_bytes()));
+
+    loop {
+      with_retry!(
+        purge_ledger_blocks_op(before_height, &ledger).await,
+        handle,
+        &self.cache,
+        &ledger
+      );
+    }
+  }
+
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError> {
+    let client = self.client.clone();
+    let tenants = client
+      .database(&self.dbname)
+      .collection::<TenantEntry>(TENANT_COLLECTION);
+
+    let res = tenants
+      .find_one(doc! { "_id": tenant }, None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+
+    match res {
+      Some(entry) => Ok(TenantRecord {
+        max_ledgers: checked_conversion!(entry.max_ledgers, u64),
+        max_appends_per_sec: checked_conversion!(entry.max_appends_per_sec, u64),
+        max_stored_bytes: checked_conversion!(entry.max_stored_bytes, u64),
+        num_ledgers: checked_conversion!(entry.num_ledgers, u64),
+        stored_bytes: checked_conversion!(entry.stored_bytes, u64),
+      }),
+      None => Ok(TenantRecord::default()),
+    }
+  }
+
+  async fn write_tenant(
+    &self,
+    tenant: &str,
+    record: &TenantRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let client = self.client.clone();
+    let tenants = client
+      .database(&self.dbname)
+      .collection::<TenantEntry>(TENANT_COLLECTION);
+
+    let entry = TenantEntry {
+      tenant: tenant.to_string(),
+      max_ledgers: checked_conversion!(record.max_ledgers, i64),
+      max_appends_per_sec: checked_conversion!(record.max_appends_per_sec, i64),
+      max_stored_bytes: checked_conversion!(record.max_stored_bytes, i64),
+      num_ledgers: checked_conversion!(record.num_ledgers, i64),
+      stored_bytes: checked_conversion!(record.stored_bytes, i64),
+    };
+    tenants
+      .replace_one(
+        doc! { "_id": tenant },
+        entry,
+        ReplaceOptions::builder().upsert(true).build(),
+      )
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    Ok(())
+  }
+
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    let client = self.client.clone();
+    let lease = client
+      .database(&self.dbname)
+      .collection::<LeaseEntry>(LEASE_COLLECTION);
+
+    let res = lease
+      .find_one(doc! { "_id": LEASE_ID }, None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+
+    match res {
+      Some(entry) => Ok(LeaseRecord {
+        holder: entry.holder,
+        expires_at: checked_conversion!(entry.expires_at, u64),
+        epoch: checked_conversion!(entry.epoch, u64),
+      }),
+      None => Ok(LeaseRecord::default()),
+    }
+  }
+
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let client = self.client.clone();
+    let lease = client
+      .database(&self.dbname)
+      .collection::<LeaseEntry>(LEASE_COLLECTION);
+
+    let entry = LeaseEntry {
+      id: LEASE_ID.to_string(),
+      holder: new.holder.clone(),
+      expires_at: checked_conversion!(new.expires_at, i64),
+      epoch: checked_conversion!(new.epoch, i64),
+    };
+    // the default lease is only inserted if there is no lease yet: an upsert that does not match
+    // the stored lease collides with its id
+    let res = lease
+      .replace_one(
+        doc! {
+          "_id": LEASE_ID,
+          "holder": expected.holder.clone(),
+          "expires_at": checked_conversion!(expected.expires_at, i64),
+          "epoch": checked_conversion!(expected.epoch, i64),
+        },
+        entry,
+        ReplaceOptions::builder()
+          .upsert(*expected == LeaseRecord::default())
+          .build(),
+      )
+      .await;
+    match res {
+      Ok(res) if res.matched_count == 0 && res.upserted_id.is_none() => Err(
+        LedgerStoreError::LedgerError(StorageError::IncorrectConditionalData),
+      ),
+      Ok(_) => Ok(()),
+      Err(error) => match error.kind.as_ref() {
+        mongodb::error::ErrorKind::Write(WriteError(write_error))
+          if write_error.code == DUPLICATE_KEY_CODE =>
+        {
+          Err(LedgerStoreError::LedgerError(
+            StorageError::IncorrectConditionalData,
+          ))
+        },
+        _ => Err(LedgerStoreError::MongoDBError(error)),
+      },
+    }
+  }
+
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError> {
+    let client = self.client.clone();
+    let intents = client
+      .database(&self.dbname)
+      .collection::<IntentEntry>(INTENT_COLLECTION);
+
+    let id = intent_id(&intent.handle, intent.height);
+    let entry = IntentEntry {
+      id: id.clone(),
+      handle: hex::encode(intent.handle.to_bytes()),
+      height: checked_conversion!(intent.height, i64),
+      block_hash: hex::encode(intent.block_hash.to_bytes()),
+      request_id: intent.request_id.clone(),
+    };
+    intents
+      .replace_one(
+        doc! { "_id": id },
+        entry,
+        ReplaceOptions::builder().upsert(true).build(),
+      )
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    Ok(())
+  }
+
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError> {
+    let client = self.client.clone();
+    let intents = client
+      .database(&self.dbname)
+      .collection::<IntentEntry>(INTENT_COLLECTION);
+
+    intents
+      .delete_one(doc! { "_id": intent_id(handle, height) }, None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    Ok(())
+  }
+
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError> {
+    let client = self.client.clone();
+    let intents = client
+      .database(&self.dbname)
+      .collection::<IntentEntry>(INTENT_COLLECTION);
+
+    let digest = |hex_str: &str| {
+      hex::decode(hex_str)
+        .ok()
+        .and_then(|bytes| NimbleDigest::from_bytes(&bytes).ok())
+        .ok_or(LedgerStoreError::LedgerError(
+          StorageError::DeserializationError,
+        ))
+    };
+    let mut cursor = intents
+      .find(None, None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    let mut records = Vec::new();
+    while cursor
+      .advance()
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?
+    {
+      let entry = cursor
+        .deserialize_current()
+        .map_err(LedgerStoreError::MongoDBError)?;
+      records.push(IntentRecord {
+        handle: digest(&entry.handle)?,
+        height: checked_conversion!(entry.height, usize),
+        block_hash: digest(&entry.block_hash)?,
+        request_id: entry.request_id,
+      });
+    }
+    Ok(records)
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, LedgerStoreError> {
+    let client = self.client.clone();
+    let append_tokens = client
+      .database(&self.dbname)
+      .collection::<AppendTokensEntry>(TOKENS_COLLECTION);
+
+    let res = append_tokens
+      .find_one(doc! { "_id": hex::encode(handle.to_bytes()) }, None)
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    let entry = match res {
+      Some(entry) => entry,
+      None => return Ok(vec![]),
+    };
+
+    let mut tokens = Vec::with_capacity(entry.tokens.len());
+    for token in entry.tokens {
+      let block_hash = hex::decode(&token.block_hash)
+        .ok()
+        .and_then(|bytes| NimbleDigest::from_bytes(&bytes).ok())
+        .ok_or(LedgerStoreError::LedgerError(
+          StorageError::DeserializationError,
+        ))?;
+      tokens.push(AppendToken {
+        request_id: token.request_id,
+        height: checked_conversion!(token.height, usize),
+        block_hash,
+      });
+    }
+    Ok(tokens)
+  }
+
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError> {
+    let client = self.client.clone();
+    let append_tokens = client
+      .database(&self.dbname)
+      .collection::<AppendTokensEntry>(TOKENS_COLLECTION);
+
+    let id = hex::encode(handle.to_bytes());
+    let mut entry = AppendTokensEntry {
+      id: id.clone(),
+      tokens: Vec::with_capacity(tokens.len()),
+    };
+    for token in tokens {
+      entry.tokens.push(AppendTokenEntry {
+        request_id: token.request_id.clone(),
+        height: checked_conversion!(token.height, i64),
+        block_hash: hex::encode(token.block_hash.to_bytes()),
+      });
+    }
+    append_tokens
+      .replace_one(
+        doc! { "_id": id },
+        entry,
+        ReplaceOptions::builder().upsert(true).build(),
+      )
+      .await
+      .map_err(LedgerStoreError::MongoDBError)?;
+    Ok(())
+  }
+
   async fn reset_store(&self) -> Result<(), LedgerStoreError> {
     let client = self.client.clone();
     client
@@ -0,0 +1,961 @@
+//! A `LedgerStore` over RocksDB for coordinators that hold many ledgers on local disk.
+//!
+//! Column families:
+//!
+//! * `blocks` maps `handle || height` to the entry at that height of a ledger.
+//! * `tails` maps `handle` to the height of the ledger's tail.
+//! * `nonces` holds `handle || nonce` for nonces not yet attached to an entry.
+//! * `view` maps `height` to the entry at that height of the view ledger.
+//! * `info` maps `handle` to the creation time, metadata, and client handle of the ledger.
+//! * `tenants` maps the id of a tenant to its quota and usage.
+//! * `purged` maps `handle || height` to the hash of the block at that height of a ledger if the
+//!   contents of the block were purged, in which case the block of its entry is empty.
+//! * `intents` maps `handle || height` to the intent of an append at that height of a ledger
+//!   until the append is committed.
+//! * `append_tokens` maps `handle` to the request IDs that the coordinator retains for the appends
+//!   to the ledger.
+//! * `lease` holds the lease of the coordinators under a single key; since RocksDB locks its
+//!   directory, a standby coordinator cannot share the store with the one that holds the lease.
+//!
+//! Heights are encoded as big-endian `u64`s so that the entries of a ledger sort by height and
+//! the tails sort by handle. An append writes the new entry, the new tail and the drained nonces
+//! in one synced write batch; appends to the same ledger are serialized by an in-process lock,
+//! which together with the lock RocksDB holds on its directory makes the tail check and the
+//! batch atomic.
+use crate::{
+  errors::{LedgerStoreError, StorageError},
+  ledger::{
+    AppendToken, IntentRecord, LeaseRecord, LedgerEntry, LedgerInfo, LedgerStore, TenantRecord,
+  },
+};
+use async_trait::async_trait;
+use ledger::{Block, CustomSerde, Handle, NimbleDigest, NimbleHashTrait, Nonce, Nonces, Receipts};
+use rocksdb::{
+  BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, DBCompressionType, Direction,
+  IteratorMode, Options, WriteBatch, WriteOptions, DB,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::HashMap,
+  convert::{TryFrom, TryInto},
+  sync::{Mutex, MutexGuard},
+};
+
+const BLOCKS_CF: &str = "blocks";
+const TAILS_CF: &str = "tails";
+const NONCES_CF: &str = "nonces";
+const VIEW_CF: &str = "view";
+const INFO_CF: &str = "info";
+const TENANTS_CF: &str = "tenants";
+const PURGED_CF: &str = "purged";
+const LEASE_CF: &str = "lease";
+const INTENTS_CF: &str = "intents";
+const TOKENS_CF: &str = "append_tokens";
+const COLUMN_FAMILIES: [&str; 10] = [
+  BLOCKS_CF, TAILS_CF, NONCES_CF, VIEW_CF, INFO_CF, TENANTS_CF, PURGED_CF, LEASE_CF, INTENTS_CF,
+  TOKENS_CF,
+];
+const LEASE_KEY: &[u8] = b"lease";
+
+const DEFAULT_CACHE_MB: usize = 512;
+const NUM_LOCK_STRIPES: usize = 256;
+
+macro_rules! checked_conversion {
+  ($x:expr, $type:tt) => {
+    match $type::try_from($x) {
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(StorageError::IntegerOverflow));
+      },
+      Ok(v) => v,
+    }
+  };
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBEntry {
+  pub block: Vec<u8>,
+  pub receipts: Vec<u8>,
+  pub nonces: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBInfoEntry {
+  pub created_at: u64,
+  pub metadata: Vec<u8>,
+  pub handle_bytes: Vec<u8>,
+  pub app_bytes: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBTenantEntry {
+  pub max_ledgers: u64,
+  pub max_appends_per_sec: u64,
+  pub max_stored_bytes: u64,
+  pub num_ledgers: u64,
+  pub stored_bytes: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBLeaseEntry {
+  pub holder: String,
+  pub expires_at: u64,
+  pub epoch: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBIntentEntry {
+  pub block_hash: Vec<u8>,
+  pub request_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DBAppendTokenEntry {
+  pub request_id: String,
+  pub height: u64,
+  pub block_hash: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct RocksDBLedgerStore {
+  db: DB,
+  // appends, nonces and receipts of a ledger are serialized by the stripe its handle maps to
+  ledger_locks: Vec<Mutex<()>>,
+  view_lock: Mutex<()>,
+  lease_lock: Mutex<()>,
+}
+
+fn rocksdb_error(e: rocksdb::Error) -> LedgerStoreError {
+  eprintln!("RocksDB error: {:?}", e);
+  LedgerStoreError::LedgerError(StorageError::UnhandledError)
+}
+
+fn height_key(height: u64) -> [u8; 8] {
+  height.to_be_bytes()
+}
+
+fn ledger_key(handle: &Handle, height: u64) -> Vec<u8> {
+  [handle.to_bytes(), height_key(height).to_vec()].concat()
+}
+
+fn nonce_key(handle: &Handle, nonce: &Nonce) -> Vec<u8> {
+  [handle.to_bytes(), nonce.to_bytes()].concat()
+}
+
+fn parse_height(bytes: &[u8]) -> Result<u64, LedgerStoreError> {
+  match <[u8; 8]>::try_from(bytes) {
+    Ok(b) => Ok(u64::from_be_bytes(b)),
+    Err(_) => Err(LedgerStoreError::LedgerError(
+      StorageError::DeserializationError,
+    )),
+  }
+}
+
+fn serialize_entry(entry: &DBEntry) -> Result<Vec<u8>, LedgerStoreError> {
+  bincode::serialize(entry)
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))
+}
+
+fn deserialize_entry(bytes: &[u8]) -> Result<DBEntry, LedgerStoreError> {
+  bincode::deserialize(bytes)
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))
+}
+
+fn to_ledger_entry(entry: &DBEntry) -> Result<LedgerEntry, LedgerStoreError> {
+  match (
+    Block::from_bytes(&entry.block),
+    Receipts::from_bytes(&entry.receipts),
+    Nonces::from_bytes(&entry.nonces),
+  ) {
+    (Ok(block), Ok(receipts), Ok(nonces)) => Ok(LedgerEntry::new(block, receipts, Some(nonces))),
+    _ => Err(LedgerStoreError::LedgerError(
+      StorageError::DeserializationError,
+    )),
+  }
+}
+
+fn parse_compression(name: &str) -> Result<DBCompressionType, LedgerStoreError> {
+  match name {
+    "none" => Ok(DBCompressionType::None),
+    "snappy" => Ok(DBCompressionType::Snappy),
+    "lz4" => Ok(DBCompressionType::Lz4),
+    "zstd" => Ok(DBCompressionType::Zstd),
+    _ => {
+      eprintln!("Unknown RocksDB compression {}", name);
+      Err(LedgerStoreError::LedgerError(StorageError::BadRequest))
+    },
+  }
+}
+
+impl RocksDBLedgerStore {
+  /// Opens (creating if needed) the database in `NIMBLE_ROCKSDB_DIR`. `NIMBLE_ROCKSDB_CACHE_MB`
+  /// sets the size of the block cache (default 512) and `NIMBLE_ROCKSDB_COMPRESSION` one of
+  /// `none`, `snappy`, `lz4` (default) or `zstd`.
+  pub async fn new(args: &HashMap<String, String>) -> Result<Self, LedgerStoreError> {
+    if !args.contains_key("NIMBLE_ROCKSDB_DIR") {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::MissingArguments,
+      ));
+    }
+    let dir_path = &args["NIMBLE_ROCKSDB_DIR"];
+
+    let cache_mb = match args.get("NIMBLE_ROCKSDB_CACHE_MB") {
+      Some(mb) => match mb.parse::<usize>() {
+        Ok(mb) => mb,
+        Err(_) => {
+          eprintln!("Invalid RocksDB cache size {}", mb);
+          return Err(LedgerStoreError::LedgerError(StorageError::BadRequest));
+        },
+      },
+      None => DEFAULT_CACHE_MB,
+    };
+    let compression = match args.get("NIMBLE_ROCKSDB_COMPRESSION") {
+      Some(c) => parse_compression(c)?,
+      None => DBCompressionType::Lz4,
+    };
+    let cache_bytes = match cache_mb.checked_mul(1024 * 1024) {
+      Some(b) => b,
+      None => return Err(LedgerStoreError::LedgerError(StorageError::IntegerOverflow)),
+    };
+
+    // all column families share one block cache; point lookups are helped by bloom filters
+    let cache = Cache::new_lru_cache(cache_bytes);
+    let mut table_opts = BlockBasedOptions::default();
+    table_opts.set_block_cache(&cache);
+    table_opts.set_bloom_filter(10.0, false);
+
+    let mut cf_opts = Options::default();
+    cf_opts.set_compression_type(compression);
+    cf_opts.set_block_based_table_factory(&table_opts);
+
+    let mut db_opts = Options::default();
+    db_opts.create_if_missing(true);
+    db_opts.create_missing_column_families(true);
+
+    let cfs = COLUMN_FAMILIES
+      .iter()
+      .map(|name| ColumnFamilyDescriptor::new(*name, cf_opts.clone()))
+      .collect::<Vec<ColumnFamilyDescriptor>>();
+
+    let db = DB::open_cf_descriptors(&db_opts, dir_path, cfs).map_err(|e| {
+      eprintln!("Unable to open RocksDB at {}: {:?}", dir_path, e);
+      LedgerStoreError::LedgerError(StorageError::InvalidDBUri)
+    })?;
+
+    let store = RocksDBLedgerStore {
+      db,
+      ledger_locks: (0..NUM_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+      view_lock: Mutex::new(()),
+      lease_lock: Mutex::new(()),
+    };
+
+    // Initialize the view ledger on first use
+    let view_cf = store.cf(VIEW_CF)?;
+    if store
+      .db
+      .get_cf(view_cf, height_key(0))
+      .map_err(rocksdb_error)?
+      .is_none()
+    {
+      let entry = serialize_entry(&DBEntry {
+        block: Block::new(&[0; 0]).to_bytes(),
+        receipts: Receipts::new().to_bytes(),
+        nonces: Nonces::new().to_bytes(),
+      })?;
+      let mut batch = WriteBatch::default();
+      batch.put_cf(view_cf, height_key(0), entry);
+      store.write(batch)?;
+    }
+
+    Ok(store)
+  }
+
+  fn cf(&self, name: &str) -> Result<&ColumnFamily, LedgerStoreError> {
+    match self.db.cf_handle(name) {
+      Some(cf) => Ok(cf),
+      None => Err(LedgerStoreError::LedgerError(StorageError::UnhandledError)),
+    }
+  }
+
+  fn lock_ledger(&self, handle: &Handle) -> Result<MutexGuard<'_, ()>, LedgerStoreError> {
+    let stripe = handle.to_bytes()[0] as usize % NUM_LOCK_STRIPES;
+    self.ledger_locks[stripe]
+      .lock()
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::LedgerWriteLockFailed))
+  }
+
+  fn lock_view(&self) -> Result<MutexGuard<'_, ()>, LedgerStoreError> {
+    self
+      .view_lock
+      .lock()
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::ViewLedgerWriteLockFailed))
+  }
+
+  fn read_lease_entry(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    let res = self
+      .db
+      .get_cf(self.cf(LEASE_CF)?, LEASE_KEY)
+      .map_err(rocksdb_error)?;
+    match res {
+      Some(bytes) => {
+        let entry: DBLeaseEntry = bincode::deserialize(&bytes)
+          .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?;
+        Ok(LeaseRecord {
+          holder: entry.holder,
+          expires_at: entry.expires_at,
+          epoch: entry.epoch,
+        })
+      },
+      None => Ok(LeaseRecord::default()),
+    }
+  }
+
+  fn write(&self, batch: WriteBatch) -> Result<(), LedgerStoreError> {
+    let mut write_opts = WriteOptions::default();
+    write_opts.set_sync(true);
+    self.db.write_opt(batch, &write_opts).map_err(rocksdb_error)
+  }
+
+  fn read_tail_height(&self, handle: &Handle) -> Result<Option<u64>, LedgerStoreError> {
+    match self
+      .db
+      .get_cf(self.cf(TAILS_CF)?, handle.to_bytes())
+      .map_err(rocksdb_error)?
+    {
+      Some(bytes) => Ok(Some(parse_height(&bytes)?)),
+      None => Ok(None),
+    }
+  }
+
+  fn read_view_tail_height(&self) -> Result<u64, LedgerStoreError> {
+    let mut iter = self.db.iterator_cf(self.cf(VIEW_CF)?, IteratorMode::End);
+    match iter.next() {
+      Some(Ok((key, _))) => parse_height(&key),
+      Some(Err(e)) => Err(rocksdb_error(e)),
+      None => Err(LedgerStoreError::LedgerError(
+        StorageError::FailedToInitializeViewLedger,
+      )),
+    }
+  }
+
+  fn read_entry(&self, cf_name: &str, key: &[u8]) -> Result<Option<DBEntry>, LedgerStoreError> {
+    match self
+      .db
+      .get_cf(self.cf(cf_name)?, key)
+      .map_err(rocksdb_error)?
+    {
+      Some(bytes) => Ok(Some(deserialize_entry(&bytes)?)),
+      None => Ok(None),
+    }
+  }
+
+  /// reads the entry of a ledger at `key`, which keeps the hash of its block if it was purged
+  fn read_ledger_entry(&self, key: &[u8]) -> Result<Option<LedgerEntry>, LedgerStoreError> {
+    let entry = match self.read_entry(BLOCKS_CF, key)? {
+      Some(e) => e,
+      None => return Ok(None),
+    };
+    let ledger_entry = to_ledger_entry(&entry)?;
+    if !entry.block.is_empty() {
+      return Ok(Some(ledger_entry));
+    }
+    match self
+      .db
+      .get_cf(self.cf(PURGED_CF)?, key)
+      .map_err(rocksdb_error)?
+    {
+      Some(bytes) => match NimbleDigest::from_bytes(&bytes) {
+        Ok(block_hash) => Ok(Some(LedgerEntry::new_purged(
+          block_hash,
+          ledger_entry.get_receipts().clone(),
+          ledger_entry.get_nonces().clone(),
+        ))),
+        Err(_) => Err(LedgerStoreError::LedgerError(
+          StorageError::DeserializationError,
+        )),
+      },
+      None => Ok(Some(ledger_entry)),
+    }
+  }
+
+  /// returns the nonces of `handle` waiting for the next append along with their keys
+  fn pending_nonces(&self, handle: &Handle) -> Result<(Nonces, Vec<Box<[u8]>>), LedgerStoreError> {
+    let prefix = handle.to_bytes();
+    let mut nonces = Nonces::new();
+    let mut keys = Vec::new();
+    for item in self.db.iterator_cf(
+      self.cf(NONCES_CF)?,
+      IteratorMode::From(&prefix, Direction::Forward),
+    ) {
+      let (key, _) = item.map_err(rocksdb_error)?;
+      if !key.starts_with(&prefix) {
+        break;
+      }
+      match Nonce::from_bytes(&key[prefix.len()..]) {
+        Ok(n) => nonces.add(n),
+        Err(_) => {
+          return Err(LedgerStoreError::LedgerError(
+            StorageError::DeserializationError,
+          ));
+        },
+      }
+      keys.push(key);
+    }
+    Ok((nonces, keys))
+  }
+}
+
+#[async_trait]
+impl LedgerStore for RocksDBLedgerStore {
+  async fn create_ledger(
+    &self,
+    handle: &NimbleDigest,
+    genesis_block: Block,
+    info: &LedgerInfo,
+  ) -> Result<(), LedgerStoreError> {
+    let _guard = self.lock_ledger(handle)?;
+
+    if self.read_tail_height(handle)?.is_some() {
+      return Err(LedgerStoreError::LedgerError(StorageError::DuplicateKey));
+    }
+
+    let entry = serialize_entry(&DBEntry {
+      block: genesis_block.to_bytes(),
+      receipts: Receipts::new().to_bytes(),
+      nonces: Nonces::new().to_bytes(),
+    })?;
+
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(BLOCKS_CF)?, ledger_key(handle, 0), entry);
+    batch.put_cf(self.cf(TAILS_CF)?, handle.to_bytes(), height_key(0));
+    let info_entry = bincode::serialize(&DBInfoEntry {
+      created_at: info.created_at,
+      metadata: info.metadata.clone(),
+      handle_bytes: info.handle_bytes.clone(),
+      app_bytes: info.app_bytes.clone(),
+    })
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))?;
+    batch.put_cf(self.cf(INFO_CF)?, handle.to_bytes(), info_entry);
+    self.write(batch)
+  }
+
+  async fn append_ledger(
+    &self,
+    handle: &Handle,
+    block: &Block,
+    expected_height: usize,
+  ) -> Result<(usize, Nonces), LedgerStoreError> {
+    let _guard = self.lock_ledger(handle)?;
+
+    let tail_height = match self.read_tail_height(handle)? {
+      Some(h) => h,
+      None => {
+        return Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist));
+      },
+    };
+
+    let height = checked_conversion!(expected_height, u64);
+    if tail_height.checked_add(1) != Some(height) {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData,
+      ));
+    }
+
+    let (nonces, nonce_keys) = self.pending_nonces(handle)?;
+    let entry = serialize_entry(&DBEntry {
+      block: block.to_bytes(),
+      receipts: Receipts::new().to_bytes(),
+      nonces: nonces.to_bytes(),
+    })?;
+
+    // the entry, the tail and the drained nonces change together
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(BLOCKS_CF)?, ledger_key(handle, height), entry);
+    batch.put_cf(self.cf(TAILS_CF)?, handle.to_bytes(), height_key(height));
+    let nonces_cf = self.cf(NONCES_CF)?;
+    for key in nonce_keys {
+      batch.delete_cf(nonces_cf, key);
+    }
+    self.write(batch)?;
+
+    Ok((expected_height, nonces))
+  }
+
+  async fn attach_ledger_receipts(
+    &self,
+    handle: &Handle,
+    idx: usize,
+    receipts: &Receipts,
+  ) -> Result<(), LedgerStoreError> {
+    let _guard = self.lock_ledger(handle)?;
+
+    let key = ledger_key(handle, checked_conversion!(idx, u64));
+    let mut entry = match self.read_entry(BLOCKS_CF, &key)? {
+      Some(e) => e,
+      None => {
+        return match self.read_tail_height(handle)? {
+          Some(_) => Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex)),
+          None => Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)),
+        };
+      },
+    };
+
+    let mut entry_receipts = match Receipts::from_bytes(&entry.receipts) {
+      Ok(r) => r,
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::DeserializationError,
+        ));
+      },
+    };
+    entry_receipts.merge_receipts(receipts);
+    entry.receipts = entry_receipts.to_bytes();
+
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(BLOCKS_CF)?, key, serialize_entry(&entry)?);
+    self.write(batch)
+  }
+
+  async fn attach_ledger_nonce(
+    &self,
+    handle: &Handle,
+    nonce: &Nonce,
+  ) -> Result<usize, LedgerStoreError> {
+    let _guard = self.lock_ledger(handle)?;
+
+    let tail_height = match self.read_tail_height(handle)? {
+      Some(h) => h,
+      None => {
+        return Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist));
+      },
+    };
+
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(NONCES_CF)?, nonce_key(handle, nonce), b"");
+    self.write(batch)?;
+
+    // the nonce is attached to the next entry appended to the ledger
+    match tail_height.checked_add(1) {
+      Some(h) => Ok(checked_conversion!(h, usize)),
+      None => Err(LedgerStoreError::LedgerError(
+        StorageError::LedgerHeightOverflow,
+      )),
+    }
+  }
+
+  async fn read_ledger_tail(
+    &self,
+    handle: &Handle,
+  ) -> Result<(LedgerEntry, usize), LedgerStoreError> {
+    let tail_height = match self.read_tail_height(handle)? {
+      Some(h) => h,
+      None => {
+        return Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist));
+      },
+    };
+
+    // entries are never removed, so the tail's entry exists even if an append raced us
+    match self.read_ledger_entry(&ledger_key(handle, tail_height))? {
+      Some(entry) => Ok((entry, checked_conversion!(tail_height, usize))),
+      None => Err(LedgerStoreError::LedgerError(StorageError::UnhandledError)),
+    }
+  }
+
+  async fn read_ledger_info(&self, handle: &Handle) -> Result<LedgerInfo, LedgerStoreError> {
+    let res = self
+      .db
+      .get_cf(self.cf(INFO_CF)?, handle.to_bytes())
+      .map_err(rocksdb_error)?;
+    match res {
+      Some(bytes) => {
+        let entry: DBInfoEntry = bincode::deserialize(&bytes)
+          .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?;
+        Ok(LedgerInfo {
+          created_at: entry.created_at,
+          metadata: entry.metadata,
+          handle_bytes: entry.handle_bytes,
+          app_bytes: entry.app_bytes,
+        })
+      },
+      // ledgers created before info was recorded have a tail but no info
+      None => match self.read_tail_height(handle)? {
+        Some(_) => Ok(LedgerInfo::default()),
+        None => Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)),
+      },
+    }
+  }
+
+  async fn read_ledger_by_index(
+    &self,
+    handle: &Handle,
+    idx: usize,
+  ) -> Result<LedgerEntry, LedgerStoreError> {
+    let key = ledger_key(handle, checked_conversion!(idx, u64));
+    match self.read_ledger_entry(&key)? {
+      Some(entry) => Ok(entry),
+      None => match self.read_tail_height(handle)? {
+        Some(_) => Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex)),
+        None => Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist)),
+      },
+    }
+  }
+
+  async fn append_view_ledger(
+    &self,
+    block: &Block,
+    expected_height: usize,
+  ) -> Result<usize, LedgerStoreError> {
+    let _guard = self.lock_view()?;
+
+    let height = checked_conversion!(expected_height, u64);
+    if self.read_view_tail_height()?.checked_add(1) != Some(height) {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData,
+      ));
+    }
+
+    let entry = serialize_entry(&DBEntry {
+      block: block.to_bytes(),
+      receipts: Receipts::new().to_bytes(),
+      nonces: Nonces::new().to_bytes(),
+    })?;
+
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(VIEW_CF)?, height_key(height), entry);
+    self.write(batch)?;
+
+    Ok(expected_height)
+  }
+
+  async fn attach_view_ledger_receipts(
+    &self,
+    idx: usize,
+    receipts: &Receipts,
+  ) -> Result<(), LedgerStoreError> {
+    let _guard = self.lock_view()?;
+
+    let key = height_key(checked_conversion!(idx, u64));
+    let mut entry = match self.read_entry(VIEW_CF, &key)? {
+      Some(e) => e,
+      None => {
+        return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+      },
+    };
+
+    let mut entry_receipts = match Receipts::from_bytes(&entry.receipts) {
+      Ok(r) => r,
+      Err(_) => {
+        return Err(LedgerStoreError::LedgerError(
+          StorageError::DeserializationError,
+        ));
+      },
+    };
+    entry_receipts.merge_receipts(receipts);
+    entry.receipts = entry_receipts.to_bytes();
+
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(VIEW_CF)?, key, serialize_entry(&entry)?);
+    self.write(batch)
+  }
+
+  async fn read_view_ledger_tail(&self) -> Result<(LedgerEntry, usize), LedgerStoreError> {
+    let tail_height = self.read_view_tail_height()?;
+    match self.read_entry(VIEW_CF, &height_key(tail_height))? {
+      Some(entry) => Ok((
+        to_ledger_entry(&entry)?,
+        checked_conversion!(tail_height, usize),
+      )),
+      None => Err(LedgerStoreError::LedgerError(StorageError::UnhandledError)),
+    }
+  }
+
+  async fn read_view_ledger_by_index(&self, idx: usize) -> Result<LedgerEntry, LedgerStoreError> {
+    let key = height_key(checked_conversion!(idx, u64));
+    match self.read_entry(VIEW_CF, &key)? {
+      Some(entry) => to_ledger_entry(&entry),
+      None => Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex)),
+    }
+  }
+
+  async fn list_handles(
+    &self,
+    start_after: Option<&Handle>,
+    limit: usize,
+  ) -> Result<Vec<Handle>, LedgerStoreError> {
+    // tails are keyed by handle, so they iterate in the order of handles
+    let start = start_after.map(|h| h.to_bytes()).unwrap_or_default();
+    let mut handles = Vec::new();
+    for item in self.db.iterator_cf(
+      self.cf(TAILS_CF)?,
+      IteratorMode::From(&start, Direction::Forward),
+    ) {
+      if handles.len() == limit {
+        break;
+      }
+      let (key, _) = item.map_err(rocksdb_error)?;
+      if *key == start[..] {
+        continue;
+      }
+      match NimbleDigest::from_bytes(&key) {
+        Ok(h) => handles.push(h),
+        Err(_) => {
+          return Err(LedgerStoreError::LedgerError(
+            StorageError::DeserializationError,
+          ));
+        },
+      }
+    }
+    Ok(handles)
+  }
+
+  async fn estimate_num_ledgers(&self) -> Result<Option<usize>, LedgerStoreError> {
+    // RocksDB tracks an estimate of the keys of each column family, and there is one tail per
+    // ledger
+    let estimate = self
+      .db
+      .property_int_value_cf(self.cf(TAILS_CF)?, "rocksdb.estimate-num-keys")
+      .map_err(rocksdb_error)?;
+    match estimate {
+      Some(n) => Ok(Some(checked_conversion!(n, usize))),
+      None => Ok(None),
+    }
+  }
+
+  async fn estimate_size_bytes(&self) -> Result<Option<u64>, LedgerStoreError> {
+    // RocksDB tracks an estimate of the live data of each column family
+    let mut total = 0;
+    for name in COLUMN_FAMILIES.iter() {
+      let estimate = self
+        .db
+        .property_int_value_cf(self.cf(name)?, "rocksdb.estimate-live-data-size")
+        .map_err(rocksdb_error)?;
+      match estimate {
+        Some(n) => total += n,
+        None => return Ok(None),
+      }
+    }
+    Ok(Some(total))
+  }
+
+  async fn purge_ledger_blocks(
+    &self,
+    handle: &Handle,
+    before_height: usize,
+  ) -> Result<(), LedgerStoreError> {
+    let _guard = self.lock_ledger(handle)?;
+
+    let tail_height = match self.read_tail_height(handle)? {
+      Some(h) => h,
+      None => {
+        return Err(LedgerStoreError::LedgerError(StorageError::KeyDoesNotExist));
+      },
+    };
+    let before_height = checked_conversion!(before_height, u64);
+    if before_height > tail_height {
+      return Err(LedgerStoreError::LedgerError(StorageError::InvalidIndex));
+    }
+
+    // the blocks are emptied in the same batch that records their hashes
+    let purged_cf = self.cf(PURGED_CF)?;
+    let mut batch = WriteBatch::default();
+    for height in 0..before_height {
+      let key = ledger_key(handle, height);
+      if self
+        .db
+        .get_cf(purged_cf, &key)
+        .map_err(rocksdb_error)?
+        .is_some()
+      {
+        continue;
+      }
+      let mut entry = match self.read_entry(BLOCKS_CF, &key)? {
+        Some(e) => e,
+        None => return Err(LedgerStoreError::LedgerError(StorageError::UnhandledError)),
+      };
+      let block_hash = match Block::from_bytes(&entry.block) {
+        Ok(b) => b.hash(),
+        Err(_) => {
+          return Err(LedgerStoreError::LedgerError(
+            StorageError::DeserializationError,
+          ));
+        },
+      };
+      entry.block = Block::new(&[]).to_bytes();
+      batch.put_cf(self.cf(BLOCKS_CF)?, &key, serialize_entry(&entry)?);
+      batch.put_cf(purged_cf, &key, block_hash.to_bytes());
+    }
+    self.write(batch)
+  }
+
+  async fn read_tenant(&self, tenant: &str) -> Result<TenantRecord, LedgerStoreError> {
+    let res = self
+      .db
+      .get_cf(self.cf(TENANTS_CF)?, tenant.as_bytes())
+      .map_err(rocksdb_error)?;
+    match res {
+      Some(bytes) => {
+        let entry: DBTenantEntry = bincode::deserialize(&bytes)
+          .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?;
+        Ok(TenantRecord {
+          max_ledgers: entry.max_ledgers,
+          max_appends_per_sec: entry.max_appends_per_sec,
+          max_stored_bytes: entry.max_stored_bytes,
+          num_ledgers: entry.num_ledgers,
+          stored_bytes: entry.stored_bytes,
+        })
+      },
+      None => Ok(TenantRecord::default()),
+    }
+  }
+
+  async fn write_tenant(
+    &self,
+    tenant: &str,
+    record: &TenantRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let entry = bincode::serialize(&DBTenantEntry {
+      max_ledgers: record.max_ledgers,
+      max_appends_per_sec: record.max_appends_per_sec,
+      max_stored_bytes: record.max_stored_bytes,
+      num_ledgers: record.num_ledgers,
+      stored_bytes: record.stored_bytes,
+    })
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))?;
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(TENANTS_CF)?, tenant.as_bytes(), entry);
+    self.write(batch)
+  }
+
+  async fn read_lease(&self) -> Result<LeaseRecord, LedgerStoreError> {
+    self.read_lease_entry()
+  }
+
+  async fn swap_lease(
+    &self,
+    expected: &LeaseRecord,
+    new: &LeaseRecord,
+  ) -> Result<(), LedgerStoreError> {
+    let _guard = self
+      .lease_lock
+      .lock()
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::LedgerWriteLockFailed))?;
+    if self.read_lease_entry()? != *expected {
+      return Err(LedgerStoreError::LedgerError(
+        StorageError::IncorrectConditionalData,
+      ));
+    }
+
+    let entry = bincode::serialize(&DBLeaseEntry {
+      holder: new.holder.clone(),
+      expires_at: new.expires_at,
+      epoch: new.epoch,
+    })
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))?;
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(LEASE_CF)?, LEASE_KEY, entry);
+    self.write(batch)
+  }
+
+  async fn write_intent(&self, intent: &IntentRecord) -> Result<(), LedgerStoreError> {
+    let entry = bincode::serialize(&DBIntentEntry {
+      block_hash: intent.block_hash.to_bytes(),
+      request_id: intent.request_id.clone(),
+    })
+    .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))?;
+    let key = ledger_key(&intent.handle, checked_conversion!(intent.height, u64));
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(INTENTS_CF)?, key, entry);
+    self.write(batch)
+  }
+
+  async fn commit_intent(&self, handle: &Handle, height: usize) -> Result<(), LedgerStoreError> {
+    let key = ledger_key(handle, checked_conversion!(height, u64));
+    let mut batch = WriteBatch::default();
+    batch.delete_cf(self.cf(INTENTS_CF)?, key);
+    self.write(batch)
+  }
+
+  async fn list_intents(&self) -> Result<Vec<IntentRecord>, LedgerStoreError> {
+    let deserialization_error =
+      || LedgerStoreError::LedgerError(StorageError::DeserializationError);
+    let mut intents = Vec::new();
+    for item in self
+      .db
+      .iterator_cf(self.cf(INTENTS_CF)?, IteratorMode::Start)
+    {
+      let (key, value) = item.map_err(rocksdb_error)?;
+      if key.len() < 8 {
+        return Err(deserialization_error());
+      }
+      let (handle, height) = key.split_at(key.len() - 8);
+      let handle = NimbleDigest::from_bytes(handle).map_err(|_| deserialization_error())?;
+      let height = u64::from_be_bytes(height.try_into().map_err(|_| deserialization_error())?);
+      let entry: DBIntentEntry =
+        bincode::deserialize(&value).map_err(|_| deserialization_error())?;
+      intents.push(IntentRecord {
+        handle,
+        height: checked_conversion!(height, usize),
+        block_hash: NimbleDigest::from_bytes(&entry.block_hash)
+          .map_err(|_| deserialization_error())?,
+        request_id: entry.request_id,
+      });
+    }
+    Ok(intents)
+  }
+
+  async fn read_append_tokens(
+    &self,
+    handle: &Handle,
+  ) -> Result<Vec<AppendToken>, LedgerStoreError> {
+    let res = self
+      .db
+      .get_cf(self.cf(TOKENS_CF)?, handle.to_bytes())
+      .map_err(rocksdb_error)?;
+    let entries: Vec<DBAppendTokenEntry> = match res {
+      Some(bytes) => bincode::deserialize(&bytes)
+        .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?,
+      None => return Ok(vec![]),
+    };
+    let mut tokens = Vec::with_capacity(entries.len());
+    for entry in entries {
+      tokens.push(AppendToken {
+        request_id: entry.request_id,
+        height: checked_conversion!(entry.height, usize),
+        block_hash: NimbleDigest::from_bytes(&entry.block_hash)
+          .map_err(|_| LedgerStoreError::LedgerError(StorageError::DeserializationError))?,
+      });
+    }
+    Ok(tokens)
+  }
+
+  async fn write_append_tokens(
+    &self,
+    handle: &Handle,
+    tokens: &[AppendToken],
+  ) -> Result<(), LedgerStoreError> {
+    let mut entries = Vec::with_capacity(tokens.len());
+    for token in tokens {
+      entries.push(DBAppendTokenEntry {
+        request_id: token.request_id.clone(),
+        height: checked_conversion!(token.height, u64),
+        block_hash: token.block_hash.to_bytes(),
+      });
+    }
+    let entry = bincode::serialize(&entries)
+      .map_err(|_| LedgerStoreError::LedgerError(StorageError::SerializationError))?;
+    let mut batch = WriteBatch::default();
+    batch.put_cf(self.cf(TOKENS_CF)?, handle.to_bytes(), entry);
+    self.write(batch)
+  }
+
+  async fn reset_store(&self) -> Result<(), LedgerStoreError> {
+    let mut batch = WriteBatch::default();
+    for name in COLUMN_FAMILIES.iter() {
+      let cf = self.cf(name)?;
+      for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+        let (key, _) = item.map_err(rocksdb_error)?;
+        batch.delete_cf(cf, key);
+      }
+    }
+    self.write(batch)
+  }
+}
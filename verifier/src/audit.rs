@@ -0,0 +1,481 @@
+//! The offline audit of the export bundle of a ledger (see `ledger::bundle`). The auditor trusts
+//! nothing in the bundle but the group identity: it recomputes the metablock of every entry from
+//! its block and nonces, follows the view ledger from the first view one view change at a time,
+//! and checks that the endorsers of one of those views signed the tail. The checksums of the
+//! records only locate corruption; the chain and the receipts are what vouch for the entries.
+use crate::{VerifierError, VerifierState};
+use ledger::{
+  bundle::{BundleError, BundleReader, BundleRecord},
+  compute_aggregated_block_hash, compute_view_block_hash, parse_genesis_block, CustomSerde,
+  MetaBlock, NimbleDigest,
+};
+use std::fmt;
+
+/// what an audit established about the ledger of a bundle
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditReport {
+  pub handle: Vec<u8>,
+  /// the height of the attested tail
+  pub height: usize,
+  /// the hash of the metablock of the tail
+  pub tail: NimbleDigest,
+  pub group_identity: NimbleDigest,
+  /// the number of entries of the view ledger that the audit applied
+  pub num_views: usize,
+  /// the metadata of the ledger, from its genesis block
+  pub metadata: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuditFailure {
+  /// the bundle is not well formed, e.g., a record fails its checksum or is out of order
+  Malformed(&'static str),
+  /// the metablock of an entry is not the one that its block and the entries before it lead to
+  BrokenChain,
+  /// the first view of the bundle is of another group than the one the auditor trusts
+  GroupMismatch {
+    expected: NimbleDigest,
+    found: NimbleDigest,
+  },
+  /// the receipts of a view change or of the tail do not verify
+  Verification(VerifierError),
+}
+
+/// returned if a bundle fails its audit; `offset` is that of the record at fault
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditError {
+  pub offset: usize,
+  /// the height of the ledger entry at fault, if the fault is in an entry or the tail
+  pub height: Option<u64>,
+  /// the index of the view ledger entry at fault, if the fault is in one
+  pub view_index: Option<u64>,
+  pub failure: AuditFailure,
+}
+
+impl fmt::Display for AuditFailure {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AuditFailure::Malformed(reason) => write!(f, "{}", reason),
+      AuditFailure::BrokenChain => write!(f, "the metablock does not follow from the entries"),
+      AuditFailure::GroupMismatch { expected, found } => write!(
+        f,
+        "the bundle is of group {} rather than {}",
+        found, expected
+      ),
+      AuditFailure::Verification(error) => write!(f, "{}", error),
+    }
+  }
+}
+
+impl fmt::Display for AuditError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "at offset {}", self.offset)?;
+    if let Some(height) = self.height {
+      write!(f, " (height {})", height)?;
+    }
+    if let Some(index) = self.view_index {
+      write!(f, " (view {})", index)?;
+    }
+    write!(f, ": {}", self.failure)
+  }
+}
+
+impl std::error::Error for AuditError {}
+
+/// the tail of the ledger of a bundle, which awaits a view whose endorsers signed it
+struct PendingTail {
+  offset: usize,
+  metablock: MetaBlock,
+  receipts: Vec<u8>,
+  verified: Result<NimbleDigest, VerifierError>,
+}
+
+/// audits the export bundle `bundle`. Its first view must be that of `group_identity` if one is
+/// given; otherwise the audit trusts the group of the bundle, which the caller checks against the
+/// group identity in the report
+pub fn audit_bundle(
+  bundle: &[u8],
+  group_identity: Option<&NimbleDigest>,
+) -> Result<AuditReport, AuditError> {
+  let fail = |offset, height, view_index, failure| {
+    Err(AuditError {
+      offset,
+      height,
+      view_index,
+      failure,
+    })
+  };
+  let malformed =
+    |offset, height, reason| fail(offset, height, None, AuditFailure::Malformed(reason));
+  let reader = match BundleReader::new(bundle) {
+    Ok(reader) => reader,
+    Err(BundleError { offset, reason }) => return malformed(offset, None, reason),
+  };
+
+  let mut ledger: Option<(Vec<u8>, u64)> = None;
+  let mut metablock: Option<MetaBlock> = None;
+  let mut metadata = Vec::new();
+  let mut tail: Option<PendingTail> = None;
+  let mut state: Option<VerifierState> = None;
+  let mut num_views = 0;
+  for res in reader {
+    // a record that fails to parse is attributed to the entry the audit expects next, if any
+    let next_height = match (&metablock, &tail) {
+      (Some(metablock), None) => Some(metablock.get_height() as u64 + 1),
+      (None, None) if ledger.is_some() => Some(0),
+      _ => None,
+    };
+    let (offset, record) = match res {
+      Ok(record) => record,
+      Err(BundleError { offset, reason }) => return malformed(offset, next_height, reason),
+    };
+
+    match record {
+      BundleRecord::Ledger { handle, height } if ledger.is_none() => {
+        ledger = Some((handle, height));
+      },
+      BundleRecord::Entry {
+        height,
+        block,
+        nonces,
+        metablock: stored,
+      } if next_height.is_some() => {
+        if Some(height) != next_height {
+          return malformed(offset, Some(height), "the entries are not consecutive");
+        }
+        let block_hash = compute_aggregated_block_hash(
+          &NimbleDigest::digest(&block).to_bytes(),
+          &NimbleDigest::digest(&nonces).to_bytes(),
+        );
+        let expected = match &metablock {
+          Some(metablock) => metablock.next(&block_hash),
+          None => Some(MetaBlock::genesis(&block_hash)),
+        };
+        let stored = match MetaBlock::from_bytes(&stored) {
+          Ok(stored) => stored,
+          Err(_e) => return malformed(offset, Some(height), "the metablock is malformed"),
+        };
+        if expected.as_ref() != Some(&stored) {
+          return fail(offset, Some(height), None, AuditFailure::BrokenChain);
+        }
+        if height == 0 {
+          // a ledger created before genesis blocks carried metadata has none
+          if let Ok((genesis_metadata, _block)) = parse_genesis_block(&block) {
+            metadata = genesis_metadata.to_vec();
+          }
+        }
+        metablock = Some(stored);
+      },
+      BundleRecord::Tail { height, receipts } if next_height.is_some() => {
+        let metablock = match &metablock {
+          Some(metablock) => metablock,
+          None => return malformed(offset, Some(height), "the bundle holds no entries"),
+        };
+        let ledger_height = ledger.as_ref().map(|(_handle, height)| *height);
+        if Some(height) != ledger_height || height != metablock.get_height() as u64 {
+          return malformed(offset, Some(height), "the tail is not the last entry");
+        }
+        tail = Some(PendingTail {
+          offset,
+          metablock: metablock.clone(),
+          receipts,
+          verified: Err(VerifierError::MalformedReceipts),
+        });
+      },
+      BundleRecord::View {
+        index,
+        block,
+        receipts,
+      } if tail.is_some() => {
+        let view_failure = |failure| fail(offset, None, Some(index), failure);
+        if index != num_views as u64 + 1 {
+          return view_failure(AuditFailure::Malformed("the views are not consecutive"));
+        }
+        let res = match &mut state {
+          Some(state) => state.apply_view_change(&block, &receipts),
+          None => {
+            let found = match compute_view_block_hash(&block) {
+              Ok(found) => found,
+              Err(e) => {
+                return view_failure(AuditFailure::Verification(VerifierError::from_view_block(
+                  e,
+                )))
+              },
+            };
+            match group_identity {
+              Some(expected) if *expected != found => {
+                return view_failure(AuditFailure::GroupMismatch {
+                  expected: *expected,
+                  found,
+                });
+              },
+              _ => {},
+            }
+            VerifierState::from_first_view(&found, &block, &receipts).map(|first| {
+              state = Some(first);
+            })
+          },
+        };
+        if let Err(error) = res {
+          return view_failure(AuditFailure::Verification(error));
+        }
+        num_views += 1;
+
+        // the tail stays endorsed by the view that signed it once later views replace it
+        let (verifier, pending) = (state.as_ref().unwrap(), tail.as_mut().unwrap());
+        if pending.verified.is_err() {
+          let (handle, _height) = ledger.as_ref().unwrap();
+          pending.verified = verifier.verify_append(
+            handle,
+            pending.metablock.get_block_hash(),
+            pending.metablock.get_height(),
+            Some(pending.metablock.get_prev()),
+            &pending.receipts,
+          );
+        }
+      },
+      BundleRecord::End { .. } => {
+        let (state, tail) = match (state, tail) {
+          (Some(state), Some(tail)) => (state, tail),
+          _ => return malformed(offset, None, "the bundle holds no views"),
+        };
+        let height = tail.metablock.get_height();
+        let tail_hash = match tail.verified {
+          Ok(tail_hash) => tail_hash,
+          Err(error) => {
+            return fail(
+              tail.offset,
+              Some(height as u64),
+              None,
+              AuditFailure::Verification(error),
+            )
+          },
+        };
+        return Ok(AuditReport {
+          handle: ledger.unwrap().0,
+          height,
+          tail: tail_hash,
+          group_identity: *state.get_group_identity(),
+          num_views,
+          metadata,
+        });
+      },
+      _ => return malformed(offset, next_height, "the records are out of order"),
+    }
+  }
+  // the reader fails a bundle that ends before its end record, so this is not reached
+  malformed(bundle.len(), None, "the bundle ends before its end record")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::{
+    bundle::bundle_header,
+    compute_genesis_block, compute_ledger_tail_message,
+    signature::{PrivateKey, PrivateKeyTrait, PublicKeyTrait},
+    EndorserHostnames, IdSig, NimbleHashTrait, Receipt, Receipts,
+  };
+
+  const HANDLE: &[u8] = b"ledger";
+
+  /// the records of a bundle of a ledger of three entries, whose tail the endorsers of the first
+  /// view of the group signed, with the group identity
+  fn records(keys: &[PrivateKey], tail_signers: usize) -> (Vec<BundleRecord>, NimbleDigest) {
+    let hostnames = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let pk = key.get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    let view_block = bincode::serialize(&hostnames).unwrap();
+    let group_identity = compute_view_block_hash(&view_block).unwrap();
+    let view = MetaBlock::default().next(&group_identity).unwrap();
+    let state_hash = NimbleDigest::digest(b"state");
+    let mut view_receipts = Receipts::new();
+    for key in keys {
+      let message = group_identity.digest_with(&state_hash.digest_with(&view.hash()));
+      let id_sig = IdSig::new(
+        key.get_public_key().unwrap(),
+        key.sign(&message.to_bytes()).unwrap(),
+      );
+      view_receipts.add(&Receipt::new(state_hash, view.clone(), id_sig));
+    }
+
+    let mut records = vec![BundleRecord::Ledger {
+      handle: HANDLE.to_vec(),
+      height: 2,
+    }];
+    let blocks = [
+      compute_genesis_block(b"genesis", b"metadata").to_bytes(),
+      b"first".to_vec(),
+      b"second".to_vec(),
+    ];
+    let mut metablock: Option<MetaBlock> = None;
+    for (height, block) in blocks.iter().enumerate() {
+      let nonces = vec![height as u8; 16 * height];
+      let block_hash = compute_aggregated_block_hash(
+        &NimbleDigest::digest(block).to_bytes(),
+        &NimbleDigest::digest(&nonces).to_bytes(),
+      );
+      let next = match &metablock {
+        Some(metablock) => metablock.next(&block_hash).unwrap(),
+        None => MetaBlock::genesis(&block_hash),
+      };
+      records.push(BundleRecord::Entry {
+        height: height as u64,
+        block: block.clone(),
+        nonces,
+        metablock: next.to_bytes(),
+      });
+      metablock = Some(next);
+    }
+
+    let tail = metablock.unwrap();
+    let message = compute_ledger_tail_message(
+      &group_identity,
+      &view.hash(),
+      &NimbleDigest::digest(HANDLE),
+      &tail.hash(),
+    );
+    let mut tail_receipts = Receipts::new();
+    for key in &keys[..tail_signers] {
+      let id_sig = IdSig::new(
+        key.get_public_key().unwrap(),
+        key.sign(&message.to_bytes()).unwrap(),
+      );
+      tail_receipts.add(&Receipt::new(view.hash(), tail.clone(), id_sig));
+    }
+    records.push(BundleRecord::Tail {
+      height: 2,
+      receipts: tail_receipts.to_bytes(),
+    });
+    records.push(BundleRecord::View {
+      index: 1,
+      block: view_block,
+      receipts: view_receipts.to_bytes(),
+    });
+    records.push(BundleRecord::End {
+      num_records: records.len() as u64,
+    });
+    (records, group_identity)
+  }
+
+  /// the bundle of `records`, with the offset of each record
+  fn encode(records: &[BundleRecord]) -> (Vec<u8>, Vec<usize>) {
+    let mut bytes = bundle_header();
+    let mut offsets = Vec::new();
+    for record in records {
+      offsets.push(bytes.len());
+      bytes.extend(record.encode());
+    }
+    (bytes, offsets)
+  }
+
+  #[test]
+  fn test_audit_bundle() {
+    let keys = (0..3).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let (records, group_identity) = records(&keys, 2);
+    let (bundle, offsets) = encode(&records);
+    let report = audit_bundle(&bundle, None).unwrap();
+    assert_eq!(report.handle, HANDLE);
+    assert_eq!(report.height, 2);
+    assert_eq!(report.group_identity, group_identity);
+    assert_eq!(report.num_views, 1);
+    assert_eq!(report.metadata, b"metadata");
+    assert_eq!(audit_bundle(&bundle, Some(&group_identity)), Ok(report));
+
+    // the first view must be that of the group the auditor trusts
+    let other = NimbleDigest::digest(b"other group");
+    assert_eq!(
+      audit_bundle(&bundle, Some(&other)),
+      Err(AuditError {
+        offset: offsets[5],
+        height: None,
+        view_index: Some(1),
+        failure: AuditFailure::GroupMismatch {
+          expected: other,
+          found: group_identity
+        },
+      })
+    );
+
+    // a flipped byte is located at the record and the height of the entry that holds it
+    let mut corrupt = bundle;
+    corrupt[offsets[2] + 20] ^= 1;
+    let err = audit_bundle(&corrupt, None).unwrap_err();
+    assert_eq!(
+      err,
+      AuditError {
+        offset: offsets[2],
+        height: Some(1),
+        view_index: None,
+        failure: AuditFailure::Malformed("checksum mismatch"),
+      }
+    );
+    assert_eq!(
+      err.to_string(),
+      format!("at offset {} (height 1): checksum mismatch", offsets[2])
+    );
+
+    // an entry that is replaced together with its checksum breaks the chain
+    let mut replaced = records;
+    if let BundleRecord::Entry { block, .. } = &mut replaced[2] {
+      block[0] ^= 1;
+    }
+    let (bundle, offsets) = encode(&replaced);
+    assert_eq!(
+      audit_bundle(&bundle, None),
+      Err(AuditError {
+        offset: offsets[2],
+        height: Some(1),
+        view_index: None,
+        failure: AuditFailure::BrokenChain,
+      })
+    );
+
+    // the tail needs the signatures of a majority of the endorsers
+    let (records, _group_identity) = self::records(&keys, 1);
+    let (bundle, offsets) = encode(&records);
+    assert_eq!(
+      audit_bundle(&bundle, None),
+      Err(AuditError {
+        offset: offsets[4],
+        height: Some(2),
+        view_index: None,
+        failure: AuditFailure::Verification(VerifierError::InsufficientQuorum {
+          signers: 1,
+          threshold: 2
+        }),
+      })
+    );
+
+    // a bundle without views cannot vouch for its tail
+    let mut no_views = records[..5].to_vec();
+    no_views.push(BundleRecord::End { num_records: 5 });
+    let (bundle, offsets) = encode(&no_views);
+    assert_eq!(
+      audit_bundle(&bundle, None).unwrap_err(),
+      AuditError {
+        offset: offsets[5],
+        height: None,
+        view_index: None,
+        failure: AuditFailure::Malformed("the bundle holds no views"),
+      }
+    );
+  }
+
+  fn check_audit(data: &[u8]) {
+    let _ = audit_bundle(data, None);
+  }
+
+  #[test]
+  fn test_audit_bundle_mutations() {
+    let keys = (0..3).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let (records, _group_identity) = records(&keys, 2);
+    let (bundle, _offsets) = encode(&records);
+    ledger::fuzz::run_bounded("audit_bundle", check_audit, &[bundle]);
+  }
+}
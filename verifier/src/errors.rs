@@ -0,0 +1,129 @@
+use ledger::{errors::VerificationError, NimbleDigest};
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VerifierError {
+  /// returned if the receipts cannot be parsed
+  MalformedReceipts,
+  /// returned if the receipts are from a past view rather than the one the verifier trusts
+  WrongView {
+    expected: NimbleDigest,
+    found: NimbleDigest,
+  },
+  /// returned if the receipts endorse another entry than the one being verified
+  WrongEntry,
+  /// returned if fewer endorsers of the view signed the entry than the threshold
+  InsufficientQuorum { signers: usize, threshold: usize },
+  /// returned if the signature of an endorser of the view does not verify; carries its public key
+  BadSignature(Vec<u8>),
+  /// returned if the endorsers signed the tail without the nonce of the read, e.g., on an append
+  NonceMismatch,
+  /// returned if the receipts are from a view that the verifier does not know, which may be a
+  /// later view; the client applies the entries of the view ledger from `next_index` on, which it
+  /// reads with `ReadViewByIndex`, and verifies the receipts again
+  StaleVerifier {
+    view: NimbleDigest,
+    next_index: usize,
+  },
+  /// returned if a block of the view ledger does not list the endorsers of a view
+  MalformedViewBlock,
+  /// returned if a block of the view ledger hands a key over to another without the signature of
+  /// the endorser that held it
+  InvalidKeyRotation,
+  /// returned if a threshold is at most half of the endorsers, or more than all of them
+  InvalidThreshold {
+    threshold: usize,
+    num_endorsers: usize,
+  },
+  /// returned if the latest heartbeat of a ledger is older than the client accepts, so the
+  /// coordinator stopped appending to the ledger, or serves it from a state that stopped
+  StaleHeartbeat { age_ms: u64, max_age_ms: u64 },
+  /// returned if the signature of an aggregated receipt does not verify under the keys of the
+  /// endorsers that it names
+  BadAggregateSignature,
+  /// returned if an aggregated receipt is from a view whose endorsers sign with a scheme whose
+  /// signatures do not aggregate, or with one that the verifier was built without
+  UnsupportedSignatureScheme,
+  /// returned if a block of the view ledger records a key of an endorser without a proof that the
+  /// endorser knows its private key
+  InvalidProofOfPossession,
+  /// returned if a heartbeat is stamped further ahead of the clock of the client than the clocks
+  /// may be apart
+  FutureHeartbeat { ahead_ms: u64 },
+  /// returned if a block of the view ledger records another hash algorithm than the one the
+  /// verifier was built with
+  HashAlgorithmMismatch,
+}
+
+impl VerifierError {
+  /// the error for a block of the view ledger that the ledger rejects with `e`
+  pub fn from_view_block(e: VerificationError) -> Self {
+    match e {
+      VerificationError::HashAlgorithmMismatch => VerifierError::HashAlgorithmMismatch,
+      _ => VerifierError::MalformedViewBlock,
+    }
+  }
+}
+
+impl fmt::Display for VerifierError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      VerifierError::MalformedReceipts => write!(f, "receipts are malformed"),
+      VerifierError::WrongView { expected, found } => {
+        write!(
+          f,
+          "receipts are from view {} instead of {}",
+          found, expected
+        )
+      },
+      VerifierError::WrongEntry => write!(f, "receipts endorse another entry"),
+      VerifierError::InsufficientQuorum { signers, threshold } => write!(
+        f,
+        "{} endorsers signed the entry but {} are needed",
+        signers, threshold
+      ),
+      VerifierError::BadSignature(pk) => {
+        write!(f, "signature by endorser {} is invalid", hex::encode(pk))
+      },
+      VerifierError::NonceMismatch => write!(f, "receipts do not cover the nonce of the read"),
+      VerifierError::StaleVerifier { view, next_index } => write!(
+        f,
+        "receipts are from view {}, which the verifier does not know; apply the view ledger \
+         entries from index {} on",
+        view, next_index
+      ),
+      VerifierError::MalformedViewBlock => write!(f, "view block is malformed"),
+      VerifierError::InvalidKeyRotation => write!(f, "view block rotates a key invalidly"),
+      VerifierError::InvalidThreshold {
+        threshold,
+        num_endorsers,
+      } => write!(
+        f,
+        "threshold {} is not more than half of and at most the {} endorsers",
+        threshold, num_endorsers
+      ),
+      VerifierError::BadAggregateSignature => write!(f, "aggregated signature is invalid"),
+      VerifierError::UnsupportedSignatureScheme => {
+        write!(f, "the signatures of the view do not aggregate")
+      },
+      VerifierError::InvalidProofOfPossession => {
+        write!(f, "view block records a key without a proof of possession")
+      },
+      VerifierError::StaleHeartbeat { age_ms, max_age_ms } => write!(
+        f,
+        "the latest heartbeat is {} ms old, more than the {} ms accepted",
+        age_ms, max_age_ms
+      ),
+      VerifierError::FutureHeartbeat { ahead_ms } => write!(
+        f,
+        "the latest heartbeat is {} ms ahead of the clock",
+        ahead_ms
+      ),
+      VerifierError::HashAlgorithmMismatch => {
+        write!(f, "view was created with another hash algorithm")
+      },
+    }
+  }
+}
+
+impl std::error::Error for VerifierError {}
@@ -0,0 +1,1749 @@
+//! Verification of the receipts that clients get from the coordinator, which they do not trust:
+//! an entry is accepted only if a threshold of the endorsers that the client trusts signed the
+//! exact payload that the client reconstructs, with the ledger's helpers, from the entry it
+//! expects.
+//!
+//! The endorsers that the client trusts are those of the latest entry of the view ledger that it
+//! applied. It applies the next entry only once the endorsers that it trusts endorsed it, so it
+//! follows the view changes of the coordinator one entry at a time, and can persist where it is
+//! with `to_bytes`.
+pub mod audit;
+mod errors;
+
+pub use errors::VerifierError;
+use ledger::{
+  compute_aggregated_block_hash, compute_genesis_block, compute_ledger_tail_message,
+  compute_view_block_hash, decode_view_config,
+  errors::VerificationError,
+  retrieve_public_keys_from_config, retrieve_quorum_from_config, retrieve_scheme_keys_from_config,
+  signature::{PublicKey, PublicKeyTrait, SignatureSchemeId},
+  verify_key_rotations, AggregatedReceipt, CustomSerde, CustomSerdeError, MetaBlock, NimbleDigest,
+  NimbleHashTrait, Nonce, Receipts, SchemeKeys,
+};
+use std::{
+  collections::{BTreeMap, HashSet},
+  convert::{TryFrom, TryInto},
+  time::Duration,
+};
+
+/// the endorsers of a view of the view ledger, whose receipts the client trusts
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerifierState {
+  group_identity: NimbleDigest,
+  /// the metablock of the entry of the view ledger that holds the view; its hash is the view
+  view_metablock: MetaBlock,
+  pks: HashSet<Vec<u8>>,
+  threshold: usize,
+  /// the keys that the endorsers sign with if the view records a scheme other than P-256
+  scheme_keys: Option<SchemeKeys>,
+  /// the views that the client trusted before, oldest first
+  past_views: Vec<NimbleDigest>,
+  /// the height and hash of the latest tail of each ledger that the client verified, keyed by the
+  /// hash of the handle
+  ledger_tails: BTreeMap<NimbleDigest, (usize, NimbleDigest)>,
+}
+
+impl VerifierState {
+  /// trusts the endorsers `pks` of the view held by the view ledger entry with `view_metablock`;
+  /// an entry needs the signatures of a majority of them
+  pub fn new(group_identity: &NimbleDigest, view_metablock: &MetaBlock, pks: &[PublicKey]) -> Self {
+    let pks = pks
+      .iter()
+      .map(|pk| pk.to_bytes())
+      .collect::<HashSet<Vec<u8>>>();
+    let threshold = pks.len() / 2 + 1;
+    VerifierState {
+      group_identity: *group_identity,
+      view_metablock: view_metablock.clone(),
+      pks,
+      threshold,
+      scheme_keys: None,
+      past_views: Vec::new(),
+      ledger_tails: BTreeMap::new(),
+    }
+  }
+
+  /// trusts the endorsers of the first view of the group with `group_identity`, given the first
+  /// entry of its view ledger, `view_block`, and its receipts, which the quorum that the block
+  /// records of them signed
+  pub fn from_first_view(
+    group_identity: &NimbleDigest,
+    view_block: &[u8],
+    receipts: &[u8],
+  ) -> Result<Self, VerifierError> {
+    // the group identity is the hash of the first view block
+    let block_hash = compute_view_block_hash(view_block).map_err(VerifierError::from_view_block)?;
+    if block_hash != *group_identity {
+      return Err(VerifierError::WrongEntry);
+    }
+    // no endorsers precede the first view
+    let mut state = VerifierState {
+      group_identity: *group_identity,
+      view_metablock: MetaBlock::default(),
+      pks: HashSet::new(),
+      threshold: 0,
+      scheme_keys: None,
+      past_views: Vec::new(),
+      ledger_tails: BTreeMap::new(),
+    };
+    state.apply_view_change(view_block, receipts)?;
+    state.past_views.clear();
+    Ok(state)
+  }
+
+  /// requires the signatures of `threshold` endorsers rather than of a majority; it must still be
+  /// more than half of them, or two disjoint sets of endorsers could endorse conflicting entries
+  pub fn with_threshold(mut self, threshold: usize) -> Result<Self, VerifierError> {
+    if threshold <= self.pks.len() / 2 || threshold > self.pks.len() {
+      return Err(VerifierError::InvalidThreshold {
+        threshold,
+        num_endorsers: self.pks.len(),
+      });
+    }
+    self.threshold = threshold;
+    Ok(self)
+  }
+
+  pub fn get_group_identity(&self) -> &NimbleDigest {
+    &self.group_identity
+  }
+
+  /// the view whose endorsers the client trusts
+  pub fn current_view(&self) -> NimbleDigest {
+    self.view_metablock.hash()
+  }
+
+  /// the index of the view ledger entry that holds the current view
+  pub fn current_view_index(&self) -> usize {
+    self.view_metablock.get_height()
+  }
+
+  pub fn get_threshold(&self) -> usize {
+    self.threshold
+  }
+
+  /// the scheme that the endorsers of the view sign receipts with
+  pub fn get_scheme(&self) -> SignatureSchemeId {
+    match &self.scheme_keys {
+      Some(keys) => keys.get_scheme(),
+      None => SignatureSchemeId::P256,
+    }
+  }
+
+  pub fn is_endorser(&self, pk: &PublicKey) -> bool {
+    self.pks.contains(&pk.to_bytes())
+  }
+
+  /// the height and hash of the latest tail of ledger `handle` that was recorded, if any
+  pub fn get_ledger_tail(&self, handle: &[u8]) -> Option<(usize, NimbleDigest)> {
+    self
+      .ledger_tails
+      .get(&NimbleDigest::digest(handle))
+      .copied()
+  }
+
+  /// records `tail` at `height` as the latest tail of ledger `handle` that the client verified; a
+  /// tail below the recorded one is ignored
+  pub fn set_ledger_tail(&mut self, handle: &[u8], height: usize, tail: &NimbleDigest) {
+    let recorded = self
+      .ledger_tails
+      .entry(NimbleDigest::digest(handle))
+      .or_insert((height, *tail));
+    if height > recorded.0 {
+      *recorded = (height, *tail);
+    }
+  }
+
+  /// verifies the receipts of the creation of ledger `handle` with `block` and `metadata`;
+  /// returns the hash of the tail of the new ledger, which the next append extends
+  pub fn verify_new_ledger(
+    &self,
+    handle: &[u8],
+    block: &[u8],
+    metadata: &[u8],
+    receipts: &[u8],
+  ) -> Result<NimbleDigest, VerifierError> {
+    let genesis_block = compute_genesis_block(block, metadata);
+    let block_hash = compute_aggregated_block_hash(
+      &genesis_block.hash().to_bytes(),
+      &NimbleDigest::default().to_bytes(),
+    );
+    let genesis = MetaBlock::genesis(&block_hash);
+    self.verify_receipts(handle, receipts, |metablock| *metablock == genesis, None)
+  }
+
+  /// verifies the receipts of the append of the entry with `block_hash`, the aggregated hash of
+  /// its block and nonces (see `compute_aggregated_block_hash`), at `height`; if `prev_tail` is
+  /// set, the entry must extend that tail. Returns the hash of the new tail.
+  pub fn verify_append(
+    &self,
+    handle: &[u8],
+    block_hash: &NimbleDigest,
+    height: usize,
+    prev_tail: Option<&NimbleDigest>,
+    receipts: &[u8],
+  ) -> Result<NimbleDigest, VerifierError> {
+    self.verify_receipts(
+      handle,
+      receipts,
+      |metablock| {
+        metablock.get_block_hash() == block_hash
+          && metablock.get_height() == height
+          && match prev_tail {
+            Some(prev) => metablock.get_prev() == prev,
+            None => true,
+          }
+      },
+      None,
+    )
+  }
+
+  /// verifies the receipts of a read with `nonce` of the tail of ledger `handle`, which must be
+  /// the entry with `block_hash` at `height`; returns the hash of the tail
+  pub fn verify_read_latest(
+    &self,
+    handle: &[u8],
+    nonce: &Nonce,
+    block_hash: &NimbleDigest,
+    height: usize,
+    receipts: &[u8],
+  ) -> Result<NimbleDigest, VerifierError> {
+    self.verify_receipts(
+      handle,
+      receipts,
+      |metablock| metablock.get_block_hash() == block_hash && metablock.get_height() == height,
+      Some(nonce),
+    )
+  }
+
+  /// like `verify_append`, but for an aggregated receipt (see `ledger::AggregatedReceipt`), in
+  /// which the endorsers of a view that signs with an aggregating scheme signed with one signature
+  pub fn verify_aggregated_append(
+    &self,
+    handle: &[u8],
+    block_hash: &NimbleDigest,
+    height: usize,
+    prev_tail: Option<&NimbleDigest>,
+    receipt: &[u8],
+  ) -> Result<NimbleDigest, VerifierError> {
+    self.verify_aggregated_receipt(
+      handle,
+      receipt,
+      |metablock| {
+        metablock.get_block_hash() == block_hash
+          && metablock.get_height() == height
+          && match prev_tail {
+            Some(prev) => metablock.get_prev() == prev,
+            None => true,
+          }
+      },
+      None,
+    )
+  }
+
+  /// like `verify_read_latest`, but for an aggregated receipt
+  pub fn verify_aggregated_read_latest(
+    &self,
+    handle: &[u8],
+    nonce: &Nonce,
+    block_hash: &NimbleDigest,
+    height: usize,
+    receipt: &[u8],
+  ) -> Result<NimbleDigest, VerifierError> {
+    self.verify_aggregated_receipt(
+      handle,
+      receipt,
+      |metablock| metablock.get_block_hash() == block_hash && metablock.get_height() == height,
+      Some(nonce),
+    )
+  }
+
+  /// verifies consecutive entries known by their block hashes (see `compute_aggregated_block_hash`)
+  /// through the receipts of a later tail: the metablocks are recomputed from `checkpoint`, the
+  /// metablock before the first entry (`None` if it is the genesis entry), over `block_hashes`,
+  /// which run from the first entry to the tail, and the last one must be the tail that the
+  /// receipts endorse. Returns the hash of the tail.
+  pub fn verify_chain(
+    &self,
+    handle: &[u8],
+    checkpoint: Option<&MetaBlock>,
+    block_hashes: &[NimbleDigest],
+    tail_receipts: &[u8],
+  ) -> Result<NimbleDigest, VerifierError> {
+    let mut metablock = checkpoint.cloned();
+    for block_hash in block_hashes {
+      metablock = Some(match metablock {
+        Some(metablock) => metablock
+          .next(block_hash)
+          .ok_or(VerifierError::WrongEntry)?,
+        None => MetaBlock::genesis(block_hash),
+      });
+    }
+    let tail = match (metablock, block_hashes.is_empty()) {
+      (Some(tail), false) => tail,
+      _ => return Err(VerifierError::WrongEntry),
+    };
+    self.verify_append(
+      handle,
+      tail.get_block_hash(),
+      tail.get_height(),
+      Some(tail.get_prev()),
+      tail_receipts,
+    )
+  }
+
+  /// applies the entry of the view ledger that follows the current view, given its block, which
+  /// lists the endorsers of the next view, and its receipts. The entry must be endorsed by the
+  /// threshold of the current endorsers, which finalized their view into it, and by the quorum
+  /// that the block records of the next ones, which joined it; that quorum, a majority of the next
+  /// endorsers unless the block records another, is the threshold afterwards. Applying the entry
+  /// of the current view again has no effect.
+  pub fn apply_view_change(
+    &mut self,
+    view_block: &[u8],
+    receipts: &[u8],
+  ) -> Result<(), VerifierError> {
+    let receipts =
+      Receipts::try_from_bytes(receipts).map_err(|_e| VerifierError::MalformedReceipts)?;
+    let block_hash = compute_view_block_hash(view_block).map_err(VerifierError::from_view_block)?;
+    let pks =
+      retrieve_public_keys_from_config(view_block).map_err(VerifierError::from_view_block)?;
+    if pks.is_empty() {
+      return Err(VerifierError::MalformedViewBlock);
+    }
+
+    let metablocks = receipts
+      .get()
+      .keys()
+      .map(|ex_meta_block| ex_meta_block.get_metablock())
+      .filter(|metablock| *metablock.get_block_hash() == block_hash)
+      .collect::<Vec<&MetaBlock>>();
+    if metablocks.contains(&&self.view_metablock) {
+      return Ok(());
+    }
+    let next = self
+      .view_metablock
+      .next(&block_hash)
+      .ok_or(VerifierError::WrongEntry)?;
+    if !metablocks.contains(&&next) {
+      // an entry further down the view ledger needs the entries before it
+      return match metablocks
+        .iter()
+        .find(|metablock| metablock.get_height() > next.get_height())
+      {
+        Some(metablock) => Err(VerifierError::StaleVerifier {
+          view: metablock.hash(),
+          next_index: next.get_height(),
+        }),
+        None => Err(VerifierError::WrongEntry),
+      };
+    }
+
+    let signers = self.count_view_signers(&receipts, &next, &self.pks)?;
+    if signers < self.threshold {
+      return Err(VerifierError::InsufficientQuorum {
+        signers,
+        threshold: self.threshold,
+      });
+    }
+    let threshold =
+      retrieve_quorum_from_config(view_block).map_err(VerifierError::from_view_block)?;
+    let signers = self.count_view_signers(&receipts, &next, &pks)?;
+    if signers < threshold {
+      return Err(VerifierError::InsufficientQuorum { signers, threshold });
+    }
+    // an endorser may only come back under another key if it signed the handover with its old one
+    let (_endorsers, rotations) =
+      decode_view_config(view_block).map_err(VerifierError::from_view_block)?;
+    verify_key_rotations(&self.group_identity, &rotations, Some(&self.pks), &pks)
+      .map_err(|_e| VerifierError::InvalidKeyRotation)?;
+    // the keys that the endorsers aggregate signatures with count only with proofs of possession
+    let scheme_keys =
+      retrieve_scheme_keys_from_config(view_block).map_err(VerifierError::from_view_block)?;
+    if let Some(keys) = &scheme_keys {
+      keys.verify_possession().map_err(|e| match e {
+        VerificationError::UnsupportedSignatureScheme => VerifierError::UnsupportedSignatureScheme,
+        _ => VerifierError::InvalidProofOfPossession,
+      })?;
+    }
+
+    self.past_views.push(self.current_view());
+    self.view_metablock = next;
+    self.pks = pks;
+    self.threshold = threshold;
+    self.scheme_keys = scheme_keys;
+    Ok(())
+  }
+
+  /// counts the endorsers in `pks` that signed `metablock` as an entry of the view ledger
+  fn count_view_signers(
+    &self,
+    receipts: &Receipts,
+    metablock: &MetaBlock,
+    pks: &HashSet<Vec<u8>>,
+  ) -> Result<usize, VerifierError> {
+    let metablock_hash = metablock.hash();
+    let mut signers = HashSet::new();
+    // the endorsers sign the entry together with the hash of their state, which differs between
+    // the endorsers that leave the view and those that join it
+    for (ex_meta_block, id_sigs) in receipts.get() {
+      if ex_meta_block.get_metablock() != metablock {
+        continue;
+      }
+      let message = view_entry_message(
+        &self.group_identity,
+        ex_meta_block.get_view(),
+        &metablock_hash,
+      );
+      for id_sig in id_sigs {
+        if !pks.contains(id_sig.get_id()) {
+          continue;
+        }
+        if id_sig.verify(&message).is_err() {
+          return Err(VerifierError::BadSignature(id_sig.get_id().clone()));
+        }
+        signers.insert(id_sig.get_id());
+      }
+    }
+    Ok(signers.len())
+  }
+
+  /// the payload that an endorser signs to endorse `tail_hash` as the tail of ledger `handle`
+  fn tail_message(
+    &self,
+    handle: &NimbleDigest,
+    tail_hash: &NimbleDigest,
+    nonce: Option<&Nonce>,
+  ) -> Vec<u8> {
+    ledger_tail_message(
+      &self.group_identity,
+      &self.current_view(),
+      handle,
+      tail_hash,
+      nonce,
+    )
+  }
+
+  fn verify_receipts(
+    &self,
+    handle: &[u8],
+    receipts: &[u8],
+    is_entry: impl Fn(&MetaBlock) -> bool,
+    nonce: Option<&Nonce>,
+  ) -> Result<NimbleDigest, VerifierError> {
+    let receipts =
+      Receipts::try_from_bytes(receipts).map_err(|_e| VerifierError::MalformedReceipts)?;
+    let view = self.current_view();
+    let in_view = receipts
+      .get()
+      .iter()
+      .filter(|(ex_meta_block, _id_sigs)| *ex_meta_block.get_view() == view)
+      .collect::<Vec<_>>();
+    if in_view.is_empty() {
+      return match receipts.get().keys().next() {
+        Some(ex_meta_block) => Err(self.unknown_view(ex_meta_block.get_view())),
+        None => Err(VerifierError::InsufficientQuorum {
+          signers: 0,
+          threshold: self.threshold,
+        }),
+      };
+    }
+    let (ex_meta_block, id_sigs) = in_view
+      .into_iter()
+      .find(|(ex_meta_block, _id_sigs)| is_entry(ex_meta_block.get_metablock()))
+      .ok_or(VerifierError::WrongEntry)?;
+
+    let handle = NimbleDigest::digest(handle);
+    let tail_hash = ex_meta_block.get_metablock().hash();
+    let message = self.tail_message(&handle, &tail_hash, nonce);
+    let mut signers = HashSet::new();
+    for id_sig in id_sigs {
+      // signatures by endorsers outside the view do not count
+      if !self.pks.contains(id_sig.get_id()) {
+        continue;
+      }
+      if id_sig.verify(&message).is_err() {
+        // a signature of the tail alone endorses the entry, but not that it was still the tail
+        // when the endorser saw the nonce
+        if nonce.is_some()
+          && id_sig
+            .verify(&self.tail_message(&handle, &tail_hash, None))
+            .is_ok()
+        {
+          return Err(VerifierError::NonceMismatch);
+        }
+        return Err(VerifierError::BadSignature(id_sig.get_id().clone()));
+      }
+      signers.insert(id_sig.get_id());
+    }
+
+    if signers.len() < self.threshold {
+      return Err(VerifierError::InsufficientQuorum {
+        signers: signers.len(),
+        threshold: self.threshold,
+      });
+    }
+    Ok(tail_hash)
+  }
+
+  /// the error for receipts from `view`, which is not the current view
+  fn unknown_view(&self, view: &NimbleDigest) -> VerifierError {
+    // the endorsers of a past view may no longer be trusted
+    if self.past_views.contains(view) {
+      VerifierError::WrongView {
+        expected: self.current_view(),
+        found: *view,
+      }
+    } else {
+      VerifierError::StaleVerifier {
+        view: *view,
+        next_index: self.current_view_index() + 1,
+      }
+    }
+  }
+
+  fn verify_aggregated_receipt(
+    &self,
+    handle: &[u8],
+    receipt: &[u8],
+    is_entry: impl Fn(&MetaBlock) -> bool,
+    nonce: Option<&Nonce>,
+  ) -> Result<NimbleDigest, VerifierError> {
+    let receipt =
+      AggregatedReceipt::from_bytes(receipt).map_err(|_e| VerifierError::MalformedReceipts)?;
+    if *receipt.get_view() != self.current_view() {
+      return Err(self.unknown_view(receipt.get_view()));
+    }
+    let keys = self
+      .scheme_keys
+      .as_ref()
+      .ok_or(VerifierError::UnsupportedSignatureScheme)?;
+    if !is_entry(receipt.get_metablock()) {
+      return Err(VerifierError::WrongEntry);
+    }
+    let signers = receipt
+      .signer_positions(keys.len())
+      .map_err(|_e| VerifierError::MalformedReceipts)?
+      .len();
+    if signers < self.threshold {
+      return Err(VerifierError::InsufficientQuorum {
+        signers,
+        threshold: self.threshold,
+      });
+    }
+
+    let handle = NimbleDigest::digest(handle);
+    let tail_hash = receipt.get_metablock().hash();
+    let message = self.tail_message(&handle, &tail_hash, nonce);
+    match receipt.verify(keys, &message) {
+      Ok(_signers) => Ok(tail_hash),
+      Err(VerificationError::UnsupportedSignatureScheme) => {
+        Err(VerifierError::UnsupportedSignatureScheme)
+      },
+      // the aggregate does not say whose signature is bad, nor whether only the nonce is amiss
+      Err(_e)
+        if nonce.is_some()
+          && receipt
+            .verify(keys, &self.tail_message(&handle, &tail_hash, None))
+            .is_ok() =>
+      {
+        Err(VerifierError::NonceMismatch)
+      },
+      Err(_e) => Err(VerifierError::BadAggregateSignature),
+    }
+  }
+}
+
+/// the message that an endorser signs in `view` to endorse `tail_hash` as the tail of ledger
+/// `handle`, which it signs together with the nonce of a read
+pub fn ledger_tail_message(
+  group_identity: &NimbleDigest,
+  view: &NimbleDigest,
+  handle: &NimbleDigest,
+  tail_hash: &NimbleDigest,
+  nonce: Option<&Nonce>,
+) -> Vec<u8> {
+  let tail_hash = match nonce {
+    Some(nonce) => tail_hash.digest_with_bytes(&nonce.to_bytes()),
+    None => *tail_hash,
+  };
+  compute_ledger_tail_message(group_identity, view, handle, &tail_hash).to_bytes()
+}
+
+/// the message that an endorser signs to endorse the entry of the view ledger with
+/// `metablock_hash`, with the hash of its state as `view`
+pub fn view_entry_message(
+  group_identity: &NimbleDigest,
+  view: &NimbleDigest,
+  metablock_hash: &NimbleDigest,
+) -> Vec<u8> {
+  group_identity
+    .digest_with(&view.digest_with(metablock_hash))
+    .to_bytes()
+}
+
+/// checks that a heartbeat (see `ledger::compute_heartbeat_block`) that the coordinator stamped
+/// with `timestamp_ms` by its clock is at most `max_age` old at `now_ms` by the clock of the
+/// client; the clocks may be up to `max_skew` apart either way, so a heartbeat up to `max_skew`
+/// ahead of the client is accepted, and one is stale only once it is older than `max_age` plus
+/// `max_skew`. Returns the age of the heartbeat by the clock of the client, which is zero for a
+/// heartbeat ahead of it. The receipts of the entry are verified separately, by a read of the tail
+/// with a fresh nonce, since an old entry is fresh only if it is still the tail.
+pub fn check_freshness(
+  timestamp_ms: u64,
+  now_ms: u64,
+  max_age: Duration,
+  max_skew: Duration,
+) -> Result<Duration, VerifierError> {
+  let max_skew_ms = max_skew.as_millis() as u64;
+  if timestamp_ms > now_ms.saturating_add(max_skew_ms) {
+    return Err(VerifierError::FutureHeartbeat {
+      ahead_ms: timestamp_ms - now_ms,
+    });
+  }
+  let age_ms = now_ms.saturating_sub(timestamp_ms);
+  let max_age_ms = max_age.as_millis() as u64;
+  if age_ms > max_age_ms.saturating_add(max_skew_ms) {
+    return Err(VerifierError::StaleHeartbeat { age_ms, max_age_ms });
+  }
+  Ok(Duration::from_millis(age_ms))
+}
+
+/// Version tag prefixed to the encoding of `VerifierState`
+const VERIFIER_STATE_ENCODING_VERSION: u8 = 3;
+/// The version before the keys of signature schemes were recorded, which is still read
+const VERIFIER_STATE_ENCODING_VERSION_NO_SCHEME: u8 = 2;
+/// The version before the tails of ledgers were recorded, which is still read
+const VERIFIER_STATE_ENCODING_VERSION_NO_TAILS: u8 = 1;
+
+fn read_slice<'a>(
+  bytes: &'a [u8],
+  pos: &mut usize,
+  len: usize,
+) -> Result<&'a [u8], CustomSerdeError> {
+  let end = pos
+    .checked_add(len)
+    .ok_or(CustomSerdeError::IncorrectLength)?;
+  if end > bytes.len() {
+    return Err(CustomSerdeError::IncorrectLength);
+  }
+  let slice = &bytes[*pos..end];
+  *pos = end;
+  Ok(slice)
+}
+
+fn read_u32_le(bytes: &[u8], pos: &mut usize) -> Result<u32, CustomSerdeError> {
+  let slice = read_slice(bytes, pos, std::mem::size_of::<u32>())?;
+  Ok(u32::from_le_bytes(
+    slice
+      .try_into()
+      .map_err(|_| CustomSerdeError::IncorrectLength)?,
+  ))
+}
+
+/// The layout is a version byte, the group identity, the metablock of the current view, the
+/// threshold, the number of endorsers and their public keys in sorted order, the number of past
+/// views and their hashes, and the number of ledgers and, for each in the order of its handle's
+/// hash, that hash, the height of its tail as u64 LE and the hash of the tail, and the length of
+/// the encoding of the scheme keys of the view, zero if it has none, and that encoding, with the
+/// numbers as u32 LE unless noted. Version 2 ends after the tails, and version 1 after the past
+/// views.
+impl CustomSerde for VerifierState {
+  fn to_bytes(&self) -> Vec<u8> {
+    let mut pks = self.pks.iter().collect::<Vec<&Vec<u8>>>();
+    pks.sort();
+    let mut bytes = vec![VERIFIER_STATE_ENCODING_VERSION];
+    bytes.extend(&self.group_identity.to_bytes());
+    bytes.extend(&self.view_metablock.to_bytes());
+    bytes.extend(&(self.threshold as u32).to_le_bytes());
+    bytes.extend(&(pks.len() as u32).to_le_bytes());
+    for pk in pks {
+      bytes.extend(pk);
+    }
+    bytes.extend(&(self.past_views.len() as u32).to_le_bytes());
+    for view in &self.past_views {
+      bytes.extend(&view.to_bytes());
+    }
+    bytes.extend(&(self.ledger_tails.len() as u32).to_le_bytes());
+    for (handle, (height, tail)) in &self.ledger_tails {
+      bytes.extend(&handle.to_bytes());
+      bytes.extend(&(*height as u64).to_le_bytes());
+      bytes.extend(&tail.to_bytes());
+    }
+    let scheme_keys = match &self.scheme_keys {
+      Some(keys) => keys.to_bytes(),
+      None => Vec::new(),
+    };
+    bytes.extend(&(scheme_keys.len() as u32).to_le_bytes());
+    bytes.extend(&scheme_keys);
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, CustomSerdeError> {
+    let mut pos = 0;
+    let version = read_slice(bytes, &mut pos, 1)?[0];
+    if version != VERIFIER_STATE_ENCODING_VERSION
+      && version != VERIFIER_STATE_ENCODING_VERSION_NO_SCHEME
+      && version != VERIFIER_STATE_ENCODING_VERSION_NO_TAILS
+    {
+      return Err(CustomSerdeError::UnsupportedVersion);
+    }
+    let group_identity =
+      NimbleDigest::from_bytes(read_slice(bytes, &mut pos, NimbleDigest::num_bytes())?)?;
+    let view_metablock =
+      MetaBlock::from_bytes(read_slice(bytes, &mut pos, MetaBlock::num_bytes())?)?;
+    let threshold = read_u32_le(bytes, &mut pos)? as usize;
+
+    let num_pks = read_u32_le(bytes, &mut pos)?;
+    let mut pks = HashSet::new();
+    for _ in 0..num_pks {
+      let pk = PublicKey::from_bytes(read_slice(bytes, &mut pos, PublicKey::num_bytes())?)
+        .map_err(CustomSerdeError::InvalidIdSig)?;
+      if !pks.insert(pk.to_bytes()) {
+        return Err(CustomSerdeError::DuplicateEntry);
+      }
+    }
+    if threshold <= pks.len() / 2 || threshold > pks.len() {
+      return Err(CustomSerdeError::InternalError);
+    }
+
+    let num_past_views = read_u32_le(bytes, &mut pos)?;
+    let mut past_views = Vec::new();
+    for _ in 0..num_past_views {
+      past_views.push(NimbleDigest::from_bytes(read_slice(
+        bytes,
+        &mut pos,
+        NimbleDigest::num_bytes(),
+      )?)?);
+    }
+
+    let mut ledger_tails = BTreeMap::new();
+    if version != VERIFIER_STATE_ENCODING_VERSION_NO_TAILS {
+      let num_ledgers = read_u32_le(bytes, &mut pos)?;
+      for _ in 0..num_ledgers {
+        let handle =
+          NimbleDigest::from_bytes(read_slice(bytes, &mut pos, NimbleDigest::num_bytes())?)?;
+        let height = u64::from_le_bytes(
+          read_slice(bytes, &mut pos, std::mem::size_of::<u64>())?
+            .try_into()
+            .map_err(|_| CustomSerdeError::IncorrectLength)?,
+        );
+        let height = usize::try_from(height).map_err(|_| CustomSerdeError::InternalError)?;
+        let tail =
+          NimbleDigest::from_bytes(read_slice(bytes, &mut pos, NimbleDigest::num_bytes())?)?;
+        if ledger_tails.insert(handle, (height, tail)).is_some() {
+          return Err(CustomSerdeError::DuplicateEntry);
+        }
+      }
+    }
+
+    let mut scheme_keys = None;
+    if version == VERIFIER_STATE_ENCODING_VERSION {
+      let len = read_u32_le(bytes, &mut pos)? as usize;
+      if len > 0 {
+        let keys = SchemeKeys::from_bytes(read_slice(bytes, &mut pos, len)?)?;
+        // one key for each endorser of the view
+        if keys.len() != pks.len() || pks.iter().any(|pk| keys.position(pk).is_none()) {
+          return Err(CustomSerdeError::InternalError);
+        }
+        scheme_keys = Some(keys);
+      }
+    }
+    if pos != bytes.len() {
+      return Err(CustomSerdeError::IncorrectLength);
+    }
+
+    Ok(VerifierState {
+      group_identity,
+      view_metablock,
+      pks,
+      threshold,
+      scheme_keys,
+      past_views,
+      ledger_tails,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::{
+    compute_key_rotation_message, encode_view_config,
+    hash::{HashAlgorithm, HASH_ALGORITHM},
+    signature::{PrivateKey, PrivateKeyTrait},
+    EndorserHostnames, IdSig, KeyRotation, Receipt,
+  };
+
+  include!("../../ledger/testdata/receipts.rs");
+  use golden::*;
+
+  fn digest(hex: &str) -> NimbleDigest {
+    NimbleDigest::from_bytes(&hex::decode(hex).unwrap()).unwrap()
+  }
+
+  /// the metablock of the first view, whose block hash is the group identity
+  fn golden_view_metablock() -> MetaBlock {
+    MetaBlock::default()
+      .next(&digest(GOLDEN_GROUP_IDENTITY))
+      .unwrap()
+  }
+
+  fn golden_state() -> VerifierState {
+    let pk = PublicKey::from_bytes(&hex::decode(GOLDEN_PUBLIC_KEY).unwrap()).unwrap();
+    VerifierState::new(
+      &digest(GOLDEN_GROUP_IDENTITY),
+      &golden_view_metablock(),
+      &[pk],
+    )
+  }
+
+  #[test]
+  fn test_golden_receipts() {
+    // the vectors are of SHA-256 digests
+    if HASH_ALGORITHM != HashAlgorithm::Sha256 {
+      return;
+    }
+    let state = golden_state();
+    assert_eq!(state.current_view(), digest(GOLDEN_VIEW));
+    let receipts = |vector: &ReceiptVector| hex::decode(vector.receipts).unwrap();
+
+    // the receipts of the writes chain the entries through their tails
+    let tail = state
+      .verify_new_ledger(
+        GOLDEN_HANDLE,
+        GOLDEN_GENESIS_BLOCK,
+        GOLDEN_GENESIS_METADATA,
+        &receipts(&GOLDEN_NEW_LEDGER),
+      )
+      .unwrap();
+    assert_eq!(tail, digest(GOLDEN_APPEND.prev));
+    let block_hash = compute_aggregated_block_hash(
+      &NimbleDigest::digest(GOLDEN_BLOCK).to_bytes(),
+      &NimbleDigest::default().to_bytes(),
+    );
+    assert_eq!(block_hash, digest(GOLDEN_APPEND.block_hash));
+    let tail = state
+      .verify_append(
+        GOLDEN_HANDLE,
+        &block_hash,
+        GOLDEN_APPEND.height,
+        Some(&tail),
+        &receipts(&GOLDEN_APPEND),
+      )
+      .unwrap();
+    let nonce = Nonce::try_from_bytes(&hex::decode(GOLDEN_READ_LATEST.nonce).unwrap()).unwrap();
+    let res = state.verify_read_latest(
+      GOLDEN_HANDLE,
+      &nonce,
+      &block_hash,
+      GOLDEN_READ_LATEST.height,
+      &receipts(&GOLDEN_READ_LATEST),
+    );
+    assert_eq!(res, Ok(tail));
+
+    // the genesis entry is covered by the receipts of the entry after it
+    let block_hashes = [digest(GOLDEN_NEW_LEDGER.block_hash), block_hash];
+    let res = state.verify_chain(
+      GOLDEN_HANDLE,
+      None,
+      &block_hashes,
+      &receipts(&GOLDEN_APPEND),
+    );
+    assert_eq!(res, Ok(tail));
+    let genesis = MetaBlock::genesis(&block_hashes[0]);
+    let res = state.verify_chain(
+      GOLDEN_HANDLE,
+      Some(&genesis),
+      &block_hashes[1..],
+      &receipts(&GOLDEN_APPEND),
+    );
+    assert_eq!(res, Ok(tail));
+    let res = state.verify_chain(
+      GOLDEN_HANDLE,
+      None,
+      &block_hashes[1..],
+      &receipts(&GOLDEN_APPEND),
+    );
+    assert_eq!(res, Err(VerifierError::WrongEntry));
+
+    // the payloads are the ones that the endorser signed
+    let handle = NimbleDigest::digest(GOLDEN_HANDLE);
+    for vector in [&GOLDEN_NEW_LEDGER, &GOLDEN_APPEND, &GOLDEN_READ_LATEST] {
+      let metablock = MetaBlock::new(
+        &digest(vector.prev),
+        &digest(vector.block_hash),
+        vector.height,
+      );
+      let nonce = Nonce::try_from_bytes(&hex::decode(vector.nonce).unwrap()).ok();
+      assert_eq!(
+        state.tail_message(&handle, &metablock.hash(), nonce.as_ref()),
+        hex::decode(vector.message).unwrap()
+      );
+    }
+  }
+
+  /// renders the golden vectors as the JSON of `ledger/testdata/receipts.json`, which the tests in
+  /// other languages read to check that they parse receipts and compute the payloads that the
+  /// endorsers sign as the verifier does
+  fn golden_vectors_json() -> String {
+    let vectors = [
+      (
+        "new_ledger",
+        GOLDEN_GENESIS_BLOCK,
+        GOLDEN_GENESIS_METADATA,
+        &GOLDEN_NEW_LEDGER,
+      ),
+      ("append", GOLDEN_BLOCK, &b""[..], &GOLDEN_APPEND),
+      ("read_latest", GOLDEN_BLOCK, &b""[..], &GOLDEN_READ_LATEST),
+    ];
+    let vectors = vectors
+      .iter()
+      .map(|(name, block, metadata, vector)| {
+        let tail = MetaBlock::new(
+          &digest(vector.prev),
+          &digest(vector.block_hash),
+          vector.height,
+        )
+        .hash();
+        format!(
+          concat!(
+            "    {{\n",
+            "      \"name\": \"{}\",\n",
+            "      \"block\": \"{}\",\n",
+            "      \"metadata\": \"{}\",\n",
+            "      \"prev\": \"{}\",\n",
+            "      \"block_hash\": \"{}\",\n",
+            "      \"height\": {},\n",
+            "      \"tail\": \"{}\",\n",
+            "      \"nonce\": \"{}\",\n",
+            "      \"message\": \"{}\",\n",
+            "      \"receipts\": \"{}\"\n",
+            "    }}"
+          ),
+          name,
+          hex::encode(block),
+          hex::encode(metadata),
+          vector.prev,
+          vector.block_hash,
+          vector.height,
+          tail,
+          vector.nonce,
+          vector.message,
+          vector.receipts
+        )
+      })
+      .collect::<Vec<String>>();
+    format!(
+      concat!(
+        "{{\n",
+        "  \"hash_algorithm\": \"sha256\",\n",
+        "  \"group_identity\": \"{}\",\n",
+        "  \"view\": \"{}\",\n",
+        "  \"public_key\": \"{}\",\n",
+        "  \"handle\": \"{}\",\n",
+        "  \"vectors\": [\n{}\n",
+        "  ]\n",
+        "}}\n"
+      ),
+      GOLDEN_GROUP_IDENTITY,
+      GOLDEN_VIEW,
+      GOLDEN_PUBLIC_KEY,
+      hex::encode(GOLDEN_HANDLE),
+      vectors.join(",\n")
+    )
+  }
+
+  #[test]
+  fn test_golden_vectors_json() {
+    if HASH_ALGORITHM != HashAlgorithm::Sha256 {
+      return;
+    }
+    // the JSON is checked in, so that the tests in other languages do not need to build Rust
+    let path = concat!(
+      env!("CARGO_MANIFEST_DIR"),
+      "/../ledger/testdata/receipts.json"
+    );
+    let json = golden_vectors_json();
+    if std::env::var_os("NIMBLE_UPDATE_TEST_VECTORS").is_some() {
+      std::fs::write(path, &json).unwrap();
+    }
+    assert_eq!(
+      std::fs::read_to_string(path).unwrap(),
+      json,
+      "{} is stale; run the test with NIMBLE_UPDATE_TEST_VECTORS=1 to write it",
+      path
+    );
+  }
+
+  #[test]
+  fn test_verification_failures() {
+    if HASH_ALGORITHM != HashAlgorithm::Sha256 {
+      return;
+    }
+    let state = golden_state();
+    let receipts = hex::decode(GOLDEN_APPEND.receipts).unwrap();
+    let block_hash = digest(GOLDEN_APPEND.block_hash);
+    let prev = digest(GOLDEN_APPEND.prev);
+    let verify_append = |state: &VerifierState, height: usize, receipts: &[u8]| {
+      state.verify_append(GOLDEN_HANDLE, &block_hash, height, Some(&prev), receipts)
+    };
+    assert!(verify_append(&state, 1, &receipts).is_ok());
+
+    // another entry, or the same entry of another ledger
+    assert_eq!(
+      verify_append(&state, 2, &receipts),
+      Err(VerifierError::WrongEntry)
+    );
+    let res = state.verify_append(b"another-ledger", &block_hash, 1, None, &receipts);
+    assert_eq!(
+      res,
+      Err(VerifierError::BadSignature(
+        hex::decode(GOLDEN_PUBLIC_KEY).unwrap()
+      ))
+    );
+
+    // a corrupted signature, and one that is not from the verifier's view
+    let mut corrupted = receipts.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+      verify_append(&state, 1, &corrupted),
+      Err(VerifierError::BadSignature(_))
+    ));
+    let other_view = VerifierState::new(
+      state.get_group_identity(),
+      &MetaBlock::genesis(&NimbleDigest::digest(b"another view")),
+      &[PublicKey::from_bytes(&hex::decode(GOLDEN_PUBLIC_KEY).unwrap()).unwrap()],
+    );
+    assert_eq!(
+      verify_append(&other_view, 1, &receipts),
+      Err(VerifierError::StaleVerifier {
+        view: digest(GOLDEN_VIEW),
+        next_index: 1,
+      })
+    );
+
+    // one of two endorsers is not a majority
+    let other = ledger::signature::PrivateKey::new()
+      .get_public_key()
+      .unwrap();
+    let pks = [
+      PublicKey::from_bytes(&hex::decode(GOLDEN_PUBLIC_KEY).unwrap()).unwrap(),
+      other,
+    ];
+    let two = VerifierState::new(state.get_group_identity(), &golden_view_metablock(), &pks);
+    assert_eq!(two.get_threshold(), 2);
+    assert_eq!(
+      verify_append(&two, 1, &receipts),
+      Err(VerifierError::InsufficientQuorum {
+        signers: 1,
+        threshold: 2
+      })
+    );
+    assert!(two.clone().with_threshold(1).is_err());
+    assert!(two.with_threshold(3).is_err());
+    assert_eq!(
+      verify_append(&state, 1, &receipts[1..]),
+      Err(VerifierError::MalformedReceipts)
+    );
+
+    // the receipts of an append endorse the tail, but not that it still was when the endorser
+    // saw the nonce; those of a read with another nonce endorse nothing
+    let res = state.verify_read_latest(GOLDEN_HANDLE, &Nonce::new(), &block_hash, 1, &receipts);
+    assert_eq!(res, Err(VerifierError::NonceMismatch));
+    let read_receipts = hex::decode(GOLDEN_READ_LATEST.receipts).unwrap();
+    let res =
+      state.verify_read_latest(GOLDEN_HANDLE, &Nonce::new(), &block_hash, 1, &read_receipts);
+    assert!(matches!(res, Err(VerifierError::BadSignature(_))));
+  }
+
+  /// the block of a view ledger entry that lists the endorsers with `keys`
+  fn view_block(keys: &[&PrivateKey]) -> Vec<u8> {
+    let hostnames = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let pk = key.get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    bincode::serialize(&hostnames).unwrap()
+  }
+
+  /// adds the receipts of `keys` on `metablock`, which they sign with `message` in `view`
+  fn sign(
+    receipts: &mut Receipts,
+    keys: &[&PrivateKey],
+    view: &NimbleDigest,
+    metablock: &MetaBlock,
+    message: &NimbleDigest,
+  ) {
+    for key in keys {
+      let id_sig = IdSig::new(
+        key.get_public_key().unwrap(),
+        key.sign(&message.to_bytes()).unwrap(),
+      );
+      receipts.add(&Receipt::new(*view, metablock.clone(), id_sig));
+    }
+  }
+
+  /// adds the receipts of `keys` on `metablock` as an entry of the view ledger, which they sign
+  /// together with the hash of their state
+  fn sign_view_entry(
+    receipts: &mut Receipts,
+    keys: &[&PrivateKey],
+    group_identity: &NimbleDigest,
+    state_hash: &NimbleDigest,
+    metablock: &MetaBlock,
+  ) {
+    let message = group_identity.digest_with(&state_hash.digest_with(&metablock.hash()));
+    sign(receipts, keys, state_hash, metablock, &message);
+  }
+
+  #[test]
+  fn test_view_changes() {
+    let keys = (0..5).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let old_keys = [&keys[0], &keys[1], &keys[2]];
+    let new_keys = [&keys[2], &keys[3], &keys[4]];
+    let (old_state_hash, new_state_hash) =
+      (NimbleDigest::digest(b"old"), NimbleDigest::digest(b"new"));
+
+    // the client trusts the first view once a majority of its endorsers joined it
+    let first_block = view_block(&old_keys);
+    let group_identity = compute_view_block_hash(&first_block).unwrap();
+    let first = MetaBlock::default().next(&group_identity).unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &old_keys[..1],
+      &group_identity,
+      &new_state_hash,
+      &first,
+    );
+    let res = VerifierState::from_first_view(&group_identity, &first_block, &receipts.to_bytes());
+    assert_eq!(
+      res,
+      Err(VerifierError::InsufficientQuorum {
+        signers: 1,
+        threshold: 2
+      })
+    );
+    sign_view_entry(
+      &mut receipts,
+      &old_keys[1..2],
+      &group_identity,
+      &new_state_hash,
+      &first,
+    );
+    let mut state =
+      VerifierState::from_first_view(&group_identity, &first_block, &receipts.to_bytes()).unwrap();
+    assert_eq!(state.current_view(), first.hash());
+    assert_eq!(state.current_view_index(), 1);
+    let first_receipts = receipts.to_bytes();
+    assert_eq!(
+      state.apply_view_change(&first_block, &first_receipts),
+      Ok(())
+    );
+
+    // an append in the second view
+    let second_block = view_block(&new_keys);
+    let second = first
+      .next(&compute_view_block_hash(&second_block).unwrap())
+      .unwrap();
+    let block_hash = NimbleDigest::digest(b"block");
+    let entry = MetaBlock::genesis(&block_hash);
+    let mut append_receipts = Receipts::new();
+    let message = compute_ledger_tail_message(
+      &group_identity,
+      &second.hash(),
+      &NimbleDigest::digest(b"ledger"),
+      &entry.hash(),
+    );
+    sign(
+      &mut append_receipts,
+      &new_keys[1..],
+      &second.hash(),
+      &entry,
+      &message,
+    );
+    let append_receipts = append_receipts.to_bytes();
+    let verify_append = |state: &VerifierState| {
+      state.verify_append(b"ledger", &block_hash, 0, None, &append_receipts)
+    };
+    assert_eq!(
+      verify_append(&state),
+      Err(VerifierError::StaleVerifier {
+        view: second.hash(),
+        next_index: 2
+      })
+    );
+
+    // the second view needs the threshold of the endorsers of the first, which finalized the first
+    // view into it, and a majority of its own, which joined it
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &old_keys[..1],
+      &group_identity,
+      &old_state_hash,
+      &second,
+    );
+    sign_view_entry(
+      &mut receipts,
+      &new_keys[1..],
+      &group_identity,
+      &new_state_hash,
+      &second,
+    );
+    assert_eq!(
+      state.apply_view_change(&second_block, &receipts.to_bytes()),
+      Err(VerifierError::InsufficientQuorum {
+        signers: 1,
+        threshold: 2
+      })
+    );
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &old_keys,
+      &group_identity,
+      &old_state_hash,
+      &second,
+    );
+    sign_view_entry(
+      &mut receipts,
+      &new_keys[..1],
+      &group_identity,
+      &new_state_hash,
+      &second,
+    );
+    assert!(matches!(
+      state.apply_view_change(&second_block, &receipts.to_bytes()),
+      Err(VerifierError::InsufficientQuorum { .. })
+    ));
+    sign_view_entry(
+      &mut receipts,
+      &new_keys[1..],
+      &group_identity,
+      &new_state_hash,
+      &second,
+    );
+    let second_receipts = receipts.to_bytes();
+
+    // the entry of a later view needs the entries before it
+    let third_block = view_block(&old_keys);
+    let third = second
+      .next(&compute_view_block_hash(&third_block).unwrap())
+      .unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &new_keys,
+      &group_identity,
+      &old_state_hash,
+      &third,
+    );
+    sign_view_entry(
+      &mut receipts,
+      &old_keys,
+      &group_identity,
+      &new_state_hash,
+      &third,
+    );
+    assert_eq!(
+      state.apply_view_change(&third_block, &receipts.to_bytes()),
+      Err(VerifierError::StaleVerifier {
+        view: third.hash(),
+        next_index: 2
+      })
+    );
+    assert_eq!(
+      state.apply_view_change(&first_block, &second_receipts),
+      Err(VerifierError::WrongEntry)
+    );
+
+    // the state persists across the view change, after which the receipts of the first view are
+    // no longer accepted
+    let saved = state.to_bytes();
+    assert_eq!(VerifierState::from_bytes(&saved), Ok(state.clone()));
+    assert!(state
+      .apply_view_change(&second_block, &second_receipts)
+      .is_ok());
+    assert_eq!(state.current_view(), second.hash());
+    assert_eq!(state.current_view_index(), 2);
+    assert!(verify_append(&state).is_ok());
+    let mut old_receipts = Receipts::new();
+    let message = compute_ledger_tail_message(
+      &group_identity,
+      &first.hash(),
+      &NimbleDigest::digest(b"ledger"),
+      &entry.hash(),
+    );
+    sign(
+      &mut old_receipts,
+      &old_keys,
+      &first.hash(),
+      &entry,
+      &message,
+    );
+    assert_eq!(
+      state.verify_append(b"ledger", &block_hash, 0, None, &old_receipts.to_bytes()),
+      Err(VerifierError::WrongView {
+        expected: second.hash(),
+        found: first.hash()
+      })
+    );
+
+    // so do the tails of the ledgers, which only move up
+    let tail = NimbleDigest::digest(b"tail");
+    state.set_ledger_tail(b"ledger", 3, &tail);
+    state.set_ledger_tail(b"ledger", 2, &NimbleDigest::digest(b"stale"));
+    state.set_ledger_tail(b"other", 0, &NimbleDigest::digest(b"genesis"));
+    assert_eq!(state.get_ledger_tail(b"ledger"), Some((3, tail)));
+    assert_eq!(state.get_ledger_tail(b"none"), None);
+
+    let bytes = state.to_bytes();
+    let restored = VerifierState::from_bytes(&bytes).unwrap();
+    assert_eq!(restored, state);
+    assert_eq!(restored.to_bytes(), bytes);
+    assert!(verify_append(&restored).is_ok());
+    for len in 0..bytes.len() {
+      assert!(VerifierState::from_bytes(&bytes[..len]).is_err());
+    }
+    // a state saved before the scheme keys, or the tails, were recorded is read without them
+    let mut v2 = bytes.clone();
+    v2.truncate(v2.len() - std::mem::size_of::<u32>());
+    v2[0] = VERIFIER_STATE_ENCODING_VERSION_NO_SCHEME;
+    assert_eq!(VerifierState::from_bytes(&v2), Ok(state.clone()));
+    let mut no_tails = state.clone();
+    no_tails.ledger_tails.clear();
+    let mut v1 = no_tails.to_bytes();
+    v1.truncate(v1.len() - 2 * std::mem::size_of::<u32>());
+    v1[0] = VERIFIER_STATE_ENCODING_VERSION_NO_TAILS;
+    assert_eq!(VerifierState::from_bytes(&v1), Ok(no_tails));
+    let mut unknown_version = bytes.clone();
+    unknown_version[0] = 4;
+    assert_eq!(
+      VerifierState::from_bytes(&unknown_version),
+      Err(CustomSerdeError::UnsupportedVersion)
+    );
+  }
+
+  #[test]
+  fn test_key_rotation() {
+    let keys = (0..4).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let old_keys = [&keys[0], &keys[1], &keys[2]];
+    let new_keys = [&keys[3], &keys[1], &keys[2]];
+    let state_hash = NimbleDigest::digest(b"state");
+    let first_block = view_block(&old_keys);
+    let group_identity = compute_view_block_hash(&first_block).unwrap();
+    let first = MetaBlock::default().next(&group_identity).unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &old_keys,
+      &group_identity,
+      &state_hash,
+      &first,
+    );
+    let state =
+      VerifierState::from_first_view(&group_identity, &first_block, &receipts.to_bytes()).unwrap();
+
+    // the first endorser hands its key over to the fourth key, and signs the handover with it
+    let (old_pk, new_pk) = (
+      keys[0].get_public_key().unwrap(),
+      keys[3].get_public_key().unwrap(),
+    );
+    let message =
+      compute_key_rotation_message(&group_identity, &old_pk.to_bytes(), &new_pk.to_bytes());
+    let rotation = KeyRotation::new(
+      &old_pk,
+      &new_pk,
+      &keys[0].sign(&message.to_bytes()).unwrap(),
+    );
+    let forged = KeyRotation::new(
+      &old_pk,
+      &new_pk,
+      &keys[3].sign(&message.to_bytes()).unwrap(),
+    );
+    let apply_view = |rotations: &[KeyRotation]| {
+      let hostnames = bincode::deserialize::<EndorserHostnames>(&view_block(&new_keys)).unwrap();
+      let block = encode_view_config(&hostnames, None, rotations);
+      let second = first
+        .next(&compute_view_block_hash(&block).unwrap())
+        .unwrap();
+      let mut receipts = Receipts::new();
+      sign_view_entry(
+        &mut receipts,
+        &old_keys,
+        &group_identity,
+        &state_hash,
+        &second,
+      );
+      sign_view_entry(
+        &mut receipts,
+        &new_keys[..1],
+        &group_identity,
+        &state_hash,
+        &second,
+      );
+      let mut state = state.clone();
+      state
+        .apply_view_change(&block, &receipts.to_bytes())
+        .map(|()| state)
+    };
+    let rotated = apply_view(&[rotation]).unwrap();
+    assert_eq!(rotated.current_view_index(), 2);
+    assert_eq!(
+      apply_view(&[forged]),
+      Err(VerifierError::InvalidKeyRotation)
+    );
+    // a view change without rotations still replaces the endorser
+    assert!(apply_view(&[]).is_ok());
+  }
+
+  #[test]
+  fn test_view_quorum() {
+    let keys = (0..3).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let keys = keys.iter().collect::<Vec<_>>();
+    let state_hash = NimbleDigest::digest(b"state");
+    let first_block = view_block(&keys);
+    let group_identity = compute_view_block_hash(&first_block).unwrap();
+    let first = MetaBlock::default().next(&group_identity).unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &keys[..2],
+      &group_identity,
+      &state_hash,
+      &first,
+    );
+    let mut state =
+      VerifierState::from_first_view(&group_identity, &first_block, &receipts.to_bytes()).unwrap();
+    assert_eq!(state.get_threshold(), 2);
+
+    // the second view keeps the endorsers, but needs all of them
+    let hostnames = bincode::deserialize::<EndorserHostnames>(&first_block).unwrap();
+    let second_block = encode_view_config(&hostnames, Some(3), &[]);
+    let second = first
+      .next(&compute_view_block_hash(&second_block).unwrap())
+      .unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(
+      &mut receipts,
+      &keys[..2],
+      &group_identity,
+      &state_hash,
+      &second,
+    );
+    assert_eq!(
+      state.apply_view_change(&second_block, &receipts.to_bytes()),
+      Err(VerifierError::InsufficientQuorum {
+        signers: 2,
+        threshold: 3
+      })
+    );
+    sign_view_entry(
+      &mut receipts,
+      &keys[2..],
+      &group_identity,
+      &state_hash,
+      &second,
+    );
+    let old_state = state.clone();
+    state
+      .apply_view_change(&second_block, &receipts.to_bytes())
+      .unwrap();
+    assert_eq!(state.get_threshold(), 3);
+
+    // the receipts of each view are held to the quorum of that view
+    let block_hash = NimbleDigest::digest(b"block");
+    let entry = MetaBlock::genesis(&block_hash);
+    let append_receipts = |view: &MetaBlock, signers: usize| {
+      let message = compute_ledger_tail_message(
+        &group_identity,
+        &view.hash(),
+        &NimbleDigest::digest(b"ledger"),
+        &entry.hash(),
+      );
+      let mut receipts = Receipts::new();
+      sign(
+        &mut receipts,
+        &keys[..signers],
+        &view.hash(),
+        &entry,
+        &message,
+      );
+      receipts.to_bytes()
+    };
+    let verify_append = |state: &VerifierState, receipts: &[u8]| {
+      state.verify_append(b"ledger", &block_hash, 0, None, receipts)
+    };
+    assert!(verify_append(&old_state, &append_receipts(&first, 2)).is_ok());
+    assert_eq!(
+      verify_append(&state, &append_receipts(&second, 2)),
+      Err(VerifierError::InsufficientQuorum {
+        signers: 2,
+        threshold: 3
+      })
+    );
+    assert!(verify_append(&state, &append_receipts(&second, 3)).is_ok());
+
+    // a view block may not record less than a majority
+    let minority = encode_view_config(&hostnames, Some(1), &[]);
+    assert_eq!(
+      state.apply_view_change(&minority, &receipts.to_bytes()),
+      Err(VerifierError::MalformedViewBlock)
+    );
+  }
+
+  #[test]
+  fn test_view_hash_algorithm() {
+    let keys = (0..3).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let keys = keys.iter().collect::<Vec<_>>();
+    let other = match HASH_ALGORITHM {
+      HashAlgorithm::Sha256 => HashAlgorithm::Blake3,
+      HashAlgorithm::Blake3 => HashAlgorithm::Sha256,
+    };
+    let block = [
+      &view_block(&keys)[..],
+      b"NimbleViewHash",
+      &other.id().to_le_bytes(),
+    ]
+    .concat();
+    assert_eq!(
+      VerifierState::from_first_view(&NimbleDigest::default(), &block, &[]),
+      Err(VerifierError::HashAlgorithmMismatch)
+    );
+    // a block cut short within the algorithm is malformed rather than of another algorithm
+    assert_eq!(
+      VerifierState::from_first_view(&NimbleDigest::default(), &block[..block.len() - 1], &[]),
+      Err(VerifierError::MalformedViewBlock)
+    );
+  }
+
+  #[cfg(feature = "bls")]
+  #[test]
+  fn test_aggregated_receipts() {
+    use ledger::{encode_view_config_with_scheme, signature::bls};
+
+    let keys = (0..3).map(|_| PrivateKey::new()).collect::<Vec<_>>();
+    let keys = keys.iter().collect::<Vec<_>>();
+    let bls_keys = (0..3).map(|_| bls::PrivateKey::new()).collect::<Vec<_>>();
+    let state_hash = NimbleDigest::digest(b"state");
+    let first_block = view_block(&keys);
+    let group_identity = compute_view_block_hash(&first_block).unwrap();
+    let first = MetaBlock::default().next(&group_identity).unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(&mut receipts, &keys, &group_identity, &state_hash, &first);
+    let mut state =
+      VerifierState::from_first_view(&group_identity, &first_block, &receipts.to_bytes()).unwrap();
+    assert_eq!(state.get_scheme(), SignatureSchemeId::P256);
+
+    // the second view keeps the endorsers, which sign receipts with BLS keys from then on
+    let hostnames = bincode::deserialize::<EndorserHostnames>(&first_block).unwrap();
+    let view_keys = |proofs: &[bls::Signature]| {
+      SchemeKeys::new(
+        SignatureSchemeId::Bls12381,
+        keys
+          .iter()
+          .zip(&bls_keys)
+          .zip(proofs)
+          .map(|((key, bls_key), proof)| {
+            (
+              key.get_public_key().unwrap().to_bytes(),
+              bls_key.get_public_key().to_bytes(),
+              proof.to_bytes(),
+            )
+          })
+          .collect(),
+      )
+    };
+    let proofs = bls_keys
+      .iter()
+      .map(|key| key.prove_possession())
+      .collect::<Vec<_>>();
+    let scheme_keys = view_keys(&proofs);
+    let second_block = encode_view_config_with_scheme(&hostnames, None, Some(&scheme_keys), &[]);
+    let second = first
+      .next(&compute_view_block_hash(&second_block).unwrap())
+      .unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(&mut receipts, &keys, &group_identity, &state_hash, &second);
+    let old_state = state.clone();
+    state
+      .apply_view_change(&second_block, &receipts.to_bytes())
+      .unwrap();
+    assert_eq!(state.get_scheme(), SignatureSchemeId::Bls12381);
+    let restored = VerifierState::from_bytes(&state.to_bytes()).unwrap();
+    assert_eq!(restored, state);
+
+    // a key without a proof of possession is not taken
+    let mut rogue_proofs = proofs.clone();
+    rogue_proofs[0] = bls_keys[0].sign(b"not a proof");
+    let rogue_block =
+      encode_view_config_with_scheme(&hostnames, None, Some(&view_keys(&rogue_proofs)), &[]);
+    let rogue = first
+      .next(&compute_view_block_hash(&rogue_block).unwrap())
+      .unwrap();
+    let mut receipts = Receipts::new();
+    sign_view_entry(&mut receipts, &keys, &group_identity, &state_hash, &rogue);
+    assert_eq!(
+      old_state
+        .clone()
+        .apply_view_change(&rogue_block, &receipts.to_bytes()),
+      Err(VerifierError::InvalidProofOfPossession)
+    );
+
+    // the signers of an aggregated receipt are named by their positions in the view
+    let block_hash = NimbleDigest::digest(b"block");
+    let entry = MetaBlock::genesis(&block_hash);
+    let aggregate = |view: &MetaBlock, signers: &[usize], nonce: Option<&Nonce>| {
+      let message = ledger_tail_message(
+        &group_identity,
+        &view.hash(),
+        &NimbleDigest::digest(b"ledger"),
+        &entry.hash(),
+        nonce,
+      );
+      let signatures = signers
+        .iter()
+        .map(|i| {
+          let position = scheme_keys
+            .position(&keys[*i].get_public_key().unwrap().to_bytes())
+            .unwrap();
+          (position, bls_keys[*i].sign(&message))
+        })
+        .collect::<Vec<_>>();
+      Receipt::aggregate::<bls::Bls>(&view.hash(), &entry, 3, &signatures)
+        .unwrap()
+        .to_bytes()
+    };
+    let tail = entry.hash();
+    assert_eq!(
+      state.verify_aggregated_append(
+        b"ledger",
+        &block_hash,
+        0,
+        None,
+        &aggregate(&second, &[0, 2], None)
+      ),
+      Ok(tail)
+    );
+    assert_eq!(
+      state.verify_aggregated_append(
+        b"ledger",
+        &block_hash,
+        0,
+        None,
+        &aggregate(&second, &[1], None)
+      ),
+      Err(VerifierError::InsufficientQuorum {
+        signers: 1,
+        threshold: 2
+      })
+    );
+    assert_eq!(
+      state.verify_aggregated_append(
+        b"other",
+        &block_hash,
+        0,
+        None,
+        &aggregate(&second, &[0, 1], None)
+      ),
+      Err(VerifierError::BadAggregateSignature)
+    );
+    assert_eq!(
+      state.verify_aggregated_append(
+        b"ledger",
+        &block_hash,
+        1,
+        None,
+        &aggregate(&second, &[0, 1], None)
+      ),
+      Err(VerifierError::WrongEntry)
+    );
+    let nonce = Nonce::new();
+    assert_eq!(
+      state.verify_aggregated_read_latest(
+        b"ledger",
+        &nonce,
+        &block_hash,
+        0,
+        &aggregate(&second, &[0, 1, 2], Some(&nonce))
+      ),
+      Ok(tail)
+    );
+    assert_eq!(
+      state.verify_aggregated_read_latest(
+        b"ledger",
+        &nonce,
+        &block_hash,
+        0,
+        &aggregate(&second, &[0, 1, 2], None)
+      ),
+      Err(VerifierError::NonceMismatch)
+    );
+    assert_eq!(
+      state.verify_aggregated_append(b"ledger", &block_hash, 0, None, &[0u8; 8]),
+      Err(VerifierError::MalformedReceipts)
+    );
+
+    // receipts of the P-256 view do not aggregate
+    assert_eq!(
+      old_state.verify_aggregated_append(
+        b"ledger",
+        &block_hash,
+        0,
+        None,
+        &aggregate(&first, &[0, 1], None)
+      ),
+      Err(VerifierError::UnsupportedSignatureScheme)
+    );
+    assert_eq!(
+      state.verify_aggregated_append(
+        b"ledger",
+        &block_hash,
+        0,
+        None,
+        &aggregate(&first, &[0, 1], None)
+      ),
+      Err(VerifierError::WrongView {
+        expected: second.hash(),
+        found: first.hash()
+      })
+    );
+  }
+
+  #[test]
+  fn test_check_freshness() {
+    let now = 1_700_000_000_000;
+    let minute = Duration::from_secs(60);
+    let second = Duration::from_secs(1);
+    assert_eq!(
+      check_freshness(now - 30_000, now, minute, second),
+      Ok(Duration::from_secs(30))
+    );
+    // the clocks may be a second apart either way
+    assert_eq!(
+      check_freshness(now - 60_500, now, minute, second),
+      Ok(Duration::from_millis(60_500))
+    );
+    assert_eq!(
+      check_freshness(now + 500, now, minute, second),
+      Ok(Duration::ZERO)
+    );
+    assert_eq!(
+      check_freshness(now - 61_001, now, minute, second),
+      Err(VerifierError::StaleHeartbeat {
+        age_ms: 61_001,
+        max_age_ms: 60_000
+      })
+    );
+    assert_eq!(
+      check_freshness(now + 1_001, now, minute, second),
+      Err(VerifierError::FutureHeartbeat { ahead_ms: 1_001 })
+    );
+    assert!(check_freshness(0, now, minute, Duration::ZERO).is_err());
+  }
+}
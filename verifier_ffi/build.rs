@@ -0,0 +1,8 @@
+fn main() {
+  let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+  println!("cargo:rerun-if-changed=src/lib.rs");
+  println!("cargo:rerun-if-changed=cbindgen.toml");
+  cbindgen::generate(&crate_dir)
+    .expect("failed to generate the C header")
+    .write_to_file(format!("{}/include/verifier_ffi.h", crate_dir));
+}
@@ -0,0 +1,159 @@
+#ifndef NIMBLE_VERIFIER_FFI_H
+#define NIMBLE_VERIFIER_FFI_H
+
+/* Generated by cbindgen from verifier_ffi/src/lib.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * the length of a digest, such as a block hash or the hash of a tail, in bytes
+ */
+#define NIMBLE_DIGEST_LEN 32
+
+typedef enum NimbleStatus {
+  NIMBLE_STATUS_OK = 0,
+  /**
+   * a pointer is null but its length is not zero, or an output pointer is null
+   */
+  NIMBLE_STATUS_NULL_ARGUMENT = 1,
+  /**
+   * a buffer is not of the expected length or encoding, e.g., a digest that is not 32 bytes
+   */
+  NIMBLE_STATUS_MALFORMED_INPUT = 2,
+  /**
+   * the output buffer is too small; the length it needs is returned
+   */
+  NIMBLE_STATUS_BUFFER_TOO_SMALL = 3,
+  NIMBLE_STATUS_MALFORMED_RECEIPTS = 4,
+  /**
+   * the receipts are from a past view rather than the one the verifier trusts
+   */
+  NIMBLE_STATUS_WRONG_VIEW = 5,
+  /**
+   * the receipts endorse another entry than the one being verified
+   */
+  NIMBLE_STATUS_WRONG_ENTRY = 6,
+  NIMBLE_STATUS_INSUFFICIENT_QUORUM = 7,
+  NIMBLE_STATUS_BAD_SIGNATURE = 8,
+  NIMBLE_STATUS_NONCE_MISMATCH = 9,
+  /**
+   * the receipts are from a view that the verifier does not know; the caller applies the entries
+   * of the view ledger after the current view and verifies the receipts again
+   */
+  NIMBLE_STATUS_STALE_VERIFIER = 10,
+  NIMBLE_STATUS_MALFORMED_VIEW_BLOCK = 11,
+  NIMBLE_STATUS_INVALID_THRESHOLD = 12,
+  /**
+   * the verifier panicked, which is a bug
+   */
+  NIMBLE_STATUS_PANIC = 13,
+  /**
+   * a heartbeat is older than the freshness bound allows
+   */
+  NIMBLE_STATUS_STALE_HEARTBEAT = 14,
+  /**
+   * a heartbeat is ahead of the local clock by more than the skew tolerance
+   */
+  NIMBLE_STATUS_FUTURE_HEARTBEAT = 15,
+  /**
+   * an aggregated receipt is from a view whose signatures do not aggregate, or with a scheme that
+   * the verifier was built without
+   */
+  NIMBLE_STATUS_UNSUPPORTED_SIGNATURE_SCHEME = 16,
+  /**
+   * a view block records another hash algorithm than the one the verifier was built with
+   */
+  NIMBLE_STATUS_HASH_ALGORITHM_MISMATCH = 17,
+} NimbleStatus;
+
+/**
+ * a verifier state that the caller owns; it is created with `nimble_verifier_new` and freed
+ * with `nimble_verifier_free`
+ */
+typedef struct NimbleVerifier NimbleVerifier;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a verifier from a verifier state that was serialized with `nimble_verifier_to_bytes`,
+ * or by the Rust verifier; it is returned in `out`.
+ *
+ * # Safety
+ *
+ * `state` points to `state_len` readable bytes and `out` to a writable pointer.
+ */
+NimbleStatus nimble_verifier_new(const uint8_t *state, size_t state_len, NimbleVerifier **out);
+
+/**
+ * Frees a verifier; a null `ctx` is ignored.
+ *
+ * # Safety
+ *
+ * `ctx` was returned by `nimble_verifier_new` and is not used afterwards.
+ */
+void nimble_verifier_free(NimbleVerifier *ctx);
+
+/**
+ * Applies the entry of the view ledger after the current view of the verifier, given its block
+ * and receipts, as `ReadViewByIndex` returns them. Applying the entry of the current view again
+ * has no effect.
+ *
+ * # Safety
+ *
+ * `ctx` is a verifier, and each buffer points to as many readable bytes as its length.
+ */
+NimbleStatus nimble_verifier_apply_view_change(NimbleVerifier *ctx,
+                                               const uint8_t *view_block,
+                                               size_t view_block_len,
+                                               const uint8_t *receipts,
+                                               size_t receipts_len);
+
+/**
+ * Verifies the receipts of the append of the entry with `block_hash` at `height` to ledger
+ * `handle`; `block_hash` is the aggregated hash of the block and its nonces. If `prev_tail` is
+ * not empty, the entry must extend that tail. If `out_tail` is not null, the hash of the new
+ * tail, `NIMBLE_DIGEST_LEN` bytes, is written to it.
+ *
+ * # Safety
+ *
+ * `ctx` is a verifier, each buffer points to as many readable bytes as its length, and
+ * `out_tail` is null or points to `NIMBLE_DIGEST_LEN` writable bytes.
+ */
+NimbleStatus nimble_verify_append(const NimbleVerifier *ctx,
+                                  const uint8_t *handle,
+                                  size_t handle_len,
+                                  const uint8_t *block_hash,
+                                  size_t block_hash_len,
+                                  uint64_t height,
+                                  const uint8_t *prev_tail,
+                                  size_t prev_tail_len,
+                                  const uint8_t *receipts,
+                                  size_t receipts_len,
+                                  uint8_t *out_tail);
+
+/**
+ * Serializes the state of the verifier, which follows the view changes that it applied, into
+ * `out`, which holds `out_cap` bytes; the length of the state is returned in `out_len`. If `out`
+ * is too small, nothing is written to it and `NIMBLE_STATUS_BUFFER_TOO_SMALL` is returned.
+ *
+ * # Safety
+ *
+ * `ctx` is a verifier, `out` is null or points to `out_cap` writable bytes, and `out_len` points
+ * to a writable length.
+ */
+NimbleStatus nimble_verifier_to_bytes(const NimbleVerifier *ctx,
+                                      uint8_t *out,
+                                      size_t out_cap,
+                                      size_t *out_len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* NIMBLE_VERIFIER_FFI_H */
@@ -0,0 +1,424 @@
+//! A C API of the verifier, for clients in C and C++, and in Java through JNI, that check the
+//! receipts of the coordinator without the Rust client. `include/verifier_ffi.h` is generated from
+//! this file by the build.
+//!
+//! Every buffer is passed as a pointer and a length; a null pointer is only accepted with a zero
+//! length. Every function returns a `NimbleStatus`, and a panic is caught and returned as
+//! `NIMBLE_STATUS_PANIC` rather than unwound into the caller.
+use ledger::{CustomSerde, NimbleDigest};
+use std::{
+  convert::TryFrom,
+  panic::{catch_unwind, AssertUnwindSafe},
+  ptr, slice,
+};
+use verifier::{VerifierError, VerifierState};
+
+/// the length of a digest, such as a block hash or the hash of a tail, in bytes
+pub const NIMBLE_DIGEST_LEN: usize = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NimbleStatus {
+  Ok = 0,
+  /// a pointer is null but its length is not zero, or an output pointer is null
+  NullArgument = 1,
+  /// a buffer is not of the expected length or encoding, e.g., a digest that is not 32 bytes
+  MalformedInput = 2,
+  /// the output buffer is too small; the length it needs is returned
+  BufferTooSmall = 3,
+  MalformedReceipts = 4,
+  /// the receipts are from a past view rather than the one the verifier trusts
+  WrongView = 5,
+  /// the receipts endorse another entry than the one being verified
+  WrongEntry = 6,
+  InsufficientQuorum = 7,
+  BadSignature = 8,
+  NonceMismatch = 9,
+  /// the receipts are from a view that the verifier does not know; the caller applies the entries
+  /// of the view ledger after the current view and verifies the receipts again
+  StaleVerifier = 10,
+  MalformedViewBlock = 11,
+  InvalidThreshold = 12,
+  /// the verifier panicked, which is a bug
+  Panic = 13,
+  /// a heartbeat is older than the freshness bound allows
+  StaleHeartbeat = 14,
+  /// a heartbeat is ahead of the local clock by more than the skew tolerance
+  FutureHeartbeat = 15,
+  /// an aggregated receipt is from a view whose signatures do not aggregate, or with a scheme that
+  /// the verifier was built without
+  UnsupportedSignatureScheme = 16,
+  /// a view block records another hash algorithm than the one the verifier was built with
+  HashAlgorithmMismatch = 17,
+}
+
+impl From<VerifierError> for NimbleStatus {
+  fn from(error: VerifierError) -> Self {
+    match error {
+      VerifierError::MalformedReceipts => NimbleStatus::MalformedReceipts,
+      VerifierError::WrongView { .. } => NimbleStatus::WrongView,
+      VerifierError::WrongEntry => NimbleStatus::WrongEntry,
+      VerifierError::InsufficientQuorum { .. } => NimbleStatus::InsufficientQuorum,
+      VerifierError::BadSignature(_) | VerifierError::BadAggregateSignature => {
+        NimbleStatus::BadSignature
+      },
+      VerifierError::NonceMismatch => NimbleStatus::NonceMismatch,
+      VerifierError::StaleVerifier { .. } => NimbleStatus::StaleVerifier,
+      VerifierError::MalformedViewBlock
+      | VerifierError::InvalidKeyRotation
+      | VerifierError::InvalidProofOfPossession => NimbleStatus::MalformedViewBlock,
+      VerifierError::InvalidThreshold { .. } => NimbleStatus::InvalidThreshold,
+      VerifierError::StaleHeartbeat { .. } => NimbleStatus::StaleHeartbeat,
+      VerifierError::FutureHeartbeat { .. } => NimbleStatus::FutureHeartbeat,
+      VerifierError::UnsupportedSignatureScheme => NimbleStatus::UnsupportedSignatureScheme,
+      VerifierError::HashAlgorithmMismatch => NimbleStatus::HashAlgorithmMismatch,
+    }
+  }
+}
+
+/// a verifier state that the caller owns; it is created with `nimble_verifier_new` and freed
+/// with `nimble_verifier_free`
+pub struct NimbleVerifier {
+  state: VerifierState,
+}
+
+/// runs `f`, returning `NimbleStatus::Panic` if it panics
+fn guard(f: impl FnOnce() -> Result<(), NimbleStatus>) -> NimbleStatus {
+  match catch_unwind(AssertUnwindSafe(f)) {
+    Ok(Ok(())) => NimbleStatus::Ok,
+    Ok(Err(status)) => status,
+    Err(_) => NimbleStatus::Panic,
+  }
+}
+
+/// the buffer at `data` with `len` bytes
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], NimbleStatus> {
+  if data.is_null() {
+    return match len {
+      0 => Ok(&[]),
+      _ => Err(NimbleStatus::NullArgument),
+    };
+  }
+  Ok(slice::from_raw_parts(data, len))
+}
+
+unsafe fn digest(data: *const u8, len: usize) -> Result<NimbleDigest, NimbleStatus> {
+  NimbleDigest::from_bytes(bytes(data, len)?).map_err(|_e| NimbleStatus::MalformedInput)
+}
+
+unsafe fn verifier<'a>(ctx: *const NimbleVerifier) -> Result<&'a NimbleVerifier, NimbleStatus> {
+  ctx.as_ref().ok_or(NimbleStatus::NullArgument)
+}
+
+/// Creates a verifier from a verifier state that was serialized with `nimble_verifier_to_bytes`,
+/// or by the Rust verifier; it is returned in `out`.
+///
+/// # Safety
+///
+/// `state` points to `state_len` readable bytes and `out` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nimble_verifier_new(
+  state: *const u8,
+  state_len: usize,
+  out: *mut *mut NimbleVerifier,
+) -> NimbleStatus {
+  guard(|| {
+    if out.is_null() {
+      return Err(NimbleStatus::NullArgument);
+    }
+    *out = ptr::null_mut();
+    let state = VerifierState::from_bytes(bytes(state, state_len)?)
+      .map_err(|_e| NimbleStatus::MalformedInput)?;
+    *out = Box::into_raw(Box::new(NimbleVerifier { state }));
+    Ok(())
+  })
+}
+
+/// Frees a verifier; a null `ctx` is ignored.
+///
+/// # Safety
+///
+/// `ctx` was returned by `nimble_verifier_new` and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn nimble_verifier_free(ctx: *mut NimbleVerifier) {
+  if !ctx.is_null() {
+    // dropping the state does not panic, and a panic could not be returned here anyway
+    drop(Box::from_raw(ctx));
+  }
+}
+
+/// Applies the entry of the view ledger after the current view of the verifier, given its block
+/// and receipts, as `ReadViewByIndex` returns them. Applying the entry of the current view again
+/// has no effect.
+///
+/// # Safety
+///
+/// `ctx` is a verifier, and each buffer points to as many readable bytes as its length.
+#[no_mangle]
+pub unsafe extern "C" fn nimble_verifier_apply_view_change(
+  ctx: *mut NimbleVerifier,
+  view_block: *const u8,
+  view_block_len: usize,
+  receipts: *const u8,
+  receipts_len: usize,
+) -> NimbleStatus {
+  guard(|| {
+    let ctx = ctx.as_mut().ok_or(NimbleStatus::NullArgument)?;
+    let view_block = bytes(view_block, view_block_len)?;
+    let receipts = bytes(receipts, receipts_len)?;
+    ctx
+      .state
+      .apply_view_change(view_block, receipts)
+      .map_err(NimbleStatus::from)
+  })
+}
+
+/// Verifies the receipts of the append of the entry with `block_hash` at `height` to ledger
+/// `handle`; `block_hash` is the aggregated hash of the block and its nonces. If `prev_tail` is
+/// not empty, the entry must extend that tail. If `out_tail` is not null, the hash of the new
+/// tail, `NIMBLE_DIGEST_LEN` bytes, is written to it.
+///
+/// # Safety
+///
+/// `ctx` is a verifier, each buffer points to as many readable bytes as its length, and
+/// `out_tail` is null or points to `NIMBLE_DIGEST_LEN` writable bytes.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn nimble_verify_append(
+  ctx: *const NimbleVerifier,
+  handle: *const u8,
+  handle_len: usize,
+  block_hash: *const u8,
+  block_hash_len: usize,
+  height: u64,
+  prev_tail: *const u8,
+  prev_tail_len: usize,
+  receipts: *const u8,
+  receipts_len: usize,
+  out_tail: *mut u8,
+) -> NimbleStatus {
+  guard(|| {
+    let ctx = verifier(ctx)?;
+    let handle = bytes(handle, handle_len)?;
+    let block_hash = digest(block_hash, block_hash_len)?;
+    let height = usize::try_from(height).map_err(|_e| NimbleStatus::MalformedInput)?;
+    let prev_tail = match prev_tail_len {
+      0 => None,
+      _ => Some(digest(prev_tail, prev_tail_len)?),
+    };
+    let receipts = bytes(receipts, receipts_len)?;
+    let tail =
+      ctx
+        .state
+        .verify_append(handle, &block_hash, height, prev_tail.as_ref(), receipts)?;
+    if !out_tail.is_null() {
+      ptr::copy_nonoverlapping(tail.to_bytes().as_ptr(), out_tail, NIMBLE_DIGEST_LEN);
+    }
+    Ok(())
+  })
+}
+
+/// Serializes the state of the verifier, which follows the view changes that it applied, into
+/// `out`, which holds `out_cap` bytes; the length of the state is returned in `out_len`. If `out`
+/// is too small, nothing is written to it and `NIMBLE_STATUS_BUFFER_TOO_SMALL` is returned.
+///
+/// # Safety
+///
+/// `ctx` is a verifier, `out` is null or points to `out_cap` writable bytes, and `out_len` points
+/// to a writable length.
+#[no_mangle]
+pub unsafe extern "C" fn nimble_verifier_to_bytes(
+  ctx: *const NimbleVerifier,
+  out: *mut u8,
+  out_cap: usize,
+  out_len: *mut usize,
+) -> NimbleStatus {
+  guard(|| {
+    let ctx = verifier(ctx)?;
+    if out_len.is_null() {
+      return Err(NimbleStatus::NullArgument);
+    }
+    let state = ctx.state.to_bytes();
+    *out_len = state.len();
+    if state.len() > out_cap {
+      return Err(NimbleStatus::BufferTooSmall);
+    }
+    if out.is_null() {
+      return Err(NimbleStatus::NullArgument);
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), out, state.len());
+    Ok(())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ledger::{
+    compute_ledger_tail_message, compute_view_block_hash,
+    signature::{PrivateKey, PrivateKeyTrait, PublicKeyTrait},
+    EndorserHostnames, IdSig, MetaBlock, NimbleHashTrait, Receipt, Receipts,
+  };
+
+  fn view_block(keys: &[&PrivateKey]) -> Vec<u8> {
+    let hostnames = keys
+      .iter()
+      .enumerate()
+      .map(|(i, key)| {
+        let pk = key.get_public_key().unwrap().to_bytes();
+        (pk, format!("http://endorser{}:9090", i))
+      })
+      .collect::<EndorserHostnames>();
+    bincode::serialize(&hostnames).unwrap()
+  }
+
+  fn sign(
+    receipts: &mut Receipts,
+    keys: &[&PrivateKey],
+    view: &NimbleDigest,
+    metablock: &MetaBlock,
+    message: &NimbleDigest,
+  ) {
+    for key in keys {
+      let id_sig = IdSig::new(
+        key.get_public_key().unwrap(),
+        key.sign(&message.to_bytes()).unwrap(),
+      );
+      receipts.add(&Receipt::new(*view, metablock.clone(), id_sig));
+    }
+  }
+
+  /// the block and receipts of the view ledger entry after `prev` with the endorsers `next`,
+  /// which they sign together with those of the view of `prev`
+  fn view_entry(
+    group_identity: Option<&NimbleDigest>,
+    prev: &MetaBlock,
+    keys: &[&PrivateKey],
+    next: &[&PrivateKey],
+  ) -> (Vec<u8>, Vec<u8>, MetaBlock) {
+    let block = view_block(next);
+    let block_hash = compute_view_block_hash(&block).unwrap();
+    let group_identity = group_identity.copied().unwrap_or(block_hash);
+    let metablock = prev.next(&block_hash).unwrap();
+    let state_hash = NimbleDigest::digest(b"state");
+    let message = group_identity.digest_with(&state_hash.digest_with(&metablock.hash()));
+    let mut receipts = Receipts::new();
+    sign(&mut receipts, keys, &state_hash, &metablock, &message);
+    sign(&mut receipts, next, &state_hash, &metablock, &message);
+    (block, receipts.to_bytes(), metablock)
+  }
+
+  #[test]
+  fn test_verifier_ffi() {
+    let (first, second) = (PrivateKey::new(), PrivateKey::new());
+    let (block, receipts, first_view) = view_entry(None, &MetaBlock::default(), &[], &[&first]);
+    let group_identity = compute_view_block_hash(&block).unwrap();
+    let state = VerifierState::from_first_view(&group_identity, &block, &receipts).unwrap();
+    let state = state.to_bytes();
+
+    let mut ctx = ptr::null_mut();
+    unsafe {
+      assert_eq!(
+        nimble_verifier_new(state.as_ptr(), state.len() - 1, &mut ctx),
+        NimbleStatus::MalformedInput
+      );
+      assert!(ctx.is_null());
+      assert_eq!(
+        nimble_verifier_new(ptr::null(), 1, &mut ctx),
+        NimbleStatus::NullArgument
+      );
+      assert_eq!(
+        nimble_verifier_new(state.as_ptr(), state.len(), &mut ctx),
+        NimbleStatus::Ok
+      );
+
+      // the verifier follows a view change, once
+      let (block, receipts, second_view) =
+        view_entry(Some(&group_identity), &first_view, &[&first], &[&second]);
+      for _ in 0..2 {
+        assert_eq!(
+          nimble_verifier_apply_view_change(
+            ctx,
+            block.as_ptr(),
+            block.len(),
+            receipts.as_ptr(),
+            receipts.len()
+          ),
+          NimbleStatus::Ok
+        );
+      }
+
+      // and accepts the receipts of the endorsers of the new view
+      let handle = b"ledger";
+      let block_hash = NimbleDigest::digest(b"block");
+      let prev = NimbleDigest::digest(b"prev");
+      let metablock = MetaBlock::new(&prev, &block_hash, 1);
+      let message = compute_ledger_tail_message(
+        &group_identity,
+        &second_view.hash(),
+        &NimbleDigest::digest(handle),
+        &metablock.hash(),
+      );
+      let mut receipts = Receipts::new();
+      sign(
+        &mut receipts,
+        &[&second],
+        &second_view.hash(),
+        &metablock,
+        &message,
+      );
+      let receipts = receipts.to_bytes();
+      let block_hash = block_hash.to_bytes();
+      let prev = prev.to_bytes();
+      let verify_append = |height: u64, prev_len: usize, out_tail: *mut u8| {
+        nimble_verify_append(
+          ctx,
+          handle.as_ptr(),
+          handle.len(),
+          block_hash.as_ptr(),
+          block_hash.len(),
+          height,
+          prev.as_ptr(),
+          prev_len,
+          receipts.as_ptr(),
+          receipts.len(),
+          out_tail,
+        )
+      };
+      let mut tail = [0u8; NIMBLE_DIGEST_LEN];
+      assert_eq!(
+        verify_append(1, prev.len(), tail.as_mut_ptr()),
+        NimbleStatus::Ok
+      );
+      assert_eq!(tail.to_vec(), metablock.hash().to_bytes());
+      assert_eq!(verify_append(1, 0, ptr::null_mut()), NimbleStatus::Ok);
+      assert_eq!(
+        verify_append(2, prev.len(), ptr::null_mut()),
+        NimbleStatus::WrongEntry
+      );
+      assert_eq!(
+        verify_append(1, 1, ptr::null_mut()),
+        NimbleStatus::MalformedInput
+      );
+
+      // the state that the verifier persists holds the view change
+      let mut len = 0;
+      assert_eq!(
+        nimble_verifier_to_bytes(ctx, ptr::null_mut(), 0, &mut len),
+        NimbleStatus::BufferTooSmall
+      );
+      let mut out = vec![0u8; len];
+      assert_eq!(
+        nimble_verifier_to_bytes(ctx, out.as_mut_ptr(), out.len(), &mut len),
+        NimbleStatus::Ok
+      );
+      let state = VerifierState::from_bytes(&out).unwrap();
+      assert_eq!(state.current_view(), second_view.hash());
+      nimble_verifier_free(ctx);
+      nimble_verifier_free(ptr::null_mut());
+    }
+
+    // panics do not cross the boundary
+    assert_eq!(guard(|| panic!("a bug")), NimbleStatus::Panic);
+  }
+}
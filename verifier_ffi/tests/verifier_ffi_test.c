@@ -0,0 +1,134 @@
+/*
+ * Exercises the C API of the verifier with the golden receipts of ledger/testdata/receipts.rs,
+ * which are of SHA-256 digests, the default. Run it with `make -C verifier_ffi test`.
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "verifier_ffi.h"
+
+/* the state of a verifier that trusts the golden endorser in the first view of its group */
+static const char *GOLDEN_STATE =
+    "02"
+    "9c00ae256e55635a9bd056d34fc792f3d5e7f7933bdc1d4380c12010442a1ee3"
+    "834a709ba2534ebe3ee1397fd4f7bd288b2acc1d20a08d6c862dcd99b6f04400"
+    "9c00ae256e55635a9bd056d34fc792f3d5e7f7933bdc1d4380c12010442a1ee3"
+    "0100000000000000"
+    "01000000"
+    "01000000"
+    "02c1af9a5a14375c21d42cd451e948afe2e03be15fe8256434b2dad1b80905b28c"
+    "00000000"
+    "00000000";
+static const char *GOLDEN_HANDLE = "golden-ledger";
+static const char *GOLDEN_APPEND_PREV =
+    "627432973f707e04b4f4d763558abeb16e897f6d21b1e3bb7e407a889cb7f3a3";
+static const char *GOLDEN_APPEND_BLOCK_HASH =
+    "1d6c13c0f60f81519764ce7fa51e9ffd3be7124f9d3dcc5760ebf4769068efbc";
+static const char *GOLDEN_APPEND_RECEIPTS =
+    "0101000000f7a5f5df5a2a39ffb756e291a0349fc5d953f49119d834680862eff33b230c0b627432"
+    "973f707e04b4f4d763558abeb16e897f6d21b1e3bb7e407a889cb7f3a31d6c13c0f60f81519764ce"
+    "7fa51e9ffd3be7124f9d3dcc5760ebf4769068efbc0100000000000000010000002100000002c1af"
+    "9a5a14375c21d42cd451e948afe2e03be15fe8256434b2dad1b80905b28c400000007d28528ea1e7"
+    "d43afcc2c3f10516bfd96cb83742caa4230491f3dfbb0a147e69835d10f0acce330b72d1952b7323"
+    "3bd67a1d411ff6b2d5fdd31bcc451ceaf3c9";
+/* the hash of the tail that the golden append leads to */
+static const char *GOLDEN_APPEND_TAIL =
+    "0a3bb12e306a516be3948878c13c15a674843a8c249e4c4470c1d677332ca1e3";
+
+static int failures = 0;
+
+#define CHECK(cond)                                                   \
+  do {                                                                \
+    if (!(cond)) {                                                    \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      failures++;                                                     \
+    }                                                                 \
+  } while (0)
+
+/* decodes `hex` into `out`, which holds at least half as many bytes; returns the length */
+static size_t decode_hex(const char *hex, uint8_t *out) {
+  size_t len = strlen(hex) / 2;
+  for (size_t i = 0; i < len; i++) {
+    unsigned int byte;
+    sscanf(hex + 2 * i, "%2x", &byte);
+    out[i] = (uint8_t)byte;
+  }
+  return len;
+}
+
+int main(void) {
+  uint8_t state[256], prev[NIMBLE_DIGEST_LEN], block_hash[NIMBLE_DIGEST_LEN];
+  uint8_t expected_tail[NIMBLE_DIGEST_LEN], receipts[512];
+  size_t state_len = decode_hex(GOLDEN_STATE, state);
+  decode_hex(GOLDEN_APPEND_PREV, prev);
+  decode_hex(GOLDEN_APPEND_BLOCK_HASH, block_hash);
+  decode_hex(GOLDEN_APPEND_TAIL, expected_tail);
+  size_t receipts_len = decode_hex(GOLDEN_APPEND_RECEIPTS, receipts);
+  const uint8_t *handle = (const uint8_t *)GOLDEN_HANDLE;
+  size_t handle_len = strlen(GOLDEN_HANDLE);
+
+  /* nimble_verifier_new */
+  NimbleVerifier *ctx = NULL;
+  CHECK(nimble_verifier_new(state, state_len - 1, &ctx) == NIMBLE_STATUS_MALFORMED_INPUT);
+  CHECK(ctx == NULL);
+  CHECK(nimble_verifier_new(NULL, state_len, &ctx) == NIMBLE_STATUS_NULL_ARGUMENT);
+  CHECK(nimble_verifier_new(state, state_len, NULL) == NIMBLE_STATUS_NULL_ARGUMENT);
+  CHECK(nimble_verifier_new(state, state_len, &ctx) == NIMBLE_STATUS_OK);
+  if (ctx == NULL) {
+    fprintf(stderr, "cannot create a verifier\n");
+    return 1;
+  }
+
+  /* nimble_verify_append */
+  uint8_t tail[NIMBLE_DIGEST_LEN] = {0};
+  CHECK(nimble_verify_append(ctx, handle, handle_len, block_hash, sizeof(block_hash), 1, prev,
+                             sizeof(prev), receipts, receipts_len, tail) == NIMBLE_STATUS_OK);
+  CHECK(memcmp(tail, expected_tail, NIMBLE_DIGEST_LEN) == 0);
+  CHECK(nimble_verify_append(ctx, handle, handle_len, block_hash, sizeof(block_hash), 1, NULL, 0,
+                             receipts, receipts_len, NULL) == NIMBLE_STATUS_OK);
+  CHECK(nimble_verify_append(ctx, handle, handle_len, block_hash, sizeof(block_hash), 2, prev,
+                             sizeof(prev), receipts, receipts_len, NULL) ==
+        NIMBLE_STATUS_WRONG_ENTRY);
+  CHECK(nimble_verify_append(ctx, (const uint8_t *)"another-ledger", 14, block_hash,
+                             sizeof(block_hash), 1, prev, sizeof(prev), receipts, receipts_len,
+                             NULL) == NIMBLE_STATUS_BAD_SIGNATURE);
+  CHECK(nimble_verify_append(ctx, handle, handle_len, block_hash, sizeof(block_hash), 1, prev,
+                             sizeof(prev), receipts + 1, receipts_len - 1, NULL) ==
+        NIMBLE_STATUS_MALFORMED_RECEIPTS);
+  CHECK(nimble_verify_append(ctx, handle, handle_len, block_hash, sizeof(block_hash) - 1, 1, prev,
+                             sizeof(prev), receipts, receipts_len, NULL) ==
+        NIMBLE_STATUS_MALFORMED_INPUT);
+  CHECK(nimble_verify_append(NULL, handle, handle_len, block_hash, sizeof(block_hash), 1, prev,
+                             sizeof(prev), receipts, receipts_len, NULL) ==
+        NIMBLE_STATUS_NULL_ARGUMENT);
+
+  /* nimble_verifier_apply_view_change, which does not change the state if it fails */
+  const char *not_a_view_block = "not a view block";
+  CHECK(nimble_verifier_apply_view_change(ctx, (const uint8_t *)not_a_view_block,
+                                          strlen(not_a_view_block), receipts, receipts_len) ==
+        NIMBLE_STATUS_MALFORMED_VIEW_BLOCK);
+  CHECK(nimble_verifier_apply_view_change(ctx, (const uint8_t *)not_a_view_block,
+                                          strlen(not_a_view_block), receipts + 1,
+                                          receipts_len - 1) == NIMBLE_STATUS_MALFORMED_RECEIPTS);
+  CHECK(nimble_verifier_apply_view_change(NULL, NULL, 0, NULL, 0) == NIMBLE_STATUS_NULL_ARGUMENT);
+
+  /* nimble_verifier_to_bytes */
+  uint8_t saved[256];
+  size_t saved_len = 0;
+  CHECK(nimble_verifier_to_bytes(ctx, NULL, 0, &saved_len) == NIMBLE_STATUS_BUFFER_TOO_SMALL);
+  CHECK(saved_len == state_len);
+  CHECK(nimble_verifier_to_bytes(ctx, saved, sizeof(saved), &saved_len) == NIMBLE_STATUS_OK);
+  CHECK(saved_len == state_len && memcmp(saved, state, state_len) == 0);
+  CHECK(nimble_verifier_to_bytes(ctx, saved, sizeof(saved), NULL) == NIMBLE_STATUS_NULL_ARGUMENT);
+
+  /* nimble_verifier_free */
+  nimble_verifier_free(ctx);
+  nimble_verifier_free(NULL);
+
+  if (failures > 0) {
+    fprintf(stderr, "%d checks failed\n", failures);
+    return 1;
+  }
+  printf("all checks passed\n");
+  return 0;
+}
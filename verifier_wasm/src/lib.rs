@@ -0,0 +1,138 @@
+//! The verifier for JavaScript, built with `wasm-pack` for browsers, e.g., for auditors that check
+//! receipts client-side. Buffers are passed as `Uint8Array`s; a failed verification throws an
+//! `Error` whose message describes the `VerifierError`.
+//!
+//! It verifies signatures with the pure-Rust backend of the ledger, so it builds for
+//! `wasm32-unknown-unknown` without a C toolchain.
+use ledger::{compute_aggregated_block_hash, CustomSerde, NimbleDigest, Nonce};
+use std::fmt::Display;
+use verifier::VerifierState;
+use wasm_bindgen::prelude::*;
+
+fn js_error(error: impl Display) -> JsError {
+  JsError::new(&error.to_string())
+}
+
+fn digest(bytes: &[u8], name: &str) -> Result<NimbleDigest, JsError> {
+  NimbleDigest::from_bytes(bytes).map_err(|_e| js_error(format!("{} is not a digest", name)))
+}
+
+/// the aggregated hash of `block` and of its nonces, whose hash is `hash_nonces` (`hash_nonces` in
+/// `AppendResp`), or empty if the entry has no nonces; it is the block hash that receipts endorse
+#[wasm_bindgen]
+pub fn aggregated_block_hash(block: &[u8], hash_nonces: &[u8]) -> Result<Vec<u8>, JsError> {
+  let hash_nonces = match hash_nonces.len() {
+    0 => NimbleDigest::default(),
+    _ => digest(hash_nonces, "hash_nonces")?,
+  };
+  let block_hash = compute_aggregated_block_hash(
+    &NimbleDigest::digest(block).to_bytes(),
+    &hash_nonces.to_bytes(),
+  );
+  Ok(block_hash.to_bytes())
+}
+
+/// a verifier state, which trusts the endorsers of a view of the view ledger
+#[wasm_bindgen]
+pub struct Verifier {
+  state: VerifierState,
+}
+
+#[wasm_bindgen]
+impl Verifier {
+  /// restores a verifier state that was serialized with `to_bytes`, or by the Rust verifier
+  #[wasm_bindgen(constructor)]
+  pub fn new(state: &[u8]) -> Result<Verifier, JsError> {
+    let state =
+      VerifierState::from_bytes(state).map_err(|_e| js_error("malformed verifier state"))?;
+    Ok(Verifier { state })
+  }
+
+  /// trusts the endorsers of the first view of the group with `group_identity`, given the first
+  /// entry of its view ledger and its receipts
+  pub fn from_first_view(
+    group_identity: &[u8],
+    view_block: &[u8],
+    receipts: &[u8],
+  ) -> Result<Verifier, JsError> {
+    let group_identity = digest(group_identity, "group_identity")?;
+    let state =
+      VerifierState::from_first_view(&group_identity, view_block, receipts).map_err(js_error)?;
+    Ok(Verifier { state })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    self.state.to_bytes()
+  }
+
+  /// the hash of the view whose endorsers the verifier trusts
+  pub fn current_view(&self) -> Vec<u8> {
+    self.state.current_view().to_bytes()
+  }
+
+  /// applies the entry of the view ledger after the current view, given its block and receipts,
+  /// as `ReadViewByIndex` returns them
+  pub fn apply_view_change(&mut self, view_block: &[u8], receipts: &[u8]) -> Result<(), JsError> {
+    self
+      .state
+      .apply_view_change(view_block, receipts)
+      .map_err(js_error)
+  }
+
+  /// verifies the receipts of the creation of ledger `handle`; returns the hash of its tail
+  pub fn verify_new_ledger(
+    &self,
+    handle: &[u8],
+    block: &[u8],
+    metadata: &[u8],
+    receipts: &[u8],
+  ) -> Result<Vec<u8>, JsError> {
+    let tail = self
+      .state
+      .verify_new_ledger(handle, block, metadata, receipts)
+      .map_err(js_error)?;
+    Ok(tail.to_bytes())
+  }
+
+  /// verifies the receipts of the append of the entry with `block_hash` (see
+  /// `aggregated_block_hash`) at `height`; if `prev_tail` is not empty, the entry must extend it.
+  /// Returns the hash of the new tail.
+  pub fn verify_append(
+    &self,
+    handle: &[u8],
+    block_hash: &[u8],
+    height: usize,
+    prev_tail: &[u8],
+    receipts: &[u8],
+  ) -> Result<Vec<u8>, JsError> {
+    let block_hash = digest(block_hash, "block_hash")?;
+    let prev_tail = match prev_tail.len() {
+      0 => None,
+      _ => Some(digest(prev_tail, "prev_tail")?),
+    };
+    let tail = self
+      .state
+      .verify_append(handle, &block_hash, height, prev_tail.as_ref(), receipts)
+      .map_err(js_error)?;
+    Ok(tail.to_bytes())
+  }
+
+  /// verifies the receipts of a read with `nonce` of the tail of ledger `handle`, which must be
+  /// the entry with `block_hash` at `height`; returns the hash of the tail
+  pub fn verify_read_latest(
+    &self,
+    handle: &[u8],
+    nonce: &[u8],
+    block_hash: &[u8],
+    height: usize,
+    receipts: &[u8],
+  ) -> Result<Vec<u8>, JsError> {
+    let nonce = Nonce::try_from_bytes(nonce).map_err(|_e| js_error("nonce is not 16 bytes"))?;
+    let block_hash = digest(block_hash, "block_hash")?;
+    let tail = self
+      .state
+      .verify_read_latest(handle, &nonce, &block_hash, height, receipts)
+      .map_err(js_error)?;
+    Ok(tail.to_bytes())
+  }
+}
@@ -0,0 +1,113 @@
+//! Runs the golden vectors of `ledger/testdata/receipts.json` through the JavaScript API in a
+//! headless browser: `wasm-pack test --headless --chrome verifier_wasm`.
+#![cfg(target_arch = "wasm32")]
+use ledger::{
+  signature::{PublicKey, PublicKeyTrait},
+  CustomSerde, MetaBlock, NimbleDigest,
+};
+use serde_json::Value;
+use verifier::VerifierState;
+use verifier_wasm::{aggregated_block_hash, Verifier};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const VECTORS: &str = include_str!("../../ledger/testdata/receipts.json");
+
+fn hex_field(value: &Value, field: &str) -> Vec<u8> {
+  hex::decode(value[field].as_str().unwrap()).unwrap()
+}
+
+fn height(vector: &Value) -> usize {
+  vector["height"].as_u64().unwrap() as usize
+}
+
+/// the vectors and a verifier that trusts the endorser that signed them
+fn golden() -> (Value, Verifier) {
+  let vectors: Value = serde_json::from_str(VECTORS).unwrap();
+  let group_identity = NimbleDigest::from_bytes(&hex_field(&vectors, "group_identity")).unwrap();
+  // the first view's metablock has the group identity as its block hash
+  let view_metablock = MetaBlock::default().next(&group_identity).unwrap();
+  let pk = PublicKey::from_bytes(&hex_field(&vectors, "public_key")).unwrap();
+  let state = VerifierState::new(&group_identity, &view_metablock, &[pk]);
+  let verifier = Verifier::new(&state.to_bytes()).unwrap();
+  assert_eq!(verifier.current_view(), hex_field(&vectors, "view"));
+  (vectors, verifier)
+}
+
+#[wasm_bindgen_test]
+fn test_golden_vectors() {
+  let (vectors, verifier) = golden();
+  let handle = hex_field(&vectors, "handle");
+  let vector = |name: &str| {
+    vectors["vectors"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|vector| vector["name"] == name)
+      .unwrap()
+      .clone()
+  };
+
+  let new_ledger = vector("new_ledger");
+  let tail = verifier
+    .verify_new_ledger(
+      &handle,
+      &hex_field(&new_ledger, "block"),
+      &hex_field(&new_ledger, "metadata"),
+      &hex_field(&new_ledger, "receipts"),
+    )
+    .unwrap();
+  assert_eq!(tail, hex_field(&new_ledger, "tail"));
+
+  let append = vector("append");
+  let block_hash = aggregated_block_hash(&hex_field(&append, "block"), &[]).unwrap();
+  assert_eq!(block_hash, hex_field(&append, "block_hash"));
+  let tail = verifier
+    .verify_append(
+      &handle,
+      &block_hash,
+      height(&append),
+      &tail,
+      &hex_field(&append, "receipts"),
+    )
+    .unwrap();
+  assert_eq!(tail, hex_field(&append, "tail"));
+
+  let read_latest = vector("read_latest");
+  let res = verifier.verify_read_latest(
+    &handle,
+    &hex_field(&read_latest, "nonce"),
+    &block_hash,
+    height(&read_latest),
+    &hex_field(&read_latest, "receipts"),
+  );
+  assert_eq!(res.ok(), Some(tail));
+}
+
+#[wasm_bindgen_test]
+fn test_rejections() {
+  let (vectors, verifier) = golden();
+  let handle = hex_field(&vectors, "handle");
+  let append = &vectors["vectors"][1];
+  let block_hash = hex_field(append, "block_hash");
+  let mut receipts = hex_field(append, "receipts");
+
+  // another height, another ledger, and a tampered signature are rejected
+  assert!(verifier
+    .verify_append(&handle, &block_hash, 2, &[], &receipts)
+    .is_err());
+  assert!(verifier
+    .verify_append(b"other", &block_hash, height(append), &[], &receipts)
+    .is_err());
+  let last = receipts.len() - 1;
+  receipts[last] ^= 1;
+  assert!(verifier
+    .verify_append(&handle, &block_hash, height(append), &[], &receipts)
+    .is_err());
+
+  // the state survives a round trip through its bytes
+  let restored = Verifier::new(&verifier.to_bytes()).unwrap();
+  assert_eq!(restored.to_bytes(), verifier.to_bytes());
+  assert!(Verifier::new(&[1, 2, 3]).is_err());
+}